@@ -6,10 +6,17 @@ use compositor_layer::{CompositorData, CompositorLayer, WantsScrollEventsFlag};
 use compositor_task::{CompositorEventListener, CompositorProxy, CompositorReceiver};
 use compositor_task::Msg;
 use constellation::SendableFrameTree;
+use damage::{self, Damage};
+use highlight;
 use pipeline::CompositionPipeline;
-use scrolling::ScrollingTimerProxy;
+use scrollbars::{self, Axis, ScrollbarRenderer, Thumb};
+use scrolling::{RESIZE_REFLOW_INTERVAL, SCROLLBAR_FADE_DELAY, ScrollingTimerProxy};
+use vsync::VsyncTimerProxy;
 use windowing;
-use windowing::{MouseWindowEvent, WindowEvent, WindowMethods, WindowNavigateMsg};
+use windowing::{CredentialsPrompt, MouseWindowEvent, PermissionPrompt, PositionRequest};
+use windowing::WindowEvent;
+use windowing::WindowMethods;
+use windowing::WindowNavigateMsg;
 
 use euclid::Matrix4;
 use euclid::point::{Point2D, TypedPoint2D};
@@ -19,6 +26,7 @@ use euclid::size::{Size2D, TypedSize2D};
 use gfx_traits::color;
 use gfx::paint_task::Msg as PaintMsg;
 use gfx::paint_task::PaintRequest;
+use gfx::pdf::PaperSize;
 use gleam::gl::types::{GLint, GLsizei};
 use gleam::gl;
 use layers::geometry::{DevicePixel, LayerPixel};
@@ -28,8 +36,9 @@ use layers::rendergl::RenderContext;
 use layers::rendergl;
 use layers::scene::Scene;
 use layout_traits::{LayoutControlChan, LayoutControlMsg};
+use net_traits::image::base::Image;
 use msg::compositor_msg::{Epoch, FrameTreeId, LayerId, LayerKind};
-use msg::compositor_msg::{LayerProperties, ScrollPolicy};
+use msg::compositor_msg::{LayerProperties, ScrollBehavior, ScrollPolicy};
 use msg::constellation_msg::AnimationState;
 use msg::constellation_msg::Msg as ConstellationMsg;
 use msg::constellation_msg::{ConstellationChan, NavigationDirection};
@@ -39,17 +48,21 @@ use png;
 use profile_traits::mem;
 use profile_traits::time::{self, ProfilerCategory, profile};
 use script_traits::{ConstellationControlMsg, ScriptControlChan};
-use std::collections::HashMap;
+use std::collections::{HashMap, VecDeque};
 use std::collections::hash_map::Entry::{Occupied, Vacant};
+use std::borrow::ToOwned;
 use std::mem as std_mem;
 use std::rc::Rc;
-use std::slice::bytes::copy_memory;
-use std::sync::mpsc::Sender;
-use style::viewport::ViewportConstraints;
+use std::sync::Arc;
+use std::sync::mpsc::{Sender, channel};
+use std::thread::sleep_ms;
+use style::viewport::{UserZoom, ViewportConstraints};
 use time::{precise_time_ns, precise_time_s};
 use url::Url;
+use util::bezier::Bezier;
 use util::geometry::{Au, PagePx, ScreenPx, ViewportPx};
 use util::opts;
+use util::task::spawn_named;
 
 /// Holds the state when running reftests that determines when it is
 /// safe to save the output image.
@@ -87,6 +100,19 @@ pub struct IOCompositor<Window: WindowMethods> {
     /// The application window size.
     window_size: TypedSize2D<DevicePixel, u32>,
 
+    /// The window size the page was last asked to reflow for.
+    reflow_window_size: TypedSize2D<DevicePixel, u32>,
+
+    /// The window size the layers of the root pipeline are laid out for. Until layout catches up
+    /// with a resize, they are scaled to fill the window.
+    layout_window_size: TypedSize2D<DevicePixel, u32>,
+
+    /// The time at which the page was last asked to reflow for a new window size.
+    last_resize_reflow_time: u64,
+
+    /// Whether the scrolling timer is counting down to the next reflow for a resize.
+    waiting_for_resize_timeout: bool,
+
     /// "Mobile-style" zoom that does not reflow the page.
     viewport_zoom: ScaleFactor<PagePx, ViewportPx, f32>,
 
@@ -94,6 +120,9 @@ pub struct IOCompositor<Window: WindowMethods> {
     min_viewport_zoom: Option<ScaleFactor<PagePx, ViewportPx, f32>>,
     max_viewport_zoom: Option<ScaleFactor<PagePx, ViewportPx, f32>>,
 
+    /// Whether @viewport allows the user to change the viewport zoom.
+    viewport_user_zoom: UserZoom,
+
     /// "Desktop-style" zoom that resizes the viewport to fit the window.
     /// See `ViewportPx` docs in util/geom.rs for details.
     page_zoom: ScaleFactor<ViewportPx, ScreenPx, f32>,
@@ -106,12 +135,25 @@ pub struct IOCompositor<Window: WindowMethods> {
     /// A handle to the scrolling timer.
     scrolling_timer: ScrollingTimerProxy,
 
+    /// A handle to the timer that wakes us up at the display's vertical blanks.
+    vsync_timer: VsyncTimerProxy,
+
+    /// Whether we have asked the vsync timer for a frame that hasn't started yet.
+    waiting_for_vsync: bool,
+
+    /// Whether a vertical blank has passed since the last frame, so that the next one may
+    /// start.
+    frame_due: bool,
+
     /// The type of composition to perform
     composite_target: CompositeTarget,
 
     /// Tracks whether we should composite this frame.
     composition_request: CompositionRequest,
 
+    /// The part of the window that has changed since the last composite.
+    damage: Damage,
+
     /// Tracks whether we are in the process of shutting down, or have shut down and should close
     /// the compositor.
     shutdown_state: ShutdownState,
@@ -148,12 +190,46 @@ pub struct IOCompositor<Window: WindowMethods> {
     /// Pending scroll events.
     pending_scroll_events: Vec<ScrollEvent>,
 
+    /// The smooth scroll currently in progress, if any.
+    scroll_animation: Option<ScrollAnimation>,
+
     /// Has a Quit event been seen?
     has_seen_quit_event: bool,
 
     /// Used by the logic that determines when it is safe to output an
     /// image for the reftest framework.
     ready_to_save_state: ReadyState,
+
+    /// An icon that was loaded for a pipeline that wasn't yet the root one, to be shown if it
+    /// becomes the root.
+    pending_favicon: Option<(PipelineId, Arc<Image>)>,
+
+    /// Draws the scrollbars. This will be `None` until compositing is initialized.
+    scrollbar_renderer: Option<ScrollbarRenderer>,
+
+    /// The time of the last scroll, which the scrollbars fade out after.
+    last_scroll_time: u64,
+
+    /// The opacity the scrollbars were drawn with in the last composite.
+    scrollbar_opacity: f32,
+
+    /// The scrollbar thumb being dragged, if any.
+    scrollbar_drag: Option<ScrollbarDrag>,
+
+    /// Screenshots that have been asked for, to be taken once the page is stable.
+    pending_screenshots: Vec<ScreenshotRequest>,
+
+    /// When the oldest pending screenshot is taken even if the page isn't stable yet.
+    screenshot_deadline: u64,
+
+    /// The page being printed, if any.
+    printing: Option<PrintJob>,
+
+    /// The rectangle of a page that the developer tools are pointing out, in page pixels.
+    highlight: Option<(PipelineId, Rect<f32>)>,
+
+    /// Painted tiles waiting to be handed to their layers, a frame's worth at a time.
+    queued_tiles: VecDeque<QueuedTile>,
 }
 
 pub struct ScrollEvent {
@@ -161,6 +237,53 @@ pub struct ScrollEvent {
     cursor: TypedPoint2D<DevicePixel,i32>,
 }
 
+/// The length of a smooth scroll, in nanoseconds.
+const SMOOTH_SCROLL_DURATION: u64 = 300_000_000;
+
+/// The limits on how far the page can be pinched in or out, unless its viewport sets its own.
+const MIN_VIEWPORT_ZOOM: f32 = 0.1;
+const MAX_VIEWPORT_ZOOM: f32 = 10.0;
+
+/// How far beyond each edge of the window tiles are painted, as a fraction of the window's size,
+/// so that content scrolled into view has usually been painted already.
+const PAINT_AHEAD_FACTOR: f32 = 0.5;
+
+/// How long a screenshot waits for the page to become stable, in nanoseconds, before the page
+/// is taken as it is.
+const SCREENSHOT_TIMEOUT: u64 = 5_000_000_000;
+
+/// How long the paint task has to print a page, in nanoseconds, before printing is given up on.
+const PRINT_TIMEOUT: u64 = 30_000_000_000;
+
+/// How long the scrollbars take to fade out, in nanoseconds.
+const SCROLLBAR_FADE_DURATION: u64 = 300_000_000;
+
+/// How many bytes of painted tiles are handed to the layers before a frame is drawn. Their
+/// textures are uploaded while the frame is drawn, so a burst of tiles is spread over several
+/// frames instead of stalling one.
+///
+/// This only throttles the uploads, which still block the compositor while they happen.
+/// TODO: Stream tiles to the GPU through pixel buffer objects, with a fence per upload and two
+/// textures per tile, so that a tile is drawn from its old texture until its new one has been
+/// uploaded. The layers crate binds tile textures itself, so it has to expose that first.
+const TILE_UPLOAD_BUDGET: usize = 8 * 512 * 512 * 4;
+
+/// A drag of a scrollbar thumb, which scrolls its layer in proportion to how far the mouse moves.
+struct ScrollbarDrag {
+    thumb: Thumb,
+    start_cursor: TypedPoint2D<DevicePixel, f32>,
+    start_offset: TypedPoint2D<LayerPixel, f32>,
+}
+
+/// A smooth scroll of a layer from one point to another, driven by successive composites.
+struct ScrollAnimation {
+    pipeline_id: PipelineId,
+    layer_id: LayerId,
+    start_point: Point2D<f32>,
+    end_point: Point2D<f32>,
+    start_time: u64,
+}
+
 #[derive(PartialEq)]
 enum CompositionRequest {
     NoCompositingNecessary,
@@ -192,6 +315,9 @@ struct PipelineDetails {
 
     /// Whether there are animation callbacks
     animation_callbacks_running: bool,
+
+    /// Whether animated images are playing
+    animated_images_running: bool,
 }
 
 impl PipelineDetails {
@@ -201,6 +327,7 @@ impl PipelineDetails {
             current_epoch: Epoch(0),
             animations_running: false,
             animation_callbacks_running: false,
+            animated_images_running: false,
         }
     }
 }
@@ -210,13 +337,42 @@ enum CompositeTarget {
     /// Normal composition to a window
     Window,
 
-    /// Compose as normal, but also return a PNG of the composed output
-    WindowAndPng,
-
     /// Compose to a PNG, write it to disk, and then exit the browser (used for reftests)
     PngFile
 }
 
+/// A tile that has been painted but not yet handed to its layer. The tile it replaces stays on
+/// the screen until then.
+struct QueuedTile {
+    pipeline_id: PipelineId,
+    layer_id: LayerId,
+    epoch: Epoch,
+    frame_tree_id: FrameTreeId,
+    buffer: Box<LayerBuffer>,
+}
+
+/// A page being printed to a PDF file.
+struct PrintJob {
+    path: String,
+    paper_size: PaperSize,
+    /// The epoch that the root pipeline had painted when it was asked to lay out for the paper.
+    /// The page is printed once a later one has been painted.
+    resize_epoch: Epoch,
+    /// When printing is given up on, once the paint task has been asked to print.
+    deadline: Option<u64>,
+}
+
+/// Where a screenshot goes once it has been taken.
+enum ScreenshotRequest {
+    /// Sent back over a channel, for webdriver.
+    Reply(Sender<Option<png::Image>>),
+    /// Encoded as a PNG file at the given path, for the embedder.
+    File(String),
+    /// Not kept; the page being printed is printed instead, once it has been laid out for the
+    /// paper and is stable.
+    Print,
+}
+
 fn initialize_png(width: usize, height: usize) -> (Vec<gl::GLuint>, Vec<gl::GLuint>) {
     let framebuffer_ids = gl::gen_framebuffers(1);
     gl::bind_framebuffer(gl::FRAMEBUFFER, framebuffer_ids[0]);
@@ -224,8 +380,9 @@ fn initialize_png(width: usize, height: usize) -> (Vec<gl::GLuint>, Vec<gl::GLui
     let texture_ids = gl::gen_textures(1);
     gl::bind_texture(gl::TEXTURE_2D, texture_ids[0]);
 
-    gl::tex_image_2d(gl::TEXTURE_2D, 0, gl::RGB as GLint, width as GLsizei,
-                     height as GLsizei, 0, gl::RGB, gl::UNSIGNED_BYTE, None);
+    // OpenGL ES only guarantees that RGBA textures can be rendered to and read back.
+    gl::tex_image_2d(gl::TEXTURE_2D, 0, gl::RGBA as GLint, width as GLsizei,
+                     height as GLsizei, 0, gl::RGBA, gl::UNSIGNED_BYTE, None);
     gl::tex_parameter_i(gl::TEXTURE_2D, gl::TEXTURE_MAG_FILTER, gl::NEAREST as GLint);
     gl::tex_parameter_i(gl::TEXTURE_2D, gl::TEXTURE_MIN_FILTER, gl::NEAREST as GLint);
 
@@ -256,6 +413,8 @@ impl<Window: WindowMethods> IOCompositor<Window> {
             None => CompositeTarget::Window
         };
         let native_display = window.native_display();
+        let refresh_interval = window.refresh_interval();
+        let vblank_source = window.vblank_source();
         IOCompositor {
             window: window,
             native_display: native_display,
@@ -268,17 +427,29 @@ impl<Window: WindowMethods> IOCompositor<Window> {
                 size: window_size.as_f32(),
             }),
             window_size: window_size,
+            reflow_window_size: window_size,
+            layout_window_size: window_size,
+            last_resize_reflow_time: 0,
+            waiting_for_resize_timeout: false,
             hidpi_factor: hidpi_factor,
             channel_to_self: sender.clone_compositor_proxy(),
+            vsync_timer: VsyncTimerProxy::new(sender.clone_compositor_proxy(),
+                                              refresh_interval,
+                                              vblank_source),
+            waiting_for_vsync: false,
+            frame_due: false,
             scrolling_timer: ScrollingTimerProxy::new(sender),
             composition_request: CompositionRequest::NoCompositingNecessary,
+            damage: Damage::Everything,
             pending_scroll_events: Vec::new(),
+            scroll_animation: None,
             composite_target: composite_target,
             shutdown_state: ShutdownState::NotShuttingDown,
             page_zoom: ScaleFactor::new(1.0),
             viewport_zoom: ScaleFactor::new(1.0),
             min_viewport_zoom: None,
             max_viewport_zoom: None,
+            viewport_user_zoom: UserZoom::Zoom,
             zoom_action: false,
             zoom_time: 0f64,
             got_load_complete_message: false,
@@ -290,6 +461,16 @@ impl<Window: WindowMethods> IOCompositor<Window> {
             last_composite_time: 0,
             has_seen_quit_event: false,
             ready_to_save_state: ReadyState::Unknown,
+            pending_favicon: None,
+            scrollbar_renderer: None,
+            last_scroll_time: 0,
+            scrollbar_opacity: 0.0,
+            scrollbar_drag: None,
+            pending_screenshots: Vec::new(),
+            screenshot_deadline: 0,
+            printing: None,
+            highlight: None,
+            queued_tiles: VecDeque::new(),
         }
     }
 
@@ -340,6 +521,14 @@ impl<Window: WindowMethods> IOCompositor<Window> {
                 self.change_running_animations_state(pipeline_id, animation_state);
             }
 
+            (Msg::ChangeRunningAnimatedImagesState(pipeline_id, running),
+             ShutdownState::NotShuttingDown) => {
+                self.get_or_create_pipeline_details(pipeline_id).animated_images_running = running;
+                if running {
+                    self.composite_if_necessary(CompositingReason::Animation);
+                }
+            }
+
             (Msg::ChangePageTitle(pipeline_id, title), ShutdownState::NotShuttingDown) => {
                 self.change_page_title(pipeline_id, title);
             }
@@ -357,6 +546,9 @@ impl<Window: WindowMethods> IOCompositor<Window> {
 
             (Msg::InitializeLayersForPipeline(pipeline_id, epoch, properties), ShutdownState::NotShuttingDown) => {
                 self.get_or_create_pipeline_details(pipeline_id).current_epoch = epoch;
+                if self.root_pipeline.as_ref().map(|pipeline| pipeline.id) == Some(pipeline_id) {
+                    self.root_layers_laid_out();
+                }
                 for (index, layer_properties) in properties.iter().enumerate() {
                     if index == 0 {
                         self.create_or_update_base_layer(pipeline_id, *layer_properties);
@@ -387,9 +579,9 @@ impl<Window: WindowMethods> IOCompositor<Window> {
                 }
             }
 
-            (Msg::ScrollFragmentPoint(pipeline_id, layer_id, point),
+            (Msg::ScrollFragmentPoint(pipeline_id, layer_id, point, behavior),
              ShutdownState::NotShuttingDown) => {
-                self.scroll_fragment_to_point(pipeline_id, layer_id, point);
+                self.scroll_fragment_to_point(pipeline_id, layer_id, point, behavior);
             }
 
             (Msg::LoadStart(back, forward), ShutdownState::NotShuttingDown) => {
@@ -404,6 +596,19 @@ impl<Window: WindowMethods> IOCompositor<Window> {
                     self.composite_if_necessary(CompositingReason::Headless);
                 }
 
+                // Print the page if asked to on the command line.
+                if let Some(ref path) = opts::get().print_pdf {
+                    let paper_size = match PaperSize::from_name(&opts::get().paper_size) {
+                        Some(paper_size) => paper_size,
+                        None => {
+                            warn!("unknown paper size {}; printing on A4",
+                                  opts::get().paper_size);
+                            PaperSize::a4()
+                        }
+                    };
+                    self.start_printing(path.clone(), paper_size);
+                }
+
                 // Inform the embedder that the load has finished.
                 //
                 // TODO(pcwalton): Specify which frame's load completed.
@@ -416,7 +621,8 @@ impl<Window: WindowMethods> IOCompositor<Window> {
                     CompositionRequest::CompositeOnScrollTimeout(this_timestamp) => {
                         if timestamp == this_timestamp {
                             self.composition_request = CompositionRequest::CompositeNow(
-                                CompositingReason::HitScrollTimeout)
+                                CompositingReason::HitScrollTimeout);
+                            self.damage = Damage::Everything;
                         }
                     }
                     _ => {}
@@ -425,7 +631,25 @@ impl<Window: WindowMethods> IOCompositor<Window> {
 
             (Msg::RecompositeAfterScroll, ShutdownState::NotShuttingDown) => {
                 self.composition_request =
-                    CompositionRequest::CompositeNow(CompositingReason::ContinueScroll)
+                    CompositionRequest::CompositeNow(CompositingReason::ContinueScroll);
+                self.damage = Damage::Everything;
+            }
+
+            (Msg::ResizeTimeout, ShutdownState::NotShuttingDown) => {
+                self.waiting_for_resize_timeout = false;
+                self.reflow_for_resize_if_necessary();
+            }
+
+            (Msg::ScrollbarFadeTimeout, ShutdownState::NotShuttingDown) => {
+                // The composites that follow keep the fade going until the scrollbars are gone.
+                if self.scrollbar_opacity > 0.0 {
+                    self.composite_if_necessary(CompositingReason::Animation);
+                }
+            }
+
+            (Msg::Vsync(_), ShutdownState::NotShuttingDown) => {
+                self.waiting_for_vsync = false;
+                self.frame_due = true;
             }
 
             (Msg::KeyEvent(key, state, modified), ShutdownState::NotShuttingDown) => {
@@ -438,9 +662,41 @@ impl<Window: WindowMethods> IOCompositor<Window> {
                 self.window.set_cursor(cursor)
             }
 
+            (Msg::ImeCaretRect(pipeline_id, rect), ShutdownState::NotShuttingDown) => {
+                self.set_ime_caret_rect(pipeline_id, rect);
+            }
+
+            (Msg::HighlightRect(pipeline_id, rect), ShutdownState::NotShuttingDown) => {
+                self.highlight = rect.map(|rect| (pipeline_id, rect));
+                self.composite_if_necessary(CompositingReason::Highlight);
+            }
+
             (Msg::CreatePng(reply), ShutdownState::NotShuttingDown) => {
-                let img = self.composite_specific_target(CompositeTarget::WindowAndPng);
-                reply.send(img).unwrap();
+                self.request_screenshot(ScreenshotRequest::Reply(reply));
+            }
+
+            (Msg::CreatePng(reply), _) => {
+                let _ = reply.send(None);
+            }
+
+            (Msg::ScreenshotTimeout, ShutdownState::NotShuttingDown) => {
+                if !self.pending_screenshots.is_empty() {
+                    self.composite_if_necessary(CompositingReason::Screenshot);
+                }
+            }
+
+            (Msg::PrintedToPdf(path, printed), ShutdownState::NotShuttingDown) => {
+                if self.printing.as_ref().map_or(false, |job| job.path == path) {
+                    self.finish_printing(printed);
+                }
+            }
+
+            (Msg::PrintTimeout, ShutdownState::NotShuttingDown) => {
+                let deadline = self.printing.as_ref().and_then(|job| job.deadline);
+                let timed_out = deadline.map_or(false, |deadline| precise_time_ns() >= deadline);
+                if timed_out {
+                    self.finish_printing(false);
+                }
             }
 
             (Msg::PaintTaskExited(pipeline_id), ShutdownState::NotShuttingDown) => {
@@ -466,10 +722,56 @@ impl<Window: WindowMethods> IOCompositor<Window> {
                 self.window.set_favicon(url);
             }
 
+            (Msg::ChangeFavicon(pipeline_id, image), ShutdownState::NotShuttingDown) => {
+                self.change_favicon(pipeline_id, image);
+            }
+
             (Msg::HeadParsed, ShutdownState::NotShuttingDown) => {
                 self.window.head_parsed();
             }
 
+            (Msg::RequestPermission(pipeline_id, name, url), ShutdownState::NotShuttingDown) => {
+                let prompt = PermissionPrompt::new(pipeline_id,
+                                                   name,
+                                                   url,
+                                                   self.constellation_chan.clone());
+                self.window.request_permission(prompt);
+            }
+
+            (Msg::ShowNotification(pipeline_id, id, notification),
+             ShutdownState::NotShuttingDown) => {
+                self.window.show_notification(pipeline_id, id, notification);
+            }
+
+            (Msg::CloseNotification(pipeline_id, id), ShutdownState::NotShuttingDown) => {
+                self.window.close_notification(pipeline_id, id);
+            }
+
+            (Msg::RequestGeolocationPosition(pipeline_id, enable_high_accuracy),
+             ShutdownState::NotShuttingDown) => {
+                let request = PositionRequest::new(pipeline_id,
+                                                   enable_high_accuracy,
+                                                   self.constellation_chan.clone());
+                self.window.request_position(request);
+            }
+
+            (Msg::MixedContent(pipeline_id, url, state), ShutdownState::NotShuttingDown) => {
+                self.window.mixed_content(pipeline_id, url, state);
+            }
+
+            (Msg::CertificateError(pipeline_id, error), ShutdownState::NotShuttingDown) => {
+                let allowed = self.window.certificate_error(pipeline_id, &error);
+                let ConstellationChan(ref chan) = self.constellation_chan;
+                chan.send(ConstellationMsg::CertificateErrorResponse(pipeline_id, error, allowed))
+                    .unwrap();
+            }
+
+            (Msg::RequestCredentials(pipeline_id, request, sender),
+             ShutdownState::NotShuttingDown) => {
+                let prompt = CredentialsPrompt::new(pipeline_id, request, sender);
+                self.window.request_credentials(prompt);
+            }
+
             // When we are shutting_down, we need to avoid performing operations
             // such as Paint that may crash because we have begun tearing down
             // the rest of our resources.
@@ -534,6 +836,17 @@ impl<Window: WindowMethods> IOCompositor<Window> {
         }
     }
 
+    fn change_favicon(&mut self, pipeline_id: PipelineId, image: Arc<Image>) {
+        let is_root = self.root_pipeline.as_ref().map_or(false, |root_pipeline| {
+            root_pipeline.id == pipeline_id
+        });
+        if is_root {
+            self.window.set_favicon_image(image);
+        } else {
+            self.pending_favicon = Some((pipeline_id, image));
+        }
+    }
+
     fn change_page_url(&mut self, _: PipelineId, url: Url) {
         self.window.set_page_url(url);
     }
@@ -545,6 +858,13 @@ impl<Window: WindowMethods> IOCompositor<Window> {
         response_chan.send(()).unwrap();
 
         self.root_pipeline = Some(frame_tree.pipeline.clone());
+        if let Some((pipeline_id, image)) = self.pending_favicon.take() {
+            if pipeline_id == frame_tree.pipeline.id {
+                self.window.set_favicon_image(image);
+            } else {
+                self.pending_favicon = Some((pipeline_id, image));
+            }
+        }
 
         // If we have an old root layer, release all old tiles before replacing it.
         match self.scene.root {
@@ -623,12 +943,14 @@ impl<Window: WindowMethods> IOCompositor<Window> {
 
             self.pipeline_details.remove(&pipeline_id);
         }
+        self.damage = Damage::Everything;
     }
 
     fn update_layer_if_exists(&mut self, pipeline_id: PipelineId, properties: LayerProperties) -> bool {
         match self.find_layer_with_pipeline_and_layer_id(pipeline_id, properties.id) {
             Some(existing_layer) => {
                 existing_layer.update_layer(properties);
+                self.damage = Damage::Everything;
                 true
             }
             None => false,
@@ -663,6 +985,7 @@ impl<Window: WindowMethods> IOCompositor<Window> {
             // layers were added previously when creating the layer tree
             // skeleton in create_frame_tree_root_layers.
             root_layer.children().insert(0, base_layer);
+            self.damage = Damage::Everything;
         }
 
         self.scroll_layer_to_fragment_point_if_necessary(pipeline_id,
@@ -674,6 +997,7 @@ impl<Window: WindowMethods> IOCompositor<Window> {
 
         if !self.update_layer_if_exists(pipeline_id, layer_properties) {
             self.create_descendant_layer(pipeline_id, layer_properties);
+            self.damage = Damage::Everything;
         }
         self.scroll_layer_to_fragment_point_if_necessary(pipeline_id,
                                                          layer_properties.id);
@@ -756,6 +1080,7 @@ impl<Window: WindowMethods> IOCompositor<Window> {
             None => panic!("Compositor received SetLayerRect for nonexistent \
                             layer: {:?}", pipeline_id),
         };
+        self.damage = Damage::Everything;
 
         self.send_buffer_requests_for_all_layers();
     }
@@ -775,7 +1100,16 @@ impl<Window: WindowMethods> IOCompositor<Window> {
             if let Some(layer) = self.find_layer_with_pipeline_and_layer_id(pipeline_id, layer_id) {
                 let requested_epoch = layer.extra_data.borrow().requested_epoch;
                 if requested_epoch == epoch {
-                    self.assign_painted_buffers_to_layer(layer, new_layer_buffer_set, epoch);
+                    for buffer in new_layer_buffer_set.buffers.into_iter() {
+                        self.queued_tiles.push_back(QueuedTile {
+                            pipeline_id: pipeline_id,
+                            layer_id: layer_id,
+                            epoch: epoch,
+                            frame_tree_id: frame_tree_id,
+                            buffer: buffer,
+                        });
+                    }
+                    self.composite_if_necessary(CompositingReason::NewPaintedBuffers);
                     return
                 } else {
                     debug!("assign_painted_buffers epoch mismatch {:?} {:?} req={:?} actual={:?}",
@@ -807,17 +1141,130 @@ impl<Window: WindowMethods> IOCompositor<Window> {
         // FIXME(pcwalton): This is going to cause problems with inconsistent frames since
         // we only composite one layer at a time.
         layer.add_buffers(self, new_layer_buffer_set, epoch);
+        self.damage_layer(&*layer);
         self.composite_if_necessary(CompositingReason::NewPaintedBuffers);
     }
 
+    /// Hands queued tiles to their layers until `budget` bytes of them have been handed over, or
+    /// all of them if there is no budget. Tiles whose layer has moved on to another epoch, or
+    /// whose frame tree has changed, go back to their paint task.
+    fn assign_queued_tiles(&mut self, budget: Option<usize>) {
+        let mut assigned = 0;
+        while budget.map_or(true, |budget| assigned < budget) {
+            let tile = match self.queued_tiles.pop_front() {
+                Some(tile) => tile,
+                None => break,
+            };
+            assigned += tile.buffer.screen_pos.size.width * tile.buffer.screen_pos.size.height * 4;
+
+            if tile.frame_tree_id == self.frame_tree_id {
+                let layer = self.find_layer_with_pipeline_and_layer_id(tile.pipeline_id,
+                                                                       tile.layer_id);
+                if let Some(layer) = layer {
+                    if layer.extra_data.borrow().requested_epoch == tile.epoch {
+                        let buffers = box LayerBufferSet {
+                            buffers: vec!(tile.buffer),
+                        };
+                        self.assign_painted_buffers_to_layer(layer, buffers, tile.epoch);
+                        continue
+                    }
+                }
+            }
+            self.send_back_queued_tile(tile);
+        }
+    }
+
+    fn send_back_queued_tile(&self, tile: QueuedTile) {
+        if let Some(details) = self.pipeline_details.get(&tile.pipeline_id) {
+            if let Some(ref pipeline) = details.pipeline {
+                let _ = pipeline.paint_chan.send_opt(PaintMsg::UnusedBuffer(vec!(tile.buffer)));
+            }
+        }
+    }
+
+    /// Marks the part of the window that `layer` is drawn in as damaged.
+    fn damage_layer(&mut self, layer: &Layer<CompositorData>) {
+        let rect = match self.scene.root {
+            Some(ref root) => damage::rect_in_window_for_layer(&**root,
+                                                               layer.pipeline_id(),
+                                                               layer.extra_data.borrow().id,
+                                                               self.scene.scale),
+            None => None,
+        };
+        self.damage = match rect {
+            Some(rect) => self.damage.add(rect),
+            None => Damage::Everything,
+        };
+    }
+
     fn scroll_fragment_to_point(&mut self,
                                 pipeline_id: PipelineId,
                                 layer_id: LayerId,
-                                point: Point2D<f32>) {
-        if self.move_layer(pipeline_id, layer_id, Point2D::from_untyped(&point)) {
-            self.perform_updates_after_scroll()
+                                point: Point2D<f32>,
+                                behavior: ScrollBehavior) {
+        let layer = match self.find_layer_with_pipeline_and_layer_id(pipeline_id, layer_id) {
+            Some(layer) => layer,
+            None => {
+                self.fragment_point = Some(point);
+                return
+            }
+        };
+
+        match behavior {
+            ScrollBehavior::Instant => {
+                self.scroll_animation = None;
+                self.move_layer(pipeline_id, layer_id, Point2D::from_untyped(&point));
+                self.perform_updates_after_scroll()
+            }
+            ScrollBehavior::Smooth => {
+                let scroll_offset = layer.extra_data.borrow().scroll_offset;
+                let start_point = Point2D::typed(0f32, 0f32) - scroll_offset;
+                self.scroll_animation = Some(ScrollAnimation {
+                    pipeline_id: pipeline_id,
+                    layer_id: layer_id,
+                    start_point: start_point.to_untyped(),
+                    end_point: point,
+                    start_time: precise_time_ns(),
+                });
+                self.composite_if_necessary(CompositingReason::Animation);
+            }
+        }
+    }
+
+    /// Advances the smooth scroll in progress, if any, and schedules another composite if it has
+    /// not yet reached its destination.
+    fn process_scroll_animation(&mut self) {
+        let (pipeline_id, layer_id, point, finished) = match self.scroll_animation {
+            None => return,
+            Some(ref animation) => {
+                let elapsed = precise_time_ns() - animation.start_time;
+                let finished = elapsed >= SMOOTH_SCROLL_DURATION;
+                let progress = if finished {
+                    1.0
+                } else {
+                    // The `ease-in-out` timing function.
+                    let ease = Bezier::new(Point2D::new(0.42, 0.0), Point2D::new(0.58, 1.0));
+                    ease.solve(elapsed as f32 / SMOOTH_SCROLL_DURATION as f32, 0.001)
+                };
+                let delta = animation.end_point - animation.start_point;
+                let point = animation.start_point + Point2D::new(delta.x * progress,
+                                                                 delta.y * progress);
+                (animation.pipeline_id, animation.layer_id, point, finished)
+            }
+        };
+
+        if !self.move_layer(pipeline_id, layer_id, Point2D::from_untyped(&point)) {
+            // The layer went away underneath us.
+            self.scroll_animation = None;
+            return
+        }
+        self.perform_updates_after_scroll();
+
+        if finished {
+            self.scroll_animation = None;
+            self.send_viewport_rects_for_all_layers();
         } else {
-            self.fragment_point = Some(point)
+            self.composite_if_necessary(CompositingReason::Animation);
         }
     }
 
@@ -833,6 +1280,14 @@ impl<Window: WindowMethods> IOCompositor<Window> {
                 self.initialize_compositing();
             }
 
+            WindowEvent::Suspend => {
+                self.suspend_compositing();
+            }
+
+            WindowEvent::Resume => {
+                self.resume_compositing();
+            }
+
             WindowEvent::Resize(size) => {
                 self.on_resize_window_event(size);
             }
@@ -861,8 +1316,12 @@ impl<Window: WindowMethods> IOCompositor<Window> {
                 self.on_zoom_reset_window_event();
             }
 
-            WindowEvent::PinchZoom(magnification) => {
-                self.on_pinch_zoom_window_event(magnification);
+            WindowEvent::PinchZoom(magnification, focal_point) => {
+                self.on_pinch_zoom_window_event(magnification, focal_point);
+            }
+
+            WindowEvent::PinchZoomEnd => {
+                self.on_pinch_zoom_end_window_event();
             }
 
             WindowEvent::Navigation(direction) => {
@@ -873,6 +1332,30 @@ impl<Window: WindowMethods> IOCompositor<Window> {
                 self.on_key_event(key, state, modifiers);
             }
 
+            WindowEvent::ImeEvent(event) => {
+                let ConstellationChan(ref chan) = self.constellation_chan;
+                chan.send(ConstellationMsg::ImeEvent(event)).unwrap()
+            }
+
+            WindowEvent::SaveScreenshot(path) => {
+                self.request_screenshot(ScreenshotRequest::File(path));
+            }
+
+            WindowEvent::PrintToPdf(path, paper_size) => {
+                self.start_printing(path, paper_size);
+            }
+
+            WindowEvent::NotificationEvent(pipeline_id, id, event_type) => {
+                let ConstellationChan(ref chan) = self.constellation_chan;
+                chan.send(ConstellationMsg::NotificationEvent(pipeline_id, id, event_type))
+                    .unwrap();
+            }
+
+            WindowEvent::AllowMixedContent(pipeline_id) => {
+                let ConstellationChan(ref chan) = self.constellation_chan;
+                chan.send(ConstellationMsg::AllowMixedContent(pipeline_id)).unwrap();
+            }
+
             WindowEvent::Quit => {
                 if !self.has_seen_quit_event {
                     self.has_seen_quit_event = true;
@@ -901,8 +1384,52 @@ impl<Window: WindowMethods> IOCompositor<Window> {
 
         self.window_size = new_size;
 
-        self.scene.set_root_layer_size(new_size.as_f32());
-        self.send_window_size();
+        // Scale the page we have to the new size, until layout catches up with it.
+        self.update_zoom_transform();
+        self.send_viewport_rects_for_all_layers();
+        self.composite_if_necessary(CompositingReason::Resize);
+        self.reflow_for_resize_if_necessary();
+    }
+
+    /// Asks for the page to be reflowed for the current window size, unless it was asked to
+    /// reflow for a resize too recently, in which case the scrolling timer is started to ask
+    /// again once enough time has passed.
+    fn reflow_for_resize_if_necessary(&mut self) {
+        if self.reflow_window_size == self.window_size {
+            return;
+        }
+
+        let now = precise_time_ns();
+        if now - self.last_resize_reflow_time >= RESIZE_REFLOW_INTERVAL {
+            self.reflow_window_size = self.window_size;
+            self.last_resize_reflow_time = now;
+            self.send_window_size();
+        } else if !self.waiting_for_resize_timeout {
+            self.waiting_for_resize_timeout = true;
+            self.scrolling_timer.resize_reflow_sent(self.last_resize_reflow_time);
+        }
+    }
+
+    /// Stops scaling the layers of the root pipeline, now that they have been laid out for the
+    /// window size that was last asked for.
+    fn root_layers_laid_out(&mut self) {
+        if self.layout_window_size == self.reflow_window_size {
+            return;
+        }
+        self.layout_window_size = self.reflow_window_size;
+        self.update_zoom_transform();
+        self.scene.mark_layer_contents_as_changed_recursively();
+        self.composite_if_necessary(CompositingReason::Resize);
+    }
+
+    /// How much the layers of the root pipeline are scaled to fill the window while layout
+    /// catches up with a resize.
+    fn resize_scale(&self) -> ScaleFactor<DevicePixel, DevicePixel, f32> {
+        let layout_width = self.layout_window_size.width.get();
+        if layout_width == 0 || layout_width == self.window_size.width.get() {
+            return ScaleFactor::new(1.0);
+        }
+        ScaleFactor::new(self.window_size.width.get() as f32 / layout_width as f32)
     }
 
     fn on_load_url_window_event(&mut self, url_string: String) {
@@ -919,25 +1446,152 @@ impl<Window: WindowMethods> IOCompositor<Window> {
         chan.send(msg).unwrap()
     }
 
-    fn on_mouse_window_event_class(&self, mouse_window_event: MouseWindowEvent) {
+    fn on_mouse_window_event_class(&mut self, mouse_window_event: MouseWindowEvent) {
         let point = match mouse_window_event {
             MouseWindowEvent::Click(_, p) => p,
             MouseWindowEvent::MouseDown(_, p) => p,
             MouseWindowEvent::MouseUp(_, p) => p,
         };
+
+        // Presses on a scrollbar are kept from the page underneath.
+        if let MouseWindowEvent::MouseUp(..) = mouse_window_event {
+            if self.scrollbar_drag.take().is_some() {
+                return
+            }
+        }
+        if let Some(thumb) = self.visible_thumb_at_point(point) {
+            if let MouseWindowEvent::MouseDown(..) = mouse_window_event {
+                self.start_scrollbar_drag(thumb, point);
+            }
+            return
+        }
+
         match self.find_topmost_layer_at_point(point / self.scene.scale) {
             Some(result) => result.layer.send_mouse_event(self, mouse_window_event, result.point),
             None => {},
         }
     }
 
-    fn on_mouse_window_move_event_class(&self, cursor: TypedPoint2D<DevicePixel, f32>) {
+    fn on_mouse_window_move_event_class(&mut self, cursor: TypedPoint2D<DevicePixel, f32>) {
+        if self.scrollbar_drag.is_some() {
+            self.continue_scrollbar_drag(cursor);
+            return
+        }
+
         match self.find_topmost_layer_at_point(cursor / self.scene.scale) {
             Some(result) => result.layer.send_mouse_move_event(self, result.point),
             None => {},
         }
     }
 
+    /// Finds the scrollbar thumb under `point`, as long as the scrollbars are showing.
+    fn visible_thumb_at_point(&self, point: TypedPoint2D<DevicePixel, f32>) -> Option<Thumb> {
+        if self.scrollbar_opacity <= 0.0 {
+            return None
+        }
+        match self.scene.root {
+            Some(ref layer) => {
+                let thumbs = scrollbars::thumbs_for_layer_tree(&**layer,
+                                                               self.scene.scale,
+                                                               self.hidpi_factor);
+                scrollbars::thumb_at_point(&thumbs, point)
+            }
+            None => None,
+        }
+    }
+
+    fn start_scrollbar_drag(&mut self, thumb: Thumb, cursor: TypedPoint2D<DevicePixel, f32>) {
+        let layer = match self.find_layer_with_pipeline_and_layer_id(thumb.pipeline_id,
+                                                                     thumb.layer_id) {
+            Some(layer) => layer,
+            None => return,
+        };
+        let start_offset = layer.extra_data.borrow().scroll_offset;
+        self.scrollbar_drag = Some(ScrollbarDrag {
+            thumb: thumb,
+            start_cursor: cursor,
+            start_offset: start_offset,
+        });
+    }
+
+    /// Scrolls the layer whose thumb is being dragged so that the thumb follows the cursor.
+    fn continue_scrollbar_drag(&mut self, cursor: TypedPoint2D<DevicePixel, f32>) {
+        let (layer, new_offset) = match self.scrollbar_drag {
+            Some(ref drag) => {
+                let layer = match self.find_layer_with_pipeline_and_layer_id(
+                        drag.thumb.pipeline_id, drag.thumb.layer_id) {
+                    Some(layer) => layer,
+                    None => return,
+                };
+                // Moving the thumb down or right moves the content up or left.
+                let delta = cursor - drag.start_cursor;
+                let scroll = drag.thumb.scroll_per_device_pixel;
+                let new_offset = match drag.thumb.axis {
+                    Axis::Horizontal => {
+                        Point2D::typed(drag.start_offset.x.get() - delta.x.get() * scroll,
+                                       drag.start_offset.y.get())
+                    }
+                    Axis::Vertical => {
+                        Point2D::typed(drag.start_offset.x.get(),
+                                       drag.start_offset.y.get() - delta.y.get() * scroll)
+                    }
+                };
+                (layer, new_offset)
+            }
+            None => return,
+        };
+
+        layer.clamp_scroll_offset_and_scroll_layer(new_offset);
+        self.perform_updates_after_scroll();
+        self.send_viewport_rects_for_all_layers();
+    }
+
+    /// How opaque to draw the scrollbars: fully shown while scrolling and for a little while
+    /// afterwards, and then fading out.
+    fn current_scrollbar_opacity(&self) -> f32 {
+        if self.scrollbar_drag.is_some() {
+            return 1.0
+        }
+        let elapsed = precise_time_ns() - self.last_scroll_time;
+        if elapsed < SCROLLBAR_FADE_DELAY {
+            1.0
+        } else if elapsed < SCROLLBAR_FADE_DELAY + SCROLLBAR_FADE_DURATION {
+            1.0 - (elapsed - SCROLLBAR_FADE_DELAY) as f32 / SCROLLBAR_FADE_DURATION as f32
+        } else {
+            0.0
+        }
+    }
+
+    fn draw_scrollbars(&mut self) {
+        self.scrollbar_opacity = self.current_scrollbar_opacity();
+        let renderer = match self.scrollbar_renderer {
+            Some(ref renderer) => renderer,
+            None => return,
+        };
+        if let Some(ref layer) = self.scene.root {
+            let thumbs = scrollbars::thumbs_for_layer_tree(&**layer,
+                                                           self.scene.scale,
+                                                           self.hidpi_factor);
+            renderer.draw(&thumbs, self.window_size, self.scrollbar_opacity);
+        }
+    }
+
+    fn draw_highlight(&self) {
+        let (renderer, layer, (pipeline_id, rect)) =
+            match (&self.scrollbar_renderer, &self.scene.root, self.highlight) {
+                (&Some(ref renderer), &Some(ref layer), Some(highlight)) => {
+                    (renderer, layer, highlight)
+                }
+                _ => return,
+            };
+        highlight::draw_highlight(renderer,
+                                  &**layer,
+                                  pipeline_id,
+                                  rect,
+                                  self.scene.scale,
+                                  self.window_size);
+    }
+
     fn on_scroll_window_event(&mut self,
                               delta: TypedPoint2D<DevicePixel, f32>,
                               cursor: TypedPoint2D<DevicePixel, i32>) {
@@ -949,23 +1603,37 @@ impl<Window: WindowMethods> IOCompositor<Window> {
         self.composite_if_necessary(CompositingReason::Scroll);
     }
 
+    /// Applies the scroll events that arrived since the last frame. Events over the same point
+    /// are added together, so that however many come in, each frame scrolls the layers, updates
+    /// the display ports and requests tiles once. Script hears of the new offsets afterwards,
+    /// without holding up the frame.
     fn process_pending_scroll_events(&mut self) {
-        let had_scroll_events = self.pending_scroll_events.len() > 0;
+        let mut scroll_events: Vec<ScrollEvent> = vec!();
         for scroll_event in std_mem::replace(&mut self.pending_scroll_events,
                                              Vec::new()).into_iter() {
+            if let Some(last_event) = scroll_events.last_mut() {
+                if last_event.cursor == scroll_event.cursor {
+                    last_event.delta = last_event.delta + scroll_event.delta;
+                    continue
+                }
+            }
+            scroll_events.push(scroll_event);
+        }
+        if scroll_events.is_empty() {
+            return
+        }
+
+        for scroll_event in scroll_events.iter() {
             let delta = scroll_event.delta / self.scene.scale;
             let cursor = scroll_event.cursor.as_f32() / self.scene.scale;
 
             if let Some(ref mut layer) = self.scene.root {
                 layer.handle_scroll_event(delta, cursor);
             }
-
-            self.perform_updates_after_scroll();
         }
 
-        if had_scroll_events {
-            self.send_viewport_rects_for_all_layers();
-        }
+        self.perform_updates_after_scroll();
+        self.send_viewport_rects_for_all_layers();
     }
 
     /// Computes new display ports for each layer, taking the scroll position into account, and
@@ -1018,6 +1686,9 @@ impl<Window: WindowMethods> IOCompositor<Window> {
     /// Performs buffer requests and starts the scrolling timer or schedules a recomposite as
     /// necessary.
     fn perform_updates_after_scroll(&mut self) {
+        self.last_scroll_time = precise_time_ns();
+        self.scrolling_timer.scrollbars_shown(self.last_scroll_time);
+        self.damage = Damage::Everything;
         self.send_updated_display_ports_to_layout();
         if self.send_buffer_requests_for_all_layers() {
             self.start_scrolling_timer_if_necessary();
@@ -1027,6 +1698,9 @@ impl<Window: WindowMethods> IOCompositor<Window> {
     }
 
     /// If there are any animations running, dispatches appropriate messages to the constellation.
+    /// Animated images are advanced by their paint tasks, which only need to repaint. This
+    /// happens once per frame, so that every animation and animation frame callback advances
+    /// together.
     fn process_animations(&mut self) {
         for (pipeline_id, pipeline_details) in self.pipeline_details.iter() {
             if pipeline_details.animations_running ||
@@ -1035,6 +1709,47 @@ impl<Window: WindowMethods> IOCompositor<Window> {
                 self.constellation_chan.0.send(ConstellationMsg::TickAnimation(*pipeline_id))
                                          .unwrap();
             }
+            if pipeline_details.animated_images_running {
+                if let Some(ref pipeline) = pipeline_details.pipeline {
+                    let _ = pipeline.paint_chan.send_opt(PaintMsg::TickAnimatedImages);
+                }
+            }
+        }
+    }
+
+    /// Returns true if any pipeline has animations, animation frame callbacks, or animated images
+    /// that need to be ticked in the next frame.
+    fn animations_running(&self) -> bool {
+        self.pipeline_details.values().any(|details| {
+            details.animations_running || details.animation_callbacks_running ||
+                details.animated_images_running
+        })
+    }
+
+    /// Starts a frame if a vertical blank has passed since the last one: the animations are
+    /// ticked, and the scene is composited if anything has changed since the last frame. Then
+    /// the next frame is asked for if there will be anything to do in it.
+    fn run_frame_if_due(&mut self) {
+        if self.frame_due {
+            self.frame_due = false;
+            self.process_animations();
+            match self.composition_request {
+                CompositionRequest::NoCompositingNecessary |
+                CompositionRequest::CompositeOnScrollTimeout(_) => {}
+                CompositionRequest::CompositeNow(_) => {
+                    self.composite()
+                }
+            }
+        }
+
+        let composite_pending = match self.composition_request {
+            CompositionRequest::CompositeNow(_) => true,
+            CompositionRequest::NoCompositingNecessary |
+            CompositionRequest::CompositeOnScrollTimeout(_) => false,
+        };
+        if !self.waiting_for_vsync && (composite_pending || self.animations_running()) {
+            self.vsync_timer.request_frame(self.last_composite_time);
+            self.waiting_for_vsync = true;
         }
     }
 
@@ -1049,6 +1764,7 @@ impl<Window: WindowMethods> IOCompositor<Window> {
             self.viewport_zoom = constraints.initial_zoom;
             self.min_viewport_zoom = constraints.min_zoom;
             self.max_viewport_zoom = constraints.max_zoom;
+            self.viewport_user_zoom = constraints.user_zoom;
             self.update_zoom_transform();
         }
     }
@@ -1064,12 +1780,14 @@ impl<Window: WindowMethods> IOCompositor<Window> {
     }
 
     fn device_pixels_per_page_px(&self) -> ScaleFactor<PagePx, DevicePixel, f32> {
-        self.viewport_zoom * self.page_zoom * self.device_pixels_per_screen_px()
+        self.viewport_zoom * self.page_zoom * self.device_pixels_per_screen_px() *
+            self.resize_scale()
     }
 
     fn update_zoom_transform(&mut self) {
         let scale = self.device_pixels_per_page_px();
         self.scene.scale = ScaleFactor::new(scale.get());
+        self.damage = Damage::Everything;
 
         // We need to set the size of the root layer again, since the window size
         // has changed in unscaled layer pixels.
@@ -1088,32 +1806,34 @@ impl<Window: WindowMethods> IOCompositor<Window> {
         self.send_window_size();
     }
 
+    /// Magnifies the page around `focal_point`, keeping the content under it in place, within
+    /// the zoom limits of the page's viewport. The page is scaled from its existing tiles until
+    /// the pinch ends.
     // TODO(pcwalton): I think this should go through the same queuing as scroll events do.
-    fn on_pinch_zoom_window_event(&mut self, magnification: f32) {
-        use num::Float;
+    fn on_pinch_zoom_window_event(&mut self,
+                                  magnification: f32,
+                                  focal_point: TypedPoint2D<DevicePixel, f32>) {
+        if self.viewport_user_zoom == UserZoom::Fixed {
+            return
+        }
 
         self.zoom_action = true;
         self.zoom_time = precise_time_s();
-        let old_viewport_zoom = self.viewport_zoom;
-
-        let mut viewport_zoom = self.viewport_zoom.get() * magnification;
-        if let Some(min_zoom) = self.min_viewport_zoom.as_ref() {
-            viewport_zoom = min_zoom.get().max(viewport_zoom)
-        }
-        let viewport_zoom = self.max_viewport_zoom
-                                .as_ref()
-                                .map_or(1., |z| z.get())
-                                .min(viewport_zoom);
-        let viewport_zoom = ScaleFactor::new(viewport_zoom);
-        self.viewport_zoom = viewport_zoom;
+        let old_scale = self.device_pixels_per_page_px();
 
+        let min_zoom = self.min_viewport_zoom.map_or(MIN_VIEWPORT_ZOOM, |zoom| zoom.get());
+        let max_zoom = self.max_viewport_zoom.map_or(MAX_VIEWPORT_ZOOM, |zoom| zoom.get());
+        let viewport_zoom = (self.viewport_zoom.get() * magnification).max(min_zoom)
+                                                                       .min(max_zoom);
+        self.viewport_zoom = ScaleFactor::new(viewport_zoom);
         self.update_zoom_transform();
 
-        // Scroll as needed
-        let window_size = self.window_size.as_f32();
-        let page_delta: TypedPoint2D<LayerPixel, f32> = Point2D::typed(
-            window_size.width.get() * (viewport_zoom.inv() - old_viewport_zoom.inv()).get() * 0.5,
-            window_size.height.get() * (viewport_zoom.inv() - old_viewport_zoom.inv()).get() * 0.5);
+        // The point of the page under the focal point is the focal point over the scale, less
+        // the scroll offset, so scroll by however much the first term changed.
+        let new_scale = self.device_pixels_per_page_px();
+        let change = new_scale.inv().get() - old_scale.inv().get();
+        let page_delta: TypedPoint2D<LayerPixel, f32> =
+            Point2D::typed(focal_point.x.get() * change, focal_point.y.get() * change);
 
         let cursor = Point2D::typed(-1f32, -1f32);  // Make sure this hits the base layer.
         match self.scene.root {
@@ -1123,10 +1843,21 @@ impl<Window: WindowMethods> IOCompositor<Window> {
             None => { }
         }
 
+        self.send_updated_display_ports_to_layout();
         self.send_viewport_rects_for_all_layers();
         self.composite_if_necessary(CompositingReason::Zoom);
     }
 
+    /// Paints the page again at the scale a pinch left it at.
+    fn on_pinch_zoom_end_window_event(&mut self) {
+        if !self.zoom_action {
+            return
+        }
+        self.zoom_action = false;
+        self.scene.mark_layer_contents_as_changed_recursively();
+        self.send_buffer_requests_for_all_layers();
+    }
+
     fn on_navigation_window_event(&self, direction: WindowNavigateMsg) {
         let direction = match direction {
             windowing::WindowNavigateMsg::Forward => NavigationDirection::Forward,
@@ -1136,6 +1867,20 @@ impl<Window: WindowMethods> IOCompositor<Window> {
         chan.send(ConstellationMsg::Navigate(None, direction)).unwrap()
     }
 
+    /// Tells the window where the caret is, given as a rect in the page of the given pipeline.
+    fn set_ime_caret_rect(&self, pipeline_id: PipelineId, rect: Rect<f32>) {
+        let root = match self.scene.root {
+            Some(ref root) => root,
+            None => return,
+        };
+        if let Some(rect) = highlight::rect_in_window_for_pipeline(&**root,
+                                                                   pipeline_id,
+                                                                   rect,
+                                                                   self.scene.scale) {
+            self.window.set_ime_caret_rect(rect);
+        }
+    }
+
     fn on_key_event(&self, key: Key, state: KeyState, modifiers: KeyModifiers) {
         let ConstellationChan(ref chan) = self.constellation_chan;
         chan.send(ConstellationMsg::KeyEvent(key, state, modifiers)).unwrap()
@@ -1225,10 +1970,24 @@ impl<Window: WindowMethods> IOCompositor<Window> {
                                               &Point2D::zero());
         }
 
+        // Tiles are requested for the parts of layers within the scene's viewport, so widen it
+        // while asking, to paint ahead of scrolling, and put it back afterwards.
+        let window_size = self.window_size.as_f32();
+        let (width, height) = (window_size.width.get(), window_size.height.get());
+        self.scene.viewport = Rect::new(
+            Point2D::typed(-width * PAINT_AHEAD_FACTOR, -height * PAINT_AHEAD_FACTOR),
+            Size2D::typed(width * (1.0 + 2.0 * PAINT_AHEAD_FACTOR),
+                          height * (1.0 + 2.0 * PAINT_AHEAD_FACTOR)));
+
         let mut layers_and_requests = Vec::new();
         let mut unused_buffers = Vec::new();
         self.scene.get_buffer_requests(&mut layers_and_requests, &mut unused_buffers);
 
+        self.scene.viewport = Rect {
+            origin: Point2D::zero(),
+            size: window_size,
+        };
+
         // Return unused tiles first, so that they can be reused by any new BufferRequests.
         self.send_back_unused_buffers(unused_buffers);
 
@@ -1277,6 +2036,11 @@ impl<Window: WindowMethods> IOCompositor<Window> {
     /// output matches the current frame tree output, and if the
     /// associated script tasks are idle.
     fn is_ready_to_paint_image_output(&mut self) -> bool {
+        // Painted tiles that haven't reached their layers yet aren't in the image.
+        if !self.queued_tiles.is_empty() {
+            return false
+        }
+
         match self.ready_to_save_state {
             ReadyState::Unknown => {
                 // Unsure if the output image is stable.
@@ -1303,7 +2067,8 @@ impl<Window: WindowMethods> IOCompositor<Window> {
                 for (id, details) in self.pipeline_details.iter() {
                     // If animations are currently running, then don't bother checking
                     // with the constellation if the output image is stable.
-                    if details.animations_running || details.animation_callbacks_running {
+                    if details.animations_running || details.animation_callbacks_running ||
+                       details.animated_images_running {
                         return false;
                     }
 
@@ -1337,6 +2102,189 @@ impl<Window: WindowMethods> IOCompositor<Window> {
     fn composite(&mut self) {
         let target = self.composite_target;
         self.composite_specific_target(target);
+        if !self.pending_screenshots.is_empty() {
+            self.take_screenshot();
+        }
+    }
+
+    /// Queues a screenshot, to be taken once the page is stable or it has waited too long.
+    fn request_screenshot(&mut self, request: ScreenshotRequest) {
+        if self.pending_screenshots.is_empty() {
+            self.start_screenshot_timer();
+        }
+        self.pending_screenshots.push(request);
+        self.composite_if_necessary(CompositingReason::Screenshot);
+    }
+
+    /// Asks for a `ScreenshotTimeout` once the oldest pending screenshot has waited too long.
+    fn start_screenshot_timer(&mut self) {
+        self.screenshot_deadline = precise_time_ns() + SCREENSHOT_TIMEOUT;
+        let compositor_proxy = self.channel_to_self.clone_compositor_proxy();
+        spawn_named("ScreenshotTimer".to_owned(), move || {
+            sleep_ms((SCREENSHOT_TIMEOUT / 1_000_000) as u32);
+            compositor_proxy.send(Msg::ScreenshotTimeout);
+        });
+    }
+
+    /// Draws the page again offscreen for the oldest pending screenshot request, if the page
+    /// is stable or the request has waited too long. The window isn't presented again.
+    fn take_screenshot(&mut self) {
+        let timed_out = precise_time_ns() >= self.screenshot_deadline;
+        if let Some(&ScreenshotRequest::Print) = self.pending_screenshots.first() {
+            if !self.is_laid_out_for_printing() && !timed_out {
+                return
+            }
+        }
+        if !self.is_ready_to_paint_image_output() && !timed_out {
+            return
+        }
+        if timed_out {
+            debug!("compositor: the page didn't become stable, taking the screenshot anyway");
+        }
+        let image = self.render_to_png();
+        self.finish_screenshot(image);
+    }
+
+    /// Draws the page into an offscreen framebuffer and reads it back, without the scrollbars
+    /// and highlights that are drawn over it in the window.
+    fn render_to_png(&mut self) -> Option<png::Image> {
+        let context = match self.context {
+            Some(context) => context,
+            None => return None,
+        };
+        let (width, height) =
+            (self.window_size.width.get() as usize, self.window_size.height.get() as usize);
+        let (framebuffer_ids, texture_ids) = initialize_png(width, height);
+        if let Some(ref layer) = self.scene.root {
+            rendergl::render_scene(layer.clone(), context, &self.scene);
+        }
+        Some(self.draw_png(framebuffer_ids, texture_ids, width, height))
+    }
+
+    /// Hands a frame to the oldest pending screenshot request, or `None` if the page couldn't
+    /// be drawn. Any others are given later frames.
+    fn finish_screenshot(&mut self, image: Option<png::Image>) {
+        if self.pending_screenshots.is_empty() {
+            return
+        }
+        match self.pending_screenshots.remove(0) {
+            ScreenshotRequest::Reply(reply) => {
+                let _ = reply.send(image);
+            }
+            ScreenshotRequest::File(path) => {
+                let saved = match image {
+                    Some(mut image) => png::store_png(&mut image, &path).is_ok(),
+                    None => false,
+                };
+                if !saved {
+                    warn!("failed to save a screenshot to {}", path);
+                }
+            }
+            ScreenshotRequest::Print => self.print_to_pdf(),
+        }
+        if !self.pending_screenshots.is_empty() {
+            self.start_screenshot_timer();
+            self.composite_if_necessary(CompositingReason::Screenshot);
+        }
+    }
+
+    /// Lays the page out for the paper it is to be printed on, and queues it to be printed once
+    /// it is stable. The window shows the page laid out for the paper until then. Only one page
+    /// is printed at a time.
+    fn start_printing(&mut self, path: String, paper_size: PaperSize) {
+        if let Some(ref job) = self.printing {
+            warn!("still printing to {}; not printing to {}", job.path, path);
+            return
+        }
+        let root_pipeline_id = match self.root_pipeline {
+            Some(ref root_pipeline) => root_pipeline.id,
+            None => {
+                warn!("no page to print to {}", path);
+                return
+            }
+        };
+        let resize_epoch = self.pipeline_details.get(&root_pipeline_id)
+                                                .map_or(Epoch(0), |details| details.current_epoch);
+
+        // Paper sizes are in points, and there are 96 CSS pixels to the 72 points of an inch.
+        // Page zoom doesn't apply to printing.
+        let page_size = Size2D::typed(paper_size.width * 96. / 72., paper_size.height * 96. / 72.);
+        let ConstellationChan(ref chan) = self.constellation_chan;
+        chan.send(ConstellationMsg::ResizedWindow(WindowSizeData {
+            device_pixel_ratio: ScaleFactor::new(self.device_pixels_per_screen_px().get()),
+            initial_viewport: page_size,
+            visible_viewport: page_size,
+        })).unwrap();
+
+        self.printing = Some(PrintJob {
+            path: path,
+            paper_size: paper_size,
+            resize_epoch: resize_epoch,
+            deadline: None,
+        });
+        self.request_screenshot(ScreenshotRequest::Print);
+    }
+
+    /// Whether the root pipeline has painted the page laid out for the paper it is printed on.
+    fn is_laid_out_for_printing(&self) -> bool {
+        let (root_pipeline, job) = match (&self.root_pipeline, &self.printing) {
+            (&Some(ref root_pipeline), &Some(ref job)) => (root_pipeline, job),
+            _ => return true,
+        };
+        self.pipeline_details.get(&root_pipeline.id).map_or(false, |details| {
+            details.current_epoch > job.resize_epoch
+        })
+    }
+
+    /// Has the paint task of the root pipeline print the page, and lays the page out for the
+    /// window again. The paint task prints the display list it was last given, so later ones
+    /// don't affect the print. It replies with `PrintedToPdf`, unless it takes so long that
+    /// printing is given up on.
+    fn print_to_pdf(&mut self) {
+        let (path, paper_size) = match self.printing {
+            Some(ref job) => (job.path.clone(), job.paper_size),
+            None => return,
+        };
+        let (sender, receiver) = channel();
+        match self.root_pipeline {
+            Some(ref root_pipeline) => {
+                root_pipeline.paint_chan.send(PaintMsg::PrintToPdf(path.clone(),
+                                                                   paper_size,
+                                                                   sender));
+            }
+            None => return self.finish_printing(false),
+        }
+        self.send_window_size();
+
+        let compositor_proxy = self.channel_to_self.clone_compositor_proxy();
+        spawn_named("PrintWaiter".to_owned(), move || {
+            let printed = receiver.recv().unwrap_or(false);
+            compositor_proxy.send(Msg::PrintedToPdf(path, printed));
+        });
+
+        self.printing.as_mut().unwrap().deadline = Some(precise_time_ns() + PRINT_TIMEOUT);
+        let compositor_proxy = self.channel_to_self.clone_compositor_proxy();
+        spawn_named("PrintTimer".to_owned(), move || {
+            sleep_ms((PRINT_TIMEOUT / 1_000_000) as u32);
+            compositor_proxy.send(Msg::PrintTimeout);
+        });
+    }
+
+    /// Ends the current print. When printing was asked for on the command line, exits.
+    fn finish_printing(&mut self, printed: bool) {
+        let job = match self.printing.take() {
+            Some(job) => job,
+            None => return,
+        };
+        if !printed {
+            warn!("failed to print to {}", job.path);
+        }
+        if opts::get().print_pdf.as_ref() == Some(&job.path) {
+            debug!("shutting down the constellation after printing");
+            let ConstellationChan(ref chan) = self.constellation_chan;
+            chan.send(ConstellationMsg::Exit).unwrap();
+            self.shutdown_state = ShutdownState::ShuttingDown;
+        }
     }
 
     pub fn composite_specific_target(&mut self, target: CompositeTarget) -> Option<png::Image> {
@@ -1349,8 +2297,14 @@ impl<Window: WindowMethods> IOCompositor<Window> {
             return None
         }
 
+        // An image of the page needs every tile that has been painted.
+        self.assign_queued_tiles(match target {
+            CompositeTarget::Window => Some(TILE_UPLOAD_BUDGET),
+            CompositeTarget::PngFile => None,
+        });
+
         match target {
-            CompositeTarget::WindowAndPng | CompositeTarget::PngFile => {
+            CompositeTarget::PngFile => {
                 if !self.is_ready_to_paint_image_output() {
                     return None
                 }
@@ -1358,6 +2312,33 @@ impl<Window: WindowMethods> IOCompositor<Window> {
             _ => {}
         }
 
+        // Windows that keep the contents of their back buffer only need the parts of it that
+        // have changed to be drawn again.
+        let damage = std_mem::replace(&mut self.damage, Damage::Nothing);
+        let partial_present = target == CompositeTarget::Window &&
+                              self.window.supports_partial_present();
+        let damage_rect = if partial_present {
+            match damage.to_window_rect(self.window_size) {
+                Some(rect) => rect,
+                None => {
+                    debug!("compositor: nothing has changed on the screen, skipping composite");
+                    self.composition_request = CompositionRequest::NoCompositingNecessary;
+                    return None
+                }
+            }
+        } else {
+            Rect::from_untyped(&Rect::new(Point2D::new(0, 0), self.window_size.to_untyped()))
+        };
+        let scissor = partial_present && damage_rect.size != self.window_size;
+        if scissor {
+            let rect = damage_rect.to_untyped();
+            gl::enable(gl::SCISSOR_TEST);
+            gl::scissor(rect.origin.x as GLint,
+                        (height as u32 - rect.max_y()) as GLint,
+                        rect.size.width as GLsizei,
+                        rect.size.height as GLsizei);
+        }
+
         let (framebuffer_ids, texture_ids) = match target {
             CompositeTarget::Window => (vec!(), vec!()),
             _ => initialize_png(width, height)
@@ -1382,11 +2363,18 @@ impl<Window: WindowMethods> IOCompositor<Window> {
             }
         });
 
+        // Scrollbars and highlights are left out of the images taken for reftests.
+        if target == CompositeTarget::Window {
+            self.draw_highlight();
+            self.draw_scrollbars();
+        }
+
+        if scissor {
+            gl::disable(gl::SCISSOR_TEST);
+        }
+
         let rv = match target {
             CompositeTarget::Window => None,
-            CompositeTarget::WindowAndPng => {
-                Some(self.draw_png(framebuffer_ids, texture_ids, width, height))
-            }
             CompositeTarget::PngFile => {
                 let mut img = self.draw_png(framebuffer_ids, texture_ids, width, height);
                 let path = opts::get().output_file.as_ref().unwrap();
@@ -1402,13 +2390,20 @@ impl<Window: WindowMethods> IOCompositor<Window> {
         };
 
         // Perform the page flip. This will likely block for a while.
-        self.window.present();
+        if partial_present {
+            self.window.present_damage(damage_rect);
+        } else {
+            self.window.present();
+        }
 
         self.last_composite_time = precise_time_ns();
 
         self.composition_request = CompositionRequest::NoCompositingNecessary;
+        if !self.queued_tiles.is_empty() {
+            self.composite_if_necessary(CompositingReason::NewPaintedBuffers);
+        }
         self.process_pending_scroll_events();
-        self.process_animations();
+        self.process_scroll_animation();
         rv
     }
 
@@ -1418,25 +2413,23 @@ impl<Window: WindowMethods> IOCompositor<Window> {
                 width: usize,
                 height: usize)
                 -> png::Image {
-        let mut pixels = gl::read_pixels(0, 0,
-                                         width as gl::GLsizei,
-                                         height as gl::GLsizei,
-                                         gl::RGB, gl::UNSIGNED_BYTE);
+        let rgba_pixels = gl::read_pixels(0, 0,
+                                          width as gl::GLsizei,
+                                          height as gl::GLsizei,
+                                          gl::RGBA, gl::UNSIGNED_BYTE);
 
         gl::bind_framebuffer(gl::FRAMEBUFFER, 0);
 
         gl::delete_buffers(&texture_ids);
         gl::delete_frame_buffers(&framebuffer_ids);
 
-        // flip image vertically (texture is upside down)
-        let orig_pixels = pixels.clone();
-        let stride = width * 3;
-        for y in 0..height {
-            let dst_start = y * stride;
-            let src_start = (height - y - 1) * stride;
-            let src_slice = &orig_pixels[src_start .. src_start + stride];
-            copy_memory(&src_slice[..stride],
-                        &mut pixels[dst_start .. dst_start + stride]);
+        // Drop the alpha channel, and flip the image vertically (the texture is upside down).
+        let mut pixels = Vec::with_capacity(width * height * 3);
+        for y in (0..height).rev() {
+            let row = &rgba_pixels[y * width * 4 .. (y + 1) * width * 4];
+            for pixel in row.chunks(4) {
+                pixels.push_all(&pixel[..3]);
+            }
         }
         png::Image {
             width: width as u32,
@@ -1445,7 +2438,12 @@ impl<Window: WindowMethods> IOCompositor<Window> {
         }
     }
 
+    /// Schedules a composite. New painted buffers only damage the layers they were painted for;
+    /// anything else draws the whole window again.
     fn composite_if_necessary(&mut self, reason: CompositingReason) {
+        if reason != CompositingReason::NewPaintedBuffers {
+            self.damage = Damage::Everything;
+        }
         if self.composition_request == CompositionRequest::NoCompositingNecessary {
             self.composition_request = CompositionRequest::CompositeNow(reason)
         }
@@ -1455,7 +2453,28 @@ impl<Window: WindowMethods> IOCompositor<Window> {
         let show_debug_borders = opts::get().show_debug_borders;
         self.context = Some(rendergl::RenderContext::new(self.native_display.clone(),
                                                          show_debug_borders,
-                                                         opts::get().output_file.is_some()))
+                                                         opts::get().output_file.is_some()));
+        self.scrollbar_renderer = ScrollbarRenderer::new();
+    }
+
+    /// Lets go of everything that belongs to the GL context, since the context may not outlive
+    /// the window's surface. The tiles are given back to the paint tasks, and painted again once
+    /// compositing resumes.
+    fn suspend_compositing(&mut self) {
+        if let Some(ref layer) = self.scene.root {
+            layer.clear_all_tiles(self);
+        }
+        while let Some(tile) = self.queued_tiles.pop_front() {
+            self.send_back_queued_tile(tile);
+        }
+        self.context = None;
+        self.scrollbar_renderer = None;
+    }
+
+    fn resume_compositing(&mut self) {
+        self.initialize_compositing();
+        self.send_buffer_requests_for_all_layers();
+        self.composite_if_necessary(CompositingReason::Resume);
     }
 
     fn find_topmost_layer_at_point_for_layer(&self,
@@ -1572,24 +2591,37 @@ impl<Window> CompositorEventListener for IOCompositor<Window> where Window: Wind
             return false;
         }
 
-        // Handle any messages coming from the windowing system.
-        for message in messages.into_iter() {
+        // Handle any messages coming from the windowing system. Of several resizes, only the
+        // last one matters.
+        let last_resize = messages.iter().rposition(|message| {
+            match *message {
+                WindowEvent::Resize(_) => true,
+                _ => false,
+            }
+        });
+        for (index, message) in messages.into_iter().enumerate() {
+            if let WindowEvent::Resize(_) = message {
+                if Some(index) != last_resize {
+                    continue
+                }
+            }
             self.handle_window_message(message);
         }
 
-        // If a pinch-zoom happened recently, ask for tiles at the new resolution
+        // If a pinch-zoom happened recently, ask for tiles at the new resolution, in case the
+        // window can't tell when pinches end.
         if self.zoom_action && precise_time_s() - self.zoom_time > 0.3 {
-            self.zoom_action = false;
-            self.scene.mark_layer_contents_as_changed_recursively();
-            self.send_buffer_requests_for_all_layers();
+            self.on_pinch_zoom_end_window_event();
         }
 
-        match self.composition_request {
-            CompositionRequest::NoCompositingNecessary |
-            CompositionRequest::CompositeOnScrollTimeout(_) => {}
-            CompositionRequest::CompositeNow(_) => {
-                self.composite()
-            }
+        // Keep compositing while the scrollbars fade out, until they are gone.
+        if self.scrollbar_opacity > 0.0 && self.scrollbar_drag.is_none() &&
+           precise_time_ns() - self.last_scroll_time >= SCROLLBAR_FADE_DELAY {
+            self.composite_if_necessary(CompositingReason::Animation);
+        }
+
+        if self.shutdown_state == ShutdownState::NotShuttingDown {
+            self.run_frame_if_due();
         }
 
         self.shutdown_state != ShutdownState::FinishedShuttingDown
@@ -1628,10 +2660,11 @@ impl<Window> CompositorEventListener for IOCompositor<Window> where Window: Wind
         // another task from finishing (i.e. SetFrameTree).
         while self.port.try_recv_compositor_msg().is_some() {}
 
-        // Tell the profiler, memory profiler, and scrolling timer to shut down.
+        // Tell the profiler, memory profiler, scrolling and vsync timers to shut down.
         self.time_profiler_chan.send(time::ProfilerMsg::Exit);
         self.mem_profiler_chan.send(mem::ProfilerMsg::Exit);
         self.scrolling_timer.shutdown();
+        self.vsync_timer.shutdown();
     }
 
     fn pinch_zoom_level(&self) -> f32 {
@@ -1667,4 +2700,12 @@ pub enum CompositingReason {
     NewPaintedBuffers,
     /// The window has been zoomed.
     Zoom,
+    /// The window has been resized, or the page has been laid out for its new size.
+    Resize,
+    /// An image of the page has been asked for.
+    Screenshot,
+    /// The developer tools are pointing out a different part of the page.
+    Highlight,
+    /// The window has a rendering surface again after being suspended.
+    Resume,
 }
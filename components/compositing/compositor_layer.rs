@@ -172,7 +172,7 @@ impl Clampable for f32 {
     }
 }
 
-fn calculate_content_size_for_layer(layer: &Layer<CompositorData>)
+pub fn calculate_content_size_for_layer(layer: &Layer<CompositorData>)
                                     -> TypedSize2D<LayerPixel, f32> {
     layer.children().iter().fold(Rect::zero(),
                                  |unioned_rect, child_rect| {
@@ -16,15 +16,20 @@ use euclid::rect::Rect;
 use layers::platform::surface::NativeDisplay;
 use layers::layers::LayerBufferSet;
 use msg::compositor_msg::{Epoch, LayerId, LayerProperties, FrameTreeId};
-use msg::compositor_msg::{PaintListener, ScriptListener};
-use msg::constellation_msg::{AnimationState, ConstellationChan, PipelineId};
+use msg::compositor_msg::{PaintListener, ScriptListener, ScrollBehavior};
+use msg::constellation_msg::{AnimationState, AuthenticationRequest, CertificateError};
+use msg::constellation_msg::{ConstellationChan, Credentials, PipelineId};
 use msg::constellation_msg::{Key, KeyState, KeyModifiers};
+use msg::constellation_msg::{MixedContentState, NotificationData, NotificationId};
+use msg::constellation_msg::PermissionName;
+use net_traits::image::base::Image;
 use profile_traits::mem;
 use profile_traits::time;
 use png;
 use std::sync::mpsc::{channel, Sender, Receiver};
 use std::fmt::{Error, Formatter, Debug};
 use std::rc::Rc;
+use std::sync::Arc;
 use style::viewport::ViewportConstraints;
 use url::Url;
 use util::cursor::Cursor;
@@ -66,8 +71,9 @@ impl ScriptListener for Box<CompositorProxy+'static+Send> {
     fn scroll_fragment_point(&mut self,
                              pipeline_id: PipelineId,
                              layer_id: LayerId,
-                             point: Point2D<f32>) {
-        self.send(Msg::ScrollFragmentPoint(pipeline_id, layer_id, point));
+                             point: Point2D<f32>,
+                             behavior: ScrollBehavior) {
+        self.send(Msg::ScrollFragmentPoint(pipeline_id, layer_id, point, behavior));
     }
 
     fn close(&mut self) {
@@ -87,6 +93,14 @@ impl ScriptListener for Box<CompositorProxy+'static+Send> {
     fn send_key_event(&mut self, key: Key, state: KeyState, modifiers: KeyModifiers) {
         self.send(Msg::KeyEvent(key, state, modifiers));
     }
+
+    fn highlight_rect(&mut self, pipeline_id: PipelineId, rect: Option<Rect<f32>>) {
+        self.send(Msg::HighlightRect(pipeline_id, rect))
+    }
+
+    fn set_ime_caret_rect(&mut self, pipeline_id: PipelineId, rect: Rect<f32>) {
+        self.send(Msg::ImeCaretRect(pipeline_id, rect))
+    }
 }
 
 /// Implementation of the abstract `PaintListener` interface.
@@ -124,6 +138,10 @@ impl PaintListener for Box<CompositorProxy+'static+Send> {
     fn notify_paint_task_exiting(&mut self, pipeline_id: PipelineId) {
         self.send(Msg::PaintTaskExited(pipeline_id))
     }
+
+    fn set_animated_images_running(&mut self, pipeline_id: PipelineId, running: bool) {
+        self.send(Msg::ChangeRunningAnimatedImagesState(pipeline_id, running))
+    }
 }
 
 /// Messages from the painting task and the constellation task to the compositor task.
@@ -148,8 +166,8 @@ pub enum Msg {
     InitializeLayersForPipeline(PipelineId, Epoch, Vec<LayerProperties>),
     /// Alerts the compositor that the specified layer's rect has changed.
     SetLayerRect(PipelineId, LayerId, Rect<f32>),
-    /// Scroll a page in a window
-    ScrollFragmentPoint(PipelineId, LayerId, Point2D<f32>),
+    /// Scroll a page in a window, either immediately or by animating towards the point.
+    ScrollFragmentPoint(PipelineId, LayerId, Point2D<f32>, ScrollBehavior),
     /// Requests that the compositor assign the painted buffers to the given layers.
     AssignPaintedBuffers(PipelineId, Epoch, Vec<(LayerId, Box<LayerBufferSet>)>, FrameTreeId),
     /// Alerts the compositor that the current page has changed its title.
@@ -158,6 +176,9 @@ pub enum Msg {
     ChangePageUrl(PipelineId, Url),
     /// Alerts the compositor that the given pipeline has changed whether it is running animations.
     ChangeRunningAnimationsState(PipelineId, AnimationState),
+    /// Alerts the compositor that the given pipeline has started or stopped showing animated
+    /// images that are playing.
+    ChangeRunningAnimatedImagesState(PipelineId, bool),
     /// Replaces the current frame tree, typically called during main frame navigation.
     SetFrameTree(SendableFrameTree, Sender<()>, ConstellationChan),
     /// The load of a page has begun: (can go back, can go forward).
@@ -168,10 +189,29 @@ pub enum Msg {
     /// composite should happen. (See the `scrolling` module.)
     ScrollTimeout(u64),
     RecompositeAfterScroll,
+    /// Indicates that enough time has passed since the last reflow for a window resize that
+    /// another may be asked for. (See the `scrolling` module.)
+    ResizeTimeout,
+    /// Indicates that the scrollbars are due to start fading out. (See the `scrolling` module.)
+    ScrollbarFadeTimeout,
+    /// Indicates that the oldest pending screenshot has waited long enough for the page to
+    /// become stable.
+    ScreenshotTimeout,
+    /// Tells whether the page was printed to the PDF file at the given path.
+    PrintedToPdf(String, bool),
+    /// Indicates that the paint task has had long enough to print the page.
+    PrintTimeout,
+    /// Indicates that the display has reached the vertical blank at the given time, and a frame
+    /// may be composited. (See the `vsync` module.)
+    Vsync(u64),
     /// Sends an unconsumed key event back to the compositor.
     KeyEvent(Key, KeyState, KeyModifiers),
     /// Changes the cursor.
     SetCursor(Cursor),
+    /// Outlines a rectangle of a page, in page pixels, or removes the outline.
+    HighlightRect(PipelineId, Option<Rect<f32>>),
+    /// Tells the embedder where the caret of the focused text field is, in page pixels.
+    ImeCaretRect(PipelineId, Rect<f32>),
     /// Composite to a PNG file and return the Image over a passed channel.
     CreatePng(Sender<Option<png::Image>>),
     /// Informs the compositor that the paint task for the given pipeline has exited.
@@ -182,8 +222,24 @@ pub enum Msg {
     IsReadyToSaveImageReply(bool),
     /// A favicon was detected
     NewFavicon(Url),
+    /// The icon of the document in the given pipeline was loaded.
+    ChangeFavicon(PipelineId, Arc<Image>),
     /// <head> tag finished parsing
     HeadParsed,
+    /// Asks the user whether the document at the given URL may use a feature.
+    RequestPermission(PipelineId, PermissionName, Url),
+    /// Asks the embedder to display a notification.
+    ShowNotification(PipelineId, NotificationId, NotificationData),
+    /// Asks the embedder to stop displaying a notification.
+    CloseNotification(PipelineId, NotificationId),
+    /// Asks the embedder for the user's current location.
+    RequestGeolocationPosition(PipelineId, bool),
+    /// Tells the embedder that a secure document requested insecure content.
+    MixedContent(PipelineId, Url, MixedContentState),
+    /// Asks the embedder whether to trust a server whose certificate couldn't be verified.
+    CertificateError(PipelineId, CertificateError),
+    /// Asks the embedder for the user's credentials, to be sent back on the given channel.
+    RequestCredentials(Option<PipelineId>, AuthenticationRequest, Sender<Option<Credentials>>),
 }
 
 impl Debug for Msg {
@@ -197,6 +253,9 @@ impl Debug for Msg {
             Msg::ScrollFragmentPoint(..) => write!(f, "ScrollFragmentPoint"),
             Msg::AssignPaintedBuffers(..) => write!(f, "AssignPaintedBuffers"),
             Msg::ChangeRunningAnimationsState(..) => write!(f, "ChangeRunningAnimationsState"),
+            Msg::ChangeRunningAnimatedImagesState(..) => {
+                write!(f, "ChangeRunningAnimatedImagesState")
+            }
             Msg::ChangePageTitle(..) => write!(f, "ChangePageTitle"),
             Msg::ChangePageUrl(..) => write!(f, "ChangePageUrl"),
             Msg::SetFrameTree(..) => write!(f, "SetFrameTree"),
@@ -204,14 +263,30 @@ impl Debug for Msg {
             Msg::LoadStart(..) => write!(f, "LoadStart"),
             Msg::ScrollTimeout(..) => write!(f, "ScrollTimeout"),
             Msg::RecompositeAfterScroll => write!(f, "RecompositeAfterScroll"),
+            Msg::ResizeTimeout => write!(f, "ResizeTimeout"),
+            Msg::ScrollbarFadeTimeout => write!(f, "ScrollbarFadeTimeout"),
+            Msg::ScreenshotTimeout => write!(f, "ScreenshotTimeout"),
+            Msg::PrintedToPdf(..) => write!(f, "PrintedToPdf"),
+            Msg::PrintTimeout => write!(f, "PrintTimeout"),
+            Msg::Vsync(..) => write!(f, "Vsync"),
             Msg::KeyEvent(..) => write!(f, "KeyEvent"),
             Msg::SetCursor(..) => write!(f, "SetCursor"),
+            Msg::HighlightRect(..) => write!(f, "HighlightRect"),
+            Msg::ImeCaretRect(..) => write!(f, "ImeCaretRect"),
             Msg::CreatePng(..) => write!(f, "CreatePng"),
             Msg::PaintTaskExited(..) => write!(f, "PaintTaskExited"),
             Msg::ViewportConstrained(..) => write!(f, "ViewportConstrained"),
             Msg::IsReadyToSaveImageReply(..) => write!(f, "IsReadyToSaveImageReply"),
             Msg::NewFavicon(..) => write!(f, "NewFavicon"),
+            Msg::ChangeFavicon(..) => write!(f, "ChangeFavicon"),
             Msg::HeadParsed => write!(f, "HeadParsed"),
+            Msg::RequestPermission(..) => write!(f, "RequestPermission"),
+            Msg::ShowNotification(..) => write!(f, "ShowNotification"),
+            Msg::CloseNotification(..) => write!(f, "CloseNotification"),
+            Msg::RequestGeolocationPosition(..) => write!(f, "RequestGeolocationPosition"),
+            Msg::MixedContent(..) => write!(f, "MixedContent"),
+            Msg::CertificateError(..) => write!(f, "CertificateError"),
+            Msg::RequestCredentials(..) => write!(f, "RequestCredentials"),
         }
     }
 }
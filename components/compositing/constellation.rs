@@ -13,25 +13,29 @@ use pipeline::{Pipeline, CompositionPipeline};
 
 use compositor_task::CompositorProxy;
 use compositor_task::Msg as CompositorMsg;
+use content_process::ContentProcess;
 use devtools_traits::{DevtoolsControlChan, DevtoolsControlMsg};
 use euclid::point::Point2D;
 use euclid::rect::{Rect, TypedRect};
 use euclid::size::Size2D;
 use euclid::scale_factor::ScaleFactor;
+use gamepad::GamepadMonitor;
 use gfx::font_cache_task::FontCacheTask;
 use layout_traits::{LayoutControlChan, LayoutControlMsg, LayoutTaskFactory};
 use libc;
 use msg::compositor_msg::{Epoch, LayerId};
-use msg::constellation_msg::AnimationState;
+use msg::constellation_msg::{AnimationState, CertificateError, DocumentState};
 use msg::constellation_msg::Msg as ConstellationMsg;
 use msg::constellation_msg::{FrameId, PipelineExitType, PipelineId};
 use msg::constellation_msg::{IFrameSandboxState, MozBrowserEvent, NavigationDirection};
-use msg::constellation_msg::{Key, KeyState, KeyModifiers, LoadData};
+use msg::constellation_msg::PermissionName;
+use msg::constellation_msg::{ImeEvent, Key, KeyState, KeyModifiers, LoadData};
 use msg::constellation_msg::{SubpageId, WindowSizeData};
 use msg::constellation_msg::{self, ConstellationChan, Failure};
 use msg::constellation_msg::WebDriverCommandMsg;
 use msg::webdriver_msg;
-use net_traits::{self, ResourceTask};
+use net_traits::{self, LoadPriority, ResourceTask, serialize_origin};
+use net_traits::image::base::load_favicon_from_memory;
 use net_traits::image_cache_task::ImageCacheTask;
 use net_traits::storage_task::{StorageTask, StorageTaskMsg};
 use profile_traits::mem;
@@ -39,13 +43,15 @@ use profile_traits::time;
 use script_traits::{CompositorEvent, ConstellationControlMsg};
 use script_traits::{ScriptControlChan, ScriptState, ScriptTaskFactory};
 use std::borrow::ToOwned;
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use std::io::{self, Write};
 use std::marker::PhantomData;
+use std::sync::Arc;
 use std::mem::replace;
+use std::rc::Rc;
 use std::sync::mpsc::{Sender, Receiver, channel};
 use style::viewport::ViewportConstraints;
-use url::Url;
+use url::{Url, UrlParser};
 use util::cursor::Cursor;
 use util::geometry::PagePx;
 use util::opts;
@@ -125,33 +131,99 @@ pub struct Constellation<LTF, STF> {
     clipboard_ctx: Option<ClipboardContext>,
 
     /// Bits of state used to interact with the webdriver implementation
-    webdriver: WebDriverData
+    webdriver: WebDriverData,
+
+    /// The user's answers to permission requests, keyed by feature and origin.
+    permissions: HashMap<(PermissionName, String), bool>,
+
+    /// Polls the game controllers for the script tasks that watch them.
+    gamepad_monitor: GamepadMonitor,
+
+    /// The pipelines whose documents named their own icons.
+    pipelines_with_favicons: HashSet<PipelineId>,
+
+    /// The instant the next session history entry is given.
+    next_history_instant: u64,
+
+    /// The state frozen documents were in, to restore should their pipelines be closed and
+    /// their session history entries be reloaded.
+    document_states: HashMap<PipelineId, DocumentState>,
+
+    /// The state to restore in reloaded session history entries once they finish loading.
+    pending_document_states: HashMap<PipelineId, DocumentState>,
+
+    /// Pipelines whose script or layout task crashed, and which are closed once the error pages
+    /// replacing them are ready to paint.
+    crashed_pipelines: HashSet<PipelineId>,
 }
 
+/// The size favicons are picked for, in pixels. Embedders scale the icon to fit.
+const FAVICON_SIZE: u32 = 32;
+
 /// Stores the navigation context for a single frame in the frame tree.
 pub struct Frame {
-    prev: Vec<PipelineId>,
+    prev: Vec<FrameEntry>,
     current: PipelineId,
-    next: Vec<PipelineId>,
+    /// When the current session history entry was added.
+    instant: u64,
+    next: Vec<FrameEntry>,
+    /// The session history entry that a traversal made current, if its pipeline was closed and
+    /// has yet to be loaded again. Until it is, the frame keeps displaying `current`.
+    reload: Option<FrameEntry>,
+}
+
+/// A session history entry of a frame, other than its current one.
+struct FrameEntry {
+    /// The frozen pipeline displaying this entry, or `None` if it was closed to limit the
+    /// number of frozen pipelines, in which case the entry is reloaded when revisited.
+    pipeline_id: Option<PipelineId>,
+    url: Url,
+    /// The origin of the entry's document, which it keeps if reloaded.
+    origin: String,
+    /// When this entry was added. This orders the entries of all the frames in a frame tree
+    /// into its joint session history.
+    instant: u64,
+    /// The state of the document when its pipeline was closed.
+    state: Option<DocumentState>,
 }
 
 impl Frame {
-    fn new(pipeline_id: PipelineId) -> Frame {
+    fn new(pipeline_id: PipelineId, instant: u64) -> Frame {
         Frame {
             prev: vec!(),
             current: pipeline_id,
+            instant: instant,
             next: vec!(),
+            reload: None,
         }
     }
 
-    fn load(&mut self, pipeline_id: PipelineId) -> Vec<PipelineId> {
-        // TODO(gw): To also allow navigations within subframes
-        // to affect the parent navigation history, this should bubble
-        // up the navigation change to each parent.
-        self.prev.push(self.current);
+    /// Adds a new session history entry after the current one, and returns the entries it
+    /// evicted from the forward history.
+    fn load(&mut self, pipeline_id: PipelineId, instant: u64, current_url: Url,
+            current_origin: String)
+            -> Vec<FrameEntry> {
+        self.prev.push(FrameEntry {
+            pipeline_id: Some(self.current),
+            url: current_url,
+            origin: current_origin,
+            instant: self.instant,
+            state: None,
+        });
         self.current = pipeline_id;
+        self.instant = instant;
         replace(&mut self.next, vec!())
     }
+
+    /// The pipelines of all the session history entries that are kept alive.
+    fn pipelines(&self) -> Vec<PipelineId> {
+        let mut pipelines: Vec<PipelineId> = self.prev.iter()
+                                                      .filter_map(|entry| entry.pipeline_id)
+                                                      .collect();
+        pipelines.push(self.current);
+        pipelines.extend(self.next.iter().filter_map(|entry| entry.pipeline_id));
+        pipelines
+    }
 }
 
 /// Represents a pending change in the frame tree, that will be applied
@@ -160,6 +232,9 @@ struct FrameChange {
     old_pipeline_id: Option<PipelineId>,
     new_pipeline_id: PipelineId,
     painter_ready: bool,
+    /// Whether the new pipeline reloads a session history entry that has already been made
+    /// current, rather than adding a new entry.
+    reloads_entry: bool,
 }
 
 /// An iterator over a frame tree, returning nodes in depth-first order.
@@ -254,7 +329,14 @@ impl<LTF: LayoutTaskFactory, STF: ScriptTaskFactory> Constellation<LTF, STF> {
                 } else {
                     None
                 },
-                webdriver: WebDriverData::new()
+                webdriver: WebDriverData::new(),
+                permissions: HashMap::new(),
+                gamepad_monitor: GamepadMonitor::new(),
+                pipelines_with_favicons: HashSet::new(),
+                next_history_instant: 0,
+                document_states: HashMap::new(),
+                pending_document_states: HashMap::new(),
+                crashed_pipelines: HashSet::new(),
             };
             constellation.run();
         });
@@ -271,8 +353,11 @@ impl<LTF: LayoutTaskFactory, STF: ScriptTaskFactory> Constellation<LTF, STF> {
     }
 
     /// Helper function for creating a pipeline
+    /// Creates a pipeline for `load_data`. `creator` is the pipeline whose document caused the
+    /// load, if any, which an about:blank document takes its origin from.
     fn new_pipeline(&mut self,
                     parent_info: Option<(PipelineId, SubpageId)>,
+                    creator: Option<PipelineId>,
                     initial_window_rect: Option<TypedRect<PagePx, f32>>,
                     script_channel: Option<ScriptControlChan>,
                     load_data: LoadData)
@@ -281,6 +366,14 @@ impl<LTF: LayoutTaskFactory, STF: ScriptTaskFactory> Constellation<LTF, STF> {
         let PipelineId(ref mut i) = self.next_pipeline_id;
         *i += 1;
 
+        let origin = self.origin_for(&load_data.url, creator);
+
+        let (content_process, script_channel) = if opts::get().multiprocess {
+            self.content_process_for(parent_info, script_channel)
+        } else {
+            (None, script_channel)
+        };
+
         let pipeline = Pipeline::create::<LTF, STF>(pipeline_id,
                                                     parent_info,
                                                     self.chan.clone(),
@@ -295,13 +388,62 @@ impl<LTF: LayoutTaskFactory, STF: ScriptTaskFactory> Constellation<LTF, STF> {
                                                     initial_window_rect,
                                                     script_channel,
                                                     load_data,
-                                                    self.window_size.device_pixel_ratio);
+                                                    self.window_size.device_pixel_ratio,
+                                                    content_process);
 
         assert!(!self.pipelines.contains_key(&pipeline_id));
         self.pipelines.insert(pipeline_id, pipeline);
+        self.mut_pipeline(pipeline_id).origin = origin;
         pipeline_id
     }
 
+    /// Returns the content process that a new pipeline runs in, with the script task it should
+    /// share, if any. An iframe runs in the process of the pipeline it is in, and other pipelines
+    /// start a process of their own, as do iframes whose parent's process has crashed. Those
+    /// can't share a script task with their parent.
+    fn content_process_for(&self,
+                           parent_info: Option<(PipelineId, SubpageId)>,
+                           script_channel: Option<ScriptControlChan>)
+                           -> (Option<Rc<ContentProcess>>, Option<ScriptControlChan>) {
+        if let Some((parent_id, _)) = parent_info {
+            if let Some(parent) = self.pipelines.get(&parent_id) {
+                match parent.content_process {
+                    Some(ref content_process) if content_process.is_alive() => {
+                        return (Some(content_process.clone()), script_channel)
+                    }
+                    Some(_) => {}
+                    None => return (None, script_channel),
+                }
+            }
+        }
+
+        match ContentProcess::spawn(self.chan.clone(),
+                                    self.compositor_proxy.clone_compositor_proxy(),
+                                    self.resource_task.clone(),
+                                    self.image_cache_task.clone(),
+                                    self.font_cache_task.clone(),
+                                    self.storage_task.clone(),
+                                    self.time_profiler_chan.clone(),
+                                    self.mem_profiler_chan.clone()) {
+            Ok(content_process) => (Some(Rc::new(content_process)), None),
+            Err(error) => {
+                warn!("couldn't start a content process, running content here: {}", error);
+                (None, None)
+            }
+        }
+    }
+
+    /// The origin of a document loaded from `url` because of `creator`'s document.
+    /// https://html.spec.whatwg.org/multipage/#origin-2
+    fn origin_for(&self, url: &Url, creator: Option<PipelineId>) -> String {
+        match creator {
+            Some(creator) if url.serialize() == "about:blank" => {
+                self.pipeline(creator).origin.clone()
+            }
+            _ => serialize_origin(url),
+        }
+    }
+
     // Push a new (loading) pipeline to the list of pending frame changes
     fn push_pending_frame(&mut self, new_pipeline_id: PipelineId,
                           old_pipeline_id: Option<PipelineId>) {
@@ -309,6 +451,7 @@ impl<LTF: LayoutTaskFactory, STF: ScriptTaskFactory> Constellation<LTF, STF> {
             old_pipeline_id: old_pipeline_id,
             new_pipeline_id: new_pipeline_id,
             painter_ready: false,
+            reloads_entry: false,
         });
     }
 
@@ -326,13 +469,20 @@ impl<LTF: LayoutTaskFactory, STF: ScriptTaskFactory> Constellation<LTF, STF> {
         }
     }
 
+    // Get the instant to give a new session history entry.
+    fn new_history_instant(&mut self) -> u64 {
+        let instant = self.next_history_instant;
+        self.next_history_instant += 1;
+        instant
+    }
+
     // Create a new frame and update the internal bookkeeping.
     fn new_frame(&mut self, pipeline_id: PipelineId) -> FrameId {
         let id = self.next_frame_id;
         let FrameId(ref mut i) = self.next_frame_id;
         *i += 1;
 
-        let frame = Frame::new(pipeline_id);
+        let frame = Frame::new(pipeline_id, self.new_history_instant());
 
         assert!(!self.pipeline_to_frame_map.contains_key(&pipeline_id));
         assert!(!self.frames.contains_key(&id));
@@ -365,7 +515,7 @@ impl<LTF: LayoutTaskFactory, STF: ScriptTaskFactory> Constellation<LTF, STF> {
                 debug!("constellation got frame rect message");
                 self.handle_frame_rect_msg(pipeline_id, subpage_id, Rect::from_untyped(&rect));
             }
-            ConstellationMsg::ScriptLoadedURLInIFrame(url,
+            ConstellationMsg::ScriptLoadedURLInIFrame(load_data,
                                                       source_pipeline_id,
                                                       new_subpage_id,
                                                       old_subpage_id,
@@ -374,7 +524,7 @@ impl<LTF: LayoutTaskFactory, STF: ScriptTaskFactory> Constellation<LTF, STF> {
                        source_pipeline_id,
                        old_subpage_id,
                        new_subpage_id);
-                self.handle_script_loaded_url_in_iframe_msg(url,
+                self.handle_script_loaded_url_in_iframe_msg(load_data,
                                                             source_pipeline_id,
                                                             new_subpage_id,
                                                             old_subpage_id,
@@ -405,6 +555,21 @@ impl<LTF: LayoutTaskFactory, STF: ScriptTaskFactory> Constellation<LTF, STF> {
                 debug!("constellation got navigation message");
                 self.handle_navigate_msg(pipeline_info, direction);
             }
+            ConstellationMsg::TraverseHistory(pipeline_id, delta) => {
+                debug!("constellation got traverse history message");
+                self.handle_traverse_history_msg(pipeline_id, delta);
+            }
+            ConstellationMsg::GetHistoryLength(pipeline_id, sender) => {
+                debug!("constellation got get history length message");
+                let length = self.history_length(pipeline_id);
+                sender.send(length).unwrap();
+            }
+            ConstellationMsg::SaveDocumentState(pipeline_id, state) => {
+                debug!("constellation got save document state message");
+                if self.pipelines.contains_key(&pipeline_id) {
+                    self.document_states.insert(pipeline_id, state);
+                }
+            }
             // Notification that painting has finished and is requesting permission to paint.
             ConstellationMsg::PainterReady(pipeline_id) => {
                 debug!("constellation got painter ready message");
@@ -418,6 +583,10 @@ impl<LTF: LayoutTaskFactory, STF: ScriptTaskFactory> Constellation<LTF, STF> {
                 debug!("constellation got key event message");
                 self.handle_key_msg(key, state, modifiers);
             }
+            ConstellationMsg::ImeEvent(event) => {
+                debug!("constellation got IME event message");
+                self.handle_ime_msg(event);
+            }
             ConstellationMsg::GetPipelineTitle(pipeline_id) => {
                 debug!("constellation got get-pipeline-title message");
                 self.handle_get_pipeline_title_msg(pipeline_id);
@@ -468,14 +637,81 @@ impl<LTF: LayoutTaskFactory, STF: ScriptTaskFactory> Constellation<LTF, STF> {
                 debug!("constellation got remove iframe message");
                 self.handle_remove_iframe_msg(containing_pipeline_id, subpage_id);
             }
-            ConstellationMsg::NewFavicon(url) => {
+            ConstellationMsg::NewFavicon(pipeline_id, url) => {
                 debug!("constellation got new favicon message");
-                self.compositor_proxy.send(CompositorMsg::NewFavicon(url));
+                self.handle_new_favicon_msg(pipeline_id, url);
             }
             ConstellationMsg::HeadParsed => {
                 debug!("constellation got head parsed message");
                 self.compositor_proxy.send(CompositorMsg::HeadParsed);
             }
+            ConstellationMsg::RequestPermission(pipeline_id, name, url) => {
+                debug!("constellation got permission request");
+                self.handle_request_permission_msg(pipeline_id, name, url);
+            }
+            ConstellationMsg::PermissionResponse(pipeline_id, name, url, granted) => {
+                debug!("constellation got permission response");
+                self.handle_permission_response_msg(pipeline_id, name, url, granted);
+            }
+            ConstellationMsg::GetPermission(name, url, sender) => {
+                debug!("constellation got get permission message");
+                let granted = self.permissions.get(&(name, serialize_origin(&url))).cloned();
+                sender.send(granted).unwrap();
+            }
+            ConstellationMsg::ShowNotification(pipeline_id, id, notification) => {
+                debug!("constellation got show notification message");
+                self.compositor_proxy.send(CompositorMsg::ShowNotification(pipeline_id,
+                                                                           id,
+                                                                           notification));
+            }
+            ConstellationMsg::CloseNotification(pipeline_id, id) => {
+                debug!("constellation got close notification message");
+                self.compositor_proxy.send(CompositorMsg::CloseNotification(pipeline_id, id));
+            }
+            ConstellationMsg::NotificationEvent(pipeline_id, id, event_type) => {
+                debug!("constellation got notification event message");
+                self.send_to_script(pipeline_id,
+                                    ConstellationControlMsg::NotificationEvent(pipeline_id,
+                                                                               id,
+                                                                               event_type));
+            }
+            ConstellationMsg::RequestGeolocationPosition(pipeline_id, enable_high_accuracy) => {
+                debug!("constellation got geolocation position request");
+                self.compositor_proxy.send(
+                    CompositorMsg::RequestGeolocationPosition(pipeline_id, enable_high_accuracy));
+            }
+            ConstellationMsg::GeolocationPositionResponse(pipeline_id, position) => {
+                debug!("constellation got geolocation position response");
+                self.send_to_script(pipeline_id,
+                                    ConstellationControlMsg::GeolocationPosition(pipeline_id,
+                                                                                position));
+            }
+            ConstellationMsg::MixedContent(pipeline_id, url, state) => {
+                debug!("constellation got mixed content message");
+                self.compositor_proxy.send(CompositorMsg::MixedContent(pipeline_id, url, state));
+            }
+            ConstellationMsg::AllowMixedContent(pipeline_id) => {
+                debug!("constellation got allow mixed content message");
+                self.handle_allow_mixed_content_msg(pipeline_id);
+            }
+            ConstellationMsg::CertificateError(pipeline_id, error) => {
+                debug!("constellation got certificate error message");
+                self.compositor_proxy.send(CompositorMsg::CertificateError(pipeline_id, error));
+            }
+            ConstellationMsg::CertificateErrorResponse(pipeline_id, error, allowed) => {
+                debug!("constellation got certificate error response");
+                self.handle_certificate_error_response_msg(pipeline_id, error, allowed);
+            }
+            ConstellationMsg::RequestCredentials(pipeline_id, request, sender) => {
+                debug!("constellation got credentials request");
+                self.compositor_proxy.send(CompositorMsg::RequestCredentials(pipeline_id,
+                                                                             request,
+                                                                             sender));
+            }
+            ConstellationMsg::WatchGamepads(sender) => {
+                debug!("constellation got watch gamepads message");
+                self.gamepad_monitor.watch(sender);
+            }
         }
         true
     }
@@ -507,8 +743,13 @@ impl<LTF: LayoutTaskFactory, STF: ScriptTaskFactory> Constellation<LTF, STF> {
             unsafe { libc::exit(1); }
         }
 
-        self.close_pipeline(pipeline_id, ExitPipelineMode::Force);
+        // Script and layout may both report the failure of a pipeline, which is only replaced once.
+        if !self.pipelines.contains_key(&pipeline_id) ||
+                self.crashed_pipelines.contains(&pipeline_id) {
+            return;
+        }
 
+        // Abandon any navigation away from the crashed document.
         loop {
             let pending_pipeline_id = self.pending_frames.iter().find(|pending| {
                 pending.old_pipeline_id == Some(pipeline_id)
@@ -521,22 +762,74 @@ impl<LTF: LayoutTaskFactory, STF: ScriptTaskFactory> Constellation<LTF, STF> {
                 None => break,
             }
         }
-        debug!("creating replacement pipeline for about:failure");
 
-        let window_rect = self.pipeline(pipeline_id).rect;
-        let new_pipeline_id =
-            self.new_pipeline(parent_info,
-                              window_rect,
-                              None,
-                              LoadData::new(Url::parse("about:failure").unwrap()));
+        let frame_id = self.pipeline_to_frame_map.get(&pipeline_id).map(|frame_id| *frame_id);
+        let is_current = frame_id.map_or(false, |frame_id| {
+            self.frame(frame_id).current == pipeline_id
+        });
+        let pending_change = self.pending_frames.iter().find(|frame_change| {
+            frame_change.new_pipeline_id == pipeline_id
+        }).map(|frame_change| frame_change.old_pipeline_id);
+
+        if !is_current && pending_change.is_none() {
+            // A frozen document crashed. Its session history entry is reloaded if revisited.
+            if let Some(frame_id) = frame_id {
+                let state = self.document_states.remove(&pipeline_id);
+                let frame = self.mut_frame(frame_id);
+                let entry = frame.prev.iter_mut().chain(frame.next.iter_mut()).find(|entry| {
+                    entry.pipeline_id == Some(pipeline_id)
+                });
+                if let Some(entry) = entry {
+                    entry.pipeline_id = None;
+                    entry.state = state;
+                }
+            }
+            self.close_pipeline(pipeline_id, ExitPipelineMode::Force);
+            return;
+        }
+
+        debug!("creating replacement pipeline for about:crashed");
+        let (window_rect, url) = {
+            let pipeline = self.pipeline(pipeline_id);
+            (pipeline.rect, pipeline.url.serialize())
+        };
+        let mut crashed_url = Url::parse("about:crashed").unwrap();
+        crashed_url.set_query_from_pairs(vec![("url", &*url)].into_iter());
+        let new_pipeline_id = self.new_pipeline(parent_info,
+                                                None,
+                                                window_rect,
+                                                None,
+                                                LoadData::new(crashed_url));
+        if let Some(parent_info) = parent_info {
+            self.subpage_map.insert(parent_info, new_pipeline_id);
+        }
 
-        self.push_pending_frame(new_pipeline_id, Some(pipeline_id));
+        match pending_change {
+            Some(old_pipeline_id) => {
+                // The crashed document was still loading, so the error page takes its place
+                // in the navigation.
+                self.close_pipeline(pipeline_id, ExitPipelineMode::Force);
+                self.push_pending_frame(new_pipeline_id, old_pipeline_id);
+            }
+            None => {
+                // The error page takes over the session history entry of the crashed document,
+                // which is closed once the error page is ready to paint. Until then its frame,
+                // and the frames around it, are left as they are.
+                self.crashed_pipelines.insert(pipeline_id);
+                self.pending_frames.push(FrameChange {
+                    old_pipeline_id: Some(pipeline_id),
+                    new_pipeline_id: new_pipeline_id,
+                    painter_ready: false,
+                    reloads_entry: true,
+                });
+            }
+        }
     }
 
     fn handle_init_load(&mut self, url: Url) {
         let window_rect = Rect::new(Point2D::zero(), self.window_size.visible_viewport);
         let root_pipeline_id =
-            self.new_pipeline(None, Some(window_rect), None, LoadData::new(url.clone()));
+            self.new_pipeline(None, None, Some(window_rect), None, LoadData::new(url.clone()));
         self.handle_load_start_msg(&root_pipeline_id);
         self.push_pending_frame(root_pipeline_id, None);
         self.compositor_proxy.send(CompositorMsg::ChangePageUrl(root_pipeline_id, url));
@@ -580,7 +873,7 @@ impl<LTF: LayoutTaskFactory, STF: ScriptTaskFactory> Constellation<LTF, STF> {
     // containing_page_pipeline_id's frame tree's children. This message is never the result of a
     // page navigation.
     fn handle_script_loaded_url_in_iframe_msg(&mut self,
-                                              url: Url,
+                                              load_data: LoadData,
                                               containing_pipeline_id: PipelineId,
                                               new_subpage_id: SubpageId,
                                               old_subpage_id: Option<SubpageId>,
@@ -590,19 +883,17 @@ impl<LTF: LayoutTaskFactory, STF: ScriptTaskFactory> Constellation<LTF, STF> {
         let script_chan = {
             let source_pipeline = self.pipeline(containing_pipeline_id);
 
-            let source_url = source_pipeline.url.clone();
-
-            let same_script = (source_url.host() == url.host() &&
-                               source_url.port() == url.port()) &&
-                               sandbox == IFrameSandboxState::IFrameUnsandboxed;
+            let same_origin = self.origin_for(&load_data.url, Some(containing_pipeline_id)) ==
+                              source_pipeline.origin;
+            let same_script = same_origin && sandbox == IFrameSandboxState::IFrameUnsandboxed;
 
             // FIXME(tkuehn): Need to follow the standardized spec for checking same-origin
             // Reuse the script task if the URL is same-origin
             if same_script {
-                debug!("Constellation: loading same-origin iframe at {:?}", url);
+                debug!("Constellation: loading same-origin iframe at {:?}", load_data.url);
                 Some(source_pipeline.script_chan.clone())
             } else {
-                debug!("Constellation: loading cross-origin iframe at {:?}", url);
+                debug!("Constellation: loading cross-origin iframe at {:?}", load_data.url);
                 None
             }
         };
@@ -615,9 +906,10 @@ impl<LTF: LayoutTaskFactory, STF: ScriptTaskFactory> Constellation<LTF, STF> {
             self.pipeline(old_pipeline_id).rect
         });
         let new_pipeline_id = self.new_pipeline(Some((containing_pipeline_id, new_subpage_id)),
+                                                Some(containing_pipeline_id),
                                                 window_rect,
                                                 script_chan,
-                                                LoadData::new(url));
+                                                load_data);
         self.subpage_map.insert((containing_pipeline_id, new_subpage_id), new_pipeline_id);
         self.push_pending_frame(new_pipeline_id, old_pipeline_id);
     }
@@ -677,7 +969,8 @@ impl<LTF: LayoutTaskFactory, STF: ScriptTaskFactory> Constellation<LTF, STF> {
 
                 // Create the new pipeline
                 let window_rect = self.pipeline(source_id).rect;
-                let new_pipeline_id = self.new_pipeline(None, window_rect, None, load_data);
+                let new_pipeline_id = self.new_pipeline(None, Some(source_id), window_rect, None,
+                                                        load_data);
                 self.push_pending_frame(new_pipeline_id, Some(source_id));
 
                 // Send message to ScriptTask that will suspend all timers
@@ -688,22 +981,37 @@ impl<LTF: LayoutTaskFactory, STF: ScriptTaskFactory> Constellation<LTF, STF> {
         }
     }
 
+    /// Loads the pipeline's document again, this time letting it load insecure content.
+    fn handle_allow_mixed_content_msg(&mut self, pipeline_id: PipelineId) {
+        let url = match self.pipelines.get(&pipeline_id) {
+            Some(pipeline) => pipeline.url.clone(),
+            None => return,
+        };
+        let mut load_data = LoadData::new(url);
+        load_data.allow_mixed_content = true;
+        self.load_url(pipeline_id, load_data);
+    }
+
     fn handle_load_start_msg(&mut self, pipeline_id: &PipelineId) {
-        if let Some(id) = self.pipeline_to_frame_map.get(pipeline_id) {
-            let forward = !self.frame(*id).next.is_empty();
-            let back = !self.frame(*id).prev.is_empty();
+        if self.pipeline_to_frame_map.contains_key(pipeline_id) {
+            let (back, forward) = self.can_traverse_history();
             self.compositor_proxy.send(CompositorMsg::LoadStart(back, forward));
         }
     }
 
     fn handle_load_complete_msg(&mut self, pipeline_id: &PipelineId) {
-        let frame_id = match self.pipeline_to_frame_map.get(pipeline_id) {
-            Some(frame) => *frame,
-            None => return
-        };
+        // A reloaded session history entry gets back the state it was left in.
+        if let Some(state) = self.pending_document_states.remove(pipeline_id) {
+            self.send_to_script(*pipeline_id,
+                                ConstellationControlMsg::RestoreDocumentState(*pipeline_id,
+                                                                              state));
+        }
 
-        let forward = !self.frame(frame_id).next.is_empty();
-        let back = !self.frame(frame_id).prev.is_empty();
+        if !self.pipeline_to_frame_map.contains_key(pipeline_id) {
+            return
+        }
+
+        let (back, forward) = self.can_traverse_history();
         self.compositor_proxy.send(CompositorMsg::LoadComplete(back, forward));
 
         let mut webdriver_reset = false;
@@ -716,6 +1024,50 @@ impl<LTF: LayoutTaskFactory, STF: ScriptTaskFactory> Constellation<LTF, STF> {
         if webdriver_reset {
             self.webdriver.load_channel = None;
         }
+
+        // Pages that don't name an icon get the one at the root of their server, if any.
+        if !self.pipelines_with_favicons.contains(pipeline_id) {
+            let url = self.pipeline(*pipeline_id).url.clone();
+            if url.scheme == "http" || url.scheme == "https" {
+                if let Ok(favicon_url) = UrlParser::new().base_url(&url).parse("/favicon.ico") {
+                    self.load_favicon(*pipeline_id, favicon_url);
+                }
+            }
+        }
+    }
+
+    fn handle_new_favicon_msg(&mut self, pipeline_id: PipelineId, url: Url) {
+        self.pipelines_with_favicons.insert(pipeline_id);
+        self.compositor_proxy.send(CompositorMsg::NewFavicon(url.clone()));
+        self.load_favicon(pipeline_id, url);
+    }
+
+    /// Fetches and decodes the icon of a top-level document, and passes it to the compositor.
+    fn load_favicon(&self, pipeline_id: PipelineId, url: Url) {
+        match self.pipelines.get(&pipeline_id) {
+            Some(pipeline) if pipeline.parent_info.is_none() => {}
+            _ => return,
+        }
+        let resource_task = self.resource_task.clone();
+        let mut compositor_proxy = self.compositor_proxy.clone_compositor_proxy();
+        spawn_named("FaviconLoader".to_owned(), move || {
+            let image = match net_traits::load_whole_resource_with_priority(&resource_task,
+                                                                           url.clone(),
+                                                                           LoadPriority::Image) {
+                Ok((_, bytes)) => load_favicon_from_memory(&bytes, FAVICON_SIZE),
+                Err(error) => {
+                    debug!("failed to load favicon {}: {}", url.serialize(), error);
+                    return
+                }
+            };
+            match image {
+                Some(image) => {
+                    let msg = CompositorMsg::ChangeFavicon(pipeline_id, Arc::new(image));
+                    compositor_proxy.send(msg)
+                }
+                None => debug!("failed to decode favicon {}", url.serialize()),
+            }
+        });
     }
 
     fn handle_navigate_msg(&mut self,
@@ -730,38 +1082,169 @@ impl<LTF: LayoutTaskFactory, STF: ScriptTaskFactory> Constellation<LTF, STF> {
             self.pipeline_to_frame_map.get(&pipeline_id).map(|id| *id)
         }).unwrap();
 
+        self.traverse_joint_session_history(frame_id, direction);
+        self.load_pending_reloads(frame_id);
+    }
+
+    fn handle_traverse_history_msg(&mut self, pipeline_id: PipelineId, delta: isize) {
+        let frame_id = match self.top_level_frame_id(pipeline_id) {
+            Some(frame_id) => frame_id,
+            None => return,
+        };
+        let direction = if delta < 0 {
+            NavigationDirection::Back
+        } else {
+            NavigationDirection::Forward
+        };
+        for _ in 0..delta.abs() {
+            if !self.traverse_joint_session_history(frame_id, direction) {
+                break;
+            }
+        }
+        self.load_pending_reloads(frame_id);
+    }
+
+    /// The frame of the top-level browsing context that the given pipeline is nested in.
+    fn top_level_frame_id(&self, pipeline_id: PipelineId) -> Option<FrameId> {
+        let mut pipeline_id = pipeline_id;
+        while let Some((parent_pipeline_id, _)) =
+                self.pipelines.get(&pipeline_id).and_then(|pipeline| pipeline.parent_info) {
+            pipeline_id = parent_pipeline_id;
+        }
+        self.pipeline_to_frame_map.get(&pipeline_id).map(|id| *id)
+    }
+
+    /// The number of entries in the joint session history of the top-level browsing context
+    /// that the given pipeline is nested in.
+    /// https://html.spec.whatwg.org/multipage/#dom-history-length
+    fn history_length(&self, pipeline_id: PipelineId) -> u32 {
+        let frame_id = match self.top_level_frame_id(pipeline_id) {
+            Some(frame_id) => frame_id,
+            None => return 1,
+        };
+        self.current_frame_tree_iter(Some(frame_id)).fold(1, |length, frame| {
+            length + (frame.prev.len() + frame.next.len()) as u32
+        })
+    }
+
+    /// Whether the root frame tree can go back and forward in its joint session history.
+    fn can_traverse_history(&self) -> (bool, bool) {
+        match self.root_frame_id {
+            Some(root_frame_id) => {
+                (self.joint_session_history_target(root_frame_id,
+                                                   NavigationDirection::Back).is_some(),
+                 self.joint_session_history_target(root_frame_id,
+                                                   NavigationDirection::Forward).is_some())
+            }
+            None => (false, false),
+        }
+    }
+
+    /// Finds the frame in the given frame tree whose session history changes when its joint
+    /// session history goes one entry in the given direction: going back undoes the latest
+    /// navigation of any of the frames, and going forward redoes the earliest one undone.
+    fn joint_session_history_target(&self,
+                                    frame_id_root: FrameId,
+                                    direction: NavigationDirection)
+                                    -> Option<FrameId> {
+        let mut target: Option<(u64, PipelineId)> = None;
+        for frame in self.current_frame_tree_iter(Some(frame_id_root)) {
+            let instant = match direction {
+                NavigationDirection::Back if !frame.prev.is_empty() => frame.instant,
+                NavigationDirection::Forward if !frame.next.is_empty() => {
+                    frame.next[frame.next.len() - 1].instant
+                }
+                _ => continue,
+            };
+            let is_target = target.map_or(true, |(target_instant, _)| {
+                match direction {
+                    NavigationDirection::Back => instant > target_instant,
+                    NavigationDirection::Forward => instant < target_instant,
+                }
+            });
+            if is_target {
+                target = Some((instant, frame.current));
+            }
+        }
+        target.and_then(|(_, pipeline_id)| self.pipeline_to_frame_map.get(&pipeline_id))
+              .map(|id| *id)
+    }
+
+    /// Moves the joint session history of the given frame tree one entry in the given
+    /// direction. Returns false if there is no entry to move to, or if the frame that has
+    /// to navigate is still loading.
+    fn traverse_joint_session_history(&mut self,
+                                      frame_id_root: FrameId,
+                                      direction: NavigationDirection)
+                                      -> bool {
+        let frame_id = match self.joint_session_history_target(frame_id_root, direction) {
+            Some(frame_id) => frame_id,
+            None => {
+                debug!("no page to navigate {:?} to", direction);
+                return false;
+            }
+        };
+
+        // Don't navigate away from a document that is already being replaced.
+        let prev_pipeline_id = self.frame(frame_id).current;
+        if self.pending_frames.iter().any(|frame_change| {
+            frame_change.old_pipeline_id == Some(prev_pipeline_id)
+        }) {
+            return false;
+        }
+
         // Check if the currently focused pipeline is the pipeline being replaced
         // (or a child of it). This has to be done here, before the current
         // frame tree is modified below.
         let update_focus_pipeline = self.focused_pipeline_in_tree(frame_id);
 
-        // Get the ids for the previous and next pipelines.
-        let (prev_pipeline_id, next_pipeline_id) = {
+        // Make the current entry a history entry, and get the one to navigate to. If an earlier
+        // step of this traversal reached an entry that has to be reloaded, that entry is the
+        // current one, and the displayed pipeline is a history entry already.
+        let pending_reload = self.mut_frame(frame_id).reload.take();
+        let reload_was_pending = pending_reload.is_some();
+        let current_entry = match pending_reload {
+            Some(entry) => entry,
+            None => FrameEntry {
+                pipeline_id: Some(prev_pipeline_id),
+                url: self.pipeline(prev_pipeline_id).url.clone(),
+                origin: self.pipeline(prev_pipeline_id).origin.clone(),
+                instant: self.frame(frame_id).instant,
+                state: None,
+            },
+        };
+        let entry = {
             let frame = self.mut_frame(frame_id);
-
-            let next = match direction {
+            let entry = match direction {
                 NavigationDirection::Forward => {
-                    if frame.next.is_empty() {
-                        debug!("no next page to navigate to");
-                        return;
-                    }
-                    frame.prev.push(frame.current);
+                    frame.prev.push(current_entry);
                     frame.next.pop().unwrap()
                 }
                 NavigationDirection::Back => {
-                    if frame.prev.is_empty() {
-                        debug!("no previous page to navigate to");
-                        return;
-                    }
-                    frame.next.push(frame.current);
+                    frame.next.push(current_entry);
                     frame.prev.pop().unwrap()
                 }
             };
-            let prev = frame.current;
-            frame.current = next;
-            (prev, next)
+            frame.instant = entry.instant;
+            entry
         };
 
+        // Suspend the old pipeline.
+        if !reload_was_pending {
+            self.pipeline(prev_pipeline_id).freeze();
+        }
+
+        let next_pipeline_id = match entry.pipeline_id {
+            Some(next_pipeline_id) => next_pipeline_id,
+            None => {
+                // The entry's pipeline was closed. It is loaded again once the traversal is
+                // over, so that going several entries at once only loads the last of them.
+                self.mut_frame(frame_id).reload = Some(entry);
+                return true;
+            }
+        };
+        self.mut_frame(frame_id).current = next_pipeline_id;
+
         // If the currently focused pipeline is the one being changed (or a child
         // of the pipeline being changed) then update the focus pipeline to be
         // the replacement.
@@ -769,8 +1252,7 @@ impl<LTF: LayoutTaskFactory, STF: ScriptTaskFactory> Constellation<LTF, STF> {
             self.focus_pipeline_id = Some(next_pipeline_id);
         }
 
-        // Suspend the old pipeline, and resume the new one.
-        self.pipeline(prev_pipeline_id).freeze();
+        // Resume the new pipeline.
         self.pipeline(next_pipeline_id).thaw();
 
         // Set paint permissions correctly for the compositor layers.
@@ -779,7 +1261,7 @@ impl<LTF: LayoutTaskFactory, STF: ScriptTaskFactory> Constellation<LTF, STF> {
 
         // Update the owning iframe to point to the new subpage id.
         // This makes things like contentDocument work correctly.
-        if let Some((parent_pipeline_id, subpage_id)) = pipeline_info {
+        if let Some((parent_pipeline_id, subpage_id)) = self.pipeline(prev_pipeline_id).parent_info {
             let ScriptControlChan(ref script_chan) = self.pipeline(parent_pipeline_id).script_chan;
             let (_, new_subpage_id) = self.pipeline(next_pipeline_id).parent_info.unwrap();
             script_chan.send(ConstellationControlMsg::UpdateSubpageId(parent_pipeline_id,
@@ -790,6 +1272,96 @@ impl<LTF: LayoutTaskFactory, STF: ScriptTaskFactory> Constellation<LTF, STF> {
             // This is the result of a back/forward navigation.
             self.trigger_mozbrowserlocationchange(next_pipeline_id);
         }
+
+        self.close_excess_frozen_pipelines();
+        true
+    }
+
+    /// Loads the session history entries that traversing the given frame tree made current,
+    /// but whose pipelines were closed. Each replaces the pipeline its frame displays once it
+    /// is ready to paint, and gets the state of its document back once loaded.
+    fn load_pending_reloads(&mut self, frame_id_root: FrameId) {
+        let frame_ids: Vec<FrameId> =
+            self.current_frame_tree_iter(Some(frame_id_root))
+                .filter(|frame| frame.reload.is_some())
+                .filter_map(|frame| self.pipeline_to_frame_map.get(&frame.current).map(|id| *id))
+                .collect();
+        for frame_id in frame_ids {
+            let entry = self.mut_frame(frame_id).reload.take().unwrap();
+            let prev_pipeline_id = self.frame(frame_id).current;
+            self.handle_load_start_msg(&prev_pipeline_id);
+            let (parent_info, window_rect) = {
+                let prev_pipeline = self.pipeline(prev_pipeline_id);
+                (prev_pipeline.parent_info, prev_pipeline.rect)
+            };
+            let next_pipeline_id = self.new_pipeline(parent_info,
+                                                     None,
+                                                     window_rect,
+                                                     None,
+                                                     LoadData::new(entry.url));
+            // The entry keeps the origin it had.
+            self.mut_pipeline(next_pipeline_id).origin = entry.origin;
+            if let Some(parent_info) = parent_info {
+                self.subpage_map.insert(parent_info, next_pipeline_id);
+            }
+            self.pending_frames.push(FrameChange {
+                old_pipeline_id: Some(prev_pipeline_id),
+                new_pipeline_id: next_pipeline_id,
+                painter_ready: false,
+                reloads_entry: true,
+            });
+            if let Some(state) = entry.state {
+                self.pending_document_states.insert(next_pipeline_id, state);
+            }
+        }
+    }
+
+    /// Closes the frozen pipelines of the top-level session history entries furthest from
+    /// the current one, beyond the number that are to be kept alive. The entries are
+    /// reloaded should they be revisited.
+    fn close_excess_frozen_pipelines(&mut self) {
+        let max_frozen_pipelines = match opts::get().frozen_pipelines {
+            Some(max_frozen_pipelines) => max_frozen_pipelines,
+            None => return,
+        };
+        let root_frame_id = match self.root_frame_id {
+            Some(root_frame_id) => root_frame_id,
+            None => return,
+        };
+
+        // A history entry's pipeline is still displayed while the entry that replaces it loads,
+        // and must be kept until then.
+        let mut displayed_pipelines: Vec<PipelineId> =
+            self.pending_frames.iter().filter_map(|frame_change| frame_change.old_pipeline_id)
+                               .collect();
+        displayed_pipelines.push(self.frame(root_frame_id).current);
+
+        let mut pipelines_to_close = vec!();
+        {
+            let frame = self.frames.get_mut(&root_frame_id).unwrap();
+            let (prev_len, next_len) = (frame.prev.len(), frame.next.len());
+            let mut entries: Vec<(usize, &mut FrameEntry)> =
+                frame.prev.iter_mut().enumerate().map(|(i, entry)| (prev_len - i, entry))
+                     .chain(frame.next.iter_mut().enumerate().map(|(i, entry)| {
+                         (next_len - i, entry)
+                     }))
+                     .filter(|&(_, ref entry)| {
+                         entry.pipeline_id.map_or(false, |pipeline_id| {
+                             !displayed_pipelines.contains(&pipeline_id)
+                         })
+                     })
+                     .collect();
+            entries.sort_by(|a, b| a.0.cmp(&b.0));
+            for &mut (_, ref mut entry) in entries.iter_mut().skip(max_frozen_pipelines) {
+                let pipeline_id = entry.pipeline_id.take().unwrap();
+                entry.state = self.document_states.remove(&pipeline_id);
+                pipelines_to_close.push(pipeline_id);
+            }
+        }
+
+        for pipeline_id in &pipelines_to_close {
+            self.close_pipeline(*pipeline_id, ExitPipelineMode::Normal);
+        }
     }
 
     fn handle_key_msg(&self, key: Key, state: KeyState, mods: KeyModifiers) {
@@ -815,6 +1387,20 @@ impl<LTF: LayoutTaskFactory, STF: ScriptTaskFactory> Constellation<LTF, STF> {
         }
     }
 
+    fn handle_ime_msg(&self, event: ImeEvent) {
+        // Input methods compose text for the focused pipeline, like key events.
+        let target_pipeline_id = self.focus_pipeline_id.or(self.root_frame_id.map(|frame_id| {
+            self.frame(frame_id).current
+        }));
+
+        if let Some(target_pipeline_id) = target_pipeline_id {
+            let pipeline = self.pipeline(target_pipeline_id);
+            let ScriptControlChan(ref chan) = pipeline.script_chan;
+            let event = CompositorEvent::ImeEvent(event);
+            chan.send(ConstellationControlMsg::SendEvent(pipeline.id, event)).unwrap();
+        }
+    }
+
     fn handle_get_pipeline_title_msg(&mut self, pipeline_id: PipelineId) {
         match self.pipelines.get(&pipeline_id) {
             None => self.compositor_proxy.send(CompositorMsg::ChangePageTitle(pipeline_id, None)),
@@ -879,6 +1465,65 @@ impl<LTF: LayoutTaskFactory, STF: ScriptTaskFactory> Constellation<LTF, STF> {
         self.focus_parent_pipeline(pipeline_id);
     }
 
+    fn handle_request_permission_msg(&mut self,
+                                     pipeline_id: PipelineId,
+                                     name: PermissionName,
+                                     url: Url) {
+        // Only ask the user once per origin.
+        let granted = self.permissions.get(&(name, serialize_origin(&url))).cloned();
+        match granted {
+            Some(granted) => {
+                let msg = ConstellationControlMsg::Permission(pipeline_id, name, granted);
+                self.send_to_script(pipeline_id, msg);
+            }
+            None => {
+                self.compositor_proxy.send(CompositorMsg::RequestPermission(pipeline_id,
+                                                                            name,
+                                                                            url));
+            }
+        }
+    }
+
+    fn handle_permission_response_msg(&mut self,
+                                      pipeline_id: PipelineId,
+                                      name: PermissionName,
+                                      url: Url,
+                                      granted: bool) {
+        // Every document with an opaque origin is an origin of its own, so there is nothing
+        // to remember the answer for.
+        let origin = serialize_origin(&url);
+        if origin != "null" {
+            self.permissions.insert((name, origin), granted);
+        }
+        self.send_to_script(pipeline_id,
+                            ConstellationControlMsg::Permission(pipeline_id, name, granted));
+    }
+
+    /// Trusts the host if the embedder said to, and loads the page that was replaced by an
+    /// error page again.
+    fn handle_certificate_error_response_msg(&mut self,
+                                             pipeline_id: PipelineId,
+                                             error: CertificateError,
+                                             allowed: bool) {
+        if !allowed {
+            return;
+        }
+        let msg = net_traits::ControlMsg::AllowCertificate(error.host, error.fingerprint);
+        self.resource_task.send(msg).unwrap();
+        if self.pipelines.contains_key(&pipeline_id) {
+            self.load_url(pipeline_id, LoadData::new(error.url));
+        }
+    }
+
+    /// Sends a message to the script task of a pipeline, unless the pipeline has gone away
+    /// in the meantime.
+    fn send_to_script(&self, pipeline_id: PipelineId, msg: ConstellationControlMsg) {
+        if let Some(pipeline) = self.pipelines.get(&pipeline_id) {
+            let ScriptControlChan(ref chan) = pipeline.script_chan;
+            chan.send(msg).unwrap();
+        }
+    }
+
     fn handle_remove_iframe_msg(&mut self, containing_pipeline_id: PipelineId, subpage_id: SubpageId) {
         let pipeline_id = self.find_subpage(containing_pipeline_id, subpage_id).id;
         let frame_id = self.pipeline_to_frame_map.get(&pipeline_id).map(|id| *id);
@@ -912,6 +1557,17 @@ impl<LTF: LayoutTaskFactory, STF: ScriptTaskFactory> Constellation<LTF, STF> {
                 let ScriptControlChan(ref script_channel) = pipeline.script_chan;
                 script_channel.send(control_msg).unwrap();
             },
+            WebDriverCommandMsg::SendKeys(pipeline_id, keys) => {
+                let pipeline = self.pipeline(pipeline_id);
+                let ScriptControlChan(ref script_channel) = pipeline.script_chan;
+                for (key, mods) in keys {
+                    for state in [KeyState::Pressed, KeyState::Released].iter() {
+                        let event = CompositorEvent::KeyEvent(key, *state, mods);
+                        script_channel.send(
+                            ConstellationControlMsg::SendEvent(pipeline_id, event)).unwrap();
+                    }
+                }
+            },
             WebDriverCommandMsg::TakeScreenshot(pipeline_id, reply) => {
                 let current_pipeline_id = self.root_frame_id.map(|frame_id| {
                     let frame = self.frames.get(&frame_id).unwrap();
@@ -945,8 +1601,20 @@ impl<LTF: LayoutTaskFactory, STF: ScriptTaskFactory> Constellation<LTF, STF> {
                 self.revoke_paint_permission(old_pipeline_id);
 
                 // Add new pipeline to navigation frame, and return frames evicted from history.
+                // A reloaded history entry is already current, and only needs its pipeline.
                 let frame_id = *self.pipeline_to_frame_map.get(&old_pipeline_id).unwrap();
-                let evicted_frames = self.mut_frame(frame_id).load(frame_change.new_pipeline_id);
+                let evicted_frames = if frame_change.reloads_entry {
+                    self.mut_frame(frame_id).current = frame_change.new_pipeline_id;
+                    vec!()
+                } else {
+                    let instant = self.new_history_instant();
+                    let (old_url, old_origin) = {
+                        let old_pipeline = self.pipeline(old_pipeline_id);
+                        (old_pipeline.url.clone(), old_pipeline.origin.clone())
+                    };
+                    self.mut_frame(frame_id).load(frame_change.new_pipeline_id, instant, old_url,
+                                                  old_origin)
+                };
                 self.pipeline_to_frame_map.insert(frame_change.new_pipeline_id, frame_id);
 
                 Some(evicted_frames)
@@ -979,12 +1647,27 @@ impl<LTF: LayoutTaskFactory, STF: ScriptTaskFactory> Constellation<LTF, STF> {
         // This is the result of a link being clicked and a navigation completing.
         self.trigger_mozbrowserlocationchange(frame_change.new_pipeline_id);
 
-        // Remove any evicted frames
+        // Remove any evicted frames. The pipeline being replaced may be evicted too, if it was
+        // displayed while a reloaded entry loaded, but it is the newest history entry now.
         if let Some(evicted_frames) = evicted_frames {
-            for pipeline_id in &evicted_frames {
-                self.close_pipeline(*pipeline_id, ExitPipelineMode::Normal);
+            for entry in &evicted_frames {
+                if let Some(pipeline_id) = entry.pipeline_id {
+                    if Some(pipeline_id) == frame_change.old_pipeline_id {
+                        continue;
+                    }
+                    self.close_pipeline(pipeline_id, ExitPipelineMode::Normal);
+                }
+            }
+        }
+
+        // The error page replacing a crashed document is now showing.
+        if let Some(old_pipeline_id) = frame_change.old_pipeline_id {
+            if self.crashed_pipelines.contains(&old_pipeline_id) {
+                self.close_pipeline(old_pipeline_id, ExitPipelineMode::Force);
             }
         }
+
+        self.close_excess_frozen_pipelines();
     }
 
     fn handle_painter_ready_msg(&mut self, pipeline_id: PipelineId) {
@@ -1048,8 +1731,9 @@ impl<LTF: LayoutTaskFactory, STF: ScriptTaskFactory> Constellation<LTF, STF> {
             let ScriptControlChan(ref chan) = pipeline.script_chan;
             let _ = chan.send(ConstellationControlMsg::Resize(pipeline.id, new_size));
 
-            for pipeline_id in frame.prev.iter().chain(frame.next.iter()) {
-                let pipeline = self.pipelines.get(pipeline_id).unwrap();
+            let entries = frame.prev.iter().chain(frame.next.iter());
+            for pipeline_id in entries.filter_map(|entry| entry.pipeline_id) {
+                let pipeline = self.pipelines.get(&pipeline_id).unwrap();
                 let ScriptControlChan(ref chan) = pipeline.script_chan;
                 let _ = chan.send(ConstellationControlMsg::ResizeInactive(pipeline.id, new_size));
             }
@@ -1160,16 +1844,7 @@ impl<LTF: LayoutTaskFactory, STF: ScriptTaskFactory> Constellation<LTF, STF> {
         // ordering is vital - so that if close_pipeline() ends up closing
         // any child frames, they can be removed from the parent frame correctly.
         let parent_info = self.pipeline(self.frame(frame_id).current).parent_info;
-        let pipelines_to_close = {
-            let mut pipelines_to_close = vec!();
-
-            let frame = self.frame(frame_id);
-            pipelines_to_close.push_all(&frame.next);
-            pipelines_to_close.push(frame.current);
-            pipelines_to_close.push_all(&frame.prev);
-
-            pipelines_to_close
-        };
+        let pipelines_to_close = self.frame(frame_id).pipelines();
 
         for pipeline_id in &pipelines_to_close {
             self.close_pipeline(*pipeline_id, exit_mode);
@@ -1205,13 +1880,20 @@ impl<LTF: LayoutTaskFactory, STF: ScriptTaskFactory> Constellation<LTF, STF> {
 
         let pipeline = self.pipelines.remove(&pipeline_id).unwrap();
 
-        // If a child pipeline, remove from subpage map
+        // If a child pipeline, remove from subpage map, unless another pipeline has since taken
+        // its place there
         if let Some(info) = pipeline.parent_info {
-            self.subpage_map.remove(&info);
+            if self.subpage_map.get(&info) == Some(&pipeline_id) {
+                self.subpage_map.remove(&info);
+            }
         }
 
         // Remove assocation between this pipeline and its holding frame
         self.pipeline_to_frame_map.remove(&pipeline_id);
+        self.pipelines_with_favicons.remove(&pipeline_id);
+        self.document_states.remove(&pipeline_id);
+        self.pending_document_states.remove(&pipeline_id);
+        self.crashed_pipelines.remove(&pipeline_id);
 
         // Remove this pipeline from pending frames if it hasn't loaded yet.
         let pending_index = self.pending_frames.iter().position(|frame_change| {
@@ -1221,6 +1903,10 @@ impl<LTF: LayoutTaskFactory, STF: ScriptTaskFactory> Constellation<LTF, STF> {
             self.pending_frames.remove(pending_index);
         }
 
+        // Images the page is still waiting for aren't worth decoding. A frozen page keeps
+        // waiting for its images, since it may be shown again.
+        self.image_cache_task.cancel_requests(pipeline_id);
+
         // Inform script, compositor that this pipeline has exited.
         match exit_mode {
             ExitPipelineMode::Normal => pipeline.exit(PipelineExitType::PipelineOnly),
@@ -0,0 +1,1564 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+//! The messages that a content process and the parent process exchange in place of those that
+//! their tasks would send each other within a process, and the tasks that translate between the
+//! two on either side.
+//!
+//! On the sending side, a `proxy_` function gives tasks a channel of the kind they expect, and
+//! translates what is sent on it into IPC messages. On the receiving side, a `serve_` function
+//! turns IPC messages back into the messages of the task that they are meant for. Channels that
+//! messages carry for replies become IPC channels, and the trait objects that some messages
+//! carry stay in the process that sent them, which is told about events through a channel or an
+//! id instead.
+
+use CompositorProxy;
+
+use euclid::point::Point2D;
+use euclid::rect::Rect;
+use euclid::size::Size2D;
+use gfx::display_list::StackingContext;
+use gfx::display_list::serialize::{self, FontTemplates};
+use gfx::font_cache_task::{Command as FontCacheCommand, FontCacheTask, Reply as FontCacheReply};
+use gfx::font_template::FontTemplateDescriptor;
+use gfx::paint_task::Msg as PaintMsg;
+use gfx::paint_task::PaintChan;
+use gfx::platform::font_template::FontTemplateData;
+use gfx::text::util::HanVariant;
+use layout_traits::{LayoutControlChan, LayoutControlMsg};
+use msg::compositor_msg::{Epoch, LayerId, ScriptListener, ScrollBehavior};
+use msg::constellation_msg::Msg as ConstellationMsg;
+use msg::constellation_msg::{AnimationState, CertificateError, ConstellationChan, DocumentState};
+use msg::constellation_msg::{Failure, GamepadUpdate, GeolocationPosition, IFrameSandboxState};
+use msg::constellation_msg::{Key, KeyModifiers, KeyState, LoadData, MixedContentState};
+use msg::constellation_msg::{MozBrowserEvent, NavigationDirection, NotificationData};
+use msg::constellation_msg::{NotificationEventType, NotificationId, PermissionName};
+use msg::constellation_msg::{PipelineExitType, PipelineId, SubpageId, WindowSizeData};
+use msg::webdriver_msg::{WebDriverFrameId, WebDriverJSError, WebDriverJSResult};
+use msg::webdriver_msg::{WebDriverJSValue, WebDriverScriptCommand};
+use net_traits::image::base::{AnimatedImage, Image, decode_image, encode_image};
+use net_traits::image_cache_task::{ImageCacheChan, ImageCacheCommand, ImageCacheResult};
+use net_traits::image_cache_task::{ImageCacheTask, ImageResponder, ImageResponse, ImageState};
+use net_traits::image_cache_task::UsePlaceholder;
+use net_traits::{AsyncResponseTarget, ControlMsg, CookieSource, LoadConsumer, LoadPriority};
+use net_traits::{LoadResponse, Metadata, ProgressMsg, ResourceTask, ResponseAction};
+use net_traits::LoadData as ResourceLoadData;
+use profile_traits::mem::{self, Report, Reporter, ReportsChan};
+use profile_traits::time::{self, ProfilerCategory, TimerMetadata};
+use rustc_serialize::{Decodable, Decoder, Encodable, Encoder};
+use script_traits::{CompositorEvent, ConstellationControlMsg, ScriptControlChan, ScriptState};
+use std::borrow::ToOwned;
+use std::collections::{HashMap, HashSet};
+use std::sync::mpsc::{Sender, channel};
+use std::sync::{Arc, Mutex};
+use string_cache::Atom;
+use style::font_face::{Source, UnicodeRange};
+use style::viewport::ViewportConstraints;
+use url::Url;
+use util::cursor::Cursor;
+use util::geometry::Au;
+use util::ipc::{self, IpcReceiver, IpcSender};
+use util::str::LowercaseString;
+use util::task::spawn_named;
+
+/// Returns an IPC sender whose messages are passed on to `sender`, after `convert`ing them.
+fn ipc_sender_for<T, U, F>(sender: Sender<U>, convert: F) -> IpcSender<T>
+                           where T: Decodable + Encodable + Send + 'static,
+                                 U: Send + 'static,
+                                 F: Fn(T) -> U + Send + 'static {
+    let (ipc_sender, ipc_receiver) = ipc::channel();
+    spawn_named("IpcReplyRouter".to_owned(), move || {
+        while let Ok(message) = ipc_receiver.recv() {
+            if sender.send(convert(message)).is_err() {
+                break
+            }
+        }
+    });
+    ipc_sender
+}
+
+/// Returns a sender whose messages are passed on to `ipc_sender`, after `convert`ing them.
+fn sender_for<T, U, F>(ipc_sender: IpcSender<U>, convert: F) -> Sender<T>
+                       where T: Send + 'static,
+                             U: Encodable + Send + 'static,
+                             F: Fn(T) -> U + Send + 'static {
+    let (sender, receiver) = channel();
+    spawn_named("IpcReplyRouter".to_owned(), move || {
+        while let Ok(message) = receiver.recv() {
+            if ipc_sender.send(convert(message)).is_err() {
+                break
+            }
+        }
+    });
+    sender
+}
+
+fn same<T>(message: T) -> T {
+    message
+}
+
+/// Replies of type `Result<T, ()>` travel as `Option<T>`.
+fn result_to_option<T>(result: Result<T, ()>) -> Option<T> {
+    result.ok()
+}
+
+fn option_to_result<T>(option: Option<T>) -> Result<T, ()> {
+    option.ok_or(())
+}
+
+/// An image, which is decoded into a new one on the receiving side.
+pub struct ImageData(pub Arc<Image>);
+
+impl Encodable for ImageData {
+    fn encode<S: Encoder>(&self, s: &mut S) -> Result<(), S::Error> {
+        encode_image(&*self.0, s)
+    }
+}
+
+impl Decodable for ImageData {
+    fn decode<D: Decoder>(d: &mut D) -> Result<ImageData, D::Error> {
+        Ok(ImageData(Arc::new(try!(decode_image(d)))))
+    }
+}
+
+/// The frames of an animated image.
+pub struct AnimationData(pub Arc<AnimatedImage>);
+
+impl Encodable for AnimationData {
+    fn encode<S: Encoder>(&self, s: &mut S) -> Result<(), S::Error> {
+        (*self.0).encode(s)
+    }
+}
+
+impl Decodable for AnimationData {
+    fn decode<D: Decoder>(d: &mut D) -> Result<AnimationData, D::Error> {
+        Ok(AnimationData(Arc::new(try!(Decodable::decode(d)))))
+    }
+}
+
+/// The messages that content sends to the constellation.
+#[derive(RustcEncodable, RustcDecodable)]
+pub enum ConstellationWireMsg {
+    Failure(Failure),
+    LoadComplete(PipelineId),
+    FrameRect(PipelineId, SubpageId, Rect<f32>),
+    LoadUrl(PipelineId, LoadData),
+    ScriptLoadedURLInIFrame(LoadData, PipelineId, SubpageId, Option<SubpageId>,
+                            IFrameSandboxState),
+    Navigate(Option<(PipelineId, SubpageId)>, NavigationDirection),
+    TraverseHistory(PipelineId, isize),
+    GetHistoryLength(PipelineId, IpcSender<u32>),
+    SaveDocumentState(PipelineId, DocumentState),
+    SetCursor(Cursor),
+    MozBrowserEvent(PipelineId, SubpageId, MozBrowserEvent),
+    ChangeRunningAnimationsState(PipelineId, AnimationState),
+    Focus(PipelineId),
+    GetClipboardContents(IpcSender<String>),
+    ViewportConstrained(PipelineId, ViewportConstraints),
+    RemoveIFrame(PipelineId, SubpageId),
+    NewFavicon(PipelineId, Url),
+    HeadParsed,
+    RequestPermission(PipelineId, PermissionName, Url),
+    GetPermission(PermissionName, Url, IpcSender<Option<bool>>),
+    ShowNotification(PipelineId, NotificationId, NotificationData),
+    CloseNotification(PipelineId, NotificationId),
+    RequestGeolocationPosition(PipelineId, bool),
+    MixedContent(PipelineId, Url, MixedContentState),
+    CertificateError(PipelineId, CertificateError),
+    WatchGamepads(IpcSender<GamepadUpdate>),
+}
+
+/// Returns a constellation channel for the tasks of a content process.
+pub fn proxy_constellation(ipc_sender: IpcSender<ConstellationWireMsg>) -> ConstellationChan {
+    let (sender, receiver) = channel();
+    spawn_named("ConstellationProxy".to_owned(), move || {
+        while let Ok(msg) = receiver.recv() {
+            let wire_msg = match msg {
+                ConstellationMsg::Failure(failure) => ConstellationWireMsg::Failure(failure),
+                ConstellationMsg::LoadComplete(pipeline_id) => {
+                    ConstellationWireMsg::LoadComplete(pipeline_id)
+                }
+                ConstellationMsg::FrameRect(pipeline_id, subpage_id, rect) => {
+                    ConstellationWireMsg::FrameRect(pipeline_id, subpage_id, rect)
+                }
+                ConstellationMsg::LoadUrl(pipeline_id, load_data) => {
+                    ConstellationWireMsg::LoadUrl(pipeline_id, load_data)
+                }
+                ConstellationMsg::ScriptLoadedURLInIFrame(load_data, pipeline_id, subpage_id,
+                                                          old_subpage_id, sandbox) => {
+                    ConstellationWireMsg::ScriptLoadedURLInIFrame(load_data, pipeline_id,
+                                                                  subpage_id, old_subpage_id,
+                                                                  sandbox)
+                }
+                ConstellationMsg::Navigate(pipeline_info, direction) => {
+                    ConstellationWireMsg::Navigate(pipeline_info, direction)
+                }
+                ConstellationMsg::TraverseHistory(pipeline_id, delta) => {
+                    ConstellationWireMsg::TraverseHistory(pipeline_id, delta)
+                }
+                ConstellationMsg::GetHistoryLength(pipeline_id, reply) => {
+                    ConstellationWireMsg::GetHistoryLength(pipeline_id, ipc_sender_for(reply, same))
+                }
+                ConstellationMsg::SaveDocumentState(pipeline_id, state) => {
+                    ConstellationWireMsg::SaveDocumentState(pipeline_id, state)
+                }
+                ConstellationMsg::SetCursor(cursor) => ConstellationWireMsg::SetCursor(cursor),
+                ConstellationMsg::MozBrowserEvent(pipeline_id, subpage_id, event) => {
+                    ConstellationWireMsg::MozBrowserEvent(pipeline_id, subpage_id, event)
+                }
+                ConstellationMsg::ChangeRunningAnimationsState(pipeline_id, state) => {
+                    ConstellationWireMsg::ChangeRunningAnimationsState(pipeline_id, state)
+                }
+                ConstellationMsg::Focus(pipeline_id) => ConstellationWireMsg::Focus(pipeline_id),
+                ConstellationMsg::GetClipboardContents(reply) => {
+                    ConstellationWireMsg::GetClipboardContents(ipc_sender_for(reply, same))
+                }
+                ConstellationMsg::ViewportConstrained(pipeline_id, constraints) => {
+                    ConstellationWireMsg::ViewportConstrained(pipeline_id, constraints)
+                }
+                ConstellationMsg::RemoveIFrame(pipeline_id, subpage_id) => {
+                    ConstellationWireMsg::RemoveIFrame(pipeline_id, subpage_id)
+                }
+                ConstellationMsg::NewFavicon(pipeline_id, url) => {
+                    ConstellationWireMsg::NewFavicon(pipeline_id, url)
+                }
+                ConstellationMsg::HeadParsed => ConstellationWireMsg::HeadParsed,
+                ConstellationMsg::RequestPermission(pipeline_id, name, url) => {
+                    ConstellationWireMsg::RequestPermission(pipeline_id, name, url)
+                }
+                ConstellationMsg::GetPermission(name, url, reply) => {
+                    ConstellationWireMsg::GetPermission(name, url, ipc_sender_for(reply, same))
+                }
+                ConstellationMsg::ShowNotification(pipeline_id, id, data) => {
+                    ConstellationWireMsg::ShowNotification(pipeline_id, id, data)
+                }
+                ConstellationMsg::CloseNotification(pipeline_id, id) => {
+                    ConstellationWireMsg::CloseNotification(pipeline_id, id)
+                }
+                ConstellationMsg::RequestGeolocationPosition(pipeline_id, high_accuracy) => {
+                    ConstellationWireMsg::RequestGeolocationPosition(pipeline_id, high_accuracy)
+                }
+                ConstellationMsg::MixedContent(pipeline_id, url, state) => {
+                    ConstellationWireMsg::MixedContent(pipeline_id, url, state)
+                }
+                ConstellationMsg::CertificateError(pipeline_id, error) => {
+                    ConstellationWireMsg::CertificateError(pipeline_id, error)
+                }
+                ConstellationMsg::WatchGamepads(updates) => {
+                    ConstellationWireMsg::WatchGamepads(ipc_sender_for(updates, same))
+                }
+                _ => {
+                    warn!("content process sent a message meant for the parent process only");
+                    continue
+                }
+            };
+            if ipc_sender.send(wire_msg).is_err() {
+                break
+            }
+        }
+    });
+    ConstellationChan(sender)
+}
+
+/// Passes the messages that a content process sends to the constellation on to it, until the
+/// content process is gone.
+pub fn serve_constellation(ipc_receiver: IpcReceiver<ConstellationWireMsg>,
+                           constellation_chan: ConstellationChan) {
+    let ConstellationChan(ref chan) = constellation_chan;
+    while let Ok(wire_msg) = ipc_receiver.recv() {
+        let msg = match wire_msg {
+            ConstellationWireMsg::Failure(failure) => ConstellationMsg::Failure(failure),
+            ConstellationWireMsg::LoadComplete(pipeline_id) => {
+                ConstellationMsg::LoadComplete(pipeline_id)
+            }
+            ConstellationWireMsg::FrameRect(pipeline_id, subpage_id, rect) => {
+                ConstellationMsg::FrameRect(pipeline_id, subpage_id, rect)
+            }
+            ConstellationWireMsg::LoadUrl(pipeline_id, load_data) => {
+                ConstellationMsg::LoadUrl(pipeline_id, load_data)
+            }
+            ConstellationWireMsg::ScriptLoadedURLInIFrame(load_data, pipeline_id, subpage_id,
+                                                          old_subpage_id, sandbox) => {
+                ConstellationMsg::ScriptLoadedURLInIFrame(load_data, pipeline_id, subpage_id,
+                                                          old_subpage_id, sandbox)
+            }
+            ConstellationWireMsg::Navigate(pipeline_info, direction) => {
+                ConstellationMsg::Navigate(pipeline_info, direction)
+            }
+            ConstellationWireMsg::TraverseHistory(pipeline_id, delta) => {
+                ConstellationMsg::TraverseHistory(pipeline_id, delta)
+            }
+            ConstellationWireMsg::GetHistoryLength(pipeline_id, reply) => {
+                ConstellationMsg::GetHistoryLength(pipeline_id, sender_for(reply, same))
+            }
+            ConstellationWireMsg::SaveDocumentState(pipeline_id, state) => {
+                ConstellationMsg::SaveDocumentState(pipeline_id, state)
+            }
+            ConstellationWireMsg::SetCursor(cursor) => ConstellationMsg::SetCursor(cursor),
+            ConstellationWireMsg::MozBrowserEvent(pipeline_id, subpage_id, event) => {
+                ConstellationMsg::MozBrowserEvent(pipeline_id, subpage_id, event)
+            }
+            ConstellationWireMsg::ChangeRunningAnimationsState(pipeline_id, state) => {
+                ConstellationMsg::ChangeRunningAnimationsState(pipeline_id, state)
+            }
+            ConstellationWireMsg::Focus(pipeline_id) => ConstellationMsg::Focus(pipeline_id),
+            ConstellationWireMsg::GetClipboardContents(reply) => {
+                ConstellationMsg::GetClipboardContents(sender_for(reply, same))
+            }
+            ConstellationWireMsg::ViewportConstrained(pipeline_id, constraints) => {
+                ConstellationMsg::ViewportConstrained(pipeline_id, constraints)
+            }
+            ConstellationWireMsg::RemoveIFrame(pipeline_id, subpage_id) => {
+                ConstellationMsg::RemoveIFrame(pipeline_id, subpage_id)
+            }
+            ConstellationWireMsg::NewFavicon(pipeline_id, url) => {
+                ConstellationMsg::NewFavicon(pipeline_id, url)
+            }
+            ConstellationWireMsg::HeadParsed => ConstellationMsg::HeadParsed,
+            ConstellationWireMsg::RequestPermission(pipeline_id, name, url) => {
+                ConstellationMsg::RequestPermission(pipeline_id, name, url)
+            }
+            ConstellationWireMsg::GetPermission(name, url, reply) => {
+                ConstellationMsg::GetPermission(name, url, sender_for(reply, same))
+            }
+            ConstellationWireMsg::ShowNotification(pipeline_id, id, data) => {
+                ConstellationMsg::ShowNotification(pipeline_id, id, data)
+            }
+            ConstellationWireMsg::CloseNotification(pipeline_id, id) => {
+                ConstellationMsg::CloseNotification(pipeline_id, id)
+            }
+            ConstellationWireMsg::RequestGeolocationPosition(pipeline_id, high_accuracy) => {
+                ConstellationMsg::RequestGeolocationPosition(pipeline_id, high_accuracy)
+            }
+            ConstellationWireMsg::MixedContent(pipeline_id, url, state) => {
+                ConstellationMsg::MixedContent(pipeline_id, url, state)
+            }
+            ConstellationWireMsg::CertificateError(pipeline_id, error) => {
+                ConstellationMsg::CertificateError(pipeline_id, error)
+            }
+            ConstellationWireMsg::WatchGamepads(updates) => {
+                ConstellationMsg::WatchGamepads(sender_for(updates, same))
+            }
+        };
+        if chan.send(msg).is_err() {
+            break
+        }
+    }
+}
+
+/// The calls that the script tasks of a content process make on the compositor.
+#[derive(RustcEncodable, RustcDecodable)]
+pub enum ScriptListenerMsg {
+    ScrollFragmentPoint(PipelineId, LayerId, Point2D<f32>, ScrollBehavior),
+    SetTitle(PipelineId, Option<String>),
+    Close(IpcSender<()>),
+    SendKeyEvent(Key, KeyState, KeyModifiers),
+    HighlightRect(PipelineId, Option<Rect<f32>>),
+    SetImeCaretRect(PipelineId, Rect<f32>),
+}
+
+/// The compositor, as the script tasks of a content process see it.
+#[derive(Clone)]
+pub struct ScriptListenerProxy(pub IpcSender<ScriptListenerMsg>);
+
+impl ScriptListener for ScriptListenerProxy {
+    fn scroll_fragment_point(&mut self,
+                             pipeline_id: PipelineId,
+                             layer_id: LayerId,
+                             point: Point2D<f32>,
+                             behavior: ScrollBehavior) {
+        let msg = ScriptListenerMsg::ScrollFragmentPoint(pipeline_id, layer_id, point, behavior);
+        let _ = self.0.send(msg);
+    }
+
+    fn set_title(&mut self, pipeline_id: PipelineId, title: Option<String>) {
+        let _ = self.0.send(ScriptListenerMsg::SetTitle(pipeline_id, title));
+    }
+
+    fn close(&mut self) {
+        let (chan, port) = ipc::channel();
+        if self.0.send(ScriptListenerMsg::Close(chan)).is_ok() {
+            let _ = port.recv();
+        }
+    }
+
+    fn dup(&mut self) -> Box<ScriptListener+'static> {
+        box self.clone() as Box<ScriptListener+'static>
+    }
+
+    fn send_key_event(&mut self, key: Key, state: KeyState, modifiers: KeyModifiers) {
+        let _ = self.0.send(ScriptListenerMsg::SendKeyEvent(key, state, modifiers));
+    }
+
+    fn highlight_rect(&mut self, pipeline_id: PipelineId, rect: Option<Rect<f32>>) {
+        let _ = self.0.send(ScriptListenerMsg::HighlightRect(pipeline_id, rect));
+    }
+
+    fn set_ime_caret_rect(&mut self, pipeline_id: PipelineId, rect: Rect<f32>) {
+        let _ = self.0.send(ScriptListenerMsg::SetImeCaretRect(pipeline_id, rect));
+    }
+}
+
+/// Makes the calls that the script tasks of a content process make on the compositor.
+pub fn serve_script_listener(ipc_receiver: IpcReceiver<ScriptListenerMsg>,
+                             mut compositor_proxy: Box<CompositorProxy+'static+Send>) {
+    spawn_named("ScriptListenerServer".to_owned(), move || {
+        while let Ok(msg) = ipc_receiver.recv() {
+            match msg {
+                ScriptListenerMsg::ScrollFragmentPoint(pipeline_id, layer_id, point, behavior) => {
+                    compositor_proxy.scroll_fragment_point(pipeline_id, layer_id, point, behavior)
+                }
+                ScriptListenerMsg::SetTitle(pipeline_id, title) => {
+                    compositor_proxy.set_title(pipeline_id, title)
+                }
+                ScriptListenerMsg::Close(reply) => {
+                    compositor_proxy.close();
+                    let _ = reply.send(());
+                }
+                ScriptListenerMsg::SendKeyEvent(key, state, modifiers) => {
+                    compositor_proxy.send_key_event(key, state, modifiers)
+                }
+                ScriptListenerMsg::HighlightRect(pipeline_id, rect) => {
+                    compositor_proxy.highlight_rect(pipeline_id, rect)
+                }
+                ScriptListenerMsg::SetImeCaretRect(pipeline_id, rect) => {
+                    compositor_proxy.set_ime_caret_rect(pipeline_id, rect)
+                }
+            }
+        }
+    });
+}
+
+/// The result of a script that WebDriver ran.
+#[derive(RustcEncodable, RustcDecodable)]
+pub enum WebDriverJSWireResult {
+    Value(WebDriverJSValue),
+    Error(WebDriverJSError),
+}
+
+fn js_result_to_wire(result: WebDriverJSResult) -> WebDriverJSWireResult {
+    match result {
+        Ok(value) => WebDriverJSWireResult::Value(value),
+        Err(error) => WebDriverJSWireResult::Error(error),
+    }
+}
+
+fn js_result_from_wire(result: WebDriverJSWireResult) -> WebDriverJSResult {
+    match result {
+        WebDriverJSWireResult::Value(value) => Ok(value),
+        WebDriverJSWireResult::Error(error) => Err(error),
+    }
+}
+
+/// The commands that WebDriver sends to script.
+#[derive(RustcEncodable, RustcDecodable)]
+pub enum WebDriverScriptWireCommand {
+    ElementClick(String, IpcSender<Option<()>>),
+    ExecuteScript(String, IpcSender<WebDriverJSWireResult>),
+    ExecuteAsyncScript(String, IpcSender<WebDriverJSWireResult>),
+    FindElementCSS(String, IpcSender<Option<Option<String>>>),
+    FindElementsCSS(String, IpcSender<Option<Vec<String>>>),
+    FocusElement(String, IpcSender<Option<()>>),
+    GetActiveElement(IpcSender<Option<String>>),
+    GetElementTagName(String, IpcSender<Option<String>>),
+    GetElementText(String, IpcSender<Option<String>>),
+    GetFrameId(WebDriverFrameId, IpcSender<Option<Option<(PipelineId, SubpageId)>>>),
+    GetTitle(IpcSender<String>),
+}
+
+fn webdriver_command_to_wire(command: WebDriverScriptCommand) -> WebDriverScriptWireCommand {
+    match command {
+        WebDriverScriptCommand::ElementClick(id, reply) => {
+            WebDriverScriptWireCommand::ElementClick(id, ipc_sender_for(reply, option_to_result))
+        }
+        WebDriverScriptCommand::ExecuteScript(script, reply) => {
+            WebDriverScriptWireCommand::ExecuteScript(script,
+                                                      ipc_sender_for(reply, js_result_from_wire))
+        }
+        WebDriverScriptCommand::ExecuteAsyncScript(script, reply) => {
+            let reply = ipc_sender_for(reply, js_result_from_wire);
+            WebDriverScriptWireCommand::ExecuteAsyncScript(script, reply)
+        }
+        WebDriverScriptCommand::FindElementCSS(selector, reply) => {
+            let reply = ipc_sender_for(reply, option_to_result);
+            WebDriverScriptWireCommand::FindElementCSS(selector, reply)
+        }
+        WebDriverScriptCommand::FindElementsCSS(selector, reply) => {
+            let reply = ipc_sender_for(reply, option_to_result);
+            WebDriverScriptWireCommand::FindElementsCSS(selector, reply)
+        }
+        WebDriverScriptCommand::FocusElement(id, reply) => {
+            WebDriverScriptWireCommand::FocusElement(id, ipc_sender_for(reply, option_to_result))
+        }
+        WebDriverScriptCommand::GetActiveElement(reply) => {
+            WebDriverScriptWireCommand::GetActiveElement(ipc_sender_for(reply, same))
+        }
+        WebDriverScriptCommand::GetElementTagName(id, reply) => {
+            let reply = ipc_sender_for(reply, option_to_result);
+            WebDriverScriptWireCommand::GetElementTagName(id, reply)
+        }
+        WebDriverScriptCommand::GetElementText(id, reply) => {
+            WebDriverScriptWireCommand::GetElementText(id, ipc_sender_for(reply, option_to_result))
+        }
+        WebDriverScriptCommand::GetFrameId(frame_id, reply) => {
+            WebDriverScriptWireCommand::GetFrameId(frame_id,
+                                                   ipc_sender_for(reply, option_to_result))
+        }
+        WebDriverScriptCommand::GetTitle(reply) => {
+            WebDriverScriptWireCommand::GetTitle(ipc_sender_for(reply, same))
+        }
+    }
+}
+
+fn webdriver_command_from_wire(command: WebDriverScriptWireCommand) -> WebDriverScriptCommand {
+    match command {
+        WebDriverScriptWireCommand::ElementClick(id, reply) => {
+            WebDriverScriptCommand::ElementClick(id, sender_for(reply, result_to_option))
+        }
+        WebDriverScriptWireCommand::ExecuteScript(script, reply) => {
+            WebDriverScriptCommand::ExecuteScript(script, sender_for(reply, js_result_to_wire))
+        }
+        WebDriverScriptWireCommand::ExecuteAsyncScript(script, reply) => {
+            WebDriverScriptCommand::ExecuteAsyncScript(script,
+                                                       sender_for(reply, js_result_to_wire))
+        }
+        WebDriverScriptWireCommand::FindElementCSS(selector, reply) => {
+            WebDriverScriptCommand::FindElementCSS(selector, sender_for(reply, result_to_option))
+        }
+        WebDriverScriptWireCommand::FindElementsCSS(selector, reply) => {
+            WebDriverScriptCommand::FindElementsCSS(selector, sender_for(reply, result_to_option))
+        }
+        WebDriverScriptWireCommand::FocusElement(id, reply) => {
+            WebDriverScriptCommand::FocusElement(id, sender_for(reply, result_to_option))
+        }
+        WebDriverScriptWireCommand::GetActiveElement(reply) => {
+            WebDriverScriptCommand::GetActiveElement(sender_for(reply, same))
+        }
+        WebDriverScriptWireCommand::GetElementTagName(id, reply) => {
+            WebDriverScriptCommand::GetElementTagName(id, sender_for(reply, result_to_option))
+        }
+        WebDriverScriptWireCommand::GetElementText(id, reply) => {
+            WebDriverScriptCommand::GetElementText(id, sender_for(reply, result_to_option))
+        }
+        WebDriverScriptWireCommand::GetFrameId(frame_id, reply) => {
+            WebDriverScriptCommand::GetFrameId(frame_id, sender_for(reply, result_to_option))
+        }
+        WebDriverScriptWireCommand::GetTitle(reply) => {
+            WebDriverScriptCommand::GetTitle(sender_for(reply, same))
+        }
+    }
+}
+
+/// The messages that the constellation sends to a script task in a content process. Layouts
+/// are attached to script tasks when their pipelines are started instead.
+#[derive(RustcEncodable, RustcDecodable)]
+pub enum ScriptControlWireMsg {
+    Resize(PipelineId, WindowSizeData),
+    ResizeInactive(PipelineId, WindowSizeData),
+    ExitPipeline(PipelineId, PipelineExitType),
+    SendEvent(PipelineId, CompositorEvent),
+    ReflowComplete(PipelineId, u32),
+    Viewport(PipelineId, Rect<f32>),
+    GetTitle(PipelineId),
+    Freeze(PipelineId),
+    Thaw(PipelineId),
+    RestoreDocumentState(PipelineId, DocumentState),
+    Navigate(PipelineId, SubpageId, LoadData),
+    MozBrowserEvent(PipelineId, SubpageId, MozBrowserEvent),
+    UpdateSubpageId(PipelineId, SubpageId, SubpageId),
+    FocusIFrame(PipelineId, SubpageId),
+    WebDriverScriptCommand(PipelineId, WebDriverScriptWireCommand),
+    TickAllAnimations(PipelineId),
+    GetCurrentState(IpcSender<ScriptState>, PipelineId),
+    Permission(PipelineId, PermissionName, bool),
+    NotificationEvent(PipelineId, NotificationId, NotificationEventType),
+    GeolocationPosition(PipelineId, Option<GeolocationPosition>),
+    WebFontLoaded(PipelineId),
+}
+
+/// Returns a channel to a script task in a content process.
+pub fn proxy_script_control(ipc_sender: IpcSender<ScriptControlWireMsg>) -> ScriptControlChan {
+    let (sender, receiver) = channel();
+    spawn_named("ScriptControlProxy".to_owned(), move || {
+        while let Ok(msg) = receiver.recv() {
+            let wire_msg = match msg {
+                ConstellationControlMsg::Resize(pipeline_id, size) => {
+                    ScriptControlWireMsg::Resize(pipeline_id, size)
+                }
+                ConstellationControlMsg::ResizeInactive(pipeline_id, size) => {
+                    ScriptControlWireMsg::ResizeInactive(pipeline_id, size)
+                }
+                ConstellationControlMsg::ExitPipeline(pipeline_id, exit_type) => {
+                    ScriptControlWireMsg::ExitPipeline(pipeline_id, exit_type)
+                }
+                ConstellationControlMsg::SendEvent(pipeline_id, event) => {
+                    ScriptControlWireMsg::SendEvent(pipeline_id, event)
+                }
+                ConstellationControlMsg::ReflowComplete(pipeline_id, reflow_id) => {
+                    ScriptControlWireMsg::ReflowComplete(pipeline_id, reflow_id)
+                }
+                ConstellationControlMsg::Viewport(pipeline_id, rect) => {
+                    ScriptControlWireMsg::Viewport(pipeline_id, rect)
+                }
+                ConstellationControlMsg::GetTitle(pipeline_id) => {
+                    ScriptControlWireMsg::GetTitle(pipeline_id)
+                }
+                ConstellationControlMsg::Freeze(pipeline_id) => {
+                    ScriptControlWireMsg::Freeze(pipeline_id)
+                }
+                ConstellationControlMsg::Thaw(pipeline_id) => {
+                    ScriptControlWireMsg::Thaw(pipeline_id)
+                }
+                ConstellationControlMsg::RestoreDocumentState(pipeline_id, state) => {
+                    ScriptControlWireMsg::RestoreDocumentState(pipeline_id, state)
+                }
+                ConstellationControlMsg::Navigate(pipeline_id, subpage_id, load_data) => {
+                    ScriptControlWireMsg::Navigate(pipeline_id, subpage_id, load_data)
+                }
+                ConstellationControlMsg::MozBrowserEvent(pipeline_id, subpage_id, event) => {
+                    ScriptControlWireMsg::MozBrowserEvent(pipeline_id, subpage_id, event)
+                }
+                ConstellationControlMsg::UpdateSubpageId(pipeline_id, old_id, new_id) => {
+                    ScriptControlWireMsg::UpdateSubpageId(pipeline_id, old_id, new_id)
+                }
+                ConstellationControlMsg::FocusIFrame(pipeline_id, subpage_id) => {
+                    ScriptControlWireMsg::FocusIFrame(pipeline_id, subpage_id)
+                }
+                ConstellationControlMsg::WebDriverScriptCommand(pipeline_id, command) => {
+                    let command = webdriver_command_to_wire(command);
+                    ScriptControlWireMsg::WebDriverScriptCommand(pipeline_id, command)
+                }
+                ConstellationControlMsg::TickAllAnimations(pipeline_id) => {
+                    ScriptControlWireMsg::TickAllAnimations(pipeline_id)
+                }
+                ConstellationControlMsg::GetCurrentState(reply, pipeline_id) => {
+                    ScriptControlWireMsg::GetCurrentState(ipc_sender_for(reply, same), pipeline_id)
+                }
+                ConstellationControlMsg::Permission(pipeline_id, name, allowed) => {
+                    ScriptControlWireMsg::Permission(pipeline_id, name, allowed)
+                }
+                ConstellationControlMsg::NotificationEvent(pipeline_id, id, event_type) => {
+                    ScriptControlWireMsg::NotificationEvent(pipeline_id, id, event_type)
+                }
+                ConstellationControlMsg::GeolocationPosition(pipeline_id, position) => {
+                    ScriptControlWireMsg::GeolocationPosition(pipeline_id, position)
+                }
+                ConstellationControlMsg::WebFontLoaded(pipeline_id) => {
+                    ScriptControlWireMsg::WebFontLoaded(pipeline_id)
+                }
+                ConstellationControlMsg::AttachLayout(..) |
+                ConstellationControlMsg::StylesheetLoadComplete(..) |
+                ConstellationControlMsg::CollectReports(..) => {
+                    warn!("a message for a script task can't be sent to a content process");
+                    continue
+                }
+            };
+            if ipc_sender.send(wire_msg).is_err() {
+                break
+            }
+        }
+    });
+    ScriptControlChan(sender)
+}
+
+/// Passes the messages that the constellation sends to a script task on to it.
+pub fn serve_script_control(ipc_receiver: IpcReceiver<ScriptControlWireMsg>,
+                            sender: Sender<ConstellationControlMsg>) {
+    spawn_named("ScriptControlServer".to_owned(), move || {
+        while let Ok(wire_msg) = ipc_receiver.recv() {
+            let msg = match wire_msg {
+                ScriptControlWireMsg::Resize(pipeline_id, size) => {
+                    ConstellationControlMsg::Resize(pipeline_id, size)
+                }
+                ScriptControlWireMsg::ResizeInactive(pipeline_id, size) => {
+                    ConstellationControlMsg::ResizeInactive(pipeline_id, size)
+                }
+                ScriptControlWireMsg::ExitPipeline(pipeline_id, exit_type) => {
+                    ConstellationControlMsg::ExitPipeline(pipeline_id, exit_type)
+                }
+                ScriptControlWireMsg::SendEvent(pipeline_id, event) => {
+                    ConstellationControlMsg::SendEvent(pipeline_id, event)
+                }
+                ScriptControlWireMsg::ReflowComplete(pipeline_id, reflow_id) => {
+                    ConstellationControlMsg::ReflowComplete(pipeline_id, reflow_id)
+                }
+                ScriptControlWireMsg::Viewport(pipeline_id, rect) => {
+                    ConstellationControlMsg::Viewport(pipeline_id, rect)
+                }
+                ScriptControlWireMsg::GetTitle(pipeline_id) => {
+                    ConstellationControlMsg::GetTitle(pipeline_id)
+                }
+                ScriptControlWireMsg::Freeze(pipeline_id) => {
+                    ConstellationControlMsg::Freeze(pipeline_id)
+                }
+                ScriptControlWireMsg::Thaw(pipeline_id) => {
+                    ConstellationControlMsg::Thaw(pipeline_id)
+                }
+                ScriptControlWireMsg::RestoreDocumentState(pipeline_id, state) => {
+                    ConstellationControlMsg::RestoreDocumentState(pipeline_id, state)
+                }
+                ScriptControlWireMsg::Navigate(pipeline_id, subpage_id, load_data) => {
+                    ConstellationControlMsg::Navigate(pipeline_id, subpage_id, load_data)
+                }
+                ScriptControlWireMsg::MozBrowserEvent(pipeline_id, subpage_id, event) => {
+                    ConstellationControlMsg::MozBrowserEvent(pipeline_id, subpage_id, event)
+                }
+                ScriptControlWireMsg::UpdateSubpageId(pipeline_id, old_id, new_id) => {
+                    ConstellationControlMsg::UpdateSubpageId(pipeline_id, old_id, new_id)
+                }
+                ScriptControlWireMsg::FocusIFrame(pipeline_id, subpage_id) => {
+                    ConstellationControlMsg::FocusIFrame(pipeline_id, subpage_id)
+                }
+                ScriptControlWireMsg::WebDriverScriptCommand(pipeline_id, command) => {
+                    let command = webdriver_command_from_wire(command);
+                    ConstellationControlMsg::WebDriverScriptCommand(pipeline_id, command)
+                }
+                ScriptControlWireMsg::TickAllAnimations(pipeline_id) => {
+                    ConstellationControlMsg::TickAllAnimations(pipeline_id)
+                }
+                ScriptControlWireMsg::GetCurrentState(reply, pipeline_id) => {
+                    ConstellationControlMsg::GetCurrentState(sender_for(reply, same), pipeline_id)
+                }
+                ScriptControlWireMsg::Permission(pipeline_id, name, allowed) => {
+                    ConstellationControlMsg::Permission(pipeline_id, name, allowed)
+                }
+                ScriptControlWireMsg::NotificationEvent(pipeline_id, id, event_type) => {
+                    ConstellationControlMsg::NotificationEvent(pipeline_id, id, event_type)
+                }
+                ScriptControlWireMsg::GeolocationPosition(pipeline_id, position) => {
+                    ConstellationControlMsg::GeolocationPosition(pipeline_id, position)
+                }
+                ScriptControlWireMsg::WebFontLoaded(pipeline_id) => {
+                    ConstellationControlMsg::WebFontLoaded(pipeline_id)
+                }
+            };
+            if sender.send(msg).is_err() {
+                break
+            }
+        }
+    });
+}
+
+/// The messages that the constellation and the compositor send to a layout task in a content
+/// process.
+#[derive(RustcEncodable, RustcDecodable)]
+pub enum LayoutControlWireMsg {
+    ExitNow(PipelineExitType),
+    GetCurrentEpoch(IpcSender<Epoch>),
+    TickAnimations,
+    SetVisibleRects(Vec<(LayerId, Rect<Au>)>),
+}
+
+/// Returns a channel to a layout task in a content process.
+pub fn proxy_layout_control(ipc_sender: IpcSender<LayoutControlWireMsg>) -> LayoutControlChan {
+    let (sender, receiver) = channel();
+    spawn_named("LayoutControlProxy".to_owned(), move || {
+        while let Ok(msg) = receiver.recv() {
+            let wire_msg = match msg {
+                LayoutControlMsg::ExitNow(exit_type) => LayoutControlWireMsg::ExitNow(exit_type),
+                LayoutControlMsg::GetCurrentEpoch(reply) => {
+                    LayoutControlWireMsg::GetCurrentEpoch(ipc_sender_for(reply, same))
+                }
+                LayoutControlMsg::TickAnimations => LayoutControlWireMsg::TickAnimations,
+                LayoutControlMsg::SetVisibleRects(rects) => {
+                    LayoutControlWireMsg::SetVisibleRects(rects)
+                }
+            };
+            if ipc_sender.send(wire_msg).is_err() {
+                break
+            }
+        }
+    });
+    LayoutControlChan(sender)
+}
+
+/// Passes the messages meant for a layout task on to it.
+pub fn serve_layout_control(ipc_receiver: IpcReceiver<LayoutControlWireMsg>,
+                            sender: Sender<LayoutControlMsg>) {
+    spawn_named("LayoutControlServer".to_owned(), move || {
+        while let Ok(wire_msg) = ipc_receiver.recv() {
+            let msg = match wire_msg {
+                LayoutControlWireMsg::ExitNow(exit_type) => LayoutControlMsg::ExitNow(exit_type),
+                LayoutControlWireMsg::GetCurrentEpoch(reply) => {
+                    LayoutControlMsg::GetCurrentEpoch(sender_for(reply, same))
+                }
+                LayoutControlWireMsg::TickAnimations => LayoutControlMsg::TickAnimations,
+                LayoutControlWireMsg::SetVisibleRects(rects) => {
+                    LayoutControlMsg::SetVisibleRects(rects)
+                }
+            };
+            if sender.send(msg).is_err() {
+                break
+            }
+        }
+    });
+}
+
+/// The messages that a layout task in a content process sends to its paint task.
+#[derive(RustcEncodable, RustcDecodable)]
+pub enum PaintWireMsg {
+    PaintInit(Epoch, Arc<StackingContext>),
+    Exit(Option<IpcSender<()>>, PipelineExitType),
+    /// The images and text runs with these ids aren't used by display lists anymore.
+    ForgetData(Vec<u32>),
+}
+
+/// Returns a channel to a paint task in the parent process.
+pub fn proxy_paint(ipc_sender: IpcSender<PaintWireMsg>) -> PaintChan {
+    let (receiver, paint_chan) = PaintChan::new();
+    spawn_named("PaintProxy".to_owned(), move || {
+        while let Ok(msg) = receiver.recv() {
+            let result = match msg {
+                PaintMsg::PaintInit(epoch, stacking_context) => {
+                    let msg = PaintWireMsg::PaintInit(epoch, stacking_context);
+                    ipc_sender.send(msg).and_then(|()| {
+                        let unused = serialize::sweep_sent_data();
+                        if unused.is_empty() {
+                            Ok(())
+                        } else {
+                            ipc_sender.send(PaintWireMsg::ForgetData(unused))
+                        }
+                    })
+                }
+                PaintMsg::Exit(reply, exit_type) => {
+                    let reply = reply.map(|reply| ipc_sender_for(reply, same));
+                    ipc_sender.send(PaintWireMsg::Exit(reply, exit_type))
+                }
+                // The paint task only keeps track of canvas layers, without drawing them, so
+                // there is nothing to tell it.
+                PaintMsg::CanvasLayer(..) => continue,
+                _ => {
+                    warn!("layout sent its paint task a message only the parent process sends");
+                    continue
+                }
+            };
+            if result.is_err() {
+                break
+            }
+        }
+    });
+    paint_chan
+}
+
+/// Passes the messages that a layout task in a content process sends to its paint task on to it.
+/// Text runs are drawn with the fonts in `font_templates`. If the content process goes away, the
+/// paint task is told to exit.
+pub fn serve_paint(ipc_receiver: IpcReceiver<PaintWireMsg>,
+                   paint_chan: PaintChan,
+                   font_templates: FontTemplates) {
+    spawn_named("PaintServer".to_owned(), move || {
+        serialize::set_font_templates(font_templates);
+        loop {
+            match ipc_receiver.recv() {
+                Ok(PaintWireMsg::PaintInit(epoch, stacking_context)) => {
+                    paint_chan.send(PaintMsg::PaintInit(epoch, stacking_context))
+                }
+                Ok(PaintWireMsg::Exit(reply, exit_type)) => {
+                    let reply = reply.map(|reply| sender_for(reply, same));
+                    paint_chan.send(PaintMsg::Exit(reply, exit_type))
+                }
+                Ok(PaintWireMsg::ForgetData(ids)) => serialize::forget_received_data(&ids),
+                Err(_) => {
+                    let _ = paint_chan.send_opt(PaintMsg::Exit(None,
+                                                               PipelineExitType::PipelineOnly));
+                    break
+                }
+            }
+        }
+    });
+}
+
+/// What a load answers with, once its headers are in.
+#[derive(RustcEncodable, RustcDecodable)]
+pub struct LoadResponseWire {
+    metadata: Metadata,
+    progress_port: IpcReceiver<ProgressMsg>,
+}
+
+/// Where the response to a load goes.
+#[derive(RustcEncodable, RustcDecodable)]
+pub enum LoadConsumerWire {
+    Channel(IpcSender<LoadResponseWire>),
+    Listener(IpcSender<ResponseAction>),
+}
+
+/// The messages that content sends to the resource task.
+#[derive(RustcEncodable, RustcDecodable)]
+pub enum ResourceWireMsg {
+    Load(ResourceLoadData, LoadConsumerWire),
+    SetCookiesForUrl(Url, String, CookieSource),
+    GetCookiesForUrl(Url, IpcSender<Option<String>>, CookieSource),
+    SendBeacon(ResourceLoadData, Url, IpcSender<bool>),
+    RegisterBlobURL(Url, Vec<u8>, String, PipelineId),
+    RevokeBlobURL(Url),
+    RevokeBlobURLsForPipeline(PipelineId),
+    RaisePriority(Url, LoadPriority),
+}
+
+/// Returns a resource task for the tasks of a content process.
+pub fn proxy_resource_task(ipc_sender: IpcSender<ResourceWireMsg>) -> ResourceTask {
+    let (sender, receiver) = channel();
+    spawn_named("ResourceProxy".to_owned(), move || {
+        while let Ok(msg) = receiver.recv() {
+            let wire_msg = match msg {
+                ControlMsg::Load(load_data, LoadConsumer::Channel(response_chan)) => {
+                    let response_chan = ipc_sender_for(response_chan, |response: LoadResponseWire| {
+                        let LoadResponseWire { metadata, progress_port } = response;
+                        let (progress_chan, local_progress_port) = channel();
+                        ipc::route_to_sender(progress_port, progress_chan);
+                        LoadResponse {
+                            metadata: metadata,
+                            progress_port: local_progress_port,
+                        }
+                    });
+                    ResourceWireMsg::Load(load_data, LoadConsumerWire::Channel(response_chan))
+                }
+                ControlMsg::Load(load_data, LoadConsumer::Listener(target)) => {
+                    let (action_chan, action_port) = ipc::channel();
+                    spawn_named("ResponseListenerProxy".to_owned(), move || {
+                        while let Ok(action) = action_port.recv() {
+                            target.invoke_with_listener(action);
+                        }
+                    });
+                    ResourceWireMsg::Load(load_data, LoadConsumerWire::Listener(action_chan))
+                }
+                ControlMsg::SetCookiesForUrl(url, cookies, source) => {
+                    ResourceWireMsg::SetCookiesForUrl(url, cookies, source)
+                }
+                ControlMsg::GetCookiesForUrl(url, reply, source) => {
+                    ResourceWireMsg::GetCookiesForUrl(url, ipc_sender_for(reply, same), source)
+                }
+                ControlMsg::SendBeacon(load_data, document_url, reply) => {
+                    ResourceWireMsg::SendBeacon(load_data, document_url,
+                                                ipc_sender_for(reply, same))
+                }
+                ControlMsg::RegisterBlobURL(url, bytes, content_type, pipeline_id) => {
+                    ResourceWireMsg::RegisterBlobURL(url, bytes, content_type, pipeline_id)
+                }
+                ControlMsg::RevokeBlobURL(url) => ResourceWireMsg::RevokeBlobURL(url),
+                ControlMsg::RevokeBlobURLsForPipeline(pipeline_id) => {
+                    ResourceWireMsg::RevokeBlobURLsForPipeline(pipeline_id)
+                }
+                ControlMsg::RaisePriority(url, priority) => {
+                    ResourceWireMsg::RaisePriority(url, priority)
+                }
+                _ => {
+                    warn!("content process sent the resource task a message it can't send");
+                    continue
+                }
+            };
+            if ipc_sender.send(wire_msg).is_err() {
+                break
+            }
+        }
+    });
+    sender
+}
+
+/// Passes the events of a load on to a listener in a content process.
+struct ResponseListenerProxy(IpcSender<ResponseAction>);
+
+impl AsyncResponseTarget for ResponseListenerProxy {
+    fn invoke_with_listener(&self, action: ResponseAction) {
+        let _ = self.0.send(action);
+    }
+}
+
+/// Passes the messages that a content process sends to the resource task on to it.
+pub fn serve_resource_task(ipc_receiver: IpcReceiver<ResourceWireMsg>,
+                           resource_task: ResourceTask) {
+    spawn_named("ResourceServer".to_owned(), move || {
+        while let Ok(wire_msg) = ipc_receiver.recv() {
+            let msg = match wire_msg {
+                ResourceWireMsg::Load(load_data, LoadConsumerWire::Channel(response_chan)) => {
+                    let response_chan = sender_for(response_chan, |response: LoadResponse| {
+                        let (progress_chan, progress_port) = ipc::channel();
+                        ipc::route_to_ipc_sender(response.progress_port, progress_chan);
+                        LoadResponseWire {
+                            metadata: response.metadata,
+                            progress_port: progress_port,
+                        }
+                    });
+                    ControlMsg::Load(load_data, LoadConsumer::Channel(response_chan))
+                }
+                ResourceWireMsg::Load(load_data, LoadConsumerWire::Listener(action_chan)) => {
+                    let target = box ResponseListenerProxy(action_chan);
+                    ControlMsg::Load(load_data, LoadConsumer::Listener(target))
+                }
+                ResourceWireMsg::SetCookiesForUrl(url, cookies, source) => {
+                    ControlMsg::SetCookiesForUrl(url, cookies, source)
+                }
+                ResourceWireMsg::GetCookiesForUrl(url, reply, source) => {
+                    ControlMsg::GetCookiesForUrl(url, sender_for(reply, same), source)
+                }
+                ResourceWireMsg::SendBeacon(load_data, document_url, reply) => {
+                    ControlMsg::SendBeacon(load_data, document_url, sender_for(reply, same))
+                }
+                ResourceWireMsg::RegisterBlobURL(url, bytes, content_type, pipeline_id) => {
+                    ControlMsg::RegisterBlobURL(url, bytes, content_type, pipeline_id)
+                }
+                ResourceWireMsg::RevokeBlobURL(url) => ControlMsg::RevokeBlobURL(url),
+                ResourceWireMsg::RevokeBlobURLsForPipeline(pipeline_id) => {
+                    ControlMsg::RevokeBlobURLsForPipeline(pipeline_id)
+                }
+                ResourceWireMsg::RaisePriority(url, priority) => {
+                    ControlMsg::RaisePriority(url, priority)
+                }
+            };
+            if resource_task.send(msg).is_err() {
+                break
+            }
+        }
+    });
+}
+
+/// Whether an image is available, and the image if it is.
+#[derive(RustcEncodable, RustcDecodable)]
+pub enum ImageAvailability {
+    Available(ImageData),
+    Unavailable(ImageState),
+}
+
+/// An image that was loaded for a request.
+#[derive(RustcEncodable, RustcDecodable)]
+pub enum ImageResponseWire {
+    Loaded(ImageData),
+    PlaceholderLoaded(ImageData),
+    None,
+}
+
+impl ImageResponseWire {
+    fn from_response(response: ImageResponse) -> ImageResponseWire {
+        match response {
+            ImageResponse::Loaded(image) => ImageResponseWire::Loaded(ImageData(image)),
+            ImageResponse::PlaceholderLoaded(image) => {
+                ImageResponseWire::PlaceholderLoaded(ImageData(image))
+            }
+            ImageResponse::None => ImageResponseWire::None,
+        }
+    }
+
+    fn into_response(self) -> ImageResponse {
+        match self {
+            ImageResponseWire::Loaded(ImageData(image)) => ImageResponse::Loaded(image),
+            ImageResponseWire::PlaceholderLoaded(ImageData(image)) => {
+                ImageResponse::PlaceholderLoaded(image)
+            }
+            ImageResponseWire::None => ImageResponse::None,
+        }
+    }
+}
+
+/// What becomes of the image requests of a content process, by the ids it gave them.
+#[derive(RustcEncodable, RustcDecodable)]
+pub enum ImageResultWire {
+    Response(u32, ImageResponseWire),
+    /// No more responses will come for the request.
+    Done(u32),
+}
+
+/// The messages that content sends to the image cache. Requests that are answered later are
+/// given ids, which their answers come back with.
+#[derive(RustcEncodable, RustcDecodable)]
+pub enum ImageCacheWireMsg {
+    RequestImage(Url, PipelineId, u32),
+    GetImageIfAvailable(Url, UsePlaceholder, IpcSender<ImageAvailability>),
+    GetAnimationIfAvailable(Url, IpcSender<Option<AnimationData>>),
+    Prioritize(Url),
+    SetTargetSize(Url, Size2D<u32>, PipelineId, u32),
+    CancelRequests(PipelineId),
+}
+
+/// A request of a content process that the image cache hasn't answered for good yet.
+struct PendingImageRequest {
+    pipeline_id: PipelineId,
+    url: Url,
+    result_chan: ImageCacheChan,
+    responder: Option<Box<ImageResponder>>,
+    /// Whether the request is answered only once.
+    single_response: bool,
+}
+
+/// The images that a content process has been given, so that asking for one again gives the same
+/// image, which doesn't have to be sent to the paint task again.
+type LocalImages = Arc<Mutex<HashMap<(Url, bool), Arc<Image>>>>;
+
+/// Returns an image cache task for the tasks of a content process. The answers to its
+/// asynchronous requests arrive on `results`.
+pub fn proxy_image_cache_task(ipc_sender: IpcSender<ImageCacheWireMsg>,
+                              results: IpcReceiver<ImageResultWire>)
+                              -> ImageCacheTask {
+    let pending_requests: Arc<Mutex<HashMap<u32, PendingImageRequest>>> =
+        Arc::new(Mutex::new(HashMap::new()));
+    let images: LocalImages = Arc::new(Mutex::new(HashMap::new()));
+
+    let result_pending_requests = pending_requests.clone();
+    let result_images = images.clone();
+    spawn_named("ImageResultRouter".to_owned(), move || {
+        while let Ok(result) = results.recv() {
+            match result {
+                ImageResultWire::Response(id, response) => {
+                    let mut pending_requests = result_pending_requests.lock().unwrap();
+                    let single_response = match pending_requests.get(&id) {
+                        Some(request) => request.single_response,
+                        None => continue,
+                    };
+                    let (url, result_chan, responder) = if single_response {
+                        let request = pending_requests.remove(&id).unwrap();
+                        (request.url, request.result_chan, request.responder)
+                    } else {
+                        let request = &pending_requests[&id];
+                        (request.url.clone(), request.result_chan.clone(), None)
+                    };
+                    // The image may have been decoded again at another size.
+                    {
+                        let mut images = result_images.lock().unwrap();
+                        images.remove(&(url.clone(), false));
+                        images.remove(&(url, true));
+                    }
+                    let ImageCacheChan(result_chan) = result_chan;
+                    let _ = result_chan.send(ImageCacheResult {
+                        responder: responder,
+                        image_response: response.into_response(),
+                    });
+                }
+                ImageResultWire::Done(id) => {
+                    result_pending_requests.lock().unwrap().remove(&id);
+                }
+            }
+        }
+    });
+
+    let (sender, receiver) = channel();
+    spawn_named("ImageCacheProxy".to_owned(), move || {
+        let mut next_id = 0;
+        let mut animations: HashMap<Url, Arc<AnimatedImage>> = HashMap::new();
+        while let Ok(msg) = receiver.recv() {
+            let wire_msg = match msg {
+                ImageCacheCommand::RequestImage(url, pipeline_id, result_chan, responder) => {
+                    next_id += 1;
+                    pending_requests.lock().unwrap().insert(next_id, PendingImageRequest {
+                        pipeline_id: pipeline_id,
+                        url: url.clone(),
+                        result_chan: result_chan,
+                        responder: responder,
+                        single_response: true,
+                    });
+                    ImageCacheWireMsg::RequestImage(url, pipeline_id, next_id)
+                }
+                ImageCacheCommand::GetImageIfAvailable(url, use_placeholder, reply) => {
+                    let key = (url.clone(), use_placeholder == UsePlaceholder::Yes);
+                    if let Some(image) = images.lock().unwrap().get(&key) {
+                        let _ = reply.send(Ok(image.clone()));
+                        continue
+                    }
+                    let (availability_chan, availability_port) = ipc::channel();
+                    let msg = ImageCacheWireMsg::GetImageIfAvailable(url, use_placeholder,
+                                                                     availability_chan);
+                    if ipc_sender.send(msg).is_err() {
+                        break
+                    }
+                    let result = match availability_port.recv() {
+                        Ok(ImageAvailability::Available(ImageData(image))) => {
+                            images.lock().unwrap().insert(key, image.clone());
+                            Ok(image)
+                        }
+                        Ok(ImageAvailability::Unavailable(state)) => Err(state),
+                        Err(_) => Err(ImageState::LoadError),
+                    };
+                    let _ = reply.send(result);
+                    continue
+                }
+                ImageCacheCommand::GetAnimationIfAvailable(url, reply) => {
+                    if let Some(animation) = animations.get(&url) {
+                        let _ = reply.send(Some(animation.clone()));
+                        continue
+                    }
+                    let (animation_chan, animation_port) = ipc::channel();
+                    let msg = ImageCacheWireMsg::GetAnimationIfAvailable(url.clone(),
+                                                                         animation_chan);
+                    if ipc_sender.send(msg).is_err() {
+                        break
+                    }
+                    let animation = match animation_port.recv() {
+                        Ok(Some(AnimationData(animation))) => {
+                            animations.insert(url, animation.clone());
+                            Some(animation)
+                        }
+                        _ => None,
+                    };
+                    let _ = reply.send(animation);
+                    continue
+                }
+                ImageCacheCommand::Prioritize(url) => ImageCacheWireMsg::Prioritize(url),
+                ImageCacheCommand::SetTargetSize(url, size, pipeline_id, result_chan) => {
+                    next_id += 1;
+                    pending_requests.lock().unwrap().insert(next_id, PendingImageRequest {
+                        pipeline_id: pipeline_id,
+                        url: url.clone(),
+                        result_chan: result_chan,
+                        responder: None,
+                        single_response: false,
+                    });
+                    ImageCacheWireMsg::SetTargetSize(url, size, pipeline_id, next_id)
+                }
+                ImageCacheCommand::CancelRequests(pipeline_id) => {
+                    {
+                        let mut pending_requests = pending_requests.lock().unwrap();
+                        let cancelled: Vec<u32> = pending_requests.iter().filter(|&(_, request)| {
+                            request.pipeline_id == pipeline_id
+                        }).map(|(&id, _)| id).collect();
+                        for id in cancelled.iter() {
+                            pending_requests.remove(id);
+                        }
+                    }
+                    images.lock().unwrap().clear();
+                    animations.clear();
+                    ImageCacheWireMsg::CancelRequests(pipeline_id)
+                }
+                ImageCacheCommand::CollectReports(reports_chan) => {
+                    // The image cache of the parent process reports its memory itself.
+                    reports_chan.send(vec!());
+                    continue
+                }
+                ImageCacheCommand::Exit(reply) => {
+                    let _ = reply.send(());
+                    continue
+                }
+            };
+            if ipc_sender.send(wire_msg).is_err() {
+                break
+            }
+        }
+    });
+    ImageCacheTask::new(sender)
+}
+
+/// Sends the answer to an image request back to the content process that made it.
+struct ImageResponderProxy {
+    id: u32,
+    results: IpcSender<ImageResultWire>,
+}
+
+impl ImageResponder for ImageResponderProxy {
+    fn respond(&self, response: ImageResponse) {
+        let response = ImageResponseWire::from_response(response);
+        let _ = self.results.send(ImageResultWire::Response(self.id, response));
+    }
+}
+
+/// Passes the messages that a content process sends to the image cache on to it, and sends the
+/// answers to its asynchronous requests back on `results`.
+pub fn serve_image_cache_task(ipc_receiver: IpcReceiver<ImageCacheWireMsg>,
+                              results: IpcSender<ImageResultWire>,
+                              image_cache_task: ImageCacheTask) {
+    // Image requests are answered through their responders, which know the request's id.
+    let (result_chan, result_port) = channel();
+    spawn_named("ImageResultServer".to_owned(), move || {
+        while let Ok(result) = result_port.recv() {
+            let result: ImageCacheResult = result;
+            if let Some(responder) = result.responder {
+                responder.respond(result.image_response);
+            }
+        }
+    });
+
+    spawn_named("ImageCacheServer".to_owned(), move || {
+        while let Ok(wire_msg) = ipc_receiver.recv() {
+            match wire_msg {
+                ImageCacheWireMsg::RequestImage(url, pipeline_id, id) => {
+                    let responder = box ImageResponderProxy {
+                        id: id,
+                        results: results.clone(),
+                    };
+                    image_cache_task.request_image(url,
+                                                   pipeline_id,
+                                                   ImageCacheChan(result_chan.clone()),
+                                                   Some(responder));
+                }
+                ImageCacheWireMsg::GetImageIfAvailable(url, use_placeholder, reply) => {
+                    let availability =
+                        match image_cache_task.get_image_if_available(url, use_placeholder) {
+                            Ok(image) => ImageAvailability::Available(ImageData(image)),
+                            Err(state) => ImageAvailability::Unavailable(state),
+                        };
+                    let _ = reply.send(availability);
+                }
+                ImageCacheWireMsg::GetAnimationIfAvailable(url, reply) => {
+                    let animation = image_cache_task.get_animation_if_available(url);
+                    let _ = reply.send(animation.map(AnimationData));
+                }
+                ImageCacheWireMsg::Prioritize(url) => image_cache_task.prioritize(url),
+                ImageCacheWireMsg::SetTargetSize(url, size, pipeline_id, id) => {
+                    // The image cache answers these without a responder, so each gets a channel
+                    // of its own, which the cache drops once it has nothing more to say.
+                    let (size_result_chan, size_result_port) = channel();
+                    let results = results.clone();
+                    spawn_named("ImageTargetSizeServer".to_owned(), move || {
+                        while let Ok(result) = size_result_port.recv() {
+                            let result: ImageCacheResult = result;
+                            let response = ImageResponseWire::from_response(result.image_response);
+                            if results.send(ImageResultWire::Response(id, response)).is_err() {
+                                return
+                            }
+                        }
+                        let _ = results.send(ImageResultWire::Done(id));
+                    });
+                    image_cache_task.set_target_size(url, size, pipeline_id,
+                                                     ImageCacheChan(size_result_chan));
+                }
+                ImageCacheWireMsg::CancelRequests(pipeline_id) => {
+                    image_cache_task.cancel_requests(pipeline_id)
+                }
+            }
+        }
+    });
+}
+
+/// A font template, with its data the first time it is sent to a content process.
+#[derive(RustcEncodable, RustcDecodable)]
+pub struct FontTemplateWire {
+    identifier: String,
+    bytes: Option<Vec<u8>>,
+}
+
+/// The messages that content sends to the font cache.
+#[derive(RustcEncodable, RustcDecodable)]
+pub enum FontCacheWireMsg {
+    GetFontTemplate(String, FontTemplateDescriptor,
+                    IpcSender<Vec<(FontTemplateWire, Vec<UnicodeRange>)>>),
+    GetFallbackFontTemplate(FontTemplateDescriptor, Option<HanVariant>, usize,
+                            IpcSender<Option<(usize, FontTemplateWire)>>),
+    AddWebFont(String, Vec<Source>, Vec<UnicodeRange>, IpcSender<LowercaseString>,
+               Option<IpcSender<()>>),
+}
+
+/// Returns a font cache task for the tasks of a content process.
+pub fn proxy_font_cache_task(ipc_sender: IpcSender<FontCacheWireMsg>) -> FontCacheTask {
+    let (sender, receiver) = channel();
+    spawn_named("FontCacheProxy".to_owned(), move || {
+        let mut templates: HashMap<String, Arc<FontTemplateData>> = HashMap::new();
+        let mut template_for = |template: FontTemplateWire| {
+            let FontTemplateWire { identifier, bytes } = template;
+            templates.entry(identifier.clone()).or_insert_with(|| {
+                Arc::new(FontTemplateData::new(&identifier, bytes))
+            }).clone()
+        };
+        while let Ok(command) = receiver.recv() {
+            match command {
+                FontCacheCommand::GetFontTemplate(family, descriptor, reply) => {
+                    let (templates_chan, templates_port) = ipc::channel();
+                    let msg = FontCacheWireMsg::GetFontTemplate(family, descriptor,
+                                                                templates_chan);
+                    if ipc_sender.send(msg).is_err() {
+                        break
+                    }
+                    let received = templates_port.recv().unwrap_or(vec!());
+                    let received = received.into_iter().map(|(template, unicode_range)| {
+                        (template_for(template), unicode_range)
+                    }).collect();
+                    let _ = reply.send(FontCacheReply::GetFontTemplateReply(received));
+                }
+                FontCacheCommand::GetFallbackFontTemplate(descriptor, han_variant, index,
+                                                          reply) => {
+                    let (template_chan, template_port) = ipc::channel();
+                    let msg = FontCacheWireMsg::GetFallbackFontTemplate(descriptor, han_variant,
+                                                                        index, template_chan);
+                    if ipc_sender.send(msg).is_err() {
+                        break
+                    }
+                    let template = template_port.recv().unwrap_or(None).map(|(index, template)| {
+                        (index, template_for(template))
+                    });
+                    let _ = reply.send(template);
+                }
+                FontCacheCommand::AddWebFont(family, sources, unicode_range, listener, loaded) => {
+                    let msg = FontCacheWireMsg::AddWebFont((*family).to_owned(),
+                                                           sources,
+                                                           unicode_range,
+                                                           ipc_sender_for(listener, same),
+                                                           loaded.map(|loaded| {
+                                                               ipc_sender_for(loaded, same)
+                                                           }));
+                    if ipc_sender.send(msg).is_err() {
+                        break
+                    }
+                }
+                FontCacheCommand::CollectReports(reports_chan) => {
+                    // The font cache of the parent process reports its memory itself.
+                    reports_chan.send(vec!());
+                }
+                FontCacheCommand::Exit(reply) => {
+                    let _ = reply.send(());
+                }
+                _ => warn!("content process sent the font cache a message it can't send"),
+            }
+        }
+    });
+    FontCacheTask::from_chan(sender)
+}
+
+/// Passes the messages that a content process sends to the font cache on to it. The templates
+/// that it is given are recorded in `font_templates`, for its display lists to be drawn with.
+pub fn serve_font_cache_task(ipc_receiver: IpcReceiver<FontCacheWireMsg>,
+                             font_cache_task: FontCacheTask,
+                             font_templates: FontTemplates) {
+    spawn_named("FontCacheServer".to_owned(), move || {
+        let mut sent = HashSet::new();
+        let mut wire_template = |template: Arc<FontTemplateData>| {
+            let identifier = template.identifier.clone();
+            let bytes = if sent.insert(identifier.clone()) {
+                template.sfnt_data().map(|data| data.to_vec())
+            } else {
+                None
+            };
+            font_templates.lock().unwrap().insert(identifier.clone(), template);
+            FontTemplateWire {
+                identifier: identifier,
+                bytes: bytes,
+            }
+        };
+        while let Ok(wire_msg) = ipc_receiver.recv() {
+            match wire_msg {
+                FontCacheWireMsg::GetFontTemplate(family, descriptor, reply) => {
+                    let templates = font_cache_task.get_font_templates(family, descriptor);
+                    let templates = templates.into_iter().map(|(template, unicode_range)| {
+                        (wire_template(template), unicode_range)
+                    }).collect();
+                    let _ = reply.send(templates);
+                }
+                FontCacheWireMsg::GetFallbackFontTemplate(descriptor, han_variant, index,
+                                                          reply) => {
+                    let template =
+                        font_cache_task.get_fallback_font_template(descriptor, han_variant, index);
+                    let _ = reply.send(template.map(|(index, template)| {
+                        (index, wire_template(template))
+                    }));
+                }
+                FontCacheWireMsg::AddWebFont(family, sources, unicode_range, listener, loaded) => {
+                    font_cache_task.add_web_font(Atom::from_slice(&family),
+                                                 sources,
+                                                 unicode_range,
+                                                 sender_for(listener, same),
+                                                 loaded.map(|loaded| sender_for(loaded, same)));
+                }
+            }
+        }
+    });
+}
+
+/// The timings that content sends to the time profiler.
+#[derive(RustcEncodable, RustcDecodable)]
+pub enum TimeProfilerWireMsg {
+    Time((ProfilerCategory, Option<TimerMetadata>), (u64, u64), String),
+}
+
+/// Returns a time profiler channel for the tasks of a content process.
+pub fn proxy_time_profiler(ipc_sender: IpcSender<TimeProfilerWireMsg>) -> time::ProfilerChan {
+    let (sender, receiver) = channel();
+    spawn_named("TimeProfilerProxy".to_owned(), move || {
+        while let Ok(msg) = receiver.recv() {
+            let wire_msg = match msg {
+                time::ProfilerMsg::Time(category, interval, task_name) => {
+                    TimeProfilerWireMsg::Time(category, interval, (*task_name).clone())
+                }
+                _ => {
+                    warn!("content process sent the time profiler a message it can't send");
+                    continue
+                }
+            };
+            if ipc_sender.send(wire_msg).is_err() {
+                break
+            }
+        }
+    });
+    time::ProfilerChan(sender)
+}
+
+/// Passes the timings that a content process sends to the time profiler on to it.
+pub fn serve_time_profiler(ipc_receiver: IpcReceiver<TimeProfilerWireMsg>,
+                           time_profiler_chan: time::ProfilerChan) {
+    spawn_named("TimeProfilerServer".to_owned(), move || {
+        while let Ok(TimeProfilerWireMsg::Time(category, interval, task_name)) =
+                ipc_receiver.recv() {
+            time_profiler_chan.send(time::ProfilerMsg::Time(category, interval,
+                                                            Arc::new(task_name)));
+        }
+    });
+}
+
+/// The messages that content sends to the memory profiler. A reporter is registered as the
+/// channel that its requests for reports are sent on, each with a channel for the reports.
+#[derive(RustcEncodable, RustcDecodable)]
+pub enum MemProfilerWireMsg {
+    RegisterReporter(String, IpcSender<IpcSender<Vec<Report>>>),
+    UnregisterReporter(String),
+}
+
+/// Returns a memory profiler channel for the tasks of a content process.
+pub fn proxy_mem_profiler(ipc_sender: IpcSender<MemProfilerWireMsg>) -> mem::ProfilerChan {
+    let (sender, receiver) = channel();
+    spawn_named("MemProfilerProxy".to_owned(), move || {
+        while let Ok(msg) = receiver.recv() {
+            let wire_msg = match msg {
+                mem::ProfilerMsg::RegisterReporter(name, reporter) => {
+                    let (request_chan, request_port) = ipc::channel();
+                    spawn_named("MemoryReporterProxy".to_owned(), move || {
+                        while let Ok(reports_chan) = request_port.recv() {
+                            let reports_chan: IpcSender<Vec<Report>> = reports_chan;
+                            let (local_reports_chan, reports_port) = channel();
+                            let local_reports_chan = ReportsChan(local_reports_chan);
+                            let reports = if reporter.collect_reports(local_reports_chan) {
+                                reports_port.recv().unwrap_or(vec!())
+                            } else {
+                                vec!()
+                            };
+                            let _ = reports_chan.send(reports);
+                        }
+                    });
+                    MemProfilerWireMsg::RegisterReporter(name, request_chan)
+                }
+                mem::ProfilerMsg::UnregisterReporter(name) => {
+                    MemProfilerWireMsg::UnregisterReporter(name)
+                }
+                _ => {
+                    warn!("content process sent the memory profiler a message it can't send");
+                    continue
+                }
+            };
+            if ipc_sender.send(wire_msg).is_err() {
+                break
+            }
+        }
+    });
+    mem::ProfilerChan(sender)
+}
+
+/// Asks a reporter in a content process for its reports.
+struct ReporterProxy(IpcSender<IpcSender<Vec<Report>>>);
+
+impl Reporter for ReporterProxy {
+    fn collect_reports(&self, reports_chan: ReportsChan) -> bool {
+        let ReportsChan(reports_chan) = reports_chan;
+        self.0.send(ipc_sender_for(reports_chan, same)).is_ok()
+    }
+}
+
+/// Passes the messages that a content process sends to the memory profiler on to it.
+pub fn serve_mem_profiler(ipc_receiver: IpcReceiver<MemProfilerWireMsg>,
+                          mem_profiler_chan: mem::ProfilerChan) {
+    spawn_named("MemProfilerServer".to_owned(), move || {
+        while let Ok(wire_msg) = ipc_receiver.recv() {
+            match wire_msg {
+                MemProfilerWireMsg::RegisterReporter(name, request_chan) => {
+                    let reporter = box ReporterProxy(request_chan);
+                    mem_profiler_chan.send(mem::ProfilerMsg::RegisterReporter(name, reporter))
+                }
+                MemProfilerWireMsg::UnregisterReporter(name) => {
+                    mem_profiler_chan.send(mem::ProfilerMsg::UnregisterReporter(name))
+                }
+            }
+        }
+    });
+}
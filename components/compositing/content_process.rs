@@ -0,0 +1,429 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+//! Content processes, which run the script and layout tasks of a top-level frame tree and its
+//! same-process iframes away from the parent process (`-M`).
+//!
+//! A content process is the same executable, started with `--content-process`. It is handed a
+//! `ContentProcessBootstrap` with the options and the channels to the services of the parent
+//! process, and then starts the script and layout tasks of the pipelines that the parent tells it
+//! to. The paint tasks of those pipelines stay in the parent process, which is the one that can
+//! reach the GPU and the user's files.
+//!
+//! With `--sandbox`, a content process enters the sandbox before it starts any threads. Anything
+//! it would read from the disk after that is read by the parent process: the user agent style
+//! sheets come with the bootstrap, and fonts and other resources are loaded through the font
+//! cache and resource tasks of the parent.
+
+use CompositorProxy;
+use content_channels::{self, ConstellationWireMsg, FontCacheWireMsg, ImageCacheWireMsg};
+use content_channels::{ImageResultWire, LayoutControlWireMsg, MemProfilerWireMsg};
+use content_channels::{PaintWireMsg, ResourceWireMsg, ScriptControlWireMsg};
+use content_channels::{ScriptListenerMsg, ScriptListenerProxy, TimeProfilerWireMsg};
+
+use gfx::display_list::serialize::FontTemplates;
+use gfx::font_cache_task::FontCacheTask;
+use gfx::paint_task::PaintChan;
+use layout_traits::{LayoutControlChan, LayoutTaskFactory};
+use msg::constellation_msg::Msg as ConstellationMsg;
+use msg::constellation_msg::{ConstellationChan, Failure, LoadData, PipelineId, SubpageId};
+use msg::constellation_msg::WindowSizeData;
+use net_traits::ResourceTask;
+use net_traits::image_cache_task::ImageCacheTask;
+use net_traits::storage_task::StorageTask;
+use profile_traits::mem;
+use profile_traits::time;
+use script_traits::{ConstellationControlMsg, NewLayoutInfo, ScriptControlChan};
+use script_traits::ScriptTaskFactory;
+use style::selector_matching::{QUIRKS_MODE_STYLESHEET, USER_AGENT_STYLESHEETS};
+use std::borrow::ToOwned;
+use std::collections::HashMap;
+use std::env;
+use std::io;
+use std::process::{Child, Command};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{Sender, channel};
+use std::sync::{Arc, Mutex};
+use util::ipc::{self, IpcReceiver, IpcSender};
+use util::opts::{self, Opts};
+use util::resource_files;
+use util::sandbox;
+use util::task::spawn_named;
+
+/// The argument that starts a content process, which is followed by the one that
+/// `IpcReceiver::from_inherited` takes.
+pub const CONTENT_PROCESS_ARGUMENT: &'static str = "--content-process";
+
+/// What a content process is started with.
+#[derive(RustcEncodable, RustcDecodable)]
+pub struct ContentProcessBootstrap {
+    /// The options of the parent process, which `opts::set` should be given before anything else
+    /// is done.
+    pub opts: Opts,
+    /// The resource files that the content process reads, which it may not be able to read from
+    /// the disk itself.
+    resource_files: HashMap<String, Vec<u8>>,
+    control_port: IpcReceiver<ContentProcessMsg>,
+    constellation_chan: IpcSender<ConstellationWireMsg>,
+    script_listener: IpcSender<ScriptListenerMsg>,
+    resource_task: IpcSender<ResourceWireMsg>,
+    image_cache_task: IpcSender<ImageCacheWireMsg>,
+    image_results: IpcReceiver<ImageResultWire>,
+    font_cache_task: IpcSender<FontCacheWireMsg>,
+    storage_task: StorageTask,
+    time_profiler_chan: IpcSender<TimeProfilerWireMsg>,
+    mem_profiler_chan: IpcSender<MemProfilerWireMsg>,
+}
+
+impl ContentProcessBootstrap {
+    /// Receives the bootstrap of this content process, given the argument that followed
+    /// `CONTENT_PROCESS_ARGUMENT` on the command line.
+    pub fn receive(argument: &str) -> io::Result<ContentProcessBootstrap> {
+        let port: IpcReceiver<ContentProcessBootstrap> =
+            try!(IpcReceiver::from_inherited(argument));
+        port.recv()
+    }
+}
+
+/// The script task that a new pipeline runs in.
+#[derive(RustcEncodable, RustcDecodable)]
+enum PipelineScript {
+    /// A new script task, which receives the constellation's messages on this channel.
+    New(IpcReceiver<ScriptControlWireMsg>),
+    /// The script task of the pipeline with this id, which contains the new one as an iframe.
+    Existing(PipelineId),
+}
+
+/// The initial data of a pipeline that a content process is to start.
+#[derive(RustcEncodable, RustcDecodable)]
+struct NewPipelineInfo {
+    id: PipelineId,
+    parent_info: Option<(PipelineId, SubpageId)>,
+    script: PipelineScript,
+    layout_port: IpcReceiver<LayoutControlWireMsg>,
+    paint_chan: IpcSender<PaintWireMsg>,
+    layout_shutdown_chan: IpcSender<()>,
+    window_size: Option<WindowSizeData>,
+    load_data: LoadData,
+}
+
+/// Messages from the parent process to a content process.
+#[derive(RustcEncodable, RustcDecodable)]
+enum ContentProcessMsg {
+    NewPipeline(NewPipelineInfo),
+}
+
+/// A content process, as the parent process sees it. The process is killed when this is dropped,
+/// which happens once none of its pipelines are left.
+pub struct ContentProcess {
+    child: Child,
+    control_chan: IpcSender<ContentProcessMsg>,
+    constellation_chan: ConstellationChan,
+    /// The pipelines started in the process, with the parent information of each.
+    pipelines: Arc<Mutex<Vec<(PipelineId, Option<(PipelineId, SubpageId)>)>>>,
+    /// Set once the process has gone away, whether it crashed or was killed.
+    exited: Arc<AtomicBool>,
+    /// Set before the process is killed, so that it isn't taken for a crash.
+    shutting_down: Arc<AtomicBool>,
+    /// The font templates that the process has been given, for its display lists to be drawn
+    /// with.
+    font_templates: FontTemplates,
+}
+
+impl ContentProcess {
+    /// Starts a content process, which uses the given services of this process.
+    pub fn spawn(constellation_chan: ConstellationChan,
+                 compositor_proxy: Box<CompositorProxy+'static+Send>,
+                 resource_task: ResourceTask,
+                 image_cache_task: ImageCacheTask,
+                 font_cache_task: FontCacheTask,
+                 storage_task: StorageTask,
+                 time_profiler_chan: time::ProfilerChan,
+                 mem_profiler_chan: mem::ProfilerChan)
+                 -> io::Result<ContentProcess> {
+        let mut stylesheets = USER_AGENT_STYLESHEETS.to_vec();
+        stylesheets.push(QUIRKS_MODE_STYLESHEET);
+        let preloaded_files = try!(resource_files::read_resource_files(&stylesheets));
+
+        let (bootstrap_chan, bootstrap_port) = ipc::channel();
+        let mut command = Command::new(try!(env::current_exe()));
+        command.arg(CONTENT_PROCESS_ARGUMENT);
+        let child = try!(ipc::spawn_with_receiver(&mut command, bootstrap_port));
+
+        let (control_chan, control_port) = ipc::channel();
+        let (content_constellation_chan, constellation_port) = ipc::channel();
+        let (script_listener, script_listener_port) = ipc::channel();
+        let (content_resource_task, resource_port) = ipc::channel();
+        let (content_image_cache_task, image_cache_port) = ipc::channel();
+        let (image_results_chan, image_results) = ipc::channel();
+        let (content_font_cache_task, font_cache_port) = ipc::channel();
+        let (content_time_profiler_chan, time_profiler_port) = ipc::channel();
+        let (content_mem_profiler_chan, mem_profiler_port) = ipc::channel();
+
+        let font_templates = Arc::new(Mutex::new(HashMap::new()));
+        content_channels::serve_script_listener(script_listener_port, compositor_proxy);
+        content_channels::serve_resource_task(resource_port, resource_task);
+        content_channels::serve_image_cache_task(image_cache_port, image_results_chan,
+                                                 image_cache_task);
+        content_channels::serve_font_cache_task(font_cache_port, font_cache_task,
+                                                font_templates.clone());
+        content_channels::serve_time_profiler(time_profiler_port, time_profiler_chan);
+        content_channels::serve_mem_profiler(mem_profiler_port, mem_profiler_chan);
+
+        let bootstrap = ContentProcessBootstrap {
+            opts: opts::get().clone(),
+            resource_files: preloaded_files,
+            control_port: control_port,
+            constellation_chan: content_constellation_chan,
+            script_listener: script_listener,
+            resource_task: content_resource_task,
+            image_cache_task: content_image_cache_task,
+            image_results: image_results,
+            font_cache_task: content_font_cache_task,
+            storage_task: storage_task,
+            time_profiler_chan: content_time_profiler_chan,
+            mem_profiler_chan: content_mem_profiler_chan,
+        };
+        try!(bootstrap_chan.send(bootstrap));
+
+        let content_process = ContentProcess {
+            child: child,
+            control_chan: control_chan,
+            constellation_chan: constellation_chan.clone(),
+            pipelines: Arc::new(Mutex::new(vec!())),
+            exited: Arc::new(AtomicBool::new(false)),
+            shutting_down: Arc::new(AtomicBool::new(false)),
+            font_templates: font_templates,
+        };
+
+        // The messages for the constellation stop once the process is gone. Unless it was
+        // killed, it crashed, and the pipelines that aren't iframes of others in the process are
+        // reported as failed, which closes the rest.
+        let pipelines = content_process.pipelines.clone();
+        let exited = content_process.exited.clone();
+        let shutting_down = content_process.shutting_down.clone();
+        spawn_named("ContentConstellationServer".to_owned(), move || {
+            content_channels::serve_constellation(constellation_port, constellation_chan.clone());
+            exited.store(true, Ordering::SeqCst);
+            if shutting_down.load(Ordering::SeqCst) {
+                return
+            }
+            let pipelines = pipelines.lock().unwrap();
+            let ConstellationChan(ref chan) = constellation_chan;
+            for &(pipeline_id, parent_info) in pipelines.iter() {
+                let is_nested = parent_info.map_or(false, |(parent_id, _)| {
+                    pipelines.iter().any(|&(id, _)| id == parent_id)
+                });
+                if !is_nested {
+                    let _ = chan.send(ConstellationMsg::Failure(Failure {
+                        pipeline_id: pipeline_id,
+                        parent_info: parent_info,
+                    }));
+                }
+            }
+        });
+
+        Ok(content_process)
+    }
+
+    /// Whether the process is still running.
+    pub fn is_alive(&self) -> bool {
+        !self.exited.load(Ordering::SeqCst)
+    }
+
+    /// Starts the script and layout tasks of a pipeline in the process. Its script task is a new
+    /// one, unless `script_chan` is that of the pipeline it is an iframe of. Returns the channels
+    /// to the pipeline's script and layout tasks.
+    pub fn start_pipeline(&self,
+                          id: PipelineId,
+                          parent_info: Option<(PipelineId, SubpageId)>,
+                          script_chan: Option<ScriptControlChan>,
+                          paint_chan: PaintChan,
+                          layout_shutdown_chan: Sender<()>,
+                          window_size: Option<WindowSizeData>,
+                          load_data: LoadData)
+                          -> (ScriptControlChan, LayoutControlChan) {
+        let (script, script_chan) = match script_chan {
+            Some(script_chan) => {
+                let (containing_pipeline_id, _) =
+                    parent_info.expect("script_pipeline != None but subpage_id == None");
+                (PipelineScript::Existing(containing_pipeline_id), script_chan)
+            }
+            None => {
+                let (ipc_script_chan, script_port) = ipc::channel();
+                let script_chan = content_channels::proxy_script_control(ipc_script_chan);
+                (PipelineScript::New(script_port), script_chan)
+            }
+        };
+
+        let (ipc_layout_chan, layout_port) = ipc::channel();
+        let layout_chan = content_channels::proxy_layout_control(ipc_layout_chan);
+
+        let (ipc_paint_chan, paint_port) = ipc::channel();
+        content_channels::serve_paint(paint_port, paint_chan, self.font_templates.clone());
+
+        let (ipc_layout_shutdown_chan, layout_shutdown_port) = ipc::channel();
+        ipc::route_to_sender(layout_shutdown_port, layout_shutdown_chan);
+
+        self.pipelines.lock().unwrap().push((id, parent_info));
+        let msg = ContentProcessMsg::NewPipeline(NewPipelineInfo {
+            id: id,
+            parent_info: parent_info,
+            script: script,
+            layout_port: layout_port,
+            paint_chan: ipc_paint_chan,
+            layout_shutdown_chan: ipc_layout_shutdown_chan,
+            window_size: window_size,
+            load_data: load_data,
+        });
+        if self.control_chan.send(msg).is_err() {
+            // The process went away before it could start the pipeline.
+            let ConstellationChan(ref chan) = self.constellation_chan;
+            let _ = chan.send(ConstellationMsg::Failure(Failure {
+                pipeline_id: id,
+                parent_info: parent_info,
+            }));
+        }
+
+        (script_chan, layout_chan)
+    }
+}
+
+impl Drop for ContentProcess {
+    fn drop(&mut self) {
+        self.shutting_down.store(true, Ordering::SeqCst);
+        let _ = self.child.kill();
+        let _ = self.child.wait();
+    }
+}
+
+/// Runs a content process, starting the pipelines that the parent process asks for until it
+/// goes away. `opts::set` must have been called with the options in `bootstrap` already, and no
+/// other thread may have been started, so that the sandbox covers every thread of the process.
+pub fn run<LTF, STF>(bootstrap: ContentProcessBootstrap)
+                     where LTF: LayoutTaskFactory, STF: ScriptTaskFactory {
+    let ContentProcessBootstrap {
+        resource_files: preloaded_files,
+        control_port,
+        constellation_chan,
+        script_listener,
+        resource_task,
+        image_cache_task,
+        image_results,
+        font_cache_task,
+        storage_task,
+        time_profiler_chan,
+        mem_profiler_chan,
+        ..
+    } = bootstrap;
+
+    resource_files::set_preloaded_files(preloaded_files);
+
+    // Content mustn't run unless the sandbox was asked for and entered.
+    if opts::get().sandbox {
+        if let Err(error) = sandbox::enter() {
+            error!("Failed to sandbox the content process: {}", error);
+            return
+        }
+    }
+
+    let constellation_chan = content_channels::proxy_constellation(constellation_chan);
+    let script_listener = ScriptListenerProxy(script_listener);
+    let resource_task = content_channels::proxy_resource_task(resource_task);
+    let image_cache_task = content_channels::proxy_image_cache_task(image_cache_task,
+                                                                    image_results);
+    let font_cache_task = content_channels::proxy_font_cache_task(font_cache_task);
+    let time_profiler_chan = content_channels::proxy_time_profiler(time_profiler_chan);
+    let mem_profiler_chan = content_channels::proxy_mem_profiler(mem_profiler_chan);
+
+    // The script tasks of the pipelines in this process, which iframes can be attached to.
+    let mut script_chans: HashMap<PipelineId, ScriptControlChan> = HashMap::new();
+
+    while let Ok(ContentProcessMsg::NewPipeline(info)) = control_port.recv() {
+        let NewPipelineInfo {
+            id,
+            parent_info,
+            script,
+            layout_port,
+            paint_chan,
+            layout_shutdown_chan,
+            window_size,
+            load_data,
+        } = info;
+
+        let layout_pair = ScriptTaskFactory::create_layout_channel(None::<&mut STF>);
+        let failure = Failure {
+            pipeline_id: id,
+            parent_info: parent_info,
+        };
+
+        let script_chan = match script {
+            PipelineScript::New(script_port) => {
+                let (script_chan, local_script_port) = channel();
+                content_channels::serve_script_control(script_port, script_chan.clone());
+                ScriptTaskFactory::create(None::<&mut STF>,
+                                          id,
+                                          parent_info,
+                                          script_listener.clone(),
+                                          &layout_pair,
+                                          ScriptControlChan(script_chan.clone()),
+                                          local_script_port,
+                                          constellation_chan.clone(),
+                                          failure.clone(),
+                                          resource_task.clone(),
+                                          storage_task.clone(),
+                                          image_cache_task.clone(),
+                                          None,
+                                          mem_profiler_chan.clone(),
+                                          window_size,
+                                          load_data.clone());
+                ScriptControlChan(script_chan)
+            }
+            PipelineScript::Existing(containing_pipeline_id) => {
+                let script_chan = match script_chans.get(&containing_pipeline_id) {
+                    Some(script_chan) => script_chan.clone(),
+                    None => {
+                        warn!("the pipeline that an iframe is in isn't in this content process");
+                        continue
+                    }
+                };
+                let (_, subpage_id) =
+                    parent_info.expect("script_pipeline != None but subpage_id == None");
+                let new_layout_info = NewLayoutInfo {
+                    containing_pipeline_id: containing_pipeline_id,
+                    new_pipeline_id: id,
+                    subpage_id: subpage_id,
+                    layout_chan: ScriptTaskFactory::clone_layout_channel(None::<&mut STF>,
+                                                                         &layout_pair),
+                    load_data: load_data.clone(),
+                };
+                let ScriptControlChan(ref chan) = script_chan;
+                let _ = chan.send(ConstellationControlMsg::AttachLayout(new_layout_info));
+                script_chan.clone()
+            }
+        };
+        script_chans.insert(id, script_chan.clone());
+
+        let (pipeline_chan, pipeline_port) = channel();
+        content_channels::serve_layout_control(layout_port, pipeline_chan);
+        let (shutdown_chan, shutdown_port) = channel();
+        ipc::route_to_ipc_sender(shutdown_port, layout_shutdown_chan);
+
+        LayoutTaskFactory::create(None::<&mut LTF>,
+                                  id,
+                                  load_data.url.clone(),
+                                  parent_info.is_some(),
+                                  layout_pair,
+                                  pipeline_port,
+                                  constellation_chan.clone(),
+                                  failure,
+                                  script_chan,
+                                  content_channels::proxy_paint(paint_chan),
+                                  image_cache_task.clone(),
+                                  font_cache_task.clone(),
+                                  time_profiler_chan.clone(),
+                                  mem_profiler_chan.clone(),
+                                  shutdown_chan);
+    }
+}
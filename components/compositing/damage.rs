@@ -0,0 +1,99 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+//! Keeps track of the parts of the window that have changed since the last composite, so that
+//! windows that keep the contents of their back buffer need only draw those again.
+
+use compositor_layer::CompositorData;
+
+use euclid::point::{Point2D, TypedPoint2D};
+use euclid::rect::{Rect, TypedRect};
+use euclid::scale_factor::ScaleFactor;
+use euclid::size::{Size2D, TypedSize2D};
+use layers::geometry::{DevicePixel, LayerPixel};
+use layers::layers::Layer;
+use msg::compositor_msg::LayerId;
+use msg::constellation_msg::PipelineId;
+
+/// The part of the window, in device pixels from the top left, that has to be drawn again.
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum Damage {
+    Nothing,
+    Rect(TypedRect<DevicePixel, f32>),
+    Everything,
+}
+
+impl Damage {
+    /// Adds `rect` to the damaged area.
+    pub fn add(self, rect: TypedRect<DevicePixel, f32>) -> Damage {
+        match self {
+            Damage::Nothing => Damage::Rect(rect),
+            Damage::Rect(damage) => Damage::Rect(damage.union(&rect)),
+            Damage::Everything => Damage::Everything,
+        }
+    }
+
+    /// Returns the damaged area of a window of the given size, rounded out to whole device
+    /// pixels, or `None` if nothing is damaged.
+    pub fn to_window_rect(&self, window_size: TypedSize2D<DevicePixel, u32>)
+                          -> Option<TypedRect<DevicePixel, u32>> {
+        let window_rect = Rect::new(Point2D::new(0, 0), window_size.to_untyped());
+        let rect = match *self {
+            Damage::Nothing => return None,
+            Damage::Rect(rect) => rect.to_untyped(),
+            Damage::Everything => return Some(Rect::from_untyped(&window_rect)),
+        };
+        let min_x = rect.origin.x.floor().max(0.0) as u32;
+        let min_y = rect.origin.y.floor().max(0.0) as u32;
+        let max_x = rect.max_x().ceil().max(0.0) as u32;
+        let max_y = rect.max_y().ceil().max(0.0) as u32;
+        let rect = Rect::new(Point2D::new(min_x, min_y),
+                             Size2D::new(max_x - min_x, max_y - min_y));
+        rect.intersection(&window_rect).map(|rect| Rect::from_untyped(&rect))
+    }
+}
+
+/// Finds where a layer is drawn in the window, in device pixels from the top left, once it has
+/// been scrolled. The clipping of its ancestors is not taken into account, so the rect may be
+/// too large, but never too small.
+pub fn rect_in_window_for_layer(root: &Layer<CompositorData>,
+                                pipeline_id: PipelineId,
+                                layer_id: LayerId,
+                                scale: ScaleFactor<LayerPixel, DevicePixel, f32>)
+                                -> Option<TypedRect<DevicePixel, f32>> {
+    let rect = match rect_in_window_for_layer_in_layer_pixels(root,
+                                                              pipeline_id,
+                                                              layer_id,
+                                                              Point2D::zero()) {
+        Some(rect) => rect.to_untyped(),
+        None => return None,
+    };
+    let scale = scale.get();
+    Some(Rect::from_untyped(&Rect::new(Point2D::new(rect.origin.x * scale,
+                                                    rect.origin.y * scale),
+                                       Size2D::new(rect.size.width * scale,
+                                                   rect.size.height * scale))))
+}
+
+fn rect_in_window_for_layer_in_layer_pixels(layer: &Layer<CompositorData>,
+                                            pipeline_id: PipelineId,
+                                            layer_id: LayerId,
+                                            origin: TypedPoint2D<LayerPixel, f32>)
+                                            -> Option<TypedRect<LayerPixel, f32>> {
+    let bounds = *layer.bounds.borrow();
+    if layer.pipeline_id() == pipeline_id && layer.extra_data.borrow().id == layer_id {
+        return Some(bounds.translate(&(origin + *layer.content_offset.borrow())))
+    }
+    let origin_for_children = origin + bounds.origin;
+    for child in layer.children().iter() {
+        let rect = rect_in_window_for_layer_in_layer_pixels(&*child,
+                                                            pipeline_id,
+                                                            layer_id,
+                                                            origin_for_children);
+        if rect.is_some() {
+            return rect
+        }
+    }
+    None
+}
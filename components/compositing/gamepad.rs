@@ -0,0 +1,295 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+//! Polls the platform's game controllers for the script tasks that watch them. This runs in the
+//! constellation's process rather than in content's, so that content needn't open devices. The
+//! polling task starts when a script task first watches the controllers, and exits once none is
+//! watching any more.
+
+use msg::constellation_msg::{GamepadState, GamepadUpdate, MAX_GAMEPADS};
+use std::borrow::ToOwned;
+use std::sync::mpsc::Sender;
+use std::sync::{Arc, Mutex};
+use std::thread::sleep_ms;
+use util::task::spawn_named;
+
+/// How often controllers are polled; roughly once per frame.
+const POLL_INTERVAL_MS: u32 = 16;
+
+/// How many polls pass between scans for newly plugged-in controllers.
+const POLLS_PER_DEVICE_SCAN: u32 = 60;
+
+/// A channel that updates are sent to, and whether it has been sent the controllers' state yet.
+struct Watcher {
+    sender: Sender<GamepadUpdate>,
+    is_new: bool,
+}
+
+/// The channels of the script tasks that watch the controllers, or `None` if the polling task
+/// isn't running.
+pub struct GamepadMonitor {
+    watchers: Arc<Mutex<Option<Vec<Watcher>>>>,
+}
+
+impl GamepadMonitor {
+    pub fn new() -> GamepadMonitor {
+        GamepadMonitor {
+            watchers: Arc::new(Mutex::new(None)),
+        }
+    }
+
+    /// Sends the controllers' state to `sender` now and whenever it changes, starting the
+    /// polling task if it isn't running.
+    pub fn watch(&self, sender: Sender<GamepadUpdate>) {
+        let watcher = Watcher {
+            sender: sender,
+            is_new: true,
+        };
+        let mut watchers = self.watchers.lock().unwrap();
+        if let Some(ref mut watchers) = *watchers {
+            watchers.push(watcher);
+            return
+        }
+        *watchers = Some(vec!(watcher));
+
+        let task_watchers = self.watchers.clone();
+        spawn_named("GamepadPoller".to_owned(), move || {
+            let mut devices = platform::Devices::new();
+            let mut states = vec![None; MAX_GAMEPADS];
+            let mut polls = 0;
+            loop {
+                if polls % POLLS_PER_DEVICE_SCAN == 0 {
+                    devices.scan();
+                }
+                polls += 1;
+
+                let old_timestamps = timestamps(&states);
+                let connection_changes = devices.poll(&mut states);
+                let changed = !connection_changes.is_empty() ||
+                    timestamps(&states) != old_timestamps;
+
+                let mut watchers = task_watchers.lock().unwrap();
+                let mut remaining = vec!();
+                for mut watcher in watchers.take().unwrap().into_iter() {
+                    if !changed && !watcher.is_new {
+                        remaining.push(watcher);
+                        continue
+                    }
+                    // A new watcher hears about every controller that is already connected.
+                    let connection_changes = if watcher.is_new {
+                        connected_slots(&states)
+                    } else {
+                        connection_changes.clone()
+                    };
+                    watcher.is_new = false;
+                    let update = GamepadUpdate {
+                        states: states.clone(),
+                        connection_changes: connection_changes,
+                    };
+                    if watcher.sender.send(update).is_ok() {
+                        remaining.push(watcher);
+                    }
+                }
+                if remaining.is_empty() {
+                    return
+                }
+                *watchers = Some(remaining);
+                drop(watchers);
+
+                sleep_ms(POLL_INTERVAL_MS);
+            }
+        });
+    }
+}
+
+fn timestamps(states: &[Option<GamepadState>]) -> Vec<Option<u64>> {
+    states.iter().map(|state| state.as_ref().map(|state| state.timestamp)).collect()
+}
+
+fn connected_slots(states: &[Option<GamepadState>]) -> Vec<(u32, bool)> {
+    states.iter().enumerate().filter(|&(_, state)| state.is_some())
+                             .map(|(index, _)| (index as u32, true)).collect()
+}
+
+#[cfg(target_os = "linux")]
+mod platform {
+    //! Reads controllers through the Linux joystick interface, `/dev/input/js*`.
+
+    use libc;
+    use msg::constellation_msg::{GamepadButtonState, GamepadState, MAX_GAMEPADS};
+    use std::borrow::ToOwned;
+    use std::fs::{self, File, OpenOptions};
+    use std::i16;
+    use std::io::{self, Read};
+    use std::mem;
+    use std::os::unix::io::AsRawFd;
+    use time;
+
+    const JS_EVENT_BUTTON: u8 = 0x01;
+    const JS_EVENT_AXIS: u8 = 0x02;
+    const JS_EVENT_INIT: u8 = 0x80;
+
+    /// `struct js_event` from `linux/joystick.h`.
+    #[repr(C)]
+    #[allow(dead_code)]
+    struct JsEvent {
+        time: u32,
+        value: i16,
+        type_: u8,
+        number: u8,
+    }
+
+    struct Device {
+        /// The name of the device node, e.g. `js0`.
+        node: String,
+        file: File,
+    }
+
+    impl Device {
+        #[allow(unsafe_code)]
+        fn open(node: String) -> Option<Device> {
+            let file = match OpenOptions::new().read(true).open(format!("/dev/input/{}", node)) {
+                Ok(file) => file,
+                Err(_) => return None,
+            };
+            unsafe {
+                libc::fcntl(file.as_raw_fd(), libc::F_SETFL, libc::O_NONBLOCK);
+            }
+            Some(Device {
+                node: node,
+                file: file,
+            })
+        }
+
+        fn name(&self) -> String {
+            let mut name = String::new();
+            let path = format!("/sys/class/input/{}/device/name", self.node);
+            match File::open(path).and_then(|mut file| file.read_to_string(&mut name)) {
+                Ok(_) => name.trim().to_owned(),
+                Err(_) => format!("Unknown Gamepad ({})", self.node),
+            }
+        }
+
+        /// Applies every pending event to `state`. Returns false if the device went away.
+        #[allow(unsafe_code)]
+        fn read_events(&mut self, state: &mut GamepadState) -> bool {
+            let mut buf = [0u8; 8];
+            loop {
+                match self.file.read(&mut buf) {
+                    Ok(8) => {},
+                    Ok(_) => return false,
+                    Err(ref e) if e.kind() == io::ErrorKind::WouldBlock => return true,
+                    Err(ref e) if e.kind() == io::ErrorKind::Interrupted => continue,
+                    Err(_) => return false,
+                }
+
+                let event: JsEvent = unsafe { mem::transmute(buf) };
+                let number = event.number as usize;
+                match event.type_ & !JS_EVENT_INIT {
+                    JS_EVENT_BUTTON => {
+                        while state.buttons.len() <= number {
+                            state.buttons.push(GamepadButtonState { pressed: false, value: 0. });
+                        }
+                        let pressed = event.value != 0;
+                        state.buttons[number] = GamepadButtonState {
+                            pressed: pressed,
+                            value: if pressed { 1. } else { 0. },
+                        };
+                    }
+                    JS_EVENT_AXIS => {
+                        while state.axes.len() <= number {
+                            state.axes.push(0.);
+                        }
+                        let value = event.value as f64 / i16::MAX as f64;
+                        state.axes[number] = value.max(-1.);
+                    }
+                    _ => continue,
+                }
+                state.timestamp = time::precise_time_ns();
+            }
+        }
+    }
+
+    pub struct Devices {
+        /// The device connected to each controller slot.
+        slots: Vec<Option<Device>>,
+    }
+
+    impl Devices {
+        pub fn new() -> Devices {
+            Devices {
+                slots: (0..MAX_GAMEPADS).map(|_| None).collect(),
+            }
+        }
+
+        /// Opens any joystick device that isn't connected to a slot yet.
+        pub fn scan(&mut self) {
+            let entries = match fs::read_dir("/dev/input") {
+                Ok(entries) => entries,
+                Err(_) => return,
+            };
+            for entry in entries {
+                let node = match entry {
+                    Ok(entry) => entry.file_name().to_string_lossy().into_owned(),
+                    Err(_) => continue,
+                };
+                if !node.starts_with("js") ||
+                   self.slots.iter().any(|slot| slot.as_ref().map_or(false, |d| d.node == node)) {
+                    continue;
+                }
+                let free_slot = match self.slots.iter().position(|slot| slot.is_none()) {
+                    Some(index) => index,
+                    None => return,
+                };
+                self.slots[free_slot] = Device::open(node);
+            }
+        }
+
+        /// Updates `states` from every connected device, returning the slots whose
+        /// connection status changed.
+        pub fn poll(&mut self, states: &mut [Option<GamepadState>]) -> Vec<(u32, bool)> {
+            let mut changes = vec!();
+            for (index, slot) in self.slots.iter_mut().enumerate() {
+                let connected = match *slot {
+                    Some(ref mut device) => {
+                        if states[index].is_none() {
+                            states[index] = Some(GamepadState::new(device.name()));
+                            changes.push((index as u32, true));
+                        }
+                        device.read_events(states[index].as_mut().unwrap())
+                    }
+                    None => continue,
+                };
+                if !connected {
+                    *slot = None;
+                    states[index] = None;
+                    changes.push((index as u32, false));
+                }
+            }
+            changes
+        }
+    }
+}
+
+#[cfg(not(target_os = "linux"))]
+mod platform {
+    //! There is no controller backend for this platform yet, so nothing is ever connected.
+
+    use msg::constellation_msg::GamepadState;
+
+    pub struct Devices;
+
+    impl Devices {
+        pub fn new() -> Devices {
+            Devices
+        }
+
+        pub fn scan(&mut self) {
+        }
+
+        pub fn poll(&mut self, _: &mut [Option<GamepadState>]) -> Vec<(u32, bool)> {
+            vec!()
+        }
+    }
+}
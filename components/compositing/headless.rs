@@ -96,21 +96,61 @@ impl CompositorEventListener for NullCompositor {
             Msg::SetLayerRect(..) |
             Msg::AssignPaintedBuffers(..) |
             Msg::ChangeRunningAnimationsState(..) |
+            Msg::ChangeRunningAnimatedImagesState(..) |
             Msg::ScrollFragmentPoint(..) |
             Msg::LoadStart(..) |
             Msg::LoadComplete(..) |
             Msg::ScrollTimeout(..) |
             Msg::RecompositeAfterScroll |
+            Msg::ResizeTimeout |
+            Msg::ScrollbarFadeTimeout |
+            Msg::ScreenshotTimeout |
+            Msg::PrintedToPdf(..) |
+            Msg::PrintTimeout |
+            Msg::Vsync(..) |
             Msg::ChangePageTitle(..) |
             Msg::ChangePageUrl(..) |
             Msg::KeyEvent(..) |
             Msg::SetCursor(..) |
+            Msg::HighlightRect(..) |
+            Msg::ImeCaretRect(..) |
             Msg::ViewportConstrained(..) => {}
-            Msg::CreatePng(..) |
+            // Nothing is drawn, so there is nothing to take a screenshot of.
+            Msg::CreatePng(reply) => {
+                let _ = reply.send(None);
+            }
             Msg::PaintTaskExited(..) |
             Msg::IsReadyToSaveImageReply(..) => {}
             Msg::NewFavicon(..) => {}
+            Msg::ChangeFavicon(..) => {}
             Msg::HeadParsed => {}
+
+            // There is nobody to ask, so permission is always denied.
+            Msg::RequestPermission(pipeline_id, name, url) => {
+                let ConstellationChan(ref chan) = self.constellation_chan;
+                chan.send(ConstellationMsg::PermissionResponse(pipeline_id, name, url, false))
+                    .unwrap();
+            }
+            Msg::ShowNotification(..) |
+            Msg::CloseNotification(..) |
+            Msg::MixedContent(..) => {}
+
+            // There is no location service to ask.
+            Msg::RequestGeolocationPosition(pipeline_id, _) => {
+                let ConstellationChan(ref chan) = self.constellation_chan;
+                chan.send(ConstellationMsg::GeolocationPositionResponse(pipeline_id, None))
+                    .unwrap();
+            }
+
+            // There is nobody to ask, so the error page stays.
+            Msg::CertificateError(pipeline_id, error) => {
+                let ConstellationChan(ref chan) = self.constellation_chan;
+                chan.send(ConstellationMsg::CertificateErrorResponse(pipeline_id, error, false))
+                    .unwrap();
+            }
+            Msg::RequestCredentials(_, _, sender) => {
+                let _ = sender.send(None);
+            }
         }
         true
     }
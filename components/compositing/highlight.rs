@@ -0,0 +1,72 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+//! The highlight the compositor fills in over the box of the node that the developer tools are
+//! inspecting, and where rectangles of a page end up in the window.
+
+use compositor_layer::{CompositorData, CompositorLayer};
+use scrollbars::ScrollbarRenderer;
+
+use euclid::point::{Point2D, TypedPoint2D};
+use euclid::rect::{Rect, TypedRect};
+use euclid::scale_factor::ScaleFactor;
+use euclid::size::{Size2D, TypedSize2D};
+use layers::geometry::{DevicePixel, LayerPixel};
+use layers::layers::Layer;
+use msg::compositor_msg::LayerId;
+use msg::constellation_msg::PipelineId;
+
+/// The color of the box of an inspected node, a translucent light blue, with premultiplied alpha.
+const HIGHLIGHT_COLOR: [f32; 4] = [0.21, 0.32, 0.37, 0.4];
+
+/// Fills in `rect`, the box of the node being inspected, in page pixels from the top left of the
+/// document of the given pipeline.
+pub fn draw_highlight(renderer: &ScrollbarRenderer,
+                      root: &Layer<CompositorData>,
+                      pipeline_id: PipelineId,
+                      rect: Rect<f32>,
+                      scale: ScaleFactor<LayerPixel, DevicePixel, f32>,
+                      window_size: TypedSize2D<DevicePixel, u32>) {
+    if let Some(rect) = rect_in_window_for_pipeline(root, pipeline_id, rect, scale) {
+        renderer.fill_rects(&[rect], HIGHLIGHT_COLOR, window_size);
+    }
+}
+
+/// Finds where a rectangle of a page, in page pixels from the top left of its document, is drawn
+/// in the window.
+pub fn rect_in_window_for_pipeline(root: &Layer<CompositorData>,
+                                   pipeline_id: PipelineId,
+                                   rect: Rect<f32>,
+                                   scale: ScaleFactor<LayerPixel, DevicePixel, f32>)
+                                   -> Option<TypedRect<DevicePixel, f32>> {
+    let origin = match content_origin_for_pipeline(root, pipeline_id, Point2D::zero()) {
+        Some(origin) => origin.to_untyped(),
+        None => return None,
+    };
+    let scale = scale.get();
+    Some(Rect::from_untyped(&Rect::new(Point2D::new((origin.x + rect.origin.x) * scale,
+                                                    (origin.y + rect.origin.y) * scale),
+                                       Size2D::new(rect.size.width * scale,
+                                                   rect.size.height * scale))))
+}
+
+/// Finds the top left of the document of a pipeline, in layer pixels from the top left of the
+/// window, once it has been scrolled.
+fn content_origin_for_pipeline(layer: &Layer<CompositorData>,
+                               pipeline_id: PipelineId,
+                               origin: TypedPoint2D<LayerPixel, f32>)
+                               -> Option<TypedPoint2D<LayerPixel, f32>> {
+    let bounds = *layer.bounds.borrow();
+    if layer.pipeline_id() == pipeline_id && layer.extra_data.borrow().id == LayerId::null() {
+        return Some(origin + bounds.origin + *layer.content_offset.borrow())
+    }
+    let origin_for_children = origin + bounds.origin;
+    for child in layer.children().iter() {
+        let child_origin = content_origin_for_pipeline(&*child, pipeline_id, origin_for_children);
+        if child_origin.is_some() {
+            return child_origin
+        }
+    }
+    None
+}
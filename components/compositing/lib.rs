@@ -3,7 +3,6 @@
  * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
 
 #![feature(box_syntax)]
-#![feature(slice_bytes)]
 #![feature(vec_push_all)]
 
 #[macro_use]
@@ -30,6 +29,8 @@ extern crate gleam;
 extern crate clipboard;
 
 extern crate libc;
+extern crate rustc_serialize;
+extern crate string_cache;
 extern crate time;
 extern crate url;
 
@@ -42,11 +43,18 @@ pub use compositor_task::{CompositorEventListener, CompositorProxy, CompositorTa
 pub use constellation::Constellation;
 
 pub mod compositor_task;
+pub mod content_process;
 
 mod compositor_layer;
 mod compositor;
+mod content_channels;
+mod damage;
+mod gamepad;
 mod headless;
+mod highlight;
+mod scrollbars;
 mod scrolling;
+mod vsync;
 
 pub mod pipeline;
 pub mod constellation;
@@ -3,6 +3,7 @@
  * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
 
 use CompositorProxy;
+use content_process::ContentProcess;
 use layout_traits::{LayoutControlMsg, LayoutTaskFactory, LayoutControlChan};
 use script_traits::{ScriptControlChan, ScriptTaskFactory};
 use script_traits::{NewLayoutInfo, ConstellationControlMsg};
@@ -18,9 +19,10 @@ use msg::constellation_msg::{ConstellationChan, Failure, FrameId, PipelineId, Su
 use msg::constellation_msg::{LoadData, WindowSizeData, PipelineExitType, MozBrowserEvent};
 use profile_traits::mem;
 use profile_traits::time;
-use net_traits::ResourceTask;
+use net_traits::{ResourceTask, serialize_origin};
 use net_traits::image_cache_task::ImageCacheTask;
 use net_traits::storage_task::StorageTask;
+use std::rc::Rc;
 use std::sync::mpsc::{Receiver, channel};
 use url::Url;
 use util::geometry::{PagePx, ViewportPx};
@@ -38,6 +40,9 @@ pub struct Pipeline {
     pub paint_shutdown_port: Receiver<()>,
     /// URL corresponding to the most recently-loaded page.
     pub url: Url,
+    /// The serialized origin of the page. An about:blank page has the origin of the page that
+    /// created it.
+    pub origin: String,
     /// The title of the most recently-loaded page.
     pub title: Option<String>,
     pub rect: Option<TypedRect<PagePx, f32>>,
@@ -45,6 +50,9 @@ pub struct Pipeline {
     /// animations cause composites to be continually scheduled.
     pub running_animations: bool,
     pub children: Vec<FrameId>,
+    /// The content process that runs the script and layout tasks of this pipeline, if they
+    /// don't run in this one.
+    pub content_process: Option<Rc<ContentProcess>>,
 }
 
 /// The subset of the pipeline that is needed for layer composition.
@@ -74,64 +82,98 @@ impl Pipeline {
                            window_rect: Option<TypedRect<PagePx, f32>>,
                            script_chan: Option<ScriptControlChan>,
                            load_data: LoadData,
-                           device_pixel_ratio: ScaleFactor<ViewportPx, DevicePixel, f32>)
+                           device_pixel_ratio: ScaleFactor<ViewportPx, DevicePixel, f32>,
+                           content_process: Option<Rc<ContentProcess>>)
                            -> Pipeline
                            where LTF: LayoutTaskFactory, STF:ScriptTaskFactory {
-        let layout_pair = ScriptTaskFactory::create_layout_channel(None::<&mut STF>);
         let (paint_port, paint_chan) = PaintChan::new();
         let (paint_shutdown_chan, paint_shutdown_port) = channel();
         let (layout_shutdown_chan, layout_shutdown_port) = channel();
-        let (pipeline_chan, pipeline_port) = channel();
 
         let failure = Failure {
             pipeline_id: id,
             parent_info: parent_info,
         };
 
-        let script_chan = match script_chan {
+        let window_size = window_rect.map(|rect| {
+            WindowSizeData {
+                visible_viewport: rect.size,
+                initial_viewport: rect.size * ScaleFactor::new(1.0),
+                device_pixel_ratio: device_pixel_ratio,
+            }
+        });
+
+        let (script_chan, layout_chan) = match content_process {
+            Some(ref content_process) => {
+                content_process.start_pipeline(id,
+                                               parent_info,
+                                               script_chan,
+                                               paint_chan.clone(),
+                                               layout_shutdown_chan,
+                                               window_size,
+                                               load_data.clone())
+            }
             None => {
-                let (script_chan, script_port) = channel();
+                let layout_pair = ScriptTaskFactory::create_layout_channel(None::<&mut STF>);
+                let (pipeline_chan, pipeline_port) = channel();
 
-                let window_size = window_rect.map(|rect| {
-                    WindowSizeData {
-                        visible_viewport: rect.size,
-                        initial_viewport: rect.size * ScaleFactor::new(1.0),
-                        device_pixel_ratio: device_pixel_ratio,
+                let script_chan = match script_chan {
+                    None => {
+                        let (script_chan, script_port) = channel();
+                        ScriptTaskFactory::create(None::<&mut STF>,
+                                                  id,
+                                                  parent_info,
+                                                  compositor_proxy.clone_compositor_proxy(),
+                                                  &layout_pair,
+                                                  ScriptControlChan(script_chan.clone()),
+                                                  script_port,
+                                                  constellation_chan.clone(),
+                                                  failure.clone(),
+                                                  resource_task,
+                                                  storage_task.clone(),
+                                                  image_cache_task.clone(),
+                                                  devtools_chan,
+                                                  mem_profiler_chan.clone(),
+                                                  window_size,
+                                                  load_data.clone());
+                        ScriptControlChan(script_chan)
                     }
-                });
+                    Some(script_chan) => {
+                        let (containing_pipeline_id, subpage_id) =
+                            parent_info.expect("script_pipeline != None but subpage_id == None");
+                        let new_layout_info = NewLayoutInfo {
+                            containing_pipeline_id: containing_pipeline_id,
+                            new_pipeline_id: id,
+                            subpage_id: subpage_id,
+                            layout_chan:
+                                ScriptTaskFactory::clone_layout_channel(None::<&mut STF>,
+                                                                        &layout_pair),
+                            load_data: load_data.clone(),
+                        };
 
-                ScriptTaskFactory::create(None::<&mut STF>,
+                        let ScriptControlChan(ref chan) = script_chan;
+                        chan.send(ConstellationControlMsg::AttachLayout(new_layout_info)).unwrap();
+                        script_chan.clone()
+                    }
+                };
+
+                LayoutTaskFactory::create(None::<&mut LTF>,
                                           id,
-                                          parent_info,
-                                          compositor_proxy.clone_compositor_proxy(),
-                                          &layout_pair,
-                                          ScriptControlChan(script_chan.clone()),
-                                          script_port,
+                                          load_data.url.clone(),
+                                          parent_info.is_some(),
+                                          layout_pair,
+                                          pipeline_port,
                                           constellation_chan.clone(),
                                           failure.clone(),
-                                          resource_task,
-                                          storage_task.clone(),
-                                          image_cache_task.clone(),
-                                          devtools_chan,
-                                          window_size,
-                                          load_data.clone());
-                ScriptControlChan(script_chan)
-            }
-            Some(script_chan) => {
-                let (containing_pipeline_id, subpage_id) =
-                    parent_info.expect("script_pipeline != None but subpage_id == None");
-                let new_layout_info = NewLayoutInfo {
-                    containing_pipeline_id: containing_pipeline_id,
-                    new_pipeline_id: id,
-                    subpage_id: subpage_id,
-                    layout_chan: ScriptTaskFactory::clone_layout_channel(None::<&mut STF>,
-                                                                         &layout_pair),
-                    load_data: load_data.clone(),
-                };
+                                          script_chan.clone(),
+                                          paint_chan.clone(),
+                                          image_cache_task,
+                                          font_cache_task.clone(),
+                                          time_profiler_chan.clone(),
+                                          mem_profiler_chan.clone(),
+                                          layout_shutdown_chan);
 
-                let ScriptControlChan(ref chan) = script_chan;
-                chan.send(ConstellationControlMsg::AttachLayout(new_layout_info)).unwrap();
-                script_chan.clone()
+                (script_chan, LayoutControlChan(pipeline_chan))
             }
         };
 
@@ -140,38 +182,23 @@ impl Pipeline {
                           paint_chan.clone(),
                           paint_port,
                           compositor_proxy,
-                          constellation_chan.clone(),
-                          font_cache_task.clone(),
-                          failure.clone(),
-                          time_profiler_chan.clone(),
-                          mem_profiler_chan.clone(),
+                          constellation_chan,
+                          font_cache_task,
+                          failure,
+                          time_profiler_chan,
+                          mem_profiler_chan,
                           paint_shutdown_chan);
 
-        LayoutTaskFactory::create(None::<&mut LTF>,
-                                  id,
-                                  load_data.url.clone(),
-                                  parent_info.is_some(),
-                                  layout_pair,
-                                  pipeline_port,
-                                  constellation_chan,
-                                  failure,
-                                  script_chan.clone(),
-                                  paint_chan.clone(),
-                                  image_cache_task,
-                                  font_cache_task,
-                                  time_profiler_chan,
-                                  mem_profiler_chan,
-                                  layout_shutdown_chan);
-
         Pipeline::new(id,
                       parent_info,
                       script_chan,
-                      LayoutControlChan(pipeline_chan),
+                      layout_chan,
                       paint_chan,
                       layout_shutdown_port,
                       paint_shutdown_port,
                       load_data.url,
-                      window_rect)
+                      window_rect,
+                      content_process)
     }
 
     pub fn new(id: PipelineId,
@@ -182,7 +209,8 @@ impl Pipeline {
                layout_shutdown_port: Receiver<()>,
                paint_shutdown_port: Receiver<()>,
                url: Url,
-               rect: Option<TypedRect<PagePx, f32>>)
+               rect: Option<TypedRect<PagePx, f32>>,
+               content_process: Option<Rc<ContentProcess>>)
                -> Pipeline {
         Pipeline {
             id: id,
@@ -192,11 +220,13 @@ impl Pipeline {
             paint_chan: paint_chan,
             layout_shutdown_port: layout_shutdown_port,
             paint_shutdown_port: paint_shutdown_port,
+            origin: serialize_origin(&url),
             url: url,
             title: None,
             children: vec!(),
             rect: rect,
             running_animations: false,
+            content_process: content_process,
         }
     }
 
@@ -234,15 +264,17 @@ impl Pipeline {
         let _ = script_channel.send(ConstellationControlMsg::Thaw(self.id)).unwrap();
     }
 
+    /// Tells the tasks of this pipeline to exit without waiting for them, tolerating any of them
+    /// having already crashed.
     pub fn force_exit(&self) {
         let ScriptControlChan(ref script_channel) = self.script_chan;
         let _ = script_channel.send(
             ConstellationControlMsg::ExitPipeline(self.id,
-                                                  PipelineExitType::PipelineOnly)).unwrap();
+                                                  PipelineExitType::PipelineOnly));
         let _ = self.paint_chan.send(PaintMsg::Exit(None, PipelineExitType::PipelineOnly));
         let LayoutControlChan(ref layout_channel) = self.layout_chan;
         let _ = layout_channel.send(
-            LayoutControlMsg::ExitNow(PipelineExitType::PipelineOnly)).unwrap();
+            LayoutControlMsg::ExitNow(PipelineExitType::PipelineOnly));
     }
 
     pub fn to_sendable(&self) -> CompositionPipeline {
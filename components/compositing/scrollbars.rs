@@ -0,0 +1,303 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+//! Overlay scrollbars, drawn by the compositor on top of the layers that scroll. Only the
+//! thumbs are drawn, and they fade out a little while after the last scroll, as on mobile.
+
+use compositor_layer::{CompositorData, CompositorLayer, WantsScrollEventsFlag};
+use compositor_layer::calculate_content_size_for_layer;
+
+use euclid::point::{Point2D, TypedPoint2D};
+use euclid::rect::{Rect, TypedRect};
+use euclid::scale_factor::ScaleFactor;
+use euclid::size::{Size2D, TypedSize2D};
+use gleam::gl;
+use layers::geometry::{DevicePixel, LayerPixel};
+use layers::layers::Layer;
+use msg::compositor_msg::LayerId;
+use msg::constellation_msg::PipelineId;
+use util::geometry::ScreenPx;
+
+/// The thickness of a thumb, in screen pixels.
+const THICKNESS: f32 = 6.0;
+
+/// The gap between a thumb and the edges of its layer, in screen pixels.
+const INSET: f32 = 2.0;
+
+/// The shortest a thumb gets, however long its layer's content, in screen pixels.
+const MIN_LENGTH: f32 = 20.0;
+
+/// The color of the thumbs, with premultiplied alpha, before they start to fade.
+const COLOR: [f32; 4] = [0.0, 0.0, 0.0, 0.5];
+
+/// The lines the shaders are compiled with, in the order they're tried: desktop GLSL 1.10, then
+/// GLSL ES 1.00, for OpenGL ES, whose fragment shaders have to give the precision of floats.
+static SHADER_HEADERS: [&'static str; 2] = [
+    "#version 110\n",
+    "#version 100\nprecision mediump float;\n",
+];
+
+static VERTEX_SHADER: &'static str = "
+    attribute vec2 aPosition;
+    uniform vec2 uWindowSize;
+
+    void main() {
+        vec2 position = aPosition / uWindowSize * 2.0 - 1.0;
+        gl_Position = vec4(position.x, -position.y, 0.0, 1.0);
+    }
+";
+
+static FRAGMENT_SHADER: &'static str = "
+    uniform vec4 uColor;
+
+    void main() {
+        gl_FragColor = uColor;
+    }
+";
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Axis {
+    Horizontal,
+    Vertical,
+}
+
+/// The thumb of one of a layer's scrollbars.
+#[derive(Clone, Copy)]
+pub struct Thumb {
+    /// The pipeline of the layer that the thumb scrolls.
+    pub pipeline_id: PipelineId,
+
+    /// The ID of the layer that the thumb scrolls.
+    pub layer_id: LayerId,
+
+    /// The direction the thumb moves in.
+    pub axis: Axis,
+
+    /// Where the thumb is drawn in the window.
+    pub rect: TypedRect<DevicePixel, f32>,
+
+    /// How far the layer scrolls each time the thumb is dragged by a device pixel.
+    pub scroll_per_device_pixel: f32,
+}
+
+/// Finds the thumbs of every layer that can be scrolled, parents before their children, so that
+/// the last thumb is the one on top.
+pub fn thumbs_for_layer_tree(root: &Layer<CompositorData>,
+                             scale: ScaleFactor<LayerPixel, DevicePixel, f32>,
+                             hidpi_factor: ScaleFactor<ScreenPx, DevicePixel, f32>)
+                             -> Vec<Thumb> {
+    let mut thumbs = vec!();
+    add_thumbs_for_layer(root, Point2D::zero(), scale.get(), hidpi_factor.get(), &mut thumbs);
+    thumbs
+}
+
+fn add_thumbs_for_layer(layer: &Layer<CompositorData>,
+                        origin: TypedPoint2D<LayerPixel, f32>,
+                        scale: f32,
+                        hidpi_factor: f32,
+                        thumbs: &mut Vec<Thumb>) {
+    let bounds = *layer.bounds.borrow();
+
+    // Content offsets are already the sum of every scroll above the layer, so only the origins
+    // of its ancestors need adding up.
+    if layer.wants_scroll_events() == WantsScrollEventsFlag::WantsScrollEvents {
+        let layer_rect = bounds.translate(&(origin + *layer.content_offset.borrow()));
+        let layer_rect = Rect::new(Point2D::new(layer_rect.origin.x.get() * scale,
+                                                layer_rect.origin.y.get() * scale),
+                                   Size2D::new(layer_rect.size.width.get() * scale,
+                                               layer_rect.size.height.get() * scale));
+        let content_size = calculate_content_size_for_layer(layer);
+        let content_size = Size2D::new(content_size.width.get(), content_size.height.get());
+        let layer_size = bounds.size.to_untyped();
+        let scroll_offset = layer.extra_data.borrow().scroll_offset.to_untyped();
+
+        let scrolls_horizontally = content_size.width > layer_size.width;
+        let scrolls_vertically = content_size.height > layer_size.height;
+        let thickness = THICKNESS * hidpi_factor;
+        let inset = INSET * hidpi_factor;
+        // Leave the corner free when there are two scrollbars.
+        let corner = if scrolls_horizontally && scrolls_vertically {
+            thickness + inset
+        } else {
+            0.0
+        };
+
+        let mut add_thumb = |axis: Axis,
+                             track_start: f32,
+                             track_length: f32,
+                             layer_length: f32,
+                             content_length: f32,
+                             offset: f32| {
+            let track_length = track_length - inset * 2.0 - corner;
+            let length = (track_length * layer_length / content_length)
+                .max(MIN_LENGTH * hidpi_factor);
+            if length >= track_length {
+                return
+            }
+            let scroll_range = content_length - layer_length;
+            let position = track_start + inset + (track_length - length) * -offset / scroll_range;
+            let rect = match axis {
+                Axis::Horizontal => {
+                    Rect::new(Point2D::new(position,
+                                           layer_rect.max_y() - inset - thickness),
+                              Size2D::new(length, thickness))
+                }
+                Axis::Vertical => {
+                    Rect::new(Point2D::new(layer_rect.max_x() - inset - thickness,
+                                           position),
+                              Size2D::new(thickness, length))
+                }
+            };
+            thumbs.push(Thumb {
+                pipeline_id: layer.pipeline_id(),
+                layer_id: layer.extra_data.borrow().id,
+                axis: axis,
+                rect: Rect::from_untyped(&rect),
+                scroll_per_device_pixel: scroll_range / (track_length - length),
+            });
+        };
+
+        if scrolls_horizontally {
+            add_thumb(Axis::Horizontal,
+                      layer_rect.origin.x,
+                      layer_rect.size.width,
+                      layer_size.width,
+                      content_size.width,
+                      scroll_offset.x);
+        }
+        if scrolls_vertically {
+            add_thumb(Axis::Vertical,
+                      layer_rect.origin.y,
+                      layer_rect.size.height,
+                      layer_size.height,
+                      content_size.height,
+                      scroll_offset.y);
+        }
+    }
+
+    let origin_for_children = origin + bounds.origin;
+    for child in layer.children().iter() {
+        add_thumbs_for_layer(&*child, origin_for_children, scale, hidpi_factor, thumbs);
+    }
+}
+
+/// Draws thumbs over the composited layers.
+pub struct ScrollbarRenderer {
+    program: gl::GLuint,
+    position_attribute: gl::GLint,
+    window_size_uniform: gl::GLint,
+    color_uniform: gl::GLint,
+    vertex_buffer: gl::GLuint,
+}
+
+impl ScrollbarRenderer {
+    /// Compiles the shaders, for whichever version of GLSL the compositor's GL context takes.
+    /// The context must be current. Returns `None` if the shaders can't be compiled, in which
+    /// case the compositor draws no scrollbars.
+    pub fn new() -> Option<ScrollbarRenderer> {
+        let program = match SHADER_HEADERS.iter().filter_map(|header| link_program(header)).next() {
+            Some(program) => program,
+            None => {
+                warn!("failed to compile the scrollbar shaders; scrollbars won't be drawn");
+                return None
+            }
+        };
+
+        Some(ScrollbarRenderer {
+            program: program,
+            position_attribute: gl::get_attrib_location(program, "aPosition"),
+            window_size_uniform: gl::get_uniform_location(program, "uWindowSize"),
+            color_uniform: gl::get_uniform_location(program, "uColor"),
+            vertex_buffer: gl::gen_buffers(1)[0],
+        })
+    }
+
+    /// Draws `thumbs` at `opacity`, between 0 for invisible and 1 for fully shown.
+    pub fn draw(&self,
+                thumbs: &[Thumb],
+                window_size: TypedSize2D<DevicePixel, u32>,
+                opacity: f32) {
+        if thumbs.is_empty() || opacity <= 0.0 {
+            return
+        }
+        let rects: Vec<_> = thumbs.iter().map(|thumb| thumb.rect).collect();
+        let color = [
+            COLOR[0] * opacity,
+            COLOR[1] * opacity,
+            COLOR[2] * opacity,
+            COLOR[3] * opacity,
+        ];
+        self.fill_rects(&rects, color, window_size);
+    }
+
+    /// Fills `rects` with `color`, which has premultiplied alpha. The highlight of the node being
+    /// inspected is drawn with this too.
+    pub fn fill_rects(&self,
+                      rects: &[TypedRect<DevicePixel, f32>],
+                      color: [f32; 4],
+                      window_size: TypedSize2D<DevicePixel, u32>) {
+        let (width, height) = (window_size.width.get(), window_size.height.get());
+        gl::viewport(0, 0, width as gl::GLsizei, height as gl::GLsizei);
+        gl::enable(gl::BLEND);
+        gl::blend_func(gl::ONE, gl::ONE_MINUS_SRC_ALPHA);
+
+        gl::use_program(self.program);
+        gl::uniform_2f(self.window_size_uniform, width as f32, height as f32);
+        gl::uniform_4f(self.color_uniform, color[0], color[1], color[2], color[3]);
+
+        let position_attribute = self.position_attribute as gl::GLuint;
+        gl::bind_buffer(gl::ARRAY_BUFFER, self.vertex_buffer);
+        gl::enable_vertex_attrib_array(position_attribute);
+        for rect in rects.iter() {
+            let rect = rect.to_untyped();
+            let vertices = [
+                rect.origin.x, rect.origin.y,
+                rect.max_x(), rect.origin.y,
+                rect.origin.x, rect.max_y(),
+                rect.max_x(), rect.max_y(),
+            ];
+            gl::buffer_data(gl::ARRAY_BUFFER, &vertices, gl::STREAM_DRAW);
+            gl::vertex_attrib_pointer_f32(position_attribute, 2, false, 0, 0);
+            gl::draw_arrays(gl::TRIANGLE_STRIP, 0, 4);
+        }
+        gl::disable_vertex_attrib_array(position_attribute);
+    }
+}
+
+/// Finds the thumb under `point`, if any, looking at the ones drawn on top first.
+pub fn thumb_at_point(thumbs: &[Thumb], point: TypedPoint2D<DevicePixel, f32>) -> Option<Thumb> {
+    thumbs.iter().rev().find(|thumb| thumb.rect.contains(&point)).map(|thumb| *thumb)
+}
+
+/// Compiles and links the shaders, each starting with `header`.
+fn link_program(header: &str) -> Option<gl::GLuint> {
+    let program = gl::create_program();
+    for &(shader_type, source) in [(gl::VERTEX_SHADER, VERTEX_SHADER),
+                                   (gl::FRAGMENT_SHADER, FRAGMENT_SHADER)].iter() {
+        let shader = gl::create_shader(shader_type);
+        gl::shader_source(shader, &[header.as_bytes(), source.as_bytes()]);
+        gl::compile_shader(shader);
+        let compiled = gl::get_shader_iv(shader, gl::COMPILE_STATUS) != (0 as gl::GLint);
+        if compiled {
+            gl::attach_shader(program, shader);
+        } else {
+            debug!("failed to compile scrollbar shader with {:?}: {}",
+                   header,
+                   gl::get_shader_info_log(shader));
+        }
+        // The program keeps the shaders it has attached until it is deleted.
+        gl::delete_shader(shader);
+        if !compiled {
+            gl::delete_program(program);
+            return None
+        }
+    }
+    gl::link_program(program);
+    if gl::get_program_iv(program, gl::LINK_STATUS) == (0 as gl::GLint) {
+        debug!("failed to link scrollbar shaders with {:?}", header);
+        gl::delete_program(program);
+        return None
+    }
+    Some(program)
+}
@@ -2,11 +2,13 @@
  * License, v. 2.0. If a copy of the MPL was not distributed with this
  * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
 
-//! A timer thread that gives the painting task a little time to catch up when the user scrolls.
+//! A timer thread that gives the painting task a little time to catch up when the user scrolls,
+//! and spaces out the reflows the compositor asks for while the window is being resized. A
+//! second thread wakes the compositor when it is time for the scrollbars to fade out.
 
 use compositor_task::{CompositorProxy, Msg};
 
-use std::sync::mpsc::{Receiver, Sender, channel};
+use std::sync::mpsc::{Receiver, Sender, TryRecvError, channel};
 use std::thread::{Builder, sleep_ms};
 use time;
 
@@ -15,8 +17,16 @@ use time;
 /// and composite anyway (showing a "checkerboard") to avoid dropping the frame.
 static TIMEOUT: i64 = 12_000_000;
 
+/// The least amount of time in nanoseconds between the reflows the compositor asks for while the
+/// window is being resized. In between, the compositor scales the page it already has.
+pub const RESIZE_REFLOW_INTERVAL: u64 = 100_000_000;
+
+/// How long the scrollbars stay fully shown after a scroll, in nanoseconds.
+pub const SCROLLBAR_FADE_DELAY: u64 = 1_000_000_000;
+
 pub struct ScrollingTimerProxy {
     sender: Sender<ToScrollingTimerMsg>,
+    fade_sender: Sender<u64>,
 }
 
 pub struct ScrollingTimer {
@@ -27,10 +37,17 @@ pub struct ScrollingTimer {
 enum ToScrollingTimerMsg {
     ExitMsg,
     ScrollEventProcessedMsg(u64),
+    ResizeReflowSentMsg(u64),
 }
 
 impl ScrollingTimerProxy {
     pub fn new(compositor_proxy: Box<CompositorProxy+Send>) -> ScrollingTimerProxy {
+        let (fade_sender, fade_receiver) = channel();
+        let fade_compositor_proxy = compositor_proxy.clone_compositor_proxy();
+        Builder::new().spawn(move || {
+            run_scrollbar_fade_timer(fade_compositor_proxy, fade_receiver);
+        }).unwrap();
+
         let (to_scrolling_timer_sender, to_scrolling_timer_receiver) = channel();
         Builder::new().spawn(move || {
             let mut scrolling_timer = ScrollingTimer {
@@ -41,6 +58,7 @@ impl ScrollingTimerProxy {
         }).unwrap();
         ScrollingTimerProxy {
             sender: to_scrolling_timer_sender,
+            fade_sender: fade_sender,
         }
     }
 
@@ -48,6 +66,18 @@ impl ScrollingTimerProxy {
         self.sender.send(ToScrollingTimerMsg::ScrollEventProcessedMsg(timestamp)).unwrap()
     }
 
+    /// Asks for a `ResizeTimeout` once it is time for another reflow after the one asked for at
+    /// `timestamp`.
+    pub fn resize_reflow_sent(&mut self, timestamp: u64) {
+        self.sender.send(ToScrollingTimerMsg::ResizeReflowSentMsg(timestamp)).unwrap()
+    }
+
+    /// Asks for a `ScrollbarFadeTimeout` once the scrollbars shown by the scroll at `timestamp`
+    /// are due to start fading out. A later scroll puts the timeout off.
+    pub fn scrollbars_shown(&mut self, timestamp: u64) {
+        self.fade_sender.send(timestamp).unwrap()
+    }
+
     pub fn shutdown(&mut self) {
         self.sender.send(ToScrollingTimerMsg::ExitMsg).unwrap()
     }
@@ -63,9 +93,46 @@ impl ScrollingTimer {
                     sleep_ms((delta_ns / 1000000) as u32);
                     self.compositor_proxy.send(Msg::ScrollTimeout(timestamp));
                 }
+                Ok(ToScrollingTimerMsg::ResizeReflowSentMsg(timestamp)) => {
+                    let target = timestamp + RESIZE_REFLOW_INTERVAL;
+                    let now = time::precise_time_ns();
+                    if target > now {
+                        sleep_ms(((target - now) / 1000000) as u32);
+                    }
+                    self.compositor_proxy.send(Msg::ResizeTimeout);
+                }
                 Ok(ToScrollingTimerMsg::ExitMsg) | Err(_) => break,
             }
         }
     }
 }
 
+
+/// Waits until the scrollbars are due to fade, for as long as scrolls keep putting that off. The
+/// thread exits once the compositor has dropped its proxy.
+fn run_scrollbar_fade_timer(compositor_proxy: Box<CompositorProxy+Send>, receiver: Receiver<u64>) {
+    while let Ok(mut timestamp) = receiver.recv() {
+        loop {
+            let target = timestamp + SCROLLBAR_FADE_DELAY;
+            let now = time::precise_time_ns();
+            if target > now {
+                sleep_ms(((target - now) / 1000000) as u32 + 1);
+            }
+            let mut put_off = false;
+            loop {
+                match receiver.try_recv() {
+                    Ok(later) => {
+                        timestamp = later;
+                        put_off = true;
+                    }
+                    Err(TryRecvError::Empty) => break,
+                    Err(TryRecvError::Disconnected) => return,
+                }
+            }
+            if !put_off {
+                break
+            }
+        }
+        compositor_proxy.send(Msg::ScrollbarFadeTimeout);
+    }
+}
@@ -0,0 +1,104 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+//! A timer thread that wakes the compositor up at the display's vertical blanks, so that it
+//! composites at most once per frame, and only in frames in which something asked for it.
+//!
+//! The vertical blanks come from the window's `VblankSource` where the platform reports them.
+//! Elsewhere they are estimated by counting refresh intervals from the last composite, which
+//! keeps frames evenly spaced but not in phase with the display.
+
+use compositor_task::{CompositorProxy, Msg};
+use windowing::VblankSource;
+
+use std::sync::mpsc::{Receiver, Sender, channel};
+use std::thread::{Builder, sleep_ms};
+use time;
+
+pub struct VsyncTimerProxy {
+    sender: Sender<ToVsyncTimerMsg>,
+}
+
+pub struct VsyncTimer {
+    compositor_proxy: Box<CompositorProxy>,
+    receiver: Receiver<ToVsyncTimerMsg>,
+    /// The time between vertical blanks, in nanoseconds.
+    interval: u64,
+    /// Where the vertical blanks are waited for, if the platform reports them.
+    vblank_source: Option<Box<VblankSource>>,
+}
+
+enum ToVsyncTimerMsg {
+    ExitMsg,
+    FrameRequestedMsg(u64),
+}
+
+impl VsyncTimerProxy {
+    pub fn new(compositor_proxy: Box<CompositorProxy+Send>,
+               interval: u64,
+               vblank_source: Option<Box<VblankSource>>)
+               -> VsyncTimerProxy {
+        let (to_vsync_timer_sender, to_vsync_timer_receiver) = channel();
+        Builder::new().spawn(move || {
+            let mut vsync_timer = VsyncTimer {
+                compositor_proxy: compositor_proxy,
+                receiver: to_vsync_timer_receiver,
+                interval: interval,
+                vblank_source: vblank_source,
+            };
+            vsync_timer.run();
+        }).unwrap();
+        VsyncTimerProxy {
+            sender: to_vsync_timer_sender,
+        }
+    }
+
+    /// Asks for a `Vsync` at the next vertical blank. `last_vblank` is the time of a known
+    /// vertical blank, such as when the last frame was presented, which the frames are lined up
+    /// with.
+    pub fn request_frame(&mut self, last_vblank: u64) {
+        self.sender.send(ToVsyncTimerMsg::FrameRequestedMsg(last_vblank)).unwrap()
+    }
+
+    pub fn shutdown(&mut self) {
+        self.sender.send(ToVsyncTimerMsg::ExitMsg).unwrap()
+    }
+}
+
+impl VsyncTimer {
+    pub fn run(&mut self) {
+        loop {
+            match self.receiver.recv() {
+                Ok(ToVsyncTimerMsg::FrameRequestedMsg(last_vblank)) => {
+                    let vblank = self.wait_for_vblank(last_vblank);
+                    self.compositor_proxy.send(Msg::Vsync(vblank));
+                }
+                Ok(ToVsyncTimerMsg::ExitMsg) | Err(_) => break,
+            }
+        }
+    }
+
+    /// Waits for the next vertical blank, and returns its time.
+    fn wait_for_vblank(&mut self, last_vblank: u64) -> u64 {
+        if let Some(vblank) = self.vblank_source.as_mut().and_then(|source| {
+            source.wait_for_vblank()
+        }) {
+            return vblank
+        }
+        self.vblank_source = None;
+
+        let now = time::precise_time_ns();
+        let target = next_vblank(last_vblank, now, self.interval);
+        sleep_ms(((target - now) / 1000000) as u32);
+        target
+    }
+}
+
+/// Returns the first vertical blank after `now`, given that there was one at `last_vblank`.
+fn next_vblank(last_vblank: u64, now: u64, interval: u64) -> u64 {
+    if interval == 0 || now < last_vblank {
+        return now
+    }
+    last_vblank + ((now - last_vblank) / interval + 1) * interval
+}
@@ -7,18 +7,32 @@
 use compositor_task::{CompositorProxy, CompositorReceiver};
 
 use euclid::point::TypedPoint2D;
+use euclid::rect::TypedRect;
 use euclid::scale_factor::ScaleFactor;
 use euclid::size::TypedSize2D;
+use gfx::pdf::PaperSize;
 use layers::geometry::DevicePixel;
 use layers::platform::surface::NativeDisplay;
-use msg::constellation_msg::{Key, KeyState, KeyModifiers};
+use msg::constellation_msg::{ImeEvent, Key, KeyState, KeyModifiers};
+use msg::constellation_msg::{AuthenticationRequest, CertificateError, Credentials};
+use msg::constellation_msg::{GeolocationPosition, MixedContentState, NotificationData};
+use msg::constellation_msg::{ConstellationChan, NotificationEventType, NotificationId};
+use msg::constellation_msg::{PermissionName, PipelineId};
+use msg::constellation_msg::Msg as ConstellationMsg;
 use net::net_error_list::NetError;
+use net_traits::image::base::Image;
 use script_traits::MouseButton;
 use url::Url;
 use util::cursor::Cursor;
 use util::geometry::ScreenPx;
 use std::fmt::{Error, Formatter, Debug};
 use std::rc::Rc;
+use std::sync::Arc;
+use std::sync::mpsc::Sender;
+
+/// The time between vertical blanks, in nanoseconds, of a 60Hz display. Windows that can't find
+/// out the refresh rate of their display assume this one.
+pub const DEFAULT_REFRESH_INTERVAL: u64 = 16_666_667;
 
 #[derive(Clone)]
 pub enum MouseWindowEvent {
@@ -49,6 +63,14 @@ pub enum WindowEvent {
     /// Sent to initialize the GL context. The windowing system must have a valid, current GL
     /// context when this message is sent.
     InitializeCompositing,
+    /// Sent before the window's rendering surface is destroyed, as when an Android activity is
+    /// paused. The GL context may be lost along with it, so nothing is composited until
+    /// `Resume`.
+    Suspend,
+    /// Sent once the window has a rendering surface again. The windowing system must have a
+    /// valid, current GL context when this message is sent, which may not be the one compositing
+    /// was initialized with.
+    Resume,
     /// Sent when the window is resized.
     Resize(TypedSize2D<DevicePixel, u32>),
     /// Sent when a new URL is to be loaded.
@@ -62,8 +84,11 @@ pub enum WindowEvent {
     Scroll(TypedPoint2D<DevicePixel, f32>, TypedPoint2D<DevicePixel, i32>),
     /// Sent when the user zooms.
     Zoom(f32),
-    /// Simulated "pinch zoom" gesture for non-touch platforms (e.g. ctrl-scrollwheel).
-    PinchZoom(f32),
+    /// Sent as the user pinches, or simulates a pinch on non-touch platforms (e.g. with
+    /// ctrl-scrollwheel). The page is magnified around the given point.
+    PinchZoom(f32, TypedPoint2D<DevicePixel, f32>),
+    /// Sent when a pinch ends, so that the page can be painted again at its new scale.
+    PinchZoomEnd,
     /// Sent when the user resets zoom to default.
     ResetZoom,
     /// Sent when the user uses chrome navigation (i.e. backspace or shift-backspace).
@@ -72,6 +97,19 @@ pub enum WindowEvent {
     Quit,
     /// Sent when a key input state changes
     KeyEvent(Key, KeyState, KeyModifiers),
+    /// Sent when the input method editor composes text, or asks where the caret is.
+    ImeEvent(ImeEvent),
+    /// Sent when the user interacts with a notification shown by `show_notification`.
+    NotificationEvent(PipelineId, NotificationId, NotificationEventType),
+    /// Sent to load the secure document of the given pipeline again, letting it load the
+    /// insecure content that was blocked.
+    AllowMixedContent(PipelineId),
+    /// Sent to save an image of the page as a PNG file at the given path. The image is taken
+    /// once the page has finished loading and laying out.
+    SaveScreenshot(String),
+    /// Sent to print the page to a PDF file at the given path, on paper of the given size. The
+    /// page is printed once it has finished loading and laying out.
+    PrintToPdf(String, PaperSize),
 }
 
 impl Debug for WindowEvent {
@@ -80,21 +118,170 @@ impl Debug for WindowEvent {
             WindowEvent::Idle => write!(f, "Idle"),
             WindowEvent::Refresh => write!(f, "Refresh"),
             WindowEvent::InitializeCompositing => write!(f, "InitializeCompositing"),
+            WindowEvent::Suspend => write!(f, "Suspend"),
+            WindowEvent::Resume => write!(f, "Resume"),
             WindowEvent::Resize(..) => write!(f, "Resize"),
             WindowEvent::KeyEvent(..) => write!(f, "Key"),
+            WindowEvent::ImeEvent(..) => write!(f, "ImeEvent"),
             WindowEvent::LoadUrl(..) => write!(f, "LoadUrl"),
             WindowEvent::MouseWindowEventClass(..) => write!(f, "Mouse"),
             WindowEvent::MouseWindowMoveEventClass(..) => write!(f, "MouseMove"),
             WindowEvent::Scroll(..) => write!(f, "Scroll"),
             WindowEvent::Zoom(..) => write!(f, "Zoom"),
             WindowEvent::PinchZoom(..) => write!(f, "PinchZoom"),
+            WindowEvent::PinchZoomEnd => write!(f, "PinchZoomEnd"),
             WindowEvent::ResetZoom => write!(f, "ResetZoom"),
             WindowEvent::Navigation(..) => write!(f, "Navigation"),
             WindowEvent::Quit => write!(f, "Quit"),
+            WindowEvent::NotificationEvent(..) => write!(f, "NotificationEvent"),
+            WindowEvent::AllowMixedContent(..) => write!(f, "AllowMixedContent"),
+            WindowEvent::SaveScreenshot(..) => write!(f, "SaveScreenshot"),
+            WindowEvent::PrintToPdf(..) => write!(f, "PrintToPdf"),
+        }
+    }
+}
+
+/// A document's request for permission to use a feature. The answer may be given from any
+/// thread, and is remembered for the document's origin. Dropping the prompt without answering
+/// denies the permission.
+pub struct PermissionPrompt {
+    /// The feature the document wants to use.
+    pub name: PermissionName,
+    /// The URL of the document.
+    pub url: Url,
+    pipeline_id: PipelineId,
+    constellation_chan: Option<ConstellationChan>,
+}
+
+impl PermissionPrompt {
+    pub fn new(pipeline_id: PipelineId,
+               name: PermissionName,
+               url: Url,
+               constellation_chan: ConstellationChan)
+               -> PermissionPrompt {
+        PermissionPrompt {
+            name: name,
+            url: url,
+            pipeline_id: pipeline_id,
+            constellation_chan: Some(constellation_chan),
+        }
+    }
+
+    /// Gives the user's answer to the document.
+    pub fn respond(mut self, granted: bool) {
+        self.send_response(granted)
+    }
+
+    fn send_response(&mut self, granted: bool) {
+        if let Some(ConstellationChan(chan)) = self.constellation_chan.take() {
+            let msg = ConstellationMsg::PermissionResponse(self.pipeline_id,
+                                                           self.name,
+                                                           self.url.clone(),
+                                                           granted);
+            // The constellation may have shut down while the user was deciding.
+            let _ = chan.send(msg);
+        }
+    }
+}
+
+impl Drop for PermissionPrompt {
+    fn drop(&mut self) {
+        self.send_response(false)
+    }
+}
+
+/// A document's request for the current location of the device. The position may be given
+/// from any thread. Dropping the request without answering reports that no position is
+/// available.
+pub struct PositionRequest {
+    /// Whether the document asked for the best position available, such as from GPS rather
+    /// than from the network, even if it is slower or uses more power.
+    pub enable_high_accuracy: bool,
+    pipeline_id: PipelineId,
+    constellation_chan: Option<ConstellationChan>,
+}
+
+impl PositionRequest {
+    pub fn new(pipeline_id: PipelineId,
+               enable_high_accuracy: bool,
+               constellation_chan: ConstellationChan)
+               -> PositionRequest {
+        PositionRequest {
+            enable_high_accuracy: enable_high_accuracy,
+            pipeline_id: pipeline_id,
+            constellation_chan: Some(constellation_chan),
+        }
+    }
+
+    /// Gives the position to the document, or `None` if it could not be determined.
+    pub fn respond(mut self, position: Option<GeolocationPosition>) {
+        self.send_response(position)
+    }
+
+    fn send_response(&mut self, position: Option<GeolocationPosition>) {
+        if let Some(ConstellationChan(chan)) = self.constellation_chan.take() {
+            let msg = ConstellationMsg::GeolocationPositionResponse(self.pipeline_id, position);
+            // The constellation may have shut down while the position was being looked up.
+            let _ = chan.send(msg);
+        }
+    }
+}
+
+impl Drop for PositionRequest {
+    fn drop(&mut self) {
+        self.send_response(None)
+    }
+}
+
+/// A server's or proxy's request for the user's credentials. The load waits until they are
+/// given, which may be done from any thread. Dropping the prompt without answering declines,
+/// in which case the server's response is shown instead.
+pub struct CredentialsPrompt {
+    /// The page the credentials are for, if the load is one of a page's.
+    pub pipeline_id: Option<PipelineId>,
+    pub request: AuthenticationRequest,
+    sender: Option<Sender<Option<Credentials>>>,
+}
+
+impl CredentialsPrompt {
+    pub fn new(pipeline_id: Option<PipelineId>,
+               request: AuthenticationRequest,
+               sender: Sender<Option<Credentials>>)
+               -> CredentialsPrompt {
+        CredentialsPrompt {
+            pipeline_id: pipeline_id,
+            request: request,
+            sender: Some(sender),
+        }
+    }
+
+    /// Gives the user's credentials to the load, or `None` if they declined.
+    pub fn respond(mut self, credentials: Option<Credentials>) {
+        self.send_response(credentials)
+    }
+
+    fn send_response(&mut self, credentials: Option<Credentials>) {
+        if let Some(sender) = self.sender.take() {
+            // The load may have been cancelled while the user was deciding.
+            let _ = sender.send(credentials);
         }
     }
 }
 
+impl Drop for CredentialsPrompt {
+    fn drop(&mut self) {
+        self.send_response(None)
+    }
+}
+
+/// Reports the display's vertical blanks to the compositor's vsync timer, on a thread of its own.
+pub trait VblankSource: Send {
+    /// Waits for the next vertical blank, and returns its time in nanoseconds, on the clock that
+    /// `precise_time_ns` reads. Returns `None` once vertical blanks can't be reported any more,
+    /// after which the timer counts refresh intervals instead.
+    fn wait_for_vblank(&mut self) -> Option<u64>;
+}
+
 pub trait WindowMethods {
     /// Returns the size of the window in hardware pixels.
     fn framebuffer_size(&self) -> TypedSize2D<DevicePixel, u32>;
@@ -102,6 +289,18 @@ pub trait WindowMethods {
     fn size(&self) -> TypedSize2D<ScreenPx, f32>;
     /// Presents the window to the screen (perhaps by page flipping).
     fn present(&self);
+    /// Returns the time between the display's vertical blanks, in nanoseconds.
+    fn refresh_interval(&self) -> u64;
+    /// Returns a source of the display's vertical blanks, if the platform reports them.
+    /// Otherwise, frames are timed by counting refresh intervals from the last composite.
+    fn vblank_source(&self) -> Option<Box<VblankSource>>;
+    /// Returns true if the contents of the window's back buffer are kept from one frame to the
+    /// next, so that only the parts of the window that have changed need to be drawn again.
+    fn supports_partial_present(&self) -> bool;
+    /// Presents the window to the screen, when only `damage`, in device pixels from the top
+    /// left, has been drawn since the last frame. Windows with EGL_KHR_swap_buffers_with_damage
+    /// can pass this on to the display. Only called if `supports_partial_present` returns true.
+    fn present_damage(&self, damage: TypedRect<DevicePixel, u32>);
 
     /// Sets the page title for the current page.
     fn set_page_title(&self, title: Option<String>);
@@ -141,9 +340,51 @@ pub trait WindowMethods {
     /// Process a key event.
     fn handle_key(&self, key: Key, mods: KeyModifiers);
 
+    /// Tells the input method editor where the caret of the focused text field is, in device
+    /// pixels from the top left of the window, so that it can show its candidate window next
+    /// to the text being composed.
+    fn set_ime_caret_rect(&self, rect: TypedRect<DevicePixel, f32>);
+
     /// Does this window support a clipboard
     fn supports_clipboard(&self) -> bool;
 
     /// Add a favicon
     fn set_favicon(&self, url: Url);
+
+    /// Shows the icon of the current page, once it has been loaded and decoded. The image is
+    /// premultiplied BGRA, and may need scaling to the size the embedder shows icons at.
+    fn set_favicon_image(&self, image: Arc<Image>);
+
+    /// Asks the user whether a document may use a feature. The window should answer through
+    /// `PermissionPrompt::respond` once the user has decided, without waiting for them here.
+    fn request_permission(&self, prompt: PermissionPrompt);
+
+    /// Displays a notification. The user's interactions with it should be reported back
+    /// through `WindowEvent::NotificationEvent`.
+    fn show_notification(&self,
+                         pipeline_id: PipelineId,
+                         id: NotificationId,
+                         notification: NotificationData);
+
+    /// Stops displaying a notification.
+    fn close_notification(&self, pipeline_id: PipelineId, id: NotificationId);
+
+    /// Looks up the current location of the device with the platform's location service. The
+    /// window should answer through `PositionRequest::respond` once it is known, without
+    /// waiting for it here.
+    fn request_position(&self, request: PositionRequest);
+
+    /// Called when a secure document requests content at an insecure URL. To let a page load
+    /// blocked content, send `WindowEvent::AllowMixedContent`.
+    fn mixed_content(&self, pipeline_id: PipelineId, url: Url, state: MixedContentState);
+
+    /// Called when a page was replaced by an error page because its server's certificate
+    /// couldn't be verified. Returns whether to trust the host anyway, in which case the
+    /// page is loaded again.
+    fn certificate_error(&self, pipeline_id: PipelineId, error: &CertificateError) -> bool;
+
+    /// Asks the user for a user name and password to give a server or proxy. Implementations
+    /// call `CredentialsPrompt::respond` once the user has answered, without waiting for them
+    /// here.
+    fn request_credentials(&self, prompt: CredentialsPrompt);
 }
@@ -5,17 +5,21 @@
 //! Liberally derived from the [Firefox JS implementation]
 //! (http://mxr.mozilla.org/mozilla-central/source/toolkit/devtools/server/actors/inspector.js).
 
-use devtools_traits::{DevtoolScriptControlMsg, NodeInfo};
+use devtools_traits::{AppliedRule, DevtoolScriptControlMsg, NodeInfo, NodeMutation};
 use devtools_traits::DevtoolScriptControlMsg::{GetRootNode, GetDocumentElement, GetChildren};
-use devtools_traits::DevtoolScriptControlMsg::{GetLayout, ModifyAttribute};
+use devtools_traits::DevtoolScriptControlMsg::GetAppliedRules;
+use devtools_traits::DevtoolScriptControlMsg::{GetLayout, ModifyAttribute, SetNodeValue};
+use devtools_traits::DevtoolScriptControlMsg::{HighlightNode, WantsMutationNotifications};
 
 use actor::{Actor, ActorRegistry};
 use protocol::JsonPacketStream;
 
-use std::collections::BTreeMap;
+use std::borrow::ToOwned;
+use std::collections::{BTreeMap, HashMap};
 use msg::constellation_msg::PipelineId;
 use rustc_serialize::json::{self, Json, ToJson};
 use std::cell::RefCell;
+use std::mem;
 use std::net::TcpStream;
 use std::sync::mpsc::{channel, Sender};
 
@@ -41,6 +45,8 @@ struct HighlighterMsg {
 
 struct HighlighterActor {
     name: String,
+    script_chan: Sender<DevtoolScriptControlMsg>,
+    pipeline: PipelineId,
 }
 
 pub struct NodeActor {
@@ -65,12 +71,15 @@ impl Actor for HighlighterActor {
     }
 
     fn handle_message(&self,
-                      _registry: &ActorRegistry,
+                      registry: &ActorRegistry,
                       msg_type: &str,
-                      _msg: &json::Object,
+                      msg: &json::Object,
                       stream: &mut TcpStream) -> Result<bool, ()> {
         Ok(match msg_type {
             "showBoxModel" => {
+                let target = msg.get(&"node".to_string()).unwrap().as_string().unwrap();
+                let node = registry.actor_to_script(target.to_string());
+                self.script_chan.send(HighlightNode(self.pipeline, Some(node))).unwrap();
                 let msg = ShowBoxModelReply {
                     from: self.name(),
                 };
@@ -79,6 +88,7 @@ impl Actor for HighlighterActor {
             }
 
             "hideBoxModel" => {
+                self.script_chan.send(HighlightNode(self.pipeline, None)).unwrap();
                 let msg = HideBoxModelReply {
                     from: self.name(),
                 };
@@ -96,6 +106,11 @@ struct ModifyAttributeReply{
     from: String,
 }
 
+#[derive(RustcEncodable)]
+struct SetNodeValueReply {
+    from: String,
+}
+
 impl Actor for NodeActor {
     fn name(&self) -> String {
         self.name.clone()
@@ -125,6 +140,20 @@ impl Actor for NodeActor {
                 true
             }
 
+            "setNodeValue" => {
+                let target = msg.get(&"to".to_string()).unwrap().as_string().unwrap();
+                let value = msg.get(&"value".to_string()).unwrap().as_string().unwrap();
+                self.script_chan.send(SetNodeValue(self.pipeline,
+                                                   registry.actor_to_script(target.to_string()),
+                                                   value.to_string()))
+                                .unwrap();
+                let reply = SetNodeValueReply {
+                    from: self.name(),
+                };
+                stream.write_json_packet(&reply);
+                true
+            }
+
             _ => false,
         })
     }
@@ -240,10 +269,68 @@ impl NodeInfoToProtocol for NodeInfo {
     }
 }
 
-struct WalkerActor {
+pub struct WalkerActor {
     name: String,
     script_chan: Sender<DevtoolScriptControlMsg>,
     pipeline: PipelineId,
+    /// The clients to tell when there are new mutations to fetch.
+    streams: RefCell<Vec<TcpStream>>,
+    /// Changes to nodes the clients know about, waiting for them to ask with `getMutations`.
+    pending_mutations: RefCell<Vec<Json>>,
+}
+
+#[derive(RustcEncodable)]
+struct NewMutationsMsg {
+    from: String,
+    __type__: String,
+}
+
+#[derive(RustcEncodable)]
+struct GetMutationsReply {
+    from: String,
+    mutations: Vec<Json>,
+}
+
+impl WalkerActor {
+    /// Queues a change to the DOM for the clients, letting them know if it is the first one since
+    /// they last asked. Changes to nodes they have not been sent are dropped.
+    pub fn add_mutation(&self, registry: &ActorRegistry, mutation: NodeMutation) {
+        let mut msg = BTreeMap::new();
+        let (kind, target) = match mutation {
+            NodeMutation::Attributes { target, attributeName, newValue } => {
+                msg.insert("attributeName".to_string(), attributeName.to_json());
+                msg.insert("newValue".to_string(), newValue.to_json());
+                ("attributes", target)
+            }
+            NodeMutation::CharacterData { target, newValue } => {
+                msg.insert("newValue".to_string(), newValue.to_json());
+                ("characterData", target)
+            }
+            NodeMutation::ChildList { target, numChildren } => {
+                msg.insert("added".to_string(), Json::Array(vec!()));
+                msg.insert("removed".to_string(), Json::Array(vec!()));
+                msg.insert("numChildren".to_string(), numChildren.to_json());
+                ("childList", target)
+            }
+        };
+        if !registry.script_actor_registered(target.clone()) {
+            return
+        }
+        msg.insert("type".to_string(), kind.to_string().to_json());
+        msg.insert("target".to_string(), registry.script_to_actor(target).to_json());
+
+        let mut pending_mutations = self.pending_mutations.borrow_mut();
+        pending_mutations.push(Json::Object(msg));
+        if pending_mutations.len() == 1 {
+            let msg = NewMutationsMsg {
+                from: self.name(),
+                __type__: "newMutations".to_string(),
+            };
+            for stream in self.streams.borrow_mut().iter_mut() {
+                stream.write_json_packet(&msg);
+            }
+        }
+    }
 }
 
 #[derive(RustcEncodable)]
@@ -318,11 +405,21 @@ impl Actor for WalkerActor {
                                                   registry.actor_to_script(target.to_string()),
                                                   tx))
                                 .unwrap();
-                let children = rx.recv().unwrap();
+                let mut children = rx.recv().unwrap();
+
+                // Large nodes are sent a page of children at a time.
+                let max_nodes = msg.get(&"maxNodes".to_string()).and_then(|max| max.as_u64());
+                let has_last = match max_nodes {
+                    Some(max_nodes) if children.len() > max_nodes as usize => {
+                        children.truncate(max_nodes as usize);
+                        false
+                    }
+                    _ => true,
+                };
 
                 let msg = ChildrenReply {
                     hasFirst: true,
-                    hasLast: true,
+                    hasLast: has_last,
                     nodes: children.into_iter().map(|child| {
                         child.encode(registry, true, self.script_chan.clone(), self.pipeline)
                     }).collect(),
@@ -332,6 +429,15 @@ impl Actor for WalkerActor {
                 true
             }
 
+            "getMutations" => {
+                let msg = GetMutationsReply {
+                    from: self.name(),
+                    mutations: mem::replace(&mut *self.pending_mutations.borrow_mut(), vec!()),
+                };
+                stream.write_json_packet(&msg);
+                true
+            }
+
             _ => false,
         })
     }
@@ -352,19 +458,23 @@ struct PageStyleActor {
     name: String,
     script_chan: Sender<DevtoolScriptControlMsg>,
     pipeline: PipelineId,
+    /// The actors for style sheets, keyed by URL.
+    sheet_actors: RefCell<HashMap<String, String>>,
+    /// The actors for style rules, keyed by style sheet URL, line and selectors.
+    rule_actors: RefCell<HashMap<(String, usize, String), String>>,
 }
 
 #[derive(RustcEncodable)]
 struct GetAppliedReply {
     entries: Vec<AppliedEntry>,
-    rules: Vec<AppliedRule>,
+    rules: Vec<AppliedRuleMsg>,
     sheets: Vec<AppliedSheet>,
     from: String,
 }
 
 #[derive(RustcEncodable)]
 struct GetComputedReply {
-    computed: Vec<u32>, //XXX all css props
+    computed: BTreeMap<String, Json>,
     from: String,
 }
 
@@ -377,7 +487,7 @@ struct AppliedEntry {
 }
 
 #[derive(RustcEncodable)]
-struct AppliedRule {
+struct AppliedRuleMsg {
     actor: String,
     __type__: u32,
     href: String,
@@ -385,6 +495,7 @@ struct AppliedRule {
     line: u32,
     column: u32,
     parentStyleSheet: String,
+    selectors: Vec<String>,
 }
 
 #[derive(RustcEncodable)]
@@ -399,21 +510,82 @@ struct AppliedSheet {
     ruleCount: usize,
 }
 
-#[derive(RustcEncodable)]
-struct GetLayoutReply {
-    width: i32,
-    height: i32,
-    autoMargins: Json,
-    from: String,
+/// A style sheet that rules reported to the client belong to.
+struct StyleSheetActor {
+    name: String,
 }
 
-#[derive(RustcEncodable)]
-#[allow(dead_code)]
-struct AutoMargins {
-    top: String,
-    bottom: String,
-    left: String,
-    right: String,
+impl Actor for StyleSheetActor {
+    fn name(&self) -> String {
+        self.name.clone()
+    }
+
+    fn handle_message(&self,
+                      _registry: &ActorRegistry,
+                      _msg_type: &str,
+                      _msg: &json::Object,
+                      _stream: &mut TcpStream) -> Result<bool, ()> {
+        Ok(false)
+    }
+}
+
+/// A style rule reported to the client. Rules can't be edited yet.
+struct StyleRuleActor {
+    name: String,
+}
+
+impl Actor for StyleRuleActor {
+    fn name(&self) -> String {
+        self.name.clone()
+    }
+
+    fn handle_message(&self,
+                      _registry: &ActorRegistry,
+                      _msg_type: &str,
+                      _msg: &json::Object,
+                      _stream: &mut TcpStream) -> Result<bool, ()> {
+        Ok(false)
+    }
+}
+
+/// The `type` of a style rule, from `CSSRule`.
+const STYLE_RULE: u32 = 1;
+
+impl PageStyleActor {
+    fn applied_rules(&self, registry: &ActorRegistry, msg: &json::Object) -> Vec<AppliedRule> {
+        let target = msg.get(&"node".to_string()).unwrap().as_string().unwrap();
+        let (tx, rx) = channel();
+        self.script_chan.send(GetAppliedRules(self.pipeline,
+                                              registry.actor_to_script(target.to_string()),
+                                              tx))
+                        .unwrap();
+        rx.recv().unwrap()
+    }
+
+    fn sheet_actor(&self, registry: &ActorRegistry, href: &str) -> String {
+        let mut sheet_actors = self.sheet_actors.borrow_mut();
+        sheet_actors.entry(href.to_owned()).or_insert_with(|| {
+            let actor = StyleSheetActor {
+                name: registry.new_name("stylesheet"),
+            };
+            let name = actor.name();
+            registry.register_later(box actor);
+            name
+        }).clone()
+    }
+
+    fn rule_actor(&self, registry: &ActorRegistry, rule: &AppliedRule) -> String {
+        let key = (rule.href.clone(), rule.line, rule.selectorText.clone());
+        let mut rule_actors = self.rule_actors.borrow_mut();
+        rule_actors.entry(key).or_insert_with(|| {
+            let actor = StyleRuleActor {
+                name: registry.new_name("domstylerule"),
+            };
+            let name = actor.name();
+            registry.register_later(box actor);
+            name
+        }).clone()
+    }
 }
 
 impl Actor for PageStyleActor {
@@ -428,11 +600,55 @@ impl Actor for PageStyleActor {
                       stream: &mut TcpStream) -> Result<bool, ()> {
         Ok(match msg_type {
             "getApplied" => {
-                //TODO: query script for relevant applied styles to node (msg.node)
+                let rules = self.applied_rules(registry, msg);
+
+                let mut sheets: Vec<AppliedSheet> = vec!();
+                let mut rule_msgs = vec!();
+                let mut entries = vec!();
+                // The client lists the rules that win the cascade first.
+                for rule in rules.iter().rev() {
+                    let sheet = self.sheet_actor(registry, &rule.href);
+                    if !sheets.iter().any(|applied| applied.actor == sheet) {
+                        sheets.push(AppliedSheet {
+                            actor: sheet.clone(),
+                            href: rule.href.clone(),
+                            nodeHref: rule.href.clone(),
+                            disabled: false,
+                            title: String::new(),
+                            system: rule.isSystem,
+                            styleSheetIndex: sheets.len() as isize,
+                            //TODO: count every rule of the sheet, not just the matching ones
+                            ruleCount: 0,
+                        });
+                    }
+
+                    let actor = self.rule_actor(registry, rule);
+                    let css_text = rule.declarations.iter().map(|declaration| {
+                        let priority = if declaration.important { " !important" } else { "" };
+                        format!("{}: {}{};", declaration.name, declaration.value, priority)
+                    }).collect::<Vec<_>>().connect(" ");
+                    rule_msgs.push(AppliedRuleMsg {
+                        actor: actor.clone(),
+                        __type__: STYLE_RULE,
+                        href: rule.href.clone(),
+                        cssText: css_text,
+                        line: rule.line as u32,
+                        column: 0,
+                        parentStyleSheet: sheet,
+                        selectors: vec!(rule.selectorText.clone()),
+                    });
+                    entries.push(AppliedEntry {
+                        rule: actor,
+                        pseudoElement: Json::Null,
+                        isSystem: rule.isSystem,
+                        matchedSelectors: vec!(rule.selectorText.clone()),
+                    });
+                }
+
                 let msg = GetAppliedReply {
-                    entries: vec!(),
-                    rules: vec!(),
-                    sheets: vec!(),
+                    entries: entries,
+                    rules: rule_msgs,
+                    sheets: sheets,
                     from: self.name(),
                 };
                 stream.write_json_packet(&msg);
@@ -440,16 +656,34 @@ impl Actor for PageStyleActor {
             }
 
             "getComputed" => {
-                //TODO: query script for relevant computed styles on node (msg.node)
+                // Layout can't serialize computed values yet, so this reports the cascaded value
+                // of each property that a matching rule sets.
+                let rules = self.applied_rules(registry, msg);
+                let mut computed = BTreeMap::new();
+                for &important in [false, true].iter() {
+                    for rule in rules.iter() {
+                        for declaration in rule.declarations.iter() {
+                            if declaration.important != important {
+                                continue
+                            }
+                            let mut value = BTreeMap::new();
+                            value.insert("value".to_string(), declaration.value.to_json());
+                            let priority = if important { "important" } else { "" };
+                            value.insert("priority".to_string(), priority.to_json());
+                            value.insert("matched".to_string(), true.to_json());
+                            computed.insert(declaration.name.clone(), Json::Object(value));
+                        }
+                    }
+                }
+
                 let msg = GetComputedReply {
-                    computed: vec!(),
+                    computed: computed,
                     from: self.name(),
                 };
                 stream.write_json_packet(&msg);
                 true
             }
 
-            //TODO: query script for box layout properties of node (msg.node)
             "getLayout" => {
                 let target = msg.get(&"node".to_string()).unwrap().as_string().unwrap();
                 let (tx, rx) = channel();
@@ -457,31 +691,48 @@ impl Actor for PageStyleActor {
                                       registry.actor_to_script(target.to_string()),
                                       tx))
                                 .unwrap();
-                let (width, height) = rx.recv().unwrap();
+                let layout = rx.recv().unwrap();
 
                 let auto_margins = msg.get(&"autoMargins".to_string()).unwrap().as_boolean().unwrap();
 
-                //TODO: the remaining layout properties (margin, border, padding, position)
-                //      as specified in getLayout in
-                //      http://mxr.mozilla.org/mozilla-central/source/toolkit/devtools/server/actors/styles.js
-                let msg = GetLayoutReply {
-                    width: width.round() as i32,
-                    height: height.round() as i32,
-                    autoMargins: if auto_margins {
-                        //TODO: real values like processMargins in
-                        //  http://mxr.mozilla.org/mozilla-central/source/toolkit/devtools/server/actors/styles.js
-                        let mut m = BTreeMap::new();
-                        m.insert("top".to_string(), "auto".to_string().to_json());
-                        m.insert("bottom".to_string(), "auto".to_string().to_json());
-                        m.insert("left".to_string(), "auto".to_string().to_json());
-                        m.insert("right".to_string(), "auto".to_string().to_json());
-                        Json::Object(m)
-                    } else {
-                        Json::Null
-                    },
-                    from: self.name(),
-                };
-                stream.write_json_packet(&msg);
+                // The sizes of each area use CSS property names as keys, as specified in
+                // getLayout in
+                // http://mxr.mozilla.org/mozilla-central/source/toolkit/devtools/server/actors/styles.js
+                let mut reply = BTreeMap::new();
+                reply.insert("from".to_string(), self.name().to_json());
+                reply.insert("width".to_string(), (layout.width.round() as i32).to_json());
+                reply.insert("height".to_string(), (layout.height.round() as i32).to_json());
+                reply.insert("position".to_string(), layout.position.to_json());
+                let areas = [
+                    ("margin", "", &layout.margin),
+                    ("border", "-width", &layout.border),
+                    ("padding", "", &layout.padding),
+                ];
+                for &(area, suffix, sides) in areas.iter() {
+                    let sides = [
+                        ("top", sides.top),
+                        ("right", sides.right),
+                        ("bottom", sides.bottom),
+                        ("left", sides.left),
+                    ];
+                    for &(side, width) in sides.iter() {
+                        reply.insert(format!("{}-{}{}", area, side, suffix),
+                                     format!("{}px", width).to_json());
+                    }
+                }
+                reply.insert("autoMargins".to_string(), if auto_margins {
+                    //TODO: real values like processMargins in
+                    //  http://mxr.mozilla.org/mozilla-central/source/toolkit/devtools/server/actors/styles.js
+                    let mut m = BTreeMap::new();
+                    m.insert("top".to_string(), "auto".to_string().to_json());
+                    m.insert("bottom".to_string(), "auto".to_string().to_json());
+                    m.insert("left".to_string(), "auto".to_string().to_json());
+                    m.insert("right".to_string(), "auto".to_string().to_json());
+                    Json::Object(m)
+                } else {
+                    Json::Null
+                });
+                stream.write_json_packet(&Json::Object(reply));
                 true
             }
 
@@ -507,10 +758,14 @@ impl Actor for InspectorActor {
                         name: registry.new_name("walker"),
                         script_chan: self.script_chan.clone(),
                         pipeline: self.pipeline,
+                        streams: RefCell::new(vec!()),
+                        pending_mutations: RefCell::new(vec!()),
                     };
+                    walker.streams.borrow_mut().push(stream.try_clone().unwrap());
                     let mut walker_name = self.walker.borrow_mut();
                     *walker_name = Some(walker.name());
                     registry.register_later(box walker);
+                    self.script_chan.send(WantsMutationNotifications(self.pipeline, true)).unwrap();
                 }
 
                 let (tx, rx) = channel();
@@ -536,6 +791,8 @@ impl Actor for InspectorActor {
                         name: registry.new_name("pageStyle"),
                         script_chan: self.script_chan.clone(),
                         pipeline: self.pipeline,
+                        sheet_actors: RefCell::new(HashMap::new()),
+                        rule_actors: RefCell::new(HashMap::new()),
                     };
                     let mut pageStyle = self.pageStyle.borrow_mut();
                     *pageStyle = Some(style.name());
@@ -559,6 +816,8 @@ impl Actor for InspectorActor {
                 if self.highlighter.borrow().is_none() {
                     let highlighter_actor = HighlighterActor {
                         name: registry.new_name("highlighter"),
+                        script_chan: self.script_chan.clone(),
+                        pipeline: self.pipeline,
                     };
                     let mut highlighter = self.highlighter.borrow_mut();
                     *highlighter = Some(highlighter_actor.name());
@@ -12,8 +12,9 @@ use actor::{Actor, ActorRegistry};
 use protocol::JsonPacketStream;
 use rustc_serialize::json;
 use std::net::TcpStream;
+use time::Tm;
 use url::Url;
-use hyper::header::Headers;
+use hyper::header::{ContentType, Headers};
 use hyper::http::RawStatus;
 use hyper::method::Method;
 
@@ -22,18 +23,28 @@ struct HttpRequest {
     method: Method,
     headers: Headers,
     body: Option<Vec<u8>>,
+    startedDateTime: Option<Tm>,
 }
 
 struct HttpResponse {
     headers: Option<Headers>,
     status: Option<RawStatus>,
-    body: Option<Vec<u8>>
+    body: Option<Vec<u8>>,
+    /// The size of the whole body, once it has been received.
+    size: Option<usize>,
+}
+
+/// How long a request took, in milliseconds.
+struct Timings {
+    wait: u64,
+    receive: u64,
 }
 
 pub struct NetworkEventActor {
     pub name: String,
     request: HttpRequest,
     response: HttpResponse,
+    timings: Option<Timings>,
 }
 
 #[derive(RustcEncodable)]
@@ -53,18 +64,120 @@ pub struct ResponseStartMsg {
     pub remotePort: u32,
     pub status: String,
     pub statusText: String,
-    pub headersSize: u32,
+    pub headersSize: usize,
     pub discardResponseBody: bool,
 }
 
 #[derive(RustcEncodable)]
-struct GetRequestHeadersReply {
+pub struct HeadersUpdateMsg {
+    from: String,
+    __type__: String,
+    updateType: String,
+    headers: usize,
+    headersSize: usize,
+}
+
+#[derive(RustcEncodable)]
+pub struct ResponseContentUpdateMsg {
+    from: String,
+    __type__: String,
+    updateType: String,
+    mimeType: String,
+    contentSize: usize,
+    transferredSize: usize,
+    discardResponseBody: bool,
+}
+
+#[derive(RustcEncodable)]
+pub struct EventTimingsUpdateMsg {
     from: String,
-    headers: Vec<String>,
-    headerSize: u8,
+    __type__: String,
+    updateType: String,
+    totalTime: u64,
+}
+
+#[derive(RustcEncodable)]
+struct HeaderMsg {
+    name: String,
+    value: String,
+}
+
+#[derive(RustcEncodable)]
+struct GetHeadersReply {
+    from: String,
+    headers: Vec<HeaderMsg>,
+    headerSize: usize,
     rawHeaders: String
 }
 
+#[derive(RustcEncodable)]
+struct GetCookiesReply {
+    from: String,
+    cookies: Vec<String>,
+}
+
+#[derive(RustcEncodable)]
+struct PostDataMsg {
+    text: String,
+}
+
+#[derive(RustcEncodable)]
+struct GetRequestPostDataReply {
+    from: String,
+    postData: PostDataMsg,
+    postDataDiscarded: bool,
+}
+
+#[derive(RustcEncodable)]
+struct ContentMsg {
+    mimeType: String,
+    size: usize,
+    text: String,
+}
+
+#[derive(RustcEncodable)]
+struct GetResponseContentReply {
+    from: String,
+    content: ContentMsg,
+    contentDiscarded: bool,
+}
+
+#[derive(RustcEncodable)]
+struct TimingsMsg {
+    blocked: u64,
+    dns: u64,
+    connect: u64,
+    send: u64,
+    wait: u64,
+    receive: u64,
+}
+
+#[derive(RustcEncodable)]
+struct GetEventTimingsReply {
+    from: String,
+    timings: TimingsMsg,
+    totalTime: u64,
+}
+
+/// Lists headers as the network monitor shows them, with their size as they were sent.
+fn header_msgs(headers: &Headers) -> (Vec<HeaderMsg>, usize, String) {
+    let mut raw_headers = String::new();
+    let msgs = headers.iter().map(|header| {
+        let msg = HeaderMsg {
+            name: header.name().to_string(),
+            value: header.value_string(),
+        };
+        raw_headers.push_str(&format!("{}: {}\r\n", msg.name, msg.value));
+        msg
+    }).collect();
+    let size = raw_headers.len();
+    (msgs, size, raw_headers)
+}
+
+fn body_text(body: &Option<Vec<u8>>) -> String {
+    body.as_ref().map_or(String::new(), |body| String::from_utf8_lossy(body).into_owned())
+}
+
 impl Actor for NetworkEventActor {
     fn name(&self) -> String {
         self.name.clone()
@@ -77,30 +190,82 @@ impl Actor for NetworkEventActor {
                       stream: &mut TcpStream) -> Result<bool, ()> {
         Ok(match msg_type {
             "getRequestHeaders" => {
-                // TODO: Pass the correct values for headers, headerSize, rawHeaders
-                let msg = GetRequestHeadersReply {
+                let (headers, size, raw_headers) = header_msgs(&self.request.headers);
+                let msg = GetHeadersReply {
                     from: self.name(),
-                    headers: Vec::new(),
-                    headerSize: 10,
-                    rawHeaders: "Raw headers".to_string(),
+                    headers: headers,
+                    headerSize: size,
+                    rawHeaders: raw_headers,
                 };
                 stream.write_json_packet(&msg);
                 true
             }
-            "getRequestCookies" => {
-                false
+            "getRequestCookies" | "getResponseCookies" => {
+                //TODO: send the cookies once the cookie headers are reported to devtools
+                let msg = GetCookiesReply {
+                    from: self.name(),
+                    cookies: vec!(),
+                };
+                stream.write_json_packet(&msg);
+                true
             }
             "getRequestPostData" => {
-                false
+                let msg = GetRequestPostDataReply {
+                    from: self.name(),
+                    postData: PostDataMsg {
+                        text: body_text(&self.request.body),
+                    },
+                    postDataDiscarded: false,
+                };
+                stream.write_json_packet(&msg);
+                true
             }
             "getResponseHeaders" => {
-                false
-            }
-            "getResponseCookies" => {
-                false
+                let (headers, size, raw_headers) = match self.response.headers {
+                    Some(ref headers) => header_msgs(headers),
+                    None => (vec!(), 0, String::new()),
+                };
+                let msg = GetHeadersReply {
+                    from: self.name(),
+                    headers: headers,
+                    headerSize: size,
+                    rawHeaders: raw_headers,
+                };
+                stream.write_json_packet(&msg);
+                true
             }
             "getResponseContent" => {
-                false
+                let msg = GetResponseContentReply {
+                    from: self.name(),
+                    content: ContentMsg {
+                        mimeType: self.mime_type(),
+                        size: self.response.size.unwrap_or(0),
+                        text: body_text(&self.response.body),
+                    },
+                    contentDiscarded: self.response.body.is_none(),
+                };
+                stream.write_json_packet(&msg);
+                true
+            }
+            "getEventTimings" => {
+                let (wait, receive) = match self.timings {
+                    Some(ref timings) => (timings.wait, timings.receive),
+                    None => (0, 0),
+                };
+                let msg = GetEventTimingsReply {
+                    from: self.name(),
+                    timings: TimingsMsg {
+                        blocked: 0,
+                        dns: 0,
+                        connect: 0,
+                        send: 0,
+                        wait: wait,
+                        receive: receive,
+                    },
+                    totalTime: wait + receive,
+                };
+                stream.write_json_packet(&msg);
+                true
             }
             _ => false
         })
@@ -115,21 +280,30 @@ impl NetworkEventActor {
                 url: String::new(),
                 method: Method::Get,
                 headers: Headers::new(),
-                body: None
+                body: None,
+                startedDateTime: None,
             },
             response: HttpResponse {
                 headers: None,
                 status: None,
                 body: None,
-            }
+                size: None,
+            },
+            timings: None,
         }
     }
 
-    pub fn add_request(&mut self, url: Url, method: Method, headers: Headers, body: Option<Vec<u8>>) {
+    pub fn add_request(&mut self,
+                       url: Url,
+                       method: Method,
+                       headers: Headers,
+                       body: Option<Vec<u8>>,
+                       started: Tm) {
         self.request.url = url.serialize();
         self.request.method = method.clone();
         self.request.headers = headers.clone();
         self.request.body = body;
+        self.request.startedDateTime = Some(started);
     }
 
     pub fn add_response(&mut self, headers: Option<Headers>, status: Option<RawStatus>, body: Option<Vec<u8>>) {
@@ -138,29 +312,98 @@ impl NetworkEventActor {
         self.response.body = body.clone();
     }
 
+    /// Records that the whole body arrived, with its size and how long it took in nanoseconds.
+    pub fn add_response_end(&mut self, size: usize, wait: u64, receive: u64) {
+        self.response.size = Some(size);
+        self.timings = Some(Timings {
+            wait: wait / 1_000_000,
+            receive: receive / 1_000_000,
+        });
+    }
+
     pub fn event_actor(&self) -> EventActor {
-        // TODO: Send the correct values for startedDateTime, isXHR, private
+        // TODO: Send the correct values for isXHR, private
         EventActor {
             actor: self.name(),
             url: self.request.url.clone(),
             method: format!("{}", self.request.method),
-            startedDateTime: "2015-04-22T20:47:08.545Z".to_string(),
+            startedDateTime: self.request.startedDateTime.map_or(String::new(), |started| {
+                format!("{}", started.rfc3339())
+            }),
             isXHR: false,
             private: false,
         }
     }
 
     pub fn response_start(&self) -> ResponseStartMsg {
-        // TODO: Send the correct values for all these fields.
-        //       This is a fake message.
+        // TODO: Send the correct values for httpVersion, remoteAddress and remotePort.
+        let (status, status_text) = match self.response.status {
+            Some(RawStatus(code, ref text)) => (code.to_string(), text.to_string()),
+            None => (String::new(), String::new()),
+        };
         ResponseStartMsg {
             httpVersion: "HTTP/1.1".to_string(),
             remoteAddress: "63.245.217.43".to_string(),
             remotePort: 443,
-            status: "200".to_string(),
-            statusText: "OK".to_string(),
-            headersSize: 337,
+            status: status,
+            statusText: status_text,
+            headersSize: self.response.headers.as_ref().map_or(0, |headers| {
+                header_msgs(headers).1
+            }),
             discardResponseBody: true
         }
     }
+
+    pub fn request_headers_update(&self) -> HeadersUpdateMsg {
+        HeadersUpdateMsg {
+            from: self.name(),
+            __type__: "networkEventUpdate".to_string(),
+            updateType: "requestHeaders".to_string(),
+            headers: self.request.headers.len(),
+            headersSize: header_msgs(&self.request.headers).1,
+        }
+    }
+
+    pub fn response_headers_update(&self) -> HeadersUpdateMsg {
+        let (count, size) = match self.response.headers {
+            Some(ref headers) => (headers.len(), header_msgs(headers).1),
+            None => (0, 0),
+        };
+        HeadersUpdateMsg {
+            from: self.name(),
+            __type__: "networkEventUpdate".to_string(),
+            updateType: "responseHeaders".to_string(),
+            headers: count,
+            headersSize: size,
+        }
+    }
+
+    pub fn response_content_update(&self) -> ResponseContentUpdateMsg {
+        let size = self.response.size.unwrap_or(0);
+        ResponseContentUpdateMsg {
+            from: self.name(),
+            __type__: "networkEventUpdate".to_string(),
+            updateType: "responseContent".to_string(),
+            mimeType: self.mime_type(),
+            contentSize: size,
+            transferredSize: size,
+            discardResponseBody: self.response.body.is_none(),
+        }
+    }
+
+    pub fn event_timings_update(&self) -> EventTimingsUpdateMsg {
+        EventTimingsUpdateMsg {
+            from: self.name(),
+            __type__: "networkEventUpdate".to_string(),
+            updateType: "eventTimings".to_string(),
+            totalTime: self.timings.as_ref().map_or(0, |timings| timings.wait + timings.receive),
+        }
+    }
+
+    fn mime_type(&self) -> String {
+        self.response.headers.as_ref().and_then(|headers| headers.get::<ContentType>())
+                                      .map_or(String::new(), |&ContentType(ref mime)| {
+                                          mime.to_string()
+                                      })
+    }
 }
@@ -3,7 +3,9 @@
  * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
 
 use msg::constellation_msg::PipelineId;
+use profile_traits::time::{ProfilerCategory, ProfilerChan, ProfilerMsg, TimedEvent};
 use rustc_serialize::{json, Encoder, Encodable};
+use std::borrow::ToOwned;
 use std::cell::RefCell;
 use std::collections::{HashMap, VecDeque};
 use std::mem;
@@ -11,7 +13,7 @@ use std::net::TcpStream;
 use std::thread::sleep_ms;
 use std::sync::{Arc, Mutex};
 use std::sync::mpsc::{channel, Sender, Receiver};
-use time::PreciseTime;
+use time::{PreciseTime, precise_time_ns};
 
 use actor::{Actor, ActorRegistry};
 use actors::memory::{MemoryActor, TimelineMemoryReply};
@@ -28,6 +30,7 @@ pub struct TimelineActor {
     devtools_sender: Sender<DevtoolsControlMsg>,
     marker_types: Vec<TimelineMarkerType>,
     pipeline: PipelineId,
+    time_profiler_chan: ProfilerChan,
     is_recording: Arc<Mutex<bool>>,
     stream: RefCell<Option<TcpStream>>,
 
@@ -113,6 +116,15 @@ impl HighResolutionStamp {
     pub fn wrap(time: f64) -> HighResolutionStamp {
         HighResolutionStamp(time)
     }
+
+    /// Converts a time returned by `precise_time_ns`, the clock `PreciseTime` also reads.
+    pub fn from_ns(start_stamp: PreciseTime, time: u64) -> HighResolutionStamp {
+        let now = precise_time_ns();
+        let since_start = start_stamp.to(PreciseTime::now()).num_microseconds()
+                                     .expect("Too big duration in microseconds");
+        let ago = (now as f64 - time as f64) / 1000 as f64;
+        HighResolutionStamp((since_start as f64 - ago) / 1000 as f64)
+    }
 }
 
 impl Encodable for HighResolutionStamp {
@@ -127,15 +139,18 @@ impl TimelineActor {
     pub fn new(name: String,
                pipeline: PipelineId,
                script_sender: Sender<DevtoolScriptControlMsg>,
-               devtools_sender: Sender<DevtoolsControlMsg>) -> TimelineActor {
+               devtools_sender: Sender<DevtoolsControlMsg>,
+               time_profiler_chan: ProfilerChan) -> TimelineActor {
 
         let marker_types = vec!(TimelineMarkerType::Reflow,
-                                TimelineMarkerType::DOMEvent);
+                                TimelineMarkerType::DOMEvent,
+                                TimelineMarkerType::Javascript);
 
         TimelineActor {
             name: name,
             pipeline: pipeline,
             marker_types: marker_types,
+            time_profiler_chan: time_profiler_chan,
             script_sender: script_sender,
             devtools_sender: devtools_sender,
             is_recording: Arc::new(Mutex::new(false)),
@@ -146,7 +161,13 @@ impl TimelineActor {
         }
     }
 
-    fn pull_timeline_data(&self, receiver: Receiver<TimelineMarker>, mut emitter: Emitter) {
+    /// Sends the markers from script and the events timed by the profiler to the client, until
+    /// recording stops. The profiler doesn't know which page its events belong to, so they show
+    /// up on every page's timeline.
+    fn pull_timeline_data(&self,
+                          receiver: Receiver<TimelineMarker>,
+                          profiler_receiver: Receiver<TimedEvent>,
+                          mut emitter: Emitter) {
         let is_recording = self.is_recording.clone();
 
         if !*is_recording.lock().unwrap() {
@@ -194,6 +215,7 @@ impl TimelineActor {
             let mut queues = HashMap::new();
             queues.insert("Reflow".to_string(), VecDeque::new());
             queues.insert("DOMEvent".to_string(), VecDeque::new());
+            queues.insert("Javascript".to_string(), VecDeque::new());
 
             loop {
                 if !*is_recording.lock().unwrap() {
@@ -213,6 +235,12 @@ impl TimelineActor {
                     }
                 }
 
+                while let Ok(event) = profiler_receiver.try_recv() {
+                    if let Some(name) = marker_name(&event.category) {
+                        emitter.add_timed_event(name, event);
+                    }
+                }
+
                 // Emit all markers
                 for (_, queue) in queues.iter_mut() {
                     let start_payload = queue.pop_front();
@@ -243,6 +271,9 @@ impl Actor for TimelineActor {
                 let (tx, rx) = channel::<TimelineMarker>();
                 self.script_sender.send(SetTimelineMarkers(self.pipeline, self.marker_types.clone(), tx)).unwrap();
 
+                let (profiler_tx, profiler_rx) = channel();
+                self.time_profiler_chan.send(ProfilerMsg::Watch(profiler_tx));
+
                 *self.stream.borrow_mut() = stream.try_clone().ok();
 
                 // init memory actor
@@ -269,7 +300,7 @@ impl Actor for TimelineActor {
                                            self.memory_actor.borrow().clone(),
                                            self.framerate_actor.borrow().clone());
 
-                self.pull_timeline_data(rx, emitter);
+                self.pull_timeline_data(rx, profiler_rx, emitter);
 
                 let msg = StartReply {
                     from: self.name(),
@@ -348,6 +379,16 @@ impl Emitter {
         });
     }
 
+    fn add_timed_event(&mut self, name: &str, event: TimedEvent) {
+        self.markers.push(TimelineMarkerReply {
+            name: name.to_owned(),
+            start: HighResolutionStamp::from_ns(self.start_stamp, event.start),
+            end: HighResolutionStamp::from_ns(self.start_stamp, event.end),
+            stack: None,
+            endStack: None,
+        });
+    }
+
     fn send(&mut self) -> () {
         let end_time = PreciseTime::now();
         let reply = MarkersEmitterReply {
@@ -384,3 +425,14 @@ impl Emitter {
         }
     }
 }
+
+/// The name of the markers for events of a profiler category, if the timeline shows them.
+fn marker_name(category: &ProfilerCategory) -> Option<&'static str> {
+    match *category {
+        ProfilerCategory::LayoutStyleRecalc => Some("Styles"),
+        ProfilerCategory::LayoutMain => Some("Layout"),
+        ProfilerCategory::Painting => Some("Paint"),
+        ProfilerCategory::Compositing => Some("Composite"),
+        _ => None,
+    }
+}
@@ -25,6 +25,7 @@ extern crate core;
 extern crate devtools_traits;
 extern crate rustc_serialize;
 extern crate msg;
+extern crate profile_traits;
 extern crate time;
 extern crate util;
 extern crate hyper;
@@ -34,16 +35,17 @@ use actor::{Actor, ActorRegistry};
 use actors::console::ConsoleActor;
 use actors::network_event::{NetworkEventActor, EventActor, ResponseStartMsg};
 use actors::framerate::FramerateActor;
-use actors::inspector::InspectorActor;
+use actors::inspector::{InspectorActor, WalkerActor};
 use actors::root::RootActor;
 use actors::tab::TabActor;
 use actors::timeline::TimelineActor;
 use actors::worker::WorkerActor;
 use protocol::JsonPacketStream;
 
-use devtools_traits::{ConsoleMessage, DevtoolsControlMsg, NetworkEvent, LogLevel};
+use devtools_traits::{ConsoleMessage, DevtoolsControlMsg, NetworkEvent, LogLevel, NodeMutation};
 use devtools_traits::{DevtoolsPageInfo, DevtoolScriptControlMsg};
 use msg::constellation_msg::{PipelineId, WorkerId};
+use profile_traits::time::ProfilerChan;
 use util::task::spawn_named;
 
 use std::borrow::ToOwned;
@@ -104,12 +106,12 @@ struct NetworkEventUpdateMsg {
 }
 
 /// Spin up a devtools server that listens for connections on the specified port.
-pub fn start_server(port: u16) -> Sender<DevtoolsControlMsg> {
+pub fn start_server(port: u16, time_profiler_chan: ProfilerChan) -> Sender<DevtoolsControlMsg> {
     let (sender, receiver) = channel();
     {
         let sender = sender.clone();
         spawn_named("Devtools".to_owned(), move || {
-            run_server(sender, receiver, port)
+            run_server(sender, receiver, port, time_profiler_chan)
         });
     }
     sender
@@ -117,7 +119,8 @@ pub fn start_server(port: u16) -> Sender<DevtoolsControlMsg> {
 
 fn run_server(sender: Sender<DevtoolsControlMsg>,
               receiver: Receiver<DevtoolsControlMsg>,
-              port: u16) {
+              port: u16,
+              time_profiler_chan: ProfilerChan) {
     let listener = TcpListener::bind(&("127.0.0.1", port)).unwrap();
 
     let mut registry = ActorRegistry::new();
@@ -186,6 +189,7 @@ fn run_server(sender: Sender<DevtoolsControlMsg>,
                          ids: (PipelineId, Option<WorkerId>),
                          script_sender: Sender<DevtoolScriptControlMsg>,
                          devtools_sender: Sender<DevtoolsControlMsg>,
+                         time_profiler_chan: ProfilerChan,
                          actor_pipelines: &mut HashMap<PipelineId, String>,
                          actor_workers: &mut HashMap<(PipelineId, WorkerId), String>,
                          page_info: DevtoolsPageInfo) {
@@ -213,7 +217,8 @@ fn run_server(sender: Sender<DevtoolsControlMsg>,
             let timeline = TimelineActor::new(actors.new_name("timeline"),
                                               pipeline,
                                               script_sender,
-                                              devtools_sender);
+                                              devtools_sender,
+                                              time_profiler_chan);
 
             let DevtoolsPageInfo { title, url } = page_info;
             let tab = TabActor {
@@ -276,6 +281,24 @@ fn run_server(sender: Sender<DevtoolsControlMsg>,
         }
     }
 
+    fn handle_node_mutation(actors: Arc<Mutex<ActorRegistry>>,
+                            id: PipelineId,
+                            mutation: NodeMutation,
+                            actor_pipelines: &HashMap<PipelineId, String>) {
+        let actors = actors.lock().unwrap();
+        let tab_actor = match actor_pipelines.get(&id) {
+            Some(tab_actor_name) => actors.find::<TabActor>(tab_actor_name),
+            None => return,
+        };
+        let inspector_actor = actors.find::<InspectorActor>(&tab_actor.inspector);
+        let walker_actor_name = match *inspector_actor.walker.borrow() {
+            Some(ref walker_actor_name) => walker_actor_name.clone(),
+            None => return,
+        };
+        let walker_actor = actors.find::<WalkerActor>(&walker_actor_name);
+        walker_actor.add_mutation(&actors, mutation);
+    }
+
     fn find_console_actor(actors: Arc<Mutex<ActorRegistry>>,
                           id: PipelineId,
                           actor_pipelines: &HashMap<PipelineId, String>) -> String {
@@ -300,9 +323,9 @@ fn run_server(sender: Sender<DevtoolsControlMsg>,
         let actor = actors.find_mut::<NetworkEventActor>(&netevent_actor_name);
 
         match network_event {
-            NetworkEvent::HttpRequest(url, method, headers, body) => {
+            NetworkEvent::HttpRequest(url, method, headers, body, started) => {
                 //Store the request information in the actor
-                actor.add_request(url, method, headers, body);
+                actor.add_request(url, method, headers, body, started);
 
                 //Send a networkEvent message to the client
                 let msg = NetworkEventMsg {
@@ -310,8 +333,10 @@ fn run_server(sender: Sender<DevtoolsControlMsg>,
                     __type__: "networkEvent".to_string(),
                     eventActor: actor.event_actor(),
                 };
+                let headers_msg = actor.request_headers_update();
                 for stream in connections.iter_mut() {
                     stream.write_json_packet(&msg);
+                    stream.write_json_packet(&headers_msg);
                 }
             }
             NetworkEvent::HttpResponse(headers, status, body) => {
@@ -326,12 +351,25 @@ fn run_server(sender: Sender<DevtoolsControlMsg>,
                     response: actor.response_start()
                 };
 
+                let headers_msg = actor.response_headers_update();
                 for stream in connections.iter_mut() {
                     stream.write_json_packet(&msg);
+                    stream.write_json_packet(&headers_msg);
+                }
+            }
+            NetworkEvent::HttpResponseComplete(size, wait, receive) => {
+                actor.add_response_end(size, wait, receive);
+
+                //Send the responseContent and eventTimings updates to the client
+                let content_msg = actor.response_content_update();
+                let timings_msg = actor.event_timings_update();
+                for stream in connections.iter_mut() {
+                    stream.write_json_packet(&content_msg);
+                    stream.write_json_packet(&timings_msg);
                 }
             }
             //TODO: Send the other types of update messages at appropriate times
-            //      requestHeaders, requestCookies, responseHeaders, securityInfo, etc
+            //      requestCookies, responseCookies, securityInfo, etc
         }
     }
 
@@ -377,21 +415,26 @@ fn run_server(sender: Sender<DevtoolsControlMsg>,
             Ok(DevtoolsControlMsg::FramerateTick(actor_name, tick)) =>
                 handle_framerate_tick(actors.clone(), actor_name, tick),
             Ok(DevtoolsControlMsg::NewGlobal(ids, script_sender, pageinfo)) =>
-                handle_new_global(actors.clone(), ids, script_sender, sender.clone(), &mut actor_pipelines,
+                handle_new_global(actors.clone(), ids, script_sender, sender.clone(),
+                                  time_profiler_chan.clone(), &mut actor_pipelines,
                                   &mut actor_workers, pageinfo),
             Ok(DevtoolsControlMsg::SendConsoleMessage(id, console_message)) =>
                 handle_console_message(actors.clone(), id, console_message,
                                        &actor_pipelines),
-            Ok(DevtoolsControlMsg::NetworkEventMessage(request_id, network_event)) => {
+            Ok(DevtoolsControlMsg::NodeMutated(id, mutation)) =>
+                handle_node_mutation(actors.clone(), id, mutation, &actor_pipelines),
+            Ok(DevtoolsControlMsg::NetworkEventMessage(pipeline_id, request_id, network_event)) => {
+                // Requests made before the page's global was created have nowhere to go.
+                if !actor_pipelines.contains_key(&pipeline_id) {
+                    continue
+                }
                 // copy the accepted_connections vector
                 let mut connections = Vec::<TcpStream>::new();
                 for stream in accepted_connections.iter() {
                     connections.push(stream.try_clone().unwrap());
                 }
-                //TODO: Get pipeline_id from NetworkEventMessage after fixing the send in http_loader
-                // For now, the id of the first pipeline is passed
                 handle_network_event(actors.clone(), connections, &actor_pipelines, &mut actor_requests,
-                                     PipelineId(0), request_id, network_event);
+                                     pipeline_id, request_id, network_event);
             },
             Ok(DevtoolsControlMsg::ServerExitMsg) | Err(RecvError) => break
         }
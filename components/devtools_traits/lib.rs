@@ -51,7 +51,9 @@ pub enum DevtoolsControlMsg {
     NewGlobal((PipelineId, Option<WorkerId>), Sender<DevtoolScriptControlMsg>, DevtoolsPageInfo),
     SendConsoleMessage(PipelineId, ConsoleMessage),
     ServerExitMsg,
-    NetworkEventMessage(String, NetworkEvent),
+    NetworkEventMessage(PipelineId, String, NetworkEvent),
+    /// A node in the page was changed, and an inspector asked to hear about it.
+    NodeMutated(PipelineId, NodeMutation),
 }
 
 /// Serialized JS return values
@@ -92,6 +94,41 @@ pub struct NodeInfo {
     pub incompleteValue: bool,
 }
 
+/// The box model of an element, in CSS pixels.
+pub struct ComputedNodeLayout {
+    pub width: f32,
+    pub height: f32,
+    pub position: String,
+    pub margin: BoxSides,
+    pub border: BoxSides,
+    pub padding: BoxSides,
+}
+
+/// The widths of the sides of one of the areas of a box model.
+pub struct BoxSides {
+    pub top: f32,
+    pub right: f32,
+    pub bottom: f32,
+    pub left: f32,
+}
+
+/// A style rule that matches an element, in the order the cascade applies them.
+pub struct AppliedRule {
+    pub href: String,
+    pub line: usize,
+    /// Whether the rule comes from one of the browser's own style sheets.
+    pub isSystem: bool,
+    pub selectorText: String,
+    pub specificity: u32,
+    pub declarations: Vec<AppliedDeclaration>,
+}
+
+pub struct AppliedDeclaration {
+    pub name: String,
+    pub value: String,
+    pub important: bool,
+}
+
 #[derive(PartialEq, Eq)]
 pub enum TracingMetadata {
     Default,
@@ -112,6 +149,8 @@ pub struct TimelineMarker {
 pub enum TimelineMarkerType {
     Reflow,
     DOMEvent,
+    /// The evaluation of a script element.
+    Javascript,
 }
 
 /// Messages to process in a particular script task, as instructed by a devtools client.
@@ -120,13 +159,43 @@ pub enum DevtoolScriptControlMsg {
     GetRootNode(PipelineId, Sender<NodeInfo>),
     GetDocumentElement(PipelineId, Sender<NodeInfo>),
     GetChildren(PipelineId, String, Sender<Vec<NodeInfo>>),
-    GetLayout(PipelineId, String, Sender<(f32, f32)>),
+    GetLayout(PipelineId, String, Sender<ComputedNodeLayout>),
+    /// Asks for the style rules that match an element.
+    GetAppliedRules(PipelineId, String, Sender<Vec<AppliedRule>>),
     GetCachedMessages(PipelineId, CachedConsoleMessageTypes, Sender<Vec<CachedConsoleMessage>>),
     ModifyAttribute(PipelineId, String, Vec<Modification>),
     WantsLiveNotifications(PipelineId, bool),
     SetTimelineMarkers(PipelineId, Vec<TimelineMarkerType>, Sender<TimelineMarker>),
     DropTimelineMarkers(PipelineId, Vec<TimelineMarkerType>),
     RequestAnimationFrame(PipelineId, Box<Fn(f64, ) + Send>),
+    /// Replaces the text of a text or comment node.
+    SetNodeValue(PipelineId, String, String),
+    /// Outlines the given node in the window, or nothing if there is no node.
+    HighlightNode(PipelineId, Option<String>),
+    /// Starts or stops reporting changes to the DOM with `NodeMutated` messages.
+    WantsMutationNotifications(PipelineId, bool),
+}
+
+/// A change to the DOM, named after the records of a `MutationObserver`. Nodes are given by
+/// their unique IDs.
+#[derive(Clone)]
+pub enum NodeMutation {
+    /// An attribute was set or, when there is no new value, removed.
+    Attributes {
+        target: String,
+        attributeName: String,
+        newValue: Option<String>,
+    },
+    /// The text of a text or comment node changed.
+    CharacterData {
+        target: String,
+        newValue: String,
+    },
+    /// Children were added to or removed from a node.
+    ChildList {
+        target: String,
+        numChildren: usize,
+    },
 }
 
 #[derive(RustcEncodable)]
@@ -203,10 +272,15 @@ pub enum CachedConsoleMessage {
     },
 }
 
+/// The stages of an HTTP request, reported to the network monitor.
 #[derive(Clone)]
 pub enum NetworkEvent {
-    HttpRequest(Url, Method, Headers, Option<Vec<u8>>),
-    HttpResponse(Option<Headers>, Option<RawStatus>, Option<Vec<u8>>)
+    /// The request was sent, with its body, at the given time.
+    HttpRequest(Url, Method, Headers, Option<Vec<u8>>, time::Tm),
+    HttpResponse(Option<Headers>, Option<RawStatus>, Option<Vec<u8>>),
+    /// The whole body was received: its size in bytes, and the nanoseconds spent waiting for the
+    /// response and then receiving its body.
+    HttpResponseComplete(usize, u64, u64),
 }
 
 impl TimelineMarker {
@@ -0,0 +1,641 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+//! Color glyphs, as used by emoji fonts, and the other glyphs that fonts store images of.
+//!
+//! Azure only fills glyphs with a single color, and doesn't draw the images that fonts store for
+//! glyphs, so these glyphs are painted separately: glyphs from the `COLR` table as layers of
+//! ordinary glyphs, each filled with a color from the `CPAL` table, glyphs from the `CBDT` and
+//! `sbix` tables as the PNG images stored for them, and glyphs from the `EBDT` table as the
+//! monochrome or grayscale images stored for them, filled with the color of the text.
+//!
+//! https://www.microsoft.com/typography/otspec/colr.htm
+//! https://www.microsoft.com/typography/otspec/cbdt.htm
+//! https://www.microsoft.com/typography/otspec/ebdt.htm
+//! https://developer.apple.com/fonts/TrueType-Reference-Manual/RM06/Chap6sbix.html
+
+use font::FontTableTag;
+use platform::font_template::FontTemplateData;
+use sfnt::{read_i16, read_u16, read_u32};
+use text::glyph::GlyphId;
+
+use azure::azure_hl::Color;
+use gfx_traits::color;
+use libc::c_void;
+use net_traits::image::base::{Image, load_from_memory};
+use png::{self, PixelsByColorType};
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::i16;
+use std::sync::Arc;
+use util::cache::LRUCache;
+use util::mem::{HeapSizeOf, heap_size_of};
+
+const CBDT: FontTableTag = 0x43424454;
+const CBLC: FontTableTag = 0x43424C43;
+const COLR: FontTableTag = 0x434F4C52;
+const CPAL: FontTableTag = 0x4350414C;
+const EBDT: FontTableTag = 0x45424454;
+const EBLC: FontTableTag = 0x45424C43;
+const SBIX: FontTableTag = 0x73626978;
+
+/// The graphic type of PNG images in the `sbix` table.
+const SBIX_PNG: u32 = 0x706E6720;
+
+/// The palette index that stands for the color of the text.
+const FOREGROUND_PALETTE_INDEX: u16 = 0xFFFF;
+
+const CBLC_HEADER_SIZE: usize = 8;
+const BITMAP_SIZE_RECORD_SIZE: usize = 48;
+const BIG_GLYPH_METRICS_SIZE: usize = 8;
+const SMALL_GLYPH_METRICS_SIZE: usize = 5;
+const SBIX_HEADER_SIZE: usize = 8;
+const SBIX_GLYPH_HEADER_SIZE: usize = 8;
+
+/// The number of decoded glyph images that each font keeps. Color emoji images are tens of
+/// kilobytes each, so a page using many emoji doesn't keep them all.
+const BITMAP_CACHE_SIZE: usize = 128;
+
+/// One layer of a color glyph.
+#[derive(Clone, Copy)]
+pub struct ColorLayer {
+    pub glyph_id: GlyphId,
+    /// The color to fill the layer with, or `None` for the color of the text.
+    pub color: Option<Color>,
+}
+
+/// The image stored for a glyph, and where it goes relative to the glyph origin, in the pixels
+/// of the image.
+#[derive(Clone)]
+pub struct GlyphBitmap {
+    pub image: Arc<Image>,
+    /// Whether the image is the coverage of the glyph, to be filled with the color of the text,
+    /// rather than an image in colors of its own.
+    pub is_mask: bool,
+    /// The number of pixels per em that the image was drawn for.
+    pub ppem: u16,
+    /// The distance from the origin to the left edge of the image.
+    pub bearing_x: i16,
+    /// The distance from the baseline up to the top edge of the image.
+    pub bearing_y: i16,
+}
+
+/// A size of glyph images in a `CBLC` or `EBLC` table.
+struct BitmapStrike {
+    ppem: u8,
+    /// The number of bits in each pixel of monochrome and grayscale images.
+    bit_depth: u8,
+    /// The offset of the strike's index subtable array in the `CBLC` or `EBLC` table.
+    index_array_offset: usize,
+    index_subtable_count: usize,
+    first_glyph: u16,
+    last_glyph: u16,
+}
+
+/// Where the image of a glyph is in a `CBDT` or `EBDT` table.
+struct BitmapLocation {
+    image_format: u16,
+    offset: usize,
+    length: usize,
+    /// For image formats 5 and 19, which keep their metrics in the `CBLC` or `EBLC` table, the
+    /// offset of those metrics.
+    metrics_offset: Option<usize>,
+}
+
+/// The strikes of a `CBLC` or `EBLC` table, with the tags of that table and the `CBDT` or `EBDT`
+/// table that has the images.
+struct BitmapTables {
+    strikes: Vec<BitmapStrike>,
+    location: FontTableTag,
+    data: FontTableTag,
+}
+
+/// A size of glyph images in the `sbix` table.
+struct SbixStrike {
+    ppem: u16,
+    /// The offset of the strike in the `sbix` table.
+    offset: usize,
+}
+
+/// The tables that glyph images come from.
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+enum BitmapSource {
+    Color,
+    Sbix,
+    Mask,
+}
+
+/// The color glyphs of a font. The tables with the images of glyphs are read from the font
+/// whenever an image is decoded, rather than copied.
+pub struct ColorGlyphs {
+    template: Arc<FontTemplateData>,
+    layers: HashMap<GlyphId, Vec<ColorLayer>>,
+    color_bitmaps: Option<BitmapTables>,
+    mask_bitmaps: Option<BitmapTables>,
+    sbix_strikes: Vec<SbixStrike>,
+    /// Recently decoded glyph images, by the table they come from, glyph and number of pixels
+    /// per em.
+    bitmaps: RefCell<LRUCache<(BitmapSource, GlyphId, u16), Option<GlyphBitmap>>>,
+}
+
+impl ColorGlyphs {
+    /// Reads the color glyphs of a font, or returns `None` if it has none.
+    pub fn new(template: Arc<FontTemplateData>) -> Option<ColorGlyphs> {
+        let layers = template.with_font_table(CPAL, read_palette).and_then(|palette| {
+            template.with_font_table(COLR, |colr| read_layers(colr, &palette))
+        }).unwrap_or(HashMap::new());
+        let color_bitmaps = BitmapTables::new(&template, CBLC, CBDT);
+        let mask_bitmaps = BitmapTables::new(&template, EBLC, EBDT);
+        let sbix_strikes = template.with_font_table(SBIX, read_sbix_strikes).unwrap_or(vec!());
+        if layers.is_empty() && color_bitmaps.is_none() && mask_bitmaps.is_none() &&
+                sbix_strikes.is_empty() {
+            return None
+        }
+        Some(ColorGlyphs {
+            template: template,
+            layers: layers,
+            color_bitmaps: color_bitmaps,
+            mask_bitmaps: mask_bitmaps,
+            sbix_strikes: sbix_strikes,
+            bitmaps: RefCell::new(LRUCache::new(BITMAP_CACHE_SIZE)),
+        })
+    }
+
+    /// Returns the layers that make up a glyph, from bottom to top, if it is a layered color
+    /// glyph.
+    pub fn layers(&self, glyph_id: GlyphId) -> Option<&[ColorLayer]> {
+        self.layers.get(&glyph_id).map(|layers| &**layers)
+    }
+
+    /// Returns the image of a glyph for the given size in pixels. Color images are scaled from
+    /// the strike that best suits the size: the smallest strike at least that large, or the
+    /// largest one if none are. Monochrome and grayscale images are only used at the size they
+    /// were drawn for, since they are drawn for small sizes that outlines don't suit.
+    pub fn bitmap(&self, glyph_id: GlyphId, pixel_size: f32) -> Option<GlyphBitmap> {
+        if glyph_id > 0xFFFF {
+            return None
+        }
+        let glyph_id16 = glyph_id as u16;
+
+        if let Some(ref tables) = self.color_bitmaps {
+            let strike = tables.strikes.iter().filter(|strike| {
+                strike.first_glyph <= glyph_id16 && glyph_id16 <= strike.last_glyph
+            }).fold(None, |best: Option<&BitmapStrike>, strike| {
+                match best {
+                    Some(best) if !is_closer_strike(strike.ppem as u16,
+                                                    best.ppem as u16,
+                                                    pixel_size) => Some(best),
+                    _ => Some(strike),
+                }
+            });
+            if let Some(strike) = strike {
+                return self.cached_bitmap(BitmapSource::Color, glyph_id, strike.ppem as u16, || {
+                    self.decode_table_bitmap(tables, strike, glyph_id16)
+                })
+            }
+        }
+
+        let sbix_strike = self.sbix_strikes.iter().fold(None, |best: Option<&SbixStrike>, strike| {
+            match best {
+                Some(best) if !is_closer_strike(strike.ppem, best.ppem, pixel_size) => Some(best),
+                _ => Some(strike),
+            }
+        });
+        if let Some(strike) = sbix_strike {
+            let bitmap = self.cached_bitmap(BitmapSource::Sbix, glyph_id, strike.ppem, || {
+                self.template.with_font_table(SBIX, |sbix| {
+                    decode_sbix_bitmap(sbix, strike, glyph_id16)
+                }).and_then(|bitmap| bitmap)
+            });
+            if bitmap.is_some() {
+                return bitmap
+            }
+        }
+
+        if let Some(ref tables) = self.mask_bitmaps {
+            let ppem = pixel_size.round();
+            let strike = tables.strikes.iter().find(|strike| {
+                strike.ppem as f32 == ppem &&
+                    strike.first_glyph <= glyph_id16 && glyph_id16 <= strike.last_glyph
+            });
+            if let Some(strike) = strike {
+                return self.cached_bitmap(BitmapSource::Mask, glyph_id, strike.ppem as u16, || {
+                    self.decode_table_bitmap(tables, strike, glyph_id16)
+                })
+            }
+        }
+
+        None
+    }
+
+    /// Returns the image of a glyph from the cache, or decodes it with `decode` and caches it.
+    fn cached_bitmap<F>(&self, source: BitmapSource, glyph_id: GlyphId, ppem: u16, decode: F)
+                        -> Option<GlyphBitmap>
+                        where F: FnOnce() -> Option<GlyphBitmap> {
+        let key = (source, glyph_id, ppem);
+        if let Some(bitmap) = self.bitmaps.borrow_mut().find(&key) {
+            return bitmap
+        }
+        let bitmap = decode();
+        self.bitmaps.borrow_mut().insert(key, bitmap.clone());
+        bitmap
+    }
+
+    /// Finds and decodes the image of a glyph in a strike of a `CBLC` or `EBLC` table.
+    fn decode_table_bitmap(&self, tables: &BitmapTables, strike: &BitmapStrike, glyph_id: u16)
+                           -> Option<GlyphBitmap> {
+        let template = &self.template;
+        template.with_font_table(tables.location, |eblc| {
+            find_bitmap(eblc, strike, glyph_id).and_then(|location| {
+                template.with_font_table(tables.data, |ebdt| {
+                    decode_bitmap(eblc, ebdt, strike, &location)
+                }).and_then(|bitmap| bitmap)
+            })
+        }).and_then(|bitmap| bitmap)
+    }
+}
+
+/// Returns true if a strike of `ppem` pixels per em suits `pixel_size` better than one of
+/// `best_ppem`: if it is the smaller of the two that are at least that large, or the larger of
+/// the two if neither is.
+fn is_closer_strike(ppem: u16, best_ppem: u16, pixel_size: f32) -> bool {
+    let large_enough = ppem as f32 >= pixel_size;
+    let best_large_enough = best_ppem as f32 >= pixel_size;
+    (large_enough && (!best_large_enough || ppem < best_ppem)) ||
+        (!large_enough && !best_large_enough && ppem > best_ppem)
+}
+
+impl BitmapTables {
+    /// Reads the strikes of the `location` table of a font, if it has that table and the `data`
+    /// one.
+    fn new(template: &FontTemplateData, location: FontTableTag, data: FontTableTag)
+           -> Option<BitmapTables> {
+        if template.with_font_table(data, |_| ()).is_none() {
+            return None
+        }
+        match template.with_font_table(location, read_strikes) {
+            Some(ref strikes) if strikes.is_empty() => None,
+            Some(strikes) => {
+                Some(BitmapTables {
+                    strikes: strikes,
+                    location: location,
+                    data: data,
+                })
+            }
+            None => None,
+        }
+    }
+}
+
+/// Finds the image of a glyph in a strike. `CBLC` tables are laid out like `EBLC` ones.
+fn find_bitmap(eblc: &[u8], strike: &BitmapStrike, glyph_id: u16) -> Option<BitmapLocation> {
+    for index in 0..strike.index_subtable_count {
+        let record = strike.index_array_offset + index * 8;
+        if record + 8 > eblc.len() {
+            return None
+        }
+        let first_glyph = read_u16(eblc, record);
+        let last_glyph = read_u16(eblc, record + 2);
+        if glyph_id < first_glyph || glyph_id > last_glyph {
+            continue
+        }
+        let subtable = strike.index_array_offset + read_u32(eblc, record + 4) as usize;
+        if subtable + 8 > eblc.len() {
+            return None
+        }
+        let index_format = read_u16(eblc, subtable);
+        let image_format = read_u16(eblc, subtable + 2);
+        let image_data_offset = read_u32(eblc, subtable + 4) as usize;
+        let glyph_index = (glyph_id - first_glyph) as usize;
+        let header_end = subtable + 8;
+
+        let (start, end, metrics_offset) = match index_format {
+            // Offsets to each glyph's image, as 32 or 16-bit numbers.
+            1 | 3 => {
+                let size = if index_format == 1 { 4 } else { 2 };
+                let offset = header_end + glyph_index * size;
+                if offset + size * 2 > eblc.len() {
+                    return None
+                }
+                let read = |offset| {
+                    if size == 4 {
+                        read_u32(eblc, offset) as usize
+                    } else {
+                        read_u16(eblc, offset) as usize
+                    }
+                };
+                (read(offset), read(offset + size), None)
+            }
+            // Images of the same size, one after the other.
+            2 => {
+                if header_end + 4 + BIG_GLYPH_METRICS_SIZE > eblc.len() {
+                    return None
+                }
+                let image_size = read_u32(eblc, header_end) as usize;
+                (glyph_index * image_size, (glyph_index + 1) * image_size,
+                 Some(header_end + 4))
+            }
+            // Offsets to the images of a sparse set of glyphs.
+            4 => {
+                if header_end + 4 > eblc.len() {
+                    return None
+                }
+                let glyph_count = read_u32(eblc, header_end) as usize;
+                let pairs = header_end + 4;
+                if pairs + (glyph_count + 1) * 4 > eblc.len() {
+                    return None
+                }
+                let position = (0..glyph_count).position(|pair| {
+                    read_u16(eblc, pairs + pair * 4) == glyph_id
+                });
+                match position {
+                    Some(pair) => (read_u16(eblc, pairs + pair * 4 + 2) as usize,
+                                   read_u16(eblc, pairs + pair * 4 + 6) as usize,
+                                   None),
+                    None => continue,
+                }
+            }
+            // Images of the same size for a sparse set of glyphs.
+            5 => {
+                let glyph_ids = header_end + 4 + BIG_GLYPH_METRICS_SIZE + 4;
+                if glyph_ids > eblc.len() {
+                    return None
+                }
+                let image_size = read_u32(eblc, header_end) as usize;
+                let glyph_count = read_u32(eblc, glyph_ids - 4) as usize;
+                if glyph_ids + glyph_count * 2 > eblc.len() {
+                    return None
+                }
+                match (0..glyph_count).position(|id| {
+                    read_u16(eblc, glyph_ids + id * 2) == glyph_id
+                }) {
+                    Some(id) => (id * image_size, (id + 1) * image_size, Some(header_end + 4)),
+                    None => continue,
+                }
+            }
+            _ => return None,
+        };
+        if start >= end {
+            return None
+        }
+        return Some(BitmapLocation {
+            image_format: image_format,
+            offset: image_data_offset + start,
+            length: end - start,
+            metrics_offset: metrics_offset,
+        })
+    }
+    None
+}
+
+/// Decodes the image at `location` in the `CBDT` or `EBDT` table `ebdt`, whose strike is in the
+/// `CBLC` or `EBLC` table `eblc`.
+fn decode_bitmap(eblc: &[u8], ebdt: &[u8], strike: &BitmapStrike, location: &BitmapLocation)
+                 -> Option<GlyphBitmap> {
+    if location.offset > ebdt.len() || location.length > ebdt.len() - location.offset {
+        return None
+    }
+    let data = &ebdt[location.offset..location.offset + location.length];
+
+    // The sizes and horizontal bearings are at the same place in small and big glyph
+    // metrics.
+    let (metrics, image_offset, bit_aligned) = match location.image_format {
+        1 | 17 => (data, SMALL_GLYPH_METRICS_SIZE, false),
+        2 => (data, SMALL_GLYPH_METRICS_SIZE, true),
+        6 | 18 => (data, BIG_GLYPH_METRICS_SIZE, false),
+        7 => (data, BIG_GLYPH_METRICS_SIZE, true),
+        5 | 19 => match location.metrics_offset {
+            Some(offset) if offset + BIG_GLYPH_METRICS_SIZE <= eblc.len() => {
+                (&eblc[offset..], 0, true)
+            }
+            _ => return None,
+        },
+        _ => return None,
+    };
+    if metrics.len() < 4 || image_offset > data.len() {
+        return None
+    }
+    let image = &data[image_offset..];
+
+    let (image, is_mask) = if location.image_format >= 17 {
+        // PNG images, after their length.
+        if image.len() < 4 {
+            return None
+        }
+        let png_length = read_u32(image, 0) as usize;
+        if png_length > image.len() - 4 {
+            return None
+        }
+        match load_from_memory(&image[4..4 + png_length]) {
+            Some(image) => (image, false),
+            None => return None,
+        }
+    } else {
+        let (width, height) = (metrics[1] as usize, metrics[0] as usize);
+        match decode_mask(image, width, height, strike.bit_depth, bit_aligned) {
+            Some(pixels) => {
+                (png::Image {
+                    width: width as u32,
+                    height: height as u32,
+                    pixels: PixelsByColorType::K8(pixels),
+                }, true)
+            }
+            None => return None,
+        }
+    };
+    Some(GlyphBitmap {
+        image: Arc::new(image),
+        is_mask: is_mask,
+        ppem: strike.ppem as u16,
+        bearing_x: metrics[2] as i8 as i16,
+        bearing_y: metrics[3] as i8 as i16,
+    })
+}
+
+/// Unpacks a monochrome or grayscale image of `bit_depth` bits per pixel into a byte per pixel.
+/// The rows of bit-aligned images follow each other without padding, and those of byte-aligned
+/// images start on a byte.
+fn decode_mask(data: &[u8], width: usize, height: usize, bit_depth: u8, bit_aligned: bool)
+               -> Option<Vec<u8>> {
+    let bit_depth = bit_depth as usize;
+    if bit_depth == 0 || 8 % bit_depth != 0 {
+        return None
+    }
+    let row_bits = if bit_aligned {
+        width * bit_depth
+    } else {
+        (width * bit_depth + 7) / 8 * 8
+    };
+    if (row_bits * height + 7) / 8 > data.len() {
+        return None
+    }
+    let max_value = (1 << bit_depth) - 1;
+    let mut pixels = Vec::with_capacity(width * height);
+    for y in 0..height {
+        for x in 0..width {
+            let bit = y * row_bits + x * bit_depth;
+            let value = (data[bit / 8] as usize >> (8 - bit_depth - bit % 8)) & max_value;
+            pixels.push((value * 255 / max_value) as u8);
+        }
+    }
+    Some(pixels)
+}
+
+/// Decodes the PNG image of a glyph in a strike of the `sbix` table.
+fn decode_sbix_bitmap(sbix: &[u8], strike: &SbixStrike, glyph_id: u16) -> Option<GlyphBitmap> {
+    let offsets = strike.offset + 4 + glyph_id as usize * 4;
+    if offsets + 8 > sbix.len() {
+        return None
+    }
+    let start = strike.offset + read_u32(sbix, offsets) as usize;
+    let end = strike.offset + read_u32(sbix, offsets + 4) as usize;
+    if end > sbix.len() || start + SBIX_GLYPH_HEADER_SIZE > end {
+        return None
+    }
+    if read_u32(sbix, start + 4) != SBIX_PNG {
+        return None
+    }
+    load_from_memory(&sbix[start + SBIX_GLYPH_HEADER_SIZE..end]).and_then(|image| {
+        // The origin offsets are from the glyph origin to the bottom left of the image.
+        let bearing_y = read_i16(sbix, start + 2) as i32 + image.height as i32;
+        if bearing_y > i16::MAX as i32 {
+            return None
+        }
+        Some(GlyphBitmap {
+            image: Arc::new(image),
+            is_mask: false,
+            ppem: strike.ppem,
+            bearing_x: read_i16(sbix, start),
+            bearing_y: bearing_y as i16,
+        })
+    })
+}
+
+impl HeapSizeOf for ColorGlyphs {
+    fn heap_size_of_children(&self) -> usize {
+        // The hash table and cache themselves aren't measured, only the layers and images in
+        // them. The font data is shared with the font template, which measures it.
+        let layers = self.layers.values().fold(0, |size, layers| {
+            size + heap_size_of(layers.as_ptr() as *const c_void)
+        });
+        let bitmaps = self.bitmaps.borrow().iter().fold(0, |size, &(_, ref bitmap)| {
+            size + bitmap.as_ref().map_or(0, |bitmap| image_size(&bitmap.image))
+        });
+        let tables = [&self.color_bitmaps, &self.mask_bitmaps].iter().fold(0, |size, tables| {
+            size + tables.as_ref().map_or(0, |tables| {
+                heap_size_of(tables.strikes.as_ptr() as *const c_void)
+            })
+        });
+        layers + tables + heap_size_of(self.sbix_strikes.as_ptr() as *const c_void) + bitmaps
+    }
+}
+
+fn image_size(image: &Image) -> usize {
+    match image.pixels {
+        PixelsByColorType::K8(ref pixels) |
+        PixelsByColorType::KA8(ref pixels) |
+        PixelsByColorType::RGB8(ref pixels) |
+        PixelsByColorType::RGBA8(ref pixels) => pixels.len(),
+    }
+}
+
+/// Reads the colors of the first palette in a `CPAL` table.
+fn read_palette(cpal: &[u8]) -> Vec<Color> {
+    if cpal.len() < 14 {
+        return vec!()
+    }
+    let entry_count = read_u16(cpal, 2) as usize;
+    let records_offset = read_u32(cpal, 8) as usize;
+    let first_record = records_offset + read_u16(cpal, 12) as usize * 4;
+    if first_record + entry_count * 4 > cpal.len() {
+        return vec!()
+    }
+    (0..entry_count).map(|index| {
+        let record = first_record + index * 4;
+        // Colors are stored as blue, green, red and alpha.
+        color::new(cpal[record + 2] as f32 / 255.0,
+                   cpal[record + 1] as f32 / 255.0,
+                   cpal[record] as f32 / 255.0,
+                   cpal[record + 3] as f32 / 255.0)
+    }).collect()
+}
+
+fn read_layers(colr: &[u8], palette: &[Color]) -> HashMap<GlyphId, Vec<ColorLayer>> {
+    let mut glyphs = HashMap::new();
+    if colr.len() < 14 {
+        return glyphs
+    }
+    let base_glyph_count = read_u16(colr, 2) as usize;
+    let base_glyphs_offset = read_u32(colr, 4) as usize;
+    let layers_offset = read_u32(colr, 8) as usize;
+    let layer_count = read_u16(colr, 12) as usize;
+    if base_glyphs_offset + base_glyph_count * 6 > colr.len() ||
+            layers_offset + layer_count * 4 > colr.len() {
+        return glyphs
+    }
+    for index in 0..base_glyph_count {
+        let record = base_glyphs_offset + index * 6;
+        let first_layer = read_u16(colr, record + 2) as usize;
+        let count = read_u16(colr, record + 4) as usize;
+        if first_layer + count > layer_count {
+            continue
+        }
+        let layers = (first_layer..first_layer + count).map(|layer| {
+            let layer_record = layers_offset + layer * 4;
+            let palette_index = read_u16(colr, layer_record + 2);
+            ColorLayer {
+                glyph_id: read_u16(colr, layer_record) as GlyphId,
+                color: if palette_index == FOREGROUND_PALETTE_INDEX {
+                    None
+                } else {
+                    palette.get(palette_index as usize).cloned()
+                },
+            }
+        }).collect();
+        glyphs.insert(read_u16(colr, record) as GlyphId, layers);
+    }
+    glyphs
+}
+
+/// Reads the strikes of a `CBLC` or `EBLC` table.
+fn read_strikes(cblc: &[u8]) -> Vec<BitmapStrike> {
+    if cblc.len() < CBLC_HEADER_SIZE {
+        return vec!()
+    }
+    let strike_count = read_u32(cblc, 4) as usize;
+    if strike_count > (cblc.len() - CBLC_HEADER_SIZE) / BITMAP_SIZE_RECORD_SIZE {
+        return vec!()
+    }
+    (0..strike_count).map(|index| {
+        let record = CBLC_HEADER_SIZE + index * BITMAP_SIZE_RECORD_SIZE;
+        BitmapStrike {
+            ppem: cblc[record + 44],
+            bit_depth: cblc[record + 46],
+            index_array_offset: read_u32(cblc, record) as usize,
+            index_subtable_count: read_u32(cblc, record + 8) as usize,
+            first_glyph: read_u16(cblc, record + 40),
+            last_glyph: read_u16(cblc, record + 42),
+        }
+    }).collect()
+}
+
+fn read_sbix_strikes(sbix: &[u8]) -> Vec<SbixStrike> {
+    if sbix.len() < SBIX_HEADER_SIZE {
+        return vec!()
+    }
+    let strike_count = read_u32(sbix, 4) as usize;
+    if strike_count > (sbix.len() - SBIX_HEADER_SIZE) / 4 {
+        return vec!()
+    }
+    (0..strike_count).filter_map(|index| {
+        let offset = read_u32(sbix, SBIX_HEADER_SIZE + index * 4) as usize;
+        if offset + 4 > sbix.len() {
+            return None
+        }
+        Some(SbixStrike {
+            ppem: read_u16(sbix, offset),
+            offset: offset,
+        })
+    }).collect()
+}
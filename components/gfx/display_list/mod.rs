@@ -33,7 +33,7 @@ use euclid::num::Zero;
 use libc::uintptr_t;
 use paint_task::PaintLayer;
 use msg::compositor_msg::{LayerId, LayerKind};
-use net_traits::image::base::Image;
+use net_traits::image::base::{AnimatedImage, Image};
 use util::opts;
 use util::cursor::Cursor;
 use util::linked_list::prepend_from;
@@ -53,6 +53,7 @@ use style::properties::ComputedValues;
 pub use azure::azure_hl::GradientStop;
 
 pub mod optimizer;
+pub mod serialize;
 
 /// The factor that we multiply the blur radius by in order to inflate the boundaries of display
 /// items that involve a blur. This ensures that the display item boundaries include all the ink.
@@ -66,7 +67,7 @@ const MIN_INDENTATION_LENGTH: usize = 4;
 /// Because the script task's GC does not trace layout, node data cannot be safely stored in layout
 /// data structures. Also, layout code tends to be faster when the DOM is not being accessed, for
 /// locality reasons. Using `OpaqueNode` enforces this invariant.
-#[derive(Clone, PartialEq, Copy, Debug, HeapSizeOf)]
+#[derive(Clone, PartialEq, Copy, Debug, HeapSizeOf, RustcEncodable, RustcDecodable)]
 pub struct OpaqueNode(pub uintptr_t);
 
 impl OpaqueNode {
@@ -82,7 +83,7 @@ impl OpaqueNode {
 ///
 /// TODO(pcwalton): We could reduce the size of this structure with a more "skip list"-like
 /// structure, omitting several pointers and lengths.
-#[derive(HeapSizeOf)]
+#[derive(HeapSizeOf, RustcEncodable, RustcDecodable)]
 pub struct DisplayList {
     /// The border and backgrounds for the root of this stacking context: steps 1 and 2.
     pub background_and_borders: LinkedList<DisplayItem>,
@@ -198,6 +199,9 @@ impl DisplayList {
                     DisplayItem::LineClass(ref line) => {
                         println!("{:?} Line. {:?}", indentation, line.base.bounds)
                     }
+                    DisplayItem::WavyLineClass(ref wavy_line) => {
+                        println!("{:?} WavyLine. {:?}", indentation, wavy_line.base.bounds)
+                    }
                     DisplayItem::BoxShadowClass(ref box_shadow) => {
                         println!("{:?} Box_shadow. {:?}", indentation, box_shadow.base.bounds)
                     }
@@ -306,6 +310,7 @@ impl StackingContext {
                 clip_rect: clip_rect.map(|clip_rect| *clip_rect),
                 transient_clip: None,
                 layer_kind: paint_context.layer_kind,
+                animation_time: paint_context.animation_time,
             };
 
             if opts::get().dump_display_list_optimized {
@@ -598,6 +603,29 @@ impl StackingContext {
                          self.display_list.background_and_borders.iter().rev())
     }
 
+    /// Adds the animated images shown by this stacking context and its descendants to
+    /// `animations`.
+    pub fn collect_animated_images(&self, animations: &mut Vec<Arc<AnimatedImage>>) {
+        let display_list = &self.display_list;
+        for list in [&display_list.background_and_borders,
+                     &display_list.block_backgrounds_and_borders,
+                     &display_list.floats,
+                     &display_list.content,
+                     &display_list.positioned_content,
+                     &display_list.outlines].iter() {
+            for display_item in list.iter() {
+                if let DisplayItem::ImageClass(ref image_item) = *display_item {
+                    if let Some(ref animation) = image_item.animation {
+                        animations.push(animation.clone());
+                    }
+                }
+            }
+        }
+        for kid in display_list.children.iter() {
+            kid.collect_animated_images(animations);
+        }
+    }
+
     pub fn print(&self, mut indentation: String) {
         // We cover the case of an empty string.
         if indentation.len() == 0 {
@@ -639,7 +667,7 @@ pub fn find_stacking_context_with_layer_id(this: &Arc<StackingContext>, layer_id
 }
 
 /// One drawing command in the list.
-#[derive(Clone, HeapSizeOf)]
+#[derive(Clone, HeapSizeOf, RustcEncodable, RustcDecodable)]
 pub enum DisplayItem {
     SolidColorClass(Box<SolidColorDisplayItem>),
     TextClass(Box<TextDisplayItem>),
@@ -647,11 +675,12 @@ pub enum DisplayItem {
     BorderClass(Box<BorderDisplayItem>),
     GradientClass(Box<GradientDisplayItem>),
     LineClass(Box<LineDisplayItem>),
+    WavyLineClass(Box<WavyLineDisplayItem>),
     BoxShadowClass(Box<BoxShadowDisplayItem>),
 }
 
 /// Information common to all display items.
-#[derive(Clone, HeapSizeOf)]
+#[derive(Clone, HeapSizeOf, RustcEncodable, RustcDecodable)]
 pub struct BaseDisplayItem {
     /// The boundaries of the display item, in layer coordinates.
     pub bounds: Rect<Au>,
@@ -679,7 +708,7 @@ impl BaseDisplayItem {
 /// A clipping region for a display item. Currently, this can describe rectangles, rounded
 /// rectangles (for `border-radius`), or arbitrary intersections of the two. Arbitrary transforms
 /// are not supported because those are handled by the higher-level `StackingContext` abstraction.
-#[derive(Clone, PartialEq, Debug, HeapSizeOf)]
+#[derive(Clone, PartialEq, Debug, HeapSizeOf, RustcEncodable, RustcDecodable)]
 pub struct ClippingRegion {
     /// The main rectangular region. This does not include any corners.
     pub main: Rect<Au>,
@@ -693,7 +722,7 @@ pub struct ClippingRegion {
 /// A complex clipping region. These don't as easily admit arbitrary intersection operations, so
 /// they're stored in a list over to the side. Currently a complex clipping region is just a
 /// rounded rectangle, but the CSS WGs will probably make us throw more stuff in here eventually.
-#[derive(Clone, PartialEq, Debug, HeapSizeOf)]
+#[derive(Clone, PartialEq, Debug, HeapSizeOf, RustcEncodable, RustcDecodable)]
 pub struct ComplexClippingRegion {
     /// The boundaries of the rectangle.
     pub rect: Rect<Au>,
@@ -805,7 +834,7 @@ impl ClippingRegion {
 /// Metadata attached to each display item. This is useful for performing auxiliary tasks with
 /// the display list involving hit testing: finding the originating DOM node and determining the
 /// cursor to use when the element is hovered over.
-#[derive(Clone, Copy, HeapSizeOf)]
+#[derive(Clone, Copy, HeapSizeOf, RustcEncodable, RustcDecodable)]
 pub struct DisplayItemMetadata {
     /// The DOM node from which this display item originated.
     pub node: OpaqueNode,
@@ -856,9 +885,19 @@ pub struct TextDisplayItem {
     /// The range of text within the text run.
     pub range: Range<CharIndex>,
 
+    /// The extra space after each justification opportunity in the range, if the text is
+    /// justified.
+    pub justification_space: Au,
+
     /// The color of the text.
     pub text_color: Color,
 
+    /// The color of the outline drawn around the glyphs.
+    pub stroke_color: Color,
+
+    /// The width of the outline drawn around the glyphs. If zero, the glyphs aren't outlined.
+    pub stroke_width: Au,
+
     /// The position of the start of the baseline of this text.
     pub baseline_origin: Point2D<Au>,
 
@@ -869,7 +908,7 @@ pub struct TextDisplayItem {
     pub blur_radius: Au,
 }
 
-#[derive(Clone, Eq, PartialEq, HeapSizeOf)]
+#[derive(Clone, Eq, PartialEq, HeapSizeOf, RustcEncodable, RustcDecodable)]
 pub enum TextOrientation {
     Upright,
     SidewaysLeft,
@@ -883,6 +922,11 @@ pub struct ImageDisplayItem {
     #[ignore_heap_size_of = "Because it is non-owning"]
     pub image: Arc<Image>,
 
+    /// All the frames of the image, if it is animated. The frame that is painted depends on
+    /// the time, so the image changes without the display list being rebuilt.
+    #[ignore_heap_size_of = "Because it is non-owning"]
+    pub animation: Option<Arc<AnimatedImage>>,
+
     /// The dimensions to which the image display item should be stretched. If this is smaller than
     /// the bounds of this display item, then the image will be repeated in the appropriate
     /// direction to tile the entire bounds.
@@ -949,7 +993,7 @@ pub struct BorderDisplayItem {
 /// Information about the border radii.
 ///
 /// TODO(pcwalton): Elliptical radii.
-#[derive(Clone, Default, PartialEq, Debug, Copy, HeapSizeOf)]
+#[derive(Clone, Default, PartialEq, Debug, Copy, HeapSizeOf, RustcEncodable, RustcDecodable)]
 pub struct BorderRadii<T> {
     pub top_left: T,
     pub top_right: T,
@@ -990,6 +1034,15 @@ pub struct LineDisplayItem {
     pub style: border_style::T
 }
 
+/// Paints a wavy line across its bounds, such as the one under misspelled words.
+#[derive(Clone, HeapSizeOf)]
+pub struct WavyLineDisplayItem {
+    pub base: BaseDisplayItem,
+
+    /// The line color.
+    pub color: Color,
+}
+
 /// Paints a box shadow per CSS-BACKGROUNDS.
 #[derive(Clone, HeapSizeOf)]
 pub struct BoxShadowDisplayItem {
@@ -1016,7 +1069,7 @@ pub struct BoxShadowDisplayItem {
 }
 
 /// How a box shadow should be clipped.
-#[derive(Clone, Copy, Debug, PartialEq, HeapSizeOf)]
+#[derive(Clone, Copy, Debug, PartialEq, HeapSizeOf, RustcEncodable, RustcDecodable)]
 pub enum BoxShadowClipMode {
     /// No special clipping should occur. This is used for (shadowed) text decorations.
     None,
@@ -1072,6 +1125,11 @@ impl DisplayItem {
                 // brush instead.
                 debug!("Drawing image at {:?}.", image_item.base.bounds);
 
+                let image = match image_item.animation {
+                    Some(ref animation) => animation.frame_at(paint_context.animation_time),
+                    None => image_item.image.clone(),
+                };
+
                 let mut y_offset = Au(0);
                 while y_offset < image_item.base.bounds.size.height {
                     let mut x_offset = Au(0);
@@ -1082,7 +1140,7 @@ impl DisplayItem {
                         bounds.size = image_item.stretch_size;
 
                         paint_context.draw_image(&bounds,
-                                                 image_item.image.clone(),
+                                                 image.clone(),
                                                  image_item.image_rendering.clone());
 
                         x_offset = x_offset + image_item.stretch_size.width;
@@ -1111,6 +1169,10 @@ impl DisplayItem {
                 paint_context.draw_line(&line.base.bounds, line.color, line.style)
             }
 
+            DisplayItem::WavyLineClass(ref wavy_line) => {
+                paint_context.draw_wavy_line(&wavy_line.base.bounds, wavy_line.color)
+            }
+
             DisplayItem::BoxShadowClass(ref box_shadow) => {
                 paint_context.draw_box_shadow(&box_shadow.box_bounds,
                                               &box_shadow.offset,
@@ -1130,6 +1192,7 @@ impl DisplayItem {
             DisplayItem::BorderClass(ref border) => &border.base,
             DisplayItem::GradientClass(ref gradient) => &gradient.base,
             DisplayItem::LineClass(ref line) => &line.base,
+            DisplayItem::WavyLineClass(ref wavy_line) => &wavy_line.base,
             DisplayItem::BoxShadowClass(ref box_shadow) => &box_shadow.base,
         }
     }
@@ -1142,6 +1205,7 @@ impl DisplayItem {
             DisplayItem::BorderClass(ref mut border) => &mut border.base,
             DisplayItem::GradientClass(ref mut gradient) => &mut gradient.base,
             DisplayItem::LineClass(ref mut line) => &mut line.base,
+            DisplayItem::WavyLineClass(ref mut wavy_line) => &mut wavy_line.base,
             DisplayItem::BoxShadowClass(ref mut box_shadow) => &mut box_shadow.base,
         }
     }
@@ -1169,6 +1233,7 @@ impl fmt::Debug for DisplayItem {
                 DisplayItem::BorderClass(_) => "Border",
                 DisplayItem::GradientClass(_) => "Gradient",
                 DisplayItem::LineClass(_) => "Line",
+                DisplayItem::WavyLineClass(_) => "WavyLine",
                 DisplayItem::BoxShadowClass(_) => "BoxShadow",
             },
             self.base().bounds,
@@ -0,0 +1,653 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+//! Encoding of display lists, so that layout running in a content process can send them to the
+//! paint task in the parent process.
+//!
+//! Images and text runs are shared between display lists, and are much too big to send with each
+//! of them. The task that sends display lists sends each one once, naming it by an id afterwards,
+//! and after each display list tells the receiving task which ids no display list uses anymore.
+//! Both ends keep their table in thread-local storage, so each pair of tasks has its own. Fonts
+//! are sent as their identifiers, and looked up among the templates that the font cache handed
+//! to the content process.
+
+use display_list::{BorderDisplayItem, BoxShadowDisplayItem, GradientDisplayItem};
+use display_list::{ImageDisplayItem, LineDisplayItem, SolidColorDisplayItem, StackingContext};
+use display_list::{TextDisplayItem, WavyLineDisplayItem};
+use paint_task::PaintLayer;
+use platform::font_template::FontTemplateData;
+use text::TextRun;
+
+use azure::azure::AzFloat;
+use azure::azure_hl::{Color, GradientStop};
+use euclid::{Matrix4, SideOffsets2D};
+use gfx_traits::color;
+use net_traits::image::base::{AnimatedImage, Image, decode_image, encode_image};
+use rustc_serialize::{Decodable, Decoder, Encodable, Encoder};
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+
+/// The font templates that the font cache has handed to a content process, by identifier.
+pub type FontTemplates = Arc<Mutex<HashMap<String, Arc<FontTemplateData>>>>;
+
+/// Data that display lists share, which is sent once and named by its id after that.
+#[derive(Clone)]
+enum SharedData {
+    Image(Arc<Image>),
+    Animation(Arc<AnimatedImage>),
+    TextRun(Arc<Box<TextRun>>),
+}
+
+struct SentData {
+    id: u32,
+    /// Kept so that the address isn't taken by other data while the id names it.
+    #[allow(dead_code)]
+    data: SharedData,
+    /// Whether a display list sent since the last sweep uses the data.
+    used: bool,
+}
+
+/// The shared data that display lists sent from a task have used, by address.
+struct SentDataTable {
+    entries: HashMap<usize, SentData>,
+    next_id: u32,
+}
+
+impl SentDataTable {
+    /// Returns the id of the data at `address`, and whether it has to be sent along with it.
+    fn use_data(&mut self, address: usize, data: SharedData) -> (u32, bool) {
+        if let Some(entry) = self.entries.get_mut(&address) {
+            entry.used = true;
+            return (entry.id, false)
+        }
+        let id = self.next_id;
+        self.next_id += 1;
+        self.entries.insert(address, SentData {
+            id: id,
+            data: data,
+            used: true,
+        });
+        (id, true)
+    }
+}
+
+thread_local!(static SENT_DATA: RefCell<SentDataTable> = RefCell::new(SentDataTable {
+    entries: HashMap::new(),
+    next_id: 0,
+}));
+thread_local!(static RECEIVED_DATA: RefCell<HashMap<u32, SharedData>> =
+                  RefCell::new(HashMap::new()));
+thread_local!(static FONT_TEMPLATES: RefCell<Option<FontTemplates>> = RefCell::new(None));
+
+/// Forgets the shared data that no display list sent from this task since the last sweep uses,
+/// and returns its ids, which the receiving task should be given to `forget_received_data`.
+pub fn sweep_sent_data() -> Vec<u32> {
+    SENT_DATA.with(|sent_data| {
+        let mut sent_data = sent_data.borrow_mut();
+        let unused: Vec<usize> = sent_data.entries.iter().filter(|&(_, entry)| {
+            !entry.used
+        }).map(|(&address, _)| address).collect();
+        let mut ids = vec!();
+        for address in unused.iter() {
+            ids.push(sent_data.entries.remove(address).unwrap().id);
+        }
+        for entry in sent_data.entries.values_mut() {
+            entry.used = false;
+        }
+        ids
+    })
+}
+
+/// Forgets the shared data that the sending task has swept.
+pub fn forget_received_data(ids: &[u32]) {
+    RECEIVED_DATA.with(|received_data| {
+        let mut received_data = received_data.borrow_mut();
+        for id in ids.iter() {
+            received_data.remove(id);
+        }
+    })
+}
+
+/// Sets the font templates that text runs received on this task are drawn with.
+pub fn set_font_templates(font_templates: FontTemplates) {
+    FONT_TEMPLATES.with(|templates| *templates.borrow_mut() = Some(font_templates));
+}
+
+fn encode_shared_data<S, F>(address: usize, data: SharedData, s: &mut S, encode_data: F)
+                            -> Result<(), S::Error>
+                            where S: Encoder, F: FnOnce(&mut S) -> Result<(), S::Error> {
+    let (id, new) = SENT_DATA.with(|sent_data| sent_data.borrow_mut().use_data(address, data));
+    s.emit_struct("SharedData", 2, |s| {
+        try!(s.emit_struct_field("id", 0, |s| id.encode(s)));
+        s.emit_struct_field("data", 1, |s| {
+            s.emit_option(|s| {
+                if new {
+                    s.emit_option_some(encode_data)
+                } else {
+                    s.emit_option_none()
+                }
+            })
+        })
+    })
+}
+
+fn decode_shared_data<D, F>(d: &mut D, decode_data: F) -> Result<SharedData, D::Error>
+                            where D: Decoder, F: Fn(&mut D) -> Result<SharedData, D::Error> {
+    d.read_struct("SharedData", 2, |d| {
+        let id: u32 = try!(d.read_struct_field("id", 0, Decodable::decode));
+        let data = try!(d.read_struct_field("data", 1, |d| {
+            d.read_option(|d, present| {
+                if present {
+                    decode_data(d).map(Some)
+                } else {
+                    Ok(None)
+                }
+            })
+        }));
+        match data {
+            Some(data) => {
+                RECEIVED_DATA.with(|received_data| {
+                    received_data.borrow_mut().insert(id, data.clone())
+                });
+                Ok(data)
+            }
+            None => {
+                match RECEIVED_DATA.with(|received_data| received_data.borrow().get(&id).cloned()) {
+                    Some(data) => Ok(data),
+                    None => Err(d.error("the display list names data that wasn't sent")),
+                }
+            }
+        }
+    })
+}
+
+fn encode_image_data<S: Encoder>(image: &Arc<Image>, s: &mut S) -> Result<(), S::Error> {
+    let address = &**image as *const Image as usize;
+    encode_shared_data(address, SharedData::Image(image.clone()), s, |s| encode_image(image, s))
+}
+
+fn decode_image_data<D: Decoder>(d: &mut D) -> Result<Arc<Image>, D::Error> {
+    match try!(decode_shared_data(d, |d| decode_image(d).map(|image| {
+        SharedData::Image(Arc::new(image))
+    }))) {
+        SharedData::Image(image) => Ok(image),
+        _ => Err(d.error("the display list names data that isn't an image")),
+    }
+}
+
+fn encode_animation_data<S: Encoder>(animation: &Arc<AnimatedImage>, s: &mut S)
+                                     -> Result<(), S::Error> {
+    let address = &**animation as *const AnimatedImage as usize;
+    encode_shared_data(address, SharedData::Animation(animation.clone()), s, |s| {
+        animation.encode(s)
+    })
+}
+
+fn decode_animation_data<D: Decoder>(d: &mut D) -> Result<Arc<AnimatedImage>, D::Error> {
+    match try!(decode_shared_data(d, |d| {
+        Decodable::decode(d).map(|animation| SharedData::Animation(Arc::new(animation)))
+    })) {
+        SharedData::Animation(animation) => Ok(animation),
+        _ => Err(d.error("the display list names data that isn't an animated image")),
+    }
+}
+
+fn encode_text_run_data<S: Encoder>(text_run: &Arc<Box<TextRun>>, s: &mut S)
+                                    -> Result<(), S::Error> {
+    let address = &***text_run as *const TextRun as usize;
+    encode_shared_data(address, SharedData::TextRun(text_run.clone()), s, |s| {
+        text_run.encode(s)
+    })
+}
+
+fn decode_text_run_data<D: Decoder>(d: &mut D) -> Result<Arc<Box<TextRun>>, D::Error> {
+    match try!(decode_shared_data(d, |d| {
+        Decodable::decode(d).map(|text_run| SharedData::TextRun(Arc::new(text_run)))
+    })) {
+        SharedData::TextRun(text_run) => Ok(text_run),
+        _ => Err(d.error("the display list names data that isn't a text run")),
+    }
+}
+
+// Azure's colors, gradient stops and matrices don't implement `Encodable` and `Decodable`, so
+// they are sent as their components.
+
+fn encode_color<S: Encoder>(color: &Color, s: &mut S) -> Result<(), S::Error> {
+    (color.r, color.g, color.b, color.a).encode(s)
+}
+
+fn decode_color<D: Decoder>(d: &mut D) -> Result<Color, D::Error> {
+    let (r, g, b, a): (AzFloat, AzFloat, AzFloat, AzFloat) = try!(Decodable::decode(d));
+    Ok(color::new(r, g, b, a))
+}
+
+fn encode_side_colors<S: Encoder>(colors: &SideOffsets2D<Color>, s: &mut S)
+                                  -> Result<(), S::Error> {
+    s.emit_seq(4, |s| {
+        try!(s.emit_seq_elt(0, |s| encode_color(&colors.top, s)));
+        try!(s.emit_seq_elt(1, |s| encode_color(&colors.right, s)));
+        try!(s.emit_seq_elt(2, |s| encode_color(&colors.bottom, s)));
+        s.emit_seq_elt(3, |s| encode_color(&colors.left, s))
+    })
+}
+
+fn decode_side_colors<D: Decoder>(d: &mut D) -> Result<SideOffsets2D<Color>, D::Error> {
+    d.read_seq(|d, length| {
+        if length != 4 {
+            return Err(d.error("a border has four sides"))
+        }
+        Ok(SideOffsets2D::new(try!(d.read_seq_elt(0, decode_color)),
+                              try!(d.read_seq_elt(1, decode_color)),
+                              try!(d.read_seq_elt(2, decode_color)),
+                              try!(d.read_seq_elt(3, decode_color))))
+    })
+}
+
+fn encode_side_offsets<T, S>(offsets: &SideOffsets2D<T>, s: &mut S) -> Result<(), S::Error>
+                             where T: Encodable, S: Encoder {
+    (&offsets.top, &offsets.right, &offsets.bottom, &offsets.left).encode(s)
+}
+
+fn decode_side_offsets<T, D>(d: &mut D) -> Result<SideOffsets2D<T>, D::Error>
+                             where T: Decodable, D: Decoder {
+    let (top, right, bottom, left) = try!(Decodable::decode(d));
+    Ok(SideOffsets2D {
+        top: top,
+        right: right,
+        bottom: bottom,
+        left: left,
+    })
+}
+
+fn encode_gradient_stops<S: Encoder>(stops: &[GradientStop], s: &mut S) -> Result<(), S::Error> {
+    s.emit_seq(stops.len(), |s| {
+        for (index, stop) in stops.iter().enumerate() {
+            try!(s.emit_seq_elt(index, |s| {
+                try!(stop.offset.encode(s));
+                encode_color(&stop.color, s)
+            }));
+        }
+        Ok(())
+    })
+}
+
+fn decode_gradient_stops<D: Decoder>(d: &mut D) -> Result<Vec<GradientStop>, D::Error> {
+    d.read_seq(|d, length| {
+        let mut stops = Vec::with_capacity(length);
+        for index in 0..length {
+            stops.push(try!(d.read_seq_elt(index, |d| {
+                Ok(GradientStop {
+                    offset: try!(Decodable::decode(d)),
+                    color: try!(decode_color(d)),
+                })
+            })));
+        }
+        Ok(stops)
+    })
+}
+
+fn encode_matrix<S: Encoder>(matrix: &Matrix4, s: &mut S) -> Result<(), S::Error> {
+    let components = [
+        matrix.m11, matrix.m12, matrix.m13, matrix.m14,
+        matrix.m21, matrix.m22, matrix.m23, matrix.m24,
+        matrix.m31, matrix.m32, matrix.m33, matrix.m34,
+        matrix.m41, matrix.m42, matrix.m43, matrix.m44,
+    ];
+    components[..].encode(s)
+}
+
+fn decode_matrix<D: Decoder>(d: &mut D) -> Result<Matrix4, D::Error> {
+    let m: Vec<f32> = try!(Decodable::decode(d));
+    if m.len() != 16 {
+        return Err(d.error("a matrix has sixteen components"))
+    }
+    Ok(Matrix4 {
+        m11: m[0], m12: m[1], m13: m[2], m14: m[3],
+        m21: m[4], m22: m[5], m23: m[6], m24: m[7],
+        m31: m[8], m32: m[9], m33: m[10], m34: m[11],
+        m41: m[12], m42: m[13], m43: m[14], m44: m[15],
+    })
+}
+
+impl Encodable for StackingContext {
+    fn encode<S: Encoder>(&self, s: &mut S) -> Result<(), S::Error> {
+        s.emit_struct("StackingContext", 10, |s| {
+            try!(s.emit_struct_field("display_list", 0, |s| self.display_list.encode(s)));
+            try!(s.emit_struct_field("layer", 1, |s| self.layer.encode(s)));
+            try!(s.emit_struct_field("bounds", 2, |s| self.bounds.encode(s)));
+            try!(s.emit_struct_field("overflow", 3, |s| self.overflow.encode(s)));
+            try!(s.emit_struct_field("z_index", 4, |s| self.z_index.encode(s)));
+            try!(s.emit_struct_field("filters", 5, |s| self.filters.encode(s)));
+            try!(s.emit_struct_field("blend_mode", 6, |s| self.blend_mode.encode(s)));
+            try!(s.emit_struct_field("transform", 7, |s| encode_matrix(&self.transform, s)));
+            try!(s.emit_struct_field("perspective", 8, |s| encode_matrix(&self.perspective, s)));
+            s.emit_struct_field("establishes_3d_context", 9, |s| {
+                self.establishes_3d_context.encode(s)
+            })
+        })
+    }
+}
+
+impl Decodable for StackingContext {
+    fn decode<D: Decoder>(d: &mut D) -> Result<StackingContext, D::Error> {
+        d.read_struct("StackingContext", 10, |d| {
+            Ok(StackingContext {
+                display_list: try!(d.read_struct_field("display_list", 0, Decodable::decode)),
+                layer: try!(d.read_struct_field("layer", 1, Decodable::decode)),
+                bounds: try!(d.read_struct_field("bounds", 2, Decodable::decode)),
+                overflow: try!(d.read_struct_field("overflow", 3, Decodable::decode)),
+                z_index: try!(d.read_struct_field("z_index", 4, Decodable::decode)),
+                filters: try!(d.read_struct_field("filters", 5, Decodable::decode)),
+                blend_mode: try!(d.read_struct_field("blend_mode", 6, Decodable::decode)),
+                transform: try!(d.read_struct_field("transform", 7, decode_matrix)),
+                perspective: try!(d.read_struct_field("perspective", 8, decode_matrix)),
+                establishes_3d_context: try!(d.read_struct_field("establishes_3d_context", 9,
+                                                                 Decodable::decode)),
+            })
+        })
+    }
+}
+
+impl Encodable for PaintLayer {
+    fn encode<S: Encoder>(&self, s: &mut S) -> Result<(), S::Error> {
+        s.emit_struct("PaintLayer", 3, |s| {
+            try!(s.emit_struct_field("id", 0, |s| self.id.encode(s)));
+            try!(s.emit_struct_field("background_color", 1, |s| {
+                encode_color(&self.background_color, s)
+            }));
+            s.emit_struct_field("scroll_policy", 2, |s| self.scroll_policy.encode(s))
+        })
+    }
+}
+
+impl Decodable for PaintLayer {
+    fn decode<D: Decoder>(d: &mut D) -> Result<PaintLayer, D::Error> {
+        d.read_struct("PaintLayer", 3, |d| {
+            Ok(PaintLayer {
+                id: try!(d.read_struct_field("id", 0, Decodable::decode)),
+                background_color: try!(d.read_struct_field("background_color", 1, decode_color)),
+                scroll_policy: try!(d.read_struct_field("scroll_policy", 2, Decodable::decode)),
+            })
+        })
+    }
+}
+
+impl Encodable for SolidColorDisplayItem {
+    fn encode<S: Encoder>(&self, s: &mut S) -> Result<(), S::Error> {
+        s.emit_struct("SolidColorDisplayItem", 2, |s| {
+            try!(s.emit_struct_field("base", 0, |s| self.base.encode(s)));
+            s.emit_struct_field("color", 1, |s| encode_color(&self.color, s))
+        })
+    }
+}
+
+impl Decodable for SolidColorDisplayItem {
+    fn decode<D: Decoder>(d: &mut D) -> Result<SolidColorDisplayItem, D::Error> {
+        d.read_struct("SolidColorDisplayItem", 2, |d| {
+            Ok(SolidColorDisplayItem {
+                base: try!(d.read_struct_field("base", 0, Decodable::decode)),
+                color: try!(d.read_struct_field("color", 1, decode_color)),
+            })
+        })
+    }
+}
+
+impl Encodable for TextDisplayItem {
+    fn encode<S: Encoder>(&self, s: &mut S) -> Result<(), S::Error> {
+        s.emit_struct("TextDisplayItem", 10, |s| {
+            try!(s.emit_struct_field("base", 0, |s| self.base.encode(s)));
+            try!(s.emit_struct_field("text_run", 1, |s| encode_text_run_data(&self.text_run, s)));
+            try!(s.emit_struct_field("range", 2, |s| self.range.encode(s)));
+            try!(s.emit_struct_field("justification_space", 3, |s| {
+                self.justification_space.encode(s)
+            }));
+            try!(s.emit_struct_field("text_color", 4, |s| encode_color(&self.text_color, s)));
+            try!(s.emit_struct_field("stroke_color", 5, |s| encode_color(&self.stroke_color, s)));
+            try!(s.emit_struct_field("stroke_width", 6, |s| self.stroke_width.encode(s)));
+            try!(s.emit_struct_field("baseline_origin", 7, |s| self.baseline_origin.encode(s)));
+            try!(s.emit_struct_field("orientation", 8, |s| self.orientation.encode(s)));
+            s.emit_struct_field("blur_radius", 9, |s| self.blur_radius.encode(s))
+        })
+    }
+}
+
+impl Decodable for TextDisplayItem {
+    fn decode<D: Decoder>(d: &mut D) -> Result<TextDisplayItem, D::Error> {
+        d.read_struct("TextDisplayItem", 10, |d| {
+            Ok(TextDisplayItem {
+                base: try!(d.read_struct_field("base", 0, Decodable::decode)),
+                text_run: try!(d.read_struct_field("text_run", 1, decode_text_run_data)),
+                range: try!(d.read_struct_field("range", 2, Decodable::decode)),
+                justification_space: try!(d.read_struct_field("justification_space", 3,
+                                                              Decodable::decode)),
+                text_color: try!(d.read_struct_field("text_color", 4, decode_color)),
+                stroke_color: try!(d.read_struct_field("stroke_color", 5, decode_color)),
+                stroke_width: try!(d.read_struct_field("stroke_width", 6, Decodable::decode)),
+                baseline_origin: try!(d.read_struct_field("baseline_origin", 7,
+                                                          Decodable::decode)),
+                orientation: try!(d.read_struct_field("orientation", 8, Decodable::decode)),
+                blur_radius: try!(d.read_struct_field("blur_radius", 9, Decodable::decode)),
+            })
+        })
+    }
+}
+
+impl Encodable for ImageDisplayItem {
+    fn encode<S: Encoder>(&self, s: &mut S) -> Result<(), S::Error> {
+        s.emit_struct("ImageDisplayItem", 5, |s| {
+            try!(s.emit_struct_field("base", 0, |s| self.base.encode(s)));
+            try!(s.emit_struct_field("image", 1, |s| encode_image_data(&self.image, s)));
+            try!(s.emit_struct_field("animation", 2, |s| {
+                s.emit_option(|s| {
+                    match self.animation {
+                        Some(ref animation) => {
+                            s.emit_option_some(|s| encode_animation_data(animation, s))
+                        }
+                        None => s.emit_option_none(),
+                    }
+                })
+            }));
+            try!(s.emit_struct_field("stretch_size", 3, |s| self.stretch_size.encode(s)));
+            s.emit_struct_field("image_rendering", 4, |s| self.image_rendering.encode(s))
+        })
+    }
+}
+
+impl Decodable for ImageDisplayItem {
+    fn decode<D: Decoder>(d: &mut D) -> Result<ImageDisplayItem, D::Error> {
+        d.read_struct("ImageDisplayItem", 5, |d| {
+            Ok(ImageDisplayItem {
+                base: try!(d.read_struct_field("base", 0, Decodable::decode)),
+                image: try!(d.read_struct_field("image", 1, decode_image_data)),
+                animation: try!(d.read_struct_field("animation", 2, |d| {
+                    d.read_option(|d, present| {
+                        if present {
+                            decode_animation_data(d).map(Some)
+                        } else {
+                            Ok(None)
+                        }
+                    })
+                })),
+                stretch_size: try!(d.read_struct_field("stretch_size", 3, Decodable::decode)),
+                image_rendering: try!(d.read_struct_field("image_rendering", 4,
+                                                          Decodable::decode)),
+            })
+        })
+    }
+}
+
+impl Encodable for GradientDisplayItem {
+    fn encode<S: Encoder>(&self, s: &mut S) -> Result<(), S::Error> {
+        s.emit_struct("GradientDisplayItem", 4, |s| {
+            try!(s.emit_struct_field("base", 0, |s| self.base.encode(s)));
+            try!(s.emit_struct_field("start_point", 1, |s| self.start_point.encode(s)));
+            try!(s.emit_struct_field("end_point", 2, |s| self.end_point.encode(s)));
+            s.emit_struct_field("stops", 3, |s| encode_gradient_stops(&self.stops, s))
+        })
+    }
+}
+
+impl Decodable for GradientDisplayItem {
+    fn decode<D: Decoder>(d: &mut D) -> Result<GradientDisplayItem, D::Error> {
+        d.read_struct("GradientDisplayItem", 4, |d| {
+            Ok(GradientDisplayItem {
+                base: try!(d.read_struct_field("base", 0, Decodable::decode)),
+                start_point: try!(d.read_struct_field("start_point", 1, Decodable::decode)),
+                end_point: try!(d.read_struct_field("end_point", 2, Decodable::decode)),
+                stops: try!(d.read_struct_field("stops", 3, decode_gradient_stops)),
+            })
+        })
+    }
+}
+
+impl Encodable for BorderDisplayItem {
+    fn encode<S: Encoder>(&self, s: &mut S) -> Result<(), S::Error> {
+        s.emit_struct("BorderDisplayItem", 5, |s| {
+            try!(s.emit_struct_field("base", 0, |s| self.base.encode(s)));
+            try!(s.emit_struct_field("border_widths", 1, |s| {
+                encode_side_offsets(&self.border_widths, s)
+            }));
+            try!(s.emit_struct_field("color", 2, |s| encode_side_colors(&self.color, s)));
+            try!(s.emit_struct_field("style", 3, |s| encode_side_offsets(&self.style, s)));
+            s.emit_struct_field("radius", 4, |s| self.radius.encode(s))
+        })
+    }
+}
+
+impl Decodable for BorderDisplayItem {
+    fn decode<D: Decoder>(d: &mut D) -> Result<BorderDisplayItem, D::Error> {
+        d.read_struct("BorderDisplayItem", 5, |d| {
+            Ok(BorderDisplayItem {
+                base: try!(d.read_struct_field("base", 0, Decodable::decode)),
+                border_widths: try!(d.read_struct_field("border_widths", 1,
+                                                        decode_side_offsets)),
+                color: try!(d.read_struct_field("color", 2, decode_side_colors)),
+                style: try!(d.read_struct_field("style", 3, decode_side_offsets)),
+                radius: try!(d.read_struct_field("radius", 4, Decodable::decode)),
+            })
+        })
+    }
+}
+
+impl Encodable for LineDisplayItem {
+    fn encode<S: Encoder>(&self, s: &mut S) -> Result<(), S::Error> {
+        s.emit_struct("LineDisplayItem", 3, |s| {
+            try!(s.emit_struct_field("base", 0, |s| self.base.encode(s)));
+            try!(s.emit_struct_field("color", 1, |s| encode_color(&self.color, s)));
+            s.emit_struct_field("style", 2, |s| self.style.encode(s))
+        })
+    }
+}
+
+impl Decodable for LineDisplayItem {
+    fn decode<D: Decoder>(d: &mut D) -> Result<LineDisplayItem, D::Error> {
+        d.read_struct("LineDisplayItem", 3, |d| {
+            Ok(LineDisplayItem {
+                base: try!(d.read_struct_field("base", 0, Decodable::decode)),
+                color: try!(d.read_struct_field("color", 1, decode_color)),
+                style: try!(d.read_struct_field("style", 2, Decodable::decode)),
+            })
+        })
+    }
+}
+
+impl Encodable for WavyLineDisplayItem {
+    fn encode<S: Encoder>(&self, s: &mut S) -> Result<(), S::Error> {
+        s.emit_struct("WavyLineDisplayItem", 2, |s| {
+            try!(s.emit_struct_field("base", 0, |s| self.base.encode(s)));
+            s.emit_struct_field("color", 1, |s| encode_color(&self.color, s))
+        })
+    }
+}
+
+impl Decodable for WavyLineDisplayItem {
+    fn decode<D: Decoder>(d: &mut D) -> Result<WavyLineDisplayItem, D::Error> {
+        d.read_struct("WavyLineDisplayItem", 2, |d| {
+            Ok(WavyLineDisplayItem {
+                base: try!(d.read_struct_field("base", 0, Decodable::decode)),
+                color: try!(d.read_struct_field("color", 1, decode_color)),
+            })
+        })
+    }
+}
+
+impl Encodable for BoxShadowDisplayItem {
+    fn encode<S: Encoder>(&self, s: &mut S) -> Result<(), S::Error> {
+        s.emit_struct("BoxShadowDisplayItem", 7, |s| {
+            try!(s.emit_struct_field("base", 0, |s| self.base.encode(s)));
+            try!(s.emit_struct_field("box_bounds", 1, |s| self.box_bounds.encode(s)));
+            try!(s.emit_struct_field("offset", 2, |s| self.offset.encode(s)));
+            try!(s.emit_struct_field("color", 3, |s| encode_color(&self.color, s)));
+            try!(s.emit_struct_field("blur_radius", 4, |s| self.blur_radius.encode(s)));
+            try!(s.emit_struct_field("spread_radius", 5, |s| self.spread_radius.encode(s)));
+            s.emit_struct_field("clip_mode", 6, |s| self.clip_mode.encode(s))
+        })
+    }
+}
+
+impl Decodable for BoxShadowDisplayItem {
+    fn decode<D: Decoder>(d: &mut D) -> Result<BoxShadowDisplayItem, D::Error> {
+        d.read_struct("BoxShadowDisplayItem", 7, |d| {
+            Ok(BoxShadowDisplayItem {
+                base: try!(d.read_struct_field("base", 0, Decodable::decode)),
+                box_bounds: try!(d.read_struct_field("box_bounds", 1, Decodable::decode)),
+                offset: try!(d.read_struct_field("offset", 2, Decodable::decode)),
+                color: try!(d.read_struct_field("color", 3, decode_color)),
+                blur_radius: try!(d.read_struct_field("blur_radius", 4, Decodable::decode)),
+                spread_radius: try!(d.read_struct_field("spread_radius", 5, Decodable::decode)),
+                clip_mode: try!(d.read_struct_field("clip_mode", 6, Decodable::decode)),
+            })
+        })
+    }
+}
+
+impl Encodable for TextRun {
+    fn encode<S: Encoder>(&self, s: &mut S) -> Result<(), S::Error> {
+        s.emit_struct("TextRun", 10, |s| {
+            try!(s.emit_struct_field("text", 0, |s| self.text.encode(s)));
+            try!(s.emit_struct_field("font_template", 1, |s| {
+                s.emit_str(&self.font_template.identifier)
+            }));
+            try!(s.emit_struct_field("actual_pt_size", 2, |s| self.actual_pt_size.encode(s)));
+            try!(s.emit_struct_field("font_metrics", 3, |s| self.font_metrics.encode(s)));
+            try!(s.emit_struct_field("hyphen_advance", 4, |s| self.hyphen_advance.encode(s)));
+            try!(s.emit_struct_field("vertical", 5, |s| self.vertical.encode(s)));
+            try!(s.emit_struct_field("options", 6, |s| self.options.encode(s)));
+            try!(s.emit_struct_field("glyphs", 7, |s| self.glyphs.encode(s)));
+            try!(s.emit_struct_field("leading_marks", 8, |s| self.leading_marks.encode(s)));
+            s.emit_struct_field("base_advance", 9, |s| self.base_advance.encode(s))
+        })
+    }
+}
+
+impl Decodable for TextRun {
+    fn decode<D: Decoder>(d: &mut D) -> Result<TextRun, D::Error> {
+        d.read_struct("TextRun", 10, |d| {
+            let text = try!(d.read_struct_field("text", 0, Decodable::decode));
+            let identifier = try!(d.read_struct_field("font_template", 1, |d| d.read_str()));
+            let font_template = FONT_TEMPLATES.with(|templates| {
+                templates.borrow().as_ref().and_then(|templates| {
+                    templates.lock().unwrap().get(&identifier).cloned()
+                })
+            });
+            let font_template = match font_template {
+                Some(font_template) => font_template,
+                None => return Err(d.error(&format!("unknown font {}", identifier))),
+            };
+            Ok(TextRun {
+                text: text,
+                font_template: font_template,
+                actual_pt_size: try!(d.read_struct_field("actual_pt_size", 2, Decodable::decode)),
+                font_metrics: try!(d.read_struct_field("font_metrics", 3, Decodable::decode)),
+                hyphen_advance: try!(d.read_struct_field("hyphen_advance", 4, Decodable::decode)),
+                vertical: try!(d.read_struct_field("vertical", 5, Decodable::decode)),
+                options: try!(d.read_struct_field("options", 6, Decodable::decode)),
+                glyphs: try!(d.read_struct_field("glyphs", 7, Decodable::decode)),
+                leading_marks: try!(d.read_struct_field("leading_marks", 8, Decodable::decode)),
+                base_advance: try!(d.read_struct_field("base_advance", 9, Decodable::decode)),
+            })
+        })
+    }
+}
@@ -3,24 +3,32 @@
  * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
 
 use euclid::{Point2D, Rect, Size2D};
+use rustc_serialize::{Decodable, Decoder, Encodable, Encoder};
 use smallvec::SmallVec8;
 use std::borrow::ToOwned;
 use std::mem;
 use std::slice;
 use std::rc::Rc;
-use std::cell::RefCell;
-use util::cache::HashCache;
-use style::computed_values::{font_stretch, font_variant, font_weight};
+use std::cell::{Cell, RefCell};
+use std::collections::HashMap;
+use util::cache::{HashCache, LRUCache};
+use style::computed_values::{font_size_adjust, font_stretch, font_variant, font_weight};
+use style::font_face::UnicodeRange;
 use style::properties::style_structs::Font as FontStyle;
 use std::sync::Arc;
 
 use platform::font_context::FontContextHandle;
 use platform::font::{FontHandle, FontTable};
 use util::geometry::Au;
+use util::mem::HeapSizeOf;
 use text::glyph::{GlyphStore, GlyphId};
-use text::shaping::ShaperMethods;
-use text::Shaper;
+use text::hyphenation::HyphenationMode;
+use text::shaped_run_cache::{ShapedRunCache, ShapedRunKey};
+use text::shaping::{self, Shaper};
+use text::text_run::GlyphRun;
+use text::util::HanVariant;
 use font_template::FontTemplateDescriptor;
+use sfnt::{read_i16, read_u16, read_u32};
 use platform::font_template::FontTemplateData;
 
 // FontHandle encapsulates access to the platform's font API,
@@ -69,7 +77,7 @@ pub trait FontTableMethods {
     fn with_buffer<F>(&self, F) where F: FnOnce(*const u8, usize);
 }
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, RustcEncodable, RustcDecodable)]
 pub struct FontMetrics {
     pub underline_size:   Au,
     pub underline_offset: Au,
@@ -85,6 +93,18 @@ pub struct FontMetrics {
     pub line_gap:         Au,
 }
 
+impl FontMetrics {
+    /// Returns the ratio of the font's x-height to its size, or `None` if the font doesn't say
+    /// what its x-height is.
+    pub fn aspect(&self) -> Option<f32> {
+        if self.x_height > Au(0) && self.em_size > Au(0) {
+            Some(self.x_height.to_f32_px() / self.em_size.to_f32_px())
+        } else {
+            None
+        }
+    }
+}
+
 pub type SpecifiedFontStyle = FontStyle;
 pub type UsedFontStyle = FontStyle;
 
@@ -95,9 +115,104 @@ pub struct Font {
     pub descriptor: FontTemplateDescriptor,
     pub requested_pt_size: Au,
     pub actual_pt_size: Au,
-    pub shaper: Option<Shaper>,
+    /// The `font-size-adjust` that `actual_pt_size` was scaled from `requested_pt_size` for.
+    pub size_adjust: font_size_adjust::T,
+    pub shaper: Option<Box<Shaper>>,
     pub shape_cache: HashCache<ShapeCacheEntry,Arc<GlyphStore>>,
+    /// The glyph runs of the most recently made text runs, which are looked in before the text
+    /// is broken up and each part is looked for in `shape_cache`.
+    pub text_run_cache: LRUCache<TextRunCacheEntry, Arc<Vec<GlyphRun>>>,
     pub glyph_advance_cache: HashCache<u32,FractionalPixel>,
+    /// The text shaped by the fonts of every thread, which is looked in before shaping text
+    /// that isn't in `shape_cache`.
+    pub shaped_runs: Arc<ShapedRunCache>,
+    /// The font's vertical metrics, once they have been needed for vertical shaping.
+    pub vertical_metrics: Option<VerticalMetrics>,
+    /// The tags of the features in the font's `GSUB` table, once they have been needed.
+    pub gsub_features: Option<Vec<FontTableTag>>,
+    /// The characters the font is used for, from the `unicode-range` of its `@font-face` rule.
+    pub unicode_range: Vec<UnicodeRange>,
+}
+
+const GSUB: FontTableTag = 0x47535542;
+const HEAD: FontTableTag = 0x68656164;
+const VHEA: FontTableTag = 0x76686561;
+const VMTX: FontTableTag = 0x766D7478;
+const VORG: FontTableTag = 0x564F5247;
+
+/// The metrics of a font for vertical text, from its `vhea`, `vmtx` and `VORG` tables, in font
+/// units. Fonts without these tables have empty metrics.
+pub struct VerticalMetrics {
+    units_per_em: u16,
+    /// The vertical advance of each glyph. Glyphs past the end have the last advance.
+    advances: Vec<u16>,
+    /// The height of the vertical origin of glyphs above the baseline, and the exceptions.
+    default_origin_y: Option<i16>,
+    origins_y: HashMap<u16, i16>,
+}
+
+impl VerticalMetrics {
+    fn new(font: &Font) -> VerticalMetrics {
+        let mut metrics = VerticalMetrics {
+            units_per_em: 0,
+            advances: vec!(),
+            default_origin_y: None,
+            origins_y: HashMap::new(),
+        };
+        match font.table_bytes(HEAD) {
+            Some(ref head) if head.len() >= 20 => metrics.units_per_em = read_u16(head, 18),
+            _ => return metrics,
+        }
+
+        if let (Some(vhea), Some(vmtx)) = (font.table_bytes(VHEA), font.table_bytes(VMTX)) {
+            if vhea.len() >= 36 {
+                let count = read_u16(&vhea, 34) as usize;
+                if count * 4 <= vmtx.len() {
+                    metrics.advances = (0..count).map(|glyph| read_u16(&vmtx, glyph * 4)).collect();
+                }
+            }
+        }
+
+        if let Some(vorg) = font.table_bytes(VORG) {
+            if vorg.len() >= 8 {
+                let count = read_u16(&vorg, 6) as usize;
+                if 8 + count * 4 <= vorg.len() {
+                    metrics.default_origin_y = Some(read_i16(&vorg, 4));
+                    metrics.origins_y = (0..count).map(|index| {
+                        (read_u16(&vorg, 8 + index * 4), read_i16(&vorg, 10 + index * 4))
+                    }).collect();
+                }
+            }
+        }
+        metrics
+    }
+
+    fn advance(&self, glyph: GlyphId) -> Option<u16> {
+        if self.units_per_em == 0 {
+            return None
+        }
+        match self.advances.get(glyph as usize) {
+            Some(&advance) => Some(advance),
+            None => self.advances.last().cloned(),
+        }
+    }
+
+    fn origin_y(&self, glyph: GlyphId) -> Option<i16> {
+        if self.units_per_em == 0 || glyph > 0xFFFF {
+            return None
+        }
+        match self.origins_y.get(&(glyph as u16)) {
+            Some(&origin_y) => Some(origin_y),
+            None => self.default_origin_y,
+        }
+    }
+}
+
+impl HeapSizeOf for VerticalMetrics {
+    fn heap_size_of_children(&self) -> usize {
+        // FIXME: Measure `origins_y` once hash tables can be measured.
+        self.advances.heap_size_of_children()
+    }
 }
 
 bitflags! {
@@ -106,13 +221,60 @@ bitflags! {
         const IS_WHITESPACE_SHAPING_FLAG = 0x01,
         #[doc="Set if we are to ignore ligatures."]
         const IGNORE_LIGATURES_SHAPING_FLAG = 0x02,
-        #[doc="Set if we are to disable kerning."]
-        const DISABLE_KERNING_SHAPING_FLAG = 0x04
+        #[doc="Set if the text is set vertically, with upright glyphs stacked from top to bottom."]
+        const VERTICAL_SHAPING_FLAG = 0x08
+    }
+}
+
+impl Encodable for ShapingFlags {
+    fn encode<S: Encoder>(&self, s: &mut S) -> Result<(), S::Error> {
+        s.emit_u8(self.bits())
     }
 }
 
+impl Decodable for ShapingFlags {
+    fn decode<D: Decoder>(d: &mut D) -> Result<ShapingFlags, D::Error> {
+        Ok(ShapingFlags::from_bits_truncate(try!(d.read_u8())))
+    }
+}
+
+/// An OpenType feature to set when shaping, such as `liga` for standard ligatures or `tnum` for
+/// tabular numerals.
+#[derive(Clone, Copy, Eq, PartialEq, Hash, Debug, RustcEncodable, RustcDecodable)]
+pub struct FontFeature {
+    pub tag: FontTableTag,
+    /// 0 to turn the feature off, 1 to turn it on, or the index of an alternate glyph.
+    pub value: u32,
+}
+
+/// Whether to use the kerning information in fonts. Corresponds to the CSS `font-kerning`
+/// property.
+#[derive(Clone, Copy, Eq, PartialEq, Hash, Debug, RustcEncodable, RustcDecodable)]
+pub enum FontKerning {
+    /// Kern text if the shaper does so by default.
+    Auto,
+    /// Always kern text.
+    Normal,
+    /// Never kern text.
+    None,
+}
+
+/// Which kinds of ligatures to use. Each is turned on or off, or `None` to leave it as the font
+/// has it. Corresponds to the CSS `font-variant-ligatures` property.
+#[derive(Clone, Copy, Eq, PartialEq, Hash, Debug, RustcEncodable, RustcDecodable)]
+pub struct FontLigatures {
+    /// Common ligatures, such as `fi`, and contextual ligatures.
+    pub common: Option<bool>,
+    /// Discretionary ligatures, which fonts leave off by default.
+    pub discretionary: Option<bool>,
+    /// Historical ligatures, which fonts leave off by default.
+    pub historical: Option<bool>,
+    /// Contextual alternates, which change the shapes of glyphs to fit the glyphs around them.
+    pub contextual: Option<bool>,
+}
+
 /// Various options that control text shaping.
-#[derive(Clone, Eq, PartialEq, Hash, Copy)]
+#[derive(Clone, Eq, PartialEq, Hash, RustcEncodable, RustcDecodable)]
 pub struct ShapingOptions {
     /// Spacing to add between each letter. Corresponds to the CSS 2.1 `letter-spacing` property.
     /// NB: You will probably want to set the `IGNORE_LIGATURES_SHAPING_FLAG` if this is non-null.
@@ -121,6 +283,13 @@ pub struct ShapingOptions {
     pub word_spacing: Au,
     /// Various flags.
     pub flags: ShapingFlags,
+    /// Whether to kern the text.
+    pub kerning: FontKerning,
+    /// Which kinds of ligatures to use.
+    pub ligatures: FontLigatures,
+    /// OpenType features to set, in order. Later settings of a feature override earlier ones.
+    /// Corresponds to the CSS `font-feature-settings` and `font-variant-*` properties.
+    pub features: Arc<Vec<FontFeature>>,
 }
 
 /// An entry in the shape cache.
@@ -130,6 +299,16 @@ pub struct ShapeCacheEntry {
     options: ShapingOptions,
 }
 
+/// What the glyph runs of a text run were made from, besides the font. The options are part of
+/// it, so that text that is shaped with different options in turn, such as with and without
+/// letter spacing, is cached for each of them instead of replacing the other.
+#[derive(Clone, PartialEq)]
+pub struct TextRunCacheEntry {
+    pub text: String,
+    pub options: ShapingOptions,
+    pub hyphenation: HyphenationMode<'static>,
+}
+
 impl Font {
     pub fn shape_text(&mut self, text: &str, options: &ShapingOptions) -> Arc<GlyphStore> {
         self.make_shaper(options);
@@ -144,14 +323,31 @@ impl Font {
             return glyphs.clone();
         }
 
-        let mut glyphs = GlyphStore::new(text.chars().count(),
-                                         options.flags.contains(IS_WHITESPACE_SHAPING_FLAG));
-        shaper.as_ref().unwrap().shape_text(text, options, &mut glyphs);
+        let shared_key = ShapedRunKey {
+            font: self.handle.template().identifier.clone(),
+            pt_size: self.actual_pt_size,
+            text: text.to_owned(),
+            options: options.clone(),
+        };
+        let glyphs = match self.shaped_runs.find(&shared_key) {
+            Some(glyphs) => glyphs,
+            None => {
+                let is_whitespace = options.flags.contains(IS_WHITESPACE_SHAPING_FLAG);
+                let mut glyphs = GlyphStore::new(text.chars().count(), is_whitespace);
+                shaper.as_ref().unwrap().shape_text(text, options, &mut glyphs);
+                glyphs.set_grapheme_clusters(text);
+                glyphs.add_spacing(text,
+                                   options.letter_spacing.unwrap_or(Au(0)),
+                                   options.word_spacing);
+                let glyphs = Arc::new(glyphs);
+                self.shaped_runs.insert(shared_key, glyphs.clone());
+                glyphs
+            }
+        };
 
-        let glyphs = Arc::new(glyphs);
         self.shape_cache.insert(ShapeCacheEntry {
             text: text.to_owned(),
-            options: *options,
+            options: options.clone(),
         }, glyphs.clone());
         glyphs
     }
@@ -160,12 +356,12 @@ impl Font {
         // fast path: already created a shaper
         if let Some(ref mut shaper) = self.shaper {
             shaper.set_options(options);
-            return shaper
+            return &**shaper
         }
 
-        let shaper = Shaper::new(self, options);
+        let shaper = shaping::create_shaper(self, options);
         self.shaper = Some(shaper);
-        self.shaper.as_ref().unwrap()
+        &**self.shaper.as_ref().unwrap()
     }
 
     pub fn get_table_for_tag(&self, tag: FontTableTag) -> Option<FontTable> {
@@ -181,18 +377,94 @@ impl Font {
 
     #[inline]
     pub fn glyph_index(&self, codepoint: char) -> Option<GlyphId> {
-        let codepoint = match self.variant {
-            font_variant::T::small_caps => codepoint.to_uppercase().next().unwrap(), //FIXME: #5938
-            font_variant::T::normal => codepoint,
-        };
         self.handle.glyph_index(codepoint)
     }
 
+    /// Returns true if the font is used for the given character. It still may not have a glyph
+    /// for it.
+    pub fn covers(&self, character: char) -> bool {
+        self.unicode_range.iter().any(|range| range.contains(character))
+    }
+
+    /// Returns true if the font's `GSUB` table has the given feature, such as `smcp` for small
+    /// capitals.
+    pub fn has_gsub_feature(&mut self, tag: FontTableTag) -> bool {
+        if self.gsub_features.is_none() {
+            let mut features = vec!();
+            if let Some(gsub) = self.table_bytes(GSUB) {
+                if gsub.len() >= 10 {
+                    let feature_list = read_u16(&gsub, 6) as usize;
+                    if feature_list + 2 <= gsub.len() {
+                        let feature_count = read_u16(&gsub, feature_list) as usize;
+                        for index in 0..feature_count {
+                            let record = feature_list + 2 + index * 6;
+                            if record + 6 > gsub.len() {
+                                break
+                            }
+                            features.push(read_u32(&gsub, record));
+                        }
+                    }
+                }
+            }
+            self.gsub_features = Some(features);
+        }
+        self.gsub_features.as_ref().unwrap().contains(&tag)
+    }
+
     pub fn glyph_h_kerning(&mut self, first_glyph: GlyphId, second_glyph: GlyphId)
                            -> FractionalPixel {
         self.handle.glyph_h_kerning(first_glyph, second_glyph)
     }
 
+    /// Returns the distance that the pen moves down after the glyph in vertical text. Fonts
+    /// without vertical metrics advance by the height of their lines.
+    pub fn glyph_v_advance(&mut self, glyph: GlyphId) -> FractionalPixel {
+        let advance = self.vertical_metrics().advance(glyph);
+        match advance {
+            Some(advance) => advance as FractionalPixel * self.pixels_per_font_unit(),
+            None => (self.metrics.ascent + self.metrics.descent).to_f64_px(),
+        }
+    }
+
+    /// Returns the point of the glyph that is put on the pen in vertical text, relative to its
+    /// origin in horizontal text, with y pointing up. This is centered horizontally, and at the
+    /// height the font gives or else at the ascent.
+    pub fn glyph_v_origin(&mut self, glyph: GlyphId) -> Point2D<FractionalPixel> {
+        let origin_y = self.vertical_metrics().origin_y(glyph);
+        let y = match origin_y {
+            Some(origin_y) => origin_y as FractionalPixel * self.pixels_per_font_unit(),
+            None => self.metrics.ascent.to_f64_px(),
+        };
+        Point2D::new(self.glyph_h_advance(glyph) / 2.0, y)
+    }
+
+    fn vertical_metrics(&mut self) -> &VerticalMetrics {
+        if self.vertical_metrics.is_none() {
+            self.vertical_metrics = Some(VerticalMetrics::new(self));
+        }
+        self.vertical_metrics.as_ref().unwrap()
+    }
+
+    fn pixels_per_font_unit(&self) -> FractionalPixel {
+        match self.vertical_metrics {
+            Some(ref metrics) if metrics.units_per_em != 0 => {
+                self.actual_pt_size.to_f64_px() / metrics.units_per_em as FractionalPixel
+            }
+            _ => 0.0,
+        }
+    }
+
+    /// Returns a copy of the font's table with the given tag.
+    pub fn table_bytes(&self, tag: FontTableTag) -> Option<Vec<u8>> {
+        self.get_table_for_tag(tag).map(|table| {
+            let mut bytes = vec!();
+            table.with_buffer(|buffer, length| {
+                bytes = unsafe { slice::from_raw_parts(buffer, length) }.to_vec();
+            });
+            bytes
+        })
+    }
+
     pub fn glyph_h_advance(&mut self, glyph: GlyphId) -> FractionalPixel {
         let handle = &self.handle;
         self.glyph_advance_cache.find_or_create(&glyph, |glyph| {
@@ -204,16 +476,74 @@ impl Font {
     }
 }
 
+impl HeapSizeOf for Font {
+    fn heap_size_of_children(&self) -> usize {
+        // The glyph stores in `shape_cache` and `text_run_cache` are mostly shared with
+        // `shaped_runs`, which the font cache task reports, so only the text they are cached by
+        // and the lists of glyph runs that hold them are measured here. The hash tables and the
+        // platform font aren't measured.
+        let shape_cache = self.shape_cache.iter().fold(0, |size, (entry, _)| {
+            size + entry.text.heap_size_of_children()
+        });
+        let text_run_cache = self.text_run_cache.iter().fold(0, |size, &(ref entry, ref runs)| {
+            size + entry.text.heap_size_of_children() + runs.heap_size_of_children()
+        });
+        shape_cache + text_run_cache + self.vertical_metrics.heap_size_of_children() +
+            self.gsub_features.heap_size_of_children() +
+            self.unicode_range.heap_size_of_children()
+    }
+}
+
 pub struct FontGroup {
-    pub fonts: SmallVec8<Rc<RefCell<Font>>>,
+    /// The fonts of the families the style asks for, then those of the platform's fallback fonts
+    /// that have been needed so far. There is always at least one.
+    fonts: RefCell<SmallVec8<Rc<RefCell<Font>>>>,
+    /// The index in the platform's fallback list of the next family to take a fallback font
+    /// from, or `None` once the list has been used up. Fallback fonts are only created by
+    /// `FontContext::font_with_glyph` when no font before them has a glyph for a character.
+    pub next_fallback: Cell<Option<usize>>,
+    /// The forms of Han characters that fallback fonts are preferred for.
+    pub han_variant: Option<HanVariant>,
+    /// The style that the fallback fonts are created in.
+    pub descriptor: FontTemplateDescriptor,
+    pub pt_size: Au,
+    pub variant: font_variant::T,
+    pub size_adjust: font_size_adjust::T,
 }
 
 impl FontGroup {
-    pub fn new(fonts: SmallVec8<Rc<RefCell<Font>>>) -> FontGroup {
+    pub fn new(fonts: SmallVec8<Rc<RefCell<Font>>>,
+               han_variant: Option<HanVariant>,
+               descriptor: FontTemplateDescriptor,
+               pt_size: Au,
+               variant: font_variant::T,
+               size_adjust: font_size_adjust::T)
+               -> FontGroup {
         FontGroup {
-            fonts: fonts,
+            fonts: RefCell::new(fonts),
+            next_fallback: Cell::new(Some(0)),
+            han_variant: han_variant,
+            descriptor: descriptor,
+            pt_size: pt_size,
+            variant: variant,
+            size_adjust: size_adjust,
         }
     }
+
+    /// Returns the font with the given index, which must have been created already.
+    pub fn font(&self, index: usize) -> Rc<RefCell<Font>> {
+        self.fonts.borrow()[index].clone()
+    }
+
+    /// Returns the number of fonts that have been created so far.
+    pub fn len(&self) -> usize {
+        self.fonts.borrow().len()
+    }
+
+    /// Adds a fallback font after the fonts created so far.
+    pub fn push(&self, font: Rc<RefCell<Font>>) {
+        self.fonts.borrow_mut().push(font)
+    }
 }
 
 pub struct RunMetrics {
@@ -0,0 +1,128 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+//! The data of a font, which is mapped into memory from the font's file where it can be, so
+//! that large fonts only take up memory for the parts of them that are used.
+//!
+//! Reading a mapped file after it has been truncated raises `SIGBUS`, so only large files that
+//! only the system can change are mapped. Other fonts, such as ones in a user's own font
+//! directory, are read into memory.
+
+use libc::{c_void, size_t};
+use libc::{MAP_FAILED, MAP_PRIVATE, PROT_READ};
+use libc::{mmap, munmap};
+use std::fs::File;
+use std::io::{self, Read};
+use std::ops::Deref;
+use std::os::unix::fs::MetadataExt;
+use std::os::unix::io::AsRawFd;
+use std::ptr;
+use std::slice;
+use util::mem::HeapSizeOf;
+
+/// Font files smaller than this are read rather than mapped, since mapping them saves little.
+const MIN_MAPPED_FILE_SIZE: u64 = 1024 * 1024;
+
+/// The contents of a font file.
+pub enum FontBytes {
+    /// Data that was read into memory, such as a downloaded web font.
+    Owned(Vec<u8>),
+    /// A font file that is mapped into memory.
+    Mapped(MappedFile),
+}
+
+impl FontBytes {
+    /// Maps the font file at `path` into memory if it is large and only the system can change
+    /// it, and reads it otherwise, or if it can't be mapped.
+    pub fn from_file(path: &str) -> io::Result<FontBytes> {
+        let mut file = try!(File::open(path));
+        if try!(is_mappable(&file)) {
+            match MappedFile::new(&file) {
+                Ok(mapped_file) => return Ok(FontBytes::Mapped(mapped_file)),
+                Err(error) => debug!("Failed to map font file: path={} ({})", path, error),
+            }
+        }
+        let mut buffer = vec!();
+        try!(file.read_to_end(&mut buffer));
+        Ok(FontBytes::Owned(buffer))
+    }
+}
+
+/// Returns whether a font file is worth mapping and can't be truncated while it is mapped,
+/// which is when it is large and owned by root with no one else allowed to write to it.
+fn is_mappable(file: &File) -> io::Result<bool> {
+    let metadata = try!(file.metadata());
+    Ok(metadata.len() >= MIN_MAPPED_FILE_SIZE &&
+       metadata.uid() == 0 &&
+       metadata.mode() & 0o022 == 0)
+}
+
+impl Deref for FontBytes {
+    type Target = [u8];
+
+    fn deref(&self) -> &[u8] {
+        match *self {
+            FontBytes::Owned(ref bytes) => bytes,
+            FontBytes::Mapped(ref mapped_file) => mapped_file,
+        }
+    }
+}
+
+impl HeapSizeOf for FontBytes {
+    fn heap_size_of_children(&self) -> usize {
+        match *self {
+            FontBytes::Owned(ref bytes) => bytes.heap_size_of_children(),
+            // The pages of a mapped file belong to the page cache, not the heap.
+            FontBytes::Mapped(_) => 0,
+        }
+    }
+}
+
+/// A read-only, private mapping of the whole of a file.
+pub struct MappedFile {
+    ptr: *const u8,
+    len: usize,
+}
+
+// The mapping is read-only and lives until it is dropped.
+unsafe impl Send for MappedFile {}
+unsafe impl Sync for MappedFile {}
+
+impl MappedFile {
+    fn new(file: &File) -> io::Result<MappedFile> {
+        let len = try!(file.metadata()).len() as usize;
+        if len == 0 {
+            // Empty files can't be mapped.
+            return Err(io::Error::new(io::ErrorKind::Other, "the file is empty"))
+        }
+        let ptr = unsafe {
+            mmap(ptr::null_mut(), len as size_t, PROT_READ, MAP_PRIVATE, file.as_raw_fd(), 0)
+        };
+        if ptr == MAP_FAILED {
+            return Err(io::Error::last_os_error())
+        }
+        Ok(MappedFile {
+            ptr: ptr as *const u8,
+            len: len,
+        })
+    }
+}
+
+impl Deref for MappedFile {
+    type Target = [u8];
+
+    fn deref(&self) -> &[u8] {
+        unsafe {
+            slice::from_raw_parts(self.ptr, self.len)
+        }
+    }
+}
+
+impl Drop for MappedFile {
+    fn drop(&mut self) {
+        unsafe {
+            munmap(self.ptr as *mut c_void, self.len as size_t);
+        }
+    }
+}
@@ -6,19 +6,30 @@ use platform::font_list::get_available_families;
 use platform::font_list::get_system_default_family;
 use platform::font_list::get_variations_for_family;
 use platform::font_list::get_last_resort_font_families;
+use platform::font_list::get_local_font;
+use platform::font_list::get_substitute_families;
 use platform::font_context::FontContextHandle;
 
+use font_list_cache::FontListCache;
 use font_template::{FontTemplate, FontTemplateDescriptor};
-use net_traits::{ResourceTask, load_whole_resource};
+use net_traits::{LoadPriority, ResourceTask, load_whole_resource_with_priority};
 use platform::font_template::FontTemplateData;
+use profile_traits::mem::{Report, ReportUnits, Reporter, ReportsChan};
 use std::borrow::ToOwned;
 use std::collections::HashMap;
+use std::path::Path;
 use std::sync::Arc;
 use std::sync::mpsc::{Sender, Receiver, channel};
 use string_cache::Atom;
-use style::font_face::Source;
+use style::font_face::{Source, UnicodeRange};
+use text::shaped_run_cache::ShapedRunCache;
+use text::util::HanVariant;
+use util::mem::HeapSizeOf;
+use util::opts;
 use util::str::LowercaseString;
 use util::task::spawn_named;
+use url::Url;
+use woff;
 
 /// A list of font templates that make up a given font family.
 struct FontFamily {
@@ -32,52 +43,101 @@ impl FontFamily {
         }
     }
 
-    /// Find a font in this family that matches a given descriptor.
-    fn find_font_for_style<'a>(&'a mut self, desc: &FontTemplateDescriptor, fctx: &FontContextHandle)
+    /// Find a font in this family that matches a given descriptor. If `unicode_range` is given,
+    /// only the fonts that are used for that range of characters are considered.
+    fn find_font_for_style<'a>(&'a mut self,
+                               desc: &FontTemplateDescriptor,
+                               fctx: &FontContextHandle,
+                               unicode_range: Option<&[UnicodeRange]>)
                                -> Option<Arc<FontTemplateData>> {
         // TODO(Issue #189): optimize lookup for
         // regular/bold/italic/bolditalic with fixed offsets and a
         // static decision table for fallback between these values.
+        let mut closest: Option<(usize, (u32, u32, u32))> = None;
+        for (index, template) in self.templates.iter_mut().enumerate() {
+            if unicode_range.map_or(false, |range| template.unicode_range() != range) {
+                continue
+            }
+            if let Some(actual_desc) = template.descriptor(fctx) {
+                let distance = actual_desc.distance_from(desc);
+                if closest.map_or(true, |(_, closest_distance)| distance < closest_distance) {
+                    closest = Some((index, distance));
+                }
+            }
+        }
 
-        // TODO(Issue #190): if not in the fast path above, do
-        // expensive matching of weights, etc.
-        for template in self.templates.iter_mut() {
-            let maybe_template = template.get_if_matches(fctx, desc);
-            if maybe_template.is_some() {
-                return maybe_template;
+        // If there is no exact match for the descriptor, the closest font is used.
+        match closest {
+            Some((index, _)) => {
+                let template = &mut self.templates[index];
+                match template.get_if_matches(fctx, desc) {
+                    Some(data) => Some(data),
+                    None => template.get(),
+                }
             }
+            None => None,
         }
+    }
 
-        // If a request is made for a font family that exists,
-        // pick the first valid font in the family if we failed
-        // to find an exact match for the descriptor.
-        for template in self.templates.iter_mut() {
-            let maybe_template = template.get();
-            if maybe_template.is_some() {
-                return maybe_template;
+    /// Find a font in this family that matches a given descriptor for each of the distinct
+    /// `unicode-range`s of the `@font-face` rules that added fonts to it, in the order the
+    /// rules were added.
+    fn find_fonts_for_style(&mut self, desc: &FontTemplateDescriptor, fctx: &FontContextHandle)
+                            -> Vec<(Arc<FontTemplateData>, Vec<UnicodeRange>)> {
+        let mut unicode_ranges: Vec<Vec<UnicodeRange>> = vec!();
+        for template in self.templates.iter() {
+            if !unicode_ranges.iter().any(|range| &**range == template.unicode_range()) {
+                unicode_ranges.push(template.unicode_range().to_vec());
             }
         }
 
-        None
+        let mut fonts = vec!();
+        for unicode_range in unicode_ranges.into_iter() {
+            if let Some(font) = self.find_font_for_style(desc, fctx, Some(&*unicode_range)) {
+                fonts.push((font, unicode_range));
+            }
+        }
+        fonts
     }
 
-    fn add_template(&mut self, identifier: &str, maybe_data: Option<Vec<u8>>) {
-        for template in self.templates.iter() {
-            if template.identifier() == identifier {
+    fn add_template(&mut self,
+                    identifier: &str,
+                    maybe_data: Option<Vec<u8>>,
+                    unicode_range: &[UnicodeRange]) {
+        for template in self.templates.iter_mut() {
+            if template.identifier() == identifier && template.unicode_range() == unicode_range {
+                // A web font that was evicted can be given its data again.
+                if template.needs_reload() && maybe_data.is_some() {
+                    template.reload(maybe_data);
+                }
                 return;
             }
         }
 
-        let template = FontTemplate::new(identifier, maybe_data);
+        let template = FontTemplate::new(identifier, maybe_data, unicode_range.to_vec());
         self.templates.push(template);
     }
 }
 
+impl HeapSizeOf for FontFamily {
+    fn heap_size_of_children(&self) -> usize {
+        self.templates.heap_size_of_children()
+    }
+}
+
 /// Commands that the FontContext sends to the font cache task.
 pub enum Command {
     GetFontTemplate(String, FontTemplateDescriptor, Sender<Reply>),
-    GetLastResortFontTemplate(FontTemplateDescriptor, Sender<Reply>),
-    AddWebFont(Atom, Source, Sender<()>),
+    GetFallbackFontTemplate(FontTemplateDescriptor,
+                            Option<HanVariant>,
+                            usize,
+                            Sender<Option<(usize, Arc<FontTemplateData>)>>),
+    AddWebFont(Atom, Vec<Source>, Vec<UnicodeRange>, Sender<LowercaseString>, Option<Sender<()>>),
+    AddDownloadedWebFont(LowercaseString, Url, Vec<UnicodeRange>, Vec<u8>, Option<Sender<()>>),
+    /// A web font that was evicted finished downloading again, or failed to.
+    ReloadedWebFont(LowercaseString, String, Option<Vec<u8>>),
+    AddLocalFont(String, Vec<u8>, Sender<()>),
+    CollectReports(ReportsChan),
     Exit(Sender<()>),
 }
 
@@ -85,7 +145,7 @@ unsafe impl Send for Command {}
 
 /// Reply messages sent from the font cache task to the FontContext caller.
 pub enum Reply {
-    GetFontTemplateReply(Option<Arc<FontTemplateData>>),
+    GetFontTemplateReply(Vec<(Arc<FontTemplateData>, Vec<UnicodeRange>)>),
 }
 
 unsafe impl Send for Reply {}
@@ -94,11 +154,49 @@ unsafe impl Send for Reply {}
 /// font templates that are currently in use.
 struct FontCache {
     port: Receiver<Command>,
+    /// The channel the tasks that download web fonts send them back to the cache on.
+    chan: Sender<Command>,
     generic_fonts: HashMap<LowercaseString, LowercaseString>,
     local_families: HashMap<LowercaseString, FontFamily>,
+    /// The saved list of the local families and their fonts.
+    font_list: FontListCache,
+    /// The installed families that the platform substitutes for each family that isn't
+    /// installed and has been asked for, best first.
+    substitute_families: HashMap<LowercaseString, Vec<String>>,
     web_families: HashMap<LowercaseString, FontFamily>,
+    /// The senders that were given with the fonts of each web font family, which are sent the
+    /// family's name when one of its fonts has downloaded after it was first used, or has
+    /// downloaded again after it was evicted.
+    web_font_listeners: HashMap<LowercaseString, Vec<Sender<LowercaseString>>>,
     font_context: FontContextHandle,
     resource_task: ResourceTask,
+    /// The downloaded web fonts that are loaded, by URL.
+    loaded_web_fonts: HashMap<String, LoadedWebFont>,
+    /// The number of times web fonts have been added or looked up, which orders their uses.
+    web_font_uses: u64,
+    /// The text shaped by all of the font contexts, for memory reports.
+    shaped_runs: Arc<ShapedRunCache>,
+}
+
+/// A downloaded web font whose data the font cache holds.
+struct LoadedWebFont {
+    size: usize,
+    /// When the font was last added or looked up, from `FontCache::web_font_uses`.
+    last_used: u64,
+}
+
+/// Downloads a web font, and returns its data in the sfnt form the platform reads.
+fn load_web_font(resource_task: &ResourceTask, url: &Url) -> Result<Vec<u8>, &'static str> {
+    let maybe_resource = load_whole_resource_with_priority(resource_task,
+                                                           url.clone(),
+                                                           LoadPriority::Style);
+    // WOFF fonts are unpacked into the sfnt data the platform reads.
+    match maybe_resource {
+        Ok((_, ref bytes)) if woff::is_woff(bytes) => woff::decode_woff(bytes),
+        Ok((_, ref bytes)) if woff::is_woff2(bytes) => woff::decode_woff2(bytes),
+        Ok((_, bytes)) => Ok(bytes),
+        Err(_) => Err("couldn't load the resource"),
+    }
 }
 
 fn add_generic_font(generic_fonts: &mut HashMap<LowercaseString, LowercaseString>,
@@ -119,44 +217,115 @@ impl FontCache {
             match msg {
                 Command::GetFontTemplate(family, descriptor, result) => {
                     let family = LowercaseString::new(&family);
-                    let maybe_font_template = self.get_font_template(&family, &descriptor);
-                    result.send(Reply::GetFontTemplateReply(maybe_font_template)).unwrap();
+                    let font_templates = self.get_font_templates(&family, &descriptor);
+                    result.send(Reply::GetFontTemplateReply(font_templates)).unwrap();
                 }
-                Command::GetLastResortFontTemplate(descriptor, result) => {
-                    let font_template = self.get_last_resort_font_template(&descriptor);
-                    result.send(Reply::GetFontTemplateReply(Some(font_template))).unwrap();
+                Command::GetFallbackFontTemplate(descriptor, han_variant, index, result) => {
+                    let font_template = self.get_fallback_font_template(&descriptor,
+                                                                        han_variant,
+                                                                        index);
+                    result.send(font_template).unwrap();
                 }
-                Command::AddWebFont(family_name, src, result) => {
+                Command::AddWebFont(family_name, sources, unicode_range, listener, result) => {
                     let family_name = LowercaseString::new(&family_name);
                     if !self.web_families.contains_key(&family_name) {
                         let family = FontFamily::new();
                         self.web_families.insert(family_name.clone(), family);
+                        self.web_font_listeners.insert(family_name.clone(), vec!());
                     }
-
-                    match src {
-                        Source::Url(ref url_source) => {
-                            let url = &url_source.url;
-                            let maybe_resource = load_whole_resource(&self.resource_task, url.clone());
-                            match maybe_resource {
-                                Ok((_, bytes)) => {
-                                    let family = &mut self.web_families.get_mut(&family_name).unwrap();
-                                    family.add_template(&url.to_string(), Some(bytes));
-                                },
-                                Err(_) => {
-                                    debug!("Failed to load web font: family={:?} url={}", family_name, url);
-                                }
+                    self.web_font_listeners.get_mut(&family_name).unwrap().push(listener);
+
+                    // Fonts that are installed are used before any that would have to be
+                    // downloaded, and the first source that gives a font is the only one used.
+                    let found_local = sources.iter().any(|source| {
+                        match *source {
+                            Source::Local(ref name) => {
+                                self.add_local_web_font(&family_name, name, &unicode_range)
                             }
+                            Source::Url(_) => false,
                         }
-                        Source::Local(ref local_family_name) => {
-                            let family = &mut self.web_families.get_mut(&family_name).unwrap();
-                            get_variations_for_family(&local_family_name, |path| {
-                                family.add_template(&path, None);
-                            });
+                    });
+                    // Text is only laid out with fonts that are installed after this command has
+                    // been handled, so there is nothing to tell the listener.
+                    if found_local {
+                        if let Some(result) = result {
+                            let _ = result.send(());
+                        }
+                    } else {
+                        let urls = sources.into_iter().filter_map(|source| {
+                            match source {
+                                Source::Url(url_source) => Some(url_source.url),
+                                Source::Local(_) => None,
+                            }
+                        }).collect();
+                        self.download_web_font(family_name, urls, unicode_range, result);
+                    }
+                }
+                Command::AddDownloadedWebFont(family_name, url, unicode_range, bytes, result) => {
+                    let last_used = self.next_web_font_use();
+                    self.loaded_web_fonts.insert(url.to_string(), LoadedWebFont {
+                        size: bytes.len(),
+                        last_used: last_used,
+                    });
+                    self.web_families.get_mut(&family_name).unwrap().add_template(&url.to_string(),
+                                                                                  Some(bytes),
+                                                                                  &unicode_range);
+                    self.evict_web_fonts();
+                    // A layout task that waited for the font hasn't laid text out without it.
+                    match result {
+                        Some(result) => {
+                            let _ = result.send(());
                         }
+                        None => self.notify_web_font_listeners(&family_name),
                     }
+                }
+                Command::ReloadedWebFont(family_name, url, maybe_bytes) => {
+                    self.handle_reloaded_web_font(family_name, url, maybe_bytes);
+                }
+                Command::AddLocalFont(family_name, bytes, result) => {
+                    self.add_local_font(LowercaseString::new(&family_name), bytes);
                     result.send(()).unwrap();
                 }
+                Command::CollectReports(reports_chan) => {
+                    let mut reports: Vec<Report> = self.loaded_web_fonts.iter().map(|(url, font)| {
+                        Report {
+                            path: path!["font-cache", "web-fonts", format!("url({})", url)],
+                            units: ReportUnits::Bytes,
+                            size: font.size,
+                        }
+                    }).collect();
+                    // Web fonts are reported by URL above, so only the local fonts that are
+                    // loaded are reported by family.
+                    for (family_name, family) in self.local_families.iter() {
+                        let size = family.heap_size_of_children();
+                        if size > 0 {
+                            reports.push(Report {
+                                path: path!["font-cache", "local-fonts",
+                                            format!("family({})", &**family_name)],
+                                units: ReportUnits::Bytes,
+                                size: size,
+                            });
+                        }
+                    }
+                    reports.push(Report {
+                        path: path!["font-cache", "shaped-runs"],
+                        units: ReportUnits::Bytes,
+                        size: self.shaped_runs.heap_size_of_children(),
+                    });
+                    let stats = self.shaped_runs.stats();
+                    for &(name, count) in [("shaped-runs", stats.runs),
+                                           ("shaped-run-hits", stats.hits),
+                                           ("shaped-run-misses", stats.misses)].iter() {
+                        reports.push(Report {
+                            path: path![format!("font-cache-{}", name)],
+                            units: ReportUnits::Count,
+                            size: count,
+                        });
+                    }
+                    reports_chan.send(reports);
+                }
                 Command::Exit(result) => {
+                    self.font_list.save();
                     result.send(()).unwrap();
                     break;
                 }
@@ -164,15 +333,219 @@ impl FontCache {
         }
     }
 
+    /// Adds the installed font named by a `local()` source of an `@font-face` rule to a web
+    /// font family. Returns false if there is no such font. Only full names and PostScript
+    /// names match, not family names, as the spec requires.
+    /// http://dev.w3.org/csswg/css-fonts/#src-desc
+    fn add_local_web_font(&mut self,
+                          family_name: &LowercaseString,
+                          name: &str,
+                          unicode_range: &[UnicodeRange])
+                          -> bool {
+        match get_local_font(name) {
+            Some(path) => {
+                let family = self.web_families.get_mut(family_name).unwrap();
+                family.add_template(&path, None, unicode_range);
+                true
+            }
+            None => {
+                debug!("Failed to find local font: family={:?} name={}", family_name, name);
+                false
+            }
+        }
+    }
+
+    /// Adds a font from data in memory to a local family, creating the family if there is no
+    /// installed one of that name. The installed fonts of a family that fonts are added to this
+    /// way aren't used.
+    fn add_local_font(&mut self, family_name: LowercaseString, bytes: Vec<u8>) {
+        let decoded = if woff::is_woff(&bytes) {
+            woff::decode_woff(&bytes)
+        } else if woff::is_woff2(&bytes) {
+            woff::decode_woff2(&bytes)
+        } else {
+            Ok(bytes)
+        };
+        let bytes = match decoded {
+            Ok(bytes) => bytes,
+            Err(error) => {
+                debug!("Failed to decode local font: family={:?} ({})", family_name, error);
+                return
+            }
+        };
+
+        if !self.local_families.contains_key(&family_name) {
+            self.local_families.insert(family_name.clone(), FontFamily::new());
+        }
+        let family = self.local_families.get_mut(&family_name).unwrap();
+        let identifier = format!("memory:{}#{}", &*family_name, family.templates.len());
+        family.add_template(&identifier, Some(bytes), &[UnicodeRange::all()]);
+    }
+
+    /// Downloads a web font from the first of `urls` that gives one, on a task of its own so
+    /// that the font cache can answer other requests meanwhile, and then adds it to the family.
+    /// `result` is sent to once the font has been added, or none of the URLs gave a font.
+    fn download_web_font(&self,
+                         family_name: LowercaseString,
+                         urls: Vec<Url>,
+                         unicode_range: Vec<UnicodeRange>,
+                         result: Option<Sender<()>>) {
+        let resource_task = self.resource_task.clone();
+        let chan = self.chan.clone();
+        spawn_named("WebFontLoader".to_owned(), move || {
+            for url in urls.into_iter() {
+                match load_web_font(&resource_task, &url) {
+                    Ok(bytes) => {
+                        chan.send(Command::AddDownloadedWebFont(family_name,
+                                                                url,
+                                                                unicode_range,
+                                                                bytes,
+                                                                result)).unwrap();
+                        return
+                    }
+                    Err(error) => {
+                        debug!("Failed to load web font: family={:?} url={} ({})",
+                               family_name, url, error);
+                    }
+                }
+            }
+            if let Some(result) = result {
+                let _ = result.send(());
+            }
+        });
+    }
+
+    /// Tells the layout tasks that use a web font family that a font of it has been added, so
+    /// that they lay out the text in the family again.
+    fn notify_web_font_listeners(&mut self, family_name: &LowercaseString) {
+        // Layout tasks that have gone away aren't listening any more.
+        self.web_font_listeners.get_mut(family_name).unwrap().retain(|listener| {
+            listener.send(family_name.clone()).is_ok()
+        });
+    }
+
+    fn next_web_font_use(&mut self) -> u64 {
+        self.web_font_uses += 1;
+        self.web_font_uses
+    }
+
+    /// Starts downloading the fonts of a web font family that were evicted and are no longer in
+    /// use again, on a task of their own, and marks the fonts of the family as used. Until they
+    /// have downloaded, the family is used without them.
+    fn reload_web_fonts(&mut self, family_name: &LowercaseString) {
+        let mut evicted: Vec<String> = match self.web_families.get_mut(family_name) {
+            Some(family) => family.templates.iter_mut().filter(|template| {
+                template.needs_reload() && template.start_reload()
+            }).map(|template| template.identifier().to_owned()).collect(),
+            None => return,
+        };
+        // A font is added once for each `unicode-range` it is used for.
+        evicted.sort();
+        evicted.dedup();
+
+        for url in evicted.into_iter() {
+            let resource_task = self.resource_task.clone();
+            let chan = self.chan.clone();
+            let family_name = family_name.clone();
+            spawn_named("WebFontLoader".to_owned(), move || {
+                let maybe_bytes = match Url::parse(&url) {
+                    Ok(parsed_url) => load_web_font(&resource_task, &parsed_url).ok(),
+                    Err(_) => None,
+                };
+                // The font cache may have exited meanwhile.
+                let _ = chan.send(Command::ReloadedWebFont(family_name, url, maybe_bytes));
+            });
+        }
+
+        let last_used = self.next_web_font_use();
+        let family = self.web_families.get(family_name).unwrap();
+        for template in family.templates.iter() {
+            if let Some(font) = self.loaded_web_fonts.get_mut(template.identifier()) {
+                font.last_used = last_used;
+            }
+        }
+    }
+
+    /// Gives the data of an evicted web font that has downloaded again to its templates, and
+    /// tells the layout tasks that use its family that it can be used again.
+    fn handle_reloaded_web_font(&mut self,
+                                family_name: LowercaseString,
+                                url: String,
+                                maybe_bytes: Option<Vec<u8>>) {
+        {
+            let family = self.web_families.get_mut(&family_name).unwrap();
+            for template in family.templates.iter_mut() {
+                if template.identifier() == url {
+                    template.reload(maybe_bytes.clone());
+                }
+            }
+        }
+        let bytes = match maybe_bytes {
+            Some(bytes) => bytes,
+            None => {
+                debug!("Failed to reload web font: url={}", url);
+                return
+            }
+        };
+
+        let last_used = self.next_web_font_use();
+        self.loaded_web_fonts.insert(url, LoadedWebFont {
+            size: bytes.len(),
+            last_used: last_used,
+        });
+        self.notify_web_font_listeners(&family_name);
+        self.evict_web_fonts();
+    }
+
+    /// Unloads the least recently used web fonts until the ones that are loaded fit in
+    /// `opts::get().web_font_cache_size`. The data of fonts that are still in use stays in memory
+    /// until they aren't.
+    fn evict_web_fonts(&mut self) {
+        let mut size = self.loaded_web_fonts.values().fold(0, |size, font| size + font.size);
+        while size > opts::get().web_font_cache_size {
+            let oldest = self.loaded_web_fonts.iter().fold(None, |oldest, (url, font)| {
+                match oldest {
+                    Some((_, oldest_font)) if oldest_font.last_used <= font.last_used => oldest,
+                    _ => Some((url, font)),
+                }
+            });
+            let url: String = match oldest {
+                Some((url, _)) => url.clone(),
+                None => break,
+            };
+            size -= self.loaded_web_fonts.remove(&url).unwrap().size;
+            for family in self.web_families.values_mut() {
+                for template in family.templates.iter_mut() {
+                    if template.identifier() == url {
+                        template.unload();
+                    }
+                }
+            }
+            debug!("Evicted web font: url={}", url);
+        }
+    }
+
     fn refresh_local_families(&mut self) {
         self.local_families.clear();
-        get_available_families(|family_name| {
-            let family_name = LowercaseString::new(&family_name);
-            if !self.local_families.contains_key(&family_name) {
-                let family = FontFamily::new();
-                self.local_families.insert(family_name, family);
+        self.substitute_families.clear();
+        if self.font_list.is_empty() {
+            get_available_families(|family_name| {
+                let family_name = LowercaseString::new(&family_name);
+                if !self.local_families.contains_key(&family_name) {
+                    let family = FontFamily::new();
+                    self.local_families.insert(family_name, family);
+                }
+            });
+            let families = self.local_families.keys().map(|family_name| {
+                (**family_name).to_owned()
+            }).collect();
+            self.font_list.set_families(families);
+            self.font_list.save();
+        } else {
+            for family_name in self.font_list.families().iter() {
+                self.local_families.insert(LowercaseString::new(family_name), FontFamily::new());
             }
-        });
+        }
     }
 
     fn transform_family(&self, family: &LowercaseString) -> LowercaseString {
@@ -191,14 +564,28 @@ impl FontCache {
             let s = self.local_families.get_mut(family_name).unwrap();
 
             if s.templates.len() == 0 {
-                get_variations_for_family(family_name, |path| {
-                    s.add_template(&path, None);
-                });
+                let cached = match self.font_list.variations(family_name) {
+                    Some(variations) => {
+                        for path in variations.iter() {
+                            s.add_template(path, None, &[UnicodeRange::all()]);
+                        }
+                        true
+                    }
+                    None => false,
+                };
+                if !cached {
+                    let mut variations = vec!();
+                    get_variations_for_family(family_name, |path| {
+                        s.add_template(&path, None, &[UnicodeRange::all()]);
+                        variations.push(path);
+                    });
+                    self.font_list.set_variations(family_name, variations);
+                }
             }
 
             // TODO(Issue #192: handle generic font families, like 'serif' and 'sans-serif'.
             // if such family exists, try to match style to a font
-            let result = s.find_font_for_style(desc, &self.font_context);
+            let result = s.find_font_for_style(desc, &self.font_context, None);
             if result.is_some() {
                 return result;
             }
@@ -210,40 +597,80 @@ impl FontCache {
         }
     }
 
-    fn find_font_in_web_family<'a>(&'a mut self, family_name: &LowercaseString, desc: &FontTemplateDescriptor)
-                                -> Option<Arc<FontTemplateData>> {
+    fn find_fonts_in_web_family(&mut self,
+                                family_name: &LowercaseString,
+                                desc: &FontTemplateDescriptor)
+                                -> Vec<(Arc<FontTemplateData>, Vec<UnicodeRange>)> {
         if self.web_families.contains_key(family_name) {
             let family = self.web_families.get_mut(family_name).unwrap();
-            let maybe_font = family.find_font_for_style(desc, &self.font_context);
-            maybe_font
+            family.find_fonts_for_style(desc, &self.font_context)
         } else {
-            None
+            vec!()
         }
     }
 
-    fn get_font_template(&mut self, family: &LowercaseString, desc: &FontTemplateDescriptor)
-                            -> Option<Arc<FontTemplateData>> {
+    /// Returns the fonts to use for a family, each with the range of characters it is used for.
+    /// A web font family has a font for each `unicode-range` of its `@font-face` rules; a local
+    /// family has a single font used for every character.
+    fn get_font_templates(&mut self, family: &LowercaseString, desc: &FontTemplateDescriptor)
+                          -> Vec<(Arc<FontTemplateData>, Vec<UnicodeRange>)> {
         let transformed_family_name = self.transform_family(family);
-        let mut maybe_template = self.find_font_in_web_family(&transformed_family_name, desc);
-        if maybe_template.is_none() {
-            maybe_template = self.find_font_in_local_family(&transformed_family_name, desc);
+        self.reload_web_fonts(&transformed_family_name);
+        let templates = self.find_fonts_in_web_family(&transformed_family_name, desc);
+        self.evict_web_fonts();
+        if !templates.is_empty() {
+            return templates
+        }
+        let template = match self.find_font_in_local_family(&transformed_family_name, desc) {
+            Some(template) => Some(template),
+            None => self.find_font_in_alias_family(&transformed_family_name, desc),
+        };
+        match template {
+            Some(template) => vec!((template, vec!(UnicodeRange::all()))),
+            None => vec!(),
         }
-        maybe_template
     }
 
-    fn get_last_resort_font_template(&mut self, desc: &FontTemplateDescriptor)
-                                        -> Arc<FontTemplateData> {
-        let last_resort = get_last_resort_font_families();
+    /// Finds a font in the first installed family that the platform substitutes for a family
+    /// that isn't installed.
+    fn find_font_in_alias_family(&mut self,
+                                 family_name: &LowercaseString,
+                                 desc: &FontTemplateDescriptor)
+                                 -> Option<Arc<FontTemplateData>> {
+        if !self.substitute_families.contains_key(family_name) {
+            let substitutes = get_substitute_families(family_name);
+            self.substitute_families.insert(family_name.clone(), substitutes);
+        }
+        let aliases = self.substitute_families[family_name].clone();
+        for alias in aliases.iter() {
+            let alias = LowercaseString::new(alias);
+            if alias == *family_name {
+                continue
+            }
+            let template = self.find_font_in_local_family(&alias, desc);
+            if template.is_some() {
+                debug!("FontList: Using alias family={} for family={}", &*alias, &**family_name);
+                return template
+            }
+        }
+        None
+    }
 
-        for family in last_resort.iter() {
+    /// Returns a font from the first family of the platform's fallback list, from the one with
+    /// the given index on, that is installed, and the index of that family.
+    fn get_fallback_font_template(&mut self,
+                                  desc: &FontTemplateDescriptor,
+                                  han_variant: Option<HanVariant>,
+                                  index: usize)
+                                  -> Option<(usize, Arc<FontTemplateData>)> {
+        let last_resort = get_last_resort_font_families(han_variant);
+        for (index, family) in last_resort.iter().enumerate().skip(index) {
             let family = LowercaseString::new(family);
-            let maybe_font_in_family = self.find_font_in_local_family(&family, desc);
-            if maybe_font_in_family.is_some() {
-                return maybe_font_in_family.unwrap();
+            if let Some(font_template) = self.find_font_in_local_family(&family, desc) {
+                return Some((index, font_template))
             }
         }
-
-        panic!("Unable to find any fonts that match (do you have fallback fonts installed?)");
+        None
     }
 }
 
@@ -252,11 +679,23 @@ impl FontCache {
 #[derive(Clone)]
 pub struct FontCacheTask {
     chan: Sender<Command>,
+    /// The text shaped by any of the font contexts that use this task, for the others to reuse.
+    shaped_runs: Arc<ShapedRunCache>,
+}
+
+impl Reporter for FontCacheTask {
+    // Just injects an appropriate event into the font cache task's queue.
+    fn collect_reports(&self, reports_chan: ReportsChan) -> bool {
+        self.chan.send(Command::CollectReports(reports_chan)).is_ok()
+    }
 }
 
 impl FontCacheTask {
     pub fn new(resource_task: ResourceTask) -> FontCacheTask {
         let (chan, port) = channel();
+        let shaped_runs = Arc::new(ShapedRunCache::new());
+        let cache_shaped_runs = shaped_runs.clone();
+        let cache_chan = chan.clone();
 
         spawn_named("FontCacheTask".to_owned(), move || {
             // TODO: Allow users to specify these.
@@ -269,11 +708,20 @@ impl FontCacheTask {
 
             let mut cache = FontCache {
                 port: port,
+                chan: cache_chan,
                 generic_fonts: generic_fonts,
                 local_families: HashMap::new(),
+                font_list: FontListCache::load(opts::get().profile_dir.as_ref().map(|dir| {
+                    Path::new(dir).join("font-list.json")
+                })),
+                substitute_families: HashMap::new(),
                 web_families: HashMap::new(),
+                web_font_listeners: HashMap::new(),
                 font_context: FontContextHandle::new(),
                 resource_task: resource_task,
+                loaded_web_fonts: HashMap::new(),
+                web_font_uses: 0,
+                shaped_runs: cache_shaped_runs,
             };
 
             cache.refresh_local_families();
@@ -282,11 +730,27 @@ impl FontCacheTask {
 
         FontCacheTask {
             chan: chan,
+            shaped_runs: shaped_runs,
         }
     }
 
-    pub fn get_font_template(&self, family: String, desc: FontTemplateDescriptor)
-                                                -> Option<Arc<FontTemplateData>> {
+    /// Returns a handle that sends its commands on `chan`, for a task that answers them on behalf
+    /// of a font cache task in another process.
+    pub fn from_chan(chan: Sender<Command>) -> FontCacheTask {
+        FontCacheTask {
+            chan: chan,
+            shaped_runs: Arc::new(ShapedRunCache::new()),
+        }
+    }
+
+    /// Returns the cache of shaped text shared by every font context that uses this task.
+    pub fn shaped_run_cache(&self) -> Arc<ShapedRunCache> {
+        self.shaped_runs.clone()
+    }
+
+    /// Returns the fonts to use for a family, each with the range of characters it is used for.
+    pub fn get_font_templates(&self, family: String, desc: FontTemplateDescriptor)
+                              -> Vec<(Arc<FontTemplateData>, Vec<UnicodeRange>)> {
 
         let (response_chan, response_port) = channel();
         self.chan.send(Command::GetFontTemplate(family, desc, response_chan)).unwrap();
@@ -300,24 +764,44 @@ impl FontCacheTask {
         }
     }
 
-    pub fn get_last_resort_font_template(&self, desc: FontTemplateDescriptor)
-                                                -> Arc<FontTemplateData> {
-
+    /// Returns a font of the platform's fallback list, which is for characters that the fonts
+    /// a page asks for don't have glyphs for. The font comes from the first family that is
+    /// installed from the one with the given index on, and is returned with that family's
+    /// index. Fonts with the forms of Han characters that `han_variant` is written with come
+    /// before the others that have them.
+    pub fn get_fallback_font_template(&self,
+                                      desc: FontTemplateDescriptor,
+                                      han_variant: Option<HanVariant>,
+                                      index: usize)
+                                      -> Option<(usize, Arc<FontTemplateData>)> {
         let (response_chan, response_port) = channel();
-        self.chan.send(Command::GetLastResortFontTemplate(desc, response_chan)).unwrap();
-
-        let reply = response_port.recv().unwrap();
+        self.chan.send(Command::GetFallbackFontTemplate(desc, han_variant, index, response_chan))
+                 .unwrap();
+        response_port.recv().unwrap()
+    }
 
-        match reply {
-            Reply::GetFontTemplateReply(data) => {
-                data.unwrap()
-            }
-        }
+    /// Adds the font of an `@font-face` rule to a web font family, from the first of its
+    /// sources that gives one. If `loaded` is given, it is sent to once the font has been added,
+    /// which is after it has downloaded for a `url()` source, or once none of the sources gave a
+    /// font. Otherwise `listener` is sent the family's name once a downloaded font has been
+    /// added. It is also sent the name whenever a font of the family that was evicted has
+    /// downloaded again.
+    pub fn add_web_font(&self,
+                        family: Atom,
+                        sources: Vec<Source>,
+                        unicode_range: Vec<UnicodeRange>,
+                        listener: Sender<LowercaseString>,
+                        loaded: Option<Sender<()>>) {
+        self.chan.send(Command::AddWebFont(family, sources, unicode_range, listener, loaded))
+                 .unwrap();
     }
 
-    pub fn add_web_font(&self, family: Atom, src: Source) {
+    /// Adds a font from data in memory, such as one bundled with an embedding application, to
+    /// the fonts installed on the system under the name `family`. The data may be TrueType,
+    /// OpenType or WOFF.
+    pub fn add_local_font(&self, family: String, bytes: Vec<u8>) {
         let (response_chan, response_port) = channel();
-        self.chan.send(Command::AddWebFont(family, src, response_chan)).unwrap();
+        self.chan.send(Command::AddLocalFont(family, bytes, response_chan)).unwrap();
         response_port.recv().unwrap();
     }
 
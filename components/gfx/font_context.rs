@@ -2,10 +2,13 @@
  * License, v. 2.0. If a copy of the MPL was not distributed with this
  * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
 
+use color_glyphs::ColorGlyphs;
 use font::{Font, FontGroup};
 use font::SpecifiedFontStyle;
 use platform::font_context::FontContextHandle;
-use style::computed_values::{font_style, font_variant};
+use style::computed_values::{font_size_adjust, font_style, font_variant};
+use style::font_face::UnicodeRange;
+use text::util::HanVariant;
 
 use font::FontHandleMethods;
 use font_cache_task::FontCacheTask;
@@ -14,7 +17,7 @@ use fnv::FnvHasher;
 use platform::font::FontHandle;
 use platform::font_template::FontTemplateData;
 use smallvec::SmallVec8;
-use util::cache::HashCache;
+use util::cache::{HashCache, LRUCache};
 use util::geometry::Au;
 use util::mem::HeapSizeOf;
 
@@ -35,7 +38,8 @@ use azure::scaled_font::FontInfo;
 
 #[cfg(any(target_os="linux", target_os = "android"))]
 fn create_scaled_font(template: &Arc<FontTemplateData>, pt_size: Au) -> ScaledFont {
-    ScaledFont::new(BackendType::Skia, FontInfo::FontData(&template.bytes),
+    // Azure takes the data as a vector, which it copies, so mapped fonts are copied here.
+    ScaledFont::new(BackendType::Skia, FontInfo::FontData(&template.bytes.to_vec()),
                     pt_size.to_f32_px())
 }
 
@@ -47,12 +51,21 @@ fn create_scaled_font(template: &Arc<FontTemplateData>, pt_size: Au) -> ScaledFo
 
 static SMALL_CAPS_SCALE_FACTOR: f32 = 0.8;      // Matches FireFox (see gfxFont.h)
 
+/// The number of text runs whose glyphs each font keeps.
+const TEXT_RUN_CACHE_SIZE: usize = 64;
+
 struct LayoutFontCacheEntry {
     family: String,
     font: Option<Rc<RefCell<Font>>>,
 }
 
+/// A font of the platform's fallback list, in one style.
 struct FallbackFontCacheEntry {
+    identifier: String,
+    descriptor: FontTemplateDescriptor,
+    pt_size: Au,
+    variant: font_variant::T,
+    size_adjust: font_size_adjust::T,
     font: Rc<RefCell<Font>>,
 }
 
@@ -80,8 +93,18 @@ pub struct FontContext {
     /// per frame. TODO: Make this weak when incremental redraw is done.
     paint_font_cache: Vec<PaintFontCacheEntry>,
 
+    /// The color glyphs of the fonts used for painting, by template identifier.
+    color_glyphs_cache: HashMap<String, Option<Rc<ColorGlyphs>>>,
+
     layout_font_group_cache:
         HashMap<LayoutFontGroupCacheKey,Rc<FontGroup>,DefaultState<FnvHasher>>,
+
+    /// The fonts that small capitals are synthesized with, by the template identifier and
+    /// size of the font they are for.
+    small_caps_font_cache: HashMap<(String, Au), Rc<RefCell<Font>>>,
+
+    /// The web font epoch of the layout task when the layout fonts were cached.
+    web_font_epoch: usize,
 }
 
 impl FontContext {
@@ -93,26 +116,50 @@ impl FontContext {
             layout_font_cache: vec!(),
             fallback_font_cache: vec!(),
             paint_font_cache: vec!(),
+            color_glyphs_cache: HashMap::new(),
             layout_font_group_cache: HashMap::with_hash_state(Default::default()),
+            small_caps_font_cache: HashMap::new(),
+            web_font_epoch: 0,
+        }
+    }
+
+    /// Sets the number of times web fonts that its layout task uses have loaded so far. The
+    /// fonts found for a family before a web font of it loaded may not be the right ones any
+    /// more, such as when text was laid out in a fallback font while it downloaded, so the
+    /// layout fonts are looked up again when the epoch changes.
+    pub fn set_web_font_epoch(&mut self, web_font_epoch: usize) {
+        if web_font_epoch != self.web_font_epoch {
+            self.layout_font_cache.clear();
+            self.layout_font_group_cache.clear();
+            self.web_font_epoch = web_font_epoch;
         }
     }
 
     /// Create a font for use in layout calculations.
     fn create_layout_font(&self, template: Arc<FontTemplateData>,
                             descriptor: FontTemplateDescriptor, pt_size: Au,
-                            variant: font_variant::T) -> Result<Font, ()> {
-        // TODO: (Bug #3463): Currently we only support fake small-caps
-        // painting. We should also support true small-caps (where the
-        // font supports it) in the future.
-        let actual_pt_size = match variant {
-            font_variant::T::small_caps => pt_size.scale_by(SMALL_CAPS_SCALE_FACTOR),
-            font_variant::T::normal => pt_size,
-        };
-
-        let handle: Result<FontHandle, _> =
-            FontHandleMethods::new_from_template(&self.platform_handle, template,
+                            variant: font_variant::T,
+                            size_adjust: font_size_adjust::T,
+                            unicode_range: Vec<UnicodeRange>) -> Result<Font, ()> {
+        // Small capitals come from the font's `smcp` feature, or are synthesized in layout with
+        // the font from `get_small_caps_font`, so the font is the same size either way.
+        let mut actual_pt_size = pt_size;
+
+        let mut handle: Result<FontHandle, _> =
+            FontHandleMethods::new_from_template(&self.platform_handle, template.clone(),
                                                  Some(actual_pt_size));
 
+        // `font-size-adjust` scales the font so that its x-height is the given fraction of the
+        // requested size, which keeps fonts that are substituted for others as readable.
+        if let font_size_adjust::T::Number(adjust) = size_adjust {
+            let aspect = handle.as_ref().ok().and_then(|handle| handle.metrics().aspect());
+            if let Some(aspect) = aspect {
+                actual_pt_size = pt_size.scale_by(adjust / aspect);
+                handle = FontHandleMethods::new_from_template(&self.platform_handle, template,
+                                                              Some(actual_pt_size));
+            }
+        }
+
         handle.map(|handle| {
             let metrics = handle.metrics();
 
@@ -123,13 +170,46 @@ impl FontContext {
                 descriptor: descriptor,
                 requested_pt_size: pt_size,
                 actual_pt_size: actual_pt_size,
+                size_adjust: size_adjust,
                 metrics: metrics,
                 shape_cache: HashCache::new(),
+                text_run_cache: LRUCache::new(TEXT_RUN_CACHE_SIZE),
                 glyph_advance_cache: HashCache::new(),
+                shaped_runs: self.font_cache_task.shaped_run_cache(),
+                vertical_metrics: None,
+                gsub_features: None,
+                unicode_range: unicode_range,
             }
         })
     }
 
+    /// Returns the font to synthesize small capitals for `font` with, if it has no `smcp`
+    /// feature: the same font at a smaller size, which lowercase letters are drawn with as
+    /// uppercase letters.
+    pub fn get_small_caps_font(&mut self, font: &Font) -> Option<Rc<RefCell<Font>>> {
+        let template = font.handle.template();
+        let key = (template.identifier.clone(), font.requested_pt_size);
+        if let Some(small_caps_font) = self.small_caps_font_cache.get(&key) {
+            return Some(small_caps_font.clone())
+        }
+
+        let pt_size = font.requested_pt_size.scale_by(SMALL_CAPS_SCALE_FACTOR);
+        let small_caps_font = self.create_layout_font(template,
+                                                      font.descriptor,
+                                                      pt_size,
+                                                      font_variant::T::small_caps,
+                                                      font.size_adjust,
+                                                      font.unicode_range.clone());
+        match small_caps_font {
+            Ok(small_caps_font) => {
+                let small_caps_font = Rc::new(RefCell::new(small_caps_font));
+                self.small_caps_font_cache.insert(key, small_caps_font.clone());
+                Some(small_caps_font)
+            }
+            Err(()) => None,
+        }
+    }
+
     /// Create a group of fonts for use in layout calculations. May return
     /// a cached font if this font instance has already been used by
     /// this context.
@@ -162,6 +242,8 @@ impl FontContext {
 
         for family in style.font_family.0.iter() {
             // GWTODO: Check on real pages if this is faster as Vec() or HashMap().
+            // A web font family can have a font for each `unicode-range` of its `@font-face`
+            // rules, so every matching font of the family is used.
             let mut cache_hit = false;
             for cached_font_entry in self.layout_font_cache.iter() {
                 if cached_font_entry.family == family.name() {
@@ -173,11 +255,12 @@ impl FontContext {
                         Some(ref cached_font_ref) => {
                             let cached_font = (*cached_font_ref).borrow();
                             if cached_font.descriptor == desc &&
+                               cached_font.descriptor.weight == desc.weight &&
                                cached_font.requested_pt_size == style.font_size &&
-                               cached_font.variant == style.font_variant {
+                               cached_font.variant == style.font_variant &&
+                               cached_font.size_adjust == style.font_size_adjust {
                                 fonts.push((*cached_font_ref).clone());
                                 cache_hit = true;
-                                break;
                             }
                         }
                     }
@@ -185,77 +268,152 @@ impl FontContext {
             }
 
             if !cache_hit {
-                let font_template = self.font_cache_task.get_font_template(family.name()
-                                                                                 .to_owned(),
-                                                                           desc.clone());
-                match font_template {
-                    Some(font_template) => {
-                        let layout_font = self.create_layout_font(font_template,
-                                                                  desc.clone(),
-                                                                  style.font_size,
-                                                                  style.font_variant);
-                        let font = match layout_font {
-                            Ok(layout_font) => {
-                                let layout_font = Rc::new(RefCell::new(layout_font));
-                                fonts.push(layout_font.clone());
-
-                                Some(layout_font)
-                            }
-                            Err(_) => None
-                        };
-
-                        self.layout_font_cache.push(LayoutFontCacheEntry {
-                            family: family.name().to_owned(),
-                            font: font
-                        });
-                    }
-                    None => {
+                let font_templates = self.font_cache_task.get_font_templates(family.name()
+                                                                                   .to_owned(),
+                                                                             desc.clone());
+                let mut found = false;
+                for (font_template, unicode_range) in font_templates.into_iter() {
+                    let layout_font = self.create_layout_font(font_template,
+                                                              desc.clone(),
+                                                              style.font_size,
+                                                              style.font_variant,
+                                                              style.font_size_adjust,
+                                                              unicode_range);
+                    if let Ok(layout_font) = layout_font {
+                        let layout_font = Rc::new(RefCell::new(layout_font));
+                        fonts.push(layout_font.clone());
                         self.layout_font_cache.push(LayoutFontCacheEntry {
                             family: family.name().to_owned(),
-                            font: None,
+                            font: Some(layout_font),
                         });
+                        found = true;
                     }
                 }
+
+                if !found {
+                    self.layout_font_cache.push(LayoutFontCacheEntry {
+                        family: family.name().to_owned(),
+                        font: None,
+                    });
+                }
             }
         }
 
-        // If unable to create any of the specified fonts, create one from the
-        // list of last resort fonts for this platform.
-        if fonts.len() == 0 {
-            let mut cache_hit = false;
-            for cached_font_entry in self.fallback_font_cache.iter() {
-                let cached_font = cached_font_entry.font.borrow();
-                if cached_font.descriptor == desc &&
-                            cached_font.requested_pt_size == style.font_size &&
-                            cached_font.variant == style.font_variant {
-                    fonts.push(cached_font_entry.font.clone());
-                    cache_hit = true;
-                    break;
-                }
+        // The platform's fallback fonts come after the specified ones, for characters that none
+        // of those have glyphs for. They are only created once such a character turns up,
+        // except that the first one is needed at once if none of the specified fonts could be
+        // created. Han characters are drawn in the forms of the language of the text, where it
+        // is known.
+        let han_variant = style._servo_lang.0.as_ref().and_then(|lang| {
+            HanVariant::from_lang(lang.as_slice())
+        });
+        let no_fonts = fonts.is_empty();
+        let font_group = Rc::new(FontGroup::new(fonts,
+                                                han_variant,
+                                                desc,
+                                                style.font_size,
+                                                style.font_variant,
+                                                style.font_size_adjust));
+        if no_fonts {
+            match self.next_fallback_font(&*font_group) {
+                Some(font) => font_group.push(font),
+                None => panic!("Unable to find any fonts that match (do you have fallback fonts \
+                                installed?)"),
             }
+        }
+        self.layout_font_group_cache.insert(layout_font_group_cache_key, font_group.clone());
+        font_group
+    }
 
-            if !cache_hit {
-                let font_template = self.font_cache_task.get_last_resort_font_template(desc.clone());
-                let layout_font = self.create_layout_font(font_template,
-                                                          desc.clone(),
-                                                          style.font_size,
-                                                          style.font_variant);
-                match layout_font {
-                    Ok(layout_font) => {
-                        let layout_font = Rc::new(RefCell::new(layout_font));
-                        self.fallback_font_cache.push(FallbackFontCacheEntry {
-                            font: layout_font.clone(),
-                        });
-                        fonts.push(layout_font);
-                    }
-                    Err(_) => debug!("Failed to create fallback layout font!")
+    /// Returns the index of the first font of `group` that is used for `character` by its
+    /// `unicode-range` and has a glyph for it, or `None` if none of them do. The platform's
+    /// fallback fonts are added to the group as they are got to.
+    pub fn font_with_glyph(&mut self, group: &FontGroup, character: char) -> Option<usize> {
+        let has_glyph = |font: &Rc<RefCell<Font>>| {
+            let font = font.borrow();
+            font.covers(character) && font.glyph_index(character).is_some()
+        };
+        if let Some(index) = (0..group.len()).position(|index| has_glyph(&group.font(index))) {
+            return Some(index)
+        }
+        while let Some(font) = self.next_fallback_font(group) {
+            group.push(font.clone());
+            if has_glyph(&font) {
+                return Some(group.len() - 1)
+            }
+        }
+        None
+    }
+
+    /// Returns the next font of the platform's fallback list for `group` that is installed and
+    /// isn't in the group already, or `None` once the list has been used up.
+    fn next_fallback_font(&mut self, group: &FontGroup) -> Option<Rc<RefCell<Font>>> {
+        while let Some(index) = group.next_fallback.get() {
+            let font_template = self.font_cache_task.get_fallback_font_template(
+                group.descriptor.clone(), group.han_variant, index);
+            let font_template = match font_template {
+                Some((index, font_template)) => {
+                    group.next_fallback.set(Some(index + 1));
+                    font_template
+                }
+                None => {
+                    group.next_fallback.set(None);
+                    break
                 }
+            };
+
+            let in_group = (0..group.len()).any(|index| {
+                group.font(index).borrow().handle.template().identifier ==
+                    font_template.identifier
+            });
+            if !in_group {
+                if let Some(font) = self.get_fallback_font(font_template, group) {
+                    return Some(font)
+                }
+            }
+        }
+        None
+    }
+
+    /// Creates a font of the platform's fallback list in the style of `group`. May return a
+    /// cached font if it has already been used in that style by this context.
+    fn get_fallback_font(&mut self, template: Arc<FontTemplateData>, group: &FontGroup)
+                         -> Option<Rc<RefCell<Font>>> {
+        for cached_font_entry in self.fallback_font_cache.iter() {
+            if cached_font_entry.identifier == template.identifier &&
+                    cached_font_entry.descriptor == group.descriptor &&
+                    cached_font_entry.descriptor.weight == group.descriptor.weight &&
+                    cached_font_entry.pt_size == group.pt_size &&
+                    cached_font_entry.variant == group.variant &&
+                    cached_font_entry.size_adjust == group.size_adjust {
+                return Some(cached_font_entry.font.clone())
             }
         }
 
-        let font_group = Rc::new(FontGroup::new(fonts));
-        self.layout_font_group_cache.insert(layout_font_group_cache_key, font_group.clone());
-        font_group
+        let identifier = template.identifier.clone();
+        match self.create_layout_font(template,
+                                      group.descriptor.clone(),
+                                      group.pt_size,
+                                      group.variant,
+                                      group.size_adjust,
+                                      vec!(UnicodeRange::all())) {
+            Ok(layout_font) => {
+                let layout_font = Rc::new(RefCell::new(layout_font));
+                self.fallback_font_cache.push(FallbackFontCacheEntry {
+                    identifier: identifier,
+                    descriptor: group.descriptor.clone(),
+                    pt_size: group.pt_size,
+                    variant: group.variant,
+                    size_adjust: group.size_adjust,
+                    font: layout_font.clone(),
+                });
+                Some(layout_font)
+            }
+            Err(_) => {
+                debug!("Failed to create fallback layout font!");
+                None
+            }
+        }
     }
 
     /// Create a paint font for use with azure. May return a cached
@@ -280,6 +438,19 @@ impl FontContext {
         paint_font
     }
 
+    /// Returns the color glyphs of a font for painting, or `None` if it has none. May return a
+    /// cached reference if already used by this font context.
+    pub fn get_color_glyphs_from_template(&mut self, template: &Arc<FontTemplateData>)
+                                          -> Option<Rc<ColorGlyphs>> {
+        if let Some(color_glyphs) = self.color_glyphs_cache.get(&template.identifier) {
+            return color_glyphs.clone()
+        }
+
+        let color_glyphs = ColorGlyphs::new(template.clone()).map(Rc::new);
+        self.color_glyphs_cache.insert(template.identifier.clone(), color_glyphs.clone());
+        color_glyphs
+    }
+
     /// Returns a reference to the font cache task.
     pub fn font_cache_task(&self) -> FontCacheTask {
         self.font_cache_task.clone()
@@ -288,8 +459,22 @@ impl FontContext {
 
 impl HeapSizeOf for FontContext {
     fn heap_size_of_children(&self) -> usize {
-        // FIXME(njn): Measure other fields eventually.
-        self.platform_handle.heap_size_of_children()
+        // The font groups hold fonts from the other caches, so they aren't measured again.
+        // FIXME(njn): Measure the paint fonts, which Azure owns, eventually.
+        let layout_fonts = self.layout_font_cache.iter().fold(0, |size, entry| {
+            size + entry.family.heap_size_of_children() + entry.font.heap_size_of_children()
+        });
+        let fallback_fonts = self.fallback_font_cache.iter().fold(0, |size, entry| {
+            size + entry.identifier.heap_size_of_children() + entry.font.heap_size_of_children()
+        });
+        let small_caps_fonts = self.small_caps_font_cache.iter().fold(0, |size, (key, font)| {
+            size + key.0.heap_size_of_children() + font.heap_size_of_children()
+        });
+        let color_glyphs = self.color_glyphs_cache.iter().fold(0, |size, (identifier, glyphs)| {
+            size + identifier.heap_size_of_children() + glyphs.heap_size_of_children()
+        });
+        self.platform_handle.heap_size_of_children() + layout_fonts + fallback_fonts +
+            small_caps_fonts + color_glyphs
     }
 }
 
@@ -305,6 +490,8 @@ impl PartialEq for LayoutFontGroupCacheKey {
             self.pointer.font_stretch == other.pointer.font_stretch &&
             self.pointer.font_style == other.pointer.font_style &&
             self.pointer.font_weight as u16 == other.pointer.font_weight as u16 &&
+            self.pointer.font_size_adjust == other.pointer.font_size_adjust &&
+            self.pointer._servo_lang == other.pointer._servo_lang &&
             self.size == other.size
     }
 }
@@ -0,0 +1,220 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+//! A copy of the list of installed font families, and of the fonts in each family that have
+//! been looked up, saved to the profile directory so that the fonts don't have to be
+//! enumerated through the platform on every start. The saved list is only used while none of
+//! the directories fonts are installed in have been modified since it was made, and the fonts
+//! saved for a family only while none of their files have been modified.
+
+use platform::font_list::font_directories;
+
+use rustc_serialize::json;
+use std::borrow::ToOwned;
+use std::collections::HashMap;
+use std::fs::{self, File};
+use std::io::{Read, Write};
+use std::os::unix::fs::MetadataExt;
+use std::path::{Path, PathBuf};
+
+/// The modification time of a font directory when the list was made.
+#[derive(PartialEq, RustcEncodable, RustcDecodable)]
+struct DirectoryTime {
+    path: String,
+    mtime: i64,
+}
+
+/// A font of a family, with the modification time and length of its file when the list was
+/// made. Fonts that aren't identified by the path of their file have no time or length.
+#[derive(Clone, RustcEncodable, RustcDecodable)]
+struct StoredFont {
+    identifier: String,
+    file: Option<(i64, u64)>,
+}
+
+#[derive(RustcEncodable, RustcDecodable)]
+struct StoredFamily {
+    name: String,
+    variations: Option<Vec<StoredFont>>,
+}
+
+#[derive(RustcEncodable, RustcDecodable)]
+struct StoredFontList {
+    directories: Vec<DirectoryTime>,
+    families: Vec<StoredFamily>,
+}
+
+pub struct FontListCache {
+    /// The file the list is saved to, if any.
+    path: Option<PathBuf>,
+    /// The font directories and everything in them, with their current modification times.
+    directories: Vec<DirectoryTime>,
+    /// The fonts of each family, by lowercase family name, for the families whose fonts have
+    /// been looked up.
+    families: HashMap<String, Option<Vec<StoredFont>>>,
+    /// Whether the list has changed since it was read or last saved.
+    dirty: bool,
+}
+
+impl FontListCache {
+    /// Reads the list saved to `path`. The list is empty if nothing was saved there yet, or if
+    /// the fonts installed have changed since.
+    pub fn load(path: Option<PathBuf>) -> FontListCache {
+        let mut cache = FontListCache {
+            path: path,
+            directories: vec!(),
+            families: HashMap::new(),
+            dirty: false,
+        };
+        // Without a file to save to, there's no need to walk the font directories.
+        let path = match cache.path {
+            Some(ref path) => path.clone(),
+            None => return cache,
+        };
+        cache.directories = current_directory_times();
+
+        let mut contents = String::new();
+        if File::open(&path).and_then(|mut file| file.read_to_string(&mut contents)).is_err() {
+            // Nothing was saved yet.
+            return cache;
+        }
+        let stored: StoredFontList = match json::decode(&contents) {
+            Ok(stored) => stored,
+            Err(e) => {
+                warn!("ignoring malformed font list {}: {}", path.display(), e);
+                return cache;
+            }
+        };
+        if stored.directories != cache.directories {
+            debug!("font directories changed since {} was saved", path.display());
+            return cache;
+        }
+        for family in stored.families.into_iter() {
+            cache.families.insert(family.name, family.variations);
+        }
+        cache
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.families.is_empty()
+    }
+
+    /// Returns the lowercase names of the families in the list.
+    pub fn families(&self) -> Vec<String> {
+        self.families.keys().cloned().collect()
+    }
+
+    /// Replaces the families in the list with the given lowercase names. The fonts of each
+    /// family are looked up again when they are needed.
+    pub fn set_families(&mut self, families: Vec<String>) {
+        self.families.clear();
+        for family in families.into_iter() {
+            self.families.insert(family, None);
+        }
+        self.dirty = true;
+    }
+
+    /// Returns the identifiers of the fonts of a family, if they have been looked up and none
+    /// of their files have been replaced since.
+    pub fn variations(&self, family: &str) -> Option<Vec<String>> {
+        let variations = match self.families.get(family) {
+            Some(&Some(ref variations)) => variations,
+            _ => return None,
+        };
+        if variations.iter().any(|font| font.file != file_time(&font.identifier)) {
+            debug!("fonts of {} changed since the font list was saved", family);
+            return None
+        }
+        Some(variations.iter().map(|font| font.identifier.clone()).collect())
+    }
+
+    pub fn set_variations(&mut self, family: &str, variations: Vec<String>) {
+        let variations = variations.into_iter().map(|identifier| {
+            StoredFont {
+                file: file_time(&identifier),
+                identifier: identifier,
+            }
+        }).collect();
+        self.families.insert(family.to_owned(), Some(variations));
+        self.dirty = true;
+    }
+
+    /// Writes the list to disk, if it has changed.
+    pub fn save(&mut self) {
+        if !self.dirty {
+            return;
+        }
+        let path = match self.path {
+            Some(ref path) => path,
+            None => return,
+        };
+        let stored = StoredFontList {
+            directories: self.directories.iter().map(|directory| {
+                DirectoryTime {
+                    path: directory.path.clone(),
+                    mtime: directory.mtime,
+                }
+            }).collect(),
+            families: self.families.iter().map(|(name, variations)| {
+                StoredFamily {
+                    name: name.clone(),
+                    variations: variations.clone(),
+                }
+            }).collect(),
+        };
+        let result = File::create(path).and_then(|mut file| {
+            file.write_all(json::encode(&stored).unwrap().as_bytes())
+        });
+        match result {
+            Ok(()) => self.dirty = false,
+            Err(e) => warn!("failed to save the font list to {}: {}", path.display(), e),
+        }
+    }
+}
+
+/// Returns the modification time and length of the file that a font identifier names, or
+/// `None` if it isn't the path of a file. A font replaced in place, without changing the
+/// directory it is in, changes these.
+fn file_time(identifier: &str) -> Option<(i64, u64)> {
+    match fs::metadata(identifier) {
+        Ok(ref metadata) if metadata.is_file() => Some((metadata.mtime() as i64, metadata.len())),
+        _ => None,
+    }
+}
+
+/// Returns the modification times of the font directories and of the directories inside them,
+/// as fonts are usually installed into a directory of their own.
+fn current_directory_times() -> Vec<DirectoryTime> {
+    let mut directories = vec!();
+    for directory in font_directories().iter() {
+        add_directory_times(directory, &mut directories);
+    }
+    directories.sort_by(|a, b| a.path.cmp(&b.path));
+    directories
+}
+
+fn add_directory_times(directory: &Path, directories: &mut Vec<DirectoryTime>) {
+    let mtime = match fs::metadata(directory) {
+        Ok(ref metadata) if metadata.is_dir() => metadata.mtime() as i64,
+        _ => return,
+    };
+    directories.push(DirectoryTime {
+        path: directory.display().to_string(),
+        mtime: mtime,
+    });
+    if let Ok(entries) = fs::read_dir(directory) {
+        for entry in entries {
+            // Symbolic links aren't followed, so that a link to a parent directory doesn't
+            // make this loop forever.
+            let path = match entry {
+                Ok(entry) => entry.path(),
+                Err(_) => continue,
+            };
+            match fs::symlink_metadata(&path) {
+                Ok(ref metadata) if metadata.is_dir() => add_directory_times(&path, directories),
+                _ => {}
+            }
+        }
+    }
+}
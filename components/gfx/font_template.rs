@@ -2,20 +2,45 @@
  * License, v. 2.0. If a copy of the MPL was not distributed with this
  * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
 
-use font::FontHandleMethods;
+use font::{FontHandleMethods, FontTableTag};
+use font_variations::{self, FontVariations, WGHT};
 use platform::font_context::FontContextHandle;
 use platform::font::FontHandle;
 use platform::font_template::FontTemplateData;
+use sfnt;
 
 use std::borrow::ToOwned;
 use std::sync::{Arc, Weak};
 use style::computed_values::{font_stretch, font_weight};
+use style::font_face::UnicodeRange;
+use util::mem::HeapSizeOf;
+
+/// The number of `font-stretch` and `font-weight` values, which is more than any distance
+/// between two of them in the preferred direction.
+const STRETCH_COUNT: u32 = 9;
+const WEIGHT_COUNT: u32 = 9;
+
+const HEAD: FontTableTag = 0x68656164;
+const OS2: FontTableTag = 0x4f532f32;
+
+const HEAD_MAC_STYLE_OFFSET: usize = 44;
+const MAC_STYLE_BOLD: u16 = 1 << 0;
+const MAC_STYLE_ITALIC: u16 = 1 << 1;
+
+const OS2_WEIGHT_CLASS_OFFSET: usize = 4;
+const OS2_WIDTH_CLASS_OFFSET: usize = 6;
+const OS2_FS_SELECTION_OFFSET: usize = 62;
+const FS_SELECTION_ITALIC: u16 = 1 << 0;
+
+/// The number of times in a row that an evicted web font may fail to download again before it
+/// is given up on.
+const MAX_RELOAD_FAILURES: u32 = 3;
 
 /// Describes how to select a font from a given family. This is very basic at the moment and needs
 /// to be expanded or refactored when we support more of the font styling parameters.
 ///
 /// NB: If you change this, you will need to update `style::properties::compute_font_hash()`.
-#[derive(Clone, Copy, Eq, Hash)]
+#[derive(Clone, Copy, Eq, Hash, RustcEncodable, RustcDecodable)]
 pub struct FontTemplateDescriptor {
     pub weight: font_weight::T,
     pub stretch: font_stretch::T,
@@ -34,6 +59,84 @@ impl FontTemplateDescriptor {
     }
 }
 
+impl FontTemplateDescriptor {
+    /// Returns how far the style of a font with this descriptor is from the style requested by
+    /// `requested`. Of the fonts of a family, the one with the lowest distance is used, which
+    /// picks fonts by the order of the font matching algorithm of CSS Fonts: the closest stretch
+    /// first, then style, then weight.
+    /// https://drafts.csswg.org/css-fonts-3/#font-style-matching
+    pub fn distance_from(&self, requested: &FontTemplateDescriptor) -> (u32, u32, u32) {
+        let style = if self.italic == requested.italic { 0 } else { 1 };
+        (stretch_distance(stretch_index(self.stretch), stretch_index(requested.stretch)),
+         style,
+         weight_distance(self.weight as u32, requested.weight as u32))
+    }
+}
+
+/// The position of a `font-stretch` value from `ultra-condensed`, 1, to `ultra-expanded`, 9.
+fn stretch_index(stretch: font_stretch::T) -> u32 {
+    match stretch {
+        font_stretch::T::ultra_condensed => 1,
+        font_stretch::T::extra_condensed => 2,
+        font_stretch::T::condensed => 3,
+        font_stretch::T::semi_condensed => 4,
+        font_stretch::T::normal => 5,
+        font_stretch::T::semi_expanded => 6,
+        font_stretch::T::expanded => 7,
+        font_stretch::T::extra_expanded => 8,
+        font_stretch::T::ultra_expanded => 9,
+    }
+}
+
+/// Narrower fonts are preferred for requests of `normal` or narrower stretches, and wider
+/// fonts for requests of wider ones.
+fn stretch_distance(actual: u32, requested: u32) -> u32 {
+    if actual == requested {
+        return 0
+    }
+    let preferred_narrower = requested <= stretch_index(font_stretch::T::normal);
+    match (actual < requested, preferred_narrower) {
+        (true, true) => requested - actual,
+        (false, false) => actual - requested,
+        (true, false) => STRETCH_COUNT + requested - actual,
+        (false, true) => STRETCH_COUNT + actual - requested,
+    }
+}
+
+/// A request for 400 tries 500 first and a request for 500 tries 400 first. Otherwise lighter
+/// fonts are preferred for requests lighter than 400, and heavier fonts for requests heavier
+/// than 500.
+fn weight_distance(actual: u32, requested: u32) -> u32 {
+    if actual == requested {
+        return 0
+    }
+    match requested {
+        400 | 500 => {
+            if actual == 400 || actual == 500 {
+                1
+            } else if actual < 400 {
+                1 + (400 - actual) / 100
+            } else {
+                WEIGHT_COUNT + (actual - 500) / 100
+            }
+        }
+        _ if requested < 400 => {
+            if actual < requested {
+                (requested - actual) / 100
+            } else {
+                WEIGHT_COUNT + (actual - requested) / 100
+            }
+        }
+        _ => {
+            if actual > requested {
+                (actual - requested) / 100
+            } else {
+                WEIGHT_COUNT + (requested - actual) / 100
+            }
+        }
+    }
+}
+
 impl PartialEq for FontTemplateDescriptor {
     fn eq(&self, other: &FontTemplateDescriptor) -> bool {
         self.weight.is_bold() == other.weight.is_bold() &&
@@ -49,16 +152,32 @@ pub struct FontTemplate {
     identifier: String,
     descriptor: Option<FontTemplateDescriptor>,
     weak_ref: Option<Weak<FontTemplateData>>,
-    // GWTODO: Add code path to unset the strong_ref for web fonts!
+    /// The data of a downloaded web font, which can't be read again from a file. It is dropped
+    /// by `unload` when the font cache evicts the font.
     strong_ref: Option<Arc<FontTemplateData>>,
+    /// Whether `unload` dropped the data of a downloaded web font.
+    unloaded: bool,
+    /// Whether the font is being downloaded again after being unloaded.
+    reloading: bool,
+    /// The number of times in a row the font failed to download again.
+    reload_failures: u32,
     is_valid: bool,
+    /// The axes and named instances of the font if it is a variable font, once it has been
+    /// loaded to find out.
+    variations: Option<Option<FontVariations>>,
+    /// Instances of a variable font that have been made, by their coordinates.
+    instances: Vec<(Vec<f32>, Weak<FontTemplateData>)>,
+    /// The characters the font is used for, from the `unicode-range` descriptor of the
+    /// `@font-face` rule that added it.
+    unicode_range: Vec<UnicodeRange>,
 }
 
 /// Holds all of the template information for a font that
 /// is common, regardless of the number of instances of
 /// this font handle per thread.
 impl FontTemplate {
-    pub fn new(identifier: &str, maybe_bytes: Option<Vec<u8>>) -> FontTemplate {
+    pub fn new(identifier: &str, maybe_bytes: Option<Vec<u8>>, unicode_range: Vec<UnicodeRange>)
+               -> FontTemplate {
         let maybe_data = match maybe_bytes {
             Some(_) => Some(FontTemplateData::new(identifier, maybe_bytes)),
             None => None,
@@ -79,7 +198,13 @@ impl FontTemplate {
             descriptor: None,
             weak_ref: maybe_weak_ref,
             strong_ref: maybe_strong_ref,
+            unloaded: false,
+            reloading: false,
+            reload_failures: 0,
             is_valid: true,
+            variations: None,
+            instances: vec!(),
+            unicode_range: unicode_range,
         }
     }
 
@@ -87,57 +212,119 @@ impl FontTemplate {
         &*self.identifier
     }
 
-    /// Get the data for creating a font if it matches a given descriptor.
-    pub fn get_if_matches(&mut self,
-                          fctx: &FontContextHandle,
-                          requested_desc: &FontTemplateDescriptor)
-                          -> Option<Arc<FontTemplateData>> {
+    pub fn unicode_range(&self) -> &[UnicodeRange] {
+        &*self.unicode_range
+    }
+
+    /// Drops the template's own reference to the data of a downloaded web font. The data stays
+    /// in memory for as long as fonts made from it do.
+    pub fn unload(&mut self) {
+        if self.strong_ref.take().is_some() {
+            self.unloaded = true;
+        }
+    }
+
+    /// Returns true if the data of a downloaded web font was unloaded and is no longer in use,
+    /// so that it has to be downloaded again and passed to `reload` before the font is used.
+    pub fn needs_reload(&self) -> bool {
+        self.unloaded && self.weak_ref.as_ref().map_or(true, |data| data.upgrade().is_none())
+    }
+
+    /// Notes that a font that needs to be reloaded is being downloaded again. Returns false if
+    /// it already was, so that it is only downloaded once at a time.
+    pub fn start_reload(&mut self) -> bool {
+        if self.reloading {
+            return false
+        }
+        self.reloading = true;
+        true
+    }
+
+    /// Replaces the data of a downloaded web font that was unloaded. If it couldn't be
+    /// downloaded again, the font stays unloaded so that it is tried again the next time it is
+    /// looked up, unless that has failed too many times, in which case the template becomes
+    /// invalid.
+    pub fn reload(&mut self, maybe_bytes: Option<Vec<u8>>) {
+        self.reloading = false;
+        match maybe_bytes {
+            Some(bytes) => {
+                let data = Arc::new(FontTemplateData::new(&self.identifier, Some(bytes)));
+                self.weak_ref = Some(data.downgrade());
+                self.strong_ref = Some(data);
+                self.unloaded = false;
+                self.reload_failures = 0;
+            }
+            None => {
+                self.reload_failures += 1;
+                if self.reload_failures >= MAX_RELOAD_FAILURES {
+                    self.descriptor = None;
+                    self.is_valid = false;
+                    self.unloaded = false;
+                }
+            }
+        }
+    }
+
+    /// Returns the style of the font, or `None` if it can't be used.
+    pub fn descriptor(&mut self, fctx: &FontContextHandle) -> Option<FontTemplateDescriptor> {
+        if self.needs_reload() {
+            return None
+        }
+
         // The font template data can be unloaded when nothing is referencing
         // it (via the Weak reference to the Arc above). However, if we have
         // already loaded a font, store the style information about it separately,
         // so that we can do font matching against it again in the future
         // without having to reload the font (unless it is an actual match).
-        match self.descriptor {
-            Some(actual_desc) => {
-                if *requested_desc == actual_desc {
-                    Some(self.get_data())
-                } else {
-                    None
-                }
-            },
-            None if self.is_valid => {
-                let data = self.get_data();
+        if self.descriptor.is_some() || !self.is_valid {
+            return self.descriptor
+        }
+
+        let data = self.get_data();
+        // The style of a font is read from its tables where they can be, so that faces
+        // are only created for the fonts that are used.
+        let actual_desc = match data.sfnt_data().and_then(descriptor_from_tables) {
+            Some(actual_desc) => actual_desc,
+            None => {
                 let handle: Result<FontHandle, ()> =
                     FontHandleMethods::new_from_template(fctx, data.clone(), None);
                 match handle {
-                    Ok(handle) => {
-                        let actual_desc = FontTemplateDescriptor::new(handle.boldness(),
-                                                                      handle.stretchiness(),
-                                                                      handle.is_italic());
-                        let desc_match = actual_desc == *requested_desc;
-
-                        self.descriptor = Some(actual_desc);
-                        self.is_valid = true;
-                        if desc_match {
-                            Some(data)
-                        } else {
-                            None
-                        }
-                    }
+                    Ok(handle) => FontTemplateDescriptor::new(handle.boldness(),
+                                                              handle.stretchiness(),
+                                                              handle.is_italic()),
                     Err(()) => {
                         self.is_valid = false;
                         debug!("Unable to create a font from template {}", self.identifier);
-                        None
+                        return None
                     }
                 }
             }
-            None => None,
+        };
+        self.descriptor = Some(actual_desc);
+        self.descriptor
+    }
+
+    /// Get the data for creating a font if it matches a given descriptor.
+    pub fn get_if_matches(&mut self,
+                          fctx: &FontContextHandle,
+                          requested_desc: &FontTemplateDescriptor)
+                          -> Option<Arc<FontTemplateData>> {
+        let actual_desc = match self.descriptor(fctx) {
+            Some(actual_desc) => actual_desc,
+            None => return None,
+        };
+        if let Some(instance) = self.get_weight_instance(requested_desc, &actual_desc) {
+            Some(instance)
+        } else if *requested_desc == actual_desc {
+            Some(self.get_data())
+        } else {
+            None
         }
     }
 
     /// Get the data for creating a font.
     pub fn get(&mut self) -> Option<Arc<FontTemplateData>> {
-        if self.is_valid {
+        if self.is_valid && !self.needs_reload() {
             Some(self.get_data())
         } else {
             None
@@ -162,4 +349,157 @@ impl FontTemplate {
         self.weak_ref = Some(template_data.downgrade());
         template_data
     }
+
+    /// Returns the axes and named instances of the font, or `None` if it isn't a variable font.
+    pub fn variations(&mut self) -> Option<FontVariations> {
+        if !self.is_valid || self.needs_reload() {
+            return None
+        }
+        if self.variations.is_none() {
+            let data = self.get_data();
+            let variations = data.sfnt_data().and_then(FontVariations::from_font_data);
+            self.variations = Some(variations);
+        }
+        self.variations.clone().unwrap()
+    }
+
+    /// Get the data for creating the instance of a variable font at the given coordinates, in
+    /// the units of each of the axes returned by `variations()`.
+    pub fn get_instance(&mut self, coordinates: &[f32]) -> Option<Arc<FontTemplateData>> {
+        let variations = match self.variations() {
+            Some(variations) => variations,
+            None => return None,
+        };
+
+        self.instances.retain(|&(_, ref instance)| instance.upgrade().is_some());
+        for &(ref instance_coordinates, ref instance) in self.instances.iter() {
+            if &**instance_coordinates == coordinates {
+                if let Some(instance) = instance.upgrade() {
+                    return Some(instance)
+                }
+            }
+        }
+
+        let data = self.get_data();
+        let bytes = match data.sfnt_data() {
+            Some(font) => font_variations::instantiate(font, &variations, coordinates),
+            None => return None,
+        };
+        match bytes {
+            Ok(bytes) => {
+                let identifier = format!("{}#{}", self.identifier, coordinates.iter().map(|value| {
+                    value.to_string()
+                }).collect::<Vec<_>>().connect(","));
+                let instance = Arc::new(FontTemplateData::new(&identifier, Some(bytes)));
+                self.instances.push((coordinates.to_vec(), instance.downgrade()));
+                Some(instance)
+            }
+            Err(reason) => {
+                debug!("Unable to instance variable font {} ({})", self.identifier, reason);
+                None
+            }
+        }
+    }
+
+    /// Get the data for creating one of the named instances of a variable font.
+    pub fn get_named_instance(&mut self, index: usize) -> Option<Arc<FontTemplateData>> {
+        let coordinates = match self.variations() {
+            Some(variations) => match variations.instances.get(index) {
+                Some(instance) => instance.coordinates.clone(),
+                None => return None,
+            },
+            None => return None,
+        };
+        self.get_instance(&coordinates)
+    }
+
+    /// If this is a variable font whose weight axis covers the requested weight, and it
+    /// otherwise matches the requested descriptor, get the data for the instance at that weight.
+    ///
+    /// FIXME: Computed `font-weight` values are still multiples of 100.
+    fn get_weight_instance(&mut self,
+                           requested_desc: &FontTemplateDescriptor,
+                           actual_desc: &FontTemplateDescriptor)
+                           -> Option<Arc<FontTemplateData>> {
+        if requested_desc.stretch != actual_desc.stretch ||
+                requested_desc.italic != actual_desc.italic {
+            return None
+        }
+        let variations = match self.variations() {
+            Some(variations) => variations,
+            None => return None,
+        };
+        let weight = requested_desc.weight as u16 as f32;
+        let covered = match variations.axis(WGHT) {
+            Some(axis) => axis.min <= weight && weight <= axis.max && weight != axis.default,
+            None => false,
+        };
+        if !covered {
+            return None
+        }
+        self.get_instance(&variations.coordinates_for_weight(weight))
+    }
+}
+
+/// Reads the style of a font from its `OS/2` table, or from the `head` table of fonts without
+/// one. Returns `None` if the font doesn't have either table.
+/// https://www.microsoft.com/typography/otspec/os2.htm
+fn descriptor_from_tables(font: &[u8]) -> Option<FontTemplateDescriptor> {
+    if let Some(os2) = sfnt::find_table(font, OS2) {
+        if os2.len() >= OS2_FS_SELECTION_OFFSET + 2 {
+            let weight = match sfnt::read_u16(os2, OS2_WEIGHT_CLASS_OFFSET) {
+                1 | 100...199 => font_weight::T::Weight100,
+                2 | 200...299 => font_weight::T::Weight200,
+                3 | 300...399 => font_weight::T::Weight300,
+                5 | 500...599 => font_weight::T::Weight500,
+                6 | 600...699 => font_weight::T::Weight600,
+                7 | 700...799 => font_weight::T::Weight700,
+                8 | 800...899 => font_weight::T::Weight800,
+                9 | 900...999 => font_weight::T::Weight900,
+                _ => font_weight::T::Weight400,
+            };
+            let stretch = match sfnt::read_u16(os2, OS2_WIDTH_CLASS_OFFSET) {
+                1 => font_stretch::T::ultra_condensed,
+                2 => font_stretch::T::extra_condensed,
+                3 => font_stretch::T::condensed,
+                4 => font_stretch::T::semi_condensed,
+                6 => font_stretch::T::semi_expanded,
+                7 => font_stretch::T::expanded,
+                8 => font_stretch::T::extra_expanded,
+                9 => font_stretch::T::ultra_expanded,
+                _ => font_stretch::T::normal,
+            };
+            let italic = sfnt::read_u16(os2, OS2_FS_SELECTION_OFFSET) & FS_SELECTION_ITALIC != 0;
+            return Some(FontTemplateDescriptor::new(weight, stretch, italic))
+        }
+    }
+
+    match sfnt::find_table(font, HEAD) {
+        Some(head) if head.len() >= HEAD_MAC_STYLE_OFFSET + 2 => {
+            let mac_style = sfnt::read_u16(head, HEAD_MAC_STYLE_OFFSET);
+            let weight = if mac_style & MAC_STYLE_BOLD != 0 {
+                font_weight::T::Weight700
+            } else {
+                font_weight::T::Weight400
+            };
+            Some(FontTemplateDescriptor::new(weight,
+                                             font_stretch::T::normal,
+                                             mac_style & MAC_STYLE_ITALIC != 0))
+        }
+        _ => None,
+    }
+}
+
+impl HeapSizeOf for FontTemplate {
+    fn heap_size_of_children(&self) -> usize {
+        // Only the data that is still loaded is measured, including the instances of a variable
+        // font that are still in use.
+        let data = self.weak_ref.as_ref().and_then(|data| data.upgrade());
+        let instances = self.instances.iter().fold(0, |size, &(ref coordinates, ref instance)| {
+            size + coordinates.heap_size_of_children() +
+                instance.upgrade().map_or(0, |instance| instance.heap_size_of_children())
+        });
+        self.identifier.heap_size_of_children() + data.heap_size_of_children() + instances +
+            self.unicode_range.heap_size_of_children()
+    }
 }
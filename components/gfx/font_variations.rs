@@ -0,0 +1,1031 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+//! Variable fonts, which describe a range of styles along axes such as weight and width.
+//!
+//! Neither the platform font backends nor the painting backend can be told to use a point in
+//! that range, so fonts are instanced here instead: the glyph variations are applied to the
+//! outlines and advances, and the result is written out as a static font that every backend
+//! loads like any other. Only TrueType outlines are instanced.
+//!
+//! https://www.microsoft.com/typography/otspec/otvaroverview.htm
+
+use font::FontTableTag;
+use sfnt::{self, SfntTable, push_u16, push_u32, read_f2dot14, read_fixed, read_u16, read_u32};
+use sfnt::{write_u16, write_u32};
+
+use std::cmp::{max, min};
+
+const AVAR: FontTableTag = 0x61766172;
+const CVAR: FontTableTag = 0x63766172;
+const FVAR: FontTableTag = 0x66766172;
+const GLYF: FontTableTag = 0x676C7966;
+const GVAR: FontTableTag = 0x67766172;
+const HDMX: FontTableTag = 0x68646D78;
+const HEAD: FontTableTag = 0x68656164;
+const HHEA: FontTableTag = 0x68686561;
+const HMTX: FontTableTag = 0x686D7478;
+const HVAR: FontTableTag = 0x48564152;
+const LOCA: FontTableTag = 0x6C6F6361;
+const LTSH: FontTableTag = 0x4C545348;
+const MAXP: FontTableTag = 0x6D617870;
+const MVAR: FontTableTag = 0x4D564152;
+const OS_2: FontTableTag = 0x4F532F32;
+const STAT: FontTableTag = 0x53544154;
+const VDMX: FontTableTag = 0x56444D58;
+
+/// The tag of the weight axis.
+pub const WGHT: FontTableTag = 0x77676874;
+
+/// Tables that describe variations, or device metrics that no longer hold once the outlines
+/// have changed, and so are left out of instances.
+const DROPPED_TABLES: [FontTableTag; 10] =
+    [AVAR, CVAR, FVAR, GVAR, HDMX, HVAR, LTSH, MVAR, STAT, VDMX];
+
+// Simple glyph flags.
+const ON_CURVE_POINT: u8 = 0x01;
+const X_SHORT_VECTOR: u8 = 0x02;
+const Y_SHORT_VECTOR: u8 = 0x04;
+const REPEAT_FLAG: u8 = 0x08;
+const X_IS_SAME_OR_POSITIVE: u8 = 0x10;
+const Y_IS_SAME_OR_POSITIVE: u8 = 0x20;
+
+// Composite glyph flags.
+const ARG_1_AND_2_ARE_WORDS: u16 = 0x0001;
+const ARGS_ARE_XY_VALUES: u16 = 0x0002;
+const WE_HAVE_A_SCALE: u16 = 0x0008;
+const MORE_COMPONENTS: u16 = 0x0020;
+const WE_HAVE_AN_X_AND_Y_SCALE: u16 = 0x0040;
+const WE_HAVE_A_TWO_BY_TWO: u16 = 0x0080;
+const WE_HAVE_INSTRUCTIONS: u16 = 0x0100;
+
+// Glyph variation flags.
+const SHARED_POINT_NUMBERS: u16 = 0x8000;
+const EMBEDDED_PEAK_TUPLE: u16 = 0x8000;
+const INTERMEDIATE_REGION: u16 = 0x4000;
+const PRIVATE_POINT_NUMBERS: u16 = 0x2000;
+const TUPLE_INDEX_MASK: u16 = 0x0FFF;
+
+/// How many components deep composite glyphs are followed when computing their bounds.
+const MAX_COMPOSITE_DEPTH: u32 = 8;
+
+/// An axis along which a variable font varies.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct VariationAxis {
+    pub tag: FontTableTag,
+    pub min: f32,
+    pub default: f32,
+    pub max: f32,
+}
+
+/// A point in the variation space that the font designer gave a name to, such as "Semibold".
+#[derive(Clone, PartialEq, Debug)]
+pub struct NamedInstance {
+    /// The ID of the instance's name in the `name` table.
+    pub subfamily_name_id: u16,
+    /// The instance's coordinate on each axis, in the units of that axis.
+    pub coordinates: Vec<f32>,
+}
+
+/// The axes and named instances of a variable font, from its `fvar` table, along with the
+/// mappings of its `avar` table.
+#[derive(Clone, PartialEq, Debug)]
+pub struct FontVariations {
+    pub axes: Vec<VariationAxis>,
+    pub instances: Vec<NamedInstance>,
+    /// For each axis, pairs of normalized coordinates and what they are mapped to.
+    segment_maps: Vec<Vec<(f32, f32)>>,
+}
+
+impl FontVariations {
+    /// Reads the variations of a font, or returns `None` if it isn't a variable font.
+    pub fn from_font_data(font: &[u8]) -> Option<FontVariations> {
+        let fvar = match sfnt::find_table(font, FVAR) {
+            Some(fvar) if fvar.len() >= 16 => fvar,
+            _ => return None,
+        };
+        let axes_offset = read_u16(fvar, 4) as usize;
+        let axis_count = read_u16(fvar, 8) as usize;
+        let axis_size = read_u16(fvar, 10) as usize;
+        let instance_count = read_u16(fvar, 12) as usize;
+        let instance_size = read_u16(fvar, 14) as usize;
+        let instances_offset = axes_offset + axis_count * axis_size;
+        if axis_count == 0 || axis_size < 20 || instance_size < 4 + axis_count * 4 ||
+                instances_offset + instance_count * instance_size > fvar.len() {
+            return None
+        }
+
+        let axes = (0..axis_count).map(|index| {
+            let record = axes_offset + index * axis_size;
+            let default = read_fixed(fvar, record + 8);
+            VariationAxis {
+                tag: read_u32(fvar, record),
+                min: read_fixed(fvar, record + 4).min(default),
+                default: default,
+                max: read_fixed(fvar, record + 12).max(default),
+            }
+        }).collect();
+        let instances = (0..instance_count).map(|index| {
+            let record = instances_offset + index * instance_size;
+            NamedInstance {
+                subfamily_name_id: read_u16(fvar, record),
+                coordinates: (0..axis_count).map(|axis| {
+                    read_fixed(fvar, record + 4 + axis * 4)
+                }).collect(),
+            }
+        }).collect();
+        let segment_maps = sfnt::find_table(font, AVAR).and_then(|avar| {
+            read_segment_maps(avar, axis_count)
+        }).unwrap_or(vec!());
+
+        Some(FontVariations {
+            axes: axes,
+            instances: instances,
+            segment_maps: segment_maps,
+        })
+    }
+
+    pub fn axis(&self, tag: FontTableTag) -> Option<&VariationAxis> {
+        self.axes.iter().find(|axis| axis.tag == tag)
+    }
+
+    /// Returns the coordinates of the default instance.
+    pub fn default_coordinates(&self) -> Vec<f32> {
+        self.axes.iter().map(|axis| axis.default).collect()
+    }
+
+    /// Returns the coordinates of the default instance, with the weight axis, if there is one,
+    /// moved to `weight`.
+    pub fn coordinates_for_weight(&self, weight: f32) -> Vec<f32> {
+        self.axes.iter().map(|axis| {
+            if axis.tag == WGHT {
+                weight.max(axis.min).min(axis.max)
+            } else {
+                axis.default
+            }
+        }).collect()
+    }
+
+    /// Maps coordinates in the units of each axis to the range from -1 to 1 that glyph
+    /// variations are given in, where the default instance is at 0. Missing coordinates are
+    /// taken to be at the default.
+    pub fn normalize(&self, coordinates: &[f32]) -> Vec<f32> {
+        self.axes.iter().enumerate().map(|(index, axis)| {
+            let value = coordinates.get(index).cloned().unwrap_or(axis.default);
+            let value = value.max(axis.min).min(axis.max);
+            let normalized = if value < axis.default {
+                (value - axis.default) / (axis.default - axis.min)
+            } else if value > axis.default {
+                (value - axis.default) / (axis.max - axis.default)
+            } else {
+                0.0
+            };
+            match self.segment_maps.get(index) {
+                Some(segment_map) => apply_segment_map(segment_map, normalized),
+                None => normalized,
+            }
+        }).collect()
+    }
+}
+
+fn read_segment_maps(avar: &[u8], axis_count: usize) -> Option<Vec<Vec<(f32, f32)>>> {
+    if avar.len() < 8 || read_u16(avar, 6) as usize != axis_count {
+        return None
+    }
+    let mut offset = 8;
+    let mut segment_maps = vec!();
+    for _ in 0..axis_count {
+        if offset + 2 > avar.len() {
+            return None
+        }
+        let count = read_u16(avar, offset) as usize;
+        offset += 2;
+        if offset + count * 4 > avar.len() {
+            return None
+        }
+        segment_maps.push((0..count).map(|index| {
+            (read_f2dot14(avar, offset + index * 4), read_f2dot14(avar, offset + index * 4 + 2))
+        }).collect());
+        offset += count * 4;
+    }
+    Some(segment_maps)
+}
+
+fn apply_segment_map(segment_map: &[(f32, f32)], value: f32) -> f32 {
+    if segment_map.len() < 2 {
+        return value
+    }
+    for segment in segment_map.windows(2) {
+        let (from_start, to_start) = segment[0];
+        let (from_end, to_end) = segment[1];
+        if value <= from_end {
+            if value <= from_start || from_end == from_start {
+                return to_start
+            }
+            return to_start + (value - from_start) * (to_end - to_start) / (from_end - from_start)
+        }
+    }
+    segment_map[segment_map.len() - 1].1
+}
+
+/// Writes a static font with the outlines and advances of the instance at the given
+/// coordinates, in the units of each axis.
+pub fn instantiate(font: &[u8], variations: &FontVariations, coordinates: &[f32])
+                   -> Result<Vec<u8>, &'static str> {
+    let flavor = try!(sfnt::flavor(font).ok_or("truncated font"));
+    let tables = try!(sfnt::tables(font).ok_or("bad table directory"));
+    let find_table = |tag| tables.iter().find(|&&(table_tag, _)| table_tag == tag);
+    let (glyf, loca, head, hhea, hmtx, maxp) =
+        match (find_table(GLYF), find_table(LOCA), find_table(HEAD), find_table(HHEA),
+               find_table(HMTX), find_table(MAXP)) {
+            (Some(&(_, glyf)), Some(&(_, loca)), Some(&(_, head)), Some(&(_, hhea)),
+             Some(&(_, hmtx)), Some(&(_, maxp))) => (glyf, loca, head, hhea, hmtx, maxp),
+            _ => return Err("only TrueType outlines can be instanced"),
+        };
+    if head.len() < 54 || hhea.len() < 36 || maxp.len() < 6 {
+        return Err("truncated tables")
+    }
+    let glyph_count = read_u16(maxp, 4) as usize;
+    let long_loca = read_u16(head, 50) != 0;
+    let metric_count = read_u16(hhea, 34) as usize;
+    let short_metric_count = glyph_count.saturating_sub(metric_count);
+    if metric_count == 0 || hmtx.len() < metric_count * 4 + short_metric_count * 2 {
+        return Err("truncated hmtx table")
+    }
+
+    let normalized = variations.normalize(coordinates);
+    let glyph_variations = match find_table(GVAR) {
+        Some(&(_, gvar)) => Some(try!(GlyphVariations::new(gvar, variations.axes.len()))),
+        None => None,
+    };
+
+    let mut glyphs = Vec::with_capacity(glyph_count);
+    let mut metrics = Vec::with_capacity(glyph_count);
+    for glyph_id in 0..glyph_count {
+        let mut glyph = try!(Glyph::read(try!(glyph_data(glyf, loca, long_loca, glyph_id))));
+        let advance = read_u16(hmtx, min(glyph_id, metric_count - 1) * 4) as f32;
+        let left_side_bearing = if glyph_id < metric_count {
+            read_u16(hmtx, glyph_id * 4 + 2) as i16 as f32
+        } else {
+            read_u16(hmtx, metric_count * 4 + (glyph_id - metric_count) * 2) as i16 as f32
+        };
+
+        // The phantom points at the glyph's origin and advance are varied along with its
+        // outline.
+        let origin = glyph.x_min as f32 - left_side_bearing;
+        let mut points = glyph.points();
+        points.push((origin, 0.0));
+        points.push((origin + advance, 0.0));
+        points.push((0.0, 0.0));
+        points.push((0.0, 0.0));
+        if let Some(ref glyph_variations) = glyph_variations {
+            let deltas = try!(glyph_variations.deltas(glyph_id, &glyph, &points, &normalized));
+            for (point, delta) in points.iter_mut().zip(deltas.into_iter()) {
+                point.0 += delta.0;
+                point.1 += delta.1;
+            }
+        }
+
+        // Move the outline so that the origin stays at zero.
+        let phantom = points.len() - 4;
+        let origin = points[phantom].0;
+        let advance = (points[phantom + 1].0 - origin).round().max(0.0) as u16;
+        for point in points[..phantom].iter_mut() {
+            point.0 -= origin;
+        }
+        glyph.set_points(&points[..phantom]);
+        glyphs.push(glyph);
+        metrics.push(advance);
+    }
+
+    // Now that every simple glyph has its final outline, the bounds of the composite glyphs
+    // can be worked out.
+    for glyph_id in 0..glyph_count {
+        let is_composite = match glyphs[glyph_id].outline {
+            Outline::Composite(..) => true,
+            _ => false,
+        };
+        if !is_composite {
+            continue
+        }
+        if let Some(bounds) = composite_bounds(&glyphs, glyph_id, 0) {
+            glyphs[glyph_id].set_bounds(bounds);
+        }
+    }
+
+    let mut new_glyf = vec!();
+    let mut new_loca = vec!();
+    let mut new_hmtx = vec!();
+    let mut advance_max = 0;
+    for (glyph, &advance) in glyphs.iter().zip(metrics.iter()) {
+        push_u32(&mut new_loca, new_glyf.len() as u32);
+        glyph.write(&mut new_glyf);
+        while new_glyf.len() % 4 != 0 {
+            new_glyf.push(0);
+        }
+        push_u16(&mut new_hmtx, advance);
+        push_u16(&mut new_hmtx, glyph.x_min as u16);
+        advance_max = max(advance_max, advance);
+    }
+    push_u32(&mut new_loca, new_glyf.len() as u32);
+
+    let mut new_head = head.to_vec();
+    write_u32(&mut new_head, 8, 0);
+    write_u16(&mut new_head, 50, 1);
+    let mut new_hhea = hhea.to_vec();
+    write_u16(&mut new_hhea, 10, advance_max);
+    write_u16(&mut new_hhea, 34, glyph_count as u16);
+
+    let mut instance_tables = vec!();
+    for &(tag, table) in tables.iter() {
+        if DROPPED_TABLES.contains(&tag) {
+            continue
+        }
+        let data = match tag {
+            GLYF => new_glyf.clone(),
+            LOCA => new_loca.clone(),
+            HEAD => new_head.clone(),
+            HHEA => new_hhea.clone(),
+            HMTX => new_hmtx.clone(),
+            OS_2 => {
+                let mut os2 = table.to_vec();
+                let weight = variations.axes.iter().position(|axis| axis.tag == WGHT);
+                if let (Some(index), true) = (weight, os2.len() >= 6) {
+                    let weight = coordinates.get(index).cloned()
+                                            .unwrap_or(variations.axes[index].default);
+                    write_u16(&mut os2, 4, weight.round().max(1.0).min(1000.0) as u16);
+                }
+                os2
+            }
+            _ => table.to_vec(),
+        };
+        instance_tables.push(SfntTable {
+            tag: tag,
+            checksum: sfnt::checksum(&data),
+            data: data,
+        });
+    }
+    Ok(sfnt::write_sfnt(flavor, &instance_tables))
+}
+
+fn glyph_data<'a>(glyf: &'a [u8], loca: &[u8], long_loca: bool, glyph_id: usize)
+                  -> Result<&'a [u8], &'static str> {
+    let (start, end) = if long_loca {
+        if (glyph_id + 2) * 4 > loca.len() {
+            return Err("truncated loca table")
+        }
+        (read_u32(loca, glyph_id * 4) as usize, read_u32(loca, glyph_id * 4 + 4) as usize)
+    } else {
+        if (glyph_id + 2) * 2 > loca.len() {
+            return Err("truncated loca table")
+        }
+        (read_u16(loca, glyph_id * 2) as usize * 2, read_u16(loca, glyph_id * 2 + 2) as usize * 2)
+    };
+    if start > end || end > glyf.len() {
+        return Err("glyph out of bounds")
+    }
+    Ok(&glyf[start..end])
+}
+
+/// Reads big-endian numbers from a table, failing instead of panicking at its end.
+struct Reader<'a> {
+    data: &'a [u8],
+    position: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn new(data: &'a [u8], position: usize) -> Reader<'a> {
+        Reader {
+            data: data,
+            position: position,
+        }
+    }
+
+    fn bytes(&mut self, length: usize) -> Result<&'a [u8], &'static str> {
+        if self.position > self.data.len() || length > self.data.len() - self.position {
+            return Err("unexpected end of table")
+        }
+        let bytes = &self.data[self.position..self.position + length];
+        self.position += length;
+        Ok(bytes)
+    }
+
+    fn u8(&mut self) -> Result<u8, &'static str> {
+        Ok(try!(self.bytes(1))[0])
+    }
+
+    fn u16(&mut self) -> Result<u16, &'static str> {
+        Ok(read_u16(try!(self.bytes(2)), 0))
+    }
+
+    fn i16(&mut self) -> Result<i16, &'static str> {
+        Ok(try!(self.u16()) as i16)
+    }
+
+    fn f2dot14(&mut self) -> Result<f32, &'static str> {
+        Ok(read_f2dot14(try!(self.bytes(2)), 0))
+    }
+}
+
+struct Component {
+    flags: u16,
+    glyph_id: u16,
+    /// The offset of the component, or the points that are matched up to place it, depending
+    /// on `ARGS_ARE_XY_VALUES`.
+    arguments: (f32, f32),
+    /// The scale or transform, as stored in the glyph.
+    transform: Vec<u8>,
+}
+
+enum Outline {
+    Empty,
+    Simple {
+        end_points: Vec<u16>,
+        instructions: Vec<u8>,
+        points: Vec<(f32, f32)>,
+        on_curve: Vec<bool>,
+    },
+    Composite(Vec<Component>, Vec<u8>),
+}
+
+struct Glyph {
+    x_min: i16,
+    y_min: i16,
+    x_max: i16,
+    y_max: i16,
+    outline: Outline,
+}
+
+impl Glyph {
+    fn read(data: &[u8]) -> Result<Glyph, &'static str> {
+        if data.is_empty() {
+            return Ok(Glyph {
+                x_min: 0,
+                y_min: 0,
+                x_max: 0,
+                y_max: 0,
+                outline: Outline::Empty,
+            })
+        }
+        let mut reader = Reader::new(data, 0);
+        let contour_count = try!(reader.i16());
+        let x_min = try!(reader.i16());
+        let y_min = try!(reader.i16());
+        let x_max = try!(reader.i16());
+        let y_max = try!(reader.i16());
+        let outline = if contour_count >= 0 {
+            try!(read_simple_outline(&mut reader, contour_count as usize))
+        } else {
+            try!(read_composite_outline(&mut reader))
+        };
+        Ok(Glyph {
+            x_min: x_min,
+            y_min: y_min,
+            x_max: x_max,
+            y_max: y_max,
+            outline: outline,
+        })
+    }
+
+    /// The points that glyph variations move: the points of a simple glyph's outline, or the
+    /// offsets of a composite glyph's components.
+    fn points(&self) -> Vec<(f32, f32)> {
+        match self.outline {
+            Outline::Empty => vec!(),
+            Outline::Simple { ref points, .. } => points.clone(),
+            Outline::Composite(ref components, _) => {
+                components.iter().map(|component| component.arguments).collect()
+            }
+        }
+    }
+
+    fn set_points(&mut self, new_points: &[(f32, f32)]) {
+        match self.outline {
+            Outline::Empty => {}
+            Outline::Simple { ref mut points, .. } => {
+                for (point, new_point) in points.iter_mut().zip(new_points.iter()) {
+                    *point = (new_point.0.round(), new_point.1.round());
+                }
+            }
+            Outline::Composite(ref mut components, _) => {
+                for (component, new_point) in components.iter_mut().zip(new_points.iter()) {
+                    if component.flags & ARGS_ARE_XY_VALUES != 0 {
+                        component.arguments = (new_point.0.round(), new_point.1.round());
+                    }
+                }
+            }
+        }
+        let bounds = match self.outline {
+            Outline::Simple { ref points, .. } => bounds_of_points(points.iter().cloned()),
+            _ => None,
+        };
+        if let Some(bounds) = bounds {
+            self.set_bounds(bounds);
+        }
+    }
+
+    fn set_bounds(&mut self, (x_min, y_min, x_max, y_max): (f32, f32, f32, f32)) {
+        self.x_min = clamp_to_i16(x_min);
+        self.y_min = clamp_to_i16(y_min);
+        self.x_max = clamp_to_i16(x_max);
+        self.y_max = clamp_to_i16(y_max);
+    }
+
+    fn write(&self, glyf: &mut Vec<u8>) {
+        let contour_count = match self.outline {
+            Outline::Empty => return,
+            Outline::Simple { ref end_points, .. } => end_points.len() as u16,
+            Outline::Composite(..) => -1i16 as u16,
+        };
+        push_u16(glyf, contour_count);
+        push_u16(glyf, self.x_min as u16);
+        push_u16(glyf, self.y_min as u16);
+        push_u16(glyf, self.x_max as u16);
+        push_u16(glyf, self.y_max as u16);
+        match self.outline {
+            Outline::Empty => {}
+            Outline::Simple { ref end_points, ref instructions, ref points, ref on_curve } => {
+                for &end_point in end_points.iter() {
+                    push_u16(glyf, end_point);
+                }
+                push_u16(glyf, instructions.len() as u16);
+                glyf.push_all(instructions);
+                // Every coordinate is written as a 16-bit difference from the last one.
+                for &on_curve in on_curve.iter() {
+                    glyf.push(if on_curve { ON_CURVE_POINT } else { 0 });
+                }
+                let mut last = 0;
+                for &(x, _) in points.iter() {
+                    push_u16(glyf, (clamp_to_i16(x) as i32 - last) as u16);
+                    last = clamp_to_i16(x) as i32;
+                }
+                last = 0;
+                for &(_, y) in points.iter() {
+                    push_u16(glyf, (clamp_to_i16(y) as i32 - last) as u16);
+                    last = clamp_to_i16(y) as i32;
+                }
+            }
+            Outline::Composite(ref components, ref instructions) => {
+                for component in components.iter() {
+                    push_u16(glyf, component.flags | ARG_1_AND_2_ARE_WORDS);
+                    push_u16(glyf, component.glyph_id);
+                    push_u16(glyf, clamp_to_i16(component.arguments.0) as u16);
+                    push_u16(glyf, clamp_to_i16(component.arguments.1) as u16);
+                    glyf.push_all(&component.transform);
+                }
+                let has_instructions = components.iter().any(|component| {
+                    component.flags & WE_HAVE_INSTRUCTIONS != 0
+                });
+                if has_instructions {
+                    push_u16(glyf, instructions.len() as u16);
+                    glyf.push_all(instructions);
+                }
+            }
+        }
+    }
+}
+
+fn read_simple_outline(reader: &mut Reader, contour_count: usize)
+                       -> Result<Outline, &'static str> {
+    let mut end_points = Vec::with_capacity(contour_count);
+    for _ in 0..contour_count {
+        end_points.push(try!(reader.u16()));
+    }
+    let point_count = end_points.last().map_or(0, |&end_point| end_point as usize + 1);
+    let instruction_length = try!(reader.u16()) as usize;
+    let instructions = try!(reader.bytes(instruction_length)).to_vec();
+
+    let mut flags = Vec::with_capacity(point_count);
+    while flags.len() < point_count {
+        let flag = try!(reader.u8());
+        flags.push(flag);
+        if flag & REPEAT_FLAG != 0 {
+            for _ in 0..try!(reader.u8()) {
+                flags.push(flag);
+            }
+        }
+    }
+    flags.truncate(point_count);
+
+    let mut xs = Vec::with_capacity(point_count);
+    let mut x = 0i32;
+    for &flag in flags.iter() {
+        x += try!(read_coordinate(reader, flag, X_SHORT_VECTOR, X_IS_SAME_OR_POSITIVE));
+        xs.push(x as f32);
+    }
+    let mut points = Vec::with_capacity(point_count);
+    let mut y = 0i32;
+    for (&flag, &x) in flags.iter().zip(xs.iter()) {
+        y += try!(read_coordinate(reader, flag, Y_SHORT_VECTOR, Y_IS_SAME_OR_POSITIVE));
+        points.push((x, y as f32));
+    }
+
+    Ok(Outline::Simple {
+        end_points: end_points,
+        instructions: instructions,
+        points: points,
+        on_curve: flags.iter().map(|&flag| flag & ON_CURVE_POINT != 0).collect(),
+    })
+}
+
+/// Reads the difference of a coordinate from the previous one.
+fn read_coordinate(reader: &mut Reader, flag: u8, short_vector: u8, same_or_positive: u8)
+                   -> Result<i32, &'static str> {
+    if flag & short_vector != 0 {
+        let value = try!(reader.u8()) as i32;
+        Ok(if flag & same_or_positive != 0 { value } else { -value })
+    } else if flag & same_or_positive != 0 {
+        Ok(0)
+    } else {
+        Ok(try!(reader.i16()) as i32)
+    }
+}
+
+fn read_composite_outline(reader: &mut Reader) -> Result<Outline, &'static str> {
+    let mut components = vec!();
+    let mut have_instructions = false;
+    loop {
+        let flags = try!(reader.u16());
+        let glyph_id = try!(reader.u16());
+        let arguments = if flags & ARG_1_AND_2_ARE_WORDS != 0 {
+            (try!(reader.i16()) as f32, try!(reader.i16()) as f32)
+        } else if flags & ARGS_ARE_XY_VALUES != 0 {
+            (try!(reader.u8()) as i8 as f32, try!(reader.u8()) as i8 as f32)
+        } else {
+            (try!(reader.u8()) as f32, try!(reader.u8()) as f32)
+        };
+        let transform_length = if flags & WE_HAVE_A_SCALE != 0 {
+            2
+        } else if flags & WE_HAVE_AN_X_AND_Y_SCALE != 0 {
+            4
+        } else if flags & WE_HAVE_A_TWO_BY_TWO != 0 {
+            8
+        } else {
+            0
+        };
+        let transform = try!(reader.bytes(transform_length)).to_vec();
+        have_instructions = have_instructions || flags & WE_HAVE_INSTRUCTIONS != 0;
+        components.push(Component {
+            flags: flags,
+            glyph_id: glyph_id,
+            arguments: arguments,
+            transform: transform,
+        });
+        if flags & MORE_COMPONENTS == 0 {
+            break
+        }
+    }
+    let instructions = if have_instructions {
+        let length = try!(reader.u16()) as usize;
+        try!(reader.bytes(length)).to_vec()
+    } else {
+        vec!()
+    };
+    Ok(Outline::Composite(components, instructions))
+}
+
+fn clamp_to_i16(value: f32) -> i16 {
+    value.round().max(-32768.0).min(32767.0) as i16
+}
+
+fn bounds_of_points<I>(points: I) -> Option<(f32, f32, f32, f32)>
+                       where I: Iterator<Item=(f32, f32)> {
+    points.fold(None, |bounds, (x, y)| {
+        Some(match bounds {
+            None => (x, y, x, y),
+            Some((x_min, y_min, x_max, y_max)) => {
+                (x_min.min(x), y_min.min(y), x_max.max(x), y_max.max(y))
+            }
+        })
+    })
+}
+
+/// Works out the bounds of a composite glyph from the bounds of its components, placed and
+/// transformed as the composite glyph says. Components placed by matching points are taken
+/// to be at their own origin.
+fn composite_bounds(glyphs: &[Glyph], glyph_id: usize, depth: u32)
+                    -> Option<(f32, f32, f32, f32)> {
+    let glyph = match glyphs.get(glyph_id) {
+        Some(glyph) => glyph,
+        None => return None,
+    };
+    let components = match glyph.outline {
+        Outline::Empty => return None,
+        Outline::Simple { .. } => {
+            return Some((glyph.x_min as f32, glyph.y_min as f32,
+                         glyph.x_max as f32, glyph.y_max as f32))
+        }
+        Outline::Composite(ref components, _) => components,
+    };
+    if depth >= MAX_COMPOSITE_DEPTH {
+        return None
+    }
+    let mut corners = vec!();
+    for component in components.iter() {
+        let (x_min, y_min, x_max, y_max) =
+            match composite_bounds(glyphs, component.glyph_id as usize, depth + 1) {
+                Some(bounds) => bounds,
+                None => continue,
+            };
+        let (a, b, c, d) = match component.transform.len() {
+            2 => {
+                let scale = read_f2dot14(&component.transform, 0);
+                (scale, 0.0, 0.0, scale)
+            }
+            4 => (read_f2dot14(&component.transform, 0), 0.0,
+                  0.0, read_f2dot14(&component.transform, 2)),
+            8 => (read_f2dot14(&component.transform, 0), read_f2dot14(&component.transform, 2),
+                  read_f2dot14(&component.transform, 4), read_f2dot14(&component.transform, 6)),
+            _ => (1.0, 0.0, 0.0, 1.0),
+        };
+        let (dx, dy) = if component.flags & ARGS_ARE_XY_VALUES != 0 {
+            component.arguments
+        } else {
+            (0.0, 0.0)
+        };
+        for &(x, y) in [(x_min, y_min), (x_min, y_max), (x_max, y_min), (x_max, y_max)].iter() {
+            corners.push((a * x + c * y + dx, b * x + d * y + dy));
+        }
+    }
+    bounds_of_points(corners.into_iter())
+}
+
+/// The glyph variations of a font, from its `gvar` table.
+struct GlyphVariations<'a> {
+    gvar: &'a [u8],
+    axis_count: usize,
+    shared_tuples: Vec<Vec<f32>>,
+    /// Where the variation data of each glyph starts and ends.
+    glyph_offsets: Vec<usize>,
+}
+
+impl<'a> GlyphVariations<'a> {
+    fn new(gvar: &'a [u8], axis_count: usize) -> Result<GlyphVariations<'a>, &'static str> {
+        let mut reader = Reader::new(gvar, 4);
+        if try!(reader.u16()) as usize != axis_count {
+            return Err("gvar and fvar have different axes")
+        }
+        let shared_tuple_count = try!(reader.u16()) as usize;
+        let shared_tuples_offset = read_u32(try!(reader.bytes(4)), 0) as usize;
+        let glyph_count = try!(reader.u16()) as usize;
+        let long_offsets = try!(reader.u16()) & 1 != 0;
+        let data_offset = read_u32(try!(reader.bytes(4)), 0) as usize;
+
+        let mut glyph_offsets = Vec::with_capacity(glyph_count + 1);
+        for _ in 0..glyph_count + 1 {
+            let offset = if long_offsets {
+                read_u32(try!(reader.bytes(4)), 0) as usize
+            } else {
+                try!(reader.u16()) as usize * 2
+            };
+            glyph_offsets.push(data_offset + offset);
+        }
+
+        let mut reader = Reader::new(gvar, shared_tuples_offset);
+        let mut shared_tuples = Vec::with_capacity(shared_tuple_count);
+        for _ in 0..shared_tuple_count {
+            let mut tuple = Vec::with_capacity(axis_count);
+            for _ in 0..axis_count {
+                tuple.push(try!(reader.f2dot14()));
+            }
+            shared_tuples.push(tuple);
+        }
+
+        Ok(GlyphVariations {
+            gvar: gvar,
+            axis_count: axis_count,
+            shared_tuples: shared_tuples,
+            glyph_offsets: glyph_offsets,
+        })
+    }
+
+    /// Works out how far each of the given points of a glyph, followed by its four phantom
+    /// points, moves at the given normalized coordinates.
+    fn deltas(&self, glyph_id: usize, glyph: &Glyph, points: &[(f32, f32)], coordinates: &[f32])
+              -> Result<Vec<(f32, f32)>, &'static str> {
+        let point_count = points.len();
+        let mut total = vec![(0.0, 0.0); point_count];
+        let (start, end) = match (self.glyph_offsets.get(glyph_id),
+                                  self.glyph_offsets.get(glyph_id + 1)) {
+            (Some(&start), Some(&end)) if start < end => (start, end),
+            _ => return Ok(total),
+        };
+        if end > self.gvar.len() {
+            return Err("glyph variations out of bounds")
+        }
+        let data = &self.gvar[start..end];
+
+        let mut header = Reader::new(data, 0);
+        let tuple_count = try!(header.u16());
+        let mut serialized = Reader::new(data, try!(header.u16()) as usize);
+        let shared_points = if tuple_count & SHARED_POINT_NUMBERS != 0 {
+            try!(read_packed_points(&mut serialized))
+        } else {
+            None
+        };
+
+        for _ in 0..(tuple_count & TUPLE_INDEX_MASK) {
+            let size = try!(header.u16()) as usize;
+            let tuple_index = try!(header.u16());
+            let peak = if tuple_index & EMBEDDED_PEAK_TUPLE != 0 {
+                try!(self.read_tuple(&mut header))
+            } else {
+                match self.shared_tuples.get((tuple_index & TUPLE_INDEX_MASK) as usize) {
+                    Some(tuple) => tuple.clone(),
+                    None => return Err("bad shared tuple index"),
+                }
+            };
+            let intermediate = if tuple_index & INTERMEDIATE_REGION != 0 {
+                let start = try!(self.read_tuple(&mut header));
+                let end = try!(self.read_tuple(&mut header));
+                Some((start, end))
+            } else {
+                None
+            };
+            let mut tuple_data = Reader::new(try!(serialized.bytes(size)), 0);
+
+            let scalar = tuple_scalar(&peak, intermediate.as_ref(), coordinates);
+            if scalar == 0.0 {
+                continue
+            }
+
+            let private_points;
+            let tuple_points = if tuple_index & PRIVATE_POINT_NUMBERS != 0 {
+                private_points = try!(read_packed_points(&mut tuple_data));
+                &private_points
+            } else {
+                &shared_points
+            };
+            let delta_count = tuple_points.as_ref().map_or(point_count, |points| points.len());
+            let xs = try!(read_packed_deltas(&mut tuple_data, delta_count));
+            let ys = try!(read_packed_deltas(&mut tuple_data, delta_count));
+
+            match *tuple_points {
+                None => {
+                    for (index, delta) in total.iter_mut().enumerate() {
+                        delta.0 += xs[index] * scalar;
+                        delta.1 += ys[index] * scalar;
+                    }
+                }
+                Some(ref tuple_points) => {
+                    let mut deltas = vec![(0.0, 0.0); point_count];
+                    let mut touched = vec![false; point_count];
+                    for (index, &point) in tuple_points.iter().enumerate() {
+                        let point = point as usize;
+                        if point < point_count {
+                            deltas[point] = (xs[index], ys[index]);
+                            touched[point] = true;
+                        }
+                    }
+                    if let Outline::Simple { ref end_points, .. } = glyph.outline {
+                        interpolate_untouched_points(points, end_points, &mut deltas, &touched);
+                    }
+                    for (delta, tuple_delta) in total.iter_mut().zip(deltas.iter()) {
+                        delta.0 += tuple_delta.0 * scalar;
+                        delta.1 += tuple_delta.1 * scalar;
+                    }
+                }
+            }
+        }
+        Ok(total)
+    }
+
+    fn read_tuple(&self, reader: &mut Reader) -> Result<Vec<f32>, &'static str> {
+        let mut tuple = Vec::with_capacity(self.axis_count);
+        for _ in 0..self.axis_count {
+            tuple.push(try!(reader.f2dot14()));
+        }
+        Ok(tuple)
+    }
+}
+
+/// Works out how much of a tuple's deltas apply at the given coordinates.
+fn tuple_scalar(peak: &[f32], intermediate: Option<&(Vec<f32>, Vec<f32>)>, coordinates: &[f32])
+                -> f32 {
+    let mut scalar = 1.0;
+    for (index, &peak) in peak.iter().enumerate() {
+        let value = coordinates.get(index).cloned().unwrap_or(0.0);
+        if peak == 0.0 || value == peak {
+            continue
+        }
+        let (start, end) = match intermediate {
+            Some(&(ref start, ref end)) => (start[index], end[index]),
+            None => (peak.min(0.0), peak.max(0.0)),
+        };
+        // Regions that are invalid or that span the default instance are ignored.
+        if start > peak || peak > end || (start < 0.0 && end > 0.0) {
+            continue
+        }
+        if value <= start || value >= end {
+            return 0.0
+        }
+        scalar *= if value < peak {
+            (value - start) / (peak - start)
+        } else {
+            (end - value) / (end - peak)
+        };
+    }
+    scalar
+}
+
+/// Reads a list of point numbers, or returns `None` if it stands for every point.
+fn read_packed_points(reader: &mut Reader) -> Result<Option<Vec<u16>>, &'static str> {
+    let first = try!(reader.u8()) as usize;
+    if first == 0 {
+        return Ok(None)
+    }
+    let count = if first & 0x80 != 0 {
+        (first & 0x7F) << 8 | try!(reader.u8()) as usize
+    } else {
+        first
+    };
+    let mut points = Vec::with_capacity(count);
+    let mut point = 0u16;
+    while points.len() < count {
+        let control = try!(reader.u8());
+        for _ in 0..(control & 0x7F) as usize + 1 {
+            let difference = if control & 0x80 != 0 {
+                try!(reader.u16())
+            } else {
+                try!(reader.u8()) as u16
+            };
+            point = point.wrapping_add(difference);
+            points.push(point);
+        }
+    }
+    points.truncate(count);
+    Ok(Some(points))
+}
+
+fn read_packed_deltas(reader: &mut Reader, count: usize) -> Result<Vec<f32>, &'static str> {
+    let mut deltas = Vec::with_capacity(count);
+    while deltas.len() < count {
+        let control = try!(reader.u8());
+        for _ in 0..(control & 0x3F) as usize + 1 {
+            let delta = if control & 0x80 != 0 {
+                0.0
+            } else if control & 0x40 != 0 {
+                try!(reader.i16()) as f32
+            } else {
+                try!(reader.u8()) as i8 as f32
+            };
+            deltas.push(delta);
+        }
+    }
+    deltas.truncate(count);
+    Ok(deltas)
+}
+
+/// Infers how far the points of a simple glyph that a tuple doesn't mention move, from the
+/// nearest points on either side of them in their contour that it does.
+fn interpolate_untouched_points(points: &[(f32, f32)],
+                                end_points: &[u16],
+                                deltas: &mut [(f32, f32)],
+                                touched: &[bool]) {
+    let mut start = 0;
+    for &end in end_points.iter() {
+        let end = end as usize;
+        if end < start || end >= points.len() - 4 {
+            break
+        }
+        let touched_points = (start..end + 1).filter(|&point| touched[point])
+                                             .collect::<Vec<_>>();
+        if !touched_points.is_empty() && touched_points.len() <= end - start {
+            for point in start..end + 1 {
+                if touched[point] {
+                    continue
+                }
+                let next = touched_points.iter().cloned().find(|&other| other > point)
+                                         .unwrap_or(touched_points[0]);
+                let previous = touched_points.iter().rev().cloned().find(|&other| other < point)
+                                             .unwrap_or(touched_points[touched_points.len() - 1]);
+                deltas[point] = (interpolate(points[point].0,
+                                             points[previous].0, deltas[previous].0,
+                                             points[next].0, deltas[next].0),
+                                 interpolate(points[point].1,
+                                             points[previous].1, deltas[previous].1,
+                                             points[next].1, deltas[next].1));
+            }
+        }
+        start = end + 1;
+    }
+}
+
+fn interpolate(value: f32, a: f32, delta_a: f32, b: f32, delta_b: f32) -> f32 {
+    if a == b {
+        return if delta_a == delta_b { delta_a } else { 0.0 }
+    }
+    let (low, delta_low, high, delta_high) = if a < b {
+        (a, delta_a, b, delta_b)
+    } else {
+        (b, delta_b, a, delta_a)
+    };
+    if value <= low {
+        delta_low
+    } else if value >= high {
+        delta_high
+    } else {
+        delta_low + (value - low) * (delta_high - delta_low) / (high - low)
+    }
+}
@@ -21,6 +21,8 @@ extern crate log;
 
 extern crate azure;
 #[macro_use] extern crate bitflags;
+extern crate brotli;
+extern crate flate2;
 extern crate fnv;
 extern crate euclid;
 extern crate layers;
@@ -46,8 +48,8 @@ extern crate url;
 extern crate gfx_traits;
 extern crate canvas_traits;
 
-// Eventually we would like the shaper to be pluggable, as many operating systems have their own
-// shapers. For now, however, this is a hard dependency.
+// The default shaper. Other shapers, such as those of the operating system, can be plugged in
+// behind the `text::shaping::Shaper` trait.
 extern crate harfbuzz;
 
 // Linux and Android-specific library dependencies
@@ -70,12 +72,19 @@ mod paint_context;
 #[path="display_list/mod.rs"]
 pub mod display_list;
 pub mod paint_task;
+pub mod pdf;
 
 // Fonts
+pub mod color_glyphs;
 pub mod font;
+pub mod font_bytes;
 pub mod font_context;
 pub mod font_cache_task;
+pub mod font_list_cache;
 pub mod font_template;
+pub mod font_variations;
+pub mod sfnt;
+pub mod woff;
 
 // Misc.
 mod buffer_map;
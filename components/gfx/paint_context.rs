@@ -4,6 +4,7 @@
 
 //! Painting of display lists using Moz2D/Azure.
 
+use color_glyphs::{ColorGlyphs, GlyphBitmap};
 use gfx_traits::color;
 use display_list::TextOrientation::{SidewaysLeft, SidewaysRight, Upright};
 use display_list::{BLUR_INFLATION_FACTOR, BorderRadii, BoxShadowClipMode, ClippingRegion};
@@ -23,6 +24,7 @@ use azure::azure_hl::{Pattern, PatternRef, Path, PathBuilder, CompositionOp, Ant
 use azure::scaled_font::ScaledFont;
 use azure::{AzFloat, struct__AzDrawOptions, struct__AzGlyph};
 use azure::{struct__AzGlyphBuffer, struct__AzPoint, AzDrawTargetFillGlyphs};
+use azure::AzScaledFontGetPathForGlyphs;
 use euclid::matrix2d::Matrix2D;
 use euclid::point::Point2D;
 use euclid::rect::Rect;
@@ -57,6 +59,8 @@ pub struct PaintContext<'a> {
     pub transient_clip: Option<ClippingRegion>,
     /// A temporary hack to disable clipping optimizations on 3d layers.
     pub layer_kind: LayerKind,
+    /// The time at which animated images are shown, in nanoseconds.
+    pub animation_time: u64,
 }
 
 #[derive(Copy, Clone)]
@@ -106,6 +110,33 @@ impl<'a> PaintContext<'a> {
         self.draw_line_segment(bounds, &Default::default(), color, style);
     }
 
+    /// Draws a wavy line along `bounds`, as a zigzag whose peaks touch its top and bottom edges.
+    pub fn draw_wavy_line(&self, bounds: &Rect<Au>, color: Color) {
+        self.draw_target.make_current();
+
+        let rect = bounds.to_nearest_azure_rect();
+        if rect.size.height <= 0.0 {
+            return
+        }
+
+        let thickness = rect.size.height / 3.0;
+        let draw_opts = DrawOptions::new(1.0, CompositionOp::Over, AntialiasMode::None);
+        let stroke_opts = StrokeOptions::new(thickness,
+                                             JoinStyle::Round,
+                                             CapStyle::Round,
+                                             10 as AzFloat,
+                                             &[]);
+        let pattern = ColorPattern::new(color);
+        let (top, bottom) = (rect.origin.y + thickness * 0.5, rect.max_y() - thickness * 0.5);
+        let mut start = Point2D::new(rect.origin.x, bottom);
+        while start.x < rect.max_x() {
+            let end_y = if start.y == bottom { top } else { bottom };
+            let end = Point2D::new((start.x + rect.size.height).min(rect.max_x()), end_y);
+            self.draw_target.stroke_line(start, end, &pattern, &stroke_opts, &draw_opts);
+            start = end;
+        }
+    }
+
     pub fn draw_push_clip(&self, bounds: &Rect<Au>) {
         let rect = bounds.to_nearest_azure_rect();
         let path_builder = self.draw_target.create_path_builder();
@@ -855,14 +886,20 @@ impl<'a> PaintContext<'a> {
             // FIXME(https://github.com/rust-lang/rust/issues/23338)
             let font = self.font_context.get_paint_font_from_template(
                 &text.text_run.font_template, text.text_run.actual_pt_size);
+            let color_glyphs =
+                self.font_context.get_color_glyphs_from_template(&text.text_run.font_template);
             font
             .borrow()
             .draw_text(&temporary_draw_target.draw_target,
                        &*text.text_run,
                        &text.range,
+                       text.justification_space,
                        baseline_origin,
                        text.text_color,
-                       opts::get().enable_text_antialiasing);
+                       text.stroke_color,
+                       text.stroke_width,
+                       opts::get().enable_text_antialiasing,
+                       color_glyphs.as_ref().map(|color_glyphs| &**color_glyphs));
         }
 
         // Blur, if necessary.
@@ -1209,9 +1246,27 @@ trait ScaledFontExtensionMethods {
                  draw_target: &DrawTarget,
                  run: &Box<TextRun>,
                  range: &Range<CharIndex>,
+                 justification_space: Au,
                  baseline_origin: Point2D<Au>,
                  color: Color,
-                 antialias: bool);
+                 stroke_color: Color,
+                 stroke_width: Au,
+                 antialias: bool,
+                 color_glyphs: Option<&ColorGlyphs>);
+
+    fn fill_glyphs(&self,
+                   draw_target: &DrawTarget,
+                   azglyphs: &mut Vec<struct__AzGlyph>,
+                   color: Color,
+                   antialias: bool);
+
+    /// Draws the outlines of glyphs, centered on the edges of their shapes.
+    fn stroke_glyphs(&self,
+                     draw_target: &DrawTarget,
+                     azglyphs: &mut Vec<struct__AzGlyph>,
+                     color: Color,
+                     width: Au,
+                     antialias: bool);
 }
 
 impl ScaledFontExtensionMethods for ScaledFont {
@@ -1219,43 +1274,126 @@ impl ScaledFontExtensionMethods for ScaledFont {
                  draw_target: &DrawTarget,
                  run: &Box<TextRun>,
                  range: &Range<CharIndex>,
+                 justification_space: Au,
                  baseline_origin: Point2D<Au>,
                  color: Color,
-                 antialias: bool) {
-        let pattern = ColorPattern::new(color);
-        let azure_pattern = pattern.azure_color_pattern;
-        assert!(!azure_pattern.is_null());
-
-        let mut options = struct__AzDrawOptions {
-            mAlpha: 1f64 as AzFloat,
-            mCompositionOp: CompositionOp::Over as u8,
-            mAntialiasMode: if antialias { AntialiasMode::Subpixel as u8 }
-                            else { AntialiasMode::None as u8 }
-        };
-
+                 stroke_color: Color,
+                 stroke_width: Au,
+                 antialias: bool,
+                 color_glyphs: Option<&ColorGlyphs>) {
+        let pixel_size = run.actual_pt_size.to_f32_px();
         let mut origin = baseline_origin.clone();
         let mut azglyphs = vec!();
         azglyphs.reserve(range.length().to_usize());
 
+        // Color glyphs are painted after the others, with their own colors.
+        let mut layered_glyphs = vec!();
+        let mut bitmap_glyphs = vec!();
+
+        let mut previous_index = None;
         for slice in run.natural_word_slices_in_range(range) {
-            for (_i, glyph) in slice.glyphs.iter_glyphs_for_char_range(&slice.range) {
+            for (i, glyph) in slice.glyphs.iter_glyphs_for_char_range(&slice.range) {
+                // Justification space goes after the last glyph of each justification
+                // opportunity.
+                let index = slice.offset + i;
+                if justification_space != Au(0) {
+                    match previous_index {
+                        Some(previous_index) if previous_index != index &&
+                                run.is_justification_opportunity(previous_index) => {
+                            origin = if run.vertical {
+                                Point2D::new(origin.x, origin.y + justification_space)
+                            } else {
+                                Point2D::new(origin.x + justification_space, origin.y)
+                            }
+                        }
+                        _ => {}
+                    }
+                    previous_index = Some(index)
+                }
+
                 let glyph_advance = glyph.advance();
-                let glyph_offset = glyph.offset().unwrap_or(Point2D::zero());
-                let azglyph = struct__AzGlyph {
+                let mut glyph_offset = glyph.offset().unwrap_or(Point2D::zero());
+                // Marks at the start of the run are centered over the last glyphs of the run
+                // before, which they are drawn on.
+                if !run.vertical && index < run.leading_marks {
+                    glyph_offset.x = glyph_offset.x - run.base_advance / 2
+                }
+                let position = Point2D::new((origin.x + glyph_offset.x).to_f32_px(),
+                                            (origin.y + glyph_offset.y).to_f32_px());
+                // The glyphs of vertically shaped runs advance down the page.
+                origin = if run.vertical {
+                    Point2D::new(origin.x, origin.y + glyph_advance)
+                } else {
+                    Point2D::new(origin.x + glyph_advance, origin.y)
+                };
+
+                if let Some(color_glyphs) = color_glyphs {
+                    if let Some(layers) = color_glyphs.layers(glyph.id()) {
+                        layered_glyphs.push((layers, position));
+                        continue
+                    }
+                    if let Some(bitmap) = color_glyphs.bitmap(glyph.id(), pixel_size) {
+                        bitmap_glyphs.push((bitmap, position));
+                        continue
+                    }
+                }
+
+                azglyphs.push(struct__AzGlyph {
                     mIndex: glyph.id() as uint32_t,
                     mPosition: struct__AzPoint {
-                        x: (origin.x + glyph_offset.x).to_f32_px(),
-                        y: (origin.y + glyph_offset.y).to_f32_px(),
+                        x: position.x,
+                        y: position.y,
                     }
-                };
-                origin = Point2D::new(origin.x + glyph_advance, origin.y);
-                azglyphs.push(azglyph)
+                })
             };
         }
 
+        // The outline is drawn over the glyphs, so that half of it covers their edges.
+        self.fill_glyphs(draw_target, &mut azglyphs, color, antialias);
+        if stroke_width > Au(0) {
+            self.stroke_glyphs(draw_target, &mut azglyphs, stroke_color, stroke_width, antialias);
+        }
+
+        for &(layers, position) in layered_glyphs.iter() {
+            for layer in layers.iter() {
+                let mut layer_glyphs = vec!(struct__AzGlyph {
+                    mIndex: layer.glyph_id as uint32_t,
+                    mPosition: struct__AzPoint {
+                        x: position.x,
+                        y: position.y,
+                    }
+                });
+                self.fill_glyphs(draw_target,
+                                 &mut layer_glyphs,
+                                 layer.color.unwrap_or(color),
+                                 antialias);
+            }
+        }
+
+        for &(ref bitmap, position) in bitmap_glyphs.iter() {
+            draw_glyph_bitmap(draw_target, bitmap, position, pixel_size, color);
+        }
+    }
+
+    fn fill_glyphs(&self,
+                   draw_target: &DrawTarget,
+                   azglyphs: &mut Vec<struct__AzGlyph>,
+                   color: Color,
+                   antialias: bool) {
         let azglyph_buf_len = azglyphs.len();
         if azglyph_buf_len == 0 { return; } // Otherwise the Quartz backend will assert.
 
+        let pattern = ColorPattern::new(color);
+        let azure_pattern = pattern.azure_color_pattern;
+        assert!(!azure_pattern.is_null());
+
+        let mut options = struct__AzDrawOptions {
+            mAlpha: 1f64 as AzFloat,
+            mCompositionOp: CompositionOp::Over as u8,
+            mAntialiasMode: if antialias { AntialiasMode::Subpixel as u8 }
+                            else { AntialiasMode::None as u8 }
+        };
+
         let mut glyphbuf = struct__AzGlyphBuffer {
             mGlyphs: azglyphs.as_mut_ptr(),
             mNumGlyphs: azglyph_buf_len as uint32_t
@@ -1271,6 +1409,95 @@ impl ScaledFontExtensionMethods for ScaledFont {
                                    ptr::null_mut());
         }
     }
+
+    fn stroke_glyphs(&self,
+                     draw_target: &DrawTarget,
+                     azglyphs: &mut Vec<struct__AzGlyph>,
+                     color: Color,
+                     width: Au,
+                     antialias: bool) {
+        if azglyphs.is_empty() {
+            return
+        }
+
+        let mut glyphbuf = struct__AzGlyphBuffer {
+            mGlyphs: azglyphs.as_mut_ptr(),
+            mNumGlyphs: azglyphs.len() as uint32_t
+        };
+        let path = unsafe {
+            // TODO(Issue #64): this call needs to move into azure_hl.rs
+            Path::new(AzScaledFontGetPathForGlyphs(self.get_ref(),
+                                                   &mut glyphbuf,
+                                                   draw_target.azure_draw_target))
+        };
+
+        let stroke_options = StrokeOptions::new(width.to_f32_px(),
+                                                JoinStyle::Round,
+                                                CapStyle::Round,
+                                                10 as AzFloat,
+                                                &[]);
+        let antialias_mode = if antialias {
+            AntialiasMode::Subpixel
+        } else {
+            AntialiasMode::None
+        };
+        draw_target.stroke(&path,
+                           PatternRef::Color(&ColorPattern::new(color)),
+                           &stroke_options,
+                           &DrawOptions::new(1.0, CompositionOp::Over, antialias_mode));
+    }
+}
+
+/// Draws the image of a glyph with its origin at `position`, scaled from the size it was drawn
+/// for to `pixel_size`. Monochrome and grayscale images are filled with `color`.
+fn draw_glyph_bitmap(draw_target: &DrawTarget,
+                     bitmap: &GlyphBitmap,
+                     position: Point2D<AzFloat>,
+                     pixel_size: AzFloat,
+                     color: Color) {
+    let image = &bitmap.image;
+    let tinted_pixels;
+    let (pixel_width, pixels, source_format) = match image.pixels {
+        PixelsByColorType::K8(ref pixels) if bitmap.is_mask => {
+            tinted_pixels = tint_glyph_mask(pixels, color);
+            (4, &tinted_pixels, SurfaceFormat::B8G8R8A8)
+        }
+        PixelsByColorType::RGBA8(ref pixels) => (4, pixels, SurfaceFormat::B8G8R8A8),
+        PixelsByColorType::K8(ref pixels) => (1, pixels, SurfaceFormat::A8),
+        PixelsByColorType::RGB8(_) | PixelsByColorType::KA8(_) => return,
+    };
+    draw_target.make_current();
+    let surface = draw_target.create_source_surface_from_data(
+        pixels,
+        Size2D::new(image.width as i32, image.height as i32),
+        (image.width * pixel_width) as i32,
+        source_format);
+
+    let scale = pixel_size / bitmap.ppem as AzFloat;
+    let source_rect = Rect::new(Point2D::new(0.0, 0.0),
+                                Size2D::new(image.width as AzFloat, image.height as AzFloat));
+    let dest_rect = Rect::new(Point2D::new(position.x + bitmap.bearing_x as AzFloat * scale,
+                                           position.y - bitmap.bearing_y as AzFloat * scale),
+                              Size2D::new(image.width as AzFloat * scale,
+                                          image.height as AzFloat * scale));
+    draw_target.draw_surface(surface,
+                             dest_rect,
+                             source_rect,
+                             DrawSurfaceOptions::new(Filter::Linear, true),
+                             DrawOptions::new(1.0, CompositionOp::Over, AntialiasMode::None));
+}
+
+/// Fills the coverage of a glyph with `color`, as premultiplied blue, green, red and alpha.
+fn tint_glyph_mask(coverage: &[u8], color: Color) -> Vec<u8> {
+    let mut pixels = Vec::with_capacity(coverage.len() * 4);
+    for &value in coverage.iter() {
+        let alpha = color.a * value as AzFloat / 255.0;
+        pixels.push((color.b * alpha * 255.0) as u8);
+        pixels.push((color.g * alpha * 255.0) as u8);
+        pixels.push((color.r * alpha * 255.0) as u8);
+        pixels.push((alpha * 255.0) as u8);
+    }
+    pixels
 }
 
 trait DrawTargetExtensions {
@@ -9,6 +9,7 @@ use display_list::{self, StackingContext};
 use font_cache_task::FontCacheTask;
 use font_context::FontContext;
 use paint_context::PaintContext;
+use pdf::{self, PaperSize, PdfPage};
 
 use azure::azure_hl::{SurfaceFormat, Color, DrawTarget, BackendType};
 use azure::AzFloat;
@@ -16,6 +17,7 @@ use euclid::Matrix4;
 use euclid::point::Point2D;
 use euclid::rect::Rect;
 use euclid::size::Size2D;
+use gfx_traits::color;
 use layers::platform::surface::{NativeDisplay, NativeSurface};
 use layers::layers::{BufferRequest, LayerBuffer, LayerBufferSet};
 use layers;
@@ -25,17 +27,22 @@ use msg::compositor_msg::{LayerProperties, PaintListener, ScrollPolicy};
 use msg::constellation_msg::Msg as ConstellationMsg;
 use msg::constellation_msg::{ConstellationChan, Failure, PipelineId};
 use msg::constellation_msg::PipelineExitType;
-use profile_traits::mem::{self, Report, Reporter, ReportsChan};
+use net_traits::image::base::AnimatedImage;
+use profile_traits::mem::{self, Report, ReportUnits, Reporter, ReportsChan};
 use profile_traits::time::{self, profile};
 use rand::{self, Rng};
 use skia::SkiaGrGLNativeContextRef;
 use std::borrow::ToOwned;
+use std::fs::File;
+use std::io;
 use std::mem as std_mem;
 use std::sync::{Arc, Mutex};
 use std::sync::mpsc::{Receiver, Sender, channel};
 use std::collections::HashMap;
+use time::precise_time_ns;
 use url::Url;
 use util::geometry::{Au, ZERO_POINT};
+use util::mem::HeapSizeOf;
 use util::opts;
 use util::task::spawn_named_with_send_on_failure;
 use util::task_state;
@@ -79,6 +86,12 @@ pub enum Msg {
     PaintPermissionGranted,
     PaintPermissionRevoked,
     CollectReports(ReportsChan),
+    /// Sent by the compositor every frame while animated images are playing, so that they can
+    /// be repainted when their frames change.
+    TickAnimatedImages,
+    /// Prints the page to a PDF file at the given path, on paper of the given size, and replies
+    /// with whether the file could be written.
+    PrintToPdf(String, PaperSize, Sender<bool>),
     Exit(Option<Sender<()>>, PipelineExitType),
 }
 
@@ -149,6 +162,18 @@ pub struct PaintTask<C> {
 
     /// A map to track the canvas specific layers
     canvas_map: HashMap<LayerId, Arc<Mutex<Sender<CanvasMsg>>>>,
+
+    /// The animated images shown by the root stacking context.
+    animated_images: Vec<Arc<AnimatedImage>>,
+
+    /// The time animated images are painted at, in nanoseconds.
+    animation_time: u64,
+
+    /// Whether the compositor has been told that animated images are playing.
+    animated_images_running: bool,
+
+    /// The font cache, for the font context used to print.
+    font_cache_task: FontCacheTask,
 }
 
 // If we implement this as a function, we get borrowck errors from borrowing
@@ -180,7 +205,7 @@ impl<C> PaintTask<C> where C: PaintListener + Send + 'static {
                 let native_display = compositor.native_display().map(
                     |display| display);
                 let worker_threads = WorkerThreadProxy::spawn(native_display.clone(),
-                                                              font_cache_task,
+                                                              font_cache_task.clone(),
                                                               time_profiler_chan.clone());
 
                 // Register this thread as a memory reporter, via its own channel.
@@ -206,7 +231,11 @@ impl<C> PaintTask<C> where C: PaintListener + Send + 'static {
                     buffer_map: BufferMap::new(10000000),
                     worker_threads: worker_threads,
                     used_buffer_count: 0,
-                    canvas_map: HashMap::new()
+                    canvas_map: HashMap::new(),
+                    animated_images: vec!(),
+                    animation_time: precise_time_ns(),
+                    animated_images_running: false,
+                    font_cache_task: font_cache_task,
                 };
 
                 paint_task.start();
@@ -239,6 +268,12 @@ impl<C> PaintTask<C> where C: PaintListener + Send + 'static {
                     self.current_epoch = Some(epoch);
                     self.root_stacking_context = Some(stacking_context.clone());
 
+                    self.animated_images.clear();
+                    stacking_context.collect_animated_images(&mut self.animated_images);
+                    self.animation_time = precise_time_ns();
+                    let animation_time = self.animation_time;
+                    self.update_animated_images_state(animation_time);
+
                     if !self.paint_permission {
                         debug!("PaintTask: paint ready msg");
                         let ConstellationChan(ref mut c) = self.constellation_chan;
@@ -272,10 +307,12 @@ impl<C> PaintTask<C> where C: PaintListener + Send + 'static {
                     }
 
                     let mut replies = Vec::new();
+                    let animation_time = self.animation_time;
                     for PaintRequest { buffer_requests, scale, layer_id, epoch, layer_kind }
                           in requests.into_iter() {
                         if self.current_epoch == Some(epoch) {
-                            self.paint(&mut replies, buffer_requests, scale, layer_id, layer_kind);
+                            self.paint(&mut replies, buffer_requests, scale, layer_id, layer_kind,
+                                       animation_time);
                         } else {
                             debug!("painter epoch mismatch: {:?} != {:?}", self.current_epoch, epoch);
                         }
@@ -319,12 +356,45 @@ impl<C> PaintTask<C> where C: PaintListener + Send + 'static {
                 Msg::CollectReports(reports_chan) => {
                     // FIXME(njn): should eventually measure other parts of the paint task.
                     let mut reports = vec![];
+                    let page = format!("url({}) pipeline({})", self.url, self.id.0);
                     reports.push(Report {
-                        path: path!["pages", format!("url({})", self.url), "paint-task", "buffer-map"],
+                        path: path!["pages", page, "paint-task", "buffer-map"],
+                        units: ReportUnits::Bytes,
                         size: self.buffer_map.mem(),
                     });
+                    for (i, worker_thread) in self.worker_threads.iter_mut().enumerate() {
+                        reports.push(Report {
+                            path: path!["pages", page,
+                                        format!("paint-worker-{}-font-context", i)],
+                            units: ReportUnits::Bytes,
+                            size: worker_thread.heap_size_of_font_context(),
+                        });
+                    }
                     reports_chan.send(reports);
                 }
+                Msg::TickAnimatedImages => {
+                    let now = precise_time_ns();
+                    let frame_changed = self.animated_images.iter().any(|animation| {
+                        animation.frame_index_at(now) !=
+                            animation.frame_index_at(self.animation_time)
+                    });
+                    if frame_changed {
+                        self.animation_time = now;
+                        // Sending the layers again makes the compositor ask for them to be
+                        // repainted, with no need for a new display list.
+                        if self.paint_permission && !waiting_for_compositor_buffers_to_exit {
+                            self.initialize_layers();
+                        }
+                    }
+                    self.update_animated_images_state(now);
+                }
+                Msg::PrintToPdf(path, paper_size, reply) => {
+                    let result = self.print_to_pdf(&path, paper_size);
+                    if let Err(ref error) = result {
+                        warn!("failed to print to {}: {}", path, error);
+                    }
+                    let _ = reply.send(result.is_ok());
+                }
                 Msg::Exit(response_channel, exit_type) => {
                     let msg = mem::ProfilerMsg::UnregisterReporter(self.reporter_name.clone());
                     self.mem_profiler_chan.send(msg);
@@ -404,7 +474,8 @@ impl<C> PaintTask<C> where C: PaintListener + Send + 'static {
               mut tiles: Vec<BufferRequest>,
               scale: f32,
               layer_id: LayerId,
-              layer_kind: LayerKind) {
+              layer_kind: LayerKind,
+              animation_time: u64) {
         time::profile(time::ProfilerCategory::Painting, None, self.time_profiler_chan.clone(), || {
             // Bail out if there is no appropriate stacking context.
             let stacking_context = if let Some(ref stacking_context) = self.root_stacking_context {
@@ -429,7 +500,8 @@ impl<C> PaintTask<C> where C: PaintListener + Send + 'static {
                                                           layer_buffer,
                                                           stacking_context.clone(),
                                                           scale,
-                                                          layer_kind);
+                                                          layer_kind,
+                                                          animation_time);
             }
             let new_buffers = (0..tile_count).map(|i| {
                 let thread_id = i % self.worker_threads.len();
@@ -443,6 +515,99 @@ impl<C> PaintTask<C> where C: PaintListener + Send + 'static {
         })
     }
 
+    /// Paints the page onto sheets of `paper_size`, and writes them to a PDF file at `path`. The
+    /// compositor has the page laid out for the paper first, but content wider than the paper is
+    /// scaled down to fit. Only the root stacking context is printed, so content with layers of
+    /// its own, and the content of iframes, is left out.
+    fn print_to_pdf(&mut self, path: &str, paper_size: PaperSize) -> io::Result<()> {
+        let stacking_context = match self.root_stacking_context {
+            Some(ref stacking_context) => stacking_context.clone(),
+            None => return Err(io::Error::new(io::ErrorKind::Other, "the page has no layout")),
+        };
+
+        // Paper sizes are in points, and there are 96 CSS pixels to the 72 points of an inch.
+        let page_size = Size2D::new((paper_size.width * 96. / 72.).round(),
+                                    (paper_size.height * 96. / 72.).round());
+        let content_size = Size2D::new(stacking_context.overflow.size.width.to_f32_px(),
+                                       stacking_context.overflow.size.height.to_f32_px());
+        let scale = if content_size.width > page_size.width {
+            page_size.width / content_size.width
+        } else {
+            1.0
+        };
+        let page_height = page_size.height / scale;
+        let page_count = ((content_size.height / page_height).ceil() as usize).max(1);
+
+        let mut font_context = box FontContext::new(self.font_cache_task.clone());
+        let size = Size2D::new(page_size.width as i32, page_size.height as i32);
+        let mut pages = vec!();
+        for page_index in 0..page_count {
+            let draw_target = DrawTarget::new(BackendType::Skia, size, SurfaceFormat::B8G8R8A8);
+            {
+                let page_rect = Rect::new(Point2D::new(0.0, page_index as f32 * page_height),
+                                          Size2D::new(page_size.width / scale, page_height));
+                let mut paint_context = PaintContext {
+                    draw_target: draw_target.clone(),
+                    font_context: &mut font_context,
+                    page_rect: page_rect,
+                    screen_rect: Rect::new(Point2D::zero(),
+                                           Size2D::new(size.width as usize, size.height as usize)),
+                    clip_rect: None,
+                    transient_clip: None,
+                    layer_kind: LayerKind::Layer2D,
+                    animation_time: self.animation_time,
+                };
+                paint_context.draw_solid_color(&Rect::new(Point2D::zero(),
+                                                          Size2D::new(Au::from_px(size.width),
+                                                                      Au::from_px(size.height))),
+                                               color::white());
+
+                // As with tiles, the stacking context is painted from its overflow rect's origin.
+                let page_bounds = page_rect.translate(
+                    &Point2D::new(stacking_context.overflow.origin.x.to_f32_px(),
+                                  stacking_context.overflow.origin.y.to_f32_px()));
+                let matrix = Matrix4::identity();
+                let matrix = matrix.scale(scale as AzFloat, scale as AzFloat, 1.0);
+                let matrix = matrix.translate(-page_bounds.origin.x as AzFloat,
+                                              -page_bounds.origin.y as AzFloat,
+                                              0.0);
+                stacking_context.optimize_and_draw_into_context(&mut paint_context,
+                                                                &page_bounds,
+                                                                &matrix,
+                                                                None);
+                paint_context.draw_target.flush();
+            }
+
+            // The page is opaque, so the premultiplied BGRA pixels only need reordering.
+            let mut pixels = Vec::with_capacity((size.width * size.height * 3) as usize);
+            draw_target.snapshot().get_data_surface().with_data(|data| {
+                for bgra in data.chunks(4) {
+                    pixels.push(bgra[2]);
+                    pixels.push(bgra[1]);
+                    pixels.push(bgra[0]);
+                }
+            });
+            pages.push(PdfPage {
+                width: size.width as u32,
+                height: size.height as u32,
+                pixels: pixels,
+            });
+        }
+
+        let mut file = try!(File::create(path));
+        pdf::write_pdf(&mut file, paper_size, &pages)
+    }
+
+    /// Tells the compositor whether any animated images are still playing at `time`, if that
+    /// has changed.
+    fn update_animated_images_state(&mut self, time: u64) {
+        let running = self.animated_images.iter().any(|animation| !animation.has_finished(time));
+        if running != self.animated_images_running {
+            self.animated_images_running = running;
+            self.compositor.set_animated_images_running(self.id, running);
+        }
+    }
+
     fn initialize_layers(&mut self) {
         let root_stacking_context = match self.root_stacking_context {
             None => return,
@@ -562,19 +727,31 @@ impl WorkerThreadProxy {
                   layer_buffer: Option<Box<LayerBuffer>>,
                   stacking_context: Arc<StackingContext>,
                   scale: f32,
-                  layer_kind: LayerKind) {
+                  layer_kind: LayerKind,
+                  animation_time: u64) {
         let msg = MsgToWorkerThread::PaintTile(thread_id,
                                                tile,
                                                layer_buffer,
                                                stacking_context,
                                                scale,
-                                               layer_kind);
+                                               layer_kind,
+                                               animation_time);
         self.sender.send(msg).unwrap()
     }
 
     fn get_painted_tile_buffer(&mut self) -> Box<LayerBuffer> {
         match self.receiver.recv().unwrap() {
             MsgFromWorkerThread::PaintedTile(layer_buffer) => layer_buffer,
+            MsgFromWorkerThread::HeapSizeOfFontContext(_) => panic!("Expected a painted tile!"),
+        }
+    }
+
+    /// Measures the fonts, shaped text and glyph images the worker has cached.
+    fn heap_size_of_font_context(&mut self) -> usize {
+        self.sender.send(MsgToWorkerThread::HeapSizeOfFontContext).unwrap();
+        match self.receiver.recv().unwrap() {
+            MsgFromWorkerThread::HeapSizeOfFontContext(size) => size,
+            MsgFromWorkerThread::PaintedTile(_) => panic!("Expected a font context size!"),
         }
     }
 
@@ -613,12 +790,18 @@ impl WorkerThread {
         loop {
             match self.receiver.recv().unwrap() {
                 MsgToWorkerThread::Exit => break,
-                MsgToWorkerThread::PaintTile(thread_id, tile, layer_buffer, stacking_context, scale, layer_kind) => {
+                MsgToWorkerThread::HeapSizeOfFontContext => {
+                    let size = self.font_context.heap_size_of_children();
+                    self.sender.send(MsgFromWorkerThread::HeapSizeOfFontContext(size)).unwrap()
+                }
+                MsgToWorkerThread::PaintTile(thread_id, tile, layer_buffer, stacking_context, scale, layer_kind,
+                                             animation_time) => {
                     let draw_target = self.optimize_and_paint_tile(thread_id,
                                                                    &tile,
                                                                    stacking_context,
                                                                    scale,
-                                                                   layer_kind);
+                                                                   layer_kind,
+                                                                   animation_time);
                     let buffer = self.create_layer_buffer_for_painted_tile(&tile,
                                                                            layer_buffer,
                                                                            draw_target,
@@ -634,7 +817,8 @@ impl WorkerThread {
                                tile: &BufferRequest,
                                stacking_context: Arc<StackingContext>,
                                scale: f32,
-                               layer_kind: LayerKind)
+                               layer_kind: LayerKind,
+                               animation_time: u64)
                                -> DrawTarget {
         let size = Size2D::new(tile.screen_rect.size.width as i32, tile.screen_rect.size.height as i32);
         let draw_target = if !opts::get().gpu_painting {
@@ -663,6 +847,7 @@ impl WorkerThread {
                 clip_rect: None,
                 transient_clip: None,
                 layer_kind: layer_kind,
+                animation_time: animation_time,
             };
 
             // Apply a translation to start at the boundaries of the stacking context, since the
@@ -758,11 +943,13 @@ impl WorkerThread {
 
 enum MsgToWorkerThread {
     Exit,
-    PaintTile(usize, BufferRequest, Option<Box<LayerBuffer>>, Arc<StackingContext>, f32, LayerKind),
+    HeapSizeOfFontContext,
+    PaintTile(usize, BufferRequest, Option<Box<LayerBuffer>>, Arc<StackingContext>, f32, LayerKind, u64),
 }
 
 enum MsgFromWorkerThread {
     PaintedTile(Box<LayerBuffer>),
+    HeapSizeOfFontContext(usize),
 }
 
 pub static THREAD_TINT_COLORS: [Color; 8] = [
@@ -0,0 +1,145 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+//! Writes printed pages out as a PDF document.
+//!
+//! Each page is a painted image of the page's display list, compressed and scaled to the paper
+//! size. See the PDF 1.4 reference for the file structure.
+
+use flate2::Compression;
+use flate2::write::ZlibEncoder;
+use std::ascii::AsciiExt;
+use std::io::{self, Write};
+
+/// The width and height of a sheet of paper, in points (1/72 of an inch).
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct PaperSize {
+    pub width: f32,
+    pub height: f32,
+}
+
+impl PaperSize {
+    /// ISO 216 A4, 210 × 297 mm.
+    pub fn a4() -> PaperSize {
+        PaperSize {
+            width: 595.276,
+            height: 841.89,
+        }
+    }
+
+    /// US Letter, 8.5 × 11 inches.
+    pub fn letter() -> PaperSize {
+        PaperSize {
+            width: 612.,
+            height: 792.,
+        }
+    }
+
+    /// Parses the name of a paper size, as given on the command line.
+    pub fn from_name(name: &str) -> Option<PaperSize> {
+        match &*name.to_ascii_lowercase() {
+            "a4" => Some(PaperSize::a4()),
+            "letter" => Some(PaperSize::letter()),
+            _ => None,
+        }
+    }
+}
+
+/// A painted page, as RGB pixels with eight bits per channel.
+pub struct PdfPage {
+    pub width: u32,
+    pub height: u32,
+    pub pixels: Vec<u8>,
+}
+
+/// Writes `pages` as a PDF document with one page of `paper_size` each. Every image fills the
+/// width of its page, and is placed at the top of it.
+pub fn write_pdf<W: Write>(output: &mut W, paper_size: PaperSize, pages: &[PdfPage])
+                           -> io::Result<()> {
+    let mut document = PdfDocument::new();
+
+    // The catalog and page tree are objects 1 and 2, and each page takes three objects after
+    // that: the page, its content stream, and its image.
+    let kids = (0..pages.len()).map(|i| format!("{} 0 R", 3 + i * 3))
+                               .collect::<Vec<_>>()
+                               .connect(" ");
+    document.object(b"<< /Type /Catalog /Pages 2 0 R >>");
+    document.object(format!("<< /Type /Pages /Kids [{}] /Count {} >>",
+                            kids, pages.len()).as_bytes());
+
+    for (i, page) in pages.iter().enumerate() {
+        let content_id = 4 + i * 3;
+        let image_id = 5 + i * 3;
+        document.object(format!("<< /Type /Page /Parent 2 0 R /MediaBox [0 0 {} {}] \
+                                 /Resources << /XObject << /Page{} {} 0 R >> >> \
+                                 /Contents {} 0 R >>",
+                                paper_size.width, paper_size.height,
+                                i, image_id, content_id).as_bytes());
+
+        let image_height = paper_size.width * (page.height as f32) / (page.width as f32);
+        let content = format!("q {} 0 0 {} 0 {} cm /Page{} Do Q",
+                              paper_size.width, image_height,
+                              paper_size.height - image_height, i);
+        document.stream(b"", content.as_bytes());
+
+        let mut encoder = ZlibEncoder::new(Vec::new(), Compression::Default);
+        try!(encoder.write_all(&page.pixels));
+        let compressed = try!(encoder.finish());
+        document.stream(format!("/Type /XObject /Subtype /Image /Width {} /Height {} \
+                                 /ColorSpace /DeviceRGB /BitsPerComponent 8 \
+                                 /Filter /FlateDecode",
+                                page.width, page.height).as_bytes(),
+                        &compressed);
+    }
+
+    output.write_all(&document.finish())
+}
+
+/// A PDF file being written, with the offsets of the objects written so far.
+struct PdfDocument {
+    data: Vec<u8>,
+    offsets: Vec<usize>,
+}
+
+impl PdfDocument {
+    fn new() -> PdfDocument {
+        PdfDocument {
+            data: b"%PDF-1.4\n".to_vec(),
+            offsets: vec!(),
+        }
+    }
+
+    /// Writes the next indirect object.
+    fn object(&mut self, object: &[u8]) {
+        self.offsets.push(self.data.len());
+        let header = format!("{} 0 obj\n", self.offsets.len());
+        self.data.extend(header.as_bytes().iter().cloned());
+        self.data.extend(object.iter().cloned());
+        self.data.extend(b"\nendobj\n".iter().cloned());
+    }
+
+    /// Writes the next indirect object as a stream, with `dictionary` as the entries of its
+    /// dictionary other than its length.
+    fn stream(&mut self, dictionary: &[u8], stream: &[u8]) {
+        let mut object = b"<< ".to_vec();
+        object.extend(dictionary.iter().cloned());
+        object.extend(format!(" /Length {} >>\nstream\n", stream.len()).as_bytes().iter().cloned());
+        object.extend(stream.iter().cloned());
+        object.extend(b"\nendstream".iter().cloned());
+        self.object(&object);
+    }
+
+    /// Writes the cross-reference table and trailer, and returns the complete file.
+    fn finish(mut self) -> Vec<u8> {
+        let xref_offset = self.data.len();
+        let mut xref = format!("xref\n0 {}\n0000000000 65535 f \n", self.offsets.len() + 1);
+        for offset in self.offsets.iter() {
+            xref.push_str(&format!("{:010} 00000 n \n", offset));
+        }
+        xref.push_str(&format!("trailer\n<< /Size {} /Root 1 0 R >>\nstartxref\n{}\n%%EOF\n",
+                               self.offsets.len() + 1, xref_offset));
+        self.data.extend(xref.as_bytes().iter().cloned());
+        self.data
+    }
+}
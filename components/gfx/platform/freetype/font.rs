@@ -15,11 +15,11 @@ use style::computed_values::{font_stretch, font_weight};
 use platform::font_template::FontTemplateData;
 
 use freetype::freetype::{FT_Get_Char_Index, FT_Get_Postscript_Name};
-use freetype::freetype::{FT_Load_Glyph, FT_Set_Char_Size};
+use freetype::freetype::{FT_Load_Glyph, FT_Select_Size, FT_Set_Char_Size};
 use freetype::freetype::{FT_Get_Kerning, FT_Get_Sfnt_Table};
 use freetype::freetype::{FT_New_Memory_Face, FT_Done_Face};
 use freetype::freetype::{FTErrorMethods, FT_F26Dot6, FT_Face, FT_FaceRec};
-use freetype::freetype::{FT_GlyphSlot, FT_Library, FT_Long, FT_ULong};
+use freetype::freetype::{FT_GlyphSlot, FT_Int, FT_Library, FT_Long, FT_ULong};
 use freetype::freetype::{FT_KERNING_DEFAULT, FT_STYLE_FLAG_ITALIC, FT_STYLE_FLAG_BOLD};
 use freetype::freetype::{FT_SizeRec, FT_UInt, FT_Size_Metrics, struct_FT_Vector_};
 use freetype::freetype::{ft_sfnt_os2};
@@ -28,6 +28,7 @@ use freetype::tt_os2::TT_OS2;
 use libc::c_char;
 use std::mem;
 use std::ptr;
+use std::slice;
 use std::sync::Arc;
 
 fn float_to_fixed_ft(f: f64) -> i32 {
@@ -158,8 +159,24 @@ impl FontHandleMethods for FontHandle {
         }
     }
     fn stretchiness(&self) -> font_stretch::T {
-        // TODO(pcwalton): Implement this.
-        font_stretch::T::normal
+        unsafe {
+            let os2 = FT_Get_Sfnt_Table(self.face, ft_sfnt_os2) as *mut TT_OS2;
+            let valid = !os2.is_null() && (*os2).version != 0xffff;
+            if !valid {
+                return font_stretch::T::normal
+            }
+            match (*os2).usWidthClass {
+                1 => font_stretch::T::ultra_condensed,
+                2 => font_stretch::T::extra_condensed,
+                3 => font_stretch::T::condensed,
+                4 => font_stretch::T::semi_condensed,
+                6 => font_stretch::T::semi_expanded,
+                7 => font_stretch::T::expanded,
+                8 => font_stretch::T::extra_expanded,
+                9 => font_stretch::T::ultra_expanded,
+                _ => font_stretch::T::normal,
+            }
+        }
     }
 
     fn glyph_index(&self, codepoint: char) -> Option<GlyphId> {
@@ -207,6 +224,9 @@ impl FontHandleMethods for FontHandle {
     fn metrics(&self) -> FontMetrics {
         /* TODO(Issue #76): complete me */
         let face = self.face_rec_mut();
+        if face.units_per_EM == 0 {
+            return self.bitmap_strike_metrics();
+        }
 
         let underline_size = self.font_units_to_au(face.underline_thickness as f64);
         let underline_offset = self.font_units_to_au(face.underline_position as f64);
@@ -272,23 +292,69 @@ impl<'a> FontHandle {
 
         unsafe {
             let result = FT_Set_Char_Size(face, char_width, 0, 0, 0);
+            if result.succeeded() {
+                return Ok(())
+            }
+
+            // Fonts that only have embedded bitmaps can only be used at the sizes of their
+            // strikes, so use the one closest to the requested size.
+            let face_rec = &*face;
+            if face_rec.num_fixed_sizes <= 0 {
+                return Err(())
+            }
+            let strikes = slice::from_raw_parts(face_rec.available_sizes,
+                                                face_rec.num_fixed_sizes as usize);
+            let closest_strike = (0..strikes.len()).min_by(|&index| {
+                (strikes[index].y_ppem as i64 - char_width as i64).abs()
+            }).unwrap();
+            let result = FT_Select_Size(face, closest_strike as FT_Int);
             if result.succeeded() { Ok(()) } else { Err(()) }
         }
     }
 
+    /// Returns the metrics of a font that only has embedded bitmaps, which has no font units,
+    /// from those of its selected strike.
+    fn bitmap_strike_metrics(&self) -> FontMetrics {
+        let metrics = self.size_metrics();
+        let ascent = Au::from_f64_px(fixed_to_float_ft(metrics.ascender as i32));
+        let descent = Au::from_f64_px(-fixed_to_float_ft(metrics.descender as i32));
+        let height = Au::from_f64_px(fixed_to_float_ft(metrics.height as i32));
+        let max_advance = Au::from_f64_px(fixed_to_float_ft(metrics.max_advance as i32));
+        let em_size = Au::from_px(metrics.y_ppem as i32);
+        FontMetrics {
+            underline_size:   Au::from_px(1),
+            underline_offset: Au::from_px(-1),
+            strikeout_size:   Au::from_px(1),
+            strikeout_offset: ascent / 3,
+            leading:          height - (ascent + descent),
+            x_height:         em_size / 2,
+            em_size:          em_size,
+            ascent:           ascent,
+            descent:          descent,
+            max_advance:      max_advance,
+            average_advance:  max_advance,
+            line_gap:         height,
+        }
+    }
+
     fn face_rec_mut(&'a self) -> &'a mut FT_FaceRec {
         unsafe {
             &mut (*self.face)
         }
     }
 
-    fn font_units_to_au(&self, value: f64) -> Au {
+    fn size_metrics(&'a self) -> &'a FT_Size_Metrics {
         let face = self.face_rec_mut();
 
         // face.size is a *c_void in the bindings, presumably to avoid
         // recursive structural types
         let size: &FT_SizeRec = unsafe { mem::transmute(&(*face.size)) };
-        let metrics: &FT_Size_Metrics = &(*size).metrics;
+        &(*size).metrics
+    }
+
+    fn font_units_to_au(&self, value: f64) -> Au {
+        let face = self.face_rec_mut();
+        let metrics = self.size_metrics();
 
         let em_size = face.units_per_EM as f64;
         let x_scale = (metrics.x_ppem as f64) / em_size as f64;
@@ -11,7 +11,7 @@ use fontconfig::fontconfig::{FcChar8, FcResultMatch, FcSetSystem};
 use fontconfig::fontconfig::{
     FcConfigGetCurrent, FcConfigGetFonts,
     FcConfigSubstitute, FcDefaultSubstitute,
-    FcFontMatch,
+    FcFontMatch, FcFontSort,
     FcNameParse, FcPatternGetString,
     FcPatternDestroy, FcFontSetDestroy,
     FcMatchPattern,
@@ -20,18 +20,23 @@ use fontconfig::fontconfig::{
     FcObjectSetAdd, FcPatternGetInteger
 };
 
+use text::util::HanVariant;
 use util::str::c_str_to_string;
 
 use libc;
 use libc::{c_int, c_char};
 use std::borrow::ToOwned;
+use std::env;
 use std::ffi::CString;
+use std::path::PathBuf;
 use std::ptr;
 
 static FC_FAMILY: &'static [u8] = b"family\0";
 static FC_FILE: &'static [u8] = b"file\0";
 static FC_INDEX: &'static [u8] = b"index\0";
 static FC_FONTFORMAT: &'static [u8] = b"fontformat\0";
+static FC_FULLNAME: &'static [u8] = b"fullname\0";
+static FC_POSTSCRIPT_NAME: &'static [u8] = b"postscriptname\0";
 
 pub fn get_available_families<F>(mut callback: F) where F: FnMut(String) {
     unsafe {
@@ -117,6 +122,109 @@ pub fn get_variations_for_family<F>(family_name: &str, mut callback: F)
     }
 }
 
+/// Returns the file of the installed font with the given full name or PostScript name, as used
+/// by `local()` sources of `@font-face` rules.
+pub fn get_local_font(name: &str) -> Option<String> {
+    debug!("looking for local font {}", name);
+    find_font_with_name(FC_FULLNAME, name).or_else(|| find_font_with_name(FC_POSTSCRIPT_NAME, name))
+}
+
+fn find_font_with_name(object: &[u8], name: &str) -> Option<String> {
+    unsafe {
+        let config = FcConfigGetCurrent();
+        let mut font_set = FcConfigGetFonts(config, FcSetSystem);
+        let font_set_array_ptr = &mut font_set;
+        let pattern = FcPatternCreate();
+        assert!(!pattern.is_null());
+        let name_c = CString::new(name).unwrap();
+        let ok = FcPatternAddString(pattern, object.as_ptr() as *mut c_char,
+                                    name_c.as_ptr() as *mut FcChar8);
+        assert!(ok != 0);
+
+        let object_set = FcObjectSetCreate();
+        assert!(!object_set.is_null());
+        FcObjectSetAdd(object_set, FC_FILE.as_ptr() as *mut c_char);
+
+        let matches = FcFontSetList(config, font_set_array_ptr, 1, pattern, object_set);
+        let file = if (*matches).nfont > 0 {
+            let mut file: *mut FcChar8 = ptr::null_mut();
+            let result = FcPatternGetString(*(*matches).fonts, FC_FILE.as_ptr() as *mut c_char, 0,
+                                            &mut file);
+            if result == FcResultMatch {
+                Some(c_str_to_string(file as *const c_char))
+            } else {
+                None
+            }
+        } else {
+            None
+        };
+
+        FcFontSetDestroy(matches);
+        FcPatternDestroy(pattern);
+        FcObjectSetDestroy(object_set);
+        file
+    }
+}
+
+/// A family name that no font has, which is added to a pattern after the family it is for so
+/// that the families fontconfig's configuration puts before it are the ones bound to that family.
+static SENTINEL_FAMILY: &'static [u8] = b"-servo-sentinel\0";
+
+/// Returns the installed families that fontconfig's configuration substitutes for a family,
+/// such as metrically compatible ones for a family that isn't installed, best match first.
+pub fn get_substitute_families(family_name: &str) -> Vec<String> {
+    let family_name_c = CString::new(family_name).unwrap();
+    unsafe {
+        let pattern = FcPatternCreate();
+        assert!(!pattern.is_null());
+        let ok = FcPatternAddString(pattern, FC_FAMILY.as_ptr() as *mut c_char,
+                                    family_name_c.as_ptr() as *mut FcChar8);
+        assert!(ok != 0);
+        let ok = FcPatternAddString(pattern, FC_FAMILY.as_ptr() as *mut c_char,
+                                    SENTINEL_FAMILY.as_ptr() as *mut FcChar8);
+        assert!(ok != 0);
+        FcConfigSubstitute(ptr::null_mut(), pattern, FcMatchPattern);
+
+        // Families that are only appended for every pattern, such as the default sans-serif
+        // ones, come after the sentinel, and aren't substitutes for this family in particular.
+        let mut bound_families = vec!();
+        let mut family: *mut FcChar8 = ptr::null_mut();
+        let mut index = 0;
+        while FcPatternGetString(pattern, FC_FAMILY.as_ptr() as *mut c_char, index,
+                                 &mut family) == FcResultMatch {
+            let name = c_str_to_string(family as *const c_char);
+            if name.as_bytes() == &SENTINEL_FAMILY[..SENTINEL_FAMILY.len() - 1] {
+                break
+            }
+            bound_families.push(name.to_lowercase());
+            index += 1;
+        }
+
+        FcDefaultSubstitute(pattern);
+        let mut result = 0;
+        let font_set = FcFontSort(ptr::null_mut(), pattern, 1, ptr::null_mut(), &mut result);
+        let mut families: Vec<String> = vec!();
+        if !font_set.is_null() {
+            for i in 0..((*font_set).nfont as isize) {
+                let font = (*font_set).fonts.offset(i);
+                let mut family: *mut FcChar8 = ptr::null_mut();
+                if FcPatternGetString(*font, FC_FAMILY.as_ptr() as *mut c_char, 0,
+                                      &mut family) != FcResultMatch {
+                    continue
+                }
+                let name = c_str_to_string(family as *const c_char);
+                if bound_families.contains(&name.to_lowercase()) && !families.contains(&name) {
+                    families.push(name);
+                }
+            }
+            FcFontSetDestroy(font_set);
+        }
+
+        FcPatternDestroy(pattern);
+        families
+    }
+}
+
 pub fn get_system_default_family(generic_name: &str) -> Option<String> {
     let generic_name_c = CString::new(generic_name).unwrap();
     let generic_name_ptr = generic_name_c.as_ptr();
@@ -145,16 +253,51 @@ pub fn get_system_default_family(generic_name: &str) -> Option<String> {
     }
 }
 
+/// The directories fonts are installed in. Fonts are added or removed from the system by
+/// changing the contents of these.
+#[cfg(target_os="linux")]
+pub fn font_directories() -> Vec<PathBuf> {
+    let mut directories = vec!(PathBuf::from("/usr/share/fonts"),
+                               PathBuf::from("/usr/local/share/fonts"));
+    if let Some(home) = env::home_dir() {
+        directories.push(home.join(".fonts"));
+        directories.push(home.join(".local/share/fonts"));
+    }
+    directories
+}
+
+#[cfg(target_os="android")]
+pub fn font_directories() -> Vec<PathBuf> {
+    vec!(PathBuf::from("/system/fonts"))
+}
+
+/// The families that text falls back to, in order, for characters that the fonts a page asks for
+/// don't have glyphs for. The later ones cover more scripts, and fonts with the forms of Han
+/// characters that `han_variant` is written with come before the others that have them.
 #[cfg(target_os="linux")]
-pub fn get_last_resort_font_families() -> Vec<String> {
-    vec!(
-        "Fira Sans".to_owned(),
-        "DejaVu Sans".to_owned(),
-        "Arial".to_owned()
-    )
+pub fn get_last_resort_font_families(han_variant: Option<HanVariant>) -> Vec<String> {
+    let mut families = vec!("Fira Sans", "DejaVu Sans", "Arial");
+    families.extend(han_font_families(han_variant).into_iter());
+    families.push("Noto Sans CJK SC");
+    families.push("Droid Sans Fallback");
+    families.into_iter().map(|family| family.to_owned()).collect()
 }
 
 #[cfg(target_os="android")]
-pub fn get_last_resort_font_families() -> Vec<String> {
-    vec!("Roboto".to_owned())
+pub fn get_last_resort_font_families(han_variant: Option<HanVariant>) -> Vec<String> {
+    let mut families = vec!("Roboto");
+    families.extend(han_font_families(han_variant).into_iter());
+    families.push("Droid Sans Fallback");
+    families.into_iter().map(|family| family.to_owned()).collect()
+}
+
+/// The families with the forms of Han characters that `han_variant` is written with. Simplified
+/// Chinese forms are the ones the fallback fonts have anyway.
+fn han_font_families(han_variant: Option<HanVariant>) -> Vec<&'static str> {
+    match han_variant {
+        Some(HanVariant::Japanese) => vec!("Noto Sans CJK JP", "IPAGothic", "TakaoPGothic"),
+        Some(HanVariant::Korean) => vec!("Noto Sans CJK KR", "NanumGothic"),
+        Some(HanVariant::TraditionalChinese) => vec!("Noto Sans CJK TC", "AR PL UMing TW"),
+        Some(HanVariant::SimplifiedChinese) | None => vec!(),
+    }
 }
@@ -2,16 +2,19 @@
  * License, v. 2.0. If a copy of the MPL was not distributed with this
  * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
 
+use font::FontTableTag;
+use font_bytes::FontBytes;
+use sfnt;
 use std::borrow::ToOwned;
-use std::fs::File;
-use std::io::Read;
+use util::mem::HeapSizeOf;
 
 /// Platform specific font representation for Linux.
 /// The identifier is an absolute path, and the bytes
 /// field is the loaded data that can be passed to
-/// freetype and azure directly.
+/// freetype and azure directly. Installed fonts are
+/// mapped from their files rather than read.
 pub struct FontTemplateData {
-    pub bytes: Vec<u8>,
+    pub bytes: FontBytes,
     pub identifier: String,
 }
 
@@ -19,14 +22,11 @@ impl FontTemplateData {
     pub fn new(identifier: &str, font_data: Option<Vec<u8>>) -> FontTemplateData {
         let bytes = match font_data {
             Some(bytes) => {
-                bytes
+                FontBytes::Owned(bytes)
             },
             None => {
                 // TODO: Handle file load failure!
-                let mut file = File::open(identifier).unwrap();
-                let mut buffer = vec![];
-                file.read_to_end(&mut buffer).unwrap();
-                buffer
+                FontBytes::from_file(identifier).unwrap()
             },
         };
 
@@ -35,4 +35,21 @@ impl FontTemplateData {
             identifier: identifier.to_owned(),
         }
     }
+
+    /// Returns the contents of the font file.
+    pub fn sfnt_data(&self) -> Option<&[u8]> {
+        Some(&*self.bytes)
+    }
+
+    /// Calls `f` with the table of the font with the given tag, without copying it.
+    pub fn with_font_table<F, R>(&self, tag: FontTableTag, f: F) -> Option<R>
+                                 where F: FnOnce(&[u8]) -> R {
+        sfnt::find_table(&self.bytes, tag).map(f)
+    }
+}
+
+impl HeapSizeOf for FontTemplateData {
+    fn heap_size_of_children(&self) -> usize {
+        self.bytes.heap_size_of_children() + self.identifier.heap_size_of_children()
+    }
 }
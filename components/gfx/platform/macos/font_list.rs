@@ -2,13 +2,25 @@
  * License, v. 2.0. If a copy of the MPL was not distributed with this
  * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
 
+use core_foundation::array::{CFArray, CFArrayRef};
 use core_foundation::base::TCFType;
 use core_foundation::string::{CFString, CFStringRef};
+use core_text::font::CTFontRef;
 use core_text::font_descriptor::{CTFontDescriptor, CTFontDescriptorRef};
 use core_text;
+use text::util::HanVariant;
 
 use std::borrow::ToOwned;
+use std::env;
 use std::mem;
+use std::path::PathBuf;
+use std::ptr;
+
+#[link(name = "CoreText", kind = "framework")]
+extern {
+    fn CTFontCopyDefaultCascadeListForLanguages(font: CTFontRef, languages: CFArrayRef)
+                                                -> CFArrayRef;
+}
 
 pub fn get_available_families<F>(mut callback: F) where F: FnMut(String) {
     let family_names = core_text::font_collection::get_family_names();
@@ -35,10 +47,86 @@ pub fn get_variations_for_family<F>(family_name: &str, mut callback: F) where F:
     }
 }
 
+/// Returns the PostScript name of the installed font with the given full name or PostScript
+/// name, as used by `local()` sources of `@font-face` rules.
+pub fn get_local_font(name: &str) -> Option<String> {
+    debug!("Looking for local font: {}", name);
+
+    // Core Text falls back to another font for names it doesn't know, so the font it finds has
+    // to be checked.
+    let ctfont = match core_text::font::new_from_name(name, 0.0) {
+        Ok(ctfont) => ctfont,
+        Err(()) => return None,
+    };
+    let postscript_name = ctfont.postscript_name();
+    if postscript_name == name || ctfont.display_name() == name {
+        Some(postscript_name)
+    } else {
+        None
+    }
+}
+
+/// Returns the families that Core Text uses in place of a family that it knows by a name that
+/// isn't one of the names of installed families, such as a localized name: the family of the
+/// font it finds for the name, followed by the families of that font's cascade list.
+pub fn get_substitute_families(family_name: &str) -> Vec<String> {
+    // Core Text falls back to another font for names it doesn't know, which isn't a substitute.
+    let ctfont = match core_text::font::new_from_name(family_name, 0.0) {
+        Ok(ctfont) => ctfont,
+        Err(()) => return vec!(),
+    };
+    let name = family_name.to_lowercase();
+    if ctfont.family_name().to_lowercase() != name &&
+       ctfont.display_name().to_lowercase() != name &&
+       ctfont.postscript_name().to_lowercase() != name {
+        return vec!()
+    }
+
+    let mut families = vec!(ctfont.family_name());
+    let cascade_list: CFArray = unsafe {
+        TCFType::wrap_under_create_rule(
+            CTFontCopyDefaultCascadeListForLanguages(ctfont.as_concrete_TypeRef(),
+                                                     ptr::null()))
+    };
+    for descref in cascade_list.iter() {
+        let descref: CTFontDescriptorRef = unsafe { mem::transmute(descref) };
+        let desc: CTFontDescriptor = unsafe { TCFType::wrap_under_get_rule(descref) };
+        let family = desc.family_name();
+        if !families.contains(&family) {
+            families.push(family);
+        }
+    }
+    families
+}
+
 pub fn get_system_default_family(_generic_name: &str) -> Option<String> {
     None
 }
 
-pub fn get_last_resort_font_families() -> Vec<String> {
-    vec!("Arial Unicode MS".to_owned(), "Arial".to_owned())
+/// The directories fonts are installed in. Fonts are added or removed from the system by
+/// changing the contents of these.
+pub fn font_directories() -> Vec<PathBuf> {
+    let mut directories = vec!(PathBuf::from("/System/Library/Fonts"),
+                               PathBuf::from("/Library/Fonts"));
+    if let Some(home) = env::home_dir() {
+        directories.push(home.join("Library/Fonts"));
+    }
+    directories
+}
+
+/// The families that text falls back to, in order, for characters that the fonts a page asks for
+/// don't have glyphs for. Fonts with the forms of Han characters that `han_variant` is written
+/// with come first, since Arial Unicode MS has Han characters too.
+pub fn get_last_resort_font_families(han_variant: Option<HanVariant>) -> Vec<String> {
+    let mut families = match han_variant {
+        Some(HanVariant::Japanese) => vec!("Hiragino Kaku Gothic ProN", "Hiragino Sans"),
+        Some(HanVariant::Korean) => vec!("Apple SD Gothic Neo", "AppleGothic"),
+        Some(HanVariant::TraditionalChinese) => vec!("PingFang TC", "Heiti TC"),
+        Some(HanVariant::SimplifiedChinese) => vec!("PingFang SC", "Heiti SC"),
+        None => vec!(),
+    };
+    families.push("Arial Unicode MS");
+    families.push("Arial");
+    families.push("Apple Symbols");
+    families.into_iter().map(|family| family.to_owned()).collect()
 }
@@ -6,13 +6,18 @@ use core_graphics::data_provider::CGDataProvider;
 use core_graphics::font::CGFont;
 use core_text::font::CTFont;
 use core_text;
+use font::FontTableTag;
+use sfnt;
 
 use std::borrow::ToOwned;
+use util::mem::HeapSizeOf;
 
 /// Platform specific font representation for mac.
 /// The identifier is a PostScript font name. The
 /// CTFont object is cached here for use by the
 /// paint functions that create CGFont references.
+/// Core Text maps the files of installed fonts itself,
+/// so only web fonts have their data in memory here.
 pub struct FontTemplateData {
     pub ctfont: Option<CTFont>,
     pub identifier: String,
@@ -44,4 +49,28 @@ impl FontTemplateData {
             font_data: font_data
         }
     }
+
+    /// Returns the contents of the font file, which is only available for web fonts.
+    pub fn sfnt_data(&self) -> Option<&[u8]> {
+        self.font_data.as_ref().map(|data| &**data)
+    }
+
+    /// Calls `f` with the table of the font with the given tag, which Core Text finds for
+    /// installed fonts.
+    pub fn with_font_table<F, R>(&self, tag: FontTableTag, f: F) -> Option<R>
+                                 where F: FnOnce(&[u8]) -> R {
+        if let Some(ref data) = self.font_data {
+            return sfnt::find_table(data, tag).map(f)
+        }
+        self.ctfont.as_ref().and_then(|ctfont| ctfont.get_font_table(tag)).map(|table| {
+            f(table.bytes())
+        })
+    }
+}
+
+impl HeapSizeOf for FontTemplateData {
+    fn heap_size_of_children(&self) -> usize {
+        // FIXME: Measure the CTFont, which Core Text owns.
+        self.font_data.heap_size_of_children() + self.identifier.heap_size_of_children()
+    }
 }
@@ -8,6 +8,10 @@ pub use platform::freetype::{font, font_context, font_list, font_template};
 #[cfg(target_os="macos")]
 pub use platform::macos::{font, font_context, font_list, font_template};
 
+// TODO: Add a `windows` backend built on DirectWrite, with the same four modules. It needs
+// DirectWrite bindings, and a Windows build of rust-azure to rasterize its glyphs with, since
+// `create_scaled_font` in gfx::font_context hands the fonts to azure.
+
 #[cfg(any(target_os="linux", target_os = "android"))]
 pub mod freetype {
     pub mod font;
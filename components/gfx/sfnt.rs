@@ -0,0 +1,145 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+//! Reading and writing the sfnt container that TrueType and OpenType fonts are stored in.
+//! https://www.microsoft.com/typography/otspec/otff.htm
+
+use font::FontTableTag;
+
+const SFNT_HEADER_SIZE: usize = 12;
+const SFNT_TABLE_RECORD_SIZE: usize = 16;
+
+/// A table to write into a font, with the checksum to record for it.
+pub struct SfntTable {
+    pub tag: FontTableTag,
+    pub checksum: u32,
+    pub data: Vec<u8>,
+}
+
+/// Returns the version tag of a font, which says what kind of outlines it has.
+pub fn flavor(font: &[u8]) -> Option<u32> {
+    if font.len() < SFNT_HEADER_SIZE {
+        return None
+    }
+    Some(read_u32(font, 0))
+}
+
+/// Returns the tables of a font in the order of its table directory, or `None` if the
+/// directory is truncated or points outside the font.
+pub fn tables<'a>(font: &'a [u8]) -> Option<Vec<(FontTableTag, &'a [u8])>> {
+    if font.len() < SFNT_HEADER_SIZE {
+        return None
+    }
+    let table_count = read_u16(font, 4) as usize;
+    if SFNT_HEADER_SIZE + table_count * SFNT_TABLE_RECORD_SIZE > font.len() {
+        return None
+    }
+    let mut tables = vec!();
+    for index in 0..table_count {
+        let record = SFNT_HEADER_SIZE + index * SFNT_TABLE_RECORD_SIZE;
+        let offset = read_u32(font, record + 8) as usize;
+        let length = read_u32(font, record + 12) as usize;
+        if offset > font.len() || length > font.len() - offset {
+            return None
+        }
+        tables.push((read_u32(font, record), &font[offset..offset + length]));
+    }
+    Some(tables)
+}
+
+/// Returns the table of a font with the given tag.
+pub fn find_table<'a>(font: &'a [u8], tag: FontTableTag) -> Option<&'a [u8]> {
+    tables(font).and_then(|tables| {
+        tables.into_iter().find(|&(table_tag, _)| table_tag == tag).map(|(_, table)| table)
+    })
+}
+
+/// Computes the checksum of a table: the sum of its contents as big-endian 32-bit integers.
+pub fn checksum(table: &[u8]) -> u32 {
+    let mut sum = 0u32;
+    for chunk in table.chunks(4) {
+        let mut word = 0;
+        for index in 0..4 {
+            word = word << 8 | chunk.get(index).cloned().unwrap_or(0) as u32;
+        }
+        sum = sum.wrapping_add(word);
+    }
+    sum
+}
+
+/// Writes a font made of the given tables, which should be sorted by tag. Each table starts on
+/// a four byte boundary.
+pub fn write_sfnt(flavor: u32, tables: &[SfntTable]) -> Vec<u8> {
+    let table_count = tables.len();
+    let mut offset = SFNT_HEADER_SIZE + table_count * SFNT_TABLE_RECORD_SIZE;
+    let mut font = vec!();
+    push_u32(&mut font, flavor);
+    push_u16(&mut font, table_count as u16);
+    let mut entry_selector = 0;
+    while (2 << entry_selector) <= table_count {
+        entry_selector += 1;
+    }
+    let search_range = (1 << entry_selector) * SFNT_TABLE_RECORD_SIZE;
+    push_u16(&mut font, search_range as u16);
+    push_u16(&mut font, entry_selector as u16);
+    let range_shift = (table_count * SFNT_TABLE_RECORD_SIZE).saturating_sub(search_range);
+    push_u16(&mut font, range_shift as u16);
+    for table in tables.iter() {
+        push_u32(&mut font, table.tag);
+        push_u32(&mut font, table.checksum);
+        push_u32(&mut font, offset as u32);
+        push_u32(&mut font, table.data.len() as u32);
+        offset += (table.data.len() + 3) & !3;
+    }
+    for table in tables.iter() {
+        font.push_all(&table.data);
+        while font.len() % 4 != 0 {
+            font.push(0);
+        }
+    }
+    font
+}
+
+pub fn read_u16(data: &[u8], offset: usize) -> u16 {
+    (data[offset] as u16) << 8 | data[offset + 1] as u16
+}
+
+pub fn read_i16(data: &[u8], offset: usize) -> i16 {
+    read_u16(data, offset) as i16
+}
+
+pub fn read_u32(data: &[u8], offset: usize) -> u32 {
+    (data[offset] as u32) << 24 | (data[offset + 1] as u32) << 16 |
+        (data[offset + 2] as u32) << 8 | data[offset + 3] as u32
+}
+
+/// Reads a 16.16 fixed point number.
+pub fn read_fixed(data: &[u8], offset: usize) -> f32 {
+    read_u32(data, offset) as i32 as f32 / 65536.0
+}
+
+/// Reads a 2.14 fixed point number.
+pub fn read_f2dot14(data: &[u8], offset: usize) -> f32 {
+    read_i16(data, offset) as f32 / 16384.0
+}
+
+pub fn push_u16(data: &mut Vec<u8>, value: u16) {
+    data.push((value >> 8) as u8);
+    data.push(value as u8);
+}
+
+pub fn push_u32(data: &mut Vec<u8>, value: u32) {
+    push_u16(data, (value >> 16) as u16);
+    push_u16(data, value as u16);
+}
+
+pub fn write_u16(data: &mut [u8], offset: usize, value: u16) {
+    data[offset] = (value >> 8) as u8;
+    data[offset + 1] = value as u8;
+}
+
+pub fn write_u32(data: &mut [u8], offset: usize, value: u32) {
+    write_u16(data, offset, (value >> 16) as u16);
+    write_u16(data, offset + 2, value as u16);
+}
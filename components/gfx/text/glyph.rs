@@ -8,8 +8,11 @@ use std::iter::repeat;
 use std::mem;
 use std::u16;
 use std::vec::Vec;
+use text::util::is_combining_char;
 use util::geometry::Au;
+use util::mem::HeapSizeOf;
 use util::range::{self, Range, RangeIndex, EachIndex};
+use util::str::GraphemeClusterBreaker;
 use util::vec::*;
 
 /// GlyphEntry is a port of Gecko's CompressedGlyph scheme for storing glyph data compactly.
@@ -20,7 +23,7 @@ use util::vec::*;
 /// In the uncommon case (multiple glyphs per unicode character, large glyph index/advance, or
 /// glyph offsets), we pack the glyph count into GlyphEntry, and store the other glyph information
 /// in DetailedGlyphStore.
-#[derive(Clone, Debug, Copy)]
+#[derive(Clone, Debug, Copy, HeapSizeOf, RustcEncodable, RustcDecodable)]
 struct GlyphEntry {
     value: u32,
 }
@@ -251,7 +254,7 @@ impl GlyphEntry {
 
 // Stores data for a detailed glyph, in the case that several glyphs
 // correspond to one character, or the glyph's data couldn't be packed.
-#[derive(Clone, Debug, Copy)]
+#[derive(Clone, Debug, Copy, HeapSizeOf, RustcEncodable, RustcDecodable)]
 struct DetailedGlyph {
     id: GlyphId,
     // glyph's advance, in the text's direction (LTR or RTL)
@@ -270,7 +273,7 @@ impl DetailedGlyph {
     }
 }
 
-#[derive(PartialEq, Clone, Eq, Debug, Copy)]
+#[derive(PartialEq, Clone, Eq, Debug, Copy, HeapSizeOf, RustcEncodable, RustcDecodable)]
 struct DetailedGlyphRecord {
     // source string offset/GlyphEntry offset in the TextRun
     entry_offset: CharIndex,
@@ -294,7 +297,7 @@ impl Ord for DetailedGlyphRecord {
 // until a lookup is actually performed; this matches the expected
 // usage pattern of setting/appending all the detailed glyphs, and
 // then querying without setting.
-#[derive(Clone)]
+#[derive(Clone, HeapSizeOf, RustcEncodable, RustcDecodable)]
 struct DetailedGlyphStore {
     // TODO(pcwalton): Allocation of this buffer is expensive. Consider a small-vector
     // optimization.
@@ -356,9 +359,7 @@ impl<'a> DetailedGlyphStore {
             detail_offset: 0, // unused
         };
 
-        let i = self.detail_lookup.binary_search_index(&key)
-            .expect("Invalid index not found in detailed glyph lookup table!");
-
+        let i = self.detail_offset_for_entry(entry_offset);
         assert!(i + (count as usize) <= self.detail_buffer.len());
         // return a slice into the buffer
         &self.detail_buffer[i .. i + count as usize]
@@ -371,6 +372,24 @@ impl<'a> DetailedGlyphStore {
         assert!((detail_offset as usize) <= self.detail_buffer.len());
         assert!(self.lookup_is_sorted);
 
+        let i = self.detail_offset_for_entry(entry_offset);
+        assert!(i + (detail_offset as usize) < self.detail_buffer.len());
+        &self.detail_buffer[i + (detail_offset as usize)]
+    }
+
+    fn get_detailed_glyph_with_index_mut(&'a mut self,
+                                         entry_offset: CharIndex,
+                                         detail_offset: u16)
+            -> &'a mut DetailedGlyph {
+        assert!(self.lookup_is_sorted);
+
+        let i = self.detail_offset_for_entry(entry_offset);
+        assert!(i + (detail_offset as usize) < self.detail_buffer.len());
+        &mut self.detail_buffer[i + (detail_offset as usize)]
+    }
+
+    /// Returns the offset in the detail buffer of the first detailed glyph of an entry.
+    fn detail_offset_for_entry(&self, entry_offset: CharIndex) -> usize {
         let key = DetailedGlyphRecord {
             entry_offset: entry_offset,
             detail_offset: 0, // unused
@@ -378,9 +397,7 @@ impl<'a> DetailedGlyphStore {
 
         let i = self.detail_lookup.binary_search_index(&key)
             .expect("Invalid index not found in detailed glyph lookup table!");
-
-        assert!(i + (detail_offset as usize) < self.detail_buffer.len());
-        &self.detail_buffer[i + (detail_offset as usize)]
+        self.detail_lookup[i].detail_offset
     }
 
     fn ensure_sorted(&mut self) {
@@ -501,7 +518,7 @@ impl<'a> GlyphInfo<'a> {
 /// |               +---+---+                     |
 /// +---------------------------------------------+
 /// ~~~
-#[derive(Clone)]
+#[derive(Clone, HeapSizeOf, RustcEncodable, RustcDecodable)]
 pub struct GlyphStore {
     // TODO(pcwalton): Allocation of this buffer is expensive. Consider a small-vector
     // optimization.
@@ -511,12 +528,16 @@ pub struct GlyphStore {
     /// A store of the detailed glyph data. Detailed glyphs contained in the
     /// `entry_buffer` point to locations in this data structure.
     detail_store: DetailedGlyphStore,
+    /// The indices of the characters that continue the grapheme cluster of the character before
+    /// them instead of starting one, in order. Clusters are never split between lines or by the
+    /// caret.
+    grapheme_cluster_continuations: Vec<CharIndex>,
 
     is_whitespace: bool,
 }
 
 int_range_index! {
-    #[derive(RustcEncodable)]
+    #[derive(RustcEncodable, RustcDecodable)]
     #[doc = "An index that refers to a character in a text run. This could \
              point to the middle of a glyph."]
     #[derive(HeapSizeOf)]
@@ -533,6 +554,7 @@ impl<'a> GlyphStore {
             entry_buffer: repeat(GlyphEntry::initial()).take(length)
                                                        .collect(),
             detail_store: DetailedGlyphStore::new(),
+            grapheme_cluster_continuations: vec!(),
             is_whitespace: is_whitespace,
         }
     }
@@ -676,6 +698,12 @@ impl<'a> GlyphStore {
         self.entry_buffer[i.to_usize()].is_cluster_start()
     }
 
+    /// Returns true if the character at `i` starts a grapheme cluster.
+    pub fn is_grapheme_cluster_start(&self, i: CharIndex) -> bool {
+        assert!(i < self.char_len());
+        self.grapheme_cluster_continuations.binary_search(&i).is_err()
+    }
+
     pub fn can_break_before(&self, i: CharIndex) -> BreakType {
         assert!(i < self.char_len());
         self.entry_buffer[i.to_usize()].can_break_before()
@@ -706,34 +734,86 @@ impl<'a> GlyphStore {
         self.entry_buffer[i.to_usize()] = entry.set_can_break_before(t);
     }
 
-    pub fn space_count_in_range(&self, range: &Range<CharIndex>) -> u32 {
-        let mut spaces = 0;
-        for index in range.each_index() {
-            if self.char_is_space(index) {
-                spaces += 1
+    /// Finds the grapheme clusters of `text`, which these glyphs were shaped from. The first
+    /// character always starts one, since glyph stores are only split between clusters.
+    pub fn set_grapheme_clusters(&mut self, text: &str) {
+        self.grapheme_cluster_continuations.clear();
+        let mut breaker = GraphemeClusterBreaker::new();
+        for (index, character) in text.chars().enumerate() {
+            if !breaker.is_boundary_before(character) {
+                self.grapheme_cluster_continuations.push(CharIndex(index as isize))
             }
         }
-        spaces
     }
 
-    pub fn distribute_extra_space_in_range(&mut self, range: &Range<CharIndex>, space: f64) {
-        debug_assert!(space >= 0.0);
-        if range.is_empty() {
+    /// Adds `letter_spacing` after each character of `text`, which these glyphs were shaped
+    /// from, and `word_spacing` after each space and no-break space. The spacing of a character
+    /// goes after the last glyph of the ligature or cluster that it belongs to, so the characters
+    /// that a ligature joins add their spacing after the ligature. Combining marks aren't letters
+    /// of their own, so they get no letter spacing.
+    pub fn add_spacing(&mut self, text: &str, letter_spacing: Au, word_spacing: Au) {
+        if letter_spacing == Au(0) && word_spacing == Au(0) {
             return
         }
-        for index in range.each_index() {
-            // TODO(pcwalton): Handle spaces that are detailed glyphs -- these are uncommon but
-            // possible.
-            let entry = &mut self.entry_buffer[index.to_usize()];
-            if entry.is_simple() && entry.char_is_space() {
-                // FIXME(pcwalton): This can overflow for very large font-sizes.
-                let advance =
-                    ((entry.value & GLYPH_ADVANCE_MASK) >> GLYPH_ADVANCE_SHIFT) +
-                    Au::from_f64_px(space).0 as u32;
-                entry.value = (entry.value & !GLYPH_ADVANCE_MASK) |
-                    (advance << GLYPH_ADVANCE_SHIFT);
+
+        // Find the spacing to add after the glyphs of each character that has glyphs, which
+        // includes that of the characters without glyphs that follow it.
+        let mut spacings: Vec<(CharIndex, Au)> = vec!();
+        for (index, character) in text.chars().enumerate() {
+            let entry = self.entry_buffer[index];
+            let has_glyphs = entry.is_simple() ||
+                (entry.has_flag(FLAG_NOT_MISSING) && entry.glyph_count() > 0);
+            if has_glyphs {
+                spacings.push((CharIndex(index as isize), Au(0)));
+            }
+            if let Some(&mut (_, ref mut spacing)) = spacings.last_mut() {
+                if !is_combining_char(character) {
+                    *spacing = *spacing + letter_spacing
+                }
+                if character == ' ' || character == '\u{a0}' {
+                    *spacing = *spacing + word_spacing
+                }
             }
         }
+
+        // Detailed glyphs can only be looked up while the detail store is sorted, so change them
+        // before moving any simple glyphs whose advances no longer fit into the detail store.
+        for &(i, spacing) in spacings.iter() {
+            let entry = self.entry_buffer[i.to_usize()];
+            if !entry.is_simple() {
+                let last_glyph = entry.glyph_count() - 1;
+                let glyph = self.detail_store.get_detailed_glyph_with_index_mut(i, last_glyph);
+                glyph.advance = glyph.advance + spacing;
+            }
+        }
+        for &(i, spacing) in spacings.iter() {
+            if self.entry_buffer[i.to_usize()].is_simple() {
+                self.add_to_simple_advance(i, spacing)
+            }
+        }
+
+        self.finalize_changes();
+    }
+
+    /// Adds `amount` to the advance of the simple glyph of the character at `i`.
+    fn add_to_simple_advance(&mut self, i: CharIndex, amount: Au) {
+        let entry = self.entry_buffer[i.to_usize()];
+        let advance = entry.advance() + amount;
+        if is_simple_advance(advance) {
+            self.entry_buffer[i.to_usize()] =
+                GlyphEntry::new((entry.value & !GLYPH_ADVANCE_MASK) |
+                                ((advance.0 as u32) << GLYPH_ADVANCE_SHIFT));
+            return
+        }
+
+        // Simple entries can't hold negative or very large advances, so move the glyph to the
+        // detail store, keeping the character's flags.
+        let glyph = [DetailedGlyph::new(entry.id(), advance, Point2D::zero())];
+        self.detail_store.add_detailed_glyphs_for_entry(i, &glyph);
+        let character_flags = entry.value & (FLAG_CHAR_IS_SPACE | FLAG_CAN_BREAK_MASK);
+        self.entry_buffer[i.to_usize()] =
+            GlyphEntry::complex(true, true, 1).adapt_character_flags_of_entry(
+                GlyphEntry::new(character_flags));
     }
 }
 
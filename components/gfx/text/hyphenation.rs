@@ -0,0 +1,212 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+//! Automatic hyphenation, for `hyphens: auto`.
+//!
+//! Words are hyphenated with Liang's algorithm, as in TeX, using the pattern dictionaries of
+//! each language. The dictionaries are loaded once at startup, like the command line options,
+//! and are shared by every layout task.
+
+use std::ascii::AsciiExt;
+use std::borrow::ToOwned;
+use std::cmp::{max, min};
+use std::collections::HashMap;
+use std::fs::{self, File};
+use std::io::{self, BufRead, BufReader};
+use std::mem;
+use std::path::Path;
+
+/// The fewest characters that are left before a hyphen, unless the patterns say otherwise.
+const DEFAULT_LEFT_MIN: usize = 2;
+/// The fewest characters that are carried over after a hyphen, unless the patterns say otherwise.
+const DEFAULT_RIGHT_MIN: usize = 3;
+
+/// How the text of a text run is hyphenated.
+#[derive(Copy, Clone)]
+pub enum HyphenationMode<'a> {
+    /// No hyphens are drawn.
+    None,
+    /// A hyphen is drawn where a line breaks at a soft hyphen.
+    Manual,
+    /// Words are also hyphenated with the given hyphenator.
+    Auto(&'a Hyphenator),
+}
+
+impl<'a> PartialEq for HyphenationMode<'a> {
+    fn eq(&self, other: &HyphenationMode<'a>) -> bool {
+        match (*self, *other) {
+            (HyphenationMode::None, HyphenationMode::None) |
+            (HyphenationMode::Manual, HyphenationMode::Manual) => true,
+            // Hyphenators are loaded once, so two modes hyphenate alike if they share one.
+            (HyphenationMode::Auto(hyphenator), HyphenationMode::Auto(other_hyphenator)) => {
+                hyphenator as *const Hyphenator == other_hyphenator as *const Hyphenator
+            }
+            _ => false,
+        }
+    }
+}
+
+/// Finds the places where the words of a language can be hyphenated, from Liang patterns.
+pub struct Hyphenator {
+    /// The values between the letters of each pattern, keyed by its letters. Each pattern has
+    /// one more value than it has letters.
+    patterns: HashMap<String, Vec<u8>>,
+    /// The number of letters in the longest pattern.
+    max_pattern_length: usize,
+    /// The fewest characters that are left before a hyphen.
+    pub left_min: usize,
+    /// The fewest characters that are carried over after a hyphen.
+    pub right_min: usize,
+}
+
+impl Hyphenator {
+    /// Creates a hyphenator from patterns such as `hy3ph` and `.ex5`, where the digits are the
+    /// values between letters and `.` matches the start or the end of the word.
+    pub fn new<'a, I>(patterns: I) -> Hyphenator where I: Iterator<Item=&'a str> {
+        let mut hyphenator = Hyphenator {
+            patterns: HashMap::new(),
+            max_pattern_length: 0,
+            left_min: DEFAULT_LEFT_MIN,
+            right_min: DEFAULT_RIGHT_MIN,
+        };
+        for pattern in patterns {
+            hyphenator.add_pattern(pattern)
+        }
+        hyphenator
+    }
+
+    /// Reads a pattern file with whitespace-separated patterns, such as the `.pat.txt` files of
+    /// the hyph-utf8 project. Text after a `%` is a comment.
+    pub fn from_file(path: &Path) -> io::Result<Hyphenator> {
+        let file = try!(File::open(path));
+        let mut patterns = vec!();
+        for line in BufReader::new(file).lines() {
+            let line = try!(line);
+            let line = match line.find('%') {
+                Some(index) => &line[..index],
+                None => &line[..],
+            };
+            patterns.extend(line.split_whitespace().map(|pattern| pattern.to_owned()));
+        }
+        Ok(Hyphenator::new(patterns.iter().map(|pattern| &**pattern)))
+    }
+
+    fn add_pattern(&mut self, pattern: &str) {
+        let mut letters = String::new();
+        let mut values = vec!(0);
+        for character in pattern.chars() {
+            match character.to_digit(10) {
+                Some(value) => {
+                    let last = values.len() - 1;
+                    values[last] = value as u8
+                }
+                None => {
+                    letters.push(character);
+                    values.push(0)
+                }
+            }
+        }
+        if letters.is_empty() {
+            return
+        }
+        self.max_pattern_length = max(self.max_pattern_length, values.len() - 1);
+        self.patterns.insert(letters, values);
+    }
+
+    /// Returns the character indices in `word` before which it can be hyphenated, in order.
+    pub fn hyphenation_points(&self, word: &str) -> Vec<usize> {
+        // Lowercase each character on its own, so that the indices still match `word`.
+        let mut characters = vec!('.');
+        characters.extend(word.chars().map(|character| {
+            character.to_lowercase().next().unwrap_or(character)
+        }));
+        characters.push('.');
+
+        let word_length = characters.len() - 2;
+        if word_length < self.left_min + self.right_min {
+            return vec!()
+        }
+
+        // Each value is the highest of the values that the patterns matching around it give,
+        // and the word can be hyphenated where that is odd.
+        let mut values = vec![0; characters.len() + 1];
+        let mut letters = String::new();
+        for start in 0..characters.len() {
+            let end = min(characters.len(), start + self.max_pattern_length);
+            letters.truncate(0);
+            for length in 1..(end - start + 1) {
+                letters.push(characters[start + length - 1]);
+                if let Some(pattern_values) = self.patterns.get(&letters) {
+                    for (index, &value) in pattern_values.iter().enumerate() {
+                        values[start + index] = max(values[start + index], value)
+                    }
+                }
+            }
+        }
+
+        // The value before the `n`th character of the word is `values[n + 1]`, because of the
+        // leading `.`.
+        (self.left_min..(word_length - self.right_min + 1)).filter(|&index| {
+            values[index + 1] % 2 == 1
+        }).collect()
+    }
+}
+
+/// Loads the hyphenators for each language from a directory of `hyph-<language>.pat.txt` files.
+pub fn load_hyphenators(directory: &Path) -> io::Result<HashMap<String, Hyphenator>> {
+    let mut hyphenators = HashMap::new();
+    for entry in try!(fs::read_dir(directory)) {
+        let path = try!(entry).path();
+        let language = match path.file_name().and_then(|name| name.to_str()) {
+            Some(name) if name.starts_with("hyph-") && name.ends_with(".pat.txt") => {
+                name["hyph-".len()..(name.len() - ".pat.txt".len())].to_ascii_lowercase()
+            }
+            _ => continue,
+        };
+        hyphenators.insert(language, try!(Hyphenator::from_file(&path)));
+    }
+    Ok(hyphenators)
+}
+
+static mut HYPHENATORS: *mut HashMap<String, Hyphenator> =
+    0 as *mut HashMap<String, Hyphenator>;
+
+/// Sets the hyphenators for each language, keyed by lowercase BCP 47 tags. This must be called
+/// at most once, before any layout task starts.
+pub fn set_hyphenators(hyphenators: HashMap<String, Hyphenator>) {
+    unsafe {
+        assert!(HYPHENATORS.is_null());
+        let box_hyphenators = box hyphenators;
+        HYPHENATORS = mem::transmute(box_hyphenators);
+    }
+}
+
+/// Returns the hyphenator for `lang`, a BCP 47 tag. Subtags are dropped from the end of the tag
+/// until one matches, and a language on its own matches any of its regional patterns, so that
+/// `en` is hyphenated with `en-us` patterns if those are all there are.
+pub fn hyphenator_for_lang<'a>(lang: &str) -> Option<&'a Hyphenator> {
+    let hyphenators = unsafe {
+        if HYPHENATORS.is_null() {
+            return None
+        }
+        &*HYPHENATORS
+    };
+
+    let mut tag = lang.to_ascii_lowercase().replace("_", "-");
+    loop {
+        if let Some(hyphenator) = hyphenators.get(&tag) {
+            return Some(hyphenator)
+        }
+        match tag.rfind('-') {
+            Some(index) => tag.truncate(index),
+            None => break,
+        }
+    }
+
+    let prefix = format!("{}-", tag);
+    hyphenators.iter()
+               .filter(|&(key, _)| key.starts_with(&prefix))
+               .min_by(|&(key, _)| key.clone())
+               .map(|(_, hyphenator)| hyphenator)
+}
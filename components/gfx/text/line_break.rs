@@ -0,0 +1,308 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+//! Finds the places where lines may break in text, using the Unicode line breaking algorithm.
+//!
+//! http://www.unicode.org/reports/tr14/
+//!
+//! Characters are classified with a table of the classes of the characters that matter most to
+//! line breaking, and every other character is treated as alphabetic. Complex-context scripts such
+//! as Thai, whose words need a dictionary to find, are treated as alphabetic too, so they only
+//! break at spaces and punctuation.
+
+use text::util::is_combining_char;
+
+use std::str::CharIndices;
+use util::str::GraphemeClusterBreaker;
+
+/// The line breaking classes of UAX #14 that this implementation distinguishes.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum LineBreakClass {
+    /// Mandatory break: vertical tab, form feed and the line and paragraph separators.
+    BK,
+    /// Carriage return.
+    CR,
+    /// Line feed.
+    LF,
+    /// Combining marks and joiners, which take the class of the character they follow.
+    CM,
+    /// Space.
+    SP,
+    /// Zero width space.
+    ZW,
+    /// Word joiner.
+    WJ,
+    /// Non-breaking ("glue") characters, such as the no-break space.
+    GL,
+    /// Opening punctuation.
+    OP,
+    /// Closing punctuation.
+    CL,
+    /// Closing parentheses.
+    CP,
+    /// Quotation marks.
+    QU,
+    /// Characters that can't start a line, such as small kana and iteration marks.
+    NS,
+    /// Exclamation and interrogation marks.
+    EX,
+    /// Solidus.
+    SY,
+    /// Infix separators, such as the comma and full stop.
+    IS,
+    /// Prefixes of numbers, such as currency symbols.
+    PR,
+    /// Postfixes of numbers, such as the percent sign.
+    PO,
+    /// Digits.
+    NU,
+    /// Alphabetic characters and symbols.
+    AL,
+    /// Ideographs, kana and Hangul syllables, between which lines may break.
+    ID,
+    /// Inseparable characters, such as the ellipsis.
+    IN,
+    /// The hyphen-minus.
+    HY,
+    /// Characters after which lines may break, such as the soft hyphen.
+    BA,
+    /// Characters before which lines may break.
+    BB,
+    /// The em dash, around which lines may break but not between two of them.
+    B2,
+}
+
+/// Returns the line breaking class of `ch`.
+pub fn line_break_class(ch: char) -> LineBreakClass {
+    match ch {
+        '\u{B}' | '\u{C}' | '\u{85}' | '\u{2028}' | '\u{2029}' => LineBreakClass::BK,
+        '\r' => LineBreakClass::CR,
+        '\n' => LineBreakClass::LF,
+        // Tabs are break-after characters, but are trimmed at the ends of lines like spaces.
+        ' ' | '\t' => LineBreakClass::SP,
+        '\u{200B}' => LineBreakClass::ZW,
+        '\u{2060}' | '\u{FEFF}' => LineBreakClass::WJ,
+        '\u{A0}' | '\u{34F}' | '\u{F08}' | '\u{F0C}' | '\u{180E}' | '\u{2007}' | '\u{2011}' |
+        '\u{202F}' => LineBreakClass::GL,
+        '(' | '[' | '{' | '\u{A1}' | '\u{BF}' | '\u{201A}' | '\u{201E}' | '\u{2045}' |
+        '\u{207D}' | '\u{208D}' | '\u{2329}' | '\u{3008}' | '\u{300A}' | '\u{300C}' |
+        '\u{300E}' | '\u{3010}' | '\u{3014}' | '\u{3016}' | '\u{3018}' | '\u{301A}' |
+        '\u{301D}' | '\u{FE59}' | '\u{FE5B}' | '\u{FE5D}' | '\u{FF08}' | '\u{FF3B}' |
+        '\u{FF5B}' | '\u{FF5F}' | '\u{FF62}' => LineBreakClass::OP,
+        '}' | '\u{2046}' | '\u{207E}' | '\u{208E}' | '\u{232A}' | '\u{3001}' | '\u{3002}' |
+        '\u{3009}' | '\u{300B}' | '\u{300D}' | '\u{300F}' | '\u{3011}' | '\u{3015}' |
+        '\u{3017}' | '\u{3019}' | '\u{301B}' | '\u{301E}' | '\u{301F}' | '\u{FE50}' |
+        '\u{FE52}' | '\u{FE5A}' | '\u{FE5C}' | '\u{FE5E}' | '\u{FF0C}' | '\u{FF0E}' |
+        '\u{FF5D}' | '\u{FF60}' | '\u{FF61}' | '\u{FF63}' | '\u{FF64}' => LineBreakClass::CL,
+        ')' | ']' | '\u{FF09}' | '\u{FF3D}' => LineBreakClass::CP,
+        '"' | '\'' | '\u{AB}' | '\u{BB}' | '\u{2018}' | '\u{2019}' | '\u{201B}'...'\u{201D}' |
+        '\u{201F}' | '\u{2039}' | '\u{203A}' | '\u{275B}'...'\u{275E}' => LineBreakClass::QU,
+        '\u{17D6}' | '\u{203C}' | '\u{203D}' | '\u{2047}'...'\u{2049}' | '\u{3005}' |
+        '\u{301C}' | '\u{303B}' | '\u{303C}' | '\u{309B}'...'\u{309E}' | '\u{30A0}' |
+        '\u{30FB}'...'\u{30FE}' | '\u{A015}' | '\u{FE54}' | '\u{FE55}' | '\u{FF1A}' |
+        '\u{FF1B}' | '\u{FF65}' | '\u{FF9E}' | '\u{FF9F}' => LineBreakClass::NS,
+        // Small kana, which are treated like other characters that can't start a line.
+        '\u{3041}' | '\u{3043}' | '\u{3045}' | '\u{3047}' | '\u{3049}' | '\u{3063}' |
+        '\u{3083}' | '\u{3085}' | '\u{3087}' | '\u{308E}' | '\u{3095}' | '\u{3096}' |
+        '\u{30A1}' | '\u{30A3}' | '\u{30A5}' | '\u{30A7}' | '\u{30A9}' | '\u{30C3}' |
+        '\u{30E3}' | '\u{30E5}' | '\u{30E7}' | '\u{30EE}' | '\u{30F5}' | '\u{30F6}' |
+        '\u{31F0}'...'\u{31FF}' | '\u{FF67}'...'\u{FF70}' => LineBreakClass::NS,
+        '!' | '?' | '\u{5C6}' | '\u{61B}' | '\u{61E}' | '\u{61F}' | '\u{6D4}' | '\u{7F9}' |
+        '\u{F0D}'...'\u{F11}' | '\u{F14}' | '\u{1802}' | '\u{1803}' | '\u{1808}' | '\u{1809}' |
+        '\u{1944}' | '\u{1945}' | '\u{2762}' | '\u{2763}' | '\u{2CF9}' | '\u{2CFE}' |
+        '\u{2E2E}' | '\u{FE15}' | '\u{FE16}' | '\u{FE56}' | '\u{FE57}' | '\u{FF01}' |
+        '\u{FF1F}' => LineBreakClass::EX,
+        '/' => LineBreakClass::SY,
+        ',' | '.' | ':' | ';' | '\u{37E}' | '\u{589}' | '\u{60C}' | '\u{60D}' | '\u{7F8}' |
+        '\u{2044}' | '\u{FE10}' | '\u{FE13}' | '\u{FE14}' => LineBreakClass::IS,
+        '$' | '+' | '\\' | '\u{A3}'...'\u{A5}' | '\u{B1}' | '\u{20A0}'...'\u{20A6}' |
+        '\u{20A8}'...'\u{20B5}' | '\u{20B7}'...'\u{20CF}' | '\u{2116}' | '\u{2212}' |
+        '\u{2213}' | '\u{FE69}' | '\u{FF04}' | '\u{FFE1}' | '\u{FFE5}' |
+        '\u{FFE6}' => LineBreakClass::PR,
+        '%' | '\u{A2}' | '\u{B0}' | '\u{609}'...'\u{60B}' | '\u{66A}' | '\u{2030}'...'\u{2037}' |
+        '\u{20A7}' | '\u{20B6}' | '\u{2103}' | '\u{2109}' | '\u{FE6A}' | '\u{FF05}' |
+        '\u{FFE0}' => LineBreakClass::PO,
+        '0'...'9' | '\u{660}'...'\u{669}' | '\u{66B}' | '\u{66C}' | '\u{6F0}'...'\u{6F9}' |
+        '\u{7C0}'...'\u{7C9}' | '\u{966}'...'\u{96F}' | '\u{9E6}'...'\u{9EF}' |
+        '\u{A66}'...'\u{A6F}' | '\u{AE6}'...'\u{AEF}' | '\u{B66}'...'\u{B6F}' |
+        '\u{BE6}'...'\u{BEF}' | '\u{C66}'...'\u{C6F}' | '\u{CE6}'...'\u{CEF}' |
+        '\u{D66}'...'\u{D6F}' | '\u{E50}'...'\u{E59}' | '\u{ED0}'...'\u{ED9}' |
+        '\u{F20}'...'\u{F29}' | '\u{1040}'...'\u{1049}' | '\u{17E0}'...'\u{17E9}' |
+        '\u{1810}'...'\u{1819}' | '\u{FF10}'...'\u{FF19}' => LineBreakClass::NU,
+        '\u{2024}'...'\u{2026}' | '\u{22EF}' | '\u{FE19}' => LineBreakClass::IN,
+        '-' => LineBreakClass::HY,
+        '|' | '\u{AD}' | '\u{58A}' | '\u{5BE}' | '\u{964}' | '\u{965}' | '\u{E5A}' |
+        '\u{E5B}' | '\u{1361}' | '\u{1680}' | '\u{17D4}' | '\u{17D5}' | '\u{2000}'...'\u{2006}' |
+        '\u{2008}'...'\u{200A}' | '\u{2010}' | '\u{2012}' | '\u{2013}' | '\u{2027}' |
+        '\u{205F}' | '\u{2E17}' | '\u{3000}' => LineBreakClass::BA,
+        '\u{B4}' | '\u{2C8}' | '\u{2CC}' | '\u{2DF}' | '\u{F01}'...'\u{F04}' | '\u{1806}' |
+        '\u{A874}' | '\u{A875}' => LineBreakClass::BB,
+        '\u{2014}' | '\u{2E3A}' | '\u{2E3B}' => LineBreakClass::B2,
+        '\u{2E80}'...'\u{2FFF}' |   // CJK Radicals, Kangxi Radicals and Description Characters
+        '\u{3003}'...'\u{3004}' |
+        '\u{3006}'...'\u{3007}' |
+        '\u{3012}'...'\u{3013}' |
+        '\u{3020}'...'\u{303A}' |
+        '\u{303D}'...'\u{30FF}' |   // Hiragana and Katakana
+        '\u{3100}'...'\u{31EF}' |   // Bopomofo, Hangul Compatibility Jamo, Kanbun and Strokes
+        '\u{3200}'...'\u{4DBF}' |   // Enclosed CJK, CJK Compatibility and Extension A
+        '\u{4E00}'...'\u{9FFF}' |   // CJK Unified Ideographs
+        '\u{A000}'...'\u{A4CF}' |   // Yi
+        '\u{AC00}'...'\u{D7A3}' |   // Hangul Syllables
+        '\u{F900}'...'\u{FAFF}' |   // CJK Compatibility Ideographs
+        '\u{FE30}'...'\u{FE4F}' |   // CJK Compatibility Forms
+        '\u{FF00}'...'\u{FF60}' |   // Fullwidth Forms
+        '\u{FFE0}'...'\u{FFE6}' |
+        '\u{1F000}'...'\u{1F2FF}' | // Mahjong, Domino and Playing Cards, Enclosed Ideographics
+        '\u{1F300}'...'\u{1F64F}' | // Pictographs and Emoticons
+        '\u{1F680}'...'\u{1F6FF}' | // Transport and Map Symbols
+        '\u{20000}'...'\u{2FFFD}' | // CJK Extensions B to E and Compatibility Supplement
+        '\u{30000}'...'\u{3FFFD}' => LineBreakClass::ID,
+        _ if is_combining_char(ch) => LineBreakClass::CM,
+        _ => LineBreakClass::AL,
+    }
+}
+
+/// Returns true if a line may not break between a character of class `before` and one of class
+/// `after`, with spaces between them if `spaces` is true.
+fn is_break_prohibited(before: LineBreakClass, after: LineBreakClass, spaces: bool) -> bool {
+    use self::LineBreakClass::*;
+
+    // Rules that hold even across spaces (LB11, LB13, LB14, LB15, LB16 and LB17).
+    match (before, after) {
+        (_, WJ) | (_, CL) | (_, CP) | (_, EX) | (_, IS) | (_, SY) | (OP, _) | (QU, OP) |
+        (CL, NS) | (CP, NS) | (B2, B2) => return true,
+        _ => {}
+    }
+    if spaces {
+        // LB18: break after spaces.
+        return false
+    }
+
+    match (before, after) {
+        // LB11 and LB12: don't break around word joiners and after glue.
+        (WJ, _) | (GL, _) => true,
+        // LB12a: don't break before glue, except after spaces and hyphens.
+        (BA, GL) | (HY, GL) => false,
+        (_, GL) => true,
+        // LB19: don't break around quotation marks.
+        (_, QU) | (QU, _) => true,
+        // LB21: don't break before hyphens and small kana, or after break-before characters.
+        (_, BA) | (_, HY) | (_, NS) | (BB, _) => true,
+        // LB22: don't break before ellipses.
+        (AL, IN) | (EX, IN) | (ID, IN) | (IN, IN) | (NU, IN) => true,
+        // LB23 and LB24: don't break inside words, or between numbers and their affixes.
+        (AL, NU) | (NU, AL) | (ID, PO) | (PR, ID) | (PR, AL) | (PO, AL) => true,
+        // LB25: don't break inside numbers.
+        (CL, PO) | (CL, PR) | (CP, PO) | (CP, PR) | (NU, PO) | (NU, PR) | (PO, OP) |
+        (PO, NU) | (PR, OP) | (PR, NU) | (HY, NU) | (IS, NU) | (NU, NU) | (SY, NU) => true,
+        // LB28 and LB29: don't break between letters, or after infix separators before them.
+        (AL, AL) | (IS, AL) => true,
+        // LB30: don't break between letters and numbers and the parentheses around them.
+        (AL, OP) | (NU, OP) | (CP, AL) | (CP, NU) => true,
+        // LB31: break everywhere else.
+        _ => false,
+    }
+}
+
+/// An iterator over the byte offsets in a string before which lines may break. The start of the
+/// string is never one of them.
+pub struct LineBreakIterator<'a> {
+    chars: CharIndices<'a>,
+    /// The class of the last character before any spaces, or `None` at the start of the text and
+    /// after mandatory breaks.
+    before: Option<LineBreakClass>,
+    /// Whether spaces follow the last character.
+    spaces: bool,
+    /// Finds the grapheme clusters of the text, which lines don't break inside.
+    grapheme_clusters: GraphemeClusterBreaker,
+}
+
+impl<'a> LineBreakIterator<'a> {
+    pub fn new(text: &'a str) -> LineBreakIterator<'a> {
+        LineBreakIterator {
+            chars: text.char_indices(),
+            before: None,
+            spaces: false,
+            grapheme_clusters: GraphemeClusterBreaker::new(),
+        }
+    }
+}
+
+impl<'a> Iterator for LineBreakIterator<'a> {
+    type Item = usize;
+
+    fn next(&mut self) -> Option<usize> {
+        while let Some((offset, ch)) = self.chars.next() {
+            let mut class = line_break_class(ch);
+            let starts_cluster = self.grapheme_clusters.is_boundary_before(ch);
+
+            // LB4 and LB5: break after mandatory breaks, but not between a carriage return and a
+            // line feed.
+            match (self.before, class) {
+                (Some(LineBreakClass::CR), LineBreakClass::LF) => {
+                    self.before = Some(LineBreakClass::LF);
+                    continue
+                }
+                (Some(LineBreakClass::BK), _) |
+                (Some(LineBreakClass::CR), _) |
+                (Some(LineBreakClass::LF), _) => {
+                    self.before = None;
+                    self.spaces = false;
+                    if class == LineBreakClass::SP {
+                        self.spaces = true;
+                    } else {
+                        self.before = Some(if class == LineBreakClass::CM {
+                            LineBreakClass::AL
+                        } else {
+                            class
+                        });
+                    }
+                    return Some(offset)
+                }
+                _ => {}
+            }
+
+            // LB6 and LB7: don't break before mandatory breaks or spaces.
+            if class == LineBreakClass::SP {
+                self.spaces = true;
+                continue
+            }
+
+            // LB9 and LB10: combining marks take the class of the character they follow, unless
+            // they follow a space or start the text, in which case they're alphabetic.
+            if class == LineBreakClass::CM {
+                if self.before.is_some() && !self.spaces {
+                    continue
+                }
+                class = LineBreakClass::AL
+            }
+
+            // LB8a, LB30a and LB30b: don't break inside emoji ZWJ sequences, flags or emoji with
+            // modifiers, which are drawn as one glyph.
+            if !starts_cluster {
+                continue
+            }
+
+            let before = self.before;
+            let spaces = self.spaces;
+            self.before = Some(class);
+            self.spaces = false;
+
+            let can_break = match (before, class) {
+                (_, LineBreakClass::BK) | (_, LineBreakClass::CR) | (_, LineBreakClass::LF) |
+                (_, LineBreakClass::ZW) => false,
+                // Break after the spaces at the start of the text.
+                (None, _) => spaces,
+                // LB8: break after zero width spaces, and any spaces that follow them.
+                (Some(LineBreakClass::ZW), _) => true,
+                (Some(before), _) => !is_break_prohibited(before, class, spaces),
+            };
+            if can_break {
+                return Some(offset)
+            }
+        }
+        None
+    }
+}
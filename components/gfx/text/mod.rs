@@ -12,6 +12,9 @@ pub use text::shaping::Shaper;
 pub use text::text_run::TextRun;
 
 pub mod glyph;
+pub mod hyphenation;
+pub mod line_break;
+pub mod shaped_run_cache;
 #[path="shaping/mod.rs"] pub mod shaping;
 pub mod text_run;
 pub mod util;
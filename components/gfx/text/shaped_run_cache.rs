@@ -0,0 +1,101 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+//! A cache of shaped text shared by all of the threads that shape text, so that a word shaped
+//! by one layout thread doesn't have to be shaped again by the others. The cache is split into
+//! shards, each behind its own lock, so that threads looking up different words rarely wait
+//! for each other.
+
+use font::ShapingOptions;
+use text::glyph::GlyphStore;
+
+use std::collections::HashMap;
+use std::hash::{Hash, Hasher, SipHasher};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use util::geometry::Au;
+use util::mem::HeapSizeOf;
+
+/// The number of separately locked parts of the cache.
+const SHARD_COUNT: usize = 16;
+
+/// The number of runs kept in each shard. A shard that fills up is emptied.
+const MAX_RUNS_PER_SHARD: usize = 4096;
+
+/// What a run of text was shaped with.
+#[derive(Clone, Eq, PartialEq, Hash)]
+pub struct ShapedRunKey {
+    /// The identifier of the font template the font was made from.
+    pub font: String,
+    pub pt_size: Au,
+    pub text: String,
+    pub options: ShapingOptions,
+}
+
+/// How well the cache has done so far.
+#[derive(Clone, Copy, Debug)]
+pub struct ShapedRunCacheStats {
+    pub hits: usize,
+    pub misses: usize,
+    pub runs: usize,
+}
+
+pub struct ShapedRunCache {
+    shards: Vec<Mutex<HashMap<ShapedRunKey, Arc<GlyphStore>>>>,
+    hits: AtomicUsize,
+    misses: AtomicUsize,
+}
+
+impl ShapedRunCache {
+    pub fn new() -> ShapedRunCache {
+        ShapedRunCache {
+            shards: (0..SHARD_COUNT).map(|_| Mutex::new(HashMap::new())).collect(),
+            hits: AtomicUsize::new(0),
+            misses: AtomicUsize::new(0),
+        }
+    }
+
+    fn shard(&self, key: &ShapedRunKey) -> &Mutex<HashMap<ShapedRunKey, Arc<GlyphStore>>> {
+        let mut hasher = SipHasher::new();
+        key.hash(&mut hasher);
+        &self.shards[hasher.finish() as usize % SHARD_COUNT]
+    }
+
+    pub fn find(&self, key: &ShapedRunKey) -> Option<Arc<GlyphStore>> {
+        let glyphs = self.shard(key).lock().unwrap().get(key).cloned();
+        match glyphs {
+            Some(_) => self.hits.fetch_add(1, Ordering::Relaxed),
+            None => self.misses.fetch_add(1, Ordering::Relaxed),
+        };
+        glyphs
+    }
+
+    pub fn insert(&self, key: ShapedRunKey, glyphs: Arc<GlyphStore>) {
+        let mut shard = self.shard(&key).lock().unwrap();
+        if shard.len() >= MAX_RUNS_PER_SHARD {
+            shard.clear();
+        }
+        shard.insert(key, glyphs);
+    }
+
+    pub fn stats(&self) -> ShapedRunCacheStats {
+        ShapedRunCacheStats {
+            hits: self.hits.load(Ordering::Relaxed),
+            misses: self.misses.load(Ordering::Relaxed),
+            runs: self.shards.iter().fold(0, |runs, shard| runs + shard.lock().unwrap().len()),
+        }
+    }
+}
+
+impl HeapSizeOf for ShapedRunCache {
+    fn heap_size_of_children(&self) -> usize {
+        // The hash tables themselves aren't measured, only the text and glyphs in them.
+        self.shards.iter().fold(0, |size, shard| {
+            shard.lock().unwrap().iter().fold(size, |size, (key, glyphs)| {
+                size + key.font.heap_size_of_children() + key.text.heap_size_of_children() +
+                    glyphs.heap_size_of_children()
+            })
+        })
+    }
+}
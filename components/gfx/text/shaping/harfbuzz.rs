@@ -4,15 +4,15 @@
 
 extern crate harfbuzz;
 
-use font::{DISABLE_KERNING_SHAPING_FLAG, Font, FontHandleMethods, FontTableMethods, FontTableTag};
-use font::{IGNORE_LIGATURES_SHAPING_FLAG, ShapingOptions};
+use font::{Font, FontHandleMethods, FontKerning, FontLigatures, FontTableMethods, FontTableTag};
+use font::{IGNORE_LIGATURES_SHAPING_FLAG, ShapingOptions, VERTICAL_SHAPING_FLAG};
 use platform::font::FontTable;
 use text::glyph::{CharIndex, GlyphStore, GlyphId, GlyphData};
-use text::shaping::ShaperMethods;
-use text::util::{float_to_fixed, fixed_to_float};
+use text::shaping::Shaper;
+use text::util::{float_to_fixed, fixed_to_float, is_attached_mark};
 
 use euclid::Point2D;
-use harfbuzz::{HB_MEMORY_MODE_READONLY, HB_DIRECTION_LTR};
+use harfbuzz::{HB_MEMORY_MODE_READONLY, HB_DIRECTION_LTR, HB_DIRECTION_TTB};
 use harfbuzz::{RUST_hb_blob_create, RUST_hb_face_create_for_tables};
 use harfbuzz::{hb_blob_t};
 use harfbuzz::{hb_bool_t};
@@ -31,6 +31,8 @@ use harfbuzz::{RUST_hb_font_funcs_destroy};
 use harfbuzz::{RUST_hb_font_funcs_set_glyph_func};
 use harfbuzz::{RUST_hb_font_funcs_set_glyph_h_advance_func};
 use harfbuzz::{RUST_hb_font_funcs_set_glyph_h_kerning_func};
+use harfbuzz::{RUST_hb_font_funcs_set_glyph_v_advance_func};
+use harfbuzz::{RUST_hb_font_funcs_set_glyph_v_origin_func};
 use harfbuzz::{hb_font_funcs_t, hb_buffer_t, hb_codepoint_t};
 use harfbuzz::{RUST_hb_font_set_funcs};
 use harfbuzz::{RUST_hb_font_set_ppem};
@@ -48,17 +50,17 @@ use std::mem;
 use std::cmp;
 use std::ptr;
 
-macro_rules! hb_tag {
-    ($t1:expr, $t2:expr, $t3:expr, $t4:expr) => (
-        (($t1 as u32) << 24) | (($t2 as u32) << 16) | (($t3 as u32) << 8) | ($t4 as u32)
-    );
-}
-
 static NO_GLYPH: i32 = -1;
 static CONTINUATION_BYTE: i32 = -2;
 
+static CALT: u32 = hb_tag!('c', 'a', 'l', 't');
+static CLIG: u32 = hb_tag!('c', 'l', 'i', 'g');
+static DLIG: u32 = hb_tag!('d', 'l', 'i', 'g');
+static HLIG: u32 = hb_tag!('h', 'l', 'i', 'g');
 static KERN: u32 = hb_tag!('k', 'e', 'r', 'n');
 static LIGA: u32 = hb_tag!('l', 'i', 'g', 'a');
+static VERT: u32 = hb_tag!('v', 'e', 'r', 't');
+static VRT2: u32 = hb_tag!('v', 'r', 't', '2');
 
 pub struct ShapedGlyphData {
     count: usize,
@@ -106,22 +108,35 @@ impl ShapedGlyphData {
     }
 
     /// Returns shaped glyph data for one glyph, and updates the y-position of the pen.
-    pub fn get_entry_for_glyph(&self, i: usize, y_pos: &mut Au) -> ShapedGlyphEntry {
+    ///
+    /// For vertical text, the advance is the distance down to the next glyph, and the offset is
+    /// where to put the glyph's horizontal origin relative to the pen, with y pointing down.
+    pub fn get_entry_for_glyph(&self, i: usize, y_pos: &mut Au, vertical: bool)
+                               -> ShapedGlyphEntry {
         assert!(i < self.count);
 
         unsafe {
             let glyph_info_i = self.glyph_infos.offset(i as isize);
             let pos_info_i = self.pos_infos.offset(i as isize);
-            let x_offset = Shaper::fixed_to_float((*pos_info_i).x_offset);
-            let y_offset = Shaper::fixed_to_float((*pos_info_i).y_offset);
-            let x_advance = Shaper::fixed_to_float((*pos_info_i).x_advance);
-            let y_advance = Shaper::fixed_to_float((*pos_info_i).y_advance);
+            let x_offset = HarfBuzzShaper::fixed_to_float((*pos_info_i).x_offset);
+            let y_offset = HarfBuzzShaper::fixed_to_float((*pos_info_i).y_offset);
+            let x_advance = HarfBuzzShaper::fixed_to_float((*pos_info_i).x_advance);
+            let y_advance = HarfBuzzShaper::fixed_to_float((*pos_info_i).y_advance);
 
             let x_offset = Au::from_f64_px(x_offset);
             let y_offset = Au::from_f64_px(y_offset);
             let x_advance = Au::from_f64_px(x_advance);
             let y_advance = Au::from_f64_px(y_advance);
 
+            if vertical {
+                // HarfBuzz has y pointing up, so it moves the pen down by negative advances.
+                return ShapedGlyphEntry {
+                    codepoint: (*glyph_info_i).codepoint as GlyphId,
+                    advance: -y_advance,
+                    offset: Some(Point2D::new(x_offset, -y_offset)),
+                }
+            }
+
             let offset = if x_offset == Au(0) && y_offset == Au(0) && y_advance == Au(0) {
                 None
             } else {
@@ -147,14 +162,14 @@ struct FontAndShapingOptions {
     options: ShapingOptions,
 }
 
-pub struct Shaper {
+pub struct HarfBuzzShaper {
     hb_face: *mut hb_face_t,
     hb_font: *mut hb_font_t,
     hb_funcs: *mut hb_font_funcs_t,
     font_and_shaping_options: Box<FontAndShapingOptions>,
 }
 
-impl Drop for Shaper {
+impl Drop for HarfBuzzShaper {
     fn drop(&mut self) {
         unsafe {
             assert!(!self.hb_face.is_null());
@@ -169,12 +184,12 @@ impl Drop for Shaper {
     }
 }
 
-impl Shaper {
-    pub fn new(font: &mut Font, options: &ShapingOptions) -> Shaper {
+impl HarfBuzzShaper {
+    pub fn new(font: &mut Font, options: &ShapingOptions) -> HarfBuzzShaper {
         unsafe {
             let mut font_and_shaping_options = box FontAndShapingOptions {
                 font: font,
-                options: *options,
+                options: options.clone(),
             };
             let hb_face: *mut hb_face_t =
                 RUST_hb_face_create_for_tables(get_font_table_func,
@@ -190,19 +205,28 @@ impl Shaper {
 
             // Set scaling. Note that this takes 16.16 fixed point.
             RUST_hb_font_set_scale(hb_font,
-                                   Shaper::float_to_fixed(pt_size) as c_int,
-                                   Shaper::float_to_fixed(pt_size) as c_int);
+                                   HarfBuzzShaper::float_to_fixed(pt_size) as c_int,
+                                   HarfBuzzShaper::float_to_fixed(pt_size) as c_int);
 
             // configure static function callbacks.
             // NB. This funcs structure could be reused globally, as it never changes.
             let hb_funcs: *mut hb_font_funcs_t = RUST_hb_font_funcs_create();
             RUST_hb_font_funcs_set_glyph_func(hb_funcs, glyph_func, ptr::null_mut(), None);
             RUST_hb_font_funcs_set_glyph_h_advance_func(hb_funcs, glyph_h_advance_func, ptr::null_mut(), None);
+            // The kerning callback is given the options, which are replaced by `set_options`
+            // whenever a shaper that has already been made is used again.
             RUST_hb_font_funcs_set_glyph_h_kerning_func(
-                hb_funcs, glyph_h_kerning_func, ptr::null_mut(), ptr::null_mut());
+                hb_funcs,
+                glyph_h_kerning_func,
+                &mut *font_and_shaping_options as *mut FontAndShapingOptions as *mut c_void,
+                ptr::null_mut());
+            RUST_hb_font_funcs_set_glyph_v_advance_func(
+                hb_funcs, glyph_v_advance_func, ptr::null_mut(), None);
+            RUST_hb_font_funcs_set_glyph_v_origin_func(
+                hb_funcs, glyph_v_origin_func, ptr::null_mut(), None);
             RUST_hb_font_set_funcs(hb_font, hb_funcs, font as *mut Font as *mut c_void, None);
 
-            Shaper {
+            HarfBuzzShaper {
                 hb_face: hb_face,
                 hb_font: hb_font,
                 hb_funcs: hb_funcs,
@@ -211,10 +235,6 @@ impl Shaper {
         }
     }
 
-    pub fn set_options(&mut self, options: &ShapingOptions) {
-        self.font_and_shaping_options.options = *options
-    }
-
     fn float_to_fixed(f: f64) -> i32 {
         float_to_fixed(16, f)
     }
@@ -224,13 +244,19 @@ impl Shaper {
     }
 }
 
-impl ShaperMethods for Shaper {
+impl Shaper for HarfBuzzShaper {
+    fn set_options(&mut self, options: &ShapingOptions) {
+        self.font_and_shaping_options.options = options.clone()
+    }
+
     /// Calculate the layout metrics associated with the given text when painted in a specific
     /// font.
     fn shape_text(&self, text: &str, options: &ShapingOptions, glyphs: &mut GlyphStore) {
         unsafe {
+            let vertical = options.flags.contains(VERTICAL_SHAPING_FLAG);
             let hb_buffer: *mut hb_buffer_t = RUST_hb_buffer_create();
-            RUST_hb_buffer_set_direction(hb_buffer, HB_DIRECTION_LTR);
+            let direction = if vertical { HB_DIRECTION_TTB } else { HB_DIRECTION_LTR };
+            RUST_hb_buffer_set_direction(hb_buffer, direction);
 
             RUST_hb_buffer_add_utf8(hb_buffer,
                                     text.as_ptr() as *const c_char,
@@ -238,23 +264,15 @@ impl ShaperMethods for Shaper {
                                     0,
                                     text.len() as c_int);
 
-            let mut features = Vec::new();
-            if options.flags.contains(IGNORE_LIGATURES_SHAPING_FLAG) {
-                features.push(hb_feature_t {
-                    _tag: LIGA,
-                    _value: 0,
-                    _start: 0,
-                    _end: RUST_hb_buffer_get_length(hb_buffer),
-                })
-            }
-            if options.flags.contains(DISABLE_KERNING_SHAPING_FLAG) {
-                features.push(hb_feature_t {
-                    _tag: KERN,
-                    _value: 0,
+            let length = RUST_hb_buffer_get_length(hb_buffer);
+            let mut features = shaping_features(options).into_iter().map(|(tag, value)| {
+                hb_feature_t {
+                    _tag: tag,
+                    _value: value,
                     _start: 0,
-                    _end: RUST_hb_buffer_get_length(hb_buffer),
-                })
-            }
+                    _end: length,
+                }
+            }).collect::<Vec<_>>();
 
             RUST_hb_shape(self.hb_font, hb_buffer, features.as_mut_ptr(), features.len() as u32);
             self.save_glyph_results(text, options, glyphs, hb_buffer);
@@ -263,7 +281,7 @@ impl ShaperMethods for Shaper {
     }
 }
 
-impl Shaper {
+impl HarfBuzzShaper {
     fn save_glyph_results(&self,
                           text: &str,
                           options: &ShapingOptions,
@@ -328,6 +346,10 @@ impl Shaper {
         // so, end() points to first byte of last+1 char, if it's less than byte_max.
         let mut char_byte_span: Range<usize> = Range::empty();
         let mut y_pos = Au(0);
+        let vertical = options.flags.contains(VERTICAL_SHAPING_FLAG);
+        // The advance of the last glyph that wasn't a combining mark, which marks after it are
+        // centered over if the font doesn't position them.
+        let mut base_advance = None;
 
         // main loop over each glyph. each iteration usually processes 1 glyph and 1+ chars.
         // in cases with complex glyph-character associations, 2+ glyphs and 1+ chars can be
@@ -449,14 +471,18 @@ impl Shaper {
                 // (i.e., pretend there are no combining character sequences).
                 // 1-to-1 mapping of character to glyph also treated as ligature start.
                 //
-                // NB: When we acquire the ability to handle ligatures that cross word boundaries,
-                // we'll need to do something special to handle `word-spacing` properly.
-                let shape = glyph_data.get_entry_for_glyph(glyph_span.begin(), &mut y_pos);
+                let shape =
+                    glyph_data.get_entry_for_glyph(glyph_span.begin(), &mut y_pos, vertical);
                 let character = text.char_at(char_byte_span.begin());
-                let advance = self.advance_for_shaped_glyph(shape.advance, character, options);
+                let advance = self.advance_for_shaped_glyph(shape.advance, character);
+                let (advance, offset) = self.position_mark(character,
+                                                           advance,
+                                                           shape.offset,
+                                                           &mut base_advance,
+                                                           vertical);
                 let data = GlyphData::new(shape.codepoint,
                                           advance,
-                                          shape.offset,
+                                          offset,
                                           false,
                                           true,
                                           true);
@@ -465,11 +491,28 @@ impl Shaper {
                 // collect all glyphs to be assigned to the first character.
                 let mut datas = vec!();
 
+                // Clusters with a glyph for each character, such as a letter and the marks on
+                // it, have their marks positioned like lone ones.
+                let characters = text[covered_byte_span.begin()..covered_byte_span.end()]
+                    .chars()
+                    .collect::<Vec<_>>();
+                let glyph_characters = characters.len() == glyph_span.length();
+
                 for glyph_i in glyph_span.each_index() {
-                    let shape = glyph_data.get_entry_for_glyph(glyph_i, &mut y_pos);
+                    let shape = glyph_data.get_entry_for_glyph(glyph_i, &mut y_pos, vertical);
+                    let (advance, offset) = if glyph_characters {
+                        self.position_mark(characters[glyph_i - glyph_span.begin()],
+                                           shape.advance,
+                                           shape.offset,
+                                           &mut base_advance,
+                                           vertical)
+                    } else {
+                        base_advance = None;
+                        (shape.advance, shape.offset)
+                    };
                     datas.push(GlyphData::new(shape.codepoint,
-                                              shape.advance,
-                                              shape.offset,
+                                              advance,
+                                              offset,
                                               false, // not missing
                                               true,  // treat as cluster start
                                               glyph_i > glyph_span.begin()));
@@ -504,25 +547,42 @@ impl Shaper {
         glyphs.finalize_changes();
     }
 
-    fn advance_for_shaped_glyph(&self, mut advance: Au, character: char, options: &ShapingOptions)
-                                -> Au {
-        match options.letter_spacing {
-            None => {}
-            Some(letter_spacing) => advance = advance + letter_spacing,
-        };
-
-        // CSS 2.1 § 16.4 states that "word spacing affects each space (U+0020) and non-breaking
-        // space (U+00A0) left in the text after the white space processing rules have been
-        // applied. The effect of the property on other word-separator characters is undefined."
-        // We elect to only space the two required code points.
-        if character == ' ' || character == '\u{a0}' {
-            advance = advance + options.word_spacing
-        } else if character == '\t' {
-            let tab_size = 8f64;
-            advance = Au::from_f64_px(tab_size * glyph_space_advance(self.font_and_shaping_options.font));
+    /// Returns the advance and offset of the glyph for `character`. Combining marks that still
+    /// have an advance after shaping weren't positioned by the font, which happens when it has
+    /// no mark positioning or when the mark fell back to a different font from the character it
+    /// is on. Those are centered over the glyph before them instead, with no advance. If that
+    /// glyph is in another run, they are centered on the pen position, and the text run moves
+    /// them back over it once layout knows its advance. `base_advance` is the advance of the
+    /// last glyph that isn't a mark, which this updates.
+    fn position_mark(&self,
+                     character: char,
+                     advance: Au,
+                     offset: Option<Point2D<Au>>,
+                     base_advance: &mut Option<Au>,
+                     vertical: bool)
+                     -> (Au, Option<Point2D<Au>>) {
+        if !is_attached_mark(character) {
+            *base_advance = Some(advance);
+            return (advance, offset)
+        }
+        if vertical || advance == Au(0) {
+            return (advance, offset)
         }
 
-        advance
+        let base_advance = base_advance.unwrap_or(Au(0));
+        let offset = offset.unwrap_or(Point2D::zero());
+        (Au(0), Some(Point2D::new(offset.x - (base_advance + advance) / 2, offset.y)))
+    }
+
+    fn advance_for_shaped_glyph(&self, advance: Au, character: char) -> Au {
+        // Letter and word spacing are added by the glyph store after shaping, so that they're
+        // placed after whole ligatures and clusters.
+        if character == '\t' {
+            let tab_size = 8f64;
+            Au::from_f64_px(tab_size * glyph_space_advance(self.font_and_shaping_options.font))
+        } else {
+            advance
+        }
     }
 }
 
@@ -558,10 +618,87 @@ extern fn glyph_h_advance_func(_: *mut hb_font_t,
 
     unsafe {
         let advance = (*font).glyph_h_advance(glyph as GlyphId);
-        Shaper::float_to_fixed(advance)
+        HarfBuzzShaper::float_to_fixed(advance)
+    }
+}
+
+extern fn glyph_v_advance_func(_: *mut hb_font_t,
+                               font_data: *mut c_void,
+                               glyph: hb_codepoint_t,
+                               _: *mut c_void)
+                            -> hb_position_t {
+    let font: *mut Font = font_data as *mut Font;
+    assert!(!font.is_null());
+
+    unsafe {
+        // HarfBuzz has y pointing up, so advances down the page are negative.
+        let advance = (*font).glyph_v_advance(glyph as GlyphId);
+        HarfBuzzShaper::float_to_fixed(-advance)
+    }
+}
+
+extern fn glyph_v_origin_func(_: *mut hb_font_t,
+                              font_data: *mut c_void,
+                              glyph: hb_codepoint_t,
+                              x: *mut hb_position_t,
+                              y: *mut hb_position_t,
+                              _: *mut c_void)
+                           -> hb_bool_t {
+    let font: *mut Font = font_data as *mut Font;
+    assert!(!font.is_null());
+
+    unsafe {
+        let origin = (*font).glyph_v_origin(glyph as GlyphId);
+        *x = HarfBuzzShaper::float_to_fixed(origin.x);
+        *y = HarfBuzzShaper::float_to_fixed(origin.y);
+        true as hb_bool_t
     }
 }
 
+/// Returns the OpenType features that `options` set, and their values, in the order that they are
+/// applied, so that later settings of a feature override earlier ones.
+pub fn shaping_features(options: &ShapingOptions) -> Vec<(hb_tag_t, u32)> {
+    // The ligatures and kerning come before the features from the style, so that
+    // `font-feature-settings` overrides them, and the flags come after so that they take
+    // precedence over both.
+    let mut features = ligature_features(&options.ligatures).into_iter().map(|(tag, on)| {
+        (tag, on as u32)
+    }).collect::<Vec<_>>();
+    match options.kerning {
+        FontKerning::Auto => {}
+        FontKerning::Normal => features.push((KERN, 1)),
+        FontKerning::None => features.push((KERN, 0)),
+    }
+    features.extend(options.features.iter().map(|feature| (feature.tag, feature.value)));
+    if options.flags.contains(IGNORE_LIGATURES_SHAPING_FLAG) {
+        features.push((LIGA, 0))
+    }
+    if options.flags.contains(VERTICAL_SHAPING_FLAG) {
+        // Substitute the glyphs that fonts have for characters that are drawn differently in
+        // vertical text, such as brackets and punctuation.
+        features.push((VERT, 1));
+        features.push((VRT2, 1));
+    }
+    features
+}
+
+/// Returns the OpenType features that turn the kinds of ligatures in `ligatures` on or off.
+fn ligature_features(ligatures: &FontLigatures) -> Vec<(hb_tag_t, bool)> {
+    let kinds = [
+        (ligatures.common, &[LIGA, CLIG][..]),
+        (ligatures.discretionary, &[DLIG][..]),
+        (ligatures.historical, &[HLIG][..]),
+        (ligatures.contextual, &[CALT][..]),
+    ];
+    let mut features = vec!();
+    for &(on, tags) in kinds.iter() {
+        if let Some(on) = on {
+            features.extend(tags.iter().map(|&tag| (tag, on)))
+        }
+    }
+    features
+}
+
 fn glyph_space_advance(font: *mut Font) -> f64 {
     let space_unicode = ' ';
     let space_glyph: hb_codepoint_t;
@@ -579,14 +716,20 @@ extern fn glyph_h_kerning_func(_: *mut hb_font_t,
                                font_data: *mut c_void,
                                first_glyph: hb_codepoint_t,
                                second_glyph: hb_codepoint_t,
-                               _: *mut c_void)
+                               user_data: *mut c_void)
                             -> hb_position_t {
     let font: *mut Font = font_data as *mut Font;
     assert!(!font.is_null());
 
     unsafe {
+        let font_and_shaping_options = user_data as *const FontAndShapingOptions;
+        assert!(!font_and_shaping_options.is_null());
+        if (*font_and_shaping_options).options.kerning == FontKerning::None {
+            return 0
+        }
+
         let advance = (*font).glyph_h_kerning(first_glyph as GlyphId, second_glyph as GlyphId);
-        Shaper::float_to_fixed(advance)
+        HarfBuzzShaper::float_to_fixed(advance)
     }
 }
 
@@ -5,16 +5,33 @@
 //! Shaper encapsulates a specific shaper, such as Harfbuzz,
 //! Uniscribe, Pango, or Coretext.
 //!
-//! Currently, only harfbuzz bindings are implemented.
+//! Currently, only harfbuzz bindings are implemented, behind a fast path for simple runs of
+//! ASCII text. Other backends implement the `Shaper` trait, and are chosen in `create_shaper`.
 
-use font::ShapingOptions;
+use font::{Font, ShapingOptions};
 use text::glyph::GlyphStore;
+use text::shaping::simple::SimpleShaper;
 
-pub use text::shaping::harfbuzz::Shaper;
+/// Makes an OpenType tag, such as a table or feature tag, out of its four characters.
+macro_rules! hb_tag {
+    ($t1:expr, $t2:expr, $t3:expr, $t4:expr) => (
+        (($t1 as u32) << 24) | (($t2 as u32) << 16) | (($t3 as u32) << 8) | ($t4 as u32)
+    );
+}
 
 pub mod harfbuzz;
+pub mod simple;
+
+/// A text shaping backend, which turns runs of text into glyphs for a single font.
+pub trait Shaper {
+    /// Changes the options that the shaper's font callbacks see.
+    fn set_options(&mut self, options: &ShapingOptions);
 
-pub trait ShaperMethods {
+    /// Shapes `text`, and stores the resulting glyphs in `glyphs`.
     fn shape_text(&self, text: &str, options: &ShapingOptions, glyphs: &mut GlyphStore);
 }
 
+/// Creates the shaper to use for `font`.
+pub fn create_shaper(font: &mut Font, options: &ShapingOptions) -> Box<Shaper> {
+    box SimpleShaper::new(font, options)
+}
@@ -0,0 +1,240 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+//! A fast path for shaping runs of plain ASCII text, which places glyphs with the font's `cmap`,
+//! `hmtx` and `kern` tables directly instead of going through HarfBuzz.
+//!
+//! HarfBuzz would give the same glyphs for these runs as long as none of the features it applies
+//! to them has lookups in the font's `GSUB` or `GPOS` table, since it only substitutes and
+//! positions glyphs with those, so every other run is handed to HarfBuzz. Most Latin fonts have
+//! those tables, but only for features such as small capitals and old-style numerals that are
+//! off unless the style turns them on.
+
+use font::{Font, FontTableTag, ShapingOptions, VERTICAL_SHAPING_FLAG};
+use sfnt::{read_u16, read_u32};
+use text::glyph::{CharIndex, GlyphData, GlyphId, GlyphStore};
+use text::shaping::Shaper;
+use text::shaping::harfbuzz::{HarfBuzzShaper, shaping_features};
+use util::geometry::Au;
+
+const GPOS: FontTableTag = 0x47504F53;
+const GSUB: FontTableTag = 0x47535542;
+
+static DFLT: FontTableTag = hb_tag!('D', 'F', 'L', 'T');
+static KERN: FontTableTag = hb_tag!('k', 'e', 'r', 'n');
+static LATN: FontTableTag = hb_tag!('l', 'a', 't', 'n');
+
+/// The features that HarfBuzz applies to horizontal, left-to-right Latin text unless they're
+/// turned off.
+static DEFAULT_FEATURES: [FontTableTag; 17] = [
+    hb_tag!('r', 'v', 'r', 'n'), hb_tag!('l', 't', 'r', 'a'), hb_tag!('l', 't', 'r', 'm'),
+    hb_tag!('a', 'b', 'v', 'm'), hb_tag!('b', 'l', 'w', 'm'), hb_tag!('c', 'c', 'm', 'p'),
+    hb_tag!('l', 'o', 'c', 'l'), hb_tag!('m', 'a', 'r', 'k'), hb_tag!('m', 'k', 'm', 'k'),
+    hb_tag!('r', 'l', 'i', 'g'), hb_tag!('c', 'a', 'l', 't'), hb_tag!('c', 'l', 'i', 'g'),
+    hb_tag!('c', 'u', 'r', 's'), hb_tag!('d', 'i', 's', 't'), hb_tag!('k', 'e', 'r', 'n'),
+    hb_tag!('l', 'i', 'g', 'a'), hb_tag!('r', 'c', 'l', 't'),
+];
+
+pub struct SimpleShaper {
+    font: *mut Font,
+    /// The features that the font's `GSUB` and `GPOS` tables have lookups for in the scripts
+    /// that HarfBuzz uses for ASCII text, or `None` if every run has to go to HarfBuzz.
+    layout_features: Option<Vec<FontTableTag>>,
+    /// Whether the font has a `GPOS` table, in which case HarfBuzz doesn't kern with the `kern`
+    /// table, even if `GPOS` has no kerning of its own.
+    has_gpos: bool,
+    /// The shaper for the runs that can't be shaped simply.
+    harfbuzz: HarfBuzzShaper,
+}
+
+impl SimpleShaper {
+    pub fn new(font: &mut Font, options: &ShapingOptions) -> SimpleShaper {
+        let mut layout_features = Some(vec!());
+        let mut has_gpos = false;
+        for &tag in [GSUB, GPOS].iter() {
+            if let Some(table) = font.table_bytes(tag) {
+                has_gpos = has_gpos || tag == GPOS;
+                layout_features = match (layout_features, default_features(&table)) {
+                    (Some(mut features), Some(table_features)) => {
+                        features.extend(table_features.into_iter());
+                        Some(features)
+                    }
+                    _ => None,
+                }
+            }
+        }
+        let harfbuzz = HarfBuzzShaper::new(font, options);
+        SimpleShaper {
+            font: font,
+            layout_features: layout_features,
+            has_gpos: has_gpos,
+            harfbuzz: harfbuzz,
+        }
+    }
+
+    /// Returns the glyphs of the characters of `text` if it can be shaped without HarfBuzz: it
+    /// must be horizontal and printable ASCII, none of the `features` that are on may have
+    /// lookups in the font, and the font must have glyphs for all of it.
+    fn simple_glyphs(&self, text: &str, options: &ShapingOptions, features: &[FontTableTag])
+                     -> Option<Vec<GlyphId>> {
+        let layout_features = match self.layout_features {
+            Some(ref layout_features) => layout_features,
+            None => return None,
+        };
+        if options.flags.contains(VERTICAL_SHAPING_FLAG) ||
+                features.iter().any(|feature| layout_features.contains(feature)) {
+            return None
+        }
+        if !text.bytes().all(|byte| byte >= b' ' && byte <= b'~') {
+            return None
+        }
+        let font = unsafe {
+            &*self.font
+        };
+        text.chars().map(|character| font.glyph_index(character)).collect()
+    }
+}
+
+/// Returns the OpenType features that HarfBuzz applies to horizontal text shaped with `options`:
+/// the ones it turns on by default, with the changes that `options` make to them.
+pub fn enabled_features(options: &ShapingOptions) -> Vec<FontTableTag> {
+    let mut features = DEFAULT_FEATURES.to_vec();
+    for (tag, value) in shaping_features(options).into_iter() {
+        features.retain(|&feature| feature != tag);
+        if value != 0 {
+            features.push(tag)
+        }
+    }
+    features
+}
+
+/// Returns the features that the language systems of the `latn` and `DFLT` scripts of `table`, a
+/// `GSUB` or `GPOS` table, have lookups for. HarfBuzz picks one of those scripts for ASCII text,
+/// depending on whether it has letters, and one of their language systems depending on the
+/// language, which is the locale's since runs aren't tagged with one, so all of them are
+/// included. Returns `None` if the table can't be read or if one of those language systems has a
+/// required feature, which is applied whatever the options are.
+pub fn default_features(table: &[u8]) -> Option<Vec<FontTableTag>> {
+    if table.len() < 10 {
+        return None
+    }
+    let script_list = read_u16(table, 4) as usize;
+    let feature_list = read_u16(table, 6) as usize;
+    if script_list + 2 > table.len() || feature_list + 2 > table.len() {
+        return None
+    }
+    let script_count = read_u16(table, script_list) as usize;
+
+    let mut features = vec!();
+    for index in 0..script_count {
+        let record = script_list + 2 + index * 6;
+        if record + 6 > table.len() {
+            return None
+        }
+        let script_tag = read_u32(table, record);
+        if script_tag != LATN && script_tag != DFLT {
+            continue
+        }
+        let script = script_list + read_u16(table, record + 4) as usize;
+        if script + 4 > table.len() {
+            return None
+        }
+        let default_language_system = read_u16(table, script) as usize;
+        if default_language_system != 0 {
+            if !add_language_system_features(table,
+                                             script + default_language_system,
+                                             feature_list,
+                                             &mut features) {
+                return None
+            }
+        }
+        let language_system_count = read_u16(table, script + 2) as usize;
+        if script + 4 + language_system_count * 6 > table.len() {
+            return None
+        }
+        for index in 0..language_system_count {
+            let language_system = read_u16(table, script + 4 + index * 6 + 4) as usize;
+            if !add_language_system_features(table,
+                                             script + language_system,
+                                             feature_list,
+                                             &mut features) {
+                return None
+            }
+        }
+    }
+    Some(features)
+}
+
+/// Adds the features that the language system at `language_system` in `table` has lookups for
+/// to `features`. Returns false if the table can't be read or if the language system has a
+/// required feature.
+fn add_language_system_features(table: &[u8],
+                                language_system: usize,
+                                feature_list: usize,
+                                features: &mut Vec<FontTableTag>)
+                                -> bool {
+    if language_system + 6 > table.len() {
+        return false
+    }
+    if read_u16(table, language_system + 2) != 0xFFFF {
+        return false
+    }
+    let feature_count = read_u16(table, feature_list) as usize;
+    let feature_index_count = read_u16(table, language_system + 4) as usize;
+    if language_system + 6 + feature_index_count * 2 > table.len() {
+        return false
+    }
+    for index in 0..feature_index_count {
+        let feature_index = read_u16(table, language_system + 6 + index * 2) as usize;
+        if feature_index >= feature_count {
+            return false
+        }
+        let record = feature_list + 2 + feature_index * 6;
+        if record + 6 > table.len() {
+            return false
+        }
+        let feature = feature_list + read_u16(table, record + 4) as usize;
+        if feature + 4 > table.len() {
+            return false
+        }
+        let lookup_count = read_u16(table, feature + 2);
+        let tag = read_u32(table, record);
+        if lookup_count != 0 && !features.contains(&tag) {
+            features.push(tag)
+        }
+    }
+    true
+}
+
+impl Shaper for SimpleShaper {
+    fn set_options(&mut self, options: &ShapingOptions) {
+        self.harfbuzz.set_options(options)
+    }
+
+    fn shape_text(&self, text: &str, options: &ShapingOptions, glyphs: &mut GlyphStore) {
+        let features = enabled_features(options);
+        let glyph_ids = match self.simple_glyphs(text, options, &features) {
+            Some(glyph_ids) => glyph_ids,
+            None => return self.harfbuzz.shape_text(text, options, glyphs),
+        };
+        let kern = features.contains(&KERN) && !self.has_gpos;
+
+        let font = unsafe {
+            &mut *self.font
+        };
+        for (index, (character, &glyph_id)) in text.chars().zip(glyph_ids.iter()).enumerate() {
+            // Kerning moves the glyph after a pair, as HarfBuzz does with the `kern` table of
+            // fonts without `GPOS`.
+            let mut advance = font.glyph_h_advance(glyph_id);
+            if kern {
+                if let Some(&next_glyph_id) = glyph_ids.get(index + 1) {
+                    advance += font.glyph_h_kerning(glyph_id, next_glyph_id)
+                }
+            }
+            let data = GlyphData::new(glyph_id, Au::from_f64_px(advance), None, false, true, true);
+            glyphs.add_glyph_for_char_index(CharIndex(index as isize), Some(character), &data);
+        }
+        glyphs.finalize_changes();
+    }
+}
@@ -3,15 +3,18 @@
  * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
 
 use font::{Font, FontHandleMethods, FontMetrics, IS_WHITESPACE_SHAPING_FLAG, RunMetrics};
-use font::{ShapingOptions};
+use font::{ShapingOptions, TextRunCacheEntry, VERTICAL_SHAPING_FLAG};
 use platform::font_template::FontTemplateData;
 use util::geometry::Au;
 use util::range::Range;
 use util::vec::{Comparator, FullBinarySearchMethods};
-use std::cmp::{Ordering, max};
+use std::cmp::{Ordering, max, min};
 use std::slice::Iter;
 use std::sync::Arc;
 use text::glyph::{CharIndex, GlyphStore};
+use text::hyphenation::HyphenationMode;
+use text::line_break::{LineBreakClass, LineBreakIterator, line_break_class};
+use text::util::{is_attached_mark, is_combining_char};
 
 /// A single "paragraph" of text in one font size and style.
 #[derive(Clone)]
@@ -21,21 +24,45 @@ pub struct TextRun {
     pub font_template: Arc<FontTemplateData>,
     pub actual_pt_size: Au,
     pub font_metrics: FontMetrics,
+    /// The advance of the hyphen that is drawn where a line breaks at a hyphenation point.
+    pub hyphen_advance: Au,
+    /// Whether the glyphs are upright in vertical text, so that their advances go down the page.
+    pub vertical: bool,
+    /// The options the text was shaped with, which a word is shaped with again when a line
+    /// breaks at one of its hyphenation points.
+    pub options: ShapingOptions,
     /// The glyph runs that make up this text run.
     pub glyphs: Arc<Vec<GlyphRun>>,
+    /// The number of combining marks at the start of the text, which are drawn on the last
+    /// character of the run before this one, as happens when they fell back to another font.
+    pub leading_marks: CharIndex,
+    /// The advance of the glyphs that the leading marks are drawn on, which layout sets from the
+    /// run before this one so that they are centered over them.
+    pub base_advance: Au,
 }
 
 /// A single series of glyphs within a text run.
-#[derive(Clone)]
+#[derive(Clone, HeapSizeOf, RustcEncodable, RustcDecodable)]
 pub struct GlyphRun {
     /// The glyphs.
+    #[ignore_heap_size_of = "Shared with the shaped run cache, which measures it"]
     pub glyph_store: Arc<GlyphStore>,
     /// The range of characters in the containing run.
     pub range: Range<CharIndex>,
+    /// The indices of the characters in the glyph store before which a line may break at a
+    /// hyphenation point, drawing a hyphen, in order. A soft hyphen at the end of the glyph run
+    /// puts its length here.
+    pub hyphenation_points: Vec<CharIndex>,
+    /// The indices of the characters in the glyph store that extra space goes after when the text
+    /// is justified, in order.
+    pub justification_opportunities: Vec<CharIndex>,
 }
 
 pub struct NaturalWordSliceIterator<'a> {
     glyph_iter: Iter<'a, GlyphRun>,
+    /// The glyph run that is being sliced at its hyphenation points, and the index in its glyph
+    /// store that the next slice starts at.
+    glyph_run: Option<(&'a GlyphRun, CharIndex)>,
     range: Range<CharIndex>,
 }
 
@@ -80,24 +107,43 @@ impl<'a> Iterator for NaturalWordSliceIterator<'a> {
     // inline(always) due to the inefficient rt failures messing up inline heuristics, I think.
     #[inline(always)]
     fn next(&mut self) -> Option<TextRunSlice<'a>> {
-        let slice_glyphs = self.glyph_iter.next();
-        if slice_glyphs.is_none() {
-            return None;
-        }
-        let slice_glyphs = slice_glyphs.unwrap();
+        loop {
+            let (slice_glyphs, start) = match self.glyph_run.take() {
+                Some(glyph_run) => glyph_run,
+                None => match self.glyph_iter.next() {
+                    Some(slice_glyphs) => (slice_glyphs, CharIndex(0)),
+                    None => return None,
+                },
+            };
 
-        let mut char_range = self.range.intersect(&slice_glyphs.range);
-        let slice_range_begin = slice_glyphs.range.begin();
-        char_range.shift_by(-slice_range_begin);
+            // Words are shaped whole, but lines can also break at their hyphenation points.
+            let length = slice_glyphs.range.length();
+            let end = match slice_glyphs.hyphenation_points.iter().find(|&&point| {
+                point > start && point < length
+            }) {
+                Some(&point) => {
+                    self.glyph_run = Some((slice_glyphs, point));
+                    point
+                }
+                None => length,
+            };
 
-        if !char_range.is_empty() {
-            Some(TextRunSlice {
+            let slice_range_begin = slice_glyphs.range.begin();
+            let slice_range = Range::new(slice_range_begin + start, end - start);
+            if slice_range.begin() >= self.range.end() {
+                return None
+            }
+            let mut char_range = self.range.intersect(&slice_range);
+            if char_range.is_empty() {
+                continue
+            }
+            char_range.shift_by(-slice_range_begin);
+
+            return Some(TextRunSlice {
                 glyphs: &*slice_glyphs.glyph_store,
                 offset: slice_range_begin,
                 range: char_range,
             })
-        } else {
-            None
         }
     }
 }
@@ -120,8 +166,17 @@ impl<'a> Iterator for CharacterSliceIterator<'a> {
         };
 
         debug_assert!(!self.range.is_empty());
-        let index_to_return = self.range.begin();
-        self.range.adjust_by(CharIndex(1), CharIndex(-1));
+        let index_within_glyph_run = self.range.begin() - glyph_run.range.begin();
+
+        // Take the whole grapheme cluster, so that it is never split.
+        let mut length = CharIndex(1);
+        while length < self.range.length() &&
+                index_within_glyph_run + length < glyph_run.range.length() &&
+                !glyph_run.glyph_store.is_grapheme_cluster_start(index_within_glyph_run + length) {
+            length = length + CharIndex(1)
+        }
+
+        self.range.adjust_by(length, CharIndex(0) - length);
         if self.range.is_empty() {
             // We're done.
             self.glyph_run = None
@@ -130,11 +185,10 @@ impl<'a> Iterator for CharacterSliceIterator<'a> {
             self.glyph_run = self.glyph_run_iter.next();
         }
 
-        let index_within_glyph_run = index_to_return - glyph_run.range.begin();
         Some(TextRunSlice {
             glyphs: &*glyph_run.glyph_store,
             offset: glyph_run.range.begin(),
-            range: Range::new(index_within_glyph_run, CharIndex(1)),
+            range: Range::new(index_within_glyph_run, length),
         })
     }
 }
@@ -186,88 +240,165 @@ impl<'a> Iterator for LineIterator<'a> {
 }
 
 impl<'a> TextRun {
-    pub fn new(font: &mut Font, text: String, options: &ShapingOptions) -> TextRun {
-        let glyphs = TextRun::break_and_shape(font, &text, options);
+    /// Makes a text run of `text` in `font`. If the text was `edited` since it was last laid out,
+    /// the glyph runs of the words around the edit are taken from the recent run of its previous
+    /// text instead of being made again.
+    pub fn new(font: &mut Font,
+               text: String,
+               options: &ShapingOptions,
+               hyphenation: HyphenationMode<'static>,
+               edited: bool)
+               -> TextRun {
+        let cache_entry = TextRunCacheEntry {
+            text: text.clone(),
+            options: options.clone(),
+            hyphenation: hyphenation,
+        };
+        let glyphs = match font.text_run_cache.find(&cache_entry) {
+            Some(glyphs) => glyphs,
+            None => {
+                // The previous text of an edited node is likely to be among the recent runs in
+                // this font, so only the words around the edit need to be shaped again. Runs
+                // that have less than half of the new text in common with it aren't worth
+                // breaking into words again.
+                let mut previous = None;
+                if edited {
+                    let mut most_unchanged = max(text.len() / 2, 1);
+                    for &(ref entry, ref glyphs) in font.text_run_cache.iter() {
+                        if entry.options != *options || entry.hyphenation != hyphenation {
+                            continue
+                        }
+                        let (prefix, suffix) = common_prefix_and_suffix(&entry.text, &text);
+                        if prefix + suffix >= most_unchanged {
+                            most_unchanged = prefix + suffix + 1;
+                            previous = Some((entry.text.clone(), glyphs.clone()))
+                        }
+                    }
+                }
+                let previous = previous.as_ref().map(|&(ref previous_text, ref previous_glyphs)| {
+                    (&previous_text[..], &previous_glyphs[..])
+                });
+                let glyphs = Arc::new(TextRun::break_and_shape(font,
+                                                               &text,
+                                                               options,
+                                                               hyphenation,
+                                                               previous));
+                font.text_run_cache.insert(cache_entry, glyphs.clone());
+                glyphs
+            }
+        };
+        let hyphen_advance = match font.glyph_index('-') {
+            Some(glyph) => Au::from_f64_px(font.glyph_h_advance(glyph)),
+            None => Au(0),
+        };
+        let leading_marks = text.chars().take_while(|&ch| is_attached_mark(ch)).count();
         let run = TextRun {
             text: Arc::new(text),
             font_metrics: font.metrics.clone(),
             font_template: font.handle.template(),
             actual_pt_size: font.actual_pt_size,
-            glyphs: Arc::new(glyphs),
+            hyphen_advance: hyphen_advance,
+            vertical: options.flags.contains(VERTICAL_SHAPING_FLAG),
+            options: options.clone(),
+            glyphs: glyphs,
+            leading_marks: CharIndex(leading_marks as isize),
+            base_advance: Au(0),
         };
         return run;
     }
 
-    pub fn break_and_shape(font: &mut Font, text: &str, options: &ShapingOptions)
+    /// Splits the text into glyph runs at the places where lines may break, with the whitespace
+    /// around those places in runs of its own so that it can be trimmed from the ends of lines,
+    /// and shapes each run. Words are shaped whole, with their hyphenation points and any soft
+    /// hyphen they end with recorded, unless `hyphenation` is `None`.
+    ///
+    /// If `previous` has the text and glyph runs of an earlier run with the same options, such
+    /// as the text of a node before it was edited, the glyph runs of the words that the change
+    /// didn't touch are taken from it instead of being shaped again.
+    pub fn break_and_shape(font: &mut Font,
+                           text: &str,
+                           options: &ShapingOptions,
+                           hyphenation: HyphenationMode,
+                           previous: Option<(&str, &[GlyphRun])>)
                            -> Vec<GlyphRun> {
-        // TODO(Issue #230): do a better job. See Gecko's LineBreaker.
+        let unchanged_text = previous.map(|(previous_text, previous_glyphs)| {
+            UnchangedText::new(previous_text, previous_glyphs, text)
+        });
+
         let mut glyphs = vec!();
-        let (mut byte_i, mut char_i) = (0, CharIndex(0));
-        let mut cur_slice_is_whitespace = false;
-        let (mut byte_last_boundary, mut char_last_boundary) = (0, CharIndex(0));
-        while byte_i < text.len() {
-            let range = text.char_range_at(byte_i);
-            let ch = range.ch;
-            let next = range.next;
-
-            // Slices alternate between whitespace and non-whitespace,
-            // representing line break opportunities.
-            let can_break_before = if cur_slice_is_whitespace {
-                match ch {
-                    ' ' | '\t' | '\n' => false,
-                    _ => {
-                        cur_slice_is_whitespace = false;
-                        true
+        let mut char_last_boundary = CharIndex(0);
+        let mut byte_last_boundary = 0;
+        let breaks = LineBreakIterator::new(text).chain(Some(text.len()).into_iter());
+        for byte_break in breaks {
+            if let Some(ref unchanged_text) = unchanged_text {
+                let previous_glyph_runs =
+                    unchanged_text.glyph_runs_for_segment(byte_last_boundary, byte_break);
+                if let Some(previous_glyph_runs) = previous_glyph_runs {
+                    for previous_glyph_run in previous_glyph_runs.iter() {
+                        let mut glyph_run = previous_glyph_run.clone();
+                        glyph_run.range = Range::new(char_last_boundary,
+                                                     previous_glyph_run.range.length());
+                        char_last_boundary = glyph_run.range.end();
+                        glyphs.push(glyph_run);
                     }
+                    byte_last_boundary = byte_break;
+                    continue
                 }
-            } else {
-                match ch {
-                    ' ' | '\t' | '\n' => {
-                        cur_slice_is_whitespace = true;
-                        true
-                    },
-                    _ => false
-                }
+            }
+
+            let segment = &text[byte_last_boundary..byte_break];
+            let body_start = segment.len() - segment.trim_left_matches(is_trimmable).len();
+            let body_end = segment.trim_right_matches(is_trimmable).len();
+            let body_end = max(body_start, body_end);
+
+            let body = &segment[body_start..body_end];
+            let mut body_hyphenation_points =
+                hyphenation_points(body, hyphenation).into_iter().map(|point| {
+                    CharIndex(body[..point].chars().count() as isize)
+                }).collect::<Vec<_>>();
+            let ends_with_soft_hyphen = match hyphenation {
+                HyphenationMode::None => false,
+                HyphenationMode::Manual | HyphenationMode::Auto(_) => body.ends_with('\u{AD}'),
             };
+            if ends_with_soft_hyphen {
+                body_hyphenation_points.push(CharIndex(body.chars().count() as isize))
+            }
 
-            // Create a glyph store for this slice if it's nonempty.
-            if can_break_before && byte_i > byte_last_boundary {
-                let slice = &text[byte_last_boundary .. byte_i];
+            let pieces = [
+                (0, body_start, true, vec!()),
+                (body_start, body_end, false, body_hyphenation_points),
+                (body_end, segment.len(), true, vec!()),
+            ];
+            for &(start, end, is_whitespace, ref hyphenation_points) in pieces.iter() {
+                if start == end {
+                    continue
+                }
+                let slice = &segment[start..end];
                 debug!("creating glyph store for slice {} (ws? {}), {} - {} in run {}",
-                        slice, !cur_slice_is_whitespace, byte_last_boundary, byte_i, text);
-
-                let mut options = *options;
-                if !cur_slice_is_whitespace {
+                       slice,
+                       is_whitespace,
+                       byte_last_boundary + start,
+                       byte_last_boundary + end,
+                       text);
+
+                let mut options = options.clone();
+                if is_whitespace {
                     options.flags.insert(IS_WHITESPACE_SHAPING_FLAG);
                 }
 
+                let glyph_store = font.shape_text(slice, &options);
+                let next_character = text[(byte_last_boundary + end)..].chars().next();
+                let char_length = CharIndex(slice.chars().count() as isize);
                 glyphs.push(GlyphRun {
-                    glyph_store: font.shape_text(slice, &options),
-                    range: Range::new(char_last_boundary, char_i - char_last_boundary),
+                    glyph_store: glyph_store,
+                    range: Range::new(char_last_boundary, char_length),
+                    hyphenation_points: hyphenation_points.clone(),
+                    justification_opportunities: justification_opportunities(slice,
+                                                                             next_character),
                 });
-                byte_last_boundary = byte_i;
-                char_last_boundary = char_i;
+                char_last_boundary = char_last_boundary + char_length;
             }
-
-            byte_i = next;
-            char_i = char_i + CharIndex(1);
-        }
-
-        // Create a glyph store for the final slice if it's nonempty.
-        if byte_i > byte_last_boundary {
-            let slice = &text[byte_last_boundary..];
-            debug!("creating glyph store for final slice {} (ws? {}), {} - {} in run {}",
-                slice, cur_slice_is_whitespace, byte_last_boundary, text.len(), text);
-
-            let mut options = *options;
-            if cur_slice_is_whitespace {
-                options.flags.insert(IS_WHITESPACE_SHAPING_FLAG);
-            }
-
-            glyphs.push(GlyphRun {
-                glyph_store: font.shape_text(slice, &options),
-                range: Range::new(char_last_boundary, char_i - char_last_boundary),
-            });
+            byte_last_boundary = byte_break;
         }
 
         glyphs
@@ -284,6 +415,91 @@ impl<'a> TextRun {
         &*self.glyphs
     }
 
+    /// Returns true if a hyphen is drawn before the character at `index` when a line breaks
+    /// there.
+    pub fn is_hyphenation_point(&self, index: CharIndex) -> bool {
+        if index == CharIndex(0) {
+            return false
+        }
+        match self.first_glyph_run_containing(index - CharIndex(1)) {
+            Some(glyph_run) => {
+                let index = index - glyph_run.range.begin();
+                glyph_run.hyphenation_points.binary_search(&index).is_ok()
+            }
+            None => false,
+        }
+    }
+
+    /// Returns a copy of this run in which the word that has a hyphenation point at `index` is
+    /// shaped again as two glyph runs, one on each side of it, because a line breaks there.
+    pub fn split_word_at_hyphenation_point(&self, font: &mut Font, index: CharIndex) -> TextRun {
+        let glyph_run_index = match self.index_of_first_glyph_run_containing(index) {
+            Some(glyph_run_index) if self.glyphs[glyph_run_index].range.begin() < index => {
+                glyph_run_index
+            }
+            _ => return self.clone(),
+        };
+        let byte_offset = |index: CharIndex| {
+            self.text.char_indices().nth(index.to_usize()).map_or(self.text.len(), |(offset, _)| {
+                offset
+            })
+        };
+
+        let word = &self.glyphs[glyph_run_index];
+        let mut glyphs = Vec::with_capacity(self.glyphs.len() + 1);
+        glyphs.extend(self.glyphs[..glyph_run_index].iter().cloned());
+        for range in [Range::new(word.range.begin(), index - word.range.begin()),
+                      Range::new(index, word.range.end() - index)].iter() {
+            let slice = &self.text[byte_offset(range.begin())..byte_offset(range.end())];
+            let start = range.begin() - word.range.begin();
+            let hyphenation_points = word.hyphenation_points.iter().filter(|&&point| {
+                point > start && point <= start + range.length()
+            }).map(|&point| point - start).collect();
+            let next_character = self.text[byte_offset(range.end())..].chars().next();
+            glyphs.push(GlyphRun {
+                glyph_store: font.shape_text(slice, &self.options),
+                range: *range,
+                hyphenation_points: hyphenation_points,
+                justification_opportunities: justification_opportunities(slice, next_character),
+            })
+        }
+        glyphs.extend(self.glyphs[(glyph_run_index + 1)..].iter().cloned());
+        TextRun {
+            glyphs: Arc::new(glyphs),
+            ..self.clone()
+        }
+    }
+
+    /// Returns the number of justification opportunities in `range`.
+    pub fn justification_opportunity_count(&self, range: &Range<CharIndex>) -> u32 {
+        let index = match self.index_of_first_glyph_run_containing(range.begin()) {
+            None => return 0,
+            Some(index) => index,
+        };
+        let mut count = 0;
+        for glyph_run in self.glyphs[index..].iter() {
+            if glyph_run.range.begin() >= range.end() {
+                break
+            }
+            count += glyph_run.justification_opportunities.iter().filter(|&&index| {
+                range.contains(glyph_run.range.begin() + index)
+            }).count() as u32
+        }
+        count
+    }
+
+    /// Returns true if extra space goes after the character at `index` when the text is
+    /// justified.
+    pub fn is_justification_opportunity(&self, index: CharIndex) -> bool {
+        match self.first_glyph_run_containing(index) {
+            Some(glyph_run) => {
+                let index = index - glyph_run.range.begin();
+                glyph_run.justification_opportunities.binary_search(&index).is_ok()
+            }
+            None => false,
+        }
+    }
+
     pub fn range_is_trimmable_whitespace(&self, range: &Range<CharIndex>) -> bool {
         self.natural_word_slices_in_range(range).all(|slice| slice.glyphs.is_whitespace())
     }
@@ -305,6 +521,23 @@ impl<'a> TextRun {
             })
     }
 
+    /// Returns the advance of the last grapheme cluster, which the leading marks of the run after
+    /// this one are drawn on.
+    pub fn last_grapheme_cluster_advance(&self) -> Au {
+        let glyph_store = match self.glyphs.last() {
+            None => return Au(0),
+            Some(glyph_run) => &glyph_run.glyph_store,
+        };
+        let mut start = glyph_store.char_len();
+        while start > CharIndex(0) {
+            start = start - CharIndex(1);
+            if glyph_store.is_grapheme_cluster_start(start) {
+                break
+            }
+        }
+        glyph_store.advance_for_char_range(&Range::new(start, glyph_store.char_len() - start))
+    }
+
     pub fn metrics_for_range(&self, range: &Range<CharIndex>) -> RunMetrics {
         RunMetrics::new(self.advance_for_range(range),
                         self.font_metrics.ascent,
@@ -346,12 +579,13 @@ impl<'a> TextRun {
         };
         NaturalWordSliceIterator {
             glyph_iter: self.glyphs[index..].iter(),
+            glyph_run: None,
             range: *range,
         }
     }
 
     /// Returns an iterator that will iterate over all slices of glyphs that represent individual
-    /// characters in the given range.
+    /// grapheme clusters in the given range.
     pub fn character_slices_in_range(&'a self, range: &Range<CharIndex>)
                                      -> CharacterSliceIterator<'a> {
         let index = match self.index_of_first_glyph_run_containing(range.begin()) {
@@ -375,3 +609,159 @@ impl<'a> TextRun {
         }
     }
 }
+
+/// The parts of the previous text of a text run that are still in its new text, whose glyph runs
+/// don't need to be shaped again.
+pub struct UnchangedText<'b> {
+    /// The byte offset in the new text that the changed part of it starts at.
+    dirty_start: usize,
+    /// The byte offset in the new text that the changed part of it ends at.
+    dirty_end: usize,
+    /// How many bytes longer the previous text is than the new text.
+    length_difference: isize,
+    /// The byte offsets in the previous text where its lines may break, with its start and end,
+    /// and the character index of each.
+    previous_breaks: Vec<(usize, CharIndex)>,
+    previous_glyphs: &'b [GlyphRun],
+}
+
+impl<'b> UnchangedText<'b> {
+    pub fn new(previous_text: &str, previous_glyphs: &'b [GlyphRun], text: &str)
+               -> UnchangedText<'b> {
+        let (prefix, suffix) = common_prefix_and_suffix(previous_text, text);
+        let mut previous_breaks = vec!((0, CharIndex(0)));
+        let mut char_break = 0;
+        let mut byte_last_break = 0;
+        let breaks = LineBreakIterator::new(previous_text)
+            .chain(Some(previous_text.len()).into_iter());
+        for byte_break in breaks {
+            char_break += previous_text[byte_last_break..byte_break].chars().count();
+            byte_last_break = byte_break;
+            previous_breaks.push((byte_break, CharIndex(char_break as isize)));
+        }
+        UnchangedText {
+            dirty_start: prefix,
+            dirty_end: text.len() - suffix,
+            length_difference: previous_text.len() as isize - text.len() as isize,
+            previous_breaks: previous_breaks,
+            previous_glyphs: previous_glyphs,
+        }
+    }
+
+    /// Returns the glyph runs of the previous text for the text between the line break
+    /// opportunities `start` and `end` of the new text, if it is unchanged and the previous text
+    /// breaks at the same places around it. Text that is followed by the change is shaped again,
+    /// since where it may break and how it is justified depend on the character after it.
+    pub fn glyph_runs_for_segment(&self, start: usize, end: usize) -> Option<&'b [GlyphRun]> {
+        let (previous_start, previous_end) = if end < self.dirty_start {
+            (start, end)
+        } else if start >= self.dirty_end {
+            ((start as isize + self.length_difference) as usize,
+             (end as isize + self.length_difference) as usize)
+        } else {
+            return None
+        };
+
+        let index = match self.previous_breaks.binary_search_by(|&(offset, _)| {
+            offset.cmp(&previous_start)
+        }) {
+            Ok(index) => index,
+            Err(_) => return None,
+        };
+        let (char_start, char_end) = match self.previous_breaks.get(index + 1) {
+            Some(&(offset, char_end)) if offset == previous_end => {
+                (self.previous_breaks[index].1, char_end)
+            }
+            _ => return None,
+        };
+
+        let first = match self.previous_glyphs.binary_search_by(|glyph_run| {
+            glyph_run.range.begin().cmp(&char_start)
+        }) {
+            Ok(first) => first,
+            Err(_) => return None,
+        };
+        let count = self.previous_glyphs[first..].iter().take_while(|glyph_run| {
+            glyph_run.range.end() <= char_end
+        }).count();
+        Some(&self.previous_glyphs[first..(first + count)])
+    }
+}
+
+/// Returns the lengths in bytes of the longest start and end that `a` and `b` have in common,
+/// which don't overlap in either.
+pub fn common_prefix_and_suffix(a: &str, b: &str) -> (usize, usize) {
+    let prefix = a.char_indices()
+                  .zip(b.chars())
+                  .find(|&((_, a_char), b_char)| a_char != b_char)
+                  .map(|((offset, _), _)| offset)
+                  .unwrap_or(min(a.len(), b.len()));
+    let suffix = a[prefix..].chars().rev()
+                            .zip(b[prefix..].chars().rev())
+                            .take_while(|&(a_char, b_char)| a_char == b_char)
+                            .fold(0, |length, (a_char, _)| length + a_char.len_utf8());
+    (prefix, suffix)
+}
+
+/// Returns true if `ch` is whitespace that is trimmed from the ends of lines.
+fn is_trimmable(ch: char) -> bool {
+    match ch {
+        ' ' | '\t' | '\n' => true,
+        _ => false,
+    }
+}
+
+/// Returns the byte offsets in `text` before which its words can be hyphenated, in order. Words
+/// are runs of letters.
+fn hyphenation_points(text: &str, hyphenation: HyphenationMode) -> Vec<usize> {
+    let hyphenator = match hyphenation {
+        HyphenationMode::Auto(hyphenator) => hyphenator,
+        HyphenationMode::None | HyphenationMode::Manual => return vec!(),
+    };
+
+    let mut points = vec!();
+    let mut word_start = None;
+    for (offset, character) in text.char_indices().chain(Some((text.len(), ' ')).into_iter()) {
+        match (character.is_alphabetic(), word_start) {
+            (true, None) => word_start = Some(offset),
+            (false, Some(start)) => {
+                let word = &text[start..offset];
+                let offsets = word.char_indices().map(|(offset, _)| offset).collect::<Vec<_>>();
+                points.extend(hyphenator.hyphenation_points(word).into_iter().map(|index| {
+                    start + offsets[index]
+                }));
+                word_start = None
+            }
+            (true, Some(_)) | (false, None) => {}
+        }
+    }
+    points
+}
+
+/// Returns the indices of the characters in `text`, which `next_character` follows, that extra
+/// space goes after when the text is justified: word separators, and the places between
+/// characters where CJK text is spaced out.
+pub fn justification_opportunities(text: &str, next_character: Option<char>) -> Vec<CharIndex> {
+    let characters = text.chars().chain(next_character.into_iter()).collect::<Vec<_>>();
+    let length = text.chars().count();
+    (0..length).filter(|&index| {
+        let character = characters[index];
+        match characters.get(index + 1) {
+            _ if character == ' ' || character == '\u{A0}' => true,
+            Some(&next) => {
+                !next.is_whitespace() && !is_combining_char(next) &&
+                    (is_cjk(character) || is_cjk(next))
+            }
+            None => false,
+        }
+    }).map(|index| CharIndex(index as isize)).collect()
+}
+
+/// Returns true if `ch` is an ideograph, kana or Hangul syllable, between which CJK text is
+/// spaced out when it is justified.
+fn is_cjk(ch: char) -> bool {
+    match line_break_class(ch) {
+        LineBreakClass::ID | LineBreakClass::NS => true,
+        _ => false,
+    }
+}
@@ -2,6 +2,10 @@
  * License, v. 2.0. If a copy of the MPL was not distributed with this
  * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
 
+use std::ascii::AsciiExt;
+
+pub use util::str::is_combining_char;
+
 #[derive(PartialEq, Eq, Copy, Clone)]
 pub enum CompressionMode {
     CompressNone,
@@ -17,25 +21,27 @@ pub enum CompressionMode {
 // * Issue #113: consider incoming text state (arabic, etc)
 //               and propagate outgoing text state (dual of above)
 //
-// * Issue #114: record skipped and kept chars for mapping original to new text
-//
 // * Untracked: various edge cases for bidi, CJK, etc.
+//
+// The index of the character of `text` that each character pushed onto `output_text` comes from
+// is pushed onto `original_indices`, so that positions in the new text can be mapped back to the
+// original one. A space that collapsed whitespace is replaced with comes from the first character
+// of that whitespace.
 pub fn transform_text(text: &str,
                       mode: CompressionMode,
                       incoming_whitespace: bool,
-                      output_text: &mut String)
+                      output_text: &mut String,
+                      original_indices: &mut Vec<usize>)
                       -> bool {
     let out_whitespace = match mode {
         CompressionMode::CompressNone | CompressionMode::DiscardNewline => {
-            for ch in text.chars() {
-                if is_discardable_char(ch, mode) {
-                    // TODO: record skipped char
-                } else {
-                    // TODO: record kept char
+            for (index, ch) in text.chars().enumerate() {
+                if !is_discardable_char(ch, mode) {
                     if ch == '\t' {
                         // TODO: set "has tab" flag
                     }
                     output_text.push(ch);
+                    original_indices.push(index);
                 }
             }
             text.len() > 0 && is_in_whitespace(text.char_at_reverse(0), mode)
@@ -43,7 +49,7 @@ pub fn transform_text(text: &str,
 
         CompressionMode::CompressWhitespace | CompressionMode::CompressWhitespaceNewline => {
             let mut in_whitespace: bool = incoming_whitespace;
-            for ch in text.chars() {
+            for (index, ch) in text.chars().enumerate() {
                 // TODO: discard newlines between CJK chars
                 let mut next_in_whitespace: bool = is_in_whitespace(ch, mode);
 
@@ -51,17 +57,14 @@ pub fn transform_text(text: &str,
                     if is_always_discardable_char(ch) {
                         // revert whitespace setting, since this char was discarded
                         next_in_whitespace = in_whitespace;
-                        // TODO: record skipped char
                     } else {
-                        // TODO: record kept char
                         output_text.push(ch);
+                        original_indices.push(index);
                     }
                 } else { /* next_in_whitespace; possibly add a space char */
-                    if in_whitespace {
-                        // TODO: record skipped char
-                    } else {
-                        // TODO: record kept char
+                    if !in_whitespace {
                         output_text.push(' ');
+                        original_indices.push(index);
                     }
                 }
                 // save whitespace context for next char
@@ -98,6 +101,75 @@ pub fn transform_text(text: &str,
     }
 }
 
+/// The forms of the Han characters that Chinese, Japanese and Korean share, which differ enough
+/// between the languages that fonts for the language of the text are preferred.
+#[derive(PartialEq, Eq, Copy, Clone, Debug, RustcEncodable, RustcDecodable)]
+pub enum HanVariant {
+    Japanese,
+    Korean,
+    SimplifiedChinese,
+    TraditionalChinese,
+}
+
+impl HanVariant {
+    /// Returns the forms of Han characters that text in the language of `lang`, a BCP 47 tag,
+    /// is written with, or `None` if the language isn't written with them.
+    pub fn from_lang(lang: &str) -> Option<HanVariant> {
+        let lang = lang.to_ascii_lowercase();
+        let mut subtags = lang.split(|c| c == '-' || c == '_');
+        match subtags.next() {
+            Some("ja") => Some(HanVariant::Japanese),
+            Some("ko") => Some(HanVariant::Korean),
+            Some("zh") => {
+                let traditional = subtags.any(|subtag| {
+                    subtag == "hant" || subtag == "tw" || subtag == "hk" || subtag == "mo"
+                });
+                if traditional {
+                    Some(HanVariant::TraditionalChinese)
+                } else {
+                    Some(HanVariant::SimplifiedChinese)
+                }
+            }
+            _ => None,
+        }
+    }
+}
+
+/// Returns true if `ch` is set upright in vertical text with `text-orientation: mixed`, rather
+/// than turned sideways. These are the characters of CJK scripts and the symbols used with them,
+/// an approximation of the characters that UAX #50 gives an upright vertical orientation.
+///
+/// http://www.unicode.org/reports/tr50/
+pub fn is_upright_in_vertical_text(ch: char) -> bool {
+    match ch {
+        '\u{1100}'...'\u{11FF}' |   // Hangul Jamo
+        '\u{2E80}'...'\u{2FFF}' |   // CJK Radicals, Kangxi Radicals, Ideographic Description
+        '\u{3000}'...'\u{A4CF}' |   // CJK Symbols to Yi, including kana and ideographs
+        '\u{A960}'...'\u{A97F}' |   // Hangul Jamo Extended-A
+        '\u{AC00}'...'\u{D7FF}' |   // Hangul Syllables and Jamo Extended-B
+        '\u{E000}'...'\u{FAFF}' |   // Private Use Area and CJK Compatibility Ideographs
+        '\u{FE10}'...'\u{FE1F}' |   // Vertical Forms
+        '\u{FE30}'...'\u{FE6F}' |   // CJK Compatibility Forms and Small Form Variants
+        '\u{FF00}'...'\u{FF60}' |   // Fullwidth Forms
+        '\u{FFE0}'...'\u{FFE6}' |
+        '\u{1F000}'...'\u{1F2FF}' | // Mahjong, Domino and Playing Cards, Enclosed Ideographics
+        '\u{1F300}'...'\u{1F64F}' | // Pictographs and Emoticons
+        '\u{1F680}'...'\u{1F6FF}' | // Transport and Map Symbols
+        '\u{20000}'...'\u{2FFFD}' | // CJK Extensions B to E and Compatibility Supplement
+        '\u{30000}'...'\u{3FFFD}' => true,
+        _ => false,
+    }
+}
+
+/// Returns true if `ch` is a combining mark that is drawn on the character before it, rather than
+/// a joiner or a variation selector, which have no glyphs of their own.
+pub fn is_attached_mark(ch: char) -> bool {
+    match ch {
+        '\u{200C}'...'\u{200D}' | '\u{FE00}'...'\u{FE0F}' | '\u{E0100}'...'\u{E01EF}' => false,
+        _ => is_combining_char(ch),
+    }
+}
+
 pub fn float_to_fixed(before: usize, f: f64) -> i32 {
     ((1i32 << before) as f64 * f) as i32
 }
@@ -0,0 +1,656 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+//! Decoding of web fonts in the WOFF containers, which wrap the tables of a TrueType or
+//! OpenType font compressed. The platform font backends only read plain sfnt data, so the
+//! tables are unpacked into a font file of that form.
+//!
+//! WOFF compresses each table separately with zlib. WOFF 2.0 compresses all of them together
+//! with Brotli, and can also store the glyf, loca and hmtx tables in a transformed form that
+//! compresses better, which is turned back into the original tables here.
+//! http://www.w3.org/TR/WOFF/
+//! http://www.w3.org/TR/WOFF2/
+
+use font::FontTableTag;
+use sfnt::{self, SfntTable, push_u16, push_u32, read_i16, read_u16, read_u32, write_sfnt};
+use sfnt::write_u32;
+
+use brotli::Decompressor;
+use flate2::read::ZlibDecoder;
+use std::cmp;
+use std::io::Read;
+
+const WOFF_SIGNATURE: u32 = 0x774F4646;
+const WOFF2_SIGNATURE: u32 = 0x774F4632;
+const WOFF_HEADER_SIZE: usize = 44;
+const WOFF2_HEADER_SIZE: usize = 48;
+const WOFF_TABLE_ENTRY_SIZE: usize = 20;
+const SFNT_HEADER_SIZE: usize = 12;
+const SFNT_TABLE_ENTRY_SIZE: usize = 16;
+
+/// The flavor of a font collection, which WOFF 2.0 fonts can hold.
+const COLLECTION_FLAVOR: u32 = 0x74746366;
+
+const GLYF: FontTableTag = 0x676C7966;
+const HEAD: FontTableTag = 0x68656164;
+const HHEA: FontTableTag = 0x68686561;
+const HMTX: FontTableTag = 0x686D7478;
+const LOCA: FontTableTag = 0x6C6F6361;
+
+/// The tags that the table directory of a WOFF 2.0 font refers to by their index.
+static KNOWN_TABLE_TAGS: [&'static [u8; 4]; 63] = [
+    b"cmap", b"head", b"hhea", b"hmtx", b"maxp", b"name", b"OS/2", b"post",
+    b"cvt ", b"fpgm", b"glyf", b"loca", b"prep", b"CFF ", b"VORG", b"EBDT",
+    b"EBLC", b"gasp", b"hdmx", b"kern", b"LTSH", b"PCLT", b"VDMX", b"vhea",
+    b"vmtx", b"BASE", b"GDEF", b"GPOS", b"GSUB", b"EBSC", b"JSTF", b"MATH",
+    b"CBDT", b"CBLC", b"COLR", b"CPAL", b"SVG ", b"sbix", b"acnt", b"avar",
+    b"bdat", b"bloc", b"bsln", b"cvar", b"fdsc", b"feat", b"fmtx", b"fvar",
+    b"gvar", b"hsty", b"just", b"lcar", b"mort", b"morx", b"opbd", b"prop",
+    b"trak", b"Zapf", b"Silf", b"Glat", b"Gloc", b"Feat", b"Sill",
+];
+
+/// The index in the table directory of a WOFF 2.0 font that says the tag follows.
+const ARBITRARY_TAG_INDEX: u8 = 63;
+
+/// The flags of simple glyph points in the glyf table.
+const ON_CURVE_POINT: u8 = 0x01;
+const X_SHORT_VECTOR: u8 = 0x02;
+const Y_SHORT_VECTOR: u8 = 0x04;
+const REPEAT_FLAG: u8 = 0x08;
+const X_IS_SAME_OR_POSITIVE: u8 = 0x10;
+const Y_IS_SAME_OR_POSITIVE: u8 = 0x20;
+const OVERLAP_SIMPLE: u8 = 0x40;
+
+/// The flags of the components of composite glyphs in the glyf table.
+const ARG_1_AND_2_ARE_WORDS: u16 = 0x0001;
+const WE_HAVE_A_SCALE: u16 = 0x0008;
+const MORE_COMPONENTS: u16 = 0x0020;
+const WE_HAVE_AN_X_AND_Y_SCALE: u16 = 0x0040;
+const WE_HAVE_A_TWO_BY_TWO: u16 = 0x0080;
+const WE_HAVE_INSTRUCTIONS: u16 = 0x0100;
+
+/// The largest font that will be unpacked. Compressed tables can claim to be any size, so this
+/// keeps a small download from using up memory.
+const MAX_SFNT_SIZE: usize = 32 * 1024 * 1024;
+
+/// Returns true if `data` starts with the WOFF signature.
+pub fn is_woff(data: &[u8]) -> bool {
+    data.len() >= 4 && read_u32(data, 0) == WOFF_SIGNATURE
+}
+
+/// Returns true if `data` starts with the WOFF 2.0 signature.
+pub fn is_woff2(data: &[u8]) -> bool {
+    data.len() >= 4 && read_u32(data, 0) == WOFF2_SIGNATURE
+}
+
+/// Unpacks a WOFF font into the sfnt font it was made from.
+pub fn decode_woff(data: &[u8]) -> Result<Vec<u8>, &'static str> {
+    if data.len() < WOFF_HEADER_SIZE || !is_woff(data) {
+        return Err("not a WOFF font")
+    }
+    let flavor = read_u32(data, 4);
+    if read_u32(data, 8) as usize != data.len() {
+        return Err("wrong length")
+    }
+    let table_count = read_u16(data, 12) as usize;
+    if table_count == 0 {
+        return Err("no tables")
+    }
+    if WOFF_HEADER_SIZE + table_count * WOFF_TABLE_ENTRY_SIZE > data.len() {
+        return Err("truncated table directory")
+    }
+    // The size of the unpacked font, which the tables must fit in.
+    let total_sfnt_size = read_u32(data, 16) as usize;
+    if total_sfnt_size > MAX_SFNT_SIZE {
+        return Err("font too large")
+    }
+    let mut sfnt_size = SFNT_HEADER_SIZE + table_count * SFNT_TABLE_ENTRY_SIZE;
+
+    let mut tables = vec!();
+    for index in 0..table_count {
+        let entry = WOFF_HEADER_SIZE + index * WOFF_TABLE_ENTRY_SIZE;
+        let tag = read_u32(data, entry);
+        let start = read_u32(data, entry + 4) as usize;
+        let compressed_length = read_u32(data, entry + 8) as usize;
+        let length = read_u32(data, entry + 12) as usize;
+        let checksum = read_u32(data, entry + 16);
+        if start > data.len() || compressed_length > data.len() - start {
+            return Err("table out of bounds")
+        }
+        sfnt_size += (length + 3) & !3;
+        if sfnt_size > total_sfnt_size {
+            return Err("tables larger than the font")
+        }
+        let compressed = &data[start..start + compressed_length];
+
+        // Tables that zlib couldn't make smaller are stored as they are.
+        let table = if compressed_length == length {
+            compressed.to_vec()
+        } else if compressed_length < length {
+            // Reading one byte more than the table's length finds tables that decompress to
+            // more than it, without decompressing all of them.
+            let mut table = Vec::with_capacity(length);
+            let mut decoder = ZlibDecoder::new(compressed).take(length as u64 + 1);
+            if decoder.read_to_end(&mut table).is_err() {
+                return Err("bad compressed table")
+            }
+            if table.len() != length {
+                return Err("wrong decompressed table length")
+            }
+            table
+        } else {
+            return Err("compressed table larger than the original")
+        };
+
+        tables.push(SfntTable {
+            tag: tag,
+            checksum: checksum,
+            data: table,
+        });
+    }
+    Ok(write_sfnt(flavor, &tables))
+}
+
+/// An entry of the table directory of a WOFF 2.0 font.
+struct Woff2TableEntry {
+    tag: FontTableTag,
+    /// Whether the table is stored in its transformed form.
+    transformed: bool,
+    /// The length of the table in the original font.
+    original_length: usize,
+    /// The length of the table in the decompressed data.
+    length: usize,
+}
+
+/// Unpacks a WOFF 2.0 font into the sfnt font it was made from. Font collections aren't
+/// supported.
+pub fn decode_woff2(data: &[u8]) -> Result<Vec<u8>, &'static str> {
+    if data.len() < WOFF2_HEADER_SIZE || !is_woff2(data) {
+        return Err("not a WOFF 2.0 font")
+    }
+    let flavor = read_u32(data, 4);
+    if flavor == COLLECTION_FLAVOR {
+        return Err("font collections aren't supported")
+    }
+    if read_u32(data, 8) as usize != data.len() {
+        return Err("wrong length")
+    }
+    let table_count = read_u16(data, 12) as usize;
+    if table_count == 0 {
+        return Err("no tables")
+    }
+    let compressed_length = read_u32(data, 20) as usize;
+
+    let mut directory = Stream::new(&data[WOFF2_HEADER_SIZE..]);
+    let mut entries = vec!();
+    let mut decompressed_length = 0;
+    for _ in 0..table_count {
+        let flags = try!(directory.read_u8());
+        let tag_index = flags & 0x3F;
+        let tag = if tag_index == ARBITRARY_TAG_INDEX {
+            try!(directory.read_u32())
+        } else {
+            read_u32(KNOWN_TABLE_TAGS[tag_index as usize], 0)
+        };
+        let transform_version = flags >> 6;
+        let original_length = try!(directory.read_base128()) as usize;
+        // The glyf and loca tables are transformed unless their transform version is 3, and
+        // the others are if it isn't 0.
+        let transformed = match (tag, transform_version) {
+            (GLYF, 0) | (LOCA, 0) | (HMTX, 1) => true,
+            (GLYF, 3) | (LOCA, 3) | (_, 0) => false,
+            _ => return Err("unknown table transform"),
+        };
+        let length = if transformed {
+            try!(directory.read_base128()) as usize
+        } else {
+            original_length
+        };
+        if length > MAX_SFNT_SIZE - decompressed_length {
+            return Err("font too large")
+        }
+        decompressed_length += length;
+        entries.push(Woff2TableEntry {
+            tag: tag,
+            transformed: transformed,
+            original_length: original_length,
+            length: length,
+        });
+    }
+
+    let compressed_start = WOFF2_HEADER_SIZE + directory.offset;
+    if compressed_length > data.len() - compressed_start {
+        return Err("truncated font data")
+    }
+    let compressed = &data[compressed_start..compressed_start + compressed_length];
+    // As with WOFF, reading one byte more than expected finds data that decompresses to more.
+    let mut decompressed = Vec::with_capacity(decompressed_length);
+    let mut decoder = Decompressor::new(compressed).take(decompressed_length as u64 + 1);
+    if decoder.read_to_end(&mut decompressed).is_err() {
+        return Err("bad compressed font data")
+    }
+    if decompressed.len() != decompressed_length {
+        return Err("wrong decompressed font data length")
+    }
+
+    let mut offset = 0;
+    let mut table_data = vec!();
+    for entry in entries.iter() {
+        table_data.push(&decompressed[offset..offset + entry.length]);
+        offset += entry.length;
+    }
+    let find_entry = |tag: FontTableTag| entries.iter().position(|entry| entry.tag == tag);
+
+    let mut tables = vec!();
+    let mut glyph_x_mins = None;
+    match (find_entry(GLYF), find_entry(LOCA)) {
+        (Some(glyf), Some(loca)) if entries[glyf].transformed || entries[loca].transformed => {
+            if !entries[glyf].transformed || !entries[loca].transformed ||
+                    entries[loca].length != 0 {
+                return Err("only one of glyf and loca is transformed")
+            }
+            let (glyf_table, loca_table, x_mins) = try!(reconstruct_glyf(table_data[glyf]));
+            if loca_table.len() != entries[loca].original_length {
+                return Err("wrong loca table length")
+            }
+            tables.push((GLYF, glyf_table));
+            tables.push((LOCA, loca_table));
+            glyph_x_mins = Some(x_mins);
+        }
+        (Some(glyf), None) | (None, Some(glyf)) if entries[glyf].transformed => {
+            return Err("only one of glyf and loca is present")
+        }
+        _ => {}
+    }
+    for (index, entry) in entries.iter().enumerate() {
+        if !entry.transformed {
+            tables.push((entry.tag, table_data[index].to_vec()));
+        } else if entry.tag == HMTX {
+            let x_mins = match glyph_x_mins {
+                Some(ref x_mins) => x_mins,
+                None => return Err("hmtx is transformed but glyf isn't"),
+            };
+            let metrics_count = match find_entry(HHEA) {
+                Some(hhea) if table_data[hhea].len() >= 36 => read_u16(table_data[hhea], 34),
+                _ => return Err("no hhea table"),
+            };
+            let hmtx = try!(reconstruct_hmtx(table_data[index], metrics_count as usize, x_mins));
+            if hmtx.len() != entry.original_length {
+                return Err("wrong hmtx table length")
+            }
+            tables.push((HMTX, hmtx));
+        }
+    }
+    tables.sort_by(|&(a, _), &(b, _)| a.cmp(&b));
+
+    // WOFF 2.0 doesn't keep the checksums, so they are worked out again, with the font's
+    // checksum adjustment in the head table last.
+    let mut head_offset = None;
+    let mut table_offset = SFNT_HEADER_SIZE + tables.len() * SFNT_TABLE_ENTRY_SIZE;
+    for &mut (tag, ref mut table) in tables.iter_mut() {
+        if tag == HEAD {
+            if table.len() < 12 {
+                return Err("head table too short")
+            }
+            write_u32(table, 8, 0);
+            head_offset = Some(table_offset);
+        }
+        table_offset += (table.len() + 3) & !3;
+    }
+    if table_offset > MAX_SFNT_SIZE {
+        return Err("font too large")
+    }
+    let tables: Vec<SfntTable> = tables.into_iter().map(|(tag, table)| {
+        SfntTable {
+            tag: tag,
+            checksum: sfnt::checksum(&table),
+            data: table,
+        }
+    }).collect();
+    let mut font = write_sfnt(flavor, &tables);
+    if let Some(head_offset) = head_offset {
+        let adjustment = 0xB1B0AFBAu32.wrapping_sub(sfnt::checksum(&font));
+        write_u32(&mut font, head_offset + 8, adjustment);
+    }
+    Ok(font)
+}
+
+/// Rebuilds the glyf and loca tables from the transformed glyf table of a WOFF 2.0 font, and
+/// returns them with the smallest x coordinate of each glyph, for rebuilding hmtx.
+fn reconstruct_glyf(data: &[u8]) -> Result<(Vec<u8>, Vec<u8>, Vec<i16>), &'static str> {
+    let mut header = Stream::new(data);
+    try!(header.read_u16());
+    let option_flags = try!(header.read_u16());
+    let glyph_count = try!(header.read_u16()) as usize;
+    let index_format = try!(header.read_u16());
+    let mut stream_lengths = [0; 7];
+    for length in stream_lengths.iter_mut() {
+        *length = try!(header.read_u32()) as usize;
+    }
+
+    // The transformed table is the header followed by these streams, one after the other.
+    let mut streams = Stream::new(&data[header.offset..]);
+    let mut contour_counts = Stream::new(try!(streams.read_bytes(stream_lengths[0])));
+    let mut point_counts = Stream::new(try!(streams.read_bytes(stream_lengths[1])));
+    let mut point_flags = Stream::new(try!(streams.read_bytes(stream_lengths[2])));
+    let mut glyphs = Stream::new(try!(streams.read_bytes(stream_lengths[3])));
+    let mut composites = Stream::new(try!(streams.read_bytes(stream_lengths[4])));
+    let mut bboxes = Stream::new(try!(streams.read_bytes(stream_lengths[5])));
+    let mut instructions = Stream::new(try!(streams.read_bytes(stream_lengths[6])));
+    let bbox_bitmap = try!(bboxes.read_bytes(4 * ((glyph_count + 31) / 32)));
+    let overlap_bitmap = if option_flags & 1 != 0 {
+        Some(try!(streams.read_bytes((glyph_count + 7) / 8)))
+    } else {
+        None
+    };
+    let has_bit = |bitmap: &[u8], glyph: usize| bitmap[glyph >> 3] & (0x80 >> (glyph & 7)) != 0;
+
+    let mut glyf = vec!();
+    let mut glyph_offsets = Vec::with_capacity(glyph_count + 1);
+    let mut x_mins = Vec::with_capacity(glyph_count);
+    for glyph in 0..glyph_count {
+        glyph_offsets.push(glyf.len());
+        let contour_count = try!(contour_counts.read_i16());
+        let has_bbox = has_bit(bbox_bitmap, glyph);
+        if contour_count == 0 {
+            if has_bbox {
+                return Err("empty glyph with a bounding box")
+            }
+            x_mins.push(0);
+            continue
+        }
+
+        if contour_count == -1 {
+            if !has_bbox {
+                return Err("composite glyph without a bounding box")
+            }
+            let bbox = try!(bboxes.read_bytes(8));
+            let start = composites.offset;
+            let mut have_instructions = false;
+            loop {
+                let flags = try!(composites.read_u16());
+                let mut length = 2;
+                length += if flags & ARG_1_AND_2_ARE_WORDS != 0 { 4 } else { 2 };
+                if flags & WE_HAVE_A_SCALE != 0 {
+                    length += 2;
+                } else if flags & WE_HAVE_AN_X_AND_Y_SCALE != 0 {
+                    length += 4;
+                } else if flags & WE_HAVE_A_TWO_BY_TWO != 0 {
+                    length += 8;
+                }
+                try!(composites.read_bytes(length));
+                have_instructions |= flags & WE_HAVE_INSTRUCTIONS != 0;
+                if flags & MORE_COMPONENTS == 0 {
+                    break
+                }
+            }
+            push_u16(&mut glyf, contour_count as u16);
+            glyf.extend(bbox.iter().cloned());
+            glyf.extend(composites.data[start..composites.offset].iter().cloned());
+            if have_instructions {
+                let instruction_length = try!(glyphs.read_255_u16());
+                push_u16(&mut glyf, instruction_length);
+                let glyph_instructions = try!(instructions.read_bytes(instruction_length as usize));
+                glyf.extend(glyph_instructions.iter().cloned());
+            }
+            x_mins.push(read_i16(bbox, 0));
+        } else if contour_count > 0 {
+            let mut end_points = Vec::with_capacity(contour_count as usize);
+            let mut point_count = 0;
+            for _ in 0..contour_count {
+                let contour_point_count = try!(point_counts.read_255_u16()) as usize;
+                point_count += contour_point_count;
+                if contour_point_count == 0 || point_count > 0x10000 {
+                    return Err("bad contour")
+                }
+                end_points.push((point_count - 1) as u16);
+            }
+
+            let (mut x, mut y) = (0, 0);
+            let mut points = Vec::with_capacity(point_count);
+            for _ in 0..point_count {
+                let flag = try!(point_flags.read_u8());
+                let (dx, dy) = try!(read_triplet(flag, &mut glyphs));
+                x += dx;
+                y += dy;
+                if x < -0x8000 || x > 0x7FFF || y < -0x8000 || y > 0x7FFF {
+                    return Err("glyph point out of range")
+                }
+                points.push((x, y, flag & 0x80 == 0));
+            }
+            let instruction_length = try!(glyphs.read_255_u16());
+            let glyph_instructions = try!(instructions.read_bytes(instruction_length as usize));
+
+            let bbox = if has_bbox {
+                let bbox = try!(bboxes.read_bytes(8));
+                [read_i16(bbox, 0), read_i16(bbox, 2), read_i16(bbox, 4), read_i16(bbox, 6)]
+            } else {
+                let mut bbox = [points[0].0, points[0].1, points[0].0, points[0].1];
+                for &(x, y, _) in points.iter() {
+                    bbox = [cmp::min(bbox[0], x), cmp::min(bbox[1], y),
+                            cmp::max(bbox[2], x), cmp::max(bbox[3], y)];
+                }
+                [bbox[0] as i16, bbox[1] as i16, bbox[2] as i16, bbox[3] as i16]
+            };
+
+            push_u16(&mut glyf, contour_count as u16);
+            for &value in bbox.iter() {
+                push_u16(&mut glyf, value as u16);
+            }
+            for &end_point in end_points.iter() {
+                push_u16(&mut glyf, end_point);
+            }
+            push_u16(&mut glyf, instruction_length);
+            glyf.extend(glyph_instructions.iter().cloned());
+            let overlap = overlap_bitmap.map_or(false, |bitmap| has_bit(bitmap, glyph));
+            write_points(&mut glyf, &points, overlap);
+            x_mins.push(bbox[0]);
+        } else {
+            return Err("bad contour count")
+        }
+
+        while glyf.len() % 4 != 0 {
+            glyf.push(0);
+        }
+        if glyf.len() > MAX_SFNT_SIZE {
+            return Err("font too large")
+        }
+    }
+    glyph_offsets.push(glyf.len());
+
+    let mut loca = vec!();
+    for &offset in glyph_offsets.iter() {
+        if index_format == 0 {
+            // The short format stores half the offsets.
+            if offset > 0x1FFFE {
+                return Err("glyf table too large for short loca offsets")
+            }
+            push_u16(&mut loca, (offset / 2) as u16);
+        } else {
+            push_u32(&mut loca, offset as u32);
+        }
+    }
+    Ok((glyf, loca, x_mins))
+}
+
+/// Writes the flags and coordinates of the points of a simple glyph, as deltas from the point
+/// before. `overlap` sets the flag that says the glyph's contours overlap.
+fn write_points(glyf: &mut Vec<u8>, points: &[(i32, i32, bool)], overlap: bool) {
+    let mut flags = vec!();
+    let mut x_coordinates = vec!();
+    let mut y_coordinates = vec!();
+    let (mut last_x, mut last_y) = (0, 0);
+    let mut last_flag = None;
+    let mut repeat_count = 0;
+    for (index, &(x, y, on_curve)) in points.iter().enumerate() {
+        let mut flag = if on_curve { ON_CURVE_POINT } else { 0 };
+        if index == 0 && overlap {
+            flag |= OVERLAP_SIMPLE;
+        }
+        flag |= write_coordinate(&mut x_coordinates, x - last_x, X_SHORT_VECTOR,
+                                 X_IS_SAME_OR_POSITIVE);
+        flag |= write_coordinate(&mut y_coordinates, y - last_y, Y_SHORT_VECTOR,
+                                 Y_IS_SAME_OR_POSITIVE);
+        last_x = x;
+        last_y = y;
+
+        // Runs of the same flag are written once, followed by how many more times it repeats.
+        if last_flag == Some(flag) && repeat_count < 255 {
+            if repeat_count == 0 {
+                *flags.last_mut().unwrap() |= REPEAT_FLAG;
+                flags.push(0);
+            }
+            repeat_count += 1;
+            *flags.last_mut().unwrap() = repeat_count;
+        } else {
+            flags.push(flag);
+            last_flag = Some(flag);
+            repeat_count = 0;
+        }
+    }
+    glyf.extend(flags.into_iter());
+    glyf.extend(x_coordinates.into_iter());
+    glyf.extend(y_coordinates.into_iter());
+}
+
+/// Writes the change in one coordinate of a point, and returns the flags that say how it was
+/// written.
+fn write_coordinate(coordinates: &mut Vec<u8>, delta: i32, short_flag: u8, same_flag: u8) -> u8 {
+    if delta == 0 {
+        same_flag
+    } else if delta > -256 && delta < 256 {
+        coordinates.push(delta.abs() as u8);
+        if delta > 0 { short_flag | same_flag } else { short_flag }
+    } else {
+        push_u16(coordinates, delta as i16 as u16);
+        0
+    }
+}
+
+/// Reads the change in position from the previous point to the next one of a simple glyph,
+/// which is stored in a number of bytes of `glyphs` that depends on the point's flag.
+fn read_triplet(flag: u8, glyphs: &mut Stream) -> Result<(i32, i32), &'static str> {
+    fn with_sign(flag: u8, value: i32) -> i32 {
+        if flag & 1 != 0 { value } else { -value }
+    }
+
+    let flag = flag & 0x7F;
+    let bytes = try!(glyphs.read_bytes(match flag {
+        0...83 => 1,
+        84...119 => 2,
+        120...123 => 3,
+        _ => 4,
+    }));
+    let byte = |index: usize| bytes[index] as i32;
+    let value = flag as i32;
+    Ok(match flag {
+        0...9 => (0, with_sign(flag, ((value & 14) << 7) + byte(0))),
+        10...19 => (with_sign(flag, (((value - 10) & 14) << 7) + byte(0)), 0),
+        20...83 => {
+            let value = value - 20;
+            (with_sign(flag, 1 + (value & 0x30) + (byte(0) >> 4)),
+             with_sign(flag >> 1, 1 + ((value & 0x0C) << 2) + (byte(0) & 0x0F)))
+        }
+        84...119 => {
+            let value = value - 84;
+            (with_sign(flag, 1 + ((value / 12) << 8) + byte(0)),
+             with_sign(flag >> 1, 1 + (((value % 12) >> 2) << 8) + byte(1)))
+        }
+        120...123 => (with_sign(flag, (byte(0) << 4) + (byte(1) >> 4)),
+                      with_sign(flag >> 1, ((byte(1) & 0x0F) << 8) + byte(2))),
+        _ => (with_sign(flag, (byte(0) << 8) + byte(1)),
+              with_sign(flag >> 1, (byte(2) << 8) + byte(3))),
+    })
+}
+
+/// Rebuilds the hmtx table from its transformed form in a WOFF 2.0 font, in which the left
+/// side bearings that equal the smallest x coordinate of their glyph are left out.
+fn reconstruct_hmtx(data: &[u8], metrics_count: usize, x_mins: &[i16])
+                    -> Result<Vec<u8>, &'static str> {
+    let glyph_count = x_mins.len();
+    if metrics_count == 0 || metrics_count > glyph_count {
+        return Err("bad number of horizontal metrics")
+    }
+    let mut stream = Stream::new(data);
+    let flags = try!(stream.read_u8());
+    let advances = try!(stream.read_bytes(metrics_count * 2));
+    let mut hmtx = Vec::with_capacity(metrics_count * 2 + glyph_count * 2);
+    for glyph in 0..glyph_count {
+        // Bit 0 leaves out the bearings of the glyphs with an advance of their own, and bit 1
+        // those of the glyphs that share the last advance.
+        let omitted = if glyph < metrics_count { flags & 1 != 0 } else { flags & 2 != 0 };
+        let bearing = if omitted { x_mins[glyph] } else { try!(stream.read_i16()) };
+        if glyph < metrics_count {
+            hmtx.extend(advances[glyph * 2..glyph * 2 + 2].iter().cloned());
+        }
+        push_u16(&mut hmtx, bearing as u16);
+    }
+    Ok(hmtx)
+}
+
+/// Reads the data of a WOFF 2.0 font, failing instead of reading past its end.
+struct Stream<'a> {
+    data: &'a [u8],
+    offset: usize,
+}
+
+impl<'a> Stream<'a> {
+    fn new(data: &'a [u8]) -> Stream<'a> {
+        Stream {
+            data: data,
+            offset: 0,
+        }
+    }
+
+    fn read_bytes(&mut self, length: usize) -> Result<&'a [u8], &'static str> {
+        if length > self.data.len() - self.offset {
+            return Err("truncated font data")
+        }
+        self.offset += length;
+        Ok(&self.data[self.offset - length..self.offset])
+    }
+
+    fn read_u8(&mut self) -> Result<u8, &'static str> {
+        self.read_bytes(1).map(|bytes| bytes[0])
+    }
+
+    fn read_u16(&mut self) -> Result<u16, &'static str> {
+        self.read_bytes(2).map(|bytes| read_u16(bytes, 0))
+    }
+
+    fn read_i16(&mut self) -> Result<i16, &'static str> {
+        self.read_bytes(2).map(|bytes| read_i16(bytes, 0))
+    }
+
+    fn read_u32(&mut self) -> Result<u32, &'static str> {
+        self.read_bytes(4).map(|bytes| read_u32(bytes, 0))
+    }
+
+    /// Reads a UIntBase128: up to five bytes holding seven bits each, most significant first,
+    /// with the top bit set on all but the last.
+    fn read_base128(&mut self) -> Result<u32, &'static str> {
+        let mut value = 0u32;
+        for index in 0..5 {
+            let byte = try!(self.read_u8());
+            if (index == 0 && byte == 0x80) || value & 0xFE000000 != 0 {
+                return Err("bad UIntBase128")
+            }
+            value = value << 7 | (byte & 0x7F) as u32;
+            if byte & 0x80 == 0 {
+                return Ok(value)
+            }
+        }
+        Err("bad UIntBase128")
+    }
+
+    /// Reads a 255UInt16, which takes one byte for small values.
+    fn read_255_u16(&mut self) -> Result<u16, &'static str> {
+        match try!(self.read_u8()) {
+            253 => self.read_u16(),
+            254 => self.read_u8().map(|byte| byte as u16 + 253 * 2),
+            255 => self.read_u8().map(|byte| byte as u16 + 253),
+            code => Ok(code as u16),
+        }
+    }
+}
@@ -677,10 +677,14 @@ impl<'a> FlowConstructor<'a> {
                                               fragments: &mut IntermediateInlineFragments,
                                               node: &ThreadSafeLayoutNode,
                                               style: &Arc<ComputedValues>) {
+        let spellcheck = node.is_spellchecked();
+        let changed_range = node.changed_text_range();
         for content_item in node.text_content().into_iter() {
             let specific = match content_item {
                 ContentItem::String(string) => {
-                    let info = UnscannedTextFragmentInfo::from_text(string);
+                    let mut info = UnscannedTextFragmentInfo::from_text(string);
+                    info.spellcheck = spellcheck;
+                    info.changed_range = changed_range;
                     SpecificFragmentInfo::UnscannedText(info)
                 }
                 content_item => {
@@ -870,7 +874,26 @@ impl<'a> FlowConstructor<'a> {
                 self.create_fragments_for_node_text_content(&mut fragments, node, &style)
             }
             (PseudoElementType::Normal, _) => {
-                fragments.fragments.push_back(self.build_fragment_for_block(node));
+                let fragment = self.build_fragment_for_block(node);
+                let alt = match fragment.specific {
+                    SpecificFragmentInfo::Image(ref image_info) if image_info.broken => {
+                        node.image_alt()
+                    }
+                    _ => None,
+                };
+                fragments.fragments.push_back(fragment);
+
+                // An image that failed to load is followed by the text it stands for.
+                if let Some(alt) = alt {
+                    if !alt.is_empty() {
+                        let info = UnscannedTextFragmentInfo::from_text(alt);
+                        fragments.fragments.push_back(Fragment::from_opaque_node_and_style(
+                                node.opaque(),
+                                style.clone(),
+                                node.restyle_damage(),
+                                SpecificFragmentInfo::UnscannedText(info)));
+                    }
+                }
             }
             (_, _) => self.create_fragments_for_node_text_content(&mut fragments, node, &style),
         }
@@ -15,8 +15,8 @@ use euclid::{Rect, Size2D};
 use gfx::display_list::OpaqueNode;
 use gfx::font_cache_task::FontCacheTask;
 use gfx::font_context::FontContext;
-use msg::constellation_msg::ConstellationChan;
-use net_traits::image::base::Image;
+use msg::constellation_msg::{ConstellationChan, PipelineId};
+use net_traits::image::base::{AnimatedImage, Image};
 use net_traits::image_cache_task::{ImageCacheChan, ImageCacheTask, ImageResponse, ImageState};
 use net_traits::image_cache_task::{UsePlaceholder};
 use script::layout_interface::{Animation, LayoutChan, ReflowGoal};
@@ -55,12 +55,14 @@ pub fn heap_size_of_local_context() -> usize {
 
 fn create_or_get_local_context(shared_layout_context: &SharedLayoutContext)
                                -> Rc<LocalLayoutContext> {
+    let web_font_epoch = shared_layout_context.web_font_epoch;
     LOCAL_CONTEXT_KEY.with(|r| {
         let mut r = r.borrow_mut();
         if let Some(context) = r.clone() {
             if shared_layout_context.screen_size_changed {
                 context.applicable_declarations_cache.borrow_mut().evict_all();
             }
+            context.font_context.borrow_mut().set_web_font_epoch(web_font_epoch);
             context
         } else {
             let context = Rc::new(LocalLayoutContext {
@@ -68,6 +70,7 @@ fn create_or_get_local_context(shared_layout_context: &SharedLayoutContext)
                 applicable_declarations_cache: RefCell::new(ApplicableDeclarationsCache::new()),
                 style_sharing_candidate_cache: RefCell::new(StyleSharingCandidateCache::new()),
             });
+            context.font_context.borrow_mut().set_web_font_epoch(web_font_epoch);
             *r = Some(context.clone());
             context
         }
@@ -79,6 +82,9 @@ pub struct SharedLayoutContext {
     /// The shared image cache task.
     pub image_cache_task: ImageCacheTask,
 
+    /// The pipeline that images are requested on behalf of.
+    pub pipeline_id: PipelineId,
+
     /// A channel for the image cache to send responses to.
     pub image_cache_sender: ImageCacheChan,
 
@@ -88,6 +94,9 @@ pub struct SharedLayoutContext {
     /// Screen sized changed?
     pub screen_size_changed: bool,
 
+    /// The number of device pixels in each CSS pixel.
+    pub device_pixel_ratio: f32,
+
     /// A channel up to the constellation.
     pub constellation_chan: ConstellationChan,
 
@@ -97,6 +106,9 @@ pub struct SharedLayoutContext {
     /// Interface to the font cache task.
     pub font_cache_task: FontCacheTask,
 
+    /// The number of times web fonts used by this layout task have loaded so far.
+    pub web_font_epoch: usize,
+
     /// The CSS selector stylist.
     ///
     /// FIXME(#2604): Make this no longer an unsafe pointer once we have fast `RWArc`s.
@@ -166,45 +178,64 @@ impl<'a> LayoutContext<'a> {
 
     pub fn get_or_request_image(&self, url: Url, use_placeholder: UsePlaceholder)
                                 -> Option<Arc<Image>> {
+        self.get_or_request_image_state(url, use_placeholder).ok()
+    }
+
+    /// Like `get_or_request_image`, but tells an image that failed to load apart from one
+    /// that is still loading.
+    pub fn get_or_request_image_state(&self, url: Url, use_placeholder: UsePlaceholder)
+                                      -> Result<Arc<Image>, ImageState> {
         // See if the image is already available
         let result = self.shared.image_cache_task.get_image_if_available(url.clone(),
                                                                          use_placeholder);
 
         match result {
-            Ok(image) => Some(image),
+            Ok(image) => Ok(image),
             Err(state) => {
                 // If we are emitting an output file, then we need to block on
                 // image load or we risk emitting an output file missing the image.
                 let is_sync = opts::get().output_file.is_some();
 
                 match (state, is_sync) {
-                    // Image failed to load, so just return nothing
-                    (ImageState::LoadError, _) => None,
+                    // Image failed to load
+                    (ImageState::LoadError, _) => Err(ImageState::LoadError),
                     // Not loaded, test mode - load the image synchronously
                     (_, true) => {
                         let (sync_tx, sync_rx) = channel();
                         self.shared.image_cache_task.request_image(url,
+                                                                   self.shared.pipeline_id,
                                                                    ImageCacheChan(sync_tx),
                                                                    None);
                         match sync_rx.recv().unwrap().image_response {
-                            ImageResponse::Loaded(image) |
-                            ImageResponse::PlaceholderLoaded(image) => Some(image),
-                            ImageResponse::None => None,
+                            ImageResponse::Loaded(image) => Ok(image),
+                            ImageResponse::PlaceholderLoaded(image) => {
+                                match use_placeholder {
+                                    UsePlaceholder::Yes => Ok(image),
+                                    UsePlaceholder::No => Err(ImageState::LoadError),
+                                }
+                            }
+                            ImageResponse::None => Err(ImageState::LoadError),
                         }
                     }
                     // Not yet requested, async mode - request image from the cache
                     (ImageState::NotRequested, false) => {
                         self.shared.image_cache_task.request_image(url,
+                                                                   self.shared.pipeline_id,
                                                                    self.shared.image_cache_sender.clone(),
                                                                    None);
-                        None
+                        Err(ImageState::Pending)
                     }
                     // Image has been requested, is still pending. Return no image
                     // for this paint loop. When the image loads it will trigger
                     // a reflow and/or repaint.
-                    (ImageState::Pending, false) => None,
+                    (ImageState::Pending, false) => Err(ImageState::Pending),
                 }
             }
         }
     }
+
+    /// Returns all the frames of a loaded image, if it is animated.
+    pub fn get_animation(&self, url: Url) -> Option<Arc<AnimatedImage>> {
+        self.shared.image_cache_task.get_animation_if_available(url)
+    }
 }
@@ -31,7 +31,9 @@ use gfx::display_list::{GradientDisplayItem};
 use gfx::display_list::{GradientStop, ImageDisplayItem, LineDisplayItem};
 use gfx::display_list::{OpaqueNode, SolidColorDisplayItem};
 use gfx::display_list::{StackingContext, TextDisplayItem, TextOrientation};
+use gfx::display_list::{WavyLineDisplayItem};
 use gfx::paint_task::{PaintLayer, THREAD_TINT_COLORS};
+use gfx::text::glyph::CharIndex;
 use msg::compositor_msg::{ScrollPolicy, LayerId};
 use msg::constellation_msg::ConstellationChan;
 use msg::constellation_msg::Msg as ConstellationMsg;
@@ -58,6 +60,8 @@ use util::cursor::Cursor;
 use util::geometry::{Au, ZERO_POINT};
 use util::logical_geometry::{LogicalPoint, LogicalRect, LogicalSize, WritingMode};
 use util::opts;
+use util::range::Range;
+use util::spellcheck;
 
 use canvas_traits::{CanvasMsg, CanvasCommonMsg};
 use std::sync::mpsc::channel;
@@ -208,6 +212,14 @@ pub trait FragmentDisplayListBuilding {
                                             offset: Point2D<Au>,
                                             layout_context: &LayoutContext);
 
+    /// Tells the image cache how big an image is drawn, so that a large image can be decoded
+    /// at a reduced size. Only images whose size doesn't depend on the image itself are
+    /// reported, since a reduced image would otherwise be drawn smaller.
+    fn set_image_target_size(&self,
+                             image_fragment: &ImageFragmentInfo,
+                             stacking_relative_border_box: &Rect<Au>,
+                             layout_context: &LayoutContext);
+
     /// Returns the appropriate clipping region for descendants of this flow.
     fn clipping_region_for_children(&self,
                                     current_clip: &ClippingRegion,
@@ -242,6 +254,14 @@ pub trait FragmentDisplayListBuilding {
                                               clip: &ClippingRegion,
                                               blur_radius: Au);
 
+    /// Creates the wavy underlines for the words in a text fragment that are not in the spell
+    /// checking dictionary.
+    fn build_display_list_for_misspelled_words(&self,
+                                               display_list: &mut DisplayList,
+                                               text_fragment: &ScannedTextFragmentInfo,
+                                               stacking_relative_content_box: &LogicalRect<Au>,
+                                               clip: &ClippingRegion);
+
     /// A helper method that `build_display_list` calls to create per-fragment-type display items.
     fn build_fragment_type_specific_display_items(&mut self,
                                                   display_list: &mut DisplayList,
@@ -542,6 +562,7 @@ impl FragmentDisplayListBuilding for Fragment {
                                                                     Cursor::DefaultCursor),
                                            clip),
                 image: image.clone(),
+                animation: None,
                 stretch_size: Size2D::new(image_size.width, image_size.height),
                 image_rendering: style.get_effects().image_rendering.clone(),
             }), level);
@@ -1012,6 +1033,50 @@ impl FragmentDisplayListBuilding for Fragment {
                                                       stacking_relative_border_box.origin,
                                                       layout_context)
         }
+
+        // An image that is still loading is about to be displayed, so its load should go
+        // ahead of those of images elsewhere on the page.
+        if let SpecificFragmentInfo::Image(ref image_fragment) = self.specific {
+            match (&image_fragment.image, &image_fragment.url) {
+                (&None, &Some(ref url)) => {
+                    layout_context.shared.image_cache_task.prioritize(url.clone())
+                }
+                _ => {}
+            }
+            self.set_image_target_size(&**image_fragment,
+                                       stacking_relative_border_box,
+                                       layout_context);
+        }
+    }
+
+    fn set_image_target_size(&self,
+                             image_fragment: &ImageFragmentInfo,
+                             stacking_relative_border_box: &Rect<Au>,
+                             layout_context: &LayoutContext) {
+        let url = match image_fragment.url {
+            Some(ref url) if !image_fragment.broken => url,
+            _ => return,
+        };
+        if !image_fragment.replaced_image_fragment_info.has_specified_size(&*self.style) {
+            return
+        }
+        let content_box = self.stacking_relative_content_box(stacking_relative_border_box);
+        let device_pixel_ratio = layout_context.shared.device_pixel_ratio;
+        let target_size =
+            Size2D::new((content_box.size.width.to_f32_px() * device_pixel_ratio).ceil() as u32,
+                        (content_box.size.height.to_f32_px() * device_pixel_ratio).ceil() as u32);
+        // A loaded image is only reported when it's smaller than it's drawn, in case it was
+        // decoded at a reduced size.
+        if let Some(ref image) = image_fragment.image {
+            if image.width >= target_size.width && image.height >= target_size.height {
+                return
+            }
+        }
+        layout_context.shared.image_cache_task.set_target_size(
+            url.clone(),
+            target_size,
+            layout_context.shared.pipeline_id,
+            layout_context.shared.image_cache_sender.clone());
     }
 
     fn build_fragment_type_specific_display_items(&mut self,
@@ -1085,6 +1150,7 @@ impl FragmentDisplayListBuilding for Fragment {
                                                                             Cursor::DefaultCursor),
                                                    (*clip).clone()),
                         image: image.clone(),
+                        animation: image_fragment.animation.clone(),
                         stretch_size: stacking_relative_content_box.size,
                         image_rendering: self.style.get_effects().image_rendering.clone(),
                     }));
@@ -1116,6 +1182,7 @@ impl FragmentDisplayListBuilding for Fragment {
                         height: height as u32,
                         pixels: PixelsByColorType::RGBA8(canvas_data),
                     }),
+                    animation: None,
                     stretch_size: stacking_relative_content_box.size,
                     image_rendering: image_rendering::T::Auto,
                 }));
@@ -1335,9 +1402,12 @@ impl FragmentDisplayListBuilding for Fragment {
                                             shadow_blur_radius: Option<Au>,
                                             offset: &Point2D<Au>,
                                             clip: &ClippingRegion) {
-        // Determine the orientation and cursor to use.
+        // Determine the orientation and cursor to use. Upright runs in vertical text were shaped
+        // vertically, so their glyphs are drawn without turning them.
         let (orientation, cursor) = if self.style.writing_mode.is_vertical() {
-            if self.style.writing_mode.is_sideways_left() {
+            if text_fragment.run.vertical {
+                (TextOrientation::Upright, Cursor::VerticalTextCursor)
+            } else if self.style.writing_mode.is_sideways_left() {
                 (TextOrientation::SidewaysLeft, Cursor::VerticalTextCursor)
             } else {
                 (TextOrientation::SidewaysRight, Cursor::VerticalTextCursor)
@@ -1352,11 +1422,25 @@ impl FragmentDisplayListBuilding for Fragment {
         let container_size = Size2D::zero();
         let metrics = &text_fragment.run.font_metrics;
         let stacking_relative_content_box = stacking_relative_content_box.translate(offset);
-        let baseline_origin = stacking_relative_content_box.origin +
-            LogicalPoint::new(self.style.writing_mode,
-                              Au(0),
-                              metrics.ascent).to_physical(self.style.writing_mode,
-                                                          container_size);
+        let baseline_origin = if text_fragment.run.vertical {
+            // Glyphs shaped vertically hang from the pen, which goes down the middle of the line.
+            Point2D::new(stacking_relative_content_box.origin.x +
+                             stacking_relative_content_box.size.width / 2,
+                         stacking_relative_content_box.origin.y)
+        } else {
+            stacking_relative_content_box.origin +
+                LogicalPoint::new(self.style.writing_mode,
+                                  Au(0),
+                                  metrics.ascent).to_physical(self.style.writing_mode,
+                                                              container_size)
+        };
+
+        // Shadows of outlined text are outlined in the color of the shadow.
+        let inherited_text_style = self.style.get_inheritedtext();
+        let stroke_color = match shadow_blur_radius {
+            Some(_) => text_color,
+            None => self.style.resolve_color(inherited_text_style._webkit_text_stroke_color),
+        };
 
         // Create the text display item.
         display_list.content.push_back(DisplayItem::TextClass(box TextDisplayItem {
@@ -1365,7 +1449,10 @@ impl FragmentDisplayListBuilding for Fragment {
                                        (*clip).clone()),
             text_run: text_fragment.run.clone(),
             range: text_fragment.range,
+            justification_space: text_fragment.justification_space,
             text_color: text_color.to_gfx_color(),
+            stroke_color: stroke_color.to_gfx_color(),
+            stroke_width: inherited_text_style._webkit_text_stroke_width,
             orientation: orientation,
             baseline_origin: baseline_origin,
             blur_radius: shadow_blur_radius.unwrap_or(Au(0)),
@@ -1419,6 +1506,13 @@ impl FragmentDisplayListBuilding for Fragment {
                                                         clip,
                                                         shadow_blur_radius.unwrap_or(Au(0)))
         }
+
+        if text_fragment.spellcheck && shadow_blur_radius.is_none() {
+            self.build_display_list_for_misspelled_words(display_list,
+                                                         text_fragment,
+                                                         &stacking_relative_content_box,
+                                                         clip)
+        }
     }
 
     fn build_display_list_for_text_decoration(&self,
@@ -1448,6 +1542,49 @@ impl FragmentDisplayListBuilding for Fragment {
             clip_mode: BoxShadowClipMode::None,
         }))
     }
+
+    fn build_display_list_for_misspelled_words(&self,
+                                               display_list: &mut DisplayList,
+                                               text_fragment: &ScannedTextFragmentInfo,
+                                               stacking_relative_content_box: &LogicalRect<Au>,
+                                               clip: &ClippingRegion) {
+        let dictionary = match spellcheck::dictionary() {
+            Some(dictionary) => dictionary,
+            None => return,
+        };
+
+        let run = &text_fragment.run;
+        let begin = text_fragment.range.begin();
+        let text = run.text
+                      .chars()
+                      .skip(begin.to_usize())
+                      .take(text_fragment.range.length().to_usize())
+                      .collect::<String>();
+
+        // FIXME(pcwalton, #2795): Get the real container size.
+        let container_size = Size2D::zero();
+        let metrics = &run.font_metrics;
+        let metadata = DisplayItemMetadata::new(self.node, &*self.style, Cursor::TextCursor);
+        for word in spellcheck::misspelled_words(dictionary, &text).into_iter() {
+            let start = begin + CharIndex(word.start as isize);
+            let length = CharIndex((word.end - word.start) as isize);
+            let mut stacking_relative_box = *stacking_relative_content_box;
+            stacking_relative_box.start.i = stacking_relative_box.start.i +
+                text_fragment.advance_for_range(&Range::new(begin, start - begin));
+            stacking_relative_box.start.b = stacking_relative_box.start.b + metrics.ascent -
+                metrics.underline_offset;
+            stacking_relative_box.size.inline = run.advance_for_range(&Range::new(start, length));
+            stacking_relative_box.size.block = cmp::max(metrics.underline_size * 3,
+                                                        Au::from_px(3));
+
+            let bounds = stacking_relative_box.to_physical(self.style.writing_mode,
+                                                           container_size);
+            display_list.content.push_back(DisplayItem::WavyLineClass(box WavyLineDisplayItem {
+                base: BaseDisplayItem::new(bounds, metadata, (*clip).clone()),
+                color: color::rgb(255, 0, 0),
+            }))
+        }
+    }
 }
 
 pub trait BlockFlowDisplayListBuilding {
@@ -26,8 +26,8 @@ use gfx::display_list::{BLUR_INFLATION_FACTOR, OpaqueNode};
 use gfx::text::glyph::CharIndex;
 use gfx::text::text_run::{TextRun, TextRunSlice};
 use msg::constellation_msg::{ConstellationChan, Msg, PipelineId, SubpageId};
-use net_traits::image::base::Image;
-use net_traits::image_cache_task::UsePlaceholder;
+use net_traits::image::base::{AnimatedImage, Image, broken_image_glyph};
+use net_traits::image_cache_task::{ImageState, UsePlaceholder};
 use rustc_serialize::{Encodable, Encoder};
 use script_traits::UntrustedNodeAddress;
 use std::borrow::ToOwned;
@@ -46,7 +46,7 @@ use style::values::computed::{LengthOrPercentage, LengthOrPercentageOrAuto};
 use style::values::computed::{LengthOrPercentageOrNone};
 use text::TextRunScanner;
 use url::Url;
-use util::geometry::{Au, ZERO_POINT};
+use util::geometry::{Au, MAX_AU, ZERO_POINT};
 use util::logical_geometry::{LogicalRect, LogicalSize, LogicalMargin, WritingMode};
 use util::range::*;
 use util::str::is_whitespace;
@@ -317,6 +317,12 @@ pub struct ImageFragmentInfo {
     /// The image held within this fragment.
     pub replaced_image_fragment_info: ReplacedImageFragmentInfo,
     pub image: Option<Arc<Image>>,
+    /// All the frames of the image, if it is animated.
+    pub animation: Option<Arc<AnimatedImage>>,
+    /// The URL the image is loaded from, so that the load can be hurried once it's visible.
+    pub url: Option<Url>,
+    /// Whether the image failed to load, in which case `image` is the broken image glyph.
+    pub broken: bool,
 }
 
 impl ImageFragmentInfo {
@@ -335,15 +341,27 @@ impl ImageFragmentInfo {
                    .map(Au::from_px)
         }
 
-        let image = url.and_then(|url| {
-            layout_context.get_or_request_image(url, UsePlaceholder::Yes)
+        let state = url.clone().map(|url| {
+            layout_context.get_or_request_image_state(url, UsePlaceholder::No)
         });
+        let (image, broken) = match state {
+            Some(Ok(image)) => (Some(image), false),
+            Some(Err(ImageState::LoadError)) => (Some(Arc::new(broken_image_glyph())), true),
+            Some(Err(_)) | None => (None, false),
+        };
+        let animation = match (&image, &url) {
+            (&Some(_), &Some(ref url)) if !broken => layout_context.get_animation(url.clone()),
+            _ => None,
+        };
 
         ImageFragmentInfo {
             replaced_image_fragment_info: ReplacedImageFragmentInfo::new(node,
                 convert_length(node, &atom!("width")),
                 convert_length(node, &atom!("height"))),
             image: image,
+            animation: animation,
+            url: url,
+            broken: broken,
         }
     }
 
@@ -437,6 +455,19 @@ impl ReplacedImageFragmentInfo {
     //
     // `dom_length`: inline-size or block-size as specified in the `img` tag.
     // `style_length`: inline-size as given in the CSS
+    /// Whether the style or attributes give both dimensions of the image, so that how big it
+    /// is drawn doesn't depend on the size of the image itself.
+    pub fn has_specified_size(&self, style: &ComputedValues) -> bool {
+        fn is_specified(style_length: LengthOrPercentageOrAuto, dom_length: Option<Au>) -> bool {
+            match (style_length, dom_length) {
+                (LengthOrPercentageOrAuto::Auto, None) => false,
+                _ => true,
+            }
+        }
+        is_specified(style.content_inline_size(), self.dom_inline_size) &&
+            is_specified(style.content_block_size(), self.dom_block_size)
+    }
+
     pub fn style_length(style_length: LengthOrPercentageOrAuto,
                         dom_length: Option<Au>,
                         container_inline_size: Au) -> MaybeAuto {
@@ -629,6 +660,21 @@ pub struct ScannedTextFragmentInfo {
     /// Whether a line break is required after this fragment if wrapping on newlines (e.g. if
     /// `white-space: pre` is in effect).
     pub requires_line_break_afterward_if_wrapping_on_newlines: bool,
+
+    /// Whether misspelled words in this fragment are underlined.
+    pub spellcheck: bool,
+
+    /// Where the characters of the text run came from in the text of the fragment before it was
+    /// scanned, which whitespace compression and `text-transform` change.
+    pub original_offsets: Arc<OriginalOffsets>,
+
+    /// The extra space after each justification opportunity in the range, if the line this
+    /// fragment is on is justified.
+    pub justification_space: Au,
+
+    /// If this fragment is a tab, the distance between the tab stops that inline layout advances
+    /// it to.
+    pub tab_interval: Option<Au>,
 }
 
 impl ScannedTextFragmentInfo {
@@ -645,6 +691,62 @@ impl ScannedTextFragmentInfo {
             range_end_including_stripped_whitespace: range.end(),
             requires_line_break_afterward_if_wrapping_on_newlines:
                 requires_line_break_afterward_if_wrapping_on_newlines,
+            spellcheck: false,
+            original_offsets: Arc::new(OriginalOffsets {
+                run_start: range.begin(),
+                offsets: vec!(),
+            }),
+            justification_space: Au(0),
+            tab_interval: None,
+        }
+    }
+
+    /// Returns the advance of the characters in `range` of the text run, including the
+    /// justification space after them.
+    pub fn advance_for_range(&self, range: &Range<CharIndex>) -> Au {
+        let advance = self.run.advance_for_range(range);
+        if self.justification_space == Au(0) {
+            return advance
+        }
+        let opportunities = self.run.justification_opportunity_count(range);
+        advance + self.justification_space * (opportunities as i32)
+    }
+}
+
+/// A mapping between the characters of a text run and the characters of the text of the node
+/// they came from. This is used to map DOM offsets, such as the position of the caret in a text
+/// field, into the text run and back.
+#[derive(Clone, Debug)]
+pub struct OriginalOffsets {
+    /// The index in the text run of the first character that came from the original text.
+    pub run_start: CharIndex,
+    /// The offset in the original text of each character of the text run from `run_start` on,
+    /// which never decreases. If this is empty, the text run is the same as the original text.
+    pub offsets: Vec<usize>,
+}
+
+impl OriginalOffsets {
+    /// Returns the index of the first character in the text run that comes from the original text
+    /// at or after `offset`.
+    pub fn run_index_for_original_offset(&self, offset: usize) -> CharIndex {
+        if self.offsets.is_empty() {
+            return self.run_start + CharIndex(offset as isize)
+        }
+        let index = self.offsets.iter().position(|&original_offset| original_offset >= offset)
+                                       .unwrap_or(self.offsets.len());
+        self.run_start + CharIndex(index as isize)
+    }
+
+    /// Returns the offset in the original text of the character at `index` in the text run.
+    /// Characters that case mapping turned one character into all map to that character.
+    pub fn original_offset_for_run_index(&self, index: CharIndex) -> usize {
+        let index = (index - self.run_start).to_usize();
+        if self.offsets.is_empty() {
+            return index
+        }
+        match self.offsets.get(index) {
+            Some(&original_offset) => original_offset,
+            None => self.offsets[self.offsets.len() - 1] + 1,
         }
     }
 }
@@ -677,6 +779,9 @@ pub struct SplitResult {
     pub inline_end: Option<SplitInfo>,
     /// The text run which is being split.
     pub text_run: Arc<Box<TextRun>>,
+    /// Whether the split is at a hyphenation point, so that a hyphen is drawn after the part of
+    /// the fragment on the first line.
+    pub hyphenated: bool,
 }
 
 /// Describes how a fragment should be truncated.
@@ -696,6 +801,17 @@ pub struct UnscannedTextFragmentInfo {
     /// FIXME(pcwalton): Is there something more clever we can do here that avoids the double
     /// indirection while not penalizing all fragments?
     pub text: Box<String>,
+
+    /// Whether misspelled words in the text are underlined.
+    pub spellcheck: bool,
+
+    /// The offset in characters of the text from the start of the text of its node, which isn't
+    /// zero for preformatted text that was split after a newline or around a tab.
+    pub node_offset: usize,
+
+    /// The characters of the text of the node that changed since it was last laid out, from the
+    /// start of the text of the node, if any did.
+    pub changed_range: Option<(usize, usize)>,
 }
 
 impl UnscannedTextFragmentInfo {
@@ -704,6 +820,9 @@ impl UnscannedTextFragmentInfo {
     pub fn from_text(text: String) -> UnscannedTextFragmentInfo {
         UnscannedTextFragmentInfo {
             text: box text,
+            spellcheck: false,
+            node_offset: 0,
+            changed_range: None,
         }
     }
 }
@@ -836,25 +955,40 @@ impl Fragment {
                                     self.border_box.size.block);
         let requires_line_break_afterward_if_wrapping_on_newlines =
             self.requires_line_break_afterward_if_wrapping_on_newlines();
-        let info = box ScannedTextFragmentInfo::new(
+        let mut info = box ScannedTextFragmentInfo::new(
             text_run,
             split.range,
             size,
             requires_line_break_afterward_if_wrapping_on_newlines);
+        info.spellcheck = self.is_spellchecked();
+        if let SpecificFragmentInfo::ScannedText(ref old_info) = self.specific {
+            info.original_offsets = old_info.original_offsets.clone();
+        }
         self.transform(size, SpecificFragmentInfo::ScannedText(info))
     }
 
     /// Transforms this fragment into an ellipsis fragment, preserving all the other data.
     pub fn transform_into_ellipsis(&self, layout_context: &LayoutContext) -> Fragment {
-        let mut unscanned_ellipsis_fragments = LinkedList::new();
-        unscanned_ellipsis_fragments.push_back(self.transform(
+        self.transform_into_text(layout_context, "…".to_owned())
+    }
+
+    /// Transforms this fragment into the hyphen that is drawn where a line breaks at a
+    /// hyphenation point, preserving all the other data.
+    pub fn transform_into_hyphen(&self, layout_context: &LayoutContext) -> Fragment {
+        self.transform_into_text(layout_context, "-".to_owned())
+    }
+
+    /// Transforms this fragment into a scanned text fragment of `text`, preserving all the other
+    /// data.
+    fn transform_into_text(&self, layout_context: &LayoutContext, text: String) -> Fragment {
+        let mut unscanned_fragments = LinkedList::new();
+        unscanned_fragments.push_back(self.transform(
                 self.border_box.size,
-                SpecificFragmentInfo::UnscannedText(UnscannedTextFragmentInfo::from_text(
-                        "…".to_owned()))));
-        let ellipsis_fragments = TextRunScanner::new().scan_for_runs(&mut layout_context.font_context(),
-                                                                     unscanned_ellipsis_fragments);
-        debug_assert!(ellipsis_fragments.len() == 1);
-        ellipsis_fragments.fragments.into_iter().next().unwrap()
+                SpecificFragmentInfo::UnscannedText(UnscannedTextFragmentInfo::from_text(text))));
+        let fragments = TextRunScanner::new().scan_for_runs(&mut layout_context.font_context(),
+                                                            unscanned_fragments);
+        debug_assert!(fragments.len() == 1);
+        fragments.fragments.into_iter().next().unwrap()
     }
 
     pub fn restyle_damage(&self) -> RestyleDamage {
@@ -1232,6 +1366,32 @@ impl Fragment {
         }
     }
 
+    /// If this fragment is a tab, sets its inline size so that it ends at the first tab stop at
+    /// least half the width of a digit past its start. `inline_start` is where its margin box
+    /// starts, from the start of its line.
+    pub fn advance_to_tab_stop(&mut self, inline_start: Au) {
+        let text_fragment_info = match self.specific {
+            SpecificFragmentInfo::ScannedText(ref mut text_fragment_info) => text_fragment_info,
+            _ => return,
+        };
+        let advance = match text_fragment_info.tab_interval {
+            None => return,
+            Some(tab_interval) if tab_interval <= Au(0) => Au(0),
+            Some(tab_interval) => {
+                // Tab stops can be far apart, so work in 64 bits to keep from overflowing.
+                let start = inline_start.0 as i64 + self.margin.inline_start.0 as i64 +
+                    self.border_padding.inline_start.0 as i64;
+                let minimum_end =
+                    start + text_fragment_info.run.font_metrics.average_advance.0 as i64 / 2;
+                let tab_interval = tab_interval.0 as i64;
+                let tab_stop = (minimum_end + tab_interval - 1) / tab_interval * tab_interval;
+                Au(min(tab_stop - start, MAX_AU.0 as i64) as i32)
+            }
+        };
+        text_fragment_info.content_size.inline = advance;
+        self.border_box.size.inline = advance + self.border_padding.inline_start_end();
+    }
+
     /// Returns true if this element can be split. This is true for text fragments, unless
     /// `white-space: pre` is set.
     pub fn can_split(&self) -> bool {
@@ -1299,9 +1459,11 @@ impl Fragment {
 
                 // See http://dev.w3.org/csswg/css-sizing/#max-content-inline-size.
                 // TODO: Account for soft wrap opportunities.
-                let max_line_inline_size = text_fragment_info.run
-                                                             .metrics_for_range(range)
-                                                             .advance_width;
+                let max_line_inline_size = if text_fragment_info.tab_interval.is_some() {
+                    text_fragment_info.content_size.inline
+                } else {
+                    text_fragment_info.run.metrics_for_range(range).advance_width
+                };
 
                 let min_line_inline_size = match self.style.get_inheritedtext().white_space {
                     white_space::T::pre | white_space::T::nowrap => max_line_inline_size,
@@ -1494,8 +1656,18 @@ impl Fragment {
             let metrics = text_fragment_info.run.metrics_for_slice(slice.glyphs, &slice.range);
             let advance = metrics.advance_width;
 
+            // The line can only break at a hyphenation point after this slice if the hyphen
+            // fits too.
+            let slice_end = slice.text_run_range().end();
+            let hyphen_advance = if slice_end < text_fragment_info.range.end() &&
+                    text_fragment_info.run.is_hyphenation_point(slice_end) {
+                text_fragment_info.run.hyphen_advance
+            } else {
+                Au(0)
+            };
+
             // Have we found the split point?
-            if advance <= remaining_inline_size || slice.glyphs.is_whitespace() {
+            if advance + hyphen_advance <= remaining_inline_size || slice.glyphs.is_whitespace() {
                 // Keep going; we haven't found the split point yet.
                 if flags.contains(STARTS_LINE) &&
                         pieces_processed_count == 0 &&
@@ -1574,11 +1746,14 @@ impl Fragment {
         let inline_end = inline_end_range.map(|inline_end_range| {
             SplitInfo::new(inline_end_range, &**text_fragment_info)
         });
+        let hyphenated = inline_start.is_some() && inline_end.is_some() &&
+            text_fragment_info.run.is_hyphenation_point(inline_start_range.end());
 
         Some(SplitResult {
             inline_start: inline_start,
             inline_end: inline_end,
             text_run: text_fragment_info.run.clone(),
+            hyphenated: hyphenated,
         })
     }
 
@@ -1590,6 +1765,7 @@ impl Fragment {
              &SpecificFragmentInfo::ScannedText(ref other_info)) => {
                 debug_assert!(util::arc_ptr_eq(&this_info.run, &other_info.run));
                 this_info.range.extend_to(other_info.range_end_including_stripped_whitespace);
+                this_info.justification_space = Au(0);
                 this_info.content_size.inline =
                     this_info.run.metrics_for_range(&this_info.range).advance_width;
                 this_info.requires_line_break_afterward_if_wrapping_on_newlines =
@@ -1670,9 +1846,10 @@ impl Fragment {
                 block_flow.base.block_container_inline_size = self.border_box.size.inline;
                 block_flow.base.block_container_writing_mode = self.style.writing_mode;
             }
-            SpecificFragmentInfo::ScannedText(ref info) => {
+            SpecificFragmentInfo::ScannedText(ref mut info) => {
                 // Scanned text fragments will have already had their content inline-sizes assigned
-                // by this point.
+                // by this point. Lines are justified again after they are broken.
+                info.justification_space = Au(0);
                 self.border_box.size.inline = info.content_size.inline + noncontent_inline_size
             }
             SpecificFragmentInfo::Image(ref mut image_fragment_info) => {
@@ -2077,6 +2254,15 @@ impl Fragment {
         }
     }
 
+    /// Returns true if misspelled words in this text fragment are underlined.
+    pub fn is_spellchecked(&self) -> bool {
+        match self.specific {
+            SpecificFragmentInfo::ScannedText(ref scanned_text) => scanned_text.spellcheck,
+            SpecificFragmentInfo::UnscannedText(ref unscanned_text) => unscanned_text.spellcheck,
+            _ => false,
+        }
+    }
+
     pub fn strip_leading_whitespace_if_necessary(&mut self) {
         let mut scanned_text_fragment_info = match self.specific {
             SpecificFragmentInfo::ScannedText(ref mut scanned_text_fragment_info) => {
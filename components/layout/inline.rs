@@ -330,7 +330,8 @@ impl LineBreaker {
                  &SpecificFragmentInfo::ScannedText(ref candidate_info)) => {
                     util::arc_ptr_eq(&result_info.run, &candidate_info.run) &&
                         inline_contexts_are_equal(&result.inline_context,
-                                                  &candidate.inline_context)
+                                                  &candidate.inline_context) &&
+                        result_info.tab_interval.is_none() && candidate_info.tab_interval.is_none()
                 }
                 _ => false,
             };
@@ -503,6 +504,10 @@ impl LineBreaker {
             self.pending_line.green_zone = line_bounds.size;
         }
 
+        // Tab stops are measured from the start of the line.
+        let indentation = self.indentation_for_pending_fragment();
+        fragment.advance_to_tab_stop(self.pending_line.bounds.size.inline + indentation);
+
         debug!("LineBreaker: trying to append to line {} (fragment size: {:?}, green zone: {:?}): \
                {:?}",
                self.lines.len(),
@@ -536,7 +541,6 @@ impl LineBreaker {
         // If we're not going to overflow the green zone vertically, we might still do so
         // horizontally. We'll try to place the whole fragment on this line and break somewhere if
         // it doesn't fit.
-        let indentation = self.indentation_for_pending_fragment();
         let new_inline_size = self.pending_line.bounds.size.inline +
             fragment.margin_box_inline_size() + indentation;
         if new_inline_size <= green_zone.inline {
@@ -575,20 +579,40 @@ impl LineBreaker {
             Some(split_result) => split_result,
         };
 
+        // Words are shaped whole, so the one that the line breaks inside of is shaped again as
+        // two.
+        let text_run = match split_result.inline_start {
+            Some(ref inline_start) if split_result.hyphenated => {
+                text::split_word_at_hyphenation_point(&mut layout_context.font_context(),
+                                                      fragment.style().get_font_arc(),
+                                                      &split_result.text_run,
+                                                      inline_start.range.end())
+            }
+            _ => split_result.text_run.clone(),
+        };
+
         inline_start_fragment = split_result.inline_start.as_ref().map(|x| {
-            fragment.transform_with_split_info(x, split_result.text_run.clone())
+            fragment.transform_with_split_info(x, text_run.clone())
         });
         inline_end_fragment = split_result.inline_end.as_ref().map(|x| {
-            fragment.transform_with_split_info(x, split_result.text_run.clone())
+            fragment.transform_with_split_info(x, text_run.clone())
         });
 
         // Push the first fragment onto the line we're working on and start off the next line with
         // the second fragment. If there's no second fragment, the next line will start off empty.
         match (inline_start_fragment, inline_end_fragment) {
             (Some(inline_start_fragment), Some(inline_end_fragment)) => {
-                self.push_fragment_to_line(layout_context,
-                                           inline_start_fragment,
-                                           LineFlushMode::Flush);
+                if split_result.hyphenated {
+                    let hyphen = inline_start_fragment.transform_into_hyphen(layout_context);
+                    self.push_fragment_to_line(layout_context,
+                                               inline_start_fragment,
+                                               LineFlushMode::No);
+                    self.push_fragment_to_line(layout_context, hyphen, LineFlushMode::Flush);
+                } else {
+                    self.push_fragment_to_line(layout_context,
+                                               inline_start_fragment,
+                                               LineFlushMode::Flush);
+                }
                 self.work_list.push_front(inline_end_fragment)
             },
             (Some(fragment), None) => {
@@ -935,7 +959,9 @@ impl InlineFlow {
     }
 
     /// Justifies the given set of inline fragments, distributing the `slack_inline_size` among all
-    /// of them according to the value of `text-justify`.
+    /// of them according to the value of `text-justify`. The extra space goes after each
+    /// justification opportunity (spaces, and the places between characters where CJK text is
+    /// spaced out) and is added when the text is painted, so the text runs aren't changed.
     fn justify_inline_fragments(fragments: &mut InlineFragments,
                                 line: &Line,
                                 slack_inline_size: Au) {
@@ -944,55 +970,59 @@ impl InlineFlow {
             return
         }
 
-        // First, calculate the number of expansion opportunities (spaces, normally).
-        let mut expansion_opportunities = 0i32;
+        // First, calculate the number of justification opportunities. There's none after the
+        // last character on the line, since nothing follows it.
+        let mut last_text_fragment_index = None;
+        for fragment_index in line.range.each_index() {
+            match fragments.get(fragment_index.to_usize()).specific {
+                SpecificFragmentInfo::ScannedText(ref info) if !info.range.is_empty() => {
+                    last_text_fragment_index = Some(fragment_index)
+                }
+                _ => {}
+            }
+        }
+        let mut expansion_opportunities = 0;
         for fragment_index in line.range.each_index() {
             let fragment = fragments.get(fragment_index.to_usize());
             let scanned_text_fragment_info = match fragment.specific {
                 SpecificFragmentInfo::ScannedText(ref info) if !info.range.is_empty() => info,
                 _ => continue
             };
+            let run = &scanned_text_fragment_info.run;
             let fragment_range = scanned_text_fragment_info.range;
-
-            for slice in scanned_text_fragment_info.run.character_slices_in_range(&fragment_range) {
-                expansion_opportunities += slice.glyphs.space_count_in_range(&slice.range) as i32
+            expansion_opportunities += run.justification_opportunity_count(&fragment_range);
+            if Some(fragment_index) == last_text_fragment_index &&
+                    run.is_justification_opportunity(fragment_range.end() - CharIndex(1)) {
+                expansion_opportunities -= 1
             }
         }
+        if expansion_opportunities == 0 {
+            return
+        }
 
         // Then distribute all the space across the expansion opportunities.
-        let space_per_expansion_opportunity = slack_inline_size.to_f64_px() /
-            (expansion_opportunities as f64);
+        let space_per_expansion_opportunity = slack_inline_size / (expansion_opportunities as i32);
         for fragment_index in line.range.each_index() {
             let fragment = fragments.get_mut(fragment_index.to_usize());
-            let mut scanned_text_fragment_info = match fragment.specific {
-                SpecificFragmentInfo::ScannedText(ref mut info) if !info.range.is_empty() => info,
-                _ => continue
-            };
-            let fragment_range = scanned_text_fragment_info.range;
-
-            // FIXME(pcwalton): This is an awful lot of uniqueness making. I don't see any easy way
-            // to get rid of it without regressing the performance of the non-justified case,
-            // though.
-            let run = Arc::make_unique(&mut scanned_text_fragment_info.run);
-            {
-                let glyph_runs = Arc::make_unique(&mut run.glyphs);
-                for mut glyph_run in glyph_runs.iter_mut() {
-                    let mut range = glyph_run.range.intersect(&fragment_range);
-                    if range.is_empty() {
-                        continue
+            let new_inline_size = match fragment.specific {
+                SpecificFragmentInfo::ScannedText(ref mut info) if !info.range.is_empty() => {
+                    info.justification_space = space_per_expansion_opportunity;
+                    let inline_size = info.advance_for_range(&info.range);
+                    if Some(fragment_index) == last_text_fragment_index &&
+                            info.run.is_justification_opportunity(info.range.end() -
+                                                                  CharIndex(1)) {
+                        inline_size - space_per_expansion_opportunity
+                    } else {
+                        inline_size
                     }
-                    range.shift_by(-glyph_run.range.begin());
-
-                    let glyph_store = Arc::make_unique(&mut glyph_run.glyph_store);
-                    glyph_store.distribute_extra_space_in_range(&range,
-                                                                space_per_expansion_opportunity);
                 }
-            }
+                _ => continue
+            };
 
             // Recompute the fragment's border box size.
-            let new_inline_size = run.advance_for_range(&fragment_range);
             let new_size = LogicalSize::new(fragment.style.writing_mode,
-                                            new_inline_size,
+                                            new_inline_size +
+                                                fragment.border_padding.inline_start_end(),
                                             fragment.border_box.size.block);
             fragment.border_box = LogicalRect::from_point_size(fragment.style.writing_mode,
                                                                fragment.border_box.start,
@@ -1176,7 +1206,11 @@ impl Flow for InlineFlow {
         let mut intrinsic_sizes_for_flow = IntrinsicISizesContribution::new();
         let mut intrinsic_sizes_for_inline_run = IntrinsicISizesContribution::new();
         let mut intrinsic_sizes_for_nonbroken_run = IntrinsicISizesContribution::new();
+        // The inline size of the preformatted text on the current line so far, which tabs are
+        // advanced to their tab stops from.
+        let mut preformatted_line_inline_size = Au(0);
         for fragment in self.fragments.fragments.iter_mut() {
+            fragment.advance_to_tab_stop(preformatted_line_inline_size);
             let intrinsic_sizes_for_fragment = fragment.compute_intrinsic_inline_sizes().finish();
             match fragment.style.get_inheritedtext().white_space {
                 white_space::T::nowrap => {
@@ -1186,10 +1220,13 @@ impl Flow for InlineFlow {
                 white_space::T::pre => {
                     intrinsic_sizes_for_nonbroken_run.union_nonbreaking_inline(
                         &intrinsic_sizes_for_fragment);
+                    preformatted_line_inline_size = preformatted_line_inline_size +
+                        intrinsic_sizes_for_fragment.preferred_inline_size;
 
                     // Flush the intrinsic sizes we've been gathering up in order to handle the
                     // line break, if necessary.
                     if fragment.requires_line_break_afterward_if_wrapping_on_newlines() {
+                        preformatted_line_inline_size = Au(0);
                         intrinsic_sizes_for_inline_run.union_inline(
                             &intrinsic_sizes_for_nonbroken_run.finish());
                         intrinsic_sizes_for_nonbroken_run = IntrinsicISizesContribution::new();
@@ -15,7 +15,7 @@ use data::LayoutDataWrapper;
 use display_list_builder::ToGfxColor;
 use flow::{self, Flow, ImmutableFlowUtils, MutableFlowUtils, MutableOwnedFlowUtils};
 use flow_ref::FlowRef;
-use fragment::{Fragment, FragmentBorderBoxIterator};
+use fragment::{Fragment, FragmentBorderBoxIterator, SpecificFragmentInfo};
 use incremental::{LayoutDamageComputation, REFLOW, REFLOW_ENTIRE_DOCUMENT, REPAINT};
 use layout_debug;
 use opaque_node::OpaqueNodeMethods;
@@ -25,6 +25,7 @@ use wrapper::LayoutNode;
 
 use azure::azure::AzColor;
 use canvas_traits::CanvasMsg;
+use cssparser::ToCss;
 use encoding::EncodingRef;
 use encoding::all::UTF_8;
 use fnv::FnvHasher;
@@ -44,24 +45,28 @@ use log;
 use msg::compositor_msg::{Epoch, ScrollPolicy, LayerId};
 use msg::constellation_msg::Msg as ConstellationMsg;
 use msg::constellation_msg::{ConstellationChan, Failure, PipelineExitType, PipelineId};
-use profile_traits::mem::{self, Report, ReportsChan};
+use profile_traits::mem::{self, Report, ReportUnits, ReportsChan};
 use profile_traits::time::{self, ProfilerMetadata, profile};
 use profile_traits::time::{TimerMetadataFrameType, TimerMetadataReflowType};
 use net_traits::{load_bytes_iter, PendingAsyncLoad};
 use net_traits::image_cache_task::{ImageCacheTask, ImageCacheResult, ImageCacheChan};
 use script::dom::bindings::js::LayoutJS;
 use script::dom::node::{LayoutData, Node};
-use script::layout_interface::{Animation, ContentBoxResponse, ContentBoxesResponse};
-use script::layout_interface::{HitTestResponse, LayoutChan, LayoutRPC};
-use script::layout_interface::{MouseOverResponse, Msg, Reflow, ReflowGoal, ReflowQueryType};
-use script::layout_interface::{ScriptLayoutChan, ScriptReflow, TrustedNodeAddress};
+use script::layout_interface::{Animation, BoxModel, BoxModelResponse, CaretRectResponse};
+use script::layout_interface::ContentBoxResponse;
+use script::layout_interface::{ContentBoxesResponse, HitTestResponse, LayoutChan, LayoutRPC};
+use script::layout_interface::{MatchedRule, MatchedRulesResponse, MouseOverResponse, Msg};
+use script::layout_interface::{Reflow, ReflowGoal, ReflowQueryType};
+use script::layout_interface::{ScriptLayoutChan, ScriptReflow, ScrollAreaResponse};
+use script::layout_interface::TrustedNodeAddress;
 use script_traits::{ConstellationControlMsg, OpaqueScriptLayoutChannel};
 use script_traits::{ScriptControlChan, StylesheetLoadResponder};
+use selectors::Node as SelectorsNode;
 use std::borrow::ToOwned;
 use std::cell::Cell;
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use std::collections::hash_state::DefaultState;
-use std::mem::transmute;
+use std::mem::{self, transmute};
 use std::ops::{Deref, DerefMut};
 use std::sync::mpsc::{channel, Sender, Receiver, Select};
 use std::sync::{Arc, Mutex, MutexGuard};
@@ -75,6 +80,8 @@ use util::geometry::{Au, MAX_RECT};
 use util::logical_geometry::LogicalPoint;
 use util::mem::HeapSizeOf;
 use util::opts;
+use util::range::Range;
+use util::str::LowercaseString;
 use util::task::spawn_named_with_send_on_failure;
 use util::task_state;
 use util::workqueue::WorkQueue;
@@ -101,6 +108,15 @@ pub struct LayoutTaskData {
     /// The size of the viewport.
     pub screen_size: Size2D<Au>,
 
+    /// The size of the initial viewport, before any @viewport constraints are applied.
+    pub initial_viewport: Size2D<Au>,
+
+    /// Whether a stylesheet with @viewport rules was added since the viewport was last constrained.
+    pub viewport_rules_changed: bool,
+
+    /// The number of device pixels in each CSS pixel.
+    pub device_pixel_ratio: f32,
+
     /// The root stacking context.
     pub stacking_context: Option<Arc<StackingContext>>,
 
@@ -123,6 +139,18 @@ pub struct LayoutTaskData {
     /// A queued response for the content boxes of a node.
     pub content_boxes_response: Vec<Rect<Au>>,
 
+    /// A queued response for the box model of a node.
+    pub box_model_response: Option<BoxModel>,
+
+    /// A queued response for the style rules that match a node.
+    pub matched_rules_response: Vec<MatchedRule>,
+
+    /// A queued response for where the caret of a text field is drawn.
+    pub caret_rect_response: Option<Rect<Au>>,
+
+    /// A queued response for the size of the area the page can be scrolled over.
+    pub scroll_area_response: Size2D<Au>,
+
     /// The list of currently-running animations.
     pub running_animations: Vec<Animation>,
 
@@ -136,6 +164,15 @@ pub struct LayoutTaskData {
     /// A counter for epoch messages
     epoch: Epoch,
 
+    /// The number of times web fonts that this layout task uses have loaded, which font
+    /// contexts check to know when the fonts they found for a family may no longer be the
+    /// right ones.
+    pub web_font_epoch: usize,
+
+    /// The web font families that have loaded since the last reflow, whose text is styled and
+    /// laid out again in the next one.
+    pub loaded_web_font_families: HashSet<LowercaseString>,
+
     /// The position and size of the visible rect for each layer. We do not build display lists
     /// for any areas more than `DISPLAY_PORT_SIZE_FACTOR` screens away from this area.
     pub visible_rects: Arc<HashMap<LayerId, Rect<Au>, DefaultState<FnvHasher>>>,
@@ -164,6 +201,13 @@ pub struct LayoutTask {
     /// The channel on which the image cache can send messages to ourself.
     image_cache_sender: ImageCacheChan,
 
+    /// The port on which we hear the families of web fonts that have loaded.
+    font_cache_receiver: Receiver<LowercaseString>,
+
+    /// The channel on which the font cache can tell us the families of web fonts that have
+    /// loaded.
+    font_cache_sender: Sender<LowercaseString>,
+
     /// The channel on which we or others can send messages to ourselves.
     pub chan: LayoutChan,
 
@@ -312,6 +356,7 @@ impl LayoutTask {
         // Create the channel on which new animations can be sent.
         let (new_animations_sender, new_animations_receiver) = channel();
         let (image_cache_sender, image_cache_receiver) = channel();
+        let (font_cache_sender, font_cache_receiver) = channel();
         let (canvas_layers_sender, canvas_layers_receiver) = channel();
 
         LayoutTask {
@@ -332,6 +377,8 @@ impl LayoutTask {
             first_reflow: Cell::new(true),
             image_cache_receiver: image_cache_receiver,
             image_cache_sender: ImageCacheChan(image_cache_sender),
+            font_cache_receiver: font_cache_receiver,
+            font_cache_sender: font_cache_sender,
             canvas_layers_receiver: canvas_layers_receiver,
             canvas_layers_sender: canvas_layers_sender,
             rw_data: Arc::new(Mutex::new(
@@ -340,6 +387,9 @@ impl LayoutTask {
                     image_cache_task: image_cache_task,
                     constellation_chan: constellation_chan,
                     screen_size: screen_size,
+                    initial_viewport: screen_size,
+                    viewport_rules_changed: false,
+                    device_pixel_ratio: 1.0,
                     stacking_context: None,
                     stylist: box Stylist::new(device),
                     parallel_traversal: parallel_traversal,
@@ -347,11 +397,17 @@ impl LayoutTask {
                     generation: 0,
                     content_box_response: Rect::zero(),
                     content_boxes_response: Vec::new(),
+                    box_model_response: None,
+                    matched_rules_response: Vec::new(),
+                    caret_rect_response: None,
+                    scroll_area_response: Size2D::zero(),
                     running_animations: Vec::new(),
                     visible_rects: Arc::new(HashMap::with_hash_state(Default::default())),
                     new_animations_receiver: new_animations_receiver,
                     new_animations_sender: new_animations_sender,
                     epoch: Epoch(0),
+                    web_font_epoch: 0,
+                    loaded_web_font_families: HashSet::new(),
               })),
         }
     }
@@ -374,12 +430,15 @@ impl LayoutTask {
                                    -> SharedLayoutContext {
         SharedLayoutContext {
             image_cache_task: rw_data.image_cache_task.clone(),
+            pipeline_id: self.id,
             image_cache_sender: self.image_cache_sender.clone(),
             screen_size: rw_data.screen_size.clone(),
             screen_size_changed: screen_size_changed,
+            device_pixel_ratio: rw_data.device_pixel_ratio,
             constellation_chan: rw_data.constellation_chan.clone(),
             layout_chan: self.chan.clone(),
             font_cache_task: self.font_cache_task.clone(),
+            web_font_epoch: rw_data.web_font_epoch,
             canvas_layers_sender: self.canvas_layers_sender.clone(),
             stylist: &*rw_data.stylist,
             url: (*url).clone(),
@@ -400,6 +459,7 @@ impl LayoutTask {
             Pipeline,
             Script,
             ImageCache,
+            FontCache,
         }
 
         let port_to_read = {
@@ -407,10 +467,12 @@ impl LayoutTask {
             let mut port1 = sel.handle(&self.port);
             let mut port2 = sel.handle(&self.pipeline_port);
             let mut port3 = sel.handle(&self.image_cache_receiver);
+            let mut port4 = sel.handle(&self.font_cache_receiver);
             unsafe {
                 port1.add();
                 port2.add();
                 port3.add();
+                port4.add();
             }
             let ret = sel.wait();
             if ret == port1.id() {
@@ -419,6 +481,8 @@ impl LayoutTask {
                 PortToRead::Pipeline
             } else if ret == port3.id() {
                 PortToRead::ImageCache
+            } else if ret == port4.id() {
+                PortToRead::FontCache
             } else {
                 panic!("invalid select result");
             }
@@ -452,6 +516,10 @@ impl LayoutTask {
                 let _ = self.image_cache_receiver.recv().unwrap();
                 self.repaint(possibly_locked_rw_data)
             }
+            PortToRead::FontCache => {
+                let family_name = self.font_cache_receiver.recv().unwrap();
+                self.handle_web_font_loaded(family_name, possibly_locked_rw_data)
+            }
         }
     }
 
@@ -509,6 +577,31 @@ impl LayoutTask {
         true
     }
 
+    /// Records that a font of a web font family has loaded, and asks script for a reflow, in
+    /// which the text that uses the family is styled again, since it may have been laid out in
+    /// a fallback font while the font downloaded. Flows have to be built again for the text to
+    /// be shaped with the new font. Fonts that load before that reflow share it.
+    fn handle_web_font_loaded<'a>(&'a self,
+                                  family_name: LowercaseString,
+                                  possibly_locked_rw_data:
+                                    &mut Option<MutexGuard<'a, LayoutTaskData>>)
+                                  -> bool {
+        let mut rw_data = self.lock_rw_data(possibly_locked_rw_data);
+        let reflow_requested = !rw_data.loaded_web_font_families.is_empty();
+        rw_data.loaded_web_font_families.insert(family_name);
+        while let Ok(family_name) = self.font_cache_receiver.try_recv() {
+            rw_data.loaded_web_font_families.insert(family_name);
+        }
+        rw_data.web_font_epoch += 1;
+        LayoutTask::return_rw_data(possibly_locked_rw_data, rw_data);
+
+        if !reflow_requested {
+            let ScriptControlChan(ref chan) = self.script_chan;
+            chan.send(ConstellationControlMsg::WebFontLoaded(self.id)).unwrap();
+        }
+        true
+    }
+
     /// Receives and dispatches messages from other tasks.
     fn handle_request_helper<'a>(&'a self,
                                  request: Msg,
@@ -569,16 +662,19 @@ impl LayoutTask {
         let mut reports = vec![];
 
         // FIXME(njn): Just measuring the display tree for now.
+        let page = format!("url({}) pipeline({})", self.url, self.id.0);
         let rw_data = self.lock_rw_data(possibly_locked_rw_data);
         let stacking_context = rw_data.stacking_context.as_ref();
         reports.push(Report {
-            path: path!["pages", format!("url({})", self.url), "layout-task", "display-list"],
+            path: path!["pages", page, "layout-task", "display-list"],
+            units: ReportUnits::Bytes,
             size: stacking_context.map_or(0, |sc| sc.heap_size_of_children()),
         });
 
         // The LayoutTask has a context in TLS...
         reports.push(Report {
-            path: path!["pages", format!("url({})", self.url), "layout-task", "local-context"],
+            path: path!["pages", page, "layout-task", "local-context"],
+            units: ReportUnits::Bytes,
             size: heap_size_of_local_context(),
         });
 
@@ -587,8 +683,9 @@ impl LayoutTask {
             let sizes = traversal.heap_size_of_tls(heap_size_of_local_context);
             for (i, size) in sizes.iter().enumerate() {
                 reports.push(Report {
-                    path: path!["pages", format!("url({})", self.url),
+                    path: path!["pages", page,
                                 format!("layout-worker-{}-local-context", i)],
+                    units: ReportUnits::Bytes,
                     size: *size
                 });
             }
@@ -688,10 +785,29 @@ impl LayoutTask {
 
         if mq.evaluate(&rw_data.stylist.device) {
             for font_face in sheet.effective_rules(&rw_data.stylist.device).font_face() {
-                for source in font_face.sources.iter() {
-                    self.font_cache_task.add_web_font(font_face.family.clone(), source.clone());
+                // Text is laid out in fallback fonts until web fonts load if fonts load
+                // asynchronously; otherwise layout waits for each of them.
+                // Either way, we hear when fonts of the family that were evicted have downloaded
+                // again.
+                if opts::get().async_web_fonts {
+                    self.font_cache_task.add_web_font(font_face.family.clone(),
+                                                      font_face.sources.clone(),
+                                                      font_face.unicode_range.clone(),
+                                                      self.font_cache_sender.clone(),
+                                                      None);
+                } else {
+                    let (loaded_sender, loaded_receiver) = channel();
+                    self.font_cache_task.add_web_font(font_face.family.clone(),
+                                                      font_face.sources.clone(),
+                                                      font_face.unicode_range.clone(),
+                                                      self.font_cache_sender.clone(),
+                                                      Some(loaded_sender));
+                    loaded_receiver.recv().unwrap();
                 }
             }
+            if sheet.effective_rules(&rw_data.stylist.device).viewport().next().is_some() {
+                rw_data.viewport_rules_changed = true;
+            }
             rw_data.stylist.add_stylesheet(sheet);
         }
 
@@ -816,6 +932,68 @@ impl LayoutTask {
         rw_data.content_boxes_response = iterator.rects;
     }
 
+    fn process_box_model_request<'a>(&'a self,
+                                     requested_node: TrustedNodeAddress,
+                                     layout_root: &mut FlowRef,
+                                     rw_data: &mut RWGuard<'a>) {
+        let requested_node: OpaqueNode = OpaqueNodeMethods::from_script_node(requested_node);
+        let mut iterator = BoxModelFragmentBorderBoxIterator::new(requested_node);
+        sequential::iterate_through_flow_tree_fragment_border_boxes(layout_root, &mut iterator);
+        rw_data.box_model_response = iterator.box_model;
+    }
+
+    fn process_caret_rect_request<'a>(&'a self,
+                                      requested_node: TrustedNodeAddress,
+                                      offset: usize,
+                                      layout_root: &mut FlowRef,
+                                      rw_data: &mut RWGuard<'a>) {
+        let requested_node: OpaqueNode = OpaqueNodeMethods::from_script_node(requested_node);
+        let mut iterator = CaretRectFragmentBorderBoxIterator::new(requested_node, offset);
+        sequential::iterate_through_flow_tree_fragment_border_boxes(layout_root, &mut iterator);
+        rw_data.caret_rect_response = iterator.rect.or(iterator.fallback_rect);
+    }
+
+    /// The page can be scrolled over the root flow, which is the size of the root layer.
+    fn process_scroll_area_request<'a>(&'a self,
+                                       layout_root: &mut FlowRef,
+                                       rw_data: &mut RWGuard<'a>) {
+        let root_flow = flow::base(&**layout_root);
+        rw_data.scroll_area_response = root_flow.position.size.to_physical(root_flow.writing_mode)
+    }
+
+    fn process_matched_rules_request<'a>(&'a self,
+                                         requested_node: TrustedNodeAddress,
+                                         rw_data: &mut RWGuard<'a>) {
+        let mut node: LayoutJS<Node> = unsafe {
+            LayoutJS::from_trusted_node_address(requested_node)
+        };
+        let node: &mut LayoutNode = unsafe {
+            transmute(&mut node)
+        };
+        let rules = match node.as_element() {
+            Some(element) => {
+                rw_data.stylist.matched_rules(&element).into_iter().map(|matched| {
+                    let declarations = &matched.rule.declarations;
+                    let normal = declarations.normal.iter().map(|declaration| (declaration, false));
+                    let important =
+                        declarations.important.iter().map(|declaration| (declaration, true));
+                    MatchedRule {
+                        stylesheet_url: matched.stylesheet.url.clone(),
+                        line: matched.rule.line,
+                        origin: matched.stylesheet.origin,
+                        selectors: matched.rule.selector_text.clone(),
+                        specificity: matched.specificity,
+                        declarations: normal.chain(important).map(|(declaration, important)| {
+                            (declaration.name().to_owned(), declaration.value(), important)
+                        }).collect(),
+                    }
+                }).collect()
+            }
+            None => vec!(),
+        };
+        rw_data.matched_rules_response = rules;
+    }
+
     fn compute_abs_pos_and_build_display_list<'a>(&'a self,
                                                   data: &Reflow,
                                                   layout_root: &mut FlowRef,
@@ -915,13 +1093,15 @@ impl LayoutTask {
 
         let initial_viewport = data.window_size.initial_viewport;
         let old_screen_size = rw_data.screen_size;
-        let current_screen_size = Size2D::new(Au::from_f32_px(initial_viewport.width.get()),
-                                              Au::from_f32_px(initial_viewport.height.get()));
-        rw_data.screen_size = current_screen_size;
+        let current_initial_viewport = Size2D::new(Au::from_f32_px(initial_viewport.width.get()),
+                                                   Au::from_f32_px(initial_viewport.height.get()));
+        rw_data.device_pixel_ratio = data.window_size.device_pixel_ratio.get();
+
+        if current_initial_viewport != rw_data.initial_viewport || rw_data.viewport_rules_changed {
+            rw_data.initial_viewport = current_initial_viewport;
+            rw_data.viewport_rules_changed = false;
+            rw_data.screen_size = current_initial_viewport;
 
-        // Handle conditions where the entire flow tree is invalid.
-        let screen_size_changed = current_screen_size != old_screen_size;
-        if screen_size_changed {
             // Calculate the actual viewport as per DEVICE-ADAPT § 6
             let device = Device::new(MediaType::Screen, initial_viewport);
             rw_data.stylist.set_device(device);
@@ -942,12 +1122,19 @@ impl LayoutTask {
             }
         }
 
+        // Handle conditions where the entire flow tree is invalid.
+        let screen_size_changed = rw_data.screen_size != old_screen_size;
+
         // If the entire flow tree is invalid, then it will be reflowed anyhow.
         let needs_dirtying = rw_data.stylist.update();
         let needs_reflow = screen_size_changed && !needs_dirtying;
+        let loaded_web_font_families = mem::replace(&mut rw_data.loaded_web_font_families,
+                                                    HashSet::new());
         unsafe {
             if needs_dirtying {
                 LayoutTask::dirty_all_nodes(node);
+            } else if !loaded_web_font_families.is_empty() {
+                LayoutTask::dirty_nodes_using_families(node, &loaded_web_font_families);
             }
         }
         if needs_reflow {
@@ -1009,6 +1196,18 @@ impl LayoutTask {
             ReflowQueryType::ContentBoxesQuery(node) => {
                 self.process_content_boxes_request(node, &mut root_flow, &mut rw_data)
             }
+            ReflowQueryType::BoxModelQuery(node) => {
+                self.process_box_model_request(node, &mut root_flow, &mut rw_data)
+            }
+            ReflowQueryType::MatchedRulesQuery(node) => {
+                self.process_matched_rules_request(node, &mut rw_data)
+            }
+            ReflowQueryType::CaretRectQuery(node, offset) => {
+                self.process_caret_rect_request(node, offset, &mut root_flow, &mut rw_data)
+            }
+            ReflowQueryType::ScrollAreaQuery => {
+                self.process_scroll_area_request(&mut root_flow, &mut rw_data)
+            }
             ReflowQueryType::NoQuery => {}
         }
 
@@ -1198,6 +1397,38 @@ impl LayoutTask {
         }
     }
 
+    /// Marks the nodes whose `font-family` has one of the given families in it as changed, so
+    /// that they are styled again and their flows are built again.
+    unsafe fn dirty_nodes_using_families(node: &mut LayoutNode,
+                                         families: &HashSet<LowercaseString>) {
+        for node in node.traverse_preorder() {
+            let uses_family = match *node.borrow_layout_data() {
+                Some(ref layout_data) => {
+                    layout_data.shared_data.style.as_ref().map_or(false, |style| {
+                        style.get_font().font_family.0.iter().any(|family| {
+                            families.contains(&LowercaseString::new(family.name()))
+                        })
+                    })
+                }
+                None => false,
+            };
+            if !uses_family {
+                continue
+            }
+
+            node.set_changed(true);
+            node.set_dirty(true);
+            let mut ancestor = node.parent_node();
+            while let Some(parent) = ancestor {
+                if parent.has_dirty_descendants() {
+                    break
+                }
+                parent.set_dirty_descendants(true);
+                ancestor = parent.parent_node();
+            }
+        }
+    }
+
     fn reflow_all_nodes(flow: &mut Flow) {
         debug!("reflowing all nodes!");
         flow::mut_base(flow).restyle_damage.insert(REFLOW | REPAINT);
@@ -1308,6 +1539,30 @@ impl LayoutRPC for LayoutRPCImpl {
             Ok(MouseOverResponse(response_list))
         }
     }
+
+    fn box_model(&self) -> BoxModelResponse {
+        let &LayoutRPCImpl(ref rw_data) = self;
+        let rw_data = rw_data.lock().unwrap();
+        BoxModelResponse(rw_data.box_model_response.clone())
+    }
+
+    fn matched_rules(&self) -> MatchedRulesResponse {
+        let &LayoutRPCImpl(ref rw_data) = self;
+        let rw_data = rw_data.lock().unwrap();
+        MatchedRulesResponse(rw_data.matched_rules_response.clone())
+    }
+
+    fn caret_rect(&self) -> CaretRectResponse {
+        let &LayoutRPCImpl(ref rw_data) = self;
+        let rw_data = rw_data.lock().unwrap();
+        CaretRectResponse(rw_data.caret_rect_response)
+    }
+
+    fn scroll_area(&self) -> ScrollAreaResponse {
+        let &LayoutRPCImpl(ref rw_data) = self;
+        let rw_data = rw_data.lock().unwrap();
+        ScrollAreaResponse(rw_data.scroll_area_response)
+    }
 }
 
 struct UnioningFragmentBorderBoxIterator {
@@ -1365,6 +1620,93 @@ impl FragmentBorderBoxIterator for CollectingFragmentBorderBoxIterator {
     }
 }
 
+/// Records the box model of the first fragment of a node.
+struct BoxModelFragmentBorderBoxIterator {
+    node_address: OpaqueNode,
+    box_model: Option<BoxModel>,
+}
+
+impl BoxModelFragmentBorderBoxIterator {
+    fn new(node_address: OpaqueNode) -> BoxModelFragmentBorderBoxIterator {
+        BoxModelFragmentBorderBoxIterator {
+            node_address: node_address,
+            box_model: None,
+        }
+    }
+}
+
+impl FragmentBorderBoxIterator for BoxModelFragmentBorderBoxIterator {
+    fn process(&mut self, fragment: &Fragment, border_box: &Rect<Au>) {
+        let writing_mode = fragment.style.writing_mode;
+        let border = fragment.style.logical_border_width();
+        let padding = fragment.border_padding - border;
+        self.box_model = Some(BoxModel {
+            border_box: *border_box,
+            margin: fragment.margin.to_physical(writing_mode),
+            border: border.to_physical(writing_mode),
+            padding: padding.to_physical(writing_mode),
+            position: fragment.style.get_box().position.to_css_string(),
+        });
+    }
+
+    fn should_process(&mut self, fragment: &Fragment) -> bool {
+        self.box_model.is_none() && fragment.contains_node(self.node_address)
+    }
+}
+
+/// Finds where the caret is drawn in the text of a text field, a number of characters into it.
+/// The caret is a zero-width rect as tall as the line of text it is in.
+struct CaretRectFragmentBorderBoxIterator {
+    node_address: OpaqueNode,
+    /// The offset of the caret in the text of the node.
+    offset: usize,
+    rect: Option<Rect<Au>>,
+    /// The start of the field, for when it has no text the caret can be placed in.
+    fallback_rect: Option<Rect<Au>>,
+}
+
+impl CaretRectFragmentBorderBoxIterator {
+    fn new(node_address: OpaqueNode, offset: usize) -> CaretRectFragmentBorderBoxIterator {
+        CaretRectFragmentBorderBoxIterator {
+            node_address: node_address,
+            offset: offset,
+            rect: None,
+            fallback_rect: None,
+        }
+    }
+}
+
+impl FragmentBorderBoxIterator for CaretRectFragmentBorderBoxIterator {
+    fn process(&mut self, fragment: &Fragment, border_box: &Rect<Au>) {
+        let info = match fragment.specific {
+            SpecificFragmentInfo::ScannedText(ref info) => info,
+            _ => {
+                if self.fallback_rect.is_none() {
+                    self.fallback_rect = Some(Rect::new(border_box.origin,
+                                                        Size2D::new(Au(0),
+                                                                    border_box.size.height)));
+                }
+                return
+            }
+        };
+        // The text run may differ from the text of the node because of `text-transform` and
+        // whitespace compression.
+        let index = info.original_offsets.run_index_for_original_offset(self.offset);
+        if index < info.range.begin() || index > info.range.end() {
+            return
+        }
+        let range = Range::new(info.range.begin(), index - info.range.begin());
+        let advance = info.advance_for_range(&range);
+        self.rect = Some(Rect::new(Point2D::new(border_box.origin.x + advance,
+                                                border_box.origin.y),
+                                   Size2D::new(Au(0), border_box.size.height)));
+    }
+
+    fn should_process(&mut self, fragment: &Fragment) -> bool {
+        self.rect.is_none() && fragment.contains_node(self.node_address)
+    }
+}
+
 // The default computed value for background-color is transparent (see
 // http://dev.w3.org/csswg/css-backgrounds/#background-color). However, we
 // need to propagate the background color from the root HTML/Body
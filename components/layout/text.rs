@@ -6,27 +6,37 @@
 
 #![deny(unsafe_code)]
 
-use fragment::{Fragment, SpecificFragmentInfo, ScannedTextFragmentInfo, UnscannedTextFragmentInfo};
+use fragment::{Fragment, OriginalOffsets, SpecificFragmentInfo, ScannedTextFragmentInfo};
+use fragment::UnscannedTextFragmentInfo;
 use inline::InlineFragments;
 
-use gfx::font::{DISABLE_KERNING_SHAPING_FLAG, FontMetrics, IGNORE_LIGATURES_SHAPING_FLAG};
-use gfx::font::{RunMetrics, ShapingFlags, ShapingOptions};
+use gfx::font::{Font, FontFeature, FontHandleMethods, FontKerning, FontLigatures, FontMetrics};
+use gfx::font::FontTableTag;
+use gfx::font::{IGNORE_LIGATURES_SHAPING_FLAG, RunMetrics, ShapingFlags, ShapingOptions};
+use gfx::font::VERTICAL_SHAPING_FLAG;
 use gfx::font_context::FontContext;
 use gfx::text::glyph::CharIndex;
+use gfx::text::hyphenation::{self, HyphenationMode};
 use gfx::text::text_run::TextRun;
 use gfx::text::util::{self, CompressionMode};
 use std::borrow::ToOwned;
+use std::cmp::{max, min};
 use std::collections::LinkedList;
 use std::mem;
 use std::sync::Arc;
-use style::computed_values::{line_height, text_orientation, text_rendering, text_transform};
-use style::computed_values::{white_space};
+use style::computed_values::{font_variant, font_variant_numeric, line_height, text_orientation};
+use style::computed_values::{font_kerning, hyphens, text_rendering, text_transform};
+use style::computed_values::{tab_size, white_space};
 use style::properties::ComputedValues;
 use style::properties::style_structs::Font as FontStyle;
-use util::geometry::Au;
+use util::geometry::{Au, MAX_AU};
 use util::linked_list::split_off_head;
 use util::logical_geometry::{LogicalSize, WritingMode};
 use util::range::{Range, RangeIndex};
+use util::str::{GraphemeClusterBreaker, is_extended_pictographic, is_regional_indicator};
+
+/// The OpenType feature for small capitals.
+const SMCP: FontTableTag = 0x736D6370;
 
 /// A stack-allocated object for scanning an inline flow into `TextRun`-containing `TextFragment`s.
 pub struct TextRunScanner {
@@ -52,14 +62,14 @@ impl TextRunScanner {
         let mut last_whitespace = true;
         while !fragments.is_empty() {
             // Create a clump.
-            split_first_fragment_at_newline_if_necessary(&mut fragments);
+            split_first_fragment_at_newline_or_tab_if_necessary(&mut fragments);
             self.clump.append(&mut split_off_head(&mut fragments));
             while !fragments.is_empty() && self.clump
                                                .back()
                                                .unwrap()
                                                .can_merge_with_fragment(fragments.front()
                                                                                  .unwrap()) {
-                split_first_fragment_at_newline_if_necessary(&mut fragments);
+                split_first_fragment_at_newline_or_tab_if_necessary(&mut fragments);
                 self.clump.append(&mut split_off_head(&mut fragments));
             }
 
@@ -101,13 +111,22 @@ impl TextRunScanner {
 
         // Concatenate all of the transformed strings together, saving the new character indices.
         let mut mappings: Vec<RunMapping> = Vec::new();
-        let runs = {
+        let (runs, tab_intervals) = {
             let fontgroup;
             let compression;
             let text_transform;
             let letter_spacing;
             let word_spacing;
+            let tab_size;
+            let hyphens;
+            let lang;
             let text_rendering;
+            let font_kerning;
+            let font_ligatures;
+            let small_caps;
+            let features;
+            let vertical;
+            let orientation;
             {
                 let in_fragment = self.clump.front().unwrap();
                 let font_style = in_fragment.style().get_font_arc();
@@ -122,62 +141,133 @@ impl TextRunScanner {
                 text_transform = inherited_text_style.text_transform;
                 letter_spacing = inherited_text_style.letter_spacing;
                 word_spacing = inherited_text_style.word_spacing.unwrap_or(Au(0));
+                tab_size = inherited_text_style.tab_size;
+                hyphens = inherited_text_style.hyphens;
+                lang = in_fragment.style().get_font()._servo_lang.clone();
                 text_rendering = inherited_text_style.text_rendering;
+                font_kerning = in_fragment.style().get_font().font_kerning;
+                font_ligatures = in_fragment.style().get_font().font_variant_ligatures;
+                small_caps = in_fragment.style().get_font().font_variant ==
+                    font_variant::T::small_caps;
+                features = Arc::new(font_features_for_style(in_fragment.style().get_font()));
+                vertical = in_fragment.style().writing_mode.is_vertical();
+                orientation = in_fragment.style().get_inheritedbox().text_orientation;
             }
 
             // First, transform/compress text of all the nodes.
             let (mut run_info_list, mut run_info) = (Vec::new(), RunInfo::new());
             for (fragment_index, in_fragment) in self.clump.iter().enumerate() {
                 let mut mapping = RunMapping::new(&run_info_list[..], &run_info, fragment_index);
-                let text = match in_fragment.specific {
+                let (text, node_offset, changed_range) = match in_fragment.specific {
                     SpecificFragmentInfo::UnscannedText(ref text_fragment_info) => {
-                        &text_fragment_info.text
+                        (&text_fragment_info.text,
+                         text_fragment_info.node_offset,
+                         text_fragment_info.changed_range)
                     }
                     _ => panic!("Expected an unscanned text fragment!"),
                 };
 
                 let (mut start_position, mut end_position) = (0, 0);
-                for character in text.chars() {
-                    // Search for the first font in this font group that contains a glyph for this
-                    // character.
-                    for font_index in 0..fontgroup.fonts.len() {
-                        if font_index < fontgroup.fonts.len() - 1 &&
-                                fontgroup.fonts
-                                         .get(font_index)
-                                         .unwrap()
-                                         .borrow()
-                                         .glyph_index(character)
-                                         .is_none() {
-                            continue
-                        }
+                let mut previous_font_index = None;
+                let mut grapheme_clusters = GraphemeClusterBreaker::new();
+                let mut in_emoji_sequence = false;
+                for (character_index, character) in text.chars().enumerate() {
+                    // Emoji ZWJ sequences, modified emoji and flags are drawn as one glyph by
+                    // the font of the emoji they start with, so the rest of them go to that font
+                    // whether or not the other fonts have glyphs for their parts.
+                    let continues_cluster = !grapheme_clusters.is_boundary_before(character);
+                    if !continues_cluster {
+                        in_emoji_sequence = is_extended_pictographic(character) ||
+                            is_regional_indicator(character)
+                    }
 
-                        // We found the font we want to use. Now, if necessary, flush the mapping
-                        // we were building up.
-                        if run_info.font_index != font_index {
-                            if run_info.text.len() > 0 {
-                                mapping.flush(&mut mappings,
-                                              &mut run_info,
-                                              &**text,
-                                              compression,
-                                              text_transform,
-                                              &mut last_whitespace,
-                                              &mut start_position,
-                                              end_position);
-                                run_info_list.push(run_info);
-                                run_info = RunInfo::new();
-                                mapping = RunMapping::new(&run_info_list[..],
-                                                          &run_info,
-                                                          fragment_index);
+                    // In vertical text, CJK characters are set upright and others are turned
+                    // sideways, unless `text-orientation` sets them all one way. Marks and the
+                    // rest of a cluster go the way of the character they follow.
+                    let upright = if continues_cluster {
+                        run_info.upright
+                    } else {
+                        vertical && match orientation {
+                            text_orientation::T::upright => true,
+                            text_orientation::T::mixed => {
+                                util::is_upright_in_vertical_text(character)
                             }
-
-                            run_info.font_index = font_index
+                            text_orientation::T::sideways |
+                            text_orientation::T::sideways_left |
+                            text_orientation::T::sideways_right => false,
+                        }
+                    };
+
+                    // Use the first font in this font group, which ends with the platform's
+                    // fallback fonts, that is used for this character by its `unicode-range`
+                    // and contains a glyph for it. If none of them do, the character is drawn
+                    // missing in the first font. Combining marks stay in the font of the
+                    // character they follow if it has them, so that clusters aren't split.
+                    let has_glyph = |font_index: usize| {
+                        let font = fontgroup.font(font_index);
+                        let font = font.borrow();
+                        font.covers(character) && font.glyph_index(character).is_some()
+                    };
+                    let font_index = match previous_font_index {
+                        Some(index) if continues_cluster && in_emoji_sequence => index,
+                        Some(index) if util::is_combining_char(character) && has_glyph(index) => {
+                            index
+                        }
+                        _ => font_context.font_with_glyph(&*fontgroup, character).unwrap_or(0),
+                    };
+                    previous_font_index = Some(font_index);
+
+                    // Small capitals are synthesized for lowercase letters in fonts without
+                    // their own. Characters without case stay in the run they're in.
+                    let synthesize_small_caps = if !small_caps {
+                        false
+                    } else if character.is_lowercase() {
+                        let font = fontgroup.font(font_index);
+                        let mut font = font.borrow_mut();
+                        !font.has_gsub_feature(SMCP)
+                    } else if character.is_uppercase() {
+                        false
+                    } else {
+                        run_info.synthesize_small_caps
+                    };
+
+                    // If the font changes, flush the mapping we were building up, so that a new
+                    // text run is started.
+                    if run_info.font_index != font_index ||
+                            run_info.synthesize_small_caps != synthesize_small_caps ||
+                            run_info.upright != upright {
+                        if run_info.text.len() > 0 {
+                            mapping.flush(&mut mappings,
+                                          &mut run_info,
+                                          &**text,
+                                          compression,
+                                          text_transform,
+                                          &mut last_whitespace,
+                                          &mut start_position,
+                                          end_position);
+                            run_info_list.push(run_info);
+                            run_info = RunInfo::new();
+                            mapping = RunMapping::new(&run_info_list[..],
+                                                      &run_info,
+                                                      fragment_index);
                         }
 
+                        run_info.font_index = font_index;
+                        run_info.synthesize_small_caps = synthesize_small_caps;
+                        run_info.upright = upright
+                    }
 
-                        // Consume this character.
-                        end_position += character.len_utf8();
-                        break
+                    // Only the runs with changed text look for the glyphs of their previous text
+                    // to reuse.
+                    if let Some((changed_start, changed_end)) = changed_range {
+                        let node_index = node_offset + character_index;
+                        if changed_start <= node_index + 1 && node_index <= changed_end {
+                            run_info.edited = true
+                        }
                     }
+
+                    // Consume this character.
+                    end_position += character.len_utf8();
                 }
 
                 // If the mapping is zero-length, don't flush it.
@@ -210,19 +300,80 @@ impl TextRunScanner {
             }
             if text_rendering == text_rendering::T::optimizespeed {
                 flags.insert(IGNORE_LIGATURES_SHAPING_FLAG);
-                flags.insert(DISABLE_KERNING_SHAPING_FLAG)
             }
+            // `text-rendering: optimizeSpeed` only turns kerning off if `font-kerning` leaves it
+            // up to us.
+            let kerning = match font_kerning {
+                font_kerning::T::auto if text_rendering == text_rendering::T::optimizespeed => {
+                    FontKerning::None
+                }
+                font_kerning::T::auto => FontKerning::Auto,
+                font_kerning::T::normal => FontKerning::Normal,
+                font_kerning::T::none => FontKerning::None,
+            };
+            let ligatures = FontLigatures {
+                common: font_ligatures.common,
+                discretionary: font_ligatures.discretionary,
+                historical: font_ligatures.historical,
+                contextual: font_ligatures.contextual,
+            };
             let options = ShapingOptions {
                 letter_spacing: letter_spacing,
                 word_spacing: word_spacing,
                 flags: flags,
+                kerning: kerning,
+                ligatures: ligatures,
+                features: features,
+            };
+
+            // Words are only hyphenated automatically if there are patterns for their language.
+            let hyphenation = match hyphens {
+                hyphens::T::none => HyphenationMode::None,
+                hyphens::T::manual => HyphenationMode::Manual,
+                hyphens::T::auto => {
+                    let hyphenator = lang.0.as_ref().and_then(|lang| {
+                        hyphenation::hyphenator_for_lang(lang.as_slice())
+                    });
+                    match hyphenator {
+                        Some(hyphenator) => HyphenationMode::Auto(hyphenator),
+                        None => HyphenationMode::Manual,
+                    }
+                }
             };
 
-            // FIXME(https://github.com/rust-lang/rust/issues/23338)
-            run_info_list.into_iter().map(|run_info| {
-                let mut font = fontgroup.fonts.get(run_info.font_index).unwrap().borrow_mut();
-                Arc::new(box TextRun::new(&mut *font, run_info.text, &options))
-            }).collect::<Vec<_>>()
+            let mut runs = Vec::with_capacity(run_info_list.len());
+            let mut tab_intervals = Vec::with_capacity(run_info_list.len());
+            for run_info in run_info_list.into_iter() {
+                let mut font = fontgroup.font(run_info.font_index);
+                if run_info.synthesize_small_caps {
+                    let small_caps_font = font_context.get_small_caps_font(&*font.borrow());
+                    if let Some(small_caps_font) = small_caps_font {
+                        font = small_caps_font
+                    }
+                }
+                // FIXME(https://github.com/rust-lang/rust/issues/23338)
+                let mut font = font.borrow_mut();
+                // Upright text is shaped vertically, so that its glyphs go down the page.
+                let mut options = options.clone();
+                if run_info.upright {
+                    options.flags.insert(VERTICAL_SHAPING_FLAG);
+                }
+                tab_intervals.push(tab_interval(&mut *font, &options, tab_size));
+                let mut run = TextRun::new(&mut *font,
+                                           run_info.text,
+                                           &options,
+                                           hyphenation,
+                                           run_info.edited);
+                // Marks at the start of a run fell back to a different font from the character
+                // they are on, which ends the run before.
+                if run.leading_marks > CharIndex(0) {
+                    if let Some(previous_run) = runs.last() {
+                        run.base_advance = previous_run.last_grapheme_cluster_advance()
+                    }
+                }
+                runs.push(Arc::new(box run))
+            }
+            (runs, tab_intervals)
         };
 
         // Make new fragments with the runs and adjusted text indices.
@@ -243,8 +394,13 @@ impl TextRunScanner {
                     run.text.char_at_reverse(mapping.byte_range.end()) == '\n';
                 if requires_line_break_afterward_if_wrapping_on_newlines {
                     mapping.char_range.extend_by(CharIndex(-1));
+                    mapping.original_offsets.pop();
                 }
 
+                // Tabs are fragments of their own, which inline layout advances to tab stops.
+                let is_tab = &run.text[mapping.byte_range.begin()..mapping.byte_range.end()] ==
+                    "\t";
+
                 let text_size = old_fragment.border_box.size;
                 let mut new_text_fragment_info = box ScannedTextFragmentInfo::new(
                     run,
@@ -252,6 +408,26 @@ impl TextRunScanner {
                     text_size,
                     requires_line_break_afterward_if_wrapping_on_newlines);
 
+                new_text_fragment_info.spellcheck = old_fragment.is_spellchecked();
+                if is_tab {
+                    new_text_fragment_info.tab_interval =
+                        Some(tab_intervals[mapping.text_run_index]);
+                }
+                // The offsets are from the start of the unscanned fragment, which may be part of
+                // the text of its node.
+                let node_offset = match old_fragment.specific {
+                    SpecificFragmentInfo::UnscannedText(ref text_fragment_info) => {
+                        text_fragment_info.node_offset
+                    }
+                    _ => 0,
+                };
+                new_text_fragment_info.original_offsets = Arc::new(OriginalOffsets {
+                    run_start: mapping.char_range.begin(),
+                    offsets: mapping.original_offsets.iter().map(|&offset| {
+                        offset + node_offset
+                    }).collect(),
+                });
+
                 let new_metrics = new_text_fragment_info.run.metrics_for_range(&mapping.char_range);
                 let writing_mode = old_fragment.style.writing_mode;
                 let bounding_box_size = bounding_box_for_run_metrics(&new_metrics, writing_mode);
@@ -277,15 +453,17 @@ fn bounding_box_for_run_metrics(metrics: &RunMetrics, writing_mode: WritingMode)
     // This will be a reminder to update the code below.
     let dummy: Option<text_orientation::T> = None;
     match dummy {
+        Some(text_orientation::T::mixed) |
+        Some(text_orientation::T::upright) |
         Some(text_orientation::T::sideways_right) |
         Some(text_orientation::T::sideways_left) |
         Some(text_orientation::T::sideways) |
         None => {}
     }
 
-    // In vertical sideways or horizontal upright text,
-    // the "width" of text metrics is always inline
-    // This will need to be updated when other text orientations are supported.
+    // In vertical sideways or horizontal text, the "width" of text metrics is always inline.
+    // Upright text in vertical writing modes is shaped vertically, so its "width" is the sum of
+    // its vertical advances, which is inline too.
     LogicalSize::new(
         writing_mode,
         metrics.bounding_box.size.width,
@@ -301,10 +479,96 @@ pub fn font_metrics_for_style(font_context: &mut FontContext, font_style: Arc<Fo
                               -> FontMetrics {
     let fontgroup = font_context.get_layout_font_group_for_style(font_style);
     // FIXME(https://github.com/rust-lang/rust/issues/23338)
-    let font = fontgroup.fonts[0].borrow();
+    let font = fontgroup.font(0);
+    let font = font.borrow();
     font.metrics.clone()
 }
 
+/// Returns a copy of `run`, which text in `font_style` was shaped into, in which the word that
+/// has a hyphenation point at `index` is shaped again as two, because a line breaks there.
+/// Returns `run` itself if the font it was shaped with can't be found.
+pub fn split_word_at_hyphenation_point(font_context: &mut FontContext,
+                                       font_style: Arc<FontStyle>,
+                                       run: &Arc<Box<TextRun>>,
+                                       index: CharIndex)
+                                       -> Arc<Box<TextRun>> {
+    let fontgroup = font_context.get_layout_font_group_for_style(font_style);
+    for font_index in 0..fontgroup.len() {
+        let mut font = fontgroup.font(font_index);
+        if font.borrow().handle.template().identifier != run.font_template.identifier {
+            continue
+        }
+        // Lowercase letters drawn as small capitals are shaped with a smaller copy of the font.
+        if font.borrow().actual_pt_size != run.actual_pt_size {
+            let small_caps_font = font_context.get_small_caps_font(&*font.borrow());
+            match small_caps_font {
+                Some(ref small_caps_font) if small_caps_font.borrow().actual_pt_size ==
+                        run.actual_pt_size => {}
+                Some(_) | None => continue,
+            }
+            font = small_caps_font.unwrap()
+        }
+        // FIXME(https://github.com/rust-lang/rust/issues/23338)
+        let mut font = font.borrow_mut();
+        return Arc::new(box run.split_word_at_hyphenation_point(&mut *font, index))
+    }
+    run.clone()
+}
+
+/// Returns the OpenType features that the `font-variant-*` and `font-feature-settings`
+/// properties of the given style ask for, in the order they should be applied. Ligatures are
+/// passed to the shaper on their own, and come before all of these.
+///
+/// http://dev.w3.org/csswg/css-fonts/#font-feature-resolution
+fn font_features_for_style(font_style: &FontStyle) -> Vec<FontFeature> {
+    fn tag(name: &str) -> u32 {
+        name.bytes().fold(0, |tag, byte| tag << 8 | byte as u32)
+    }
+
+    let mut features = vec!();
+    if font_style.font_variant == font_variant::T::small_caps {
+        features.push(FontFeature {
+            tag: SMCP,
+            value: 1,
+        });
+    }
+
+    let numeric = font_style.font_variant_numeric;
+    let numeric_features = [
+        match numeric.figures {
+            Some(font_variant_numeric::Figures::Lining) => Some("lnum"),
+            Some(font_variant_numeric::Figures::Oldstyle) => Some("onum"),
+            None => None,
+        },
+        match numeric.spacing {
+            Some(font_variant_numeric::Spacing::Proportional) => Some("pnum"),
+            Some(font_variant_numeric::Spacing::Tabular) => Some("tnum"),
+            None => None,
+        },
+        match numeric.fractions {
+            Some(font_variant_numeric::Fractions::Diagonal) => Some("frac"),
+            Some(font_variant_numeric::Fractions::Stacked) => Some("afrc"),
+            None => None,
+        },
+        if numeric.ordinal { Some("ordn") } else { None },
+        if numeric.slashed_zero { Some("zero") } else { None },
+    ];
+    for name in numeric_features.iter().filter_map(|name| *name) {
+        features.push(FontFeature {
+            tag: tag(name),
+            value: 1,
+        });
+    }
+
+    for feature in font_style.font_feature_settings.0.iter() {
+        features.push(FontFeature {
+            tag: tag(&feature.tag),
+            value: feature.value,
+        });
+    }
+    features
+}
+
 /// Returns the line block-size needed by the given computed style and font size.
 pub fn line_height_from_style(style: &ComputedValues, metrics: &FontMetrics) -> Au {
     let font_size = style.get_font().font_size;
@@ -315,7 +579,27 @@ pub fn line_height_from_style(style: &ComputedValues, metrics: &FontMetrics) ->
     }
 }
 
-fn split_first_fragment_at_newline_if_necessary(fragments: &mut LinkedList<Fragment>) {
+/// Returns the distance between the tab stops of text in `font`, for the given `tab-size` and
+/// letter and word spacing.
+fn tab_interval(font: &mut Font, options: &ShapingOptions, tab_size: tab_size::T) -> Au {
+    match tab_size {
+        tab_size::T::Spaces(spaces) => {
+            let space_advance = match font.glyph_index(' ') {
+                Some(glyph) => Au::from_f64_px(font.glyph_h_advance(glyph)),
+                None => Au(0),
+            };
+            let spacing = options.letter_spacing.unwrap_or(Au(0)) + options.word_spacing;
+            // Authors can ask for any number of spaces, so clamp the interval to what fits.
+            let interval = (space_advance + spacing).0 as i64 * spaces as i64;
+            Au(min(max(interval, 0), MAX_AU.0 as i64) as i32)
+        }
+        tab_size::T::Length(length) => length,
+    }
+}
+
+/// Splits preformatted text after its first newline, and around its first tab, so that inline
+/// layout can break lines after newlines and advance tabs to the tab stops on their lines.
+fn split_first_fragment_at_newline_or_tab_if_necessary(fragments: &mut LinkedList<Fragment>) {
     if fragments.len() < 1 {
         return
     }
@@ -323,6 +607,8 @@ fn split_first_fragment_at_newline_if_necessary(fragments: &mut LinkedList<Fragm
     let new_fragment = {
         let mut first_fragment = fragments.front_mut().unwrap();
         let string_before;
+        let node_offset;
+        let changed_range;
         {
             let unscanned_text_fragment_info = match first_fragment.specific {
                 SpecificFragmentInfo::UnscannedText(ref mut unscanned_text_fragment_info) => {
@@ -335,21 +621,32 @@ fn split_first_fragment_at_newline_if_necessary(fragments: &mut LinkedList<Fragm
                 return
             }
 
-            let position = match unscanned_text_fragment_info.text.find('\n') {
-                Some(position) if position < unscanned_text_fragment_info.text.len() - 1 => {
-                    position
+            let split_position = {
+                let text = &unscanned_text_fragment_info.text;
+                match text.find(|c| c == '\n' || c == '\t') {
+                    // Text before a tab is split off from it, and the tab from the text after it.
+                    Some(position) if position > 0 && text.as_bytes()[position] == b'\t' => {
+                        position
+                    }
+                    Some(position) if position < text.len() - 1 => position + 1,
+                    Some(_) | None => return,
                 }
-                Some(_) | None => return,
             };
 
             string_before =
-                box unscanned_text_fragment_info.text[..(position + 1)].to_owned();
+                box unscanned_text_fragment_info.text[..split_position].to_owned();
             unscanned_text_fragment_info.text =
-                box unscanned_text_fragment_info.text[(position + 1)..].to_owned();
+                box unscanned_text_fragment_info.text[split_position..].to_owned();
+            node_offset = unscanned_text_fragment_info.node_offset;
+            unscanned_text_fragment_info.node_offset = node_offset + string_before.chars().count();
+            changed_range = unscanned_text_fragment_info.changed_range;
         }
         first_fragment.transform(first_fragment.border_box.size,
                                  SpecificFragmentInfo::UnscannedText(UnscannedTextFragmentInfo {
             text: string_before,
+            spellcheck: first_fragment.is_spellchecked(),
+            node_offset: node_offset,
+            changed_range: changed_range,
         }))
     };
 
@@ -364,6 +661,13 @@ struct RunInfo {
     font_index: usize,
     /// A cached copy of the number of Unicode characters in the text run.
     character_length: usize,
+    /// Whether the text is lowercase letters to draw as small capitals with a smaller font.
+    synthesize_small_caps: bool,
+    /// Whether the text is set upright in vertical text, rather than turned sideways.
+    upright: bool,
+    /// Whether the text has characters that changed since their node was last laid out, or that
+    /// are next to ones that were removed.
+    edited: bool,
 }
 
 impl RunInfo {
@@ -372,13 +676,16 @@ impl RunInfo {
             text: String::new(),
             font_index: 0,
             character_length: 0,
+            synthesize_small_caps: false,
+            upright: false,
+            edited: false,
         }
     }
 }
 
 /// A mapping from a portion of an unscanned text fragment to the text run we're going to create
 /// for it.
-#[derive(Copy, Clone, Debug)]
+#[derive(Clone, Debug)]
 struct RunMapping {
     /// The range of characters within the text fragment.
     char_range: Range<CharIndex>,
@@ -388,6 +695,9 @@ struct RunMapping {
     old_fragment_index: usize,
     /// The index of the text run we're going to create.
     text_run_index: usize,
+    /// For each character in `char_range`, the index of the character in the text fragment that
+    /// it came from.
+    original_offsets: Vec<usize>,
 }
 
 impl RunMapping {
@@ -402,6 +712,7 @@ impl RunMapping {
             byte_range: Range::new(0, 0),
             old_fragment_index: fragment_index,
             text_run_index: run_info_list.len(),
+            original_offsets: vec!(),
         }
     }
 
@@ -420,12 +731,24 @@ impl RunMapping {
         *last_whitespace = util::transform_text(&text[(*start_position)..end_position],
                                                 compression,
                                                 *last_whitespace,
-                                                &mut run_info.text);
+                                                &mut run_info.text,
+                                                &mut self.original_offsets);
+        let start_offset = text[..(*start_position)].chars().count();
+        for original_offset in self.original_offsets.iter_mut() {
+            *original_offset += start_offset
+        }
 
         // Account for `text-transform`. (Confusingly, this is not handled in "text
-        // transformation" above, but we follow Gecko in the naming.)
+        // transformation" above, but we follow Gecko in the naming.) Synthesized small
+        // capitals are uppercase letters in a smaller font.
+        let text_transform = if run_info.synthesize_small_caps {
+            text_transform::T::uppercase
+        } else {
+            text_transform
+        };
         let character_count = apply_style_transform_if_necessary(&mut run_info.text,
                                                                  old_byte_length,
+                                                                 &mut self.original_offsets,
                                                                  text_transform);
 
         run_info.character_length = run_info.character_length + character_count;
@@ -444,67 +767,80 @@ impl RunMapping {
 }
 
 
-/// Accounts for `text-transform`.
+/// Accounts for `text-transform`. `original_offsets` holds the offset in the original text of
+/// each character after `first_character_position`, and is updated to match the transformed
+/// text, in which one character may become several (for example, `ß` becomes `SS`).
 ///
-/// FIXME(#4311, pcwalton): Case mapping can change length of the string; case mapping should
-/// be language-specific; `full-width`; use graphemes instead of characters.
+/// FIXME(#4311, pcwalton): Case mapping should be language-specific; `full-width`; use graphemes
+/// instead of characters.
 fn apply_style_transform_if_necessary(string: &mut String,
                                       first_character_position: usize,
+                                      original_offsets: &mut Vec<usize>,
                                       text_transform: text_transform::T)
                                       -> usize {
-    match text_transform {
-        text_transform::T::none => string[first_character_position..].chars().count(),
-        text_transform::T::uppercase => {
-            let original = string[first_character_position..].to_owned();
-            string.truncate(first_character_position);
-            let mut count = 0;
-            for character in original.chars() {
-                string.push(character.to_uppercase().next().unwrap());
-                count += 1;
+    if text_transform == text_transform::T::none {
+        return original_offsets.len()
+    }
+
+    let original = string[first_character_position..].to_owned();
+    string.truncate(first_character_position);
+    let old_offsets = mem::replace(original_offsets, Vec::with_capacity(original.len()));
+
+    // FIXME(pcwalton): This may not always be correct in the case of something like
+    // `f<span>oo</span>`.
+    let mut capitalize_next_letter = true;
+    for (character, original_offset) in original.chars().zip(old_offsets.into_iter()) {
+        match text_transform {
+            text_transform::T::none => unreachable!(),
+            text_transform::T::uppercase => {
+                push_mapped_characters(string,
+                                       original_offsets,
+                                       character.to_uppercase(),
+                                       original_offset)
             }
-            count
-        }
-        text_transform::T::lowercase => {
-            let original = string[first_character_position..].to_owned();
-            string.truncate(first_character_position);
-            let mut count = 0;
-            for character in original.chars() {
-                string.push(character.to_lowercase().next().unwrap());
-                count += 1;
+            text_transform::T::lowercase => {
+                push_mapped_characters(string,
+                                       original_offsets,
+                                       character.to_lowercase(),
+                                       original_offset)
             }
-            count
-        }
-        text_transform::T::capitalize => {
-            let original = string[first_character_position..].to_owned();
-            string.truncate(first_character_position);
-
-            // FIXME(pcwalton): This may not always be correct in the case of something like
-            // `f<span>oo</span>`.
-            let mut capitalize_next_letter = true;
-            let mut count = 0;
-            for character in original.chars() {
-                count += 1;
-
-                // FIXME(#4311, pcwalton): Should be the CSS/Unicode notion of a *typographic
-                // letter unit*, not an *alphabetic* character:
-                //
-                //    http://dev.w3.org/csswg/css-text/#typographic-letter-unit
-                if capitalize_next_letter && character.is_alphabetic() {
-                    string.push(character.to_uppercase().next().unwrap());
-                    capitalize_next_letter = false;
-                    continue
-                }
-
+            // FIXME(#4311, pcwalton): Should be the CSS/Unicode notion of a *typographic
+            // letter unit*, not an *alphabetic* character:
+            //
+            //    http://dev.w3.org/csswg/css-text/#typographic-letter-unit
+            text_transform::T::capitalize if capitalize_next_letter &&
+                    character.is_alphabetic() => {
+                push_mapped_characters(string,
+                                       original_offsets,
+                                       character.to_uppercase(),
+                                       original_offset);
+                capitalize_next_letter = false
+            }
+            text_transform::T::capitalize => {
                 string.push(character);
+                original_offsets.push(original_offset);
 
                 // FIXME(#4311, pcwalton): Try UAX29 instead of just whitespace.
                 if character.is_whitespace() {
                     capitalize_next_letter = true
                 }
             }
-
-            count
         }
     }
+
+    original_offsets.len()
+}
+
+/// Pushes the characters that a character was case mapped to, all of which come from the same
+/// offset in the original text.
+fn push_mapped_characters<I>(string: &mut String,
+                             original_offsets: &mut Vec<usize>,
+                             characters: I,
+                             original_offset: usize)
+                             where I: Iterator<Item=char> {
+    for character in characters {
+        string.push(character);
+        original_offsets.push(original_offset)
+    }
 }
 
@@ -243,6 +243,7 @@ impl<'a> PostorderDomTraversal for ConstructFlows<'a> {
 
         unsafe {
             node.set_changed(false);
+            node.clear_changed_text_range();
             node.set_dirty(false);
             node.set_dirty_siblings(false);
             node.set_dirty_descendants(false);
@@ -60,6 +60,7 @@ use script::layout_interface::LayoutChan;
 use smallvec::VecLike;
 use msg::constellation_msg::{PipelineId, SubpageId};
 use util::str::is_whitespace;
+use std::ascii::AsciiExt;
 use std::borrow::ToOwned;
 use std::cell::{Ref, RefMut};
 use std::marker::PhantomData;
@@ -296,6 +297,13 @@ impl<'ln> LayoutNode<'ln> {
         self.node.set_flag(HAS_DIRTY_DESCENDANTS, value)
     }
 
+    /// Forgets which characters of the text of this node changed, once it has been laid out.
+    pub unsafe fn clear_changed_text_range(&self) {
+        if let Some(text) = TextCast::to_layout_js(&self.node) {
+            CharacterDataCast::from_layout_js(&text).clear_changed_range_for_layout()
+        }
+    }
+
     /// Borrows the layout data without checks.
     #[inline(always)]
     pub unsafe fn borrow_layout_data_unchecked(&self) -> *const Option<LayoutDataWrapper> {
@@ -383,6 +391,16 @@ impl<'le> LayoutElement<'le> {
         };
         style
     }
+
+    /// Whether the element is in the `:active` state, which the selectors crate this tree uses
+    /// can't match yet.
+    #[inline]
+    pub fn get_active_state(&self) -> bool {
+        let node = NodeCast::from_layout_js(&self.element);
+        unsafe {
+            (*node.unsafe_get()).get_active_state_for_layout()
+        }
+    }
 }
 
 impl<'le> ::selectors::Element for LayoutElement<'le> {
@@ -949,6 +967,66 @@ impl<'ln> ThreadSafeLayoutNode<'ln> {
         panic!("not text!")
     }
 
+    /// Returns the characters of the text of this node that changed since it was last laid out,
+    /// as the index of the first one and the index after the last one, or `None` if none did.
+    /// Form controls don't keep track of which characters of their value changed, so all of them
+    /// count as changed when it does.
+    pub fn changed_text_range(&self) -> Option<(usize, usize)> {
+        if self.pseudo != PseudoElementType::Normal {
+            return None
+        }
+
+        let this = unsafe { self.get_jsmanaged() };
+        if let Some(text) = TextCast::to_layout_js(this) {
+            return unsafe { CharacterDataCast::from_layout_js(&text).changed_range_for_layout() }
+        }
+        if !self.node.has_changed() {
+            return None
+        }
+        let value = if let Some(input) = HTMLInputElementCast::to_layout_js(this) {
+            unsafe { input.get_value_for_layout() }
+        } else if let Some(area) = HTMLTextAreaElementCast::to_layout_js(this) {
+            unsafe { area.get_value_for_layout() }
+        } else {
+            return None
+        };
+        Some((0, value.chars().count()))
+    }
+
+    /// Returns true if the text of this node is editable and its misspelled words should be
+    /// underlined, according to the `contenteditable` and `spellcheck` attributes of the node
+    /// and its ancestors.
+    ///
+    /// https://html.spec.whatwg.org/multipage/#spelling-and-grammar-checking
+    pub fn is_spellchecked(&self) -> bool {
+        if self.pseudo != PseudoElementType::Normal {
+            return false
+        }
+
+        let (mut editable, mut spellcheck) = (None, None);
+        let mut node = Some(self.node);
+        while let Some(current) = node {
+            if let Some(element) = current.as_element() {
+                if editable.is_none() {
+                    editable = match current.type_id() {
+                        NodeTypeId::Element(ElementTypeId::HTMLElement(
+                                HTMLElementTypeId::HTMLTextAreaElement)) => Some(true),
+                        _ => {
+                            let contenteditable = Atom::from_slice("contenteditable");
+                            true_or_false_attribute(element.get_attr(&ns!(""), &contenteditable))
+                        }
+                    };
+                }
+                if spellcheck.is_none() {
+                    let attribute = element.get_attr(&ns!(""), &Atom::from_slice("spellcheck"));
+                    spellcheck = true_or_false_attribute(attribute);
+                }
+            }
+            node = current.parent_node();
+        }
+        editable.unwrap_or(false) && spellcheck.unwrap_or(true)
+    }
+
     /// If this is an image element, returns its URL. If this is not an image element, fails.
     ///
     /// FIXME(pcwalton): Don't copy URLs.
@@ -960,6 +1038,11 @@ impl<'ln> ThreadSafeLayoutNode<'ln> {
         }
     }
 
+    /// If this is an image element, returns the text to show in place of the image, if any.
+    pub fn image_alt(&self) -> Option<String> {
+        self.as_element().get_attr(&ns!(""), &atom!("alt")).map(|alt| alt.to_owned())
+    }
+
     pub fn renderer(&self) -> Option<Sender<CanvasMsg>> {
         unsafe {
             let canvas_element = HTMLCanvasElementCast::to_layout_js(self.get_jsmanaged());
@@ -1047,6 +1130,16 @@ impl<'a> Iterator for ThreadSafeLayoutNodeChildrenIterator<'a> {
     }
 }
 
+/// Returns the state of an enumerated attribute with the keywords `true` (or the empty string) and
+/// `false`, or `None` if it is missing or invalid.
+fn true_or_false_attribute(value: Option<&str>) -> Option<bool> {
+    match value {
+        Some(value) if value.is_empty() || value.eq_ignore_ascii_case("true") => Some(true),
+        Some(value) if value.eq_ignore_ascii_case("false") => Some(false),
+        Some(_) | None => None,
+    }
+}
+
 /// A wrapper around elements that ensures layout can only ever access safe properties and cannot
 /// race on elements.
 pub struct ThreadSafeLayoutElement<'le> {
@@ -15,7 +15,7 @@ use std::fmt;
 use constellation_msg::PipelineId;
 
 /// A newtype struct for denoting the age of messages; prevents race conditions.
-#[derive(PartialEq, Eq, Debug, Copy, Clone, PartialOrd, Ord)]
+#[derive(PartialEq, Eq, Debug, Copy, Clone, PartialOrd, Ord, RustcEncodable, RustcDecodable)]
 pub struct Epoch(pub u32);
 
 impl Epoch {
@@ -25,7 +25,7 @@ impl Epoch {
     }
 }
 
-#[derive(PartialEq, Eq, Debug, Copy, Clone)]
+#[derive(PartialEq, Eq, Debug, Copy, Clone, RustcEncodable, RustcDecodable)]
 pub struct FrameTreeId(pub u32);
 
 impl FrameTreeId {
@@ -35,7 +35,7 @@ impl FrameTreeId {
     }
 }
 
-#[derive(Clone, PartialEq, Eq, Copy, Hash)]
+#[derive(Clone, PartialEq, Eq, Copy, Hash, RustcEncodable, RustcDecodable)]
 pub struct LayerId(pub usize, pub u32);
 
 impl Debug for LayerId {
@@ -52,14 +52,14 @@ impl LayerId {
     }
 }
 
-#[derive(Clone, Copy, Debug, PartialEq)]
+#[derive(Clone, Copy, Debug, PartialEq, RustcEncodable, RustcDecodable)]
 pub enum LayerKind {
     Layer2D,
     Layer3D,
 }
 
 /// The scrolling policy of a layer.
-#[derive(Clone, PartialEq, Eq, Copy)]
+#[derive(Clone, PartialEq, Eq, Copy, RustcEncodable, RustcDecodable)]
 pub enum ScrollPolicy {
     /// These layers scroll when the parent receives a scrolling message.
     Scrollable,
@@ -67,6 +67,15 @@ pub enum ScrollPolicy {
     FixedPosition,
 }
 
+/// How a programmatic scroll should reach its destination.
+#[derive(Clone, Copy, PartialEq, Eq, Debug, RustcEncodable, RustcDecodable)]
+pub enum ScrollBehavior {
+    /// Jump straight to the destination.
+    Instant,
+    /// Animate towards the destination over a short period of time.
+    Smooth,
+}
+
 /// All layer-specific information that the painting task sends to the compositor other than the
 /// buffer contents of the layer itself.
 #[derive(Copy, Clone)]
@@ -110,6 +119,10 @@ pub trait PaintListener {
 
     // Notification that the paint task wants to exit.
     fn notify_paint_task_exiting(&mut self, pipeline_id: PipelineId);
+
+    /// Informs the compositor whether the given pipeline is showing animated images that are
+    /// still playing, so that it knows to keep ticking them.
+    fn set_animated_images_running(&mut self, pipeline_id: PipelineId, running: bool);
 }
 
 /// The interface used by the script task to tell the compositor to update its ready state,
@@ -118,10 +131,17 @@ pub trait ScriptListener {
     fn scroll_fragment_point(&mut self,
                              pipeline_id: PipelineId,
                              layer_id: LayerId,
-                             point: Point2D<f32>);
+                             point: Point2D<f32>,
+                             behavior: ScrollBehavior);
     /// Informs the compositor that the title of the page with the given pipeline ID has changed.
     fn set_title(&mut self, pipeline_id: PipelineId, new_title: Option<String>);
     fn close(&mut self);
     fn dup(&mut self) -> Box<ScriptListener+'static>;
     fn send_key_event(&mut self, key: Key, state: KeyState, modifiers: KeyModifiers);
+    /// Outlines a rectangle of the page with the given pipeline ID, for the developer tools, or
+    /// removes the outline if there is no rectangle.
+    fn highlight_rect(&mut self, pipeline_id: PipelineId, rect: Option<Rect<f32>>);
+    /// Tells the embedder where the caret of the focused text field is in the page with the
+    /// given pipeline ID, for the input method editor.
+    fn set_ime_caret_rect(&mut self, pipeline_id: PipelineId, rect: Rect<f32>);
 }
@@ -6,6 +6,7 @@
 //! reduce coupling between these two components.
 
 use compositor_msg::Epoch;
+use euclid::point::Point2D;
 use euclid::rect::Rect;
 use euclid::size::TypedSize2D;
 use euclid::scale_factor::ScaleFactor;
@@ -13,8 +14,11 @@ use hyper::header::Headers;
 use hyper::method::Method;
 use layers::geometry::DevicePixel;
 use png;
+use rustc_serialize::{Decodable, Decoder, Encodable, Encoder};
+use serialize;
 use util::cursor::Cursor;
 use util::geometry::{PagePx, ViewportPx};
+use std::ascii::AsciiExt;
 use std::collections::HashMap;
 use std::sync::mpsc::{channel, Sender, Receiver};
 use style::viewport::ViewportConstraints;
@@ -31,14 +35,14 @@ impl ConstellationChan {
     }
 }
 
-#[derive(PartialEq, Eq, Copy, Clone, Debug)]
+#[derive(PartialEq, Eq, Copy, Clone, Debug, RustcEncodable, RustcDecodable)]
 pub enum IFrameSandboxState {
     IFrameSandboxed,
     IFrameUnsandboxed
 }
 
 // We pass this info to various tasks, so it lives in a separate, cloneable struct.
-#[derive(Clone, Copy)]
+#[derive(Clone, Copy, RustcEncodable, RustcDecodable)]
 pub struct Failure {
     pub pipeline_id: PipelineId,
     pub parent_info: Option<(PipelineId, SubpageId)>,
@@ -57,7 +61,52 @@ pub struct WindowSizeData {
     pub device_pixel_ratio: ScaleFactor<ViewportPx, DevicePixel, f32>,
 }
 
-#[derive(PartialEq, Eq, Copy, Clone)]
+// `DevicePixel` comes from rust-layers and can't be decoded, so the ratio is sent as a number.
+impl Encodable for WindowSizeData {
+    fn encode<S: Encoder>(&self, s: &mut S) -> Result<(), S::Error> {
+        s.emit_struct("WindowSizeData", 3, |s| {
+            try!(s.emit_struct_field("initial_viewport", 0, |s| self.initial_viewport.encode(s)));
+            try!(s.emit_struct_field("visible_viewport", 1, |s| self.visible_viewport.encode(s)));
+            s.emit_struct_field("device_pixel_ratio", 2, |s| {
+                s.emit_f32(self.device_pixel_ratio.get())
+            })
+        })
+    }
+}
+
+impl Decodable for WindowSizeData {
+    fn decode<D: Decoder>(d: &mut D) -> Result<WindowSizeData, D::Error> {
+        d.read_struct("WindowSizeData", 3, |d| {
+            Ok(WindowSizeData {
+                initial_viewport: try!(d.read_struct_field("initial_viewport", 0,
+                                                           Decodable::decode)),
+                visible_viewport: try!(d.read_struct_field("visible_viewport", 1,
+                                                           Decodable::decode)),
+                device_pixel_ratio: ScaleFactor::new(try!(d.read_struct_field(
+                    "device_pixel_ratio", 2, |d| d.read_f32()))),
+            })
+        })
+    }
+}
+
+/// An event from the input method editor, through which the user composes text that can't be
+/// typed key by key.
+#[derive(Clone, Debug, PartialEq, RustcEncodable, RustcDecodable)]
+pub enum ImeEvent {
+    /// The text being composed has changed to the given string, with the caret the given number
+    /// of characters into it.
+    SetComposition(String, usize),
+    /// The composition is finished, and the given string replaces the text that was being
+    /// composed.
+    Commit(String),
+    /// The composition was abandoned, and the text that was being composed is removed.
+    Cancel,
+    /// Asks where the caret of the focused text field is, to place the candidate window next
+    /// to it. The answer comes through `WindowMethods::set_ime_caret_rect`.
+    QueryCaretRect,
+}
+
+#[derive(PartialEq, Eq, Copy, Clone, RustcEncodable, RustcDecodable)]
 pub enum KeyState {
     Pressed,
     Released,
@@ -65,7 +114,7 @@ pub enum KeyState {
 }
 
 //N.B. Based on the glutin key enum
-#[derive(Debug, PartialEq, Eq, Copy, Clone)]
+#[derive(Debug, PartialEq, Eq, Copy, Clone, RustcEncodable, RustcDecodable)]
 pub enum Key {
     Space,
     Apostrophe,
@@ -200,6 +249,18 @@ bitflags! {
     }
 }
 
+impl Encodable for KeyModifiers {
+    fn encode<S: Encoder>(&self, s: &mut S) -> Result<(), S::Error> {
+        s.emit_u8(self.bits())
+    }
+}
+
+impl Decodable for KeyModifiers {
+    fn decode<D: Decoder>(d: &mut D) -> Result<KeyModifiers, D::Error> {
+        Ok(KeyModifiers::from_bits_truncate(try!(d.read_u8())))
+    }
+}
+
 /// Specifies the type of focus event that is sent to a pipeline
 #[derive(Copy, Clone, PartialEq)]
 pub enum FocusType {
@@ -215,11 +276,23 @@ pub enum Msg {
     LoadComplete(PipelineId),
     FrameRect(PipelineId, SubpageId, Rect<f32>),
     LoadUrl(PipelineId, LoadData),
-    ScriptLoadedURLInIFrame(Url, PipelineId, SubpageId, Option<SubpageId>, IFrameSandboxState),
+    ScriptLoadedURLInIFrame(LoadData, PipelineId, SubpageId, Option<SubpageId>,
+                            IFrameSandboxState),
     Navigate(Option<(PipelineId, SubpageId)>, NavigationDirection),
+    /// Moves through the joint session history of the given pipeline's top-level browsing
+    /// context by the given number of entries, as `history.go()` does.
+    TraverseHistory(PipelineId, isize),
+    /// Requests the number of entries in the joint session history of the given pipeline's
+    /// top-level browsing context.
+    GetHistoryLength(PipelineId, Sender<u32>),
+    /// The state of a document that was just frozen, to restore should its session history
+    /// entry have to be reloaded.
+    SaveDocumentState(PipelineId, DocumentState),
     PainterReady(PipelineId),
     ResizedWindow(WindowSizeData),
     KeyEvent(Key, KeyState, KeyModifiers),
+    /// An input method editor event, for the focused document.
+    ImeEvent(ImeEvent),
     /// Requests that the constellation inform the compositor of the title of the pipeline
     /// immediately.
     GetPipelineTitle(PipelineId),
@@ -249,13 +322,194 @@ pub enum Msg {
     IsReadyToSaveImage(HashMap<PipelineId, Epoch>),
     /// Notification that this iframe should be removed.
     RemoveIFrame(PipelineId, SubpageId),
-    /// Favicon detected
-    NewFavicon(Url),
+    /// The document in the given pipeline named an icon.
+    NewFavicon(PipelineId, Url),
     /// <head> tag finished parsing
     HeadParsed,
+    /// Requests permission for the document at the given URL to use a feature.
+    RequestPermission(PipelineId, PermissionName, Url),
+    /// The user's answer to a permission request.
+    PermissionResponse(PipelineId, PermissionName, Url, bool),
+    /// Asks whether the user has already answered a request for permission to use a feature
+    /// from the origin of the given URL.
+    GetPermission(PermissionName, Url, Sender<Option<bool>>),
+    /// Requests that the embedder display a notification.
+    ShowNotification(PipelineId, NotificationId, NotificationData),
+    /// Requests that the embedder stop displaying a notification.
+    CloseNotification(PipelineId, NotificationId),
+    /// The user interacted with a displayed notification.
+    NotificationEvent(PipelineId, NotificationId, NotificationEventType),
+    /// Requests the user's current location from the embedder. The flag asks for the most
+    /// accurate position available, even if it is slower or uses more power.
+    RequestGeolocationPosition(PipelineId, bool),
+    /// The embedder's answer to a position request. `None` means no position is available.
+    GeolocationPositionResponse(PipelineId, Option<GeolocationPosition>),
+    /// A secure document requested content over an insecure connection.
+    MixedContent(PipelineId, Url, MixedContentState),
+    /// Load the given pipeline's document again, letting it load insecure content.
+    AllowMixedContent(PipelineId),
+    /// A document was replaced by an error page because its server's certificate couldn't
+    /// be verified.
+    CertificateError(PipelineId, CertificateError),
+    /// The embedder's answer to a certificate error: whether to trust the host anyway.
+    CertificateErrorResponse(PipelineId, CertificateError, bool),
+    /// Asks the embedder for the user's credentials on behalf of a load, which waits for
+    /// them. `None` means the user declined.
+    RequestCredentials(Option<PipelineId>, AuthenticationRequest, Sender<Option<Credentials>>),
+    /// Starts sending the state of the game controllers to the given channel whenever it
+    /// changes, until the channel is closed.
+    WatchGamepads(Sender<GamepadUpdate>),
+}
+
+/// What became of insecure content that a secure document requested.
+/// https://w3c.github.io/webappsec/specs/mixedcontent/
+#[derive(Clone, Copy, PartialEq, Eq, Debug, RustcEncodable, RustcDecodable)]
+pub enum MixedContentState {
+    /// Active content, such as a script or stylesheet, which wasn't loaded.
+    Blocked,
+    /// Passive content such as an image, or active content the user has allowed, which
+    /// was loaded anyway.
+    Loaded,
+}
+
+/// A server certificate that couldn't be verified.
+#[derive(Clone, Debug, RustcEncodable, RustcDecodable)]
+pub struct CertificateError {
+    /// The URL whose load failed.
+    pub url: Url,
+    /// The host that presented the certificate.
+    pub host: String,
+    /// Why the certificate couldn't be verified.
+    pub reason: String,
+    /// The certificates the server presented, PEM-encoded, starting with its own.
+    pub chain: Vec<String>,
+    /// The SHA-256 fingerprint of the server's own certificate, which trusting the host anyway
+    /// applies to.
+    pub fingerprint: String,
+}
+
+/// A server's or proxy's request for credentials.
+#[derive(Clone, Debug)]
+pub struct AuthenticationRequest {
+    /// The URL being loaded.
+    pub url: Url,
+    /// The host and port of the server or proxy that asked.
+    pub host: String,
+    /// The name the server gives to the protection space the credentials are for.
+    pub realm: String,
+    /// Whether it was a proxy that asked.
+    pub proxy: bool,
+}
+
+/// A user name and password to authenticate with.
+#[derive(Clone, PartialEq, Eq)]
+pub struct Credentials {
+    pub username: String,
+    pub password: String,
+}
+
+/// The features that a document needs the user's permission to use.
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug, RustcEncodable, RustcDecodable)]
+pub enum PermissionName {
+    Notifications,
+    Geolocation,
+}
+
+/// Identifies a notification among those shown by a pipeline.
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug, RustcEncodable, RustcDecodable)]
+pub struct NotificationId(pub u32);
+
+/// The contents of a notification, to be displayed by the embedder.
+/// https://notifications.spec.whatwg.org/#concept-notification
+#[derive(Clone, RustcEncodable, RustcDecodable)]
+pub struct NotificationData {
+    pub title: String,
+    pub body: String,
+    pub tag: String,
+    pub lang: String,
+    pub dir: NotificationDirection,
+    pub icon: Option<Url>,
+}
+
+#[derive(Clone, Copy, PartialEq, Debug, RustcEncodable, RustcDecodable)]
+pub enum NotificationDirection {
+    Auto,
+    Ltr,
+    Rtl,
 }
 
-#[derive(Clone, Eq, PartialEq)]
+/// The ways the user can interact with a displayed notification.
+#[derive(Clone, Copy, PartialEq, Debug, RustcEncodable, RustcDecodable)]
+pub enum NotificationEventType {
+    /// The user activated the notification.
+    Click,
+    /// The user dismissed the notification.
+    Close,
+}
+
+/// A position of the device, as reported by the embedder.
+/// http://dev.w3.org/geo/api/spec-source.html#coordinates_interface
+#[derive(Clone, Copy, PartialEq, Debug, RustcEncodable, RustcDecodable)]
+pub struct GeolocationPosition {
+    /// In decimal degrees.
+    pub latitude: f64,
+    /// In decimal degrees.
+    pub longitude: f64,
+    /// In meters above the WGS84 ellipsoid.
+    pub altitude: Option<f64>,
+    /// The accuracy of the latitude and longitude, in meters.
+    pub accuracy: f64,
+    /// The accuracy of the altitude, in meters.
+    pub altitude_accuracy: Option<f64>,
+    /// The direction of travel, in degrees clockwise from true north.
+    pub heading: Option<f64>,
+    /// In meters per second.
+    pub speed: Option<f64>,
+    /// When the position was acquired, in milliseconds since the epoch.
+    pub timestamp: u64,
+}
+
+/// The number of game controller slots exposed to content.
+pub const MAX_GAMEPADS: usize = 4;
+
+#[derive(Clone, Debug, RustcEncodable, RustcDecodable)]
+pub struct GamepadButtonState {
+    pub pressed: bool,
+    pub value: f64,
+}
+
+/// The state of a connected game controller.
+#[derive(Clone, Debug, RustcEncodable, RustcDecodable)]
+pub struct GamepadState {
+    pub id: String,
+    /// When the state last changed, as returned by `time::precise_time_ns`.
+    pub timestamp: u64,
+    pub buttons: Vec<GamepadButtonState>,
+    pub axes: Vec<f64>,
+}
+
+impl GamepadState {
+    pub fn new(id: String) -> GamepadState {
+        GamepadState {
+            id: id,
+            timestamp: 0,
+            buttons: vec!(),
+            axes: vec!(),
+        }
+    }
+}
+
+/// The game controllers' state after some of it changed, sent to script by the constellation,
+/// which reads the devices so that content needn't.
+#[derive(RustcEncodable, RustcDecodable)]
+pub struct GamepadUpdate {
+    /// The state of each controller slot, or `None` if nothing is connected to it.
+    pub states: Vec<Option<GamepadState>>,
+    /// The slots that were connected (`true`) or disconnected since the last update.
+    pub connection_changes: Vec<(u32, bool)>,
+}
+
+#[derive(Clone, Eq, PartialEq, RustcEncodable, RustcDecodable)]
 pub enum AnimationState {
     AnimationsPresent,
     AnimationCallbacksPresent,
@@ -264,6 +518,7 @@ pub enum AnimationState {
 }
 
 // https://developer.mozilla.org/en-US/docs/Web/API/Using_the_Browser_API#Events
+#[derive(RustcEncodable, RustcDecodable)]
 pub enum MozBrowserEvent {
     /// Sent when the scroll position within a browser <iframe> changes.
     AsyncScroll,
@@ -331,6 +586,7 @@ impl MozBrowserEvent {
 pub enum WebDriverCommandMsg {
     LoadUrl(PipelineId, LoadData, Sender<LoadStatus>),
     ScriptCommand(PipelineId, WebDriverScriptCommand),
+    SendKeys(PipelineId, Vec<(Key, KeyModifiers)>),
     TakeScreenshot(PipelineId, Sender<Option<png::Image>>)
 }
 
@@ -343,6 +599,48 @@ pub struct LoadData {
     pub method: Method,
     pub headers: Headers,
     pub data: Option<Vec<u8>>,
+    /// The URL of the document the load was started from, for the Referer header.
+    pub referrer_url: Option<Url>,
+    pub referrer_policy: Option<ReferrerPolicy>,
+    /// Whether the document this loads may load insecure active content even though it's
+    /// secure, because the user has allowed it for this load.
+    pub allow_mixed_content: bool,
+}
+
+impl Encodable for LoadData {
+    fn encode<S: Encoder>(&self, s: &mut S) -> Result<(), S::Error> {
+        s.emit_struct("LoadData", 7, |s| {
+            try!(s.emit_struct_field("url", 0, |s| self.url.encode(s)));
+            try!(s.emit_struct_field("method", 1, |s| {
+                serialize::encode_method(&self.method, s)
+            }));
+            try!(s.emit_struct_field("headers", 2, |s| {
+                serialize::encode_headers(&self.headers, s)
+            }));
+            try!(s.emit_struct_field("data", 3, |s| self.data.encode(s)));
+            try!(s.emit_struct_field("referrer_url", 4, |s| self.referrer_url.encode(s)));
+            try!(s.emit_struct_field("referrer_policy", 5, |s| self.referrer_policy.encode(s)));
+            s.emit_struct_field("allow_mixed_content", 6, |s| self.allow_mixed_content.encode(s))
+        })
+    }
+}
+
+impl Decodable for LoadData {
+    fn decode<D: Decoder>(d: &mut D) -> Result<LoadData, D::Error> {
+        d.read_struct("LoadData", 7, |d| {
+            Ok(LoadData {
+                url: try!(d.read_struct_field("url", 0, Decodable::decode)),
+                method: try!(d.read_struct_field("method", 1, serialize::decode_method)),
+                headers: try!(d.read_struct_field("headers", 2, serialize::decode_headers)),
+                data: try!(d.read_struct_field("data", 3, Decodable::decode)),
+                referrer_url: try!(d.read_struct_field("referrer_url", 4, Decodable::decode)),
+                referrer_policy: try!(d.read_struct_field("referrer_policy", 5,
+                                                          Decodable::decode)),
+                allow_mixed_content: try!(d.read_struct_field("allow_mixed_content", 6,
+                                                              Decodable::decode)),
+            })
+        })
+    }
 }
 
 impl LoadData {
@@ -352,31 +650,85 @@ impl LoadData {
             method: Method::Get,
             headers: Headers::new(),
             data: None,
+            referrer_url: None,
+            referrer_policy: None,
+            allow_mixed_content: false,
         }
     }
 }
 
-#[derive(Clone, PartialEq, Eq, Copy, Hash, Debug)]
+/// How much of a document's URL its requests reveal in the Referer header. Requests
+/// without a policy behave as `NoReferrerWhenDowngrade`.
+/// https://w3c.github.io/webappsec/specs/referrer-policy/#referrer-policies
+#[derive(Clone, Copy, PartialEq, Eq, Debug, RustcEncodable, RustcDecodable)]
+pub enum ReferrerPolicy {
+    NoReferrer,
+    NoReferrerWhenDowngrade,
+    Origin,
+    OriginWhenCrossOrigin,
+    UnsafeUrl,
+}
+
+impl ReferrerPolicy {
+    /// Parses a `Referrer-Policy` header or a `referrerpolicy` attribute. A header may list
+    /// several policies, in which case the last one that is understood wins.
+    pub fn parse(value: &str) -> Option<ReferrerPolicy> {
+        value.split(',').filter_map(|token| {
+            match &*token.trim().to_ascii_lowercase() {
+                "no-referrer" | "never" => Some(ReferrerPolicy::NoReferrer),
+                "no-referrer-when-downgrade" | "default" =>
+                    Some(ReferrerPolicy::NoReferrerWhenDowngrade),
+                "origin" => Some(ReferrerPolicy::Origin),
+                "origin-when-cross-origin" | "origin-when-crossorigin" =>
+                    Some(ReferrerPolicy::OriginWhenCrossOrigin),
+                "unsafe-url" | "always" => Some(ReferrerPolicy::UnsafeUrl),
+                _ => None,
+            }
+        }).last()
+    }
+}
+
+#[derive(Clone, PartialEq, Eq, Copy, Hash, Debug, RustcEncodable, RustcDecodable)]
 pub enum NavigationDirection {
     Forward,
     Back,
 }
 
-#[derive(Clone, PartialEq, Eq, Copy, Hash, Debug)]
+/// What the user did to a document that is worth restoring when its session history entry
+/// is revisited.
+#[derive(Clone, Debug, RustcEncodable, RustcDecodable)]
+pub struct DocumentState {
+    /// The scroll position of the viewport.
+    pub scroll_position: Point2D<f32>,
+    /// The form controls the user edited, by their index among the document's `input` and
+    /// `textarea` elements in tree order.
+    pub form_controls: Vec<(usize, FormControlState)>,
+}
+
+/// The user-edited state of a single form control.
+#[derive(Clone, Debug, RustcEncodable, RustcDecodable)]
+pub enum FormControlState {
+    Value(String),
+    Checked(bool),
+}
+
+// Identifiers and the simple types that go with them can be serialized, so that they can be
+// sent to tasks running in other processes.
+#[derive(Clone, PartialEq, Eq, Copy, Hash, Debug, RustcEncodable, RustcDecodable)]
 pub struct FrameId(pub u32);
 
-#[derive(Clone, PartialEq, Eq, Copy, Hash, Debug)]
+#[derive(Clone, PartialEq, Eq, Copy, Hash, Debug, RustcEncodable, RustcDecodable)]
 pub struct WorkerId(pub u32);
 
-#[derive(Clone, PartialEq, Eq, Copy, Hash, Debug)]
+#[derive(Clone, PartialEq, Eq, Copy, Hash, Debug, RustcEncodable, RustcDecodable)]
 pub struct PipelineId(pub u32);
 
-#[derive(Clone, PartialEq, Eq, Copy, Hash, Debug)]
+#[derive(Clone, PartialEq, Eq, Copy, Hash, Debug, RustcEncodable, RustcDecodable)]
 pub struct SubpageId(pub u32);
 
 // The type of pipeline exit. During complete shutdowns, pipelines do not have to
 // release resources automatically released on process termination.
-#[derive(Copy, Clone, Debug)]
+#[derive(Copy, Clone, Debug, RustcEncodable, RustcDecodable)]
 pub enum PipelineExitType {
     PipelineOnly,
     Complete,
@@ -20,4 +20,5 @@ extern crate io_surface;
 
 pub mod compositor_msg;
 pub mod constellation_msg;
+pub mod serialize;
 pub mod webdriver_msg;
@@ -0,0 +1,92 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+//! Encoding of the HTTP types that messages between processes carry, which come from hyper and so
+//! can't implement `Encodable` and `Decodable` themselves. The types that contain them call these
+//! functions from their own implementations.
+
+use hyper::header::{ContentType, Headers};
+use hyper::http::RawStatus;
+use hyper::method::Method;
+use rustc_serialize::{Decodable, Decoder, Encodable, Encoder};
+use std::borrow::ToOwned;
+
+pub fn encode_method<S: Encoder>(method: &Method, s: &mut S) -> Result<(), S::Error> {
+    s.emit_str(&method.to_string())
+}
+
+pub fn decode_method<D: Decoder>(d: &mut D) -> Result<Method, D::Error> {
+    let method = try!(d.read_str());
+    method.parse().map_err(|_| d.error(&format!("invalid method {}", method)))
+}
+
+/// Headers are encoded as their names, each with its raw values, so that headers that hyper
+/// doesn't know how to parse survive the trip.
+pub fn encode_headers<S: Encoder>(headers: &Headers, s: &mut S) -> Result<(), S::Error> {
+    let raw_headers: Vec<(String, Vec<Vec<u8>>)> = headers.iter().map(|header| {
+        let values = headers.get_raw(header.name()).map_or(vec!(), |values| values.to_vec());
+        (header.name().to_owned(), values)
+    }).collect();
+    raw_headers.encode(s)
+}
+
+pub fn decode_headers<D: Decoder>(d: &mut D) -> Result<Headers, D::Error> {
+    let raw_headers: Vec<(String, Vec<Vec<u8>>)> = try!(Decodable::decode(d));
+    let mut headers = Headers::new();
+    for (name, values) in raw_headers.into_iter() {
+        headers.set_raw(name, values);
+    }
+    Ok(headers)
+}
+
+pub fn encode_optional_headers<S: Encoder>(headers: &Option<Headers>, s: &mut S)
+                                           -> Result<(), S::Error> {
+    s.emit_option(|s| {
+        match *headers {
+            Some(ref headers) => s.emit_option_some(|s| encode_headers(headers, s)),
+            None => s.emit_option_none(),
+        }
+    })
+}
+
+pub fn decode_optional_headers<D: Decoder>(d: &mut D) -> Result<Option<Headers>, D::Error> {
+    d.read_option(|d, present| {
+        if present {
+            decode_headers(d).map(Some)
+        } else {
+            Ok(None)
+        }
+    })
+}
+
+pub fn encode_optional_status<S: Encoder>(status: &Option<RawStatus>, s: &mut S)
+                                          -> Result<(), S::Error> {
+    let status = status.as_ref().map(|&RawStatus(code, ref reason)| (code, reason.to_string()));
+    status.encode(s)
+}
+
+pub fn decode_optional_status<D: Decoder>(d: &mut D) -> Result<Option<RawStatus>, D::Error> {
+    let status: Option<(u16, String)> = try!(Decodable::decode(d));
+    Ok(status.map(|(code, reason)| RawStatus(code, reason.into())))
+}
+
+pub fn encode_optional_content_type<S: Encoder>(content_type: &Option<ContentType>, s: &mut S)
+                                                -> Result<(), S::Error> {
+    let content_type = content_type.as_ref().map(|&ContentType(ref mime)| mime.to_string());
+    content_type.encode(s)
+}
+
+pub fn decode_optional_content_type<D: Decoder>(d: &mut D)
+                                                -> Result<Option<ContentType>, D::Error> {
+    let content_type: Option<String> = try!(Decodable::decode(d));
+    match content_type {
+        Some(content_type) => {
+            match content_type.parse() {
+                Ok(mime) => Ok(Some(ContentType(mime))),
+                Err(_) => Err(d.error(&format!("invalid MIME type {}", content_type))),
+            }
+        }
+        None => Ok(None),
+    }
+}
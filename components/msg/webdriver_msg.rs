@@ -8,10 +8,12 @@ use constellation_msg::{PipelineId, SubpageId};
 use std::sync::mpsc::Sender;
 
 pub enum WebDriverScriptCommand {
+    ElementClick(String, Sender<Result<(), ()>>),
     ExecuteScript(String, Sender<WebDriverJSResult>),
     ExecuteAsyncScript(String, Sender<WebDriverJSResult>),
     FindElementCSS(String, Sender<Result<Option<String>, ()>>),
     FindElementsCSS(String, Sender<Result<Vec<String>, ()>>),
+    FocusElement(String, Sender<Result<(), ()>>),
     GetActiveElement(Sender<Option<String>>),
     GetElementTagName(String, Sender<Result<String, ()>>),
     GetElementText(String, Sender<Result<String, ()>>),
@@ -19,6 +21,7 @@ pub enum WebDriverScriptCommand {
     GetTitle(Sender<String>)
 }
 
+#[derive(RustcEncodable, RustcDecodable)]
 pub enum WebDriverJSValue {
     Undefined,
     Null,
@@ -28,6 +31,7 @@ pub enum WebDriverJSValue {
     // TODO: Object and WebElement
 }
 
+#[derive(RustcEncodable, RustcDecodable)]
 pub enum WebDriverJSError {
     Timeout,
     UnknownType
@@ -35,6 +39,7 @@ pub enum WebDriverJSError {
 
 pub type WebDriverJSResult = Result<WebDriverJSValue, WebDriverJSError>;
 
+#[derive(RustcEncodable, RustcDecodable)]
 pub enum WebDriverFrameId {
     Short(u16),
     Element(String),
@@ -2,8 +2,10 @@
  * License, v. 2.0. If a copy of the MPL was not distributed with this
  * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
 
+//! Serves the built-in about: pages.
+
 use net_traits::{LoadData, Metadata, LoadConsumer};
-use net_traits::ProgressMsg::Done;
+use net_traits::ProgressMsg::{Payload, Done};
 use mime_classifier::MIMEClassifier;
 use resource_task::start_sending;
 use file_loader;
@@ -12,25 +14,86 @@ use url::Url;
 use hyper::header::ContentType;
 use hyper::http::RawStatus;
 use hyper::mime::{Mime, TopLevel, SubLevel};
+use profile_traits::mem::{self, ReportUnits, ReportsSummary};
 use util::resource_files::resources_dir_path;
+use util::str::escape_html;
+use util::task::spawn_named;
 
+use std::borrow::ToOwned;
+use std::boxed::FnBox;
 use std::fs::PathExt;
 use std::sync::Arc;
+use std::sync::mpsc::channel;
+
+pub fn factory(mem_profiler_chan: Option<mem::ProfilerChan>)
+               -> Box<FnBox(LoadData, LoadConsumer, Arc<MIMEClassifier>) + Send> {
+    box move |load_data, senders, classifier| {
+        load(load_data, senders, classifier, mem_profiler_chan)
+    }
+}
 
-pub fn factory(mut load_data: LoadData, start_chan: LoadConsumer, classifier: Arc<MIMEClassifier>) {
+fn load(mut load_data: LoadData, start_chan: LoadConsumer, classifier: Arc<MIMEClassifier>,
+        mem_profiler_chan: Option<mem::ProfilerChan>) {
     match load_data.url.non_relative_scheme_data().unwrap() {
         "blank" => {
-            let chan = start_sending(start_chan, Metadata {
-                final_url: load_data.url,
-                content_type: Some(ContentType(Mime(TopLevel::Text, SubLevel::Html, vec![]))),
-                charset: Some("utf-8".to_string()),
-                headers: None,
-                status: Some(RawStatus(200, "OK".into())),
-            });
+            let chan = start_sending(start_chan, html_metadata(load_data.url));
             chan.send(Done(Ok(()))).unwrap();
             return
         }
+        "memory" => {
+            let mem_profiler_chan = match mem_profiler_chan {
+                Some(mem_profiler_chan) => mem_profiler_chan,
+                None => {
+                    start_sending(start_chan, Metadata::default(load_data.url))
+                        .send(Done(Err("The memory profiler isn't running.".to_owned()))).unwrap();
+                    return
+                }
+            };
+            // Reporters may take a while to answer, so don't hold up other loads.
+            spawn_named("AboutMemory".to_owned(), move || {
+                // about:memory?json dumps the reports for tools to process.
+                if load_data.url.query.as_ref().map_or(false, |query| query == "json") {
+                    let (json_chan, json_port) = channel();
+                    mem_profiler_chan.send(mem::ProfilerMsg::DumpJson(json_chan));
+                    let mut metadata = Metadata::default(load_data.url);
+                    metadata.set_content_type(Some(&Mime(TopLevel::Application,
+                                                         SubLevel::Json, vec![])));
+                    let chan = start_sending(start_chan, metadata);
+                    match json_port.recv() {
+                        Ok(json) => {
+                            chan.send(Payload(json.into_bytes())).unwrap();
+                            chan.send(Done(Ok(()))).unwrap();
+                        }
+                        Err(_) => {
+                            let message = "The memory profiler didn't answer.".to_owned();
+                            chan.send(Done(Err(message))).unwrap();
+                        }
+                    }
+                    return
+                }
+
+                let (summary_chan, summary_port) = channel();
+                mem_profiler_chan.send(mem::ProfilerMsg::Summarize(summary_chan));
+                let page = match summary_port.recv() {
+                    Ok(summaries) => memory_page(&summaries),
+                    Err(_) => "<p>The memory profiler didn't answer.</p>".to_owned(),
+                };
+                let chan = start_sending(start_chan, html_metadata(load_data.url));
+                chan.send(Payload(page.into_bytes())).unwrap();
+                chan.send(Done(Ok(()))).unwrap();
+            });
+            return
+        }
         "crash" => panic!("Loading the about:crash URL."),
+        "crashed" => {
+            let crashed_url = load_data.url.query_pairs().and_then(|pairs| {
+                pairs.into_iter().find(|&(ref name, _)| name == "url").map(|(_, url)| url)
+            }).unwrap_or(String::new());
+            let chan = start_sending(start_chan, html_metadata(load_data.url));
+            chan.send(Payload(crashed_page(&crashed_url).into_bytes())).unwrap();
+            chan.send(Done(Ok(()))).unwrap();
+            return
+        }
         "failure" => {
             let mut path = resources_dir_path();
             path.push("failure.html");
@@ -45,3 +108,70 @@ pub fn factory(mut load_data: LoadData, start_chan: LoadConsumer, classifier: Ar
     };
     file_loader::factory(load_data, start_chan, classifier)
 }
+
+fn html_metadata(url: Url) -> Metadata {
+    Metadata {
+        final_url: url,
+        content_type: Some(ContentType(Mime(TopLevel::Text, SubLevel::Html, vec![]))),
+        charset: Some("utf-8".to_string()),
+        headers: None,
+        status: Some(RawStatus(200, "OK".into())),
+        certificate_error: None,
+    }
+}
+
+/// Renders the memory reports as nested lists, in the same order as the profiler prints them.
+pub fn memory_page(summaries: &[ReportsSummary]) -> String {
+    let mut page = "<!DOCTYPE html>\n<html><head><meta charset=\"utf-8\">\
+                    <title>about:memory</title></head><body>\n<h1>Memory reports</h1>\n\
+                    <p><a href=\"about:memory?json\">View as JSON</a></p>\n"
+                       .to_owned();
+    for summary in summaries.iter() {
+        page.push_str("<ul>\n");
+        push_summary(&mut page, summary);
+        page.push_str("</ul>\n");
+    }
+    page.push_str("</body></html>\n");
+    page
+}
+
+/// Renders the page shown in place of a document whose script or layout task crashed, with a
+/// link to load `url` again. The URL comes from the query of the page's own URL, which any page
+/// can link to, so only http, https and file URLs get a link.
+pub fn crashed_page(url: &str) -> String {
+    let reloadable = match Url::parse(url) {
+        Ok(url) => ["http", "https", "file"].contains(&&*url.scheme),
+        Err(_) => false,
+    };
+    let url = escape_html(url);
+    let reload_link = if reloadable {
+        format!("<p><a href=\"{}\">Reload the page</a></p>\n", url)
+    } else {
+        String::new()
+    };
+    format!("<!DOCTYPE html>\n<html><head><meta charset=\"utf-8\">\
+             <title>Page crashed</title></head><body>\n<h1>This page crashed</h1>\n\
+             <p><code>{}</code> stopped working. The rest of the browser is unaffected.</p>\n\
+             {}</body></html>\n", url, reload_link)
+}
+
+fn push_summary(page: &mut String, summary: &ReportsSummary) {
+    let mebi = 1024f64 * 1024f64;
+    let size = match summary.units {
+        ReportUnits::Bytes => format!("{:.2} MiB", (summary.size as f64) / mebi),
+        ReportUnits::Count => summary.size.to_string(),
+    };
+    page.push_str(&format!("<li><code>{}</code> &mdash; {}",
+                           size, escape_html(&summary.path_seg)));
+    if summary.count > 1 {
+        page.push_str(&format!(" [{}]", summary.count));
+    }
+    if !summary.children.is_empty() {
+        page.push_str("\n<ul>\n");
+        for child in summary.children.iter() {
+            push_summary(page, child);
+        }
+        page.push_str("</ul>\n");
+    }
+    page.push_str("</li>\n");
+}
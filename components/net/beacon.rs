@@ -0,0 +1,55 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+//! Bookkeeping for beacons: small POST requests that are sent on behalf of a document
+//! but delivered independently of it, so they complete even if the document is unloaded.
+
+use net_traits::serialize_origin;
+pub use net_traits::BEACON_QUOTA;
+
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use url::Url;
+
+/// Tracks how much beacon data each origin has queued but not yet delivered.
+#[derive(Clone)]
+pub struct BeaconQuota {
+    in_flight: Arc<Mutex<HashMap<String, usize>>>,
+}
+
+impl BeaconQuota {
+    pub fn new() -> BeaconQuota {
+        BeaconQuota {
+            in_flight: Arc::new(Mutex::new(HashMap::new())),
+        }
+    }
+
+    /// Reserves `len` bytes of the quota of `origin`'s origin. Returns false, reserving
+    /// nothing, if that would exceed the quota.
+    pub fn reserve(&self, origin: &Url, len: usize) -> bool {
+        let mut in_flight = self.in_flight.lock().unwrap();
+        let used = in_flight.entry(serialize_origin(origin)).or_insert(0);
+        if *used + len > BEACON_QUOTA {
+            return false;
+        }
+        *used += len;
+        true
+    }
+
+    /// Returns `len` bytes to the quota of `origin`'s origin once a beacon has been delivered.
+    pub fn release(&self, origin: &Url, len: usize) {
+        let key = serialize_origin(origin);
+        let mut in_flight = self.in_flight.lock().unwrap();
+        let remaining = match in_flight.get_mut(&key) {
+            Some(used) => {
+                *used -= len;
+                *used
+            }
+            None => return,
+        };
+        if remaining == 0 {
+            in_flight.remove(&key);
+        }
+    }
+}
@@ -0,0 +1,110 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+//! Loads blob: URLs, which script mints for in-memory Blob data with
+//! `URL.createObjectURL`. http://dev.w3.org/2006/webapi/FileAPI/#url
+
+use mime_classifier::MIMEClassifier;
+use net_traits::{LoadData, LoadConsumer, Metadata};
+use net_traits::ProgressMsg::{Payload, Done};
+use resource_task::start_sending;
+
+use hyper::mime::Mime;
+use msg::constellation_msg::PipelineId;
+use std::borrow::ToOwned;
+use std::boxed::FnBox;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use url::Url;
+
+struct BlobEntry {
+    bytes: Arc<Vec<u8>>,
+    content_type: String,
+    /// The pipeline whose document minted the URL, which lives only as long as the document.
+    pipeline_id: PipelineId,
+}
+
+/// The data behind each blob: URL that hasn't been revoked yet. Clones share the same
+/// entries.
+#[derive(Clone)]
+pub struct BlobURLStore {
+    entries: Arc<Mutex<HashMap<String, BlobEntry>>>,
+}
+
+impl BlobURLStore {
+    pub fn new() -> BlobURLStore {
+        BlobURLStore {
+            entries: Arc::new(Mutex::new(HashMap::new())),
+        }
+    }
+
+    pub fn register(&self, url: &Url, bytes: Vec<u8>, content_type: String,
+                    pipeline_id: PipelineId) {
+        let entry = BlobEntry {
+            bytes: Arc::new(bytes),
+            content_type: content_type,
+            pipeline_id: pipeline_id,
+        };
+        self.entries.lock().unwrap().insert(key(url), entry);
+    }
+
+    pub fn revoke(&self, url: &Url) {
+        self.entries.lock().unwrap().remove(&key(url));
+    }
+
+    /// Revokes every URL minted by `pipeline_id`'s document.
+    /// http://dev.w3.org/2006/webapi/FileAPI/#lifeTime
+    pub fn revoke_for_pipeline(&self, pipeline_id: PipelineId) {
+        let mut entries = self.entries.lock().unwrap();
+        let urls = entries.iter()
+                          .filter(|&(_, entry)| entry.pipeline_id == pipeline_id)
+                          .map(|(url, _)| url.clone())
+                          .collect::<Vec<_>>();
+        for url in urls.iter() {
+            entries.remove(url);
+        }
+    }
+
+    /// The data and content type registered for `url`.
+    pub fn get(&self, url: &Url) -> Option<(Arc<Vec<u8>>, String)> {
+        self.entries.lock().unwrap().get(&key(url)).map(|entry| {
+            (entry.bytes.clone(), entry.content_type.clone())
+        })
+    }
+}
+
+/// Blob URLs are looked up without their fragment.
+fn key(url: &Url) -> String {
+    let mut url = url.clone();
+    url.fragment = None;
+    url.serialize()
+}
+
+pub fn factory(store: BlobURLStore)
+               -> Box<FnBox(LoadData, LoadConsumer, Arc<MIMEClassifier>) + Send> {
+    box move |load_data: LoadData, senders, _classifier| {
+        // The data is already in memory, so there's no need for a separate task.
+        load(load_data, senders, store)
+    }
+}
+
+fn load(load_data: LoadData, start_chan: LoadConsumer, store: BlobURLStore) {
+    let url = load_data.url;
+    assert!(&*url.scheme == "blob");
+
+    let mut metadata = Metadata::default(url.clone());
+    let (bytes, content_type) = match store.get(&url) {
+        Some(entry) => entry,
+        None => {
+            start_sending(start_chan, metadata).send(Done(Err("invalid blob url".to_owned()))).unwrap();
+            return;
+        }
+    };
+
+    let content_type: Option<Mime> = content_type.parse().ok();
+    metadata.set_content_type(content_type.as_ref());
+    let progress_chan = start_sending(start_chan, metadata);
+    progress_chan.send(Payload((*bytes).clone())).unwrap();
+    progress_chan.send(Done(Ok(()))).unwrap();
+}
@@ -0,0 +1,150 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+//! Verification of the certificates servers present over https. A server whose certificate
+//! can't be verified gets an error page in place of the document it was asked for, and a
+//! network error for anything else, unless the user has chosen to trust that certificate
+//! from that host anyway.
+
+use msg::constellation_msg::CertificateError;
+use openssl::crypto::hash::Type as HashType;
+use openssl::ssl::{SslContext, SSL_VERIFY_PEER};
+use openssl::x509::{X509, X509StoreContext};
+use std::ascii::AsciiExt;
+use std::borrow::ToOwned;
+use std::collections::HashSet;
+use std::sync::{Arc, Mutex};
+use url::Url;
+use util::resource_files::resources_dir_path;
+use util::str::escape_html;
+
+/// The certificates that are accepted from their hosts without being verified, because the
+/// user said so, by host and fingerprint. A host that presents a different certificate later
+/// isn't trusted. Clones share the same certificates.
+#[derive(Clone)]
+pub struct CertificateOverrides {
+    certificates: Arc<Mutex<HashSet<(String, String)>>>,
+}
+
+impl CertificateOverrides {
+    pub fn new() -> CertificateOverrides {
+        CertificateOverrides {
+            certificates: Arc::new(Mutex::new(HashSet::new())),
+        }
+    }
+
+    pub fn allow(&self, host: &str, fingerprint: &str) {
+        let key = (host.to_ascii_lowercase(), fingerprint.to_owned());
+        self.certificates.lock().unwrap().insert(key);
+    }
+
+    pub fn is_allowed(&self, host: &str, fingerprint: &str) -> bool {
+        let key = (host.to_ascii_lowercase(), fingerprint.to_owned());
+        self.certificates.lock().unwrap().contains(&key)
+    }
+}
+
+struct Verification {
+    /// The first problem found with the chain, if any.
+    error: Option<String>,
+    /// The certificates checked so far, PEM-encoded, starting with the root.
+    chain: Vec<String>,
+    /// The fingerprint of the last certificate checked, which ends up being the server's own.
+    fingerprint: String,
+}
+
+/// The outcome of verifying the certificates of one connection. Clones share the same
+/// outcome, so that it can be read once the handshake is over.
+#[derive(Clone)]
+pub struct CertificateCheck {
+    verification: Arc<Mutex<Verification>>,
+}
+
+impl CertificateCheck {
+    pub fn new() -> CertificateCheck {
+        CertificateCheck {
+            verification: Arc::new(Mutex::new(Verification {
+                error: None,
+                chain: vec!(),
+                fingerprint: String::new(),
+            })),
+        }
+    }
+
+    /// Sets up a connection to verify the server's certificates against Servo's CA list.
+    /// The handshake goes ahead whatever the outcome, so that the whole chain is seen;
+    /// `error` must be checked before anything is sent over the connection.
+    pub fn verifier(&self) -> Box<Fn(&mut SslContext) + Send> {
+        let check = self.clone();
+        box move |ssl: &mut SslContext| {
+            ssl.set_verify_with_data(SSL_VERIFY_PEER, verify, check.clone());
+            let mut certs = resources_dir_path();
+            certs.push("certs");
+            ssl.set_CA_file(&certs).unwrap();
+        }
+    }
+
+    /// The error to report for the connection made to load `url`, if its certificates
+    /// couldn't be verified.
+    pub fn error(&self, url: &Url) -> Option<CertificateError> {
+        let verification = self.verification.lock().unwrap();
+        verification.error.as_ref().map(|reason| {
+            CertificateError {
+                url: url.clone(),
+                host: url.domain().unwrap_or("").to_owned(),
+                reason: reason.clone(),
+                chain: verification.chain.iter().rev().cloned().collect(),
+                fingerprint: verification.fingerprint.clone(),
+            }
+        })
+    }
+}
+
+fn verify(preverify_ok: bool, x509_ctx: &X509StoreContext, check: &CertificateCheck) -> bool {
+    let mut verification = check.verification.lock().unwrap();
+    if let Some(cert) = x509_ctx.get_current_cert() {
+        let mut pem = vec!();
+        if cert.write_pem(&mut pem).is_ok() {
+            if let Ok(pem) = String::from_utf8(pem) {
+                // Certificates are checked again for each problem found with them.
+                if !verification.chain.contains(&pem) {
+                    verification.chain.push(pem);
+                    verification.fingerprint = fingerprint(&cert);
+                }
+            }
+        }
+    }
+    if !preverify_ok && verification.error.is_none() {
+        verification.error = Some(match x509_ctx.get_error() {
+            Some(error) => format!("{:?}", error),
+            None => "unknown verification error".to_owned(),
+        });
+    }
+    true
+}
+
+/// The SHA-256 fingerprint of a certificate, as colon-separated hexadecimal bytes.
+fn fingerprint(cert: &X509) -> String {
+    let digest = cert.fingerprint(HashType::SHA256).unwrap_or(vec!());
+    digest.iter().map(|byte| format!("{:02X}", byte)).collect::<Vec<_>>().connect(":")
+}
+
+/// The page shown in place of a response from a server that couldn't be trusted.
+pub fn error_page(error: &CertificateError) -> String {
+    let mut page = "<!DOCTYPE html>\n<html><head><meta charset=\"utf-8\">\
+                    <title>Untrusted connection</title></head><body>\n\
+                    <h1>This connection is untrusted</h1>\n".to_owned();
+    page.push_str(&format!("<p>The certificate presented by <strong>{}</strong> couldn't be \
+                            verified, so the page at <code>{}</code> wasn't loaded.</p>\n",
+                           escape_html(&error.host), escape_html(&error.url.serialize())));
+    page.push_str(&format!("<p>Reason: <code>{}</code></p>\n", escape_html(&error.reason)));
+    if !error.chain.is_empty() {
+        page.push_str("<h2>Certificate chain</h2>\n");
+        for cert in error.chain.iter() {
+            page.push_str(&format!("<pre>{}</pre>\n", escape_html(cert)));
+        }
+    }
+    page.push_str("</body></html>\n");
+    page
+}
@@ -8,21 +8,129 @@
 use net_traits::CookieSource;
 use url::Url;
 use cookie::Cookie;
+use cookie_rs;
+use rustc_serialize::json;
+use std::borrow::ToOwned;
 use std::cmp::Ordering;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Read, Write};
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{channel, Receiver, Sender};
+use time::{self, Timespec};
+use util::task::spawn_named;
+
+/// The number of cookies kept for a single domain.
+/// http://tools.ietf.org/html/rfc6265#section-6.1
+pub const MAX_COOKIES_PER_DOMAIN: usize = 50;
+
+/// The number of cookies kept in total.
+pub const MAX_COOKIES: usize = 3000;
 
 pub struct CookieStorage {
-    cookies: Vec<Cookie>
+    cookies: Vec<Cookie>,
+    /// The file persistent cookies are saved to, if any.
+    path: Option<PathBuf>,
+    /// Whether the cookies saved by a previous session have been read yet.
+    loaded: bool,
+    /// Whether the persistent cookies have changed since they were last saved.
+    changed: bool,
+    /// The task that writes the persistent cookies to disk, if they are saved.
+    writer: Option<Sender<CookieWriterMsg>>,
+}
+
+/// Messages to the task that writes the persistent cookies to disk.
+enum CookieWriterMsg {
+    /// Replace the saved cookies with these ones, already serialized.
+    Write(String),
+    /// Reply once everything sent before has been written.
+    Flush(Sender<()>),
 }
 
 impl CookieStorage {
     pub fn new() -> CookieStorage {
         CookieStorage {
-            cookies: Vec::new()
+            cookies: Vec::new(),
+            path: None,
+            loaded: true,
+            changed: false,
+            writer: None,
+        }
+    }
+
+    /// Creates a storage that saves persistent cookies to `path`. The cookies saved there
+    /// are read the first time the storage is used, rather than right away.
+    pub fn new_persistent(path: PathBuf) -> CookieStorage {
+        let (writer, port) = channel();
+        let writer_path = path.clone();
+        spawn_named("CookieWriter".to_owned(), move || write_cookies(writer_path, port));
+        CookieStorage {
+            cookies: Vec::new(),
+            path: Some(path),
+            loaded: false,
+            changed: false,
+            writer: Some(writer),
+        }
+    }
+
+    fn ensure_loaded(&mut self) {
+        if self.loaded {
+            return;
+        }
+        self.loaded = true;
+
+        let path = self.path.clone().unwrap();
+        let mut contents = String::new();
+        if File::open(&path).and_then(|mut file| file.read_to_string(&mut contents)).is_err() {
+            // Nothing was saved yet.
+            return;
+        }
+        let stored: Vec<StoredCookie> = match json::decode(&contents) {
+            Ok(stored) => stored,
+            Err(e) => {
+                warn!("ignoring malformed cookie file {}: {}", path.display(), e);
+                return;
+            }
+        };
+        self.cookies = stored.into_iter().map(StoredCookie::into_cookie).collect();
+        self.remove_expired();
+    }
+
+    /// Whether there are persistent cookies that haven't been saved yet.
+    pub fn needs_saving(&self) -> bool {
+        self.writer.is_some() && self.changed
+    }
+
+    /// Hands the persistent cookies to the writer task, which saves them to disk without
+    /// holding up this one. Session cookies are never saved.
+    pub fn save(&mut self) {
+        if !self.needs_saving() {
+            return;
+        }
+        self.changed = false;
+        self.remove_expired();
+        let stored = self.cookies.iter()
+                                 .filter(|cookie| cookie.persistent)
+                                 .map(StoredCookie::from_cookie)
+                                 .collect::<Vec<_>>();
+        let contents = json::encode(&stored).unwrap();
+        let _ = self.writer.as_ref().unwrap().send(CookieWriterMsg::Write(contents));
+    }
+
+    /// Saves the persistent cookies, and waits until they are on disk.
+    pub fn flush(&mut self) {
+        self.save();
+        if let Some(ref writer) = self.writer {
+            let (done_chan, done_port) = channel();
+            if writer.send(CookieWriterMsg::Flush(done_chan)).is_ok() {
+                let _ = done_port.recv();
+            }
         }
     }
 
     // http://tools.ietf.org/html/rfc6265#section-5.3
     pub fn remove(&mut self, cookie: &Cookie, source: CookieSource) -> Result<Option<Cookie>, ()> {
+        self.ensure_loaded();
+
         // Step 1
         let position = self.cookies.iter().position(|c| {
             c.cookie.domain == cookie.cookie.domain &&
@@ -32,6 +140,7 @@ impl CookieStorage {
 
         if let Some(ind) = position {
             let c = self.cookies.remove(ind);
+            self.changed = self.changed || c.persistent;
 
             // http://tools.ietf.org/html/rfc6265#section-5.3 step 11.2
             if !c.cookie.httponly || source == CookieSource::HTTP {
@@ -65,7 +174,55 @@ impl CookieStorage {
         }
 
         // Step 12
+        let domain = cookie.cookie.domain.clone();
+        self.changed = self.changed || cookie.persistent;
         self.cookies.push(cookie);
+        self.evict(domain);
+    }
+
+    /// Evicts expired cookies, then the least recently used cookies of `domain` if it has
+    /// too many, then the least recently used cookies overall.
+    /// http://tools.ietf.org/html/rfc6265#section-5.3 step 12, note
+    fn evict(&mut self, domain: Option<String>) {
+        self.remove_expired();
+        loop {
+            let (count, oldest) = self.least_recently_used(|c| c.cookie.domain == domain);
+            if count <= MAX_COOKIES_PER_DOMAIN {
+                break;
+            }
+            let evicted = self.cookies.remove(oldest.unwrap());
+            self.changed = self.changed || evicted.persistent;
+        }
+        while self.cookies.len() > MAX_COOKIES {
+            let (_, oldest) = self.least_recently_used(|_| true);
+            let evicted = self.cookies.remove(oldest.unwrap());
+            self.changed = self.changed || evicted.persistent;
+        }
+    }
+
+    /// The number of cookies matching `filter`, and the index of the one that was accessed
+    /// the longest time ago.
+    fn least_recently_used<F: Fn(&Cookie) -> bool>(&self, filter: F) -> (usize, Option<usize>) {
+        let mut count = 0;
+        let mut oldest: Option<(usize, Timespec)> = None;
+        for (index, cookie) in self.cookies.iter().enumerate().filter(|&(_, c)| filter(c)) {
+            count += 1;
+            let last_access = cookie.last_access.to_timespec();
+            if oldest.map_or(true, |(_, oldest_access)| last_access < oldest_access) {
+                oldest = Some((index, last_access));
+            }
+        }
+        (count, oldest.map(|(index, _)| index))
+    }
+
+    /// http://tools.ietf.org/html/rfc6265#section-5.3
+    /// "The user agent MUST evict all expired cookies from the cookie store if, at any time,
+    /// an expired cookie exists in the cookie store."
+    fn remove_expired(&mut self) {
+        let now = time::get_time();
+        self.cookies.retain(|cookie| {
+            cookie.expiry_time.map_or(true, |expiry| expiry.to_timespec() > now)
+        });
     }
 
     pub fn cookie_comparator(a: &Cookie, b: &Cookie) -> Ordering {
@@ -85,6 +242,9 @@ impl CookieStorage {
 
     // http://tools.ietf.org/html/rfc6265#section-5.4
     pub fn cookies_for_url(&mut self, url: &Url, source: CookieSource) -> Option<String> {
+        self.ensure_loaded();
+        self.remove_expired();
+
         let filterer = |c: &&mut Cookie| -> bool {
             info!(" === SENT COOKIE : {} {} {:?} {:?}",
                   c.cookie.name, c.cookie.value, c.cookie.domain, c.cookie.path);
@@ -116,3 +276,103 @@ impl CookieStorage {
         }
     }
 }
+
+/// Writes the cookies sent by a storage to `path` until the storage goes away.
+fn write_cookies(path: PathBuf, port: Receiver<CookieWriterMsg>) {
+    while let Ok(msg) = port.recv() {
+        match msg {
+            CookieWriterMsg::Write(contents) => {
+                if let Err(e) = write_atomically(&path, &contents) {
+                    warn!("failed to save cookies to {}: {}", path.display(), e);
+                }
+            }
+            CookieWriterMsg::Flush(done_chan) => {
+                let _ = done_chan.send(());
+            }
+        }
+    }
+}
+
+/// Replaces the file at `path` with `contents`. They are written to a file of their own that is
+/// then renamed over the old one, so that a crash part way through leaves the old file intact.
+fn write_atomically(path: &Path, contents: &str) -> io::Result<()> {
+    let mut temp_path = path.as_os_str().to_owned();
+    temp_path.push(".tmp");
+    let temp_path = PathBuf::from(temp_path);
+    // The file is only created with the right permissions if it doesn't exist yet.
+    let _ = fs::remove_file(&temp_path);
+    {
+        let mut file = try!(create_private_file(&temp_path));
+        try!(file.write_all(contents.as_bytes()));
+        try!(file.sync_all());
+    }
+    fs::rename(&temp_path, path)
+}
+
+/// Creates a file that only the user can read, since cookies are credentials.
+#[cfg(unix)]
+fn create_private_file(path: &Path) -> io::Result<File> {
+    use std::os::unix::fs::OpenOptionsExt;
+    OpenOptions::new().write(true).create(true).truncate(true).mode(0o600).open(path)
+}
+
+#[cfg(not(unix))]
+fn create_private_file(path: &Path) -> io::Result<File> {
+    OpenOptions::new().write(true).create(true).truncate(true).open(path)
+}
+
+/// The on-disk form of a persistent cookie. Times are in nanoseconds since the epoch.
+#[derive(RustcEncodable, RustcDecodable)]
+struct StoredCookie {
+    name: String,
+    value: String,
+    domain: Option<String>,
+    path: Option<String>,
+    secure: bool,
+    httponly: bool,
+    host_only: bool,
+    creation_time: i64,
+    last_access: i64,
+    expiry_time: Option<i64>,
+}
+
+impl StoredCookie {
+    fn from_cookie(cookie: &Cookie) -> StoredCookie {
+        StoredCookie {
+            name: cookie.cookie.name.clone(),
+            value: cookie.cookie.value.clone(),
+            domain: cookie.cookie.domain.clone(),
+            path: cookie.cookie.path.clone(),
+            secure: cookie.cookie.secure,
+            httponly: cookie.cookie.httponly,
+            host_only: cookie.host_only,
+            creation_time: to_nanoseconds(cookie.creation_time.to_timespec()),
+            last_access: to_nanoseconds(cookie.last_access.to_timespec()),
+            expiry_time: cookie.expiry_time.map(|expiry| to_nanoseconds(expiry.to_timespec())),
+        }
+    }
+
+    fn into_cookie(self) -> Cookie {
+        let mut cookie = cookie_rs::Cookie::new(self.name, self.value);
+        cookie.domain = self.domain;
+        cookie.path = self.path;
+        cookie.secure = self.secure;
+        cookie.httponly = self.httponly;
+        Cookie {
+            cookie: cookie,
+            host_only: self.host_only,
+            persistent: true,
+            creation_time: from_nanoseconds(self.creation_time),
+            last_access: from_nanoseconds(self.last_access),
+            expiry_time: self.expiry_time.map(from_nanoseconds),
+        }
+    }
+}
+
+fn to_nanoseconds(time: Timespec) -> i64 {
+    time.sec * 1_000_000_000 + time.nsec as i64
+}
+
+fn from_nanoseconds(nanoseconds: i64) -> time::Tm {
+    time::at(Timespec::new(nanoseconds / 1_000_000_000, (nanoseconds % 1_000_000_000) as i32))
+}
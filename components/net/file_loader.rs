@@ -7,12 +7,16 @@ use net_traits::ProgressMsg::{Payload, Done};
 use mime_classifier::MIMEClassifier;
 use resource_task::{start_sending, start_sending_sniffed, ProgressSender};
 
+use hyper::mime::{Mime, TopLevel, SubLevel};
 use std::borrow::ToOwned;
 use std::error::Error;
-use std::fs::File;
+use std::fs::{self, File, PathExt};
 use std::io::Read;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use std::sync::Arc;
+use time;
+use url::Url;
+use util::str::escape_html;
 use util::task::spawn_named;
 
 static READ_SIZE: usize = 8192;
@@ -44,13 +48,90 @@ fn read_all(reader: &mut File, progress_chan: &ProgressSender)
     }
 }
 
+/// Generates an HTML index of the directory at `path`, listing subdirectories first.
+pub fn directory_listing(path: &Path) -> Result<String, String> {
+    let mut entries = vec!();
+    for entry in try!(fs::read_dir(path).map_err(|e| e.description().to_owned())) {
+        let entry = try!(entry.map_err(|e| e.description().to_owned()));
+        let metadata = try!(entry.metadata().map_err(|e| e.description().to_owned()));
+        let name = entry.file_name().to_string_lossy().into_owned();
+        entries.push((!metadata.is_dir(), name, entry.path(), metadata));
+    }
+    entries.sort_by(|a, b| (a.0, &a.1).cmp(&(b.0, &b.1)));
+
+    let title = escape_html(&path.to_string_lossy());
+    let mut page = format!("<!DOCTYPE html>\n<html><head><meta charset=\"utf-8\">\
+                            <title>Index of {0}</title></head><body>\n\
+                            <h1>Index of {0}</h1>\n<table>\n\
+                            <tr><th>Name</th><th>Size</th><th>Last modified</th></tr>\n",
+                           title);
+    if let Some(parent) = path.parent() {
+        if let Ok(parent_url) = Url::from_directory_path(parent) {
+            page.push_str(&format!("<tr><td><a href=\"{}\">Parent directory</a></td>\
+                                    <td></td><td></td></tr>\n",
+                                   escape_html(&parent_url.serialize())));
+        }
+    }
+    for &(is_file, ref name, ref entry_path, ref metadata) in entries.iter() {
+        let entry_url = if is_file {
+            Url::from_file_path(entry_path)
+        } else {
+            Url::from_directory_path(entry_path)
+        };
+        let entry_url = match entry_url {
+            Ok(entry_url) => entry_url,
+            Err(()) => continue,
+        };
+        let (name, size) = if is_file {
+            (escape_html(name), metadata.len().to_string())
+        } else {
+            (format!("{}/", escape_html(name)), String::new())
+        };
+        page.push_str(&format!("<tr><td><a href=\"{}\">{}</a></td><td>{}</td><td>{}</td></tr>\n",
+                               escape_html(&entry_url.serialize()), name, size,
+                               modified_time(metadata)));
+    }
+    page.push_str("</table>\n</body></html>\n");
+    Ok(page)
+}
+
+#[cfg(unix)]
+fn modified_time(metadata: &fs::Metadata) -> String {
+    use std::os::unix::fs::MetadataExt;
+    let modified = time::at_utc(time::Timespec::new(metadata.mtime() as i64, 0));
+    match modified.strftime("%Y-%m-%d %H:%M:%S UTC") {
+        Ok(modified) => modified.to_string(),
+        Err(_) => String::new(),
+    }
+}
+
+#[cfg(not(unix))]
+fn modified_time(_metadata: &fs::Metadata) -> String {
+    String::new()
+}
+
 pub fn factory(load_data: LoadData, senders: LoadConsumer, classifier: Arc<MIMEClassifier>) {
     let url = load_data.url;
     assert!(&*url.scheme == "file");
     spawn_named("file_loader".to_owned(), move || {
-        let metadata = Metadata::default(url.clone());
+        let mut metadata = Metadata::default(url.clone());
         let file_path: Result<PathBuf, ()> = url.to_file_path();
         match file_path {
+            Ok(ref file_path) if file_path.is_dir() => {
+                match directory_listing(file_path) {
+                    Ok(page) => {
+                        let mime = Mime(TopLevel::Text, SubLevel::Html, vec![]);
+                        metadata.set_content_type(Some(&mime));
+                        let progress_chan = start_sending(senders, metadata);
+                        progress_chan.send(Payload(page.into_bytes())).unwrap();
+                        progress_chan.send(Done(Ok(()))).unwrap();
+                    }
+                    Err(e) => {
+                        let progress_chan = start_sending(senders, metadata);
+                        progress_chan.send(Done(Err(e))).unwrap();
+                    }
+                }
+            }
             Ok(file_path) => {
                 match File::open(&file_path) {
                     Ok(ref mut reader) => {
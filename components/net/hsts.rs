@@ -0,0 +1,167 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+//! HTTP Strict Transport Security, as specified in https://tools.ietf.org/html/rfc6797.
+//! Hosts known to require https, either from the preload list or because they said so
+//! in a Strict-Transport-Security header, are never contacted over plain http.
+
+use std::ascii::AsciiExt;
+use std::borrow::ToOwned;
+use std::cmp::min;
+use std::i64;
+use std::net::{Ipv4Addr, Ipv6Addr};
+use std::str;
+use std::sync::{Arc, Mutex};
+use time::{self, Duration, Timespec};
+use url::Url;
+
+/// Hosts that require https before they have ever been visited, and whether their
+/// subdomains do too. A subset of the list shipped by other browsers.
+static PRELOAD_LIST: [(&'static str, bool); 12] = [
+    ("accounts.google.com", true),
+    ("checkout.google.com", true),
+    ("mail.google.com", true),
+    ("encrypted.google.com", true),
+    ("login.yahoo.com", true),
+    ("paypal.com", false),
+    ("www.paypal.com", false),
+    ("twitter.com", false),
+    ("www.twitter.com", false),
+    ("github.com", true),
+    ("torproject.org", false),
+    ("www.torproject.org", true),
+];
+
+#[derive(Clone, Debug)]
+pub struct HSTSEntry {
+    pub host: String,
+    pub include_subdomains: bool,
+    /// When the entry lapses; preloaded entries never do.
+    pub expires_at: Option<Timespec>,
+}
+
+impl HSTSEntry {
+    fn is_expired(&self) -> bool {
+        self.expires_at.map_or(false, |expires_at| time::get_time() >= expires_at)
+    }
+
+    /// https://tools.ietf.org/html/rfc6797#section-8.2
+    fn matches_host(&self, host: &str) -> bool {
+        if host == self.host {
+            return true;
+        }
+        self.include_subdomains &&
+            host.ends_with(&*self.host) &&
+            host.as_bytes()[host.len() - self.host.len() - 1] == b'.'
+    }
+}
+
+/// The hosts known to require https. Clones share the same entries.
+#[derive(Clone)]
+pub struct HSTSList {
+    entries: Arc<Mutex<Vec<HSTSEntry>>>,
+}
+
+impl HSTSList {
+    pub fn new() -> HSTSList {
+        HSTSList {
+            entries: Arc::new(Mutex::new(vec!())),
+        }
+    }
+
+    /// Creates a list that starts out with the preloaded hosts.
+    pub fn new_preloaded() -> HSTSList {
+        let entries = PRELOAD_LIST.iter().map(|&(host, include_subdomains)| {
+            HSTSEntry {
+                host: host.to_owned(),
+                include_subdomains: include_subdomains,
+                expires_at: None,
+            }
+        }).collect();
+        HSTSList {
+            entries: Arc::new(Mutex::new(entries)),
+        }
+    }
+
+    /// Whether requests to `host` must be made over https.
+    pub fn is_host_secure(&self, host: &str) -> bool {
+        let host = host.to_ascii_lowercase();
+        let mut entries = self.entries.lock().unwrap();
+        entries.retain(|entry| !entry.is_expired());
+        entries.iter().any(|entry| entry.matches_host(&host))
+    }
+
+    /// Records the policy a Strict-Transport-Security header received from `host` over
+    /// https asks for. Malformed headers are ignored.
+    /// https://tools.ietf.org/html/rfc6797#section-8.1
+    pub fn update(&self, host: &str, header: &[Vec<u8>]) {
+        // Policies can't be set for IP addresses.
+        if host.parse::<Ipv4Addr>().is_ok() || host.parse::<Ipv6Addr>().is_ok() {
+            return;
+        }
+        // Only the first header counts.
+        let (max_age, include_subdomains) = match header.first().and_then(|value| {
+            str::from_utf8(value).ok().and_then(parse_header)
+        }) {
+            Some(policy) => policy,
+            None => return,
+        };
+
+        let host = host.to_ascii_lowercase();
+        let mut entries = self.entries.lock().unwrap();
+        entries.retain(|entry| entry.host != host);
+        // A max-age of zero tells us to forget the host. Durations are measured in
+        // milliseconds that fit in an i64, so longer policies are cut short.
+        if max_age > 0 {
+            let max_age = min(max_age, i64::MAX as u64 / 1000) as i64;
+            entries.push(HSTSEntry {
+                host: host,
+                include_subdomains: include_subdomains,
+                expires_at: Some(time::get_time() + Duration::seconds(max_age)),
+            });
+        }
+    }
+}
+
+/// Parses a Strict-Transport-Security header value into its max-age and whether it
+/// includes subdomains.
+/// https://tools.ietf.org/html/rfc6797#section-6.1
+fn parse_header(value: &str) -> Option<(u64, bool)> {
+    let mut max_age = None;
+    let mut include_subdomains = false;
+    for directive in value.split(';') {
+        let mut parts = directive.splitn(2, '=');
+        let name = parts.next().unwrap().trim().to_ascii_lowercase();
+        let value = parts.next().map(|value| value.trim().trim_matches('"'));
+        match (&*name, value) {
+            ("max-age", Some(value)) => {
+                // Each directive may appear only once.
+                if max_age.is_some() {
+                    return None;
+                }
+                max_age = Some(match value.parse() {
+                    Ok(max_age) => max_age,
+                    Err(_) => return None,
+                });
+            }
+            ("includesubdomains", None) => include_subdomains = true,
+            _ => {}
+        }
+    }
+    max_age.map(|max_age| (max_age, include_subdomains))
+}
+
+/// The https equivalent of an http URL.
+/// https://tools.ietf.org/html/rfc6797#section-8.3
+pub fn secure_url(url: &Url) -> Url {
+    let mut secure_url = url.clone();
+    secure_url.scheme = "https".to_owned();
+    if let Some(data) = secure_url.relative_scheme_data_mut() {
+        if data.port == Some(80) {
+            data.port = None;
+        }
+        data.default_port = Some(443);
+    }
+    secure_url
+}
@@ -0,0 +1,439 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+//! HTTP/2 for https loads. Whether an origin speaks HTTP/2 is negotiated during the TLS
+//! handshake; requests to origins that do share a single multiplexed connection, and
+//! everything else keeps going over HTTP/1.1.
+
+use hyper::header::Headers;
+use hyper::http::RawStatus;
+use hyper::method::Method;
+use hyper::status::StatusCode;
+use openssl::ssl::SslStream;
+use solicit::http::{Header, HttpError, HttpResult, StreamId};
+use solicit::http::client::{ClientConnection, ClientStream, HttpConnect, RequestStream};
+use solicit::http::client::tls::{TlsConnectError, TlsConnector};
+use solicit::http::connection::{HttpConnection, HttpFrame, ReceiveFrame, SendFrame};
+use solicit::http::frame::RawFrame;
+use solicit::http::session::{DefaultSessionState, SessionState, Stream, StreamDataChunk};
+use solicit::http::session::{StreamDataError, StreamState};
+use solicit::http::transport::TransportStream;
+use std::ascii::AsciiExt;
+use std::borrow::ToOwned;
+use std::cmp;
+use std::collections::HashMap;
+use std::io::{self, Cursor, Read};
+use std::net::TcpStream;
+use std::str;
+use std::sync::mpsc::{channel, Receiver, Sender};
+use std::sync::{Arc, Mutex};
+use url::Url;
+use util::resource_files::resources_dir_path;
+use util::task::spawn_named;
+
+/// Connection-specific headers, which HTTP/2 forbids.
+/// https://http2.github.io/http2-spec/#rfc.section.8.1.2.2
+const CONNECTION_HEADERS: [&'static str; 6] = [
+    "connection", "host", "keep-alive", "proxy-connection", "transfer-encoding", "upgrade",
+];
+
+enum OriginSupport {
+    Http2(Sender<WorkItem>),
+    /// The server didn't offer HTTP/2 during the handshake.
+    Http1,
+}
+
+/// The HTTP/2 connection to each origin, shared by all loads; clones share the same
+/// connections.
+#[derive(Clone)]
+pub struct Http2Connections {
+    origins: Arc<Mutex<HashMap<String, OriginSupport>>>,
+}
+
+pub struct Http2Response {
+    pub status: StatusCode,
+    pub status_raw: RawStatus,
+    pub headers: Headers,
+    /// The body, read as its DATA frames arrive.
+    pub body: Http2Body,
+}
+
+impl Http2Connections {
+    pub fn new() -> Http2Connections {
+        Http2Connections {
+            origins: Arc::new(Mutex::new(HashMap::new())),
+        }
+    }
+
+    /// Sends a request over the HTTP/2 connection to `url`'s origin, connecting first if
+    /// needed. Returns `Ok(None)` if the request should be made over HTTP/1.1 instead, which is
+    /// only ever the case before any of it has been sent. Once it may have reached the server,
+    /// a failure is an error, since the request mustn't be repeated.
+    pub fn send(&self, method: &Method, url: &Url, headers: &Headers, body: Option<Vec<u8>>)
+                -> Result<Option<Http2Response>, String> {
+        // The TLS connector only knows how to reach the default port.
+        if url.scheme != "https" || url.port_or_default() != Some(443) {
+            return Ok(None);
+        }
+        let host = match url.domain() {
+            Some(host) => host.to_owned(),
+            None => return Ok(None),
+        };
+        let connection = match self.connection_for(&host) {
+            Some(connection) => connection,
+            None => return Ok(None),
+        };
+
+        let mut path = url.serialize_path().unwrap_or("/".to_owned());
+        if let Some(ref query) = url.query {
+            path.push('?');
+            path.push_str(query);
+        }
+        let request_headers = headers.iter().filter_map(|header| {
+            let name = header.name().to_ascii_lowercase();
+            if CONNECTION_HEADERS.contains(&&*name) {
+                return None;
+            }
+            Some((name.into_bytes(), header.value_string().into_bytes()))
+        }).collect::<Vec<_>>();
+
+        let (headers_chan, headers_port) = channel();
+        let (body_chan, body_port) = channel();
+        let request = Http2Request {
+            method: method.as_ref().as_bytes().to_vec(),
+            path: path.into_bytes(),
+            headers: request_headers,
+            body: body,
+            headers_chan: headers_chan,
+            body_chan: body_chan,
+        };
+        if connection.send(WorkItem::Request(request)).is_err() {
+            // The connection was already gone, so nothing was sent; the next request will open
+            // a new one.
+            self.origins.lock().unwrap().remove(&host);
+            return Ok(None);
+        }
+
+        match headers_port.recv().ok().and_then(|headers| convert_response(headers, body_port)) {
+            Some(response) => Ok(Some(response)),
+            None => {
+                self.origins.lock().unwrap().remove(&host);
+                Err("HTTP/2 connection failed".to_owned())
+            }
+        }
+    }
+
+    /// The HTTP/2 connection to `host`, or `None` if it should be reached over HTTP/1.1.
+    fn connection_for(&self, host: &str) -> Option<Sender<WorkItem>> {
+        match self.origins.lock().unwrap().get(host) {
+            Some(&OriginSupport::Http2(ref connection)) => return Some(connection.clone()),
+            Some(&OriginSupport::Http1) => return None,
+            None => {}
+        }
+
+        // Don't hold the lock while connecting, so that loads from other origins can proceed.
+        let support = match connect(host) {
+            Ok(connection) => OriginSupport::Http2(connection),
+            Err(TlsConnectError::Http2NotSupported(_)) | Err(TlsConnectError::SslError(_)) => {
+                OriginSupport::Http1
+            }
+            // The network may be down for now; try again next time.
+            Err(TlsConnectError::IoError(_)) => return None,
+        };
+        let mut origins = self.origins.lock().unwrap();
+        match *origins.entry(host.to_owned()).or_insert(support) {
+            OriginSupport::Http2(ref connection) => Some(connection.clone()),
+            OriginSupport::Http1 => None,
+        }
+    }
+}
+
+/// A request waiting to be started on a connection.
+struct Http2Request {
+    method: Vec<u8>,
+    path: Vec<u8>,
+    headers: Vec<Header>,
+    body: Option<Vec<u8>>,
+    headers_chan: Sender<Vec<Header>>,
+    body_chan: Sender<BodyChunk>,
+}
+
+/// What a connection's task is asked to do next.
+enum WorkItem {
+    Request(Http2Request),
+    /// A frame was read, and waits in the frame channel.
+    HandleFrame,
+    /// A frame was written, so the next DATA frame of a request body may be queued.
+    SendData,
+}
+
+enum BodyChunk {
+    Data(Vec<u8>),
+    End,
+}
+
+/// A stream that passes the response on as it arrives instead of keeping it.
+struct ResponseStream {
+    id: StreamId,
+    state: StreamState,
+    request_body: Option<Cursor<Vec<u8>>>,
+    headers_chan: Option<Sender<Vec<Header>>>,
+    body_chan: Option<Sender<BodyChunk>>,
+}
+
+impl Stream for ResponseStream {
+    fn new(id: StreamId) -> ResponseStream {
+        ResponseStream {
+            id: id,
+            state: StreamState::Open,
+            request_body: None,
+            headers_chan: None,
+            body_chan: None,
+        }
+    }
+
+    fn new_data_chunk(&mut self, data: &[u8]) {
+        if let Some(ref body_chan) = self.body_chan {
+            let _ = body_chan.send(BodyChunk::Data(data.to_vec()));
+        }
+    }
+
+    fn set_headers(&mut self, headers: Vec<Header>) {
+        // Only the first block of headers starts the response.
+        if let Some(headers_chan) = self.headers_chan.take() {
+            let _ = headers_chan.send(headers);
+        }
+    }
+
+    fn set_state(&mut self, state: StreamState) {
+        self.state = state;
+        if self.is_closed_remote() {
+            if let Some(body_chan) = self.body_chan.take() {
+                let _ = body_chan.send(BodyChunk::End);
+            }
+        }
+    }
+
+    fn get_data_chunk(&mut self, buf: &mut [u8]) -> Result<StreamDataChunk, StreamDataError> {
+        if self.is_closed_local() {
+            return Err(StreamDataError::Closed);
+        }
+        let chunk = match self.request_body {
+            None => StreamDataChunk::Unavailable,
+            Some(ref mut body) => {
+                let read = try!(body.read(buf));
+                if body.position() as usize == body.get_ref().len() {
+                    StreamDataChunk::Last(read)
+                } else {
+                    StreamDataChunk::Chunk(read)
+                }
+            }
+        };
+        if let StreamDataChunk::Last(_) = chunk {
+            self.close_local();
+        }
+        Ok(chunk)
+    }
+
+    fn id(&self) -> StreamId {
+        self.id
+    }
+
+    fn state(&self) -> StreamState {
+        self.state
+    }
+}
+
+/// The body of a response received over HTTP/2. Reading fails if the connection is lost
+/// before the end of the body.
+pub struct Http2Body {
+    port: Receiver<BodyChunk>,
+    chunk: Cursor<Vec<u8>>,
+    done: bool,
+}
+
+impl Read for Http2Body {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        while !self.done && self.chunk.position() as usize == self.chunk.get_ref().len() {
+            match self.port.recv() {
+                Ok(BodyChunk::Data(data)) => self.chunk = Cursor::new(data),
+                Ok(BodyChunk::End) => self.done = true,
+                Err(_) => {
+                    return Err(io::Error::new(io::ErrorKind::ConnectionAborted,
+                                              "HTTP/2 connection lost"))
+                }
+            }
+        }
+        let len = cmp::min(buf.len(), self.chunk.get_ref().len() - self.chunk.position() as usize);
+        self.chunk.read(&mut buf[..len])
+    }
+}
+
+/// Queues the frames the connection sends for the writing task.
+struct FrameSender(Sender<RawFrame>);
+
+impl SendFrame for FrameSender {
+    fn send_raw_frame(&mut self, frame: RawFrame) -> HttpResult<()> {
+        self.0.send(frame).map_err(|_| connection_closed())
+    }
+}
+
+/// Hands the connection the frames the reading task has read, once it's told one is there.
+struct FrameReceiver(Receiver<HttpFrame>);
+
+impl ReceiveFrame for FrameReceiver {
+    fn recv_frame(&mut self) -> HttpResult<HttpFrame> {
+        self.0.recv().map_err(|_| connection_closed())
+    }
+}
+
+fn connection_closed() -> HttpError {
+    HttpError::from(io::Error::new(io::ErrorKind::ConnectionAborted, "HTTP/2 connection closed"))
+}
+
+type Connection = ClientConnection<FrameSender, FrameReceiver, DefaultSessionState<ResponseStream>>;
+
+/// Runs the connection's session on a task of its own, with one task reading frames and another
+/// writing them, so that neither blocks the other. Returns the channel requests are sent on.
+fn start_connection<S>(stream: ClientStream<S>) -> io::Result<Sender<WorkItem>>
+                       where S: TransportStream + Send + 'static {
+    let ClientStream(stream, scheme, host) = stream;
+    let mut writer = try!(stream.try_split());
+    let mut socket = try!(stream.try_split());
+    let mut reader = stream;
+
+    let (work_chan, work_port) = channel();
+    let (send_chan, send_port) = channel::<RawFrame>();
+    let (frame_chan, frame_port) = channel();
+    let connection = ClientConnection::with_connection(
+        HttpConnection::new(FrameSender(send_chan), FrameReceiver(frame_port), scheme),
+        DefaultSessionState::new());
+
+    let host = host.into_bytes();
+    spawn_named("Http2Connection".to_owned(), move || {
+        let _ = run_connection(connection, host, work_port);
+        // Stop the reading task, which may be waiting on the socket.
+        let _ = socket.close();
+    });
+
+    let write_work_chan = work_chan.clone();
+    spawn_named("Http2Writer".to_owned(), move || {
+        while let Ok(frame) = send_port.recv() {
+            if writer.send_raw_frame(frame).is_err() ||
+               write_work_chan.send(WorkItem::SendData).is_err() {
+                break
+            }
+        }
+    });
+
+    let read_work_chan = work_chan.clone();
+    spawn_named("Http2Reader".to_owned(), move || {
+        while let Ok(frame) = reader.recv_frame() {
+            if frame_chan.send(frame).is_err() ||
+               read_work_chan.send(WorkItem::HandleFrame).is_err() {
+                break
+            }
+        }
+    });
+
+    Ok(work_chan)
+}
+
+/// Handles the connection's work until it fails. Dropping the streams that are still open then
+/// tells their loads that the connection was lost.
+fn run_connection(mut connection: Connection, host: Vec<u8>, work_port: Receiver<WorkItem>)
+                  -> HttpResult<()> {
+    // The server's preface is the first frame read.
+    let mut initialized = false;
+    let mut next_stream_id = 1;
+    while let Ok(work) = work_port.recv() {
+        match work {
+            WorkItem::Request(request) => {
+                let mut headers = vec![
+                    (b":method".to_vec(), request.method),
+                    (b":path".to_vec(), request.path),
+                    (b":authority".to_vec(), host.clone()),
+                    (b":scheme".to_vec(), connection.scheme().as_bytes().to_vec()),
+                ];
+                headers.extend(request.headers.into_iter());
+
+                let mut stream = ResponseStream::new(next_stream_id);
+                next_stream_id += 2;
+                stream.headers_chan = Some(request.headers_chan);
+                stream.body_chan = Some(request.body_chan);
+                match request.body {
+                    Some(body) => stream.request_body = Some(Cursor::new(body)),
+                    None => stream.close_local(),
+                }
+                try!(connection.start_request(RequestStream {
+                    stream: stream,
+                    headers: headers,
+                }));
+            }
+            WorkItem::HandleFrame if !initialized => {
+                try!(connection.init());
+                initialized = true;
+            }
+            WorkItem::HandleFrame => {
+                try!(connection.handle_next_frame());
+                // Streams the server has finished with have nothing more to pass on.
+                connection.state.get_closed();
+            }
+            WorkItem::SendData => {
+                try!(connection.send_next_data());
+            }
+        }
+    }
+    Ok(())
+}
+
+fn connect(host: &str) -> Result<Sender<WorkItem>, TlsConnectError> {
+    let mut certs = resources_dir_path();
+    certs.push("certs");
+    let context = try!(TlsConnector::build_default_context(&certs));
+    let stream: ClientStream<SslStream<TcpStream>> =
+        try!(TlsConnector::with_context(host, &context).connect());
+    Ok(try!(start_connection(stream)))
+}
+
+fn convert_response(response_headers: Vec<Header>, body_port: Receiver<BodyChunk>)
+                    -> Option<Http2Response> {
+    let code = match response_headers.iter().find(|&&(ref name, _)| &**name == b":status") {
+        Some(&(_, ref value)) => {
+            match str::from_utf8(value).ok().and_then(|value| value.parse().ok()) {
+                Some(code) => code,
+                None => return None,
+            }
+        }
+        None => return None,
+    };
+    let status = StatusCode::from_u16(code);
+    let reason = status.canonical_reason().unwrap_or("").to_owned();
+
+    let mut headers = Headers::new();
+    for (name, value) in response_headers.into_iter() {
+        // Pseudo-headers such as `:status` aren't real headers.
+        if name.starts_with(b":") {
+            continue;
+        }
+        let name = match String::from_utf8(name) {
+            Ok(name) => name,
+            Err(_) => continue,
+        };
+        let mut values = headers.get_raw(&name).map_or(vec!(), |values| values.to_vec());
+        values.push(value);
+        headers.set_raw(name, values);
+    }
+
+    Some(Http2Response {
+        status: status,
+        status_raw: RawStatus(code, reason.into()),
+        headers: headers,
+        body: Http2Body {
+            port: body_port,
+            chunk: Cursor::new(vec!()),
+            done: false,
+        },
+    })
+}
@@ -0,0 +1,371 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+//! HTTP authentication with the Basic and Digest schemes, as specified in
+//! https://tools.ietf.org/html/rfc7235. When a server or proxy asks for credentials, the
+//! user is asked through the embedder, and whatever they give is remembered for the rest
+//! of the session.
+
+use hyper::header::Headers;
+use hyper::method::Method;
+use msg::constellation_msg::{AuthenticationRequest, ConstellationChan, Credentials, PipelineId};
+use msg::constellation_msg::Msg as ConstellationMsg;
+use openssl::crypto::hash::{self, Type};
+use rustc_serialize::base64::{STANDARD, ToBase64};
+use rustc_serialize::hex::ToHex;
+use std::ascii::AsciiExt;
+use std::borrow::ToOwned;
+use std::collections::{HashMap, HashSet};
+use std::iter::Peekable;
+use std::str::{self, Chars};
+use std::sync::{Arc, Mutex};
+use std::sync::mpsc::channel;
+use url::Url;
+use uuid;
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum AuthScheme {
+    Basic,
+    Digest,
+}
+
+/// A challenge from a WWW-Authenticate or Proxy-Authenticate header.
+#[derive(Clone, Debug)]
+pub struct AuthChallenge {
+    pub scheme: AuthScheme,
+    pub realm: String,
+    /// The auth-params, with lowercased names.
+    pub params: HashMap<String, String>,
+}
+
+impl AuthChallenge {
+    /// The value of the Authorization header that answers this challenge, or `None` if
+    /// the challenge asks for something that isn't supported.
+    pub fn authorization(&self, credentials: &Credentials, method: &Method, url: &Url)
+                         -> Option<String> {
+        match self.scheme {
+            AuthScheme::Basic => {
+                let user_pass = format!("{}:{}", credentials.username, credentials.password);
+                Some(format!("Basic {}", user_pass.as_bytes().to_base64(STANDARD)))
+            }
+            AuthScheme::Digest => {
+                let cnonce = uuid::Uuid::new_v4().to_simple_string();
+                self.digest_authorization(credentials, method, url, &cnonce)
+            }
+        }
+    }
+
+    /// https://tools.ietf.org/html/rfc2617#section-3.2.2
+    pub fn digest_authorization(&self, credentials: &Credentials, method: &Method, url: &Url,
+                                cnonce: &str) -> Option<String> {
+        let nonce = match self.params.get("nonce") {
+            Some(nonce) => nonce,
+            None => return None,
+        };
+        let algorithm = self.params.get("algorithm");
+        if algorithm.map_or(false, |algorithm| !algorithm.eq_ignore_ascii_case("md5")) {
+            return None;
+        }
+        let qop_auth = self.params.get("qop").map_or(false, |qop| {
+            qop.split(',').any(|qop| qop.trim().eq_ignore_ascii_case("auth"))
+        });
+
+        let mut uri = url.serialize_path().unwrap_or("/".to_owned());
+        if let Some(ref query) = url.query {
+            uri.push('?');
+            uri.push_str(query);
+        }
+        let ha1 = md5_hex(&format!("{}:{}:{}",
+                                   credentials.username, self.realm, credentials.password));
+        let ha2 = md5_hex(&format!("{}:{}", method, uri));
+
+        let mut header = format!("Digest username=\"{}\", realm=\"{}\", nonce=\"{}\", uri=\"{}\"",
+                                 quote(&credentials.username), quote(&self.realm), quote(nonce),
+                                 quote(&uri));
+        if qop_auth {
+            // Each nonce is only used once, since every request gets a new cnonce.
+            let nc = "00000001";
+            let response = md5_hex(&format!("{}:{}:{}:{}:auth:{}", ha1, nonce, nc, cnonce, ha2));
+            header.push_str(&format!(", qop=auth, nc={}, cnonce=\"{}\", response=\"{}\"",
+                                     nc, quote(cnonce), response));
+        } else {
+            let response = md5_hex(&format!("{}:{}:{}", ha1, nonce, ha2));
+            header.push_str(&format!(", response=\"{}\"", response));
+        }
+        if let Some(opaque) = self.params.get("opaque") {
+            header.push_str(&format!(", opaque=\"{}\"", quote(opaque)));
+        }
+        if let Some(algorithm) = algorithm {
+            header.push_str(&format!(", algorithm={}", algorithm));
+        }
+        Some(header)
+    }
+}
+
+fn md5_hex(input: &str) -> String {
+    hash::hash(Type::MD5, input.as_bytes()).to_hex()
+}
+
+/// Escapes a value for a quoted-string.
+fn quote(value: &str) -> String {
+    value.replace("\\", "\\\\").replace("\"", "\\\"")
+}
+
+/// Parses the values of a WWW-Authenticate or Proxy-Authenticate header, leaving out the
+/// challenges in schemes that aren't supported.
+/// https://tools.ietf.org/html/rfc7235#section-4.1
+pub fn parse_challenges(values: &[Vec<u8>]) -> Vec<AuthChallenge> {
+    let mut challenges = vec!();
+    for value in values.iter() {
+        if let Ok(value) = str::from_utf8(value) {
+            parse_challenge_list(value, &mut challenges);
+        }
+    }
+    challenges
+}
+
+fn parse_challenge_list(input: &str, challenges: &mut Vec<AuthChallenge>) {
+    // The scheme and parameters of the challenge being read.
+    let mut current: Option<(String, HashMap<String, String>)> = None;
+    let mut chars = input.chars().peekable();
+    loop {
+        while chars.peek().map_or(false, |&c| c == ',' || is_space(c)) {
+            chars.next();
+        }
+        if chars.peek().is_none() {
+            break;
+        }
+
+        let token = read_token(&mut chars);
+        while chars.peek().map_or(false, |&c| is_space(c)) {
+            chars.next();
+        }
+        if chars.peek() == Some(&'=') {
+            chars.next();
+            while chars.peek().map_or(false, |&c| is_space(c)) {
+                chars.next();
+            }
+            let value = if chars.peek() == Some(&'"') {
+                read_quoted_string(&mut chars)
+            } else {
+                read_token(&mut chars)
+            };
+            if let Some((_, ref mut params)) = current {
+                params.insert(token.to_ascii_lowercase(), value);
+            }
+        } else {
+            push_challenge(current.take(), challenges);
+            current = Some((token, HashMap::new()));
+        }
+    }
+    push_challenge(current, challenges);
+}
+
+fn push_challenge(challenge: Option<(String, HashMap<String, String>)>,
+                  challenges: &mut Vec<AuthChallenge>) {
+    let (scheme, params) = match challenge {
+        Some(challenge) => challenge,
+        None => return,
+    };
+    let scheme = if scheme.eq_ignore_ascii_case("basic") {
+        AuthScheme::Basic
+    } else if scheme.eq_ignore_ascii_case("digest") {
+        AuthScheme::Digest
+    } else {
+        return
+    };
+    let realm = match params.get("realm") {
+        Some(realm) => realm.clone(),
+        None => return,
+    };
+    challenges.push(AuthChallenge {
+        scheme: scheme,
+        realm: realm,
+        params: params,
+    });
+}
+
+fn is_space(c: char) -> bool {
+    c == ' ' || c == '\t'
+}
+
+fn read_token(chars: &mut Peekable<Chars>) -> String {
+    let mut token = String::new();
+    while let Some(&c) = chars.peek() {
+        if c == ',' || c == '=' || is_space(c) {
+            break;
+        }
+        token.push(c);
+        chars.next();
+    }
+    token
+}
+
+fn read_quoted_string(chars: &mut Peekable<Chars>) -> String {
+    let mut value = String::new();
+    chars.next();
+    while let Some(c) = chars.next() {
+        match c {
+            '"' => break,
+            '\\' => {
+                if let Some(c) = chars.next() {
+                    value.push(c);
+                }
+            }
+            c => value.push(c),
+        }
+    }
+    value
+}
+
+/// Where a set of credentials applies: a realm on a server or proxy.
+/// https://tools.ietf.org/html/rfc7235#section-2.2
+#[derive(Clone, PartialEq, Eq, Hash, Debug)]
+pub struct ProtectionSpace {
+    /// The origin of the server, or the host and port of the proxy.
+    pub server: String,
+    pub realm: String,
+    pub proxy: bool,
+}
+
+/// The origin of `url`, as used to identify the server's protection spaces.
+pub fn server_for(url: &Url) -> String {
+    format!("{}://{}:{}", url.scheme,
+            url.host().map_or(String::new(), |host| host.serialize()),
+            url.port_or_default().unwrap_or(0))
+}
+
+struct AuthEntry {
+    challenge: AuthChallenge,
+    credentials: Credentials,
+}
+
+/// The credentials for each protection space, kept for the session. Clones share the same
+/// credentials.
+#[derive(Clone)]
+pub struct AuthCache {
+    entries: Arc<Mutex<HashMap<ProtectionSpace, AuthEntry>>>,
+}
+
+impl AuthCache {
+    pub fn new() -> AuthCache {
+        AuthCache {
+            entries: Arc::new(Mutex::new(HashMap::new())),
+        }
+    }
+
+    pub fn get(&self, space: &ProtectionSpace) -> Option<Credentials> {
+        self.entries.lock().unwrap().get(space).map(|entry| entry.credentials.clone())
+    }
+
+    pub fn store(&self, space: ProtectionSpace, challenge: AuthChallenge,
+                 credentials: Credentials) {
+        self.entries.lock().unwrap().insert(space, AuthEntry {
+            challenge: challenge,
+            credentials: credentials,
+        });
+    }
+
+    pub fn remove(&self, space: &ProtectionSpace) {
+        self.entries.lock().unwrap().remove(space);
+    }
+
+    /// The Authorization header to send to `url` without waiting to be asked, which is only
+    /// done once Basic credentials are known for its server.
+    pub fn preemptive_authorization(&self, url: &Url) -> Option<String> {
+        let server = server_for(url);
+        let entries = self.entries.lock().unwrap();
+        entries.iter().find(|&(space, entry)| {
+            !space.proxy && space.server == server && entry.challenge.scheme == AuthScheme::Basic
+        }).and_then(|(_, entry)| {
+            entry.challenge.authorization(&entry.credentials, &Method::Get, url)
+        })
+    }
+}
+
+/// Answers the challenges in a 401 response, or a 407 one from `proxy`, with the credentials
+/// that are known for the protection space, or else with the ones the user gives. Returns
+/// the Authorization or Proxy-Authorization header to send the request again with, or
+/// `None` if the response is to be delivered as it is.
+/// `tried` holds the protection spaces whose known credentials this load has already
+/// tried, so that the user is asked when they turn out to be wrong.
+pub fn answer_challenges(headers: &Headers,
+                         proxy: Option<&str>,
+                         url: &Url,
+                         method: &Method,
+                         pipeline_id: Option<PipelineId>,
+                         auth_cache: &AuthCache,
+                         constellation_chan: &Option<ConstellationChan>,
+                         tried: &mut HashSet<ProtectionSpace>)
+                         -> Option<String> {
+    let challenge_header = match proxy {
+        Some(_) => "proxy-authenticate",
+        None => "www-authenticate",
+    };
+    let challenges = headers.get_raw(challenge_header).map_or(vec!(), |values| {
+        parse_challenges(values)
+    });
+    // Digest is the safer of the two, so it's preferred when both are offered.
+    let challenge = match challenges.iter().find(|challenge| {
+        challenge.scheme == AuthScheme::Digest
+    }).or(challenges.first()) {
+        Some(challenge) => challenge.clone(),
+        None => return None,
+    };
+
+    let space = ProtectionSpace {
+        server: proxy.map_or_else(|| server_for(url), |proxy| proxy.to_owned()),
+        realm: challenge.realm.clone(),
+        proxy: proxy.is_some(),
+    };
+    // A stale nonce only means the credentials have to be sent again with a new one.
+    let stale = challenge.params.get("stale").map_or(false, |stale| {
+        stale.eq_ignore_ascii_case("true")
+    });
+    let known = match auth_cache.get(&space) {
+        Some(ref credentials) if stale || !tried.contains(&space) => Some(credentials.clone()),
+        _ => None,
+    };
+    let credentials = match known {
+        Some(credentials) => credentials,
+        None => {
+            auth_cache.remove(&space);
+            let request = AuthenticationRequest {
+                url: url.clone(),
+                host: space.server.clone(),
+                realm: space.realm.clone(),
+                proxy: space.proxy,
+            };
+            match request_credentials(constellation_chan, pipeline_id, request) {
+                Some(credentials) => credentials,
+                None => return None,
+            }
+        }
+    };
+    tried.insert(space.clone());
+
+    let authorization = match challenge.authorization(&credentials, method, url) {
+        Some(authorization) => authorization,
+        None => return None,
+    };
+    auth_cache.store(space, challenge, credentials);
+    Some(authorization)
+}
+
+/// Asks the user for credentials through the embedder. Only this load waits for the answer;
+/// the embedder asks without blocking the compositor.
+fn request_credentials(constellation_chan: &Option<ConstellationChan>,
+                       pipeline_id: Option<PipelineId>,
+                       request: AuthenticationRequest)
+                       -> Option<Credentials> {
+    let chan = match *constellation_chan {
+        Some(ConstellationChan(ref chan)) => chan,
+        None => return None,
+    };
+    let (sender, receiver) = channel();
+    if chan.send(ConstellationMsg::RequestCredentials(pipeline_id, request, sender)).is_err() {
+        return None;
+    }
+    receiver.recv().unwrap_or(None)
+}
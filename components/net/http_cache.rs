@@ -0,0 +1,235 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+//! An in-memory cache of HTTP responses. Fresh responses are served without touching the
+//! network, and stale ones are revalidated with the validators the server sent along.
+//!
+//! Only responses to GET requests are stored, one per URL. A response that varies on request
+//! headers is only used for requests that send the same values for them as the one it answered.
+
+use net_traits::Metadata;
+
+use hyper::header::{CacheControl, CacheDirective, Date, Expires, Headers, HttpDate};
+use hyper::method::Method;
+use std::ascii::AsciiExt;
+use std::borrow::ToOwned;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use time::{self, Duration, Timespec};
+use url::Url;
+
+/// The total size of the response bodies the cache holds on to.
+const MAX_CACHE_SIZE: usize = 32 * 1024 * 1024;
+
+#[derive(Clone)]
+pub struct CachedResponse {
+    pub metadata: Metadata,
+    pub body: Arc<Vec<u8>>,
+    /// When the response was last received or revalidated.
+    stored_at: Timespec,
+    /// The time after which the response must be revalidated, if the server said.
+    expires_at: Option<Timespec>,
+    /// The request headers named by the response's Vary header, with the values the request
+    /// sent for them.
+    /// https://tools.ietf.org/html/rfc7234#section-4.1
+    varied_headers: Vec<(String, Option<Vec<Vec<u8>>>)>,
+}
+
+impl CachedResponse {
+    fn new(metadata: Metadata,
+           body: Vec<u8>,
+           varied_headers: Vec<(String, Option<Vec<Vec<u8>>>)>)
+           -> CachedResponse {
+        let now = time::get_time();
+        let expires_at = metadata.headers.as_ref()
+                                         .and_then(freshness_lifetime)
+                                         .map(|lifetime| now + lifetime);
+        CachedResponse {
+            metadata: metadata,
+            body: Arc::new(body),
+            stored_at: now,
+            expires_at: expires_at,
+            varied_headers: varied_headers,
+        }
+    }
+
+    /// Whether the request with these headers sends the same values for the headers the response
+    /// varies on as the request it answered.
+    fn matches(&self, request_headers: &Headers) -> bool {
+        self.varied_headers.iter().all(|&(ref name, ref value)| {
+            request_headers.get_raw(name).map(|value| value.to_vec()) == *value
+        })
+    }
+
+    /// Whether the response may be used without asking the server.
+    pub fn is_fresh(&self) -> bool {
+        self.expires_at.map_or(false, |expires_at| time::get_time() < expires_at)
+    }
+
+    /// Adds the conditional request headers that let the server answer with
+    /// 304 Not Modified if the stored response is still valid.
+    pub fn add_validators(&self, request_headers: &mut Headers) {
+        let headers = match self.metadata.headers {
+            Some(ref headers) => headers,
+            None => return,
+        };
+        if let Some(etag) = headers.get_raw("etag") {
+            request_headers.set_raw("If-None-Match".to_owned(), etag.to_vec());
+        }
+        if let Some(last_modified) = headers.get_raw("last-modified") {
+            request_headers.set_raw("If-Modified-Since".to_owned(), last_modified.to_vec());
+        }
+    }
+}
+
+/// A handle to the cache; clones share the same entries.
+#[derive(Clone)]
+pub struct HttpCache {
+    entries: Arc<Mutex<HashMap<Url, CachedResponse>>>,
+}
+
+impl HttpCache {
+    pub fn new() -> HttpCache {
+        HttpCache {
+            entries: Arc::new(Mutex::new(HashMap::new())),
+        }
+    }
+
+    /// The response stored for `url` that may answer a request with this method and headers.
+    pub fn get(&self, url: &Url, method: &Method, request_headers: &Headers)
+               -> Option<CachedResponse> {
+        if *method != Method::Get {
+            return None;
+        }
+        match self.entries.lock().unwrap().get(url) {
+            Some(entry) if entry.matches(request_headers) => Some(entry.clone()),
+            _ => None,
+        }
+    }
+
+    /// Stores the complete body of a 200 response to a GET request with the given headers, if
+    /// the response's headers allow it. Any response stored for `url` before is dropped either way.
+    pub fn store(&self,
+                 url: &Url,
+                 method: &Method,
+                 request_headers: &Headers,
+                 metadata: Metadata,
+                 body: Vec<u8>) {
+        let mut entries = self.entries.lock().unwrap();
+        entries.remove(url);
+
+        if *method != Method::Get || body.len() > MAX_CACHE_SIZE {
+            return;
+        }
+        let varied_headers = match metadata.headers {
+            Some(ref headers) if is_cacheable(headers) => {
+                match varied_headers(headers, request_headers) {
+                    Some(varied_headers) => varied_headers,
+                    None => return,
+                }
+            }
+            _ => return,
+        };
+
+        let mut size = entries.values().fold(body.len(), |size, entry| size + entry.body.len());
+        while size > MAX_CACHE_SIZE {
+            let oldest = oldest_entry(&entries).unwrap();
+            size -= entries.remove(&oldest).unwrap().body.len();
+        }
+        entries.insert(url.clone(), CachedResponse::new(metadata, body, varied_headers));
+    }
+
+    /// Refreshes the stored response for `url` after the server answered a conditional
+    /// request with 304 Not Modified, returning the response to replay.
+    pub fn revalidate(&self, url: &Url, response_headers: &Headers) -> Option<CachedResponse> {
+        let mut entries = self.entries.lock().unwrap();
+        let entry = match entries.get_mut(url) {
+            Some(entry) => entry,
+            None => return None,
+        };
+        // https://tools.ietf.org/html/rfc7234#section-4.3.4
+        if let Some(ref mut headers) = entry.metadata.headers {
+            headers.extend(response_headers.iter());
+        }
+        let now = time::get_time();
+        entry.stored_at = now;
+        entry.expires_at = entry.metadata.headers.as_ref()
+                                                 .and_then(freshness_lifetime)
+                                                 .map(|lifetime| now + lifetime);
+        Some(entry.clone())
+    }
+
+    /// Drops the stored response for `url`, after a request that may have changed it.
+    /// https://tools.ietf.org/html/rfc7234#section-4.4
+    pub fn invalidate(&self, url: &Url) {
+        self.entries.lock().unwrap().remove(url);
+    }
+}
+
+/// The URL of the entry that was stored or revalidated the longest time ago.
+fn oldest_entry(entries: &HashMap<Url, CachedResponse>) -> Option<Url> {
+    let mut oldest: Option<(&Url, Timespec)> = None;
+    for (url, entry) in entries.iter() {
+        if oldest.map_or(true, |(_, stored_at)| entry.stored_at < stored_at) {
+            oldest = Some((url, entry.stored_at));
+        }
+    }
+    oldest.map(|(url, _)| url.clone())
+}
+
+/// Whether a response with these headers may be stored at all: either it has an explicit
+/// freshness lifetime or it can be revalidated.
+fn is_cacheable(headers: &Headers) -> bool {
+    if let Some(&CacheControl(ref directives)) = headers.get::<CacheControl>() {
+        if directives.iter().any(|directive| *directive == CacheDirective::NoStore) {
+            return false;
+        }
+    }
+    freshness_lifetime(headers).is_some() ||
+        headers.get_raw("etag").is_some() ||
+        headers.get_raw("last-modified").is_some()
+}
+
+/// The names of the request headers the response varies on, lowercased, with the values the
+/// request sent for them; or `None` if the response varies on something other than headers.
+fn varied_headers(response_headers: &Headers, request_headers: &Headers)
+                  -> Option<Vec<(String, Option<Vec<Vec<u8>>>)>> {
+    let vary = match response_headers.get_raw("vary") {
+        Some(vary) => vary,
+        None => return Some(vec!()),
+    };
+    let mut varied_headers = vec!();
+    for line in vary.iter() {
+        let line = String::from_utf8_lossy(line);
+        for name in line.split(',').map(|name| name.trim()).filter(|name| !name.is_empty()) {
+            if name == "*" {
+                return None;
+            }
+            let value = request_headers.get_raw(name).map(|value| value.to_vec());
+            varied_headers.push((name.to_ascii_lowercase(), value));
+        }
+    }
+    Some(varied_headers)
+}
+
+/// https://tools.ietf.org/html/rfc7234#section-4.2.1
+fn freshness_lifetime(headers: &Headers) -> Option<Duration> {
+    if let Some(&CacheControl(ref directives)) = headers.get::<CacheControl>() {
+        if directives.iter().any(|directive| *directive == CacheDirective::NoCache) {
+            return None;
+        }
+        for directive in directives.iter() {
+            if let CacheDirective::MaxAge(seconds) = *directive {
+                return Some(Duration::seconds(seconds as i64));
+            }
+        }
+    }
+    match (headers.get::<Expires>(), headers.get::<Date>()) {
+        (Some(&Expires(HttpDate(ref expires))), Some(&Date(HttpDate(ref date)))) => {
+            let lifetime = expires.to_timespec() - date.to_timespec();
+            if lifetime > Duration::zero() { Some(lifetime) } else { None }
+        }
+        _ => None,
+    }
+}
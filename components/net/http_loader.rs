@@ -4,28 +4,38 @@
 
 use net_traits::{ControlMsg, CookieSource, LoadData, Metadata, LoadConsumer};
 use net_traits::ProgressMsg::{Payload, Done};
+use certificate::{self, CertificateCheck, CertificateOverrides};
+use http_auth::{self, AuthCache};
 use devtools_traits::{DevtoolsControlMsg, NetworkEvent};
+use hsts::{secure_url, HSTSList};
+use http_cache::{CachedResponse, HttpCache};
+use http2::{Http2Connections, Http2Response};
 use mime_classifier::MIMEClassifier;
+use proxy::{ProxyConfig, ProxyConnector};
+use referrer::determine_request_referrer;
 use resource_task::{start_sending_opt, start_sending_sniffed_opt};
 
 use log;
 use std::collections::HashSet;
-use file_loader;
-use flate2::read::{DeflateDecoder, GzDecoder};
-use hyper::client::Request;
-use hyper::header::{AcceptEncoding, Accept, ContentLength, ContentType, Host, Location, qitem, Quality, QualityItem};
-use hyper::Error as HttpError;
+use flate2::read::{DeflateDecoder, GzDecoder, ZlibDecoder};
+use hyper::client::{Request, Response};
+use hyper::header::{AcceptEncoding, Accept, ContentLength, ContentType, Headers, Host, Location};
+use hyper::header::Referer;
+use hyper::header::{qitem, Quality, QualityItem};
+use hyper::http::RawStatus;
 use hyper::method::Method;
 use hyper::mime::{Mime, TopLevel, SubLevel};
 use hyper::net::HttpConnector;
 use hyper::status::{StatusCode, StatusClass};
+use msg::constellation_msg::{CertificateError, ConstellationChan};
 use std::error::Error;
-use openssl::ssl::{SslContext, SSL_VERIFY_PEER};
-use std::io::{self, Read, Write};
+use std::ascii::AsciiExt;
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::str;
 use std::sync::Arc;
 use std::sync::mpsc::{Sender, channel};
+use time::{self, precise_time_ns};
 use util::task::spawn_named;
-use util::resource_files::resources_dir_path;
 use util::opts;
 use url::{Url, UrlParser};
 
@@ -33,11 +43,43 @@ use uuid;
 use std::borrow::ToOwned;
 use std::boxed::FnBox;
 
-pub fn factory(cookies_chan: Sender<ControlMsg>, devtools_chan: Option<Sender<DevtoolsControlMsg>>)
+pub fn factory(cookies_chan: Sender<ControlMsg>,
+               devtools_chan: Option<Sender<DevtoolsControlMsg>>,
+               http_cache: HttpCache,
+               http2_connections: Http2Connections,
+               hsts_list: HSTSList,
+               proxy_config: ProxyConfig,
+               certificate_overrides: CertificateOverrides,
+               auth_cache: AuthCache,
+               constellation_chan: Option<ConstellationChan>,
+               connection_host: Option<String>)
                -> Box<FnBox(LoadData, LoadConsumer, Arc<MIMEClassifier>) + Send> {
     box move |load_data, senders, classifier| {
-        spawn_named("http_loader".to_owned(),
-                    move || load(load_data, senders, classifier, cookies_chan, devtools_chan))
+        spawn_named("http_loader".to_owned(), move || {
+            let _slot = ConnectionSlot {
+                resource_task: cookies_chan.clone(),
+                host: connection_host,
+            };
+            load(load_data, senders, classifier, cookies_chan, devtools_chan, http_cache,
+                 http2_connections, hsts_list, proxy_config, certificate_overrides, auth_cache,
+                 constellation_chan);
+        })
+    }
+}
+
+/// The load's share of its host's connection limit, which is given back however the load
+/// ends, even if it panics.
+struct ConnectionSlot {
+    resource_task: Sender<ControlMsg>,
+    host: Option<String>,
+}
+
+impl Drop for ConnectionSlot {
+    fn drop(&mut self) {
+        // Let the resource task start the next load queued for this host.
+        if let Some(host) = self.host.take() {
+            let _ = self.resource_task.send(ControlMsg::LoadFinished(host));
+        }
     }
 }
 
@@ -51,6 +93,21 @@ fn send_error(url: Url, err: String, start_chan: LoadConsumer) {
     };
 }
 
+/// Serves an error page in place of the response from a server whose certificate couldn't
+/// be verified. The error goes along with it, so that the embedder can be told.
+fn send_certificate_error(error: CertificateError, start_chan: LoadConsumer) {
+    let page = certificate::error_page(&error);
+    let mut metadata = Metadata::default(error.url.clone());
+    metadata.content_type = Some(ContentType(Mime(TopLevel::Text, SubLevel::Html, vec![])));
+    metadata.charset = Some("utf-8".to_owned());
+    metadata.certificate_error = Some(error);
+
+    if let Ok(chan) = start_sending_opt(start_chan, metadata) {
+        let _ = chan.send(Payload(page.into_bytes()));
+        let _ = chan.send(Done(Ok(())));
+    }
+}
+
 enum ReadResult {
     Payload(Vec<u8>),
     EOF,
@@ -69,8 +126,39 @@ fn read_block<R: Read>(reader: &mut R) -> Result<ReadResult, ()> {
     }
 }
 
+/// A response received over either protocol.
+struct HttpResponse {
+    status: StatusCode,
+    status_raw: RawStatus,
+    headers: Headers,
+    body: Box<Read>,
+}
+
+impl HttpResponse {
+    fn from_http1(response: Response) -> HttpResponse {
+        HttpResponse {
+            status: response.status,
+            status_raw: response.status_raw().clone(),
+            headers: response.headers.clone(),
+            body: box response,
+        }
+    }
+
+    fn from_http2(response: Http2Response) -> HttpResponse {
+        HttpResponse {
+            status: response.status,
+            status_raw: response.status_raw,
+            headers: response.headers,
+            body: box response.body,
+        }
+    }
+}
+
 fn load(mut load_data: LoadData, start_chan: LoadConsumer, classifier: Arc<MIMEClassifier>,
-        cookies_chan: Sender<ControlMsg>, devtools_chan: Option<Sender<DevtoolsControlMsg>>) {
+        cookies_chan: Sender<ControlMsg>, devtools_chan: Option<Sender<DevtoolsControlMsg>>,
+        http_cache: HttpCache, http2_connections: Http2Connections, hsts_list: HSTSList,
+        proxy_config: ProxyConfig, certificate_overrides: CertificateOverrides,
+        auth_cache: AuthCache, constellation_chan: Option<ConstellationChan>) {
     // FIXME: At the time of writing this FIXME, servo didn't have any central
     //        location for configuration. If you're reading this and such a
     //        repository DOES exist, please update this constant to use it.
@@ -78,6 +166,12 @@ fn load(mut load_data: LoadData, start_chan: LoadConsumer, classifier: Arc<MIMEC
     let mut iters = 0;
     let mut url = load_data.url.clone();
     let mut redirected_to = HashSet::new();
+    let mut redirected = false;
+    // The credentials to send once the server or proxy has asked for them, and the
+    // protection spaces whose known credentials have been tried already.
+    let mut authorization: Option<String> = None;
+    let mut proxy_authorization: Option<String> = None;
+    let mut tried_credentials = HashSet::new();
 
     // If the URL is a view-source scheme then the scheme data contains the
     // real URL that should be used for which the source is to be viewed.
@@ -115,71 +209,71 @@ fn load(mut load_data: LoadData, start_chan: LoadConsumer, classifier: Arc<MIMEC
             }
         }
 
-        info!("requesting {}", url.serialize());
-
-        fn verifier(ssl: &mut SslContext) {
-            ssl.set_verify(SSL_VERIFY_PEER, None);
-            let mut certs = resources_dir_path();
-            certs.push("certs");
-            ssl.set_CA_file(&certs).unwrap();
-        };
-
-        let ssl_err_string = "Some(OpenSslErrors([UnknownError { library: \"SSL routines\", \
-function: \"SSL3_GET_SERVER_CERTIFICATE\", \
-reason: \"certificate verify failed\" }]))";
-
-        let mut connector = if opts::get().nossl {
-            HttpConnector(None)
-        } else {
-            HttpConnector(Some(box verifier as Box<Fn(&mut SslContext) + Send>))
-        };
-
-        let mut req = match Request::with_connector(load_data.method.clone(), url.clone(), &mut connector) {
-            Ok(req) => req,
-            Err(HttpError::Io(ref io_error)) if (
-                io_error.kind() == io::ErrorKind::Other &&
-                io_error.description() == "Error in OpenSSL" &&
-                // FIXME: This incredibly hacky. Make it more robust, and at least test it.
-                format!("{:?}", io_error.cause()) == ssl_err_string
-            ) => {
-                let mut image = resources_dir_path();
-                image.push("badcert.html");
-                let load_data = LoadData::new(Url::from_file_path(&*image).unwrap(), None);
-                file_loader::factory(load_data, start_chan, classifier);
-                return;
-            },
-            Err(e) => {
-                println!("{:?}", e);
-                send_error(url, e.description().to_string(), start_chan);
-                return;
+        // Hosts that asked for https are never contacted over plain http.
+        if url.scheme == "http" {
+            if let Some(host) = url.domain().map(|host| host.to_owned()) {
+                if hsts_list.is_host_secure(&host) {
+                    info!("upgrading {} to https", url.serialize());
+                    url = secure_url(&url);
+                }
             }
-        };
+        }
+
+        // Secure documents may not load active content over insecure connections.
+        // https://w3c.github.io/webappsec/specs/mixedcontent/#should-block-fetch
+        if load_data.block_mixed_content && url.scheme == "http" {
+            send_error(url, "blocked insecure content in a secure document".to_owned(),
+                       start_chan);
+            return;
+        }
 
-        // Preserve the `host` header set automatically by Request.
-        let host = req.headers().get::<Host>().unwrap().clone();
+        info!("requesting {}", url.serialize());
 
         // Avoid automatically preserving request headers when redirects occur.
         // See https://bugzilla.mozilla.org/show_bug.cgi?id=401564 and
         // https://bugzilla.mozilla.org/show_bug.cgi?id=216828 .
         // Only preserve ones which have been explicitly marked as such.
-        if iters == 1 {
+        let mut request_headers = if !redirected {
             let mut combined_headers = load_data.headers.clone();
             combined_headers.extend(load_data.preserved_headers.iter());
-            *req.headers_mut() = combined_headers;
+            combined_headers
         } else {
-            *req.headers_mut() = load_data.preserved_headers.clone();
+            load_data.preserved_headers.clone()
+        };
+
+        // What the Referer reveals depends on the URL being requested, so it's worked out
+        // again after each redirect.
+        request_headers.remove::<Referer>();
+        if let Some(ref referrer_url) = load_data.referrer_url {
+            if let Some(referrer) = determine_request_referrer(referrer_url,
+                                                               load_data.referrer_policy,
+                                                               &url) {
+                request_headers.set(Referer(referrer));
+            }
         }
 
-        req.headers_mut().set(host);
+        match authorization {
+            Some(ref authorization) => {
+                request_headers.set_raw("Authorization".to_owned(),
+                                        vec![authorization.clone().into_bytes()]);
+            }
+            None if request_headers.get_raw("authorization").is_none() => {
+                if let Some(authorization) = auth_cache.preemptive_authorization(&url) {
+                    request_headers.set_raw("Authorization".to_owned(),
+                                            vec![authorization.into_bytes()]);
+                }
+            }
+            None => {}
+        }
 
-        if !req.headers().has::<Accept>() {
+        if !request_headers.has::<Accept>() {
             let accept = Accept(vec![
                 qitem(Mime(TopLevel::Text, SubLevel::Html, vec![])),
                 qitem(Mime(TopLevel::Application, SubLevel::Ext("xhtml+xml".to_string()), vec![])),
                 QualityItem::new(Mime(TopLevel::Application, SubLevel::Xml, vec![]), Quality(900u16)),
                 QualityItem::new(Mime(TopLevel::Star, SubLevel::Star, vec![]), Quality(800u16)),
             ]);
-            req.headers_mut().set(accept);
+            request_headers.set(accept);
         }
 
         let (tx, rx) = channel();
@@ -187,47 +281,207 @@ reason: \"certificate verify failed\" }]))";
         if let Some(cookie_list) = rx.recv().unwrap() {
             let mut v = Vec::new();
             v.push(cookie_list.into_bytes());
-            req.headers_mut().set_raw("Cookie".to_owned(), v);
+            request_headers.set_raw("Cookie".to_owned(), v);
+        }
+
+        if !request_headers.has::<AcceptEncoding>() {
+            request_headers.set_raw("Accept-Encoding".to_owned(), vec![b"gzip, deflate".to_vec()]);
         }
 
-        if !req.headers().has::<AcceptEncoding>() {
-            req.headers_mut().set_raw("Accept-Encoding".to_owned(), vec![b"gzip, deflate".to_vec()]);
+        // Only GET requests are answered from the cache, with a response to a request that sent
+        // the same values for the headers it varies on. A fresh response is used as is; a stale
+        // one is revalidated.
+        let cached = if !viewing_source {
+            http_cache.get(&url, &load_data.method, &request_headers)
+        } else {
+            None
+        };
+        if let Some(ref cached) = cached {
+            if cached.is_fresh() {
+                info!("using cached response for {}", url.serialize());
+                send_cached(cached.clone(), start_chan, classifier);
+                return;
+            }
         }
+
+        // The response is stored along with the request headers it varies on, as they were
+        // before the validators were added.
+        let cache_request_headers = request_headers.clone();
+
+        // Unless the request is already conditional, ask the server whether the cached
+        // response is still valid.
+        let revalidating = match cached {
+            Some(ref cached) if request_headers.get_raw("if-none-match").is_none() &&
+                                request_headers.get_raw("if-modified-since").is_none() => {
+                cached.add_validators(&mut request_headers);
+                true
+            }
+            _ => false,
+        };
         if log_enabled!(log::LogLevel::Info) {
             info!("{}", load_data.method);
-            for header in req.headers().iter() {
+            for header in request_headers.iter() {
                 info!(" - {}", header);
             }
             info!("{:?}", load_data.data);
         }
 
         // Avoid automatically sending request body if a redirect has occurred.
-        let writer = match load_data.data {
-            Some(ref data) if iters == 1 => {
-                req.headers_mut().set(ContentLength(data.len() as u64));
-                let mut writer = match req.start() {
-                    Ok(w) => w,
-                    Err(e) => {
-                        send_error(url, e.description().to_string(), start_chan);
-                        return;
+        let request_body = match load_data.data {
+            Some(ref data) if !redirected => Some(data.clone()),
+            _ => None,
+        };
+
+        // Send an HttpRequest message to devtools with a unique request_id, for the page that
+        // made the request.
+        let request_id = uuid::Uuid::new_v4().to_simple_string();
+        let request_start = precise_time_ns();
+        if let (Some(ref chan), Some(pipeline_id)) = (devtools_chan.as_ref(),
+                                                      load_data.pipeline_id) {
+            let net_event = NetworkEvent::HttpRequest(url.clone(),
+                                                      load_data.method.clone(),
+                                                      request_headers.clone(),
+                                                      request_body.clone(),
+                                                      time::now_utc());
+            chan.send(DevtoolsControlMsg::NetworkEventMessage(pipeline_id,
+                                                              request_id.clone(),
+                                                              net_event)).unwrap();
+        }
+
+        let proxy = proxy_config.proxy_for(&url);
+        let proxy_server = proxy.as_ref().map(|&(ref host, port)| format!("{}:{}", host, port));
+        // Requests tunneled through the proxy go on to the server, which mustn't see the
+        // proxy's credentials, so those are sent with CONNECT instead.
+        if let (Some(_), Some(ref proxy_authorization)) = (proxy.as_ref(),
+                                                            proxy_authorization.as_ref()) {
+            if url.scheme == "http" {
+                request_headers.set_raw("Proxy-Authorization".to_owned(),
+                                        vec![proxy_authorization.clone().into_bytes()]);
+            }
+        }
+
+        // Use the origin's HTTP/2 connection if it has one, and HTTP/1.1 otherwise.
+        // Proxied requests always use HTTP/1.1.
+        let http2_response = if opts::get().nossl || proxy.is_some() {
+            None
+        } else {
+            match http2_connections.send(&load_data.method, &url, &request_headers,
+                                         request_body.clone()) {
+                Ok(response) => response,
+                Err(e) => {
+                    send_error(url, e, start_chan);
+                    return;
+                }
+            }
+        };
+
+        let mut response = match http2_response {
+            Some(response) => HttpResponse::from_http2(response),
+            None => {
+                let check = CertificateCheck::new();
+                let verifier = if opts::get().nossl {
+                    None
+                } else {
+                    Some(check.verifier())
+                };
+
+                // A proxy that wants credentials before opening a tunnel says so in its answer
+                // to CONNECT, since the request never reaches the server.
+                let mut tunnel_challenges = None;
+                let req = match proxy {
+                    Some(proxy) => {
+                        let mut connector = ProxyConnector {
+                            proxy: proxy,
+                            verifier: verifier,
+                            authorization: proxy_authorization.clone(),
+                            challenges: None,
+                        };
+                        let req = Request::with_connector(load_data.method.clone(), url.clone(),
+                                                          &mut connector);
+                        tunnel_challenges = connector.challenges.take();
+                        req
+                    }
+                    None => {
+                        let mut connector = HttpConnector(verifier);
+                        Request::with_connector(load_data.method.clone(), url.clone(),
+                                                &mut connector)
                     }
                 };
-                match writer.write_all(&*data) {
+                let mut req = match req {
+                    Ok(req) => req,
                     Err(e) => {
+                        if let Some(challenges) = tunnel_challenges {
+                            let mut headers = Headers::new();
+                            headers.set_raw("Proxy-Authenticate".to_owned(), challenges);
+                            let answer = http_auth::answer_challenges(
+                                &headers, proxy_server.as_ref().map(|s| &**s), &url,
+                                &load_data.method, load_data.pipeline_id, &auth_cache,
+                                &constellation_chan, &mut tried_credentials);
+                            if let Some(answer) = answer {
+                                proxy_authorization = Some(answer);
+                                continue;
+                            }
+                        }
+                        println!("{:?}", e);
                         send_error(url, e.description().to_string(), start_chan);
                         return;
                     }
-                    _ => {}
                 };
-                writer
-            },
-            _ => {
-                match load_data.method {
-                    Method::Get | Method::Head => (),
-                    _ => req.headers_mut().set(ContentLength(0))
+
+                // Nothing has been sent yet, so an untrusted server learns nothing.
+                if let Some(error) = check.error(&url) {
+                    if !certificate_overrides.is_allowed(&error.host, &error.fingerprint) {
+                        if load_data.is_navigation {
+                            send_certificate_error(error, start_chan);
+                        } else {
+                            send_error(url, format!("untrusted certificate: {}", error.reason),
+                                       start_chan);
+                        }
+                        return;
+                    }
                 }
-                match req.start() {
-                    Ok(w) => w,
+
+                // Preserve the `host` header set automatically by Request.
+                let host = req.headers().get::<Host>().unwrap().clone();
+                *req.headers_mut() = request_headers;
+                req.headers_mut().set(host);
+
+                let writer = match request_body {
+                    Some(ref data) => {
+                        req.headers_mut().set(ContentLength(data.len() as u64));
+                        let mut writer = match req.start() {
+                            Ok(w) => w,
+                            Err(e) => {
+                                send_error(url, e.description().to_string(), start_chan);
+                                return;
+                            }
+                        };
+                        match writer.write_all(&*data) {
+                            Err(e) => {
+                                send_error(url, e.description().to_string(), start_chan);
+                                return;
+                            }
+                            _ => {}
+                        };
+                        writer
+                    },
+                    None => {
+                        match load_data.method {
+                            Method::Get | Method::Head => (),
+                            _ => req.headers_mut().set(ContentLength(0))
+                        }
+                        match req.start() {
+                            Ok(w) => w,
+                            Err(e) => {
+                                send_error(url, e.description().to_string(), start_chan);
+                                return;
+                            }
+                        }
+                    }
+                };
+
+                match writer.send() {
+                    Ok(r) => HttpResponse::from_http1(r),
                     Err(e) => {
                         send_error(url, e.description().to_string(), start_chan);
                         return;
@@ -236,25 +490,6 @@ reason: \"certificate verify failed\" }]))";
             }
         };
 
-        // Send an HttpRequest message to devtools with a unique request_id
-        // TODO: Do this only if load_data has some pipeline_id, and send the pipeline_id in the message
-        let request_id = uuid::Uuid::new_v4().to_simple_string();
-        if let Some(ref chan) = devtools_chan {
-            let net_event = NetworkEvent::HttpRequest(load_data.url.clone(),
-                                                      load_data.method.clone(),
-                                                      load_data.headers.clone(),
-                                                      load_data.data.clone());
-            chan.send(DevtoolsControlMsg::NetworkEventMessage(request_id.clone(), net_event)).unwrap();
-        }
-
-        let mut response = match writer.send() {
-            Ok(r) => r,
-            Err(e) => {
-                send_error(url, e.description().to_string(), start_chan);
-                return;
-            }
-        };
-
         // Dump headers, but only do the iteration if info!() is enabled.
         info!("got HTTP response {}, headers:", response.status);
         if log_enabled!(log::LogLevel::Info) {
@@ -263,6 +498,14 @@ reason: \"certificate verify failed\" }]))";
             }
         }
 
+        // Strict-Transport-Security headers only count when received securely.
+        if url.scheme == "https" {
+            if let (Some(host), Some(sts)) = (url.domain(),
+                                              response.headers.get_raw("strict-transport-security")) {
+                hsts_list.update(host, sts);
+            }
+        }
+
         if let Some(cookies) = response.headers.get_raw("set-cookie") {
             for cookie in cookies.iter() {
                 if let Ok(cookies) = String::from_utf8(cookie.clone()) {
@@ -273,6 +516,42 @@ reason: \"certificate verify failed\" }]))";
             }
         }
 
+        // Send the request again with credentials if the server or proxy asks for them and
+        // they can be found.
+        let proxy_challenge = response.status == StatusCode::ProxyAuthenticationRequired;
+        if response.status == StatusCode::Unauthorized || proxy_challenge {
+            let proxy = if proxy_challenge { proxy_server.as_ref().map(|s| &**s) } else { None };
+            if !proxy_challenge || proxy.is_some() {
+                let answer = http_auth::answer_challenges(&response.headers, proxy, &url,
+                                                          &load_data.method,
+                                                          load_data.pipeline_id, &auth_cache,
+                                                          &constellation_chan,
+                                                          &mut tried_credentials);
+                if let Some(answer) = answer {
+                    if proxy_challenge {
+                        proxy_authorization = Some(answer);
+                    } else {
+                        authorization = Some(answer);
+                    }
+                    continue;
+                }
+            }
+        }
+
+        if revalidating && response.status == StatusCode::NotModified {
+            if let Some(cached) = http_cache.revalidate(&url, &response.headers) {
+                info!("revalidated cached response for {}", url.serialize());
+                send_cached(cached, start_chan, classifier);
+                return;
+            }
+        }
+
+        // A successful unsafe request may have changed the resource.
+        let safe_method = load_data.method == Method::Get || load_data.method == Method::Head;
+        if !safe_method && response.status.class() == StatusClass::Success {
+            http_cache.invalidate(&url);
+        }
+
         if response.status.class() == StatusClass::Redirection {
             match response.headers.get::<Location>() {
                 Some(&Location(ref new_url)) => {
@@ -316,66 +595,87 @@ reason: \"certificate verify failed\" }]))";
                     }
 
                     redirected_to.insert(url.clone());
+                    redirected = true;
+                    // Credentials for the old URL's server are no good for the new one's.
+                    authorization = None;
                     continue;
                 }
                 None => ()
             }
         }
 
+        let content_codings = content_codings(&response.headers);
+
         let mut adjusted_headers = response.headers.clone();
         if viewing_source {
             adjusted_headers.set(ContentType(Mime(TopLevel::Text, SubLevel::Plain, vec![])));
         }
-        let mut metadata: Metadata = Metadata::default(url);
+        // Consumers see the decoded body, so the headers describing the encoded one no
+        // longer apply.
+        if content_codings.as_ref().map_or(false, |codings| !codings.is_empty()) {
+            adjusted_headers.remove_raw("content-encoding");
+            adjusted_headers.remove::<ContentLength>();
+        }
+        let mut metadata: Metadata = Metadata::default(url.clone());
         metadata.set_content_type(match adjusted_headers.get() {
             Some(&ContentType(ref mime)) => Some(mime),
             None => None
         });
         metadata.headers = Some(adjusted_headers);
-        metadata.status = Some(response.status_raw().clone());
-
-        let mut encoding_str: Option<String> = None;
-        //FIXME: Implement Content-Encoding Header https://github.com/hyperium/hyper/issues/391
-        if let Some(encodings) = response.headers.get_raw("content-encoding") {
-            for encoding in encodings.iter() {
-                if let Ok(encodings) = String::from_utf8(encoding.clone()) {
-                    if encodings == "gzip" || encodings == "deflate" {
-                        encoding_str = Some(encodings);
-                        break;
-                    }
-                }
-            }
-        }
+        metadata.status = Some(response.status_raw.clone());
 
         // Send an HttpResponse message to devtools with the corresponding request_id
-        // TODO: Send this message only if load_data has a pipeline_id that is not None
-        if let Some(ref chan) = devtools_chan {
+        let response_start = precise_time_ns();
+        if let (Some(ref chan), Some(pipeline_id)) = (devtools_chan.as_ref(),
+                                                      load_data.pipeline_id) {
             let net_event_response = NetworkEvent::HttpResponse(
                 metadata.headers.clone(), metadata.status.clone(), None);
-            chan.send(DevtoolsControlMsg::NetworkEventMessage(request_id, net_event_response)).unwrap();
+            chan.send(DevtoolsControlMsg::NetworkEventMessage(pipeline_id,
+                                                              request_id.clone(),
+                                                              net_event_response)).unwrap();
         }
 
-        match encoding_str {
-            Some(encoding) => {
-                if encoding == "gzip" {
-                    let result = GzDecoder::new(response);
-                    match result {
-                        Ok(mut response_decoding) => {
-                            send_data(&mut response_decoding, start_chan, metadata, classifier);
-                        }
-                        Err(err) => {
-                            send_error(metadata.final_url, err.to_string(), start_chan);
-                            return;
-                        }
+        // Keep a copy of the body if the response might be stored in the cache.
+        let cache_metadata = if load_data.method == Method::Get && !viewing_source &&
+                                response.status == StatusCode::Ok {
+            Some(metadata.clone())
+        } else {
+            None
+        };
+        let mut body = cache_metadata.as_ref().map(|_| vec!());
+
+        let body_size = match content_codings {
+            Some(ref codings) if !codings.is_empty() => {
+                match decode_body(response.body, codings) {
+                    Ok(mut response_decoding) => {
+                        send_data(&mut response_decoding, start_chan, metadata, classifier,
+                                  body.as_mut())
+                    }
+                    Err(err) => {
+                        send_error(metadata.final_url, err.to_string(), start_chan);
+                        return;
                     }
-                } else if encoding == "deflate" {
-                    let mut response_decoding = DeflateDecoder::new(response);
-                    send_data(&mut response_decoding, start_chan, metadata, classifier);
                 }
             },
-            None => {
-                send_data(&mut response, start_chan, metadata, classifier);
+            // Bodies in codings we can't decode are passed along as they are.
+            _ => {
+                send_data(&mut response.body, start_chan, metadata, classifier, body.as_mut())
             }
+        };
+
+        if let (Some(_), Some(cache_metadata), Some(body)) = (body_size, cache_metadata, body) {
+            http_cache.store(&url, &load_data.method, &cache_request_headers, cache_metadata,
+                             body);
+        }
+
+        if let (Some(ref chan), Some(pipeline_id), Some(body_size)) =
+                (devtools_chan.as_ref(), load_data.pipeline_id, body_size) {
+            let response_end = precise_time_ns();
+            let net_event_complete = NetworkEvent::HttpResponseComplete(
+                body_size, response_start - request_start, response_end - response_start);
+            chan.send(DevtoolsControlMsg::NetworkEventMessage(pipeline_id,
+                                                              request_id,
+                                                              net_event_complete)).unwrap();
         }
 
         // We didn't get redirected.
@@ -383,10 +683,72 @@ reason: \"certificate verify failed\" }]))";
     }
 }
 
+/// A content coding that is decoded before the body is handed to the consumer.
+/// https://tools.ietf.org/html/rfc7231#section-3.1.2.1
+#[derive(Clone, Copy, PartialEq)]
+enum ContentCoding {
+    Gzip,
+    Deflate,
+}
+
+/// The codings listed in the Content-Encoding header, in the order they were applied,
+/// or `None` if any of them can't be decoded.
+fn content_codings(headers: &Headers) -> Option<Vec<ContentCoding>> {
+    let mut codings = vec!();
+    let values = match headers.get_raw("content-encoding") {
+        Some(values) => values,
+        None => return Some(codings),
+    };
+    for value in values.iter() {
+        let value = match str::from_utf8(value) {
+            Ok(value) => value,
+            Err(_) => return None,
+        };
+        for coding in value.split(',') {
+            match &*coding.trim().to_ascii_lowercase() {
+                "gzip" | "x-gzip" => codings.push(ContentCoding::Gzip),
+                "deflate" => codings.push(ContentCoding::Deflate),
+                "identity" | "" => {},
+                _ => return None,
+            }
+        }
+    }
+    Some(codings)
+}
+
+/// Wraps `body` in decoders that undo `codings`, last applied first.
+fn decode_body<R: Read + 'static>(body: R, codings: &[ContentCoding]) -> io::Result<Box<Read>> {
+    let mut reader = box body as Box<Read>;
+    for coding in codings.iter().rev() {
+        reader = match *coding {
+            ContentCoding::Gzip => box try!(GzDecoder::new(reader)) as Box<Read>,
+            ContentCoding::Deflate => {
+                // "deflate" is meant to be zlib-wrapped, but plenty of servers send a raw
+                // deflate stream instead. Tell the two apart by the zlib header.
+                let mut reader = BufReader::new(reader);
+                let is_zlib = {
+                    let buf = try!(reader.fill_buf());
+                    buf.len() >= 2 && buf[0] & 0x0f == 8 &&
+                        ((buf[0] as u16) << 8 | buf[1] as u16) % 31 == 0
+                };
+                if is_zlib {
+                    box ZlibDecoder::new(reader) as Box<Read>
+                } else {
+                    box DeflateDecoder::new(reader) as Box<Read>
+                }
+            }
+        };
+    }
+    Ok(reader)
+}
+
+/// Sends the response body to the consumer, appending it to `body` as well if given.
+/// Returns the size of the body if the whole of it was read and sent.
 fn send_data<R: Read>(reader: &mut R,
                       start_chan: LoadConsumer,
                       metadata: Metadata,
-                      classifier: Arc<MIMEClassifier>) {
+                      classifier: Arc<MIMEClassifier>,
+                      mut body: Option<&mut Vec<u8>>) -> Option<usize> {
     let (progress_chan, mut chunk) = {
         let buf = match read_block(reader) {
             Ok(ReadResult::Payload(buf)) => buf,
@@ -394,24 +756,46 @@ fn send_data<R: Read>(reader: &mut R,
         };
         let p = match start_sending_sniffed_opt(start_chan, metadata, classifier, &buf) {
             Ok(p) => p,
-            _ => return
+            _ => return None
         };
         (p, buf)
     };
 
+    let mut size = 0;
     loop {
+        if let Some(ref mut body) = body {
+            body.push_all(&chunk);
+        }
+        size += chunk.len();
+
         if progress_chan.send(Payload(chunk)).is_err() {
             // The send errors when the receiver is out of scope,
             // which will happen if the fetch has timed out (or has been aborted)
             // so we don't need to continue with the loading of the file here.
-            return;
+            return None;
         }
 
         chunk = match read_block(reader) {
             Ok(ReadResult::Payload(buf)) => buf,
-            Ok(ReadResult::EOF) | Err(_) => break,
+            Ok(ReadResult::EOF) => break,
+            Err(_) => {
+                let _ = progress_chan.send(Done(Err("error reading the response body".to_owned())));
+                return None;
+            }
         };
     }
 
     let _ = progress_chan.send(Done(Ok(())));
+    Some(size)
+}
+
+/// Replays a response stored in the cache.
+fn send_cached(cached: CachedResponse, start_chan: LoadConsumer, classifier: Arc<MIMEClassifier>) {
+    let progress_chan = match start_sending_sniffed_opt(start_chan, cached.metadata, classifier,
+                                                        &cached.body) {
+        Ok(p) => p,
+        _ => return
+    };
+    let _ = progress_chan.send(Payload((*cached.body).clone()));
+    let _ = progress_chan.send(Done(Ok(())));
 }
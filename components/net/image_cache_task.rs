@@ -2,21 +2,30 @@
  * License, v. 2.0. If a copy of the MPL was not distributed with this
  * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
 
-use net_traits::image::base::{Image, load_from_memory};
+use euclid::size::Size2D;
+use msg::constellation_msg::PipelineId;
+use net_traits::image::base::{AnimatedImage, Image, load_animation_from_memory, load_from_memory};
+use net_traits::image::base::load_from_memory_for_size;
+use net_traits::image::jpeg;
 use net_traits::image_cache_task::{ImageState, ImageCacheTask, ImageCacheChan, ImageCacheCommand};
 use net_traits::image_cache_task::{ImageCacheResult, ImageResponse, UsePlaceholder};
 use net_traits::load_whole_resource;
+use png::PixelsByColorType;
+use profile_traits::mem::{Report, ReportUnits, ReportsChan};
 use std::borrow::ToOwned;
+use std::cmp::max;
 use std::collections::HashMap;
 use std::collections::hash_map::Entry::{Occupied, Vacant};
 use std::mem;
 use std::sync::Arc;
 use std::sync::mpsc::{channel, Sender, Receiver, Select};
+use time::precise_time_ns;
 use util::resource_files::resources_dir_path;
 use util::task::spawn_named;
 use util::taskpool::TaskPool;
 use url::Url;
 use net_traits::{AsyncResponseTarget, ControlMsg, LoadData, ResponseAction, ResourceTask, LoadConsumer};
+use net_traits::LoadPriority;
 use net_traits::image_cache_task::ImageResponder;
 
 ///
@@ -27,12 +36,18 @@ use net_traits::image_cache_task::ImageResponder;
 ///     * Profile time in GetImageIfAvailable - might be worth caching these results per paint / layout task.
 ///
 
+/// The number of threads that decode images.
+const DECODER_TASKS: usize = 4;
+
 /// Represents an image that is either being loaded
 /// by the resource task, or decoded by a worker thread.
 struct PendingLoad {
     bytes: Vec<u8>,
     result: Option<Result<(), String>>,
     listeners: Vec<ImageListener>,
+    /// Whether every pipeline that asked for the image has been navigated away from, so
+    /// that it isn't worth decoding.
+    cancelled: bool,
 }
 
 impl PendingLoad {
@@ -41,11 +56,13 @@ impl PendingLoad {
             bytes: vec!(),
             result: None,
             listeners: vec!(),
+            cancelled: false,
         }
     }
 
     fn add_listener(&mut self, listener: ImageListener) {
         self.listeners.push(listener);
+        self.cancelled = false;
     }
 }
 
@@ -55,12 +72,28 @@ impl PendingLoad {
 /// fetched again.
 struct CompletedLoad {
     image_response: ImageResponse,
+    /// All the frames of the image, if it is animated.
+    animation: Option<Arc<AnimatedImage>>,
+    /// The encoded image, kept while it is decoded at a reduced size in case it is later
+    /// drawn larger.
+    bytes: Option<Arc<Vec<u8>>>,
+    /// Whether the image is being decoded again at a larger size.
+    redecoding: bool,
+    /// The clients to send the image to once it has been decoded again.
+    redecode_listeners: Vec<ImageListener>,
 }
 
 impl CompletedLoad {
-    fn new(image_response: ImageResponse) -> CompletedLoad {
+    fn new(image_response: ImageResponse,
+           animation: Option<Arc<AnimatedImage>>,
+           bytes: Option<Arc<Vec<u8>>>)
+           -> CompletedLoad {
         CompletedLoad {
             image_response: image_response,
+            animation: animation,
+            bytes: bytes,
+            redecoding: false,
+            redecode_listeners: vec!(),
         }
     }
 }
@@ -68,13 +101,18 @@ impl CompletedLoad {
 /// Stores information to notify a client when the state
 /// of an image changes.
 struct ImageListener {
+    pipeline_id: PipelineId,
     sender: ImageCacheChan,
     responder: Option<Box<ImageResponder>>,
 }
 
 impl ImageListener {
-    fn new(sender: ImageCacheChan, responder: Option<Box<ImageResponder>>) -> ImageListener {
+    fn new(pipeline_id: PipelineId,
+           sender: ImageCacheChan,
+           responder: Option<Box<ImageResponder>>)
+           -> ImageListener {
         ImageListener {
+            pipeline_id: pipeline_id,
             sender: sender,
             responder: responder,
         }
@@ -125,6 +163,12 @@ struct ImageCache {
     // Worker threads for decoding images.
     task_pool: TaskPool,
 
+    // Images that have loaded and are waiting for a worker thread to decode them.
+    decode_queue: Vec<DecodeJob>,
+
+    // The number of images being decoded by the worker threads.
+    decodes_in_flight: usize,
+
     // Resource task handle
     resource_task: ResourceTask,
 
@@ -134,14 +178,27 @@ struct ImageCache {
     // Images that have finished loading (successful or not)
     completed_loads: HashMap<Url, CompletedLoad>,
 
+    // The largest size each image has been drawn at, in device pixels.
+    target_sizes: HashMap<Url, Size2D<u32>>,
+
     // The placeholder image used when an image fails to load
     placeholder_image: Option<Arc<Image>>,
 }
 
+/// An image that is waiting to be decoded.
+struct DecodeJob {
+    url: Url,
+    bytes: Arc<Vec<u8>>,
+    target_size: Option<Size2D<u32>>,
+}
+
 /// Message that the decoder worker threads send to main image cache task.
 struct DecoderMsg {
     url: Url,
     image: Option<Image>,
+    animation: Option<AnimatedImage>,
+    /// The encoded image, if it was decoded at a reduced size.
+    reduced_bytes: Option<Arc<Vec<u8>>>,
 }
 
 /// The types of messages that the main image cache task receives.
@@ -208,8 +265,8 @@ impl ImageCache {
             ImageCacheCommand::Exit(sender) => {
                 return Some(sender);
             }
-            ImageCacheCommand::RequestImage(url, result_chan, responder) => {
-                self.request_image(url, result_chan, responder);
+            ImageCacheCommand::RequestImage(url, pipeline_id, result_chan, responder) => {
+                self.request_image(url, pipeline_id, result_chan, responder);
             }
             ImageCacheCommand::GetImageIfAvailable(url, use_placeholder, consumer) => {
                 let result = match self.completed_loads.get(&url) {
@@ -232,6 +289,28 @@ impl ImageCache {
                 };
                 consumer.send(result).unwrap();
             }
+            ImageCacheCommand::GetAnimationIfAvailable(url, consumer) => {
+                let animation = self.completed_loads.get(&url).and_then(|completed_load| {
+                    completed_load.animation.clone()
+                });
+                consumer.send(animation).unwrap();
+            }
+            ImageCacheCommand::Prioritize(url) => {
+                // Visible images go ahead of scripts, but still behind stylesheets.
+                if self.pending_loads.contains_key(&url) {
+                    let msg = ControlMsg::RaisePriority(url, LoadPriority::Script);
+                    self.resource_task.send(msg).unwrap();
+                }
+            }
+            ImageCacheCommand::SetTargetSize(url, size, pipeline_id, result_chan) => {
+                self.set_target_size(url, size, pipeline_id, result_chan);
+            }
+            ImageCacheCommand::CancelRequests(pipeline_id) => {
+                self.cancel_requests(pipeline_id);
+            }
+            ImageCacheCommand::CollectReports(reports_chan) => {
+                self.collect_reports(reports_chan);
+            }
         };
 
         None
@@ -248,29 +327,28 @@ impl ImageCache {
             ResponseAction::ResponseComplete(result) => {
                 match result {
                     Ok(()) => {
+                        // Nothing is waiting for a cancelled image, so it isn't decoded.
+                        if self.pending_loads[&msg.url].cancelled {
+                            self.pending_loads.remove(&msg.url);
+                            return
+                        }
+
                         let pending_load = self.pending_loads.get_mut(&msg.url).unwrap();
                         pending_load.result = Some(result);
 
                         let bytes = mem::replace(&mut pending_load.bytes, vec!());
-                        let url = msg.url.clone();
-                        let sender = self.decoder_sender.clone();
-
-                        self.task_pool.execute(move || {
-                            let image = load_from_memory(&bytes);
-                            let msg = DecoderMsg {
-                                url: url,
-                                image: image
-                            };
-                            sender.send(msg).unwrap();
-                        });
+                        let target_size = self.target_sizes.get(&msg.url).cloned();
+                        self.decode(msg.url.clone(), Arc::new(bytes), target_size);
                     }
                     Err(_) => {
                         match self.placeholder_image.clone() {
                             Some(placeholder_image) => {
                                 self.complete_load(msg.url, ImageResponse::PlaceholderLoaded(
-                                        placeholder_image))
+                                        placeholder_image), None, None)
+                            }
+                            None => {
+                                self.complete_load(msg.url, ImageResponse::None, None, None)
                             }
-                            None => self.complete_load(msg.url, ImageResponse::None),
                         }
                     }
                 }
@@ -278,20 +356,180 @@ impl ImageCache {
         }
     }
 
+    // Queue an image to be decoded on one of the worker threads.
+    fn decode(&mut self, url: Url, bytes: Arc<Vec<u8>>, target_size: Option<Size2D<u32>>) {
+        self.decode_queue.push(DecodeJob {
+            url: url,
+            bytes: bytes,
+            target_size: target_size,
+        });
+        self.start_decodes();
+    }
+
+    // Hand queued images to the worker threads as they become free. Smaller images go first,
+    // so that one huge image doesn't hold up every other image on the page.
+    fn start_decodes(&mut self) {
+        while self.decodes_in_flight < DECODER_TASKS && !self.decode_queue.is_empty() {
+            let mut next = 0;
+            for (index, job) in self.decode_queue.iter().enumerate() {
+                if job.bytes.len() < self.decode_queue[next].bytes.len() {
+                    next = index;
+                }
+            }
+            let DecodeJob { url, bytes, target_size } = self.decode_queue.remove(next);
+            self.decodes_in_flight += 1;
+            let sender = self.decoder_sender.clone();
+            self.task_pool.execute(move || {
+                decode_image(url, bytes, target_size, sender)
+            });
+        }
+    }
+
     // Handle a message from one of the decoder worker threads
     fn handle_decoder(&mut self, msg: DecoderMsg) {
-        let image = match msg.image {
-            None => ImageResponse::None,
-            Some(image) => ImageResponse::Loaded(Arc::new(image)),
+        self.decodes_in_flight -= 1;
+        if self.pending_loads.contains_key(&msg.url) {
+            self.complete_decode(msg);
+        } else {
+            self.complete_redecode(msg);
+        }
+        self.start_decodes();
+    }
+
+    // Stop sending images to a pipeline. Images that no other pipeline is waiting for are
+    // taken out of the decode queue, or aren't decoded once they've loaded. Decodes that
+    // have already started are finished, and the images kept.
+    fn cancel_requests(&mut self, pipeline_id: PipelineId) {
+        for pending_load in self.pending_loads.values_mut() {
+            if pending_load.listeners.is_empty() {
+                continue
+            }
+            pending_load.listeners.retain(|listener| listener.pipeline_id != pipeline_id);
+            pending_load.cancelled = pending_load.listeners.is_empty();
+        }
+        for completed_load in self.completed_loads.values_mut() {
+            completed_load.redecode_listeners.retain(|listener| {
+                listener.pipeline_id != pipeline_id
+            });
+        }
+
+        // Queued images are forgotten, so that they're loaded again if they're requested.
+        let pending_loads = &mut self.pending_loads;
+        self.decode_queue.retain(|job| {
+            let cancelled = pending_loads.get(&job.url).map_or(false, |pending_load| {
+                pending_load.cancelled
+            });
+            if cancelled {
+                pending_loads.remove(&job.url);
+            }
+            !cancelled
+        });
+    }
+
+    // Measure the decoded images, and the encoded images kept for decoding again. Images
+    // are shared between pipelines, so they're reported by URL.
+    fn collect_reports(&self, reports_chan: ReportsChan) {
+        let mut reports = vec![];
+        for (url, completed_load) in self.completed_loads.iter() {
+            let mut size = match completed_load.image_response {
+                ImageResponse::Loaded(ref image) => image_size(image),
+                ImageResponse::PlaceholderLoaded(_) | ImageResponse::None => 0,
+            };
+            if let Some(ref animation) = completed_load.animation {
+                // The first frame is the loaded image, which has been counted already.
+                for frame in animation.frames.iter().skip(1) {
+                    size += image_size(&frame.image);
+                }
+            }
+            size += completed_load.bytes.as_ref().map_or(0, |bytes| bytes.len());
+            reports.push(Report {
+                path: path!["image-cache", format!("url({})", url)],
+                units: ReportUnits::Bytes,
+                size: size,
+            });
+        }
+        reports_chan.send(reports);
+    }
+
+    // Handle the first decode of an image that has loaded.
+    fn complete_decode(&mut self, msg: DecoderMsg) {
+        let animation = msg.animation.map(|mut animation| {
+            animation.start_time = precise_time_ns();
+            Arc::new(animation)
+        });
+        let image = match (msg.image, &animation) {
+            (_, &Some(ref animation)) => ImageResponse::Loaded(animation.frames[0].image.clone()),
+            (Some(image), &None) => ImageResponse::Loaded(Arc::new(image)),
+            (None, &None) => ImageResponse::None,
+        };
+        self.complete_load(msg.url, image, animation, msg.reduced_bytes);
+    }
+
+    // Replace an image that was decoded at a reduced size with one decoded at a larger size.
+    fn complete_redecode(&mut self, msg: DecoderMsg) {
+        let completed_load = self.completed_loads.get_mut(&msg.url).unwrap();
+        completed_load.redecoding = false;
+        if let Some(image) = msg.image {
+            completed_load.image_response = ImageResponse::Loaded(Arc::new(image));
+            completed_load.bytes = msg.reduced_bytes;
+        }
+        let listeners = mem::replace(&mut completed_load.redecode_listeners, vec!());
+        for listener in listeners.into_iter() {
+            listener.notify(completed_load.image_response.clone());
+        }
+    }
+
+    // Record how big an image is drawn, and decode it again if it was decoded at a size that
+    // is now too small.
+    fn set_target_size(&mut self,
+                       url: Url,
+                       size: Size2D<u32>,
+                       pipeline_id: PipelineId,
+                       result_chan: ImageCacheChan) {
+        let target_size = match self.target_sizes.entry(url.clone()) {
+            Occupied(mut e) => {
+                let target_size = e.get_mut();
+                target_size.width = max(target_size.width, size.width);
+                target_size.height = max(target_size.height, size.height);
+                *target_size
+            }
+            Vacant(e) => *e.insert(size),
         };
-        self.complete_load(msg.url, image);
+
+        // A pending image is decoded at the target size once it has loaded.
+        let bytes = match self.completed_loads.get_mut(&url) {
+            Some(completed_load) => {
+                let bytes = match (&completed_load.bytes, &completed_load.image_response) {
+                    (&Some(ref bytes), &ImageResponse::Loaded(ref image)) => {
+                        if image.width >= target_size.width && image.height >= target_size.height {
+                            return
+                        }
+                        bytes.clone()
+                    }
+                    _ => return,
+                };
+                let listener = ImageListener::new(pipeline_id, result_chan, None);
+                completed_load.redecode_listeners.push(listener);
+                if completed_load.redecoding {
+                    return
+                }
+                completed_load.redecoding = true;
+                bytes
+            }
+            None => return,
+        };
+        self.decode(url, bytes, Some(target_size));
     }
 
     // Change state of a url from pending -> loaded.
-    fn complete_load(&mut self, url: Url, image_response: ImageResponse) {
+    fn complete_load(&mut self,
+                     url: Url,
+                     image_response: ImageResponse,
+                     animation: Option<Arc<AnimatedImage>>,
+                     bytes: Option<Arc<Vec<u8>>>) {
         let pending_load = self.pending_loads.remove(&url).unwrap();
 
-        let completed_load = CompletedLoad::new(image_response.clone());
+        let completed_load = CompletedLoad::new(image_response.clone(), animation, bytes);
         self.completed_loads.insert(url, completed_load);
 
         for listener in pending_load.listeners.into_iter() {
@@ -302,9 +540,10 @@ impl ImageCache {
     // Request an image from the cache
     fn request_image(&mut self,
                      url: Url,
+                     pipeline_id: PipelineId,
                      result_chan: ImageCacheChan,
                      responder: Option<Box<ImageResponder>>) {
-        let image_listener = ImageListener::new(result_chan, responder);
+        let image_listener = ImageListener::new(pipeline_id, result_chan, responder);
 
         // Check if already completed
         match self.completed_loads.get(&url) {
@@ -327,7 +566,8 @@ impl ImageCache {
                         pending_load.add_listener(image_listener);
                         e.insert(pending_load);
 
-                        let load_data = LoadData::new(url.clone(), None);
+                        let mut load_data = LoadData::new(url.clone(), None);
+                        load_data.priority = LoadPriority::Image;
                         let listener = box ResourceListener {
                             url: url,
                             sender: self.progress_sender.clone(),
@@ -341,6 +581,40 @@ impl ImageCache {
     }
 }
 
+/// The size of an image's pixels, in bytes.
+fn image_size(image: &Image) -> usize {
+    match image.pixels {
+        PixelsByColorType::K8(ref pixels) |
+        PixelsByColorType::KA8(ref pixels) |
+        PixelsByColorType::RGB8(ref pixels) |
+        PixelsByColorType::RGBA8(ref pixels) => pixels.len(),
+    }
+}
+
+/// Decode an image on one of the worker threads.
+fn decode_image(url: Url,
+                bytes: Arc<Vec<u8>>,
+                target_size: Option<Size2D<u32>>,
+                sender: Sender<DecoderMsg>) {
+    // The first frame of an animated image stands in for the image.
+    let animation = load_animation_from_memory(&bytes);
+    let image = match animation {
+        Some(_) => None,
+        None => load_from_memory_for_size(&bytes, target_size),
+    };
+    let reduced = match (&image, jpeg::read_header(&bytes)) {
+        (&Some(ref image), Some(header)) => image.width < header.width,
+        _ => false,
+    };
+    let msg = DecoderMsg {
+        url: url,
+        image: image,
+        animation: animation,
+        reduced_bytes: if reduced { Some(bytes) } else { None },
+    };
+    sender.send(msg).unwrap();
+}
+
 /// Create a new image cache.
 pub fn new_image_cache_task(resource_task: ResourceTask) -> ImageCacheTask {
     let (cmd_sender, cmd_receiver) = channel();
@@ -376,9 +650,12 @@ pub fn new_image_cache_task(resource_task: ResourceTask) -> ImageCacheTask {
             progress_receiver: progress_receiver,
             decoder_sender: decoder_sender,
             decoder_receiver: decoder_receiver,
-            task_pool: TaskPool::new(4),
+            task_pool: TaskPool::new(DECODER_TASKS as u32),
+            decode_queue: vec!(),
+            decodes_in_flight: 0,
             pending_loads: HashMap::new(),
             completed_loads: HashMap::new(),
+            target_sizes: HashMap::new(),
             resource_task: resource_task,
             placeholder_image: placeholder_image,
         };
@@ -18,11 +18,15 @@ extern crate devtools_traits;
 extern crate flate2;
 extern crate euclid;
 extern crate hyper;
+extern crate msg;
 extern crate png;
 #[macro_use]
+extern crate profile_traits;
+#[macro_use]
 extern crate log;
 extern crate openssl;
 extern crate rustc_serialize;
+extern crate solicit;
 extern crate util;
 extern crate time;
 extern crate url;
@@ -31,14 +35,24 @@ extern crate uuid;
 extern crate regex;
 
 pub mod about_loader;
+pub mod beacon;
+pub mod blob_loader;
+pub mod certificate;
 pub mod file_loader;
+pub mod hsts;
+pub mod http_auth;
+pub mod http_cache;
+pub mod http2;
 pub mod http_loader;
 pub mod data_loader;
 pub mod cookie;
 pub mod cookie_storage;
 pub mod image_cache_task;
 pub mod net_error_list;
+pub mod protocol_loader;
+pub mod proxy;
 pub mod pub_domains;
+pub mod referrer;
 pub mod resource_task;
 pub mod storage_task;
 pub mod mime_classifier;
@@ -0,0 +1,86 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+//! Loads the URLs of schemes the embedder has registered a `ProtocolHandler` for.
+
+use mime_classifier::MIMEClassifier;
+use net_traits::{LoadData, LoadConsumer, Metadata, ProtocolHandler};
+use net_traits::ProgressMsg::{Payload, Done};
+use resource_task::{start_sending, start_sending_sniffed};
+
+use hyper::header::ContentType;
+use std::borrow::ToOwned;
+use std::boxed::FnBox;
+use std::error::Error;
+use std::io::Read;
+use std::sync::Arc;
+use util::task::spawn_named;
+
+const READ_SIZE: usize = 8192;
+
+/// The schemes Servo loads itself, which embedders can't take over.
+pub static BUILTIN_SCHEMES: [&'static str; 7] = [
+    "about", "blob", "data", "file", "http", "https", "view-source",
+];
+
+pub fn factory(handler: Arc<Box<ProtocolHandler>>)
+               -> Box<FnBox(LoadData, LoadConsumer, Arc<MIMEClassifier>) + Send> {
+    box move |load_data, senders, classifier| {
+        spawn_named("protocol_loader".to_owned(), move || {
+            load(load_data, senders, classifier, handler)
+        })
+    }
+}
+
+fn load(load_data: LoadData, start_chan: LoadConsumer, classifier: Arc<MIMEClassifier>,
+        handler: Arc<Box<ProtocolHandler>>) {
+    let mut metadata = Metadata::default(load_data.url.clone());
+    let response = match handler.load(&load_data) {
+        Ok(response) => response,
+        Err(e) => {
+            start_sending(start_chan, metadata).send(Done(Err(e))).unwrap();
+            return
+        }
+    };
+
+    metadata.set_content_type(response.headers.get::<ContentType>()
+                                              .map(|&ContentType(ref mime)| mime));
+    if let Some(status) = response.status {
+        metadata.status = Some(status);
+    }
+    metadata.headers = Some(response.headers);
+
+    let mut body = response.body;
+    let mut buf = vec![0; READ_SIZE];
+    let progress_chan = match body.read(&mut buf) {
+        Ok(n) => {
+            buf.truncate(n);
+            let progress_chan = start_sending_sniffed(start_chan, metadata, classifier, &buf);
+            if n > 0 {
+                progress_chan.send(Payload(buf)).unwrap();
+            }
+            progress_chan
+        }
+        Err(e) => {
+            start_sending(start_chan, metadata).send(Done(Err(e.description().to_owned()))).unwrap();
+            return
+        }
+    };
+
+    loop {
+        let mut buf = vec![0; READ_SIZE];
+        match body.read(&mut buf) {
+            Ok(0) => break,
+            Ok(n) => {
+                buf.truncate(n);
+                progress_chan.send(Payload(buf)).unwrap();
+            }
+            Err(e) => {
+                progress_chan.send(Done(Err(e.description().to_owned()))).unwrap();
+                return
+            }
+        }
+    }
+    progress_chan.send(Done(Ok(()))).unwrap();
+}
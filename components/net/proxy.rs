@@ -0,0 +1,271 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+//! Support for loading through an HTTP proxy. Plain http requests are forwarded by the
+//! proxy, while https requests are tunneled through it with CONNECT so that TLS stays
+//! end to end.
+//!
+//! The proxy comes from the `--proxy` option if given, and from the conventional
+//! `http_proxy`, `https_proxy` and `no_proxy` environment variables otherwise.
+
+use hyper::Error as HttpError;
+use hyper::net::{NetworkConnector, NetworkStream};
+use openssl::ssl::{SslContext, SslMethod, SslStream};
+use std::ascii::AsciiExt;
+use std::borrow::ToOwned;
+use std::env;
+use std::io::{self, Read, Write};
+use std::net::{SocketAddr, TcpStream};
+use std::str;
+use url::Url;
+use util::opts;
+
+/// A proxy server's host and port.
+pub type ProxyAddress = (String, u16);
+
+#[derive(Clone, Debug)]
+pub struct ProxyConfig {
+    /// The proxy for http URLs.
+    pub http: Option<ProxyAddress>,
+    /// The proxy for https URLs.
+    pub https: Option<ProxyAddress>,
+    /// Hosts that are contacted directly. An entry matches the host itself and its
+    /// subdomains; `*` matches every host.
+    pub bypass: Vec<String>,
+}
+
+impl ProxyConfig {
+    /// Reads the proxy configuration from the command line and the environment.
+    pub fn from_opts_and_env() -> ProxyConfig {
+        let bypass = env_var("no_proxy").map_or(vec!(), |hosts| {
+            hosts.split(',')
+                 .map(|host| host.trim().trim_left_matches('.').to_ascii_lowercase())
+                 .filter(|host| !host.is_empty())
+                 .collect()
+        });
+        if let Some(proxy) = opts::get().proxy.as_ref().and_then(|proxy| parse_proxy(proxy)) {
+            return ProxyConfig {
+                http: Some(proxy.clone()),
+                https: Some(proxy),
+                bypass: bypass,
+            };
+        }
+        ProxyConfig {
+            http: env_var("http_proxy").and_then(|proxy| parse_proxy(&proxy)),
+            https: env_var("https_proxy").and_then(|proxy| parse_proxy(&proxy)),
+            bypass: bypass,
+        }
+    }
+
+    /// The proxy to load `url` through, if any.
+    pub fn proxy_for(&self, url: &Url) -> Option<ProxyAddress> {
+        let proxy = match &*url.scheme {
+            "http" => self.http.clone(),
+            "https" => self.https.clone(),
+            _ => None,
+        };
+        let host = match url.host() {
+            Some(host) => host.serialize().to_ascii_lowercase(),
+            None => return None,
+        };
+        if self.bypass.iter().any(|entry| bypass_matches(entry, &host)) {
+            return None;
+        }
+        proxy
+    }
+}
+
+fn bypass_matches(entry: &str, host: &str) -> bool {
+    entry == "*" || host == entry ||
+        (host.ends_with(entry) && host.as_bytes()[host.len() - entry.len() - 1] == b'.')
+}
+
+/// Environment variables are conventionally lowercase, but the uppercase forms are common.
+fn env_var(name: &str) -> Option<String> {
+    env::var(name).or_else(|_| env::var(name.to_ascii_uppercase()))
+                  .ok()
+                  .and_then(|value| if value.is_empty() { None } else { Some(value) })
+}
+
+/// Parses a proxy given either as `host:port` or as a URL such as `http://host:port/`.
+pub fn parse_proxy(proxy: &str) -> Option<ProxyAddress> {
+    let url = if proxy.contains("://") {
+        Url::parse(proxy)
+    } else {
+        Url::parse(&format!("http://{}", proxy))
+    };
+    let url = match url {
+        Ok(url) => url,
+        Err(_) => return None,
+    };
+    match (url.host(), url.port_or_default()) {
+        (Some(host), Some(port)) => Some((host.serialize(), port)),
+        _ => None,
+    }
+}
+
+/// Connects to servers through a proxy.
+pub struct ProxyConnector {
+    pub proxy: ProxyAddress,
+    /// Configures the TLS context for tunneled connections.
+    pub verifier: Option<Box<Fn(&mut SslContext) + Send>>,
+    /// The Proxy-Authorization header to open tunnels with.
+    pub authorization: Option<String>,
+    /// The Proxy-Authenticate headers of the proxy's answer, if it asked for credentials
+    /// before opening a tunnel.
+    pub challenges: Option<Vec<Vec<u8>>>,
+}
+
+impl NetworkConnector for ProxyConnector {
+    type Stream = ProxyStream;
+
+    fn connect(&mut self, host: &str, port: u16, scheme: &str) -> Result<ProxyStream, HttpError> {
+        let (ref proxy_host, proxy_port) = self.proxy;
+        let mut stream = try!(TcpStream::connect(&(&**proxy_host, proxy_port)));
+        match scheme {
+            "http" => Ok(ProxyStream::Forward(stream, Some(format!("http://{}:{}", host, port)))),
+            "https" => {
+                match open_tunnel(&mut stream, host, port, self.authorization.as_ref()) {
+                    Ok(()) => {}
+                    Err(TunnelError::Io(error)) => return Err(HttpError::Io(error)),
+                    Err(TunnelError::AuthenticationRequired(challenges)) => {
+                        self.challenges = Some(challenges);
+                        return Err(HttpError::Io(io::Error::new(io::ErrorKind::PermissionDenied,
+                                                                "the proxy requires credentials")));
+                    }
+                }
+                let mut context = try!(SslContext::new(SslMethod::Sslv23));
+                if let Some(ref verifier) = self.verifier {
+                    verifier(&mut context);
+                }
+                let stream = try!(SslStream::new(&context, stream));
+                Ok(ProxyStream::Tunnel(stream))
+            }
+            _ => Err(HttpError::Io(io::Error::new(io::ErrorKind::InvalidInput,
+                                                  "unsupported scheme for proxying"))),
+        }
+    }
+}
+
+enum TunnelError {
+    Io(io::Error),
+    /// The proxy answered 407 with these Proxy-Authenticate headers.
+    AuthenticationRequired(Vec<Vec<u8>>),
+}
+
+impl From<io::Error> for TunnelError {
+    fn from(error: io::Error) -> TunnelError {
+        TunnelError::Io(error)
+    }
+}
+
+/// Asks the proxy to open a tunnel to `host:port`, and waits until it has.
+/// https://tools.ietf.org/html/rfc7231#section-4.3.6
+fn open_tunnel(stream: &mut TcpStream, host: &str, port: u16, authorization: Option<&String>)
+               -> Result<(), TunnelError> {
+    try!(write!(stream, "CONNECT {0}:{1} HTTP/1.1\r\nHost: {0}:{1}\r\n", host, port));
+    if let Some(authorization) = authorization {
+        try!(write!(stream, "Proxy-Authorization: {}\r\n", authorization));
+    }
+    try!(write!(stream, "\r\n"));
+
+    // Read the response one byte at a time, so that nothing after it is consumed.
+    let mut response = vec!();
+    let mut byte = [0];
+    while !response.ends_with(b"\r\n\r\n") {
+        if try!(stream.read(&mut byte)) == 0 {
+            return Err(TunnelError::Io(io::Error::new(io::ErrorKind::ConnectionAborted,
+                                                      "the proxy closed the connection")));
+        }
+        response.push(byte[0]);
+    }
+
+    let mut lines = response.split(|&b| b == b'\n').map(|line| {
+        if line.ends_with(b"\r") { &line[..line.len() - 1] } else { line }
+    });
+    let status_line = lines.next().unwrap();
+    let status = str::from_utf8(status_line).ok().and_then(|line| line.split(' ').nth(1));
+    match status {
+        Some(status) if status.starts_with("2") => Ok(()),
+        // The proxy may keep the connection open after its answer, but a new one is made
+        // to send the credentials, so the body needn't be read.
+        Some("407") => {
+            let challenges = lines.filter_map(|line| {
+                let colon = match line.iter().position(|&b| b == b':') {
+                    Some(colon) => colon,
+                    None => return None,
+                };
+                let name = &line[..colon];
+                if !name.eq_ignore_ascii_case(b"proxy-authenticate") {
+                    return None;
+                }
+                Some(line[colon + 1..].iter().cloned().skip_while(|&b| b == b' ').collect())
+            }).collect();
+            Err(TunnelError::AuthenticationRequired(challenges))
+        }
+        _ => Err(TunnelError::Io(io::Error::new(io::ErrorKind::ConnectionRefused,
+                                                "the proxy refused to open a tunnel"))),
+    }
+}
+
+pub enum ProxyStream {
+    /// A connection to the proxy, which forwards requests itself. The scheme, host and
+    /// port of the target are still to be inserted into the request line, since the
+    /// proxy needs an absolute URL.
+    Forward(TcpStream, Option<String>),
+    /// A TLS connection to the server, tunneled through the proxy.
+    Tunnel(SslStream<TcpStream>),
+}
+
+impl Read for ProxyStream {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        match *self {
+            ProxyStream::Forward(ref mut stream, _) => stream.read(buf),
+            ProxyStream::Tunnel(ref mut stream) => stream.read(buf),
+        }
+    }
+}
+
+impl Write for ProxyStream {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        match *self {
+            ProxyStream::Forward(ref mut stream, ref mut target) => {
+                let prefix = match target.take() {
+                    Some(prefix) => prefix,
+                    None => return stream.write(buf),
+                };
+                // The request target starts after the method and the space that follows.
+                match buf.iter().position(|&b| b == b' ') {
+                    Some(space) => {
+                        try!(stream.write_all(&buf[..space + 1]));
+                        try!(stream.write_all(prefix.as_bytes()));
+                        try!(stream.write_all(&buf[space + 1..]));
+                    }
+                    None => {
+                        try!(stream.write_all(buf));
+                        *target = Some(prefix);
+                    }
+                }
+                Ok(buf.len())
+            }
+            ProxyStream::Tunnel(ref mut stream) => stream.write(buf),
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match *self {
+            ProxyStream::Forward(ref mut stream, _) => stream.flush(),
+            ProxyStream::Tunnel(ref mut stream) => stream.flush(),
+        }
+    }
+}
+
+impl NetworkStream for ProxyStream {
+    fn peer_addr(&mut self) -> io::Result<SocketAddr> {
+        match *self {
+            ProxyStream::Forward(ref mut stream, _) => stream.peer_addr(),
+            ProxyStream::Tunnel(ref mut stream) => stream.get_mut().peer_addr(),
+        }
+    }
+}
@@ -0,0 +1,55 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+//! Works out the Referer header for a request from the referrer policy that applies to it,
+//! as specified in https://w3c.github.io/webappsec/specs/referrer-policy/.
+
+use msg::constellation_msg::ReferrerPolicy;
+use url::Url;
+
+/// The Referer header to send with a request for `url` made on behalf of the document at
+/// `referrer_url`, or `None` if the policy says not to send one.
+/// https://w3c.github.io/webappsec/specs/referrer-policy/#determine-requests-referrer
+pub fn determine_request_referrer(referrer_url: &Url,
+                                  referrer_policy: Option<ReferrerPolicy>,
+                                  url: &Url)
+                                  -> Option<String> {
+    // Documents at file:, data: and other such URLs are never revealed.
+    match &*referrer_url.scheme {
+        "http" | "https" => {}
+        _ => return None,
+    }
+
+    let is_downgrade = referrer_url.scheme == "https" && url.scheme != "https";
+    let is_cross_origin = referrer_url.scheme != url.scheme ||
+                          referrer_url.host() != url.host() ||
+                          referrer_url.port_or_default() != url.port_or_default();
+    match referrer_policy.unwrap_or(ReferrerPolicy::NoReferrerWhenDowngrade) {
+        ReferrerPolicy::NoReferrer => None,
+        ReferrerPolicy::NoReferrerWhenDowngrade if is_downgrade => None,
+        ReferrerPolicy::NoReferrerWhenDowngrade |
+        ReferrerPolicy::UnsafeUrl => Some(strip_url(referrer_url, false)),
+        ReferrerPolicy::Origin => Some(strip_url(referrer_url, true)),
+        ReferrerPolicy::OriginWhenCrossOrigin => Some(strip_url(referrer_url, is_cross_origin)),
+    }
+}
+
+/// Removes the parts of a URL that are never sent, and the path and query too if only the
+/// origin is to be revealed.
+/// https://w3c.github.io/webappsec/specs/referrer-policy/#strip-url
+fn strip_url(url: &Url, origin_only: bool) -> String {
+    let mut url = url.clone();
+    url.fragment = None;
+    if origin_only {
+        url.query = None;
+    }
+    if let Some(data) = url.relative_scheme_data_mut() {
+        data.username = String::new();
+        data.password = None;
+        if origin_only {
+            data.path = vec!(String::new());
+        }
+    }
+    url.serialize()
+}
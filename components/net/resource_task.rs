@@ -5,15 +5,25 @@
 //! A task that takes a URL and streams back the binary data.
 
 use about_loader;
+use beacon::BeaconQuota;
+use blob_loader::{self, BlobURLStore};
+use certificate::CertificateOverrides;
+use http_auth::AuthCache;
 use data_loader;
 use file_loader;
+use hsts::HSTSList;
+use http_cache::HttpCache;
+use http2::Http2Connections;
 use http_loader;
 use cookie_storage::CookieStorage;
 use cookie;
 use mime_classifier::MIMEClassifier;
+use protocol_loader::{self, BUILTIN_SCHEMES};
+use proxy::ProxyConfig;
 
-use net_traits::{ControlMsg, LoadData, LoadResponse, LoadConsumer};
-use net_traits::{Metadata, ProgressMsg, ResourceTask, AsyncResponseTarget, ResponseAction};
+use net_traits::{ControlMsg, LoadData, LoadPriority, LoadResponse, LoadConsumer};
+use net_traits::{Metadata, ProgressMsg, ProtocolHandler, ResourceTask, AsyncResponseTarget};
+use net_traits::ResponseAction;
 use net_traits::ProgressMsg::Done;
 use util::opts;
 use util::task::spawn_named;
@@ -21,16 +31,28 @@ use util::task::spawn_named;
 use devtools_traits::{DevtoolsControlMsg};
 use hyper::header::{ContentType, Header, SetCookie, UserAgent};
 use hyper::mime::{Mime, TopLevel, SubLevel};
+use msg::constellation_msg::ConstellationChan;
+use profile_traits::mem;
 
+use std::ascii::AsciiExt;
 use std::borrow::ToOwned;
 use std::boxed::FnBox;
 use std::collections::HashMap;
 use std::env;
 use std::fs::File;
 use std::io::{BufReader, Read};
+use std::path::Path;
 use std::sync::Arc;
 use std::sync::mpsc::{channel, Receiver, Sender};
+use std::thread::sleep_ms;
+use url::Url;
 
+/// The number of loads from a single host that may be in progress at once; any more wait
+/// in the queue, most important first.
+const MAX_CONNECTIONS_PER_HOST: usize = 6;
+
+/// How long to wait after cookies change before saving them, in milliseconds.
+const COOKIE_SAVE_DELAY_MS: u32 = 5000;
 
 static mut HOST_TABLE: Option<*mut HashMap<String, String>> = None;
 
@@ -154,11 +176,13 @@ pub fn start_sending_opt(start_chan: LoadConsumer, metadata: Metadata) -> Result
 
 /// Create a ResourceTask
 pub fn new_resource_task(user_agent: Option<String>,
-                         devtools_chan: Option<Sender<DevtoolsControlMsg>>) -> ResourceTask {
+                         devtools_chan: Option<Sender<DevtoolsControlMsg>>,
+                         mem_profiler_chan: Option<mem::ProfilerChan>) -> ResourceTask {
     let (setup_chan, setup_port) = channel();
     let setup_chan_clone = setup_chan.clone();
     spawn_named("ResourceManager".to_owned(), move || {
-        ResourceManager::new(setup_port, user_agent, setup_chan_clone, devtools_chan).start();
+        ResourceManager::new(setup_port, user_agent, setup_chan_clone, devtools_chan,
+                             mem_profiler_chan).start();
     });
     setup_chan
 }
@@ -204,21 +228,67 @@ struct ResourceManager {
     cookie_storage: CookieStorage,
     resource_task: Sender<ControlMsg>,
     mime_classifier: Arc<MIMEClassifier>,
-    devtools_chan: Option<Sender<DevtoolsControlMsg>>
+    devtools_chan: Option<Sender<DevtoolsControlMsg>>,
+    /// Where about:memory gets its reports from.
+    mem_profiler_chan: Option<mem::ProfilerChan>,
+    beacon_quota: BeaconQuota,
+    http_cache: HttpCache,
+    http2_connections: Http2Connections,
+    hsts_list: HSTSList,
+    proxy_config: ProxyConfig,
+    blob_url_store: BlobURLStore,
+    /// Network loads waiting for a connection to their host, in the order they arrived.
+    queued_loads: Vec<QueuedLoad>,
+    /// The number of loads in progress for each host.
+    open_connections: HashMap<String, usize>,
+    /// The handlers for the schemes the embedder serves, by scheme.
+    protocol_handlers: HashMap<String, Arc<Box<ProtocolHandler>>>,
+    /// The hosts whose certificates the user has chosen to trust.
+    certificate_overrides: CertificateOverrides,
+    /// The credentials the user has given for each protection space.
+    auth_cache: AuthCache,
+    /// Where to ask the user for credentials, once the constellation has started.
+    constellation_chan: Option<ConstellationChan>,
+    /// Whether a `SaveCookies` message is on its way.
+    cookie_save_scheduled: bool,
+}
+
+struct QueuedLoad {
+    host: String,
+    load_data: LoadData,
+    consumer: LoadConsumer,
 }
 
 impl ResourceManager {
     fn new(from_client: Receiver<ControlMsg>,
            user_agent: Option<String>,
            resource_task: Sender<ControlMsg>,
-           devtools_channel: Option<Sender<DevtoolsControlMsg>>) -> ResourceManager {
+           devtools_channel: Option<Sender<DevtoolsControlMsg>>,
+           mem_profiler_chan: Option<mem::ProfilerChan>) -> ResourceManager {
         ResourceManager {
             from_client: from_client,
             user_agent: user_agent,
-            cookie_storage: CookieStorage::new(),
+            cookie_storage: match opts::get().profile_dir {
+                Some(ref dir) => CookieStorage::new_persistent(Path::new(dir).join("cookies.json")),
+                None => CookieStorage::new(),
+            },
             resource_task: resource_task,
             mime_classifier: Arc::new(MIMEClassifier::new()),
-            devtools_chan: devtools_channel
+            devtools_chan: devtools_channel,
+            mem_profiler_chan: mem_profiler_chan,
+            beacon_quota: BeaconQuota::new(),
+            http_cache: HttpCache::new(),
+            http2_connections: Http2Connections::new(),
+            hsts_list: HSTSList::new_preloaded(),
+            proxy_config: ProxyConfig::from_opts_and_env(),
+            blob_url_store: BlobURLStore::new(),
+            queued_loads: vec!(),
+            open_connections: HashMap::new(),
+            protocol_handlers: HashMap::new(),
+            certificate_overrides: CertificateOverrides::new(),
+            auth_cache: AuthCache::new(),
+            constellation_chan: None,
+            cookie_save_scheduled: false,
         }
     }
 }
@@ -239,12 +309,57 @@ impl ResourceManager {
                       self.cookie_storage.push(cookie, source);
                     }
                   }
+                  self.schedule_cookie_save();
                 }
               }
               ControlMsg::GetCookiesForUrl(url, consumer, source) => {
                 consumer.send(self.cookie_storage.cookies_for_url(&url, source)).unwrap();
               }
+              ControlMsg::SendBeacon(load_data, origin, result) => {
+                self.send_beacon(load_data, origin, result)
+              }
+              ControlMsg::RegisterBlobURL(url, bytes, content_type, pipeline_id) => {
+                self.blob_url_store.register(&url, bytes, content_type, pipeline_id)
+              }
+              ControlMsg::RevokeBlobURL(url) => {
+                self.blob_url_store.revoke(&url)
+              }
+              ControlMsg::RevokeBlobURLsForPipeline(pipeline_id) => {
+                self.blob_url_store.revoke_for_pipeline(pipeline_id)
+              }
+              ControlMsg::RaisePriority(url, priority) => {
+                for queued in self.queued_loads.iter_mut() {
+                  if queued.load_data.url == url && queued.load_data.priority < priority {
+                    queued.load_data.priority = priority;
+                  }
+                }
+              }
+              ControlMsg::LoadFinished(host) => {
+                if let Some(count) = self.open_connections.get_mut(&host) {
+                  *count -= 1;
+                }
+                self.start_queued_loads(&host)
+              }
+              ControlMsg::RegisterProtocolHandler(scheme, handler) => {
+                let scheme = scheme.to_ascii_lowercase();
+                if BUILTIN_SCHEMES.contains(&&*scheme) {
+                  warn!("resource_task: can't register a handler for built-in scheme {}", scheme);
+                } else {
+                  self.protocol_handlers.insert(scheme, Arc::new(handler));
+                }
+              }
+              ControlMsg::AllowCertificate(host, fingerprint) => {
+                self.certificate_overrides.allow(&host, &fingerprint)
+              }
+              ControlMsg::SetConstellationChan(constellation_chan) => {
+                self.constellation_chan = Some(constellation_chan)
+              }
+              ControlMsg::SaveCookies => {
+                self.cookie_save_scheduled = false;
+                self.cookie_storage.save()
+              }
               ControlMsg::Exit => {
+                self.cookie_storage.flush();
                 break
               }
             }
@@ -262,6 +377,46 @@ impl ResourceManager {
             load_data.preserved_headers.set(UserAgent(ua.clone()));
         });
 
+        match connection_host(&load_data.url) {
+            Some(host) => {
+                self.queued_loads.push(QueuedLoad {
+                    host: host.clone(),
+                    load_data: load_data,
+                    consumer: consumer,
+                });
+                self.start_queued_loads(&host)
+            }
+            None => self.start_load(load_data, consumer, None),
+        }
+    }
+
+    /// Starts the most important loads queued for `host`, as long as connections to it
+    /// are available. Loads of the same priority start in the order they were requested.
+    fn start_queued_loads(&mut self, host: &str) {
+        loop {
+            if *self.open_connections.get(host).unwrap_or(&0) >= MAX_CONNECTIONS_PER_HOST {
+                return;
+            }
+            let mut next: Option<(usize, LoadPriority)> = None;
+            for (i, queued) in self.queued_loads.iter().enumerate() {
+                if queued.host == host &&
+                   next.map_or(true, |(_, priority)| queued.load_data.priority > priority) {
+                    next = Some((i, queued.load_data.priority));
+                }
+            }
+            let queued = match next {
+                Some((i, _)) => self.queued_loads.remove(i),
+                None => return,
+            };
+            *self.open_connections.entry(host.to_owned()).or_insert(0) += 1;
+            self.start_load(queued.load_data, queued.consumer, Some(queued.host));
+        }
+    }
+
+    /// Hands a load to the loader for its scheme. `connection_host` is the host whose
+    /// connection count the load holds on to until it finishes, if any.
+    fn start_load(&mut self, load_data: LoadData, consumer: LoadConsumer,
+                  connection_host: Option<String>) {
         fn from_factory(factory: fn(LoadData, LoadConsumer, Arc<MIMEClassifier>))
                         -> Box<FnBox(LoadData, LoadConsumer, Arc<MIMEClassifier>) + Send> {
             box move |load_data, senders, classifier| {
@@ -272,9 +427,16 @@ impl ResourceManager {
         let loader = match &*load_data.url.scheme {
             "file" => from_factory(file_loader::factory),
             "http" | "https" | "view-source" =>
-                http_loader::factory(self.resource_task.clone(), self.devtools_chan.clone()),
+                http_loader::factory(self.resource_task.clone(), self.devtools_chan.clone(),
+                                     self.http_cache.clone(), self.http2_connections.clone(),
+                                     self.hsts_list.clone(), self.proxy_config.clone(),
+                                     self.certificate_overrides.clone(), self.auth_cache.clone(),
+                                     self.constellation_chan.clone(), connection_host),
             "data" => from_factory(data_loader::factory),
-            "about" => from_factory(about_loader::factory),
+            "about" => about_loader::factory(self.mem_profiler_chan.clone()),
+            "blob" => blob_loader::factory(self.blob_url_store.clone()),
+            scheme if self.protocol_handlers.contains_key(scheme) =>
+                protocol_loader::factory(self.protocol_handlers[scheme].clone()),
             _ => {
                 debug!("resource_task: no loader for scheme {}", load_data.url.scheme);
                 start_sending(consumer, Metadata::default(load_data.url))
@@ -286,4 +448,64 @@ impl ResourceManager {
 
         loader.call_box((load_data, consumer, self.mime_classifier.clone()));
     }
+
+    /// Saves the cookies a little later if they have changed, rather than after every change.
+    fn schedule_cookie_save(&mut self) {
+        if self.cookie_save_scheduled || !self.cookie_storage.needs_saving() {
+            return;
+        }
+        self.cookie_save_scheduled = true;
+        let resource_task = self.resource_task.clone();
+        spawn_named("CookieSaveTimer".to_owned(), move || {
+            sleep_ms(COOKIE_SAVE_DELAY_MS);
+            let _ = resource_task.send(ControlMsg::SaveCookies);
+        });
+    }
+
+    /// Starts a beacon's request if it fits in its origin's quota, and drops it otherwise,
+    /// replying with whether it was queued. The request is owned by this task rather than by
+    /// the document, so it completes even if the document goes away.
+    fn send_beacon(&mut self, load_data: LoadData, origin: Url, result: Sender<bool>) {
+        let len = load_data.data.as_ref().map_or(0, |data| data.len());
+        if !self.beacon_quota.reserve(&origin, len) {
+            debug!("resource_task: dropping beacon to {} over quota", load_data.url.serialize());
+            let _ = result.send(false);
+            return;
+        }
+        let _ = result.send(true);
+        let listener = BeaconListener {
+            quota: self.beacon_quota.clone(),
+            origin: origin,
+            len: len,
+        };
+        self.load(load_data, LoadConsumer::Listener(box listener));
+    }
+}
+
+/// Ignores the response to a beacon, and returns its data to its origin's quota once the loader
+/// lets go of it.
+struct BeaconListener {
+    quota: BeaconQuota,
+    origin: Url,
+    len: usize,
+}
+
+impl AsyncResponseTarget for BeaconListener {
+    fn invoke_with_listener(&self, _: ResponseAction) {
+    }
+}
+
+impl Drop for BeaconListener {
+    fn drop(&mut self) {
+        self.quota.release(&self.origin, self.len);
+    }
+}
+
+/// The host a load counts against for the per-host connection limit, or `None` if it
+/// doesn't use the network.
+fn connection_host(url: &Url) -> Option<String> {
+    match &*url.scheme {
+        "http" | "https" => url.host().map(|host| host.serialize()),
+        _ => None,
+    }
 }
@@ -2,14 +2,130 @@
  * License, v. 2.0. If a copy of the MPL was not distributed with this
  * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
 
+use euclid::size::Size2D;
+use image::{gif, icc, ico, jpeg};
 use png;
+use rustc_serialize::{Decodable, Decoder, Encodable, Encoder};
 use stb_image::image as stb_image2;
+use std::cmp::min;
+use std::sync::Arc;
+use util::opts;
 use util::vec::byte_swap;
 
 // FIXME: Images must not be copied every frame. Instead we should atomically
 // reference count them.
 pub type Image = png::Image;
 
+/// One frame of an animated image.
+#[derive(Clone)]
+pub struct ImageFrame {
+    pub image: Arc<Image>,
+    /// How long the frame is shown for, in milliseconds.
+    pub delay: u32,
+}
+
+impl Encodable for ImageFrame {
+    fn encode<S: Encoder>(&self, s: &mut S) -> Result<(), S::Error> {
+        s.emit_struct("ImageFrame", 2, |s| {
+            try!(s.emit_struct_field("image", 0, |s| encode_image(&self.image, s)));
+            s.emit_struct_field("delay", 1, |s| self.delay.encode(s))
+        })
+    }
+}
+
+impl Decodable for ImageFrame {
+    fn decode<D: Decoder>(d: &mut D) -> Result<ImageFrame, D::Error> {
+        d.read_struct("ImageFrame", 2, |d| {
+            Ok(ImageFrame {
+                image: Arc::new(try!(d.read_struct_field("image", 0, decode_image))),
+                delay: try!(d.read_struct_field("delay", 1, Decodable::decode)),
+            })
+        })
+    }
+}
+
+/// An image whose frames are shown in turn, such as an animated GIF. Every use of the image
+/// shows the same frame at the same time.
+#[derive(RustcEncodable, RustcDecodable)]
+pub struct AnimatedImage {
+    pub frames: Vec<ImageFrame>,
+    /// How many times the frames are played through, or `None` to loop forever.
+    pub loop_count: Option<u32>,
+    /// When the first frame was first shown, in nanoseconds.
+    pub start_time: u64,
+}
+
+/// Encodes an image, which comes from rust-png and so can't implement `Encodable` itself, as its
+/// size, the kind of its pixels and their bytes.
+pub fn encode_image<S: Encoder>(image: &Image, s: &mut S) -> Result<(), S::Error> {
+    let (kind, pixels) = match image.pixels {
+        png::PixelsByColorType::K8(ref pixels) => (0u8, pixels),
+        png::PixelsByColorType::KA8(ref pixels) => (1, pixels),
+        png::PixelsByColorType::RGB8(ref pixels) => (2, pixels),
+        png::PixelsByColorType::RGBA8(ref pixels) => (3, pixels),
+    };
+    (image.width, image.height, kind, pixels).encode(s)
+}
+
+pub fn decode_image<D: Decoder>(d: &mut D) -> Result<Image, D::Error> {
+    let (width, height, kind, pixels): (u32, u32, u8, Vec<u8>) = try!(Decodable::decode(d));
+    let pixels = match kind {
+        0 => png::PixelsByColorType::K8(pixels),
+        1 => png::PixelsByColorType::KA8(pixels),
+        2 => png::PixelsByColorType::RGB8(pixels),
+        3 => png::PixelsByColorType::RGBA8(pixels),
+        _ => return Err(d.error("invalid kind of pixels")),
+    };
+    Ok(png::Image {
+        width: width,
+        height: height,
+        pixels: pixels,
+    })
+}
+
+impl AnimatedImage {
+    /// How long one play through of the frames takes, in milliseconds.
+    fn duration(&self) -> u64 {
+        self.frames.iter().fold(0, |duration, frame| duration + frame.delay as u64)
+    }
+
+    /// How long the animation has been playing for at `time`, in milliseconds.
+    fn elapsed(&self, time: u64) -> u64 {
+        time.saturating_sub(self.start_time) / 1000000
+    }
+
+    /// Whether the frames have been played through as many times as the image asks for by
+    /// `time`, in nanoseconds.
+    pub fn has_finished(&self, time: u64) -> bool {
+        match self.loop_count {
+            Some(loop_count) => self.elapsed(time) >= self.duration() * loop_count as u64,
+            None => false,
+        }
+    }
+
+    /// The index of the frame to show at `time`, in nanoseconds. Once the animation has
+    /// finished, its last frame stays up.
+    pub fn frame_index_at(&self, time: u64) -> usize {
+        let duration = self.duration();
+        if duration == 0 || self.has_finished(time) {
+            return self.frames.len() - 1
+        }
+        let mut position = self.elapsed(time) % duration;
+        for (index, frame) in self.frames.iter().enumerate() {
+            if position < frame.delay as u64 {
+                return index
+            }
+            position -= frame.delay as u64;
+        }
+        self.frames.len() - 1
+    }
+
+    /// The frame to show at `time`, in nanoseconds.
+    pub fn frame_at(&self, time: u64) -> Arc<Image> {
+        self.frames[self.frame_index_at(time)].image.clone()
+    }
+}
+
 // TODO(pcwalton): Speed up with SIMD, or better yet, find some way to not do this.
 fn byte_swap_and_premultiply(data: &mut [u8]) {
     let length = data.len();
@@ -25,16 +141,31 @@ fn byte_swap_and_premultiply(data: &mut [u8]) {
 }
 
 pub fn load_from_memory(buffer: &[u8]) -> Option<Image> {
+    load_from_memory_for_size(buffer, None)
+}
+
+/// Decodes an image that will be drawn at `target_size`, in device pixels. Large JPEGs are
+/// decoded at a fraction of their size when that is still enough to fill the target.
+pub fn load_from_memory_for_size(buffer: &[u8], target_size: Option<Size2D<u32>>)
+                                 -> Option<Image> {
     if buffer.len() == 0 {
         return None;
     }
 
+    if let Some(image) = load_jpeg_from_memory(buffer, target_size) {
+        return Some(image)
+    }
+
     if png::is_png(buffer) {
         match png::load_png_from_memory(buffer) {
             Ok(mut png_image) => {
                 match png_image.pixels {
-                    png::PixelsByColorType::RGB8(ref mut data) => byte_swap(data),
+                    png::PixelsByColorType::RGB8(ref mut data) => {
+                        convert_to_srgb(buffer, data, 3);
+                        byte_swap(data)
+                    }
                     png::PixelsByColorType::RGBA8(ref mut data) => {
+                        convert_to_srgb(buffer, data, 4);
                         byte_swap_and_premultiply(data)
                     }
                     _ => {}
@@ -51,6 +182,7 @@ pub fn load_from_memory(buffer: &[u8]) -> Option<Image> {
         match stb_image2::load_from_memory_with_depth(buffer, FORCE_DEPTH, true) {
             stb_image2::LoadResult::ImageU8(mut image) => {
                 assert!(image.depth == 4);
+                convert_to_srgb(buffer, &mut image.data, 4);
                 // handle gif separately because the alpha-channel has to be premultiplied
                 if is_gif(buffer) {
                     byte_swap_and_premultiply(&mut image.data);
@@ -75,6 +207,162 @@ pub fn load_from_memory(buffer: &[u8]) -> Option<Image> {
     }
 }
 
+/// Converts the colors of a decoded image to sRGB, if the image carries an ICC profile saying
+/// they are in some other color space. `pixels` must not be byte swapped or premultiplied yet.
+fn convert_to_srgb(buffer: &[u8], pixels: &mut [u8], bytes_per_pixel: usize) {
+    if opts::get().disable_color_management {
+        return
+    }
+    let profile = if png::is_png(buffer) {
+        icc::png_profile(buffer)
+    } else {
+        icc::jpeg_profile(buffer)
+    };
+    if let Some(profile) = profile {
+        match icc::Transform::new(&profile) {
+            Ok(Some(transform)) => transform.apply(pixels, bytes_per_pixel),
+            Ok(None) => {}
+            Err(error) => debug!("ignoring ICC profile: {}", error),
+        }
+    }
+}
+
+/// Draws the glyph shown in place of an image that failed to load: a page torn in two.
+pub fn broken_image_glyph() -> Image {
+    const SIZE: i32 = 16;
+    const CLEAR: [u8; 4] = [0x00, 0x00, 0x00, 0x00];
+    const EDGE: [u8; 4] = [0x80, 0x80, 0x80, 0xff];
+    const PAGE: [u8; 4] = [0xff, 0xff, 0xff, 0xff];
+
+    let mut pixels = Vec::with_capacity((SIZE * SIZE * 4) as usize);
+    for y in 0..SIZE {
+        for x in 0..SIZE {
+            // The tear zigzags across the middle of the page.
+            let tear = 6 + (x % 4 - 2).abs();
+            let pixel = if x < 1 || x > SIZE - 2 || y == tear {
+                CLEAR
+            } else if x == 1 || x == SIZE - 2 || y == 0 || y == SIZE - 1 ||
+                    y == tear - 1 || y == tear + 1 {
+                EDGE
+            } else {
+                PAGE
+            };
+            pixels.push_all(&pixel);
+        }
+    }
+    png::Image {
+        width: SIZE as u32,
+        height: SIZE as u32,
+        pixels: png::PixelsByColorType::RGBA8(pixels),
+    }
+}
+
+/// Decodes the JPEGs that stb_image can't: progressive ones, and ones that can be decoded at
+/// a reduced scale. Everything else, and any JPEG this fails on, is left to stb_image.
+fn load_jpeg_from_memory(buffer: &[u8], target_size: Option<Size2D<u32>>) -> Option<Image> {
+    let header = match jpeg::read_header(buffer) {
+        Some(header) => header,
+        None => return None,
+    };
+    let scale = jpeg::scale_for_target_size(header.width, header.height, target_size);
+    if !header.progressive && scale == 1 {
+        return None
+    }
+    match jpeg::decode(buffer, target_size) {
+        Ok(mut image) => {
+            convert_to_srgb(buffer, &mut image.pixels, 4);
+            byte_swap(&mut image.pixels);
+            Some(png::Image {
+                width: image.width,
+                height: image.height,
+                pixels: png::PixelsByColorType::RGBA8(image.pixels),
+            })
+        }
+        Err(error) => {
+            debug!("failed to decode JPEG: {}", error);
+            None
+        }
+    }
+}
+
+/// Decodes a page's icon, which is shown at `size` pixels square. Of the images in an ICO file,
+/// the one that suits that size best is picked; other formats are decoded like any image.
+pub fn load_favicon_from_memory(buffer: &[u8], size: u32) -> Option<Image> {
+    if !ico::is_ico(buffer) {
+        return load_from_memory(buffer)
+    }
+    match ico::decode(buffer, size) {
+        Ok(ico::Icon::Png(png)) => load_from_memory(png),
+        Ok(ico::Icon::Bitmap { width, height, mut pixels }) => {
+            byte_swap_and_premultiply(&mut pixels);
+            Some(png::Image {
+                width: width,
+                height: height,
+                pixels: png::PixelsByColorType::RGBA8(pixels),
+            })
+        }
+        Err(error) => {
+            debug!("failed to decode icon: {}", error);
+            None
+        }
+    }
+}
+
+/// The pixels of a decoded image as unpremultiplied ARGB values, row by row, which is how
+/// window systems take icons. Only images with an alpha channel are converted.
+pub fn argb_pixels(image: &Image) -> Option<Vec<u32>> {
+    let pixels = match image.pixels {
+        png::PixelsByColorType::RGBA8(ref pixels) => pixels,
+        _ => return None,
+    };
+    // Decoded images are premultiplied BGRA.
+    Some(pixels.chunks(4).filter(|pixel| pixel.len() == 4).map(|pixel| {
+        let alpha = pixel[3] as u32;
+        let unpremultiply = |component: u8| {
+            if alpha == 0 { 0 } else { min(component as u32 * 255 / alpha, 255) }
+        };
+        alpha << 24 | unpremultiply(pixel[2]) << 16 | unpremultiply(pixel[1]) << 8 |
+            unpremultiply(pixel[0])
+    }).collect())
+}
+
+/// Decodes every frame of an animated image. Images with a single frame give `None`, and are
+/// loaded with `load_from_memory` instead.
+pub fn load_animation_from_memory(buffer: &[u8]) -> Option<AnimatedImage> {
+    if !is_gif(buffer) {
+        return None
+    }
+    match gif::decode(buffer) {
+        Ok(gif) => {
+            if gif.frames.len() < 2 {
+                return None
+            }
+            let (width, height) = (gif.width, gif.height);
+            let frames = gif.frames.into_iter().map(|frame| {
+                let mut pixels = frame.pixels;
+                byte_swap_and_premultiply(&mut pixels);
+                ImageFrame {
+                    image: Arc::new(png::Image {
+                        width: width,
+                        height: height,
+                        pixels: png::PixelsByColorType::RGBA8(pixels),
+                    }),
+                    delay: frame.delay,
+                }
+            }).collect();
+            Some(AnimatedImage {
+                frames: frames,
+                loop_count: gif.loop_count,
+                start_time: 0,
+            })
+        }
+        Err(error) => {
+            debug!("failed to decode GIF frames: {}", error);
+            None
+        }
+    }
+}
+
 fn is_gif(buffer: &[u8]) -> bool {
     match buffer {
         [b'G',b'I',b'F',b'8', n, b'a', ..] if n == b'7' || n == b'9' => true,
@@ -0,0 +1,421 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+//! A GIF decoder that keeps every frame of an image, so that animated GIFs can be played.
+//! http://www.w3.org/Graphics/GIF/spec-gif89a.txt
+
+use std::cmp::{max, min};
+
+/// The largest number of codes an LZW table can hold.
+const MAX_CODES: usize = 4096;
+
+/// The most pixels an image or one of its frames may have. Larger GIFs aren't decoded, rather
+/// than allocating gigabytes on the say-so of their headers.
+const MAX_PIXELS: usize = 1 << 25;
+
+/// The most bytes the decoded frames of one GIF may take up together. Frames beyond that are
+/// dropped, as if the image had been truncated.
+const MAX_DECODED_BYTES: usize = 1 << 28;
+
+/// Browsers show frames with delays this short (in milliseconds) for longer, since such GIFs
+/// are made for players that ignore the delay.
+const MIN_DELAY: u32 = 10;
+const DEFAULT_DELAY: u32 = 100;
+
+/// One frame of a GIF.
+pub struct GifFrame {
+    /// The whole image as it looks once this frame has been drawn, as RGBA.
+    pub pixels: Vec<u8>,
+    /// How long the frame is shown for, in milliseconds.
+    pub delay: u32,
+}
+
+/// A decoded GIF.
+pub struct Gif {
+    pub width: u32,
+    pub height: u32,
+    pub frames: Vec<GifFrame>,
+    /// How many times the frames are played through, or `None` to loop forever.
+    pub loop_count: Option<u32>,
+}
+
+/// Decodes every frame of a GIF. A damaged or truncated image keeps the frames found before
+/// the damage, and so does one whose frames would take up too much memory.
+pub fn decode(buffer: &[u8]) -> Result<Gif, &'static str> {
+    let mut decoder = try!(Decoder::new(buffer));
+    let mut frames = vec!();
+    // Every frame is a copy of the whole canvas, which can't be larger than this limit.
+    let frame_bytes = decoder.canvas.len();
+    loop {
+        if (frames.len() + 1) * frame_bytes > MAX_DECODED_BYTES {
+            break
+        }
+        match decoder.next_frame() {
+            Ok(Some(frame)) => frames.push(frame),
+            Ok(None) => break,
+            Err(error) => {
+                if frames.is_empty() {
+                    return Err(error)
+                }
+                break
+            }
+        }
+    }
+    if frames.is_empty() {
+        return Err("no frames")
+    }
+    Ok(Gif {
+        width: decoder.width as u32,
+        height: decoder.height as u32,
+        frames: frames,
+        loop_count: decoder.loop_count,
+    })
+}
+
+struct Reader<'a> {
+    data: &'a [u8],
+    position: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn bytes(&mut self, count: usize) -> Result<&'a [u8], &'static str> {
+        if self.data.len() - self.position < count {
+            return Err("unexpected end of data")
+        }
+        let bytes = &self.data[self.position..self.position + count];
+        self.position += count;
+        Ok(bytes)
+    }
+
+    fn byte(&mut self) -> Result<u8, &'static str> {
+        Ok(try!(self.bytes(1))[0])
+    }
+
+    fn u16(&mut self) -> Result<u16, &'static str> {
+        let bytes = try!(self.bytes(2));
+        Ok(bytes[0] as u16 | (bytes[1] as u16) << 8)
+    }
+
+    /// Reads a sequence of data sub-blocks, up to the empty block that ends it.
+    fn sub_blocks(&mut self) -> Result<Vec<u8>, &'static str> {
+        let mut data = vec!();
+        loop {
+            let length = try!(self.byte()) as usize;
+            if length == 0 {
+                return Ok(data)
+            }
+            data.push_all(try!(self.bytes(length)));
+        }
+    }
+}
+
+/// How the area of a frame is treated before the next frame is drawn.
+#[derive(Copy, Clone, PartialEq)]
+enum Disposal {
+    Keep,
+    ClearToTransparent,
+    RestorePrevious,
+}
+
+/// The settings a graphic control extension gives the image that follows it.
+#[derive(Copy, Clone)]
+struct GraphicControl {
+    disposal: Disposal,
+    transparent_index: Option<u8>,
+    delay: u32,
+}
+
+impl GraphicControl {
+    fn new() -> GraphicControl {
+        GraphicControl {
+            disposal: Disposal::Keep,
+            transparent_index: None,
+            delay: 0,
+        }
+    }
+}
+
+struct Decoder<'a> {
+    reader: Reader<'a>,
+    width: usize,
+    height: usize,
+    global_palette: Option<&'a [u8]>,
+    /// The image as drawn so far, as RGBA.
+    canvas: Vec<u8>,
+    control: GraphicControl,
+    loop_count: Option<u32>,
+}
+
+impl<'a> Decoder<'a> {
+    fn new(buffer: &'a [u8]) -> Result<Decoder<'a>, &'static str> {
+        let mut reader = Reader {
+            data: buffer,
+            position: 0,
+        };
+        let signature = try!(reader.bytes(6));
+        if signature[..3] != b"GIF"[..] {
+            return Err("not a GIF")
+        }
+        let width = try!(reader.u16()) as usize;
+        let height = try!(reader.u16()) as usize;
+        if width * height > MAX_PIXELS {
+            return Err("image too large")
+        }
+        let flags = try!(reader.byte());
+        // The background color and pixel aspect ratio are ignored, like browsers do.
+        try!(reader.bytes(2));
+        let global_palette = if flags & 0x80 != 0 {
+            Some(try!(reader.bytes(3 * (2 << (flags & 0x07)))))
+        } else {
+            None
+        };
+        Ok(Decoder {
+            reader: reader,
+            width: width,
+            height: height,
+            global_palette: global_palette,
+            canvas: vec![0; width * height * 4],
+            control: GraphicControl::new(),
+            // Without a loop count the frames are only played once.
+            loop_count: Some(1),
+        })
+    }
+
+    /// Reads up to and including the next image, returning the frame it makes, or `None` at
+    /// the end of the GIF.
+    fn next_frame(&mut self) -> Result<Option<GifFrame>, &'static str> {
+        loop {
+            match try!(self.reader.byte()) {
+                0x21 => try!(self.read_extension()),
+                0x2c => return self.read_image().map(Some),
+                0x3b => return Ok(None),
+                _ => return Err("unknown block"),
+            }
+        }
+    }
+
+    fn read_extension(&mut self) -> Result<(), &'static str> {
+        let label = try!(self.reader.byte());
+        let data = try!(self.reader.sub_blocks());
+        match label {
+            // Graphic control extension.
+            0xf9 if data.len() >= 4 => {
+                self.control.disposal = match (data[0] >> 2) & 0x07 {
+                    2 => Disposal::ClearToTransparent,
+                    3 => Disposal::RestorePrevious,
+                    _ => Disposal::Keep,
+                };
+                self.control.transparent_index = if data[0] & 0x01 != 0 {
+                    Some(data[3])
+                } else {
+                    None
+                };
+                // The delay is given in hundredths of a second.
+                let delay = (data[1] as u32 | (data[2] as u32) << 8) * 10;
+                self.control.delay = if delay <= MIN_DELAY { DEFAULT_DELAY } else { delay };
+            }
+            // The Netscape application extension, which says how often to loop.
+            0xff if data.len() >= 14 && data[..11] == b"NETSCAPE2.0"[..] && data[11] == 1 => {
+                let count = data[12] as u32 | (data[13] as u32) << 8;
+                // The count is of the repeats after the first play through.
+                self.loop_count = if count == 0 { None } else { Some(count + 1) };
+            }
+            _ => {}
+        }
+        Ok(())
+    }
+
+    fn read_image(&mut self) -> Result<GifFrame, &'static str> {
+        let left = try!(self.reader.u16()) as usize;
+        let top = try!(self.reader.u16()) as usize;
+        let width = try!(self.reader.u16()) as usize;
+        let height = try!(self.reader.u16()) as usize;
+        if width * height > MAX_PIXELS {
+            return Err("frame too large")
+        }
+        let flags = try!(self.reader.byte());
+        let palette = if flags & 0x80 != 0 {
+            try!(self.reader.bytes(3 * (2 << (flags & 0x07))))
+        } else {
+            match self.global_palette {
+                Some(palette) => palette,
+                None => return Err("no color table"),
+            }
+        };
+        let min_code_size = try!(self.reader.byte());
+        let data = try!(self.reader.sub_blocks());
+        let indices = try!(decode_lzw(&data, min_code_size, width * height));
+
+        let rows = if flags & 0x40 != 0 {
+            interlaced_rows(height)
+        } else {
+            (0..height).collect()
+        };
+        let control = self.control;
+        self.control = GraphicControl::new();
+        // Only the part of the canvas that the frame covers is drawn over, so only that part
+        // has to be restored.
+        let area_rows = min(top, self.height)..min(top + height, self.height);
+        let area_columns = min(left, self.width)..min(left + width, self.width);
+        let previous = if control.disposal == Disposal::RestorePrevious {
+            let mut previous = vec!();
+            for y in area_rows.clone() {
+                let start = (y * self.width + area_columns.start) * 4;
+                let end = (y * self.width + area_columns.end) * 4;
+                previous.push_all(&self.canvas[start..end]);
+            }
+            Some(previous)
+        } else {
+            None
+        };
+
+        // Pixels that are missing from a truncated image are left transparent.
+        for (source_row, &row) in indices.chunks(max(width, 1)).zip(rows.iter()) {
+            let y = top + row;
+            if y >= self.height {
+                continue
+            }
+            for (column, &index) in source_row.iter().enumerate() {
+                let x = left + column;
+                let entry = index as usize * 3;
+                if x >= self.width || Some(index) == control.transparent_index ||
+                        entry + 3 > palette.len() {
+                    continue
+                }
+                let offset = (y * self.width + x) * 4;
+                self.canvas[offset + 0] = palette[entry + 0];
+                self.canvas[offset + 1] = palette[entry + 1];
+                self.canvas[offset + 2] = palette[entry + 2];
+                self.canvas[offset + 3] = 0xff;
+            }
+        }
+
+        let frame = GifFrame {
+            pixels: self.canvas.clone(),
+            delay: if control.delay == 0 { DEFAULT_DELAY } else { control.delay },
+        };
+
+        let row_length = area_columns.len() * 4;
+        for (index, y) in area_rows.enumerate() {
+            let start = (y * self.width + area_columns.start) * 4;
+            let row = &mut self.canvas[start..start + row_length];
+            match (control.disposal, &previous) {
+                (Disposal::RestorePrevious, &Some(ref previous)) => {
+                    let previous_row = &previous[index * row_length..(index + 1) * row_length];
+                    for (byte, &previous_byte) in row.iter_mut().zip(previous_row.iter()) {
+                        *byte = previous_byte;
+                    }
+                }
+                (Disposal::ClearToTransparent, _) => {
+                    for byte in row {
+                        *byte = 0;
+                    }
+                }
+                _ => {}
+            }
+        }
+        Ok(frame)
+    }
+}
+
+/// The order in which the rows of an interlaced image are stored.
+fn interlaced_rows(height: usize) -> Vec<usize> {
+    let mut rows = vec!();
+    for &(start, step) in [(0, 8), (4, 8), (2, 4), (1, 2)].iter() {
+        rows.extend((start..height).step_by(step));
+    }
+    rows
+}
+
+/// Decompresses the color indices of an image, stopping once `pixel_count` have been found.
+fn decode_lzw(data: &[u8], min_code_size: u8, pixel_count: usize)
+              -> Result<Vec<u8>, &'static str> {
+    if min_code_size < 1 || min_code_size > 11 {
+        return Err("bad LZW code size")
+    }
+    let clear_code = 1u16 << min_code_size;
+    let end_code = clear_code + 1;
+
+    // Each code in the table stands for the string of its prefix's code followed by its
+    // suffix. Codes below the clear code stand for themselves.
+    let mut prefixes = vec![0u16; MAX_CODES];
+    let mut suffixes = vec![0u8; MAX_CODES];
+    for code in 0..clear_code {
+        suffixes[code as usize] = code as u8;
+    }
+
+    let mut code_size = min_code_size + 1;
+    let mut next_code = end_code + 1;
+    let mut previous_code = None;
+    let mut string = Vec::with_capacity(MAX_CODES);
+    let mut output = vec!();
+    let mut bits = 0u32;
+    let mut bit_count = 0;
+
+    for &byte in data {
+        bits |= (byte as u32) << bit_count;
+        bit_count += 8;
+        while bit_count >= code_size {
+            let code = (bits & ((1 << code_size) - 1)) as u16;
+            bits >>= code_size;
+            bit_count -= code_size;
+
+            if code == clear_code {
+                code_size = min_code_size + 1;
+                next_code = end_code + 1;
+                previous_code = None;
+                continue
+            }
+            if code == end_code {
+                return Ok(output)
+            }
+            let previous = match previous_code {
+                Some(previous) => previous,
+                None => {
+                    if code > clear_code {
+                        return Err("bad LZW code")
+                    }
+                    output.push(code as u8);
+                    previous_code = Some(code);
+                    continue
+                }
+            };
+
+            // A code that isn't in the table yet is the previous string followed by its own
+            // first byte.
+            let known = code < next_code;
+            if !known && code != next_code {
+                return Err("bad LZW code")
+            }
+            let mut current = if known { code } else { previous };
+            string.clear();
+            while current > end_code {
+                string.push(suffixes[current as usize]);
+                current = prefixes[current as usize];
+            }
+            let first_byte = current as u8;
+            string.push(first_byte);
+            output.extend(string.iter().rev().cloned());
+            if !known {
+                output.push(first_byte);
+            }
+
+            if (next_code as usize) < MAX_CODES {
+                prefixes[next_code as usize] = previous;
+                suffixes[next_code as usize] = first_byte;
+                next_code += 1;
+                if next_code == 1 << code_size && code_size < 12 {
+                    code_size += 1;
+                }
+            }
+            previous_code = Some(code);
+
+            if output.len() >= pixel_count {
+                output.truncate(pixel_count);
+                return Ok(output)
+            }
+        }
+    }
+    Ok(output)
+}
@@ -0,0 +1,322 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+//! Conversion of images with embedded ICC color profiles to sRGB, the color space pages are
+//! drawn in. Only RGB profiles built from a matrix and tone curves are understood, which
+//! covers the profiles cameras and image editors embed in photos.
+//! http://www.color.org/specification/ICC1v43_2010-12.pdf
+
+use flate2::read::ZlibDecoder;
+use std::io::Read;
+
+/// Converts XYZ relative to the D50 white point, which profiles map colors to, to linear sRGB.
+const XYZ_D50_TO_LINEAR_SRGB: [[f32; 3]; 3] = [
+    [3.1338561, -1.6168667, -0.4906146],
+    [-0.9787684, 1.9161415, 0.0334540],
+    [0.0719453, -0.2289914, 1.4052427],
+];
+
+/// How many steps the linear values are rounded to before being encoded again as sRGB.
+const OUTPUT_TABLE_SIZE: usize = 4096;
+
+/// Profiles whose colors are this close to sRGB's are left alone.
+const TOLERANCE: f32 = 0.002;
+
+/// The largest compressed profile that is decompressed. Real profiles are well under this, and
+/// a few bytes of zlib could otherwise inflate to gigabytes.
+const MAX_PROFILE_SIZE: usize = 4 << 20;
+
+/// Gathers the ICC profile of a JPEG, which is split across APP2 segments.
+pub fn jpeg_profile(buffer: &[u8]) -> Option<Vec<u8>> {
+    if buffer.len() < 4 || buffer[0] != 0xff || buffer[1] != 0xd8 {
+        return None
+    }
+    let mut chunks = vec!();
+    let mut position = 2;
+    while position + 4 <= buffer.len() && buffer[position] == 0xff {
+        let marker = buffer[position + 1];
+        // The image data starts at the start of scan marker, after every APP segment.
+        if marker == 0xda || marker == 0xd9 {
+            break
+        }
+        let length = (buffer[position + 2] as usize) << 8 | buffer[position + 3] as usize;
+        let end = position + 2 + length;
+        if length < 2 || end > buffer.len() {
+            break
+        }
+        let segment = &buffer[position + 4..end];
+        if marker == 0xe2 && segment.len() > 14 && segment.starts_with(b"ICC_PROFILE\0") {
+            chunks.push((segment[12], &segment[14..]));
+        }
+        position = end;
+    }
+    if chunks.is_empty() {
+        return None
+    }
+    chunks.sort_by(|a, b| a.0.cmp(&b.0));
+    let mut profile = vec!();
+    for &(_, chunk) in chunks.iter() {
+        profile.push_all(chunk);
+    }
+    Some(profile)
+}
+
+/// Reads the ICC profile of a PNG, which is stored compressed in its iCCP chunk.
+pub fn png_profile(buffer: &[u8]) -> Option<Vec<u8>> {
+    let mut position = 8;
+    while position + 12 <= buffer.len() {
+        let length = read_u32(buffer, position) as usize;
+        let kind = &buffer[position + 4..position + 8];
+        let start = position + 8;
+        if length > buffer.len() - start {
+            return None
+        }
+        let data = &buffer[start..start + length];
+        if kind == &b"iCCP"[..] {
+            // The profile's name comes first, then the compression method, which can only be
+            // zlib.
+            let name_length = match data.iter().position(|&byte| byte == 0) {
+                Some(name_length) => name_length,
+                None => return None,
+            };
+            if data.len() < name_length + 2 || data[name_length + 1] != 0 {
+                return None
+            }
+            let mut profile = vec!();
+            let decoder = ZlibDecoder::new(&data[name_length + 2..]);
+            return match decoder.take(MAX_PROFILE_SIZE as u64 + 1).read_to_end(&mut profile) {
+                Ok(_) if profile.len() <= MAX_PROFILE_SIZE => Some(profile),
+                _ => None,
+            }
+        }
+        // The profile has to come before the image data.
+        if kind == &b"IDAT"[..] || kind == &b"IEND"[..] {
+            return None
+        }
+        position = start + length + 4;
+    }
+    None
+}
+
+/// A tone curve, mapping encoded channel values to linear ones.
+enum Curve {
+    Gamma(f32),
+    Table(Vec<f32>),
+    /// One of the parametric curves, given as `[g, a, b, c, d, e, f]` of the general form
+    /// `(a * x + b) ^ g + e` for `x >= d`, and `c * x + f` below it.
+    Parametric([f32; 7]),
+}
+
+impl Curve {
+    fn read(profile: &[u8], offset: usize) -> Result<Curve, &'static str> {
+        if offset + 12 > profile.len() {
+            return Err("truncated curve")
+        }
+        let kind = &profile[offset..offset + 4];
+        if kind == &b"curv"[..] {
+            let count = read_u32(profile, offset + 8) as usize;
+            if offset + 12 + count * 2 > profile.len() {
+                return Err("truncated curve")
+            }
+            return Ok(match count {
+                0 => Curve::Gamma(1.0),
+                1 => Curve::Gamma(read_u16(profile, offset + 12) as f32 / 256.0),
+                _ => Curve::Table((0..count).map(|index| {
+                    read_u16(profile, offset + 12 + index * 2) as f32 / 65535.0
+                }).collect()),
+            })
+        }
+        if kind != &b"para"[..] {
+            return Err("unknown curve type")
+        }
+
+        let function = read_u16(profile, offset + 8);
+        let count = match function {
+            0 => 1,
+            1 => 3,
+            2 => 4,
+            3 => 5,
+            4 => 7,
+            _ => return Err("unknown parametric curve"),
+        };
+        if offset + 12 + count * 4 > profile.len() {
+            return Err("truncated curve")
+        }
+        let mut values = [0.0; 7];
+        for index in 0..count {
+            values[index] = read_s15_fixed16(profile, offset + 12 + index * 4);
+        }
+        let (g, a, b, c) = (values[0], values[1], values[2], values[3]);
+        // Rewrite the simpler functions in terms of the general one.
+        Ok(Curve::Parametric(match function {
+            0 => [g, 1.0, 0.0, 0.0, 0.0, 0.0, 0.0],
+            1 => [g, a, b, 0.0, -b / a, 0.0, 0.0],
+            2 => [g, a, b, 0.0, -b / a, c, c],
+            3 => [g, a, b, c, values[4], 0.0, 0.0],
+            _ => values,
+        }))
+    }
+
+    fn apply(&self, x: f32) -> f32 {
+        match *self {
+            Curve::Gamma(gamma) => x.powf(gamma),
+            Curve::Table(ref table) => {
+                let position = x * (table.len() - 1) as f32;
+                let index = position.floor() as usize;
+                if index + 1 >= table.len() {
+                    return table[table.len() - 1]
+                }
+                let fraction = position - index as f32;
+                table[index] + (table[index + 1] - table[index]) * fraction
+            }
+            Curve::Parametric(ref values) => {
+                let (g, a, b, c, d, e, f) = (values[0], values[1], values[2], values[3],
+                                             values[4], values[5], values[6]);
+                if x >= d {
+                    let base = a * x + b;
+                    if base > 0.0 { base.powf(g) + e } else { e }
+                } else {
+                    c * x + f
+                }
+            }
+        }
+    }
+}
+
+/// Converts pixels from the colors of a profile to sRGB.
+pub struct Transform {
+    /// The linear value of each encoded value of each channel.
+    input: [Vec<f32>; 3],
+    /// Maps linear channels in the profile's color space to linear sRGB.
+    matrix: [[f32; 3]; 3],
+    /// The sRGB encoding of evenly spaced linear values.
+    output: Vec<u8>,
+}
+
+impl Transform {
+    /// Reads a profile, giving `None` if its colors are already sRGB's.
+    pub fn new(profile: &[u8]) -> Result<Option<Transform>, &'static str> {
+        if profile.len() < 132 || profile[36..40] != b"acsp"[..] {
+            return Err("not an ICC profile")
+        }
+        if profile[16..20] != b"RGB "[..] {
+            return Err("not an RGB profile")
+        }
+        if profile[20..24] != b"XYZ "[..] {
+            return Err("unsupported connection space")
+        }
+
+        let tag_count = read_u32(profile, 128) as usize;
+        let find_tag = |signature: &[u8]| -> Result<usize, &'static str> {
+            for index in 0..tag_count {
+                let entry = 132 + index * 12;
+                if entry + 12 > profile.len() {
+                    break
+                }
+                if &profile[entry..entry + 4] == signature {
+                    return Ok(read_u32(profile, entry + 4) as usize)
+                }
+            }
+            Err("missing tag")
+        };
+
+        // The columns of the matrix are the colors of the three primaries.
+        let mut to_xyz = [[0.0; 3]; 3];
+        for (column, signature) in [b"rXYZ", b"gXYZ", b"bXYZ"].iter().enumerate() {
+            let offset = try!(find_tag(&signature[..]));
+            if offset + 20 > profile.len() || profile[offset..offset + 4] != b"XYZ "[..] {
+                return Err("bad primary")
+            }
+            for row in 0..3 {
+                to_xyz[row][column] = read_s15_fixed16(profile, offset + 8 + row * 4);
+            }
+        }
+        let mut matrix = [[0.0; 3]; 3];
+        for row in 0..3 {
+            for column in 0..3 {
+                matrix[row][column] = (0..3).map(|index| {
+                    XYZ_D50_TO_LINEAR_SRGB[row][index] * to_xyz[index][column]
+                }).fold(0.0, |sum, value| sum + value);
+            }
+        }
+
+        let mut input = [vec!(), vec!(), vec!()];
+        for (channel, signature) in [b"rTRC", b"gTRC", b"bTRC"].iter().enumerate() {
+            let curve = try!(Curve::read(profile, try!(find_tag(&signature[..]))));
+            input[channel] = (0..256).map(|value| curve.apply(value as f32 / 255.0)).collect();
+        }
+
+        let srgb_input: Vec<f32> = (0..256).map(|value| srgb_to_linear(value as f32 / 255.0))
+                                           .collect();
+        let is_srgb = (0..3).all(|row| {
+            (0..3).all(|column| {
+                let identity = if row == column { 1.0 } else { 0.0 };
+                (matrix[row][column] - identity).abs() < TOLERANCE
+            })
+        }) && input.iter().all(|table| {
+            table.iter().zip(srgb_input.iter()).all(|(a, b)| (a - b).abs() < TOLERANCE)
+        });
+        if is_srgb {
+            return Ok(None)
+        }
+
+        let output = (0..OUTPUT_TABLE_SIZE).map(|index| {
+            let linear = index as f32 / (OUTPUT_TABLE_SIZE - 1) as f32;
+            (linear_to_srgb(linear) * 255.0).round() as u8
+        }).collect();
+        Ok(Some(Transform {
+            input: input,
+            matrix: matrix,
+            output: output,
+        }))
+    }
+
+    /// Converts pixels of `bytes_per_pixel` bytes, the first three of which are red, green and
+    /// blue, in place. Any alpha must not be premultiplied yet.
+    pub fn apply(&self, pixels: &mut [u8], bytes_per_pixel: usize) {
+        let scale = (OUTPUT_TABLE_SIZE - 1) as f32;
+        for pixel in pixels.chunks_mut(bytes_per_pixel) {
+            let color = [
+                self.input[0][pixel[0] as usize],
+                self.input[1][pixel[1] as usize],
+                self.input[2][pixel[2] as usize],
+            ];
+            for channel in 0..3 {
+                let row = &self.matrix[channel];
+                let linear = row[0] * color[0] + row[1] * color[1] + row[2] * color[2];
+                let linear = if linear < 0.0 { 0.0 } else if linear > 1.0 { 1.0 } else { linear };
+                pixel[channel] = self.output[(linear * scale).round() as usize];
+            }
+        }
+    }
+}
+
+fn srgb_to_linear(value: f32) -> f32 {
+    if value <= 0.04045 {
+        value / 12.92
+    } else {
+        ((value + 0.055) / 1.055).powf(2.4)
+    }
+}
+
+fn linear_to_srgb(value: f32) -> f32 {
+    if value <= 0.0031308 {
+        value * 12.92
+    } else {
+        1.055 * value.powf(1.0 / 2.4) - 0.055
+    }
+}
+
+fn read_u16(data: &[u8], offset: usize) -> u16 {
+    (data[offset] as u16) << 8 | data[offset + 1] as u16
+}
+
+fn read_u32(data: &[u8], offset: usize) -> u32 {
+    (data[offset] as u32) << 24 | (data[offset + 1] as u32) << 16 |
+        (data[offset + 2] as u32) << 8 | data[offset + 3] as u32
+}
+
+fn read_s15_fixed16(data: &[u8], offset: usize) -> f32 {
+    read_u32(data, offset) as i32 as f32 / 65536.0
+}
@@ -0,0 +1,187 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+//! Reads ICO files, which hold the same icon at several sizes, each either a PNG or a bitmap.
+//! https://msdn.microsoft.com/en-us/library/ms997538.aspx
+
+use std::cmp::Ordering;
+
+const PNG_SIGNATURE: &'static [u8] = &[0x89, b'P', b'N', b'G', b'\r', b'\n', 0x1a, b'\n'];
+
+/// The image picked out of an icon.
+pub enum Icon<'a> {
+    /// A PNG, which is decoded like any other.
+    Png(&'a [u8]),
+    /// A bitmap, decoded to RGBA.
+    Bitmap {
+        width: u32,
+        height: u32,
+        pixels: Vec<u8>,
+    },
+}
+
+/// One of the images in an icon, as described by the icon's directory.
+struct Entry<'a> {
+    width: u32,
+    height: u32,
+    bit_count: u16,
+    data: &'a [u8],
+}
+
+pub fn is_ico(buffer: &[u8]) -> bool {
+    buffer.len() >= 6 && buffer[..4] == [0, 0, 1, 0][..]
+}
+
+/// Decodes the image in an icon that is best shown at `size` pixels square: the smallest one
+/// at least that big, or else the biggest one.
+pub fn decode(buffer: &[u8], size: u32) -> Result<Icon, &'static str> {
+    if !is_ico(buffer) {
+        return Err("not an icon")
+    }
+    let count = u16_at(buffer, 4) as usize;
+    let mut entries = vec!();
+    for index in 0..count {
+        let offset = 6 + index * 16;
+        if offset + 16 > buffer.len() {
+            break
+        }
+        let length = u32_at(buffer, offset + 8) as usize;
+        let start = u32_at(buffer, offset + 12) as usize;
+        if start >= buffer.len() || length > buffer.len() - start {
+            continue
+        }
+        // A size of zero stands for 256.
+        let dimension = |byte: u8| if byte == 0 { 256 } else { byte as u32 };
+        entries.push(Entry {
+            width: dimension(buffer[offset]),
+            height: dimension(buffer[offset + 1]),
+            bit_count: u16_at(buffer, offset + 6),
+            data: &buffer[start..start + length],
+        });
+    }
+
+    entries.sort_by(|a, b| compare_entries(a, b, size));
+    let entry = match entries.first() {
+        Some(entry) => entry,
+        None => return Err("no images in the icon"),
+    };
+    if entry.data.starts_with(PNG_SIGNATURE) {
+        return Ok(Icon::Png(entry.data))
+    }
+    decode_bitmap(entry.data)
+}
+
+/// Orders the images of an icon from the most to the least suitable for `size`.
+fn compare_entries(a: &Entry, b: &Entry, size: u32) -> Ordering {
+    let a_fits = a.width >= size;
+    let b_fits = b.width >= size;
+    let by_size = match (a_fits, b_fits) {
+        (true, false) => Ordering::Less,
+        (false, true) => Ordering::Greater,
+        (true, true) => a.width.cmp(&b.width),
+        (false, false) => b.width.cmp(&a.width),
+    };
+    match by_size {
+        Ordering::Equal => b.bit_count.cmp(&a.bit_count),
+        ordering => ordering,
+    }
+}
+
+/// Decodes a bitmap stored in an icon: a BITMAPINFOHEADER, a palette for the bit depths that
+/// need one, the colors, and a mask of the transparent pixels. The header gives twice the
+/// real height, to cover the mask, and rows are stored from the bottom up.
+fn decode_bitmap(data: &[u8]) -> Result<Icon, &'static str> {
+    if data.len() < 40 {
+        return Err("truncated bitmap header")
+    }
+    let header_size = u32_at(data, 0) as usize;
+    let width = u32_at(data, 4) as i32;
+    let height = (u32_at(data, 8) as i32).abs() / 2;
+    let bit_count = u16_at(data, 14) as usize;
+    let compression = u32_at(data, 16);
+    let colors_used = u32_at(data, 32) as usize;
+    if width <= 0 || height <= 0 || width > 256 || height > 256 {
+        return Err("bad bitmap size")
+    }
+    // Uncompressed colors, or 32-bit colors given with bit fields, which icons always lay
+    // out as BGRA.
+    if compression != 0 && !(compression == 3 && bit_count == 32) {
+        return Err("unsupported bitmap compression")
+    }
+    let (width, height) = (width as usize, height as usize);
+
+    let palette_size = match bit_count {
+        1 | 4 | 8 if colors_used > 0 => colors_used,
+        1 | 4 | 8 => 1 << bit_count,
+        24 | 32 => 0,
+        _ => return Err("unsupported bit depth"),
+    };
+    let palette_start = header_size + if compression == 3 { 12 } else { 0 };
+    let colors_start = palette_start + palette_size * 4;
+    let row_size = (width * bit_count + 31) / 32 * 4;
+    let mask_start = colors_start + row_size * height;
+    let mask_row_size = (width + 31) / 32 * 4;
+    if colors_start + row_size * height > data.len() {
+        return Err("truncated bitmap")
+    }
+    let palette = &data[palette_start..colors_start];
+    // Some 32-bit icons leave out the mask, and use only their alpha channel.
+    let mask = if mask_start + mask_row_size * height <= data.len() {
+        Some(&data[mask_start..])
+    } else {
+        None
+    };
+
+    let mut pixels = Vec::with_capacity(width * height * 4);
+    let mut has_alpha = false;
+    for y in 0..height {
+        let row = &data[colors_start + (height - 1 - y) * row_size..];
+        for x in 0..width {
+            let (blue, green, red, alpha) = match bit_count {
+                32 => (row[x * 4], row[x * 4 + 1], row[x * 4 + 2], row[x * 4 + 3]),
+                24 => (row[x * 3], row[x * 3 + 1], row[x * 3 + 2], 0xff),
+                _ => {
+                    let bit = x * bit_count;
+                    let index = (row[bit / 8] >> (8 - bit_count - bit % 8)) as usize &
+                                ((1 << bit_count) - 1);
+                    if index * 4 + 3 > palette.len() {
+                        (0, 0, 0, 0xff)
+                    } else {
+                        (palette[index * 4], palette[index * 4 + 1], palette[index * 4 + 2], 0xff)
+                    }
+                }
+            };
+            has_alpha = has_alpha || (bit_count == 32 && alpha != 0);
+            pixels.push_all(&[red, green, blue, alpha]);
+        }
+    }
+
+    // The mask is used when there is no alpha channel, or one that was left empty.
+    if let Some(mask) = mask {
+        if !has_alpha {
+            for y in 0..height {
+                let row = &mask[(height - 1 - y) * mask_row_size..];
+                for x in 0..width {
+                    let transparent = row[x / 8] & (0x80 >> (x % 8)) != 0;
+                    pixels[(y * width + x) * 4 + 3] = if transparent { 0 } else { 0xff };
+                }
+            }
+        }
+    }
+
+    Ok(Icon::Bitmap {
+        width: width as u32,
+        height: height as u32,
+        pixels: pixels,
+    })
+}
+
+fn u16_at(data: &[u8], offset: usize) -> u16 {
+    data[offset] as u16 | (data[offset + 1] as u16) << 8
+}
+
+fn u32_at(data: &[u8], offset: usize) -> u32 {
+    data[offset] as u32 | (data[offset + 1] as u32) << 8 | (data[offset + 2] as u32) << 16 |
+        (data[offset + 3] as u32) << 24
+}
@@ -0,0 +1,962 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+//! A JPEG decoder for baseline and progressive images, which can decode large images at 1/2
+//! or 1/4 of their size by only using the coefficients it needs for that.
+//! http://www.w3.org/Graphics/JPEG/itu-t81.pdf
+
+use euclid::size::Size2D;
+use std::cmp::max;
+use std::f32::consts::PI;
+use std::iter::repeat;
+
+/// Maps the zigzag order coefficients are stored in to their natural order.
+static ZIGZAG: [u8; 64] = [
+     0,  1,  8, 16,  9,  2,  3, 10,
+    17, 24, 32, 25, 18, 11,  4,  5,
+    12, 19, 26, 33, 40, 48, 41, 34,
+    27, 20, 13,  6,  7, 14, 21, 28,
+    35, 42, 49, 56, 57, 50, 43, 36,
+    29, 22, 15, 23, 30, 37, 44, 51,
+    58, 59, 52, 45, 38, 31, 39, 46,
+    53, 60, 61, 54, 47, 55, 62, 63,
+];
+
+/// A decoded JPEG.
+pub struct JpegImage {
+    pub width: u32,
+    pub height: u32,
+    /// The pixels, as RGBA.
+    pub pixels: Vec<u8>,
+}
+
+/// What the start of a JPEG says about the image.
+pub struct JpegHeader {
+    pub width: u32,
+    pub height: u32,
+    pub progressive: bool,
+}
+
+/// Reads the size of a JPEG, and whether it is progressive, without decoding it.
+pub fn read_header(buffer: &[u8]) -> Option<JpegHeader> {
+    if buffer.len() < 2 || buffer[0] != 0xff || buffer[1] != 0xd8 {
+        return None
+    }
+    let mut position = 2;
+    while position + 4 <= buffer.len() && buffer[position] == 0xff {
+        let marker = buffer[position + 1];
+        if marker == 0xff {
+            position += 1;
+            continue
+        }
+        match marker {
+            // The start of a frame, of any kind.
+            0xc0...0xc3 | 0xc5...0xc7 | 0xc9...0xcb | 0xcd...0xcf => {
+                if position + 9 > buffer.len() {
+                    return None
+                }
+                return Some(JpegHeader {
+                    height: (buffer[position + 5] as u32) << 8 | buffer[position + 6] as u32,
+                    width: (buffer[position + 7] as u32) << 8 | buffer[position + 8] as u32,
+                    progressive: marker == 0xc2,
+                })
+            }
+            _ => {
+                let length = (buffer[position + 2] as usize) << 8 | buffer[position + 3] as usize;
+                position += 2 + length;
+            }
+        }
+    }
+    None
+}
+
+/// Decodes a JPEG. If `target_size` is much smaller than the image, the image is decoded at
+/// the smallest of 1/2 or 1/4 of its size that still covers `target_size`.
+pub fn decode(buffer: &[u8], target_size: Option<Size2D<u32>>)
+              -> Result<JpegImage, &'static str> {
+    let mut decoder = Decoder::new(buffer);
+    try!(decoder.read_to_end(target_size));
+    decoder.output()
+}
+
+/// The factor an image of the given size is scaled down by to be shown at `target_size`.
+pub fn scale_for_target_size(width: u32, height: u32, target_size: Option<Size2D<u32>>) -> u32 {
+    let target_size = match target_size {
+        Some(target_size) => target_size,
+        None => return 1,
+    };
+    for &scale in [4, 2].iter() {
+        if scaled(width, scale) >= target_size.width &&
+                scaled(height, scale) >= target_size.height {
+            return scale
+        }
+    }
+    1
+}
+
+fn scaled(length: u32, scale: u32) -> u32 {
+    (length + scale - 1) / scale
+}
+
+/// The number of bits of Huffman codes looked up at once.
+const LOOKUP_BITS: u32 = 9;
+
+struct HuffmanTable {
+    /// For each code length, the largest code of that length, or -1 if there are none.
+    max_code: [i32; 17],
+    /// For each code length, what to add to a code of that length to find its value.
+    value_offset: [i32; 17],
+    values: Vec<u8>,
+    /// The length and value of the code that starts with each possible `LOOKUP_BITS` bits, or
+    /// a length of zero if the code is longer.
+    lookup: Vec<(u8, u8)>,
+}
+
+impl HuffmanTable {
+    fn new(counts: &[u8], values: Vec<u8>) -> Result<HuffmanTable, &'static str> {
+        let mut max_code = [-1; 17];
+        let mut value_offset = [0; 17];
+        let mut code = 0;
+        let mut index = 0;
+        for length in 1..17 {
+            let count = counts[length - 1] as i32;
+            value_offset[length] = index - code;
+            code += count;
+            index += count;
+            if count > 0 {
+                max_code[length] = code - 1;
+            }
+            code <<= 1;
+        }
+        if index as usize > values.len() {
+            return Err("bad Huffman table")
+        }
+
+        let mut lookup: Vec<(u8, u8)> = repeat((0, 0)).take(1 << LOOKUP_BITS).collect();
+        let mut code = 0;
+        let mut index = 0;
+        for length in 1..(LOOKUP_BITS as usize + 1) {
+            for _ in 0..counts[length - 1] {
+                let unused_bits = LOOKUP_BITS as usize - length;
+                for suffix in 0..(1 << unused_bits) {
+                    lookup[(code << unused_bits) | suffix] = (length as u8, values[index]);
+                }
+                code += 1;
+                index += 1;
+            }
+            code <<= 1;
+        }
+        Ok(HuffmanTable {
+            max_code: max_code,
+            value_offset: value_offset,
+            values: values,
+            lookup: lookup,
+        })
+    }
+}
+
+struct Component {
+    id: u8,
+    horizontal_sampling: usize,
+    vertical_sampling: usize,
+    quantization_table: usize,
+    /// The number of blocks across and down that cover the component's part of the image.
+    blocks_across: usize,
+    blocks_down: usize,
+    /// The number of blocks across and down that cover every MCU, which may run past the
+    /// edges of the image.
+    padded_blocks_across: usize,
+    padded_blocks_down: usize,
+    /// The coefficients of every block, in natural order.
+    coefficients: Vec<i16>,
+    dc_table: usize,
+    ac_table: usize,
+    dc_prediction: i32,
+}
+
+struct Frame {
+    progressive: bool,
+    width: usize,
+    height: usize,
+    components: Vec<Component>,
+    max_horizontal_sampling: usize,
+    max_vertical_sampling: usize,
+    mcus_across: usize,
+    mcus_down: usize,
+}
+
+/// Reads the entropy-coded data of a scan, a bit at a time.
+struct BitReader<'a> {
+    data: &'a [u8],
+    position: usize,
+    bits: u32,
+    bit_count: u32,
+    /// Whether a marker has been reached, after which only zeros are read.
+    at_marker: bool,
+}
+
+impl<'a> BitReader<'a> {
+    fn fill(&mut self) {
+        while self.bit_count <= 24 {
+            let mut byte = 0;
+            if self.position >= self.data.len() {
+                self.at_marker = true;
+            }
+            if !self.at_marker {
+                byte = self.data[self.position];
+                if byte == 0xff {
+                    match self.data.get(self.position + 1) {
+                        // A stuffed zero byte follows data bytes that look like markers.
+                        Some(&0) => self.position += 2,
+                        _ => {
+                            self.at_marker = true;
+                            byte = 0;
+                        }
+                    }
+                } else {
+                    self.position += 1;
+                }
+            }
+            self.bits |= (byte as u32) << (24 - self.bit_count);
+            self.bit_count += 8;
+        }
+    }
+
+    fn bits(&mut self, count: u32) -> u32 {
+        if count == 0 {
+            return 0
+        }
+        if self.bit_count < count {
+            self.fill();
+        }
+        let value = self.bits >> (32 - count);
+        self.bits <<= count;
+        self.bit_count -= count;
+        value
+    }
+
+    fn bit(&mut self) -> bool {
+        self.bits(1) == 1
+    }
+
+    /// Reads a value of the given length, whose sign is given by its top bit.
+    fn extended(&mut self, length: u32) -> i32 {
+        if length == 0 {
+            return 0
+        }
+        let value = self.bits(length) as i32;
+        if value < 1 << (length - 1) {
+            value - (1 << length) + 1
+        } else {
+            value
+        }
+    }
+
+    fn huffman(&mut self, table: &HuffmanTable) -> Result<u8, &'static str> {
+        if self.bit_count < LOOKUP_BITS {
+            self.fill();
+        }
+        let (length, value) = table.lookup[(self.bits >> (32 - LOOKUP_BITS)) as usize];
+        if length > 0 {
+            self.bits(length as u32);
+            return Ok(value)
+        }
+        let mut code = self.bits(LOOKUP_BITS) as i32;
+        for length in (LOOKUP_BITS as usize + 1)..17 {
+            code = (code << 1) | self.bits(1) as i32;
+            if code <= table.max_code[length] {
+                let index = (code + table.value_offset[length]) as usize;
+                return table.values.get(index).cloned().ok_or("bad Huffman code")
+            }
+        }
+        Err("bad Huffman code")
+    }
+
+    /// Skips to the restart marker that must come after every restart interval.
+    fn restart(&mut self) {
+        self.bits = 0;
+        self.bit_count = 0;
+        self.at_marker = false;
+        while self.position + 1 < self.data.len() {
+            let (byte, next) = (self.data[self.position], self.data[self.position + 1]);
+            self.position += 1;
+            if byte == 0xff && next >= 0xd0 && next <= 0xd7 {
+                self.position += 1;
+                return
+            }
+        }
+    }
+}
+
+/// The spectral selection and successive approximation of a scan.
+#[derive(Copy, Clone)]
+struct ScanParameters {
+    spectral_start: usize,
+    spectral_end: usize,
+    approximation_high: u32,
+    approximation_low: u32,
+}
+
+struct Decoder<'a> {
+    data: &'a [u8],
+    position: usize,
+    frame: Option<Frame>,
+    quantization_tables: [[u16; 64]; 4],
+    dc_tables: [Option<HuffmanTable>; 4],
+    ac_tables: [Option<HuffmanTable>; 4],
+    restart_interval: usize,
+    /// Whether an Adobe marker said that three components are RGB rather than YCbCr.
+    adobe_rgb: bool,
+    scans_read: usize,
+    /// The factor the image is scaled down by.
+    scale: usize,
+}
+
+impl<'a> Decoder<'a> {
+    fn new(data: &'a [u8]) -> Decoder<'a> {
+        Decoder {
+            data: data,
+            position: 0,
+            frame: None,
+            quantization_tables: [[0; 64]; 4],
+            dc_tables: [None, None, None, None],
+            ac_tables: [None, None, None, None],
+            restart_interval: 0,
+            adobe_rgb: false,
+            scans_read: 0,
+            scale: 1,
+        }
+    }
+
+    fn u8(&mut self) -> Result<u8, &'static str> {
+        let byte = try!(self.data.get(self.position).cloned().ok_or("unexpected end of data"));
+        self.position += 1;
+        Ok(byte)
+    }
+
+    fn u16(&mut self) -> Result<u16, &'static str> {
+        let high = try!(self.u8()) as u16;
+        Ok(high << 8 | try!(self.u8()) as u16)
+    }
+
+    /// Reads the contents of a marker segment.
+    fn segment(&mut self) -> Result<&'a [u8], &'static str> {
+        let length = try!(self.u16()) as usize;
+        if length < 2 || self.position + length - 2 > self.data.len() {
+            return Err("bad segment length")
+        }
+        let segment = &self.data[self.position..self.position + length - 2];
+        self.position += length - 2;
+        Ok(segment)
+    }
+
+    /// Reads segments up to the end of the image. A truncated image keeps what was decoded
+    /// before the data ran out, which for a progressive image is all of it at a lower
+    /// quality.
+    fn read_to_end(&mut self, target_size: Option<Size2D<u32>>) -> Result<(), &'static str> {
+        if self.data.len() < 2 || self.data[0] != 0xff || self.data[1] != 0xd8 {
+            return Err("not a JPEG")
+        }
+        self.position = 2;
+        loop {
+            // Markers may be padded with any number of 0xff bytes.
+            let mut marker = 0xff;
+            while marker == 0xff {
+                marker = match self.u8() {
+                    Ok(byte) => byte,
+                    Err(error) => {
+                        if self.scans_read > 0 {
+                            return Ok(())
+                        }
+                        return Err(error)
+                    }
+                };
+            }
+            match marker {
+                // Baseline, extended sequential and progressive frames, using Huffman coding.
+                0xc0 | 0xc1 | 0xc2 => {
+                    let segment = try!(self.segment());
+                    try!(self.read_frame(segment, marker == 0xc2, target_size));
+                }
+                0xc3 | 0xc5...0xc7 | 0xc9...0xcb | 0xcd...0xcf => {
+                    return Err("unsupported JPEG coding")
+                }
+                0xc4 => {
+                    let segment = try!(self.segment());
+                    try!(self.read_huffman_tables(segment));
+                }
+                0xdb => {
+                    let segment = try!(self.segment());
+                    try!(self.read_quantization_tables(segment));
+                }
+                0xdd => {
+                    let segment = try!(self.segment());
+                    if segment.len() < 2 {
+                        return Err("bad restart interval")
+                    }
+                    self.restart_interval = (segment[0] as usize) << 8 | segment[1] as usize;
+                }
+                0xda => {
+                    let segment = try!(self.segment());
+                    try!(self.read_scan(segment));
+                    self.scans_read += 1;
+                }
+                0xee => {
+                    let segment = try!(self.segment());
+                    if segment.len() >= 12 && segment[..5] == b"Adobe"[..] {
+                        self.adobe_rgb = segment[11] == 0;
+                    }
+                }
+                0xd9 => return Ok(()),
+                // Stray restart markers, and the fill bytes before them.
+                0x00 | 0xd0...0xd7 => {}
+                _ => {
+                    try!(self.segment());
+                }
+            }
+        }
+    }
+
+    fn read_frame(&mut self, segment: &[u8], progressive: bool, target_size: Option<Size2D<u32>>)
+                  -> Result<(), &'static str> {
+        if self.frame.is_some() {
+            return Err("more than one frame")
+        }
+        if segment.len() < 6 || segment[0] != 8 {
+            return Err("unsupported sample precision")
+        }
+        let height = (segment[1] as usize) << 8 | segment[2] as usize;
+        let width = (segment[3] as usize) << 8 | segment[4] as usize;
+        let component_count = segment[5] as usize;
+        if width == 0 || height == 0 {
+            return Err("bad image size")
+        }
+        if component_count != 1 && component_count != 3 {
+            return Err("unsupported number of components")
+        }
+        if segment.len() < 6 + component_count * 3 {
+            return Err("bad frame header")
+        }
+
+        let mut components = vec!();
+        for index in 0..component_count {
+            let data = &segment[6 + index * 3..];
+            let horizontal_sampling = (data[1] >> 4) as usize;
+            let vertical_sampling = (data[1] & 0x0f) as usize;
+            if horizontal_sampling == 0 || horizontal_sampling > 4 ||
+                    vertical_sampling == 0 || vertical_sampling > 4 || data[2] > 3 {
+                return Err("bad component")
+            }
+            components.push(Component {
+                id: data[0],
+                horizontal_sampling: horizontal_sampling,
+                vertical_sampling: vertical_sampling,
+                quantization_table: data[2] as usize,
+                blocks_across: 0,
+                blocks_down: 0,
+                padded_blocks_across: 0,
+                padded_blocks_down: 0,
+                coefficients: vec!(),
+                dc_table: 0,
+                ac_table: 0,
+                dc_prediction: 0,
+            });
+        }
+        let max_horizontal_sampling = components.iter().fold(1, |sampling, component| {
+            max(sampling, component.horizontal_sampling)
+        });
+        let max_vertical_sampling = components.iter().fold(1, |sampling, component| {
+            max(sampling, component.vertical_sampling)
+        });
+        let mcus_across = ceil_div(width, 8 * max_horizontal_sampling);
+        let mcus_down = ceil_div(height, 8 * max_vertical_sampling);
+        for component in components.iter_mut() {
+            let sampled_width =
+                ceil_div(width * component.horizontal_sampling, max_horizontal_sampling);
+            let sampled_height =
+                ceil_div(height * component.vertical_sampling, max_vertical_sampling);
+            component.blocks_across = ceil_div(sampled_width, 8);
+            component.blocks_down = ceil_div(sampled_height, 8);
+            component.padded_blocks_across = mcus_across * component.horizontal_sampling;
+            component.padded_blocks_down = mcus_down * component.vertical_sampling;
+            let block_count = component.padded_blocks_across * component.padded_blocks_down;
+            component.coefficients = repeat(0).take(block_count * 64).collect();
+        }
+
+        self.scale = scale_for_target_size(width as u32, height as u32, target_size) as usize;
+        self.frame = Some(Frame {
+            progressive: progressive,
+            width: width,
+            height: height,
+            components: components,
+            max_horizontal_sampling: max_horizontal_sampling,
+            max_vertical_sampling: max_vertical_sampling,
+            mcus_across: mcus_across,
+            mcus_down: mcus_down,
+        });
+        Ok(())
+    }
+
+    fn read_huffman_tables(&mut self, mut segment: &[u8]) -> Result<(), &'static str> {
+        while !segment.is_empty() {
+            if segment.len() < 17 {
+                return Err("bad Huffman table")
+            }
+            let (class, index) = (segment[0] >> 4, (segment[0] & 0x0f) as usize);
+            if class > 1 || index > 3 {
+                return Err("bad Huffman table")
+            }
+            let counts = &segment[1..17];
+            let value_count = counts.iter().fold(0, |total, &count| total + count as usize);
+            if segment.len() < 17 + value_count {
+                return Err("bad Huffman table")
+            }
+            let values = segment[17..17 + value_count].to_vec();
+            let table = Some(try!(HuffmanTable::new(counts, values)));
+            if class == 0 {
+                self.dc_tables[index] = table;
+            } else {
+                self.ac_tables[index] = table;
+            }
+            segment = &segment[17 + value_count..];
+        }
+        Ok(())
+    }
+
+    fn read_quantization_tables(&mut self, mut segment: &[u8]) -> Result<(), &'static str> {
+        while !segment.is_empty() {
+            let (precision, index) = (segment[0] >> 4, (segment[0] & 0x0f) as usize);
+            let length = if precision == 0 { 64 } else { 128 };
+            if precision > 1 || index > 3 || segment.len() < 1 + length {
+                return Err("bad quantization table")
+            }
+            for i in 0..64 {
+                let value = if precision == 0 {
+                    segment[1 + i] as u16
+                } else {
+                    (segment[1 + i * 2] as u16) << 8 | segment[2 + i * 2] as u16
+                };
+                self.quantization_tables[index][ZIGZAG[i] as usize] = value;
+            }
+            segment = &segment[1 + length..];
+        }
+        Ok(())
+    }
+
+    fn read_scan(&mut self, segment: &[u8]) -> Result<(), &'static str> {
+        let frame = match self.frame {
+            Some(ref mut frame) => frame,
+            None => return Err("scan before frame"),
+        };
+        if segment.is_empty() {
+            return Err("bad scan header")
+        }
+        let component_count = segment[0] as usize;
+        if component_count == 0 || component_count > 4 || segment.len() < 4 + component_count * 2 {
+            return Err("bad scan header")
+        }
+        let mut scan_components = vec!();
+        for index in 0..component_count {
+            let data = &segment[1 + index * 2..];
+            let component_index = match frame.components.iter().position(|c| c.id == data[0]) {
+                Some(component_index) => component_index,
+                None => return Err("bad scan component"),
+            };
+            let component = &mut frame.components[component_index];
+            component.dc_table = (data[1] >> 4) as usize & 3;
+            component.ac_table = (data[1] & 0x0f) as usize & 3;
+            scan_components.push(component_index);
+        }
+        let parameters = &segment[1 + component_count * 2..];
+        let scan = ScanParameters {
+            spectral_start: parameters[0] as usize,
+            spectral_end: parameters[1] as usize,
+            approximation_high: (parameters[2] >> 4) as u32,
+            approximation_low: (parameters[2] & 0x0f) as u32,
+        };
+        if frame.progressive {
+            if scan.spectral_end > 63 || scan.spectral_start > scan.spectral_end ||
+                    (scan.spectral_start == 0 && scan.spectral_end != 0) ||
+                    (scan.spectral_start > 0 && component_count != 1) {
+                return Err("bad progressive scan")
+            }
+        } else {
+            if scan.spectral_start != 0 || scan.approximation_high != 0 ||
+                    scan.approximation_low != 0 {
+                return Err("bad sequential scan")
+            }
+            // Sequential scans always cover every coefficient, whatever they claim.
+        }
+
+        let mut reader = BitReader {
+            data: self.data,
+            position: self.position,
+            bits: 0,
+            bit_count: 0,
+            at_marker: false,
+        };
+        for &index in scan_components.iter() {
+            frame.components[index].dc_prediction = 0;
+        }
+        let mut end_of_band_run = 0;
+
+        // A scan of a single component goes through its blocks one at a time; otherwise each
+        // MCU holds a group of blocks from each component.
+        let (mcus_across, mcus_down) = if component_count == 1 {
+            let component = &frame.components[scan_components[0]];
+            (component.blocks_across, component.blocks_down)
+        } else {
+            (frame.mcus_across, frame.mcus_down)
+        };
+        let mcu_count = mcus_across * mcus_down;
+        'mcus: for mcu in 0..mcu_count {
+            if self.restart_interval > 0 && mcu > 0 && mcu % self.restart_interval == 0 {
+                reader.restart();
+                for &index in scan_components.iter() {
+                    frame.components[index].dc_prediction = 0;
+                }
+                end_of_band_run = 0;
+            }
+            let (mcu_x, mcu_y) = (mcu % mcus_across, mcu / mcus_across);
+            for &index in scan_components.iter() {
+                let component = &mut frame.components[index];
+                let (blocks_across, blocks_down) = if component_count == 1 {
+                    (1, 1)
+                } else {
+                    (component.horizontal_sampling, component.vertical_sampling)
+                };
+                for block_y in 0..blocks_down {
+                    for block_x in 0..blocks_across {
+                        let x = mcu_x * blocks_across + block_x;
+                        let y = mcu_y * blocks_down + block_y;
+                        let offset = (y * component.padded_blocks_across + x) * 64;
+                        let dc_table = self.dc_tables[component.dc_table].as_ref();
+                        let ac_table = self.ac_tables[component.ac_table].as_ref();
+                        let coefficients = &mut component.coefficients[offset..offset + 64];
+                        let prediction = &mut component.dc_prediction;
+                        let result = if !frame.progressive {
+                            decode_block(&mut reader, coefficients, prediction, dc_table, ac_table)
+                        } else if scan.spectral_start == 0 {
+                            decode_dc(&mut reader, coefficients, prediction, dc_table, scan)
+                        } else if scan.approximation_high == 0 {
+                            decode_ac_first(&mut reader, coefficients, ac_table, scan,
+                                            &mut end_of_band_run)
+                        } else {
+                            decode_ac_refinement(&mut reader, coefficients, ac_table, scan,
+                                                 &mut end_of_band_run)
+                        };
+                        // Data that runs out early leaves the rest of the scan blank.
+                        match result {
+                            Ok(()) => {}
+                            Err(_) if reader.at_marker => break 'mcus,
+                            Err(error) => return Err(error),
+                        }
+                    }
+                }
+            }
+        }
+
+        // Continue after the scan's data, at the next marker.
+        self.position = reader.position;
+        while self.position + 1 < self.data.len() &&
+                (self.data[self.position] != 0xff || self.data[self.position + 1] == 0 ||
+                 (self.data[self.position + 1] >= 0xd0 && self.data[self.position + 1] <= 0xd7)) {
+            self.position += 1;
+        }
+        Ok(())
+    }
+
+    /// Converts the decoded coefficients to pixels.
+    fn output(&self) -> Result<JpegImage, &'static str> {
+        let frame = match self.frame {
+            Some(ref frame) if self.scans_read > 0 => frame,
+            _ => return Err("no image data"),
+        };
+        let scale = self.scale;
+        let block_size = 8 / scale;
+        let idct = IdctTable::new(block_size);
+
+        let planes: Vec<Vec<u8>> = frame.components.iter().map(|component| {
+            let table = &self.quantization_tables[component.quantization_table];
+            let stride = component.padded_blocks_across * block_size;
+            let mut plane: Vec<u8> =
+                repeat(0).take(stride * component.padded_blocks_down * block_size).collect();
+            for block_y in 0..component.padded_blocks_down {
+                for block_x in 0..component.padded_blocks_across {
+                    let offset = (block_y * component.padded_blocks_across + block_x) * 64;
+                    let output = block_y * block_size * stride + block_x * block_size;
+                    idct.transform(&component.coefficients[offset..offset + 64],
+                                   table,
+                                   &mut plane[output..],
+                                   stride);
+                }
+            }
+            plane
+        }).collect();
+
+        let width = ceil_div(frame.width, scale);
+        let height = ceil_div(frame.height, scale);
+        let mut pixels = Vec::with_capacity(width * height * 4);
+        // Find the sample of each component that covers each pixel.
+        let samples: Vec<_> = frame.components.iter().map(|component| {
+            let columns: Vec<usize> = (0..width).map(|x| {
+                x * component.horizontal_sampling / frame.max_horizontal_sampling
+            }).collect();
+            let rows: Vec<usize> = (0..height).map(|y| {
+                y * component.vertical_sampling / frame.max_vertical_sampling
+            }).collect();
+            (columns, rows, component.padded_blocks_across * block_size)
+        }).collect();
+        let component_ids: Vec<u8> = frame.components.iter().map(|c| c.id).collect();
+        let rgb = self.adobe_rgb || component_ids == b"RGB".to_vec();
+        for y in 0..height {
+            for x in 0..width {
+                let sample = |index: usize| {
+                    let (ref columns, ref rows, stride) = samples[index];
+                    planes[index][rows[y] * stride + columns[x]]
+                };
+                if planes.len() == 1 {
+                    let luma = sample(0);
+                    pixels.push_all(&[luma, luma, luma, 0xff]);
+                } else if rgb {
+                    pixels.push_all(&[sample(0), sample(1), sample(2), 0xff]);
+                } else {
+                    let luma = sample(0) as f32;
+                    let cb = sample(1) as f32 - 128.0;
+                    let cr = sample(2) as f32 - 128.0;
+                    pixels.push_all(&[clamp(luma + 1.402 * cr),
+                                      clamp(luma - 0.344136 * cb - 0.714136 * cr),
+                                      clamp(luma + 1.772 * cb),
+                                      0xff]);
+                }
+            }
+        }
+        Ok(JpegImage {
+            width: width as u32,
+            height: height as u32,
+            pixels: pixels,
+        })
+    }
+}
+
+fn ceil_div(numerator: usize, denominator: usize) -> usize {
+    (numerator + denominator - 1) / denominator
+}
+
+fn clamp(value: f32) -> u8 {
+    if value <= 0.0 {
+        0
+    } else if value >= 255.0 {
+        255
+    } else {
+        (value + 0.5) as u8
+    }
+}
+
+fn table<'a>(table: Option<&'a HuffmanTable>) -> Result<&'a HuffmanTable, &'static str> {
+    table.ok_or("missing Huffman table")
+}
+
+/// Decodes a block of a sequential scan.
+fn decode_block(reader: &mut BitReader, coefficients: &mut [i16], prediction: &mut i32,
+                dc_table: Option<&HuffmanTable>, ac_table: Option<&HuffmanTable>)
+                -> Result<(), &'static str> {
+    let length = try!(reader.huffman(try!(table(dc_table))));
+    *prediction += reader.extended(length as u32);
+    coefficients[0] = *prediction as i16;
+
+    let ac_table = try!(table(ac_table));
+    let mut k = 1;
+    while k < 64 {
+        let symbol = try!(reader.huffman(ac_table));
+        let (run, length) = ((symbol >> 4) as usize, (symbol & 0x0f) as u32);
+        if length == 0 {
+            if run < 15 {
+                break
+            }
+            k += 16;
+            continue
+        }
+        k += run;
+        if k > 63 {
+            break
+        }
+        coefficients[ZIGZAG[k] as usize] = reader.extended(length) as i16;
+        k += 1;
+    }
+    Ok(())
+}
+
+/// Decodes the DC coefficient of a block in a progressive scan, or refines it.
+fn decode_dc(reader: &mut BitReader, coefficients: &mut [i16], prediction: &mut i32,
+             dc_table: Option<&HuffmanTable>, scan: ScanParameters)
+             -> Result<(), &'static str> {
+    if scan.approximation_high == 0 {
+        let length = try!(reader.huffman(try!(table(dc_table))));
+        *prediction += reader.extended(length as u32);
+        coefficients[0] = (*prediction << scan.approximation_low) as i16;
+    } else if reader.bit() {
+        coefficients[0] |= 1 << scan.approximation_low;
+    }
+    Ok(())
+}
+
+/// Decodes the first approximation of a band of AC coefficients in a progressive scan.
+fn decode_ac_first(reader: &mut BitReader, coefficients: &mut [i16],
+                   ac_table: Option<&HuffmanTable>, scan: ScanParameters,
+                   end_of_band_run: &mut u32)
+                   -> Result<(), &'static str> {
+    if *end_of_band_run > 0 {
+        *end_of_band_run -= 1;
+        return Ok(())
+    }
+    let ac_table = try!(table(ac_table));
+    let mut k = scan.spectral_start;
+    while k <= scan.spectral_end {
+        let symbol = try!(reader.huffman(ac_table));
+        let (run, length) = ((symbol >> 4) as u32, (symbol & 0x0f) as u32);
+        if length == 0 {
+            if run < 15 {
+                // This block and the next `end_of_band_run` have no more coefficients in
+                // the band.
+                *end_of_band_run = (1 << run) - 1 + reader.bits(run);
+                break
+            }
+            k += 16;
+            continue
+        }
+        k += run as usize;
+        if k > 63 {
+            break
+        }
+        coefficients[ZIGZAG[k] as usize] =
+            (reader.extended(length) * (1 << scan.approximation_low)) as i16;
+        k += 1;
+    }
+    Ok(())
+}
+
+/// Refines a band of AC coefficients in a progressive scan by one bit. Coefficients that are
+/// already non-zero get a correction bit, and new ones are read for some of the rest.
+fn decode_ac_refinement(reader: &mut BitReader, coefficients: &mut [i16],
+                        ac_table: Option<&HuffmanTable>, scan: ScanParameters,
+                        end_of_band_run: &mut u32)
+                        -> Result<(), &'static str> {
+    let bit = 1 << scan.approximation_low;
+    let mut k = scan.spectral_start;
+    if *end_of_band_run == 0 {
+        let ac_table = try!(table(ac_table));
+        while k <= scan.spectral_end {
+            let symbol = try!(reader.huffman(ac_table));
+            let (mut run, length) = ((symbol >> 4) as u32, (symbol & 0x0f) as u32);
+            let mut value = 0;
+            if length == 0 {
+                if run < 15 {
+                    *end_of_band_run = (1 << run) + reader.bits(run);
+                    break
+                }
+                // Otherwise this skips 16 coefficients that are still zero.
+            } else {
+                if length != 1 {
+                    return Err("bad progressive refinement")
+                }
+                value = if reader.bit() { bit } else { -bit };
+            }
+
+            while k <= scan.spectral_end {
+                let coefficient = &mut coefficients[ZIGZAG[k] as usize];
+                k += 1;
+                if *coefficient != 0 {
+                    refine(reader, coefficient, bit);
+                } else if run == 0 {
+                    *coefficient = value;
+                    break
+                } else {
+                    run -= 1;
+                }
+            }
+        }
+    }
+    if *end_of_band_run > 0 {
+        while k <= scan.spectral_end {
+            let coefficient = &mut coefficients[ZIGZAG[k] as usize];
+            if *coefficient != 0 {
+                refine(reader, coefficient, bit);
+            }
+            k += 1;
+        }
+        *end_of_band_run -= 1;
+    }
+    Ok(())
+}
+
+fn refine(reader: &mut BitReader, coefficient: &mut i16, bit: i16) {
+    if reader.bit() && *coefficient & bit == 0 {
+        *coefficient += if *coefficient > 0 { bit } else { -bit };
+    }
+}
+
+/// An inverse DCT that turns the lowest `size` by `size` frequencies of a block into `size`
+/// by `size` samples, each of which is the average of the samples it covers in the full
+/// block.
+struct IdctTable {
+    size: usize,
+    /// The weight of each frequency in each sample.
+    weights: Vec<f32>,
+}
+
+impl IdctTable {
+    fn new(size: usize) -> IdctTable {
+        let scale = 8 / size;
+        let mut weights = Vec::with_capacity(size * size);
+        for x in 0..size {
+            for u in 0..size {
+                let normalization = if u == 0 { 0.5 / 2f32.sqrt() } else { 0.5 };
+                let sum = (0..scale).fold(0.0, |sum, j| {
+                    let position = (2 * (x * scale + j) + 1) as f32;
+                    sum + (position * u as f32 * PI / 16.0).cos()
+                });
+                weights.push(normalization * sum / scale as f32);
+            }
+        }
+        IdctTable {
+            size: size,
+            weights: weights,
+        }
+    }
+
+    fn transform(&self, coefficients: &[i16], quantization_table: &[u16; 64],
+                 output: &mut [u8], stride: usize) {
+        let size = self.size;
+        let mut dequantized = [0f32; 64];
+        for v in 0..size {
+            for u in 0..size {
+                let index = v * 8 + u;
+                dequantized[index] = coefficients[index] as f32 * quantization_table[index] as f32;
+            }
+        }
+        // Transform the rows, then the columns. Rows of zeros, which most are, stay zero.
+        let mut rows = [0f32; 64];
+        let mut last_row = 0;
+        for v in 0..size {
+            if dequantized[v * 8..v * 8 + size].iter().all(|&value| value == 0.0) {
+                continue
+            }
+            last_row = v + 1;
+            for x in 0..size {
+                let weights = &self.weights[x * size..x * size + size];
+                rows[v * 8 + x] = (0..size).fold(0.0, |sum, u| {
+                    sum + weights[u] * dequantized[v * 8 + u]
+                });
+            }
+        }
+        for y in 0..size {
+            let weights = &self.weights[y * size..y * size + size];
+            for x in 0..size {
+                let value = (0..last_row).fold(0.0, |sum, v| sum + weights[v] * rows[v * 8 + x]);
+                output[y * stride + x] = clamp(value + 128.0);
+            }
+        }
+    }
+}
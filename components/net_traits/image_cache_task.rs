@@ -2,7 +2,10 @@
  * License, v. 2.0. If a copy of the MPL was not distributed with this
  * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
 
-use image::base::Image;
+use euclid::size::Size2D;
+use image::base::{AnimatedImage, Image};
+use msg::constellation_msg::PipelineId;
+use profile_traits::mem::{Reporter, ReportsChan};
 use url::Url;
 use std::sync::Arc;
 use std::sync::mpsc::{channel, Sender};
@@ -16,7 +19,7 @@ pub trait ImageResponder : Send {
 }
 
 /// The current state of an image in the cache.
-#[derive(PartialEq, Copy, Clone)]
+#[derive(PartialEq, Copy, Clone, RustcEncodable, RustcDecodable)]
 pub enum ImageState {
     Pending,
     LoadError,
@@ -47,10 +50,10 @@ pub struct ImageCacheResult {
 
 /// Commands that the image cache understands.
 pub enum ImageCacheCommand {
-    /// Request an image asynchronously from the cache. Supply a channel
-    /// to receive the result, and optionally an image responder
+    /// Request an image asynchronously from the cache on behalf of a pipeline. Supply a
+    /// channel to receive the result, and optionally an image responder
     /// that is passed to the result channel.
-    RequestImage(Url, ImageCacheChan, Option<Box<ImageResponder>>),
+    RequestImage(Url, PipelineId, ImageCacheChan, Option<Box<ImageResponder>>),
 
     /// Synchronously check the state of an image in the cache.
     /// TODO(gw): Profile this on some real world sites and see
@@ -58,11 +61,30 @@ pub enum ImageCacheCommand {
     /// layout / paint task.
     GetImageIfAvailable(Url, UsePlaceholder, Sender<Result<Arc<Image>, ImageState>>),
 
+    /// Synchronously get all the frames of an image that has loaded, if it is animated.
+    GetAnimationIfAvailable(Url, Sender<Option<Arc<AnimatedImage>>>),
+
+    /// Hurry up the load of an image that is about to be displayed, such as one that
+    /// has scrolled into view.
+    Prioritize(Url),
+
+    /// Tell the cache how big an image is drawn, in device pixels, so that a large image can
+    /// be decoded at a reduced size. If an image that was decoded at a reduced size is later
+    /// drawn larger, it is decoded again and the new image is sent to the channel.
+    SetTargetSize(Url, Size2D<u32>, PipelineId, ImageCacheChan),
+
+    /// Stop sending images to a pipeline that has exited. Images that nothing else is waiting
+    /// for aren't decoded unless they're requested again.
+    CancelRequests(PipelineId),
+
+    /// Measure the memory used by the decoded images and send the reports to the channel.
+    CollectReports(ReportsChan),
+
     /// Clients must wait for a response before shutting down the ResourceTask
     Exit(Sender<()>),
 }
 
-#[derive(Copy, Clone, PartialEq)]
+#[derive(Copy, Clone, PartialEq, RustcEncodable, RustcDecodable)]
 pub enum UsePlaceholder {
     No,
     Yes,
@@ -75,6 +97,13 @@ pub struct ImageCacheTask {
     chan: Sender<ImageCacheCommand>,
 }
 
+impl Reporter for ImageCacheTask {
+    // Just injects an appropriate event into the image cache task's queue.
+    fn collect_reports(&self, reports_chan: ReportsChan) -> bool {
+        self.chan.send(ImageCacheCommand::CollectReports(reports_chan)).is_ok()
+    }
+}
+
 /// The public API for the image cache task.
 impl ImageCacheTask {
 
@@ -88,9 +117,10 @@ impl ImageCacheTask {
     /// Asynchronously request and image. See ImageCacheCommand::RequestImage.
     pub fn request_image(&self,
                          url: Url,
+                         pipeline_id: PipelineId,
                          result_chan: ImageCacheChan,
                          responder: Option<Box<ImageResponder>>) {
-        let msg = ImageCacheCommand::RequestImage(url, result_chan, responder);
+        let msg = ImageCacheCommand::RequestImage(url, pipeline_id, result_chan, responder);
         self.chan.send(msg).unwrap();
     }
 
@@ -103,6 +133,33 @@ impl ImageCacheTask {
         receiver.recv().unwrap()
     }
 
+    /// Get the frames of an animated image. See ImageCacheCommand::GetAnimationIfAvailable.
+    pub fn get_animation_if_available(&self, url: Url) -> Option<Arc<AnimatedImage>> {
+        let (sender, receiver) = channel();
+        self.chan.send(ImageCacheCommand::GetAnimationIfAvailable(url, sender)).unwrap();
+        receiver.recv().unwrap()
+    }
+
+    /// Ask for a pending image to be loaded sooner. See ImageCacheCommand::Prioritize.
+    pub fn prioritize(&self, url: Url) {
+        self.chan.send(ImageCacheCommand::Prioritize(url)).unwrap();
+    }
+
+    /// Report how big an image is drawn. See ImageCacheCommand::SetTargetSize.
+    pub fn set_target_size(&self,
+                           url: Url,
+                           size: Size2D<u32>,
+                           pipeline_id: PipelineId,
+                           result_chan: ImageCacheChan) {
+        let msg = ImageCacheCommand::SetTargetSize(url, size, pipeline_id, result_chan);
+        self.chan.send(msg).unwrap();
+    }
+
+    /// Forget the requests of a pipeline. See ImageCacheCommand::CancelRequests.
+    pub fn cancel_requests(&self, pipeline_id: PipelineId) {
+        self.chan.send(ImageCacheCommand::CancelRequests(pipeline_id)).unwrap();
+    }
+
     /// Shutdown the image cache task.
     pub fn exit(&self) {
         let (response_chan, response_port) = channel();
@@ -8,10 +8,13 @@
 #![feature(vec_push_all)]
 
 extern crate euclid;
+extern crate flate2;
 extern crate hyper;
 #[macro_use]
 extern crate log;
 extern crate png;
+extern crate profile_traits;
+extern crate rustc_serialize;
 extern crate stb_image;
 extern crate url;
 extern crate util;
@@ -21,9 +24,13 @@ use hyper::header::{ContentType, Headers};
 use hyper::http::RawStatus;
 use hyper::method::Method;
 use hyper::mime::{Mime, Attr};
-use msg::constellation_msg::{PipelineId};
+use msg::constellation_msg::{CertificateError, ConstellationChan, PipelineId, ReferrerPolicy};
+use msg::serialize;
+use rustc_serialize::{Decodable, Decoder, Encodable, Encoder};
 use url::Url;
 
+use std::borrow::ToOwned;
+use std::io::Read;
 use std::sync::mpsc::{channel, Receiver, Sender};
 use std::thread;
 
@@ -37,6 +44,10 @@ pub mod storage_task;
 /// caching is involved) and as a result it must live in here.
 pub mod image {
     pub mod base;
+    pub mod gif;
+    pub mod icc;
+    pub mod ico;
+    pub mod jpeg;
 }
 
 #[derive(Clone)]
@@ -50,6 +61,69 @@ pub struct LoadData {
     pub data: Option<Vec<u8>>,
     pub cors: Option<ResourceCORSData>,
     pub pipeline_id: Option<PipelineId>,
+    pub priority: LoadPriority,
+    /// The URL of the document the load was started from, for the Referer header.
+    pub referrer_url: Option<Url>,
+    pub referrer_policy: Option<ReferrerPolicy>,
+    /// Whether the load is for active content in a secure document, which may not use an
+    /// insecure connection, even after a redirect.
+    pub block_mixed_content: bool,
+    /// Whether the load is of a document to display in a browsing context. A server that
+    /// can't be trusted gets an error page in place of such a document, and a network error
+    /// for anything else.
+    pub is_navigation: bool,
+}
+
+impl Encodable for LoadData {
+    fn encode<S: Encoder>(&self, s: &mut S) -> Result<(), S::Error> {
+        s.emit_struct("LoadData", 12, |s| {
+            try!(s.emit_struct_field("url", 0, |s| self.url.encode(s)));
+            try!(s.emit_struct_field("method", 1, |s| {
+                serialize::encode_method(&self.method, s)
+            }));
+            try!(s.emit_struct_field("headers", 2, |s| {
+                serialize::encode_headers(&self.headers, s)
+            }));
+            try!(s.emit_struct_field("preserved_headers", 3, |s| {
+                serialize::encode_headers(&self.preserved_headers, s)
+            }));
+            try!(s.emit_struct_field("data", 4, |s| self.data.encode(s)));
+            try!(s.emit_struct_field("cors", 5, |s| self.cors.encode(s)));
+            try!(s.emit_struct_field("pipeline_id", 6, |s| self.pipeline_id.encode(s)));
+            try!(s.emit_struct_field("priority", 7, |s| self.priority.encode(s)));
+            try!(s.emit_struct_field("referrer_url", 8, |s| self.referrer_url.encode(s)));
+            try!(s.emit_struct_field("referrer_policy", 9, |s| self.referrer_policy.encode(s)));
+            try!(s.emit_struct_field("block_mixed_content", 10, |s| {
+                self.block_mixed_content.encode(s)
+            }));
+            s.emit_struct_field("is_navigation", 11, |s| self.is_navigation.encode(s))
+        })
+    }
+}
+
+impl Decodable for LoadData {
+    fn decode<D: Decoder>(d: &mut D) -> Result<LoadData, D::Error> {
+        d.read_struct("LoadData", 12, |d| {
+            Ok(LoadData {
+                url: try!(d.read_struct_field("url", 0, Decodable::decode)),
+                method: try!(d.read_struct_field("method", 1, serialize::decode_method)),
+                headers: try!(d.read_struct_field("headers", 2, serialize::decode_headers)),
+                preserved_headers: try!(d.read_struct_field("preserved_headers", 3,
+                                                            serialize::decode_headers)),
+                data: try!(d.read_struct_field("data", 4, Decodable::decode)),
+                cors: try!(d.read_struct_field("cors", 5, Decodable::decode)),
+                pipeline_id: try!(d.read_struct_field("pipeline_id", 6, Decodable::decode)),
+                priority: try!(d.read_struct_field("priority", 7, Decodable::decode)),
+                referrer_url: try!(d.read_struct_field("referrer_url", 8, Decodable::decode)),
+                referrer_policy: try!(d.read_struct_field("referrer_policy", 9,
+                                                          Decodable::decode)),
+                block_mixed_content: try!(d.read_struct_field("block_mixed_content", 10,
+                                                              Decodable::decode)),
+                is_navigation: try!(d.read_struct_field("is_navigation", 11,
+                                                        Decodable::decode)),
+            })
+        })
+    }
 }
 
 impl LoadData {
@@ -62,10 +136,29 @@ impl LoadData {
             data: None,
             cors: None,
             pipeline_id: id,
+            priority: LoadPriority::Urgent,
+            referrer_url: None,
+            referrer_policy: None,
+            block_mixed_content: false,
+            is_navigation: false,
         }
     }
 }
 
+/// How soon a network load should start when the resource task has to choose between
+/// loads, ordered from lowest to highest.
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Debug, RustcEncodable, RustcDecodable)]
+pub enum LoadPriority {
+    /// Resources that may be needed by a later navigation.
+    Prefetch,
+    Image,
+    Script,
+    /// Stylesheets and fonts, which block rendering.
+    Style,
+    /// Loads something is blocked on right away, such as documents and XHR.
+    Urgent,
+}
+
 /// A listener for asynchronous network events. Cancelling the underlying request is unsupported.
 pub trait AsyncResponseListener {
     /// The response headers for a request have been received.
@@ -89,6 +182,62 @@ pub enum ResponseAction {
     ResponseComplete(Result<(), String>)
 }
 
+impl Encodable for ResponseAction {
+    fn encode<S: Encoder>(&self, s: &mut S) -> Result<(), S::Error> {
+        s.emit_enum("ResponseAction", |s| {
+            match *self {
+                ResponseAction::HeadersAvailable(ref metadata) => {
+                    s.emit_enum_variant("HeadersAvailable", 0, 1, |s| {
+                        s.emit_enum_variant_arg(0, |s| metadata.encode(s))
+                    })
+                }
+                ResponseAction::DataAvailable(ref data) => {
+                    s.emit_enum_variant("DataAvailable", 1, 1, |s| {
+                        s.emit_enum_variant_arg(0, |s| data.encode(s))
+                    })
+                }
+                ResponseAction::ResponseComplete(ref status) => {
+                    s.emit_enum_variant("ResponseComplete", 2, 1, |s| {
+                        s.emit_enum_variant_arg(0, |s| encode_status(status, s))
+                    })
+                }
+            }
+        })
+    }
+}
+
+impl Decodable for ResponseAction {
+    fn decode<D: Decoder>(d: &mut D) -> Result<ResponseAction, D::Error> {
+        let names = ["HeadersAvailable", "DataAvailable", "ResponseComplete"];
+        d.read_enum("ResponseAction", |d| {
+            d.read_enum_variant(&names, |d, index| {
+                match index {
+                    0 => Ok(ResponseAction::HeadersAvailable(
+                        try!(d.read_enum_variant_arg(0, Decodable::decode)))),
+                    1 => Ok(ResponseAction::DataAvailable(
+                        try!(d.read_enum_variant_arg(0, Decodable::decode)))),
+                    2 => Ok(ResponseAction::ResponseComplete(
+                        try!(d.read_enum_variant_arg(0, decode_status)))),
+                    _ => Err(d.error("invalid ResponseAction")),
+                }
+            })
+        })
+    }
+}
+
+/// Encodes the outcome of a load as the error, if any.
+fn encode_status<S: Encoder>(status: &Result<(), String>, s: &mut S) -> Result<(), S::Error> {
+    status.as_ref().err().encode(s)
+}
+
+fn decode_status<D: Decoder>(d: &mut D) -> Result<Result<(), String>, D::Error> {
+    let error: Option<String> = try!(Decodable::decode(d));
+    Ok(match error {
+        Some(error) => Err(error),
+        None => Ok(()),
+    })
+}
+
 impl ResponseAction {
     /// Execute the default action on a provided listener.
     pub fn process(self, listener: &AsyncResponseListener) {
@@ -115,6 +264,20 @@ pub enum LoadConsumer {
 /// Handle to a resource task
 pub type ResourceTask = Sender<ControlMsg>;
 
+/// The response an embedder's protocol handler gives for a URL.
+pub struct ProtocolResponse {
+    /// The status line, if not 200 OK.
+    pub status: Option<RawStatus>,
+    pub headers: Headers,
+    pub body: Box<Read + Send>,
+}
+
+/// Loads the URLs of a scheme the embedder serves itself, such as resources bundled with
+/// an application under `app:`. Each load calls the handler on a task of its own.
+pub trait ProtocolHandler: Send + Sync {
+    fn load(&self, load_data: &LoadData) -> Result<ProtocolResponse, String>;
+}
+
 pub enum ControlMsg {
     /// Request the data associated with a particular URL
     Load(LoadData, LoadConsumer),
@@ -122,6 +285,30 @@ pub enum ControlMsg {
     SetCookiesForUrl(Url, String, CookieSource),
     /// Retrieve the stored cookies for a given URL
     GetCookiesForUrl(Url, Sender<Option<String>>, CookieSource),
+    /// Queue a beacon sent by the document at the given URL. Replies with false, dropping the
+    /// beacon, if it doesn't fit in the remaining beacon quota of the document's origin.
+    SendBeacon(LoadData, Url, Sender<bool>),
+    /// Make a blob: URL minted by the given pipeline resolve to the given data and content type
+    RegisterBlobURL(Url, Vec<u8>, String, PipelineId),
+    /// Stop a blob: URL from resolving
+    RevokeBlobURL(Url),
+    /// Stop the blob: URLs minted by the given pipeline from resolving, when its document
+    /// is unloaded
+    RevokeBlobURLsForPipeline(PipelineId),
+    /// Start any queued load of the given URL at least as soon as one of the given priority
+    RaisePriority(Url, LoadPriority),
+    /// Sent by loaders when a load that held a connection to the given host is over
+    LoadFinished(String),
+    /// Sent by the resource task to itself a while after cookies change, so that a burst of
+    /// changes is written to disk once
+    SaveCookies,
+    /// Serve the URLs of the given scheme with the embedder's handler
+    RegisterProtocolHandler(String, Box<ProtocolHandler>),
+    /// Accept the certificate with the given fingerprint from the given host even though it
+    /// can't be verified
+    AllowCertificate(String, String),
+    /// Ask the user for anything loads need, such as credentials, through the constellation
+    SetConstellationChan(ConstellationChan),
     Exit
 }
 
@@ -132,6 +319,10 @@ pub struct PendingAsyncLoad {
     resource_task: ResourceTask,
     url: Url,
     pipeline: Option<PipelineId>,
+    priority: LoadPriority,
+    referrer_url: Option<Url>,
+    referrer_policy: Option<ReferrerPolicy>,
+    block_mixed_content: bool,
     guard: PendingLoadGuard,
 }
 
@@ -154,20 +345,44 @@ impl Drop for PendingLoadGuard {
 }
 
 impl PendingAsyncLoad {
-    pub fn new(resource_task: ResourceTask, url: Url, pipeline: Option<PipelineId>)
-               -> PendingAsyncLoad {
+    pub fn new(resource_task: ResourceTask, url: Url, pipeline: Option<PipelineId>,
+               priority: LoadPriority) -> PendingAsyncLoad {
         PendingAsyncLoad {
             resource_task: resource_task,
             url: url,
             pipeline: pipeline,
+            priority: priority,
+            referrer_url: None,
+            referrer_policy: None,
+            block_mixed_content: false,
             guard: PendingLoadGuard { loaded: false, },
         }
     }
 
+    /// Sets the document the load is on behalf of, and the referrer policy that applies.
+    pub fn set_referrer(&mut self, referrer_url: Url, referrer_policy: Option<ReferrerPolicy>) {
+        self.referrer_url = Some(referrer_url);
+        self.referrer_policy = referrer_policy;
+    }
+
+    /// Overrides the document's referrer policy, as a `referrerpolicy` attribute does.
+    pub fn set_referrer_policy(&mut self, referrer_policy: ReferrerPolicy) {
+        self.referrer_policy = Some(referrer_policy);
+    }
+
+    /// Keeps the load, and any redirect, from using an insecure connection.
+    pub fn block_mixed_content(&mut self) {
+        self.block_mixed_content = true;
+    }
+
     /// Initiate the network request associated with this pending load.
     pub fn load(mut self) -> Receiver<LoadResponse> {
         self.guard.neuter();
-        let load_data = LoadData::new(self.url, self.pipeline);
+        let mut load_data = LoadData::new(self.url, self.pipeline);
+        load_data.priority = self.priority;
+        load_data.referrer_url = self.referrer_url;
+        load_data.referrer_policy = self.referrer_policy;
+        load_data.block_mixed_content = self.block_mixed_content;
         let (sender, receiver) = channel();
         let consumer = LoadConsumer::Channel(sender);
         self.resource_task.send(ControlMsg::Load(load_data, consumer)).unwrap();
@@ -177,7 +392,11 @@ impl PendingAsyncLoad {
     /// Initiate the network request associated with this pending load, using the provided target.
     pub fn load_async(mut self, listener: Box<AsyncResponseTarget + Send>) {
         self.guard.neuter();
-        let load_data = LoadData::new(self.url, self.pipeline);
+        let mut load_data = LoadData::new(self.url, self.pipeline);
+        load_data.priority = self.priority;
+        load_data.referrer_url = self.referrer_url;
+        load_data.referrer_policy = self.referrer_policy;
+        load_data.block_mixed_content = self.block_mixed_content;
         let consumer = LoadConsumer::Listener(listener);
         self.resource_task.send(ControlMsg::Load(load_data, consumer)).unwrap();
     }
@@ -195,7 +414,7 @@ pub struct LoadResponse {
     pub progress_port: Receiver<ProgressMsg>,
 }
 
-#[derive(Clone)]
+#[derive(Clone, RustcEncodable, RustcDecodable)]
 pub struct ResourceCORSData {
     /// CORS Preflight flag
     pub preflight: bool,
@@ -220,6 +439,47 @@ pub struct Metadata {
 
     /// HTTP Status
     pub status: Option<RawStatus>,
+
+    /// Why the resource was replaced by an error page, if its server couldn't be trusted.
+    pub certificate_error: Option<CertificateError>,
+}
+
+impl Encodable for Metadata {
+    fn encode<S: Encoder>(&self, s: &mut S) -> Result<(), S::Error> {
+        s.emit_struct("Metadata", 6, |s| {
+            try!(s.emit_struct_field("final_url", 0, |s| self.final_url.encode(s)));
+            try!(s.emit_struct_field("content_type", 1, |s| {
+                serialize::encode_optional_content_type(&self.content_type, s)
+            }));
+            try!(s.emit_struct_field("charset", 2, |s| self.charset.encode(s)));
+            try!(s.emit_struct_field("headers", 3, |s| {
+                serialize::encode_optional_headers(&self.headers, s)
+            }));
+            try!(s.emit_struct_field("status", 4, |s| {
+                serialize::encode_optional_status(&self.status, s)
+            }));
+            s.emit_struct_field("certificate_error", 5, |s| self.certificate_error.encode(s))
+        })
+    }
+}
+
+impl Decodable for Metadata {
+    fn decode<D: Decoder>(d: &mut D) -> Result<Metadata, D::Error> {
+        d.read_struct("Metadata", 6, |d| {
+            Ok(Metadata {
+                final_url: try!(d.read_struct_field("final_url", 0, Decodable::decode)),
+                content_type: try!(d.read_struct_field("content_type", 1,
+                                                       serialize::decode_optional_content_type)),
+                charset: try!(d.read_struct_field("charset", 2, Decodable::decode)),
+                headers: try!(d.read_struct_field("headers", 3,
+                                                  serialize::decode_optional_headers)),
+                status: try!(d.read_struct_field("status", 4,
+                                                 serialize::decode_optional_status)),
+                certificate_error: try!(d.read_struct_field("certificate_error", 5,
+                                                            Decodable::decode)),
+            })
+        })
+    }
 }
 
 impl Metadata {
@@ -232,6 +492,7 @@ impl Metadata {
             headers: None,
             // https://fetch.spec.whatwg.org/#concept-response-status-message
             status: Some(RawStatus(200, "OK".into())),
+            certificate_error: None,
         }
     }
 
@@ -253,7 +514,7 @@ impl Metadata {
 }
 
 /// The creator of a given cookie
-#[derive(PartialEq, Copy, Clone)]
+#[derive(PartialEq, Copy, Clone, RustcEncodable, RustcDecodable)]
 pub enum CookieSource {
     /// An HTTP API
     HTTP,
@@ -270,11 +531,74 @@ pub enum ProgressMsg {
     Done(Result<(), String>)
 }
 
+impl Encodable for ProgressMsg {
+    fn encode<S: Encoder>(&self, s: &mut S) -> Result<(), S::Error> {
+        s.emit_enum("ProgressMsg", |s| {
+            match *self {
+                ProgressMsg::Payload(ref data) => {
+                    s.emit_enum_variant("Payload", 0, 1, |s| {
+                        s.emit_enum_variant_arg(0, |s| data.encode(s))
+                    })
+                }
+                ProgressMsg::Done(ref status) => {
+                    s.emit_enum_variant("Done", 1, 1, |s| {
+                        s.emit_enum_variant_arg(0, |s| encode_status(status, s))
+                    })
+                }
+            }
+        })
+    }
+}
+
+impl Decodable for ProgressMsg {
+    fn decode<D: Decoder>(d: &mut D) -> Result<ProgressMsg, D::Error> {
+        d.read_enum("ProgressMsg", |d| {
+            d.read_enum_variant(&["Payload", "Done"], |d, index| {
+                match index {
+                    0 => Ok(ProgressMsg::Payload(
+                        try!(d.read_enum_variant_arg(0, Decodable::decode)))),
+                    1 => Ok(ProgressMsg::Done(try!(d.read_enum_variant_arg(0, decode_status)))),
+                    _ => Err(d.error("invalid ProgressMsg")),
+                }
+            })
+        })
+    }
+}
+
+/// The number of bytes of beacon data an origin may have in flight at once.
+/// https://fetch.spec.whatwg.org/#http-network-or-cache-fetch (keepalive)
+pub const BEACON_QUOTA: usize = 64 * 1024;
+
+/// The ASCII serialization of `url`'s origin, or "null" if its origin is opaque, as it is for
+/// `file:` and `data:` URLs.
+/// https://html.spec.whatwg.org/multipage/#ascii-serialisation-of-an-origin
+pub fn serialize_origin(url: &Url) -> String {
+    match (&*url.scheme, url.host()) {
+        ("http", Some(host)) | ("https", Some(host)) |
+        ("ws", Some(host)) | ("wss", Some(host)) | ("ftp", Some(host)) => {
+            match url.port() {
+                Some(port) => format!("{}://{}:{}", url.scheme, host.serialize(), port),
+                None => format!("{}://{}", url.scheme, host.serialize()),
+            }
+        }
+        _ => "null".to_owned(),
+    }
+}
+
 /// Convenience function for synchronously loading a whole resource.
 pub fn load_whole_resource(resource_task: &ResourceTask, url: Url)
         -> Result<(Metadata, Vec<u8>), String> {
+    load_whole_resource_with_priority(resource_task, url, LoadPriority::Urgent)
+}
+
+/// Like `load_whole_resource`, for a load that may wait for more important ones.
+pub fn load_whole_resource_with_priority(resource_task: &ResourceTask, url: Url,
+                                         priority: LoadPriority)
+        -> Result<(Metadata, Vec<u8>), String> {
+    let mut load_data = LoadData::new(url, None);
+    load_data.priority = priority;
     let (start_chan, start_port) = channel();
-    resource_task.send(ControlMsg::Load(LoadData::new(url, None), LoadConsumer::Channel(start_chan))).unwrap();
+    resource_task.send(ControlMsg::Load(load_data, LoadConsumer::Channel(start_chan))).unwrap();
     let response = start_port.recv().unwrap();
 
     let mut buf = vec!();
@@ -14,6 +14,7 @@ extern crate libc;
 extern crate profile_traits;
 #[cfg(target_os="linux")]
 extern crate regex;
+extern crate rustc_serialize;
 #[cfg(target_os="macos")]
 extern crate task_info;
 extern crate time as std_time;
@@ -4,11 +4,13 @@
 
 //! Memory profiling functions.
 
-use profile_traits::mem::{ProfilerChan, ProfilerMsg, Reporter, ReportsChan};
+use profile_traits::mem::{ProfilerChan, ProfilerMsg, ReportUnits, Reporter, ReportsChan};
+use profile_traits::mem::ReportsSummary;
+use rustc_serialize::json::{Json, ToJson};
 use self::system_reporter::SystemReporter;
 use std::borrow::ToOwned;
 use std::cmp::Ordering;
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
 use std::thread::sleep_ms;
 use std::sync::mpsc::{channel, Receiver};
 use util::task::spawn_named;
@@ -19,10 +21,13 @@ pub struct Profiler {
 
     /// Registered memory reporters.
     reporters: HashMap<String, Box<Reporter + Send>>,
+
+    /// Whether to print the reports as JSON rather than as text.
+    json: bool,
 }
 
 impl Profiler {
-    pub fn create(period: Option<f64>) -> ProfilerChan {
+    pub fn create(period: Option<f64>, json: bool) -> ProfilerChan {
         let (chan, port) = channel();
 
         // Create the timer thread if a period was provided.
@@ -42,7 +47,7 @@ impl Profiler {
         // Always spawn the memory profiler. If there is no timer thread it won't receive regular
         // `Print` events, but it will still receive the other events.
         spawn_named("Memory profiler".to_owned(), move || {
-            let mut mem_profiler = Profiler::new(port);
+            let mut mem_profiler = Profiler::new(port, json);
             mem_profiler.start();
         });
 
@@ -57,10 +62,11 @@ impl Profiler {
         mem_profiler_chan
     }
 
-    pub fn new(port: Receiver<ProfilerMsg>) -> Profiler {
+    pub fn new(port: Receiver<ProfilerMsg>, json: bool) -> Profiler {
         Profiler {
             port: port,
             reporters: HashMap::new(),
+            json: json,
         }
     }
 
@@ -103,14 +109,39 @@ impl Profiler {
                 true
             },
 
+            ProfilerMsg::Summarize(chan) => {
+                let mut forest = self.collect_reports();
+                let summaries = forest.sorted_trees().iter().map(|tree| tree.summarize()).collect();
+                let _ = chan.send(summaries);
+                true
+            },
+
+            ProfilerMsg::DumpJson(chan) => {
+                let _ = chan.send(self.collect_reports().to_json().to_string());
+                true
+            },
+
             ProfilerMsg::Exit => false
         }
     }
 
     fn handle_print_msg(&self) {
+        if self.json {
+            println!("{}", self.collect_reports().to_json());
+            return
+        }
+
         println!("Begin memory reports");
         println!("|");
 
+        self.collect_reports().print();
+
+        println!("|");
+        println!("End memory reports");
+        println!("");
+    }
+
+    fn collect_reports(&self) -> ReportsForest {
         // Collect reports from memory reporters.
         //
         // This serializes the report-gathering. It might be worth creating a new scoped thread for
@@ -123,16 +154,12 @@ impl Profiler {
             if reporter.collect_reports(ReportsChan(chan)) {
                 if let Ok(reports) = port.recv() {
                     for report in reports.iter() {
-                        forest.insert(&report.path, report.size);
+                        forest.insert(&report.path, report.units, report.size);
                     }
                 }
             }
         }
-        forest.print();
-
-        println!("|");
-        println!("End memory reports");
-        println!("");
+        forest
     }
 }
 
@@ -147,6 +174,9 @@ struct ReportsTree {
     /// For interor nodes, this is always zero.
     count: u32,
 
+    /// What the sizes in this tree measure.
+    units: ReportUnits,
+
     /// The segment from the report path that maps to this node.
     path_seg: String,
 
@@ -155,10 +185,11 @@ struct ReportsTree {
 }
 
 impl ReportsTree {
-    fn new(path_seg: String) -> ReportsTree {
+    fn new(path_seg: String, units: ReportUnits) -> ReportsTree {
         ReportsTree {
             size: 0,
             count: 0,
+            units: units,
             path_seg: path_seg,
             children: vec![]
         }
@@ -182,7 +213,7 @@ impl ReportsTree {
             let i = match t.find_child(&path_seg) {
                 Some(i) => i,
                 None => {
-                    let new_t = ReportsTree::new(path_seg.clone());
+                    let new_t = ReportsTree::new(path_seg.clone(), t.units);
                     t.children.push(new_t);
                     t.children.len() - 1
                 },
@@ -225,13 +256,46 @@ impl ReportsTree {
 
         let mebi = 1024f64 * 1024f64;
         let count_str = if self.count > 1 { format!(" [{}]", self.count) } else { "".to_owned() };
-        println!("|{}{:8.2} MiB -- {}{}",
-                 indent_str, (self.size as f64) / mebi, self.path_seg, count_str);
+        match self.units {
+            ReportUnits::Bytes => {
+                println!("|{}{:8.2} MiB -- {}{}",
+                         indent_str, (self.size as f64) / mebi, self.path_seg, count_str);
+            }
+            ReportUnits::Count => {
+                println!("|{}{:12} -- {}{}", indent_str, self.size, self.path_seg, count_str);
+            }
+        }
 
         for child in self.children.iter() {
             child.print(depth + 1);
         }
     }
+
+    fn to_json(&self) -> Json {
+        let mut object = BTreeMap::new();
+        object.insert("path".to_owned(), self.path_seg.to_json());
+        object.insert("size".to_owned(), self.size.to_json());
+        if self.units == ReportUnits::Count {
+            object.insert("units".to_owned(), "count".to_json());
+        }
+        if self.children.is_empty() {
+            object.insert("count".to_owned(), self.count.to_json());
+        } else {
+            let children = self.children.iter().map(|child| child.to_json()).collect::<Vec<_>>();
+            object.insert("children".to_owned(), Json::Array(children));
+        }
+        Json::Object(object)
+    }
+
+    fn summarize(&self) -> ReportsSummary {
+        ReportsSummary {
+            path_seg: self.path_seg.clone(),
+            units: self.units,
+            size: self.size,
+            count: self.count,
+            children: self.children.iter().map(|child| child.summarize()).collect(),
+        }
+    }
 }
 
 /// A collection of ReportsTrees. It represents the data from multiple memory reports in a form
@@ -248,18 +312,23 @@ impl ReportsForest {
     }
 
     // Insert the path and size into the forest, adding any trees and nodes as necessary.
-    fn insert(&mut self, path: &[String], size: usize) {
+    fn insert(&mut self, path: &[String], units: ReportUnits, size: usize) {
         // Get the right tree, creating it if necessary.
         if !self.trees.contains_key(&path[0]) {
-            self.trees.insert(path[0].clone(), ReportsTree::new(path[0].clone()));
+            self.trees.insert(path[0].clone(), ReportsTree::new(path[0].clone(), units));
         }
         let t = self.trees.get_mut(&path[0]).unwrap();
+        if t.units != units {
+            // Sizes in different units can't be added up.
+            panic!("reports in the '{}' tree measure different things", path[0]);
+        }
 
         // Use tail() because the 0th path segment was used to find the right tree in the forest.
         t.insert(path.tail(), size);
     }
 
-    fn print(&mut self) {
+    // Fill in the sizes of interior nodes, and return the trees in the order they are printed.
+    fn sorted_trees(&mut self) -> Vec<&ReportsTree> {
         // Fill in sizes of interior nodes, and recursively sort the sub-trees.
         for (_, tree) in self.trees.iter_mut() {
             tree.compute_interior_node_sizes_and_sort();
@@ -281,9 +350,17 @@ impl ReportsForest {
                 a.path_seg.cmp(&b.path_seg)
             }
         });
+        v
+    }
+
+    // Write the forest as a JSON array of trees, in the order they are printed.
+    fn to_json(&mut self) -> Json {
+        Json::Array(self.sorted_trees().iter().map(|tree| tree.to_json()).collect())
+    }
 
+    fn print(&mut self) {
         // Print the forest.
-        for tree in v.iter() {
+        for tree in self.sorted_trees().iter() {
             tree.print(0);
             // Print a blank line after non-degenerate trees.
             if !tree.children.is_empty() {
@@ -297,7 +374,7 @@ impl ReportsForest {
 
 mod system_reporter {
     use libc::{c_char, c_int, c_void, size_t};
-    use profile_traits::mem::{Report, Reporter, ReportsChan};
+    use profile_traits::mem::{Report, ReportUnits, Reporter, ReportsChan};
     use std::borrow::ToOwned;
     use std::ffi::CString;
     use std::mem::size_of;
@@ -314,7 +391,11 @@ mod system_reporter {
             {
                 let mut report = |path, size| {
                     if let Some(size) = size {
-                        reports.push(Report { path: path, size: size });
+                        reports.push(Report {
+                            path: path,
+                            units: ReportUnits::Bytes,
+                            size: size,
+                        });
                     }
                 };
 
@@ -4,12 +4,18 @@
 
 //! Timing functions.
 
-use profile_traits::time::{ProfilerCategory, ProfilerChan, ProfilerMsg, TimerMetadata};
+use libc;
+use profile_traits::time::{ProfilerCategory, ProfilerChan, ProfilerMsg, TimedEvent};
+use profile_traits::time::TimerMetadata;
+use rustc_serialize::json::{Json, ToJson};
 use std::borrow::ToOwned;
 use std::cmp::Ordering;
-use std::collections::BTreeMap;
+use std::collections::{BTreeMap, HashMap};
 use std::f64;
-use std::sync::mpsc::{channel, Receiver};
+use std::fs::File;
+use std::io::{self, Write};
+use std::sync::Arc;
+use std::sync::mpsc::{channel, Receiver, Sender};
 use std::thread::sleep_ms;
 use std_time::precise_time_ns;
 use util::task::spawn_named;
@@ -59,26 +65,29 @@ impl Formattable for ProfilerCategory {
             ProfilerCategory::LayoutTreeBuilder => "| + ",
             _ => ""
         };
-        let name = match *self {
-            ProfilerCategory::Compositing => "Compositing",
-            ProfilerCategory::LayoutPerform => "Layout",
-            ProfilerCategory::LayoutStyleRecalc => "Style Recalc",
-            ProfilerCategory::LayoutRestyleDamagePropagation => "Restyle Damage Propagation",
-            ProfilerCategory::LayoutNonIncrementalReset => "Non-incremental reset (temporary)",
-            ProfilerCategory::LayoutSelectorMatch => "Selector Matching",
-            ProfilerCategory::LayoutTreeBuilder => "Tree Building",
-            ProfilerCategory::LayoutDamagePropagate => "Damage Propagation",
-            ProfilerCategory::LayoutGeneratedContent => "Generated Content Resolution",
-            ProfilerCategory::LayoutMain => "Primary Layout Pass",
-            ProfilerCategory::LayoutParallelWarmup => "Parallel Warmup",
-            ProfilerCategory::LayoutShaping => "Shaping",
-            ProfilerCategory::LayoutDispListBuild => "Display List Construction",
-            ProfilerCategory::PaintingPerTile => "Painting Per Tile",
-            ProfilerCategory::PaintingPrepBuff => "Buffer Prep",
-            ProfilerCategory::Painting => "Painting",
-            ProfilerCategory::ImageDecoding => "Image Decoding",
-        };
-        format!("{}{}", padding, name)
+        format!("{}{}", padding, category_name(self))
+    }
+}
+
+fn category_name(category: &ProfilerCategory) -> &'static str {
+    match *category {
+        ProfilerCategory::Compositing => "Compositing",
+        ProfilerCategory::LayoutPerform => "Layout",
+        ProfilerCategory::LayoutStyleRecalc => "Style Recalc",
+        ProfilerCategory::LayoutRestyleDamagePropagation => "Restyle Damage Propagation",
+        ProfilerCategory::LayoutNonIncrementalReset => "Non-incremental reset (temporary)",
+        ProfilerCategory::LayoutSelectorMatch => "Selector Matching",
+        ProfilerCategory::LayoutTreeBuilder => "Tree Building",
+        ProfilerCategory::LayoutDamagePropagate => "Damage Propagation",
+        ProfilerCategory::LayoutGeneratedContent => "Generated Content Resolution",
+        ProfilerCategory::LayoutMain => "Primary Layout Pass",
+        ProfilerCategory::LayoutParallelWarmup => "Parallel Warmup",
+        ProfilerCategory::LayoutShaping => "Shaping",
+        ProfilerCategory::LayoutDispListBuild => "Display List Construction",
+        ProfilerCategory::PaintingPerTile => "Painting Per Tile",
+        ProfilerCategory::PaintingPrepBuff => "Buffer Prep",
+        ProfilerCategory::Painting => "Painting",
+        ProfilerCategory::ImageDecoding => "Image Decoding",
     }
 }
 
@@ -89,13 +98,44 @@ pub struct Profiler {
     pub port: Receiver<ProfilerMsg>,
     buckets: ProfilerBuckets,
     pub last_msg: Option<ProfilerMsg>,
+    /// The channels that every event is passed on to.
+    watchers: Vec<Sender<TimedEvent>>,
 }
 
 impl Profiler {
-    pub fn create(period: Option<f64>) -> ProfilerChan {
+    pub fn create(period: Option<f64>, trace_path: Option<String>) -> ProfilerChan {
         let (chan, port) = channel();
-        match period {
-            Some(period) => {
+        match (period, trace_path) {
+            (_, Some(trace_path)) => {
+                // Write every event to the trace file instead of printing tables.
+                spawn_named("Time profiler".to_owned(), move || {
+                    let mut trace = match TraceWriter::new(&trace_path) {
+                        Ok(trace) => Some(trace),
+                        Err(err) => {
+                            error!("couldn't create the trace file {}: {}", trace_path, err);
+                            None
+                        }
+                    };
+                    let mut watchers = vec!();
+                    loop {
+                        match port.recv() {
+                            Err(_) | Ok(ProfilerMsg::Exit) => break,
+                            Ok(ProfilerMsg::Watch(watcher)) => watchers.push(watcher),
+                            Ok(ProfilerMsg::Time(k, times, thread)) => {
+                                if let Some(ref mut trace) = trace {
+                                    trace.write_event(&k, times, &thread);
+                                }
+                                notify_watchers(&mut watchers, k, times, thread)
+                            }
+                            _ => {}
+                        }
+                    }
+                    if let Some(trace) = trace {
+                        trace.finish();
+                    }
+                });
+            }
+            (Some(period), None) => {
                 let period = (period * 1000.) as u32;
                 let chan = chan.clone();
                 spawn_named("Time profiler timer".to_owned(), move || {
@@ -112,12 +152,17 @@ impl Profiler {
                     profiler.start();
                 });
             }
-            None => {
-                // No-op to handle messages when the time profiler is inactive.
+            (None, None) => {
+                // Only pass events on to watchers when the time profiler is inactive.
                 spawn_named("Time profiler".to_owned(), move || {
+                    let mut watchers = vec!();
                     loop {
                         match port.recv() {
                             Err(_) | Ok(ProfilerMsg::Exit) => break,
+                            Ok(ProfilerMsg::Watch(watcher)) => watchers.push(watcher),
+                            Ok(ProfilerMsg::Time(k, times, thread)) => {
+                                notify_watchers(&mut watchers, k, times, thread)
+                            }
                             _ => {}
                         }
                     }
@@ -133,6 +178,7 @@ impl Profiler {
             port: port,
             buckets: BTreeMap::new(),
             last_msg: None,
+            watchers: vec!(),
         }
     }
 
@@ -161,7 +207,14 @@ impl Profiler {
 
     fn handle_msg(&mut self, msg: ProfilerMsg) -> bool {
         match msg.clone() {
-            ProfilerMsg::Time(k, t) => self.find_or_insert(k, t),
+            ProfilerMsg::Time(k, (start, end), thread) => {
+                self.find_or_insert(k.clone(), (end - start) as f64 / 1000000f64);
+                notify_watchers(&mut self.watchers, k, (start, end), thread);
+            }
+            ProfilerMsg::Watch(watcher) => {
+                self.watchers.push(watcher);
+                return true
+            }
             ProfilerMsg::Print => match self.last_msg {
                 // only print if more data has arrived since the last printout
                 Some(ProfilerMsg::Time(..)) => self.print_buckets(),
@@ -201,6 +254,106 @@ impl Profiler {
     }
 }
 
+/// Sends an event to every watcher, forgetting those that have stopped listening.
+fn notify_watchers(watchers: &mut Vec<Sender<TimedEvent>>,
+                   (category, metadata): (ProfilerCategory, Option<TimerMetadata>),
+                   (start, end): (u64, u64),
+                   thread: Arc<String>) {
+    watchers.retain(|watcher| {
+        watcher.send(TimedEvent {
+            category: category.clone(),
+            metadata: metadata.clone(),
+            start: start,
+            end: end,
+            thread: thread.clone(),
+        }).is_ok()
+    });
+}
+
+/// Writes events in the Trace Event Format that chrome://tracing loads: a JSON array with one
+/// event per line. Events are written as "complete" events, which nest like begin/end pairs but
+/// don't have to be written in order, since an event is only reported once it has ended. The
+/// array is closed when the profiler exits, but the trace still loads if Servo doesn't exit
+/// cleanly.
+struct TraceWriter {
+    file: File,
+    pid: i32,
+    /// The ids given to the threads seen so far, by name.
+    thread_ids: HashMap<String, usize>,
+    /// Whether any events have been written yet.
+    written: bool,
+}
+
+impl TraceWriter {
+    fn new(path: &str) -> io::Result<TraceWriter> {
+        let mut file = try!(File::create(path));
+        try!(file.write_all(b"["));
+        Ok(TraceWriter {
+            file: file,
+            pid: unsafe { libc::getpid() },
+            thread_ids: HashMap::new(),
+            written: false,
+        })
+    }
+
+    fn write_event(&mut self,
+                   &(ref category, ref metadata): &(ProfilerCategory, Option<TimerMetadata>),
+                   (start, end): (u64, u64),
+                   thread: &str) {
+        let tid = self.thread_id(thread);
+        let mut args = BTreeMap::new();
+        if let Some(ref metadata) = *metadata {
+            args.insert("url".to_owned(), metadata.url.to_json());
+            args.insert("iframe".to_owned(), metadata.iframe.to_json());
+            args.insert("incremental".to_owned(), metadata.incremental.to_json());
+        }
+        let mut event = trace_event(category_name(category), "X", self.pid, tid, args);
+        if let Json::Object(ref mut event) = event {
+            event.insert("cat".to_owned(), "servo".to_json());
+            // Timestamps are in microseconds.
+            event.insert("ts".to_owned(), (start as f64 / 1000.).to_json());
+            event.insert("dur".to_owned(), ((end - start) as f64 / 1000.).to_json());
+        }
+        self.write_json(event);
+    }
+
+    /// Returns the id of the named thread, naming it in the trace the first time it's seen.
+    fn thread_id(&mut self, thread: &str) -> usize {
+        if let Some(&tid) = self.thread_ids.get(thread) {
+            return tid
+        }
+
+        let tid = self.thread_ids.len() + 1;
+        self.thread_ids.insert(thread.to_owned(), tid);
+        let mut args = BTreeMap::new();
+        args.insert("name".to_owned(), thread.to_json());
+        let event = trace_event("thread_name", "M", self.pid, tid, args);
+        self.write_json(event);
+        tid
+    }
+
+    fn write_json(&mut self, event: Json) {
+        let separator = if self.written { ",\n" } else { "\n" };
+        self.written = true;
+        let _ = self.file.write_all(format!("{}{}", separator, event).as_bytes());
+    }
+
+    fn finish(mut self) {
+        let _ = self.file.write_all(b"\n]\n");
+    }
+}
+
+fn trace_event(name: &str, phase: &str, pid: i32, tid: usize, args: BTreeMap<String, Json>)
+               -> Json {
+    let mut event = BTreeMap::new();
+    event.insert("name".to_owned(), name.to_json());
+    event.insert("ph".to_owned(), phase.to_json());
+    event.insert("pid".to_owned(), pid.to_json());
+    event.insert("tid".to_owned(), tid.to_json());
+    event.insert("args".to_owned(), Json::Object(args));
+    Json::Object(event)
+}
+
 pub fn time<T, F>(msg: &str, callback: F) -> T
     where F: Fn() -> T
 {
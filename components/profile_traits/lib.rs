@@ -6,5 +6,7 @@
 //! rest of Servo. These APIs are here instead of in `profile` so that these
 //! modules won't have to depend on `profile`.
 
+extern crate rustc_serialize;
+
 pub mod mem;
 pub mod time;
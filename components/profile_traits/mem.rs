@@ -23,15 +23,47 @@ impl ProfilerChan {
     }
 }
 
+/// What the size of a report measures.
+#[derive(Clone, Copy, PartialEq, Debug, RustcEncodable, RustcDecodable)]
+pub enum ReportUnits {
+    /// An amount of memory, in bytes.
+    Bytes,
+
+    /// A number of things or events that aren't memory, such as cache hits.
+    Count,
+}
+
 /// A single memory-related measurement.
+#[derive(RustcEncodable, RustcDecodable)]
 pub struct Report {
     /// The identifying path for this report.
     pub path: Vec<String>,
 
-    /// The size, in bytes.
+    /// What `size` measures. Every report in a tree must measure the same thing.
+    pub units: ReportUnits,
+
+    /// The size, in bytes unless `units` says otherwise.
     pub size: usize,
 }
 
+/// The measurements whose paths start with a common sequence of segments, arranged as a tree.
+pub struct ReportsSummary {
+    /// The last of the common path segments.
+    pub path_seg: String,
+
+    /// What the sizes in this tree measure.
+    pub units: ReportUnits,
+
+    /// The total size of the reports in this tree, in bytes unless `units` says otherwise.
+    pub size: usize,
+
+    /// For leaf nodes, the number of reports with this path. Always zero for interior nodes.
+    pub count: u32,
+
+    /// The subtrees, largest first.
+    pub children: Vec<ReportsSummary>,
+}
+
 /// A channel through which memory reports can be sent.
 #[derive(Clone)]
 pub struct ReportsChan(pub Sender<Vec<Report>>);
@@ -79,6 +111,14 @@ pub enum ProfilerMsg {
     /// Triggers printing of the memory profiling metrics.
     Print,
 
+    /// Collects the memory profiling metrics and sends them, arranged by path, on the given
+    /// channel, in the order they would be printed.
+    Summarize(Sender<Vec<ReportsSummary>>),
+
+    /// Collects the memory profiling metrics and sends them on the given channel as a JSON
+    /// document, for tools that process memory reports.
+    DumpJson(Sender<String>),
+
     /// Tells the memory profiler to shut down.
     Exit,
 }
@@ -7,9 +7,12 @@ extern crate url;
 
 use self::std_time::precise_time_ns;
 use self::url::Url;
+use std::borrow::ToOwned;
+use std::sync::Arc;
 use std::sync::mpsc::Sender;
+use std::thread;
 
-#[derive(PartialEq, Clone, PartialOrd, Eq, Ord)]
+#[derive(PartialEq, Clone, PartialOrd, Eq, Ord, RustcEncodable, RustcDecodable)]
 pub struct TimerMetadata {
     pub url:         String,
     pub iframe:      bool,
@@ -28,16 +31,38 @@ impl ProfilerChan {
 
 #[derive(Clone)]
 pub enum ProfilerMsg {
-    /// Normal message used for reporting time
-    Time((ProfilerCategory, Option<TimerMetadata>), f64),
+    /// Normal message used for reporting time. The times are the start and the end of the
+    /// event, in nanoseconds as returned by `precise_time_ns`, and the string is the name of the
+    /// thread the event happened on.
+    Time((ProfilerCategory, Option<TimerMetadata>), (u64, u64), Arc<String>),
+    /// Asks for every event reported from now on to be sent on the given channel too, until the
+    /// other end of the channel is dropped. This works whether or not the profiler is printing.
+    Watch(Sender<TimedEvent>),
     /// Message used to force print the profiling metrics
     Print,
     /// Tells the profiler to shut down.
     Exit,
 }
 
+/// An event reported to the profiler, as sent to the channels watching it.
+#[derive(Clone)]
+pub struct TimedEvent {
+    pub category: ProfilerCategory,
+    pub metadata: Option<TimerMetadata>,
+    /// The start of the event, in nanoseconds as returned by `precise_time_ns`.
+    pub start: u64,
+    /// The end of the event, in nanoseconds as returned by `precise_time_ns`.
+    pub end: u64,
+    /// The name of the thread the event happened on.
+    pub thread: Arc<String>,
+}
+
+// The name of the current thread, shared by all the events reported from it.
+thread_local!(static THREAD_NAME: Arc<String> =
+    Arc::new(thread::current().name().unwrap_or("<unnamed>").to_owned()));
+
 #[repr(u32)]
-#[derive(PartialEq, Clone, PartialOrd, Eq, Ord)]
+#[derive(PartialEq, Clone, PartialOrd, Eq, Ord, RustcEncodable, RustcDecodable)]
 pub enum ProfilerCategory {
     Compositing,
     LayoutPerform,
@@ -83,13 +108,13 @@ pub fn profile<T, F>(category: ProfilerCategory,
     let start_time = precise_time_ns();
     let val = callback();
     let end_time = precise_time_ns();
-    let ms = (end_time - start_time) as f64 / 1000000f64;
     let meta = meta.map(|(url, iframe, reflow_type)|
         TimerMetadata {
             url: url.serialize(),
             iframe: iframe == TimerMetadataFrameType::IFrame,
             incremental: reflow_type == TimerMetadataReflowType::Incremental,
         });
-    profiler_chan.send(ProfilerMsg::Time((category, meta), ms));
+    let thread = THREAD_NAME.with(|name| name.clone());
+    profiler_chan.send(ProfilerMsg::Time((category, meta), (start_time, end_time), thread));
     return val;
 }
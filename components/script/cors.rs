@@ -16,7 +16,10 @@ use net_traits::{AsyncResponseTarget, AsyncResponseListener, ResponseAction, Met
 use std::ascii::AsciiExt;
 use std::borrow::ToOwned;
 use std::cell::RefCell;
-use std::sync::{Arc, Mutex};
+use std::cmp;
+use std::mem;
+use std::str;
+use std::sync::{Arc, Mutex, Once, ONCE_INIT};
 use time;
 use time::{now, Timespec};
 
@@ -76,7 +79,7 @@ impl CORSRequest {
             "http" | "https" => {
                 let mut req = CORSRequest::new(referer, destination, mode, method, headers);
                 req.preflight_flag = !is_simple_method(&req.method) || mode == RequestMode::ForcedPreflight;
-                if req.headers.iter().all(|h| is_simple_header(&h)) {
+                if !req.headers.iter().all(|h| is_simple_header(&h)) {
                     req.preflight_flag = true;
                 }
                 Ok(Some(req))
@@ -159,11 +162,16 @@ impl CORSRequest {
         // Step 3
         // Substep 1: Service workers (unimplemented )
         // Substep 2
-        let cache = &mut CORSCache(vec!()); // XXXManishearth Should come from user agent
-        if self.preflight_flag &&
-           !cache.match_method(self, &self.method) &&
-           !self.headers.iter().all(|h| is_simple_header(&h) && cache.match_header(self, h.name())) {
-            if !is_simple_method(&self.method) || self.mode == RequestMode::ForcedPreflight {
+        if self.preflight_flag {
+            let needs_preflight = {
+                let mut cache = cors_cache().lock().unwrap();
+                let method_miss = !cache.match_method(self, &self.method) &&
+                    (!is_simple_method(&self.method) || self.mode == RequestMode::ForcedPreflight);
+                method_miss || self.headers.iter().any(|h| {
+                    !is_simple_header(&h) && !cache.match_header(self, h.name())
+                })
+            };
+            if needs_preflight {
                 return self.preflight_fetch();
                 // Everything after this is part of XHR::fetch()
                 // Expect the organization of code to improve once we have a fetch crate
@@ -252,11 +260,10 @@ impl CORSRequest {
             Some(&AccessControlMaxAge(num)) => num,
             None => 0
         };
-        // Substep 9: Impose restrictions on max-age, if any (unimplemented)
-        // Substeps 10-12: Add a cache (partially implemented, XXXManishearth)
-        // This cache should come from the user agent, creating a new one here to check
-        // for compile time errors
-        let cache = &mut CORSCache(vec!());
+        // Substep 9
+        let max_age = cmp::min(max_age, MAX_PREFLIGHT_AGE);
+        // Substeps 10-12
+        let mut cache = cors_cache().lock().unwrap();
         for m in methods.iter() {
             let cache_match = cache.match_method_and_update(self, m, max_age);
             if !cache_match {
@@ -264,11 +271,11 @@ impl CORSRequest {
                                                  max_age, false, HeaderOrMethod::MethodData(m.clone())));
             }
         }
-        for h in response.headers.iter() {
-            let cache_match = cache.match_header_and_update(self, h.name(), max_age);
+        for h in headers.iter() {
+            let cache_match = cache.match_header_and_update(self, h, max_age);
             if !cache_match {
                 cache.insert(CORSCacheEntry::new(self.origin.clone(), self.destination.clone(),
-                                                 max_age, false, HeaderOrMethod::HeaderData(h.to_string())));
+                                                 max_age, false, HeaderOrMethod::HeaderData((**h).clone())));
             }
         }
         cors_response
@@ -299,10 +306,33 @@ impl CORSResponse {
 
 // CORS Cache stuff
 
-/// A CORS cache object. Anchor it somewhere to the user agent.
+/// The longest time, in seconds, a preflight result is cached for, whatever the server asks.
+const MAX_PREFLIGHT_AGE: u32 = 24 * 60 * 60;
+
+/// A CORS cache object, shared by every CORS request made by this user agent.
+/// https://fetch.spec.whatwg.org/#cors-preflight-cache
 #[derive(Clone)]
 pub struct CORSCache(Vec<CORSCacheEntry>);
 
+/// The user agent's CORS cache.
+#[allow(unsafe_code)]
+fn cors_cache() -> &'static Mutex<CORSCache> {
+    static mut CORS_CACHE: *const Mutex<CORSCache> = 0 as *const Mutex<CORSCache>;
+    static INIT: Once = ONCE_INIT;
+    unsafe {
+        INIT.call_once(|| {
+            CORS_CACHE = mem::transmute(box Mutex::new(CORSCache(vec!())));
+        });
+        &*CORS_CACHE
+    }
+}
+
+/// Forgets the preflight results for `request`'s origin and URL, after a CORS check failed.
+/// https://fetch.spec.whatwg.org/#concept-cache-clear
+pub fn clear_cors_cache(request: &CORSRequest) {
+    cors_cache().lock().unwrap().clear(request);
+}
+
 /// Union type for CORS cache entries
 /// Each entry might pertain to a header or method
 #[derive(Clone)]
@@ -353,16 +383,21 @@ impl CORSCacheEntry {
             created: time::now().to_timespec()
         }
     }
+
+    /// Restarts the entry's lifetime after a new preflight confirmed it.
+    fn refresh(&mut self, max_age: u32) {
+        self.max_age = max_age;
+        self.created = time::now().to_timespec();
+    }
 }
 
 impl CORSCache {
     /// https://fetch.spec.whatwg.org/#concept-cache-clear
-    #[allow(dead_code)]
     fn clear(&mut self, request: &CORSRequest) {
         let CORSCache(buf) = self.clone();
         let new_buf: Vec<CORSCacheEntry> =
             buf.into_iter()
-               .filter(|e| e.origin == request.origin && request.destination == e.url)
+               .filter(|e| !(e.origin == request.origin && request.destination == e.url))
                .collect();
         *self = CORSCache(new_buf);
     }
@@ -372,7 +407,7 @@ impl CORSCache {
         let CORSCache(buf) = self.clone();
         let now = time::now().to_timespec();
         let new_buf: Vec<CORSCacheEntry> = buf.into_iter()
-                                              .filter(|e| now.sec > e.created.sec + e.max_age as i64)
+                                              .filter(|e| now.sec < e.created.sec + e.max_age as i64)
                                               .collect();
         *self = CORSCache(new_buf);
     }
@@ -397,7 +432,7 @@ impl CORSCache {
     }
 
     fn match_header_and_update(&mut self, request: &CORSRequest, header_name: &str, new_max_age: u32) -> bool {
-        self.find_entry_by_header(request, header_name).map(|e| e.max_age = new_max_age).is_some()
+        self.find_entry_by_header(request, header_name).map(|e| e.refresh(new_max_age)).is_some()
     }
 
     fn find_entry_by_method<'a>(&'a mut self,
@@ -421,7 +456,7 @@ impl CORSCache {
     }
 
     fn match_method_and_update(&mut self, request: &CORSRequest, method: &Method, new_max_age: u32) -> bool {
-        self.find_entry_by_method(request, method).map(|e| e.max_age = new_max_age).is_some()
+        self.find_entry_by_method(request, method).map(|e| e.refresh(new_max_age)).is_some()
     }
 
     fn insert(&mut self, entry: CORSCacheEntry) {
@@ -469,3 +504,30 @@ pub fn allow_cross_origin_request(req: &CORSRequest, headers: &Headers) -> bool
         None => false
     }
 }
+
+/// The headers of a cross-origin response that are exposed to script: the simple response
+/// headers, and the ones the server listed in Access-Control-Expose-Headers.
+/// https://fetch.spec.whatwg.org/#concept-filtered-response-cors
+pub fn filter_response_headers(headers: &Headers) -> Headers {
+    let mut exposed: Vec<String> = ["cache-control", "content-language", "content-type",
+                                    "expires", "last-modified", "pragma"]
+        .iter().map(|&name| name.to_owned()).collect();
+    if let Some(values) = headers.get_raw("access-control-expose-headers") {
+        for value in values.iter() {
+            if let Ok(value) = str::from_utf8(value) {
+                exposed.extend(value.split(',')
+                                    .map(|name| name.trim().to_ascii_lowercase())
+                                    .filter(|name| !name.is_empty()));
+            }
+        }
+    }
+
+    let mut filtered = Headers::new();
+    for header in headers.iter() {
+        if exposed.iter().any(|name| header.name().eq_ignore_ascii_case(name)) {
+            filtered.set_raw(header.name().to_owned(),
+                             headers.get_raw(header.name()).unwrap().to_vec());
+        }
+    }
+    filtered
+}
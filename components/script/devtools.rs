@@ -4,6 +4,7 @@
 
 use devtools_traits::{CachedConsoleMessage, CachedConsoleMessageTypes, PAGE_ERROR, CONSOLE_API};
 use devtools_traits::{EvaluateJSReply, NodeInfo, Modification, TimelineMarker, TimelineMarkerType};
+use devtools_traits::{AppliedDeclaration, AppliedRule, BoxSides, ComputedNodeLayout};
 use dom::bindings::conversions::FromJSValConvertible;
 use dom::bindings::conversions::StringificationBehavior;
 use dom::bindings::js::Root;
@@ -11,6 +12,7 @@ use dom::bindings::codegen::InheritTypes::{NodeCast, ElementCast};
 use dom::bindings::codegen::Bindings::DocumentBinding::DocumentMethods;
 use dom::bindings::codegen::Bindings::DOMRectBinding::{DOMRectMethods};
 use dom::bindings::codegen::Bindings::ElementBinding::{ElementMethods};
+use dom::bindings::codegen::Bindings::NodeBinding::NodeMethods;
 use dom::node::{Node, NodeHelpers};
 use dom::window::{WindowHelpers, ScriptHelpers};
 use dom::document::DocumentHelpers;
@@ -19,6 +21,12 @@ use msg::constellation_msg::PipelineId;
 use script_task::{get_page, ScriptTask};
 use js::jsapi::RootedValue;
 use js::jsval::UndefinedValue;
+use euclid::point::Point2D;
+use euclid::rect::Rect;
+use euclid::side_offsets::SideOffsets2D;
+use euclid::size::Size2D;
+use style::stylesheets::Origin;
+use util::geometry::Au;
 
 use std::sync::mpsc::Sender;
 use std::rc::Rc;
@@ -89,13 +97,66 @@ pub fn handle_get_children(page: &Rc<Page>, pipeline: PipelineId, node_id: Strin
     reply.send(children).unwrap();
 }
 
-pub fn handle_get_layout(page: &Rc<Page>, pipeline: PipelineId, node_id: String, reply: Sender<(f32, f32)>) {
+pub fn handle_get_layout(page: &Rc<Page>,
+                         pipeline: PipelineId,
+                         node_id: String,
+                         reply: Sender<ComputedNodeLayout>) {
     let node = find_node_by_unique_id(&*page, pipeline, node_id);
     let elem = ElementCast::to_ref(node.r()).expect("should be getting layout of element");
     let rect = elem.GetBoundingClientRect();
     let width = *rect.r().Width();
     let height = *rect.r().Height();
-    reply.send((width, height)).unwrap();
+
+    let page = get_page(&*page, pipeline);
+    let window = page.window();
+    let box_model = window.r().box_model_query(node.r().to_trusted_node_address());
+    reply.send(ComputedNodeLayout {
+        width: width,
+        height: height,
+        position: box_model.as_ref().map_or("static".to_owned(), |b| b.position.clone()),
+        margin: box_sides(box_model.as_ref().map(|b| b.margin)),
+        border: box_sides(box_model.as_ref().map(|b| b.border)),
+        padding: box_sides(box_model.as_ref().map(|b| b.padding)),
+    }).unwrap();
+}
+
+/// Converts the sides of a box model area to CSS pixels. Nodes without a box have no sides.
+fn box_sides(sides: Option<SideOffsets2D<Au>>) -> BoxSides {
+    match sides {
+        Some(sides) => BoxSides {
+            top: sides.top.to_f32_px(),
+            right: sides.right.to_f32_px(),
+            bottom: sides.bottom.to_f32_px(),
+            left: sides.left.to_f32_px(),
+        },
+        None => BoxSides { top: 0., right: 0., bottom: 0., left: 0. },
+    }
+}
+
+pub fn handle_get_applied_rules(page: &Rc<Page>,
+                                pipeline: PipelineId,
+                                node_id: String,
+                                reply: Sender<Vec<AppliedRule>>) {
+    let node = find_node_by_unique_id(&*page, pipeline, node_id);
+    let page = get_page(&*page, pipeline);
+    let window = page.window();
+    let rules = window.r().matched_rules_query(node.r().to_trusted_node_address());
+    reply.send(rules.into_iter().map(|rule| {
+        AppliedRule {
+            href: rule.stylesheet_url.serialize(),
+            line: rule.line,
+            isSystem: rule.origin == Origin::UserAgent,
+            selectorText: rule.selectors,
+            specificity: rule.specificity,
+            declarations: rule.declarations.into_iter().map(|(name, value, important)| {
+                AppliedDeclaration {
+                    name: name,
+                    value: value,
+                    important: important,
+                }
+            }).collect(),
+        }
+    }).collect()).unwrap();
 }
 
 pub fn handle_get_cached_messages(_pipeline_id: PipelineId,
@@ -155,6 +216,31 @@ pub fn handle_modify_attribute(page: &Rc<Page>,
     }
 }
 
+pub fn handle_set_node_value(page: &Rc<Page>, pipeline: PipelineId, node_id: String, value: String) {
+    let node = find_node_by_unique_id(&*page, pipeline, node_id);
+    node.r().SetNodeValue(Some(value));
+}
+
+pub fn handle_highlight_node(page: &Rc<Page>, pipeline: PipelineId, node_id: Option<String>) {
+    let rect = node_id.map(|node_id| {
+        let node = find_node_by_unique_id(&*page, pipeline, node_id);
+        let rect = node.r().get_bounding_content_box();
+        Rect::new(Point2D::new(rect.origin.x.to_f32_px(), rect.origin.y.to_f32_px()),
+                  Size2D::new(rect.size.width.to_f32_px(), rect.size.height.to_f32_px()))
+    });
+    let page = get_page(&*page, pipeline);
+    let window = page.window();
+    window.r().compositor().highlight_rect(pipeline, rect);
+}
+
+pub fn handle_wants_mutation_notifications(page: &Rc<Page>,
+                                           pipeline_id: PipelineId,
+                                           send_notifications: bool) {
+    let page = get_page(&*page, pipeline_id);
+    let window = page.window();
+    window.r().set_devtools_wants_mutations(send_notifications);
+}
+
 pub fn handle_wants_live_notifications(page: &Rc<Page>, pipeline_id: PipelineId, send_notifications: bool) {
     let page = get_page(&*page, pipeline_id);
     let window = page.window();
@@ -167,9 +253,9 @@ pub fn handle_set_timeline_markers(page: &Rc<Page>,
                                    reply: Sender<TimelineMarker>) {
     for marker_type in &marker_types {
         match *marker_type {
-            TimelineMarkerType::Reflow => {
+            TimelineMarkerType::Reflow | TimelineMarkerType::Javascript => {
                 let window = page.window();
-                window.r().set_devtools_timeline_marker(TimelineMarkerType::Reflow, reply.clone());
+                window.r().set_devtools_timeline_marker(marker_type.clone(), reply.clone());
             }
             TimelineMarkerType::DOMEvent => {
                 script_task.set_devtools_timeline_marker(TimelineMarkerType::DOMEvent, reply.clone());
@@ -184,7 +270,7 @@ pub fn handle_drop_timeline_markers(page: &Rc<Page>,
     let window = page.window();
     for marker_type in &marker_types {
         match *marker_type {
-            TimelineMarkerType::Reflow => {
+            TimelineMarkerType::Reflow | TimelineMarkerType::Javascript => {
                 window.r().drop_devtools_timeline_markers();
             }
             TimelineMarkerType::DOMEvent => {
@@ -7,10 +7,14 @@
 
 use script_task::{ScriptMsg, ScriptChan};
 use msg::constellation_msg::{PipelineId};
-use net_traits::{Metadata, load_whole_resource, ResourceTask, PendingAsyncLoad};
+use net_traits::{Metadata, load_whole_resource_with_priority, ResourceTask, PendingAsyncLoad};
+use net_traits::{LoadPriority, ProgressMsg};
+use util::task::spawn_named;
 use net_traits::AsyncResponseTarget;
 use url::Url;
 
+use std::borrow::ToOwned;
+
 #[derive(JSTraceable, PartialEq, Clone, Debug)]
 pub enum LoadType {
     Image(Url),
@@ -21,7 +25,7 @@ pub enum LoadType {
 }
 
 impl LoadType {
-    fn url(&self) -> &Url {
+    pub fn url(&self) -> &Url {
         match *self {
             LoadType::Image(ref url) |
             LoadType::Script(ref url) |
@@ -30,6 +34,41 @@ impl LoadType {
             LoadType::PageSource(ref url) => url,
         }
     }
+
+    fn priority(&self) -> LoadPriority {
+        match *self {
+            LoadType::Image(_) => LoadPriority::Image,
+            LoadType::Script(_) => LoadPriority::Script,
+            LoadType::Stylesheet(_) => LoadPriority::Style,
+            LoadType::Subframe(_) | LoadType::PageSource(_) => LoadPriority::Urgent,
+        }
+    }
+
+    /// Whether a secure document must not load this over an insecure connection, because
+    /// it can change the whole document.
+    /// https://w3c.github.io/webappsec/specs/mixedcontent/#category-blockable
+    pub fn is_blockable(&self) -> bool {
+        match *self {
+            LoadType::Script(_) | LoadType::Stylesheet(_) | LoadType::Subframe(_) => true,
+            LoadType::Image(_) | LoadType::PageSource(_) => false,
+        }
+    }
+}
+
+/// Fetches a resource that nothing is waiting for, such as a prefetch, so that it is in
+/// the HTTP cache by the time it's needed. The load doesn't block the document.
+pub fn load_in_background(resource_task: ResourceTask, url: Url, priority: LoadPriority) {
+    let pending = PendingAsyncLoad::new(resource_task, url, None, priority);
+    let response_port = pending.load();
+    spawn_named("BackgroundLoad".to_owned(), move || {
+        if let Ok(response) = response_port.recv() {
+            while let Ok(msg) = response.progress_port.recv() {
+                if let ProgressMsg::Done(_) = msg {
+                    break;
+                }
+            }
+        }
+    });
 }
 
 #[derive(JSTraceable)]
@@ -67,9 +106,10 @@ impl DocumentLoader {
     /// the future.
     pub fn prepare_async_load(&mut self, load: LoadType) -> PendingAsyncLoad {
         let url = load.url().clone();
+        let priority = load.priority();
         self.blocking_loads.push(load);
         let pipeline = self.notifier_data.as_ref().map(|data| data.pipeline);
-        PendingAsyncLoad::new(self.resource_task.clone(), url, pipeline)
+        PendingAsyncLoad::new(self.resource_task.clone(), url, pipeline, priority)
     }
 
     /// Create and initiate a new network request.
@@ -81,7 +121,8 @@ impl DocumentLoader {
     /// Create, initiate, and await the response for a new network request.
     pub fn load_sync(&mut self, load: LoadType) -> Result<(Metadata, Vec<u8>), String> {
         self.blocking_loads.push(load.clone());
-        let result = load_whole_resource(&self.resource_task, load.url().clone());
+        let result = load_whole_resource_with_priority(&self.resource_task, load.url().clone(),
+                                                       load.priority());
         self.finish_load(load);
         result
     }
@@ -4,17 +4,15 @@
 
 //! Base classes to work with IDL callbacks.
 
-use dom::bindings::error::{Fallible, Error};
+use dom::bindings::error::{Fallible, Error, report_pending_exception};
 use dom::bindings::global::global_object_for_js_object;
 use dom::bindings::utils::Reflectable;
 use js::jsapi::{JSContext, JSObject, JS_WrapObject, IsCallable};
-use js::jsapi::{JS_GetProperty, JS_IsExceptionPending, JS_ReportPendingException};
+use js::jsapi::{JS_GetProperty, JS_IsExceptionPending};
 use js::jsapi::{RootedObject, RootedValue, MutableHandleObject, Heap};
-use js::jsapi::{JSAutoCompartment};
 use js::jsapi::{JS_BeginRequest, JS_EndRequest};
 use js::jsapi::{JS_EnterCompartment, JS_LeaveCompartment, JSCompartment};
 use js::jsapi::GetGlobalForObjectCrossCompartment;
-use js::jsapi::{JS_SaveFrameChain, JS_RestoreFrameChain};
 use js::jsval::{JSVal, UndefinedValue};
 
 use std::ffi::CString;
@@ -201,17 +199,8 @@ impl Drop for CallSetup {
             self.handling == ExceptionHandling::Report &&
             unsafe { JS_IsExceptionPending(self.cx) } != 0;
         if need_to_deal_with_exception {
-            unsafe {
-                let old_global = RootedObject::new(self.cx, self.exception_compartment.ptr);
-                let saved = JS_SaveFrameChain(self.cx) != 0;
-                {
-                    let _ac = JSAutoCompartment::new(self.cx, old_global.ptr);
-                    JS_ReportPendingException(self.cx);
-                }
-                if saved {
-                    JS_RestoreFrameChain(self.cx);
-                }
-            }
+            let old_global = RootedObject::new(self.cx, self.exception_compartment.ptr);
+            report_pending_exception(self.cx, old_global.ptr);
         }
         unsafe { JS_EndRequest(self.cx); }
     }
@@ -5,23 +5,27 @@
 //! Utilities to throw exceptions from Rust bindings.
 
 use dom::bindings::codegen::PrototypeList::proto_id_to_name;
-use dom::bindings::conversions::ToJSValConvertible;
-use dom::bindings::global::GlobalRef;
+use dom::bindings::conversions::{FromJSValConvertible, ToJSValConvertible};
+use dom::bindings::conversions::StringificationBehavior;
+use dom::bindings::global::{GlobalRef, global_object_for_js_object};
 use dom::domexception::{DOMException, DOMErrorName};
 
 use util::str::DOMString;
 
-use js::jsapi::{JSContext, JSObject, RootedValue};
-use js::jsapi::{JS_IsExceptionPending, JS_SetPendingException, JS_ReportPendingException};
+use js::jsapi::{JSContext, JSObject, RootedObject, RootedValue, HandleObject, HandleValue};
+use js::jsapi::{JS_IsExceptionPending, JS_SetPendingException};
+use js::jsapi::{JS_GetPendingException, JS_ClearPendingException, JS_ErrorFromException};
 use js::jsapi::{JS_ReportErrorNumber1, JSErrorFormatString, JSExnType};
 use js::jsapi::{JS_SaveFrameChain, JS_RestoreFrameChain};
 use js::jsapi::JSAutoCompartment;
 use js::jsval::UndefinedValue;
 
 use libc;
-use std::ffi::CString;
+use std::borrow::ToOwned;
+use std::ffi::{CStr, CString};
 use std::ptr;
 use std::mem;
+use std::slice;
 
 /// DOM exceptions that can be thrown by a native DOM method.
 #[derive(Debug, Clone)]
@@ -126,14 +130,102 @@ pub fn throw_dom_exception(cx: *mut JSContext, global: GlobalRef,
     }
 }
 
-/// Report a pending exception, thereby clearing it.
+/// The details of an uncaught exception, as exposed through `ErrorEvent`.
+pub struct ErrorInfo {
+    /// The error message.
+    pub message: DOMString,
+    /// The URL of the script in which the error occurred.
+    pub filename: DOMString,
+    /// The line number at which the error occurred.
+    pub lineno: u32,
+    /// The column number at which the error occurred.
+    pub column: u32,
+}
+
+impl ErrorInfo {
+    /// Extracts the details of a native `Error` object, if `object` is one.
+    #[allow(unsafe_code)]
+    unsafe fn from_native_error(cx: *mut JSContext, object: HandleObject) -> Option<ErrorInfo> {
+        let report = JS_ErrorFromException(cx, object);
+        if report.is_null() {
+            return None;
+        }
+
+        let filename = if (*report).filename.is_null() {
+            "".to_owned()
+        } else {
+            String::from_utf8_lossy(CStr::from_ptr((*report).filename).to_bytes()).into_owned()
+        };
+
+        let message = if (*report).ucmessage.is_null() {
+            "".to_owned()
+        } else {
+            let chars = (*report).ucmessage as *const u16;
+            let mut length = 0;
+            while *chars.offset(length) != 0 {
+                length += 1;
+            }
+            String::from_utf16_lossy(slice::from_raw_parts(chars, length as usize))
+        };
+
+        Some(ErrorInfo {
+            message: message,
+            filename: filename,
+            lineno: (*report).lineno,
+            column: (*report).column,
+        })
+    }
+
+    /// Describes an arbitrary thrown value.
+    #[allow(unsafe_code)]
+    fn from_value(cx: *mut JSContext, value: HandleValue) -> ErrorInfo {
+        if value.get().is_object() {
+            let object = RootedObject::new(cx, value.get().to_object());
+            if let Some(info) = unsafe { ErrorInfo::from_native_error(cx, object.handle()) } {
+                return info;
+            }
+        }
+
+        let message = match DOMString::from_jsval(cx, value, StringificationBehavior::Default) {
+            Ok(message) => format!("uncaught exception: {}", message),
+            Err(()) => {
+                unsafe { JS_ClearPendingException(cx); }
+                "uncaught exception: unknown (can't convert to string)".to_owned()
+            }
+        };
+
+        ErrorInfo {
+            message: message,
+            filename: "".to_owned(),
+            lineno: 0,
+            column: 0,
+        }
+    }
+}
+
+/// Report a pending exception to the global of `obj`, thereby clearing it.
 pub fn report_pending_exception(cx: *mut JSContext, obj: *mut JSObject) {
     unsafe {
         if JS_IsExceptionPending(cx) != 0 {
             let saved = JS_SaveFrameChain(cx);
             {
                 let _ac = JSAutoCompartment::new(cx, obj);
-                JS_ReportPendingException(cx);
+                let mut value = RootedValue::new(cx, UndefinedValue());
+                let error_info = if JS_GetPendingException(cx, value.handle_mut()) != 0 {
+                    JS_ClearPendingException(cx);
+                    ErrorInfo::from_value(cx, value.handle())
+                } else {
+                    JS_ClearPendingException(cx);
+                    ErrorInfo {
+                        message: "uncaught exception: unknown".to_owned(),
+                        filename: "".to_owned(),
+                        lineno: 0,
+                        column: 0,
+                    }
+                };
+
+                let global = global_object_for_js_object(obj);
+                global.r().report_an_error(error_info, value.handle());
             }
             if saved != 0 {
                 JS_RestoreFrameChain(cx);
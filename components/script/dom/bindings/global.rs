@@ -9,6 +9,7 @@
 
 use dom::bindings::codegen::Bindings::WindowBinding::WindowMethods;
 use dom::bindings::conversions::native_from_reflector_jsmanaged;
+use dom::bindings::error::ErrorInfo;
 use dom::bindings::js::{JS, Root};
 use dom::bindings::utils::{Reflectable, Reflector};
 use dom::document::DocumentHelpers;
@@ -22,7 +23,7 @@ use net_traits::ResourceTask;
 
 use js::{JSCLASS_IS_GLOBAL, JSCLASS_IS_DOMJSCLASS};
 use js::jsapi::{GetGlobalForObjectCrossCompartment};
-use js::jsapi::{JSContext, JSObject};
+use js::jsapi::{JSContext, JSObject, HandleValue};
 use js::jsapi::{JS_GetClass};
 use url::Url;
 
@@ -147,6 +148,19 @@ impl<'a> GlobalRef<'a> {
             GlobalRef::Worker(ref worker) => worker.process_event(msg),
         }
     }
+
+    /// Report an uncaught exception thrown in this global.
+    /// https://html.spec.whatwg.org/multipage/#report-the-error
+    pub fn report_an_error(&self, error_info: ErrorInfo, value: HandleValue) {
+        match *self {
+            GlobalRef::Window(ref window) => window.report_an_error(error_info, value),
+            GlobalRef::Worker(_) => {
+                // TODO: Fire the error event at the worker global scope and its Worker object.
+                error!("Error at {}:{}:{} {}", error_info.filename, error_info.lineno,
+                       error_info.column, error_info.message);
+            }
+        }
+    }
 }
 
 impl<'a> Reflectable for GlobalRef<'a> {
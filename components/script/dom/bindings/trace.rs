@@ -38,6 +38,8 @@ use canvas_traits::{CanvasGradientStop, LinearGradientStyle, RadialGradientStyle
 use canvas_traits::{LineCapStyle, LineJoinStyle, CompositionOrBlending, RepetitionStyle};
 use cssparser::RGBA;
 use encoding::types::EncodingRef;
+use gamepad::GamepadPoller;
+use parse::preload::PreloadScanner;
 use euclid::matrix2d::Matrix2D;
 use euclid::rect::Rect;
 use euclid::size::Size2D;
@@ -50,7 +52,9 @@ use js::jsval::JSVal;
 use js::rust::Runtime;
 use layout_interface::{LayoutRPC, LayoutChan};
 use libc;
-use msg::constellation_msg::{PipelineId, SubpageId, WindowSizeData, WorkerId};
+use msg::constellation_msg::{GeolocationPosition, NotificationId, PipelineId, SubpageId};
+use msg::constellation_msg::{WindowSizeData, WorkerId};
+use msg::constellation_msg::ReferrerPolicy;
 use net_traits::image_cache_task::{ImageCacheChan, ImageCacheTask};
 use net_traits::storage_task::StorageType;
 use script_traits::ScriptControlChan;
@@ -59,6 +63,7 @@ use smallvec::SmallVec1;
 use msg::compositor_msg::ScriptListener;
 use msg::constellation_msg::ConstellationChan;
 use net_traits::image::base::Image;
+use util::ipc::IpcSender;
 use util::str::{LengthOrPercentageOrAuto};
 use std::cell::{Cell, UnsafeCell, RefCell};
 use std::collections::{HashMap, HashSet};
@@ -270,6 +275,7 @@ no_jsmanaged_fields!(bool, f32, f64, String, Url);
 no_jsmanaged_fields!(usize, u8, u16, u32, u64);
 no_jsmanaged_fields!(isize, i8, i16, i32, i64);
 no_jsmanaged_fields!(Sender<T>);
+no_jsmanaged_fields!(IpcSender<T>);
 no_jsmanaged_fields!(Receiver<T>);
 no_jsmanaged_fields!(Rect<T>);
 no_jsmanaged_fields!(Size2D<T>);
@@ -283,6 +289,9 @@ no_jsmanaged_fields!(HashSet<T>);
 // in one of these make sure it is propagated properly to containing structs
 no_jsmanaged_fields!(SubpageId, WindowSizeData, PipelineId);
 no_jsmanaged_fields!(WorkerId);
+no_jsmanaged_fields!(NotificationId);
+no_jsmanaged_fields!(GeolocationPosition);
+no_jsmanaged_fields!(ReferrerPolicy);
 no_jsmanaged_fields!(QuirksMode);
 no_jsmanaged_fields!(Runtime);
 no_jsmanaged_fields!(Headers, Method);
@@ -297,6 +306,8 @@ no_jsmanaged_fields!(StorageType);
 no_jsmanaged_fields!(CanvasGradientStop, LinearGradientStyle, RadialGradientStyle);
 no_jsmanaged_fields!(LineCapStyle, LineJoinStyle, CompositionOrBlending);
 no_jsmanaged_fields!(RepetitionStyle);
+no_jsmanaged_fields!(GamepadPoller);
+no_jsmanaged_fields!(PreloadScanner);
 
 impl JSTraceable for Box<ScriptChan+Send> {
     #[inline]
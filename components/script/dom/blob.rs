@@ -79,6 +79,17 @@ impl Blob {
     }
 }
 
+pub trait BlobHelpers {
+    fn bytes(self) -> Vec<u8>;
+}
+
+impl<'a> BlobHelpers for &'a Blob {
+    /// A copy of this blob's data.
+    fn bytes(self) -> Vec<u8> {
+        self.bytes.clone().unwrap_or(vec!())
+    }
+}
+
 impl<'a> BlobMethods for &'a Blob {
     // http://dev.w3.org/2006/webapi/FileAPI/#dfn-size
     fn Size(self) -> u64{
@@ -322,9 +322,12 @@ impl CanvasRenderingContext2D {
         let window = window.r();
         let image_cache = window.image_cache_task();
         let (response_chan, response_port) = channel();
-        image_cache.request_image(url, ImageCacheChan(response_chan), None);
-        let result = response_port.recv().unwrap();
-        result.image_response
+        image_cache.request_image(url, window.pipeline(), ImageCacheChan(response_chan), None);
+        // The request is dropped if the page is navigated away from in the meantime.
+        match response_port.recv() {
+            Ok(result) => result.image_response,
+            Err(_) => ImageResponse::None,
+        }
     }
 
     fn create_drawable_rect(&self, x: f64, y: f64, w: f64, h: f64) -> Option<Rect<f32>> {
@@ -12,21 +12,27 @@ use dom::bindings::codegen::UnionTypes::NodeOrString;
 use dom::bindings::error::{Fallible, ErrorResult};
 use dom::bindings::error::Error::IndexSize;
 use dom::bindings::js::{LayoutJS, Root};
-use dom::document::Document;
+use dom::document::{Document, DocumentHelpers};
 use dom::element::Element;
 use dom::eventtarget::{EventTarget, EventTargetTypeId};
-use dom::node::{Node, NodeHelpers, NodeTypeId};
+use dom::node::{Node, NodeDamage, NodeHelpers, NodeTypeId};
 
+use devtools_traits::NodeMutation;
 use util::str::DOMString;
 
 use std::borrow::ToOwned;
-use std::cell::Ref;
+use std::cell::{Cell, Ref};
+use std::cmp::{max, min};
 
 // https://dom.spec.whatwg.org/#characterdata
 #[dom_struct]
 pub struct CharacterData {
     node: Node,
     data: DOMRefCell<DOMString>,
+    /// The characters of `data` that changed since layout last read it, as the index of the first
+    /// one and the index after the last one, so that only the words around them are shaped again.
+    /// Characters that were only removed leave an empty range where they were.
+    changed_range: Cell<Option<(usize, usize)>>,
 }
 
 impl CharacterDataDerived for EventTarget {
@@ -43,6 +49,7 @@ impl CharacterData {
         CharacterData {
             node: Node::new_inherited(NodeTypeId::CharacterData(id), document),
             data: DOMRefCell::new(data),
+            changed_range: Cell::new(None),
         }
     }
 }
@@ -55,7 +62,11 @@ impl<'a> CharacterDataMethods for &'a CharacterData {
 
     // https://dom.spec.whatwg.org/#dom-characterdata-data
     fn SetData(self, data: DOMString) {
+        let removed = self.data.borrow().chars().count();
+        let inserted = data.chars().count();
         *self.data.borrow_mut() = data;
+        self.note_data_change(0, removed, inserted);
+        self.report_data_mutation();
     }
 
     // https://dom.spec.whatwg.org/#dom-characterdata-length
@@ -79,7 +90,10 @@ impl<'a> CharacterDataMethods for &'a CharacterData {
 
     // https://dom.spec.whatwg.org/#dom-characterdata-appenddatadata
     fn AppendData(self, data: DOMString) {
+        let length = self.data.borrow().chars().count();
         self.data.borrow_mut().push_str(&data);
+        self.note_data_change(length, 0, data.chars().count());
+        self.report_data_mutation();
     }
 
     // https://dom.spec.whatwg.org/#dom-characterdata-insertdataoffset-data
@@ -111,6 +125,8 @@ impl<'a> CharacterDataMethods for &'a CharacterData {
         data.push_str(&arg);
         data.push_str(&self.data.borrow().slice_chars((offset + count) as usize, length as usize));
         *self.data.borrow_mut() = data;
+        self.note_data_change(offset as usize, count as usize, arg.chars().count());
+        self.report_data_mutation();
         // FIXME: Once we have `Range`, we should implement step7 to step11
         Ok(())
     }
@@ -159,6 +175,8 @@ pub enum CharacterDataTypeId {
 
 pub trait CharacterDataHelpers<'a> {
     fn data(self) -> Ref<'a, DOMString>;
+    fn note_data_change(self, offset: usize, removed: usize, inserted: usize);
+    fn report_data_mutation(self);
 }
 
 impl<'a> CharacterDataHelpers<'a> for &'a CharacterData {
@@ -166,11 +184,43 @@ impl<'a> CharacterDataHelpers<'a> for &'a CharacterData {
     fn data(self) -> Ref<'a, DOMString> {
         self.data.borrow()
     }
+
+    /// Adds the `inserted` characters that replaced `removed` characters at `offset` to the
+    /// characters that changed since layout last read the data, and has the node laid out again.
+    fn note_data_change(self, offset: usize, removed: usize, inserted: usize) {
+        let changed_range = match self.changed_range.get() {
+            None => (offset, offset + inserted),
+            Some((start, end)) => {
+                // The end of the earlier change moves with the characters after it.
+                let end = if end >= offset + removed {
+                    end - removed + inserted
+                } else {
+                    min(end, offset)
+                };
+                (min(start, offset), max(end, offset + inserted))
+            }
+        };
+        self.changed_range.set(Some(changed_range));
+
+        let node = NodeCast::from_ref(self);
+        node.owner_doc().r().content_changed(node, NodeDamage::OtherNodeDamage);
+    }
+
+    fn report_data_mutation(self) {
+        NodeCast::from_ref(self).report_mutation_to_devtools(|target| {
+            NodeMutation::CharacterData {
+                target: target,
+                newValue: self.data.borrow().clone(),
+            }
+        });
+    }
 }
 
 #[allow(unsafe_code)]
 pub trait LayoutCharacterDataHelpers {
     unsafe fn data_for_layout<'a>(&'a self) -> &'a str;
+    unsafe fn changed_range_for_layout(&self) -> Option<(usize, usize)>;
+    unsafe fn clear_changed_range_for_layout(&self);
 }
 
 #[allow(unsafe_code)]
@@ -179,4 +229,14 @@ impl LayoutCharacterDataHelpers for LayoutJS<CharacterData> {
     unsafe fn data_for_layout<'a>(&'a self) -> &'a str {
         &(*self.unsafe_get()).data.borrow_for_layout()
     }
+
+    #[inline]
+    unsafe fn changed_range_for_layout(&self) -> Option<(usize, usize)> {
+        (*self.unsafe_get()).changed_range.get()
+    }
+
+    #[inline]
+    unsafe fn clear_changed_range_for_layout(&self) {
+        (*self.unsafe_get()).changed_range.set(None)
+    }
 }
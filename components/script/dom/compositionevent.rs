@@ -0,0 +1,147 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+use clipboard_provider::ClipboardProvider;
+use dom::bindings::cell::DOMRefCell;
+use dom::bindings::codegen::Bindings::CompositionEventBinding;
+use dom::bindings::codegen::Bindings::CompositionEventBinding::CompositionEventMethods;
+use dom::bindings::codegen::Bindings::UIEventBinding::UIEventMethods;
+use dom::bindings::codegen::InheritTypes::{CompositionEventDerived, EventCast, UIEventCast};
+use dom::bindings::error::Fallible;
+use dom::bindings::global::GlobalRef;
+use dom::bindings::js::Root;
+use dom::bindings::utils::reflect_dom_object;
+use dom::event::{Event, EventHelpers, EventTypeId, EventBubbles, EventCancelable};
+use dom::eventtarget::EventTarget;
+use dom::uievent::UIEvent;
+use dom::window::Window;
+use msg::constellation_msg::ImeEvent;
+use textinput::{KeyReaction, TextInput};
+use util::str::DOMString;
+
+use std::borrow::ToOwned;
+
+// https://dvcs.w3.org/hg/dom3events/raw-file/tip/html/DOM3-Events.html#interface-CompositionEvent
+#[dom_struct]
+pub struct CompositionEvent {
+    uievent: UIEvent,
+    data: DOMString,
+}
+
+impl CompositionEventDerived for Event {
+    fn is_compositionevent(&self) -> bool {
+        *self.type_id() == EventTypeId::CompositionEvent
+    }
+}
+
+impl CompositionEvent {
+    fn new_inherited(data: DOMString) -> CompositionEvent {
+        CompositionEvent {
+            uievent: UIEvent::new_inherited(EventTypeId::CompositionEvent),
+            data: data,
+        }
+    }
+
+    pub fn new(window: &Window,
+               type_: DOMString,
+               can_bubble: EventBubbles,
+               cancelable: EventCancelable,
+               view: Option<&Window>,
+               detail: i32,
+               data: DOMString) -> Root<CompositionEvent> {
+        let ev = reflect_dom_object(box CompositionEvent::new_inherited(data),
+                                    GlobalRef::Window(window),
+                                    CompositionEventBinding::Wrap);
+        UIEventCast::from_ref(ev.r()).InitUIEvent(type_,
+                                                  can_bubble == EventBubbles::Bubbles,
+                                                  cancelable == EventCancelable::Cancelable,
+                                                  view, detail);
+        ev
+    }
+
+    pub fn Constructor(global: GlobalRef,
+                       type_: DOMString,
+                       init: &CompositionEventBinding::CompositionEventInit)
+                       -> Fallible<Root<CompositionEvent>> {
+        let bubbles = if init.parent.parent.bubbles {
+            EventBubbles::Bubbles
+        } else {
+            EventBubbles::DoesNotBubble
+        };
+        let cancelable = if init.parent.parent.cancelable {
+            EventCancelable::Cancelable
+        } else {
+            EventCancelable::NotCancelable
+        };
+        let event = CompositionEvent::new(global.as_window(), type_,
+                                          bubbles,
+                                          cancelable,
+                                          init.parent.view.r(),
+                                          init.parent.detail,
+                                          init.data.clone());
+        Ok(event)
+    }
+
+    /// Fires a trusted composition event of the given type at `target`, as an input method
+    /// editor composes text into it. Only compositionstart can be cancelled; returns false if
+    /// it was.
+    pub fn fire(window: &Window, target: &EventTarget, type_: &str, data: DOMString) -> bool {
+        let cancelable = if type_ == "compositionstart" {
+            EventCancelable::Cancelable
+        } else {
+            EventCancelable::NotCancelable
+        };
+        let event = CompositionEvent::new(window, type_.to_owned(), EventBubbles::Bubbles,
+                                          cancelable, Some(window), 0, data);
+        EventCast::from_ref(event.r()).fire(target)
+    }
+}
+
+impl<'a> CompositionEventMethods for &'a CompositionEvent {
+    // https://dvcs.w3.org/hg/dom3events/raw-file/tip/html/DOM3-Events.html#widl-CompositionEvent-data
+    fn Data(self) -> DOMString {
+        self.data.clone()
+    }
+}
+
+/// Applies an input method editor event to the text being edited in `target`, firing the
+/// composition events around it. `dispatch_input` is called if the text changed, before
+/// compositionend is fired.
+pub fn apply_ime_event<T, F>(window: &Window,
+                             target: &EventTarget,
+                             textinput: &DOMRefCell<TextInput<T>>,
+                             event: &ImeEvent,
+                             dispatch_input: F)
+                             where T: ClipboardProvider, F: FnOnce() {
+    let composing = textinput.borrow().is_composing();
+    match *event {
+        ImeEvent::SetComposition(ref text, _) | ImeEvent::Commit(ref text) => {
+            if !composing {
+                let selection = textinput.borrow().get_selection_text();
+                if !CompositionEvent::fire(window, target, "compositionstart", selection) {
+                    return
+                }
+            }
+            CompositionEvent::fire(window, target, "compositionupdate", text.clone());
+        }
+        ImeEvent::Cancel if composing => {}
+        ImeEvent::Cancel | ImeEvent::QueryCaretRect => return,
+    }
+
+    // The borrow has to end before the input event is dispatched.
+    let reaction = textinput.borrow_mut().handle_ime_event(event);
+    if reaction == KeyReaction::DispatchInput {
+        dispatch_input();
+    }
+
+    match *event {
+        ImeEvent::Commit(ref text) => {
+            CompositionEvent::fire(window, target, "compositionend", text.clone());
+        }
+        ImeEvent::Cancel => {
+            CompositionEvent::fire(window, target, "compositionend", "".to_owned());
+        }
+        ImeEvent::SetComposition(..) | ImeEvent::QueryCaretRect => {}
+    }
+}
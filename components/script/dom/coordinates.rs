@@ -0,0 +1,72 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+use dom::bindings::codegen::Bindings::CoordinatesBinding;
+use dom::bindings::codegen::Bindings::CoordinatesBinding::CoordinatesMethods;
+use dom::bindings::global::GlobalRef;
+use dom::bindings::js::Root;
+use dom::bindings::num::Finite;
+use dom::bindings::utils::{Reflector, reflect_dom_object};
+use dom::window::Window;
+
+use msg::constellation_msg::GeolocationPosition;
+
+// http://dev.w3.org/geo/api/spec-source.html#coordinates_interface
+#[dom_struct]
+pub struct Coordinates {
+    reflector_: Reflector,
+    position: GeolocationPosition,
+}
+
+impl Coordinates {
+    fn new_inherited(position: GeolocationPosition) -> Coordinates {
+        Coordinates {
+            reflector_: Reflector::new(),
+            position: position,
+        }
+    }
+
+    pub fn new(window: &Window, position: GeolocationPosition) -> Root<Coordinates> {
+        reflect_dom_object(box Coordinates::new_inherited(position),
+                           GlobalRef::Window(window),
+                           CoordinatesBinding::Wrap)
+    }
+}
+
+impl<'a> CoordinatesMethods for &'a Coordinates {
+    // http://dev.w3.org/geo/api/spec-source.html#lat
+    fn Latitude(self) -> Finite<f64> {
+        Finite::wrap(self.position.latitude)
+    }
+
+    // http://dev.w3.org/geo/api/spec-source.html#lon
+    fn Longitude(self) -> Finite<f64> {
+        Finite::wrap(self.position.longitude)
+    }
+
+    // http://dev.w3.org/geo/api/spec-source.html#altitude
+    fn GetAltitude(self) -> Option<Finite<f64>> {
+        self.position.altitude.map(Finite::wrap)
+    }
+
+    // http://dev.w3.org/geo/api/spec-source.html#accuracy
+    fn Accuracy(self) -> Finite<f64> {
+        Finite::wrap(self.position.accuracy)
+    }
+
+    // http://dev.w3.org/geo/api/spec-source.html#altitude-accuracy
+    fn GetAltitudeAccuracy(self) -> Option<Finite<f64>> {
+        self.position.altitude_accuracy.map(Finite::wrap)
+    }
+
+    // http://dev.w3.org/geo/api/spec-source.html#heading
+    fn GetHeading(self) -> Option<Finite<f64>> {
+        self.position.heading.map(Finite::wrap)
+    }
+
+    // http://dev.w3.org/geo/api/spec-source.html#speed
+    fn GetSpeed(self) -> Option<Finite<f64>> {
+        self.position.speed.map(Finite::wrap)
+    }
+}
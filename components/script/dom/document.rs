@@ -23,6 +23,7 @@ use dom::bindings::codegen::InheritTypes::{HTMLAreaElementDerived, HTMLEmbedElem
 use dom::bindings::codegen::InheritTypes::{HTMLFormElementDerived, HTMLImageElementDerived};
 use dom::bindings::codegen::InheritTypes::{HTMLScriptElementDerived, HTMLTitleElementDerived};
 use dom::bindings::codegen::InheritTypes::ElementDerived;
+use dom::bindings::codegen::InheritTypes::{HTMLInputElementCast, HTMLTextAreaElementCast};
 use dom::bindings::codegen::UnionTypes::NodeOrString;
 use dom::bindings::error::{ErrorResult, Fallible};
 use dom::bindings::error::Error::{NotSupported, InvalidCharacter, Security};
@@ -43,13 +44,16 @@ use dom::domimplementation::DOMImplementation;
 use dom::element::{Element, ElementCreator, ElementHelpers, AttributeHandlers};
 use dom::element::{ElementTypeId, ActivationElementHelpers, FocusElementHelpers};
 use dom::event::{Event, EventBubbles, EventCancelable, EventHelpers};
+use dom::focusevent::FocusEvent;
 use dom::eventtarget::{EventTarget, EventTargetTypeId, EventTargetHelpers};
 use dom::htmlanchorelement::HTMLAnchorElement;
 use dom::htmlcollection::{HTMLCollection, CollectionFilter};
 use dom::htmlelement::{HTMLElement, HTMLElementTypeId};
 use dom::htmlheadelement::HTMLHeadElement;
 use dom::htmlhtmlelement::HTMLHtmlElement;
+use dom::htmlinputelement::HTMLInputElementHelpers;
 use dom::htmlscriptelement::HTMLScriptElement;
+use dom::htmltextareaelement::HTMLTextAreaElementHelpers;
 use dom::location::Location;
 use dom::mouseevent::MouseEvent;
 use dom::keyboardevent::KeyboardEvent;
@@ -70,7 +74,9 @@ use msg::compositor_msg::ScriptListener;
 use msg::constellation_msg::AnimationState;
 use msg::constellation_msg::Msg as ConstellationMsg;
 use msg::constellation_msg::{ConstellationChan, FocusType, Key, KeyState, KeyModifiers, MozBrowserEvent};
+use msg::constellation_msg::ImeEvent;
 use msg::constellation_msg::{SUPER, ALT, SHIFT, CONTROL};
+use msg::constellation_msg::{FormControlState, MixedContentState, ReferrerPolicy};
 use net_traits::CookieSource::NonHTTP;
 use net_traits::ControlMsg::{SetCookiesForUrl, GetCookiesForUrl};
 use net_traits::{Metadata, PendingAsyncLoad, AsyncResponseTarget};
@@ -81,6 +87,8 @@ use util::str::{DOMString, split_html_space_chars};
 use layout_interface::{ReflowGoal, ReflowQueryType};
 
 use euclid::point::Point2D;
+use euclid::rect::Rect;
+use euclid::size::Size2D;
 use html5ever::tree_builder::{QuirksMode, NoQuirks, LimitedQuirks, Quirks};
 use layout_interface::{LayoutChan, Msg};
 use string_cache::{Atom, QualName};
@@ -149,6 +157,17 @@ pub struct Document {
     current_parser: MutNullableHeap<JS<ServoHTMLParser>>,
     /// When we should kick off a reflow. This happens during parsing.
     reflow_timeout: Cell<Option<u64>>,
+    /// The elements currently in the `:active` state, i.e. the inclusive ancestors of the
+    /// element on which the mouse button was last pressed.
+    active_elements: DOMRefCell<Vec<JS<Node>>>,
+    /// The policy from the Referrer-Policy header, which limits what this document's
+    /// requests reveal about it in their Referer header.
+    referrer_policy: Cell<Option<ReferrerPolicy>>,
+    /// Whether the user has allowed this document to load insecure active content.
+    allow_mixed_content: Cell<bool>,
+    /// The default language of the document, from a `<meta http-equiv=content-language>` or the
+    /// Content-Language header, which elements without a language of their own are in.
+    content_language: DOMRefCell<Option<Atom>>,
 }
 
 impl PartialEq for Document {
@@ -230,6 +249,11 @@ pub trait DocumentHelpers<'a> {
     fn url(self) -> Url;
     fn quirks_mode(self) -> QuirksMode;
     fn set_quirks_mode(self, mode: QuirksMode);
+    fn referrer_policy(self) -> Option<ReferrerPolicy>;
+    fn set_referrer_policy(self, policy: Option<ReferrerPolicy>);
+    fn set_content_language(self, lang: Option<Atom>);
+    fn set_allow_mixed_content(self, allow: bool);
+    fn should_block_mixed_content(self, url: &Url, blockable: bool) -> bool;
     fn set_encoding_name(self, name: DOMString);
     fn content_changed(self, node: &Node, damage: NodeDamage);
     fn content_and_heritage_changed(self, node: &Node, damage: NodeDamage);
@@ -238,7 +262,7 @@ pub trait DocumentHelpers<'a> {
     fn disarm_reflow_timeout(self);
     fn unregister_named_element(self, to_unregister: &Element, id: Atom);
     fn register_named_element(self, element: &Element, id: Atom);
-    fn load_anchor_href(self, href: DOMString);
+    fn load_anchor_href(self, href: DOMString, referrer_policy: Option<ReferrerPolicy>);
     fn find_fragment_node(self, fragid: DOMString) -> Option<Root<Element>>;
     fn hit_test(self, point: &Point2D<f32>) -> Option<UntrustedNodeAddress>;
     fn get_nodes_under_mouse(self, point: &Point2D<f32>) -> Vec<UntrustedNodeAddress>;
@@ -248,11 +272,18 @@ pub trait DocumentHelpers<'a> {
     fn begin_focus_transaction(self);
     fn request_focus(self, elem: &Element);
     fn commit_focus_transaction(self, focus_type: FocusType);
+    fn focus_next_element(self, direction: FocusDirection);
+    fn run_autofocus(self);
     fn title_changed(self);
     fn send_title_to_compositor(self);
     fn dirty_all_nodes(self);
+    fn form_control_states(self) -> Vec<(usize, FormControlState)>;
+    fn restore_form_control_states(self, states: &[(usize, FormControlState)]);
     fn dispatch_key_event(self, key: Key, state: KeyState,
         modifiers: KeyModifiers, compositor: &mut Box<ScriptListener+'static>);
+    /// Applies an input method editor event to the focused text field, and tells the
+    /// compositor where its caret is.
+    fn dispatch_ime_event(self, event: ImeEvent, compositor: &mut Box<ScriptListener+'static>);
     fn node_from_nodes_and_strings(self, nodes: Vec<NodeOrString>)
                                    -> Fallible<Root<Node>>;
     fn get_body_attribute(self, local_name: &Atom) -> DOMString;
@@ -347,6 +378,47 @@ impl<'a> DocumentHelpers<'a> for &'a Document {
         }
     }
 
+    fn referrer_policy(self) -> Option<ReferrerPolicy> {
+        self.referrer_policy.get()
+    }
+
+    fn set_referrer_policy(self, policy: Option<ReferrerPolicy>) {
+        self.referrer_policy.set(policy);
+    }
+
+    /// Sets the default language of the document, and restyles the document element, which is
+    /// in that language if it doesn't have one of its own.
+    fn set_content_language(self, lang: Option<Atom>) {
+        *self.content_language.borrow_mut() = lang;
+        if let Some(root) = self.GetDocumentElement() {
+            self.content_changed(NodeCast::from_ref(root.r()), NodeDamage::NodeStyleDamaged);
+        }
+    }
+
+    fn set_allow_mixed_content(self, allow: bool) {
+        self.allow_mixed_content.set(allow);
+    }
+
+    /// Whether a load of `url` by this document must not use an insecure connection, which
+    /// is the case for blockable content in secure documents. The embedder is told when a
+    /// secure document asks for insecure content.
+    /// https://w3c.github.io/webappsec/specs/mixedcontent/#should-block-fetch
+    fn should_block_mixed_content(self, url: &Url, blockable: bool) -> bool {
+        if self.url.scheme != "https" {
+            return false;
+        }
+        let block = blockable && !self.allow_mixed_content.get();
+        if url.scheme == "http" {
+            let state = if block { MixedContentState::Blocked } else { MixedContentState::Loaded };
+            let window = self.window.root();
+            let window = window.r();
+            let ConstellationChan(ref chan) = window.constellation_chan();
+            chan.send(ConstellationMsg::MixedContent(window.pipeline(), url.clone(), state))
+                .unwrap();
+        }
+        block
+    }
+
     fn set_encoding_name(self, name: DOMString) {
         *self.encoding_name.borrow_mut() = name;
     }
@@ -454,9 +526,9 @@ impl<'a> DocumentHelpers<'a> for &'a Document {
         }
     }
 
-    fn load_anchor_href(self, href: DOMString) {
+    fn load_anchor_href(self, href: DOMString, referrer_policy: Option<ReferrerPolicy>) {
         let window = self.window.root();
-        window.r().load_url(href);
+        window.r().load_url(href, referrer_policy.or(self.referrer_policy.get()));
     }
 
     /// Attempt to find a named element in this page's document.
@@ -551,19 +623,33 @@ impl<'a> DocumentHelpers<'a> for &'a Document {
     /// Reassign the focus context to the element that last requested focus during this
     /// transaction, or none if no elements requested it.
     fn commit_focus_transaction(self, focus_type: FocusType) {
-        //TODO: dispatch blur, focus, focusout, and focusin events
+        if self.focused.get() == self.possibly_focused.get() {
+            return;
+        }
+
+        let old_focused = self.focused.get().map(|t| t.root());
+        let new_focused = self.possibly_focused.get().map(|t| t.root());
 
-        if let Some(ref elem) = self.focused.get().map(|t| t.root()) {
+        // https://html.spec.whatwg.org/multipage/#focus-update-steps
+        if let Some(ref elem) = old_focused {
             let node = NodeCast::from_ref(elem.r());
             node.set_focus_state(false);
+
+            let related_target = new_focused.as_ref().map(|e| EventTargetCast::from_ref(e.r()));
+            self.fire_focus_event(FocusEventType::Blur, node, related_target);
+            self.fire_focus_event(FocusEventType::FocusOut, node, related_target);
         }
 
         self.focused.set(self.possibly_focused.get());
 
-        if let Some(ref elem) = self.focused.get().map(|t| t.root()) {
+        if let Some(ref elem) = new_focused {
             let node = NodeCast::from_ref(elem.r());
             node.set_focus_state(true);
 
+            let related_target = old_focused.as_ref().map(|e| EventTargetCast::from_ref(e.r()));
+            self.fire_focus_event(FocusEventType::Focus, node, related_target);
+            self.fire_focus_event(FocusEventType::FocusIn, node, related_target);
+
             // Update the focus state for all elements in the focus chain.
             // https://html.spec.whatwg.org/multipage/#focus-chain
             if focus_type == FocusType::Element {
@@ -575,6 +661,65 @@ impl<'a> DocumentHelpers<'a> for &'a Document {
         }
     }
 
+    /// Moves the focus to the next (or previous) element in the sequential focus navigation
+    /// order, wrapping around at either end.
+    /// https://html.spec.whatwg.org/multipage/#sequential-focus-navigation
+    fn focus_next_element(self, direction: FocusDirection) {
+        let root = NodeCast::from_ref(self);
+        let mut candidates: Vec<(i32, Root<Element>)> =
+            root.traverse_preorder()
+                .filter_map(ElementCast::to_root)
+                .filter(|elem| elem.r().is_sequentially_focusable())
+                .map(|elem| (elem.r().tab_index().unwrap_or(0), elem))
+                .collect();
+        if candidates.is_empty() {
+            return;
+        }
+
+        // Elements with a positive tabindex come first, in increasing order, followed by the
+        // ones with a zero tabindex. The sort is stable, so ties are kept in tree order.
+        candidates.sort_by(|a, b| (a.0 == 0, a.0).cmp(&(b.0 == 0, b.0)));
+        if direction == FocusDirection::Backward {
+            candidates.reverse();
+        }
+
+        let current = self.get_focused_element();
+        let position = current.as_ref().and_then(|current| {
+            candidates.iter().position(|&(_, ref elem)| elem.r() == current.r())
+        });
+        let next = match position {
+            Some(position) => (position + 1) % candidates.len(),
+            None => 0,
+        };
+
+        let (_, ref elem) = candidates[next];
+        self.begin_focus_transaction();
+        self.request_focus(elem.r());
+        self.commit_focus_transaction(FocusType::Element);
+    }
+
+    /// Focuses the first element in tree order that carries the `autofocus` attribute, unless
+    /// something already has the focus.
+    /// https://html.spec.whatwg.org/multipage/#autofocusing-a-form-control:-the-autofocus-attribute
+    fn run_autofocus(self) {
+        if self.focused.get().is_some() {
+            return;
+        }
+
+        let root = NodeCast::from_ref(self);
+        let autofocus = root.traverse_preorder()
+                            .filter_map(ElementCast::to_root)
+                            .find(|elem| {
+                                elem.r().has_attribute(&atom!("autofocus")) &&
+                                elem.r().is_focusable_area()
+                            });
+        if let Some(ref elem) = autofocus {
+            self.begin_focus_transaction();
+            self.request_focus(elem.r());
+            self.commit_focus_transaction(FocusType::Element);
+        }
+    }
+
     /// Handles any updates when the document's title has changed.
     fn title_changed(self) {
         // https://developer.mozilla.org/en-US/docs/Web/Events/mozbrowsertitlechange
@@ -599,6 +744,42 @@ impl<'a> DocumentHelpers<'a> for &'a Document {
         }
     }
 
+    /// The state of the `input` and `textarea` elements the user edited, by their index among
+    /// those elements in tree order, to restore if the document is reloaded from the session
+    /// history.
+    fn form_control_states(self) -> Vec<(usize, FormControlState)> {
+        let root = NodeCast::from_ref(self);
+        root.traverse_preorder()
+            .filter(|node| is_restorable_form_control(node.r()))
+            .enumerate()
+            .filter_map(|(index, node)| {
+                let state = match HTMLInputElementCast::to_ref(node.r()) {
+                    Some(input) => input.edited_state(),
+                    None => HTMLTextAreaElementCast::to_ref(node.r()).unwrap().edited_state(),
+                };
+                state.map(|state| (index, state))
+            })
+            .collect()
+    }
+
+    fn restore_form_control_states(self, states: &[(usize, FormControlState)]) {
+        let root = NodeCast::from_ref(self);
+        let controls: Vec<Root<Node>> = root.traverse_preorder()
+                                            .filter(|node| is_restorable_form_control(node.r()))
+                                            .collect();
+        for &(index, ref state) in states {
+            let control = match controls.get(index) {
+                Some(control) => control,
+                None => continue,
+            };
+            match HTMLInputElementCast::to_ref(control.r()) {
+                Some(input) => input.restore_edited_state(state),
+                None => HTMLTextAreaElementCast::to_ref(control.r()).unwrap()
+                                                                    .restore_edited_state(state),
+            }
+        }
+    }
+
     fn handle_mouse_event(self, js_runtime: *mut JSRuntime,
                           _button: MouseButton, point: Point2D<f32>,
                           mouse_event_type: MouseEventType) {
@@ -638,6 +819,13 @@ impl<'a> DocumentHelpers<'a> for &'a Document {
             self.begin_focus_transaction();
         }
 
+        // https://html.spec.whatwg.org/multipage/#selector-active
+        match mouse_event_type {
+            MouseEventType::MouseDown => self.set_active_chain(Some(node)),
+            MouseEventType::MouseUp => self.set_active_chain(None),
+            MouseEventType::Click => {}
+        }
+
         let window = self.window.root();
 
         // https://dvcs.w3.org/hg/dom3events/raw-file/tip/html/DOM3-Events.html#event-type-click
@@ -699,54 +887,55 @@ impl<'a> DocumentHelpers<'a> for &'a Document {
                                js_runtime: *mut JSRuntime,
                                point: Point2D<f32>,
                                prev_mouse_over_targets: &mut RootedVec<JS<Node>>) {
-        // Build a list of elements that are currently under the mouse.
-        let mouse_over_addresses = self.get_nodes_under_mouse(&point);
+        // Find the topmost element under the mouse by hit testing the display list.
+        let target = self.hit_test(&point).and_then(|address| {
+            let node = node::from_untrusted_node_address(js_runtime, address);
+            let element = node.r().inclusive_ancestors().find(|node| node.r().is_element());
+            element
+        });
+
+        // The hover state applies to the target and every one of its ancestors.
+        // https://html.spec.whatwg.org/multipage/#selector-hover
         let mut mouse_over_targets: RootedVec<JS<Node>> = RootedVec::new();
-        for node_address in mouse_over_addresses.iter() {
-            let node = node::from_untrusted_node_address(js_runtime, *node_address);
-            mouse_over_targets.push(node.r().inclusive_ancestors()
-                                            .find(|node| node.r().is_element())
-                                            .map(|node| JS::from_rooted(&node)).unwrap());
-        };
+        if let Some(ref target) = target {
+            for ancestor in target.r().inclusive_ancestors() {
+                if ancestor.r().is_element() {
+                    mouse_over_targets.push(JS::from_rooted(&ancestor));
+                }
+            }
+        }
 
-        // Remove hover from any elements in the previous list that are no longer
-        // under the mouse.
+        // Remove hover from any elements of the previous chain that are no longer
+        // under the mouse. Only the elements whose state actually changes are dirtied,
+        // so the reflow below restyles just them.
+        let hover_changed = prev_mouse_over_targets[..] != mouse_over_targets[..];
         for target in prev_mouse_over_targets.iter() {
             if !mouse_over_targets.contains(target) {
-                let target = target.root();
-                let target_ref = target.r();
-                if target_ref.get_hover_state() {
-                    target_ref.set_hover_state(false);
-
-                    let target = EventTargetCast::from_ref(target_ref);
-
-                    self.fire_mouse_event(point, &target, "mouseout".to_owned());
-                }
+                target.root().r().set_hover_state(false);
             }
         }
 
-        // Set hover state for any elements in the current mouse over list.
-        // Check if any of them changed state to determine whether to
-        // force a reflow below.
+        // Set hover state for any elements of the current chain.
         for target in mouse_over_targets.iter() {
-            let target = target.root();
-            let target_ref = target.r();
-            if !target_ref.get_hover_state() {
-                target_ref.set_hover_state(true);
-
-                let target = EventTargetCast::from_ref(target_ref);
-
-                self.fire_mouse_event(point, &target, "mouseover".to_owned());
+            target.root().r().set_hover_state(true);
+        }
 
+        // Fire mouseout and mouseover if the topmost element has changed.
+        if prev_mouse_over_targets.first() != mouse_over_targets.first() {
+            if let Some(old_target) = prev_mouse_over_targets.first() {
+                let old_target = old_target.root();
+                let old_target = EventTargetCast::from_ref(old_target.r());
+                self.fire_mouse_event(point, old_target, "mouseout".to_owned());
+            }
+            if let Some(ref target) = target {
+                let target = EventTargetCast::from_ref(target.r());
+                self.fire_mouse_event(point, target, "mouseover".to_owned());
             }
         }
 
         // Send mousemove event to topmost target
-        if mouse_over_addresses.len() > 0 {
-            let top_most_node =
-                node::from_untrusted_node_address(js_runtime, mouse_over_addresses[0]);
-
-            let target = EventTargetCast::from_ref(top_most_node.r());
+        if let Some(ref target) = target {
+            let target = EventTargetCast::from_ref(target.r());
             self.fire_mouse_event(point, target, "mousemove".to_owned());
         }
 
@@ -754,10 +943,17 @@ impl<'a> DocumentHelpers<'a> for &'a Document {
         prev_mouse_over_targets.clear();
         prev_mouse_over_targets.append(&mut *mouse_over_targets);
 
-        let window = self.window.root();
-        window.r().reflow(ReflowGoal::ForDisplay,
-                          ReflowQueryType::NoQuery,
-                          ReflowReason::MouseEvent);
+        // Moving the mouse within the same element only needs layout if one of the event
+        // handlers changed the document.
+        let document_changed = self.GetDocumentElement().map_or(false, |root| {
+            NodeCast::from_ref(root.r()).get_has_dirty_descendants()
+        });
+        if hover_changed || document_changed {
+            let window = self.window.root();
+            window.r().reflow(ReflowGoal::ForDisplay,
+                              ReflowQueryType::NoQuery,
+                              ReflowReason::MouseEvent);
+        }
     }
 
     /// The entry point for all key processing for web content
@@ -833,12 +1029,49 @@ impl<'a> DocumentHelpers<'a> for &'a Document {
                 let maybe_elem: Option<&Element> = ElementCast::to_ref(target);
                 maybe_elem.map(|el| el.as_maybe_activatable().map(|a| a.implicit_submission(ctrl, alt, shift, meta)));
             }
+            Key::Tab if !prevented && state != KeyState::Released => {
+                let direction = if shift { FocusDirection::Backward } else { FocusDirection::Forward };
+                self.focus_next_element(direction);
+            }
             _ => ()
         }
 
         window.r().reflow(ReflowGoal::ForDisplay, ReflowQueryType::NoQuery, ReflowReason::KeyEvent);
     }
 
+    fn dispatch_ime_event(self, event: ImeEvent, compositor: &mut Box<ScriptListener+'static>) {
+        let focused = match self.get_focused_element() {
+            Some(focused) => focused,
+            None => return,
+        };
+        let caret_offset = {
+            let input = HTMLInputElementCast::to_ref(focused.r());
+            let textarea = HTMLTextAreaElementCast::to_ref(focused.r());
+            match (input, textarea) {
+                (Some(input), _) => input.handle_ime_event(&event),
+                (_, Some(textarea)) => textarea.handle_ime_event(&event),
+                (None, None) => None,
+            }
+        };
+        let caret_offset = match caret_offset {
+            Some(caret_offset) => caret_offset,
+            None => return,
+        };
+
+        let window = self.window.root();
+        let node = NodeCast::from_ref(focused.r());
+        let rect = window.r().caret_rect_query(node.to_trusted_node_address(), caret_offset);
+        if let Some(rect) = rect {
+            let rect = Rect::new(Point2D::new(rect.origin.x.to_f32_px(),
+                                              rect.origin.y.to_f32_px()),
+                                 Size2D::new(rect.size.width.to_f32_px(),
+                                             rect.size.height.to_f32_px()));
+            compositor.set_ime_caret_rect(window.r().pipeline(), rect);
+        }
+
+        window.r().reflow(ReflowGoal::ForDisplay, ReflowQueryType::NoQuery, ReflowReason::ImeEvent);
+    }
+
     fn node_from_nodes_and_strings(self, nodes: Vec<NodeOrString>)
                                    -> Fallible<Root<Node>> {
         if nodes.len() == 1 {
@@ -950,9 +1183,12 @@ impl<'a> DocumentHelpers<'a> for &'a Document {
         let performance = window.Performance();
         let performance = performance.r();
 
+        // Every callback of this frame sees the same gamepad state.
+        window.freeze_gamepads();
         for (_, callback) in animation_frame_list {
             callback(*performance.Now());
         }
+        window.thaw_gamepads();
 
         window.reflow(ReflowGoal::ForDisplay,
                       ReflowQueryType::NoQuery,
@@ -960,13 +1196,18 @@ impl<'a> DocumentHelpers<'a> for &'a Document {
     }
 
     fn prepare_async_load(self, load: LoadType) -> PendingAsyncLoad {
+        let block_mixed_content = self.should_block_mixed_content(load.url(), load.is_blockable());
         let mut loader = self.loader.borrow_mut();
-        loader.prepare_async_load(load)
+        let mut pending = loader.prepare_async_load(load);
+        pending.set_referrer(self.url.clone(), self.referrer_policy.get());
+        if block_mixed_content {
+            pending.block_mixed_content();
+        }
+        pending
     }
 
     fn load_async(self, load: LoadType, listener: Box<AsyncResponseTarget + Send>) {
-        let mut loader = self.loader.borrow_mut();
-        loader.load_async(load, listener)
+        self.prepare_async_load(load).load_async(listener)
     }
 
     fn load_sync(self, load: LoadType) -> Result<(Metadata, Vec<u8>), String> {
@@ -1004,6 +1245,8 @@ pub enum DocumentSource {
 pub trait LayoutDocumentHelpers {
     #[allow(unsafe_code)]
     unsafe fn is_html_document_for_layout(&self) -> bool;
+    #[allow(unsafe_code)]
+    unsafe fn content_language_for_layout(&self) -> Option<Atom>;
 }
 
 impl LayoutDocumentHelpers for LayoutJS<Document> {
@@ -1013,6 +1256,12 @@ impl LayoutDocumentHelpers for LayoutJS<Document> {
     unsafe fn is_html_document_for_layout(&self) -> bool {
         (*self.unsafe_get()).is_html_document
     }
+
+    #[inline]
+    #[allow(unsafe_code)]
+    unsafe fn content_language_for_layout(&self) -> Option<Atom> {
+        (*self.unsafe_get()).content_language.borrow_for_layout().clone()
+    }
 }
 
 impl Document {
@@ -1070,6 +1319,10 @@ impl Document {
             loader: DOMRefCell::new(doc_loader),
             current_parser: Default::default(),
             reflow_timeout: Cell::new(None),
+            active_elements: DOMRefCell::new(vec!()),
+            referrer_policy: Cell::new(None),
+            content_language: DOMRefCell::new(None),
+            allow_mixed_content: Cell::new(false),
         }
     }
 
@@ -1107,6 +1360,9 @@ impl Document {
 trait PrivateDocumentHelpers {
     fn create_node_list<F: Fn(&Node) -> bool>(self, callback: F) -> Root<NodeList>;
     fn get_html_element(self) -> Option<Root<HTMLHtmlElement>>;
+    fn set_active_chain(self, target: Option<&Node>);
+    fn fire_focus_event(self, focus_event_type: FocusEventType, node: &Node,
+                        related_target: Option<&EventTarget>);
 }
 
 impl<'a> PrivateDocumentHelpers for &'a Document {
@@ -1125,6 +1381,50 @@ impl<'a> PrivateDocumentHelpers for &'a Document {
             .and_then(HTMLHtmlElementCast::to_ref)
             .map(Root::from_ref)
     }
+
+    /// Moves the `:active` state to the given node and its element ancestors, or clears it
+    /// entirely if there is no target. Only the elements whose state changes are restyled.
+    fn set_active_chain(self, target: Option<&Node>) {
+        let mut new_active_elements = vec!();
+        if let Some(target) = target {
+            for ancestor in target.inclusive_ancestors() {
+                if ancestor.r().is_element() {
+                    new_active_elements.push(JS::from_rooted(&ancestor));
+                }
+            }
+        }
+
+        let mut active_elements = self.active_elements.borrow_mut();
+        for node in active_elements.iter() {
+            if !new_active_elements.contains(node) {
+                node.root().r().set_active_state(false);
+            }
+        }
+        for node in new_active_elements.iter() {
+            node.root().r().set_active_state(true);
+        }
+        *active_elements = new_active_elements;
+    }
+
+    /// https://html.spec.whatwg.org/multipage/#fire-a-focus-event
+    fn fire_focus_event(self, focus_event_type: FocusEventType, node: &Node,
+                        related_target: Option<&EventTarget>) {
+        let (event_name, bubbles) = match focus_event_type {
+            FocusEventType::Focus => ("focus", EventBubbles::DoesNotBubble),
+            FocusEventType::FocusIn => ("focusin", EventBubbles::Bubbles),
+            FocusEventType::Blur => ("blur", EventBubbles::DoesNotBubble),
+            FocusEventType::FocusOut => ("focusout", EventBubbles::Bubbles),
+        };
+        let window = self.window.root();
+        let focus_event = FocusEvent::new(window.r(), event_name.to_owned(),
+                                          bubbles,
+                                          EventCancelable::NotCancelable,
+                                          Some(window.r()),
+                                          0,
+                                          related_target);
+        let event = EventCast::from_ref(focus_event.r());
+        event.fire(EventTargetCast::from_ref(node));
+    }
 }
 
 trait PrivateClickEventHelpers {
@@ -1832,6 +2132,27 @@ fn is_scheme_host_port_tuple(url: &Url) -> bool {
     url.host().is_some() && url.port_or_default().is_some()
 }
 
+/// Whether a node is a form control whose state is restored when its document is reloaded
+/// from the session history.
+fn is_restorable_form_control(node: &Node) -> bool {
+    HTMLInputElementCast::to_ref(node).is_some() || HTMLTextAreaElementCast::to_ref(node).is_some()
+}
+
+/// The direction in which sequential focus navigation moves.
+#[derive(Copy, Clone, PartialEq)]
+pub enum FocusDirection {
+    Forward,
+    Backward,
+}
+
+#[derive(Copy, Clone)]
+enum FocusEventType {
+    Focus,      // Element gained focus. Doesn't bubble.
+    FocusIn,    // Element gained focus. Bubbles.
+    Blur,       // Element lost focus. Doesn't bubble.
+    FocusOut,   // Element lost focus. Bubbles.
+}
+
 pub enum DocumentProgressTask {
     DOMContentLoaded,
     Load,
@@ -1859,6 +2180,8 @@ impl DocumentProgressHandler {
         let doctarget = EventTargetCast::from_ref(document.r());
         let _ = doctarget.DispatchEvent(event.r());
 
+        document.r().run_autofocus();
+
         window.r().reflow(ReflowGoal::ForDisplay, ReflowQueryType::NoQuery, ReflowReason::DOMContentLoaded);
     }
 
@@ -57,8 +57,10 @@ use dom::node::{document_from_node, NodeDamage};
 use dom::node::{window_from_node};
 use dom::nodelist::NodeList;
 use dom::virtualmethods::{VirtualMethods, vtable_for};
+use dom::window::WindowHelpers;
 
-use devtools_traits::AttrInfo;
+use devtools_traits::{AttrInfo, NodeMutation};
+use msg::constellation_msg::ReferrerPolicy;
 use smallvec::VecLike;
 use style::legacy::{UnsignedIntegerAttribute, from_declaration};
 use style::properties::{PropertyDeclarationBlock, PropertyDeclaration, parse_style_attribute};
@@ -67,7 +69,7 @@ use style::properties::longhands::{self, border_spacing, height};
 use style::values::CSSFloat;
 use style::values::specified::{self, CSSColor, CSSRGBA};
 use util::geometry::Au;
-use util::str::{DOMString, LengthOrPercentageOrAuto};
+use util::str::{DOMString, LengthOrPercentageOrAuto, parse_integer};
 
 use cssparser::Color;
 use html5ever::serialize;
@@ -457,6 +459,32 @@ impl RawLayoutElementHelpers for Element {
                 PropertyDeclaration::BorderRightWidth(SpecifiedValue(
                     longhands::border_right_width::SpecifiedValue(width_value)))));
         }
+
+        // `xml:lang` takes precedence over `lang`, and an empty value means the language is
+        // unknown. The document element is in the document's default language if it has
+        // neither.
+        // https://html.spec.whatwg.org/multipage/#the-lang-and-xml:lang-attributes
+        let lang = self.get_attr_val_for_layout(&ns!(XML), &atom!("lang")).or_else(|| {
+            self.get_attr_val_for_layout(&ns!(""), &atom!("lang"))
+        });
+        let lang = match lang {
+            Some(lang) if lang.is_empty() => Some(None),
+            Some(lang) => Some(Some(Atom::from_slice(lang))),
+            None => {
+                let node = JS::from_ref(NodeCast::from_ref(self)).to_layout();
+                match node.parent_node_ref() {
+                    Some(parent) if parent.type_id_for_layout() == NodeTypeId::Document => {
+                        node.owner_doc_for_layout().content_language_for_layout().map(Some)
+                    }
+                    _ => None,
+                }
+            }
+        };
+        if let Some(lang) = lang {
+            hints.push(from_declaration(
+                PropertyDeclaration::ServoLang(SpecifiedValue(
+                    longhands::_servo_lang::SpecifiedValue(lang)))));
+        }
     }
 
     #[inline]
@@ -554,6 +582,7 @@ pub trait ElementHelpers<'a> {
     fn serialize(self, traversal_scope: TraversalScope) -> Fallible<DOMString>;
     fn get_root_element(self) -> Root<Element>;
     fn lookup_prefix(self, namespace: Namespace) -> Option<DOMString>;
+    fn referrer_policy(self) -> Option<ReferrerPolicy>;
 }
 
 impl<'a> ElementHelpers<'a> for &'a Element {
@@ -740,6 +769,13 @@ impl<'a> ElementHelpers<'a> for &'a Element {
         }
         None
     }
+
+    /// The policy the `referrerpolicy` attribute asks for, which overrides the document's
+    /// for the loads this element starts.
+    fn referrer_policy(self) -> Option<ReferrerPolicy> {
+        self.get_attribute(&ns!(""), &Atom::from_slice("referrerpolicy"))
+            .and_then(|attr| ReferrerPolicy::parse(&attr.r().Value()))
+    }
 }
 
 pub trait FocusElementHelpers {
@@ -748,6 +784,13 @@ pub trait FocusElementHelpers {
 
     /// https://html.spec.whatwg.org/multipage/#concept-element-disabled
     fn is_actually_disabled(self) -> bool;
+
+    /// The value of the `tabindex` attribute, if present and parseable.
+    /// https://html.spec.whatwg.org/multipage/#attr-tabindex
+    fn tab_index(self) -> Option<i32>;
+
+    /// https://html.spec.whatwg.org/multipage/#sequentially-focusable
+    fn is_sequentially_focusable(self) -> bool;
 }
 
 impl<'a> FocusElementHelpers for &'a Element {
@@ -789,6 +832,19 @@ impl<'a> FocusElementHelpers for &'a Element {
             _ => false
         }
     }
+
+    fn tab_index(self) -> Option<i32> {
+        self.get_attribute(&ns!(""), &atom!("tabindex")).and_then(|attr| {
+            let value = attr.r().value();
+            parse_integer(value.chars())
+        })
+    }
+
+    fn is_sequentially_focusable(self) -> bool {
+        // Elements with a negative tabindex can be focused, but are skipped by sequential
+        // focus navigation.
+        self.is_focusable_area() && self.tab_index().map_or(true, |index| index >= 0)
+    }
 }
 
 pub trait AttributeHandlers {
@@ -1313,6 +1369,17 @@ impl<'a> ElementMethods for &'a Element {
             rect.origin.x + rect.size.width)
     }
 
+    // http://dev.w3.org/csswg/cssom-view/#dom-element-scrollintoview
+    // http://dev.w3.org/csswg/cssom-view/#dom-element-scrollintoview
+    fn ScrollIntoView(self, top: bool) {
+        let win = window_from_node(self);
+        let node = NodeCast::from_ref(self);
+        // Elements without a box aren't scrolled to.
+        if let Some(box_model) = win.r().box_model_query(node.to_trusted_node_address()) {
+            win.r().scroll_rect_into_view(box_model.border_box, top);
+        }
+    }
+
     // https://dvcs.w3.org/hg/innerhtml/raw-file/tip/index.html#widl-Element-innerHTML
     fn GetInnerHTML(self) -> Fallible<DOMString> {
         //XXX TODO: XML case
@@ -1486,6 +1553,12 @@ impl<'a> VirtualMethods for &'a Element {
         }
 
         let node = NodeCast::from_ref(*self);
+        node.report_mutation_to_devtools(|target| NodeMutation::Attributes {
+            target: target,
+            attributeName: attr.Name(),
+            newValue: Some(attr.Value()),
+        });
+
         match attr.local_name() {
             &atom!("style") => {
                 // Modifying the `style` attribute might change style.
@@ -1534,6 +1607,12 @@ impl<'a> VirtualMethods for &'a Element {
         }
 
         let node = NodeCast::from_ref(*self);
+        node.report_mutation_to_devtools(|target| NodeMutation::Attributes {
+            target: target,
+            attributeName: attr.Name(),
+            newValue: None,
+        });
+
         match attr.local_name() {
             &atom!("style") => {
                 // Modifying the `style` attribute might change style.
@@ -29,7 +29,10 @@ pub enum EventPhase {
 
 #[derive(JSTraceable, PartialEq)]
 pub enum EventTypeId {
+    CompositionEvent,
     CustomEvent,
+    FocusEvent,
+    GamepadEvent,
     HTMLEvent,
     KeyboardEvent,
     MessageEvent,
@@ -47,8 +47,7 @@ pub fn dispatch_event<'a, 'b>(target: &'a EventTarget,
             Some(listeners) => {
                 event.set_current_target(cur_target.r());
                 for listener in listeners.iter() {
-                    // Explicitly drop any exception on the floor.
-                    let _ = listener.HandleEvent_(cur_target.r(), event, Report);
+                    listener.call_or_handle_event(cur_target.r(), event, Report);
 
                     if event.stop_immediate() {
                         break;
@@ -73,8 +72,7 @@ pub fn dispatch_event<'a, 'b>(target: &'a EventTarget,
         let opt_listeners = target.get_listeners(&type_);
         for listeners in opt_listeners.iter() {
             for listener in listeners.iter() {
-                // Explicitly drop any exception on the floor.
-                let _ = listener.HandleEvent_(target, event, Report);
+                listener.call_or_handle_event(target, event, Report);
 
                 if event.stop_immediate() {
                     break;
@@ -93,8 +91,7 @@ pub fn dispatch_event<'a, 'b>(target: &'a EventTarget,
                 Some(listeners) => {
                     event.set_current_target(cur_target.r());
                     for listener in listeners.iter() {
-                        // Explicitly drop any exception on the floor.
-                        let _ = listener.HandleEvent_(cur_target.r(), event, Report);
+                        listener.call_or_handle_event(cur_target.r(), event, Report);
 
                         if event.stop_immediate() {
                             break;
@@ -2,13 +2,19 @@
  * License, v. 2.0. If a copy of the MPL was not distributed with this
  * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
 
-use dom::bindings::callback::CallbackContainer;
+use dom::bindings::callback::{CallbackContainer, ExceptionHandling};
 use dom::bindings::cell::DOMRefCell;
+use dom::bindings::codegen::Bindings::ErrorEventBinding::ErrorEventMethods;
+use dom::bindings::codegen::Bindings::EventBinding::EventMethods;
 use dom::bindings::codegen::Bindings::EventHandlerBinding::EventHandlerNonNull;
+use dom::bindings::codegen::Bindings::EventHandlerBinding::OnErrorEventHandlerNonNull;
 use dom::bindings::codegen::Bindings::EventListenerBinding::EventListener;
 use dom::bindings::codegen::Bindings::EventTargetBinding::EventTargetMethods;
+use dom::bindings::codegen::InheritTypes::{ErrorEventCast, WindowCast};
+use dom::bindings::codegen::UnionTypes::EventOrString;
 use dom::bindings::error::{Fallible, report_pending_exception};
 use dom::bindings::error::Error::InvalidState;
+use dom::bindings::global::global_object_for_js_object;
 use dom::bindings::utils::{Reflectable, Reflector};
 use dom::event::{Event, EventHelpers};
 use dom::eventdispatcher::dispatch_event;
@@ -16,8 +22,9 @@ use dom::node::NodeTypeId;
 use dom::workerglobalscope::WorkerGlobalScopeTypeId;
 use dom::xmlhttprequesteventtarget::XMLHttpRequestEventTargetTypeId;
 use dom::virtualmethods::VirtualMethods;
+use dom::window::WindowHelpers;
 use js::jsapi::{CompileFunction, JS_GetFunctionObject};
-use js::jsapi::{JSContext, RootedFunction, HandleObject};
+use js::jsapi::{JSContext, RootedFunction, RootedValue, HandleObject};
 use js::jsapi::{JSAutoCompartment, JSAutoRequest};
 use js::rust::{AutoObjectVectorWrapper, CompileOptionsWrapper};
 use util::str::DOMString;
@@ -45,6 +52,7 @@ pub enum ListenerPhase {
 #[derive(JSTraceable, Copy, Clone)]
 pub enum EventTargetTypeId {
     Node(NodeTypeId),
+    Notification,
     WebSocket,
     Window,
     Worker,
@@ -101,6 +109,42 @@ impl EventListenerType {
             EventListenerType::Inline(ref listener) => listener.clone(),
         }
     }
+
+    /// Invokes this listener for the given event. An inline `onerror` handler on a window is
+    /// passed the details of an `ErrorEvent` as separate arguments, and cancels the event by
+    /// returning true.
+    /// https://html.spec.whatwg.org/multipage/#the-event-handler-processing-algorithm
+    pub fn call_or_handle_event(&self,
+                                object: &EventTarget,
+                                event: &Event,
+                                exception_handle: ExceptionHandling) {
+        if let EventListenerType::Inline(ref listener) = *self {
+            if *object.type_id() == EventTargetTypeId::Window && event.Type() == "error" {
+                if let Some(error_event) = ErrorEventCast::to_ref(event) {
+                    let handler: Rc<OnErrorEventHandlerNonNull> =
+                        CallbackContainer::new(listener.parent.callback());
+                    let reflector = object.reflector().get_jsobject();
+                    let global = global_object_for_js_object(reflector.get());
+                    let cx = global.r().get_cx();
+                    let error = RootedValue::new(cx, error_event.Error(cx));
+                    let return_value = handler.Call_(object,
+                                                     EventOrString::eString(error_event.Message()),
+                                                     Some(error_event.Filename()),
+                                                     Some(error_event.Lineno()),
+                                                     Some(error_event.Colno()),
+                                                     error.handle(),
+                                                     exception_handle);
+                    if let Ok(true) = return_value {
+                        event.PreventDefault();
+                    }
+                    return;
+                }
+            }
+        }
+
+        // Explicitly drop any exception on the floor.
+        let _ = self.get_listener().HandleEvent_(object, event, exception_handle);
+    }
 }
 
 #[derive(JSTraceable, Clone, PartialEq)]
@@ -126,17 +170,17 @@ impl EventTarget {
         }
     }
 
-    pub fn get_listeners(&self, type_: &str) -> Option<Vec<Rc<EventListener>>> {
+    pub fn get_listeners(&self, type_: &str) -> Option<Vec<EventListenerType>> {
         self.handlers.borrow().get(type_).map(|listeners| {
-            listeners.iter().map(|entry| entry.listener.get_listener()).collect()
+            listeners.iter().map(|entry| entry.listener.clone()).collect()
         })
     }
 
     pub fn get_listeners_for(&self, type_: &str, desired_phase: ListenerPhase)
-        -> Option<Vec<Rc<EventListener>>> {
+        -> Option<Vec<EventListenerType>> {
         self.handlers.borrow().get(type_).map(|listeners| {
             let filtered = listeners.iter().filter(|entry| entry.phase == desired_phase);
-            filtered.map(|entry| entry.listener.get_listener()).collect()
+            filtered.map(|entry| entry.listener.clone()).collect()
         })
     }
 
@@ -168,6 +212,15 @@ pub trait EventTargetHelpers {
     fn has_handlers(self) -> bool;
 }
 
+/// Lets `target` start any work that only listeners for events of type `ty` need.
+fn listener_added(target: &EventTarget, ty: &str) {
+    if ty == "gamepadconnected" || ty == "gamepaddisconnected" {
+        if let Some(window) = WindowCast::to_ref(target) {
+            window.start_gamepad_polling();
+        }
+    }
+}
+
 impl<'a> EventTargetHelpers for &'a EventTarget {
     fn dispatch_event_with_target(self,
                                   target: &EventTarget,
@@ -182,6 +235,9 @@ impl<'a> EventTargetHelpers for &'a EventTarget {
     fn set_inline_event_listener(self,
                                  ty: DOMString,
                                  listener: Option<Rc<EventListener>>) {
+        if listener.is_some() {
+            listener_added(self, &ty);
+        }
         let mut handlers = self.handlers.borrow_mut();
         let entries = match handlers.entry(ty) {
             Occupied(entry) => entry.into_mut(),
@@ -295,6 +351,7 @@ impl<'a> EventTargetMethods for &'a EventTarget {
                         capture: bool) {
         match listener {
             Some(listener) => {
+                listener_added(self, &ty);
                 let mut handlers = self.handlers.borrow_mut();
                 let entry = match handlers.entry(ty) {
                     Occupied(entry) => entry.into_mut(),
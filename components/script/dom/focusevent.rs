@@ -0,0 +1,92 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+use dom::bindings::codegen::Bindings::FocusEventBinding;
+use dom::bindings::codegen::Bindings::FocusEventBinding::FocusEventMethods;
+use dom::bindings::codegen::Bindings::UIEventBinding::UIEventMethods;
+use dom::bindings::codegen::InheritTypes::{UIEventCast, FocusEventDerived};
+use dom::bindings::error::Fallible;
+use dom::bindings::global::GlobalRef;
+use dom::bindings::js::{JS, MutNullableHeap, Root, RootedReference};
+use dom::bindings::utils::reflect_dom_object;
+use dom::event::{Event, EventTypeId, EventBubbles, EventCancelable};
+use dom::eventtarget::EventTarget;
+use dom::uievent::UIEvent;
+use dom::window::Window;
+use util::str::DOMString;
+
+use std::default::Default;
+
+// https://dvcs.w3.org/hg/dom3events/raw-file/tip/html/DOM3-Events.html#interface-FocusEvent
+#[dom_struct]
+pub struct FocusEvent {
+    uievent: UIEvent,
+    related_target: MutNullableHeap<JS<EventTarget>>,
+}
+
+impl FocusEventDerived for Event {
+    fn is_focusevent(&self) -> bool {
+        *self.type_id() == EventTypeId::FocusEvent
+    }
+}
+
+impl FocusEvent {
+    fn new_inherited() -> FocusEvent {
+        FocusEvent {
+            uievent: UIEvent::new_inherited(EventTypeId::FocusEvent),
+            related_target: Default::default(),
+        }
+    }
+
+    pub fn new_uninitialized(window: &Window) -> Root<FocusEvent> {
+        reflect_dom_object(box FocusEvent::new_inherited(),
+                           GlobalRef::Window(window),
+                           FocusEventBinding::Wrap)
+    }
+
+    pub fn new(window: &Window,
+               type_: DOMString,
+               can_bubble: EventBubbles,
+               cancelable: EventCancelable,
+               view: Option<&Window>,
+               detail: i32,
+               related_target: Option<&EventTarget>) -> Root<FocusEvent> {
+        let ev = FocusEvent::new_uninitialized(window);
+        UIEventCast::from_ref(ev.r()).InitUIEvent(type_,
+                                                  can_bubble == EventBubbles::Bubbles,
+                                                  cancelable == EventCancelable::Cancelable,
+                                                  view, detail);
+        ev.r().related_target.set(related_target.map(JS::from_ref));
+        ev
+    }
+
+    pub fn Constructor(global: GlobalRef,
+                       type_: DOMString,
+                       init: &FocusEventBinding::FocusEventInit) -> Fallible<Root<FocusEvent>> {
+        let bubbles = if init.parent.parent.bubbles {
+            EventBubbles::Bubbles
+        } else {
+            EventBubbles::DoesNotBubble
+        };
+        let cancelable = if init.parent.parent.cancelable {
+            EventCancelable::Cancelable
+        } else {
+            EventCancelable::NotCancelable
+        };
+        let event = FocusEvent::new(global.as_window(), type_,
+                                    bubbles,
+                                    cancelable,
+                                    init.parent.view.r(),
+                                    init.parent.detail,
+                                    init.relatedTarget.r());
+        Ok(event)
+    }
+}
+
+impl<'a> FocusEventMethods for &'a FocusEvent {
+    // https://dvcs.w3.org/hg/dom3events/raw-file/tip/html/DOM3-Events.html#widl-FocusEvent-relatedTarget
+    fn GetRelatedTarget(self) -> Option<Root<EventTarget>> {
+        self.related_target.get().map(Root::from_rooted)
+    }
+}
@@ -0,0 +1,127 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+use dom::bindings::codegen::Bindings::GamepadBinding;
+use dom::bindings::codegen::Bindings::GamepadBinding::GamepadMethods;
+use dom::bindings::global::GlobalRef;
+use dom::bindings::js::{JS, Root};
+use dom::bindings::num::Finite;
+use dom::bindings::utils::{Reflectable, Reflector, reflect_dom_object};
+use dom::gamepadbuttonlist::{GamepadButtonList, GamepadButtonListHelpers};
+use dom::window::Window;
+use gamepad::GamepadState;
+use util::str::DOMString;
+
+use js::jsapi::{JSAutoCompartment, JSAutoRequest, JSContext, JSObject, Heap};
+use js::jsapi::{JS_NewFloat64Array, JS_GetFloat64ArrayData};
+
+use std::borrow::ToOwned;
+use std::cell::Cell;
+use std::cmp;
+use std::ptr;
+
+// https://w3c.github.io/gamepad/#gamepad-interface
+#[dom_struct]
+#[allow(raw_pointer_derive)]
+pub struct Gamepad {
+    reflector_: Reflector,
+    id: DOMString,
+    index: i32,
+    connected: Cell<bool>,
+    timestamp: Cell<f64>,
+    /// A Float64Array holding the value of each axis.
+    axes: Heap<*mut JSObject>,
+    axis_count: usize,
+    buttons: JS<GamepadButtonList>,
+}
+
+impl Gamepad {
+    #[allow(unsafe_code)]
+    pub fn new(window: &Window, index: i32, state: &GamepadState, timestamp: f64) -> Root<Gamepad> {
+        let buttons = GamepadButtonList::new(window, state.buttons.len());
+        let gamepad = box Gamepad {
+            reflector_: Reflector::new(),
+            id: state.id.clone(),
+            index: index,
+            connected: Cell::new(true),
+            timestamp: Cell::new(0.),
+            axes: Heap::default(),
+            axis_count: state.axes.len(),
+            buttons: JS::from_rooted(&buttons),
+        };
+        let gamepad = reflect_dom_object(gamepad, GlobalRef::Window(window), GamepadBinding::Wrap);
+
+        // The array is only reachable through the traced `axes` field once the gamepad is
+        // reflected, so it must not be created any earlier.
+        unsafe {
+            let cx = window.get_cx();
+            let _ar = JSAutoRequest::new(cx);
+            let _ac = JSAutoCompartment::new(cx, gamepad.r().reflector().get_jsobject().get());
+            gamepad.r().axes.set(JS_NewFloat64Array(cx, state.axes.len() as u32));
+        }
+        gamepad.r().update(state, timestamp);
+        gamepad
+    }
+}
+
+pub trait GamepadHelpers {
+    fn update(self, state: &GamepadState, timestamp: f64);
+    fn set_disconnected(self);
+}
+
+impl<'a> GamepadHelpers for &'a Gamepad {
+    /// Copies `state` into this object; `timestamp` is relative to navigation start.
+    #[allow(unsafe_code)]
+    fn update(self, state: &GamepadState, timestamp: f64) {
+        self.timestamp.set(timestamp);
+        unsafe {
+            let axes = JS_GetFloat64ArrayData(self.axes.get(), ptr::null());
+            let count = cmp::min(self.axis_count, state.axes.len());
+            ptr::copy_nonoverlapping(state.axes.as_ptr(), axes, count);
+        }
+        self.buttons.root().r().update(&state.buttons);
+    }
+
+    fn set_disconnected(self) {
+        self.connected.set(false);
+    }
+}
+
+impl<'a> GamepadMethods for &'a Gamepad {
+    // https://w3c.github.io/gamepad/#widl-Gamepad-id
+    fn Id(self) -> DOMString {
+        self.id.clone()
+    }
+
+    // https://w3c.github.io/gamepad/#widl-Gamepad-index
+    fn Index(self) -> i32 {
+        self.index
+    }
+
+    // https://w3c.github.io/gamepad/#widl-Gamepad-connected
+    fn Connected(self) -> bool {
+        self.connected.get()
+    }
+
+    // https://w3c.github.io/gamepad/#widl-Gamepad-timestamp
+    fn Timestamp(self) -> Finite<f64> {
+        Finite::wrap(self.timestamp.get())
+    }
+
+    // https://w3c.github.io/gamepad/#widl-Gamepad-mapping
+    fn Mapping(self) -> DOMString {
+        // We don't remap any controller to the standard layout.
+        "".to_owned()
+    }
+
+    // https://w3c.github.io/gamepad/#widl-Gamepad-axes
+    fn Axes(self, _: *mut JSContext) -> *mut JSObject {
+        self.axes.get()
+    }
+
+    // https://w3c.github.io/gamepad/#widl-Gamepad-buttons
+    fn Buttons(self) -> Root<GamepadButtonList> {
+        self.buttons.root()
+    }
+}
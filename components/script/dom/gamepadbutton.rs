@@ -0,0 +1,61 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+use dom::bindings::codegen::Bindings::GamepadButtonBinding;
+use dom::bindings::codegen::Bindings::GamepadButtonBinding::GamepadButtonMethods;
+use dom::bindings::global::GlobalRef;
+use dom::bindings::js::Root;
+use dom::bindings::num::Finite;
+use dom::bindings::utils::{Reflector, reflect_dom_object};
+use dom::window::Window;
+use gamepad::GamepadButtonState;
+
+use std::cell::Cell;
+
+// https://w3c.github.io/gamepad/#gamepadbutton-interface
+#[dom_struct]
+pub struct GamepadButton {
+    reflector_: Reflector,
+    pressed: Cell<bool>,
+    value: Cell<f64>,
+}
+
+impl GamepadButton {
+    fn new_inherited() -> GamepadButton {
+        GamepadButton {
+            reflector_: Reflector::new(),
+            pressed: Cell::new(false),
+            value: Cell::new(0.),
+        }
+    }
+
+    pub fn new(window: &Window) -> Root<GamepadButton> {
+        reflect_dom_object(box GamepadButton::new_inherited(),
+                           GlobalRef::Window(window),
+                           GamepadButtonBinding::Wrap)
+    }
+}
+
+pub trait GamepadButtonHelpers {
+    fn update(self, state: &GamepadButtonState);
+}
+
+impl<'a> GamepadButtonHelpers for &'a GamepadButton {
+    fn update(self, state: &GamepadButtonState) {
+        self.pressed.set(state.pressed);
+        self.value.set(state.value);
+    }
+}
+
+impl<'a> GamepadButtonMethods for &'a GamepadButton {
+    // https://w3c.github.io/gamepad/#widl-GamepadButton-pressed
+    fn Pressed(self) -> bool {
+        self.pressed.get()
+    }
+
+    // https://w3c.github.io/gamepad/#widl-GamepadButton-value
+    fn Value(self) -> Finite<f64> {
+        Finite::wrap(self.value.get())
+    }
+}
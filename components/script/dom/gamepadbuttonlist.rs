@@ -0,0 +1,59 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+use dom::bindings::codegen::Bindings::GamepadButtonListBinding;
+use dom::bindings::codegen::Bindings::GamepadButtonListBinding::GamepadButtonListMethods;
+use dom::bindings::global::GlobalRef;
+use dom::bindings::js::{JS, Root};
+use dom::bindings::utils::{Reflector, reflect_dom_object};
+use dom::gamepadbutton::{GamepadButton, GamepadButtonHelpers};
+use dom::window::Window;
+use gamepad::GamepadButtonState;
+
+#[dom_struct]
+pub struct GamepadButtonList {
+    reflector_: Reflector,
+    buttons: Vec<JS<GamepadButton>>,
+}
+
+impl GamepadButtonList {
+    fn new_inherited(window: &Window, length: usize) -> GamepadButtonList {
+        GamepadButtonList {
+            reflector_: Reflector::new(),
+            buttons: (0..length).map(|_| JS::from_rooted(&GamepadButton::new(window))).collect(),
+        }
+    }
+
+    pub fn new(window: &Window, length: usize) -> Root<GamepadButtonList> {
+        reflect_dom_object(box GamepadButtonList::new_inherited(window, length),
+                           GlobalRef::Window(window), GamepadButtonListBinding::Wrap)
+    }
+}
+
+pub trait GamepadButtonListHelpers {
+    fn update(self, states: &[GamepadButtonState]);
+}
+
+impl<'a> GamepadButtonListHelpers for &'a GamepadButtonList {
+    fn update(self, states: &[GamepadButtonState]) {
+        for (button, state) in self.buttons.iter().zip(states.iter()) {
+            button.root().r().update(state);
+        }
+    }
+}
+
+impl<'a> GamepadButtonListMethods for &'a GamepadButtonList {
+    fn Length(self) -> u32 {
+        self.buttons.len() as u32
+    }
+
+    fn Item(self, index: u32) -> Option<Root<GamepadButton>> {
+        self.buttons.get(index as usize).map(|button| button.root())
+    }
+
+    fn IndexedGetter(self, index: u32, found: &mut bool) -> Option<Root<GamepadButton>> {
+        *found = index < self.buttons.len() as u32;
+        self.Item(index)
+    }
+}
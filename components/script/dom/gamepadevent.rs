@@ -0,0 +1,75 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+use dom::bindings::codegen::Bindings::EventBinding::EventMethods;
+use dom::bindings::codegen::Bindings::GamepadEventBinding;
+use dom::bindings::codegen::Bindings::GamepadEventBinding::GamepadEventMethods;
+use dom::bindings::codegen::InheritTypes::EventCast;
+use dom::bindings::error::Fallible;
+use dom::bindings::global::GlobalRef;
+use dom::bindings::js::{JS, MutNullableHeap, Root, RootedReference};
+use dom::bindings::utils::reflect_dom_object;
+use dom::event::{Event, EventTypeId, EventBubbles, EventCancelable};
+use dom::gamepad::Gamepad;
+use util::str::DOMString;
+
+use std::default::Default;
+
+// https://w3c.github.io/gamepad/#gamepadevent-interface
+#[dom_struct]
+pub struct GamepadEvent {
+    event: Event,
+    gamepad: MutNullableHeap<JS<Gamepad>>,
+}
+
+impl GamepadEvent {
+    fn new_inherited() -> GamepadEvent {
+        GamepadEvent {
+            event: Event::new_inherited(EventTypeId::GamepadEvent),
+            gamepad: Default::default(),
+        }
+    }
+
+    pub fn new(global: GlobalRef,
+               type_: DOMString,
+               bubbles: EventBubbles,
+               cancelable: EventCancelable,
+               gamepad: Option<&Gamepad>) -> Root<GamepadEvent> {
+        let ev = reflect_dom_object(box GamepadEvent::new_inherited(),
+                                    global,
+                                    GamepadEventBinding::Wrap);
+        {
+            let event = EventCast::from_ref(ev.r());
+            event.InitEvent(type_,
+                            bubbles == EventBubbles::Bubbles,
+                            cancelable == EventCancelable::Cancelable);
+        }
+        ev.r().gamepad.set(gamepad.map(JS::from_ref));
+        ev
+    }
+
+    pub fn Constructor(global: GlobalRef,
+                       type_: DOMString,
+                       init: &GamepadEventBinding::GamepadEventInit)
+                       -> Fallible<Root<GamepadEvent>> {
+        let bubbles = if init.parent.bubbles {
+            EventBubbles::Bubbles
+        } else {
+            EventBubbles::DoesNotBubble
+        };
+        let cancelable = if init.parent.cancelable {
+            EventCancelable::Cancelable
+        } else {
+            EventCancelable::NotCancelable
+        };
+        Ok(GamepadEvent::new(global, type_, bubbles, cancelable, init.gamepad.r()))
+    }
+}
+
+impl<'a> GamepadEventMethods for &'a GamepadEvent {
+    // https://w3c.github.io/gamepad/#widl-GamepadEvent-gamepad
+    fn GetGamepad(self) -> Option<Root<Gamepad>> {
+        self.gamepad.get().map(Root::from_rooted)
+    }
+}
@@ -0,0 +1,46 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+use dom::bindings::codegen::Bindings::GamepadListBinding;
+use dom::bindings::codegen::Bindings::GamepadListBinding::GamepadListMethods;
+use dom::bindings::global::GlobalRef;
+use dom::bindings::js::{JS, Root};
+use dom::bindings::utils::{Reflector, reflect_dom_object};
+use dom::gamepad::Gamepad;
+use dom::window::Window;
+
+#[dom_struct]
+pub struct GamepadList {
+    reflector_: Reflector,
+    gamepads: Vec<Option<JS<Gamepad>>>,
+}
+
+impl GamepadList {
+    fn new_inherited(gamepads: &[Option<Root<Gamepad>>]) -> GamepadList {
+        GamepadList {
+            reflector_: Reflector::new(),
+            gamepads: gamepads.iter().map(|g| g.as_ref().map(JS::from_rooted)).collect(),
+        }
+    }
+
+    pub fn new(window: &Window, gamepads: &[Option<Root<Gamepad>>]) -> Root<GamepadList> {
+        reflect_dom_object(box GamepadList::new_inherited(gamepads),
+                           GlobalRef::Window(window), GamepadListBinding::Wrap)
+    }
+}
+
+impl<'a> GamepadListMethods for &'a GamepadList {
+    fn Length(self) -> u32 {
+        self.gamepads.len() as u32
+    }
+
+    fn Item(self, index: u32) -> Option<Root<Gamepad>> {
+        self.gamepads.get(index as usize).and_then(|g| g.as_ref().map(|g| g.root()))
+    }
+
+    fn IndexedGetter(self, index: u32, found: &mut bool) -> Option<Root<Gamepad>> {
+        *found = index < self.gamepads.len() as u32;
+        self.Item(index)
+    }
+}
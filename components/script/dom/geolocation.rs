@@ -0,0 +1,471 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+use dom::bindings::callback::ExceptionHandling;
+use dom::bindings::cell::DOMRefCell;
+use dom::bindings::codegen::Bindings::GeolocationBinding;
+use dom::bindings::codegen::Bindings::GeolocationBinding::{GeolocationMethods, PositionCallback};
+use dom::bindings::codegen::Bindings::GeolocationBinding::{PositionErrorCallback, PositionOptions};
+use dom::bindings::global::GlobalRef;
+use dom::bindings::js::{JS, Root};
+use dom::bindings::refcounted::Trusted;
+use dom::bindings::utils::{Reflector, reflect_dom_object};
+use dom::position::Position;
+use dom::positionerror::{PositionError, PositionErrorCode};
+use dom::window::{Window, WindowHelpers};
+use script_task::{Runnable, ScriptChan, ScriptMsg};
+
+use msg::constellation_msg::{ConstellationChan, GeolocationPosition, PermissionName};
+use msg::constellation_msg::Msg as ConstellationMsg;
+use util::task::spawn_named;
+
+use std::borrow::ToOwned;
+use std::cell::Cell;
+use std::rc::Rc;
+use std::sync::{Arc, Condvar, Mutex};
+use time;
+
+/// How long a watch waits after receiving a position before asking for the next one.
+const WATCH_INTERVAL_MS: u32 = 1000;
+
+/// What a request is waiting for.
+#[derive(JSTraceable, Copy, Clone, PartialEq)]
+enum RequestState {
+    /// An `Acquire` task, which will look for a position.
+    Queued,
+    /// The user's answer to the permission request.
+    WaitingForPermission,
+    /// A position from the embedder.
+    WaitingForPosition,
+}
+
+/// A call to `getCurrentPosition` or `watchPosition` that is still waiting for a position.
+#[derive(JSTraceable)]
+struct PositionRequest {
+    id: i32,
+    success_callback: Rc<PositionCallback>,
+    error_callback: Option<Rc<PositionErrorCallback>>,
+    enable_high_accuracy: bool,
+    timeout: u32,
+    maximum_age: u32,
+    /// Whether this request came from `watchPosition`, and so lasts until `clearWatch`.
+    watch: bool,
+    state: RequestState,
+    /// Counts the positions asked for, so that a timeout set for an earlier one is ignored.
+    generation: u32,
+    /// The position last passed to the success callback of a watch.
+    last_position: Option<GeolocationPosition>,
+}
+
+// http://dev.w3.org/geo/api/spec-source.html#geolocation_interface
+#[dom_struct]
+pub struct Geolocation {
+    reflector_: Reflector,
+    window: JS<Window>,
+    /// Whether the embedder allowed this document to use the user's location, or `None` if it
+    /// hasn't been asked yet.
+    permission: Cell<Option<bool>>,
+    /// Whether permission has been asked for and not answered yet.
+    permission_requested: Cell<bool>,
+    /// Whether a position has been asked for and not received yet.
+    position_requested: Cell<bool>,
+    /// The last position received, for requests that accept a cached position.
+    cached_position: Cell<Option<GeolocationPosition>>,
+    requests: DOMRefCell<Vec<PositionRequest>>,
+    /// The ID to give to the next request. Watch IDs are returned to script.
+    next_id: Cell<i32>,
+    /// Runs the timeouts and watch intervals of every request, once one has been set.
+    timer: DOMRefCell<Option<TaskTimer>>,
+}
+
+impl Geolocation {
+    fn new_inherited(window: &Window) -> Geolocation {
+        Geolocation {
+            reflector_: Reflector::new(),
+            window: JS::from_ref(window),
+            permission: Cell::new(None),
+            permission_requested: Cell::new(false),
+            position_requested: Cell::new(false),
+            cached_position: Cell::new(None),
+            requests: DOMRefCell::new(vec!()),
+            next_id: Cell::new(1),
+            timer: DOMRefCell::new(None),
+        }
+    }
+
+    pub fn new(window: &Window) -> Root<Geolocation> {
+        reflect_dom_object(box Geolocation::new_inherited(window),
+                           GlobalRef::Window(window),
+                           GeolocationBinding::Wrap)
+    }
+}
+
+/// The steps of a request that run as tasks, after `getCurrentPosition` or `watchPosition` has
+/// returned.
+enum GeolocationTask {
+    /// Start looking for a position for the given request.
+    Acquire(i32),
+    /// The given request timed out, unless it has asked for another position since.
+    Timeout(i32, u32),
+}
+
+/// Sends runnables to the script task once they are due. One thread waits for all of a
+/// `Geolocation`'s delayed tasks, however many requests and watches there are.
+struct TaskTimer {
+    state: Arc<(Mutex<TaskTimerState>, Condvar)>,
+}
+
+no_jsmanaged_fields!(TaskTimer);
+
+struct TaskTimerState {
+    /// The runnables to send, with the times they are due in milliseconds, earliest first.
+    pending: Vec<(u64, Box<Runnable + Send>)>,
+    /// Whether the timer was dropped, and so its thread should exit.
+    stopped: bool,
+}
+
+fn now_ms() -> u64 {
+    time::precise_time_ns() / 1000000
+}
+
+impl TaskTimer {
+    fn new(script_chan: Box<ScriptChan + Send>) -> TaskTimer {
+        let state = Arc::new((Mutex::new(TaskTimerState { pending: vec!(), stopped: false }),
+                              Condvar::new()));
+        let thread_state = state.clone();
+        spawn_named("Geolocation:Timer".to_owned(), move || {
+            let (ref lock, ref condvar) = *thread_state;
+            let mut state = lock.lock().unwrap();
+            while !state.stopped {
+                let now = now_ms();
+                let next_due = state.pending.first().map(|&(due, _)| due);
+                state = match next_due {
+                    Some(due) if due <= now => {
+                        let (_, runnable) = state.pending.remove(0);
+                        // The script task may have exited while we waited.
+                        if script_chan.send(ScriptMsg::RunnableMsg(runnable)).is_err() {
+                            return;
+                        }
+                        state
+                    }
+                    Some(due) => condvar.wait_timeout_ms(state, (due - now) as u32).unwrap().0,
+                    None => condvar.wait(state).unwrap(),
+                };
+            }
+        });
+        TaskTimer {
+            state: state,
+        }
+    }
+
+    fn schedule(&self, runnable: Box<Runnable + Send>, delay_ms: u32) {
+        let (ref lock, ref condvar) = *self.state;
+        let mut state = lock.lock().unwrap();
+        let due = now_ms() + delay_ms as u64;
+        let index = state.pending.iter().position(|&(other, _)| other > due)
+                                        .unwrap_or(state.pending.len());
+        state.pending.insert(index, (due, runnable));
+        condvar.notify_one();
+    }
+}
+
+impl Drop for TaskTimer {
+    fn drop(&mut self) {
+        let (ref lock, ref condvar) = *self.state;
+        lock.lock().unwrap().stopped = true;
+        condvar.notify_one();
+    }
+}
+
+struct GeolocationRunnable {
+    geolocation: Trusted<Geolocation>,
+    task: GeolocationTask,
+}
+
+impl Runnable for GeolocationRunnable {
+    fn handler(self: Box<GeolocationRunnable>) {
+        let this = *self;
+        let geolocation = this.geolocation.root();
+        match this.task {
+            GeolocationTask::Acquire(id) => geolocation.r().acquire(id),
+            GeolocationTask::Timeout(id, generation) => {
+                geolocation.r().handle_timeout(id, generation)
+            }
+        }
+    }
+}
+
+pub trait GeolocationHelpers {
+    fn handle_permission(self, granted: bool);
+    fn handle_position(self, position: Option<GeolocationPosition>);
+}
+
+impl<'a> GeolocationHelpers for &'a Geolocation {
+    /// Handles the user's answer to a permission request, by looking for a position for the
+    /// requests that were waiting for it.
+    fn handle_permission(self, granted: bool) {
+        self.permission.set(Some(granted));
+        self.permission_requested.set(false);
+        let ids: Vec<i32> = {
+            let mut requests = self.requests.borrow_mut();
+            requests.iter_mut().filter(|r| r.state == RequestState::WaitingForPermission)
+                               .map(|r| { r.state = RequestState::Queued; r.id })
+                               .collect()
+        };
+        for id in ids {
+            self.acquire(id);
+        }
+    }
+
+    /// Passes a position from the embedder to every request waiting for one.
+    fn handle_position(self, position: Option<GeolocationPosition>) {
+        self.position_requested.set(false);
+        if position.is_some() {
+            self.cached_position.set(position);
+        }
+        let ids: Vec<i32> = self.requests.borrow().iter()
+                                .filter(|r| r.state == RequestState::WaitingForPosition)
+                                .map(|r| r.id)
+                                .collect();
+        for id in ids {
+            match position {
+                Some(position) => self.succeed(id, position),
+                None => self.fail(id, PositionErrorCode::PositionUnavailable),
+            }
+            self.finish(id);
+        }
+    }
+}
+
+trait PrivateGeolocationHelpers {
+    fn add_request(self,
+                   success_callback: Rc<PositionCallback>,
+                   error_callback: Option<Rc<PositionErrorCallback>>,
+                   options: &PositionOptions,
+                   watch: bool) -> i32;
+    fn queue_task(self, task: GeolocationTask, delay_ms: u32);
+    fn acquire(self, id: i32);
+    fn handle_timeout(self, id: i32, generation: u32);
+    fn succeed(self, id: i32, position: GeolocationPosition);
+    fn fail(self, id: i32, code: PositionErrorCode);
+    fn finish(self, id: i32);
+    fn set_state(self, id: i32, state: RequestState);
+}
+
+impl<'a> PrivateGeolocationHelpers for &'a Geolocation {
+    fn add_request(self,
+                   success_callback: Rc<PositionCallback>,
+                   error_callback: Option<Rc<PositionErrorCallback>>,
+                   options: &PositionOptions,
+                   watch: bool) -> i32 {
+        let id = self.next_id.get();
+        self.next_id.set(id + 1);
+        self.requests.borrow_mut().push(PositionRequest {
+            id: id,
+            success_callback: success_callback,
+            error_callback: error_callback,
+            enable_high_accuracy: options.enableHighAccuracy,
+            timeout: options.timeout,
+            maximum_age: options.maximumAge,
+            watch: watch,
+            state: RequestState::Queued,
+            generation: 0,
+            last_position: None,
+        });
+        self.queue_task(GeolocationTask::Acquire(id), 0);
+        id
+    }
+
+    /// Queues a task on the DOM manipulation task source, after `delay_ms` milliseconds.
+    fn queue_task(self, task: GeolocationTask, delay_ms: u32) {
+        let window = self.window.root();
+        let script_chan = window.r().script_chan();
+        let runnable = box GeolocationRunnable {
+            geolocation: Trusted::new(window.r().get_cx(), self, script_chan.clone()),
+            task: task,
+        };
+        if delay_ms == 0 {
+            script_chan.send(ScriptMsg::RunnableMsg(runnable)).unwrap();
+            return;
+        }
+        let mut timer = self.timer.borrow_mut();
+        if timer.is_none() {
+            *timer = Some(TaskTimer::new(script_chan));
+        }
+        timer.as_ref().unwrap().schedule(runnable, delay_ms);
+    }
+
+    /// Looks for a position for a queued request: asks the user for permission if they
+    /// haven't been asked, uses the cached position if it is recent enough, and otherwise asks
+    /// the embedder for a new one.
+    fn acquire(self, id: i32) {
+        // The request may have been cleared, or already be waiting for an answer.
+        let queued = self.requests.borrow().iter().any(|r| {
+            r.id == id && r.state == RequestState::Queued
+        });
+        if !queued {
+            return;
+        }
+
+        let window = self.window.root();
+        let ConstellationChan(ref chan) = window.r().constellation_chan();
+
+        match self.permission.get() {
+            None => {
+                self.set_state(id, RequestState::WaitingForPermission);
+                if !self.permission_requested.get() {
+                    self.permission_requested.set(true);
+                    let msg = ConstellationMsg::RequestPermission(window.r().pipeline(),
+                                                                  PermissionName::Geolocation,
+                                                                  window.r().get_url());
+                    chan.send(msg).unwrap();
+                }
+                return;
+            }
+            Some(false) => {
+                self.fail(id, PositionErrorCode::PermissionDenied);
+                // Without permission, a watch will never receive a position.
+                self.requests.borrow_mut().retain(|r| r.id != id);
+                return;
+            }
+            Some(true) => {}
+        }
+
+        // http://dev.w3.org/geo/api/spec-source.html#max-age
+        let (maximum_age, watch) = match self.requests.borrow().iter().find(|r| r.id == id) {
+            Some(request) => (request.maximum_age, request.watch),
+            None => return,
+        };
+        if let Some(cached) = self.cached_position.get() {
+            let now = time::get_time();
+            let now = now.sec as u64 * 1000 + now.nsec as u64 / 1000000;
+            if now.saturating_sub(cached.timestamp) <= maximum_age as u64 {
+                self.succeed(id, cached);
+                // A watch goes on to look for newer positions.
+                if !watch {
+                    return self.finish(id);
+                }
+            }
+        }
+
+        let (timeout, generation, enable_high_accuracy) = {
+            let mut requests = self.requests.borrow_mut();
+            let request = match requests.iter_mut().find(|r| r.id == id) {
+                Some(request) => request,
+                None => return,
+            };
+            request.state = RequestState::WaitingForPosition;
+            request.generation += 1;
+            (request.timeout, request.generation, request.enable_high_accuracy)
+        };
+
+        // http://dev.w3.org/geo/api/spec-source.html#timeout
+        if timeout == 0 {
+            self.fail(id, PositionErrorCode::Timeout);
+            return self.finish(id);
+        }
+        if timeout != u32::max_value() {
+            self.queue_task(GeolocationTask::Timeout(id, generation), timeout);
+        }
+
+        if !self.position_requested.get() {
+            self.position_requested.set(true);
+            let msg = ConstellationMsg::RequestGeolocationPosition(window.r().pipeline(),
+                                                                   enable_high_accuracy);
+            chan.send(msg).unwrap();
+        }
+    }
+
+    fn handle_timeout(self, id: i32, generation: u32) {
+        let timed_out = self.requests.borrow().iter().any(|r| {
+            r.id == id && r.state == RequestState::WaitingForPosition &&
+            r.generation == generation
+        });
+        if timed_out {
+            self.fail(id, PositionErrorCode::Timeout);
+            self.finish(id);
+        }
+    }
+
+    /// Passes a position to a request's success callback. A watch's callback is only called
+    /// when the position has changed.
+    fn succeed(self, id: i32, position: GeolocationPosition) {
+        let callback = {
+            let mut requests = self.requests.borrow_mut();
+            let request = match requests.iter_mut().find(|r| r.id == id) {
+                Some(request) => request,
+                None => return,
+            };
+            if request.last_position == Some(position) {
+                None
+            } else {
+                request.last_position = Some(position);
+                Some(request.success_callback.clone())
+            }
+        };
+        if let Some(callback) = callback {
+            let window = self.window.root();
+            let position = Position::new(window.r(), position);
+            let _ = callback.Call__(position.r(), ExceptionHandling::Report);
+        }
+    }
+
+    /// Passes an error to a request's error callback, if it has one.
+    fn fail(self, id: i32, code: PositionErrorCode) {
+        let callback = {
+            let mut requests = self.requests.borrow_mut();
+            let request = match requests.iter_mut().find(|r| r.id == id) {
+                Some(request) => request,
+                None => return,
+            };
+            request.error_callback.clone()
+        };
+        if let Some(callback) = callback {
+            let window = self.window.root();
+            let error = PositionError::new(window.r(), code);
+            let _ = callback.Call__(error.r(), ExceptionHandling::Report);
+        }
+    }
+
+    /// Removes a request that has been answered, or asks for the next position for a watch.
+    /// This runs after the request's callback, which may have cleared the watch.
+    fn finish(self, id: i32) {
+        let watch = self.requests.borrow().iter().any(|r| r.id == id && r.watch);
+        if watch {
+            self.set_state(id, RequestState::Queued);
+            self.queue_task(GeolocationTask::Acquire(id), WATCH_INTERVAL_MS);
+        } else {
+            self.requests.borrow_mut().retain(|r| r.id != id);
+        }
+    }
+
+    fn set_state(self, id: i32, state: RequestState) {
+        if let Some(request) = self.requests.borrow_mut().iter_mut().find(|r| r.id == id) {
+            request.state = state;
+        }
+    }
+}
+
+impl<'a> GeolocationMethods for &'a Geolocation {
+    // http://dev.w3.org/geo/api/spec-source.html#get-current-position
+    fn GetCurrentPosition(self,
+                          success_callback: Rc<PositionCallback>,
+                          error_callback: Option<Rc<PositionErrorCallback>>,
+                          options: &PositionOptions) {
+        self.add_request(success_callback, error_callback, options, false);
+    }
+
+    // http://dev.w3.org/geo/api/spec-source.html#watch-position
+    fn WatchPosition(self,
+                     success_callback: Rc<PositionCallback>,
+                     error_callback: Option<Rc<PositionErrorCallback>>,
+                     options: &PositionOptions) -> i32 {
+        self.add_request(success_callback, error_callback, options, true)
+    }
+
+    // http://dev.w3.org/geo/api/spec-source.html#clear-watch
+    fn ClearWatch(self, watch_id: i32) {
+        self.requests.borrow_mut().retain(|r| !(r.id == watch_id && r.watch));
+    }
+}
@@ -0,0 +1,79 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+use dom::bindings::codegen::Bindings::HistoryBinding;
+use dom::bindings::codegen::Bindings::HistoryBinding::HistoryMethods;
+use dom::bindings::global::GlobalRef;
+use dom::bindings::js::{JS, Root};
+use dom::bindings::utils::{Reflector, reflect_dom_object};
+use dom::window::Window;
+use dom::window::WindowHelpers;
+
+use msg::constellation_msg::ConstellationChan;
+use msg::constellation_msg::Msg as ConstellationMsg;
+use std::sync::mpsc::channel;
+
+#[dom_struct]
+pub struct History {
+    reflector_: Reflector,
+    window: JS<Window>,
+}
+
+impl History {
+    fn new_inherited(window: &Window) -> History {
+        History {
+            reflector_: Reflector::new(),
+            window: JS::from_ref(window),
+        }
+    }
+
+    pub fn new(window: &Window) -> Root<History> {
+        reflect_dom_object(box History::new_inherited(window),
+                           GlobalRef::Window(window),
+                           HistoryBinding::Wrap)
+    }
+}
+
+trait PrivateHistoryHelpers {
+    fn traverse_history(self, delta: i32);
+}
+
+impl<'a> PrivateHistoryHelpers for &'a History {
+    // https://html.spec.whatwg.org/multipage/#traverse-the-history-by-a-delta
+    fn traverse_history(self, delta: i32) {
+        let window = self.window.root();
+        let ConstellationChan(ref chan) = window.r().constellation_chan();
+        chan.send(ConstellationMsg::TraverseHistory(window.r().pipeline(),
+                                                    delta as isize)).unwrap();
+    }
+}
+
+impl<'a> HistoryMethods for &'a History {
+    // https://html.spec.whatwg.org/multipage/#dom-history-length
+    fn Length(self) -> u32 {
+        let window = self.window.root();
+        let ConstellationChan(ref chan) = window.r().constellation_chan();
+        let (sender, receiver) = channel();
+        chan.send(ConstellationMsg::GetHistoryLength(window.r().pipeline(), sender)).unwrap();
+        receiver.recv().unwrap()
+    }
+
+    // https://html.spec.whatwg.org/multipage/#dom-history-go
+    fn Go(self, delta: i32) {
+        // TODO: A delta of zero should reload the document.
+        if delta != 0 {
+            self.traverse_history(delta);
+        }
+    }
+
+    // https://html.spec.whatwg.org/multipage/#dom-history-back
+    fn Back(self) {
+        self.traverse_history(-1);
+    }
+
+    // https://html.spec.whatwg.org/multipage/#dom-history-forward
+    fn Forward(self) {
+        self.traverse_history(1);
+    }
+}
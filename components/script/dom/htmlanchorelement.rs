@@ -4,7 +4,7 @@
 
 
 use dom::activation::Activatable;
-use dom::attr::AttrValue;
+use dom::attr::{Attr, AttrHelpers, AttrValue};
 use dom::bindings::codegen::Bindings::AttrBinding::AttrMethods;
 use dom::bindings::codegen::Bindings::HTMLAnchorElementBinding;
 use dom::bindings::codegen::Bindings::HTMLAnchorElementBinding::HTMLAnchorElementMethods;
@@ -13,26 +13,33 @@ use dom::bindings::codegen::Bindings::NodeBinding::NodeMethods;
 use dom::bindings::codegen::InheritTypes::{HTMLAnchorElementDerived, HTMLImageElementDerived};
 use dom::bindings::codegen::InheritTypes::{ElementCast, HTMLElementCast};
 use dom::bindings::codegen::InheritTypes::{MouseEventCast, NodeCast};
+use dom::bindings::global::GlobalRef;
 use dom::bindings::js::{JS, MutNullableHeap, Root};
+use dom::bindings::str::USVString;
 use dom::document::{Document, DocumentHelpers};
 use dom::domtokenlist::DOMTokenList;
-use dom::element::{Element, AttributeHandlers, ElementTypeId};
+use dom::element::{Element, AttributeHandlers, ElementHelpers, ElementTypeId};
 use dom::event::Event;
 use dom::eventtarget::{EventTarget, EventTargetTypeId};
 use dom::htmlelement::{HTMLElement, HTMLElementTypeId};
 use dom::node::{Node, NodeHelpers, NodeTypeId, document_from_node, window_from_node};
+use dom::urlhelper::UrlHelper;
+use dom::urlsearchparams::{URLObject, URLSearchParams, URLSearchParamsHelpers};
 use dom::virtualmethods::VirtualMethods;
 use dom::window::WindowHelpers;
 
 use num::ToPrimitive;
+use std::borrow::ToOwned;
 use std::default::Default;
 use string_cache::Atom;
+use url::{Url, UrlParser};
 use util::str::DOMString;
 
 #[dom_struct]
 pub struct HTMLAnchorElement {
     htmlelement: HTMLElement,
     rel_list: MutNullableHeap<JS<DOMTokenList>>,
+    search_params: MutNullableHeap<JS<URLSearchParams>>,
 }
 
 impl HTMLAnchorElementDerived for EventTarget {
@@ -51,6 +58,7 @@ impl HTMLAnchorElement {
             htmlelement:
                 HTMLElement::new_inherited(HTMLElementTypeId::HTMLAnchorElement, localName, prefix, document),
             rel_list: Default::default(),
+            search_params: Default::default(),
         }
     }
 
@@ -63,12 +71,81 @@ impl HTMLAnchorElement {
     }
 }
 
+pub trait HTMLAnchorElementHelpers {
+    fn set_query(self, query: Option<String>);
+}
+
+impl<'a> HTMLAnchorElementHelpers for &'a HTMLAnchorElement {
+    /// Sets the query of this element's URL on behalf of its `URLSearchParams` object.
+    fn set_query(self, query: Option<String>) {
+        if let Some(mut url) = self.url() {
+            url.query = query;
+            self.set_url(&url);
+        }
+    }
+}
+
+trait PrivateHTMLAnchorElementHelpers {
+    fn url(self) -> Option<Url>;
+    fn set_url(self, url: &Url);
+    fn update_search_params(self);
+}
+
+impl<'a> PrivateHTMLAnchorElementHelpers for &'a HTMLAnchorElement {
+    // https://url.spec.whatwg.org/#concept-urlutils-url
+    fn url(self) -> Option<Url> {
+        let element = ElementCast::from_ref(self);
+        let href = match element.get_attribute(&ns!(""), &atom!("href")) {
+            Some(href) => href.r().Value(),
+            None => return None,
+        };
+        let base = document_from_node(self).r().url();
+        UrlParser::new().base_url(&base).parse(&href).ok()
+    }
+
+    // https://url.spec.whatwg.org/#concept-urlutils-update-steps
+    fn set_url(self, url: &Url) {
+        let element = ElementCast::from_ref(self);
+        element.set_string_attribute(&atom!("href"), url.serialize());
+    }
+
+    /// Brings the `URLSearchParams` object in line with the href attribute.
+    fn update_search_params(self) {
+        if let Some(search_params) = self.search_params.get() {
+            let search_params = search_params.root();
+            let url = self.url();
+            search_params.r().set_query(url.as_ref().and_then(|url| url.query.as_ref())
+                                           .map(|q| &**q));
+        }
+    }
+}
+
 impl<'a> VirtualMethods for &'a HTMLAnchorElement {
     fn super_type<'b>(&'b self) -> Option<&'b VirtualMethods> {
         let htmlelement: &&HTMLElement = HTMLElementCast::from_borrowed_ref(self);
         Some(htmlelement as &VirtualMethods)
     }
 
+    fn after_set_attr(&self, attr: &Attr) {
+        if let Some(ref s) = self.super_type() {
+            s.after_set_attr(attr);
+        }
+
+        if attr.local_name() == &atom!("href") {
+            self.update_search_params();
+        }
+    }
+
+    fn after_remove_attr(&self, name: &Atom) {
+        if let Some(ref s) = self.super_type() {
+            s.after_remove_attr(name);
+        }
+
+        if name == &atom!("href") {
+            self.update_search_params();
+        }
+    }
+
     fn parse_plain_attribute(&self, name: &Atom, value: DOMString) -> AttrValue {
         match name {
             &atom!("rel") => AttrValue::from_serialized_tokenlist(value),
@@ -93,6 +170,61 @@ impl<'a> HTMLAnchorElementMethods for &'a HTMLAnchorElement {
             DOMTokenList::new(ElementCast::from_ref(self), &atom!("rel"))
         })
     }
+
+    // https://url.spec.whatwg.org/#dom-urlutils-href
+    fn Href(self) -> USVString {
+        match self.url() {
+            Some(url) => UrlHelper::Href(&url),
+            None => {
+                let element = ElementCast::from_ref(self);
+                USVString(element.get_string_attribute(&atom!("href")))
+            },
+        }
+    }
+
+    // https://url.spec.whatwg.org/#dom-urlutils-href
+    fn SetHref(self, value: USVString) {
+        let element = ElementCast::from_ref(self);
+        element.set_string_attribute(&atom!("href"), value.0);
+    }
+
+    // https://url.spec.whatwg.org/#dom-urlutils-search
+    fn Search(self) -> USVString {
+        self.url().map_or(USVString("".to_owned()), |url| UrlHelper::Search(&url))
+    }
+
+    // https://url.spec.whatwg.org/#dom-urlutils-search
+    fn SetSearch(self, value: USVString) {
+        if let Some(mut url) = self.url() {
+            UrlHelper::SetSearch(&mut url, value);
+            self.set_url(&url);
+        }
+    }
+
+    // https://url.spec.whatwg.org/#dom-urlutils-searchparams
+    fn SearchParams(self) -> Root<URLSearchParams> {
+        self.search_params.or_init(|| {
+            let window = window_from_node(self);
+            let url = self.url();
+            URLSearchParams::new_with_url_object(GlobalRef::Window(window.r()),
+                                                 URLObject::Anchor(JS::from_ref(self)),
+                                                 url.as_ref().and_then(|url| url.query.as_ref())
+                                                    .map(|q| &**q))
+        })
+    }
+
+    // https://url.spec.whatwg.org/#dom-urlutils-hash
+    fn Hash(self) -> USVString {
+        self.url().map_or(USVString("".to_owned()), |url| UrlHelper::Hash(&url))
+    }
+
+    // https://url.spec.whatwg.org/#dom-urlutils-hash
+    fn SetHash(self, value: USVString) {
+        if let Some(mut url) = self.url() {
+            UrlHelper::SetHash(&mut url, value);
+            self.set_url(&url);
+        }
+    }
 }
 
 impl<'a> Activatable for &'a HTMLAnchorElement {
@@ -147,7 +279,7 @@ impl<'a> Activatable for &'a HTMLAnchorElement {
                 value.push_str(&suffix);
             }
             debug!("clicked on link to {}", value);
-            doc.r().load_anchor_href(value);
+            doc.r().load_anchor_href(value, element.referrer_policy());
         }
     }
 
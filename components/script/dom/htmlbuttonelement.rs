@@ -77,6 +77,12 @@ impl<'a> HTMLButtonElementMethods for &'a HTMLButtonElement {
         ValidityState::new(window.r())
     }
 
+    // https://html.spec.whatwg.org/multipage/#dom-fe-autofocus
+    make_bool_getter!(Autofocus);
+
+    // https://html.spec.whatwg.org/multipage/#dom-fe-autofocus
+    make_bool_setter!(SetAutofocus, "autofocus");
+
     // https://www.whatwg.org/html/#dom-fe-disabled
     make_bool_getter!(Disabled);
 
@@ -21,6 +21,7 @@ use dom::cssstyledeclaration::{CSSStyleDeclaration, CSSModificationAccess};
 use dom::document::{Document, DocumentHelpers};
 use dom::domstringmap::DOMStringMap;
 use dom::element::{Element, ElementTypeId, ActivationElementHelpers, AttributeHandlers};
+use dom::element::FocusElementHelpers;
 use dom::eventtarget::{EventTarget, EventTargetHelpers, EventTargetTypeId};
 use dom::htmlinputelement::HTMLInputElement;
 use dom::htmlmediaelement::HTMLMediaElementTypeId;
@@ -188,6 +189,20 @@ impl<'a> HTMLElementMethods for &'a HTMLElement {
         element.as_maybe_activatable().map(|a| a.synthetic_click_activation(false, false, false, false));
     }
 
+    // https://html.spec.whatwg.org/multipage/#dom-tabindex
+    fn TabIndex(self) -> i32 {
+        let element = ElementCast::from_ref(self);
+        element.tab_index().unwrap_or_else(|| {
+            if element.is_focusable_area() { 0 } else { -1 }
+        })
+    }
+
+    // https://html.spec.whatwg.org/multipage/#dom-tabindex
+    fn SetTabIndex(self, tab_index: i32) {
+        let element = ElementCast::from_ref(self);
+        element.set_string_attribute(&atom!("tabindex"), tab_index.to_string());
+    }
+
     // https://html.spec.whatwg.org/multipage/#dom-focus
     fn Focus(self) {
         // TODO: Mark the element as locked for focus and run the focusing steps.
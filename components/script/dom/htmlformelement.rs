@@ -206,6 +206,8 @@ impl<'a> HTMLFormElementHelpers for &'a HTMLFormElement {
         // TODO: Handle browsing contexts, partially loaded documents (step 16-17)
 
         let mut load_data = LoadData::new(action_components);
+        load_data.referrer_url = Some(doc.r().url());
+        load_data.referrer_policy = doc.r().referrer_policy();
 
         let parsed_data = match enctype {
             FormEncType::UrlEncoded => {
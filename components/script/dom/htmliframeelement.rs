@@ -16,7 +16,7 @@ use dom::bindings::global::GlobalRef;
 use dom::bindings::js::{Root};
 use dom::bindings::utils::Reflectable;
 use dom::customevent::CustomEvent;
-use dom::document::Document;
+use dom::document::{Document, DocumentHelpers};
 use dom::element::{self, AttributeHandlers};
 use dom::event::EventHelpers;
 use dom::eventtarget::{EventTarget, EventTargetTypeId};
@@ -30,6 +30,7 @@ use page::IterablePage;
 
 use msg::constellation_msg::{PipelineId, SubpageId, ConstellationChan, MozBrowserEvent, NavigationDirection};
 use msg::constellation_msg::IFrameSandboxState::{IFrameSandboxed, IFrameUnsandboxed};
+use msg::constellation_msg::LoadData;
 use msg::constellation_msg::Msg as ConstellationMsg;
 use util::opts;
 use util::str::DOMString;
@@ -75,7 +76,7 @@ pub trait HTMLIFrameElementHelpers {
     /// https://www.whatwg.org/html/#process-the-iframe-attributes
     fn process_the_iframe_attributes(self);
     fn generate_new_subpage_id(self) -> (SubpageId, Option<SubpageId>);
-    fn navigate_child_browsing_context(self, url: Url);
+    fn navigate_child_browsing_context(self, load_data: LoadData);
     fn dispatch_mozbrowser_event(self, event: MozBrowserEvent);
     fn update_subpage_id(self, new_subpage_id: SubpageId);
 }
@@ -112,7 +113,7 @@ impl<'a> HTMLIFrameElementHelpers for &'a HTMLIFrameElement {
         (subpage_id, old_subpage_id)
     }
 
-    fn navigate_child_browsing_context(self, url: Url) {
+    fn navigate_child_browsing_context(self, mut load_data: LoadData) {
         let sandboxed = if self.is_sandboxed() {
             IFrameSandboxed
         } else {
@@ -121,12 +122,20 @@ impl<'a> HTMLIFrameElementHelpers for &'a HTMLIFrameElement {
 
         let window = window_from_node(self);
         let window = window.r();
+
+        // A secure document's frames may not be loaded insecurely; a blocked frame is left
+        // blank.
+        let document = window.Document();
+        if document.r().should_block_mixed_content(&load_data.url, true) {
+            load_data = LoadData::new(Url::parse("about:blank").unwrap());
+        }
+
         let (new_subpage_id, old_subpage_id) = self.generate_new_subpage_id();
 
         self.containing_page_pipeline_id.set(Some(window.pipeline()));
 
         let ConstellationChan(ref chan) = window.constellation_chan();
-        chan.send(ConstellationMsg::ScriptLoadedURLInIFrame(url,
+        chan.send(ConstellationMsg::ScriptLoadedURLInIFrame(load_data,
                                                             window.pipeline(),
                                                             new_subpage_id,
                                                             old_subpage_id,
@@ -144,7 +153,7 @@ impl<'a> HTMLIFrameElementHelpers for &'a HTMLIFrameElement {
             None => Url::parse("about:blank").unwrap(),
         };
 
-        self.navigate_child_browsing_context(url);
+        self.navigate_child_browsing_context(LoadData::new(url));
     }
 
     fn dispatch_mozbrowser_event(self, event: MozBrowserEvent) {
@@ -82,6 +82,10 @@ impl ImageResponder for Responder {
         // Update the image field
         let element = self.element.root();
         let element_ref = element.r();
+        let loaded = match image {
+            ImageResponse::Loaded(_) => true,
+            ImageResponse::PlaceholderLoaded(_) | ImageResponse::None => false,
+        };
         *element_ref.image.borrow_mut() = match image {
             ImageResponse::Loaded(image) | ImageResponse::PlaceholderLoaded(image) => {
                 Some(image)
@@ -94,10 +98,11 @@ impl ImageResponder for Responder {
         let document = document_from_node(node);
         document.r().content_changed(node, NodeDamage::OtherNodeDamage);
 
-        // Fire image.onload
+        // Fire image.onload, or image.onerror if the image couldn't be loaded or decoded.
         let window = window_from_node(document.r());
+        let event_type = if loaded { "load" } else { "error" };
         let event = Event::new(GlobalRef::Window(window.r()),
-                               "load".to_owned(),
+                               event_type.to_owned(),
                                EventBubbles::DoesNotBubble,
                                EventCancelable::NotCancelable);
         let event = event.r();
@@ -105,6 +110,7 @@ impl ImageResponder for Responder {
         event.fire(target);
 
         // Trigger reflow
+        window.r().image_load_finished();
         window.r().add_pending_reflow();
     }
 }
@@ -128,10 +134,14 @@ impl<'a> PrivateHTMLImageElementHelpers for &'a HTMLImageElement {
                 // FIXME: handle URL parse errors more gracefully.
                 let img_url = img_url.unwrap();
                 *self.url.borrow_mut() = Some(img_url.clone());
+                // Images are never blocked, but the embedder still hears about insecure ones.
+                document.r().should_block_mixed_content(&img_url, false);
 
                 let trusted_node = Trusted::new(window.get_cx(), self, window.script_chan());
                 let responder = box Responder::new(trusted_node);
-                image_cache.request_image(img_url, window.image_cache_chan(), Some(responder));
+                window.image_load_started();
+                image_cache.request_image(img_url, window.pipeline(), window.image_cache_chan(),
+                                          Some(responder));
             }
         }
     }
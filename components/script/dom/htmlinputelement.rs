@@ -16,6 +16,7 @@ use dom::bindings::codegen::InheritTypes::{HTMLInputElementDerived, HTMLFieldSet
 use dom::bindings::codegen::InheritTypes::KeyboardEventCast;
 use dom::bindings::global::GlobalRef;
 use dom::bindings::js::{JS, LayoutJS, Root, RootedReference};
+use dom::compositionevent::apply_ime_event;
 use dom::document::{Document, DocumentHelpers};
 use dom::element::{AttributeHandlers, Element};
 use dom::element::{RawLayoutElementHelpers, ActivationElementHelpers};
@@ -33,7 +34,7 @@ use dom::window::WindowHelpers;
 use textinput::TextInput;
 use textinput::KeyReaction::{TriggerDefaultAction, DispatchInput, Nothing};
 use textinput::Lines::Single;
-use msg::constellation_msg::ConstellationChan;
+use msg::constellation_msg::{ConstellationChan, FormControlState, ImeEvent};
 
 use util::str::DOMString;
 use string_cache::Atom;
@@ -220,6 +221,12 @@ impl RawLayoutHTMLInputElementHelpers for HTMLInputElement {
 }
 
 impl<'a> HTMLInputElementMethods for &'a HTMLInputElement {
+    // https://html.spec.whatwg.org/multipage/#dom-fe-autofocus
+    make_bool_getter!(Autofocus);
+
+    // https://html.spec.whatwg.org/multipage/#dom-fe-autofocus
+    make_bool_setter!(SetAutofocus, "autofocus");
+
     // https://www.whatwg.org/html/#dom-fe-disabled
     make_bool_getter!(Disabled);
 
@@ -339,6 +346,9 @@ pub trait HTMLInputElementHelpers {
     fn get_indeterminate_state(self) -> bool;
     fn mutable(self) -> bool;
     fn reset(self);
+    fn edited_state(self) -> Option<FormControlState>;
+    fn restore_edited_state(self, state: &FormControlState);
+    fn handle_ime_event(self, event: &ImeEvent) -> Option<usize>;
 }
 
 #[allow(unsafe_code)]
@@ -452,6 +462,54 @@ impl<'a> HTMLInputElementHelpers for &'a HTMLInputElement {
         self.value_changed.set(false);
         self.force_relayout();
     }
+
+    /// The value or checkedness the user gave this control, to restore if the document is
+    /// reloaded from the session history. Passwords aren't kept.
+    fn edited_state(self) -> Option<FormControlState> {
+        match self.input_type.get() {
+            InputType::InputCheckbox | InputType::InputRadio if self.checked_changed.get() => {
+                Some(FormControlState::Checked(self.Checked()))
+            }
+            InputType::InputText if self.value_changed.get() => {
+                Some(FormControlState::Value(self.Value()))
+            }
+            _ => None,
+        }
+    }
+
+    fn restore_edited_state(self, state: &FormControlState) {
+        match (self.input_type.get(), state) {
+            (InputType::InputCheckbox, &FormControlState::Checked(checked)) |
+            (InputType::InputRadio, &FormControlState::Checked(checked)) => {
+                self.update_checked_state(checked, true);
+            }
+            (InputType::InputText, &FormControlState::Value(ref value)) => {
+                self.SetValue(value.clone());
+            }
+            _ => (),
+        }
+    }
+
+    /// Applies an input method editor event to the text being edited, if this is a text field
+    /// the user can edit. Returns the number of characters before the caret afterwards.
+    fn handle_ime_event(self, event: &ImeEvent) -> Option<usize> {
+        match self.input_type.get() {
+            InputType::InputText | InputType::InputPassword if self.mutable() => {}
+            _ => return None,
+        }
+        let window = window_from_node(self);
+        let target = EventTargetCast::from_ref(self);
+        apply_ime_event(window.r(), target, &self.textinput, event, || {
+            self.value_changed.set(true);
+            let event = Event::new(GlobalRef::Window(window.r()),
+                                   "input".to_owned(),
+                                   EventBubbles::Bubbles,
+                                   EventCancelable::NotCancelable);
+            event.r().fire(target);
+            self.force_relayout();
+        });
+        Some(self.textinput.borrow().caret_offset())
+    }
 }
 
 impl<'a> VirtualMethods for &'a HTMLInputElement {
@@ -2,7 +2,7 @@
  * License, v. 2.0. If a copy of the MPL was not distributed with this
  * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
 
-use document_loader::LoadType;
+use document_loader::{LoadType, load_in_background};
 use dom::attr::{Attr, AttrValue};
 use dom::attr::AttrHelpers;
 use dom::bindings::codegen::Bindings::HTMLLinkElementBinding;
@@ -16,7 +16,7 @@ use dom::bindings::js::{RootedReference};
 use dom::bindings::refcounted::Trusted;
 use dom::document::{Document, DocumentHelpers};
 use dom::domtokenlist::DOMTokenList;
-use dom::element::{AttributeHandlers, Element};
+use dom::element::{AttributeHandlers, Element, ElementHelpers};
 use dom::event::{EventBubbles, EventCancelable, Event, EventHelpers};
 use dom::eventtarget::{EventTarget, EventTargetTypeId};
 use dom::element::ElementTypeId;
@@ -27,6 +27,7 @@ use dom::window::WindowHelpers;
 use layout_interface::{LayoutChan, Msg};
 use msg::constellation_msg::ConstellationChan;
 use msg::constellation_msg::Msg as ConstellationMsg;
+use net_traits::LoadPriority;
 use script_traits::StylesheetLoadResponder;
 use util::str::{DOMString, HTML_SPACE_CHARACTERS};
 use style::media_queries::parse_media_query_list;
@@ -100,6 +101,17 @@ fn is_favicon(value: &Option<String>) -> bool {
     }
 }
 
+/// https://html.spec.whatwg.org/multipage/#link-type-prefetch
+fn is_prefetch(value: &Option<String>) -> bool {
+    match *value {
+        Some(ref value) => {
+            value.split(HTML_SPACE_CHARACTERS)
+                .any(|s| s.eq_ignore_ascii_case("prefetch"))
+        },
+        None => false,
+    }
+}
+
 impl<'a> VirtualMethods for &'a HTMLLinkElement {
     fn super_type<'b>(&'b self) -> Option<&'b VirtualMethods> {
         let htmlelement: &&HTMLElement = HTMLElementCast::from_borrowed_ref(self);
@@ -125,6 +137,8 @@ impl<'a> VirtualMethods for &'a HTMLLinkElement {
                     self.handle_stylesheet_url(&attr.value());
                 } else if is_favicon(rel) {
                     self.handle_favicon_url(&attr.value());
+                } else if is_prefetch(rel) {
+                    self.handle_prefetch_url(&attr.value());
                 }
             }
             (ref rel, &atom!("media")) => {
@@ -161,6 +175,9 @@ impl<'a> VirtualMethods for &'a HTMLLinkElement {
                 (ref rel, Some(ref href)) if is_favicon(rel) => {
                     self.handle_favicon_url(href);
                 }
+                (ref rel, Some(ref href)) if is_prefetch(rel) => {
+                    self.handle_prefetch_url(href);
+                }
                 _ => {}
             }
         }
@@ -170,6 +187,7 @@ impl<'a> VirtualMethods for &'a HTMLLinkElement {
 trait PrivateHTMLLinkElementHelpers {
     fn handle_stylesheet_url(self, href: &str);
     fn handle_favicon_url(self, href: &str);
+    fn handle_prefetch_url(self, href: &str);
 }
 
 impl<'a> PrivateHTMLLinkElementHelpers for &'a HTMLLinkElement {
@@ -193,7 +211,10 @@ impl<'a> PrivateHTMLLinkElementHelpers for &'a HTMLLinkElement {
                 let link_element = Trusted::new(window.get_cx(), self, window.script_chan().clone());
                 let load_dispatcher = StylesheetLoadDispatcher::new(link_element);
 
-                let pending = doc.r().prepare_async_load(LoadType::Stylesheet(url.clone()));
+                let mut pending = doc.r().prepare_async_load(LoadType::Stylesheet(url.clone()));
+                if let Some(referrer_policy) = element.referrer_policy() {
+                    pending.set_referrer_policy(referrer_policy);
+                }
                 let LayoutChan(ref layout_chan) = window.layout_chan();
                 layout_chan.send(Msg::LoadStylesheet(url, media, pending, box load_dispatcher)).unwrap();
             }
@@ -207,12 +228,24 @@ impl<'a> PrivateHTMLLinkElementHelpers for &'a HTMLLinkElement {
         match UrlParser::new().base_url(&window.get_url()).parse(href) {
             Ok(url) => {
                 let ConstellationChan(ref chan) = window.constellation_chan();
-                let event = ConstellationMsg::NewFavicon(url.clone());
+                let event = ConstellationMsg::NewFavicon(window.pipeline(), url.clone());
                 chan.send(event).unwrap();
             }
             Err(e) => debug!("Parsing url {} failed: {}", href, e)
         }
     }
+
+    /// Fetches the resource ahead of time, behind all other loads from the same host.
+    fn handle_prefetch_url(self, href: &str) {
+        let window = window_from_node(self);
+        let window = window.r();
+        match UrlParser::new().base_url(&window.get_url()).parse(href) {
+            Ok(url) => {
+                load_in_background(window.resource_task(), url, LoadPriority::Prefetch);
+            }
+            Err(e) => debug!("Parsing url {} failed: {}", href, e)
+        }
+    }
 }
 
 impl<'a> HTMLLinkElementMethods for &'a HTMLLinkElement {
@@ -4,14 +4,25 @@
 
 use dom::bindings::codegen::Bindings::HTMLMetaElementBinding;
 use dom::bindings::codegen::Bindings::HTMLMetaElementBinding::HTMLMetaElementMethods;
-use dom::bindings::codegen::InheritTypes::HTMLMetaElementDerived;
+use dom::bindings::codegen::InheritTypes::{ElementCast, HTMLElementCast, HTMLMetaElementDerived};
+use dom::bindings::codegen::InheritTypes::NodeCast;
 use dom::bindings::js::Root;
-use dom::document::Document;
+use dom::document::{Document, DocumentHelpers};
 use dom::eventtarget::{EventTarget, EventTargetTypeId};
-use dom::element::ElementTypeId;
+use dom::element::{AttributeHandlers, ElementTypeId};
 use dom::htmlelement::{HTMLElement, HTMLElementTypeId};
-use dom::node::{Node, NodeTypeId};
-use util::str::DOMString;
+use dom::node::{Node, NodeTypeId, document_from_node, window_from_node};
+use dom::virtualmethods::VirtualMethods;
+use dom::window::WindowHelpers;
+use layout_interface::{LayoutChan, Msg};
+use util::str::{DOMString, HTML_SPACE_CHARACTERS};
+use style::media_queries::parse_media_query_list;
+use style::stylesheets::{CSSRule, Origin, Stylesheet};
+use style::viewport::ViewportRule;
+use cssparser::Parser as CssParser;
+use string_cache::Atom;
+
+use std::ascii::AsciiExt;
 
 #[dom_struct]
 pub struct HTMLMetaElement {
@@ -57,3 +68,73 @@ impl<'a> HTMLMetaElementMethods for &'a HTMLMetaElement {
     // https://html.spec.whatwg.org/multipage/#dom-meta-content
     make_setter!(SetContent, "content");
 }
+
+trait PrivateHTMLMetaElementHelpers {
+    fn apply_viewport(self);
+    fn apply_content_language(self);
+}
+
+impl<'a> PrivateHTMLMetaElementHelpers for &'a HTMLMetaElement {
+    /// Hands the constraints of a `<meta name=viewport>` to layout as an author @viewport rule.
+    fn apply_viewport(self) {
+        if !self.Name().eq_ignore_ascii_case("viewport") {
+            return;
+        }
+        let rule = match ViewportRule::from_meta(&self.Content()) {
+            Some(rule) => rule,
+            None => return,
+        };
+
+        let node = NodeCast::from_ref(self);
+        let win = window_from_node(node);
+        let win = win.r();
+        let sheet = Stylesheet {
+            rules: vec![CSSRule::Viewport(rule)],
+            origin: Origin::Author,
+            url: win.get_url(),
+        };
+        let media = parse_media_query_list(&mut CssParser::new(""));
+        let LayoutChan(ref layout_chan) = win.layout_chan();
+        layout_chan.send(Msg::AddStylesheet(sheet, media)).unwrap();
+    }
+
+    /// Sets the default language of the document from a `<meta http-equiv=content-language>`.
+    /// https://html.spec.whatwg.org/multipage/#attr-meta-http-equiv-content-language
+    fn apply_content_language(self) {
+        let element = ElementCast::from_ref(self);
+        let http_equiv = element.get_string_attribute(&Atom::from_slice("http-equiv"));
+        if !http_equiv.eq_ignore_ascii_case("content-language") {
+            return;
+        }
+        let content = self.Content();
+        if content.contains(',') {
+            return;
+        }
+        let lang = match content.split(HTML_SPACE_CHARACTERS).find(|lang| !lang.is_empty()) {
+            Some(lang) => lang,
+            None => return,
+        };
+
+        let node = NodeCast::from_ref(self);
+        let document = document_from_node(node);
+        document.r().set_content_language(Some(Atom::from_slice(lang)));
+    }
+}
+
+impl<'a> VirtualMethods for &'a HTMLMetaElement {
+    fn super_type<'b>(&'b self) -> Option<&'b VirtualMethods> {
+        let htmlelement: &&HTMLElement = HTMLElementCast::from_borrowed_ref(self);
+        Some(htmlelement as &VirtualMethods)
+    }
+
+    fn bind_to_tree(&self, tree_in_doc: bool) {
+        if let Some(ref s) = self.super_type() {
+            s.bind_to_tree(tree_in_doc);
+        }
+
+        if tree_in_doc {
+            self.apply_viewport();
+            self.apply_content_language();
+        }
+    }
+}
@@ -22,7 +22,7 @@ use dom::bindings::js::RootedReference;
 use dom::bindings::refcounted::Trusted;
 use dom::bindings::trace::JSTraceable;
 use dom::document::{Document, DocumentHelpers};
-use dom::element::{AttributeHandlers, ElementCreator};
+use dom::element::{AttributeHandlers, ElementCreator, ElementHelpers};
 use dom::eventtarget::{EventTarget, EventTargetTypeId};
 use dom::event::{Event, EventBubbles, EventCancelable, EventHelpers};
 use dom::element::ElementTypeId;
@@ -33,6 +33,7 @@ use dom::virtualmethods::VirtualMethods;
 use dom::window::{WindowHelpers, ScriptHelpers};
 use network_listener::{NetworkListener, PreInvoke};
 use script_task::{ScriptChan, ScriptMsg, Runnable};
+use devtools_traits::{TimelineMarker, TimelineMarkerType, TracingMetadata};
 use js::jsapi::RootedValue;
 use js::jsval::UndefinedValue;
 
@@ -334,7 +335,11 @@ impl<'a> HTMLScriptElementHelpers for &'a HTMLScriptElement {
                             script_chan: script_chan,
                         };
 
-                        doc.r().load_async(LoadType::Script(url), listener);
+                        let mut pending = doc.r().prepare_async_load(LoadType::Script(url));
+                        if let Some(referrer_policy) = element.referrer_policy() {
+                            pending.set_referrer_policy(referrer_policy);
+                        }
+                        pending.load_async(listener);
 
                         if self.parser_inserted.get() {
                             doc.r().get_current_parser().unwrap().r().suspend();
@@ -421,10 +426,20 @@ impl<'a> HTMLScriptElementHelpers for &'a HTMLScriptElement {
         // Step 2.b.6.
         // TODO: Create a script...
         let window = window_from_node(self);
+        if window.r().need_emit_timeline_marker(TimelineMarkerType::Javascript) {
+            let marker = TimelineMarker::new("Javascript".to_owned(),
+                                             TracingMetadata::IntervalStart);
+            window.r().emit_timeline_marker(marker);
+        }
         let mut rval = RootedValue::new(window.r().get_cx(), UndefinedValue());
         window.r().evaluate_script_on_global_with_result(&*source,
                                                          &*url.serialize(),
                                                          rval.handle_mut());
+        if window.r().need_emit_timeline_marker(TimelineMarkerType::Javascript) {
+            let marker = TimelineMarker::new("Javascript".to_owned(),
+                                             TracingMetadata::IntervalEnd);
+            window.r().emit_timeline_marker(marker);
+        }
 
         // Step 2.b.7.
         document.set_current_script(old_script.r());
@@ -68,6 +68,12 @@ impl<'a> HTMLSelectElementMethods for &'a HTMLSelectElement {
     fn Add(self, _element: HTMLOptionElementOrHTMLOptGroupElement, _before: Option<HTMLElementOrLong>) {
     }
 
+    // https://html.spec.whatwg.org/multipage/#dom-fe-autofocus
+    make_bool_getter!(Autofocus);
+
+    // https://html.spec.whatwg.org/multipage/#dom-fe-autofocus
+    make_bool_setter!(SetAutofocus, "autofocus");
+
     // https://www.whatwg.org/html/#dom-fe-disabled
     make_bool_getter!(Disabled);
 
@@ -15,6 +15,7 @@ use dom::bindings::codegen::InheritTypes::{KeyboardEventCast, TextDerived};
 use dom::bindings::global::GlobalRef;
 use dom::bindings::js::{LayoutJS, Root};
 use dom::bindings::refcounted::Trusted;
+use dom::compositionevent::apply_ime_event;
 use dom::document::{Document, DocumentHelpers};
 use dom::element::{Element, AttributeHandlers};
 use dom::event::{Event, EventBubbles, EventCancelable};
@@ -29,7 +30,7 @@ use textinput::{TextInput, Lines, KeyReaction};
 use dom::virtualmethods::VirtualMethods;
 use dom::window::WindowHelpers;
 use script_task::{ScriptMsg, Runnable};
-use msg::constellation_msg::ConstellationChan;
+use msg::constellation_msg::{ConstellationChan, FormControlState, ImeEvent};
 
 use util::str::DOMString;
 use string_cache::Atom;
@@ -124,6 +125,12 @@ impl<'a> HTMLTextAreaElementMethods for &'a HTMLTextAreaElement {
     make_uint_getter!(Cols, "cols", DEFAULT_COLS);
     make_limited_uint_setter!(SetCols, "cols", DEFAULT_COLS);
 
+    // https://html.spec.whatwg.org/multipage/#dom-fe-autofocus
+    make_bool_getter!(Autofocus);
+
+    // https://html.spec.whatwg.org/multipage/#dom-fe-autofocus
+    make_bool_setter!(SetAutofocus, "autofocus");
+
     // https://www.whatwg.org/html/#dom-fe-disabled
     make_bool_getter!(Disabled);
 
@@ -207,6 +214,9 @@ impl<'a> HTMLTextAreaElementMethods for &'a HTMLTextAreaElement {
 pub trait HTMLTextAreaElementHelpers {
     fn mutable(self) -> bool;
     fn reset(self);
+    fn edited_state(self) -> Option<FormControlState>;
+    fn restore_edited_state(self, state: &FormControlState);
+    fn handle_ime_event(self, event: &ImeEvent) -> Option<usize>;
 }
 
 impl<'a> HTMLTextAreaElementHelpers for &'a HTMLTextAreaElement {
@@ -220,6 +230,38 @@ impl<'a> HTMLTextAreaElementHelpers for &'a HTMLTextAreaElement {
         self.SetValue(self.DefaultValue());
         self.value_changed.set(false);
     }
+
+    /// The value the user typed in, to restore if the document is reloaded from the session
+    /// history.
+    fn edited_state(self) -> Option<FormControlState> {
+        if self.value_changed.get() {
+            Some(FormControlState::Value(self.Value()))
+        } else {
+            None
+        }
+    }
+
+    fn restore_edited_state(self, state: &FormControlState) {
+        if let FormControlState::Value(ref value) = *state {
+            self.SetValue(value.clone());
+        }
+    }
+
+    /// Applies an input method editor event to the text being edited, if the user can edit
+    /// it. Returns the number of characters before the caret afterwards.
+    fn handle_ime_event(self, event: &ImeEvent) -> Option<usize> {
+        if !self.mutable() {
+            return None
+        }
+        let window = window_from_node(self);
+        let target = EventTargetCast::from_ref(self);
+        apply_ime_event(window.r(), target, &self.textinput, event, || {
+            self.value_changed.set(true);
+            self.dispatch_change_event();
+            self.force_relayout();
+        });
+        Some(self.textinput.borrow().caret_offset())
+    }
 }
 
 trait PrivateHTMLTextAreaElementHelpers {
@@ -4,10 +4,12 @@
 
 use dom::bindings::codegen::Bindings::LocationBinding;
 use dom::bindings::codegen::Bindings::LocationBinding::LocationMethods;
+use dom::bindings::codegen::Bindings::WindowBinding::WindowMethods;
 use dom::bindings::global::GlobalRef;
 use dom::bindings::js::{JS, Root};
 use dom::bindings::str::USVString;
 use dom::bindings::utils::{Reflector, reflect_dom_object};
+use dom::document::DocumentHelpers;
 use dom::urlhelper::UrlHelper;
 use dom::window::Window;
 use dom::window::WindowHelpers;
@@ -39,7 +41,9 @@ impl Location {
 impl<'a> LocationMethods for &'a Location {
     // https://html.spec.whatwg.org/multipage/#dom-location-assign
     fn Assign(self, url: DOMString) {
-        self.window.root().r().load_url(url);
+        let window = self.window.root();
+        let referrer_policy = window.r().Document().r().referrer_policy();
+        window.r().load_url(url, referrer_policy);
     }
 
     // https://url.spec.whatwg.org/#dom-urlutils-hash
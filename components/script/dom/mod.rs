@@ -195,7 +195,9 @@ pub mod domrectlist;
 pub mod domstringmap;
 pub mod closeevent;
 pub mod comment;
+pub mod compositionevent;
 pub mod console;
+pub mod coordinates;
 mod create;
 pub mod customevent;
 pub mod dedicatedworkerglobalscope;
@@ -212,7 +214,15 @@ pub mod event;
 pub mod eventdispatcher;
 pub mod eventtarget;
 pub mod file;
+pub mod focusevent;
 pub mod formdata;
+pub mod gamepad;
+pub mod gamepadbutton;
+pub mod gamepadbuttonlist;
+pub mod gamepadevent;
+pub mod gamepadlist;
+pub mod geolocation;
+pub mod history;
 pub mod htmlanchorelement;
 pub mod htmlappletelement;
 pub mod htmlareaelement;
@@ -294,9 +304,12 @@ pub mod navigatorinfo;
 pub mod node;
 pub mod nodeiterator;
 pub mod nodelist;
+pub mod notification;
 pub mod processinginstruction;
 pub mod performance;
 pub mod performancetiming;
+pub mod position;
+pub mod positionerror;
 pub mod progressevent;
 pub mod range;
 pub mod screen;
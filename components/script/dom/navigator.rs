@@ -4,27 +4,43 @@
 
 use dom::bindings::codegen::Bindings::NavigatorBinding;
 use dom::bindings::codegen::Bindings::NavigatorBinding::NavigatorMethods;
+use dom::bindings::error::Error::Type;
+use dom::bindings::error::Fallible;
 use dom::bindings::global::GlobalRef;
-use dom::bindings::js::Root;
+use dom::bindings::js::{JS, Root};
 use dom::bindings::utils::{Reflector, reflect_dom_object};
+use dom::gamepadlist::GamepadList;
+use dom::geolocation::Geolocation;
 use dom::navigatorinfo;
-use dom::window::Window;
+use dom::window::{Window, WindowHelpers};
+use dom::xmlhttprequest::{Extractable, SendParam};
 use util::str::DOMString;
 
+use dom::bindings::codegen::UnionTypes::StringOrURLSearchParams::{eString, eURLSearchParams};
+
+use hyper::method::Method;
+use net_traits::{BEACON_QUOTA, ControlMsg, LoadData};
+
+use std::borrow::ToOwned;
+use std::sync::mpsc::channel;
+use url::UrlParser;
+
 #[dom_struct]
 pub struct Navigator {
     reflector_: Reflector,
+    window: JS<Window>,
 }
 
 impl Navigator {
-    fn new_inherited() -> Navigator {
+    fn new_inherited(window: &Window) -> Navigator {
         Navigator {
-            reflector_: Reflector::new()
+            reflector_: Reflector::new(),
+            window: JS::from_ref(window),
         }
     }
 
     pub fn new(window: &Window) -> Root<Navigator> {
-        reflect_dom_object(box Navigator::new_inherited(),
+        reflect_dom_object(box Navigator::new_inherited(window),
                            GlobalRef::Window(window),
                            NavigatorBinding::Wrap)
     }
@@ -58,5 +74,57 @@ impl<'a> NavigatorMethods for &'a Navigator {
     fn AppVersion(self) -> DOMString {
         navigatorinfo::AppVersion()
     }
+
+    // https://w3c.github.io/gamepad/#widl-Navigator-getGamepads-sequence-Gamepad
+    fn GetGamepads(self) -> Root<GamepadList> {
+        self.window.root().r().gamepads()
+    }
+
+    // http://dev.w3.org/geo/api/spec-source.html#navi-geo
+    fn Geolocation(self) -> Root<Geolocation> {
+        self.window.root().r().geolocation()
+    }
+
+    // https://w3c.github.io/beacon/#sec-sendBeacon-method
+    fn SendBeacon(self, url: DOMString, data: Option<SendParam>) -> Fallible<bool> {
+        let window = self.window.root();
+        let window = window.r();
+
+        // Steps 1-2.
+        let base = window.get_url();
+        let url = match UrlParser::new().base_url(&base).parse(&url) {
+            Ok(url) => url,
+            Err(_) => return Err(Type("sendBeacon: invalid URL".to_owned())),
+        };
+        match &*url.scheme {
+            "http" | "https" => {},
+            _ => return Err(Type("sendBeacon: URL must be http or https".to_owned())),
+        }
+
+        // The request belongs to no pipeline, so that unloading the document doesn't
+        // interfere with it.
+        let mut load_data = LoadData::new(url, None);
+        load_data.method = Method::Post;
+        if let Some(ref data) = data {
+            let content_type = match *data {
+                eString(_) => "text/plain;charset=UTF-8",
+                eURLSearchParams(_) => "application/x-www-form-urlencoded;charset=UTF-8",
+            };
+            load_data.headers.set_raw("content-type".to_owned(),
+                                      vec![content_type.as_bytes().to_owned()]);
+            load_data.data = Some(data.extract());
+        }
+
+        // Steps 3-5: queue the request, unless it doesn't fit in the origin's quota. Beacons
+        // that could never fit are turned away without asking the resource task, which checks
+        // the room the origin's other beacons leave.
+        if load_data.data.as_ref().map_or(0, |data| data.len()) > BEACON_QUOTA {
+            return Ok(false);
+        }
+        let (result_chan, result_port) = channel();
+        let resource_task = window.resource_task();
+        resource_task.send(ControlMsg::SendBeacon(load_data, base, result_chan)).unwrap();
+        Ok(result_port.recv().unwrap_or(false))
+    }
 }
 
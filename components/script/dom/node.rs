@@ -47,7 +47,7 @@ use dom::virtualmethods::{VirtualMethods, vtable_for};
 use dom::window::{Window, WindowHelpers};
 use euclid::rect::Rect;
 use layout_interface::{LayoutChan, Msg};
-use devtools_traits::NodeInfo;
+use devtools_traits::{NodeInfo, NodeMutation};
 use parse::html::parse_html_fragment;
 use script_traits::UntrustedNodeAddress;
 use util::geometry::Au;
@@ -165,6 +165,8 @@ bitflags! {
         #[doc = "Specifies whether this node is focusable and whether it is supposed \
                  to be reachable with using sequential focus navigation."]
         const SEQUENTIALLY_FOCUSABLE = 0x400,
+        #[doc = "Specifies whether this node is being activated by the pointing device."]
+        const IN_ACTIVE_STATE = 0x800,
     }
 }
 
@@ -464,6 +466,9 @@ pub trait NodeHelpers {
     fn get_focus_state(self) -> bool;
     fn set_focus_state(self, state: bool);
 
+    fn get_active_state(self) -> bool;
+    fn set_active_state(self, state: bool);
+
     fn get_disabled_state(self) -> bool;
     fn set_disabled_state(self, state: bool);
 
@@ -526,6 +531,7 @@ pub trait NodeHelpers {
 
     fn get_unique_id(self) -> String;
     fn summarize(self) -> NodeInfo;
+    fn report_mutation_to_devtools<F>(self, mutation: F) where F: FnOnce(String) -> NodeMutation;
 
     fn teardown(self);
 
@@ -623,6 +629,9 @@ impl<'a> NodeHelpers for &'a Node {
     }
 
     fn set_hover_state(self, state: bool) {
+        if self.get_hover_state() == state {
+            return;
+        }
         self.set_flag(IN_HOVER_STATE, state);
         self.dirty(NodeDamage::NodeStyleDamaged);
     }
@@ -636,6 +645,18 @@ impl<'a> NodeHelpers for &'a Node {
         self.dirty(NodeDamage::NodeStyleDamaged);
     }
 
+    fn get_active_state(self) -> bool {
+        self.get_flag(IN_ACTIVE_STATE)
+    }
+
+    fn set_active_state(self, state: bool) {
+        if self.get_active_state() == state {
+            return;
+        }
+        self.set_flag(IN_ACTIVE_STATE, state);
+        self.dirty(NodeDamage::NodeStyleDamaged);
+    }
+
     fn get_disabled_state(self) -> bool {
         self.get_flag(IN_DISABLED_STATE)
     }
@@ -1025,6 +1046,18 @@ impl<'a> NodeHelpers for &'a Node {
         }
     }
 
+    /// Tells the developer tools about a change to this node, if they asked to hear about
+    /// them. `mutation` builds the change from the node's unique ID.
+    fn report_mutation_to_devtools<F>(self, mutation: F) where F: FnOnce(String) -> NodeMutation {
+        if !self.is_in_doc() {
+            return
+        }
+        let window = window_from_node(self);
+        if window.r().devtools_wants_mutations() {
+            window.r().report_node_mutation(mutation(self.get_unique_id()));
+        }
+    }
+
     // https://dvcs.w3.org/hg/innerhtml/raw-file/tip/index.html#dfn-concept-parse-fragment
     fn parse_fragment(self, markup: DOMString) -> Fallible<Root<DocumentFragment>> {
         let context_node: &Node = NodeCast::from_ref(self);
@@ -1194,6 +1227,8 @@ pub trait RawLayoutNodeHelpers {
     #[allow(unsafe_code)]
     unsafe fn get_focus_state_for_layout(&self) -> bool;
     #[allow(unsafe_code)]
+    unsafe fn get_active_state_for_layout(&self) -> bool;
+    #[allow(unsafe_code)]
     unsafe fn get_disabled_state_for_layout(&self) -> bool;
     #[allow(unsafe_code)]
     unsafe fn get_enabled_state_for_layout(&self) -> bool;
@@ -1213,6 +1248,11 @@ impl RawLayoutNodeHelpers for Node {
     }
     #[inline]
     #[allow(unsafe_code)]
+    unsafe fn get_active_state_for_layout(&self) -> bool {
+        self.flags.get().contains(IN_ACTIVE_STATE)
+    }
+    #[inline]
+    #[allow(unsafe_code)]
     unsafe fn get_disabled_state_for_layout(&self) -> bool {
         self.flags.get().contains(IN_DISABLED_STATE)
     }
@@ -1702,6 +1742,11 @@ impl Node {
                 fire_observer_if_necessary(node, suppress_observers);
             }
         }
+
+        parent.report_mutation_to_devtools(|target| NodeMutation::ChildList {
+            target: target,
+            numChildren: parent.children().count(),
+        });
     }
 
     // https://dom.spec.whatwg.org/#concept-node-replace-all
@@ -1780,6 +1825,11 @@ impl Node {
 
         // Step 9.
         node.node_removed(parent.is_in_doc());
+
+        parent.report_mutation_to_devtools(|target| NodeMutation::ChildList {
+            target: target,
+            numChildren: parent.children().count(),
+        });
     }
 
     // https://dom.spec.whatwg.org/#concept-node-clone
@@ -0,0 +1,219 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+use dom::bindings::codegen::Bindings::EventHandlerBinding::EventHandlerNonNull;
+use dom::bindings::codegen::Bindings::NotificationBinding;
+use dom::bindings::codegen::Bindings::NotificationBinding::{NotificationMethods, NotificationOptions};
+use dom::bindings::codegen::Bindings::NotificationBinding::NotificationDirection;
+use dom::bindings::codegen::Bindings::NotificationBinding::NotificationPermission;
+use dom::bindings::codegen::Bindings::NotificationBinding::NotificationPermissionCallback;
+use dom::bindings::codegen::InheritTypes::EventTargetCast;
+use dom::bindings::error::Fallible;
+use dom::bindings::global::GlobalRef;
+use dom::bindings::js::{JS, Root};
+use dom::bindings::refcounted::Trusted;
+use dom::bindings::str::USVString;
+use dom::bindings::utils::reflect_dom_object;
+use dom::event::{Event, EventBubbles, EventCancelable, EventHelpers};
+use dom::eventtarget::{EventTarget, EventTargetHelpers, EventTargetTypeId};
+use dom::window::{Window, WindowHelpers};
+use script_task::{Runnable, ScriptChan, ScriptMsg};
+
+use msg::constellation_msg::{NotificationData, NotificationId};
+use msg::constellation_msg::NotificationDirection as MsgNotificationDirection;
+use util::str::DOMString;
+
+use url::{Url, UrlParser};
+
+use std::borrow::ToOwned;
+use std::rc::Rc;
+
+// https://notifications.spec.whatwg.org/#notification
+#[dom_struct]
+pub struct Notification {
+    eventtarget: EventTarget,
+    window: JS<Window>,
+    id: NotificationId,
+    title: DOMString,
+    dir: NotificationDirection,
+    lang: DOMString,
+    body: DOMString,
+    tag: DOMString,
+    icon: Option<Url>,
+}
+
+impl Notification {
+    fn new_inherited(window: &Window,
+                     id: NotificationId,
+                     title: DOMString,
+                     options: &NotificationOptions,
+                     icon: Option<Url>) -> Notification {
+        Notification {
+            eventtarget: EventTarget::new_inherited(EventTargetTypeId::Notification),
+            window: JS::from_ref(window),
+            id: id,
+            title: title,
+            dir: options.dir,
+            lang: options.lang.clone(),
+            body: options.body.clone(),
+            tag: options.tag.clone(),
+            icon: icon,
+        }
+    }
+
+    fn new(window: &Window,
+           title: DOMString,
+           options: &NotificationOptions,
+           icon: Option<Url>) -> Root<Notification> {
+        let id = window.next_notification_id();
+        reflect_dom_object(box Notification::new_inherited(window, id, title, options, icon),
+                           GlobalRef::Window(window),
+                           NotificationBinding::Wrap)
+    }
+
+    // https://notifications.spec.whatwg.org/#dom-notification-notification
+    pub fn Constructor(global: GlobalRef,
+                       title: DOMString,
+                       options: &NotificationOptions) -> Fallible<Root<Notification>> {
+        let window = global.as_window();
+
+        // An icon URL that fails to parse is ignored.
+        let icon = options.icon.as_ref().and_then(|icon| {
+            UrlParser::new().base_url(&window.get_url()).parse(&icon.0).ok()
+        });
+        let notification = Notification::new(window, title, options, icon);
+
+        if window.notification_permission() != NotificationPermission::Granted {
+            notification.r().queue_event("error");
+        } else {
+            window.show_notification(notification.r());
+            notification.r().queue_event("show");
+        }
+        Ok(notification)
+    }
+
+    // https://notifications.spec.whatwg.org/#dom-notification-permission
+    pub fn Permission(global: GlobalRef) -> NotificationPermission {
+        global.as_window().notification_permission()
+    }
+
+    // https://notifications.spec.whatwg.org/#dom-notification-requestpermission
+    pub fn RequestPermission(global: GlobalRef,
+                             callback: Option<Rc<NotificationPermissionCallback>>) {
+        global.as_window().request_notification_permission(callback);
+    }
+}
+
+pub trait NotificationHelpers {
+    fn id(self) -> NotificationId;
+    fn data(self) -> NotificationData;
+    fn fire_event(self, type_: &str) -> bool;
+    fn queue_event(self, type_: &'static str);
+}
+
+impl<'a> NotificationHelpers for &'a Notification {
+    fn id(self) -> NotificationId {
+        self.id
+    }
+
+    /// The contents of this notification, for the embedder to display.
+    fn data(self) -> NotificationData {
+        NotificationData {
+            title: self.title.clone(),
+            body: self.body.clone(),
+            tag: self.tag.clone(),
+            lang: self.lang.clone(),
+            dir: match self.dir {
+                NotificationDirection::Auto => MsgNotificationDirection::Auto,
+                NotificationDirection::Ltr => MsgNotificationDirection::Ltr,
+                NotificationDirection::Rtl => MsgNotificationDirection::Rtl,
+            },
+            icon: self.icon.clone(),
+        }
+    }
+
+    /// Fires a simple event at this notification. Returns false if it was canceled.
+    fn fire_event(self, type_: &str) -> bool {
+        let window = self.window.root();
+        let cancelable = if type_ == "click" {
+            EventCancelable::Cancelable
+        } else {
+            EventCancelable::NotCancelable
+        };
+        let event = Event::new(GlobalRef::Window(window.r()),
+                               type_.to_owned(),
+                               EventBubbles::DoesNotBubble,
+                               cancelable);
+        event.r().fire(EventTargetCast::from_ref(self))
+    }
+
+    /// Queues a task to fire a simple event at this notification.
+    fn queue_event(self, type_: &'static str) {
+        let window = self.window.root();
+        let window = window.r();
+        let script_chan = window.script_chan();
+        let runnable = box NotificationEventRunnable {
+            notification: Trusted::new(window.get_cx(), self, script_chan.clone()),
+            type_: type_,
+        };
+        script_chan.send(ScriptMsg::RunnableMsg(runnable)).unwrap();
+    }
+}
+
+impl<'a> NotificationMethods for &'a Notification {
+    event_handler!(click, GetOnclick, SetOnclick);
+    event_handler!(show, GetOnshow, SetOnshow);
+    event_handler!(error, GetOnerror, SetOnerror);
+    event_handler!(close, GetOnclose, SetOnclose);
+
+    // https://notifications.spec.whatwg.org/#dom-notification-title
+    fn Title(self) -> DOMString {
+        self.title.clone()
+    }
+
+    // https://notifications.spec.whatwg.org/#dom-notification-dir
+    fn Dir(self) -> NotificationDirection {
+        self.dir
+    }
+
+    // https://notifications.spec.whatwg.org/#dom-notification-lang
+    fn Lang(self) -> DOMString {
+        self.lang.clone()
+    }
+
+    // https://notifications.spec.whatwg.org/#dom-notification-body
+    fn Body(self) -> DOMString {
+        self.body.clone()
+    }
+
+    // https://notifications.spec.whatwg.org/#dom-notification-tag
+    fn Tag(self) -> DOMString {
+        self.tag.clone()
+    }
+
+    // https://notifications.spec.whatwg.org/#dom-notification-icon
+    fn Icon(self) -> USVString {
+        USVString(self.icon.as_ref().map_or("".to_owned(), |icon| icon.serialize()))
+    }
+
+    // https://notifications.spec.whatwg.org/#dom-notification-close
+    fn Close(self) {
+        let window = self.window.root();
+        if window.r().close_notification(self) {
+            self.queue_event("close");
+        }
+    }
+}
+
+struct NotificationEventRunnable {
+    notification: Trusted<Notification>,
+    type_: &'static str,
+}
+
+impl Runnable for NotificationEventRunnable {
+    fn handler(self: Box<NotificationEventRunnable>) {
+        let notification = self.notification.root();
+        notification.r().fire_event(self.type_);
+    }
+}
@@ -0,0 +1,50 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+use dom::bindings::codegen::Bindings::PositionBinding;
+use dom::bindings::codegen::Bindings::PositionBinding::PositionMethods;
+use dom::bindings::global::GlobalRef;
+use dom::bindings::js::{JS, Root};
+use dom::bindings::utils::{Reflector, reflect_dom_object};
+use dom::coordinates::Coordinates;
+use dom::window::Window;
+
+use msg::constellation_msg::GeolocationPosition;
+
+// http://dev.w3.org/geo/api/spec-source.html#position_interface
+#[dom_struct]
+pub struct Position {
+    reflector_: Reflector,
+    coords: JS<Coordinates>,
+    timestamp: u64,
+}
+
+impl Position {
+    fn new_inherited(coords: &Coordinates, timestamp: u64) -> Position {
+        Position {
+            reflector_: Reflector::new(),
+            coords: JS::from_ref(coords),
+            timestamp: timestamp,
+        }
+    }
+
+    pub fn new(window: &Window, position: GeolocationPosition) -> Root<Position> {
+        let coords = Coordinates::new(window, position);
+        reflect_dom_object(box Position::new_inherited(coords.r(), position.timestamp),
+                           GlobalRef::Window(window),
+                           PositionBinding::Wrap)
+    }
+}
+
+impl<'a> PositionMethods for &'a Position {
+    // http://dev.w3.org/geo/api/spec-source.html#coords
+    fn Coords(self) -> Root<Coordinates> {
+        self.coords.root()
+    }
+
+    // http://dev.w3.org/geo/api/spec-source.html#timestamp
+    fn Timestamp(self) -> u64 {
+        self.timestamp
+    }
+}
@@ -0,0 +1,66 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+use dom::bindings::codegen::Bindings::PositionErrorBinding;
+use dom::bindings::codegen::Bindings::PositionErrorBinding::PositionErrorConstants;
+use dom::bindings::codegen::Bindings::PositionErrorBinding::PositionErrorMethods;
+use dom::bindings::global::GlobalRef;
+use dom::bindings::js::Root;
+use dom::bindings::utils::{Reflector, reflect_dom_object};
+use dom::window::Window;
+use util::str::DOMString;
+
+use std::borrow::ToOwned;
+
+#[repr(u16)]
+#[derive(JSTraceable, Copy, Clone, PartialEq, Debug)]
+pub enum PositionErrorCode {
+    PermissionDenied = PositionErrorConstants::PERMISSION_DENIED,
+    PositionUnavailable = PositionErrorConstants::POSITION_UNAVAILABLE,
+    Timeout = PositionErrorConstants::TIMEOUT,
+}
+
+impl PositionErrorCode {
+    fn message(self) -> &'static str {
+        match self {
+            PositionErrorCode::PermissionDenied => "User denied geolocation",
+            PositionErrorCode::PositionUnavailable => "Position unavailable",
+            PositionErrorCode::Timeout => "Position acquisition timed out",
+        }
+    }
+}
+
+// http://dev.w3.org/geo/api/spec-source.html#position_error_interface
+#[dom_struct]
+pub struct PositionError {
+    reflector_: Reflector,
+    code: PositionErrorCode,
+}
+
+impl PositionError {
+    fn new_inherited(code: PositionErrorCode) -> PositionError {
+        PositionError {
+            reflector_: Reflector::new(),
+            code: code,
+        }
+    }
+
+    pub fn new(window: &Window, code: PositionErrorCode) -> Root<PositionError> {
+        reflect_dom_object(box PositionError::new_inherited(code),
+                           GlobalRef::Window(window),
+                           PositionErrorBinding::Wrap)
+    }
+}
+
+impl<'a> PositionErrorMethods for &'a PositionError {
+    // http://dev.w3.org/geo/api/spec-source.html#code
+    fn Code(self) -> u16 {
+        self.code as u16
+    }
+
+    // http://dev.w3.org/geo/api/spec-source.html#message
+    fn Message(self) -> DOMString {
+        self.code.message().to_owned()
+    }
+}
@@ -5,7 +5,7 @@
 //! The bulk of the HTML parser integration is in `script::parse::html`.
 //! This module is mostly about its interaction with DOM memory management.
 
-use document_loader::LoadType;
+use document_loader::{LoadType, load_in_background};
 use dom::bindings::cell::DOMRefCell;
 use dom::bindings::codegen::Bindings::ServoHTMLParserBinding;
 use dom::bindings::global::GlobalRef;
@@ -15,18 +15,21 @@ use dom::bindings::refcounted::Trusted;
 use dom::bindings::utils::{Reflector, reflect_dom_object};
 use dom::document::{Document, DocumentHelpers};
 use dom::node::{window_from_node, Node};
-use dom::window::Window;
+use dom::window::{Window, WindowHelpers};
 use network_listener::PreInvoke;
 use parse::Parser;
+use parse::preload::{PreloadKind, PreloadScanner};
 use script_task::{ScriptTask, ScriptChan};
 
 use msg::constellation_msg::{PipelineId, SubpageId};
-use net_traits::{Metadata, AsyncResponseListener};
+use net_traits::{Metadata, AsyncResponseListener, LoadPriority};
+use net_traits::image_cache_task::ImageCacheChan;
 
 use encoding::all::UTF_8;
 use encoding::types::{Encoding, DecoderTrap};
 use std::cell::{Cell, RefCell};
 use std::default::Default;
+use std::sync::mpsc::channel;
 use url::Url;
 use js::jsapi::JSTracer;
 use html5ever::tokenizer;
@@ -166,6 +169,11 @@ pub struct ServoHTMLParser {
     document: JS<Document>,
     /// True if this parser should avoid passing any further data to the tokenizer.
     suspended: Cell<bool>,
+    /// Looks ahead through the pending input for resources to fetch while the parser is
+    /// suspended.
+    preload_scanner: DOMRefCell<PreloadScanner>,
+    /// How many of the chunks at the front of `pending_input` have been scanned already.
+    scanned_chunks: Cell<usize>,
     /// Whether to expect any further input from the associated network request.
     last_chunk_received: Cell<bool>,
     /// The pipeline associated with this parse, unavailable if this parse does not
@@ -219,6 +227,8 @@ impl ServoHTMLParser {
             pending_input: DOMRefCell::new(vec!()),
             document: JS::from_ref(document),
             suspended: Cell::new(false),
+            preload_scanner: DOMRefCell::new(PreloadScanner::new(document.url())),
+            scanned_chunks: Cell::new(0),
             last_chunk_received: Cell::new(false),
             pipeline: pipeline,
         };
@@ -257,6 +267,8 @@ impl ServoHTMLParser {
             pending_input: DOMRefCell::new(vec!()),
             document: JS::from_ref(document),
             suspended: Cell::new(false),
+            preload_scanner: DOMRefCell::new(PreloadScanner::new(document.url())),
+            scanned_chunks: Cell::new(0),
             last_chunk_received: Cell::new(true),
             pipeline: None,
         };
@@ -277,6 +289,9 @@ trait PrivateServoHTMLParserHelpers {
     fn parse_sync(self);
     /// Retrieve the window object associated with this parser.
     fn window(self) -> Root<Window>;
+    /// Start fetching the resources referred to by input that is waiting for the parser
+    /// to resume.
+    fn preload_pending_input(self);
 }
 
 impl<'a> PrivateServoHTMLParserHelpers for &'a ServoHTMLParser {
@@ -287,6 +302,7 @@ impl<'a> PrivateServoHTMLParserHelpers for &'a ServoHTMLParser {
         // the parser remains unsuspended.
         loop {
             if self.suspended.get() {
+                self.preload_pending_input();
                 return;
             }
 
@@ -300,6 +316,8 @@ impl<'a> PrivateServoHTMLParserHelpers for &'a ServoHTMLParser {
             let mut pending_input = self.pending_input.borrow_mut();
             if !pending_input.is_empty() {
                 let chunk = pending_input.remove(0);
+                let scanned_chunks = self.scanned_chunks.get();
+                self.scanned_chunks.set(scanned_chunks.saturating_sub(1));
                 self.tokenizer.borrow_mut().feed(chunk.into());
             } else {
                 self.tokenizer.borrow_mut().run();
@@ -317,6 +335,52 @@ impl<'a> PrivateServoHTMLParserHelpers for &'a ServoHTMLParser {
         let doc = self.document.root();
         window_from_node(doc.r())
     }
+
+    fn preload_pending_input(self) {
+        // Only page loads block on scripts.
+        if self.pipeline.is_none() {
+            return;
+        }
+
+        let pending_input = self.pending_input.borrow();
+        let scanned_chunks = self.scanned_chunks.get();
+        if scanned_chunks == pending_input.len() {
+            return;
+        }
+        let mut scanner = self.preload_scanner.borrow_mut();
+        if scanned_chunks == 0 {
+            // The parser has been through the input that came before.
+            scanner.reset();
+        }
+        let mut preloads = vec!();
+        for chunk in pending_input[scanned_chunks..].iter() {
+            preloads.extend(scanner.scan(chunk).into_iter());
+        }
+        self.scanned_chunks.set(pending_input.len());
+
+        let window = self.window();
+        for preload in preloads.into_iter() {
+            debug!("preloading {}", preload.url.serialize());
+            match preload.kind {
+                PreloadKind::Image => {
+                    // The image cache keeps the image for when the element asks for it.
+                    let (sender, _) = channel();
+                    window.r().image_cache_task().request_image(preload.url,
+                                                                window.r().pipeline(),
+                                                                ImageCacheChan(sender),
+                                                                None);
+                }
+                PreloadKind::Script => {
+                    load_in_background(window.r().resource_task(), preload.url,
+                                       LoadPriority::Script);
+                }
+                PreloadKind::Stylesheet => {
+                    load_in_background(window.r().resource_task(), preload.url,
+                                       LoadPriority::Style);
+                }
+            }
+        }
+    }
 }
 
 pub trait ServoHTMLParserHelpers {
@@ -2,38 +2,51 @@
  * License, v. 2.0. If a copy of the MPL was not distributed with this
  * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
 
+use dom::bindings::cell::DOMRefCell;
+use dom::bindings::codegen::Bindings::BlobBinding::BlobMethods;
 use dom::bindings::codegen::Bindings::URLBinding::{self, URLMethods};
-use dom::bindings::error::{Error, Fallible};
-use dom::bindings::global::GlobalRef;
-use dom::bindings::js::Root;
+use dom::bindings::error::{Error, ErrorResult, Fallible};
+use dom::bindings::global::{GlobalField, GlobalRef};
+use dom::bindings::js::{JS, MutNullableHeap, Root};
 use dom::bindings::utils::{Reflector, reflect_dom_object};
+use dom::blob::{Blob, BlobHelpers};
 use dom::bindings::str::USVString;
 use dom::urlhelper::UrlHelper;
+use dom::urlsearchparams::{URLObject, URLSearchParams, URLSearchParamsHelpers};
 
+use net_traits::{ControlMsg, serialize_origin};
 use url::{Host, Url, UrlParser};
+use uuid;
 use util::str::DOMString;
 
 use std::borrow::ToOwned;
+use std::default::Default;
 
 // https://url.spec.whatwg.org/#url
 #[dom_struct]
 pub struct URL {
     reflector_: Reflector,
+    global: GlobalField,
 
     // https://url.spec.whatwg.org/#concept-urlutils-url
-    url: Url,
+    url: DOMRefCell<Url>,
+
+    // https://url.spec.whatwg.org/#concept-urlutils-query-object
+    search_params: MutNullableHeap<JS<URLSearchParams>>,
 }
 
 impl URL {
-    fn new_inherited(url: Url) -> URL {
+    fn new_inherited(global: GlobalRef, url: Url) -> URL {
         URL {
             reflector_: Reflector::new(),
-            url: url,
+            global: GlobalField::from_rooted(&global),
+            url: DOMRefCell::new(url),
+            search_params: Default::default(),
         }
     }
 
     pub fn new(global: GlobalRef, url: Url) -> Root<URL> {
-        reflect_dom_object(box URL::new_inherited(url),
+        reflect_dom_object(box URL::new_inherited(global, url),
                            global, URLBinding::Wrap)
     }
 }
@@ -82,52 +95,108 @@ impl URL {
             USVString("".to_owned())
         }
     }
+
+    // http://dev.w3.org/2006/webapi/FileAPI/#dfn-createObjectURL
+    pub fn CreateObjectURL(global: GlobalRef, blob: &Blob) -> DOMString {
+        let url = format!("blob:{}/{}", serialize_origin(&global.get_url()),
+                          uuid::Uuid::new_v4().to_hyphenated_string());
+        let msg = ControlMsg::RegisterBlobURL(Url::parse(&url).unwrap(), blob.bytes(), blob.Type(),
+                                              global.pipeline());
+        global.resource_task().send(msg).unwrap();
+        url
+    }
+
+    // http://dev.w3.org/2006/webapi/FileAPI/#dfn-revokeObjectURL
+    pub fn RevokeObjectURL(global: GlobalRef, url: DOMString) {
+        let url = match Url::parse(&url) {
+            Ok(url) => url,
+            Err(_) => return,
+        };
+        // Only URLs minted by this origin may be revoked.
+        let prefix = format!("blob:{}/", serialize_origin(&global.get_url()));
+        if url.scheme == "blob" && url.serialize().starts_with(&prefix) {
+            global.resource_task().send(ControlMsg::RevokeBlobURL(url)).unwrap();
+        }
+    }
 }
 
 impl<'a> URLMethods for &'a URL {
     // https://url.spec.whatwg.org/#dom-urlutils-hash
     fn Hash(self) -> USVString {
-        UrlHelper::Hash(&self.url)
+        UrlHelper::Hash(&self.url.borrow())
+    }
+
+    // https://url.spec.whatwg.org/#dom-urlutils-hash
+    fn SetHash(self, value: USVString) {
+        UrlHelper::SetHash(&mut self.url.borrow_mut(), value);
     }
 
     // https://url.spec.whatwg.org/#dom-urlutils-host
     fn Host(self) -> USVString {
-        UrlHelper::Host(&self.url)
+        UrlHelper::Host(&self.url.borrow())
     }
 
     // https://url.spec.whatwg.org/#dom-urlutils-hostname
     fn Hostname(self) -> USVString {
-        UrlHelper::Hostname(&self.url)
+        UrlHelper::Hostname(&self.url.borrow())
     }
 
     // https://url.spec.whatwg.org/#dom-urlutils-href
     fn Href(self) -> USVString {
-        UrlHelper::Href(&self.url)
+        UrlHelper::Href(&self.url.borrow())
+    }
+
+    // https://url.spec.whatwg.org/#dom-urlutils-href
+    fn SetHref(self, value: USVString) -> ErrorResult {
+        match Url::parse(&value.0) {
+            Ok(url) => {
+                *self.url.borrow_mut() = url;
+                self.update_search_params();
+                Ok(())
+            },
+            Err(error) => Err(Error::Type(format!("could not parse URL: {}", error))),
+        }
     }
 
     // https://url.spec.whatwg.org/#dom-urlutils-password
     fn Password(self) -> USVString {
-        UrlHelper::Password(&self.url)
+        UrlHelper::Password(&self.url.borrow())
     }
 
     // https://url.spec.whatwg.org/#dom-urlutils-pathname
     fn Pathname(self) -> USVString {
-        UrlHelper::Pathname(&self.url)
+        UrlHelper::Pathname(&self.url.borrow())
     }
 
     // https://url.spec.whatwg.org/#dom-urlutils-port
     fn Port(self) -> USVString {
-        UrlHelper::Port(&self.url)
+        UrlHelper::Port(&self.url.borrow())
     }
 
     // https://url.spec.whatwg.org/#dom-urlutils-protocol
     fn Protocol(self) -> USVString {
-        UrlHelper::Protocol(&self.url)
+        UrlHelper::Protocol(&self.url.borrow())
     }
 
     // https://url.spec.whatwg.org/#dom-urlutils-search
     fn Search(self) -> USVString {
-        UrlHelper::Search(&self.url)
+        UrlHelper::Search(&self.url.borrow())
+    }
+
+    // https://url.spec.whatwg.org/#dom-urlutils-search
+    fn SetSearch(self, value: USVString) {
+        UrlHelper::SetSearch(&mut self.url.borrow_mut(), value);
+        self.update_search_params();
+    }
+
+    // https://url.spec.whatwg.org/#dom-urlutils-searchparams
+    fn SearchParams(self) -> Root<URLSearchParams> {
+        self.search_params.or_init(|| {
+            let global = self.global.root();
+            URLSearchParams::new_with_url_object(global.r(),
+                                                 URLObject::URL(JS::from_ref(self)),
+                                                 self.url.borrow().query.as_ref().map(|q| &**q))
+        })
     }
 
     // https://url.spec.whatwg.org/#URLUtils-stringification-behavior
@@ -137,7 +206,32 @@ impl<'a> URLMethods for &'a URL {
 
     // https://url.spec.whatwg.org/#dom-urlutils-username
     fn Username(self) -> USVString {
-        UrlHelper::Username(&self.url)
+        UrlHelper::Username(&self.url.borrow())
+    }
+}
+
+pub trait URLHelpers {
+    fn set_query(self, query: Option<String>);
+}
+
+impl<'a> URLHelpers for &'a URL {
+    /// Sets the query of this object's URL on behalf of its `URLSearchParams` object.
+    fn set_query(self, query: Option<String>) {
+        self.url.borrow_mut().query = query;
+    }
+}
+
+trait PrivateURLHelpers {
+    fn update_search_params(self);
+}
+
+impl<'a> PrivateURLHelpers for &'a URL {
+    // https://url.spec.whatwg.org/#concept-urlutils-update
+    fn update_search_params(self) {
+        if let Some(search_params) = self.search_params.get() {
+            let search_params = search_params.root();
+            search_params.r().set_query(self.url.borrow().query.as_ref().map(|q| &**q));
+        }
     }
 }
 
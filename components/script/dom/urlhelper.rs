@@ -4,7 +4,7 @@
 
 use dom::bindings::str::USVString;
 
-use url::{Url, SchemeData};
+use url::{Url, UrlParser, SchemeData};
 
 use std::borrow::ToOwned;
 use std::fmt::Write;
@@ -21,6 +21,21 @@ impl UrlHelper {
         })
     }
 
+    // https://url.spec.whatwg.org/#dom-urlutils-hash
+    pub fn SetHash(url: &mut Url, value: USVString) {
+        // Step 2.
+        if value.0.is_empty() {
+            url.fragment = None;
+            return;
+        }
+        // Steps 3-5: parse the input as a fragment of the current URL.
+        let input = if value.0.starts_with("#") { &value.0[1..] } else { &*value.0 };
+        let parsed = UrlParser::new().base_url(url).parse(&format!("#{}", input));
+        if let Ok(parsed) = parsed {
+            *url = parsed;
+        }
+    }
+
     // https://url.spec.whatwg.org/#dom-urlutils-host
     pub fn Host(url: &Url) -> USVString {
         USVString(match url.scheme_data {
@@ -95,6 +110,23 @@ impl UrlHelper {
         })
     }
 
+    // https://url.spec.whatwg.org/#dom-urlutils-search
+    pub fn SetSearch(url: &mut Url, value: USVString) {
+        // Step 2.
+        if value.0.is_empty() {
+            url.query = None;
+            return;
+        }
+        // Steps 3-5: parse the input as a query of the current URL, which drops its
+        // fragment, so put that back afterwards.
+        let input = if value.0.starts_with("?") { &value.0[1..] } else { &*value.0 };
+        let parsed = UrlParser::new().base_url(url).parse(&format!("?{}", input));
+        if let Ok(mut parsed) = parsed {
+            parsed.fragment = url.fragment.take();
+            *url = parsed;
+        }
+    }
+
     // https://url.spec.whatwg.org/#dom-urlutils-username
     pub fn Username(url: &Url) -> USVString {
         USVString(url.username().unwrap_or("").to_owned())
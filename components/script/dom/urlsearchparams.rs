@@ -9,34 +9,58 @@ use dom::bindings::codegen::UnionTypes::StringOrURLSearchParams;
 use dom::bindings::codegen::UnionTypes::StringOrURLSearchParams::{eURLSearchParams, eString};
 use dom::bindings::error::Fallible;
 use dom::bindings::global::GlobalRef;
-use dom::bindings::js::Root;
+use dom::bindings::js::{JS, Root};
 use dom::bindings::utils::{Reflector, reflect_dom_object};
+use dom::htmlanchorelement::{HTMLAnchorElement, HTMLAnchorElementHelpers};
+use dom::url::{URL, URLHelpers};
 
 use encoding::types::EncodingRef;
 use url::form_urlencoded::{parse, serialize_with_encoding};
 use util::str::DOMString;
 
+// https://url.spec.whatwg.org/#concept-urlsearchparams-url-object
+#[derive(JSTraceable)]
+#[must_root]
+pub enum URLObject {
+    URL(JS<URL>),
+    Anchor(JS<HTMLAnchorElement>),
+}
+
 // https://url.spec.whatwg.org/#interface-urlsearchparams
 #[dom_struct]
 pub struct URLSearchParams {
     reflector_: Reflector,
     // https://url.spec.whatwg.org/#concept-urlsearchparams-list
     list: DOMRefCell<Vec<(DOMString, DOMString)>>,
+    // https://url.spec.whatwg.org/#concept-urlsearchparams-url-object
+    url_object: DOMRefCell<Option<URLObject>>,
 }
 
 impl URLSearchParams {
-    fn new_inherited() -> URLSearchParams {
+    #[allow(unrooted_must_root)]
+    fn new_inherited(url_object: Option<URLObject>) -> URLSearchParams {
         URLSearchParams {
             reflector_: Reflector::new(),
             list: DOMRefCell::new(vec![]),
+            url_object: DOMRefCell::new(url_object),
         }
     }
 
     pub fn new(global: GlobalRef) -> Root<URLSearchParams> {
-        reflect_dom_object(box URLSearchParams::new_inherited(), global,
+        reflect_dom_object(box URLSearchParams::new_inherited(None), global,
                            URLSearchParamsBinding::Wrap)
     }
 
+    /// Creates the `URLSearchParams` object reflecting the query of `url_object`'s URL.
+    #[allow(unrooted_must_root)]
+    pub fn new_with_url_object(global: GlobalRef, url_object: URLObject, query: Option<&str>)
+                               -> Root<URLSearchParams> {
+        let params = reflect_dom_object(box URLSearchParams::new_inherited(Some(url_object)),
+                                        global, URLSearchParamsBinding::Wrap);
+        params.r().set_query(query);
+        params
+    }
+
     // https://url.spec.whatwg.org/#dom-urlsearchparams-urlsearchparams
     pub fn Constructor(global: GlobalRef, init: Option<StringOrURLSearchParams>) ->
                        Fallible<Root<URLSearchParams>> {
@@ -91,6 +115,9 @@ impl<'a> URLSearchParamsMethods for &'a URLSearchParams {
         }).next()
     }
 
+    // FIXME: getAll needs sequence return values, and iteration needs `iterable<>`
+    // declarations; the bindings support neither yet.
+
     // https://url.spec.whatwg.org/#dom-urlsearchparams-has
     fn Has(self, name: DOMString) -> bool {
         let list = self.list.borrow();
@@ -118,6 +145,7 @@ impl<'a> URLSearchParamsMethods for &'a URLSearchParams {
             Some(index) => list[index].1 = value,
             None => list.push((name, value)),
         };
+        drop(list);
         self.update_steps();
     }
 
@@ -129,6 +157,7 @@ impl<'a> URLSearchParamsMethods for &'a URLSearchParams {
 
 pub trait URLSearchParamsHelpers {
     fn serialize(self, encoding: Option<EncodingRef>) -> DOMString;
+    fn set_query(self, query: Option<&str>);
 }
 
 impl<'a> URLSearchParamsHelpers for &'a URLSearchParams {
@@ -137,6 +166,15 @@ impl<'a> URLSearchParamsHelpers for &'a URLSearchParams {
         let list = self.list.borrow();
         serialize_with_encoding(list.iter(), encoding)
     }
+
+    /// Replaces the list with the pairs in `query`, after the URL object's URL changed.
+    /// Doesn't run the update steps.
+    fn set_query(self, query: Option<&str>) {
+        *self.list.borrow_mut() = match query {
+            Some(query) => parse(query.as_bytes()),
+            None => vec![],
+        };
+    }
 }
 
 trait PrivateURLSearchParamsHelpers {
@@ -146,6 +184,12 @@ trait PrivateURLSearchParamsHelpers {
 impl<'a> PrivateURLSearchParamsHelpers for &'a URLSearchParams {
     // https://url.spec.whatwg.org/#concept-uq-update
     fn update_steps(self) {
-        // XXXManishearth Implement this when the URL interface is implemented
+        let query = self.serialize(None);
+        let query = if query.is_empty() { None } else { Some(query) };
+        match *self.url_object.borrow() {
+            Some(URLObject::URL(ref url)) => url.root().r().set_query(query),
+            Some(URLObject::Anchor(ref anchor)) => anchor.root().r().set_query(query),
+            None => {},
+        }
     }
 }
@@ -13,40 +13,65 @@ use util::opts;
 use util::resource_files::resources_dir_path;
 use std::borrow::ToOwned;
 use std::fs::read_dir;
+use std::mem;
 use std::path::PathBuf;
 
+/// The URLs of the user scripts, which are listed once when Servo starts.
+static mut USER_SCRIPTS: *const Vec<String> = 0 as *const Vec<String>;
 
+/// Lists the scripts in the directory passed to `--userscripts`. This runs in the process that
+/// starts Servo, before any content does, since the script task mustn't read the disk itself.
+#[allow(unsafe_code)]
+pub fn list_user_scripts() {
+    let path_str = match opts::get().userscripts {
+        Some(ref path_str) => path_str,
+        None => return,
+    };
+    let path = if &**path_str == "" {
+        let mut p = resources_dir_path();
+        p.push("user-agent-js");
+        p
+    } else {
+        PathBuf::from(path_str)
+    };
+
+    let mut files = read_dir(&path).ok().expect("Bad path passed to --userscripts")
+                                   .filter_map(|e| e.ok())
+                                   .map(|e| e.path()).collect::<Vec<_>>();
+
+    files.sort();
+
+    let urls: Vec<String> = files.into_iter().filter_map(|file| {
+        match file.into_os_string().into_string() {
+            Ok(ref s) if s.ends_with(".js") => Some("file://".to_owned() + &s[..]),
+            _ => None
+        }
+    }).collect();
+    unsafe {
+        assert!(USER_SCRIPTS.is_null());
+        USER_SCRIPTS = mem::transmute(box urls);
+    }
+}
+
+#[allow(unsafe_code)]
 pub fn load_script(head: &HTMLHeadElement) {
-    if let Some(ref path_str) = opts::get().userscripts {
-        let node = NodeCast::from_borrowed_ref(&head);
-        let first_child = node.GetFirstChild();
-        let doc = node.owner_doc();
-        let doc = doc.r();
-
-        let path = if &**path_str == "" {
-            let mut p = resources_dir_path();
-            p.push("user-agent-js");
-            p
-        } else {
-            PathBuf::from(path_str)
-        };
-
-        let mut files = read_dir(&path).ok().expect("Bad path passed to --userscripts")
-                                       .filter_map(|e| e.ok())
-                                       .map(|e| e.path()).collect::<Vec<_>>();
-
-        files.sort();
-
-        for file in files {
-            let name = match file.into_os_string().into_string() {
-                Ok(ref s) if s.ends_with(".js") => "file://".to_owned() + &s[..],
-                _ => continue
-            };
-            let new_script = doc.CreateElement("script".to_owned()).unwrap();
-            let new_script = new_script.r();
-            new_script.set_string_attribute(&atom!("src"), name);
-            let new_script_node = NodeCast::from_borrowed_ref(&new_script);
-            node.InsertBefore(*new_script_node, first_child.r()).unwrap();
+    let urls = unsafe {
+        if USER_SCRIPTS.is_null() {
+            return
         }
+        &*USER_SCRIPTS
+    };
+
+    let node = NodeCast::from_borrowed_ref(&head);
+    let first_child = node.GetFirstChild();
+    let doc = node.owner_doc();
+    let doc = doc.r();
+
+    for url in urls {
+        let new_script = doc.CreateElement("script".to_owned()).unwrap();
+        let new_script = new_script.r();
+        new_script.set_string_attribute(&atom!("src"), url.clone());
+        let new_script_node = NodeCast::from_borrowed_ref(&new_script);
+        node.InsertBefore(*new_script_node, first_child.r()).unwrap();
     }
 }
@@ -7,10 +7,14 @@ use dom::bindings::callback::ExceptionHandling;
 use dom::bindings::codegen::Bindings::EventHandlerBinding::{OnErrorEventHandlerNonNull, EventHandlerNonNull};
 use dom::bindings::codegen::Bindings::DocumentBinding::DocumentMethods;
 use dom::bindings::codegen::Bindings::FunctionBinding::Function;
+use dom::bindings::codegen::Bindings::NotificationBinding::NotificationMethods;
+use dom::bindings::codegen::Bindings::NotificationBinding::{NotificationPermission,
+                                                           NotificationPermissionCallback};
 use dom::bindings::codegen::Bindings::WindowBinding::{self, WindowMethods, FrameRequestCallback};
-use dom::bindings::codegen::InheritTypes::{NodeCast, EventTargetCast};
+use dom::bindings::codegen::Bindings::WindowBinding::{ScrollBehavior, ScrollOptions};
+use dom::bindings::codegen::InheritTypes::{EventCast, NodeCast, EventTargetCast};
 use dom::bindings::global::global_object_for_js_object;
-use dom::bindings::error::{report_pending_exception, Fallible};
+use dom::bindings::error::{report_pending_exception, ErrorInfo, Fallible};
 use dom::bindings::error::Error::InvalidCharacter;
 use dom::bindings::global::GlobalRef;
 use dom::bindings::js::{JS, Root, MutNullableHeap};
@@ -21,15 +25,27 @@ use dom::browsercontext::BrowserContext;
 use dom::console::Console;
 use dom::document::{Document, DocumentHelpers};
 use dom::element::Element;
+use dom::errorevent::ErrorEvent;
+use dom::event::{EventBubbles, EventCancelable, EventHelpers};
 use dom::eventtarget::{EventTarget, EventTargetHelpers, EventTargetTypeId};
+use dom::gamepad::{Gamepad, GamepadHelpers};
+use dom::gamepadevent::GamepadEvent;
+use dom::gamepadlist::GamepadList;
+use dom::geolocation::Geolocation;
+use dom::history::History;
 use dom::location::Location;
 use dom::navigator::Navigator;
 use dom::node::{window_from_node, TrustedNodeAddress, NodeHelpers};
+use dom::notification::{Notification, NotificationHelpers};
 use dom::performance::Performance;
 use dom::screen::Screen;
 use dom::storage::Storage;
+use dom::urlhelper::UrlHelper;
+use gamepad::{GamepadPoller, MAX_GAMEPADS};
 use layout_interface::{ReflowGoal, ReflowQueryType, LayoutRPC, LayoutChan, Reflow, Msg};
 use layout_interface::{ContentBoxResponse, ContentBoxesResponse, ScriptReflow};
+use layout_interface::{BoxModel, BoxModelResponse, CaretRectResponse};
+use layout_interface::{MatchedRule, MatchedRulesResponse, ScrollAreaResponse};
 use page::Page;
 use script_task::{TimerSource, ScriptChan, ScriptPort, NonWorkerScriptChan};
 use script_task::ScriptMsg;
@@ -38,10 +54,16 @@ use timers::{IsInterval, TimerId, TimerManager, TimerCallback};
 use webdriver_handlers::jsval_to_webdriver;
 
 use devtools_traits::{DevtoolsControlChan, TimelineMarker, TimelineMarkerType, TracingMetadata};
-use msg::compositor_msg::ScriptListener;
+use devtools_traits::{ConsoleMessage, DevtoolsControlMsg, LogLevel, NodeMutation};
+use msg::compositor_msg::{LayerId, ScriptListener};
+use msg::compositor_msg::ScrollBehavior as CompositorScrollBehavior;
 use msg::constellation_msg::{LoadData, PipelineId, SubpageId, ConstellationChan, WindowSizeData, WorkerId};
+use msg::constellation_msg::{NotificationEventType, NotificationId, PermissionName};
+use msg::constellation_msg::ReferrerPolicy;
+use msg::constellation_msg::DocumentState;
+use msg::constellation_msg::Msg as ConstellationMsg;
 use msg::webdriver_msg::{WebDriverJSError, WebDriverJSResult};
-use net_traits::ResourceTask;
+use net_traits::{ControlMsg, ResourceTask};
 use net_traits::image_cache_task::{ImageCacheChan, ImageCacheTask};
 use net_traits::storage_task::{StorageTask, StorageType};
 use util::geometry::{self, Au, MAX_RECT};
@@ -50,7 +72,8 @@ use util::str::{DOMString,HTML_SPACE_CHARACTERS};
 
 use euclid::{Point2D, Rect, Size2D};
 use js::jsapi::{Evaluate2, MutableHandleValue};
-use js::jsapi::{JSContext, HandleValue};
+use js::jsapi::{JSContext, HandleValue, RootedValue};
+use js::jsval::NullValue;
 use js::jsapi::{JS_GC, JS_GetRuntime, JSAutoCompartment, JSAutoRequest};
 use js::rust::Runtime;
 use js::rust::CompileOptionsWrapper;
@@ -60,7 +83,7 @@ use libc;
 use rustc_serialize::base64::{FromBase64, ToBase64, STANDARD};
 use std::borrow::ToOwned;
 use std::cell::{Cell, Ref, RefMut, RefCell};
-use std::collections::HashSet;
+use std::collections::{HashMap, HashSet};
 use std::default::Default;
 use std::ffi::CString;
 use std::mem;
@@ -83,6 +106,7 @@ pub enum ReflowReason {
     RefreshTick,
     FirstLoad,
     KeyEvent,
+    ImeEvent,
     MouseEvent,
     Query,
     Timer,
@@ -92,6 +116,7 @@ pub enum ReflowReason {
     DocumentLoaded,
     ImageLoaded,
     RequestAnimationFrame,
+    WebFontLoaded,
 }
 
 #[dom_struct]
@@ -101,6 +126,8 @@ pub struct Window {
     control_chan: ScriptControlChan,
     console: MutNullableHeap<JS<Console>>,
     navigator: MutNullableHeap<JS<Navigator>>,
+    geolocation: MutNullableHeap<JS<Geolocation>>,
+    history: MutNullableHeap<JS<History>>,
     image_cache_task: ImageCacheTask,
     image_cache_chan: ImageCacheChan,
     compositor: DOMRefCell<Box<ScriptListener+'static>>,
@@ -127,6 +154,9 @@ pub struct Window {
     /// page changes.
     devtools_wants_updates: Cell<bool>,
 
+    /// Whether the developer tools have asked to be told about changes to the DOM.
+    devtools_wants_mutations: Cell<bool>,
+
     next_subpage_id: Cell<SubpageId>,
 
     /// Pending resize event, if any.
@@ -175,14 +205,44 @@ pub struct Window {
     /// to prevent creating display list items for content that is far away from the viewport.
     page_clip_rect: Cell<Rect<Au>>,
 
+    /// The rectangle of the page currently visible in the viewport.
+    current_viewport: Cell<Rect<Au>>,
+
     /// A counter of the number of pending reflows for this window.
     pending_reflow_count: Cell<u32>,
 
+    /// The number of images requested from the image cache that haven't arrived yet.
+    pending_image_count: Cell<u32>,
+
     /// A channel for communicating results of async scripts back to the webdriver server
     webdriver_script_chan: RefCell<Option<Sender<WebDriverJSResult>>>,
 
     /// The current state of the window object
     current_state: Cell<WindowState>,
+
+    /// Whether an error is currently being reported, to avoid reporting errors thrown by
+    /// the error handlers themselves.
+    /// https://html.spec.whatwg.org/multipage/#in-error-reporting-mode
+    in_error_reporting_mode: Cell<bool>,
+
+    /// The task polling the game controllers of this script task.
+    gamepad_poller: Rc<GamepadPoller>,
+
+    /// The objects reflecting the connected game controllers, one per controller slot.
+    gamepads: DOMRefCell<Vec<Option<JS<Gamepad>>>>,
+
+    /// Whether the gamepad snapshot is held for the duration of an animation frame.
+    gamepads_frozen: Cell<bool>,
+
+
+    /// Callbacks waiting for the answer to a notification permission request.
+    notification_permission_callbacks: DOMRefCell<Vec<Rc<NotificationPermissionCallback>>>,
+
+    /// The notifications currently being displayed by the embedder.
+    notifications: DOMRefCell<HashMap<NotificationId, JS<Notification>>>,
+
+    /// The ID to give to the next notification created by this window.
+    next_notification_id: Cell<NotificationId>,
 }
 
 impl Window {
@@ -344,6 +404,11 @@ impl<'a> WindowMethods for &'a Window {
         self.Document().r().Location()
     }
 
+    // https://html.spec.whatwg.org/multipage/#dom-history
+    fn History(self) -> Root<History> {
+        self.history.or_init(|| History::new(self))
+    }
+
     // https://html.spec.whatwg.org/#dom-sessionstorage
     fn SessionStorage(self) -> Root<Storage> {
         self.session_storage.or_init(|| Storage::new(&GlobalRef::Window(self), StorageType::Session))
@@ -446,11 +511,55 @@ impl<'a> WindowMethods for &'a Window {
     global_event_handlers!();
     event_handler!(unload, GetOnunload, SetOnunload);
     error_event_handler!(error, GetOnerror, SetOnerror);
+    event_handler!(gamepadconnected, GetOngamepadconnected, SetOngamepadconnected);
+    event_handler!(gamepaddisconnected, GetOngamepaddisconnected, SetOngamepaddisconnected);
 
     fn Screen(self) -> Root<Screen> {
         self.screen.or_init(|| Screen::new(self))
     }
 
+    // http://dev.w3.org/csswg/cssom-view/#dom-window-scrollx
+    fn ScrollX(self) -> Finite<f64> {
+        Finite::wrap(self.current_viewport.get().origin.x.to_f64_px())
+    }
+
+    // http://dev.w3.org/csswg/cssom-view/#dom-window-pagexoffset
+    fn PageXOffset(self) -> Finite<f64> {
+        self.ScrollX()
+    }
+
+    // http://dev.w3.org/csswg/cssom-view/#dom-window-scrolly
+    fn ScrollY(self) -> Finite<f64> {
+        Finite::wrap(self.current_viewport.get().origin.y.to_f64_px())
+    }
+
+    // http://dev.w3.org/csswg/cssom-view/#dom-window-pageyoffset
+    fn PageYOffset(self) -> Finite<f64> {
+        self.ScrollY()
+    }
+
+    // http://dev.w3.org/csswg/cssom-view/#dom-window-scroll
+    fn Scroll(self, x: f64, y: f64, options: &ScrollOptions) {
+        self.scroll(x, y, options.behavior);
+    }
+
+    // http://dev.w3.org/csswg/cssom-view/#dom-window-scrollto
+    fn ScrollTo(self, x: f64, y: f64, options: &ScrollOptions) {
+        self.Scroll(x, y, options);
+    }
+
+    // http://dev.w3.org/csswg/cssom-view/#dom-window-scrollby
+    fn ScrollBy(self, x: f64, y: f64, options: &ScrollOptions) {
+        // Step 1-2
+        let x = if x.is_finite() { x } else { 0.0f64 };
+        let y = if y.is_finite() { y } else { 0.0f64 };
+        // Step 3-4
+        let left = x + *self.ScrollX();
+        let top = y + *self.ScrollY();
+        // Step 5
+        self.scroll(left, top, options.behavior);
+    }
+
     fn Debug(self, message: DOMString) {
         debug!("{}", message);
     }
@@ -507,7 +616,7 @@ impl<'a> WindowMethods for &'a Window {
 pub trait WindowHelpers {
     fn clear_js_runtime(self);
     fn init_browser_context(self, doc: &Document, frame_element: Option<&Element>);
-    fn load_url(self, href: DOMString);
+    fn load_url(self, href: DOMString, referrer_policy: Option<ReferrerPolicy>);
     fn handle_fire_timer(self, timer_id: TimerId);
     fn force_reflow(self, goal: ReflowGoal, query_type: ReflowQueryType, reason: ReflowReason);
     fn reflow(self, goal: ReflowGoal, query_type: ReflowQueryType, reason: ReflowReason);
@@ -515,6 +624,10 @@ pub trait WindowHelpers {
     fn layout(&self) -> &LayoutRPC;
     fn content_box_query(self, content_box_request: TrustedNodeAddress) -> Rect<Au>;
     fn content_boxes_query(self, content_boxes_request: TrustedNodeAddress) -> Vec<Rect<Au>>;
+    fn box_model_query(self, node: TrustedNodeAddress) -> Option<BoxModel>;
+    fn matched_rules_query(self, node: TrustedNodeAddress) -> Vec<MatchedRule>;
+    fn caret_rect_query(self, node: TrustedNodeAddress, offset: usize) -> Option<Rect<Au>>;
+    fn scroll_area_query(self) -> Size2D<Au>;
     fn handle_reflow_complete_msg(self, reflow_id: u32);
     fn handle_resize_inactive_msg(self, new_size: WindowSizeData);
     fn set_fragment_name(self, fragment: Option<String>);
@@ -531,13 +644,38 @@ pub trait WindowHelpers {
     fn layout_is_idle(self) -> bool;
     fn get_pending_reflow_count(self) -> u32;
     fn add_pending_reflow(self);
+    fn get_pending_image_count(self) -> u32;
+    fn image_load_started(self);
+    fn image_load_finished(self);
     fn set_resize_event(self, event: WindowSizeData);
     fn steal_resize_event(self) -> Option<WindowSizeData>;
     fn set_page_clip_rect_with_new_viewport(self, viewport: Rect<f32>) -> bool;
+    fn scroll(self, x: f64, y: f64, behavior: ScrollBehavior);
+    fn scroll_rect_into_view(self, rect: Rect<Au>, align_to_top: bool);
+    fn report_an_error(self, error_info: ErrorInfo, value: HandleValue);
+    fn gamepads(self) -> Root<GamepadList>;
+    fn start_gamepad_polling(self);
+    fn update_gamepads(self);
+    fn freeze_gamepads(self);
+    fn thaw_gamepads(self);
+    fn handle_gamepad_connection_changed(self, index: u32, connected: bool);
+    fn notification_permission(self) -> NotificationPermission;
+    fn request_notification_permission(self, callback: Option<Rc<NotificationPermissionCallback>>);
+    fn handle_notification_permission(self, granted: bool);
+    fn next_notification_id(self) -> NotificationId;
+    fn show_notification(self, notification: &Notification);
+    fn close_notification(self, notification: &Notification) -> bool;
+    fn handle_notification_event(self, id: NotificationId, event_type: NotificationEventType);
+    fn geolocation(self) -> Root<Geolocation>;
     fn set_devtools_wants_updates(self, value: bool);
+    fn set_devtools_wants_mutations(self, value: bool);
+    fn devtools_wants_mutations(self) -> bool;
+    fn report_node_mutation(self, mutation: NodeMutation);
     fn IndexedGetter(self, _index: u32, _found: &mut bool) -> Option<Root<Window>>;
     fn thaw(self);
     fn freeze(self);
+    fn document_state(self) -> DocumentState;
+    fn restore_document_state(self, state: &DocumentState);
     fn need_emit_timeline_marker(self, timeline_type: TimelineMarkerType) -> bool;
     fn emit_timeline_marker(self, marker: TimelineMarker);
     fn set_devtools_timeline_marker(self, marker: TimelineMarkerType, reply: Sender<TimelineMarker>);
@@ -600,6 +738,10 @@ impl<'a> WindowHelpers for &'a Window {
         // which causes a panic!
         self.Gc();
 
+        // The blob: URLs the document minted go away with it.
+        let msg = ControlMsg::RevokeBlobURLsForPipeline(self.id);
+        let _ = self.resource_task.send(msg);
+
         self.current_state.set(WindowState::Zombie);
         *self.js_runtime.borrow_mut() = None;
         *self.browser_context.borrow_mut() = None;
@@ -744,6 +886,42 @@ impl<'a> WindowHelpers for &'a Window {
         rects
     }
 
+    fn box_model_query(self, node: TrustedNodeAddress) -> Option<BoxModel> {
+        self.reflow(ReflowGoal::ForScriptQuery,
+                    ReflowQueryType::BoxModelQuery(node),
+                    ReflowReason::Query);
+        self.join_layout();
+        let BoxModelResponse(box_model) = self.layout_rpc.box_model();
+        box_model
+    }
+
+    fn matched_rules_query(self, node: TrustedNodeAddress) -> Vec<MatchedRule> {
+        self.reflow(ReflowGoal::ForScriptQuery,
+                    ReflowQueryType::MatchedRulesQuery(node),
+                    ReflowReason::Query);
+        self.join_layout();
+        let MatchedRulesResponse(rules) = self.layout_rpc.matched_rules();
+        rules
+    }
+
+    fn caret_rect_query(self, node: TrustedNodeAddress, offset: usize) -> Option<Rect<Au>> {
+        self.reflow(ReflowGoal::ForScriptQuery,
+                    ReflowQueryType::CaretRectQuery(node, offset),
+                    ReflowReason::Query);
+        self.join_layout();
+        let CaretRectResponse(rect) = self.layout_rpc.caret_rect();
+        rect
+    }
+
+    fn scroll_area_query(self) -> Size2D<Au> {
+        self.reflow(ReflowGoal::ForScriptQuery,
+                    ReflowQueryType::ScrollAreaQuery,
+                    ReflowReason::Query);
+        self.join_layout();
+        let ScrollAreaResponse(size) = self.layout_rpc.scroll_area();
+        size
+    }
+
     fn handle_reflow_complete_msg(self, reflow_id: u32) {
         let last_reflow_id = self.last_reflow_id.get();
         if last_reflow_id == reflow_id {
@@ -762,7 +940,7 @@ impl<'a> WindowHelpers for &'a Window {
     }
 
     /// Commence a new URL load which will either replace this window or scroll to a fragment.
-    fn load_url(self, href: DOMString) {
+    fn load_url(self, href: DOMString, referrer_policy: Option<ReferrerPolicy>) {
         let base_url = self.get_url();
         debug!("current page url is {}", base_url);
         let url = UrlParser::new().base_url(&base_url).parse(&href);
@@ -773,7 +951,10 @@ impl<'a> WindowHelpers for &'a Window {
                 self.script_chan.send(ScriptMsg::TriggerFragment(self.id, fragment)).unwrap();
             },
             None => {
-                self.script_chan.send(ScriptMsg::Navigate(self.id, LoadData::new(url))).unwrap();
+                let mut load_data = LoadData::new(url);
+                load_data.referrer_url = Some(base_url);
+                load_data.referrer_policy = referrer_policy;
+                self.script_chan.send(ScriptMsg::Navigate(self.id, load_data)).unwrap();
             }
         }
     }
@@ -843,6 +1024,21 @@ impl<'a> WindowHelpers for &'a Window {
         self.pending_reflow_count.set(self.pending_reflow_count.get() + 1);
     }
 
+    fn get_pending_image_count(self) -> u32 {
+        self.pending_image_count.get()
+    }
+
+    fn image_load_started(self) {
+        self.pending_image_count.set(self.pending_image_count.get() + 1);
+    }
+
+    fn image_load_finished(self) {
+        let count = self.pending_image_count.get();
+        if count > 0 {
+            self.pending_image_count.set(count - 1);
+        }
+    }
+
     fn set_resize_event(self, event: WindowSizeData) {
         self.resize_event.set(Some(event));
     }
@@ -858,6 +1054,8 @@ impl<'a> WindowHelpers for &'a Window {
         // so that we don't collect display list items for areas too far outside the viewport,
         // but also don't trigger reflows every time the viewport changes.
         static VIEWPORT_EXPANSION: f32 = 2.0; // 2 lengths on each side plus original length is 5 total.
+       self.current_viewport.set(geometry::f32_rect_to_au_rect(viewport));
+
        let proposed_clip_rect = geometry::f32_rect_to_au_rect(
            viewport.inflate(viewport.size.width * VIEWPORT_EXPANSION,
            viewport.size.height * VIEWPORT_EXPANSION));
@@ -878,10 +1076,332 @@ impl<'a> WindowHelpers for &'a Window {
        had_clip_rect
     }
 
+    /// http://dev.w3.org/csswg/cssom-view/#dom-window-scroll
+    fn scroll(self, x: f64, y: f64, behavior: ScrollBehavior) {
+        // Step 3
+        let x = if x.is_finite() { x } else { 0.0f64 };
+        let y = if y.is_finite() { y } else { 0.0f64 };
+
+        // Step 4
+        let viewport = self.current_viewport.get();
+        if viewport.is_empty() {
+            return;
+        }
+
+        // Step 5-8
+        let scroll_area = self.scroll_area_query();
+        let max_x = (scroll_area.width - viewport.size.width).to_f64_px();
+        let max_y = (scroll_area.height - viewport.size.height).to_f64_px();
+        let x = x.min(max_x).max(0.0f64);
+        let y = y.min(max_y).max(0.0f64);
+
+        // Step 10
+        let point = Point2D::new(Au::from_f64_px(x), Au::from_f64_px(y));
+        if point == viewport.origin {
+            return;
+        }
+        self.current_viewport.set(Rect::new(point, viewport.size));
+
+        // Step 11
+        let behavior = match behavior {
+            ScrollBehavior::Smooth => CompositorScrollBehavior::Smooth,
+            ScrollBehavior::Auto | ScrollBehavior::Instant => CompositorScrollBehavior::Instant,
+        };
+        // TODO: Once layout can tell us which layer the root element lives in, scroll that
+        // instead of the root layer of this pipeline.
+        self.compositor().scroll_fragment_point(self.id,
+                                                LayerId::null(),
+                                                Point2D::new(x as f32, y as f32),
+                                                behavior);
+    }
+
+    /// http://dev.w3.org/csswg/cssom-view/#scroll-an-element-into-view
+    fn scroll_rect_into_view(self, rect: Rect<Au>, align_to_top: bool) {
+        let viewport = self.current_viewport.get();
+
+        // Align the element's edge with the viewport edge requested by the caller.
+        let y = if align_to_top {
+            rect.origin.y
+        } else {
+            rect.max_y() - viewport.size.height
+        };
+
+        // Horizontally, scroll only as much as is needed to bring the element into view.
+        let x = if rect.origin.x < viewport.origin.x {
+            rect.origin.x
+        } else if rect.max_x() > viewport.max_x() {
+            rect.max_x() - viewport.size.width
+        } else {
+            viewport.origin.x
+        };
+
+        self.scroll(x.to_f64_px(), y.to_f64_px(), ScrollBehavior::Auto);
+    }
+
+    /// https://html.spec.whatwg.org/multipage/#report-the-error
+    fn report_an_error(self, error_info: ErrorInfo, value: HandleValue) {
+        // Step 1.
+        if self.in_error_reporting_mode.get() {
+            return;
+        }
+
+        // Step 2.
+        self.in_error_reporting_mode.set(true);
+
+        // Steps 3-12.
+        // Errors in scripts from another origin are muted, so as not to leak their contents.
+        // FIXME: scripts fetched with CORS should not be muted.
+        let cx = self.get_cx();
+        let muted = match Url::parse(&error_info.filename) {
+            Ok(ref script_url) => !UrlHelper::SameOrigin(script_url, &self.get_url()),
+            Err(_) => false,
+        };
+        let (message, filename, lineno, column, error) = if muted {
+            ("Script error.".to_owned(), "".to_owned(), 0, 0, RootedValue::new(cx, NullValue()))
+        } else {
+            (error_info.message.clone(), error_info.filename.clone(),
+             error_info.lineno, error_info.column, RootedValue::new(cx, value.get()))
+        };
+        let event = ErrorEvent::new(GlobalRef::Window(self),
+                                    "error".to_owned(),
+                                    EventBubbles::DoesNotBubble,
+                                    EventCancelable::Cancelable,
+                                    message,
+                                    filename,
+                                    lineno,
+                                    column,
+                                    error.handle());
+        let event = EventCast::from_ref(event.r());
+        let not_handled = event.fire(EventTargetCast::from_ref(self));
+
+        // Step 13.
+        self.in_error_reporting_mode.set(false);
+
+        // Step 14.
+        if not_handled {
+            error!("Error at {}:{}:{} {}", error_info.filename, error_info.lineno,
+                   error_info.column, error_info.message);
+
+            if let Some(ref chan) = self.devtools_chan {
+                let console_message = ConsoleMessage {
+                    message: error_info.message,
+                    logLevel: LogLevel::Error,
+                    filename: error_info.filename,
+                    lineNumber: error_info.lineno,
+                    columnNumber: error_info.column,
+                };
+                chan.send(DevtoolsControlMsg::SendConsoleMessage(self.id, console_message))
+                    .unwrap();
+            }
+        }
+    }
+
+    /// Returns the connected game controllers. Outside of animation frame callbacks, the
+    /// snapshot of their state is refreshed first.
+    fn gamepads(self) -> Root<GamepadList> {
+        self.start_gamepad_polling();
+        if !self.gamepads_frozen.get() {
+            self.update_gamepads();
+        }
+        let gamepads: Vec<Option<Root<Gamepad>>> =
+            self.gamepads.borrow().iter().map(|g| g.as_ref().map(|g| g.root())).collect();
+        GamepadList::new(self, &gamepads)
+    }
+
+    /// Starts watching the game controllers, once content listens for their connections.
+    fn start_gamepad_polling(self) {
+        self.gamepad_poller.start();
+    }
+
+    /// Copies the latest state seen by the polling task into the gamepad objects.
+    fn update_gamepads(self) {
+        let states = self.gamepad_poller.states();
+        let states = states.lock().unwrap();
+        let mut gamepads = self.gamepads.borrow_mut();
+        for (index, (state, gamepad)) in states.iter().zip(gamepads.iter_mut()).enumerate() {
+            let existing = gamepad.as_ref().map(|g| g.root());
+            match *state {
+                Some(ref state) => {
+                    let timestamp =
+                        (state.timestamp as f64 - self.navigation_start_precise) / 1000000.;
+                    let timestamp = timestamp.max(0.);
+                    match existing {
+                        Some(existing) => existing.r().update(state, timestamp),
+                        None => {
+                            let new = Gamepad::new(self, index as i32, state, timestamp);
+                            *gamepad = Some(JS::from_rooted(&new));
+                        }
+                    }
+                }
+                None => {
+                    if let Some(existing) = existing {
+                        existing.r().set_disconnected();
+                        *gamepad = None;
+                    }
+                }
+            }
+        }
+    }
+
+    /// Takes a snapshot of the game controllers that stays unchanged until `thaw_gamepads`.
+    fn freeze_gamepads(self) {
+        self.update_gamepads();
+        self.gamepads_frozen.set(true);
+    }
+
+    fn thaw_gamepads(self) {
+        self.gamepads_frozen.set(false);
+    }
+
+    /// https://w3c.github.io/gamepad/#the-gamepadconnected-event
+    /// https://w3c.github.io/gamepad/#the-gamepaddisconnected-event
+    fn handle_gamepad_connection_changed(self, index: u32, connected: bool) {
+        let index = index as usize;
+        if index >= MAX_GAMEPADS {
+            return;
+        }
+
+        let previous = self.gamepads.borrow()[index].as_ref().map(|g| g.root());
+        self.update_gamepads();
+        let (type_, gamepad) = if connected {
+            ("gamepadconnected", self.gamepads.borrow()[index].as_ref().map(|g| g.root()))
+        } else {
+            ("gamepaddisconnected", previous)
+        };
+        let gamepad = match gamepad {
+            Some(gamepad) => gamepad,
+            None => return,
+        };
+
+        let event = GamepadEvent::new(GlobalRef::Window(self),
+                                      type_.to_owned(),
+                                      EventBubbles::DoesNotBubble,
+                                      EventCancelable::NotCancelable,
+                                      Some(gamepad.r()));
+        let event = EventCast::from_ref(event.r());
+        event.fire(EventTargetCast::from_ref(self));
+    }
+
+    /// Whether this document may display notifications. The constellation remembers the user's
+    /// answer for the document's origin, so it holds for every document of that origin.
+    fn notification_permission(self) -> NotificationPermission {
+        let ConstellationChan(ref chan) = self.constellation_chan;
+        let (sender, receiver) = channel();
+        let msg = ConstellationMsg::GetPermission(PermissionName::Notifications,
+                                                  self.get_url(),
+                                                  sender);
+        chan.send(msg).unwrap();
+        match receiver.recv().unwrap() {
+            Some(true) => NotificationPermission::Granted,
+            Some(false) => NotificationPermission::Denied,
+            None => NotificationPermission::Default,
+        }
+    }
+
+    /// Asks the user whether this document may display notifications.
+    fn request_notification_permission(self, callback: Option<Rc<NotificationPermissionCallback>>) {
+        if let Some(callback) = callback {
+            self.notification_permission_callbacks.borrow_mut().push(callback);
+        }
+        let ConstellationChan(ref chan) = self.constellation_chan;
+        let msg = ConstellationMsg::RequestPermission(self.id,
+                                                      PermissionName::Notifications,
+                                                      self.get_url());
+        chan.send(msg).unwrap();
+    }
+
+    fn handle_notification_permission(self, granted: bool) {
+        let permission = if granted {
+            NotificationPermission::Granted
+        } else {
+            NotificationPermission::Denied
+        };
+
+        let callbacks = mem::replace(&mut *self.notification_permission_callbacks.borrow_mut(),
+                                     vec!());
+        for callback in callbacks {
+            let _ = callback.Call__(permission, ExceptionHandling::Report);
+        }
+    }
+
+    fn next_notification_id(self) -> NotificationId {
+        let NotificationId(id) = self.next_notification_id.get();
+        self.next_notification_id.set(NotificationId(id + 1));
+        NotificationId(id)
+    }
+
+    /// https://notifications.spec.whatwg.org/#show-steps
+    fn show_notification(self, notification: &Notification) {
+        let ConstellationChan(ref chan) = self.constellation_chan;
+
+        // A notification replaces any notification with the same tag, without firing
+        // a close event at the old one.
+        let tag = notification.Tag();
+        if !tag.is_empty() {
+            let replaced = self.notifications.borrow().iter()
+                .map(|(_, old)| old.root())
+                .find(|old| old.r().Tag() == tag);
+            if let Some(replaced) = replaced {
+                let id = replaced.r().id();
+                self.notifications.borrow_mut().remove(&id);
+                chan.send(ConstellationMsg::CloseNotification(self.id, id)).unwrap();
+            }
+        }
+
+        let id = notification.id();
+        self.notifications.borrow_mut().insert(id, JS::from_ref(notification));
+        chan.send(ConstellationMsg::ShowNotification(self.id, id, notification.data())).unwrap();
+    }
+
+    /// Stops displaying a notification. Returns false if it wasn't being displayed.
+    fn close_notification(self, notification: &Notification) -> bool {
+        let id = notification.id();
+        if self.notifications.borrow_mut().remove(&id).is_none() {
+            return false;
+        }
+        let ConstellationChan(ref chan) = self.constellation_chan;
+        chan.send(ConstellationMsg::CloseNotification(self.id, id)).unwrap();
+        true
+    }
+
+    fn handle_notification_event(self, id: NotificationId, event_type: NotificationEventType) {
+        let notification = match self.notifications.borrow().get(&id) {
+            Some(notification) => notification.root(),
+            None => return,
+        };
+        match event_type {
+            NotificationEventType::Click => {
+                notification.r().fire_event("click");
+            }
+            NotificationEventType::Close => {
+                self.notifications.borrow_mut().remove(&id);
+                notification.r().fire_event("close");
+            }
+        }
+    }
+
+    fn geolocation(self) -> Root<Geolocation> {
+        self.geolocation.or_init(|| Geolocation::new(self))
+    }
+
     fn set_devtools_wants_updates(self, value: bool) {
         self.devtools_wants_updates.set(value);
     }
 
+    fn set_devtools_wants_mutations(self, value: bool) {
+        self.devtools_wants_mutations.set(value);
+    }
+
+    fn devtools_wants_mutations(self) -> bool {
+        self.devtools_wants_mutations.get() && self.devtools_chan.is_some()
+    }
+
+    fn report_node_mutation(self, mutation: NodeMutation) {
+        if let Some(ref chan) = self.devtools_chan {
+            chan.send(DevtoolsControlMsg::NodeMutated(self.id, mutation)).unwrap();
+        }
+    }
+
     // https://html.spec.whatwg.org/multipage/#accessing-other-browsing-contexts
     fn IndexedGetter(self, _index: u32, _found: &mut bool) -> Option<Root<Window>> {
         None
@@ -894,10 +1414,41 @@ impl<'a> WindowHelpers for &'a Window {
         // activating this document due to a navigation.
         let document = self.Document();
         document.r().title_changed();
+
+        // The compositor doesn't remember how far frozen documents were scrolled.
+        let origin = self.current_viewport.get().origin;
+        self.compositor().scroll_fragment_point(self.id,
+                                                LayerId::null(),
+                                                Point2D::new(origin.x.to_f32_px(),
+                                                             origin.y.to_f32_px()),
+                                                CompositorScrollBehavior::Instant);
     }
 
     fn freeze(self) {
         self.timers.suspend();
+
+        // The constellation may close this pipeline to save memory, in which case the document
+        // is reloaded from the network if the user goes back to it.
+        let ConstellationChan(ref chan) = self.constellation_chan;
+        chan.send(ConstellationMsg::SaveDocumentState(self.id, self.document_state())).unwrap();
+    }
+
+    /// The scroll position and edited form controls of this window's document.
+    fn document_state(self) -> DocumentState {
+        let origin = self.current_viewport.get().origin;
+        DocumentState {
+            scroll_position: Point2D::new(origin.x.to_f32_px(), origin.y.to_f32_px()),
+            form_controls: self.Document().r().form_control_states(),
+        }
+    }
+
+    /// Puts this window's freshly loaded document back in the state a previous load of it
+    /// was left in.
+    fn restore_document_state(self, state: &DocumentState) {
+        self.Document().r().restore_form_control_states(&state.form_controls);
+        self.scroll(state.scroll_position.x as f64,
+                    state.scroll_position.y as f64,
+                    ScrollBehavior::Instant);
     }
 
     fn need_emit_timeline_marker(self, timeline_type: TimelineMarkerType) -> bool {
@@ -958,7 +1509,8 @@ impl Window {
                layout_chan: LayoutChan,
                id: PipelineId,
                parent_info: Option<(PipelineId, SubpageId)>,
-               window_size: Option<WindowSizeData>)
+               window_size: Option<WindowSizeData>,
+               gamepad_poller: Rc<GamepadPoller>)
                -> Root<Window> {
         let layout_rpc: Box<LayoutRPC> = {
             let (rpc_send, rpc_recv) = channel();
@@ -976,6 +1528,8 @@ impl Window {
             compositor: DOMRefCell::new(compositor),
             page: page,
             navigator: Default::default(),
+            geolocation: Default::default(),
+            history: Default::default(),
             image_cache_task: image_cache_task,
             devtools_chan: devtools_chan,
             browser_context: DOMRefCell::new(None),
@@ -995,6 +1549,7 @@ impl Window {
             storage_task: storage_task,
             constellation_chan: constellation_chan,
             page_clip_rect: Cell::new(MAX_RECT),
+            current_viewport: Cell::new(Rect::zero()),
             fragment_name: DOMRefCell::new(None),
             last_reflow_id: Cell::new(0),
             resize_event: Cell::new(None),
@@ -1004,11 +1559,20 @@ impl Window {
             layout_join_port: DOMRefCell::new(None),
             window_size: Cell::new(window_size),
             pending_reflow_count: Cell::new(0),
+            pending_image_count: Cell::new(0),
             current_state: Cell::new(WindowState::Alive),
+            in_error_reporting_mode: Cell::new(false),
+            gamepad_poller: gamepad_poller,
+            gamepads: DOMRefCell::new((0..MAX_GAMEPADS).map(|_| None).collect()),
+            gamepads_frozen: Cell::new(false),
+            notification_permission_callbacks: DOMRefCell::new(vec!()),
+            notifications: DOMRefCell::new(HashMap::new()),
+            next_notification_id: Cell::new(NotificationId(0)),
 
             devtools_marker_sender: RefCell::new(None),
             devtools_markers: RefCell::new(HashSet::new()),
             devtools_wants_updates: Cell::new(false),
+            devtools_wants_mutations: Cell::new(false),
             webdriver_script_chan: RefCell::new(None),
         };
 
@@ -1045,6 +1609,10 @@ fn debug_reflow_events(goal: &ReflowGoal, query_type: &ReflowQueryType, reason:
         ReflowQueryType::NoQuery => "\tNoQuery",
         ReflowQueryType::ContentBoxQuery(_n) => "\tContentBoxQuery",
         ReflowQueryType::ContentBoxesQuery(_n) => "\tContentBoxesQuery",
+        ReflowQueryType::BoxModelQuery(_n) => "\tBoxModelQuery",
+        ReflowQueryType::MatchedRulesQuery(_n) => "\tMatchedRulesQuery",
+        ReflowQueryType::CaretRectQuery(_n, _o) => "\tCaretRectQuery",
+        ReflowQueryType::ScrollAreaQuery => "\tScrollAreaQuery",
     });
 
     debug_msg.push_str(match *reason {
@@ -1052,6 +1620,7 @@ fn debug_reflow_events(goal: &ReflowGoal, query_type: &ReflowQueryType, reason:
         ReflowReason::RefreshTick => "\tRefreshTick",
         ReflowReason::FirstLoad => "\tFirstLoad",
         ReflowReason::KeyEvent => "\tKeyEvent",
+        ReflowReason::ImeEvent => "\tImeEvent",
         ReflowReason::MouseEvent => "\tMouseEvent",
         ReflowReason::Query => "\tQuery",
         ReflowReason::Timer => "\tTimer",
@@ -1061,6 +1630,7 @@ fn debug_reflow_events(goal: &ReflowGoal, query_type: &ReflowQueryType, reason:
         ReflowReason::DocumentLoaded => "\tDocumentLoaded",
         ReflowReason::ImageLoaded => "\tImageLoaded",
         ReflowReason::RequestAnimationFrame => "\tRequestAnimationFrame",
+        ReflowReason::WebFontLoaded => "\tWebFontLoaded",
     });
 
     println!("{}", debug_msg);
@@ -4,6 +4,7 @@
 
 use dom::bindings::cell::DOMRefCell;
 use dom::bindings::codegen::Bindings::EventHandlerBinding::EventHandlerNonNull;
+use dom::bindings::codegen::Bindings::WindowBinding::WindowMethods;
 use dom::bindings::codegen::Bindings::XMLHttpRequestBinding;
 use dom::bindings::codegen::Bindings::XMLHttpRequestBinding::XMLHttpRequestMethods;
 use dom::bindings::codegen::Bindings::XMLHttpRequestBinding::XMLHttpRequestResponseType;
@@ -19,7 +20,7 @@ use dom::bindings::js::Root;
 use dom::bindings::refcounted::Trusted;
 use dom::bindings::str::ByteString;
 use dom::bindings::utils::{Reflectable, reflect_dom_object};
-use dom::document::Document;
+use dom::document::{Document, DocumentHelpers};
 use dom::event::{Event, EventBubbles, EventCancelable, EventHelpers};
 use dom::eventtarget::{EventTarget, EventTargetHelpers, EventTargetTypeId};
 use dom::progressevent::ProgressEvent;
@@ -48,7 +49,7 @@ use net_traits::ControlMsg::Load;
 use net_traits::{ResourceTask, ResourceCORSData, LoadData, LoadConsumer};
 use net_traits::{AsyncResponseListener, Metadata};
 use cors::{allow_cross_origin_request, CORSRequest, RequestMode, AsyncCORSResponseListener};
-use cors::CORSResponse;
+use cors::{self, CORSResponse};
 use util::str::DOMString;
 use util::task::spawn_named;
 
@@ -548,8 +549,24 @@ impl<'a> XMLHttpRequestMethods for &'a XMLHttpRequest {
 
         load_data.method = (*self.request_method.borrow()).clone();
 
-        // CORS stuff
+        // The Referer header is worked out by the resource task, which knows where
+        // redirects lead.
         let global = self.global.root();
+        load_data.referrer_url = Some(global.r().get_url());
+        load_data.referrer_policy = match global.r() {
+            GlobalRef::Window(window) => window.Document().r().referrer_policy(),
+            GlobalRef::Worker(_) => None,
+        };
+        load_data.block_mixed_content = match global.r() {
+            GlobalRef::Window(window) => {
+                window.Document().r().should_block_mixed_content(&load_data.url, true)
+            }
+            GlobalRef::Worker(_) => {
+                global.r().get_url().scheme == "https" && load_data.url.scheme == "http"
+            }
+        };
+
+        // CORS stuff
         let referer_url = self.global.root().r().get_url();
         let mode = if self.upload_events.get() {
             RequestMode::ForcedPreflight
@@ -561,18 +578,6 @@ impl<'a> XMLHttpRequestMethods for &'a XMLHttpRequest {
         let cors_request = CORSRequest::maybe_new(referer_url.clone(), load_data.url.clone(), mode,
                                                   load_data.method.clone(), combined_headers);
         match cors_request {
-            Ok(None) => {
-                let mut buf = String::new();
-                buf.push_str(&referer_url.scheme);
-                buf.push_str("://");
-                referer_url.serialize_host().map(|ref h| buf.push_str(h));
-                referer_url.port().as_ref().map(|&p| {
-                    buf.push_str(":");
-                    buf.push_str(&p.to_string());
-                });
-                referer_url.serialize_path().map(|ref h| buf.push_str(h));
-                self.request_headers.borrow_mut().set_raw("Referer".to_owned(), vec![buf.into_bytes()]);
-            },
             Ok(Some(ref req)) => self.insert_trusted_header("origin".to_owned(),
                                                             req.origin.to_string()),
             _ => {}
@@ -768,22 +773,25 @@ impl<'a> PrivateXMLHttpRequestHelpers for &'a XMLHttpRequest {
 
     fn process_headers_available(self, cors_request: Option<CORSRequest>,
                                  gen_id: GenerationId, metadata: Metadata) -> Result<(), Error> {
-        match cors_request {
+        let headers = match cors_request {
             Some(ref req) => {
                 match metadata.headers {
-                    Some(ref h) if allow_cross_origin_request(req, h) => {},
+                    // Script only gets to see the headers the server chose to expose.
+                    Some(ref h) if allow_cross_origin_request(req, h) => {
+                        Some(cors::filter_response_headers(h))
+                    },
                     _ => {
+                        cors::clear_cors_cache(req);
                         self.process_partial_response(XHRProgress::Errored(gen_id, Network));
                         return Err(Network);
                     }
                 }
             },
 
-            _ => {}
+            _ => metadata.headers
         };
-        // XXXManishearth Clear cache entries in case of a network error
         self.process_partial_response(XHRProgress::HeadersReceived(gen_id,
-            metadata.headers, metadata.status));
+            headers, metadata.status));
         Ok(())
     }
 
@@ -1127,7 +1135,7 @@ impl<'a> PrivateXMLHttpRequestHelpers for &'a XMLHttpRequest {
     }
 }
 
-trait Extractable {
+pub trait Extractable {
     fn extract(&self) -> Vec<u8>;
 }
 impl Extractable for SendParam {
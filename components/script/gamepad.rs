@@ -0,0 +1,92 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+//! Receives the state of the platform's game controllers from the constellation, which polls
+//! them so that content needn't open devices. A background task keeps a snapshot of every
+//! controller's state that windows read from, and notifies the script task whenever a
+//! controller is connected or disconnected. It only runs once a page has called
+//! `navigator.getGamepads()` or listened for controller connections.
+
+use script_task::{ScriptChan, ScriptMsg};
+
+use msg::constellation_msg::{ConstellationChan, Msg as ConstellationMsg};
+use util::task::spawn_named;
+
+use std::borrow::ToOwned;
+use std::cell::RefCell;
+use std::sync::{Arc, Mutex};
+use std::sync::mpsc::{channel, Sender, TryRecvError};
+
+pub use msg::constellation_msg::{GamepadButtonState, GamepadState, MAX_GAMEPADS};
+
+/// The state of each controller slot, or `None` if nothing is connected to it.
+pub type GamepadStates = Arc<Mutex<Vec<Option<GamepadState>>>>;
+
+/// A handle to the receiving task, which is only started once content asks for controllers.
+/// The task exits at the next update after this is dropped.
+pub struct GamepadPoller {
+    states: GamepadStates,
+    script_chan: Box<ScriptChan+Send>,
+    constellation_chan: ConstellationChan,
+    exit_chan: RefCell<Option<Sender<()>>>,
+}
+
+impl GamepadPoller {
+    pub fn new(script_chan: Box<ScriptChan+Send>,
+               constellation_chan: ConstellationChan)
+               -> GamepadPoller {
+        GamepadPoller {
+            states: Arc::new(Mutex::new(vec![None; MAX_GAMEPADS])),
+            script_chan: script_chan,
+            constellation_chan: constellation_chan,
+            exit_chan: RefCell::new(None),
+        }
+    }
+
+    /// Starts watching the platform's controllers, unless that has already happened.
+    pub fn start(&self) {
+        let mut exit_chan = self.exit_chan.borrow_mut();
+        if exit_chan.is_some() {
+            return
+        }
+
+        let (chan, exit_port) = channel();
+        *exit_chan = Some(chan);
+        let (update_chan, update_port) = channel();
+        let ConstellationChan(ref constellation_chan) = self.constellation_chan;
+        constellation_chan.send(ConstellationMsg::WatchGamepads(update_chan)).unwrap();
+
+        let task_states = self.states.clone();
+        let script_chan = self.script_chan.clone();
+        spawn_named("GamepadWatcher".to_owned(), move || {
+            // The constellation stops polling for this task once the receiver is dropped.
+            while let Ok(update) = update_port.recv() {
+                match exit_port.try_recv() {
+                    Err(TryRecvError::Empty) => {},
+                    Ok(()) | Err(TryRecvError::Disconnected) => return,
+                }
+
+                *task_states.lock().unwrap() = update.states;
+                for (index, connected) in update.connection_changes {
+                    let msg = ScriptMsg::GamepadConnectionChanged(index, connected);
+                    if script_chan.send(msg).is_err() {
+                        return;
+                    }
+                }
+            }
+        });
+    }
+
+    pub fn states(&self) -> GamepadStates {
+        self.states.clone()
+    }
+}
+
+impl Drop for GamepadPoller {
+    fn drop(&mut self) {
+        if let Some(ref exit_chan) = *self.exit_chan.borrow() {
+            let _ = exit_chan.send(());
+        }
+    }
+}
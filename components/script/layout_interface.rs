@@ -10,6 +10,8 @@ use dom::node::LayoutData;
 
 use euclid::point::Point2D;
 use euclid::rect::Rect;
+use euclid::side_offsets::SideOffsets2D;
+use euclid::size::Size2D;
 use libc::uintptr_t;
 use msg::compositor_msg::LayerId;
 use msg::constellation_msg::{PipelineExitType, WindowSizeData};
@@ -22,7 +24,7 @@ use std::any::Any;
 use std::sync::mpsc::{channel, Receiver, Sender};
 use style::animation::PropertyAnimation;
 use style::media_queries::MediaQueryList;
-use style::stylesheets::Stylesheet;
+use style::stylesheets::{Origin, Stylesheet};
 use url::Url;
 use util::geometry::Au;
 
@@ -90,12 +92,47 @@ pub trait LayoutRPC {
     /// Requests the node containing the point of interest
     fn hit_test(&self, node: TrustedNodeAddress, point: Point2D<f32>) -> Result<HitTestResponse, ()>;
     fn mouse_over(&self, node: TrustedNodeAddress, point: Point2D<f32>) -> Result<MouseOverResponse, ()>;
+    /// Requests the box model of a node, for the developer tools.
+    fn box_model(&self) -> BoxModelResponse;
+    /// Requests the style rules that match a node, for the developer tools.
+    fn matched_rules(&self) -> MatchedRulesResponse;
+    /// Requests where the caret of a text field is drawn, for the input method editor.
+    fn caret_rect(&self) -> CaretRectResponse;
+    /// Requests the size of the area the page can be scrolled over.
+    fn scroll_area(&self) -> ScrollAreaResponse;
 }
 
 pub struct ContentBoxResponse(pub Rect<Au>);
 pub struct ContentBoxesResponse(pub Vec<Rect<Au>>);
 pub struct HitTestResponse(pub UntrustedNodeAddress);
 pub struct MouseOverResponse(pub Vec<UntrustedNodeAddress>);
+pub struct BoxModelResponse(pub Option<BoxModel>);
+pub struct MatchedRulesResponse(pub Vec<MatchedRule>);
+pub struct CaretRectResponse(pub Option<Rect<Au>>);
+pub struct ScrollAreaResponse(pub Size2D<Au>);
+
+/// The areas around the first box generated for a node.
+#[derive(Clone)]
+pub struct BoxModel {
+    pub border_box: Rect<Au>,
+    pub margin: SideOffsets2D<Au>,
+    pub border: SideOffsets2D<Au>,
+    pub padding: SideOffsets2D<Au>,
+    /// The computed value of the `position` property.
+    pub position: String,
+}
+
+/// A style rule that matches a node, and where it was written.
+#[derive(Clone)]
+pub struct MatchedRule {
+    pub stylesheet_url: Url,
+    pub line: usize,
+    pub origin: Origin,
+    pub selectors: String,
+    pub specificity: u32,
+    /// Each declaration as a property name and value, with whether it is `!important`.
+    pub declarations: Vec<(String, String, bool)>,
+}
 
 /// Why we're doing reflow.
 #[derive(PartialEq, Copy, Clone, Debug)]
@@ -112,6 +149,13 @@ pub enum ReflowQueryType {
     NoQuery,
     ContentBoxQuery(TrustedNodeAddress),
     ContentBoxesQuery(TrustedNodeAddress),
+    BoxModelQuery(TrustedNodeAddress),
+    MatchedRulesQuery(TrustedNodeAddress),
+    /// Where the caret would be drawn in the text of a text field, the given number of
+    /// characters into it.
+    CaretRectQuery(TrustedNodeAddress, usize),
+    /// The size of the area the page can be scrolled over, which scroll positions are clamped to.
+    ScrollAreaQuery,
 }
 
 /// Information needed for a reflow.
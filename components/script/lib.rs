@@ -53,6 +53,7 @@ extern crate rustc_serialize;
 extern crate time;
 extern crate canvas;
 extern crate canvas_traits;
+#[macro_use]
 extern crate profile_traits;
 extern crate script_traits;
 extern crate selectors;
@@ -84,6 +85,7 @@ mod timers;
 pub mod textinput;
 pub mod clipboard_provider;
 mod devtools;
+mod gamepad;
 mod horribly_inefficient_timers;
 mod webdriver_handlers;
 
@@ -92,4 +94,5 @@ pub fn init() {
     unsafe {
         assert_eq!(js::jsapi::JS_Init(), 1);
     }
+    dom::userscripts::list_user_scripts();
 }
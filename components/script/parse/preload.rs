@@ -0,0 +1,288 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+//! A speculative scanner for the markup that is waiting while the parser is blocked on a
+//! script. It only looks for the start tags of elements that fetch subresources, so that
+//! those fetches can start before the parser gets there. Getting it wrong is harmless:
+//! at worst a resource is fetched that the document doesn't end up using.
+
+use std::ascii::AsciiExt;
+use std::cmp;
+use std::collections::HashSet;
+use url::{Url, UrlParser};
+use util::str::HTML_SPACE_CHARACTERS;
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum PreloadKind {
+    Image,
+    Script,
+    Stylesheet,
+}
+
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct Preload {
+    pub kind: PreloadKind,
+    pub url: Url,
+}
+
+pub struct PreloadScanner {
+    /// The URL that relative URLs are resolved against.
+    base_url: Url,
+    /// Input that ended in the middle of a tag or comment, kept until the rest arrives.
+    partial: String,
+    /// The end tag that closes the raw text element the input is in, if any.
+    raw_text_end: Option<&'static str>,
+    /// The URLs found so far, so that each is only preloaded once.
+    seen: HashSet<Url>,
+}
+
+impl PreloadScanner {
+    pub fn new(base_url: Url) -> PreloadScanner {
+        PreloadScanner {
+            base_url: base_url,
+            partial: String::new(),
+            raw_text_end: None,
+            seen: HashSet::new(),
+        }
+    }
+
+    /// Forgets the state carried over from earlier input, for when the next input
+    /// doesn't follow on from it.
+    pub fn reset(&mut self) {
+        self.partial.clear();
+        self.raw_text_end = None;
+    }
+
+    /// Scans the next piece of input, returning the resources it refers to that haven't
+    /// been found before.
+    pub fn scan(&mut self, input: &str) -> Vec<Preload> {
+        let mut text = String::new();
+        text.push_str(&self.partial);
+        text.push_str(input);
+        self.partial.clear();
+        // Lowercasing ASCII keeps byte offsets the same.
+        let lower = text.to_ascii_lowercase();
+
+        let mut preloads = vec!();
+        let mut pos = 0;
+        loop {
+            if let Some(end_tag) = self.raw_text_end {
+                match lower[pos..].find(end_tag) {
+                    Some(offset) => {
+                        pos += offset + end_tag.len();
+                        self.raw_text_end = None;
+                    }
+                    None => {
+                        // The end tag may be split across inputs.
+                        let keep = cmp::min(end_tag.len(), text.len() - pos);
+                        let mut start = text.len() - keep;
+                        while !text.is_char_boundary(start) {
+                            start += 1;
+                        }
+                        self.partial.push_str(&text[start..]);
+                        break;
+                    }
+                }
+            }
+
+            let start = match lower[pos..].find('<') {
+                Some(offset) => pos + offset,
+                None => break,
+            };
+            let rest = &lower[start..];
+            if rest.starts_with("<!--") {
+                match rest.find("-->") {
+                    Some(end) => pos = start + end + 3,
+                    None => {
+                        self.partial.push_str(&text[start..]);
+                        break;
+                    }
+                }
+                continue;
+            }
+
+            // End tags, doctypes and stray `<`s don't matter.
+            let is_start_tag = rest[1..].chars().next().map_or(true, |c| {
+                c.is_ascii() && c.is_alphabetic()
+            });
+            if !is_start_tag {
+                pos = start + 1;
+                continue;
+            }
+            match parse_tag(&text[start..]) {
+                Some((tag, length)) => {
+                    self.process_tag(tag, &mut preloads);
+                    pos = start + length;
+                }
+                None => {
+                    self.partial.push_str(&text[start..]);
+                    break;
+                }
+            }
+        }
+        preloads
+    }
+
+    fn process_tag(&mut self, tag: Tag, preloads: &mut Vec<Preload>) {
+        let found = match &*tag.name {
+            "img" => tag.attribute("src").map(|src| (PreloadKind::Image, src)),
+            "script" => {
+                self.raw_text_end = Some("</script");
+                tag.attribute("src").map(|src| (PreloadKind::Script, src))
+            }
+            "link" => {
+                let is_stylesheet = tag.attribute("rel").map_or(false, |rel| {
+                    rel.split(HTML_SPACE_CHARACTERS)
+                       .any(|token| token.eq_ignore_ascii_case("stylesheet"))
+                });
+                if is_stylesheet {
+                    tag.attribute("href").map(|href| (PreloadKind::Stylesheet, href))
+                } else {
+                    None
+                }
+            }
+            "base" => {
+                if let Some(href) = tag.attribute("href") {
+                    if let Ok(url) = UrlParser::new().base_url(&self.base_url).parse(href) {
+                        self.base_url = url;
+                    }
+                }
+                None
+            }
+            "style" => {
+                self.raw_text_end = Some("</style");
+                None
+            }
+            "textarea" => {
+                self.raw_text_end = Some("</textarea");
+                None
+            }
+            "title" => {
+                self.raw_text_end = Some("</title");
+                None
+            }
+            _ => None,
+        };
+
+        let (kind, value) = match found {
+            Some(found) => found,
+            None => return,
+        };
+        let value = value.trim_matches(HTML_SPACE_CHARACTERS);
+        if value.is_empty() {
+            return;
+        }
+        if let Ok(url) = UrlParser::new().base_url(&self.base_url).parse(value) {
+            if self.seen.insert(url.clone()) {
+                preloads.push(Preload {
+                    kind: kind,
+                    url: url,
+                });
+            }
+        }
+    }
+}
+
+struct Tag {
+    /// The lowercased tag name.
+    name: String,
+    /// The attributes, with lowercased names.
+    attributes: Vec<(String, String)>,
+}
+
+impl Tag {
+    fn attribute(&self, name: &str) -> Option<&str> {
+        self.attributes.iter()
+                       .find(|&&(ref attr_name, _)| *attr_name == name)
+                       .map(|&(_, ref value)| &**value)
+    }
+}
+
+fn is_space(c: char) -> bool {
+    HTML_SPACE_CHARACTERS.contains(&c)
+}
+
+/// Parses the start tag at the beginning of `input`, returning it along with its length
+/// in bytes, or `None` if the input ends before the tag does.
+fn parse_tag(input: &str) -> Option<(Tag, usize)> {
+    let mut chars = input.char_indices().skip(1).peekable();
+
+    let mut name = String::new();
+    loop {
+        match chars.peek() {
+            Some(&(_, c)) if !is_space(c) && c != '>' && c != '/' => {
+                name.push(c.to_ascii_lowercase());
+                chars.next();
+            }
+            Some(_) => break,
+            None => return None,
+        }
+    }
+
+    let mut attributes = vec!();
+    loop {
+        // Skip to the next attribute name, or the end of the tag.
+        loop {
+            match chars.peek() {
+                Some(&(i, '>')) => {
+                    return Some((Tag { name: name, attributes: attributes }, i + 1));
+                }
+                Some(&(_, c)) if is_space(c) || c == '/' => { chars.next(); }
+                Some(_) => break,
+                None => return None,
+            }
+        }
+
+        let mut attr_name = String::new();
+        loop {
+            match chars.peek() {
+                Some(&(_, c)) if !is_space(c) && c != '>' && c != '/' && c != '=' => {
+                    attr_name.push(c.to_ascii_lowercase());
+                    chars.next();
+                }
+                Some(_) => break,
+                None => return None,
+            }
+        }
+        while chars.peek().map_or(false, |&(_, c)| is_space(c)) {
+            chars.next();
+        }
+        if chars.peek().map(|&(_, c)| c) != Some('=') {
+            attributes.push((attr_name, String::new()));
+            continue;
+        }
+        chars.next();
+        while chars.peek().map_or(false, |&(_, c)| is_space(c)) {
+            chars.next();
+        }
+
+        let mut value = String::new();
+        match chars.peek().map(|&(_, c)| c) {
+            Some(quote) if quote == '"' || quote == '\'' => {
+                chars.next();
+                loop {
+                    match chars.next() {
+                        Some((_, c)) if c == quote => break,
+                        Some((_, c)) => value.push(c),
+                        None => return None,
+                    }
+                }
+            }
+            Some(_) => {
+                loop {
+                    match chars.peek() {
+                        Some(&(_, c)) if !is_space(c) && c != '>' => {
+                            value.push(c);
+                            chars.next();
+                        }
+                        Some(_) => break,
+                        None => return None,
+                    }
+                }
+            }
+            None => return None,
+        }
+        attributes.push((attr_name, value));
+    }
+}
@@ -35,6 +35,7 @@ use dom::document::{Document, IsHTMLDocument, DocumentHelpers, DocumentProgressH
                     DocumentProgressTask, DocumentSource, MouseEventType};
 use dom::element::{Element, AttributeHandlers};
 use dom::event::{EventHelpers, EventBubbles, EventCancelable};
+use dom::geolocation::GeolocationHelpers;
 use dom::htmliframeelement::{HTMLIFrameElement, HTMLIFrameElementHelpers};
 use dom::uievent::UIEvent;
 use dom::node::{Node, NodeHelpers, NodeDamage, window_from_node};
@@ -48,6 +49,7 @@ use network_listener::NetworkListener;
 use page::{Page, IterablePage, Frame};
 use timers::TimerId;
 use devtools;
+use gamepad::GamepadPoller;
 use webdriver_handlers;
 
 use devtools_traits::{DevtoolsControlChan, DevtoolsControlPort, DevtoolsPageInfo};
@@ -56,17 +58,22 @@ use devtools_traits::{TimelineMarker, TimelineMarkerType, TracingMetadata};
 use script_traits::{CompositorEvent, MouseButton};
 use script_traits::CompositorEvent::{ResizeEvent, ClickEvent};
 use script_traits::CompositorEvent::{MouseDownEvent, MouseUpEvent};
-use script_traits::CompositorEvent::{MouseMoveEvent, KeyEvent};
+use script_traits::CompositorEvent::{MouseMoveEvent, KeyEvent, ImeEvent};
 use script_traits::{NewLayoutInfo, OpaqueScriptLayoutChannel};
 use script_traits::{ConstellationControlMsg, ScriptControlChan};
 use script_traits::{ScriptState, ScriptTaskFactory};
-use msg::compositor_msg::{LayerId, ScriptListener};
-use msg::constellation_msg::{ConstellationChan, FocusType};
+use msg::compositor_msg::{LayerId, ScriptListener, ScrollBehavior};
+use msg::constellation_msg::{ConstellationChan, DocumentState, FocusType};
 use msg::constellation_msg::{LoadData, PipelineId, SubpageId, MozBrowserEvent, WorkerId};
 use msg::constellation_msg::{Failure, WindowSizeData, PipelineExitType};
+use msg::constellation_msg::GeolocationPosition;
+use msg::constellation_msg::{NotificationEventType, NotificationId, PermissionName};
+use msg::constellation_msg::ReferrerPolicy;
 use msg::constellation_msg::Msg as ConstellationMsg;
+use profile_traits::mem as profile_mem;
+use profile_traits::mem::{Report, ReportUnits, ReportsChan};
 use msg::webdriver_msg::WebDriverScriptCommand;
-use net_traits::{ResourceTask, LoadConsumer, ControlMsg, Metadata};
+use net_traits::{ResourceTask, LoadConsumer, LoadPriority, ControlMsg, Metadata};
 use net_traits::LoadData as NetLoadData;
 use net_traits::image_cache_task::{ImageCacheChan, ImageCacheTask, ImageCacheResult};
 use net_traits::storage_task::StorageTask;
@@ -81,6 +88,7 @@ use hyper::header::{LastModified, Headers};
 use js::jsapi::{JS_SetWrapObjectCallbacks, JS_AddExtraGCRootsTracer, DisableIncrementalGC};
 use js::jsapi::{JSContext, JSRuntime, JSTracer};
 use js::jsapi::{JS_SetGCCallback, JSGCStatus, JSAutoRequest, SetDOMCallbacks};
+use js::jsapi::{JS_GetGCParameter, JSGCParamKey};
 use js::jsapi::{SetDOMProxyInformation, DOMProxyShadowsResult, HandleObject, HandleId, RootedValue};
 use js::jsval::UndefinedValue;
 use js::rust::Runtime;
@@ -95,6 +103,7 @@ use std::mem;
 use std::option::Option;
 use std::ptr;
 use std::rc::Rc;
+use std::str;
 use std::result::Result;
 use std::sync::{Arc, Mutex};
 use std::sync::mpsc::{channel, Sender, Receiver, Select};
@@ -135,6 +144,8 @@ struct InProgressLoad {
     clip_rect: Option<Rect<f32>>,
     /// The requested URL of the load.
     url: Url,
+    /// Whether the user has allowed the document to load insecure active content.
+    allow_mixed_content: bool,
 }
 
 impl InProgressLoad {
@@ -151,6 +162,7 @@ impl InProgressLoad {
             window_size: window_size,
             clip_rect: None,
             url: url,
+            allow_mixed_content: false,
         }
     }
 }
@@ -196,6 +208,9 @@ pub enum ScriptMsg {
     RefcountCleanup(TrustedReference),
     /// Notify a document that all pending loads are complete.
     DocumentLoadsComplete(PipelineId),
+    /// The game controller in the given slot was connected or disconnected (only
+    /// dispatched to ScriptTask).
+    GamepadConnectionChanged(u32, bool),
 }
 
 /// A cloneable interface for communicating with an event loop.
@@ -324,6 +339,9 @@ pub struct ScriptTask {
 
     /// List of pipelines that have been owned and closed by this script task.
     closed_pipelines: RefCell<HashSet<PipelineId>>,
+
+    /// Polls game controllers for the windows of this task.
+    gamepad_poller: Rc<GamepadPoller>,
 }
 
 /// In the event of task failure, all data on the stack runs its destructor. However, there
@@ -387,10 +405,17 @@ impl ScriptTaskFactory for ScriptTask {
                  storage_task: StorageTask,
                  image_cache_task: ImageCacheTask,
                  devtools_chan: Option<DevtoolsControlChan>,
+                 mem_profiler_chan: profile_mem::ProfilerChan,
                  window_size: Option<WindowSizeData>,
                  load_data: LoadData)
                  where C: ScriptListener + Send + 'static {
         let ConstellationChan(const_chan) = constellation_chan.clone();
+
+        // Register this thread as a memory reporter, via its control channel.
+        let reporter = box control_chan.clone();
+        let reporter_name = format!("script-reporter-{}", id.0);
+        let msg = profile_mem::ProfilerMsg::RegisterReporter(reporter_name.clone(), reporter);
+        mem_profiler_chan.send(msg);
         let (script_chan, script_port) = channel();
         let layout_chan = LayoutChan(layout_chan.sender());
         spawn_named_with_send_on_failure(format!("ScriptTask {:?}", id), task_state::SCRIPT, move || {
@@ -419,6 +444,8 @@ impl ScriptTaskFactory for ScriptTask {
 
             script_task.start();
 
+            mem_profiler_chan.send(profile_mem::ProfilerMsg::UnregisterReporter(reporter_name));
+
             // This must always be the very last operation performed before the task completes
             failsafe.neuter();
         }, ConstellationMsg::Failure(failure_msg), const_chan);
@@ -484,6 +511,8 @@ impl ScriptTask {
 
         let (devtools_sender, devtools_receiver) = channel();
         let (image_cache_channel, image_cache_port) = channel();
+        let gamepad_poller = Rc::new(GamepadPoller::new(chan.clone(),
+                                                        constellation_chan.clone()));
 
         ScriptTask {
             page: DOMRefCell::new(None),
@@ -511,6 +540,7 @@ impl ScriptTask {
             js_runtime: Rc::new(runtime),
             mouse_over_targets: DOMRefCell::new(vec!()),
             closed_pipelines: RefCell::new(HashSet::new()),
+            gamepad_poller: gamepad_poller,
         }
     }
 
@@ -734,6 +764,8 @@ impl ScriptTask {
                 self.handle_freeze_msg(pipeline_id),
             ConstellationControlMsg::Thaw(pipeline_id) =>
                 self.handle_thaw_msg(pipeline_id),
+            ConstellationControlMsg::RestoreDocumentState(pipeline_id, state) =>
+                self.handle_restore_document_state_msg(pipeline_id, state),
             ConstellationControlMsg::MozBrowserEvent(parent_pipeline_id,
                                                      subpage_id,
                                                      event) =>
@@ -750,6 +782,12 @@ impl ScriptTask {
                 self.handle_webdriver_msg(pipeline_id, msg),
             ConstellationControlMsg::TickAllAnimations(pipeline_id) =>
                 self.handle_tick_all_animations(pipeline_id),
+            ConstellationControlMsg::Permission(pipeline_id, name, granted) =>
+                self.handle_permission(pipeline_id, name, granted),
+            ConstellationControlMsg::NotificationEvent(pipeline_id, id, event_type) =>
+                self.handle_notification_event(pipeline_id, id, event_type),
+            ConstellationControlMsg::GeolocationPosition(pipeline_id, position) =>
+                self.handle_geolocation_position(pipeline_id, position),
             ConstellationControlMsg::StylesheetLoadComplete(id, url, responder) => {
                 responder.respond();
                 self.handle_resource_loaded(id, LoadType::Stylesheet(url));
@@ -758,7 +796,30 @@ impl ScriptTask {
                 let state = self.handle_get_current_state(pipeline_id);
                 sender.send(state).unwrap();
             }
+            ConstellationControlMsg::CollectReports(reports_chan) =>
+                self.collect_reports(reports_chan),
+            ConstellationControlMsg::WebFontLoaded(pipeline_id) =>
+                self.handle_web_font_loaded(pipeline_id),
+        }
+    }
+
+    /// Measures the JavaScript heap, which is shared by all the pages in this task. It's
+    /// reported under the root page.
+    fn collect_reports(&self, reports_chan: ReportsChan) {
+        let mut reports = vec![];
+        if let Some(ref page) = *self.page.borrow() {
+            let url = page.document().r().url();
+            let page = format!("url({}) pipeline({})", url, page.pipeline().0);
+            let size = unsafe {
+                JS_GetGCParameter(self.js_runtime.rt(), JSGCParamKey::JSGC_BYTES)
+            };
+            reports.push(Report {
+                path: path!["pages", page, "script-task", "js-gc-heap"],
+                units: ReportUnits::Bytes,
+                size: size as usize,
+            });
         }
+        reports_chan.send(reports);
     }
 
     fn handle_msg_from_script(&self, msg: ScriptMsg) {
@@ -783,6 +844,8 @@ impl ScriptTask {
                 LiveDOMReferences::cleanup(addr),
             ScriptMsg::DocumentLoadsComplete(id) =>
                 self.handle_loads_complete(id),
+            ScriptMsg::GamepadConnectionChanged(index, connected) =>
+                self.handle_gamepad_connection_changed(index, connected),
         }
     }
 
@@ -799,6 +862,8 @@ impl ScriptTask {
                 devtools::handle_get_children(&page, id, node_id, reply),
             DevtoolScriptControlMsg::GetLayout(id, node_id, reply) =>
                 devtools::handle_get_layout(&page, id, node_id, reply),
+            DevtoolScriptControlMsg::GetAppliedRules(id, node_id, reply) =>
+                devtools::handle_get_applied_rules(&page, id, node_id, reply),
             DevtoolScriptControlMsg::GetCachedMessages(pipeline_id, message_types, reply) =>
                 devtools::handle_get_cached_messages(pipeline_id, message_types, reply),
             DevtoolScriptControlMsg::ModifyAttribute(id, node_id, modifications) =>
@@ -811,6 +876,12 @@ impl ScriptTask {
                 devtools::handle_drop_timeline_markers(&page, self, marker_types),
             DevtoolScriptControlMsg::RequestAnimationFrame(pipeline_id, callback) =>
                 devtools::handle_request_animation_frame(&page, pipeline_id, callback),
+            DevtoolScriptControlMsg::SetNodeValue(id, node_id, value) =>
+                devtools::handle_set_node_value(&page, id, node_id, value),
+            DevtoolScriptControlMsg::HighlightNode(id, node_id) =>
+                devtools::handle_highlight_node(&page, id, node_id),
+            DevtoolScriptControlMsg::WantsMutationNotifications(pipeline_id, to_send) =>
+                devtools::handle_wants_mutation_notifications(&page, pipeline_id, to_send),
         }
     }
 
@@ -821,12 +892,16 @@ impl ScriptTask {
     fn handle_webdriver_msg(&self, pipeline_id: PipelineId, msg: WebDriverScriptCommand) {
         let page = self.root_page();
         match msg {
+            WebDriverScriptCommand::ElementClick(node_id, reply) =>
+                webdriver_handlers::handle_element_click(&page, pipeline_id, node_id, reply),
             WebDriverScriptCommand::ExecuteScript(script, reply) =>
                 webdriver_handlers::handle_execute_script(&page, pipeline_id, script, reply),
             WebDriverScriptCommand::FindElementCSS(selector, reply) =>
                 webdriver_handlers::handle_find_element_css(&page, pipeline_id, selector, reply),
             WebDriverScriptCommand::FindElementsCSS(selector, reply) =>
                 webdriver_handlers::handle_find_elements_css(&page, pipeline_id, selector, reply),
+            WebDriverScriptCommand::FocusElement(node_id, reply) =>
+                webdriver_handlers::handle_focus_element(&page, pipeline_id, node_id, reply),
             WebDriverScriptCommand::GetActiveElement(reply) =>
                 webdriver_handlers::handle_get_active_element(&page, pipeline_id, reply),
             WebDriverScriptCommand::GetElementTagName(node_id, reply) =>
@@ -909,6 +984,14 @@ impl ScriptTask {
             return ScriptState::DocumentLoading;
         }
 
+        // The page will still change if images are on their way, or if it has changed since it
+        // was last laid out.
+        let window = page.window();
+        let window = window.r();
+        if window.get_pending_image_count() > 0 || window.get_pending_reflow_count() > 0 {
+            return ScriptState::DocumentLoading;
+        }
+
         // Checks if the html element has reftest-wait attribute present.
         // See http://testthewebforward.org/docs/reftests.html
         let html_element = doc.r().GetDocumentElement();
@@ -996,6 +1079,16 @@ impl ScriptTask {
         window.r().thaw();
     }
 
+    /// Handles a request to restore the state of a document reloaded from the session history
+    fn handle_restore_document_state_msg(&self, id: PipelineId, state: DocumentState) {
+        let page = match self.root_page().find(id) {
+            Some(page) => page,
+            None => return,
+        };
+        let window = page.window();
+        window.r().restore_document_state(&state);
+    }
+
     fn handle_focus_iframe_msg(&self,
                                parent_pipeline_id: PipelineId,
                                subpage_id: SubpageId) {
@@ -1158,6 +1251,18 @@ impl ScriptTask {
         return false;
     }
 
+    /// Notifies every window of this task that a game controller was connected or
+    /// disconnected.
+    fn handle_gamepad_connection_changed(&self, index: u32, connected: bool) {
+        let page = self.page.borrow();
+        if let Some(page) = page.as_ref() {
+            for page in page.iter() {
+                let window = page.window();
+                window.r().handle_gamepad_connection_changed(index, connected);
+            }
+        }
+    }
+
     /// Handles when layout task finishes all animation in one tick
     fn handle_tick_all_animations(&self, id: PipelineId) {
         let page = get_page(&self.root_page(), id);
@@ -1165,6 +1270,45 @@ impl ScriptTask {
         document.r().invoke_animation_callbacks();
     }
 
+    /// Handles a web font downloading after text that uses it was laid out in a fallback font.
+    /// Layout knows which text uses the font, so only a reflow is needed.
+    fn handle_web_font_loaded(&self, pipeline_id: PipelineId) {
+        // The page may have gone away while the font downloaded.
+        if let Some(page) = self.root_page().find(pipeline_id) {
+            let document = page.document();
+            let window = window_from_node(document.r());
+            window.r().force_reflow(ReflowGoal::ForDisplay,
+                                    ReflowQueryType::NoQuery,
+                                    ReflowReason::WebFontLoaded);
+        }
+    }
+
+    /// Handles the user's answer to a request for permission to use a feature.
+    fn handle_permission(&self, id: PipelineId, name: PermissionName, granted: bool) {
+        let page = get_page(&self.root_page(), id);
+        let window = page.window();
+        match name {
+            PermissionName::Notifications => window.r().handle_notification_permission(granted),
+            PermissionName::Geolocation => window.r().geolocation().r().handle_permission(granted),
+        }
+    }
+
+    /// Handles the user interacting with a notification shown by the embedder.
+    fn handle_notification_event(&self, id: PipelineId, notification_id: NotificationId,
+                                 event_type: NotificationEventType) {
+        let page = get_page(&self.root_page(), id);
+        let window = page.window();
+        window.r().handle_notification_event(notification_id, event_type);
+    }
+
+    /// Handles a position sent by the embedder.
+    fn handle_geolocation_position(&self, id: PipelineId, position: Option<GeolocationPosition>) {
+        let page = get_page(&self.root_page(), id);
+        let window = page.window();
+        let geolocation = window.r().geolocation();
+        geolocation.r().handle_position(position);
+    }
+
     /// The entry point to document loading. Defines bindings, sets up the window and document
     /// objects, parses HTML and CSS, and kicks off initial layout.
     fn load(&self, metadata: Metadata, incomplete: InProgressLoad) -> Root<ServoHTMLParser> {
@@ -1266,7 +1410,8 @@ impl ScriptTask {
                                  incomplete.layout_chan,
                                  incomplete.pipeline_id,
                                  incomplete.parent_info,
-                                 incomplete.window_size);
+                                 incomplete.window_size,
+                                 self.gamepad_poller.clone());
 
         let last_modified: Option<DOMString> = metadata.headers.as_ref().and_then(|headers| {
             headers.get().map(|&LastModified(HttpDate(ref tm))| dom_last_modified(tm))
@@ -1292,6 +1437,42 @@ impl ScriptTask {
                                      DocumentSource::FromParser,
                                      loader);
 
+        let referrer_policy = metadata.headers.as_ref().and_then(|headers| {
+            headers.get_raw("Referrer-Policy")
+        }).and_then(|values| {
+            values.iter().filter_map(|value| {
+                str::from_utf8(value).ok().and_then(ReferrerPolicy::parse)
+            }).last()
+        });
+        document.r().set_referrer_policy(referrer_policy);
+
+        // A header with a single language gives the document's default language.
+        // https://html.spec.whatwg.org/multipage/#the-lang-and-xml:lang-attributes
+        let content_language = metadata.headers.as_ref().and_then(|headers| {
+            headers.get_raw("Content-Language")
+        }).and_then(|values| {
+            if values.len() != 1 {
+                return None
+            }
+            str::from_utf8(&values[0]).ok().map(|value| value.trim().to_owned())
+        }).and_then(|value| {
+            if value.is_empty() || value.contains(',') {
+                None
+            } else {
+                Some(Atom::from_slice(&value))
+            }
+        });
+        if content_language.is_some() {
+            document.r().set_content_language(content_language);
+        }
+        document.r().set_allow_mixed_content(incomplete.allow_mixed_content);
+
+        // The document is an error page; the embedder decides whether to load the real one.
+        if let Some(error) = metadata.certificate_error {
+            let ConstellationChan(ref chan) = self.constellation_chan;
+            chan.send(ConstellationMsg::CertificateError(incomplete.pipeline_id, error)).unwrap();
+        }
+
         let frame_element = frame_element.r().map(|elem| ElementCast::from_ref(elem));
         window.r().init_browser_context(document.r(), frame_element);
 
@@ -1345,7 +1526,10 @@ impl ScriptTask {
         // Really what needs to happen is that this needs to go through layout to ask which
         // layer the element belongs to, and have it send the scroll message to the
         // compositor.
-        self.compositor.borrow_mut().scroll_fragment_point(pipeline_id, LayerId::null(), point);
+        self.compositor.borrow_mut().scroll_fragment_point(pipeline_id,
+                                                           LayerId::null(),
+                                                           point,
+                                                           ScrollBehavior::Instant);
     }
 
     /// Reflows non-incrementally, rebuilding the entire layout tree in the process.
@@ -1418,6 +1602,12 @@ impl ScriptTask {
                 document.r().dispatch_key_event(
                     key, state, modifiers, &mut *self.compositor.borrow_mut());
             }
+
+            ImeEvent(event) => {
+                let page = get_page(&self.root_page(), pipeline_id);
+                let document = page.document();
+                document.r().dispatch_ime_event(event, &mut *self.compositor.borrow_mut());
+            }
         }
     }
 
@@ -1447,7 +1637,7 @@ impl ScriptTask {
                     self.find_iframe(doc.r(), subpage_id)
                 });
                 if let Some(iframe) = iframe.r() {
-                    iframe.navigate_child_browsing_context(load_data.url);
+                    iframe.navigate_child_browsing_context(load_data);
                 }
             }
             None => {
@@ -1501,7 +1691,8 @@ impl ScriptTask {
 
     /// Initiate a non-blocking fetch for a specified resource. Stores the InProgressLoad
     /// argument until a notification is received that the fetch is complete.
-    fn start_page_load(&self, incomplete: InProgressLoad, mut load_data: LoadData) {
+    fn start_page_load(&self, mut incomplete: InProgressLoad, mut load_data: LoadData) {
+        incomplete.allow_mixed_content = load_data.allow_mixed_content;
         let id = incomplete.pipeline_id.clone();
         let subpage = incomplete.parent_info.clone().map(|p| p.1);
 
@@ -1527,6 +1718,11 @@ impl ScriptTask {
             data: load_data.data,
             cors: None,
             pipeline_id: Some(id),
+            priority: LoadPriority::Urgent,
+            referrer_url: load_data.referrer_url,
+            referrer_policy: load_data.referrer_policy,
+            block_mixed_content: false,
+            is_navigation: true,
         }, LoadConsumer::Listener(listener))).unwrap();
 
         self.incomplete_loads.borrow_mut().push(incomplete);
@@ -7,11 +7,12 @@
 use clipboard_provider::ClipboardProvider;
 use dom::keyboardevent::{KeyboardEvent, KeyboardEventHelpers, key_value};
 use msg::constellation_msg::{SHIFT, CONTROL, ALT, SUPER};
-use msg::constellation_msg::{Key, KeyModifiers};
+use msg::constellation_msg::{ImeEvent, Key, KeyModifiers};
 use util::str::DOMString;
+use util::str::{next_grapheme_cluster_boundary, previous_grapheme_cluster_boundary};
 
 use std::borrow::ToOwned;
-use std::cmp::{min, max};
+use std::cmp::min;
 use std::default::Default;
 
 
@@ -29,6 +30,16 @@ pub struct TextPoint {
     pub index: usize,
 }
 
+/// Text that an input method is in the middle of composing. It is shown in the control, and
+/// replaced as the input method changes it, until the input method commits to it.
+#[derive(JSTraceable, Copy, Clone)]
+struct Composition {
+    /// Where the composed text begins. It never spans lines.
+    start: TextPoint,
+    /// The number of characters of composed text.
+    length: usize,
+}
+
 /// Encapsulated state for handling keyboard input in a single or multiline text input control.
 #[derive(JSTraceable)]
 pub struct TextInput<T: ClipboardProvider> {
@@ -41,9 +52,12 @@ pub struct TextInput<T: ClipboardProvider> {
     /// Is this a multiline input?
     multiline: bool,
     clipboard_provider: T,
+    /// The text an input method is composing, if any.
+    composition: Option<Composition>,
 }
 
 /// Resulting action to be taken by the owner of a text input that is handling an event.
+#[derive(PartialEq, Debug)]
 pub enum KeyReaction {
     TriggerDefaultAction,
     DispatchInput,
@@ -112,7 +126,8 @@ impl<T: ClipboardProvider> TextInput<T> {
             edit_point: Default::default(),
             selection_begin: None,
             multiline: lines == Lines::Multiple,
-            clipboard_provider: clipboard_provider
+            clipboard_provider: clipboard_provider,
+            composition: None,
         };
         i.set_content(initial);
         i
@@ -225,12 +240,18 @@ impl<T: ClipboardProvider> TextInput<T> {
         }
 
         self.edit_point.line = target_line as usize;
-        self.edit_point.index = min(self.current_line_length(), self.edit_point.index);
+        // Keep the editing point out of the middle of grapheme clusters.
+        let mut index = min(self.current_line_length(), self.edit_point.index);
+        if index < self.current_line_length() {
+            index = previous_grapheme_cluster_boundary(&self.lines[self.edit_point.line],
+                                                       index + 1);
+        }
+        self.edit_point.index = index;
     }
 
-    /// Adjust the editing point position by a given number of columns. If the adjustment
-    /// requested is larger than is available in the current line, the editing point is
-    /// adjusted vertically and the process repeats with the remaining adjustment requested.
+    /// Adjust the editing point position by a given number of grapheme clusters. If the
+    /// adjustment requested is larger than is available in the current line, the editing point
+    /// is adjusted vertically and the process repeats with the remaining adjustment requested.
     pub fn adjust_horizontal(&mut self, adjust: isize, select: Selection) {
         if select == Selection::Selected {
             if self.selection_begin.is_none() {
@@ -245,25 +266,29 @@ impl<T: ClipboardProvider> TextInput<T> {
             }
         }
 
-        if adjust < 0 {
-            let remaining = self.edit_point.index;
-            if adjust.abs() as usize > remaining && self.edit_point.line > 0 {
-                self.adjust_vertical(-1, select);
-                self.edit_point.index = self.current_line_length();
-                self.adjust_horizontal(adjust + remaining as isize + 1, select);
+        // Move by whole grapheme clusters, so that the editing point never splits one, going on
+        // to the next or previous line at the ends of lines.
+        for _ in 0..adjust.abs() {
+            if adjust < 0 {
+                if self.edit_point.index > 0 {
+                    self.edit_point.index = previous_grapheme_cluster_boundary(
+                        &self.lines[self.edit_point.line], self.edit_point.index);
+                } else if self.edit_point.line > 0 {
+                    self.edit_point.line -= 1;
+                    self.edit_point.index = self.current_line_length();
+                } else {
+                    break
+                }
             } else {
-                self.edit_point.index = max(0, self.edit_point.index as isize + adjust) as usize;
-            }
-        } else {
-            let remaining = self.current_line_length() - self.edit_point.index;
-            if adjust as usize > remaining && self.lines.len() > self.edit_point.line + 1 {
-                self.adjust_vertical(1, select);
-                self.edit_point.index = 0;
-                // one shift is consumed by the change of line, hence the -1
-                self.adjust_horizontal(adjust - remaining as isize - 1, select);
-            } else {
-                self.edit_point.index = min(self.current_line_length(),
-                                            self.edit_point.index + adjust as usize);
+                if self.edit_point.index < self.current_line_length() {
+                    self.edit_point.index = next_grapheme_cluster_boundary(
+                        &self.lines[self.edit_point.line], self.edit_point.index);
+                } else if self.edit_point.line + 1 < self.lines.len() {
+                    self.edit_point.line += 1;
+                    self.edit_point.index = 0;
+                } else {
+                    break
+                }
             }
         }
     }
@@ -367,6 +392,104 @@ impl<T: ClipboardProvider> TextInput<T> {
         }
     }
 
+    /// Replaces the text being composed by an input method with `text`, or starts composing it
+    /// at the editing point, replacing the selection. The editing point is left `caret`
+    /// characters into the composed text.
+    pub fn set_composition(&mut self, text: String, caret: usize) {
+        self.select_composition();
+        let start = match self.selection_begin {
+            Some(_) => self.get_sorted_selection().0,
+            None => self.edit_point,
+        };
+        let text: String = text.chars().filter(|&c| c != '\n').collect();
+        let length = text.chars().count();
+        self.insert_string(text);
+        self.composition = Some(Composition {
+            start: start,
+            length: length,
+        });
+        self.edit_point = TextPoint {
+            line: start.line,
+            index: start.index + min(caret, length),
+        };
+    }
+
+    /// Replaces the text being composed by an input method, if any, with the text it committed
+    /// to.
+    pub fn commit_composition(&mut self, text: String) {
+        self.select_composition();
+        self.insert_string(text);
+    }
+
+    /// Removes the text being composed by an input method, if any.
+    pub fn cancel_composition(&mut self) {
+        if self.composition.is_some() {
+            self.select_composition();
+            self.replace_selection("".to_owned());
+        }
+    }
+
+    /// Whether an input method is composing text in the content.
+    pub fn is_composing(&self) -> bool {
+        self.composition.is_some()
+    }
+
+    /// Returns the selected text, or the empty string if nothing is selected.
+    pub fn get_selection_text(&self) -> String {
+        if self.selection_begin.is_none() {
+            return "".to_owned()
+        }
+        let (begin, end) = self.get_sorted_selection();
+        let mut text = "".to_owned();
+        for line in begin.line..end.line + 1 {
+            if line > begin.line {
+                text.push('\n');
+            }
+            let start = if line == begin.line { begin.index } else { 0 };
+            let length = self.lines[line].chars().count();
+            let end = if line == end.line { end.index } else { length };
+            text.push_str(self.lines[line].slice_chars(start, end));
+        }
+        text
+    }
+
+    /// Applies an event from the input method editor to the content. Input is only dispatched
+    /// if the content changed.
+    pub fn handle_ime_event(&mut self, event: &ImeEvent) -> KeyReaction {
+        let old_content = self.get_content();
+        match *event {
+            ImeEvent::SetComposition(ref text, caret) => self.set_composition(text.clone(), caret),
+            ImeEvent::Commit(ref text) => self.commit_composition(text.clone()),
+            ImeEvent::Cancel => self.cancel_composition(),
+            ImeEvent::QueryCaretRect => return KeyReaction::Nothing,
+        }
+        if self.get_content() == old_content {
+            KeyReaction::Nothing
+        } else {
+            KeyReaction::DispatchInput
+        }
+    }
+
+    /// Selects the text being composed by an input method, which is then no longer considered
+    /// to be composed.
+    fn select_composition(&mut self) {
+        if let Some(composition) = self.composition.take() {
+            self.selection_begin = Some(composition.start);
+            self.edit_point = TextPoint {
+                line: composition.start.line,
+                index: composition.start.index + composition.length,
+            };
+        }
+    }
+
+    /// Returns the number of characters before the editing point in the content, counting the
+    /// \n between lines.
+    pub fn caret_offset(&self) -> usize {
+        self.lines[..self.edit_point.line].iter().map(|line| line.chars().count() + 1)
+                                                 .fold(0, |offset, length| offset + length) +
+            self.edit_point.index
+    }
+
     /// Get the current contents of the text input. Multiple lines are joined by \n.
     pub fn get_content(&self) -> DOMString {
         let mut content = "".to_owned();
@@ -387,6 +510,7 @@ impl<T: ClipboardProvider> TextInput<T> {
         } else {
             vec!(content)
         };
+        self.composition = None;
         self.edit_point.line = min(self.edit_point.line, self.lines.len() - 1);
         self.edit_point.index = min(self.edit_point.index, self.current_line_length());
     }
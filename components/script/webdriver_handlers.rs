@@ -4,9 +4,11 @@
 
 use dom::bindings::conversions::FromJSValConvertible;
 use dom::bindings::conversions::StringificationBehavior;
-use dom::bindings::codegen::InheritTypes::{NodeCast, ElementCast, HTMLIFrameElementCast};
+use dom::bindings::codegen::InheritTypes::{NodeCast, ElementCast, HTMLElementCast};
+use dom::bindings::codegen::InheritTypes::HTMLIFrameElementCast;
 use dom::bindings::codegen::Bindings::DocumentBinding::DocumentMethods;
 use dom::bindings::codegen::Bindings::ElementBinding::ElementMethods;
+use dom::bindings::codegen::Bindings::HTMLElementBinding::HTMLElementMethods;
 use dom::bindings::codegen::Bindings::HTMLIFrameElementBinding::HTMLIFrameElementMethods;
 use dom::bindings::codegen::Bindings::NodeBinding::NodeMethods;
 use dom::bindings::codegen::Bindings::NodeListBinding::NodeListMethods;
@@ -16,7 +18,7 @@ use dom::window::{ScriptHelpers, WindowHelpers};
 use dom::document::DocumentHelpers;
 use js::jsapi::JSContext;
 use page::Page;
-use msg::constellation_msg::{PipelineId, SubpageId};
+use msg::constellation_msg::{FocusType, PipelineId, SubpageId};
 use msg::webdriver_msg::{WebDriverJSValue, WebDriverJSError, WebDriverJSResult, WebDriverFrameId};
 use script_task::get_page;
 use js::jsapi::{RootedValue, HandleValue};
@@ -164,3 +166,39 @@ pub fn handle_get_name(page: &Rc<Page>, pipeline: PipelineId, node_id: String, r
         None => Err(())
     }).unwrap();
 }
+
+pub fn handle_element_click(page: &Rc<Page>, pipeline: PipelineId, node_id: String,
+                            reply: Sender<Result<(), ()>>) {
+    reply.send(match find_node_by_unique_id(&*page, pipeline, node_id) {
+        Some(ref node) => {
+            match HTMLElementCast::to_ref(node.r()) {
+                Some(element) => {
+                    element.Click();
+                    Ok(())
+                },
+                None => Err(())
+            }
+        },
+        None => Err(())
+    }).unwrap();
+}
+
+pub fn handle_focus_element(page: &Rc<Page>, pipeline: PipelineId, node_id: String,
+                            reply: Sender<Result<(), ()>>) {
+    reply.send(match find_node_by_unique_id(&*page, pipeline, node_id) {
+        Some(ref node) => {
+            match ElementCast::to_ref(node.r()) {
+                Some(element) => {
+                    let page = get_page(&*page, pipeline);
+                    let document = page.document();
+                    document.r().begin_focus_transaction();
+                    document.r().request_focus(element);
+                    document.r().commit_focus_transaction(FocusType::Element);
+                    Ok(())
+                },
+                None => Err(())
+            }
+        },
+        None => Err(())
+    }).unwrap();
+}
@@ -13,19 +13,25 @@ extern crate euclid;
 extern crate libc;
 extern crate msg;
 extern crate net_traits;
+extern crate profile_traits;
+extern crate rustc_serialize;
 extern crate util;
 extern crate url;
 
 use devtools_traits::DevtoolsControlChan;
 use libc::c_void;
-use msg::constellation_msg::{ConstellationChan, PipelineId, Failure, WindowSizeData};
-use msg::constellation_msg::{LoadData, SubpageId, Key, KeyState, KeyModifiers};
+use msg::constellation_msg::{ConstellationChan, DocumentState, PipelineId, Failure};
+use msg::constellation_msg::GeolocationPosition;
+use msg::constellation_msg::WindowSizeData;
+use msg::constellation_msg::{ImeEvent, LoadData, SubpageId, Key, KeyState, KeyModifiers};
 use msg::constellation_msg::{MozBrowserEvent, PipelineExitType};
+use msg::constellation_msg::{NotificationId, NotificationEventType, PermissionName};
 use msg::compositor_msg::ScriptListener;
 use msg::webdriver_msg::WebDriverScriptCommand;
 use net_traits::ResourceTask;
 use net_traits::image_cache_task::ImageCacheTask;
 use net_traits::storage_task::StorageTask;
+use profile_traits::mem;
 use std::any::Any;
 use std::sync::mpsc::{Sender, Receiver};
 use url::Url;
@@ -60,7 +66,7 @@ pub trait StylesheetLoadResponder {
 }
 
 /// Used to determine if a script has any pending asynchronous activity.
-#[derive(Copy, Clone, Debug, PartialEq)]
+#[derive(Copy, Clone, Debug, PartialEq, RustcEncodable, RustcDecodable)]
 pub enum ScriptState {
     DocumentLoaded,
     DocumentLoading,
@@ -88,6 +94,9 @@ pub enum ConstellationControlMsg {
     Freeze(PipelineId),
     /// Notifies script task to resume all its timers
     Thaw(PipelineId),
+    /// Restores the scroll position and form state a document had when its session history
+    /// entry was last current, after the entry had to be reloaded.
+    RestoreDocumentState(PipelineId, DocumentState),
     /// Notifies script task that a url should be loaded in this iframe.
     Navigate(PipelineId, SubpageId, LoadData),
     /// Requests the script task forward a mozbrowser event to an iframe it owns
@@ -104,10 +113,21 @@ pub enum ConstellationControlMsg {
     StylesheetLoadComplete(PipelineId, Url, Box<StylesheetLoadResponder+Send>),
     /// Get the current state of the script task for a given pipeline.
     GetCurrentState(Sender<ScriptState>, PipelineId),
+    /// Notifies script whether a pipeline has been allowed to use a feature.
+    Permission(PipelineId, PermissionName, bool),
+    /// Notifies script that the user interacted with one of its notifications.
+    NotificationEvent(PipelineId, NotificationId, NotificationEventType),
+    /// Sends script the position it asked for, or `None` if it is unavailable.
+    GeolocationPosition(PipelineId, Option<GeolocationPosition>),
+    /// Requests memory reports for the script task's JavaScript heap.
+    CollectReports(mem::ReportsChan),
+    /// Notifies script that a web font has downloaded, so text that was laid out in a fallback
+    /// font while it did has to be laid out again.
+    WebFontLoaded(PipelineId),
 }
 
 /// The mouse button involved in the event.
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, RustcEncodable, RustcDecodable)]
 pub enum MouseButton {
     /// The left mouse button.
     Left,
@@ -118,6 +138,7 @@ pub enum MouseButton {
 }
 
 /// Events from the compositor that the script task needs to know about
+#[derive(RustcEncodable, RustcDecodable)]
 pub enum CompositorEvent {
     /// The window was resized.
     ResizeEvent(WindowSizeData),
@@ -131,6 +152,8 @@ pub enum CompositorEvent {
     MouseMoveEvent(Point2D<f32>),
     /// A key was pressed.
     KeyEvent(Key, KeyState, KeyModifiers),
+    /// The input method editor composed some text, or asked where the caret is.
+    ImeEvent(ImeEvent),
 }
 
 /// An opaque wrapper around script<->layout channels to avoid leaking message types into
@@ -141,6 +164,14 @@ pub struct OpaqueScriptLayoutChannel(pub (Box<Any+Send>, Box<Any+Send>));
 #[derive(Clone)]
 pub struct ScriptControlChan(pub Sender<ConstellationControlMsg>);
 
+impl mem::Reporter for ScriptControlChan {
+    // Just injects an appropriate event into the script task's queue.
+    fn collect_reports(&self, reports_chan: mem::ReportsChan) -> bool {
+        let ScriptControlChan(ref c) = *self;
+        c.send(ConstellationControlMsg::CollectReports(reports_chan)).is_ok()
+    }
+}
+
 pub trait ScriptTaskFactory {
     fn create<C>(_phantom: Option<&mut Self>,
                  id: PipelineId,
@@ -155,6 +186,7 @@ pub trait ScriptTaskFactory {
                  storage_task: StorageTask,
                  image_cache_task: ImageCacheTask,
                  devtools_chan: Option<DevtoolsControlChan>,
+                 mem_profiler_chan: mem::ProfilerChan,
                  window_size: Option<WindowSizeData>,
                  load_data: LoadData)
                  where C: ScriptListener + Send;
@@ -31,6 +31,8 @@ extern crate script;
 extern crate layout;
 extern crate gfx;
 extern crate libc;
+#[macro_use]
+extern crate log;
 extern crate webdriver_server;
 
 use compositing::CompositorEventListener;
@@ -38,6 +40,7 @@ use compositing::windowing::WindowEvent;
 
 use compositing::windowing::WindowMethods;
 use compositing::{CompositorProxy, CompositorTask, Constellation};
+use compositing::content_process::{self, ContentProcessBootstrap};
 
 use msg::constellation_msg::Msg as ConstellationMsg;
 use msg::constellation_msg::ConstellationChan;
@@ -47,21 +50,27 @@ use script::dom::bindings::codegen::RegisterBindings;
 use net::image_cache_task::new_image_cache_task;
 use net::storage_task::StorageTaskFactory;
 use net::resource_task::new_resource_task;
+use net_traits::{ControlMsg, ProtocolHandler, ResourceTask};
 use net_traits::storage_task::StorageTask;
 
 use gfx::font_cache_task::FontCacheTask;
+use gfx::text::hyphenation;
 use profile::mem as profile_mem;
 use profile::time as profile_time;
 use profile_traits::mem;
 use profile_traits::time;
 use util::opts;
+use util::spellcheck::{self, WordListDictionary};
 
-use std::borrow::Borrow;
+use std::borrow::{Borrow, ToOwned};
+use std::path::Path;
 use std::rc::Rc;
 use std::sync::mpsc::Sender;
 
 pub struct Browser {
     compositor: Box<CompositorEventListener + 'static>,
+    resource_task: ResourceTask,
+    font_cache_task: FontCacheTask,
 }
 
 /// The in-process interface to Servo.
@@ -81,6 +90,8 @@ impl Browser {
         // Global configuration options, parsed from the command line.
         let opts = opts::get();
 
+        load_language_data(opts);
+
         script::init();
         // Create the global vtables used by the (generated) DOM
         // bindings to implement JS proxies.
@@ -99,21 +110,31 @@ impl Browser {
             }
             None => false
         };
-        let time_profiler_chan = profile_time::Profiler::create(opts.time_profiler_period);
-        let mem_profiler_chan = profile_mem::Profiler::create(opts.mem_profiler_period);
+        let time_profiler_chan =
+            profile_time::Profiler::create(opts.time_profiler_period,
+                                           opts.time_profiler_trace_path.clone());
+        let mem_profiler_chan = profile_mem::Profiler::create(opts.mem_profiler_period,
+                                                              opts.mem_profiler_json);
         let devtools_chan = opts.devtools_port.map(|port| {
-            devtools::start_server(port)
+            devtools::start_server(port, time_profiler_chan.clone())
         });
 
+        let resource_task = new_resource_task(opts.user_agent.clone(), devtools_chan.clone(),
+                                              Some(mem_profiler_chan.clone()));
+        let font_cache_task = FontCacheTask::new(resource_task.clone());
+
         // Create the constellation, which maintains the engine
         // pipelines, including the script and layout threads, as well
         // as the navigation context.
         let constellation_chan = create_constellation(opts.clone(),
                                                       compositor_proxy.clone_compositor_proxy(),
+                                                      resource_task.clone(),
+                                                      font_cache_task.clone(),
                                                       time_profiler_chan.clone(),
                                                       devtools_chan,
                                                       mem_profiler_chan.clone(),
                                                       supports_clipboard);
+        resource_task.send(ControlMsg::SetConstellationChan(constellation_chan.clone())).unwrap();
 
         if let Some(port) = opts.webdriver_port {
             webdriver_server::start_server(port, constellation_chan.clone());
@@ -130,9 +151,23 @@ impl Browser {
 
         Browser {
             compositor: compositor,
+            resource_task: resource_task,
+            font_cache_task: font_cache_task,
         }
     }
 
+    /// Serves the URLs of `scheme` with `handler`. The schemes Servo loads itself, such
+    /// as http, can't be taken over.
+    pub fn register_protocol_handler(&self, scheme: String, handler: Box<ProtocolHandler>) {
+        self.resource_task.send(ControlMsg::RegisterProtocolHandler(scheme, handler)).unwrap();
+    }
+
+    /// Makes the font in `bytes` available to pages as if it were installed on the system,
+    /// under the name `family`. This is for fonts bundled with the embedding application.
+    pub fn register_font(&self, family: String, bytes: Vec<u8>) {
+        self.font_cache_task.add_local_font(family, bytes);
+    }
+
     pub fn handle_events(&mut self, events: Vec<WindowEvent>) -> bool {
         self.compositor.handle_events(events)
     }
@@ -154,18 +189,68 @@ impl Browser {
     }
 }
 
+/// Runs the script and layout tasks of a content process, as started by the constellation with
+/// `argument`, and returns once the browser process is gone.
+pub fn run_content_process(argument: &str) {
+    let bootstrap = match ContentProcessBootstrap::receive(argument) {
+        Ok(bootstrap) => bootstrap,
+        Err(error) => {
+            warn!("Failed to start the content process: {}", error);
+            return
+        }
+    };
+    opts::set(bootstrap.opts.clone());
+    load_language_data(opts::get());
+
+    script::init();
+    RegisterBindings::RegisterProxyHandlers();
+
+    content_process::run::<layout::layout_task::LayoutTask,
+                           script::script_task::ScriptTask>(bootstrap);
+}
+
+/// Loads the spell checking dictionary and the hyphenation patterns named in `opts`.
+fn load_language_data(opts: &opts::Opts) {
+    // Load the word list that editable text is spell checked against, unless the embedder
+    // has already plugged in a dictionary of its own.
+    if let Some(ref path) = opts.dictionary {
+        if spellcheck::dictionary().is_none() {
+            match WordListDictionary::from_file(Path::new(path)) {
+                Ok(dictionary) => spellcheck::set_dictionary(Box::new(dictionary)),
+                Err(error) => warn!("Failed to load the dictionary {}: {}", path, error),
+            }
+        }
+    }
+
+    // Load the hyphenation patterns for `hyphens: auto`.
+    if let Some(ref path) = opts.hyphenation_patterns {
+        match hyphenation::load_hyphenators(Path::new(path)) {
+            Ok(hyphenators) => hyphenation::set_hyphenators(hyphenators),
+            Err(error) => {
+                warn!("Failed to load the hyphenation patterns {}: {}", path, error)
+            }
+        }
+    }
+}
+
 fn create_constellation(opts: opts::Opts,
                         compositor_proxy: Box<CompositorProxy+Send>,
+                        resource_task: ResourceTask,
+                        font_cache_task: FontCacheTask,
                         time_profiler_chan: time::ProfilerChan,
                         devtools_chan: Option<Sender<devtools_traits::DevtoolsControlMsg>>,
                         mem_profiler_chan: mem::ProfilerChan,
                         supports_clipboard: bool) -> ConstellationChan {
-    let resource_task = new_resource_task(opts.user_agent.clone(), devtools_chan.clone());
-
     let image_cache_task = new_image_cache_task(resource_task.clone());
-    let font_cache_task = FontCacheTask::new(resource_task.clone());
     let storage_task: StorageTask = StorageTaskFactory::new();
 
+    // The caches are shared by every pipeline and live as long as the browser, so their
+    // reporters are never unregistered.
+    mem_profiler_chan.send(mem::ProfilerMsg::RegisterReporter("image-cache".to_owned(),
+                                                              Box::new(image_cache_task.clone())));
+    mem_profiler_chan.send(mem::ProfilerMsg::RegisterReporter("font-cache".to_owned(),
+                                                              Box::new(font_cache_task.clone())));
+
     let constellation_chan = Constellation::<layout::layout_task::LayoutTask,
     script::script_task::ScriptTask>::start(
         compositor_proxy,
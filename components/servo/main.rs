@@ -38,6 +38,7 @@ use std::rc::Rc;
 use util::opts;
 use net::resource_task;
 use servo::Browser;
+use compositing::content_process::CONTENT_PROCESS_ARGUMENT;
 use compositing::windowing::WindowEvent;
 
 #[cfg(target_os="android")]
@@ -46,15 +47,25 @@ use std::borrow::ToOwned;
 fn main() {
     env_logger::init().unwrap();
 
+    // The constellation runs script and layout in copies of this executable, told apart by
+    // their arguments.
+    let args = get_args();
+    if args.len() == 3 && args[1] == CONTENT_PROCESS_ARGUMENT {
+        servo::run_content_process(&args[2]);
+        return
+    }
+
     // Parse the command line options and store them globally
-    opts::from_cmdline_args(&*get_args());
+    opts::from_cmdline_args(&*args);
 
     setup_logging();
 
     // Possibly interpret the `HOST_FILE` environment variable
     resource_task::global_init();
 
-    let window = if opts::get().headless {
+    // Builds with the `headless` feature render offscreen, so they can still draw pages and
+    // save screenshots without a display. Others skip compositing altogether.
+    let window = if opts::get().headless && !cfg!(feature = "headless") {
         None
     } else {
         Some(app::create_window(std::ptr::null_mut()))
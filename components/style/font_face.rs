@@ -6,7 +6,9 @@ use computed_values::font_family::FontFamily;
 use cssparser::{Token, Parser, DeclarationListParser, AtRuleParser, DeclarationParser};
 use parser::{ParserContext, log_css_error};
 use properties::longhands::font_family::parse_one_family;
+use rustc_serialize::{Decodable, Decoder, Encodable, Encoder};
 use std::ascii::AsciiExt;
+use std::cmp;
 use string_cache::Atom;
 use url::{Url, UrlParser};
 
@@ -16,22 +18,85 @@ pub enum Source {
     Local(Atom),
 }
 
-#[derive(Clone, Debug, PartialEq, Eq)]
+// The name of a local font is sent as a string, since atoms only mean something in the process
+// that interned them.
+impl Encodable for Source {
+    fn encode<S: Encoder>(&self, s: &mut S) -> Result<(), S::Error> {
+        s.emit_enum("Source", |s| {
+            match *self {
+                Source::Url(ref url_source) => {
+                    s.emit_enum_variant("Url", 0, 1, |s| {
+                        s.emit_enum_variant_arg(0, |s| url_source.encode(s))
+                    })
+                }
+                Source::Local(ref name) => {
+                    s.emit_enum_variant("Local", 1, 1, |s| {
+                        s.emit_enum_variant_arg(0, |s| s.emit_str(&*name))
+                    })
+                }
+            }
+        })
+    }
+}
+
+impl Decodable for Source {
+    fn decode<D: Decoder>(d: &mut D) -> Result<Source, D::Error> {
+        d.read_enum("Source", |d| {
+            d.read_enum_variant(&["Url", "Local"], |d, index| {
+                match index {
+                    0 => Ok(Source::Url(try!(d.read_enum_variant_arg(0, Decodable::decode)))),
+                    1 => {
+                        let name = try!(d.read_enum_variant_arg(0, |d| d.read_str()));
+                        Ok(Source::Local(Atom::from_slice(&name)))
+                    }
+                    _ => Err(d.error("invalid Source")),
+                }
+            })
+        })
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, RustcEncodable, RustcDecodable)]
 pub struct UrlSource {
     pub url: Url,
     pub format_hints: Vec<String>,
 }
 
+/// A range of code points, from the `unicode-range` descriptor.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, HeapSizeOf, RustcEncodable, RustcDecodable)]
+pub struct UnicodeRange {
+    pub start: u32,
+    pub end: u32,
+}
+
+impl UnicodeRange {
+    /// The range of every code point, which is what fonts are used for by default.
+    pub fn all() -> UnicodeRange {
+        UnicodeRange {
+            start: 0,
+            end: 0x10FFFF,
+        }
+    }
+
+    pub fn contains(&self, character: char) -> bool {
+        let code_point = character as u32;
+        self.start <= code_point && code_point <= self.end
+    }
+}
+
 #[derive(Debug, PartialEq, Eq)]
 pub struct FontFaceRule {
     pub family: Atom,
     pub sources: Vec<Source>,
+    /// The characters that the font is used for.
+    pub unicode_range: Vec<UnicodeRange>,
 }
 
 pub fn parse_font_face_block(context: &ParserContext, input: &mut Parser)
                              -> Result<FontFaceRule, ()> {
     let mut family = None;
     let mut src = None;
+    let mut unicode_range = None;
     let mut iter = DeclarationListParser::new(input, FontFaceRuleParser { context: context });
     while let Some(declaration) = iter.next() {
         match declaration {
@@ -47,6 +112,9 @@ pub fn parse_font_face_block(context: &ParserContext, input: &mut Parser)
             Ok(FontFaceDescriptorDeclaration::Src(value)) => {
                 src = Some(value);
             }
+            Ok(FontFaceDescriptorDeclaration::UnicodeRange(value)) => {
+                unicode_range = Some(value);
+            }
         }
     }
     match (family, src) {
@@ -54,6 +122,7 @@ pub fn parse_font_face_block(context: &ParserContext, input: &mut Parser)
             Ok(FontFaceRule {
                 family: family,
                 sources: src,
+                unicode_range: unicode_range.unwrap_or_else(|| vec!(UnicodeRange::all())),
             })
         }
         _ => Err(())
@@ -63,6 +132,7 @@ pub fn parse_font_face_block(context: &ParserContext, input: &mut Parser)
 enum FontFaceDescriptorDeclaration {
     Family(Atom),
     Src(Vec<Source>),
+    UnicodeRange(Vec<UnicodeRange>),
 }
 
 
@@ -91,6 +161,10 @@ impl<'a, 'b> DeclarationParser for FontFaceRuleParser<'a, 'b> {
                 Ok(FontFaceDescriptorDeclaration::Src(try!(input.parse_comma_separated(|input| {
                     parse_one_src(self.context, input)
                 }))))
+            },
+            "unicode-range" => {
+                Ok(FontFaceDescriptorDeclaration::UnicodeRange(try!(
+                            input.parse_comma_separated(parse_one_unicode_range))))
             }
             _ => Err(())
         }
@@ -104,6 +178,20 @@ fn parse_one_non_generic_family_name(input: &mut Parser) -> Result<Atom, ()> {
     }
 }
 
+/// Parses one range of a `unicode-range` descriptor. Ranges past the last code point are cut
+/// short.
+/// http://dev.w3.org/csswg/css-fonts/#unicode-range-desc
+fn parse_one_unicode_range(input: &mut Parser) -> Result<UnicodeRange, ()> {
+    match try!(input.next()) {
+        Token::UnicodeRange(start, end) if start <= end && start <= 0x10FFFF => {
+            Ok(UnicodeRange {
+                start: start,
+                end: cmp::min(end, 0x10FFFF),
+            })
+        }
+        _ => Err(())
+    }
+}
 
 fn parse_one_src(context: &ParserContext, input: &mut Parser) -> Result<Source, ()> {
     let url = match input.next() {
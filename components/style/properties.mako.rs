@@ -44,12 +44,15 @@ def to_camel_case(ident):
     return re.sub("_([a-z])", lambda m: m.group(1).upper(), ident.strip("_").capitalize())
 
 class Longhand(object):
-    def __init__(self, name, derived_from=None, experimental=False):
+    def __init__(self, name, derived_from=None, experimental=False, internal=False):
         self.name = name
         self.ident = to_rust_ident(name)
         self.camel_case = to_camel_case(self.ident)
         self.style_struct = THIS_STYLE_STRUCT
         self.experimental = experimental
+        # Internal properties are only set by layout and presentational hints, never by
+        # stylesheets or script.
+        self.internal = internal
         if derived_from is None:
             self.derived_from = None
         else:
@@ -98,12 +101,13 @@ def switch_to_style_struct(name):
 
 pub mod longhands {
 
-    <%def name="raw_longhand(name, derived_from=None, experimental=False)">
+    <%def name="raw_longhand(name, derived_from=None, experimental=False, internal=False)">
     <%
         if derived_from is not None:
             derived_from = derived_from.split()
 
-        property = Longhand(name, derived_from=derived_from, experimental=experimental)
+        property = Longhand(name, derived_from=derived_from, experimental=experimental,
+                            internal=internal)
         THIS_STYLE_STRUCT.longhands.append(property)
         LONGHANDS.append(property)
         LONGHANDS_BY_NAME[name] = property
@@ -136,9 +140,9 @@ pub mod longhands {
         }
     </%def>
 
-    <%def name="longhand(name, derived_from=None, experimental=False)">
+    <%def name="longhand(name, derived_from=None, experimental=False, internal=False)">
         <%self:raw_longhand name="${name}" derived_from="${derived_from}"
-                            experimental="${experimental}">
+                            experimental="${experimental}" internal="${internal}">
             ${caller.body()}
             % if derived_from is None:
                 pub fn parse_specified(context: &ParserContext, input: &mut Parser)
@@ -1622,7 +1626,7 @@ pub mod longhands {
         }
         pub mod computed_value {
             use std::fmt;
-            #[derive(PartialEq, Eq, Copy, Clone, Hash)]
+            #[derive(PartialEq, Eq, Copy, Clone, Hash, RustcEncodable, RustcDecodable)]
             pub enum T {
                 % for weight in range(100, 901, 100):
                     Weight${weight} = ${weight},
@@ -1750,10 +1754,407 @@ pub mod longhands {
         }
     </%self:longhand>
 
+    <%self:longhand name="font-size-adjust">
+        use values::computed::ComputedValueAsSpecified;
+
+        impl ComputedValueAsSpecified for SpecifiedValue {}
+        pub type SpecifiedValue = computed_value::T;
+        pub mod computed_value {
+            use cssparser::ToCss;
+            use std::fmt;
+            use values::CSSFloat;
+
+            /// The ratio of the x-height of fonts to their size that they are scaled to have.
+            #[derive(PartialEq, Clone, Copy, Debug)]
+            pub enum T {
+                None,
+                Number(CSSFloat),
+            }
+
+            impl ToCss for T {
+                fn to_css<W>(&self, dest: &mut W) -> fmt::Result where W: fmt::Write {
+                    match self {
+                        &T::None => dest.write_str("none"),
+                        &T::Number(number) => write!(dest, "{}", number),
+                    }
+                }
+            }
+        }
+        #[inline]
+        pub fn get_initial_value() -> computed_value::T {
+            computed_value::T::None
+        }
+        /// none | <number>
+        fn parse(_context: &ParserContext, input: &mut Parser) -> Result<SpecifiedValue, ()> {
+            if input.try(|input| input.expect_ident_matching("none")).is_ok() {
+                return Ok(computed_value::T::None)
+            }
+            let number = try!(input.expect_number());
+            if number < 0.0 {
+                return Err(())
+            }
+            Ok(computed_value::T::Number(number))
+        }
+    </%self:longhand>
+
     ${single_keyword("font-stretch",
                      "normal ultra-condensed extra-condensed condensed semi-condensed semi-expanded \
                      expanded extra-expanded ultra-expanded")}
 
+    ${single_keyword("font-kerning", "auto normal none")}
+
+    // The language of an element, from its `xml:lang` or `lang` attribute or the document's
+    // default language, which fonts are picked for.
+    <%self:longhand name="-servo-lang" internal="True">
+        use values::computed::ComputedValueAsSpecified;
+
+        impl ComputedValueAsSpecified for SpecifiedValue {}
+        pub type SpecifiedValue = computed_value::T;
+        pub mod computed_value {
+            use cssparser::ToCss;
+            use std::fmt;
+            use string_cache::Atom;
+
+            /// A BCP 47 language tag, or `None` if the language is unknown.
+            #[derive(PartialEq, Eq, Clone, Hash, Debug)]
+            pub struct T(pub Option<Atom>);
+
+            impl ToCss for T {
+                fn to_css<W>(&self, dest: &mut W) -> fmt::Result where W: fmt::Write {
+                    match self.0 {
+                        Some(ref lang) => dest.write_str(lang.as_slice()),
+                        None => dest.write_str("none"),
+                    }
+                }
+            }
+        }
+        #[inline]
+        pub fn get_initial_value() -> computed_value::T {
+            computed_value::T(None)
+        }
+        fn parse(_context: &ParserContext, input: &mut Parser) -> Result<SpecifiedValue, ()> {
+            use std::ascii::AsciiExt;
+            use string_cache::Atom;
+
+            let lang = try!(input.expect_ident());
+            if lang.eq_ignore_ascii_case("none") {
+                Ok(computed_value::T(None))
+            } else {
+                Ok(computed_value::T(Some(Atom::from_slice(&lang))))
+            }
+        }
+    </%self:longhand>
+
+    <%self:longhand name="font-feature-settings">
+        use cssparser::{ToCss, Token};
+        use std::borrow::Cow;
+        use std::fmt;
+        use values::computed::ComputedValueAsSpecified;
+
+        pub use self::computed_value::T as SpecifiedValue;
+
+        pub mod computed_value {
+            /// An OpenType feature tag and the value to set it to. Features that are turned on
+            /// have the value 1, and features that pick one of several alternates have the index
+            /// of the alternate.
+            #[derive(Clone, PartialEq, Eq, Debug)]
+            pub struct FeatureTagValue {
+                pub tag: String,
+                pub value: u32,
+            }
+
+            /// The features to set, in order, or none for `normal`.
+            #[derive(Clone, PartialEq, Eq, Debug)]
+            pub struct T(pub Vec<FeatureTagValue>);
+        }
+
+        impl ComputedValueAsSpecified for SpecifiedValue {}
+
+        impl ToCss for SpecifiedValue {
+            fn to_css<W>(&self, dest: &mut W) -> fmt::Result where W: fmt::Write {
+                if self.0.is_empty() {
+                    return dest.write_str("normal")
+                }
+                let mut first = true;
+                for feature in self.0.iter() {
+                    if !first {
+                        try!(dest.write_str(", "));
+                    }
+                    first = false;
+                    try!(Token::QuotedString(Cow::from(&*feature.tag)).to_css(dest));
+                    if feature.value != 1 {
+                        try!(write!(dest, " {}", feature.value));
+                    }
+                }
+                Ok(())
+            }
+        }
+
+        #[inline]
+        pub fn get_initial_value() -> computed_value::T {
+            computed_value::T(vec!())
+        }
+
+        /// normal | <feature-tag-value>#
+        pub fn parse(_context: &ParserContext, input: &mut Parser) -> Result<SpecifiedValue, ()> {
+            if input.try(|input| input.expect_ident_matching("normal")).is_ok() {
+                return Ok(SpecifiedValue(vec!()))
+            }
+            input.parse_comma_separated(parse_one_feature).map(SpecifiedValue)
+        }
+
+        /// <string> [ <integer> | on | off ]?
+        fn parse_one_feature(input: &mut Parser) -> Result<computed_value::FeatureTagValue, ()> {
+            let tag = try!(input.expect_string()).into_owned();
+            // Feature tags are four printable ASCII characters.
+            if tag.len() != 4 || tag.chars().any(|c| c < ' ' || c > '~') {
+                return Err(())
+            }
+            let value = if let Ok(value) = input.try(|input| input.expect_integer()) {
+                if value < 0 {
+                    return Err(())
+                }
+                value as u32
+            } else if input.try(|input| input.expect_ident_matching("off")).is_ok() {
+                0
+            } else {
+                let _ = input.try(|input| input.expect_ident_matching("on"));
+                1
+            };
+            Ok(computed_value::FeatureTagValue {
+                tag: tag,
+                value: value,
+            })
+        }
+    </%self:longhand>
+
+    <%self:longhand name="font-variant-ligatures">
+        use cssparser::ToCss;
+        use std::fmt;
+        use values::computed::ComputedValueAsSpecified;
+
+        pub use self::computed_value::T as SpecifiedValue;
+
+        pub mod computed_value {
+            /// Whether each kind of ligature is turned on or off, or `None` to leave it as the
+            /// font has it.
+            #[derive(PartialEq, Eq, Copy, Clone, Debug)]
+            pub struct T {
+                pub common: Option<bool>,
+                pub discretionary: Option<bool>,
+                pub historical: Option<bool>,
+                pub contextual: Option<bool>,
+            }
+
+            #[allow(non_upper_case_globals)]
+            pub const normal: T = T {
+                common: None,
+                discretionary: None,
+                historical: None,
+                contextual: None,
+            };
+
+            #[allow(non_upper_case_globals)]
+            pub const none: T = T {
+                common: Some(false),
+                discretionary: Some(false),
+                historical: Some(false),
+                contextual: Some(false),
+            };
+        }
+
+        impl ComputedValueAsSpecified for SpecifiedValue {}
+
+        impl ToCss for SpecifiedValue {
+            fn to_css<W>(&self, dest: &mut W) -> fmt::Result where W: fmt::Write {
+                if *self == computed_value::normal {
+                    return dest.write_str("normal")
+                }
+                if *self == computed_value::none {
+                    return dest.write_str("none")
+                }
+                let keywords = [
+                    (self.common, "common-ligatures", "no-common-ligatures"),
+                    (self.discretionary, "discretionary-ligatures", "no-discretionary-ligatures"),
+                    (self.historical, "historical-ligatures", "no-historical-ligatures"),
+                    (self.contextual, "contextual", "no-contextual"),
+                ];
+                let mut space = false;
+                for &(value, on, off) in keywords.iter() {
+                    let keyword = match value {
+                        Some(true) => on,
+                        Some(false) => off,
+                        None => continue,
+                    };
+                    if space {
+                        try!(dest.write_str(" "));
+                    }
+                    try!(dest.write_str(keyword));
+                    space = true;
+                }
+                Ok(())
+            }
+        }
+
+        #[inline]
+        pub fn get_initial_value() -> computed_value::T {
+            computed_value::normal
+        }
+
+        /// normal | none | [ <common-lig-values> || <discretionary-lig-values> ||
+        /// <historical-lig-values> || <contextual-alt-values> ]
+        pub fn parse(_context: &ParserContext, input: &mut Parser) -> Result<SpecifiedValue, ()> {
+            if input.try(|input| input.expect_ident_matching("normal")).is_ok() {
+                return Ok(computed_value::normal)
+            }
+            if input.try(|input| input.expect_ident_matching("none")).is_ok() {
+                return Ok(computed_value::none)
+            }
+            let mut result = computed_value::normal;
+            while let Ok(ident) = input.try(|input| input.expect_ident()) {
+                let (kind, value) = match_ignore_ascii_case! { ident,
+                    "common-ligatures" => (&mut result.common, true),
+                    "no-common-ligatures" => (&mut result.common, false),
+                    "discretionary-ligatures" => (&mut result.discretionary, true),
+                    "no-discretionary-ligatures" => (&mut result.discretionary, false),
+                    "historical-ligatures" => (&mut result.historical, true),
+                    "no-historical-ligatures" => (&mut result.historical, false),
+                    "contextual" => (&mut result.contextual, true),
+                    "no-contextual" => (&mut result.contextual, false)
+                    _ => return Err(())
+                };
+                if kind.is_some() {
+                    return Err(())
+                }
+                *kind = Some(value);
+            }
+            if result != computed_value::normal { Ok(result) } else { Err(()) }
+        }
+    </%self:longhand>
+
+    <%self:longhand name="font-variant-numeric">
+        use cssparser::ToCss;
+        use std::fmt;
+        use values::computed::ComputedValueAsSpecified;
+
+        pub use self::computed_value::T as SpecifiedValue;
+
+        pub mod computed_value {
+            #[derive(PartialEq, Eq, Copy, Clone, Debug)]
+            pub enum Figures {
+                Lining,
+                Oldstyle,
+            }
+
+            #[derive(PartialEq, Eq, Copy, Clone, Debug)]
+            pub enum Spacing {
+                Proportional,
+                Tabular,
+            }
+
+            #[derive(PartialEq, Eq, Copy, Clone, Debug)]
+            pub enum Fractions {
+                Diagonal,
+                Stacked,
+            }
+
+            /// The styles of numerals to use. `None` leaves a style as the font has it.
+            #[derive(PartialEq, Eq, Copy, Clone, Debug)]
+            pub struct T {
+                pub figures: Option<Figures>,
+                pub spacing: Option<Spacing>,
+                pub fractions: Option<Fractions>,
+                pub ordinal: bool,
+                pub slashed_zero: bool,
+            }
+
+            #[allow(non_upper_case_globals)]
+            pub const normal: T = T {
+                figures: None,
+                spacing: None,
+                fractions: None,
+                ordinal: false,
+                slashed_zero: false,
+            };
+        }
+
+        use self::computed_value::{Figures, Fractions, Spacing};
+
+        impl ComputedValueAsSpecified for SpecifiedValue {}
+
+        impl ToCss for SpecifiedValue {
+            fn to_css<W>(&self, dest: &mut W) -> fmt::Result where W: fmt::Write {
+                if *self == computed_value::normal {
+                    return dest.write_str("normal")
+                }
+                let keywords = [
+                    match self.figures {
+                        Some(Figures::Lining) => Some("lining-nums"),
+                        Some(Figures::Oldstyle) => Some("oldstyle-nums"),
+                        None => None,
+                    },
+                    match self.spacing {
+                        Some(Spacing::Proportional) => Some("proportional-nums"),
+                        Some(Spacing::Tabular) => Some("tabular-nums"),
+                        None => None,
+                    },
+                    match self.fractions {
+                        Some(Fractions::Diagonal) => Some("diagonal-fractions"),
+                        Some(Fractions::Stacked) => Some("stacked-fractions"),
+                        None => None,
+                    },
+                    if self.ordinal { Some("ordinal") } else { None },
+                    if self.slashed_zero { Some("slashed-zero") } else { None },
+                ];
+                let mut space = false;
+                for keyword in keywords.iter().filter_map(|keyword| *keyword) {
+                    if space {
+                        try!(dest.write_str(" "));
+                    }
+                    try!(dest.write_str(keyword));
+                    space = true;
+                }
+                Ok(())
+            }
+        }
+
+        #[inline]
+        pub fn get_initial_value() -> computed_value::T {
+            computed_value::normal
+        }
+
+        /// normal | [ <numeric-figure-values> || <numeric-spacing-values> ||
+        /// <numeric-fraction-values> || ordinal || slashed-zero ]
+        pub fn parse(_context: &ParserContext, input: &mut Parser) -> Result<SpecifiedValue, ()> {
+            if input.try(|input| input.expect_ident_matching("normal")).is_ok() {
+                return Ok(computed_value::normal)
+            }
+            let mut result = computed_value::normal;
+            while let Ok(ident) = input.try(|input| input.expect_ident()) {
+                match_ignore_ascii_case! { ident,
+                    "lining-nums" => if result.figures.is_some() { return Err(()) }
+                                     else { result.figures = Some(Figures::Lining) },
+                    "oldstyle-nums" => if result.figures.is_some() { return Err(()) }
+                                       else { result.figures = Some(Figures::Oldstyle) },
+                    "proportional-nums" => if result.spacing.is_some() { return Err(()) }
+                                           else { result.spacing = Some(Spacing::Proportional) },
+                    "tabular-nums" => if result.spacing.is_some() { return Err(()) }
+                                      else { result.spacing = Some(Spacing::Tabular) },
+                    "diagonal-fractions" => if result.fractions.is_some() { return Err(()) }
+                                            else { result.fractions = Some(Fractions::Diagonal) },
+                    "stacked-fractions" => if result.fractions.is_some() { return Err(()) }
+                                           else { result.fractions = Some(Fractions::Stacked) },
+                    "ordinal" => if result.ordinal { return Err(()) }
+                                 else { result.ordinal = true },
+                    "slashed-zero" => if result.slashed_zero { return Err(()) }
+                                      else { result.slashed_zero = true }
+                    _ => return Err(())
+                }
+            }
+            if result != computed_value::normal { Ok(result) } else { Err(()) }
+        }
+    </%self:longhand>
+
     // CSS 2.1, Section 16 - Text
 
     ${new_style_struct("InheritedText", is_inherited=True)}
@@ -1910,6 +2311,69 @@ pub mod longhands {
         }
     </%self:longhand>
 
+    <%self:longhand name="tab-size">
+        use values::computed::{ToComputedValue, Context};
+        use cssparser::ToCss;
+        use std::fmt;
+
+        #[derive(Clone, Copy, PartialEq)]
+        pub enum SpecifiedValue {
+            Spaces(u32),
+            Length(specified::Length),
+        }
+
+        impl ToCss for SpecifiedValue {
+            fn to_css<W>(&self, dest: &mut W) -> fmt::Result where W: fmt::Write {
+                match *self {
+                    SpecifiedValue::Spaces(spaces) => write!(dest, "{}", spaces),
+                    SpecifiedValue::Length(l) => l.to_css(dest),
+                }
+            }
+        }
+
+        pub mod computed_value {
+            use util::geometry::Au;
+
+            /// The distance between tab stops.
+            #[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+            pub enum T {
+                /// A number of spaces, with their letter and word spacing.
+                Spaces(u32),
+                Length(Au),
+            }
+        }
+
+        #[inline]
+        pub fn get_initial_value() -> computed_value::T {
+            computed_value::T::Spaces(8)
+        }
+
+        impl ToComputedValue for SpecifiedValue {
+            type ComputedValue = computed_value::T;
+
+            #[inline]
+            fn to_computed_value(&self, context: &Context) -> computed_value::T {
+                match *self {
+                    SpecifiedValue::Spaces(spaces) => computed_value::T::Spaces(spaces),
+                    SpecifiedValue::Length(l) => {
+                        computed_value::T::Length(l.to_computed_value(context))
+                    }
+                }
+            }
+        }
+
+        /// <integer> | <length>
+        pub fn parse(_context: &ParserContext, input: &mut Parser) -> Result<SpecifiedValue, ()> {
+            if let Ok(spaces) = input.try(|input| input.expect_integer()) {
+                if spaces < 0 {
+                    return Err(())
+                }
+                return Ok(SpecifiedValue::Spaces(spaces as u32))
+            }
+            specified::Length::parse_non_negative(input).map(SpecifiedValue::Length)
+        }
+    </%self:longhand>
+
     ${predefined_type("text-indent", "LengthOrPercentage", "computed::LengthOrPercentage::Length(Au(0))")}
 
     // Also known as "word-wrap" (which is more popular because of IE), but this is the preferred
@@ -1919,6 +2383,8 @@ pub mod longhands {
     // TODO(pcwalton): Support `word-break: keep-all` once we have better CJK support.
     ${single_keyword("word-break", "normal break-all")}
 
+    ${single_keyword("hyphens", "manual none auto")}
+
     ${single_keyword("text-overflow", "clip ellipsis")}
 
     // TODO(pcwalton): Support `text-justify: distribute`.
@@ -2089,6 +2555,11 @@ pub mod longhands {
 
     ${single_keyword("text-rendering", "auto optimizespeed optimizelegibility geometricprecision")}
 
+    // https://compat.spec.whatwg.org/#text-stroke
+    ${predefined_type("-webkit-text-stroke-color", "CSSColor", "::cssparser::Color::CurrentColor")}
+
+    ${predefined_type("-webkit-text-stroke-width", "Length", "Au(0)", "parse_non_negative")}
+
     // CSS 2.1, Section 17 - Tables
     ${new_style_struct("Table", is_inherited=False)}
 
@@ -2192,9 +2663,9 @@ pub mod longhands {
 
     ${single_keyword("writing-mode", "horizontal-tb vertical-rl vertical-lr", experimental=True)}
 
-    // FIXME(SimonSapin): Add 'mixed' and 'upright' (needs vertical text support)
-    // FIXME(SimonSapin): initial (first) value should be 'mixed', when that's implemented
-    ${single_keyword("text-orientation", "sideways sideways-left sideways-right", experimental=True)}
+    ${single_keyword("text-orientation",
+                     "mixed upright sideways sideways-left sideways-right",
+                     experimental=True)}
 
     // CSS Basic User Interface Module Level 3
     // http://dev.w3.org/csswg/css-ui/
@@ -2959,7 +3430,7 @@ pub mod longhands {
             use values::CSSFloat;
             use values::specified::{Angle};
 
-            #[derive(Clone, PartialEq, Debug, HeapSizeOf)]
+            #[derive(Clone, PartialEq, Debug, HeapSizeOf, RustcEncodable, RustcDecodable)]
             pub enum Filter {
                 Blur(Au),
                 Brightness(CSSFloat),
@@ -2972,7 +3443,7 @@ pub mod longhands {
                 Sepia(CSSFloat),
             }
 
-            #[derive(Clone, PartialEq, Debug, HeapSizeOf)]
+            #[derive(Clone, PartialEq, Debug, HeapSizeOf, RustcEncodable, RustcDecodable)]
             pub struct T { pub filters: Vec<Filter> }
 
             impl T {
@@ -3766,7 +4237,7 @@ pub mod longhands {
             use cssparser::ToCss;
             use std::fmt;
 
-            #[derive(Copy, Clone, Debug, PartialEq, HeapSizeOf)]
+            #[derive(Copy, Clone, Debug, PartialEq, HeapSizeOf, RustcEncodable, RustcDecodable)]
             pub enum T {
                 Auto,
                 CrispEdges,
@@ -5226,7 +5697,7 @@ impl PropertyDeclaration {
     pub fn matches(&self, name: &str) -> bool {
         match *self {
             % for property in LONGHANDS:
-                % if property.derived_from is None:
+                % if property.derived_from is None and not property.internal:
                     PropertyDeclaration::${property.camel_case}(..) => {
                         name.eq_ignore_ascii_case("${property.name}")
                     }
@@ -5240,7 +5711,7 @@ impl PropertyDeclaration {
                  result_list: &mut Vec<PropertyDeclaration>) -> PropertyDeclarationParseResult {
         match_ignore_ascii_case! { name,
             % for property in LONGHANDS:
-                % if property.derived_from is None:
+                % if property.derived_from is None and not property.internal:
                     "${property.name}" => {
                         % if property.experimental:
                             if !::util::opts::experimental_enabled() {
@@ -5525,6 +5996,9 @@ pub fn get_writing_mode(inheritedbox_style: &style_structs::InheritedBox) -> Wri
         },
     }
     match inheritedbox_style.text_orientation {
+        // Upright glyphs are set apart from sideways ones when text runs are made.
+        computed_values::text_orientation::T::mixed |
+        computed_values::text_orientation::T::upright |
         computed_values::text_orientation::T::sideways_right => {},
         computed_values::text_orientation::T::sideways_left => {
             flags.insert(logical_geometry::FLAG_VERTICAL_LR);
@@ -6056,7 +6530,9 @@ pub fn modify_style_for_inline_sides(style: &mut Arc<ComputedValues>,
 pub fn is_supported_property(property: &str) -> bool {
     match property {
         % for property in SHORTHANDS + LONGHANDS:
-            "${property.name}" => true,
+            % if not getattr(property, "internal", False):
+                "${property.name}" => true,
+            % endif
         % endfor
         _ => false,
     }
@@ -6067,7 +6543,7 @@ macro_rules! css_properties_accessors {
     ($macro_name: ident) => {
         $macro_name! {
             % for property in SHORTHANDS + LONGHANDS:
-                % if property.derived_from is None:
+                % if property.derived_from is None and not getattr(property, "internal", False):
                     % if property != LONGHANDS[-1]:
                         [${property.camel_case}, Set${property.camel_case}, "${property.name}"],
                     % else:
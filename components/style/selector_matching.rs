@@ -5,7 +5,7 @@
 use url::Url;
 
 use selectors::bloom::BloomFilter;
-use selectors::matching::{SelectorMap, Rule};
+use selectors::matching::{SelectorMap, Rule, matches};
 use selectors::matching::DeclarationBlock as GenericDeclarationBlock;
 use selectors::parser::PseudoElement;
 use selectors::Element;
@@ -17,12 +17,27 @@ use legacy::PresentationalHintSynthesis;
 use media_queries::Device;
 use node::TElementAttributes;
 use properties::{PropertyDeclaration, PropertyDeclarationBlock};
-use stylesheets::{Stylesheet, CSSRuleIteratorExt, Origin};
+use stylesheets::{Stylesheet, StyleRule, CSSRuleIteratorExt, Origin};
 use viewport::{ViewportConstraints, ViewportRuleCascade};
 
 
 pub type DeclarationBlock = GenericDeclarationBlock<Vec<PropertyDeclaration>>;
 
+/// The user agent style sheets that every stylist starts with, in the resources directory.
+pub static USER_AGENT_STYLESHEETS: [&'static str; 3] =
+    ["user-agent.css", "servo.css", "presentational-hints.css"];
+
+/// The user agent style sheet for documents in quirks mode, in the resources directory.
+pub static QUIRKS_MODE_STYLESHEET: &'static str = "quirks-mode.css";
+
+/// A style rule that matches an element, along with the style-sheet it came from.
+pub struct MatchedRule<'a> {
+    pub stylesheet: &'a Stylesheet,
+    pub rule: &'a StyleRule,
+    /// The specificity of the most specific of the rule's selectors that matched.
+    pub specificity: u32,
+}
+
 
 pub struct Stylist {
     // List of stylesheets (including all media rules)
@@ -59,7 +74,7 @@ impl Stylist {
         // FIXME: Add iso-8859-9.css when the document’s encoding is ISO-8859-8.
         // FIXME: presentational-hints.css should be at author origin with zero specificity.
         //        (Does it make a difference?)
-        for &filename in ["user-agent.css", "servo.css", "presentational-hints.css"].iter() {
+        for &filename in USER_AGENT_STYLESHEETS.iter() {
             match read_resource_file(&[filename]) {
                 Ok(res) => {
                     let ua_stylesheet = Stylesheet::from_bytes(
@@ -163,7 +178,7 @@ impl Stylist {
     }
 
     pub fn add_quirks_mode_stylesheet(&mut self) {
-        match read_resource_file(&["quirks-mode.css"]) {
+        match read_resource_file(&[QUIRKS_MODE_STYLESHEET]) {
             Ok(res) => {
             self.add_stylesheet(Stylesheet::from_bytes(
                 &res,
@@ -267,6 +282,37 @@ impl Stylist {
         shareable
     }
 
+    /// Returns the style rules that apply to the given element itself (rather than to its
+    /// pseudo-elements), in the order the cascade applies their normal declarations: by origin,
+    /// then by specificity, then by source order. This is slow, and meant for developer tools.
+    pub fn matched_rules<'a, E>(&'a self, element: &E) -> Vec<MatchedRule<'a>> where E: Element {
+        let mut matched = vec!();
+        for &origin in [Origin::UserAgent, Origin::User, Origin::Author].iter() {
+            let start = matched.len();
+            for stylesheet in self.stylesheets.iter().filter(|sheet| sheet.origin == origin) {
+                for rule in stylesheet.effective_rules(&self.device).style() {
+                    let mut specificity = None;
+                    for (i, selector) in rule.selectors.iter().enumerate() {
+                        if selector.pseudo_element.is_none() &&
+                                matches(&rule.selectors[i..i + 1], element, &mut None) {
+                            specificity = specificity.max(Some(selector.specificity));
+                        }
+                    }
+                    if let Some(specificity) = specificity {
+                        matched.push(MatchedRule {
+                            stylesheet: stylesheet,
+                            rule: rule,
+                            specificity: specificity,
+                        });
+                    }
+                }
+            }
+            // The sort is stable, so this keeps rules of equal specificity in source order.
+            matched[start..].sort_by(|a, b| a.specificity.cmp(&b.specificity));
+        }
+        matched
+    }
+
     pub fn is_dirty(&self) -> bool {
         self.is_dirty
     }
@@ -3,6 +3,7 @@
  * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
 
 use std::ascii::AsciiExt;
+use std::borrow::ToOwned;
 use std::cell::Cell;
 use std::iter::Iterator;
 use std::slice;
@@ -44,6 +45,8 @@ pub struct Stylesheet {
     /// cascading order)
     pub rules: Vec<CSSRule>,
     pub origin: Origin,
+    /// The URL the style-sheet was loaded from, used to report where its rules came from.
+    pub url: Url,
 }
 
 
@@ -74,6 +77,10 @@ impl MediaRule {
 pub struct StyleRule {
     pub selectors: Vec<Selector>,
     pub declarations: PropertyDeclarationBlock,
+    /// The selectors as they were written.
+    pub selector_text: String,
+    /// The 1-based line of the style-sheet on which the rule's selectors start.
+    pub line: usize,
 }
 
 
@@ -134,6 +141,7 @@ impl Stylesheet {
         Stylesheet {
             origin: origin,
             rules: rules,
+            url: base_url,
         }
     }
 
@@ -392,23 +400,27 @@ impl<'a> AtRuleParser for TopLevelRuleParser<'a> {
 
 
 impl<'a> QualifiedRuleParser for TopLevelRuleParser<'a> {
-    type Prelude = Vec<Selector>;
+    type Prelude = StyleRulePrelude;
     type QualifiedRule = CSSRule;
 
     #[inline]
-    fn parse_prelude(&self, input: &mut Parser) -> Result<Vec<Selector>, ()> {
+    fn parse_prelude(&self, input: &mut Parser) -> Result<StyleRulePrelude, ()> {
         self.state.set(State::Body);
         QualifiedRuleParser::parse_prelude(&NestedRuleParser { context: &self.context }, input)
     }
 
     #[inline]
-    fn parse_block(&self, prelude: Vec<Selector>, input: &mut Parser) -> Result<CSSRule, ()> {
+    fn parse_block(&self, prelude: StyleRulePrelude, input: &mut Parser) -> Result<CSSRule, ()> {
         QualifiedRuleParser::parse_block(&NestedRuleParser { context: &self.context },
                                          prelude, input)
     }
 }
 
 
+/// The selectors of a style rule, their source text, and the line on which they start.
+type StyleRulePrelude = (Vec<Selector>, String, usize);
+
+
 struct NestedRuleParser<'a, 'b: 'a> {
     context: &'a ParserContext<'b>,
 }
@@ -459,17 +471,24 @@ impl<'a, 'b> AtRuleParser for NestedRuleParser<'a, 'b> {
 
 
 impl<'a, 'b> QualifiedRuleParser for NestedRuleParser<'a, 'b> {
-    type Prelude = Vec<Selector>;
+    type Prelude = StyleRulePrelude;
     type QualifiedRule = CSSRule;
 
-    fn parse_prelude(&self, input: &mut Parser) -> Result<Vec<Selector>, ()> {
-        parse_selector_list(&self.context.selector_context, input)
+    fn parse_prelude(&self, input: &mut Parser) -> Result<StyleRulePrelude, ()> {
+        let start = input.position();
+        let line = input.source_location(start).line;
+        let selectors = try!(parse_selector_list(&self.context.selector_context, input));
+        let end = input.position();
+        Ok((selectors, input.slice(start..end).trim().to_owned(), line))
     }
 
-    fn parse_block(&self, prelude: Vec<Selector>, input: &mut Parser) -> Result<CSSRule, ()> {
+    fn parse_block(&self, prelude: StyleRulePrelude, input: &mut Parser) -> Result<CSSRule, ()> {
+        let (selectors, selector_text, line) = prelude;
         Ok(CSSRule::Style(StyleRule {
-            selectors: prelude,
-            declarations: parse_property_declaration_list(self.context, input)
+            selectors: selectors,
+            declarations: parse_property_declaration_list(self.context, input),
+            selector_text: selector_text,
+            line: line,
         }))
     }
 }
@@ -12,7 +12,8 @@ macro_rules! define_css_keyword_enum {
     };
     ($name: ident: $( $css: expr => $variant: ident ),+) => {
         #[allow(non_camel_case_types)]
-        #[derive(Clone, Eq, PartialEq, Copy, Hash, RustcEncodable, Debug, HeapSizeOf)]
+        #[derive(Clone, Eq, PartialEq, Copy, Hash, Debug, HeapSizeOf)]
+        #[derive(RustcEncodable, RustcDecodable)]
         pub enum $name {
             $( $variant ),+
         }
@@ -43,7 +44,8 @@ macro_rules! define_numbered_css_keyword_enum {
     };
     ($name: ident: $( $css: expr => $variant: ident = $value: expr ),+) => {
         #[allow(non_camel_case_types)]
-        #[derive(Clone, Eq, PartialEq, PartialOrd, Ord, Copy, RustcEncodable, Debug, HeapSizeOf)]
+        #[derive(Clone, Eq, PartialEq, PartialOrd, Ord, Copy, Debug, HeapSizeOf)]
+        #[derive(RustcEncodable, RustcDecodable)]
         pub enum $name {
             $( $variant = $value ),+
         }
@@ -645,7 +647,7 @@ pub mod specified {
         }
     }
 
-    #[derive(Clone, PartialEq, PartialOrd, Copy, Debug, HeapSizeOf)]
+    #[derive(Clone, PartialEq, PartialOrd, Copy, Debug, HeapSizeOf, RustcEncodable, RustcDecodable)]
     pub struct Angle(pub CSSFloat);
 
     impl ToCss for Angle {
@@ -10,6 +10,7 @@ use properties::longhands;
 use stylesheets::Origin;
 use util::geometry::{Au, PagePx, ViewportPx};
 use values::specified::{AllowedNumericType, Length, LengthOrPercentageOrAuto};
+use values::specified::ViewportPercentageLength;
 
 use std::ascii::AsciiExt;
 use std::collections::hash_map::{Entry, HashMap};
@@ -220,6 +221,90 @@ impl ViewportRule {
 
         Ok(ViewportRule { declarations: valid_declarations.iter().cascade() })
     }
+
+    /// Translates the content of a `<meta name=viewport>` element into the
+    /// equivalent @viewport descriptors.
+    /// See http://dev.w3.org/csswg/css-device-adapt/#translate-meta-to-at-viewport
+    pub fn from_meta(content: &str) -> Option<ViewportRule> {
+        fn parse_length(value: &str,
+                        device_keyword: &str,
+                        unit: fn(f32) -> ViewportPercentageLength)
+                        -> Option<LengthOrPercentageOrAuto> {
+            if value.eq_ignore_ascii_case(device_keyword) {
+                return Some(LengthOrPercentageOrAuto::Length(
+                    Length::ViewportPercentage(unit(100.))))
+            }
+            value.parse::<f32>().ok().map(|length| {
+                LengthOrPercentageOrAuto::Length(Length::from_px(length.max(1.).min(10000.)))
+            })
+        }
+
+        fn parse_zoom(value: &str) -> Option<Zoom> {
+            value.parse::<f32>().ok().map(|zoom| Zoom::Number(zoom.max(0.1).min(10.)))
+        }
+
+        let mut declarations = vec![];
+        {
+            let mut push = |descriptor| {
+                declarations.push(
+                    ViewportDescriptorDeclaration::new(Origin::Author, descriptor, false))
+            };
+
+            let properties = content.split(|c| c == ',' || c == ';')
+                                    .filter_map(|property| {
+                                        let mut parts = property.splitn(2, '=');
+                                        match (parts.next(), parts.next()) {
+                                            (Some(name), Some(value)) =>
+                                                Some((name.trim(), value.trim())),
+                                            _ => None
+                                        }
+                                    });
+            for (name, value) in properties {
+                match name {
+                    n if n.eq_ignore_ascii_case("width") => {
+                        if let Some(width) =
+                                parse_length(value, "device-width", ViewportPercentageLength::Vw) {
+                            push(ViewportDescriptor::MinWidth(width));
+                            push(ViewportDescriptor::MaxWidth(width));
+                        }
+                    }
+                    n if n.eq_ignore_ascii_case("height") => {
+                        if let Some(height) =
+                                parse_length(value, "device-height", ViewportPercentageLength::Vh) {
+                            push(ViewportDescriptor::MinHeight(height));
+                            push(ViewportDescriptor::MaxHeight(height));
+                        }
+                    }
+                    n if n.eq_ignore_ascii_case("initial-scale") => {
+                        if let Some(zoom) = parse_zoom(value) {
+                            push(ViewportDescriptor::Zoom(zoom));
+                        }
+                    }
+                    n if n.eq_ignore_ascii_case("minimum-scale") => {
+                        if let Some(zoom) = parse_zoom(value) {
+                            push(ViewportDescriptor::MinZoom(zoom));
+                        }
+                    }
+                    n if n.eq_ignore_ascii_case("maximum-scale") => {
+                        if let Some(zoom) = parse_zoom(value) {
+                            push(ViewportDescriptor::MaxZoom(zoom));
+                        }
+                    }
+                    n if n.eq_ignore_ascii_case("user-scalable") => {
+                        let fixed = value.eq_ignore_ascii_case("no") || value == "0";
+                        push(ViewportDescriptor::UserZoom(
+                            if fixed { UserZoom::Fixed } else { UserZoom::Zoom }));
+                    }
+                    _ => {}
+                }
+            }
+        }
+
+        if declarations.is_empty() {
+            return None
+        }
+        Some(ViewportRule { declarations: declarations.iter().cascade() })
+    }
 }
 
 pub trait ViewportRuleCascade: Iterator + Sized {
@@ -305,7 +390,7 @@ impl<'a, I> ViewportDescriptorDeclarationCascade for I
     }
 }
 
-#[derive(Debug, PartialEq)]
+#[derive(Debug, PartialEq, RustcEncodable, RustcDecodable)]
 pub struct ViewportConstraints {
     pub size: TypedSize2D<ViewportPx, f32>,
 
@@ -3,6 +3,7 @@
  * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
 
 use std::collections::HashMap;
+use std::collections::hash_map;
 use std::collections::hash_map::Entry::{Occupied, Vacant};
 use std::collections::hash_state::DefaultState;
 use rand::Rng;
@@ -52,6 +53,10 @@ impl<K, V> HashCache<K,V>
     pub fn evict_all(&mut self) {
         self.entries.clear();
     }
+
+    pub fn iter<'a>(&'a self) -> hash_map::Iter<'a, K, V> {
+        self.entries.iter()
+    }
 }
 
 pub struct LRUCache<K, V> {
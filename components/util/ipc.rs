@@ -0,0 +1,1063 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+//! Channels whose ends can be sent to other processes, so that content can be run in processes
+//! of its own, away from the user's data.
+//!
+//! On Linux, a channel is a pair of connected `SOCK_SEQPACKET` sockets. Messages are encoded
+//! with rustc-serialize in a compact binary format, and the ends of other channels that a
+//! message contains are passed along with it as file descriptors, so a process can only reach
+//! the channels it has been given. A receiver finds out when the last of its senders is closed,
+//! in whichever process that happens. Elsewhere, channels only work within a process.
+
+use rustc_serialize::{Decodable, Decoder, Encodable, Encoder};
+use std::borrow::ToOwned;
+use std::cell::RefCell;
+use std::io;
+use std::marker::PhantomData;
+use std::mem;
+use std::process::{Child, Command};
+use std::sync::mpsc::{Receiver, Sender};
+use task::spawn_named;
+
+/// The largest message that is accepted, in bytes.
+const MAX_MESSAGE_SIZE: usize = 256 << 20;
+
+/// The most channel ends that one message can carry.
+const MAX_HANDLES_PER_MESSAGE: usize = 64;
+
+/// The sending end of a channel. It can be cloned, and sent to other tasks or processes.
+pub struct IpcSender<T> {
+    os_sender: os::OsSender,
+    phantom: PhantomData<T>,
+}
+
+/// The receiving end of a channel. It can be sent to another task or process, but not cloned.
+pub struct IpcReceiver<T> {
+    os_receiver: os::OsReceiver,
+    phantom: PhantomData<T>,
+}
+
+/// Creates a channel.
+pub fn channel<T>() -> (IpcSender<T>, IpcReceiver<T>) where T: Encodable + Decodable {
+    let (os_sender, os_receiver) = match os::channel() {
+        Ok(ends) => ends,
+        Err(error) => panic!("couldn't create a channel: {}", error),
+    };
+    let ipc_sender = IpcSender {
+        os_sender: os_sender,
+        phantom: PhantomData,
+    };
+    let ipc_receiver = IpcReceiver {
+        os_receiver: os_receiver,
+        phantom: PhantomData,
+    };
+    (ipc_sender, ipc_receiver)
+}
+
+impl<T> IpcSender<T> where T: Encodable {
+    /// Sends a message. This fails once the receiver is gone, like sending on a `Sender` does.
+    pub fn send(&self, message: T) -> io::Result<()> {
+        let (data, handles) = try!(serialize(&message));
+        self.os_sender.send(data, handles)
+    }
+}
+
+impl<T> Clone for IpcSender<T> {
+    fn clone(&self) -> IpcSender<T> {
+        IpcSender {
+            os_sender: self.os_sender.clone(),
+            phantom: PhantomData,
+        }
+    }
+}
+
+impl<T> IpcReceiver<T> where T: Decodable {
+    /// Waits for a message. This fails once every sender is gone and the messages they sent
+    /// have been received, or if a message can't be decoded.
+    pub fn recv(&self) -> io::Result<T> {
+        let (data, handles) = try!(self.os_receiver.recv());
+        deserialize(&data, handles)
+    }
+}
+
+impl<T> IpcReceiver<T> {
+    /// Takes the receiver that the parent process handed to this one with
+    /// `spawn_with_receiver`, given the argument that it added to the command line.
+    pub fn from_inherited(argument: &str) -> io::Result<IpcReceiver<T>> {
+        let os_receiver = try!(os::OsReceiver::from_inherited(argument));
+        Ok(IpcReceiver {
+            os_receiver: os_receiver,
+            phantom: PhantomData,
+        })
+    }
+}
+
+/// Starts a child process, which can take `receiver` with `IpcReceiver::from_inherited`. The
+/// argument that it needs to do that is added to the end of `command`.
+pub fn spawn_with_receiver<T>(command: &mut Command, receiver: IpcReceiver<T>)
+                              -> io::Result<Child> {
+    os::spawn_with_receiver(command, receiver.os_receiver)
+}
+
+/// Forwards the messages that arrive on `receiver` to `sender`, on a task of its own, so that
+/// they can be waited for along with messages on other channels. It stops when either end is
+/// gone, or a message can't be decoded.
+pub fn route_to_sender<T>(receiver: IpcReceiver<T>, sender: Sender<T>)
+                          where T: Decodable + Send + 'static {
+    spawn_named("IpcRouter".to_owned(), move || {
+        while let Ok(message) = receiver.recv() {
+            if sender.send(message).is_err() {
+                break
+            }
+        }
+    });
+}
+
+/// Forwards the messages that arrive on `receiver` to `sender`, on a task of its own, so that
+/// tasks that send on a `Sender` can reach another process. It stops when either end is gone.
+pub fn route_to_ipc_sender<T>(receiver: Receiver<T>, sender: IpcSender<T>)
+                              where T: Encodable + Send + 'static {
+    spawn_named("IpcRouter".to_owned(), move || {
+        while let Ok(message) = receiver.recv() {
+            if sender.send(message).is_err() {
+                break
+            }
+        }
+    });
+}
+
+// The ends of channels are encoded as the index of their handle among those that travel with the
+// message, which is all that the `Encoder` and `Decoder` pass along. The handles are collected,
+// and handed out again, through these lists while a message is being encoded or decoded.
+thread_local!(static OUTGOING_HANDLES: RefCell<Option<Vec<os::OutgoingHandle>>> =
+                  RefCell::new(None));
+thread_local!(static INCOMING_HANDLES: RefCell<Option<Vec<Option<os::IncomingHandle>>>> =
+                  RefCell::new(None));
+
+fn add_outgoing_handle(handle: os::OutgoingHandle) -> u32 {
+    OUTGOING_HANDLES.with(|handles| {
+        let mut handles = handles.borrow_mut();
+        let handles = handles.as_mut().expect("channels can only be encoded to be sent");
+        handles.push(handle);
+        (handles.len() - 1) as u32
+    })
+}
+
+fn take_incoming_handle(index: u32) -> Option<os::IncomingHandle> {
+    INCOMING_HANDLES.with(|handles| {
+        handles.borrow_mut().as_mut().and_then(|handles| {
+            handles.get_mut(index as usize).and_then(|handle| handle.take())
+        })
+    })
+}
+
+fn serialize<T>(message: &T) -> io::Result<(Vec<u8>, Vec<os::OutgoingHandle>)>
+                where T: Encodable {
+    OUTGOING_HANDLES.with(|handles| *handles.borrow_mut() = Some(vec!()));
+    let mut serializer = Serializer {
+        data: vec!(),
+    };
+    let result = message.encode(&mut serializer);
+    let handles = OUTGOING_HANDLES.with(|handles| handles.borrow_mut().take()).unwrap();
+    match result {
+        Ok(()) if handles.len() <= MAX_HANDLES_PER_MESSAGE => Ok((serializer.data, handles)),
+        Ok(()) => Err(io::Error::new(io::ErrorKind::InvalidInput,
+                                     "the message holds too many channels")),
+        Err(()) => Err(io::Error::new(io::ErrorKind::InvalidInput,
+                                      "the message can't be encoded")),
+    }
+}
+
+fn deserialize<T>(data: &[u8], handles: Vec<os::IncomingHandle>) -> io::Result<T>
+                  where T: Decodable {
+    let handles: Vec<_> = handles.into_iter().map(Some).collect();
+    INCOMING_HANDLES.with(|incoming_handles| *incoming_handles.borrow_mut() = Some(handles));
+    let mut deserializer = Deserializer {
+        data: data,
+        position: 0,
+    };
+    let result: Result<T, String> = Decodable::decode(&mut deserializer);
+    // Handles that the message didn't use are closed here.
+    INCOMING_HANDLES.with(|handles| handles.borrow_mut().take());
+    match result {
+        Ok(message) if deserializer.position == data.len() => Ok(message),
+        Ok(_) => Err(io::Error::new(io::ErrorKind::InvalidData,
+                                    "the message is longer than it should be")),
+        Err(error) => Err(io::Error::new(io::ErrorKind::InvalidData, error)),
+    }
+}
+
+impl<T> Encodable for IpcSender<T> {
+    fn encode<S: Encoder>(&self, encoder: &mut S) -> Result<(), S::Error> {
+        encoder.emit_u32(add_outgoing_handle(self.os_sender.handle()))
+    }
+}
+
+impl<T> Decodable for IpcSender<T> {
+    fn decode<D: Decoder>(decoder: &mut D) -> Result<IpcSender<T>, D::Error> {
+        let index = try!(decoder.read_u32());
+        match take_incoming_handle(index).and_then(os::OsSender::from_handle) {
+            Some(os_sender) => {
+                Ok(IpcSender {
+                    os_sender: os_sender,
+                    phantom: PhantomData,
+                })
+            }
+            None => Err(decoder.error("the message names a sender that didn't come with it")),
+        }
+    }
+}
+
+impl<T> Encodable for IpcReceiver<T> {
+    fn encode<S: Encoder>(&self, encoder: &mut S) -> Result<(), S::Error> {
+        encoder.emit_u32(add_outgoing_handle(self.os_receiver.handle()))
+    }
+}
+
+impl<T> Decodable for IpcReceiver<T> {
+    fn decode<D: Decoder>(decoder: &mut D) -> Result<IpcReceiver<T>, D::Error> {
+        let index = try!(decoder.read_u32());
+        match take_incoming_handle(index).and_then(os::OsReceiver::from_handle) {
+            Some(os_receiver) => {
+                Ok(IpcReceiver {
+                    os_receiver: os_receiver,
+                    phantom: PhantomData,
+                })
+            }
+            None => Err(decoder.error("the message names a receiver that didn't come with it")),
+        }
+    }
+}
+
+/// Encodes messages in a compact binary format: numbers are little-endian, and strings,
+/// sequences and maps are preceded by their length.
+struct Serializer {
+    data: Vec<u8>,
+}
+
+impl Serializer {
+    fn write_uint(&mut self, value: u64, size: usize) -> Result<(), ()> {
+        for index in 0..size {
+            self.data.push((value >> (index * 8)) as u8);
+        }
+        Ok(())
+    }
+}
+
+impl Encoder for Serializer {
+    type Error = ();
+
+    fn emit_nil(&mut self) -> Result<(), ()> { Ok(()) }
+    fn emit_usize(&mut self, v: usize) -> Result<(), ()> { self.write_uint(v as u64, 8) }
+    fn emit_u64(&mut self, v: u64) -> Result<(), ()> { self.write_uint(v, 8) }
+    fn emit_u32(&mut self, v: u32) -> Result<(), ()> { self.write_uint(v as u64, 4) }
+    fn emit_u16(&mut self, v: u16) -> Result<(), ()> { self.write_uint(v as u64, 2) }
+    fn emit_u8(&mut self, v: u8) -> Result<(), ()> { self.write_uint(v as u64, 1) }
+    fn emit_isize(&mut self, v: isize) -> Result<(), ()> { self.write_uint(v as u64, 8) }
+    fn emit_i64(&mut self, v: i64) -> Result<(), ()> { self.write_uint(v as u64, 8) }
+    fn emit_i32(&mut self, v: i32) -> Result<(), ()> { self.write_uint(v as u64, 4) }
+    fn emit_i16(&mut self, v: i16) -> Result<(), ()> { self.write_uint(v as u64, 2) }
+    fn emit_i8(&mut self, v: i8) -> Result<(), ()> { self.write_uint(v as u64, 1) }
+    fn emit_bool(&mut self, v: bool) -> Result<(), ()> { self.write_uint(v as u64, 1) }
+
+    fn emit_f64(&mut self, v: f64) -> Result<(), ()> {
+        self.write_uint(unsafe { mem::transmute(v) }, 8)
+    }
+
+    fn emit_f32(&mut self, v: f32) -> Result<(), ()> {
+        self.write_uint(unsafe { mem::transmute::<f32, u32>(v) } as u64, 4)
+    }
+
+    fn emit_char(&mut self, v: char) -> Result<(), ()> { self.write_uint(v as u64, 4) }
+
+    fn emit_str(&mut self, v: &str) -> Result<(), ()> {
+        try!(self.write_uint(v.len() as u64, 8));
+        self.data.extend(v.bytes());
+        Ok(())
+    }
+
+    fn emit_enum<F>(&mut self, _: &str, f: F) -> Result<(), ()>
+                    where F: FnOnce(&mut Serializer) -> Result<(), ()> {
+        f(self)
+    }
+
+    fn emit_enum_variant<F>(&mut self, _: &str, id: usize, _: usize, f: F) -> Result<(), ()>
+                            where F: FnOnce(&mut Serializer) -> Result<(), ()> {
+        try!(self.write_uint(id as u64, 4));
+        f(self)
+    }
+
+    fn emit_enum_variant_arg<F>(&mut self, _: usize, f: F) -> Result<(), ()>
+                                where F: FnOnce(&mut Serializer) -> Result<(), ()> {
+        f(self)
+    }
+
+    fn emit_enum_struct_variant<F>(&mut self, name: &str, id: usize, length: usize, f: F)
+                                   -> Result<(), ()>
+                                   where F: FnOnce(&mut Serializer) -> Result<(), ()> {
+        self.emit_enum_variant(name, id, length, f)
+    }
+
+    fn emit_enum_struct_variant_field<F>(&mut self, _: &str, _: usize, f: F) -> Result<(), ()>
+                                         where F: FnOnce(&mut Serializer) -> Result<(), ()> {
+        f(self)
+    }
+
+    fn emit_struct<F>(&mut self, _: &str, _: usize, f: F) -> Result<(), ()>
+                      where F: FnOnce(&mut Serializer) -> Result<(), ()> {
+        f(self)
+    }
+
+    fn emit_struct_field<F>(&mut self, _: &str, _: usize, f: F) -> Result<(), ()>
+                            where F: FnOnce(&mut Serializer) -> Result<(), ()> {
+        f(self)
+    }
+
+    fn emit_tuple<F>(&mut self, _: usize, f: F) -> Result<(), ()>
+                     where F: FnOnce(&mut Serializer) -> Result<(), ()> {
+        f(self)
+    }
+
+    fn emit_tuple_arg<F>(&mut self, _: usize, f: F) -> Result<(), ()>
+                         where F: FnOnce(&mut Serializer) -> Result<(), ()> {
+        f(self)
+    }
+
+    fn emit_tuple_struct<F>(&mut self, _: &str, _: usize, f: F) -> Result<(), ()>
+                            where F: FnOnce(&mut Serializer) -> Result<(), ()> {
+        f(self)
+    }
+
+    fn emit_tuple_struct_arg<F>(&mut self, _: usize, f: F) -> Result<(), ()>
+                                where F: FnOnce(&mut Serializer) -> Result<(), ()> {
+        f(self)
+    }
+
+    fn emit_option<F>(&mut self, f: F) -> Result<(), ()>
+                      where F: FnOnce(&mut Serializer) -> Result<(), ()> {
+        f(self)
+    }
+
+    fn emit_option_none(&mut self) -> Result<(), ()> { self.write_uint(0, 1) }
+
+    fn emit_option_some<F>(&mut self, f: F) -> Result<(), ()>
+                           where F: FnOnce(&mut Serializer) -> Result<(), ()> {
+        try!(self.write_uint(1, 1));
+        f(self)
+    }
+
+    fn emit_seq<F>(&mut self, length: usize, f: F) -> Result<(), ()>
+                   where F: FnOnce(&mut Serializer) -> Result<(), ()> {
+        try!(self.write_uint(length as u64, 8));
+        f(self)
+    }
+
+    fn emit_seq_elt<F>(&mut self, _: usize, f: F) -> Result<(), ()>
+                       where F: FnOnce(&mut Serializer) -> Result<(), ()> {
+        f(self)
+    }
+
+    fn emit_map<F>(&mut self, length: usize, f: F) -> Result<(), ()>
+                   where F: FnOnce(&mut Serializer) -> Result<(), ()> {
+        try!(self.write_uint(length as u64, 8));
+        f(self)
+    }
+
+    fn emit_map_elt_key<F>(&mut self, _: usize, f: F) -> Result<(), ()>
+                           where F: FnOnce(&mut Serializer) -> Result<(), ()> {
+        f(self)
+    }
+
+    fn emit_map_elt_val<F>(&mut self, _: usize, f: F) -> Result<(), ()>
+                           where F: FnOnce(&mut Serializer) -> Result<(), ()> {
+        f(self)
+    }
+}
+
+/// Decodes messages encoded by `Serializer`. Messages may come from processes that can't be
+/// trusted, so lengths are checked against the data before anything is allocated for them.
+struct Deserializer<'a> {
+    data: &'a [u8],
+    position: usize,
+}
+
+impl<'a> Deserializer<'a> {
+    fn read_bytes(&mut self, length: usize) -> Result<&'a [u8], String> {
+        if length > self.data.len() - self.position {
+            return Err("the message is shorter than it should be".to_owned())
+        }
+        let bytes = &self.data[self.position..self.position + length];
+        self.position += length;
+        Ok(bytes)
+    }
+
+    fn read_uint(&mut self, size: usize) -> Result<u64, String> {
+        let bytes = try!(self.read_bytes(size));
+        Ok(bytes.iter().rev().fold(0, |value, &byte| value << 8 | byte as u64))
+    }
+
+    /// Reads the length of a string, sequence or map, each of whose items takes up at least a
+    /// byte.
+    fn read_length(&mut self) -> Result<usize, String> {
+        let length = try!(self.read_uint(8));
+        if length > (self.data.len() - self.position) as u64 {
+            return Err("the message is shorter than it should be".to_owned())
+        }
+        Ok(length as usize)
+    }
+}
+
+impl<'a> Decoder for Deserializer<'a> {
+    type Error = String;
+
+    fn read_nil(&mut self) -> Result<(), String> { Ok(()) }
+    fn read_usize(&mut self) -> Result<usize, String> { Ok(try!(self.read_uint(8)) as usize) }
+    fn read_u64(&mut self) -> Result<u64, String> { self.read_uint(8) }
+    fn read_u32(&mut self) -> Result<u32, String> { Ok(try!(self.read_uint(4)) as u32) }
+    fn read_u16(&mut self) -> Result<u16, String> { Ok(try!(self.read_uint(2)) as u16) }
+    fn read_u8(&mut self) -> Result<u8, String> { Ok(try!(self.read_uint(1)) as u8) }
+    fn read_isize(&mut self) -> Result<isize, String> { Ok(try!(self.read_uint(8)) as isize) }
+    fn read_i64(&mut self) -> Result<i64, String> { Ok(try!(self.read_uint(8)) as i64) }
+    fn read_i32(&mut self) -> Result<i32, String> { Ok(try!(self.read_uint(4)) as i32) }
+    fn read_i16(&mut self) -> Result<i16, String> { Ok(try!(self.read_uint(2)) as i16) }
+    fn read_i8(&mut self) -> Result<i8, String> { Ok(try!(self.read_uint(1)) as i8) }
+
+    fn read_bool(&mut self) -> Result<bool, String> {
+        match try!(self.read_uint(1)) {
+            0 => Ok(false),
+            1 => Ok(true),
+            _ => Err("a boolean is neither true nor false".to_owned()),
+        }
+    }
+
+    fn read_f64(&mut self) -> Result<f64, String> {
+        Ok(unsafe { mem::transmute(try!(self.read_uint(8))) })
+    }
+
+    fn read_f32(&mut self) -> Result<f32, String> {
+        Ok(unsafe { mem::transmute(try!(self.read_uint(4)) as u32) })
+    }
+
+    fn read_char(&mut self) -> Result<char, String> {
+        let value = try!(self.read_uint(4)) as u32;
+        ::std::char::from_u32(value).ok_or_else(|| "a character is invalid".to_owned())
+    }
+
+    fn read_str(&mut self) -> Result<String, String> {
+        let length = try!(self.read_length());
+        let bytes = try!(self.read_bytes(length));
+        String::from_utf8(bytes.to_vec()).map_err(|_| "a string isn't UTF-8".to_owned())
+    }
+
+    fn read_enum<T, F>(&mut self, _: &str, f: F) -> Result<T, String>
+                       where F: FnOnce(&mut Deserializer<'a>) -> Result<T, String> {
+        f(self)
+    }
+
+    fn read_enum_variant<T, F>(&mut self, names: &[&str], mut f: F) -> Result<T, String>
+                               where F: FnMut(&mut Deserializer<'a>, usize)
+                                              -> Result<T, String> {
+        let id = try!(self.read_uint(4)) as usize;
+        if id >= names.len() {
+            return Err("an enumeration has no such variant".to_owned())
+        }
+        f(self, id)
+    }
+
+    fn read_enum_variant_arg<T, F>(&mut self, _: usize, f: F) -> Result<T, String>
+                                   where F: FnOnce(&mut Deserializer<'a>) -> Result<T, String> {
+        f(self)
+    }
+
+    fn read_enum_struct_variant<T, F>(&mut self, names: &[&str], f: F) -> Result<T, String>
+                                      where F: FnMut(&mut Deserializer<'a>, usize)
+                                                     -> Result<T, String> {
+        self.read_enum_variant(names, f)
+    }
+
+    fn read_enum_struct_variant_field<T, F>(&mut self, _: &str, _: usize, f: F)
+                                            -> Result<T, String>
+                                            where F: FnOnce(&mut Deserializer<'a>)
+                                                            -> Result<T, String> {
+        f(self)
+    }
+
+    fn read_struct<T, F>(&mut self, _: &str, _: usize, f: F) -> Result<T, String>
+                         where F: FnOnce(&mut Deserializer<'a>) -> Result<T, String> {
+        f(self)
+    }
+
+    fn read_struct_field<T, F>(&mut self, _: &str, _: usize, f: F) -> Result<T, String>
+                               where F: FnOnce(&mut Deserializer<'a>) -> Result<T, String> {
+        f(self)
+    }
+
+    fn read_tuple<T, F>(&mut self, _: usize, f: F) -> Result<T, String>
+                        where F: FnOnce(&mut Deserializer<'a>) -> Result<T, String> {
+        f(self)
+    }
+
+    fn read_tuple_arg<T, F>(&mut self, _: usize, f: F) -> Result<T, String>
+                            where F: FnOnce(&mut Deserializer<'a>) -> Result<T, String> {
+        f(self)
+    }
+
+    fn read_tuple_struct<T, F>(&mut self, _: &str, _: usize, f: F) -> Result<T, String>
+                               where F: FnOnce(&mut Deserializer<'a>) -> Result<T, String> {
+        f(self)
+    }
+
+    fn read_tuple_struct_arg<T, F>(&mut self, _: usize, f: F) -> Result<T, String>
+                                   where F: FnOnce(&mut Deserializer<'a>) -> Result<T, String> {
+        f(self)
+    }
+
+    fn read_option<T, F>(&mut self, mut f: F) -> Result<T, String>
+                         where F: FnMut(&mut Deserializer<'a>, bool) -> Result<T, String> {
+        let is_some = try!(self.read_bool());
+        f(self, is_some)
+    }
+
+    fn read_seq<T, F>(&mut self, f: F) -> Result<T, String>
+                      where F: FnOnce(&mut Deserializer<'a>, usize) -> Result<T, String> {
+        let length = try!(self.read_length());
+        f(self, length)
+    }
+
+    fn read_seq_elt<T, F>(&mut self, _: usize, f: F) -> Result<T, String>
+                          where F: FnOnce(&mut Deserializer<'a>) -> Result<T, String> {
+        f(self)
+    }
+
+    fn read_map<T, F>(&mut self, f: F) -> Result<T, String>
+                      where F: FnOnce(&mut Deserializer<'a>, usize) -> Result<T, String> {
+        let length = try!(self.read_length());
+        f(self, length)
+    }
+
+    fn read_map_elt_key<T, F>(&mut self, _: usize, f: F) -> Result<T, String>
+                              where F: FnOnce(&mut Deserializer<'a>) -> Result<T, String> {
+        f(self)
+    }
+
+    fn read_map_elt_val<T, F>(&mut self, _: usize, f: F) -> Result<T, String>
+                              where F: FnOnce(&mut Deserializer<'a>) -> Result<T, String> {
+        f(self)
+    }
+
+    fn error(&mut self, error: &str) -> String {
+        error.to_owned()
+    }
+}
+
+#[cfg(any(target_os = "linux", target_os = "android"))]
+mod os {
+    use libc::{c_char, c_int, c_long, c_uint, c_void, off_t, size_t, ssize_t};
+    use std::borrow::ToOwned;
+    use std::io;
+    use std::mem;
+    use std::process::{Child, Command};
+    use std::ptr;
+    use std::sync::Arc;
+    use super::{MAX_HANDLES_PER_MESSAGE, MAX_MESSAGE_SIZE};
+    use task::spawn_named;
+
+    const AF_UNIX: c_int = 1;
+    const SOCK_STREAM: c_int = 1;
+    const SOCK_SEQPACKET: c_int = 5;
+    const SOCK_CLOEXEC: c_int = 0o2000000;
+    const SOL_SOCKET: c_int = 1;
+    const SCM_RIGHTS: c_int = 1;
+    const MSG_CTRUNC: c_int = 0x8;
+    const MSG_TRUNC: c_int = 0x20;
+    const MSG_NOSIGNAL: c_int = 0x4000;
+    const MSG_CMSG_CLOEXEC: c_int = 0x40000000;
+    const F_GETFD: c_int = 1;
+    const F_SETFD: c_int = 2;
+    const FD_CLOEXEC: c_int = 1;
+    const EINTR: i32 = 4;
+
+    /// The largest packet that is sent on a channel's socket. Larger messages are written to a
+    /// stream socket of their own, which the packet carries.
+    const PACKET_SIZE: usize = 32 * 1024;
+
+    /// The first byte of a packet that holds a whole message.
+    const INLINE_MESSAGE: u8 = 0;
+    /// The first byte of a packet that holds the length of a message which is in an anonymous
+    /// file, the last of the file descriptors it carries.
+    const FILE_MESSAGE: u8 = 1;
+    /// The first byte of a packet that holds the length of a message which is written to a
+    /// stream socket, the last of the file descriptors it carries.
+    const STREAMED_MESSAGE: u8 = 2;
+
+    const MFD_CLOEXEC: c_uint = 1;
+
+    /// The number of the `memfd_create` system call, which the C library may not have a
+    /// function for.
+    #[cfg(target_arch = "x86_64")]
+    const MEMFD_CREATE: Option<c_long> = Some(319);
+    #[cfg(target_arch = "x86")]
+    const MEMFD_CREATE: Option<c_long> = Some(356);
+    #[cfg(target_arch = "arm")]
+    const MEMFD_CREATE: Option<c_long> = Some(385);
+    #[cfg(target_arch = "aarch64")]
+    const MEMFD_CREATE: Option<c_long> = Some(279);
+    #[cfg(not(any(target_arch = "x86_64", target_arch = "x86", target_arch = "arm",
+                  target_arch = "aarch64")))]
+    const MEMFD_CREATE: Option<c_long> = None;
+
+    #[repr(C)]
+    struct IoVec {
+        iov_base: *mut c_void,
+        iov_len: size_t,
+    }
+
+    #[repr(C)]
+    struct MsgHdr {
+        msg_name: *mut c_void,
+        msg_namelen: u32,
+        msg_iov: *mut IoVec,
+        msg_iovlen: size_t,
+        msg_control: *mut c_void,
+        msg_controllen: size_t,
+        msg_flags: c_int,
+    }
+
+    #[repr(C)]
+    struct CMsgHdr {
+        cmsg_len: size_t,
+        cmsg_level: c_int,
+        cmsg_type: c_int,
+    }
+
+    extern {
+        fn socketpair(domain: c_int, socket_type: c_int, protocol: c_int, fds: *mut c_int)
+                      -> c_int;
+        fn sendmsg(socket: c_int, message: *const MsgHdr, flags: c_int) -> ssize_t;
+        fn recvmsg(socket: c_int, message: *mut MsgHdr, flags: c_int) -> ssize_t;
+        fn read(fd: c_int, buffer: *mut c_void, count: size_t) -> ssize_t;
+        fn pread(fd: c_int, buffer: *mut c_void, count: size_t, offset: off_t) -> ssize_t;
+        fn write(fd: c_int, buffer: *const c_void, count: size_t) -> ssize_t;
+        fn close(fd: c_int) -> c_int;
+        fn fcntl(fd: c_int, command: c_int, ...) -> c_int;
+        fn syscall(number: c_long, ...) -> c_long;
+    }
+
+    /// A file descriptor, which is closed when this is dropped.
+    pub struct OwnedFd(c_int);
+
+    impl Drop for OwnedFd {
+        fn drop(&mut self) {
+            unsafe {
+                close(self.0);
+            }
+        }
+    }
+
+    pub type OutgoingHandle = c_int;
+    pub type IncomingHandle = OwnedFd;
+
+    #[derive(Clone)]
+    pub struct OsSender {
+        fd: Arc<OwnedFd>,
+    }
+
+    pub struct OsReceiver {
+        fd: OwnedFd,
+    }
+
+    pub fn channel() -> io::Result<(OsSender, OsReceiver)> {
+        let (sender_fd, receiver_fd) = try!(socket_pair(SOCK_SEQPACKET));
+        Ok((OsSender { fd: Arc::new(sender_fd) }, OsReceiver { fd: receiver_fd }))
+    }
+
+    fn socket_pair(socket_type: c_int) -> io::Result<(OwnedFd, OwnedFd)> {
+        let mut fds = [0; 2];
+        if unsafe { socketpair(AF_UNIX, socket_type | SOCK_CLOEXEC, 0, fds.as_mut_ptr()) } != 0 {
+            return Err(io::Error::last_os_error())
+        }
+        Ok((OwnedFd(fds[0]), OwnedFd(fds[1])))
+    }
+
+    fn interrupted(error: &io::Error) -> bool {
+        error.raw_os_error() == Some(EINTR)
+    }
+
+    fn align(length: usize) -> usize {
+        let alignment = mem::size_of::<size_t>();
+        (length + alignment - 1) & !(alignment - 1)
+    }
+
+    /// The room that a control message with `count` file descriptors takes up.
+    fn control_space(count: usize) -> usize {
+        align(mem::size_of::<CMsgHdr>()) + align(count * mem::size_of::<c_int>())
+    }
+
+    impl OsSender {
+        pub fn handle(&self) -> OutgoingHandle {
+            (self.fd).0
+        }
+
+        pub fn from_handle(fd: IncomingHandle) -> Option<OsSender> {
+            Some(OsSender {
+                fd: Arc::new(fd),
+            })
+        }
+
+        pub fn send(&self, data: Vec<u8>, mut fds: Vec<c_int>) -> io::Result<()> {
+            if data.len() < PACKET_SIZE {
+                let mut packet = Vec::with_capacity(data.len() + 1);
+                packet.push(INLINE_MESSAGE);
+                packet.extend(data.into_iter());
+                return self.send_packet(&packet, &fds)
+            }
+            if data.len() > MAX_MESSAGE_SIZE {
+                return Err(io::Error::new(io::ErrorKind::InvalidInput, "the message is too large"))
+            }
+
+            let mut packet = vec!(FILE_MESSAGE);
+            packet.extend((0..8).map(|index| (data.len() as u64 >> (index * 8)) as u8));
+            if let Ok(file) = anonymous_file(&data) {
+                fds.push(file.0);
+                return self.send_packet(&packet, &fds)
+            }
+
+            // Without `memfd_create`, the message goes through a stream, which the receiver only
+            // reads from once it gets to the packet. The message is written on a task of its
+            // own so that the sender doesn't have to wait for that, and is lost if this process
+            // exits first.
+            let (reader, writer) = try!(socket_pair(SOCK_STREAM));
+            packet[0] = STREAMED_MESSAGE;
+            fds.push(reader.0);
+            try!(self.send_packet(&packet, &fds));
+            drop(reader);
+            spawn_named("IpcMessageWriter".to_owned(), move || {
+                let _ = write_all(&writer, &data);
+            });
+            Ok(())
+        }
+
+        fn send_packet(&self, packet: &[u8], fds: &[c_int]) -> io::Result<()> {
+            let mut iov = IoVec {
+                iov_base: packet.as_ptr() as *mut c_void,
+                iov_len: packet.len() as size_t,
+            };
+            // A buffer of `size_t`s, so that the control message is aligned as it has to be.
+            let mut control: Vec<size_t> = if fds.is_empty() {
+                vec!()
+            } else {
+                vec![0; control_space(fds.len()) / mem::size_of::<size_t>()]
+            };
+            if !fds.is_empty() {
+                unsafe {
+                    let header = control.as_mut_ptr() as *mut CMsgHdr;
+                    (*header).cmsg_len =
+                        (align(mem::size_of::<CMsgHdr>()) + fds.len() * mem::size_of::<c_int>())
+                        as size_t;
+                    (*header).cmsg_level = SOL_SOCKET;
+                    (*header).cmsg_type = SCM_RIGHTS;
+                    let data = (header as *mut u8)
+                        .offset(align(mem::size_of::<CMsgHdr>()) as isize) as *mut c_int;
+                    ptr::copy_nonoverlapping(fds.as_ptr(), data, fds.len());
+                }
+            }
+            let message = MsgHdr {
+                msg_name: ptr::null_mut(),
+                msg_namelen: 0,
+                msg_iov: &mut iov,
+                msg_iovlen: 1,
+                msg_control: if fds.is_empty() {
+                    ptr::null_mut()
+                } else {
+                    control.as_mut_ptr() as *mut c_void
+                },
+                msg_controllen: (control.len() * mem::size_of::<size_t>()) as size_t,
+                msg_flags: 0,
+            };
+            loop {
+                if unsafe { sendmsg((self.fd).0, &message, MSG_NOSIGNAL) } >= 0 {
+                    return Ok(())
+                }
+                let error = io::Error::last_os_error();
+                if !interrupted(&error) {
+                    return Err(error)
+                }
+            }
+        }
+    }
+
+    impl OsReceiver {
+        pub fn handle(&self) -> OutgoingHandle {
+            self.fd.0
+        }
+
+        pub fn from_handle(fd: IncomingHandle) -> Option<OsReceiver> {
+            Some(OsReceiver {
+                fd: fd,
+            })
+        }
+
+        pub fn from_inherited(argument: &str) -> io::Result<OsReceiver> {
+            let fd: c_int = match argument.parse() {
+                Ok(fd) if fd > 2 => fd,
+                _ => return Err(io::Error::new(io::ErrorKind::InvalidInput,
+                                               "there is no channel to take")),
+            };
+            // The descriptor must not be handed on to the processes this one starts.
+            unsafe {
+                let flags = fcntl(fd, F_GETFD);
+                if flags < 0 || fcntl(fd, F_SETFD, flags | FD_CLOEXEC) < 0 {
+                    return Err(io::Error::last_os_error())
+                }
+            }
+            Ok(OsReceiver {
+                fd: OwnedFd(fd),
+            })
+        }
+
+        pub fn recv(&self) -> io::Result<(Vec<u8>, Vec<OwnedFd>)> {
+            let mut packet: Vec<u8> = vec![0; PACKET_SIZE + 1];
+            let mut control: Vec<size_t> =
+                vec![0; control_space(MAX_HANDLES_PER_MESSAGE + 1) / mem::size_of::<size_t>()];
+            let mut iov = IoVec {
+                iov_base: packet.as_mut_ptr() as *mut c_void,
+                iov_len: packet.len() as size_t,
+            };
+            let mut message = MsgHdr {
+                msg_name: ptr::null_mut(),
+                msg_namelen: 0,
+                msg_iov: &mut iov,
+                msg_iovlen: 1,
+                msg_control: control.as_mut_ptr() as *mut c_void,
+                msg_controllen: (control.len() * mem::size_of::<size_t>()) as size_t,
+                msg_flags: 0,
+            };
+            let mut length;
+            loop {
+                length = unsafe { recvmsg(self.fd.0, &mut message, MSG_CMSG_CLOEXEC) };
+                if length >= 0 {
+                    break
+                }
+                let error = io::Error::last_os_error();
+                if !interrupted(&error) {
+                    return Err(error)
+                }
+            }
+            let length = length as usize;
+
+            // The descriptors are taken first, so that they are closed whatever else happens.
+            let mut fds = vec!();
+            if message.msg_controllen as usize >= mem::size_of::<CMsgHdr>() {
+                unsafe {
+                    let header = control.as_ptr() as *const CMsgHdr;
+                    if (*header).cmsg_level == SOL_SOCKET && (*header).cmsg_type == SCM_RIGHTS {
+                        let data_length = (*header).cmsg_len as usize -
+                            align(mem::size_of::<CMsgHdr>());
+                        let data = (header as *const u8)
+                            .offset(align(mem::size_of::<CMsgHdr>()) as isize) as *const c_int;
+                        for index in 0..data_length / mem::size_of::<c_int>() {
+                            fds.push(OwnedFd(*data.offset(index as isize)));
+                        }
+                    }
+                }
+            }
+
+            if length == 0 {
+                return Err(io::Error::new(io::ErrorKind::BrokenPipe,
+                                          "every sender of the channel is gone"))
+            }
+            if message.msg_flags & (MSG_TRUNC | MSG_CTRUNC) != 0 {
+                return Err(io::Error::new(io::ErrorKind::InvalidData, "the message was cut short"))
+            }
+            packet.truncate(length);
+
+            match (packet[0], packet.len()) {
+                (INLINE_MESSAGE, _) => Ok((packet[1..].to_vec(), fds)),
+                (FILE_MESSAGE, 9) | (STREAMED_MESSAGE, 9) => {
+                    let length = packet[1..].iter().rev().fold(0, |length, &byte| {
+                        length << 8 | byte as u64
+                    });
+                    let stream = match fds.pop() {
+                        Some(stream) => stream,
+                        None => return Err(io::Error::new(io::ErrorKind::InvalidData,
+                                                          "the message's stream is missing")),
+                    };
+                    if length > MAX_MESSAGE_SIZE as u64 {
+                        return Err(io::Error::new(io::ErrorKind::InvalidData,
+                                                  "the message is too large"))
+                    }
+                    let positioned = packet[0] == FILE_MESSAGE;
+                    let data = try!(read_all(&stream, length as usize, positioned));
+                    Ok((data, fds))
+                }
+                _ => Err(io::Error::new(io::ErrorKind::InvalidData, "the packet is malformed")),
+            }
+        }
+    }
+
+    fn write_all(fd: &OwnedFd, data: &[u8]) -> io::Result<()> {
+        let mut written = 0;
+        while written < data.len() {
+            let result = unsafe {
+                write(fd.0, data[written..].as_ptr() as *const c_void,
+                      (data.len() - written) as size_t)
+            };
+            if result < 0 {
+                let error = io::Error::last_os_error();
+                if interrupted(&error) {
+                    continue
+                }
+                return Err(error)
+            }
+            written += result as usize;
+        }
+        Ok(())
+    }
+
+    /// Creates an anonymous file holding `data`.
+    fn anonymous_file(data: &[u8]) -> io::Result<OwnedFd> {
+        let number = match MEMFD_CREATE {
+            Some(number) => number,
+            None => return Err(io::Error::new(io::ErrorKind::Other, "there is no memfd_create")),
+        };
+        let name = b"servo-ipc\0";
+        let fd = unsafe { syscall(number, name.as_ptr() as *const c_char, MFD_CLOEXEC) };
+        if fd < 0 {
+            return Err(io::Error::last_os_error())
+        }
+        let file = OwnedFd(fd as c_int);
+        try!(write_all(&file, data));
+        Ok(file)
+    }
+
+    /// Reads `length` bytes from a stream or, if `positioned` is set, from the start of a file
+    /// whose offset the sender has moved.
+    fn read_all(fd: &OwnedFd, length: usize, positioned: bool) -> io::Result<Vec<u8>> {
+        let mut data: Vec<u8> = vec![0; length];
+        let mut read_length = 0;
+        while read_length < length {
+            let buffer = data[read_length..].as_mut_ptr() as *mut c_void;
+            let count = (length - read_length) as size_t;
+            let result = unsafe {
+                if positioned {
+                    pread(fd.0, buffer, count, read_length as off_t)
+                } else {
+                    read(fd.0, buffer, count)
+                }
+            };
+            if result < 0 {
+                let error = io::Error::last_os_error();
+                if interrupted(&error) {
+                    continue
+                }
+                return Err(error)
+            }
+            if result == 0 {
+                return Err(io::Error::new(io::ErrorKind::InvalidData,
+                                          "the message was cut short"))
+            }
+            read_length += result as usize;
+        }
+        Ok(data)
+    }
+
+    pub fn spawn_with_receiver(command: &mut Command, receiver: OsReceiver) -> io::Result<Child> {
+        // The child inherits the descriptor only if it isn't closed on exec. Other processes
+        // started meanwhile may inherit it too; they never read from it.
+        unsafe {
+            let flags = fcntl(receiver.fd.0, F_GETFD);
+            if flags < 0 || fcntl(receiver.fd.0, F_SETFD, flags & !FD_CLOEXEC) < 0 {
+                return Err(io::Error::last_os_error())
+            }
+        }
+        command.arg(receiver.fd.0.to_string()).spawn()
+    }
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "android")))]
+mod os {
+    use std::cell::RefCell;
+    use std::io;
+    use std::process::{Child, Command};
+    use std::sync::mpsc::{self, Receiver, Sender};
+
+    pub enum Handle {
+        Sender(OsSender),
+        Receiver(OsReceiver),
+    }
+
+    pub type OutgoingHandle = Handle;
+    pub type IncomingHandle = Handle;
+
+    type Message = (Vec<u8>, Vec<Handle>);
+
+    #[derive(Clone)]
+    pub struct OsSender {
+        sender: Sender<Message>,
+    }
+
+    pub struct OsReceiver {
+        receiver: RefCell<Option<Receiver<Message>>>,
+    }
+
+    pub fn channel() -> io::Result<(OsSender, OsReceiver)> {
+        let (sender, receiver) = mpsc::channel();
+        Ok((OsSender { sender: sender }, OsReceiver { receiver: RefCell::new(Some(receiver)) }))
+    }
+
+    fn closed() -> io::Error {
+        io::Error::new(io::ErrorKind::BrokenPipe, "the other end of the channel is gone")
+    }
+
+    impl OsSender {
+        pub fn handle(&self) -> OutgoingHandle {
+            Handle::Sender(self.clone())
+        }
+
+        pub fn from_handle(handle: IncomingHandle) -> Option<OsSender> {
+            match handle {
+                Handle::Sender(sender) => Some(sender),
+                Handle::Receiver(_) => None,
+            }
+        }
+
+        pub fn send(&self, data: Vec<u8>, handles: Vec<Handle>) -> io::Result<()> {
+            self.sender.send((data, handles)).map_err(|_| closed())
+        }
+    }
+
+    impl OsReceiver {
+        /// Hands the receiver over to the message it is being sent in.
+        pub fn handle(&self) -> OutgoingHandle {
+            Handle::Receiver(OsReceiver {
+                receiver: RefCell::new(self.receiver.borrow_mut().take()),
+            })
+        }
+
+        pub fn from_handle(handle: IncomingHandle) -> Option<OsReceiver> {
+            match handle {
+                Handle::Receiver(receiver) => Some(receiver),
+                Handle::Sender(_) => None,
+            }
+        }
+
+        pub fn from_inherited(_: &str) -> io::Result<OsReceiver> {
+            Err(io::Error::new(io::ErrorKind::Other,
+                               "channels can't reach other processes on this platform"))
+        }
+
+        pub fn recv(&self) -> io::Result<Message> {
+            match *self.receiver.borrow() {
+                Some(ref receiver) => receiver.recv().map_err(|_| closed()),
+                None => Err(closed()),
+            }
+        }
+    }
+
+    pub fn spawn_with_receiver(_: &mut Command, _: OsReceiver) -> io::Result<Child> {
+        Err(io::Error::new(io::ErrorKind::Other,
+                           "channels can't reach other processes on this platform"))
+    }
+}
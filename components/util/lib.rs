@@ -43,12 +43,16 @@ pub mod debug_utils;
 pub mod deque;
 pub mod linked_list;
 pub mod geometry;
+pub mod grapheme_tables;
+pub mod ipc;
 pub mod logical_geometry;
 pub mod mem;
 pub mod opts;
 pub mod persistent_list;
 pub mod range;
 pub mod resource_files;
+pub mod sandbox;
+pub mod spellcheck;
 pub mod str;
 pub mod task;
 pub mod tid;
@@ -8,6 +8,7 @@ use libc::{c_void, size_t};
 use std::cell::RefCell;
 use std::collections::LinkedList;
 use std::mem::transmute;
+use std::rc::Rc;
 use std::sync::Arc;
 
 
@@ -93,6 +94,12 @@ impl<T: HeapSizeOf> HeapSizeOf for Arc<T> {
     }
 }
 
+impl<T: HeapSizeOf> HeapSizeOf for Rc<T> {
+    fn heap_size_of_children(&self) -> usize {
+        (**self).heap_size_of_children()
+    }
+}
+
 impl<T: HeapSizeOf> HeapSizeOf for RefCell<T> {
     fn heap_size_of_children(&self) -> usize {
         self.borrow().heap_size_of_children()
@@ -23,7 +23,7 @@ use std::sync::atomic::{AtomicBool, Ordering, ATOMIC_BOOL_INIT};
 use url::{self, Url};
 
 /// Global flags for Servo, currently set on the command line.
-#[derive(Clone)]
+#[derive(Clone, RustcEncodable, RustcDecodable)]
 pub struct Opts {
     /// The initial URL to load.
     pub url: Option<Url>,
@@ -48,10 +48,18 @@ pub struct Opts {
     /// cause it to produce output on that interval (`-p`).
     pub time_profiler_period: Option<f64>,
 
+    /// The file to write the time profiler's events to as a trace for chrome://tracing, instead
+    /// of printing tables (`--profiler-trace-path`).
+    pub time_profiler_trace_path: Option<String>,
+
     /// `None` to disable the memory profiler or `Some` with an interval in seconds to enable it
     /// and cause it to produce output on that interval (`-m`).
     pub mem_profiler_period: Option<f64>,
 
+    /// True if the memory profiler should print its reports as JSON
+    /// (`--memory-profile-json`).
+    pub mem_profiler_json: bool,
+
     /// Enable experimental web features (`-e`).
     pub enable_experimental: bool,
 
@@ -68,6 +76,15 @@ pub struct Opts {
     /// won't be loaded
     pub userscripts: Option<String>,
 
+    /// Whether content processes are sandboxed, so that content can't read the disk
+    /// (`--sandbox`). This implies `multiprocess`. WebSockets and WebGL don't work in the sandbox
+    /// yet, since they still reach the network and the graphics driver from the script task.
+    pub sandbox: bool,
+
+    /// Whether script and layout run in a content process of their own for each top-level frame
+    /// tree, rather than on threads of this one (`-M`). Only supported on Linux and Android.
+    pub multiprocess: bool,
+
     pub output_file: Option<String>,
     pub headless: bool,
     pub hard_fail: bool,
@@ -150,10 +167,49 @@ pub struct Opts {
     /// Whether MIME sniffing should be used
     pub sniff_mime_types: bool,
 
+    /// A directory in which to keep data that outlives the session, such as cookies.
+    pub profile_dir: Option<String>,
+
+    /// The proxy to load http and https URLs through, as `host:port`.
+    pub proxy: Option<String>,
+
+    /// Whether images are drawn without converting the colors of their ICC profiles to sRGB,
+    /// which makes decoding them faster.
+    pub disable_color_management: bool,
+
     /// Whether Style Sharing Cache is used
     pub disable_share_style_cache: bool,
+
+    /// How many documents in the session history of a tab are kept alive, frozen, so that
+    /// going back to them doesn't reload them. `None` keeps them all.
+    pub frozen_pipelines: Option<usize>,
+
+    /// Print the page to a PDF file at this path once it has loaded, then exit.
+    pub print_pdf: Option<String>,
+
+    /// The name of the paper size to print on, such as "a4" or "letter".
+    pub paper_size: String,
+
+    /// A word list to spell check editable text against, with one word on each line. Spell
+    /// checking is off if this is `None` and the embedder does not set a dictionary itself.
+    pub dictionary: Option<String>,
+
+    /// A directory of hyphenation patterns for `hyphens: auto`, in files named like
+    /// `hyph-en-us.pat.txt` after the language they are for.
+    pub hyphenation_patterns: Option<String>,
+
+    /// The number of bytes of downloaded web fonts to keep loaded. The least recently used
+    /// fonts past this are unloaded, and downloaded again if they are needed.
+    pub web_font_cache_size: usize,
+
+    /// Whether text is laid out in fallback fonts while the web fonts it asks for download,
+    /// and again once they have, rather than layout waiting for them.
+    pub async_web_fonts: bool,
 }
 
+/// The default for `web_font_cache_size`: 32 MB.
+const DEFAULT_WEB_FONT_CACHE_SIZE: usize = 32 * 1024 * 1024;
+
 fn print_usage(app: &str, opts: &[getopts::OptGroup]) {
     let message = format!("Usage: {} [ options ... ] [URL]\n\twhere options include", app);
     println!("{}", getopts::usage(&message, opts));
@@ -204,6 +260,23 @@ static FORCE_CPU_PAINTING: bool = true;
 #[cfg(not(target_os="android"))]
 static FORCE_CPU_PAINTING: bool = false;
 
+// Content processes need channels that can be sent between processes, which `util::ipc` only
+// implements on Linux and Android.
+
+#[cfg(any(target_os="linux", target_os="android"))]
+static MULTIPROCESS_SUPPORTED: bool = true;
+
+#[cfg(not(any(target_os="linux", target_os="android")))]
+static MULTIPROCESS_SUPPORTED: bool = false;
+
+// `sandbox` only has a seccomp-bpf filter for x86-64 system calls.
+
+#[cfg(all(target_os="linux", target_arch="x86_64"))]
+static SANDBOX_SUPPORTED: bool = true;
+
+#[cfg(not(all(target_os="linux", target_arch="x86_64")))]
+static SANDBOX_SUPPORTED: bool = false;
+
 pub fn default_opts() -> Opts {
     Opts {
         url: Some(Url::parse("about:blank").unwrap()),
@@ -212,12 +285,16 @@ pub fn default_opts() -> Opts {
         tile_size: 512,
         device_pixels_per_px: None,
         time_profiler_period: None,
+        time_profiler_trace_path: None,
         mem_profiler_period: None,
+        mem_profiler_json: false,
         enable_experimental: false,
         layout_threads: 1,
         nonincremental_layout: false,
         nossl: false,
         userscripts: None,
+        sandbox: false,
+        multiprocess: false,
         output_file: None,
         headless: true,
         hard_fail: true,
@@ -242,7 +319,17 @@ pub fn default_opts() -> Opts {
         profile_tasks: false,
         resources_path: None,
         sniff_mime_types: false,
+        profile_dir: None,
+        proxy: None,
+        disable_color_management: false,
         disable_share_style_cache: false,
+        frozen_pipelines: None,
+        print_pdf: None,
+        paper_size: "a4".to_string(),
+        dictionary: None,
+        hyphenation_patterns: None,
+        web_font_cache_size: DEFAULT_WEB_FONT_CACHE_SIZE,
+        async_web_fonts: false,
     }
 }
 
@@ -259,15 +346,21 @@ pub fn from_cmdline_args(args: &[String]) {
         getopts::optflag("e", "experimental", "Enable experimental web features"),
         getopts::optopt("t", "threads", "Number of paint threads", "1"),
         getopts::optflagopt("p", "profile", "Profiler flag and output interval", "10"),
+        getopts::optopt("", "profiler-trace-path",
+                        "Write the profiler's events to a file to load in chrome://tracing",
+                        "trace.json"),
         getopts::optflagopt("m", "memory-profile", "Memory profiler flag and output interval", "10"),
+        getopts::optflag("", "memory-profile-json", "Print memory profiler reports as JSON"),
         getopts::optflag("x", "exit", "Exit after load flag"),
         getopts::optopt("y", "layout-threads", "Number of threads to use for layout", "1"),
         getopts::optflag("i", "nonincremental-layout", "Enable to turn off incremental layout."),
         getopts::optflag("", "no-ssl", "Disables ssl certificate verification."),
         getopts::optflagopt("", "userscripts",
                             "Uses userscripts in resources/user-agent-js, or a specified full path",""),
+        getopts::optflag("", "sandbox", "Keep content off the disk (implies -M)"),
+        getopts::optflag("M", "multiprocess", "Run each top-level frame tree in its own process"),
         getopts::optflag("z", "headless", "Headless mode"),
-        getopts::optflag("f", "hard-fail", "Exit on task failure instead of displaying about:failure"),
+        getopts::optflag("f", "hard-fail", "Exit on task failure instead of displaying an error page"),
         getopts::optflagopt("", "devtools", "Start remote devtools server on port", "6000"),
         getopts::optflagopt("", "webdriver", "Start remote WebDriver server on port", "7000"),
         getopts::optopt("", "resolution", "Set window resolution.", "800x600"),
@@ -277,6 +370,24 @@ pub fn from_cmdline_args(args: &[String]) {
         getopts::optflag("h", "help", "Print this message"),
         getopts::optopt("", "resources-path", "Path to find static resources", "/home/servo/resources"),
         getopts::optflag("", "sniff-mime-types" , "Enable MIME sniffing"),
+        getopts::optopt("", "profile-dir", "Directory to keep cookies and other persistent data in",
+                        "/home/servo/.servo"),
+        getopts::optopt("", "proxy", "Load http and https URLs through a proxy", "proxy.example.com:3128"),
+        getopts::optflag("", "disable-color-management",
+                         "Draw images without converting their colors to sRGB"),
+        getopts::optopt("", "frozen-pipelines",
+                        "Number of documents in session history to keep alive instead of reloading",
+                        "10"),
+        getopts::optopt("", "print-pdf", "Print the page to a PDF file and exit", "page.pdf"),
+        getopts::optopt("", "paper-size", "Paper size to print on: a4 or letter", "a4"),
+        getopts::optopt("", "dictionary", "Word list to spell check editable text against",
+                        "/usr/share/dict/words"),
+        getopts::optopt("", "hyphenation-patterns",
+                        "Directory of hyphenation patterns for hyphens: auto", "patterns"),
+        getopts::optopt("", "web-font-cache-size",
+                        "Megabytes of downloaded web fonts to keep loaded", "32"),
+        getopts::optflag("", "async-web-fonts",
+                         "Lay out text in fallback fonts while web fonts download"),
     );
 
     let opt_match = match getopts::getopts(args, &opts) {
@@ -341,6 +452,13 @@ pub fn from_cmdline_args(args: &[String]) {
     let mem_profiler_period = opt_match.opt_default("m", "5").map(|period| {
         period.parse().unwrap()
     });
+    // JSON reports are printed on the memory profiler's interval, which they turn on if needed.
+    let mem_profiler_json = opt_match.opt_present("memory-profile-json");
+    let mem_profiler_period = if mem_profiler_json {
+        mem_profiler_period.or(Some(5.))
+    } else {
+        mem_profiler_period
+    };
 
     let gpu_painting = !FORCE_CPU_PAINTING && opt_match.opt_present("g");
 
@@ -378,6 +496,16 @@ pub fn from_cmdline_args(args: &[String]) {
         }
     };
 
+    let sandbox = opt_match.opt_present("sandbox");
+    if sandbox && !SANDBOX_SUPPORTED {
+        args_fail("Sandboxing content is only supported on x86-64 Linux")
+    }
+
+    let multiprocess = opt_match.opt_present("M") || sandbox;
+    if multiprocess && !MULTIPROCESS_SUPPORTED {
+        args_fail("Running content in separate processes is only supported on Linux and Android")
+    }
+
     let opts = Opts {
         url: Some(url),
         paint_threads: paint_threads,
@@ -385,12 +513,16 @@ pub fn from_cmdline_args(args: &[String]) {
         tile_size: tile_size,
         device_pixels_per_px: device_pixels_per_px,
         time_profiler_period: time_profiler_period,
+        time_profiler_trace_path: opt_match.opt_str("profiler-trace-path"),
         mem_profiler_period: mem_profiler_period,
+        mem_profiler_json: mem_profiler_json,
         enable_experimental: opt_match.opt_present("e"),
         layout_threads: layout_threads,
         nonincremental_layout: nonincremental_layout,
         nossl: nossl,
         userscripts: opt_match.opt_default("userscripts", ""),
+        sandbox: sandbox,
+        multiprocess: multiprocess,
         output_file: opt_match.opt_str("o"),
         headless: opt_match.opt_present("z"),
         hard_fail: opt_match.opt_present("f"),
@@ -415,7 +547,18 @@ pub fn from_cmdline_args(args: &[String]) {
         validate_display_list_geometry: debug_options.contains(&"validate-display-list-geometry"),
         resources_path: opt_match.opt_str("resources-path"),
         sniff_mime_types: opt_match.opt_present("sniff-mime-types"),
+        profile_dir: opt_match.opt_str("profile-dir"),
+        proxy: opt_match.opt_str("proxy"),
+        disable_color_management: opt_match.opt_present("disable-color-management"),
         disable_share_style_cache: debug_options.contains(&"disable-share-style-cache"),
+        frozen_pipelines: opt_match.opt_str("frozen-pipelines").map(|count| count.parse().unwrap()),
+        print_pdf: opt_match.opt_str("print-pdf"),
+        paper_size: opt_match.opt_str("paper-size").unwrap_or("a4".to_string()),
+        dictionary: opt_match.opt_str("dictionary"),
+        hyphenation_patterns: opt_match.opt_str("hyphenation-patterns"),
+        web_font_cache_size: opt_match.opt_str("web-font-cache-size").map_or(
+            DEFAULT_WEB_FONT_CACHE_SIZE, |size| size.parse::<usize>().unwrap() * 1024 * 1024),
+        async_web_fonts: opt_match.opt_present("async-web-fonts"),
     };
 
     set(opts);
@@ -2,10 +2,18 @@
  * License, v. 2.0. If a copy of the MPL was not distributed with this
  * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
 
+use std::borrow::ToOwned;
+use std::collections::HashMap;
 use std::fs::File;
 use std::io::{self, Read};
+use std::mem;
 use std::path::PathBuf;
 
+/// The resource files that another process read for this one, keyed by their path relative to
+/// the resources directory.
+static mut PRELOADED_FILES: *const HashMap<String, Vec<u8>> =
+    0 as *const HashMap<String, Vec<u8>>;
+
 #[cfg(target_os = "android")]
 pub fn resources_dir_path() -> PathBuf {
     PathBuf::from("/sdcard/servo/")
@@ -44,7 +52,37 @@ pub fn resources_dir_path() -> PathBuf {
     }
 }
 
+/// Reads the resource files at the given paths, relative to the resources directory, for a
+/// process that can't read them itself.
+pub fn read_resource_files(relative_paths: &[&str]) -> io::Result<HashMap<String, Vec<u8>>> {
+    let mut files = HashMap::new();
+    for &relative_path in relative_paths {
+        let data = try!(read_resource_file(&[relative_path]));
+        files.insert(relative_path.to_owned(), data);
+    }
+    Ok(files)
+}
+
+/// Makes `read_resource_file` return the given files, which were read by another process with
+/// `read_resource_files`, instead of reading the disk. This must be called at most once, before
+/// any other task starts.
+pub fn set_preloaded_files(files: HashMap<String, Vec<u8>>) {
+    unsafe {
+        assert!(PRELOADED_FILES.is_null());
+        PRELOADED_FILES = mem::transmute(box files);
+    }
+}
+
 pub fn read_resource_file(relative_path_components: &[&str]) -> io::Result<Vec<u8>> {
+    unsafe {
+        if !PRELOADED_FILES.is_null() {
+            let relative_path = relative_path_components.connect("/");
+            if let Some(data) = (*PRELOADED_FILES).get(&relative_path) {
+                return Ok(data.clone())
+            }
+        }
+    }
+
     let mut path = resources_dir_path();
     for component in relative_path_components {
         path.push(component);
@@ -0,0 +1,178 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+//! Sandboxing for content processes, so that a compromised script or layout task can't read the
+//! user's disk. The files content needs are read for it by the parent process: fonts by the font
+//! cache task, fetched resources by the resource task and the user agent style sheets when the
+//! content process starts. The game controllers are polled by the constellation.
+//!
+//! On Linux, a seccomp-bpf filter stops the thread that enters the sandbox, and every thread it
+//! starts afterwards, from opening or examining files, running programs and tracing other
+//! processes; those system calls fail with `EPERM`. A content process enters it before it starts
+//! any threads, so the filter covers the whole process.
+
+use std::io;
+
+/// Enters the sandbox on the current thread and the threads it starts afterwards. It can't be
+/// left again.
+pub fn enter() -> io::Result<()> {
+    platform::enter()
+}
+
+#[cfg(all(target_os = "linux", target_arch = "x86_64"))]
+mod platform {
+    use libc::{c_int, c_ulong};
+    use std::io;
+
+    const PR_SET_SECCOMP: c_int = 22;
+    const PR_SET_NO_NEW_PRIVS: c_int = 38;
+    const SECCOMP_MODE_FILTER: c_ulong = 2;
+
+    const SECCOMP_RET_KILL: u32 = 0x00000000;
+    const SECCOMP_RET_ERRNO: u32 = 0x00050000;
+    const SECCOMP_RET_ALLOW: u32 = 0x7fff0000;
+    const EPERM: u32 = 1;
+
+    const BPF_LD_W_ABS: u16 = 0x20;
+    const BPF_JMP_JEQ_K: u16 = 0x15;
+    const BPF_JMP_JGE_K: u16 = 0x35;
+    const BPF_RET_K: u16 = 0x06;
+
+    /// The offsets of the fields of `struct seccomp_data`.
+    const SYSCALL_NUMBER_OFFSET: u32 = 0;
+    const ARCH_OFFSET: u32 = 4;
+
+    const AUDIT_ARCH_X86_64: u32 = 0xc000003e;
+    /// The bit set in the numbers of x32 system calls, which the filter would otherwise have to
+    /// list separately.
+    const X32_SYSCALL_BIT: u32 = 0x40000000;
+
+    /// The system calls that open, create, examine or change files, run programs or inspect
+    /// other processes.
+    static DENIED_SYSCALLS: &'static [u32] = &[
+        2,      // open
+        4,      // stat
+        6,      // lstat
+        21,     // access
+        59,     // execve
+        76,     // truncate
+        82,     // rename
+        83,     // mkdir
+        84,     // rmdir
+        85,     // creat
+        86,     // link
+        87,     // unlink
+        88,     // symlink
+        89,     // readlink
+        90,     // chmod
+        92,     // chown
+        94,     // lchown
+        101,    // ptrace
+        133,    // mknod
+        257,    // openat
+        258,    // mkdirat
+        259,    // mknodat
+        260,    // fchownat
+        262,    // newfstatat
+        263,    // unlinkat
+        264,    // renameat
+        265,    // linkat
+        266,    // symlinkat
+        267,    // readlinkat
+        268,    // fchmodat
+        269,    // faccessat
+        303,    // name_to_handle_at
+        304,    // open_by_handle_at
+        316,    // renameat2
+        322,    // execveat
+        332,    // statx
+        437,    // openat2
+        439,    // faccessat2
+    ];
+
+    /// `struct sock_filter` from `linux/filter.h`.
+    #[repr(C)]
+    struct SockFilter {
+        code: u16,
+        jt: u8,
+        jf: u8,
+        k: u32,
+    }
+
+    /// `struct sock_fprog` from `linux/filter.h`.
+    #[repr(C)]
+    struct SockFprog {
+        len: u16,
+        filter: *const SockFilter,
+    }
+
+    extern {
+        fn prctl(option: c_int, ...) -> c_int;
+    }
+
+    fn statement(code: u16, k: u32) -> SockFilter {
+        SockFilter {
+            code: code,
+            jt: 0,
+            jf: 0,
+            k: k,
+        }
+    }
+
+    fn jump(code: u16, k: u32, jt: u8, jf: u8) -> SockFilter {
+        SockFilter {
+            code: code,
+            jt: jt,
+            jf: jf,
+            k: k,
+        }
+    }
+
+    fn filter() -> Vec<SockFilter> {
+        let mut filter = vec!(
+            // System calls of other architectures have other numbers, so they're refused.
+            statement(BPF_LD_W_ABS, ARCH_OFFSET),
+            jump(BPF_JMP_JEQ_K, AUDIT_ARCH_X86_64, 1, 0),
+            statement(BPF_RET_K, SECCOMP_RET_KILL),
+            statement(BPF_LD_W_ABS, SYSCALL_NUMBER_OFFSET),
+            jump(BPF_JMP_JGE_K, X32_SYSCALL_BIT, 0, 1),
+            statement(BPF_RET_K, SECCOMP_RET_ERRNO | EPERM),
+        );
+        for &syscall in DENIED_SYSCALLS {
+            filter.push(jump(BPF_JMP_JEQ_K, syscall, 0, 1));
+            filter.push(statement(BPF_RET_K, SECCOMP_RET_ERRNO | EPERM));
+        }
+        filter.push(statement(BPF_RET_K, SECCOMP_RET_ALLOW));
+        filter
+    }
+
+    pub fn enter() -> io::Result<()> {
+        let filter = filter();
+        let program = SockFprog {
+            len: filter.len() as u16,
+            filter: filter.as_ptr(),
+        };
+        unsafe {
+            // Without this, only privileged processes may install filters.
+            if prctl(PR_SET_NO_NEW_PRIVS, 1 as c_ulong, 0 as c_ulong, 0 as c_ulong,
+                     0 as c_ulong) != 0 {
+                return Err(io::Error::last_os_error())
+            }
+            if prctl(PR_SET_SECCOMP, SECCOMP_MODE_FILTER, &program as *const SockFprog,
+                     0 as c_ulong, 0 as c_ulong) != 0 {
+                return Err(io::Error::last_os_error())
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(not(all(target_os = "linux", target_arch = "x86_64")))]
+mod platform {
+    use std::io;
+
+    pub fn enter() -> io::Result<()> {
+        Err(io::Error::new(io::ErrorKind::Other, "content can't be sandboxed on this platform"))
+    }
+}
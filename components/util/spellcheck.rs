@@ -0,0 +1,117 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+//! Spell checking for editable text.
+//!
+//! Words are looked up in a `Dictionary`, which can be a plain word list or a wrapper around a
+//! platform spell checker. The dictionary is set once at startup, like the command line options,
+//! and is shared by every layout task.
+
+use std::borrow::ToOwned;
+use std::collections::HashSet;
+use std::fs::File;
+use std::io::{self, BufRead, BufReader};
+use std::mem;
+use std::ops::Range;
+use std::path::Path;
+
+/// A source of correctly spelled words.
+pub trait Dictionary: Send + Sync {
+    /// Returns true if `word` is spelled correctly.
+    fn check(&self, word: &str) -> bool;
+}
+
+/// A dictionary backed by a list of words, such as `/usr/share/dict/words`.
+pub struct WordListDictionary {
+    words: HashSet<String>,
+}
+
+impl WordListDictionary {
+    pub fn new<I>(words: I) -> WordListDictionary where I: Iterator<Item=String> {
+        WordListDictionary {
+            words: words.collect(),
+        }
+    }
+
+    /// Reads a word list with one word on each line.
+    pub fn from_file(path: &Path) -> io::Result<WordListDictionary> {
+        let file = try!(File::open(path));
+        let mut words = HashSet::new();
+        for line in BufReader::new(file).lines() {
+            let line = try!(line);
+            let word = line.trim();
+            if !word.is_empty() {
+                words.insert(word.to_owned());
+            }
+        }
+        Ok(WordListDictionary {
+            words: words,
+        })
+    }
+}
+
+impl Dictionary for WordListDictionary {
+    /// Words are also accepted with a capitalized first letter or in capitals, so that words at
+    /// the start of sentences and in headings are not flagged.
+    fn check(&self, word: &str) -> bool {
+        self.words.contains(word) || self.words.contains(&word.to_lowercase())
+    }
+}
+
+static mut DICTIONARY: *mut Box<Dictionary> = 0 as *mut Box<Dictionary>;
+
+/// Sets the dictionary that editable text is checked against. This must be called at most once,
+/// before any layout task starts.
+pub fn set_dictionary(dictionary: Box<Dictionary>) {
+    unsafe {
+        assert!(DICTIONARY.is_null());
+        let box_dictionary = box dictionary;
+        DICTIONARY = mem::transmute(box_dictionary);
+    }
+}
+
+/// Returns the dictionary that editable text is checked against, or `None` if spell checking is
+/// off.
+pub fn dictionary<'a>() -> Option<&'a Dictionary> {
+    unsafe {
+        if DICTIONARY.is_null() {
+            return None
+        }
+        Some(&**DICTIONARY)
+    }
+}
+
+/// Returns the character ranges of the words in `text` that are not in `dictionary`.
+///
+/// A word is a run of letters, with apostrophes allowed inside it. Words that contain digits, such
+/// as `x86`, are not checked.
+pub fn misspelled_words(dictionary: &Dictionary, text: &str) -> Vec<Range<usize>> {
+    let mut misspelled = vec!();
+    let chars = text.chars().collect::<Vec<_>>();
+    let mut index = 0;
+    while index < chars.len() {
+        if !chars[index].is_alphanumeric() {
+            index += 1;
+            continue
+        }
+
+        let start = index;
+        while index < chars.len() && (chars[index].is_alphanumeric() || chars[index] == '\'') {
+            index += 1;
+        }
+        let mut end = index;
+        while chars[end - 1] == '\'' {
+            end -= 1;
+        }
+
+        if chars[start..end].iter().any(|c| c.is_numeric()) {
+            continue
+        }
+        let word = chars[start..end].iter().cloned().collect::<String>();
+        if !dictionary.check(&word) {
+            misspelled.push(start..end);
+        }
+    }
+    misspelled
+}
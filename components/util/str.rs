@@ -3,6 +3,8 @@
  * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
 
 use geometry::Au;
+use grapheme_tables::GraphemeClusterBreak::{Control, Extend, Prepend, SpacingMark, ZWJ};
+use grapheme_tables::grapheme_cluster_break;
 
 use cssparser::{self, RGBA, Color};
 
@@ -300,7 +302,7 @@ pub fn parse_legacy_color(mut input: &str) -> Result<RGBA,()> {
 }
 
 
-#[derive(Clone, Eq, PartialEq, Hash, Debug)]
+#[derive(Clone, Eq, PartialEq, Hash, Debug, RustcEncodable, RustcDecodable)]
 pub struct LowercaseString {
     inner: String,
 }
@@ -327,3 +329,204 @@ impl Deref for LowercaseString {
 pub unsafe fn c_str_to_string(s: *const c_char) -> String {
     from_utf8(CStr::from_ptr(s).to_bytes()).unwrap().to_owned()
 }
+
+/// Escapes the characters that are special in HTML text and attribute values.
+pub fn escape_html(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&#39;"),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+/// Returns true if `ch` is a combining mark or joiner, which is drawn with the font of the
+/// character it follows where that font has a glyph for it, and belongs to the same grapheme
+/// cluster as it.
+pub fn is_combining_char(ch: char) -> bool {
+    match grapheme_cluster_break(ch) {
+        Some(Extend) | Some(SpacingMark) | Some(ZWJ) => true,
+        Some(Control) | Some(Prepend) | None => false,
+    }
+}
+
+/// The parts of Hangul syllables, which are kept together in grapheme clusters.
+#[derive(Copy, Clone, PartialEq)]
+enum HangulSyllableType {
+    /// Leading consonant jamo.
+    L,
+    /// Vowel jamo.
+    V,
+    /// Trailing consonant jamo.
+    T,
+    /// Precomposed syllables of a leading consonant and a vowel.
+    LV,
+    /// Precomposed syllables of a leading consonant, a vowel and a trailing consonant.
+    LVT,
+}
+
+fn hangul_syllable_type(ch: char) -> Option<HangulSyllableType> {
+    match ch {
+        '\u{1100}'...'\u{115F}' | '\u{A960}'...'\u{A97C}' => Some(HangulSyllableType::L),
+        '\u{1160}'...'\u{11A7}' | '\u{D7B0}'...'\u{D7C6}' => Some(HangulSyllableType::V),
+        '\u{11A8}'...'\u{11FF}' | '\u{D7CB}'...'\u{D7FB}' => Some(HangulSyllableType::T),
+        '\u{AC00}'...'\u{D7A3}' if (ch as u32 - 0xAC00) % 28 == 0 => {
+            Some(HangulSyllableType::LV)
+        }
+        '\u{AC00}'...'\u{D7A3}' => Some(HangulSyllableType::LVT),
+        _ => None,
+    }
+}
+
+/// Returns true if `ch` is a regional indicator symbol, pairs of which are drawn as flags.
+pub fn is_regional_indicator(ch: char) -> bool {
+    ch >= '\u{1F1E6}' && ch <= '\u{1F1FF}'
+}
+
+/// Returns true if `ch` is a pictograph that can be joined into an emoji ZWJ sequence, such as
+/// the members of a family. This is an approximation of the `Extended_Pictographic` property.
+///
+/// http://www.unicode.org/reports/tr51/#Emoji_ZWJ_Sequences
+pub fn is_extended_pictographic(ch: char) -> bool {
+    match ch {
+        '\u{A9}' | '\u{AE}' | '\u{203C}' | '\u{2049}' | '\u{2122}' | '\u{2139}' |
+        '\u{2194}'...'\u{2199}' | '\u{21A9}'...'\u{21AA}' | '\u{231A}'...'\u{231B}' |
+        '\u{2328}' | '\u{23CF}' | '\u{23E9}'...'\u{23F3}' | '\u{23F8}'...'\u{23FA}' |
+        '\u{24C2}' | '\u{25AA}'...'\u{25AB}' | '\u{25B6}' | '\u{25C0}' |
+        '\u{25FB}'...'\u{25FE}' | '\u{2600}'...'\u{27BF}' | '\u{2934}'...'\u{2935}' |
+        '\u{2B05}'...'\u{2B07}' | '\u{2B1B}'...'\u{2B1C}' | '\u{2B50}' | '\u{2B55}' |
+        '\u{3030}' | '\u{303D}' | '\u{3297}' | '\u{3299}' |
+        '\u{1F000}'...'\u{1F0FF}' | '\u{1F10D}'...'\u{1F10F}' | '\u{1F12F}' |
+        '\u{1F16C}'...'\u{1F171}' | '\u{1F17E}'...'\u{1F17F}' | '\u{1F18E}' |
+        '\u{1F191}'...'\u{1F19A}' | '\u{1F1AD}'...'\u{1F1E5}' | '\u{1F201}'...'\u{1F20F}' |
+        '\u{1F21A}' | '\u{1F22F}' | '\u{1F232}'...'\u{1F23A}' | '\u{1F23C}'...'\u{1F23F}' |
+        '\u{1F249}'...'\u{1F3FA}' | '\u{1F400}'...'\u{1F53D}' | '\u{1F546}'...'\u{1F64F}' |
+        '\u{1F680}'...'\u{1F6FF}' | '\u{1F774}'...'\u{1F77F}' | '\u{1F7D5}'...'\u{1F7FF}' |
+        '\u{1F80C}'...'\u{1F80F}' | '\u{1F848}'...'\u{1F84F}' | '\u{1F85A}'...'\u{1F85F}' |
+        '\u{1F888}'...'\u{1F88F}' | '\u{1F8AE}'...'\u{1F8FF}' | '\u{1F90C}'...'\u{1F93A}' |
+        '\u{1F93C}'...'\u{1F945}' | '\u{1F947}'...'\u{1FAFF}' | '\u{1FC00}'...'\u{1FFFD}' => {
+            true
+        }
+        _ => false,
+    }
+}
+
+/// Returns true if a grapheme cluster boundary falls between `before` and `after`, following the
+/// rules of UAX #29 for extended grapheme clusters: CR LF, the jamo of Hangul syllables,
+/// characters and the marks, joiners, emoji modifiers and tags after them, prepended
+/// concatenation marks and what follows them, emoji ZWJ sequences, and pairs of regional
+/// indicators are kept together. `odd_regional_indicators` is whether an odd number of regional
+/// indicators, ending with `before`, comes before `after`, since each flag takes two of them.
+/// `GraphemeClusterBreaker` keeps count of them for text read in order.
+///
+/// http://www.unicode.org/reports/tr29/#Grapheme_Cluster_Boundaries
+pub fn is_grapheme_cluster_boundary(before: char, after: char, odd_regional_indicators: bool)
+                                    -> bool {
+    use self::HangulSyllableType::{L, V, T, LV, LVT};
+
+    if before == '\r' && after == '\n' {
+        return false
+    }
+    let is_control = |ch: char| {
+        ch == '\r' || ch == '\n' || grapheme_cluster_break(ch) == Some(Control)
+    };
+    if is_control(before) || is_control(after) {
+        return true
+    }
+    match grapheme_cluster_break(after) {
+        Some(Extend) | Some(SpacingMark) | Some(ZWJ) => return false,
+        Some(Control) | Some(Prepend) | None => {}
+    }
+    if grapheme_cluster_break(before) == Some(Prepend) {
+        return false
+    }
+    if before == '\u{200D}' && is_extended_pictographic(after) {
+        return false
+    }
+    match (hangul_syllable_type(before), hangul_syllable_type(after)) {
+        (Some(L), Some(L)) | (Some(L), Some(V)) | (Some(L), Some(LV)) | (Some(L), Some(LVT)) |
+        (Some(LV), Some(V)) | (Some(LV), Some(T)) | (Some(V), Some(V)) | (Some(V), Some(T)) |
+        (Some(LVT), Some(T)) | (Some(T), Some(T)) => false,
+        _ => {
+            !(odd_regional_indicators && is_regional_indicator(before) &&
+              is_regional_indicator(after))
+        }
+    }
+}
+
+/// Finds the grapheme cluster boundaries of text that is read one character at a time, counting
+/// the regional indicators before each character so that they're paired into flags.
+#[derive(Clone, Copy, Debug)]
+pub struct GraphemeClusterBreaker {
+    /// The character before the next one.
+    previous_character: Option<char>,
+    /// How many regional indicators end the text so far.
+    regional_indicators: usize,
+}
+
+impl GraphemeClusterBreaker {
+    pub fn new() -> GraphemeClusterBreaker {
+        GraphemeClusterBreaker {
+            previous_character: None,
+            regional_indicators: 0,
+        }
+    }
+
+    /// Returns true if a grapheme cluster boundary falls before `ch`, the next character of the
+    /// text. The start of the text is always one.
+    pub fn is_boundary_before(&mut self, ch: char) -> bool {
+        let boundary = match self.previous_character {
+            Some(previous_character) => {
+                is_grapheme_cluster_boundary(previous_character,
+                                             ch,
+                                             self.regional_indicators % 2 == 1)
+            }
+            None => true,
+        };
+        self.previous_character = Some(ch);
+        if is_regional_indicator(ch) {
+            self.regional_indicators += 1
+        } else {
+            self.regional_indicators = 0
+        }
+        boundary
+    }
+}
+
+/// Returns the character indices of `text` that grapheme clusters start at, in order.
+fn grapheme_cluster_starts(text: &str) -> Vec<usize> {
+    let mut breaker = GraphemeClusterBreaker::new();
+    text.chars().enumerate().filter_map(|(index, ch)| {
+        if breaker.is_boundary_before(ch) {
+            Some(index)
+        } else {
+            None
+        }
+    }).collect()
+}
+
+/// Returns the index of the character that the grapheme cluster after the one at the character
+/// index `index` of `text` starts at, or the number of characters in `text` if there is none.
+pub fn next_grapheme_cluster_boundary(text: &str, index: usize) -> usize {
+    let starts = grapheme_cluster_starts(text);
+    match starts.iter().find(|&&start| start > index) {
+        Some(&start) => start,
+        None => text.chars().count(),
+    }
+}
+
+/// Returns the index of the character that the grapheme cluster before the character index
+/// `index` of `text` starts at, or 0 if there is none.
+pub fn previous_grapheme_cluster_boundary(text: &str, index: usize) -> usize {
+    let starts = grapheme_cluster_starts(text);
+    match starts.iter().rev().find(|&&start| start < index) {
+        Some(&start) => start,
+        None => 0,
+    }
+}
@@ -0,0 +1,140 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+use msg::constellation_msg::{Key, KeyModifiers};
+use msg::constellation_msg::{ALT, CONTROL, NONE, SHIFT, SUPER};
+use std::ascii::AsciiExt;
+
+/// Converts the characters of a WebDriver send keys command into key presses, along with the
+/// modifiers held for each. The modifier code points toggle their modifier for the rest of the
+/// sequence, and the null code point releases all of them.
+///
+/// https://w3c.github.io/webdriver/webdriver-spec.html#sendkeys
+pub fn keycodes_to_keys(keycodes: &[char]) -> Result<Vec<(Key, KeyModifiers)>, String> {
+    let mut rv = vec!();
+    let mut held = NONE;
+
+    for &c in keycodes {
+        match c {
+            '\u{E000}' => held = NONE,
+            '\u{E008}' | '\u{E050}' => held.toggle(SHIFT),
+            '\u{E009}' | '\u{E051}' => held.toggle(CONTROL),
+            '\u{E00A}' | '\u{E052}' => held.toggle(ALT),
+            '\u{E03D}' | '\u{E053}' => held.toggle(SUPER),
+            _ => {
+                let (key, mods) = try!(key_from_char(c).ok_or(
+                    format!("Unsupported character {:?}", c)));
+                rv.push((key, mods | held));
+            }
+        }
+    }
+
+    Ok(rv)
+}
+
+fn key_from_char(c: char) -> Option<(Key, KeyModifiers)> {
+    let key = match c {
+        ' ' | '\u{E00D}' => (Key::Space, NONE),
+        '\'' => (Key::Apostrophe, NONE),
+        '"' => (Key::Apostrophe, SHIFT),
+        ',' => (Key::Comma, NONE),
+        '<' => (Key::Comma, SHIFT),
+        '-' => (Key::Minus, NONE),
+        '_' => (Key::Minus, SHIFT),
+        '.' => (Key::Period, NONE),
+        '>' => (Key::Period, SHIFT),
+        '/' => (Key::Slash, NONE),
+        '?' => (Key::Slash, SHIFT),
+        '0' => (Key::Num0, NONE),
+        ')' => (Key::Num0, SHIFT),
+        '1' => (Key::Num1, NONE),
+        '!' => (Key::Num1, SHIFT),
+        '2' => (Key::Num2, NONE),
+        '@' => (Key::Num2, SHIFT),
+        '3' => (Key::Num3, NONE),
+        '#' => (Key::Num3, SHIFT),
+        '4' => (Key::Num4, NONE),
+        '$' => (Key::Num4, SHIFT),
+        '5' => (Key::Num5, NONE),
+        '%' => (Key::Num5, SHIFT),
+        '6' => (Key::Num6, NONE),
+        '^' => (Key::Num6, SHIFT),
+        '7' => (Key::Num7, NONE),
+        '&' => (Key::Num7, SHIFT),
+        '8' => (Key::Num8, NONE),
+        '*' => (Key::Num8, SHIFT),
+        '9' => (Key::Num9, NONE),
+        '(' => (Key::Num9, SHIFT),
+        ';' => (Key::Semicolon, NONE),
+        ':' => (Key::Semicolon, SHIFT),
+        '=' => (Key::Equal, NONE),
+        '+' => (Key::Equal, SHIFT),
+        '[' => (Key::LeftBracket, NONE),
+        '{' => (Key::LeftBracket, SHIFT),
+        '\\' => (Key::Backslash, NONE),
+        '|' => (Key::Backslash, SHIFT),
+        ']' => (Key::RightBracket, NONE),
+        '}' => (Key::RightBracket, SHIFT),
+        '`' => (Key::GraveAccent, NONE),
+        '~' => (Key::GraveAccent, SHIFT),
+        'a'...'z' | 'A'...'Z' => {
+            let mods = if c.is_uppercase() { SHIFT } else { NONE };
+            let key = match c.to_ascii_lowercase() {
+                'a' => Key::A, 'b' => Key::B, 'c' => Key::C, 'd' => Key::D, 'e' => Key::E,
+                'f' => Key::F, 'g' => Key::G, 'h' => Key::H, 'i' => Key::I, 'j' => Key::J,
+                'k' => Key::K, 'l' => Key::L, 'm' => Key::M, 'n' => Key::N, 'o' => Key::O,
+                'p' => Key::P, 'q' => Key::Q, 'r' => Key::R, 's' => Key::S, 't' => Key::T,
+                'u' => Key::U, 'v' => Key::V, 'w' => Key::W, 'x' => Key::X, 'y' => Key::Y,
+                _ => Key::Z,
+            };
+            (key, mods)
+        },
+        '\u{E003}' => (Key::Backspace, NONE),
+        '\u{E004}' => (Key::Tab, NONE),
+        '\u{E006}' | '\u{E007}' => (Key::Enter, NONE),
+        '\u{E00B}' => (Key::Pause, NONE),
+        '\u{E00C}' => (Key::Escape, NONE),
+        '\u{E00E}' => (Key::PageUp, NONE),
+        '\u{E00F}' => (Key::PageDown, NONE),
+        '\u{E010}' => (Key::End, NONE),
+        '\u{E011}' => (Key::Home, NONE),
+        '\u{E012}' => (Key::Left, NONE),
+        '\u{E013}' => (Key::Up, NONE),
+        '\u{E014}' => (Key::Right, NONE),
+        '\u{E015}' => (Key::Down, NONE),
+        '\u{E016}' => (Key::Insert, NONE),
+        '\u{E017}' => (Key::Delete, NONE),
+        '\u{E018}' => (Key::Semicolon, NONE),
+        '\u{E019}' => (Key::Equal, NONE),
+        '\u{E01A}' => (Key::Kp0, NONE),
+        '\u{E01B}' => (Key::Kp1, NONE),
+        '\u{E01C}' => (Key::Kp2, NONE),
+        '\u{E01D}' => (Key::Kp3, NONE),
+        '\u{E01E}' => (Key::Kp4, NONE),
+        '\u{E01F}' => (Key::Kp5, NONE),
+        '\u{E020}' => (Key::Kp6, NONE),
+        '\u{E021}' => (Key::Kp7, NONE),
+        '\u{E022}' => (Key::Kp8, NONE),
+        '\u{E023}' => (Key::Kp9, NONE),
+        '\u{E024}' => (Key::KpMultiply, NONE),
+        '\u{E025}' => (Key::KpAdd, NONE),
+        '\u{E027}' => (Key::KpSubtract, NONE),
+        '\u{E028}' => (Key::KpDecimal, NONE),
+        '\u{E029}' => (Key::KpDivide, NONE),
+        '\u{E031}' => (Key::F1, NONE),
+        '\u{E032}' => (Key::F2, NONE),
+        '\u{E033}' => (Key::F3, NONE),
+        '\u{E034}' => (Key::F4, NONE),
+        '\u{E035}' => (Key::F5, NONE),
+        '\u{E036}' => (Key::F6, NONE),
+        '\u{E037}' => (Key::F7, NONE),
+        '\u{E038}' => (Key::F8, NONE),
+        '\u{E039}' => (Key::F9, NONE),
+        '\u{E03A}' => (Key::F10, NONE),
+        '\u{E03B}' => (Key::F11, NONE),
+        '\u{E03C}' => (Key::F12, NONE),
+        _ => return None,
+    };
+    Some(key)
+}
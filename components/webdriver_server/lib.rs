@@ -18,16 +18,19 @@ extern crate util;
 extern crate rustc_serialize;
 extern crate uuid;
 
+mod keys;
+
 use msg::constellation_msg::{ConstellationChan, LoadData, FrameId, PipelineId, NavigationDirection,
                              WebDriverCommandMsg};
 use msg::constellation_msg::Msg as ConstellationMsg;
 use std::sync::mpsc::{channel, Receiver};
+use keys::keycodes_to_keys;
 use msg::webdriver_msg::{WebDriverFrameId, WebDriverScriptCommand, WebDriverJSError, WebDriverJSResult, LoadStatus};
 
 use url::Url;
 use webdriver::command::{WebDriverMessage, WebDriverCommand};
 use webdriver::command::{GetParameters, JavascriptCommandParameters, LocatorParameters,
-                         SwitchToFrameParameters, TimeoutsParameters};
+                         SendKeysParameters, SwitchToFrameParameters, TimeoutsParameters};
 use webdriver::common::{LocatorStrategy, WebElement};
 use webdriver::response::{
     WebDriverResponse, NewSessionResponse, ValueResponse};
@@ -369,6 +372,47 @@ impl Handler {
         }
     }
 
+    fn handle_element_click(&self, element: &WebElement) -> WebDriverResult<WebDriverResponse> {
+        let pipeline_id = try!(self.get_frame_pipeline());
+
+        let (sender, reciever) = channel();
+        let ConstellationChan(ref const_chan) = self.constellation_chan;
+        let cmd = WebDriverScriptCommand::ElementClick(element.id.clone(), sender);
+        let cmd_msg = WebDriverCommandMsg::ScriptCommand(pipeline_id, cmd);
+        const_chan.send(ConstellationMsg::WebDriverCommand(cmd_msg)).unwrap();
+        match reciever.recv().unwrap() {
+            Ok(()) => Ok(WebDriverResponse::Void),
+            Err(_) => Err(WebDriverError::new(ErrorStatus::StaleElementReference,
+                                              "Unable to find element in document"))
+        }
+    }
+
+    fn handle_element_send_keys(&self,
+                                element: &WebElement,
+                                keys: &SendKeysParameters) -> WebDriverResult<WebDriverResponse> {
+        let pipeline_id = try!(self.get_frame_pipeline());
+
+        // Key events go to the focused element, so focus the target first.
+        let (sender, reciever) = channel();
+        let ConstellationChan(ref const_chan) = self.constellation_chan;
+        let cmd = WebDriverScriptCommand::FocusElement(element.id.clone(), sender);
+        let cmd_msg = WebDriverCommandMsg::ScriptCommand(pipeline_id, cmd);
+        const_chan.send(ConstellationMsg::WebDriverCommand(cmd_msg)).unwrap();
+        if reciever.recv().unwrap().is_err() {
+            return Err(WebDriverError::new(ErrorStatus::StaleElementReference,
+                                           "Unable to find element in document"))
+        }
+
+        let keys = match keycodes_to_keys(&keys.value) {
+            Ok(keys) => keys,
+            Err(_) => return Err(WebDriverError::new(ErrorStatus::UnsupportedOperation,
+                                                     "Failed to convert keycodes"))
+        };
+        let cmd_msg = WebDriverCommandMsg::SendKeys(pipeline_id, keys);
+        const_chan.send(ConstellationMsg::WebDriverCommand(cmd_msg)).unwrap();
+        Ok(WebDriverResponse::Void)
+    }
+
     fn handle_set_timeouts(&mut self, parameters: &TimeoutsParameters) -> WebDriverResult<WebDriverResponse> {
         //TODO: this conversion is crazy, spec should limit these to u32 and check upstream
         let value = parameters.ms as u32;
@@ -498,6 +542,9 @@ impl WebDriverHandler for Handler {
             WebDriverCommand::GetActiveElement => self.handle_get_active_element(),
             WebDriverCommand::GetElementText(ref element) => self.handle_get_element_text(element),
             WebDriverCommand::GetElementTagName(ref element) => self.handle_get_element_tag_name(element),
+            WebDriverCommand::ElementClick(ref element) => self.handle_element_click(element),
+            WebDriverCommand::ElementSendKeys(ref element, ref keys) =>
+                self.handle_element_send_keys(element, keys),
             WebDriverCommand::ExecuteScript(ref x) => self.handle_execute_script(x),
             WebDriverCommand::ExecuteAsyncScript(ref x) => self.handle_execute_async_script(x),
             WebDriverCommand::SetTimeouts(ref x) => self.handle_set_timeouts(x),
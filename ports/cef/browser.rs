@@ -8,6 +8,8 @@ use frame::{ServoCefFrame, ServoCefFrameExtensions};
 use interfaces::{CefBrowser, CefBrowserHost, CefClient, CefFrame, CefRequestContext};
 use interfaces::{cef_browser_t, cef_browser_host_t, cef_client_t, cef_frame_t};
 use interfaces::{cef_request_context_t};
+use net_traits::ProtocolHandler;
+use scheme;
 use servo::Browser;
 use types::{cef_browser_settings_t, cef_string_t, cef_window_info_t, cef_window_handle_t};
 use util::task::spawn_named;
@@ -55,6 +57,14 @@ impl ServoBrowser {
             ServoBrowser::Invalid => 1.0,
         }
     }
+
+    pub fn register_protocol_handler(&self, scheme: String, handler: Box<ProtocolHandler>) {
+        match *self {
+            ServoBrowser::OnScreen(ref browser) => browser.register_protocol_handler(scheme, handler),
+            ServoBrowser::OffScreen(ref browser) => browser.register_protocol_handler(scheme, handler),
+            ServoBrowser::Invalid => {}
+        }
+    }
 }
 
 cef_class_impl! {
@@ -115,6 +125,8 @@ pub struct ServoCefBrowser {
     pub favicons: RefCell<Vec<String>>,
     /// the display system window handle: only to be used with host.get_window_handle()
     window_handle: cef_window_handle_t,
+    /// The frame waiting to be handed to the render handler of an off-screen browser.
+    pub pending_paint: RefCell<Option<window::PendingPaint>>,
 
     id: isize,
     servo_browser: RefCell<ServoBrowser>,
@@ -131,6 +143,7 @@ impl ServoCefBrowser {
         let servo_browser = if window_info.windowless_rendering_enabled == 0 {
             glutin_window = Some(glutin_app::create_window(window_info.parent_window as glutin_app::WindowID));
             let servo_browser = Browser::new(glutin_window.clone());
+            scheme::register_scheme_handler_factories(&servo_browser);
             window_handle = match glutin_window {
                 Some(ref win) => win.platform_window() as cef_window_handle_t,
                 None => get_null_window_handle()
@@ -158,6 +171,7 @@ impl ServoCefBrowser {
             loading: Cell::new(false),
             favicons: RefCell::new(vec!()),
             window_handle: window_handle,
+            pending_paint: RefCell::new(None),
         }
     }
 }
@@ -167,6 +181,7 @@ pub trait ServoCefBrowserExtensions {
     fn send_window_event(&self, event: WindowEvent);
     fn get_title_for_main_frame(&self);
     fn pinch_zoom_level(&self) -> f32;
+    fn register_protocol_handler(&self, scheme: String, handler: Box<ProtocolHandler>);
 }
 
 impl ServoCefBrowserExtensions for CefBrowser {
@@ -175,6 +190,7 @@ impl ServoCefBrowserExtensions for CefBrowser {
             let window = window::Window::new(window_info.width, window_info.height);
             window.set_browser(self.clone());
             let servo_browser = Browser::new(Some(window.clone()));
+            scheme::register_scheme_handler_factories(&servo_browser);
             *self.downcast().servo_browser.borrow_mut() = ServoBrowser::OffScreen(servo_browser);
         }
 
@@ -214,6 +230,10 @@ impl ServoCefBrowserExtensions for CefBrowser {
     fn pinch_zoom_level(&self) -> f32 {
         self.downcast().servo_browser.borrow().pinch_zoom_level()
     }
+
+    fn register_protocol_handler(&self, scheme: String, handler: Box<ProtocolHandler>) {
+        self.downcast().servo_browser.borrow().register_protocol_handler(scheme, handler)
+    }
 }
 
 #[cfg(target_os="macos")]
@@ -241,6 +261,7 @@ pub fn update() {
                    None => break
                }
             }
+            window::paint_pending(browser);
         }
     });
 }
@@ -14,7 +14,7 @@ use compositing::windowing::{WindowEvent, MouseWindowEvent};
 use euclid::point::Point2D;
 use euclid::size::Size2D;
 use libc::{c_double, c_int};
-use msg::constellation_msg::{self, KeyModifiers, KeyState};
+use msg::constellation_msg::{self, ImeEvent, KeyModifiers, KeyState};
 use script_traits::MouseButton;
 use std::cell::{Cell, RefCell};
 use std::mem::transmute;
@@ -27,6 +27,9 @@ pub struct ServoCefBrowserHost {
     pub client: CefClient,
     /// flag for return value of prepare_for_composite
     pub composite_ok: Cell<bool>,
+    /// The first half of a character that an input method is committing, if the second half
+    /// hasn't been sent yet.
+    high_surrogate: Cell<Option<u16>>,
 }
 
 // From blink ui/events/keycodes/keyboard_codes_posix.h.
@@ -402,6 +405,14 @@ full_cef_class_impl! {
 
         fn send_key_event(&this, event: *const cef_key_event [&cef_key_event],) -> () {{
             let event: &cef_key_event = event;
+            // Embedders pass on the text an input method commits as character events, of
+            // characters that no key makes.
+            if let KEYEVENT_CHAR = (*event).t {
+                if (*event).character >= 0x80 {
+                    this.downcast().commit_character((*event).character);
+                    return
+                }
+            }
             let key = match get_key_msg((*event).windows_key_code, (*event).character) {
                 Some(keycode) => keycode,
                 None => {
@@ -445,11 +456,13 @@ full_cef_class_impl! {
             };
             let point = Point2D::typed((*event).x as f32, (*event).y as f32);
             if mouse_up != 0 {
+                this.downcast().send_window_event(WindowEvent::MouseWindowEventClass(
+                    MouseWindowEvent::MouseUp(button_type, point)));
                 this.downcast().send_window_event(WindowEvent::MouseWindowEventClass(
                     MouseWindowEvent::Click(button_type, point)))
             } else {
                 this.downcast().send_window_event(WindowEvent::MouseWindowEventClass(
-                    MouseWindowEvent::MouseUp(button_type, point)))
+                    MouseWindowEvent::MouseDown(button_type, point)))
             }
         }}
 
@@ -481,7 +494,10 @@ full_cef_class_impl! {
         fn set_zoom_level(&this, new_zoom_level: c_double [c_double],) -> () {{
             let new_zoom_level: c_double = new_zoom_level;
             let old_zoom_level = this.get_zoom_level();
-            this.downcast().send_window_event(WindowEvent::PinchZoom((new_zoom_level / old_zoom_level) as f32))
+            let magnification = (new_zoom_level / old_zoom_level) as f32;
+            let origin = Point2D::typed(0.0, 0.0);
+            this.downcast().send_window_event(WindowEvent::PinchZoom(magnification, origin));
+            this.downcast().send_window_event(WindowEvent::PinchZoomEnd)
         }}
 
         fn initialize_compositing(&this,) -> () {{
@@ -508,6 +524,25 @@ impl ServoCefBrowserHost {
             browser: RefCell::new(None),
             client: client,
             composite_ok: Cell::new(false),
+            high_surrogate: Cell::new(None),
+        }
+    }
+
+    /// Commits a character that an input method sent as a character event, one UTF-16 code
+    /// unit at a time.
+    fn commit_character(&self, unit: u16) {
+        let high_surrogate = self.high_surrogate.get();
+        self.high_surrogate.set(None);
+        if unit >= 0xD800 && unit < 0xDC00 {
+            self.high_surrogate.set(Some(unit));
+            return
+        }
+        let units = match high_surrogate {
+            Some(high_surrogate) => vec!(high_surrogate, unit),
+            None => vec!(unit),
+        };
+        if let Ok(text) = String::from_utf16(&units) {
+            self.send_window_event(WindowEvent::ImeEvent(ImeEvent::Commit(text)));
         }
     }
 
@@ -77,6 +77,18 @@ pub extern "C" fn command_line_get_switch_value(cmd: *mut cef_command_line_t, na
     return cef_string::empty_utf16_userfree_string()
 }
 
+/// Returns true if the command line Servo was initialized with has the given switch.
+pub fn command_line_has_switch(name: &str) -> bool {
+    unsafe {
+        match GLOBAL_CMDLINE {
+            Some(cl) => (*cl).argv.iter().any(|arg| {
+                arg.starts_with("-") && arg.trim_left_matches('-') == name
+            }),
+            None => false,
+        }
+    }
+}
+
 #[no_mangle]
 pub extern "C" fn cef_command_line_create() -> *mut cef_command_line_t {
         unsafe {
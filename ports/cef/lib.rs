@@ -30,6 +30,7 @@ extern crate euclid;
 extern crate gfx;
 extern crate gleam;
 extern crate glutin_app;
+extern crate hyper;
 extern crate js;
 extern crate layers;
 extern crate png;
@@ -38,6 +39,7 @@ extern crate script;
 extern crate script_traits;
 
 extern crate net;
+extern crate net_traits;
 extern crate msg;
 extern crate util;
 extern crate style;
@@ -83,6 +85,7 @@ pub mod render_handler;
 pub mod request;
 pub mod request_context;
 pub mod response;
+pub mod scheme;
 pub mod stream;
 pub mod string;
 pub mod string_list;
@@ -4,16 +4,37 @@
 
 use interfaces::{CefBrowser, CefRenderHandler};
 use types::cef_paint_element_type_t::PET_VIEW;
+use types::cef_rect_t;
 
 use std::ptr;
 
 pub trait CefRenderHandlerExtensions {
+    /// Asks the client to call `composite` on the browser host, with its GL context current.
     fn paint(&self, browser: CefBrowser, width: usize, height: usize);
+
+    /// Hands the client a composited frame, as BGRA rows from the top down, of which only
+    /// `dirty_rect` has changed since the last one.
+    fn paint_pixels(&self,
+                    browser: CefBrowser,
+                    dirty_rect: &cef_rect_t,
+                    pixels: &[u8],
+                    width: usize,
+                    height: usize);
 }
 
 impl CefRenderHandlerExtensions for CefRenderHandler {
     fn paint(&self, browser: CefBrowser, width: usize, height: usize) {
         self.on_paint(browser, PET_VIEW, 0, ptr::null(), &mut (), width as i32, height as i32)
     }
+
+    fn paint_pixels(&self,
+                    browser: CefBrowser,
+                    dirty_rect: &cef_rect_t,
+                    pixels: &[u8],
+                    width: usize,
+                    height: usize) {
+        let buffer = unsafe { &*(pixels.as_ptr() as *const ()) };
+        self.on_paint(browser, PET_VIEW, 1, dirty_rect, buffer, width as i32, height as i32)
+    }
 }
 
@@ -0,0 +1,302 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+//! Scheme handler factories, which let an application serve the URLs of its own schemes.
+//! Each factory is handed to Servo's resource task as a protocol handler, which asks the
+//! factory for a resource handler every time a URL of its scheme is loaded.
+
+use browser::{self, ServoCefBrowserExtensions};
+use eutil::Downcast;
+use interfaces::{CefBrowser, CefCallback, CefFrame, CefRequest, CefResourceHandler, CefResponse};
+use interfaces::{CefSchemeHandlerFactory, cef_callback_t, cef_request_t, cef_response_t};
+use interfaces::{cef_scheme_handler_factory_t};
+use types::{cef_string_t, cef_string_userfree_t};
+
+use hyper::header::{ContentLength, ContentType, Headers};
+use hyper::http::RawStatus;
+use libc::c_int;
+use net_traits::{LoadData, ProtocolHandler, ProtocolResponse};
+use servo::Browser;
+use std::borrow::ToOwned;
+use std::cell::{Cell, RefCell};
+use std::io::{self, Read};
+use std::ptr;
+use std::sync::Mutex;
+use std::sync::mpsc::{channel, Sender};
+
+thread_local!(pub static SCHEME_HANDLER_FACTORIES: RefCell<Vec<(String, CefSchemeHandlerFactory)>> =
+              RefCell::new(vec!()));
+
+/// The request a resource handler is asked to process.
+pub struct ServoCefRequest {
+    url: String,
+    method: String,
+}
+
+full_cef_class_impl! {
+    ServoCefRequest : CefRequest, cef_request_t {
+        fn is_read_only(&_this,) -> c_int {{
+            1i32
+        }}
+        fn get_url(&this,) -> cef_string_userfree_t {{
+            this.downcast().url.clone()
+        }}
+        fn get_method(&this,) -> cef_string_userfree_t {{
+            this.downcast().method.clone()
+        }}
+    }
+}
+
+/// The response a resource handler fills in from `get_response_headers`.
+pub struct ServoCefResponse {
+    status: Cell<c_int>,
+    status_text: RefCell<String>,
+    mime_type: RefCell<String>,
+}
+
+impl ServoCefResponse {
+    pub fn new() -> ServoCefResponse {
+        ServoCefResponse {
+            status: Cell::new(0),
+            status_text: RefCell::new(String::new()),
+            mime_type: RefCell::new(String::new()),
+        }
+    }
+}
+
+full_cef_class_impl! {
+    ServoCefResponse : CefResponse, cef_response_t {
+        fn is_read_only(&_this,) -> c_int {{
+            0i32
+        }}
+        fn get_status(&this,) -> c_int {{
+            this.downcast().status.get()
+        }}
+        fn set_status(&this, status: c_int [c_int],) -> () {{
+            this.downcast().status.set(status)
+        }}
+        fn get_status_text(&this,) -> cef_string_userfree_t {{
+            let this = this.downcast();
+            // FIXME(https://github.com/rust-lang/rust/issues/23338)
+            let status_text = this.status_text.borrow();
+            (*status_text).clone()
+        }}
+        fn set_status_text(&this, status_text: *const cef_string_t [&[u16]],) -> () {{
+            let this = this.downcast();
+            *this.status_text.borrow_mut() = String::from_utf16(status_text).unwrap();
+        }}
+        fn get_mime_type(&this,) -> cef_string_userfree_t {{
+            let this = this.downcast();
+            // FIXME(https://github.com/rust-lang/rust/issues/23338)
+            let mime_type = this.mime_type.borrow();
+            (*mime_type).clone()
+        }}
+        fn set_mime_type(&this, mime_type: *const cef_string_t [&[u16]],) -> () {{
+            let this = this.downcast();
+            *this.mime_type.borrow_mut() = String::from_utf16(mime_type).unwrap();
+        }}
+    }
+}
+
+/// Reports whether a resource handler went on with the load or cancelled it.
+pub struct ServoCefCallback {
+    sender: Mutex<Sender<bool>>,
+}
+
+impl ServoCefCallback {
+    pub fn new(sender: Sender<bool>) -> ServoCefCallback {
+        ServoCefCallback {
+            sender: Mutex::new(sender),
+        }
+    }
+}
+
+cef_class_impl! {
+    ServoCefCallback : CefCallback, cef_callback_t {
+        fn cont(&this,) -> () {{
+            // The load may have given up waiting already.
+            let _ = this.downcast().sender.lock().unwrap().send(true);
+        }}
+        fn cancel(&this,) -> () {{
+            let _ = this.downcast().sender.lock().unwrap().send(false);
+        }}
+    }
+}
+
+/// Loads the URLs of one scheme with the resource handlers an application's factory creates.
+struct SchemeHandler {
+    scheme: String,
+    factory: CefSchemeHandlerFactory,
+}
+
+// CEF objects are reference counted atomically, and factories and resource handlers are
+// meant to be called from threads other than the one they were created on.
+unsafe impl Send for SchemeHandler {}
+unsafe impl Sync for SchemeHandler {}
+
+impl ProtocolHandler for SchemeHandler {
+    fn load(&self, load_data: &LoadData) -> Result<ProtocolResponse, String> {
+        let request = ServoCefRequest {
+            url: load_data.url.serialize(),
+            method: load_data.method.to_string(),
+        }.as_cef_interface();
+        let scheme: Vec<u16> = self.scheme.utf16_units().collect();
+        // The resource task doesn't know which browser a load is for.
+        let (browser, frame) = unsafe {
+            (CefBrowser::from_c_object(ptr::null_mut()), CefFrame::from_c_object(ptr::null_mut()))
+        };
+        let handler = self.factory.create(browser, frame, &scheme, request.clone());
+        if handler.is_null_cef_object() {
+            return Err(format!("No handler for {}.", load_data.url.serialize()));
+        }
+
+        let (sender, receiver) = channel();
+        let callback = ServoCefCallback::new(sender).as_cef_interface();
+        if handler.process_request(request, callback) == 0 {
+            return Err("The scheme handler refused the request.".to_owned());
+        }
+        if receiver.recv() != Ok(true) {
+            handler.cancel();
+            return Err("The scheme handler cancelled the request.".to_owned());
+        }
+
+        let response = ServoCefResponse::new().as_cef_interface();
+        let mut response_length = -1;
+        // Redirects aren't supported, so there's nowhere to put a redirect URL.
+        handler.get_response_headers(response.clone(), &mut response_length, ptr::null_mut());
+
+        let response = response.downcast();
+        let mut headers = Headers::new();
+        if let Ok(mime) = response.mime_type.borrow().parse() {
+            headers.set(ContentType(mime));
+        }
+        if response_length >= 0 {
+            headers.set(ContentLength(response_length as u64));
+        }
+        let status = match response.status.get() {
+            0 => None,
+            status => Some(RawStatus(status as u16, response.status_text.borrow().clone().into())),
+        };
+        Ok(ProtocolResponse {
+            status: status,
+            headers: headers,
+            body: box ResourceHandlerBody {
+                handler: handler,
+                remaining: if response_length >= 0 { Some(response_length as u64) } else { None },
+                done: false,
+            },
+        })
+    }
+}
+
+/// Reads a response body from a resource handler.
+struct ResourceHandlerBody {
+    handler: CefResourceHandler,
+    /// How much of the body is left, if the handler said how long it is.
+    remaining: Option<u64>,
+    /// Whether the handler has finished or cancelled the response.
+    done: bool,
+}
+
+unsafe impl Send for ResourceHandlerBody {}
+
+impl Read for ResourceHandlerBody {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        loop {
+            if self.done || buf.is_empty() || self.remaining == Some(0) {
+                return Ok(0);
+            }
+
+            let (sender, receiver) = channel();
+            let callback = ServoCefCallback::new(sender).as_cef_interface();
+            let mut bytes_read = 0;
+            // The generated wrapper can't pass a buffer, so call through the C struct.
+            let more = unsafe {
+                let handler = self.handler.c_object();
+                ((*handler).read_response.unwrap())(handler,
+                                                    buf.as_mut_ptr() as *mut (),
+                                                    buf.len() as c_int,
+                                                    &mut bytes_read,
+                                                    callback.c_object_addrefed())
+            };
+            if more == 0 {
+                self.done = true;
+                return Ok(0);
+            }
+            if bytes_read > 0 {
+                let bytes_read = bytes_read as usize;
+                assert!(bytes_read <= buf.len());
+                if let Some(ref mut remaining) = self.remaining {
+                    *remaining = remaining.saturating_sub(bytes_read as u64);
+                }
+                return Ok(bytes_read);
+            }
+
+            // The data isn't ready yet; the handler says when it is.
+            if receiver.recv() != Ok(true) {
+                self.done = true;
+                return Err(io::Error::new(io::ErrorKind::Other,
+                                          "the scheme handler cancelled the response"));
+            }
+        }
+    }
+}
+
+impl Drop for ResourceHandlerBody {
+    fn drop(&mut self) {
+        if !self.done && self.remaining != Some(0) {
+            self.handler.cancel();
+        }
+    }
+}
+
+fn protocol_handler(scheme: &str, factory: CefSchemeHandlerFactory) -> Box<ProtocolHandler> {
+    box SchemeHandler {
+        scheme: scheme.to_owned(),
+        factory: factory,
+    }
+}
+
+/// Hands the registered factories to a newly created browser.
+pub fn register_scheme_handler_factories(browser: &Browser) {
+    SCHEME_HANDLER_FACTORIES.with(|factories| {
+        for &(ref scheme, ref factory) in factories.borrow().iter() {
+            browser.register_protocol_handler(scheme.clone(),
+                                              protocol_handler(scheme, factory.clone()));
+        }
+    });
+}
+
+cef_static_method_impls! {
+    fn cef_register_scheme_handler_factory(scheme_name: *const cef_string_t,
+                                           _domain_name: *const cef_string_t,
+                                           factory: *mut cef_scheme_handler_factory_t)
+                                           -> c_int {{
+        let scheme_name: &[u16] = scheme_name;
+        // Handlers serve every URL of their scheme, whatever the domain.
+        let _domain_name: *const cef_string_t = _domain_name;
+        let factory: CefSchemeHandlerFactory = factory;
+        let scheme = String::from_utf16(scheme_name).unwrap().to_lowercase();
+        browser::BROWSERS.with(|browsers| {
+            for browser in browsers.borrow().iter() {
+                browser.register_protocol_handler(scheme.clone(),
+                                                  protocol_handler(&scheme, factory.clone()));
+            }
+        });
+        SCHEME_HANDLER_FACTORIES.with(|factories| {
+            let mut factories = factories.borrow_mut();
+            factories.retain(|&(ref registered, _)| *registered != scheme);
+            factories.push((scheme, factory));
+        });
+        1i32
+    }}
+    // Servo can't take a handler back once it has one, so this only affects browsers that
+    // are created afterwards.
+    fn cef_clear_scheme_handler_factories() -> c_int {{
+        SCHEME_HANDLER_FACTORIES.with(|factories| {
+            factories.borrow_mut().clear();
+        });
+        1i32
+    }}
+}
@@ -23,7 +23,6 @@ stub!(cef_add_web_plugin_directory);
 stub!(cef_add_web_plugin_path);
 stub!(cef_begin_tracing);
 stub!(cef_clear_cross_origin_whitelist);
-stub!(cef_clear_scheme_handler_factories);
 stub!(cef_create_url);
 stub!(cef_end_tracing);
 stub!(cef_force_web_plugin_shutdown);
@@ -41,7 +40,6 @@ stub!(cef_post_delayed_task);
 stub!(cef_post_task);
 stub!(cef_refresh_web_plugins);
 stub!(cef_register_extension);
-stub!(cef_register_scheme_handler_factory);
 stub!(cef_register_web_plugin_crash);
 stub!(cef_remove_cross_origin_whitelist_entry);
 stub!(cef_remove_web_plugin_path);
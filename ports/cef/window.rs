@@ -7,6 +7,7 @@
 //! This is used for off-screen rendering mode only; on-screen windows (the default embedding mode)
 //! are managed by a platform toolkit (Glutin).
 
+use command_line::command_line_has_switch;
 use core::CEF_APP;
 use eutil::Downcast;
 use interfaces::{CefApp, CefBrowser};
@@ -16,7 +17,9 @@ use types::{cef_cursor_handle_t, cef_cursor_type_t, cef_rect_t};
 use wrappers::CefWrap;
 
 use compositing::compositor_task::{self, CompositorProxy, CompositorReceiver};
-use compositing::windowing::{WindowEvent, WindowMethods};
+use compositing::windowing::{DEFAULT_REFRESH_INTERVAL, PermissionPrompt, WindowEvent};
+use compositing::windowing::{CredentialsPrompt, PositionRequest, VblankSource, WindowMethods};
+use euclid::rect::TypedRect;
 use euclid::scale_factor::ScaleFactor;
 use euclid::size::{Size2D, TypedSize2D};
 use gleam::gl;
@@ -24,14 +27,20 @@ use layers::geometry::DevicePixel;
 use layers::platform::surface::NativeDisplay;
 use libc::{c_char, c_void};
 use msg::constellation_msg::{Key, KeyModifiers};
+use msg::constellation_msg::CertificateError;
+use msg::constellation_msg::{MixedContentState, NotificationData};
+use msg::constellation_msg::{NotificationId, PipelineId};
 use net::net_error_list::NetError;
+use net_traits::image::base::Image;
+use std::cmp;
 use std::ptr;
 use std_url::Url;
 use util::cursor::Cursor;
 use util::geometry::ScreenPx;
-use std::cell::RefCell;
+use std::cell::{Cell, RefCell};
 use std::ffi::CString;
 use std::rc::Rc;
+use std::sync::Arc;
 use std::sync::mpsc::{Sender, channel};
 #[cfg(target_os="linux")]
 extern crate x11;
@@ -41,12 +50,129 @@ use self::x11::xlib::{XInitThreads,XOpenDisplay};
 #[cfg(target_os="linux")]
 pub static mut DISPLAY: *mut c_void = 0 as *mut c_void;
 
+/// The command-line switch with which clients ask to be handed each frame's pixels through
+/// `on_paint`, instead of being asked to composite into their own framebuffer.
+const PIXEL_BUFFERS_SWITCH: &'static str = "osr-pixel-buffers";
+
 /// The type of an off-screen window.
 #[allow(raw_pointer_derive)]
 #[derive(Clone)]
 pub struct Window {
     cef_browser: RefCell<Option<CefBrowser>>,
-    size: TypedSize2D<DevicePixel,u32>
+    size: TypedSize2D<DevicePixel,u32>,
+    /// Whether the client is handed pixels rather than composited into directly.
+    paints_pixels: bool,
+    /// What pages are composited into when the client is handed pixels.
+    framebuffer: RefCell<Option<Framebuffer>>,
+    /// The composited frame, as BGRA rows from the top down, which is updated where the
+    /// compositor drew.
+    frame: RefCell<Vec<u8>>,
+    /// Whether the framebuffer was made since the last frame, so that all of it is new.
+    framebuffer_is_new: Cell<bool>,
+}
+
+/// A frame waiting to be handed to the client, and the part of it that changed since the last
+/// frame the client was handed.
+pub struct PendingPaint {
+    dirty_rect: cef_rect_t,
+    pixels: Vec<u8>,
+    width: usize,
+    height: usize,
+}
+
+/// A framebuffer backed by a texture, whose pixels are read back after each composite.
+#[derive(Clone, Copy)]
+struct Framebuffer {
+    framebuffer: gl::GLuint,
+    texture: gl::GLuint,
+    width: usize,
+    height: usize,
+}
+
+impl Framebuffer {
+    fn new(width: usize, height: usize) -> Framebuffer {
+        let framebuffer = gl::gen_framebuffers(1)[0];
+        gl::bind_framebuffer(gl::FRAMEBUFFER, framebuffer);
+
+        let texture = gl::gen_textures(1)[0];
+        gl::bind_texture(gl::TEXTURE_2D, texture);
+        gl::tex_image_2d(gl::TEXTURE_2D, 0, gl::RGBA as gl::GLint, width as gl::GLsizei,
+                         height as gl::GLsizei, 0, gl::RGBA, gl::UNSIGNED_BYTE, None);
+        gl::tex_parameter_i(gl::TEXTURE_2D, gl::TEXTURE_MAG_FILTER, gl::NEAREST as gl::GLint);
+        gl::tex_parameter_i(gl::TEXTURE_2D, gl::TEXTURE_MIN_FILTER, gl::NEAREST as gl::GLint);
+        gl::framebuffer_texture_2d(gl::FRAMEBUFFER, gl::COLOR_ATTACHMENT0, gl::TEXTURE_2D,
+                                   texture, 0);
+        gl::bind_texture(gl::TEXTURE_2D, 0);
+
+        Framebuffer {
+            framebuffer: framebuffer,
+            texture: texture,
+            width: width,
+            height: height,
+        }
+    }
+
+    fn delete(&self) {
+        gl::delete_textures(&[self.texture]);
+        gl::delete_frame_buffers(&[self.framebuffer]);
+    }
+
+    /// Copies `rect`, in pixels from the top left, into `frame`, whose rows are BGRA from the
+    /// top down as CEF clients expect.
+    fn read_rect(&self, rect: &cef_rect_t, frame: &mut [u8]) {
+        let (x, width) = (rect.x as usize, rect.width as usize);
+        let (y, height) = (rect.y as usize, rect.height as usize);
+        // GL's rows go from the bottom up.
+        let pixels = gl::read_pixels(x as gl::GLint, (self.height - y - height) as gl::GLint,
+                                     width as gl::GLsizei, height as gl::GLsizei,
+                                     gl::RGBA, gl::UNSIGNED_BYTE);
+        let stride = width * 4;
+        for row in 0..height {
+            let source = &pixels[(height - 1 - row) * stride..(height - row) * stride];
+            let start = ((y + row) * self.width + x) * 4;
+            let destination = &mut frame[start..start + stride];
+            for (source, destination) in source.chunks(4).zip(destination.chunks_mut(4)) {
+                destination[0] = source[2];
+                destination[1] = source[1];
+                destination[2] = source[0];
+                destination[3] = source[3];
+            }
+        }
+    }
+}
+
+/// The smallest rectangle holding both `a` and `b`.
+fn union_rect(a: &cef_rect_t, b: &cef_rect_t) -> cef_rect_t {
+    let left = cmp::min(a.x, b.x);
+    let top = cmp::min(a.y, b.y);
+    let right = cmp::max(a.x + a.width, b.x + b.width);
+    let bottom = cmp::max(a.y + a.height, b.y + b.height);
+    cef_rect_t {
+        x: left,
+        y: top,
+        width: right - left,
+        height: bottom - top,
+    }
+}
+
+/// Hands the client the frame that is waiting for it, if any. This is done from the message
+/// loop rather than while compositing, which the client may be in the middle of asking for.
+pub fn paint_pending(browser: &CefBrowser) {
+    let paint = match browser.downcast().pending_paint.borrow_mut().take() {
+        Some(paint) => paint,
+        None => return,
+    };
+    if check_ptr_exist!(browser.get_host().get_client(), get_render_handler) &&
+       check_ptr_exist!(browser.get_host().get_client().get_render_handler(), on_paint) {
+        browser.get_host()
+               .get_client()
+               .get_render_handler()
+               .paint_pixels(browser.clone(),
+                             &paint.dirty_rect,
+                             &paint.pixels,
+                             paint.width,
+                             paint.height);
+    }
 }
 
 #[cfg(target_os="macos")]
@@ -86,10 +212,76 @@ impl Window {
 
         Rc::new(Window {
             cef_browser: RefCell::new(None),
-            size: Size2D::typed(width, height)
+            size: Size2D::typed(width, height),
+            paints_pixels: command_line_has_switch(PIXEL_BUFFERS_SWITCH),
+            framebuffer: RefCell::new(None),
+            frame: RefCell::new(vec!()),
+            framebuffer_is_new: Cell::new(false),
         })
     }
 
+    /// Directs the next composite into the window's own framebuffer, made the given size.
+    fn bind_framebuffer(&self, width: usize, height: usize) {
+        let mut framebuffer = self.framebuffer.borrow_mut();
+        let resized = match *framebuffer {
+            Some(ref framebuffer) => framebuffer.width != width || framebuffer.height != height,
+            None => true,
+        };
+        if resized {
+            if let Some(ref framebuffer) = *framebuffer {
+                framebuffer.delete();
+            }
+            *framebuffer = Some(Framebuffer::new(width, height));
+            *self.frame.borrow_mut() = vec![0; width * height * 4];
+            self.framebuffer_is_new.set(true);
+        }
+        gl::bind_framebuffer(gl::FRAMEBUFFER, framebuffer.unwrap().framebuffer);
+    }
+
+    /// Reads back the part of the frame that the compositor drew, and queues the frame to be
+    /// handed to the client.
+    fn queue_paint(&self, browser: &CefBrowser, damage: TypedRect<DevicePixel, u32>) {
+        let framebuffer = match *self.framebuffer.borrow() {
+            Some(framebuffer) => framebuffer,
+            None => return,
+        };
+        let (width, height) = (framebuffer.width, framebuffer.height);
+        let damage = damage.to_untyped();
+        let dirty_rect = if self.framebuffer_is_new.get() {
+            cef_rect_t { x: 0, y: 0, width: width as i32, height: height as i32 }
+        } else {
+            let x = cmp::min(damage.origin.x as usize, width);
+            let y = cmp::min(damage.origin.y as usize, height);
+            cef_rect_t {
+                x: x as i32,
+                y: y as i32,
+                width: cmp::min(damage.size.width as usize, width - x) as i32,
+                height: cmp::min(damage.size.height as usize, height - y) as i32,
+            }
+        };
+        self.framebuffer_is_new.set(false);
+
+        let mut frame = self.frame.borrow_mut();
+        framebuffer.read_rect(&dirty_rect, &mut frame);
+        gl::bind_framebuffer(gl::FRAMEBUFFER, 0);
+
+        // A frame the client hasn't been handed yet is replaced, but what changed in it still
+        // has to be painted.
+        let mut pending_paint = browser.downcast().pending_paint.borrow_mut();
+        let dirty_rect = match *pending_paint {
+            Some(ref paint) if paint.width == width && paint.height == height => {
+                union_rect(&paint.dirty_rect, &dirty_rect)
+            }
+            _ => dirty_rect,
+        };
+        *pending_paint = Some(PendingPaint {
+            dirty_rect: dirty_rect,
+            pixels: frame.clone(),
+            width: width,
+            height: height,
+        });
+    }
+
     /// Sets the current browser.
     pub fn set_browser(&self, browser: CefBrowser) {
         *self.cef_browser.borrow_mut() = Some(browser)
@@ -233,6 +425,26 @@ impl WindowMethods for Window {
         }
     }
 
+    fn refresh_interval(&self) -> u64 {
+        DEFAULT_REFRESH_INTERVAL
+    }
+
+    fn vblank_source(&self) -> Option<Box<VblankSource>> {
+        None
+    }
+
+    /// The window's own framebuffer keeps its contents, but the client's may not.
+    fn supports_partial_present(&self) -> bool {
+        self.paints_pixels
+    }
+
+    fn present_damage(&self, damage: TypedRect<DevicePixel, u32>) {
+        if let Some(ref browser) = *self.cef_browser.borrow() {
+            self.queue_paint(browser, damage);
+        }
+        self.present()
+    }
+
     fn hidpi_factor(&self) -> ScaleFactor<ScreenPx,DevicePixel,f32> {
         if cfg!(target_os="macos") {
             let browser = self.cef_browser.borrow();
@@ -295,6 +507,9 @@ impl WindowMethods for Window {
             }
             Some(ref browser) => {
                 if browser.downcast().host.downcast().composite_ok.get() == true {
+                    if self.paints_pixels {
+                        self.bind_framebuffer(width, height);
+                    }
                     true
                 } else {
                     if check_ptr_exist!(browser.get_host().get_client(), get_render_handler) &&
@@ -316,6 +531,38 @@ impl WindowMethods for Window {
         browser.downcast().favicons.borrow_mut().push(url.to_string().clone());
     }
 
+    fn set_favicon_image(&self, _: Arc<Image>) {
+        // CEF clients are only given the URLs of favicons, and fetch the icons themselves.
+    }
+
+    fn request_permission(&self, _: PermissionPrompt) {
+        // CEF's permission handlers aren't wired up, so dropping the prompt denies the
+        // permission.
+    }
+
+    fn show_notification(&self, _: PipelineId, _: NotificationId, _: NotificationData) {
+        // CEF clients have no way to display notifications, and pages are never allowed to
+        // show them.
+    }
+
+    fn close_notification(&self, _: PipelineId, _: NotificationId) {
+    }
+
+    fn request_position(&self, _: PositionRequest) {
+        // CEF leaves the location to the browser process, which isn't wired up.
+    }
+
+    fn mixed_content(&self, _: PipelineId, _: Url, _: MixedContentState) {
+    }
+
+    fn certificate_error(&self, _: PipelineId, _: &CertificateError) -> bool {
+        false
+    }
+
+    fn request_credentials(&self, _: CredentialsPrompt) {
+        // There is nobody to ask, so dropping the prompt declines.
+    }
+
     fn load_start(&self, back: bool, forward: bool) {
         let browser = self.cef_browser.borrow();
         let browser = match *browser {
@@ -464,6 +711,11 @@ impl WindowMethods for Window {
         }
     }
 
+    fn set_ime_caret_rect(&self, _: TypedRect<DevicePixel, f32>) {
+        // CEF has no way to tell the embedder where the caret is yet, so the embedder places
+        // its input method's windows itself.
+    }
+
     fn supports_clipboard(&self) -> bool {
         true
     }
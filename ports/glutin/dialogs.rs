@@ -0,0 +1,101 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+//! Questions for the user on behalf of pages and servers, asked with the desktop's own dialog
+//! tools: zenity on Linux and AppleScript on Mac OS X. Each dialog runs on a task of its own, so
+//! that the window keeps handling events while the user decides.
+
+use std::borrow::ToOwned;
+use std::process::Command;
+use util::task::spawn_named;
+
+/// Asks the user a yes-or-no question, and calls `answer` with their reply once they have given
+/// it. The answer is no if the dialog can't be shown.
+pub fn ask<F>(question: String, answer: F) where F: FnOnce(bool) + Send + 'static {
+    spawn_named("Dialog".to_owned(), move || answer(show_question(&question)))
+}
+
+/// Asks the user for a user name and password, and calls `answer` with them once they have
+/// given them, or with `None` if they declined or the dialog can't be shown.
+pub fn ask_credentials<F>(message: String, answer: F)
+                          where F: FnOnce(Option<(String, String)>) + Send + 'static {
+    spawn_named("Dialog".to_owned(), move || answer(show_credentials(&message)))
+}
+
+#[cfg(target_os = "linux")]
+fn show_question(question: &str) -> bool {
+    Command::new("zenity")
+        .arg("--question")
+        .arg("--title=Servo")
+        .arg("--no-markup")
+        .arg(format!("--text={}", question))
+        .status()
+        .map(|status| status.success())
+        .unwrap_or(false)
+}
+
+#[cfg(target_os = "macos")]
+fn show_question(question: &str) -> bool {
+    // The question is passed as an argument, so that it needn't be quoted in the script.
+    Command::new("osascript")
+        .arg("-e").arg("on run argv")
+        .arg("-e").arg("display dialog (item 1 of argv) with title \"Servo\" \
+                        buttons {\"Deny\", \"Allow\"} default button \"Allow\"")
+        .arg("-e").arg("end run")
+        .arg(question)
+        .output()
+        .map(|output| String::from_utf8_lossy(&output.stdout).contains("Allow"))
+        .unwrap_or(false)
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "macos")))]
+fn show_question(_: &str) -> bool {
+    false
+}
+
+#[cfg(target_os = "linux")]
+fn show_credentials(message: &str) -> Option<(String, String)> {
+    // zenity prints the user name and the password separated by a bar. A bar in the user name
+    // can't be told apart, but one in the password can.
+    let output = match Command::new("zenity")
+        .arg("--password")
+        .arg("--username")
+        .arg(format!("--title={}", message))
+        .output() {
+        Ok(ref output) if output.status.success() => output.stdout.clone(),
+        _ => return None,
+    };
+    let output = String::from_utf8_lossy(&output);
+    let output = output.trim_right_matches('\n');
+    output.find('|').map(|bar| (output[..bar].to_owned(), output[bar + 1..].to_owned()))
+}
+
+#[cfg(target_os = "macos")]
+fn show_credentials(message: &str) -> Option<(String, String)> {
+    // Each answer is printed on a line of its own; cancelling either dialog fails the script.
+    let output = match Command::new("osascript")
+        .arg("-e").arg("on run argv")
+        .arg("-e").arg("set username to text returned of (display dialog (item 1 of argv) \
+                        with title \"Servo\" default answer \"\")")
+        .arg("-e").arg("set password to text returned of (display dialog \"Password:\" \
+                        with title \"Servo\" default answer \"\" with hidden answer)")
+        .arg("-e").arg("return username & linefeed & password")
+        .arg("-e").arg("end run")
+        .arg(message)
+        .output() {
+        Ok(ref output) if output.status.success() => output.stdout.clone(),
+        _ => return None,
+    };
+    let output = String::from_utf8_lossy(&output);
+    let mut lines = output.trim_right_matches('\n').splitn(2, '\n');
+    match (lines.next(), lines.next()) {
+        (Some(username), Some(password)) => Some((username.to_owned(), password.to_owned())),
+        _ => None,
+    }
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "macos")))]
+fn show_credentials(_: &str) -> Option<(String, String)> {
+    None
+}
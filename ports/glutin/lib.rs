@@ -6,6 +6,7 @@
 
 #![feature(box_syntax)]
 
+#[cfg(target_os="android")] extern crate android_glue;
 #[macro_use] extern crate bitflags;
 #[cfg(target_os="macos")] extern crate cgl;
 extern crate compositing;
@@ -16,6 +17,7 @@ extern crate layers;
 extern crate libc;
 extern crate msg;
 extern crate net;
+extern crate net_traits;
 #[cfg(feature = "window")] extern crate script_traits;
 extern crate time;
 extern crate util;
@@ -29,6 +31,8 @@ use std::rc::Rc;
 use window::Window;
 use util::opts;
 
+#[cfg(feature = "window")] mod dialogs;
+#[cfg(feature = "window")] mod notifications;
 pub mod window;
 
 pub type WindowID = glutin::WindowID;
@@ -0,0 +1,178 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+//! Shows pages' notifications on the desktop: through the freedesktop.org notification service
+//! with notify-send and gdbus on Linux, and through AppleScript on Mac OS X. Each notification
+//! is watched by a task of its own, which reports when the user clicks or dismisses it.
+
+use compositing::windowing::WindowEvent;
+use msg::constellation_msg::{NotificationData, NotificationEventType, NotificationId, PipelineId};
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+
+use window::WindowEventSender;
+
+/// The notifications on display, with the IDs the notification service gave them.
+pub struct Notifications {
+    shown: Arc<Mutex<HashMap<(PipelineId, NotificationId), u32>>>,
+}
+
+impl Notifications {
+    pub fn new() -> Notifications {
+        Notifications {
+            shown: Arc::new(Mutex::new(HashMap::new())),
+        }
+    }
+
+    pub fn show(&self,
+                pipeline_id: PipelineId,
+                id: NotificationId,
+                notification: NotificationData,
+                events: WindowEventSender) {
+        platform::show(self.shown.clone(), pipeline_id, id, notification, events)
+    }
+
+    pub fn close(&self, pipeline_id: PipelineId, id: NotificationId) {
+        if let Some(server_id) = self.shown.lock().unwrap().remove(&(pipeline_id, id)) {
+            platform::close(server_id)
+        }
+    }
+}
+
+/// Tells the page that the user interacted with one of its notifications.
+fn send_event(events: &WindowEventSender,
+              pipeline_id: PipelineId,
+              id: NotificationId,
+              event_type: NotificationEventType) {
+    events.send(WindowEvent::NotificationEvent(pipeline_id, id, event_type))
+}
+
+#[cfg(target_os = "linux")]
+mod platform {
+    use msg::constellation_msg::{NotificationData, NotificationEventType, NotificationId};
+    use msg::constellation_msg::PipelineId;
+    use std::borrow::ToOwned;
+    use std::collections::HashMap;
+    use std::io::{BufRead, BufReader};
+    use std::process::{Command, Stdio};
+    use std::sync::{Arc, Mutex};
+    use util::task::spawn_named;
+    use window::WindowEventSender;
+
+    use super::send_event;
+
+    /// The action that notify-send reports when the body of the notification is clicked.
+    const DEFAULT_ACTION: &'static str = "default";
+
+    pub fn show(shown: Arc<Mutex<HashMap<(PipelineId, NotificationId), u32>>>,
+                pipeline_id: PipelineId,
+                id: NotificationId,
+                notification: NotificationData,
+                events: WindowEventSender) {
+        spawn_named("Notification".to_owned(), move || {
+            // notify-send prints the ID of the notification, then the name of the action the
+            // user chose, if any, and exits once the notification is gone.
+            let child = Command::new("notify-send")
+                .arg("--app-name=Servo")
+                .arg("--print-id")
+                .arg("--wait")
+                .arg(format!("--action={}=Open", DEFAULT_ACTION))
+                .arg("--")
+                .arg(&notification.title)
+                .arg(&notification.body)
+                .stdout(Stdio::piped())
+                .spawn();
+            let mut child = match child {
+                Ok(child) => child,
+                // Without notify-send, there is nothing to show the notification with.
+                Err(_) => return send_event(&events, pipeline_id, id, NotificationEventType::Close),
+            };
+
+            let mut clicked = false;
+            if let Some(stdout) = child.stdout.take() {
+                let mut lines = BufReader::new(stdout).lines();
+                if let Some(Ok(server_id)) = lines.next() {
+                    if let Ok(server_id) = server_id.trim().parse() {
+                        shown.lock().unwrap().insert((pipeline_id, id), server_id);
+                    }
+                }
+                for line in lines {
+                    if let Ok(line) = line {
+                        clicked = clicked || line.trim() == DEFAULT_ACTION;
+                    }
+                }
+            }
+            let _ = child.wait();
+
+            shown.lock().unwrap().remove(&(pipeline_id, id));
+            if clicked {
+                send_event(&events, pipeline_id, id, NotificationEventType::Click);
+            }
+            send_event(&events, pipeline_id, id, NotificationEventType::Close);
+        });
+    }
+
+    pub fn close(server_id: u32) {
+        let _ = Command::new("gdbus")
+            .arg("call")
+            .arg("--session")
+            .arg("--dest=org.freedesktop.Notifications")
+            .arg("--object-path=/org/freedesktop/Notifications")
+            .arg("--method=org.freedesktop.Notifications.CloseNotification")
+            .arg(server_id.to_string())
+            .status();
+    }
+}
+
+#[cfg(target_os = "macos")]
+mod platform {
+    //! Notification Center doesn't report clicks to scripts, so pages never hear back.
+
+    use msg::constellation_msg::{NotificationData, NotificationId, PipelineId};
+    use std::collections::HashMap;
+    use std::process::Command;
+    use std::sync::{Arc, Mutex};
+    use window::WindowEventSender;
+
+    pub fn show(_: Arc<Mutex<HashMap<(PipelineId, NotificationId), u32>>>,
+                _: PipelineId,
+                _: NotificationId,
+                notification: NotificationData,
+                _: WindowEventSender) {
+        // The text is passed as arguments, so that it needn't be quoted in the script.
+        let _ = Command::new("osascript")
+            .arg("-e").arg("on run argv")
+            .arg("-e").arg("display notification (item 2 of argv) with title (item 1 of argv)")
+            .arg("-e").arg("end run")
+            .arg(&notification.title)
+            .arg(&notification.body)
+            .spawn();
+    }
+
+    pub fn close(_: u32) {
+    }
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "macos")))]
+mod platform {
+    use msg::constellation_msg::{NotificationData, NotificationEventType, NotificationId};
+    use msg::constellation_msg::PipelineId;
+    use std::collections::HashMap;
+    use std::sync::{Arc, Mutex};
+    use window::WindowEventSender;
+
+    use super::send_event;
+
+    /// There is no notification service to show notifications with, so they are closed at once.
+    pub fn show(_: Arc<Mutex<HashMap<(PipelineId, NotificationId), u32>>>,
+                pipeline_id: PipelineId,
+                id: NotificationId,
+                _: NotificationData,
+                events: WindowEventSender) {
+        send_event(&events, pipeline_id, id, NotificationEventType::Close)
+    }
+
+    pub fn close(_: u32) {
+    }
+}
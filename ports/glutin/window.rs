@@ -5,7 +5,9 @@
 //! A windowing implementation using glutin.
 
 use compositing::compositor_task::{self, CompositorProxy, CompositorReceiver};
-use compositing::windowing::{WindowEvent, WindowMethods};
+use compositing::windowing::{DEFAULT_REFRESH_INTERVAL, PermissionPrompt, WindowEvent};
+use compositing::windowing::{CredentialsPrompt, PositionRequest, VblankSource, WindowMethods};
+use euclid::rect::TypedRect;
 use euclid::scale_factor::ScaleFactor;
 use euclid::size::{Size2D, TypedSize2D};
 use gleam::gl;
@@ -14,8 +16,13 @@ use layers::geometry::DevicePixel;
 use layers::platform::surface::NativeDisplay;
 use msg::constellation_msg;
 use msg::constellation_msg::Key;
+use msg::constellation_msg::{CertificateError, Credentials};
+use msg::constellation_msg::{MixedContentState, NotificationData};
+use msg::constellation_msg::{NotificationId, PipelineId};
 use net::net_error_list::NetError;
+use net_traits::image::base::Image;
 use std::rc::Rc;
+use std::sync::Arc;
 use std::sync::mpsc::{channel, Sender};
 use url::Url;
 use util::cursor::Cursor;
@@ -30,14 +37,36 @@ use euclid::point::Point2D;
 #[cfg(feature = "window")]
 use glutin::{Api, ElementState, Event, GlRequest, MouseButton, VirtualKeyCode, MouseScrollDelta};
 #[cfg(feature = "window")]
-use msg::constellation_msg::{KeyState, NONE, CONTROL, SHIFT, ALT, SUPER};
+use msg::constellation_msg::{ImeEvent, KeyState, NONE, CONTROL, SHIFT, ALT, SUPER};
 #[cfg(feature = "window")]
 use std::cell::{Cell, RefCell};
 #[cfg(feature = "window")]
 use util::opts;
 
+#[cfg(feature = "window")]
+use dialogs;
+#[cfg(feature = "window")]
+use msg::constellation_msg::PermissionName;
+#[cfg(feature = "window")]
+use net_traits::serialize_origin;
+#[cfg(feature = "window")]
+use notifications::Notifications;
+#[cfg(feature = "window")]
+use std::collections::HashSet;
+#[cfg(feature = "window")]
+use std::sync::mpsc::Receiver;
+
+#[cfg(all(feature = "window", target_os="android"))]
+use android_glue;
+#[cfg(all(feature = "window", target_os="android"))]
+use std::borrow::ToOwned;
+#[cfg(all(feature = "window", target_os="android"))]
+use util::task::spawn_named;
+
 #[cfg(all(feature = "headless", target_os="linux"))]
 use std::ptr;
+#[cfg(feature = "headless")]
+use std::sync::{Condvar, Mutex};
 
 #[cfg(feature = "window")]
 static mut g_nested_event_loop_listener: Option<*mut (NestedEventLoopListener + 'static)> = None;
@@ -83,6 +112,16 @@ pub struct Window {
 
     mouse_pos: Cell<Point2D<i32>>,
     key_modifiers: Cell<KeyModifiers>,
+
+    /// Events sent by other tasks, such as those watching notifications.
+    event_sender: WindowEventSender,
+    event_receiver: Receiver<WindowEvent>,
+
+    notifications: Notifications,
+
+    /// The pipelines whose user has been asked whether to load the insecure content they
+    /// requested, so that each page only asks once.
+    mixed_content_prompted: RefCell<HashSet<PipelineId>>,
 }
 
 #[cfg(feature = "window")]
@@ -104,6 +143,13 @@ impl Window {
 
         Window::load_gl_functions(&glutin_window);
 
+        let (event_sender, event_receiver) = channel();
+        let event_sender = WindowEventSender {
+            sender: event_sender,
+            window_proxy: create_window_proxy(&glutin_window),
+        };
+        Window::watch_lifecycle(event_sender.clone());
+
         let window = Window {
             window: glutin_window,
             event_queue: RefCell::new(vec!()),
@@ -112,6 +158,13 @@ impl Window {
 
             mouse_pos: Cell::new(Point2D::new(0, 0)),
             key_modifiers: Cell::new(KeyModifiers::empty()),
+
+            event_sender: event_sender,
+            event_receiver: event_receiver,
+
+            notifications: Notifications::new(),
+
+            mixed_content_prompted: RefCell::new(HashSet::new()),
         };
 
         gl::clear_color(0.6, 0.6, 0.6, 1.0);
@@ -157,6 +210,27 @@ impl Window {
     fn load_gl_functions(_: &glutin::Window) {
     }
 
+    /// Has the compositor let go of its GL context while Android takes the window's surface
+    /// away, as when the activity is paused, and take it up again once there is a new one.
+    #[cfg(target_os="android")]
+    fn watch_lifecycle(events: WindowEventSender) {
+        let (sender, receiver) = channel();
+        android_glue::add_sender(sender);
+        spawn_named("AndroidLifecycle".to_owned(), move || {
+            for event in receiver.iter() {
+                match event {
+                    android_glue::Event::TermWindow => events.send(WindowEvent::Suspend),
+                    android_glue::Event::InitWindow => events.send(WindowEvent::Resume),
+                    _ => {}
+                }
+            }
+        });
+    }
+
+    #[cfg(not(target_os="android"))]
+    fn watch_lifecycle(_: WindowEventSender) {
+    }
+
     fn handle_window_event(&self, event: glutin::Event) -> bool {
         match event {
             Event::KeyboardInput(element_state, _scan_code, virtual_key_code) => {
@@ -211,10 +285,14 @@ impl Window {
                         MouseScrollDelta::LineDelta(_, dy) => dy,
                         MouseScrollDelta::PixelDelta(_, dy) => dy
                     };
+                    let mouse_pos = self.mouse_pos.get();
+                    let focal_point = Point2D::typed(mouse_pos.x as f32, mouse_pos.y as f32);
                     if dy < 0.0 {
-                        self.event_queue.borrow_mut().push(WindowEvent::PinchZoom(1.0/1.1));
+                        self.event_queue.borrow_mut().push(
+                            WindowEvent::PinchZoom(1.0/1.1, focal_point));
                     } else if dy > 0.0 {
-                        self.event_queue.borrow_mut().push(WindowEvent::PinchZoom(1.1));
+                        self.event_queue.borrow_mut().push(
+                            WindowEvent::PinchZoom(1.1, focal_point));
                     }
                 } else {
                     match delta {
@@ -228,6 +306,14 @@ impl Window {
             Event::Refresh => {
                 self.event_queue.borrow_mut().push(WindowEvent::Refresh);
             }
+            Event::ReceivedCharacter(ch) => {
+                // Characters that no key makes, like those composed with dead keys or an input
+                // method, are committed as they are.
+                if ch as u32 >= 0x80 && !ch.is_control() {
+                    let event = ImeEvent::Commit(ch.to_string());
+                    self.event_queue.borrow_mut().push(WindowEvent::ImeEvent(event));
+                }
+            }
             _ => {}
         }
 
@@ -358,6 +444,14 @@ impl Window {
         }
 
         events.extend(mem::replace(&mut *self.event_queue.borrow_mut(), Vec::new()).into_iter());
+        while let Ok(event) = self.event_receiver.try_recv() {
+            // The compositor resumes with whichever context is current, so make it the one
+            // drawing to the new surface.
+            if let WindowEvent::Resume = event {
+                unsafe { self.window.make_current() };
+            }
+            events.push(event)
+        }
         events
     }
 
@@ -496,13 +590,36 @@ impl Window {
 // WindowProxy is not implemented for android yet
 
 #[cfg(all(feature = "window", target_os="android"))]
-fn create_window_proxy(_: &Rc<Window>) -> Option<glutin::WindowProxy> {
+fn create_window_proxy(_: &glutin::Window) -> Option<glutin::WindowProxy> {
     None
 }
 
 #[cfg(all(feature = "window", not(target_os="android")))]
-fn create_window_proxy(window: &Rc<Window>) -> Option<glutin::WindowProxy> {
-    Some(window.window.create_window_proxy())
+fn create_window_proxy(window: &glutin::Window) -> Option<glutin::WindowProxy> {
+    Some(window.create_window_proxy())
+}
+
+/// Sends events to a window from other tasks, waking its event loop.
+#[cfg(feature = "window")]
+#[derive(Clone)]
+pub struct WindowEventSender {
+    sender: Sender<WindowEvent>,
+    window_proxy: Option<glutin::WindowProxy>,
+}
+
+#[cfg(feature = "window")]
+unsafe impl Send for WindowEventSender {}
+
+#[cfg(feature = "window")]
+impl WindowEventSender {
+    pub fn send(&self, event: WindowEvent) {
+        // The window may have been closed in the meantime.
+        if self.sender.send(event).is_ok() {
+            if let Some(ref window_proxy) = self.window_proxy {
+                window_proxy.wakeup_event_loop()
+            }
+        }
+    }
 }
 
 #[cfg(feature = "window")]
@@ -522,12 +639,28 @@ impl WindowMethods for Window {
         self.window.swap_buffers()
     }
 
+    fn refresh_interval(&self) -> u64 {
+        DEFAULT_REFRESH_INTERVAL
+    }
+
+    fn vblank_source(&self) -> Option<Box<VblankSource>> {
+        None
+    }
+
+    fn supports_partial_present(&self) -> bool {
+        false
+    }
+
+    fn present_damage(&self, _: TypedRect<DevicePixel, u32>) {
+        self.present()
+    }
+
     fn create_compositor_channel(window: &Option<Rc<Window>>)
                                  -> (Box<CompositorProxy+Send>, Box<CompositorReceiver>) {
         let (sender, receiver) = channel();
 
         let window_proxy = match window {
-            &Some(ref window) => create_window_proxy(window),
+            &Some(ref window) => create_window_proxy(&window.window),
             &None => None,
         };
 
@@ -613,6 +746,100 @@ impl WindowMethods for Window {
     fn set_favicon(&self, _: Url) {
     }
 
+    #[cfg(target_os="linux")]
+    fn set_favicon_image(&self, image: Arc<Image>) {
+        use libc::{c_char, c_int, c_ulong};
+        use net_traits::image::base::argb_pixels;
+        use x11::xlib;
+
+        let pixels = match argb_pixels(&image) {
+            Some(pixels) => pixels,
+            None => return,
+        };
+        // _NET_WM_ICON is the width and height followed by the pixels, each stored in a long
+        // whatever the size of a long is.
+        // http://standards.freedesktop.org/wm-spec/latest/ar01s05.html
+        let mut data: Vec<c_ulong> = vec!(image.width as c_ulong, image.height as c_ulong);
+        data.extend(pixels.into_iter().map(|pixel| pixel as c_ulong));
+        unsafe {
+            let display = self.window.platform_display() as *mut xlib::Display;
+            let window = self.window.platform_window() as xlib::Window;
+            let property = xlib::XInternAtom(display,
+                                             b"_NET_WM_ICON\0".as_ptr() as *const c_char,
+                                             xlib::False);
+            xlib::XChangeProperty(display, window, property, xlib::XA_CARDINAL, 32,
+                                  xlib::PropModeReplace, data.as_ptr() as *const u8,
+                                  data.len() as c_int);
+            xlib::XFlush(display);
+        }
+    }
+
+    #[cfg(not(target_os="linux"))]
+    fn set_favicon_image(&self, _: Arc<Image>) {
+    }
+
+    fn request_permission(&self, prompt: PermissionPrompt) {
+        let feature = match prompt.name {
+            PermissionName::Notifications => "show notifications",
+            PermissionName::Geolocation => "use your location",
+        };
+        let question = format!("Allow {} to {}?", serialize_origin(&prompt.url), feature);
+        dialogs::ask(question, move |granted| prompt.respond(granted));
+    }
+
+    fn show_notification(&self,
+                         pipeline_id: PipelineId,
+                         id: NotificationId,
+                         notification: NotificationData) {
+        self.notifications.show(pipeline_id, id, notification, self.event_sender.clone());
+    }
+
+    fn close_notification(&self, pipeline_id: PipelineId, id: NotificationId) {
+        self.notifications.close(pipeline_id, id);
+    }
+
+    fn request_position(&self, _: PositionRequest) {
+        // There is no location service to ask, so dropping the request reports that the
+        // position is unavailable.
+    }
+
+    fn mixed_content(&self, pipeline_id: PipelineId, url: Url, state: MixedContentState) {
+        if state != MixedContentState::Blocked ||
+           !self.mixed_content_prompted.borrow_mut().insert(pipeline_id) {
+            return
+        }
+        let question = format!("This secure page tried to load insecure content from {}. \
+                                Reload the page with it?", serialize_origin(&url));
+        let events = self.event_sender.clone();
+        dialogs::ask(question, move |allowed| {
+            if allowed {
+                events.send(WindowEvent::AllowMixedContent(pipeline_id))
+            }
+        });
+    }
+
+    fn certificate_error(&self, _: PipelineId, _: &CertificateError) -> bool {
+        false
+    }
+
+    fn request_credentials(&self, prompt: CredentialsPrompt) {
+        let message = if prompt.request.proxy {
+            format!("The proxy {} asks for a user name and password for \"{}\".",
+                    prompt.request.host, prompt.request.realm)
+        } else {
+            format!("{} asks for a user name and password for \"{}\".",
+                    prompt.request.host, prompt.request.realm)
+        };
+        dialogs::ask_credentials(message, move |answer| {
+            prompt.respond(answer.map(|(username, password)| {
+                Credentials {
+                    username: username,
+                    password: password,
+                }
+            }))
+        });
+    }
+
     fn prepare_for_composite(&self, _width: usize, _height: usize) -> bool {
         true
     }
@@ -680,6 +907,11 @@ impl WindowMethods for Window {
         }
     }
 
+    fn set_ime_caret_rect(&self, _: TypedRect<DevicePixel, f32>) {
+        // glutin doesn't let the input method's windows be moved, so they stay where the
+        // platform puts them.
+    }
+
     fn supports_clipboard(&self) -> bool {
         true
     }
@@ -692,8 +924,17 @@ pub struct Window {
     context: glutin::HeadlessContext,
     width: u32,
     height: u32,
+    /// Set by the compositor proxy when it sends a message, since there are no window system
+    /// events to wake the event loop.
+    wakeup: Arc<(Mutex<bool>, Condvar)>,
 }
 
+/// How long a headless window's event loop sleeps when the compositor sends nothing, in
+/// milliseconds. The compositor may still have a frame to draw, for instance while it waits
+/// for the page to become stable before saving it.
+#[cfg(feature = "headless")]
+const HEADLESS_FRAME_INTERVAL: u32 = 16;
+
 #[cfg(feature = "headless")]
 impl Window {
     pub fn new(_is_foreground: bool,
@@ -711,12 +952,19 @@ impl Window {
             context: headless_context,
             width: window_size.width,
             height: window_size.height,
+            wakeup: Arc::new((Mutex::new(false), Condvar::new())),
         };
 
         Rc::new(window)
     }
 
     pub fn wait_events(&self) -> Vec<WindowEvent> {
+        let &(ref woken, ref condvar) = &*self.wakeup;
+        let mut woken = woken.lock().unwrap();
+        if !*woken {
+            woken = condvar.wait_timeout_ms(woken, HEADLESS_FRAME_INTERVAL).unwrap().0;
+        }
+        *woken = false;
         vec![WindowEvent::Idle]
     }
 
@@ -742,13 +990,33 @@ impl WindowMethods for Window {
     fn present(&self) {
     }
 
-    fn create_compositor_channel(_: &Option<Rc<Window>>)
+    fn refresh_interval(&self) -> u64 {
+        DEFAULT_REFRESH_INTERVAL
+    }
+
+    fn vblank_source(&self) -> Option<Box<VblankSource>> {
+        None
+    }
+
+    fn supports_partial_present(&self) -> bool {
+        false
+    }
+
+    fn present_damage(&self, _: TypedRect<DevicePixel, u32>) {
+        self.present()
+    }
+
+    fn create_compositor_channel(window: &Option<Rc<Window>>)
                                  -> (Box<CompositorProxy+Send>, Box<CompositorReceiver>) {
         let (sender, receiver) = channel();
 
-        (box GlutinCompositorProxy {
+        let wakeup = match *window {
+            Some(ref window) => window.wakeup.clone(),
+            None => Arc::new((Mutex::new(false), Condvar::new())),
+        };
+        (box HeadlessCompositorProxy {
              sender: sender,
-             window_proxy: None,
+             wakeup: wakeup,
          } as Box<CompositorProxy+Send>,
          box receiver as Box<CompositorReceiver>)
     }
@@ -778,6 +1046,33 @@ impl WindowMethods for Window {
     fn set_favicon(&self, _: Url) {
     }
 
+    fn set_favicon_image(&self, _: Arc<Image>) {
+    }
+
+    fn request_permission(&self, _: PermissionPrompt) {
+        // There is nobody to ask, so dropping the prompt denies the permission.
+    }
+
+    fn show_notification(&self, _: PipelineId, _: NotificationId, _: NotificationData) {
+    }
+
+    fn close_notification(&self, _: PipelineId, _: NotificationId) {
+    }
+
+    fn request_position(&self, _: PositionRequest) {
+    }
+
+    fn mixed_content(&self, _: PipelineId, _: Url, _: MixedContentState) {
+    }
+
+    fn certificate_error(&self, _: PipelineId, _: &CertificateError) -> bool {
+        false
+    }
+
+    fn request_credentials(&self, _: CredentialsPrompt) {
+        // There is nobody to ask, so dropping the prompt declines.
+    }
+
     fn prepare_for_composite(&self, _width: usize, _height: usize) -> bool {
         true
     }
@@ -791,19 +1086,25 @@ impl WindowMethods for Window {
     fn handle_key(&self, _: Key, _: constellation_msg::KeyModifiers) {
     }
 
+    fn set_ime_caret_rect(&self, _: TypedRect<DevicePixel, f32>) {
+    }
+
     fn supports_clipboard(&self) -> bool {
         false
     }
 }
 
+#[cfg(feature = "window")]
 struct GlutinCompositorProxy {
     sender: Sender<compositor_task::Msg>,
     window_proxy: Option<glutin::WindowProxy>,
 }
 
 // TODO: Should this be implemented here or upstream in glutin::WindowProxy?
+#[cfg(feature = "window")]
 unsafe impl Send for GlutinCompositorProxy {}
 
+#[cfg(feature = "window")]
 impl CompositorProxy for GlutinCompositorProxy {
     fn send(&mut self, msg: compositor_task::Msg) {
         // Send a message and kick the OS event loop awake.
@@ -820,6 +1121,29 @@ impl CompositorProxy for GlutinCompositorProxy {
     }
 }
 
+/// A compositor proxy that wakes a headless window's event loop when it sends a message.
+#[cfg(feature = "headless")]
+struct HeadlessCompositorProxy {
+    sender: Sender<compositor_task::Msg>,
+    wakeup: Arc<(Mutex<bool>, Condvar)>,
+}
+
+#[cfg(feature = "headless")]
+impl CompositorProxy for HeadlessCompositorProxy {
+    fn send(&mut self, msg: compositor_task::Msg) {
+        self.sender.send(msg).unwrap();
+        let &(ref woken, ref condvar) = &*self.wakeup;
+        *woken.lock().unwrap() = true;
+        condvar.notify_one();
+    }
+    fn clone_compositor_proxy(&self) -> Box<CompositorProxy+Send> {
+        box HeadlessCompositorProxy {
+            sender: self.sender.clone(),
+            wakeup: self.wakeup.clone(),
+        } as Box<CompositorProxy+Send>
+    }
+}
+
 // These functions aren't actually called. They are here as a link
 // hack because Skia references them.
 
@@ -134,6 +134,7 @@ fn read_input_device(device_path: &Path,
     let mut first_y = 0;
 
     let mut last_dist: f32 = 0f32;
+    let mut pinching = false;
     let mut touch_count: i32 = 0;
     let mut current_slot: usize = 0;
     // XXX: Need to use the real dimensions of the screen
@@ -163,6 +164,10 @@ fn read_input_device(device_path: &Path,
                         tracking_updated = false;
                         if slotA.tracking_id == -1 {
                             println!("Touch up");
+                            if pinching {
+                                pinching = false;
+                                sender.send(WindowEvent::PinchZoomEnd).ok().unwrap();
+                            }
                             let delta_x = slotA.x - first_x;
                             let delta_y = slotA.y - first_y;
                             let dist = delta_x * delta_x + delta_y * delta_y;
@@ -203,9 +208,13 @@ fn read_input_device(device_path: &Path,
                             println!("Zooming {} {} {} {}",
                                      cur_dist, last_dist, screen_dist,
                                      ((screen_dist + (cur_dist - last_dist))/screen_dist));
+                            let focal_point = Point2D::typed((slotA.x + slotB.x) as f32 / 2.0,
+                                                             (slotA.y + slotB.y) as f32 / 2.0);
                             sender.send(
-                                WindowEvent::Zoom((screen_dist + (cur_dist - last_dist))/screen_dist)).ok().unwrap();
+                                WindowEvent::PinchZoom((screen_dist + (cur_dist - last_dist))/screen_dist,
+                                                       focal_point)).ok().unwrap();
                             last_dist = cur_dist;
+                            pinching = true;
                         }
                     }
                 },
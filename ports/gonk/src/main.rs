@@ -39,6 +39,7 @@ extern crate layers;
 extern crate egl;
 extern crate url;
 extern crate net;
+extern crate net_traits;
 extern crate env_logger;
 
 #[link(name = "stlport")]
@@ -5,21 +5,28 @@
 //! A windowing implementation using gonk interfaces.
 
 use compositing::compositor_task::{self, CompositorProxy, CompositorReceiver};
-use compositing::windowing::{WindowEvent, WindowMethods};
+use compositing::windowing::{DEFAULT_REFRESH_INTERVAL, PermissionPrompt, WindowEvent};
+use compositing::windowing::{CredentialsPrompt, PositionRequest, VblankSource, WindowMethods};
+use euclid::rect::TypedRect;
 use euclid::scale_factor::ScaleFactor;
 use euclid::size::{Size2D, TypedSize2D};
 use layers::geometry::DevicePixel;
 use layers::platform::surface::NativeDisplay;
 use libc::c_int;
 use msg::constellation_msg::{Key, KeyModifiers};
+use msg::constellation_msg::CertificateError;
+use msg::constellation_msg::{MixedContentState, NotificationData};
+use msg::constellation_msg::{NotificationId, PipelineId};
 use net::net_error_list::NetError;
+use net_traits::image::base::Image;
 use std::sync::mpsc::{channel, Sender, Receiver};
 use std::rc::Rc;
+use std::sync::{Arc, Mutex};
 use std::mem::transmute;
 use std::mem::size_of;
 use std::mem::zeroed;
 use std::ptr;
-use std::ffi::CString;
+use std::ffi::{CStr, CString};
 use url::Url;
 use util::cursor::Cursor;
 use util::geometry::ScreenPx;
@@ -205,6 +212,8 @@ pub struct hwc_procs {
     hotplug: extern fn(*const hwc_procs, c_int, c_int),
 }
 
+const HWC_EVENT_VSYNC: c_int = 0;
+
 const HWC_DISPLAY_NO_ATTRIBUTE: u32 = 0;
 const HWC_DISPLAY_VSYNC_PERIOD: u32 = 1;
 const HWC_DISPLAY_WIDTH: u32 = 2;
@@ -312,6 +321,24 @@ extern {
     fn autosuspend_disable();
 }
 
+// EGL/egl.h and EGL/eglext.h
+
+const EGL_SWAP_BEHAVIOR_PRESERVED_BIT: EGLint = 0x0400;
+const EGL_EXTENSIONS: EGLint = 0x3055;
+const EGL_SWAP_BEHAVIOR: EGLint = 0x3093;
+const EGL_BUFFER_PRESERVED: EGLint = 0x3094;
+
+/// `eglSwapBuffersWithDamageKHR`, from EGL_KHR_swap_buffers_with_damage.
+type SwapBuffersWithDamage = extern fn(EGLDisplay, EGLSurface, *const EGLint, EGLint) -> u32;
+
+#[link(name = "EGL")]
+extern {
+    fn eglQueryString(dpy: EGLDisplay, name: EGLint) -> *const c_char;
+    fn eglGetProcAddress(procname: *const c_char) -> *const c_void;
+    fn eglSurfaceAttrib(dpy: EGLDisplay, surface: EGLSurface, attribute: EGLint, value: EGLint)
+                        -> u32;
+}
+
 #[allow(unused_variables)]
 extern fn setSwapInterval(base: *mut ANativeWindow,
                           interval: c_int) -> c_int {
@@ -638,6 +665,10 @@ pub struct Window {
     dpy: EGLDisplay,
     ctx: EGLContext,
     surf: EGLSurface,
+    /// Whether the surface keeps the contents of its back buffer from one frame to the next.
+    preserves_back_buffer: bool,
+    /// How to tell EGL which part of the surface has changed, if it supports that.
+    swap_buffers_with_damage: Option<SwapBuffersWithDamage>,
 }
 
 impl Window {
@@ -699,27 +730,39 @@ impl Window {
 
         assert!(ret1 == 1, "Failed to initialize EGL!");
 
-        let conf_attr =
-            [egl::EGL_SURFACE_TYPE, egl::EGL_WINDOW_BIT,
-             egl::EGL_RENDERABLE_TYPE, egl::EGL_OPENGL_ES2_BIT,
-             egl::EGL_RED_SIZE, 8,
-             egl::EGL_GREEN_SIZE, 8,
-             egl::EGL_BLUE_SIZE, 8,
-             egl::EGL_ALPHA_SIZE, 0,
-             egl::EGL_NONE, 0];
-
+        // Prefer a config whose surfaces can keep their back buffer, so that only the parts of
+        // the window that change have to be drawn again.
         let mut config: EGLConfig = unsafe { transmute(0isize) };
         let mut num_config: EGLint = 0;
-
-        let ret2 = unsafe {
-            egl::ChooseConfig(dpy, transmute(conf_attr.as_ptr()), &mut config, 1, &mut num_config)
-        };
-
-        assert!(ret2 == 1, "Failed to choose a config");
+        for &surface_type in [egl::EGL_WINDOW_BIT | EGL_SWAP_BEHAVIOR_PRESERVED_BIT,
+                              egl::EGL_WINDOW_BIT].iter() {
+            let conf_attr =
+                [egl::EGL_SURFACE_TYPE, surface_type,
+                 egl::EGL_RENDERABLE_TYPE, egl::EGL_OPENGL_ES2_BIT,
+                 egl::EGL_RED_SIZE, 8,
+                 egl::EGL_GREEN_SIZE, 8,
+                 egl::EGL_BLUE_SIZE, 8,
+                 egl::EGL_ALPHA_SIZE, 0,
+                 egl::EGL_NONE, 0];
+
+            let ret2 = unsafe {
+                egl::ChooseConfig(dpy, transmute(conf_attr.as_ptr()), &mut config, 1,
+                                  &mut num_config)
+            };
+
+            assert!(ret2 == 1, "Failed to choose a config");
+            if num_config > 0 {
+                break
+            }
+        }
 
         let usage = GRALLOC_USAGE_HW_FB | GRALLOC_USAGE_HW_RENDER | GRALLOC_USAGE_HW_COMPOSER;
         let native_window = GonkNativeWindow::new(alloc_dev, hwc_device, width, height, usage);
         let eglwindow = unsafe { egl::CreateWindowSurface(dpy, config, transmute(native_window), ptr::null()) };
+        let preserves_back_buffer = unsafe {
+            eglSurfaceAttrib(dpy, eglwindow, EGL_SWAP_BEHAVIOR, EGL_BUFFER_PRESERVED) == 1
+        };
+        let swap_buffers_with_damage = Window::swap_buffers_with_damage(dpy);
 
         let ctx_attr =
             [egl::EGL_CONTEXT_CLIENT_VERSION, 2,
@@ -763,16 +806,98 @@ impl Window {
             dpy: dpy,
             ctx: ctx,
             surf: eglwindow,
+            preserves_back_buffer: preserves_back_buffer,
+            swap_buffers_with_damage: swap_buffers_with_damage,
         };
 
         Rc::new(window)
     }
 
+    /// Looks up `eglSwapBuffersWithDamageKHR`, if the display supports it.
+    fn swap_buffers_with_damage(dpy: EGLDisplay) -> Option<SwapBuffersWithDamage> {
+        unsafe {
+            let extensions = eglQueryString(dpy, EGL_EXTENSIONS);
+            if extensions.is_null() {
+                return None
+            }
+            let extensions = String::from_utf8_lossy(CStr::from_ptr(extensions).to_bytes());
+            if !extensions.split(' ').any(|name| name == "EGL_KHR_swap_buffers_with_damage") {
+                return None
+            }
+            let name = CString::new("eglSwapBuffersWithDamageKHR").unwrap();
+            let function = eglGetProcAddress(name.as_ptr());
+            if function.is_null() {
+                None
+            } else {
+                Some(transmute(function))
+            }
+        }
+    }
+
     pub fn wait_events(&self) -> Vec<WindowEvent> {
         vec![self.event_recv.recv().unwrap()]
     }
 }
 
+/// The callbacks given to the hardware composer, followed by the channel that the vertical blanks
+/// it reports are passed on to. The composer only hands the callbacks back, so the channel is
+/// found from their address.
+#[repr(C)]
+struct VsyncProcs {
+    procs: hwc_procs,
+    sender: Mutex<Sender<u64>>,
+}
+
+extern fn hwc_invalidate(_: *const hwc_procs) {
+}
+
+extern fn hwc_vsync(procs: *const hwc_procs, _display: c_int, timestamp: i64) {
+    let procs: &VsyncProcs = unsafe { transmute(procs) };
+    let _ = procs.sender.lock().unwrap().send(timestamp as u64);
+}
+
+extern fn hwc_hotplug(_: *const hwc_procs, _: c_int, _: c_int) {
+}
+
+/// The vertical blanks of the primary display, as the hardware composer reports them. Their
+/// timestamps are on the monotonic clock, as `precise_time_ns` reads it.
+pub struct HwcVblankSource {
+    receiver: Receiver<u64>,
+}
+
+impl HwcVblankSource {
+    /// Has the hardware composer report vertical blanks. The callbacks stay registered for as
+    /// long as the composer runs, so they are never freed.
+    fn new(hwc_dev: *mut hwc_composer_device) -> HwcVblankSource {
+        let (sender, receiver) = channel();
+        let procs = box VsyncProcs {
+            procs: hwc_procs {
+                invalidate: hwc_invalidate,
+                vsync: hwc_vsync,
+                hotplug: hwc_hotplug,
+            },
+            sender: Mutex::new(sender),
+        };
+        unsafe {
+            let procs: *const VsyncProcs = transmute(procs);
+            ((*hwc_dev).registerProcs)(hwc_dev, &(*procs).procs);
+            ((*hwc_dev).eventControl)(hwc_dev, 0, HWC_EVENT_VSYNC, 1);
+        }
+        HwcVblankSource {
+            receiver: receiver,
+        }
+    }
+}
+
+impl VblankSource for HwcVblankSource {
+    fn wait_for_vblank(&mut self) -> Option<u64> {
+        // Vertical blanks keep being reported while nothing waits for them, so skip to the
+        // next one.
+        while let Ok(_) = self.receiver.try_recv() {}
+        self.receiver.recv().ok()
+    }
+}
+
 impl Drop for Window {
     fn drop (&mut self) {
         unsafe {
@@ -797,6 +922,33 @@ impl WindowMethods for Window {
         let _ = egl::SwapBuffers(self.dpy, self.surf);
     }
 
+    fn refresh_interval(&self) -> u64 {
+        DEFAULT_REFRESH_INTERVAL
+    }
+
+    fn vblank_source(&self) -> Option<Box<VblankSource>> {
+        let hwc_dev = unsafe { (*self.native_window).hwc_dev };
+        Some(box HwcVblankSource::new(hwc_dev))
+    }
+
+    fn supports_partial_present(&self) -> bool {
+        self.preserves_back_buffer
+    }
+
+    fn present_damage(&self, damage: TypedRect<DevicePixel, u32>) {
+        let swap_buffers_with_damage = match self.swap_buffers_with_damage {
+            Some(swap_buffers_with_damage) => swap_buffers_with_damage,
+            None => return self.present(),
+        };
+        // EGL measures damage from the bottom left.
+        let damage = damage.to_untyped();
+        let rect = [damage.origin.x as EGLint,
+                    self.height - (damage.origin.y + damage.size.height) as EGLint,
+                    damage.size.width as EGLint,
+                    damage.size.height as EGLint];
+        let _ = swap_buffers_with_damage(self.dpy, self.surf, rect.as_ptr(), 1);
+    }
+
     fn set_page_title(&self, _: Option<String>) {
     }
 
@@ -842,10 +994,43 @@ impl WindowMethods for Window {
     fn set_favicon(&self, _: Url) {
     }
 
+    fn set_favicon_image(&self, _: Arc<Image>) {
+    }
+
+    fn request_permission(&self, _: PermissionPrompt) {
+        // There is no UI to ask with, so dropping the prompt denies the permission.
+    }
+
+    fn show_notification(&self, _: PipelineId, _: NotificationId, _: NotificationData) {
+    }
+
+    fn close_notification(&self, _: PipelineId, _: NotificationId) {
+    }
+
+    fn request_position(&self, _: PositionRequest) {
+        // The platform's location service isn't wired up, so dropping the request reports
+        // that the position is unavailable.
+    }
+
+    fn mixed_content(&self, _: PipelineId, _: Url, _: MixedContentState) {
+    }
+
+    fn certificate_error(&self, _: PipelineId, _: &CertificateError) -> bool {
+        false
+    }
+
+    fn request_credentials(&self, _: CredentialsPrompt) {
+        // There is nobody to ask, so dropping the prompt declines.
+    }
+
     fn prepare_for_composite(&self, _width: usize, _height: usize) -> bool {
         true
     }
 
+    fn set_ime_caret_rect(&self, _: TypedRect<DevicePixel, f32>) {
+        // There is no input method on Gonk to tell.
+    }
+
     fn supports_clipboard(&self) -> bool {
         true
     }
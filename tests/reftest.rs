@@ -0,0 +1,508 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+//! The reftest runner. It renders both sides of every test in the reftest lists with Servo and
+//! compares the screenshots.
+//!
+//!     reftest cpu|gpu BASE_PATH [NAME_FILTER] [-- SERVO_ARGS...]
+//!
+//! Every `.list` file under `BASE_PATH` is read. Each of their lines, other than comments, is
+//!
+//!     [CONDITIONS] KIND LEFT RIGHT
+//!
+//! where `KIND` is `==` if `LEFT` and `RIGHT` should render the same and `!=` if they shouldn't.
+//! `CONDITIONS` is a comma-separated list of
+//!
+//! * `fuzzy(MAX_DIFFERENCE,PIXEL_COUNT)`: the renderings count as the same if at most
+//!   `PIXEL_COUNT` pixels differ, by at most `MAX_DIFFERENCE` in each channel. This is for
+//!   antialiasing differences.
+//! * `fail`: the test is expected to fail, and passing is reported as an error.
+//! * `flaky`: the result of the test doesn't matter.
+//! * `skip`: the test isn't run.
+//! * `experimental`, `fragment=FRAGMENT` and `resolution=WIDTHxHEIGHT`, which are passed on to
+//!   Servo.
+//!
+//! The first four only apply to some configurations when they are followed by `_` and one of
+//! `cpu`, `gpu`, `linux`, `macos`, `android` and `headless`, as in `fuzzy_linux(2,40)` or
+//! `fail_gpu`. The last matching `fuzzy` annotation wins, and `flaky` overrides `fail`.
+//!
+//! The screenshots of a test that fails, and an image of the pixels that differ between them,
+//! are kept in the temporary directory.
+
+#![feature(fs_walk, path_ext, test)]
+
+#[macro_use] extern crate bitflags;
+extern crate png;
+extern crate test;
+extern crate url;
+
+use std::cmp;
+use std::env;
+use std::ffi::OsStr;
+use std::fs::{File, PathExt, walk_dir};
+use std::io::{self, Read};
+use std::path::{Path, PathBuf};
+use std::process::{self, Command};
+use test::{AutoColor, DynTestName, DynTestFn, TestDesc, TestOpts, TestDescAndFn, ShouldPanic};
+use test::run_tests_console;
+use url::Url;
+
+bitflags!(
+    flags Configuration: u32 {
+        const CPU_RENDERING  = 0x00000001,
+        const GPU_RENDERING  = 0x00000010,
+        const LINUX_TARGET   = 0x00000100,
+        const MACOS_TARGET   = 0x00001000,
+        const ANDROID_TARGET = 0x00010000,
+        const HEADLESS       = 0x00100000
+    }
+);
+
+fn main() {
+    let args: Vec<String> = env::args().collect();
+    let mut parts = args[1..].split(|arg| &**arg == "--");
+
+    let harness_args = parts.next().unwrap();  // .split() is never empty
+    let servo_args = parts.next().unwrap_or(&[]);
+
+    if harness_args.len() < 2 {
+        panic!("USAGE: cpu|gpu base_path [testname filter] [-- servo args]")
+    }
+    let base_path = &harness_args[1];
+    let filter = harness_args.get(2).cloned();
+
+    let mut configuration = match &*harness_args[0] {
+        "cpu" => CPU_RENDERING,
+        "gpu" => GPU_RENDERING,
+        _ => panic!("First argument must specify cpu or gpu as rendering mode")
+    };
+    if cfg!(target_os = "linux") {
+        configuration.insert(LINUX_TARGET);
+    }
+    if cfg!(target_os = "macos") {
+        configuration.insert(MACOS_TARGET);
+    }
+    if cfg!(target_os = "android") {
+        configuration.insert(ANDROID_TARGET);
+    }
+    if cfg!(feature = "headless") {
+        configuration.insert(HEADLESS);
+    }
+
+    let mut all_tests = vec!();
+    println!("Scanning {} for manifests\n", base_path);
+
+    for file in walk_dir(base_path).unwrap() {
+        let file = file.unwrap().path();
+        if file.extension() == Some(OsStr::new("list")) && file.is_file() {
+            let mut tests = parse_list(&file, filter.as_ref(), servo_args, configuration,
+                                       all_tests.len());
+            println!("\t{} [{} tests]", file.display(), tests.len());
+            all_tests.append(&mut tests);
+        }
+    }
+
+    let test_opts = TestOpts {
+        filter: None,
+        run_ignored: false,
+        logfile: None,
+        run_tests: true,
+        bench_benchmarks: false,
+        nocapture: false,
+        color: AutoColor,
+    };
+
+    match run(test_opts, all_tests, servo_args) {
+        Ok(false) => process::exit(1), // tests failed
+        Err(_) => process::exit(2),    // I/O-related failure
+        _ => (),
+    }
+}
+
+fn run(test_opts: TestOpts, all_tests: Vec<TestDescAndFn>, servo_args: &[String])
+       -> io::Result<bool> {
+    // Verify that we're passing in valid servo arguments. Otherwise, servo
+    // will exit before we've run any tests, and it will appear to us as if
+    // all the tests are failing.
+    let output = match Command::new(&servo_path()).args(servo_args).output() {
+        Ok(output) => output,
+        Err(error) => panic!("failed to execute process: {}", error),
+    };
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    if stderr.contains("Unrecognized") {
+        println!("Servo: {}", stderr);
+        return Ok(false)
+    }
+
+    run_tests_console(&test_opts, all_tests)
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum ReftestKind {
+    Same,
+    Different,
+}
+
+/// What a test is expected to do in the configuration it runs in.
+#[derive(Clone, Copy, PartialEq)]
+enum Expectation {
+    Pass,
+    Fail,
+    Flaky,
+    Skip,
+}
+
+/// How far apart two renderings may be and still count as the same.
+#[derive(Clone, Copy)]
+struct Fuzz {
+    /// The largest difference in any channel of any pixel.
+    max_difference: u8,
+    /// The number of pixels that may differ.
+    pixel_count: usize,
+}
+
+struct Reftest {
+    name: String,
+    kind: ReftestKind,
+    files: [PathBuf; 2],
+    id: usize,
+    servo_args: Vec<String>,
+    configuration: Configuration,
+    expectation: Expectation,
+    fuzz: Option<Fuzz>,
+    experimental: bool,
+    fragment_identifier: Option<String>,
+    resolution: Option<String>,
+}
+
+struct TestLine<'a> {
+    conditions: &'a str,
+    kind: &'a str,
+    file_left: &'a str,
+    file_right: &'a str,
+}
+
+fn parse_list(file: &Path,
+              filter: Option<&String>,
+              servo_args: &[String],
+              configuration: Configuration,
+              id_offset: usize)
+              -> Vec<TestDescAndFn> {
+    let mut tests = Vec::new();
+    let contents = {
+        let mut file = File::open(file).unwrap();
+        let mut contents = String::new();
+        file.read_to_string(&mut contents).unwrap();
+        contents
+    };
+
+    for line in contents.lines() {
+        // ignore comments or empty lines
+        if line.starts_with("#") || line.trim().is_empty() {
+            continue
+        }
+
+        let parts: Vec<&str> = line.split(' ').filter(|part| !part.is_empty()).collect();
+
+        let test_line = match parts.len() {
+            3 => TestLine {
+                conditions: "",
+                kind: parts[0],
+                file_left: parts[1],
+                file_right: parts[2],
+            },
+            4 => TestLine {
+                conditions: parts[0],
+                kind: parts[1],
+                file_left: parts[2],
+                file_right: parts[3],
+            },
+            _ => panic!("reftest line: '{}' doesn't match '[CONDITIONS] KIND LEFT RIGHT'", line),
+        };
+
+        let kind = match test_line.kind {
+            "==" => ReftestKind::Same,
+            "!=" => ReftestKind::Different,
+            part => panic!("reftest line: '{}' has invalid kind '{}'", line, part)
+        };
+        let base = file.parent().unwrap();
+        let file_left = base.join(test_line.file_left);
+        let file_right = base.join(test_line.file_right);
+
+        let mut expectation = Expectation::Pass;
+        let mut flaky = false;
+        let mut fuzz = None;
+        let mut experimental = false;
+        let mut fragment_identifier = None;
+        let mut resolution = None;
+        for condition in split_conditions(test_line.conditions) {
+            if condition == "experimental" {
+                experimental = true;
+                continue
+            }
+            if condition.starts_with("fragment=") {
+                fragment_identifier = Some(condition["fragment=".len()..].to_owned());
+                continue
+            }
+            if condition.starts_with("resolution=") {
+                resolution = Some(condition["resolution=".len()..].to_owned());
+                continue
+            }
+
+            let (annotation, arguments) = match condition.find('(') {
+                Some(index) if condition.ends_with(")") => {
+                    (&condition[..index], Some(&condition[index + 1..condition.len() - 1]))
+                }
+                _ => (condition, None),
+            };
+            let (annotation, applies) = match annotation.find('_') {
+                Some(index) => {
+                    let required = match parse_configuration(&annotation[index + 1..]) {
+                        Some(required) => required,
+                        None => panic!("reftest line: '{}' has invalid condition '{}'",
+                                       line, condition),
+                    };
+                    (&annotation[..index], configuration.contains(required))
+                }
+                None => (annotation, true),
+            };
+            match (annotation, arguments) {
+                ("fuzzy", Some(arguments)) => {
+                    let fuzzy = match parse_fuzz(arguments) {
+                        Some(fuzzy) => fuzzy,
+                        None => panic!("reftest line: '{}' has invalid condition '{}'",
+                                       line, condition),
+                    };
+                    if applies {
+                        fuzz = Some(fuzzy);
+                    }
+                }
+                ("fail", None) => {
+                    if applies && expectation != Expectation::Skip {
+                        expectation = Expectation::Fail;
+                    }
+                }
+                ("flaky", None) => flaky |= applies,
+                ("skip", None) => {
+                    if applies {
+                        expectation = Expectation::Skip;
+                    }
+                }
+                _ => panic!("reftest line: '{}' has invalid condition '{}'", line, condition),
+            }
+        }
+        if flaky && expectation != Expectation::Skip {
+            expectation = Expectation::Flaky;
+        }
+
+        let reftest = Reftest {
+            name: format!("{} {} {}", test_line.file_left, test_line.kind, test_line.file_right),
+            kind: kind,
+            files: [file_left, file_right],
+            id: id_offset + tests.len(),
+            configuration: configuration,
+            servo_args: servo_args.to_vec(),
+            expectation: expectation,
+            fuzz: fuzz,
+            experimental: experimental,
+            fragment_identifier: fragment_identifier,
+            resolution: resolution,
+        };
+
+        if filter.map_or(true, |pattern| reftest.name.contains(&**pattern)) {
+            tests.push(make_test(reftest));
+        }
+    }
+    tests
+}
+
+/// Splits a list of conditions at the commas that aren't in the arguments of one.
+fn split_conditions(conditions: &str) -> Vec<&str> {
+    let mut split = vec!();
+    let mut start = 0;
+    let mut depth = 0;
+    for (index, character) in conditions.char_indices() {
+        match character {
+            '(' => depth += 1,
+            ')' => depth -= 1,
+            ',' if depth == 0 => {
+                split.push(&conditions[start..index]);
+                start = index + 1;
+            }
+            _ => {}
+        }
+    }
+    split.push(&conditions[start..]);
+    split.into_iter().filter(|condition| !condition.is_empty()).collect()
+}
+
+fn parse_configuration(name: &str) -> Option<Configuration> {
+    match name {
+        "cpu" => Some(CPU_RENDERING),
+        "gpu" => Some(GPU_RENDERING),
+        "linux" => Some(LINUX_TARGET),
+        "macos" => Some(MACOS_TARGET),
+        "android" => Some(ANDROID_TARGET),
+        "headless" => Some(HEADLESS),
+        _ => None,
+    }
+}
+
+fn parse_fuzz(arguments: &str) -> Option<Fuzz> {
+    let arguments: Vec<&str> = arguments.split(',').map(|argument| argument.trim()).collect();
+    if arguments.len() != 2 {
+        return None
+    }
+    match (arguments[0].parse(), arguments[1].parse()) {
+        (Ok(max_difference), Ok(pixel_count)) => Some(Fuzz {
+            max_difference: max_difference,
+            pixel_count: pixel_count,
+        }),
+        _ => None,
+    }
+}
+
+fn make_test(reftest: Reftest) -> TestDescAndFn {
+    let name = reftest.name.clone();
+    TestDescAndFn {
+        desc: TestDesc {
+            name: DynTestName(name),
+            ignore: reftest.expectation == Expectation::Skip,
+            should_panic: ShouldPanic::No,
+        },
+        testfn: DynTestFn(Box::new(move || {
+            check_reftest(reftest);
+        })),
+    }
+}
+
+fn output_path(reftest: &Reftest, suffix: &str) -> PathBuf {
+    env::temp_dir().join(format!("servo-reftest-{:06}-{}.png", reftest.id, suffix))
+}
+
+fn capture(reftest: &Reftest, side: usize) -> (u32, u32, Vec<u8>) {
+    let png_path = output_path(reftest, &side.to_string());
+    let mut command = Command::new(&servo_path());
+    command
+        .args(&reftest.servo_args[..])
+        // Allows pixel perfect rendering of Ahem font for reftests.
+        .arg("--disable-text-aa")
+        .args(&["-f", "-o"])
+        .arg(&png_path)
+        .arg(&{
+            let mut url = Url::from_file_path(&*reftest.files[side]).unwrap();
+            url.fragment = reftest.fragment_identifier.clone();
+            url.to_string()
+        });
+    // CPU rendering is the default
+    if reftest.configuration.contains(CPU_RENDERING) {
+        command.arg("-c");
+    }
+    if reftest.configuration.contains(GPU_RENDERING) {
+        command.arg("-g");
+    }
+    if reftest.experimental {
+        command.arg("--experimental");
+    }
+    if let Some(ref resolution) = reftest.resolution {
+        command.arg("--resolution");
+        command.arg(resolution);
+    }
+    let status = match command.status() {
+        Ok(status) => status,
+        Err(error) => panic!("failed to execute process: {}", error),
+    };
+    assert!(status.success());
+
+    let image = png::load_png(&png_path).unwrap();
+    let rgba8_bytes = match image.pixels {
+        png::PixelsByColorType::RGBA8(pixels) => pixels,
+        _ => panic!(),
+    };
+    (image.width, image.height, rgba8_bytes)
+}
+
+fn servo_path() -> PathBuf {
+    let current_exe = env::current_exe().ok().expect("Could not locate current executable");
+    current_exe.parent().unwrap().join("servo")
+}
+
+/// Compares two renderings, returning the largest difference in any channel, the number of
+/// pixels that differ and an image of the differences: white where the pixels are the same,
+/// and red where they aren't, the more so the more they differ.
+fn compare(left: &[u8], right: &[u8]) -> (u8, usize, Vec<u8>) {
+    let mut max_difference = 0;
+    let mut pixel_count = 0;
+    let mut diff = Vec::with_capacity(left.len());
+    for (left, right) in left.chunks(4).zip(right.chunks(4)) {
+        let difference = left.iter().zip(right.iter()).map(|(&a, &b)| {
+            if a > b { a - b } else { b - a }
+        }).max().unwrap_or(0);
+        if difference == 0 {
+            diff.extend([0xFF, 0xFF, 0xFF, 0xFF].iter().cloned());
+            continue
+        }
+        max_difference = cmp::max(max_difference, difference);
+        pixel_count += 1;
+        // Even the smallest difference is visible.
+        let shade = 0xC0 - (difference as u32 * 0xC0 / 0xFF) as u8;
+        diff.extend([0xFF, shade, shade, 0xFF].iter().cloned());
+    }
+    (max_difference, pixel_count, diff)
+}
+
+fn check_reftest(reftest: Reftest) {
+    let (left_width, left_height, left_bytes) = capture(&reftest, 0);
+    let (right_width, right_height, right_bytes) = capture(&reftest, 1);
+
+    assert_eq!(left_width, right_width);
+    assert_eq!(left_height, right_height);
+
+    let left_all_white = left_bytes.iter().all(|&p| p == 255);
+    let right_all_white = right_bytes.iter().all(|&p| p == 255);
+
+    if left_all_white && right_all_white {
+        panic!("Both renderings are empty")
+    }
+
+    let (max_difference, pixel_count, diff) = compare(&left_bytes, &right_bytes);
+    let same = pixel_count == 0 || reftest.fuzz.map_or(false, |fuzz| {
+        max_difference <= fuzz.max_difference && pixel_count <= fuzz.pixel_count
+    });
+    let passed = match reftest.kind {
+        ReftestKind::Same => same,
+        ReftestKind::Different => !same,
+    };
+
+    if passed {
+        if reftest.expectation == Expectation::Fail {
+            panic!("unexpected pass")
+        }
+        return
+    }
+
+    // A `!=` test fails when the renderings count as the same, so there's no difference to show.
+    let message = if same {
+        "renderings are the same".to_owned()
+    } else {
+        let diff_path = output_path(&reftest, "diff");
+        let mut image = png::Image {
+            width: left_width,
+            height: left_height,
+            pixels: png::PixelsByColorType::RGBA8(diff),
+        };
+        assert!(png::store_png(&mut image, &diff_path).is_ok());
+        format!("rendering difference of up to {} in {} pixels: {} (renderings in {} and {})",
+                max_difference,
+                pixel_count,
+                diff_path.display(),
+                output_path(&reftest, "0").display(),
+                output_path(&reftest, "1").display())
+    };
+    match reftest.expectation {
+        Expectation::Pass => panic!("{}", message),
+        Expectation::Flaky => println!("flaky test - {}", message),
+        Expectation::Fail | Expectation::Skip => {}
+    }
+}
@@ -0,0 +1,65 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+use gfx::font_template::FontTemplateDescriptor;
+use style::computed_values::{font_stretch, font_weight};
+
+fn descriptor(weight: font_weight::T, stretch: font_stretch::T, italic: bool)
+              -> FontTemplateDescriptor {
+    FontTemplateDescriptor::new(weight, stretch, italic)
+}
+
+/// Returns the index of the descriptor in `available` that is closest to `requested`.
+fn closest(available: &[FontTemplateDescriptor], requested: &FontTemplateDescriptor) -> usize {
+    let mut closest = 0;
+    for (index, desc) in available.iter().enumerate() {
+        if desc.distance_from(requested) < available[closest].distance_from(requested) {
+            closest = index;
+        }
+    }
+    closest
+}
+
+#[test]
+fn test_stretch_is_matched_before_style_and_weight() {
+    let available = [
+        descriptor(font_weight::T::Weight700, font_stretch::T::normal, true),
+        descriptor(font_weight::T::Weight400, font_stretch::T::condensed, false),
+    ];
+    let requested = descriptor(font_weight::T::Weight700, font_stretch::T::condensed, true);
+    assert_eq!(closest(&available, &requested), 1);
+}
+
+#[test]
+fn test_narrower_fonts_are_preferred_for_condensed_requests() {
+    let available = [
+        descriptor(font_weight::T::Weight400, font_stretch::T::normal, false),
+        descriptor(font_weight::T::Weight400, font_stretch::T::extra_condensed, false),
+    ];
+    let requested = descriptor(font_weight::T::Weight400, font_stretch::T::semi_condensed, false);
+    assert_eq!(closest(&available, &requested), 1);
+}
+
+#[test]
+fn test_wider_fonts_are_preferred_for_expanded_requests() {
+    let available = [
+        descriptor(font_weight::T::Weight400, font_stretch::T::semi_expanded, false),
+        descriptor(font_weight::T::Weight400, font_stretch::T::ultra_expanded, false),
+    ];
+    let requested = descriptor(font_weight::T::Weight400, font_stretch::T::expanded, false);
+    assert_eq!(closest(&available, &requested), 1);
+}
+
+#[test]
+fn test_weight_matching_order() {
+    let available = [
+        descriptor(font_weight::T::Weight300, font_stretch::T::normal, false),
+        descriptor(font_weight::T::Weight500, font_stretch::T::normal, false),
+        descriptor(font_weight::T::Weight800, font_stretch::T::normal, false),
+    ];
+    let request = |weight| descriptor(weight, font_stretch::T::normal, false);
+    assert_eq!(closest(&available, &request(font_weight::T::Weight400)), 1);
+    assert_eq!(closest(&available, &request(font_weight::T::Weight200)), 0);
+    assert_eq!(closest(&available, &request(font_weight::T::Weight600)), 2);
+}
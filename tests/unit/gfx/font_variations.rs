@@ -0,0 +1,74 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+use gfx::font_variations::{FontVariations, VariationAxis, WGHT};
+use gfx::sfnt::{SfntTable, write_sfnt};
+
+fn push_u16(data: &mut Vec<u8>, value: u16) {
+    data.extend([(value >> 8) as u8, value as u8].iter().cloned());
+}
+
+fn push_fixed(data: &mut Vec<u8>, value: u16) {
+    push_u16(data, value);
+    push_u16(data, 0);
+}
+
+fn table(tag: &[u8; 4], data: Vec<u8>) -> SfntTable {
+    SfntTable {
+        tag: (tag[0] as u32) << 24 | (tag[1] as u32) << 16 | (tag[2] as u32) << 8 | tag[3] as u32,
+        checksum: 0,
+        data: data,
+    }
+}
+
+/// Builds a font with a weight axis from 100 to 900 and a named instance at 700, whose `avar`
+/// table moves the middle of the heavier half of the axis to three quarters of the way along.
+fn variable_font() -> Vec<u8> {
+    let mut fvar = vec!();
+    for &value in [1, 0, 16, 2, 1, 20, 1, 8].iter() {
+        push_u16(&mut fvar, value);
+    }
+    fvar.extend(b"wght".iter().cloned());
+    push_fixed(&mut fvar, 100);
+    push_fixed(&mut fvar, 400);
+    push_fixed(&mut fvar, 900);
+    push_u16(&mut fvar, 0);
+    push_u16(&mut fvar, 256);
+    push_u16(&mut fvar, 257);
+    push_u16(&mut fvar, 0);
+    push_fixed(&mut fvar, 700);
+
+    let mut avar = vec!();
+    for &value in [1, 0, 0, 1, 4, 0xC000, 0xC000, 0, 0, 0x2000, 0x3000, 0x4000, 0x4000].iter() {
+        push_u16(&mut avar, value);
+    }
+
+    write_sfnt(0x00010000, &[table(b"avar", avar), table(b"fvar", fvar)])
+}
+
+#[test]
+fn test_from_font_data() {
+    let variations = FontVariations::from_font_data(&variable_font()).unwrap();
+    assert_eq!(variations.axes, vec!(VariationAxis {
+        tag: WGHT,
+        min: 100.0,
+        default: 400.0,
+        max: 900.0,
+    }));
+    assert_eq!(variations.instances.len(), 1);
+    assert_eq!(variations.instances[0].subfamily_name_id, 257);
+    assert_eq!(variations.instances[0].coordinates, vec!(700.0));
+
+    assert!(FontVariations::from_font_data(&write_sfnt(0x00010000, &[])).is_none());
+}
+
+#[test]
+fn test_normalize() {
+    let variations = FontVariations::from_font_data(&variable_font()).unwrap();
+    assert_eq!(variations.normalize(&[]), vec!(0.0));
+    assert_eq!(variations.normalize(&[250.0]), vec!(-0.5));
+    assert_eq!(variations.normalize(&[650.0]), vec!(0.75));
+    assert_eq!(variations.normalize(&[1000.0]), vec!(1.0));
+    assert_eq!(variations.coordinates_for_weight(1000.0), vec!(900.0));
+}
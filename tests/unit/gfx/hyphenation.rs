@@ -0,0 +1,24 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+use gfx::text::hyphenation::Hyphenator;
+
+fn hyphenator() -> Hyphenator {
+    Hyphenator::new("hy3ph he2n hena4 hen5at 1na n2at 1tio 2io o2n".split(' '))
+}
+
+#[test]
+fn test_hyphenation_points() {
+    let hyphenator = hyphenator();
+    assert_eq!(hyphenator.hyphenation_points("hyphenation"), vec!(2, 6));
+    assert_eq!(hyphenator.hyphenation_points("Hyphenation"), vec!(2, 6));
+}
+
+#[test]
+fn test_hyphenation_minimums() {
+    let mut hyphenator = hyphenator();
+    assert_eq!(hyphenator.hyphenation_points("hyph"), vec!());
+    hyphenator.right_min = 6;
+    assert_eq!(hyphenator.hyphenation_points("hyphenation"), vec!(2));
+}
@@ -3,5 +3,16 @@
  * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
 
 extern crate gfx;
+extern crate style;
+extern crate util;
 
+#[cfg(test)] mod font_template;
+#[cfg(test)] mod font_variations;
+#[cfg(test)] mod hyphenation;
+#[cfg(test)] mod line_break;
+#[cfg(test)] mod pdf;
+#[cfg(test)] mod text_glyph;
+#[cfg(test)] mod text_run;
+#[cfg(test)] mod text_shaping_simple;
 #[cfg(test)] mod text_util;
+#[cfg(test)] mod woff;
@@ -0,0 +1,65 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+use gfx::text::line_break::LineBreakIterator;
+
+fn breaks(text: &str) -> Vec<usize> {
+    LineBreakIterator::new(text).collect()
+}
+
+#[test]
+fn test_break_after_spaces() {
+    assert_eq!(breaks("foo bar  baz"), vec!(4, 9));
+    assert_eq!(breaks("  foo"), vec!(2));
+    assert_eq!(breaks("foo "), vec!());
+}
+
+#[test]
+fn test_no_break_at_no_break_spaces() {
+    assert_eq!(breaks("10\u{A0}km"), vec!());
+    assert_eq!(breaks("a\u{2060}b"), vec!());
+}
+
+#[test]
+fn test_break_between_ideographs() {
+    // Each ideograph may start a line, but small kana and ideographic full stops may not.
+    assert_eq!(breaks("日本語"), vec!(3, 6));
+    assert_eq!(breaks("ちょっと。"), vec!(9));
+}
+
+#[test]
+fn test_punctuation() {
+    assert_eq!(breaks("(foo) bar!"), vec!(6));
+    assert_eq!(breaks("foo ( bar )"), vec!(4));
+    assert_eq!(breaks("well-known"), vec!(5));
+    assert_eq!(breaks("$1,000.50"), vec!());
+}
+
+#[test]
+fn test_break_after_soft_hyphens() {
+    assert_eq!(breaks("hy\u{AD}phen"), vec!(4));
+}
+
+#[test]
+fn test_mandatory_breaks() {
+    assert_eq!(breaks("foo\r\nbar\nbaz"), vec!(5, 9));
+}
+
+#[test]
+fn test_combining_marks_stay_with_their_base() {
+    assert_eq!(breaks("e\u{301} x\u{301}"), vec!(4));
+}
+
+#[test]
+fn test_emoji_sequences_stay_together() {
+    // A family, a thumbs up with a skin tone and a flag.
+    let text = "\u{1F468}\u{200D}\u{1F469} \u{1F44D}\u{1F3FD}\u{1F1EB}\u{1F1F7}";
+    assert_eq!(breaks(text), vec!(12, 20));
+}
+
+#[test]
+fn test_regional_indicators_pair_into_flags() {
+    // The flags of France and Germany.
+    assert_eq!(breaks("\u{1F1EB}\u{1F1F7}\u{1F1E9}\u{1F1EA}"), vec!(8));
+}
@@ -0,0 +1,38 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+use gfx::pdf::{PaperSize, PdfPage, write_pdf};
+
+#[test]
+fn test_write_pdf_cross_references_objects() {
+    let page = || PdfPage { width: 2, height: 3, pixels: vec![255; 2 * 3 * 3] };
+    let mut output = vec!();
+    write_pdf(&mut output, PaperSize::letter(), &[page(), page()]).unwrap();
+
+    assert!(output.starts_with(b"%PDF-1.4\n"));
+    assert!(output.ends_with(b"%%EOF\n"));
+
+    // The images are compressed, but everything from the cross-reference table on is text.
+    let xref = output.windows(5).rposition(|window| window == &b"xref\n"[..]).unwrap();
+    let trailer = String::from_utf8(output[xref..].to_vec()).unwrap();
+    let entries = trailer.lines().skip(3).take_while(|line| line.ends_with(" n "));
+    let mut count = 0;
+    for (i, entry) in entries.enumerate() {
+        // Every entry is the offset of its object.
+        let offset: usize = entry[..10].parse().unwrap();
+        assert!(output[offset..].starts_with(format!("{} 0 obj\n", i + 1).as_bytes()));
+        count += 1;
+    }
+    assert_eq!(count, 8);
+
+    let pages = b"2 0 obj\n<< /Type /Pages /Kids [3 0 R 6 0 R] /Count 2 >>";
+    assert!(output.windows(pages.len()).any(|window| window == &pages[..]));
+}
+
+#[test]
+fn test_paper_size_from_name() {
+    assert_eq!(PaperSize::from_name("A4"), Some(PaperSize::a4()));
+    assert_eq!(PaperSize::from_name("letter"), Some(PaperSize::letter()));
+    assert_eq!(PaperSize::from_name("tabloid"), None);
+}
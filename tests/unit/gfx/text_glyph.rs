@@ -0,0 +1,54 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+use gfx::text::glyph::{CharIndex, GlyphData, GlyphStore};
+use util::geometry::Au;
+use util::range::Range;
+
+fn glyph(id: u32, advance: Au) -> GlyphData {
+    GlyphData::new(id, advance, None, false, true, true)
+}
+
+fn advances(glyphs: &GlyphStore) -> Vec<Au> {
+    glyphs.iter_glyphs_for_char_range(&Range::new(CharIndex(0), glyphs.char_len()))
+          .map(|(_, glyph)| glyph.advance())
+          .collect()
+}
+
+#[test]
+fn test_letter_spacing_goes_after_ligatures() {
+    // An "fi" ligature followed by an "x".
+    let mut glyphs = GlyphStore::new(3, false);
+    glyphs.add_glyph_for_char_index(CharIndex(0), None, &glyph(1, Au(600)));
+    glyphs.add_nonglyph_for_char_index(CharIndex(1), false, false);
+    glyphs.add_glyph_for_char_index(CharIndex(2), Some('x'), &glyph(2, Au(500)));
+    glyphs.finalize_changes();
+
+    glyphs.add_spacing("fix", Au(60), Au(0));
+    assert_eq!(advances(&glyphs), vec!(Au(720), Au(560)));
+}
+
+#[test]
+fn test_spacing_of_clusters_goes_after_their_last_glyph() {
+    // A character drawn with two glyphs.
+    let mut glyphs = GlyphStore::new(1, false);
+    glyphs.add_glyphs_for_char_index(CharIndex(0), &[glyph(1, Au(300)), glyph(2, Au(200))]);
+    glyphs.finalize_changes();
+
+    glyphs.add_spacing("a", Au(60), Au(0));
+    assert_eq!(advances(&glyphs), vec!(Au(300), Au(260)));
+}
+
+#[test]
+fn test_word_spacing_and_negative_letter_spacing() {
+    let mut glyphs = GlyphStore::new(3, false);
+    glyphs.add_glyph_for_char_index(CharIndex(0), Some('a'), &glyph(1, Au(30)));
+    glyphs.add_glyph_for_char_index(CharIndex(1), Some(' '), &glyph(2, Au(200)));
+    glyphs.add_glyph_for_char_index(CharIndex(2), Some('b'), &glyph(3, Au(500)));
+    glyphs.finalize_changes();
+
+    glyphs.add_spacing("a b", Au(-60), Au(120));
+    assert_eq!(advances(&glyphs), vec!(Au(-30), Au(260), Au(440)));
+    assert!(glyphs.char_is_space(CharIndex(1)));
+}
@@ -0,0 +1,77 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+use gfx::text::glyph::{CharIndex, GlyphStore};
+use gfx::text::text_run::{GlyphRun, UnchangedText, common_prefix_and_suffix};
+use gfx::text::text_run::justification_opportunities;
+use std::sync::Arc;
+use util::range::Range;
+
+/// Makes the glyph runs that `TextRun::break_and_shape` makes for "foo bar baz": one for each
+/// word and one for each space.
+fn foo_bar_baz_glyph_runs() -> Vec<GlyphRun> {
+    let runs = [(0, 3, false), (3, 1, true), (4, 3, false), (7, 1, true), (8, 3, false)];
+    runs.iter().map(|&(begin, length, is_whitespace)| {
+        GlyphRun {
+            glyph_store: Arc::new(GlyphStore::new(length, is_whitespace)),
+            range: Range::new(CharIndex(begin), CharIndex(length as isize)),
+            hyphenation_points: vec!(),
+            justification_opportunities: vec!(),
+        }
+    }).collect()
+}
+
+#[test]
+fn test_justification_opportunities_at_word_separators() {
+    assert_eq!(justification_opportunities("a b\u{A0}c ", Some('d')),
+               vec!(CharIndex(1), CharIndex(3), CharIndex(5)));
+    assert_eq!(justification_opportunities("abc", None), vec!());
+}
+
+#[test]
+fn test_justification_opportunities_between_cjk_characters() {
+    assert_eq!(justification_opportunities("日本語", None), vec!(CharIndex(0), CharIndex(1)));
+    assert_eq!(justification_opportunities("日本", Some('a')), vec!(CharIndex(0), CharIndex(1)));
+    assert_eq!(justification_opportunities("日本", Some(' ')), vec!(CharIndex(0)));
+}
+
+#[test]
+fn test_justification_opportunities_keep_combining_marks_with_their_base() {
+    assert_eq!(justification_opportunities("日\u{301}本", None),
+               vec!(CharIndex(1)));
+}
+
+#[test]
+fn test_common_prefix_and_suffix() {
+    assert_eq!(common_prefix_and_suffix("foo bar baz", "foo qux baz"), (4, 4));
+    assert_eq!(common_prefix_and_suffix("foo", "foo"), (3, 0));
+    assert_eq!(common_prefix_and_suffix("foo", "fooo"), (3, 0));
+    assert_eq!(common_prefix_and_suffix("abc", "xyz"), (0, 0));
+    assert_eq!(common_prefix_and_suffix("\u{E9}1\u{E9}", "\u{E9}2\u{E9}"), (2, 2));
+}
+
+#[test]
+fn test_glyph_runs_for_unchanged_segments() {
+    let glyph_runs = foo_bar_baz_glyph_runs();
+
+    // Words before the change are shaped again if the change follows them directly.
+    let unchanged_text = UnchangedText::new("foo bar baz", &glyph_runs, "foo quux baz");
+    assert!(unchanged_text.glyph_runs_for_segment(0, 4).is_none());
+    assert!(unchanged_text.glyph_runs_for_segment(4, 9).is_none());
+    let baz = unchanged_text.glyph_runs_for_segment(9, 12).unwrap();
+    assert_eq!(baz.len(), 1);
+    assert!(baz[0].range.begin() == CharIndex(8));
+
+    let unchanged_text = UnchangedText::new("foo bar baz", &glyph_runs, "foo bar baz qux");
+    let foo = unchanged_text.glyph_runs_for_segment(0, 4).unwrap();
+    assert_eq!(foo.len(), 2);
+    assert!(foo[1].glyph_store.is_whitespace());
+    assert_eq!(unchanged_text.glyph_runs_for_segment(4, 8).unwrap().len(), 2);
+    assert!(unchanged_text.glyph_runs_for_segment(8, 12).is_none());
+
+    // Segments that the previous text didn't break around have no glyph runs.
+    let unchanged_text = UnchangedText::new("foo bar baz", &glyph_runs, "xfoo bar baz");
+    assert!(unchanged_text.glyph_runs_for_segment(0, 5).is_none());
+    assert!(unchanged_text.glyph_runs_for_segment(5, 9).is_some());
+}
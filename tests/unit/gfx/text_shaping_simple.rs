@@ -0,0 +1,172 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+use gfx::font::{FontFeature, FontKerning, FontLigatures, ShapingFlags, ShapingOptions};
+use gfx::font::IGNORE_LIGATURES_SHAPING_FLAG;
+use gfx::sfnt::{push_u16, push_u32};
+use gfx::text::shaping::simple::{default_features, enabled_features};
+use std::sync::Arc;
+use util::geometry::Au;
+
+fn tag(name: &str) -> u32 {
+    name.bytes().fold(0, |tag, byte| tag << 8 | byte as u32)
+}
+
+/// A language system, given as its tag, the index of its required feature and the indices of its
+/// other features.
+type LanguageSystem<'a> = (&'a str, u16, &'a [u16]);
+
+fn push_language_system(table: &mut Vec<u8>, language_system: &LanguageSystem) {
+    let &(_, required_feature, feature_indices) = language_system;
+    push_u16(table, 0);
+    push_u16(table, required_feature);
+    push_u16(table, feature_indices.len() as u16);
+    for &index in feature_indices.iter() {
+        push_u16(table, index)
+    }
+}
+
+/// Makes a `GSUB` or `GPOS` table with a script for each of `scripts`, given as its tag, its
+/// default language system and its other language systems, and a feature for each of
+/// `features`, given as its tag and number of lookups.
+fn layout_table(scripts: &[(&str, LanguageSystem, &[LanguageSystem])], features: &[(&str, u16)])
+                -> Vec<u8> {
+    let script_tables = scripts.iter().map(|&(_, ref default, languages)| {
+        let mut language_systems = vec!();
+        push_language_system(&mut language_systems, default);
+        let mut offsets = vec!();
+        for language in languages.iter() {
+            offsets.push(4 + languages.len() * 6 + language_systems.len());
+            push_language_system(&mut language_systems, language)
+        }
+
+        let mut script = vec!();
+        push_u16(&mut script, (4 + languages.len() * 6) as u16);
+        push_u16(&mut script, languages.len() as u16);
+        for (&(name, _, _), &offset) in languages.iter().zip(offsets.iter()) {
+            push_u32(&mut script, tag(name));
+            push_u16(&mut script, offset as u16);
+        }
+        script.extend(language_systems.into_iter());
+        script
+    }).collect::<Vec<_>>();
+    let mut script_list = vec!();
+    push_u16(&mut script_list, scripts.len() as u16);
+    let mut offset = 2 + scripts.len() * 6;
+    for (&(name, _, _), script) in scripts.iter().zip(script_tables.iter()) {
+        push_u32(&mut script_list, tag(name));
+        push_u16(&mut script_list, offset as u16);
+        offset += script.len()
+    }
+    for script in script_tables.iter() {
+        script_list.extend(script.iter().cloned())
+    }
+
+    let mut feature_list = vec!();
+    push_u16(&mut feature_list, features.len() as u16);
+    let mut offset = 2 + features.len() * 6;
+    for &(name, lookup_count) in features.iter() {
+        push_u32(&mut feature_list, tag(name));
+        push_u16(&mut feature_list, offset as u16);
+        offset += 4 + lookup_count as usize * 2
+    }
+    for &(_, lookup_count) in features.iter() {
+        push_u16(&mut feature_list, 0);
+        push_u16(&mut feature_list, lookup_count);
+        for index in 0..lookup_count {
+            push_u16(&mut feature_list, index)
+        }
+    }
+
+    let mut table = vec!();
+    push_u32(&mut table, 0x00010000);
+    push_u16(&mut table, 10);
+    push_u16(&mut table, (10 + script_list.len()) as u16);
+    push_u16(&mut table, 0);
+    table.extend(script_list.into_iter());
+    table.extend(feature_list.into_iter());
+    table
+}
+
+fn options(kerning: FontKerning, features: Vec<FontFeature>) -> ShapingOptions {
+    ShapingOptions {
+        letter_spacing: None,
+        word_spacing: Au(0),
+        flags: ShapingFlags::empty(),
+        kerning: kerning,
+        ligatures: FontLigatures {
+            common: None,
+            discretionary: None,
+            historical: None,
+            contextual: None,
+        },
+        features: Arc::new(features),
+    }
+}
+
+#[test]
+fn test_default_features_of_latin_and_default_scripts() {
+    let features = [("kern", 1), ("liga", 2), ("smcp", 1), ("locl", 0)];
+    let table = layout_table(&[("latn", ("dflt", 0xFFFF, &[1, 2]), &[]),
+                               ("DFLT", ("dflt", 0xFFFF, &[0, 3]), &[]),
+                               ("cyrl", ("dflt", 0xFFFF, &[0]), &[])],
+                             &features);
+    assert_eq!(default_features(&table), Some(vec!(tag("liga"), tag("smcp"), tag("kern"))));
+
+    let table = layout_table(&[("cyrl", ("dflt", 0xFFFF, &[0, 1]), &[])], &features);
+    assert_eq!(default_features(&table), Some(vec!()));
+}
+
+#[test]
+fn test_default_features_of_language_systems() {
+    let features = [("kern", 1), ("locl", 1), ("smcp", 1)];
+    let table = layout_table(&[("latn", ("dflt", 0xFFFF, &[0]), &[("TRK ", 0xFFFF, &[0, 1])]),
+                               ("cyrl", ("dflt", 0xFFFF, &[]), &[("SRB ", 0xFFFF, &[2])])],
+                             &features);
+    assert_eq!(default_features(&table), Some(vec!(tag("kern"), tag("locl"))));
+
+    let table = layout_table(&[("latn", ("dflt", 0xFFFF, &[0]), &[("NLD ", 1, &[])])],
+                             &features);
+    assert_eq!(default_features(&table), None);
+}
+
+#[test]
+fn test_default_features_with_required_feature() {
+    let table = layout_table(&[("latn", ("dflt", 0, &[]), &[])], &[("ccmp", 1)]);
+    assert_eq!(default_features(&table), None);
+    assert_eq!(default_features(&table[..12]), None);
+}
+
+#[test]
+fn test_enabled_features() {
+    let features = enabled_features(&options(FontKerning::Auto, vec!()));
+    assert!(features.contains(&tag("kern")));
+    assert!(features.contains(&tag("liga")));
+    assert!(!features.contains(&tag("smcp")));
+
+    let features = enabled_features(&options(FontKerning::None, vec!()));
+    assert!(!features.contains(&tag("kern")));
+
+    // `font-feature-settings: "kern" 0, "smcp"`
+    let features = enabled_features(&options(FontKerning::Auto, vec!(
+        FontFeature { tag: tag("kern"), value: 0 },
+        FontFeature { tag: tag("smcp"), value: 1 },
+    )));
+    assert!(!features.contains(&tag("kern")));
+    assert!(features.contains(&tag("smcp")));
+
+    // `font-feature-settings` takes precedence over `font-kerning`.
+    let features = enabled_features(&options(FontKerning::Normal, vec!(
+        FontFeature { tag: tag("kern"), value: 0 },
+    )));
+    assert!(!features.contains(&tag("kern")));
+    let features = enabled_features(&options(FontKerning::None, vec!(
+        FontFeature { tag: tag("kern"), value: 1 },
+    )));
+    assert!(features.contains(&tag("kern")));
+
+    let mut ignore_ligatures = options(FontKerning::Auto, vec!());
+    ignore_ligatures.flags = IGNORE_LIGATURES_SHAPING_FLAG;
+    assert!(!enabled_features(&ignore_ligatures).contains(&tag("liga")));
+}
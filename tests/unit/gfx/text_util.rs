@@ -2,7 +2,8 @@
  * License, v. 2.0. If a copy of the MPL was not distributed with this
  * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
 
-use gfx::text::util::{CompressionMode, transform_text};
+use gfx::text::util::{CompressionMode, is_attached_mark, is_upright_in_vertical_text};
+use gfx::text::util::transform_text;
 
 #[test]
 fn test_transform_compress_none() {
@@ -19,7 +20,7 @@ fn test_transform_compress_none() {
     let mode = CompressionMode::CompressNone;
     for &test in test_strs.iter() {
         let mut trimmed_str = String::new();
-        transform_text(test, mode, true, &mut trimmed_str);
+        transform_text(test, mode, true, &mut trimmed_str, &mut vec!());
         assert_eq!(trimmed_str, test)
     }
 }
@@ -52,7 +53,7 @@ fn test_transform_discard_newline() {
     let mode = CompressionMode::DiscardNewline;
     for &(test, oracle) in test_strs.iter() {
         let mut trimmed_str = String::new();
-        transform_text(test, mode, true, &mut trimmed_str);
+        transform_text(test, mode, true, &mut trimmed_str, &mut vec!());
         assert_eq!(trimmed_str, oracle)
     }
 }
@@ -85,7 +86,7 @@ fn test_transform_compress_whitespace() {
     let mode = CompressionMode::CompressWhitespace;
     for &(test, oracle) in test_strs.iter() {
         let mut trimmed_str = String::new();
-        transform_text(test, mode, true, &mut trimmed_str);
+        transform_text(test, mode, true, &mut trimmed_str, &mut vec!());
         assert_eq!(&*trimmed_str, oracle)
     }
 }
@@ -118,7 +119,7 @@ fn test_transform_compress_whitespace_newline() {
     let mode = CompressionMode::CompressWhitespaceNewline;
     for &(test, oracle) in test_strs.iter() {
         let mut trimmed_str = String::new();
-        transform_text(test, mode, true, &mut trimmed_str);
+        transform_text(test, mode, true, &mut trimmed_str, &mut vec!());
         assert_eq!(&*trimmed_str, oracle)
     }
 }
@@ -154,7 +155,37 @@ fn test_transform_compress_whitespace_newline_no_incoming() {
     let mode = CompressionMode::CompressWhitespaceNewline;
     for &(test, oracle) in test_strs.iter() {
         let mut trimmed_str = String::new();
-        transform_text(test, mode, false, &mut trimmed_str);
+        transform_text(test, mode, false, &mut trimmed_str, &mut vec!());
         assert_eq!(trimmed_str, oracle)
     }
 }
+
+#[test]
+fn test_transform_original_indices() {
+    let mode = CompressionMode::CompressWhitespaceNewline;
+    let mut trimmed_str = String::new();
+    let mut original_indices = vec!();
+    transform_text("a  \nb c", mode, false, &mut trimmed_str, &mut original_indices);
+    assert_eq!(trimmed_str, "a b c");
+    assert_eq!(original_indices, vec!(0, 1, 4, 5, 6));
+}
+
+#[test]
+fn test_upright_in_vertical_text() {
+    assert!(is_upright_in_vertical_text('漢'));
+    assert!(is_upright_in_vertical_text('か'));
+    assert!(is_upright_in_vertical_text('한'));
+    assert!(is_upright_in_vertical_text('\u{FF01}'));
+    assert!(!is_upright_in_vertical_text('a'));
+    assert!(!is_upright_in_vertical_text('1'));
+    assert!(!is_upright_in_vertical_text('\u{FF61}'));
+}
+
+#[test]
+fn test_attached_mark() {
+    assert!(is_attached_mark('\u{301}'));
+    assert!(is_attached_mark('\u{93F}'));
+    assert!(!is_attached_mark('a'));
+    assert!(!is_attached_mark('\u{200D}'));
+    assert!(!is_attached_mark('\u{FE0F}'));
+}
@@ -0,0 +1,125 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+use gfx::sfnt::{checksum, find_table, flavor, read_i16, read_u16, read_u32, tables};
+use gfx::woff::{decode_woff, decode_woff2, is_woff, is_woff2};
+
+/// Open Sans Regular as served by Google Fonts, with transformed glyf and loca tables.
+static OPEN_SANS: &'static [u8] = include_bytes!("fonts/OpenSans-Regular.woff2");
+
+fn tag(name: &[u8; 4]) -> u32 {
+    read_u32(name, 0)
+}
+
+fn push_u32(data: &mut Vec<u8>, value: u32) {
+    data.extend([(value >> 24) as u8, (value >> 16) as u8, (value >> 8) as u8, value as u8]
+                .iter().cloned());
+}
+
+/// Builds a WOFF font of uncompressed tables.
+fn woff(tables: &[(&[u8; 4], &[u8])]) -> Vec<u8> {
+    let mut offset = 44 + tables.len() * 20;
+    let mut directory = vec!();
+    let mut data = vec!();
+    for &(tag, table) in tables {
+        directory.extend(tag.iter().cloned());
+        push_u32(&mut directory, offset as u32);
+        push_u32(&mut directory, table.len() as u32);
+        push_u32(&mut directory, table.len() as u32);
+        push_u32(&mut directory, 0x12345678);
+        data.extend(table.iter().cloned());
+        while data.len() % 4 != 0 {
+            data.push(0);
+        }
+        offset = 44 + tables.len() * 20 + data.len();
+    }
+
+    let mut font = b"wOFF".to_vec();
+    push_u32(&mut font, 0x00010000);
+    push_u32(&mut font, offset as u32);
+    font.extend([0, tables.len() as u8, 0, 0].iter().cloned());
+    push_u32(&mut font, (12 + tables.len() * 16 + data.len()) as u32);
+    for _ in 0..6 {
+        push_u32(&mut font, 0);
+    }
+    font.extend(directory.into_iter());
+    font.extend(data.into_iter());
+    font
+}
+
+#[test]
+fn test_decode_woff_lays_out_sfnt_tables() {
+    let font = woff(&[(b"cmap", b"abcde"), (b"head", b"fghi")]);
+    assert!(is_woff(&font));
+    let sfnt = decode_woff(&font).unwrap();
+
+    assert_eq!(&sfnt[..12], &[0, 1, 0, 0, 0, 2, 0, 32, 0, 1, 0, 0]);
+    assert_eq!(&sfnt[12..28], b"cmap\x12\x34\x56\x78\0\0\0\x2c\0\0\0\x05");
+    assert_eq!(&sfnt[28..44], b"head\x12\x34\x56\x78\0\0\0\x34\0\0\0\x04");
+    assert_eq!(&sfnt[44..], b"abcde\0\0\0fghi");
+}
+
+#[test]
+fn test_decode_woff_rejects_truncated_fonts() {
+    let font = woff(&[(b"cmap", b"abcde")]);
+    assert!(decode_woff(&font[..font.len() - 4]).is_err());
+    assert!(!is_woff(b"\0\x01\0\0"));
+}
+
+#[test]
+fn test_decode_woff_rejects_tables_larger_than_the_font() {
+    let mut font = woff(&[(b"cmap", b"abcde"), (b"head", b"fghi")]);
+    // Claim the unpacked font is a byte smaller than its tables need.
+    font[19] -= 1;
+    assert!(decode_woff(&font).is_err());
+}
+
+#[test]
+fn test_decode_woff2_rebuilds_glyf_and_loca() {
+    assert!(is_woff2(OPEN_SANS));
+    assert!(!is_woff(OPEN_SANS));
+    let font = decode_woff2(OPEN_SANS).unwrap();
+    assert_eq!(flavor(&font), Some(0x00010000));
+
+    let tags: Vec<u32> = tables(&font).unwrap().iter().map(|&(tag, _)| tag).collect();
+    assert_eq!(tags.len(), 17);
+    assert!(tags.windows(2).all(|pair| pair[0] < pair[1]));
+
+    let head = find_table(&font, tag(b"head")).unwrap();
+    assert_eq!(read_u32(head, 12), 0x5F0F3CF5);
+    // The rebuilt loca uses the short offsets the encoder asked for.
+    assert_eq!(read_i16(head, 50), 0);
+
+    let glyph_count = read_u16(find_table(&font, tag(b"maxp")).unwrap(), 4) as usize;
+    assert_eq!(glyph_count, 902);
+    let loca = find_table(&font, tag(b"loca")).unwrap();
+    let glyf = find_table(&font, tag(b"glyf")).unwrap();
+    assert_eq!(loca.len(), (glyph_count + 1) * 2);
+    let offsets: Vec<usize> = (0..glyph_count + 1).map(|index| {
+        read_u16(loca, index * 2) as usize * 2
+    }).collect();
+    assert_eq!(offsets[0], 0);
+    assert_eq!(offsets[glyph_count], glyf.len());
+    for glyph in offsets.windows(2) {
+        assert!(glyph[0] <= glyph[1]);
+        if glyph[0] == glyph[1] {
+            continue
+        }
+        // Every outline starts with a contour count and a bounding box that isn't inside out.
+        let contours = read_i16(glyf, glyph[0]);
+        assert!(contours >= -1);
+        assert!(read_i16(glyf, glyph[0] + 2) <= read_i16(glyf, glyph[0] + 6));
+        assert!(read_i16(glyf, glyph[0] + 4) <= read_i16(glyf, glyph[0] + 8));
+    }
+
+    // Rebuilt checksums and the head adjustment sum the whole font to the sfnt magic number.
+    assert_eq!(checksum(&font), 0xB1B0AFBA);
+}
+
+#[test]
+fn test_decode_woff2_rejects_truncated_fonts() {
+    assert!(decode_woff2(&OPEN_SANS[..OPEN_SANS.len() - 1]).is_err());
+    assert!(decode_woff2(&OPEN_SANS[..48]).is_err());
+    assert!(!is_woff2(b"wOFF"));
+}
@@ -0,0 +1,56 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+use net::about_loader::{crashed_page, memory_page};
+use profile_traits::mem::{ReportUnits, ReportsSummary};
+use std::borrow::ToOwned;
+
+#[test]
+fn test_memory_page_nests_reports() {
+    let summaries = vec!(ReportsSummary {
+        path_seg: "url(<http://example.com/>)".to_owned(),
+        units: ReportUnits::Bytes,
+        size: 3 * 1024 * 1024,
+        count: 0,
+        children: vec!(ReportsSummary {
+            path_seg: "layout-task".to_owned(),
+            units: ReportUnits::Bytes,
+            size: 3 * 1024 * 1024,
+            count: 2,
+            children: vec!(),
+        }),
+    });
+    let page = memory_page(&summaries);
+    assert!(page.contains("<li><code>3.00 MiB</code> &mdash; url(&lt;http://example.com/&gt;)\n\
+                           <ul>\n\
+                           <li><code>3.00 MiB</code> &mdash; layout-task [2]</li>\n\
+                           </ul>\n\
+                           </li>"));
+}
+
+#[test]
+fn test_memory_page_shows_counts_as_numbers() {
+    let summaries = vec!(ReportsSummary {
+        path_seg: "font-cache-shaped-run-hits".to_owned(),
+        units: ReportUnits::Count,
+        size: 1234,
+        count: 1,
+        children: vec!(),
+    });
+    let page = memory_page(&summaries);
+    assert!(page.contains("<li><code>1234</code> &mdash; font-cache-shaped-run-hits</li>"));
+}
+
+#[test]
+fn test_crashed_page_links_to_escaped_url() {
+    let page = crashed_page("http://example.com/?a=1&b=\"2\"");
+    assert!(page.contains("<a href=\"http://example.com/?a=1&amp;b=&quot;2&quot;\">"));
+}
+
+#[test]
+fn test_crashed_page_only_links_to_web_and_file_urls() {
+    let page = crashed_page("javascript:alert(1)");
+    assert!(page.contains("<code>javascript:alert(1)</code>"));
+    assert!(!page.contains("<a "));
+}
@@ -0,0 +1,30 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+use net::beacon::{BeaconQuota, BEACON_QUOTA};
+use url::Url;
+
+#[test]
+fn test_beacon_quota_is_per_origin() {
+    let quota = BeaconQuota::new();
+    let origin = Url::parse("http://example.com/index.html").unwrap();
+    let same_origin = Url::parse("http://example.com:80/other.html").unwrap();
+    let other_origin = Url::parse("https://example.com/index.html").unwrap();
+
+    assert!(quota.reserve(&origin, BEACON_QUOTA - 1));
+    assert!(!quota.reserve(&same_origin, 2));
+    assert!(quota.reserve(&other_origin, 2));
+    assert!(quota.reserve(&same_origin, 1));
+}
+
+#[test]
+fn test_beacon_quota_release() {
+    let quota = BeaconQuota::new();
+    let origin = Url::parse("http://example.com/").unwrap();
+
+    assert!(quota.reserve(&origin, BEACON_QUOTA));
+    assert!(!quota.reserve(&origin, 1));
+    quota.release(&origin, BEACON_QUOTA);
+    assert!(quota.reserve(&origin, 1));
+}
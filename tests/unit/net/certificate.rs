@@ -0,0 +1,41 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+use msg::constellation_msg::CertificateError;
+use net::certificate::{CertificateOverrides, error_page};
+use std::borrow::ToOwned;
+use url::Url;
+
+#[test]
+fn test_certificate_overrides_are_shared_and_case_insensitive() {
+    let overrides = CertificateOverrides::new();
+    let clone = overrides.clone();
+    assert!(!overrides.is_allowed("example.com", "AB:CD"));
+    clone.allow("Example.COM", "AB:CD");
+    assert!(overrides.is_allowed("example.com", "AB:CD"));
+    assert!(!overrides.is_allowed("www.example.com", "AB:CD"));
+}
+
+#[test]
+fn test_certificate_overrides_only_trust_the_same_certificate() {
+    let overrides = CertificateOverrides::new();
+    overrides.allow("example.com", "AB:CD");
+    assert!(!overrides.is_allowed("example.com", "EF:01"));
+}
+
+#[test]
+fn test_error_page_escapes_details() {
+    let error = CertificateError {
+        url: Url::parse("https://example.com/<page>").unwrap(),
+        host: "example.com".to_owned(),
+        reason: "<self-signed>".to_owned(),
+        chain: vec!("-----BEGIN CERTIFICATE-----\n<data>\n".to_owned()),
+        fingerprint: "AB:CD".to_owned(),
+    };
+    let page = error_page(&error);
+    assert!(page.contains("example.com"));
+    assert!(page.contains("&lt;self-signed&gt;"));
+    assert!(page.contains("&lt;data&gt;"));
+    assert!(!page.contains("<data>"));
+}
@@ -7,6 +7,7 @@ extern crate cookie as cookie_rs;
 use net::cookie::Cookie;
 use net::cookie_storage::CookieStorage;
 use net_traits::CookieSource;
+use std::borrow::ToOwned;
 use url::Url;
 
 
@@ -102,3 +103,61 @@ fn test_sort_order() {
     assert!(CookieStorage::cookie_comparator(&a_prime, &a) == Ordering::Greater);
     assert!(CookieStorage::cookie_comparator(&a, &a) == Ordering::Equal);
 }
+
+#[test]
+fn test_per_domain_eviction() {
+    use net::cookie_storage::MAX_COOKIES_PER_DOMAIN;
+
+    let url = &Url::parse("http://example.com/").unwrap();
+    let mut storage = CookieStorage::new();
+    for i in 0..MAX_COOKIES_PER_DOMAIN + 1 {
+        let cookie = cookie_rs::Cookie::parse(&format!("c{}=v", i)).unwrap();
+        storage.push(Cookie::new_wrapped(cookie, url, CookieSource::HTTP).unwrap(), CookieSource::HTTP);
+    }
+
+    let cookies = storage.cookies_for_url(url, CookieSource::HTTP).unwrap();
+    assert_eq!(cookies.split(';').count(), MAX_COOKIES_PER_DOMAIN);
+    // The least recently used cookie is the one that goes.
+    assert!(!cookies.split(';').any(|cookie| cookie == "c0=v"));
+}
+
+#[test]
+fn test_persistent_cookies_are_saved() {
+    use std::env;
+    use std::fs;
+
+    let path = env::temp_dir().join("servo-test-cookies.json");
+    let _ = fs::remove_file(&path);
+    let url = &Url::parse("http://example.com/").unwrap();
+
+    let mut storage = CookieStorage::new_persistent(path.clone());
+    for header in ["session=1", "persistent=2; Max-Age=3600", "expired=3; Max-Age=0"].iter() {
+        let cookie = cookie_rs::Cookie::parse(header).unwrap();
+        storage.push(Cookie::new_wrapped(cookie, url, CookieSource::HTTP).unwrap(), CookieSource::HTTP);
+    }
+    storage.flush();
+
+    let mut storage = CookieStorage::new_persistent(path.clone());
+    assert_eq!(storage.cookies_for_url(url, CookieSource::HTTP), Some("persistent=2".to_owned()));
+    let _ = fs::remove_file(&path);
+}
+
+#[cfg(unix)]
+#[test]
+fn test_saved_cookies_are_private() {
+    use std::env;
+    use std::fs;
+    use std::os::unix::fs::PermissionsExt;
+
+    let path = env::temp_dir().join("servo-test-private-cookies.json");
+    let _ = fs::remove_file(&path);
+    let url = &Url::parse("http://example.com/").unwrap();
+
+    let mut storage = CookieStorage::new_persistent(path.clone());
+    let cookie = cookie_rs::Cookie::parse("persistent=1; Max-Age=3600").unwrap();
+    storage.push(Cookie::new_wrapped(cookie, url, CookieSource::HTTP).unwrap(), CookieSource::HTTP);
+    storage.flush();
+
+    assert_eq!(fs::metadata(&path).unwrap().permissions().mode() & 0o777, 0o600);
+    let _ = fs::remove_file(&path);
+}
@@ -0,0 +1,25 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+use net::file_loader::directory_listing;
+use std::env;
+use std::fs::{self, File};
+use std::io::Write;
+
+#[test]
+fn test_directory_listing() {
+    let dir = env::temp_dir().join("servo-test-directory-listing");
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(dir.join("sub")).unwrap();
+    File::create(dir.join("a&b.txt")).unwrap().write_all(b"hello").unwrap();
+
+    let page = directory_listing(&dir).unwrap();
+    let _ = fs::remove_dir_all(&dir);
+
+    assert!(page.contains("Parent directory"));
+    // Subdirectories come first, and names are escaped.
+    let sub = page.find(">sub/</a></td><td></td>").unwrap();
+    let file = page.find(">a&amp;b.txt</a></td><td>5</td>").unwrap();
+    assert!(sub < file);
+}
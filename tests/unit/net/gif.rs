@@ -0,0 +1,58 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+use net_traits::image::base::load_animation_from_memory;
+use net_traits::image::gif::decode;
+
+/// A 2x1 GIF that loops forever. The first frame is red and green and lasts 50ms; the second
+/// draws blue over the green pixel, leaves the red one transparent, and lasts 100ms.
+const ANIMATED_GIF: &'static [u8] = &[
+    b'G', b'I', b'F', b'8', b'9', b'a', 2, 0, 1, 0, 0x81, 0, 0,
+    0, 0, 0, 255, 0, 0, 0, 255, 0, 0, 0, 255,
+    0x21, 0xff, 11, b'N', b'E', b'T', b'S', b'C', b'A', b'P', b'E', b'2', b'.', b'0',
+    3, 1, 0, 0, 0,
+    0x21, 0xf9, 4, 0x00, 5, 0, 0, 0,
+    0x2c, 0, 0, 0, 0, 2, 0, 1, 0, 0, 2, 2, 0x8c, 0x0a, 0,
+    0x21, 0xf9, 4, 0x01, 10, 0, 1, 0,
+    0x2c, 0, 0, 0, 0, 2, 0, 1, 0, 0, 2, 2, 0xcc, 0x0a, 0,
+    0x3b,
+];
+
+#[test]
+fn test_decode_frames() {
+    let gif = decode(ANIMATED_GIF).unwrap();
+    assert_eq!((gif.width, gif.height), (2, 1));
+    assert_eq!(gif.loop_count, None);
+    assert_eq!(gif.frames.len(), 2);
+    assert_eq!(gif.frames[0].pixels, vec!(255, 0, 0, 255, 0, 255, 0, 255));
+    assert_eq!(gif.frames[0].delay, 50);
+    assert_eq!(gif.frames[1].pixels, vec!(255, 0, 0, 255, 0, 0, 255, 255));
+    assert_eq!(gif.frames[1].delay, 100);
+}
+
+#[test]
+fn test_truncated_gif_keeps_decoded_frames() {
+    let gif = decode(&ANIMATED_GIF[..ANIMATED_GIF.len() - 10]).unwrap();
+    assert_eq!(gif.frames.len(), 1);
+    assert!(decode(&ANIMATED_GIF[..20]).is_err());
+}
+
+#[test]
+fn test_frame_index_at() {
+    let animation = load_animation_from_memory(ANIMATED_GIF).unwrap();
+    assert_eq!(animation.frame_index_at(0), 0);
+    assert_eq!(animation.frame_index_at(60000000), 1);
+    assert_eq!(animation.frame_index_at(160000000), 0);
+    assert!(!animation.has_finished(1000000000000));
+}
+
+#[test]
+fn test_huge_gif_is_rejected() {
+    let mut gif = ANIMATED_GIF.to_vec();
+    gif[6] = 0xff;
+    gif[7] = 0xff;
+    gif[8] = 0xff;
+    gif[9] = 0xff;
+    assert!(decode(&gif).is_err());
+}
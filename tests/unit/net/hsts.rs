@@ -0,0 +1,53 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+use net::hsts::{secure_url, HSTSList};
+use url::Url;
+
+#[test]
+fn test_hsts_header_includes_subdomains() {
+    let list = HSTSList::new();
+    list.update("example.com", &[b"max-age=31536000; includeSubDomains".to_vec()]);
+
+    assert!(list.is_host_secure("example.com"));
+    assert!(list.is_host_secure("www.example.com"));
+    assert!(!list.is_host_secure("notexample.com"));
+}
+
+#[test]
+fn test_hsts_max_age_zero_removes_host() {
+    let list = HSTSList::new();
+    list.update("example.com", &[b"max-age=31536000".to_vec()]);
+    assert!(list.is_host_secure("example.com"));
+    assert!(!list.is_host_secure("www.example.com"));
+
+    list.update("example.com", &[b"max-age=0".to_vec()]);
+    assert!(!list.is_host_secure("example.com"));
+}
+
+#[test]
+fn test_hsts_huge_max_age() {
+    let list = HSTSList::new();
+    list.update("example.com", &[b"max-age=18446744073709551615".to_vec()]);
+    assert!(list.is_host_secure("example.com"));
+}
+
+#[test]
+fn test_hsts_ignores_malformed_headers_and_ip_addresses() {
+    let list = HSTSList::new();
+    list.update("example.com", &[b"includeSubDomains".to_vec()]);
+    list.update("127.0.0.1", &[b"max-age=31536000".to_vec()]);
+
+    assert!(!list.is_host_secure("example.com"));
+    assert!(!list.is_host_secure("127.0.0.1"));
+}
+
+#[test]
+fn test_secure_url() {
+    let url = Url::parse("http://example.com:80/path?query").unwrap();
+    assert_eq!(secure_url(&url).serialize(), "https://example.com/path?query");
+
+    let url = Url::parse("http://example.com:8080/").unwrap();
+    assert_eq!(secure_url(&url).serialize(), "https://example.com:8080/");
+}
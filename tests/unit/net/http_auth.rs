@@ -0,0 +1,75 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+use hyper::method::Method;
+use msg::constellation_msg::Credentials;
+use net::http_auth::{AuthCache, AuthScheme, ProtectionSpace, parse_challenges, server_for};
+use std::borrow::ToOwned;
+use url::Url;
+
+fn credentials(username: &str, password: &str) -> Credentials {
+    Credentials {
+        username: username.to_owned(),
+        password: password.to_owned(),
+    }
+}
+
+#[test]
+fn test_parse_challenges() {
+    let header = b"Newauth realm=\"apps\", type=1, Basic realm=\"simple\", \
+                   Digest realm=\"a \\\"quoted\\\" realm\", nonce=abc, qop=\"auth,auth-int\"";
+    let challenges = parse_challenges(&[header.to_vec()]);
+    assert_eq!(challenges.len(), 2);
+    assert_eq!(challenges[0].scheme, AuthScheme::Basic);
+    assert_eq!(challenges[0].realm, "simple");
+    assert_eq!(challenges[1].scheme, AuthScheme::Digest);
+    assert_eq!(challenges[1].realm, "a \"quoted\" realm");
+    assert_eq!(challenges[1].params.get("nonce").map(|s| &**s), Some("abc"));
+    assert_eq!(challenges[1].params.get("qop").map(|s| &**s), Some("auth,auth-int"));
+}
+
+#[test]
+fn test_basic_authorization() {
+    let challenges = parse_challenges(&[b"Basic realm=\"WallyWorld\"".to_vec()]);
+    let url = Url::parse("http://example.com/").unwrap();
+    assert_eq!(challenges[0].authorization(&credentials("Aladdin", "open sesame"),
+                                           &Method::Get, &url),
+               Some("Basic QWxhZGRpbjpvcGVuIHNlc2FtZQ==".to_owned()));
+}
+
+#[test]
+fn test_digest_authorization() {
+    // The example from https://tools.ietf.org/html/rfc2617#section-3.5.
+    let header = b"Digest realm=\"testrealm@host.com\", qop=\"auth,auth-int\", \
+                   nonce=\"dcd98b7102dd2f0e8b11d0f600bfb0c093\", \
+                   opaque=\"5ccc069c403ebaf9f0171e9517f40e41\"";
+    let challenges = parse_challenges(&[header.to_vec()]);
+    let url = Url::parse("http://www.nowhere.org/dir/index.html").unwrap();
+    let authorization = challenges[0].digest_authorization(&credentials("Mufasa", "Circle Of Life"),
+                                                           &Method::Get, &url, "0a4f113b");
+    assert_eq!(authorization,
+               Some("Digest username=\"Mufasa\", realm=\"testrealm@host.com\", \
+                     nonce=\"dcd98b7102dd2f0e8b11d0f600bfb0c093\", uri=\"/dir/index.html\", \
+                     qop=auth, nc=00000001, cnonce=\"0a4f113b\", \
+                     response=\"6629fae49393a05397450978507c4ef1\", \
+                     opaque=\"5ccc069c403ebaf9f0171e9517f40e41\"".to_owned()));
+}
+
+#[test]
+fn test_preemptive_authorization_only_for_basic_on_the_same_server() {
+    let cache = AuthCache::new();
+    let url = Url::parse("http://example.com/private/").unwrap();
+    let challenges = parse_challenges(&[b"Basic realm=\"private\"".to_vec()]);
+    cache.store(ProtectionSpace {
+        server: server_for(&url),
+        realm: "private".to_owned(),
+        proxy: false,
+    }, challenges[0].clone(), credentials("user", "pass"));
+
+    let same_server = Url::parse("http://example.com/other").unwrap();
+    assert_eq!(cache.preemptive_authorization(&same_server),
+               Some("Basic dXNlcjpwYXNz".to_owned()));
+    let other_server = Url::parse("https://example.com/").unwrap();
+    assert_eq!(cache.preemptive_authorization(&other_server), None);
+}
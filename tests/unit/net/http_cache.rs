@@ -0,0 +1,89 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+use hyper::header::Headers;
+use hyper::method::Method;
+use net::http_cache::HttpCache;
+use net_traits::Metadata;
+use std::borrow::ToOwned;
+use url::Url;
+
+fn metadata_with_headers(url: &Url, headers: &[(&str, &str)]) -> Metadata {
+    let mut response_headers = Headers::new();
+    for &(name, value) in headers.iter() {
+        response_headers.set_raw(name.to_owned(), vec![value.as_bytes().to_vec()]);
+    }
+    let mut metadata = Metadata::default(url.clone());
+    metadata.headers = Some(response_headers);
+    metadata
+}
+
+#[test]
+fn test_fresh_response_is_stored() {
+    let cache = HttpCache::new();
+    let url = Url::parse("http://example.com/fresh").unwrap();
+    let metadata = metadata_with_headers(&url, &[("Cache-Control", "max-age=3600")]);
+    cache.store(&url, &Method::Get, &Headers::new(), metadata, b"body".to_vec());
+
+    let cached = cache.get(&url, &Method::Get, &Headers::new()).unwrap();
+    assert!(cached.is_fresh());
+    assert_eq!(&**cached.body, b"body");
+}
+
+#[test]
+fn test_no_store_response_is_not_stored() {
+    let cache = HttpCache::new();
+    let url = Url::parse("http://example.com/private").unwrap();
+    let metadata = metadata_with_headers(&url, &[("Cache-Control", "no-store, max-age=3600")]);
+    cache.store(&url, &Method::Get, &Headers::new(), metadata, b"body".to_vec());
+    assert!(cache.get(&url, &Method::Get, &Headers::new()).is_none());
+}
+
+#[test]
+fn test_stale_response_is_revalidated() {
+    let cache = HttpCache::new();
+    let url = Url::parse("http://example.com/etag").unwrap();
+    let metadata = metadata_with_headers(&url, &[("ETag", "\"v1\"")]);
+    cache.store(&url, &Method::Get, &Headers::new(), metadata, b"body".to_vec());
+
+    let cached = cache.get(&url, &Method::Get, &Headers::new()).unwrap();
+    assert!(!cached.is_fresh());
+    let mut request_headers = Headers::new();
+    cached.add_validators(&mut request_headers);
+    assert_eq!(request_headers.get_raw("if-none-match").unwrap(), &[b"\"v1\"".to_vec()]);
+
+    let mut not_modified_headers = Headers::new();
+    not_modified_headers.set_raw("Cache-Control".to_owned(), vec![b"max-age=60".to_vec()]);
+    let revalidated = cache.revalidate(&url, &not_modified_headers).unwrap();
+    assert!(revalidated.is_fresh());
+    assert_eq!(&**revalidated.body, b"body");
+}
+
+#[test]
+fn test_response_is_only_used_for_matching_requests() {
+    let cache = HttpCache::new();
+    let url = Url::parse("http://example.com/varied").unwrap();
+    let metadata = metadata_with_headers(&url, &[("Cache-Control", "max-age=3600"),
+                                                 ("Vary", "Accept-Language")]);
+    let mut english = Headers::new();
+    english.set_raw("Accept-Language".to_owned(), vec![b"en".to_vec()]);
+    cache.store(&url, &Method::Get, &english, metadata, b"body".to_vec());
+
+    let mut french = Headers::new();
+    french.set_raw("Accept-Language".to_owned(), vec![b"fr".to_vec()]);
+    assert!(cache.get(&url, &Method::Get, &english).is_some());
+    assert!(cache.get(&url, &Method::Get, &french).is_none());
+    assert!(cache.get(&url, &Method::Get, &Headers::new()).is_none());
+    assert!(cache.get(&url, &Method::Post, &english).is_none());
+}
+
+#[test]
+fn test_response_varying_on_everything_is_not_stored() {
+    let cache = HttpCache::new();
+    let url = Url::parse("http://example.com/star").unwrap();
+    let metadata = metadata_with_headers(&url, &[("Cache-Control", "max-age=3600"),
+                                                 ("Vary", "*")]);
+    cache.store(&url, &Method::Get, &Headers::new(), metadata, b"body".to_vec());
+    assert!(cache.get(&url, &Method::Get, &Headers::new()).is_none());
+}
@@ -0,0 +1,84 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+use net_traits::image::icc::{Transform, jpeg_profile};
+
+fn u32_bytes(value: u32) -> Vec<u8> {
+    vec!((value >> 24) as u8, (value >> 16) as u8, (value >> 8) as u8, value as u8)
+}
+
+fn fixed_bytes(value: f32) -> Vec<u8> {
+    u32_bytes((value * 65536.0).round() as i32 as u32)
+}
+
+/// An RGB profile with sRGB's primaries, and `curve` as the tone curve of every channel.
+fn profile(curve: Vec<u8>) -> Vec<u8> {
+    let primaries = [
+        [0.4360747, 0.2225045, 0.0139322],
+        [0.3850649, 0.7168786, 0.0971045],
+        [0.1430804, 0.0606169, 0.7141733],
+    ];
+    let mut tags = vec!();
+    for xyz in primaries.iter() {
+        let mut tag = b"XYZ \0\0\0\0".to_vec();
+        for &value in xyz.iter() {
+            tag.extend(fixed_bytes(value).into_iter());
+        }
+        tags.push(tag);
+    }
+    for _ in 0..3 {
+        tags.push(curve.clone());
+    }
+
+    let mut data = vec![0; 16];
+    data.extend(b"RGB XYZ ".iter().cloned());
+    data.extend([0; 12].iter().cloned());
+    data.extend(b"acsp".iter().cloned());
+    data.extend([0; 88].iter().cloned());
+    data.extend(u32_bytes(6).into_iter());
+    let mut offset = 132 + 6 * 12;
+    let signatures = ["rXYZ", "gXYZ", "bXYZ", "rTRC", "gTRC", "bTRC"];
+    for (signature, tag) in signatures.iter().zip(tags.iter()) {
+        data.extend(signature.as_bytes().iter().cloned());
+        data.extend(u32_bytes(offset as u32).into_iter());
+        data.extend(u32_bytes(tag.len() as u32).into_iter());
+        offset += tag.len();
+    }
+    for tag in tags.iter() {
+        data.extend(tag.iter().cloned());
+    }
+    data
+}
+
+#[test]
+fn test_srgb_profile_is_left_alone() {
+    // sRGB's own tone curve.
+    let mut curve = b"para\0\0\0\0\0\x03\0\0".to_vec();
+    for &value in [2.4, 1.0 / 1.055, 0.055 / 1.055, 1.0 / 12.92, 0.04045].iter() {
+        curve.extend(fixed_bytes(value).into_iter());
+    }
+    assert!(Transform::new(&profile(curve)).unwrap().is_none());
+}
+
+#[test]
+fn test_linear_profile_is_converted() {
+    let curve = b"curv\0\0\0\0\0\0\0\0".to_vec();
+    let transform = Transform::new(&profile(curve)).unwrap().unwrap();
+    let mut pixels = vec!(0, 128, 255, 255);
+    transform.apply(&mut pixels, 4);
+    assert_eq!(pixels, vec!(0, 188, 255, 255));
+}
+
+#[test]
+fn test_jpeg_profile_joins_chunks() {
+    let mut jpeg = vec!(0xff, 0xd8);
+    for &(sequence, chunk) in [(2, b"def"), (1, b"abc")].iter() {
+        jpeg.extend([0xff, 0xe2, 0, 19].iter().cloned());
+        jpeg.extend(b"ICC_PROFILE\0".iter().cloned());
+        jpeg.extend([sequence, 2].iter().cloned());
+        jpeg.extend(chunk.iter().cloned());
+    }
+    jpeg.extend([0xff, 0xda].iter().cloned());
+    assert_eq!(jpeg_profile(&jpeg), Some(b"abcdef".to_vec()));
+}
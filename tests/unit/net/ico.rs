@@ -0,0 +1,77 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+use net_traits::image::ico::{Icon, decode};
+
+fn u16_bytes(value: u16) -> Vec<u8> {
+    vec!(value as u8, (value >> 8) as u8)
+}
+
+fn u32_bytes(value: u32) -> Vec<u8> {
+    vec!(value as u8, (value >> 8) as u8, (value >> 16) as u8, (value >> 24) as u8)
+}
+
+/// A bitmap as stored in an icon: a header giving twice the height, the colors of the rows
+/// from the bottom up, and the mask.
+fn bitmap(width: u32, height: u32, bit_count: u16, colors: &[u8], mask: &[u8]) -> Vec<u8> {
+    let mut data = vec!();
+    data.extend(u32_bytes(40).iter().cloned());
+    data.extend(u32_bytes(width).iter().cloned());
+    data.extend(u32_bytes(height * 2).iter().cloned());
+    data.extend(u16_bytes(1).iter().cloned());
+    data.extend(u16_bytes(bit_count).iter().cloned());
+    data.extend([0; 24].iter().cloned());
+    data.extend(colors.iter().cloned());
+    data.extend(mask.iter().cloned());
+    data
+}
+
+/// An icon holding a 2x2 24-bit bitmap whose mask hides one pixel, and a 1x1 32-bit bitmap
+/// with a half transparent pixel.
+fn icon() -> Vec<u8> {
+    let large = bitmap(2, 2, 24,
+                       &[255, 0, 0, 255, 255, 255, 0, 0, 0, 0, 255, 0, 255, 0, 0, 0],
+                       &[0x40, 0, 0, 0, 0, 0, 0, 0]);
+    let small = bitmap(1, 1, 32, &[0, 0, 255, 128], &[0, 0, 0, 0]);
+    let mut data = vec!(0, 0, 1, 0, 2, 0);
+    let mut offset = 6 + 2 * 16;
+    for &(size, bit_count, ref image) in [(2, 24, &large), (1, 32, &small)].iter() {
+        data.extend([size, size, 0, 0].iter().cloned());
+        data.extend(u16_bytes(1).iter().cloned());
+        data.extend(u16_bytes(bit_count).iter().cloned());
+        data.extend(u32_bytes(image.len() as u32).iter().cloned());
+        data.extend(u32_bytes(offset).iter().cloned());
+        offset += image.len() as u32;
+    }
+    data.extend(large.iter().cloned());
+    data.extend(small.iter().cloned());
+    data
+}
+
+#[test]
+fn test_decode_picks_image_for_size() {
+    let icon = icon();
+    match decode(&icon, 16).unwrap() {
+        Icon::Bitmap { width, height, pixels } => {
+            assert_eq!((width, height), (2, 2));
+            assert_eq!(pixels, vec!(255, 0, 0, 255, 0, 255, 0, 255,
+                                    0, 0, 255, 255, 255, 255, 255, 0));
+        }
+        Icon::Png(..) => panic!("expected a bitmap"),
+    }
+    match decode(&icon, 1).unwrap() {
+        Icon::Bitmap { width, height, pixels } => {
+            assert_eq!((width, height), (1, 1));
+            assert_eq!(pixels, vec!(255, 0, 0, 128));
+        }
+        Icon::Png(..) => panic!("expected a bitmap"),
+    }
+}
+
+#[test]
+fn test_decode_rejects_truncated_icon() {
+    let icon = icon();
+    assert!(decode(&icon[..50], 16).is_err());
+    assert!(decode(&[0, 0, 1, 0, 0, 0], 16).is_err());
+}
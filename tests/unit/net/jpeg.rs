@@ -0,0 +1,38 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+use euclid::size::Size2D;
+use net_traits::image::jpeg::{decode, read_header, scale_for_target_size};
+
+/// A 32x23 progressive JPEG.
+const PROGRESSIVE_JPEG: &'static [u8] = include_bytes!("parsable_mime/image/jpeg/test.jpg");
+
+#[test]
+fn test_read_header() {
+    let header = read_header(PROGRESSIVE_JPEG).unwrap();
+    assert_eq!((header.width, header.height), (32, 23));
+    assert!(header.progressive);
+    assert!(read_header(b"GIF89a").is_none());
+}
+
+#[test]
+fn test_decode_progressive() {
+    let image = decode(PROGRESSIVE_JPEG, None).unwrap();
+    assert_eq!((image.width, image.height), (32, 23));
+    assert_eq!(image.pixels.len(), 32 * 23 * 4);
+    assert!(decode(&PROGRESSIVE_JPEG[..20], None).is_err());
+}
+
+#[test]
+fn test_decode_at_reduced_scale() {
+    assert_eq!(scale_for_target_size(32, 23, None), 1);
+    assert_eq!(scale_for_target_size(32, 23, Some(Size2D::new(8, 6))), 4);
+    assert_eq!(scale_for_target_size(32, 23, Some(Size2D::new(9, 6))), 2);
+    assert_eq!(scale_for_target_size(32, 23, Some(Size2D::new(17, 6))), 1);
+
+    let image = decode(PROGRESSIVE_JPEG, Some(Size2D::new(10, 10))).unwrap();
+    assert_eq!((image.width, image.height), (16, 12));
+    let image = decode(PROGRESSIVE_JPEG, Some(Size2D::new(8, 6))).unwrap();
+    assert_eq!((image.width, image.height), (8, 6));
+}
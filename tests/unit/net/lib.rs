@@ -4,12 +4,29 @@
 
 #![cfg_attr(test, feature(box_raw))]
 
+extern crate euclid;
+extern crate hyper;
+extern crate msg;
 extern crate net;
 extern crate net_traits;
+extern crate profile_traits;
 extern crate url;
 extern crate util;
 
+#[cfg(test)] mod about_loader;
+#[cfg(test)] mod beacon;
+#[cfg(test)] mod certificate;
 #[cfg(test)] mod cookie;
 #[cfg(test)] mod data_loader;
+#[cfg(test)] mod file_loader;
+#[cfg(test)] mod gif;
+#[cfg(test)] mod hsts;
+#[cfg(test)] mod http_auth;
+#[cfg(test)] mod http_cache;
+#[cfg(test)] mod icc;
+#[cfg(test)] mod ico;
+#[cfg(test)] mod jpeg;
 #[cfg(test)] mod mime_classifier;
+#[cfg(test)] mod proxy;
+#[cfg(test)] mod referrer;
 #[cfg(test)] mod resource_task;
@@ -0,0 +1,33 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+use net::proxy::{parse_proxy, ProxyConfig};
+use std::borrow::ToOwned;
+use url::Url;
+
+#[test]
+fn test_parse_proxy() {
+    assert_eq!(parse_proxy("proxy.example.com:3128"), Some(("proxy.example.com".to_owned(), 3128)));
+    assert_eq!(parse_proxy("http://proxy.example.com:8080/"),
+               Some(("proxy.example.com".to_owned(), 8080)));
+    assert_eq!(parse_proxy("proxy.example.com"), Some(("proxy.example.com".to_owned(), 80)));
+}
+
+#[test]
+fn test_proxy_bypass() {
+    let config = ProxyConfig {
+        http: Some(("proxy".to_owned(), 3128)),
+        https: None,
+        bypass: vec!["example.com".to_owned(), "localhost".to_owned()],
+    };
+
+    let proxied = Url::parse("http://servo.org/").unwrap();
+    assert_eq!(config.proxy_for(&proxied), Some(("proxy".to_owned(), 3128)));
+    let secure = Url::parse("https://servo.org/").unwrap();
+    assert_eq!(config.proxy_for(&secure), None);
+    let subdomain = Url::parse("http://www.example.com/").unwrap();
+    assert_eq!(config.proxy_for(&subdomain), None);
+    let lookalike = Url::parse("http://notexample.com/").unwrap();
+    assert!(config.proxy_for(&lookalike).is_some());
+}
@@ -14,13 +14,13 @@ use url::Url;
 
 #[test]
 fn test_exit() {
-    let resource_task = new_resource_task(None, None);
+    let resource_task = new_resource_task(None, None, None);
     resource_task.send(ControlMsg::Exit).unwrap();
 }
 
 #[test]
 fn test_bad_scheme() {
-    let resource_task = new_resource_task(None, None);
+    let resource_task = new_resource_task(None, None, None);
     let (start_chan, start) = channel();
     let url = Url::parse("bogus://whatever").unwrap();
     resource_task.send(ControlMsg::Load(LoadData::new(url, None), LoadConsumer::Channel(start_chan))).unwrap();
@@ -171,7 +171,7 @@ fn test_replace_hosts() {
     let port = listener.local_addr().unwrap().port();
 
     //Start the resource task and make a request to our TCP server
-    let resource_task = new_resource_task(None, None);
+    let resource_task = new_resource_task(None, None, None);
     let (start_chan, _) = channel();
     let url = Url::parse(&format!("http://foo.bar.com:{}", port)).unwrap();
     let msg = ControlMsg::Load(replace_hosts(LoadData::new(url, None), host_table),
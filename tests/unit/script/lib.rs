@@ -4,6 +4,8 @@
 
 extern crate script;
 extern crate msg;
+extern crate url;
 
+#[cfg(test)] mod preload_scanner;
 #[cfg(all(test, target_pointer_width = "64"))] mod size_of;
 #[cfg(test)] mod textinput;
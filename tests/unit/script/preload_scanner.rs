@@ -0,0 +1,41 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+use script::parse::preload::{Preload, PreloadKind, PreloadScanner};
+use url::Url;
+
+fn preload(kind: PreloadKind, url: &str) -> Preload {
+    Preload {
+        kind: kind,
+        url: Url::parse(url).unwrap(),
+    }
+}
+
+#[test]
+fn test_finds_subresources() {
+    let mut scanner = PreloadScanner::new(Url::parse("http://example.com/dir/page.html").unwrap());
+    let preloads = scanner.scan("<p><IMG alt='x' SRC=a.png></p>\
+                                 <script src=\"/b.js\"></script>\
+                                 <link rel='alternate stylesheet' href=c.css>\
+                                 <link rel=icon href=d.ico>");
+    assert_eq!(preloads, vec!(preload(PreloadKind::Image, "http://example.com/dir/a.png"),
+                              preload(PreloadKind::Script, "http://example.com/b.js"),
+                              preload(PreloadKind::Stylesheet, "http://example.com/dir/c.css")));
+}
+
+#[test]
+fn test_skips_comments_and_script_contents() {
+    let mut scanner = PreloadScanner::new(Url::parse("http://example.com/").unwrap());
+    let preloads = scanner.scan("<!-- <img src=a.png> --><script>var s = '<img src=b.png>';</script>\
+                                 <img src=c.png>");
+    assert_eq!(preloads, vec!(preload(PreloadKind::Image, "http://example.com/c.png")));
+}
+
+#[test]
+fn test_tags_split_across_chunks() {
+    let mut scanner = PreloadScanner::new(Url::parse("http://example.com/").unwrap());
+    assert_eq!(scanner.scan("<base href=/static/><img sr"), vec!());
+    assert_eq!(scanner.scan("c='a.png'><img src=a.png>"),
+               vec!(preload(PreloadKind::Image, "http://example.com/static/a.png")));
+}
@@ -7,14 +7,14 @@
 // option. This file may not be copied, modified, or distributed
 // except according to those terms.
 
-use msg::constellation_msg::{Key, KeyModifiers};
+use msg::constellation_msg::{ImeEvent, Key, KeyModifiers};
 
 #[cfg(target_os="macos")]
 use msg::constellation_msg::SUPER;
 #[cfg(not(target_os="macos"))]
 use msg::constellation_msg::CONTROL;
 
-use script::textinput::{TextInput, Selection, Lines, DeleteDir};
+use script::textinput::{TextInput, Selection, Lines, DeleteDir, KeyReaction};
 use script::clipboard_provider::DummyClipboardContext;
 use std::borrow::ToOwned;
 
@@ -121,6 +121,16 @@ fn test_textinput_adjust_horizontal() {
     assert_eq!(textinput.edit_point.index, 2);
 }
 
+#[test]
+fn test_textinput_adjust_horizontal_grapheme_clusters() {
+    let mut textinput = TextInput::new(Lines::Single, "ae\u{301}b".to_owned(), DummyClipboardContext::new(""));
+    textinput.adjust_horizontal(2, Selection::NotSelected);
+    assert_eq!(textinput.edit_point.index, 3);
+
+    textinput.adjust_horizontal(-1, Selection::NotSelected);
+    assert_eq!(textinput.edit_point.index, 1);
+}
+
 #[test]
 fn test_textinput_handle_return() {
     let mut single_line_textinput = TextInput::new(
@@ -190,3 +200,44 @@ fn test_clipboard_paste() {
     textinput.handle_keydown_aux(Key::V, MODIFIERS);
     assert_eq!(textinput.get_content(), "abcdefg");
 }
+
+#[test]
+fn test_textinput_composition() {
+    let mut textinput = TextInput::new(Lines::Single, "abcdefg".to_owned(), DummyClipboardContext::new(""));
+    textinput.adjust_horizontal(2, Selection::NotSelected);
+    textinput.set_composition("x".to_owned(), 1);
+    assert_eq!(textinput.get_content(), "abxcdefg");
+    textinput.set_composition("xyz".to_owned(), 1);
+    assert_eq!(textinput.get_content(), "abxyzcdefg");
+    assert_eq!(textinput.caret_offset(), 3);
+
+    textinput.commit_composition("X".to_owned());
+    assert_eq!(textinput.get_content(), "abXcdefg");
+    assert_eq!(textinput.caret_offset(), 3);
+
+    textinput.set_composition("yz".to_owned(), 2);
+    textinput.cancel_composition();
+    assert_eq!(textinput.get_content(), "abXcdefg");
+    assert_eq!(textinput.caret_offset(), 3);
+}
+
+#[test]
+fn test_textinput_ime_event_only_dispatches_input_for_changes() {
+    let mut textinput = TextInput::new(Lines::Single, "ab".to_owned(), DummyClipboardContext::new(""));
+    let event = ImeEvent::SetComposition("x".to_owned(), 1);
+    assert_eq!(textinput.handle_ime_event(&event), KeyReaction::DispatchInput);
+    assert!(textinput.is_composing());
+    assert_eq!(textinput.handle_ime_event(&event), KeyReaction::Nothing);
+    assert_eq!(textinput.handle_ime_event(&ImeEvent::Commit("x".to_owned())), KeyReaction::Nothing);
+    assert!(!textinput.is_composing());
+    assert_eq!(textinput.handle_ime_event(&ImeEvent::Cancel), KeyReaction::Nothing);
+    assert_eq!(textinput.get_content(), "xab");
+}
+
+#[test]
+fn test_textinput_caret_offset() {
+    let mut textinput = TextInput::new(Lines::Multiple, "abc\nde\nf".to_owned(), DummyClipboardContext::new(""));
+    textinput.adjust_vertical(2, Selection::NotSelected);
+    textinput.adjust_horizontal(1, Selection::NotSelected);
+    assert_eq!(textinput.caret_offset(), 8);
+}
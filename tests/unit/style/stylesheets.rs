@@ -25,6 +25,7 @@ fn test_parse_stylesheet() {
     let stylesheet = Stylesheet::from_str(css, url, Origin::UserAgent);
     assert_eq!(stylesheet, Stylesheet {
         origin: Origin::UserAgent,
+        url: Url::parse("about::test").unwrap(),
         rules: vec![
             CSSRule::Namespace(None, ns!(HTML)),
             CSSRule::Style(StyleRule {
@@ -56,6 +57,8 @@ fn test_parse_stylesheet() {
                             longhands::display::SpecifiedValue::none)),
                     ]),
                 },
+                selector_text: "input[type=hidden i]".to_owned(),
+                line: 4,
             }),
             CSSRule::Style(StyleRule {
                 selectors: vec![
@@ -95,6 +98,8 @@ fn test_parse_stylesheet() {
                     ]),
                     important: Arc::new(vec![]),
                 },
+                selector_text: "html , body /**/".to_owned(),
+                line: 5,
             }),
             CSSRule::Style(StyleRule {
                 selectors: vec![
@@ -134,6 +139,8 @@ fn test_parse_stylesheet() {
                     ]),
                     important: Arc::new(vec![]),
                 },
+                selector_text: "#d1 > .ok".to_owned(),
+                line: 6,
             }),
         ],
     });
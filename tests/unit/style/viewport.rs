@@ -8,7 +8,7 @@ use euclid::scale_factor::ScaleFactor;
 use style::media_queries::{Device, MediaType};
 use style::parser::ParserContext;
 use style::stylesheets::{Origin, Stylesheet, CSSRuleIteratorExt};
-use style::values::specified::{Length, LengthOrPercentageOrAuto};
+use style::values::specified::{Length, LengthOrPercentageOrAuto, ViewportPercentageLength};
 use style::viewport::*;
 use url::Url;
 
@@ -277,3 +277,26 @@ fn constrain_viewport() {
                    orientation: Orientation::Auto
                }));
 }
+
+#[test]
+fn viewport_rule_from_meta() {
+    assert_eq!(ViewportRule::from_meta("charset=utf-8"), None);
+
+    let rule = ViewportRule::from_meta("width=device-width, initial-scale=2; user-scalable=no")
+        .unwrap();
+    let declarations = &rule.declarations;
+    assert_decl_len!(declarations == 4);
+    let device_width = LengthOrPercentageOrAuto::Length(
+        Length::ViewportPercentage(ViewportPercentageLength::Vw(100.)));
+    assert_decl_eq!(&declarations[0], Author, MinWidth: device_width);
+    assert_decl_eq!(&declarations[1], Author, MaxWidth: device_width);
+    assert_decl_eq!(&declarations[2], Author, Zoom: Zoom::Number(2.));
+    assert_decl_eq!(&declarations[3], Author, UserZoom: UserZoom::Fixed);
+
+    let rule = ViewportRule::from_meta("WIDTH = 320, maximum-scale=100").unwrap();
+    let declarations = &rule.declarations;
+    assert_decl_len!(declarations == 3);
+    assert_decl_eq!(&declarations[1], Author, MaxWidth:
+                    LengthOrPercentageOrAuto::Length(Length::from_px(320.)));
+    assert_decl_eq!(&declarations[2], Author, MaxZoom: Zoom::Number(10.));
+}
@@ -0,0 +1,49 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+use std::borrow::ToOwned;
+use util::ipc::{self, IpcReceiver, IpcSender};
+
+#[test]
+fn test_reply_through_a_sent_sender() {
+    let (sender, receiver) = ipc::channel::<(String, IpcSender<usize>)>();
+    let (reply_sender, reply_receiver) = ipc::channel();
+    sender.send(("hello".to_owned(), reply_sender)).unwrap();
+
+    let (message, reply_sender) = receiver.recv().unwrap();
+    assert_eq!(message, "hello");
+    reply_sender.send(message.len()).unwrap();
+    assert_eq!(reply_receiver.recv().unwrap(), 5);
+}
+
+#[test]
+fn test_recv_fails_once_the_senders_are_gone() {
+    let (sender, receiver) = ipc::channel::<u32>();
+    let other_sender = sender.clone();
+    sender.send(1).unwrap();
+    drop(sender);
+    other_sender.send(2).unwrap();
+    drop(other_sender);
+
+    assert_eq!(receiver.recv().unwrap(), 1);
+    assert_eq!(receiver.recv().unwrap(), 2);
+    assert!(receiver.recv().is_err());
+}
+
+#[test]
+fn test_large_message_through_a_sent_receiver() {
+    let (sender, receiver) = ipc::channel::<IpcReceiver<Vec<u8>>>();
+    let (data_sender, data_receiver) = ipc::channel();
+    sender.send(data_receiver).unwrap();
+    let data_receiver = receiver.recv().unwrap();
+
+    let data: Vec<u8> = (0..1000000).map(|index| index as u8).collect();
+    data_sender.send(data.clone()).unwrap();
+    data_sender.send(vec!(1, 2, 3)).unwrap();
+    assert_eq!(data_receiver.recv().unwrap(), data);
+    assert_eq!(data_receiver.recv().unwrap(), vec!(1, 2, 3));
+
+    drop(data_receiver);
+    assert!(data_sender.send(vec!()).is_err());
+}
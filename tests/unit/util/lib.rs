@@ -9,8 +9,10 @@ extern crate libc;
 extern crate euclid;
 
 #[cfg(test)] mod cache;
+#[cfg(test)] mod ipc;
 #[cfg(test)] mod logical_geometry;
 #[cfg(test)] mod task;
 #[cfg(test)] mod vec;
 #[cfg(test)] mod mem;
 #[cfg(test)] mod str;
+#[cfg(test)] mod spellcheck;
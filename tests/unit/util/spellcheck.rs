@@ -0,0 +1,17 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+use std::borrow::ToOwned;
+use util::spellcheck::{WordListDictionary, misspelled_words};
+
+#[test]
+fn test_misspelled_words() {
+    let words = vec!("the", "cat", "don't", "sat").into_iter().map(|word| word.to_owned());
+    let dictionary = WordListDictionary::new(words);
+
+    assert_eq!(misspelled_words(&dictionary, "The cat sat"), vec!());
+    assert_eq!(misspelled_words(&dictionary, "the catt sat"), vec!(4..8));
+    assert_eq!(misspelled_words(&dictionary, "'don't' dont"), vec!(8..12));
+    assert_eq!(misspelled_words(&dictionary, "x86 cät, sat"), vec!(4..7));
+}
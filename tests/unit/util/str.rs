@@ -3,6 +3,8 @@
  * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
 
 use util::str::split_html_space_chars;
+use util::str::{is_grapheme_cluster_boundary, next_grapheme_cluster_boundary};
+use util::str::previous_grapheme_cluster_boundary;
 
 
 #[test]
@@ -10,3 +12,34 @@ pub fn split_html_space_chars_whitespace() {
     assert!(split_html_space_chars("").collect::<Vec<_>>().is_empty());
     assert!(split_html_space_chars("\u{0020}\u{0009}\u{000a}\u{000c}\u{000d}").collect::<Vec<_>>().is_empty());
 }
+
+#[test]
+pub fn grapheme_cluster_boundaries() {
+    assert!(is_grapheme_cluster_boundary('a', 'b', false));
+    assert!(!is_grapheme_cluster_boundary('\r', '\n', false));
+    assert!(!is_grapheme_cluster_boundary('e', '\u{301}', false));
+    assert!(!is_grapheme_cluster_boundary('\u{1100}', '\u{1161}', false));
+    assert!(!is_grapheme_cluster_boundary('\u{1F1EB}', '\u{1F1F7}', true));
+    assert!(is_grapheme_cluster_boundary('\u{1F1F7}', '\u{1F1E9}', false));
+    // Devanagari vowel signs, Arabic harakat, Hebrew points and Thai vowels.
+    assert!(!is_grapheme_cluster_boundary('\u{915}', '\u{93F}', false));
+    assert!(!is_grapheme_cluster_boundary('\u{628}', '\u{64E}', false));
+    assert!(!is_grapheme_cluster_boundary('\u{5E9}', '\u{5C1}', false));
+    assert!(!is_grapheme_cluster_boundary('\u{E01}', '\u{E31}', false));
+    assert!(!is_grapheme_cluster_boundary('\u{600}', '\u{661}', false));
+    assert!(is_grapheme_cluster_boundary('a', '\u{600}', false));
+
+    let text = "ae\u{301}\u{302}b";
+    assert_eq!(next_grapheme_cluster_boundary(text, 1), 4);
+    assert_eq!(next_grapheme_cluster_boundary(text, 4), 5);
+    assert_eq!(previous_grapheme_cluster_boundary(text, 4), 1);
+    assert_eq!(previous_grapheme_cluster_boundary(text, 1), 0);
+
+    // The flags of France and Germany.
+    let text = "\u{1F1EB}\u{1F1F7}\u{1F1E9}\u{1F1EA}";
+    assert_eq!(next_grapheme_cluster_boundary(text, 0), 2);
+    assert_eq!(next_grapheme_cluster_boundary(text, 2), 4);
+    assert_eq!(previous_grapheme_cluster_boundary(text, 4), 2);
+    assert_eq!(previous_grapheme_cluster_boundary(text, 3), 2);
+    assert_eq!(previous_grapheme_cluster_boundary(text, 2), 0);
+}